@@ -0,0 +1,9 @@
+#pragma layout color
+precision mediump float;
+// Draws shapes with their color unchanged.
+in vec4 color;
+out vec4 frag_color;
+
+void main() {
+    frag_color = color;
+}
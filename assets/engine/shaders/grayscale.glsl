@@ -0,0 +1,11 @@
+precision mediump float;
+// Draws the texture in shades of gray, keeping its transparency.
+in vec2 uv;
+uniform sampler2D tex;
+out vec4 frag_color;
+
+void main() {
+    vec4 c = texture(tex, uv);
+    float gray = dot(c.rgb, vec3(0.299, 0.587, 0.114));
+    frag_color = vec4(vec3(gray), c.a);
+}
@@ -0,0 +1,35 @@
+// Hash and value noise functions.
+// Include with #include "@engine/shaders/lib/noise.glsl"
+#ifndef VECTARINE_LIB_NOISE
+#define VECTARINE_LIB_NOISE
+
+// A pseudo random number between 0 and 1 for each point.
+float hash21(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// Smooth noise between 0 and 1, with features about 1 unit wide.
+float valueNoise(vec2 p) {
+    vec2 cell = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+    float a = hash21(cell);
+    float b = hash21(cell + vec2(1.0, 0.0));
+    float c = hash21(cell + vec2(0.0, 1.0));
+    float d = hash21(cell + vec2(1.0, 1.0));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+// Sums several octaves of value noise, for clouds, smoke or terrain.
+float fbm(vec2 p, int octaves) {
+    float value = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < octaves; i++) {
+        value += amplitude * valueNoise(p);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return value;
+}
+
+#endif
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use runtime::{
+    console::{Subsystem, print_err_from, print_info_from, print_warn_from},
+    io::messagebox::show_confirm,
+    projectinfo::ProjectInfo,
+};
+use vectarine_cli::project::importassets::{
+    ImportCache, ImportJob, ImportTrust, find_import_sources, get_external_import_commands,
+    get_import_output_stem,
+};
+
+/// Runs the import rules of the project on a thread, so that a slow converter does not freeze the editor.
+pub struct AssetImporter {
+    cache: ImportCache,
+    /// The sources being converted. A source changed again during its conversion is converted again after it.
+    running: HashSet<String>,
+    changed_while_running: HashSet<PathBuf>,
+    /// Set once the user was told that the commands of the project do not run, to tell it only once.
+    is_refusal_reported: bool,
+    sender: mpsc::Sender<(ImportJob, Result<(), String>)>,
+    results: mpsc::Receiver<(ImportJob, Result<(), String>)>,
+}
+
+impl AssetImporter {
+    pub fn new(project_folder: &Path) -> Self {
+        let (sender, results) = mpsc::channel();
+        Self {
+            cache: ImportCache::load(project_folder),
+            running: HashSet::new(),
+            changed_while_running: HashSet::new(),
+            is_refusal_reported: false,
+            sender,
+            results,
+        }
+    }
+
+    /// Converts the sources of the project that changed since their last conversion, or all of them with `force`.
+    pub fn import_all(&mut self, project_path: &Path, project_info: &ProjectInfo, force: bool) {
+        let sources = find_import_sources(project_path, project_info);
+        if force && sources.is_empty() {
+            print_info_from(
+                Subsystem::Import,
+                "No file of the project matches the import rules.".to_string(),
+            );
+        }
+        self.import(project_path, project_info, sources, force);
+    }
+
+    /// Converts the files among `paths` that are sources of the import rules, unless they did not change.
+    pub fn import(
+        &mut self,
+        project_path: &Path,
+        project_info: &ProjectInfo,
+        paths: impl IntoIterator<Item = PathBuf>,
+        force: bool,
+    ) {
+        let Some(project_folder) = project_path.parent() else {
+            return;
+        };
+        let mut jobs = Vec::new();
+        for path in paths {
+            let Some(job) = ImportJob::new(project_folder, project_info, &path) else {
+                continue;
+            };
+            if self.running.contains(&job.resource_path) {
+                self.changed_while_running.insert(path);
+                continue;
+            }
+            if (!force && self.cache.is_up_to_date(&job))
+                || jobs
+                    .iter()
+                    .any(|other: &ImportJob| other.resource_path == job.resource_path)
+            {
+                continue;
+            }
+            jobs.push(job);
+        }
+        if jobs.iter().any(|job| !job.is_built_in())
+            && !self.are_commands_allowed(project_path, project_info, force)
+        {
+            jobs.retain(ImportJob::is_built_in);
+        }
+        if jobs.is_empty() {
+            return;
+        }
+        for job in &jobs {
+            self.running.insert(job.resource_path.clone());
+        }
+
+        let project_folder = project_folder.to_path_buf();
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            for job in jobs {
+                let result = job.run(&project_folder);
+                if sender.send((job, result)).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Asks the user before running the commands of a project for the first time, or after they changed. The answer is
+    /// kept, and a refusal is asked again when the user reimports all the assets.
+    fn are_commands_allowed(
+        &mut self,
+        project_path: &Path,
+        project_info: &ProjectInfo,
+        force: bool,
+    ) -> bool {
+        let commands = get_external_import_commands(project_info);
+        let mut trust = ImportTrust::load();
+        match trust.is_allowed(project_path, &commands) {
+            Some(true) => return true,
+            Some(false) if !force => {
+                self.report_refusal();
+                return false;
+            }
+            _ => {}
+        }
+        let message = format!(
+            "The import rules of this project run these commands on your computer when its files change:\n\n{}\n\nOnly run them if you trust the project.",
+            commands.join("\n")
+        );
+        let buttons = ["Run the commands".to_string(), "Don't run".to_string()];
+        let is_allowed = show_confirm("Run the import commands?", &message, &buttons) == 0;
+        trust.record(project_path, &commands, is_allowed);
+        if is_allowed {
+            self.is_refusal_reported = false;
+        } else {
+            self.report_refusal();
+        }
+        is_allowed
+    }
+
+    fn report_refusal(&mut self) {
+        if self.is_refusal_reported {
+            return;
+        }
+        self.is_refusal_reported = true;
+        print_warn_from(
+            Subsystem::Import,
+            "The import commands of the project are not run, as you did not allow them. Use File > Reimport all assets to be asked again.".to_string(),
+        );
+    }
+
+    /// Reports the conversions that finished. Their outputs are reloaded by the watcher like any other file.
+    pub fn poll(&mut self, project_path: &Path, project_info: &ProjectInfo) {
+        let mut is_any_finished = false;
+        while let Ok((job, result)) = self.results.try_recv() {
+            is_any_finished = true;
+            self.running.remove(&job.resource_path);
+            match result {
+                Ok(()) => {
+                    self.cache.record(&job);
+                    print_info_from(
+                        Subsystem::Import,
+                        format!(
+                            "Imported {} into {}",
+                            job.resource_path,
+                            get_import_output_stem(&job.resource_path)
+                        ),
+                    );
+                }
+                Err(error) => {
+                    self.cache.forget(&job.resource_path);
+                    print_err_from(Subsystem::Import, error);
+                }
+            }
+        }
+        if is_any_finished && !self.changed_while_running.is_empty() {
+            let changed = std::mem::take(&mut self.changed_while_running);
+            self.import(project_path, project_info, changed, false);
+        }
+    }
+}
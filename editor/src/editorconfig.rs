@@ -1,6 +1,13 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
 use runtime::game_resource::ResourceId;
 use serde::{Deserialize, Serialize};
 
+pub mod configmigration;
+
+pub const DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS: u32 = 30;
+
 #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
 pub enum WindowStyle {
     #[default]
@@ -42,6 +49,88 @@ impl std::fmt::Display for TextEditor {
     }
 }
 
+#[derive(Clone, Copy, Default, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum EditorTheme {
+    /// Follow the dark or light mode of the operating system.
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+
+impl std::fmt::Display for EditorTheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                EditorTheme::System => "System",
+                EditorTheme::Dark => "Dark",
+                EditorTheme::Light => "Light",
+            }
+        )
+    }
+}
+
+/// Which messages the console shows.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ConsoleFilter {
+    pub are_infos_shown: bool,
+    pub are_warnings_shown: bool,
+    pub are_errors_shown: bool,
+}
+
+impl Default for ConsoleFilter {
+    fn default() -> Self {
+        Self {
+            are_infos_shown: true,
+            are_warnings_shown: true,
+            are_errors_shown: true,
+        }
+    }
+}
+
+/// The debugging context of a project. It is kept when the project is reloaded and when the editor restarts,
+/// as a reload is often what the user does while looking for a bug.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ProjectDebugState {
+    /// Names of the globals shown in the watcher. They are kept while the scripts do not define them.
+    pub watched_variables: Vec<String>,
+    pub console_filter: ConsoleFilter,
+    /// When set, the console does not scroll to the new messages, so that what is being read stays in place.
+    pub is_console_scroll_locked: bool,
+    /// When set, the console evaluates what is typed as a read-only expression instead of sending it to the game.
+    pub is_console_read_only: bool,
+    pub breakpoints: Vec<Breakpoint>,
+}
+
+/// A line of a script on which the debugger stops the game.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// The path of the script in the project, like `scripts/player.luau`.
+    pub file: String,
+    pub line: u32,
+}
+
+impl Breakpoint {
+    /// The scripts are named after their path, which can start with the folder of the project.
+    pub fn matches(&self, script_path: &str, line: u32) -> bool {
+        let file = self.file.trim_start_matches("./").replace('\\', "/");
+        line == self.line && (script_path == file || script_path.ends_with(&format!("/{file}")))
+    }
+}
+
+/// The step reached in a tutorial, so that closing the editor in the middle of it resumes at the same step.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TutorialProgress {
+    /// The id of the tutorial embedded in the editor.
+    pub tutorial_id: String,
+    /// The manifest of the project generated for the tutorial. The steps are only shown while it is opened.
+    pub project_path: String,
+    pub step: usize,
+}
+
 /// The editor config contains settings that are not specific to any project and are persisted across editor launches.
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct EditorConfig {
@@ -51,6 +140,28 @@ pub struct EditorConfig {
     pub is_profiler_window_shown: bool,
     pub is_plugins_window_shown: bool,
     pub is_export_window_shown: bool,
+    #[serde(default)]
+    pub is_find_window_shown: bool,
+    #[serde(default)]
+    pub is_examples_window_shown: bool,
+    #[serde(default)]
+    pub is_gamepads_window_shown: bool,
+    #[serde(default)]
+    pub is_collision_layers_window_shown: bool,
+    #[serde(default)]
+    pub is_api_browser_window_shown: bool,
+    #[serde(default)]
+    pub is_debugger_window_shown: bool,
+    #[serde(default)]
+    pub is_inspector_window_shown: bool,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub api_browser_search: String,
+    /// The line of statistics of the playtest at the top of the game.
+    #[serde(default)]
+    pub is_stats_strip_shown: bool,
+    /// Set from the statistics of the playtest, to list the resources that failed to load.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub are_only_resource_errors_shown: bool,
     // The preference window should be closed when opening Vectarine
     #[serde(skip_serializing, skip_deserializing)]
     pub is_preferences_window_shown: bool,
@@ -59,8 +170,60 @@ pub struct EditorConfig {
     pub debug_resource_shown: Option<ResourceId>,
 
     pub window_style: WindowStyle,
+    /// When the editor and the game are in separate windows, the editor window is limited to this frame rate
+    /// while it is not focused.
+    #[serde(default)]
+    pub unfocused_editor_frame_rate: Option<u32>,
+    /// Check for OpenGL errors after every draw call. Always enabled in debug builds.
+    #[serde(default)]
+    pub is_gl_error_checking_enabled: bool,
+    /// Only run the edited script again when it changes, not the scripts loading it.
+    #[serde(default)]
+    pub is_single_script_reload_enabled: bool,
+    /// How often the changes of the tool store of the running game are written to disk. None uses
+    /// `DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS`.
+    #[serde(default)]
+    pub tool_store_autosave_seconds: Option<u32>,
+    #[serde(default)]
+    pub theme: EditorTheme,
+    /// Color of the selected items and links in the editor, as RGB. None uses the default of the theme.
+    #[serde(default)]
+    pub accent_color: Option<[u8; 3]>,
+    /// Shortcuts chosen in the preferences, by action id like "toggle_console", written like "Ctrl+Shift+P".
+    /// An empty text removes the default shortcut of the action.
+    #[serde(default)]
+    pub keybindings: BTreeMap<String, String>,
+    /// By path of the project manifest. The state used when no project is opened has an empty path.
+    #[serde(default)]
+    pub project_debug_states: BTreeMap<String, ProjectDebugState>,
 
     pub opened_project_path: Option<String>,
+    #[serde(default)]
+    pub tutorial_progress: Option<TutorialProgress>,
 
     pub text_editor: Option<TextEditor>,
 }
+
+impl EditorConfig {
+    pub fn project_debug_state(&self, project_path: Option<&Path>) -> ProjectDebugState {
+        self.project_debug_states
+            .get(&debug_state_key(project_path))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_project_debug_state(
+        &mut self,
+        project_path: Option<&Path>,
+        debug_state: ProjectDebugState,
+    ) {
+        self.project_debug_states
+            .insert(debug_state_key(project_path), debug_state);
+    }
+}
+
+fn debug_state_key(project_path: Option<&Path>) -> String {
+    project_path
+        .map(|project_path| project_path.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
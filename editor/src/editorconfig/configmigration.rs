@@ -0,0 +1,227 @@
+//! The editor config file carries a `config_version`. Files written by older editors are upgraded one version at a
+//! time before being read, so that renaming or moving a setting does not silently reset it for existing users.
+//!
+//! The per-project states (watched variables, console filters, ...) are stored in the same file, under
+//! `project_debug_states`, so the migrations upgrade them too.
+//!
+//! To change the shape of the file: add a migration at the end of `MIGRATIONS`, with a fixture of the previous
+//! version in `fixtures/` and a test reading it.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use runtime::toml;
+
+use crate::editorconfig::{EditorConfig, ProjectDebugState};
+
+const CONFIG_VERSION_KEY: &str = "config_version";
+
+/// Files written before versioning have no `config_version` and are version 1.
+const FIRST_CONFIG_VERSION: u32 = 1;
+
+/// `MIGRATIONS[i]` upgrades a file from version `i + 1` to version `i + 2`.
+const MIGRATIONS: &[fn(&mut toml::Table)] = &[fill_missing_settings];
+
+/// The version of the files written by this editor.
+pub const CURRENT_CONFIG_VERSION: u32 = FIRST_CONFIG_VERSION + MIGRATIONS.len() as u32;
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigLoadError {
+    /// The file was written by a newer editor, which may have moved settings this editor does not know about.
+    TooNew(u32),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLoadError::TooNew(version) => write!(
+                f,
+                "The editor settings were saved by a newer editor (version {version}, this editor reads up to version {CURRENT_CONFIG_VERSION})"
+            ),
+            ConfigLoadError::Invalid(reason) => {
+                write!(f, "The editor settings could not be read: {reason}")
+            }
+        }
+    }
+}
+
+/// Reads a config file of any known version. Returns the config and the version the file had.
+pub fn parse_editor_config(data: &[u8]) -> Result<(EditorConfig, u32), ConfigLoadError> {
+    let mut table = toml::from_slice::<toml::Table>(data)
+        .map_err(|error| ConfigLoadError::Invalid(error.to_string()))?;
+    let version = migrate(&mut table)?;
+    let config = toml::Value::Table(table)
+        .try_into::<EditorConfig>()
+        .map_err(|error| ConfigLoadError::Invalid(error.to_string()))?;
+    Ok((config, version))
+}
+
+/// Writes the config with the current version.
+pub fn serialize_editor_config(config: &EditorConfig) -> String {
+    let Ok(toml::Value::Table(mut table)) = toml::Value::try_from(config) else {
+        return String::new();
+    };
+    table.insert(
+        CONFIG_VERSION_KEY.to_string(),
+        toml::Value::Integer(CURRENT_CONFIG_VERSION as i64),
+    );
+    toml::to_string(&table).unwrap_or_default()
+}
+
+/// Upgrades the table to the current version. Returns the version it had.
+fn migrate(table: &mut toml::Table) -> Result<u32, ConfigLoadError> {
+    let version = match table.remove(CONFIG_VERSION_KEY) {
+        None => FIRST_CONFIG_VERSION,
+        Some(toml::Value::Integer(version)) if version >= FIRST_CONFIG_VERSION as i64 => {
+            u32::try_from(version).unwrap_or(u32::MAX)
+        }
+        Some(value) => {
+            return Err(ConfigLoadError::Invalid(format!(
+                "{CONFIG_VERSION_KEY} is {value}, not a version"
+            )));
+        }
+    };
+    if version > CURRENT_CONFIG_VERSION {
+        return Err(ConfigLoadError::TooNew(version));
+    }
+    for migration in &MIGRATIONS[(version - FIRST_CONFIG_VERSION) as usize..] {
+        migration(table);
+    }
+    Ok(version)
+}
+
+/// Copies the file next to itself before the editor replaces it with the default settings. Returns the copy.
+pub fn back_up_config_file(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let backup_path = (0..100)
+        .map(|index| match index {
+            0 => path.with_file_name(format!("{file_name}.backup")),
+            index => path.with_file_name(format!("{file_name}.backup{index}")),
+        })
+        .find(|backup_path| !backup_path.exists())?;
+    std::fs::copy(path, &backup_path).ok()?;
+    Some(backup_path)
+}
+
+/// Version 1 to 2. Editors before versioning wrote the settings they knew about, and a setting added later without a
+/// default made the whole file unreadable. The missing settings take their default value instead.
+fn fill_missing_settings(table: &mut toml::Table) {
+    if let Ok(toml::Value::Table(defaults)) = toml::Value::try_from(EditorConfig::default()) {
+        fill_missing_keys(table, &defaults);
+    }
+    let Ok(toml::Value::Table(project_defaults)) =
+        toml::Value::try_from(ProjectDebugState::default())
+    else {
+        return;
+    };
+    if let Some(toml::Value::Table(project_states)) = table.get_mut("project_debug_states") {
+        for project_state in project_states.values_mut() {
+            if let toml::Value::Table(project_state) = project_state {
+                fill_missing_keys(project_state, &project_defaults);
+            }
+        }
+    }
+}
+
+fn fill_missing_keys(table: &mut toml::Table, defaults: &toml::Table) {
+    for (key, default) in defaults {
+        match (table.get_mut(key), default) {
+            (None, default) => {
+                table.insert(key.clone(), default.clone());
+            }
+            (Some(toml::Value::Table(table)), toml::Value::Table(defaults)) => {
+                fill_missing_keys(table, defaults);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{
+        CONFIG_VERSION_KEY, CURRENT_CONFIG_VERSION, ConfigLoadError, parse_editor_config,
+        serialize_editor_config,
+    };
+    use crate::editorconfig::{
+        ConsoleFilter, EditorConfig, EditorTheme, ProjectDebugState, TextEditor, WindowStyle,
+    };
+
+    const VERSION_1_FIXTURE: &str = include_str!("fixtures/editor_config_v1.toml");
+
+    #[test]
+    fn version_1_files_keep_their_settings() {
+        let (config, version) =
+            parse_editor_config(VERSION_1_FIXTURE.as_bytes()).expect("version 1 is migrated");
+        assert_eq!(version, 1);
+        assert!(config.is_console_shown);
+        assert_eq!(config.window_style, WindowStyle::GameSeparateFromEditor);
+        assert_eq!(config.text_editor, Some(TextEditor::Zed));
+        assert_eq!(
+            config.opened_project_path.as_deref(),
+            Some("/home/player/games/platformer/game.vecta")
+        );
+        // Missing from the fixture, so they take their default.
+        assert!(!config.is_export_window_shown);
+        assert_eq!(config.theme, EditorTheme::System);
+
+        let debug_state =
+            config.project_debug_state(Some(Path::new("/home/player/games/platformer/game.vecta")));
+        assert_eq!(debug_state.watched_variables, vec!["Player".to_string()]);
+        assert_eq!(
+            debug_state.console_filter,
+            ConsoleFilter {
+                are_infos_shown: false,
+                ..ConsoleFilter::default()
+            }
+        );
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let mut config = EditorConfig {
+            is_console_shown: true,
+            is_watcher_window_shown: true,
+            window_style: WindowStyle::GameSeparateFromEditor,
+            unfocused_editor_frame_rate: Some(20),
+            theme: EditorTheme::Light,
+            accent_color: Some([200, 100, 50]),
+            opened_project_path: Some("/games/puzzle/game.vecta".to_string()),
+            text_editor: Some(TextEditor::Neovim),
+            ..EditorConfig::default()
+        };
+        config
+            .keybindings
+            .insert("toggle_console".to_string(), "Ctrl+Shift+C".to_string());
+        config.set_project_debug_state(
+            Some(Path::new("/games/puzzle/game.vecta")),
+            ProjectDebugState {
+                watched_variables: vec!["Board".to_string(), "#Board.pieces".to_string()],
+                is_console_read_only: true,
+                ..ProjectDebugState::default()
+            },
+        );
+
+        let data = serialize_editor_config(&config);
+        assert!(data.contains(&format!("{CONFIG_VERSION_KEY} = {CURRENT_CONFIG_VERSION}")));
+        let (read_config, version) =
+            parse_editor_config(data.as_bytes()).expect("the current version is read");
+        assert_eq!(version, CURRENT_CONFIG_VERSION);
+        // Comparing the serialized forms covers every field, including the ones added later.
+        assert_eq!(serialize_editor_config(&read_config), data);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let data = format!("{CONFIG_VERSION_KEY} = {}\n", CURRENT_CONFIG_VERSION + 1);
+        assert_eq!(
+            parse_editor_config(data.as_bytes()).map(|_| ()),
+            Err(ConfigLoadError::TooNew(CURRENT_CONFIG_VERSION + 1))
+        );
+    }
+}
@@ -10,7 +10,7 @@ use runtime::{
     graphics::batchdraw::BatchDraw2d,
     sdl2::{
         event::{Event, WindowEvent},
-        video::{GLContext, Window},
+        video::Window,
     },
 };
 use vectarine_plugin_sdk::glow::HasContext;
@@ -60,7 +60,6 @@ impl EditorInterfaceWithGl {
 pub fn render_editor_in_extra_window(
     sdl: &runtime::sdl2::Sdl,
     gl: &Arc<glow::Context>,
-    gl_context: &GLContext,
     editor_state: &mut EditorState,
     editor_interface: &mut EditorInterfaceWithGl,
     editor_window_events: &[runtime::sdl2::event::Event],
@@ -69,12 +68,9 @@ pub fn render_editor_in_extra_window(
         .editor_batch_draw
         .drawing_target
         .enable_multisampling();
+    // The editor window is already current, see the main loop.
     editor_state.editor_specific_window.show();
 
-    editor_state
-        .editor_specific_window
-        .gl_make_current(gl_context)
-        .expect("Failed to make context current");
     let (width, height) = drawable_screen_size(&editor_state.editor_specific_window);
     let aspect_ratio = width as f32 / height as f32;
     editor_state
@@ -1,7 +1,6 @@
 use std::{
     cell::RefCell,
     fs,
-    ops::Deref,
     path::{Path, PathBuf},
     rc::Rc,
     sync::{Arc, mpsc},
@@ -14,43 +13,76 @@ use notify_debouncer_full::{
 };
 use runtime::{
     anyhow::{self},
-    console, egui_glow,
+    apiversion, console, egui_glow,
     game::drawable_screen_size,
     glow,
     graphics::batchdraw::BatchDraw2d,
     io::{
         fs::{FileSystem, ReadOnlyFileSystem},
         localfs::LocalFileSystem,
+        request_quit,
     },
-    toml,
 };
 use vectarine_plugin_sdk::glow::HasContext;
 
 use crate::{
-    editorconfig::{EditorConfig, WindowStyle},
+    editorconfig::{
+        EditorConfig, ProjectDebugState, WindowStyle,
+        configmigration::{
+            CURRENT_CONFIG_VERSION, back_up_config_file, parse_editor_config,
+            serialize_editor_config,
+        },
+    },
     editorinterface::{
         editorplugins::{draw_editor_plugin_manager, draw_editor_plugin_windows},
         editorpreferences::draw_editor_preferences,
         emptyscreen::draw_empty_screen,
     },
+    editortheme::apply_theme,
     egui_sdl2_platform,
     export::exportinterface::draw_editor_export,
     pluginsystem::trustedplugin::{self, PluginEntry, TrustedPlugin},
     projectstate::ProjectState,
 };
+use editoractions::draw_toast;
+use editorapibrowser::draw_editor_api_browser;
+use editorcollisionlayers::draw_editor_collision_layers;
+use editorcommandpalette::draw_command_palette;
 use editorconsole::draw_editor_console;
+use editordebugger::draw_editor_debugger;
+use editorexamples::draw_editor_examples;
+use editorfind::draw_editor_find;
+use editorgamepads::draw_editor_gamepads;
+use editorinspector::draw_editor_inspector;
+use editormanifestconflict::draw_editor_manifest_conflict;
 use editormenu::draw_editor_menu;
 use editorprofiler::draw_editor_profiler;
 use editorresources::draw_editor_resources;
+use editorscriptdiff::draw_editor_script_diff;
+use editorstats::draw_stats_strip;
+use editortutorial::draw_tutorial;
 use editorwatcher::draw_editor_watcher;
 use vectarine_cli::project::geteditorpaths;
 
+pub mod editoractions;
+pub mod editorapibrowser;
+pub mod editorcollisionlayers;
+pub mod editorcommandpalette;
 pub mod editorconsole;
+pub mod editordebugger;
+pub mod editorexamples;
+pub mod editorfind;
+pub mod editorgamepads;
+pub mod editorinspector;
+pub mod editormanifestconflict;
 pub mod editormenu;
 pub mod editorplugins;
 pub mod editorpreferences;
 pub mod editorprofiler;
 pub mod editorresources;
+pub mod editorscriptdiff;
+pub mod editorstats;
+pub mod editortutorial;
 pub mod editorwatcher;
 pub mod emptyscreen;
 pub mod extra;
@@ -72,6 +104,9 @@ pub struct EditorState {
 
     pub editor_want_keyboard: bool,
     pub editor_want_mouse: bool,
+    /// When egui wants the interface to be drawn again even if no event arrives, like for an animation or a tooltip
+    /// appearing after a delay. `None` when it does not need to be drawn again.
+    pub repaint_deadline: Option<Instant>,
 
     pub plugins: Vec<PluginEntry>,
 }
@@ -79,7 +114,7 @@ pub struct EditorState {
 impl EditorState {
     pub fn save_config(&self) {
         let config = &self.config.borrow();
-        let data = toml::to_string(config.deref()).unwrap_or_default();
+        let data = serialize_editor_config(config);
 
         let config_path = geteditorpaths::get_editor_config_path();
         let parent = config_path.parent();
@@ -87,13 +122,19 @@ impl EditorState {
             let _ = fs::create_dir_all(parent);
         }
 
-        LocalFileSystem.write_file(
-            config_path
-                .to_str()
-                .expect("The editor path is valid unicode"), // otherwise, your installation / OS is very cursed
-            data.as_bytes(),
-            Box::new(|_| {}),
-        );
+        LocalFileSystem.write_file(&config_path, data.as_bytes(), Box::new(|_| {}));
+    }
+
+    /// Saved right away, so that the watched variables and the console filters survive a crash of the editor.
+    pub fn set_project_debug_state(
+        &self,
+        project_path: Option<&Path>,
+        debug_state: ProjectDebugState,
+    ) {
+        self.config
+            .borrow_mut()
+            .set_project_debug_state(project_path, debug_state);
+        self.save_config();
     }
 
     /// Load the editor config from file.
@@ -108,49 +149,67 @@ impl EditorState {
         let trusted_plugins = self.get_trusted_plugins();
 
         LocalFileSystem.read_file(
-            geteditorpaths::get_editor_config_path()
-                .to_str()
-                .expect("The editor path is valid unicode"),
+            &geteditorpaths::get_editor_config_path(),
             Box::new(move |data: Option<Vec<u8>>| {
                 let Some(data) = data else {
                     return; // no config file
                 };
-                if let Ok(config) = toml::from_slice::<EditorConfig>(data.as_slice()) {
-                    let previous_project_path = config.opened_project_path.clone();
-                    if let Some(project_path_str) = &previous_project_path {
-                        let previous_project_path = PathBuf::from(project_path_str);
-                        let parent = previous_project_path.parent();
-                        if let Some(parent) = parent {
-                            let _ = debouncer.borrow_mut().unwatch(parent);
+                let config = match parse_editor_config(data.as_slice()) {
+                    Ok((config, version)) => {
+                        if version < CURRENT_CONFIG_VERSION {
+                            console::print_info(format!(
+                                "Upgraded the editor settings from version {version} to version {CURRENT_CONFIG_VERSION}"
+                            ));
                         }
+                        config
                     }
+                    Err(error) => {
+                        // Copied first, as the editor overwrites the file with the default settings when it saves.
+                        let config_path = geteditorpaths::get_editor_config_path();
+                        console::print_warn(match back_up_config_file(&config_path) {
+                            Some(backup_path) => format!(
+                                "{error}. They were copied to {} and the editor starts with the default settings.",
+                                backup_path.display()
+                            ),
+                            None => format!("{error}. The editor starts with the default settings."),
+                        });
+                        return;
+                    }
+                };
+                let previous_project_path = config.opened_project_path.clone();
+                if let Some(project_path_str) = &previous_project_path {
+                    let previous_project_path = PathBuf::from(project_path_str);
+                    let parent = previous_project_path.parent();
+                    if let Some(parent) = parent {
+                        let _ = debouncer.borrow_mut().unwatch(parent);
+                    }
+                }
 
-                    *config_store.borrow_mut() = config;
-                    if auto_start_project
-                        && let Some(project_path_str) = &config_store.borrow().opened_project_path
-                    {
-                        let project_path = PathBuf::from(project_path_str);
-                        let parent = project_path.parent();
-                        if let Some(parent) = parent {
-                            let _ = debouncer
-                                .borrow_mut()
-                                .watch(parent, notify::RecursiveMode::Recursive);
-                        }
-
-                        ProjectState::new(
-                            &project_path,
-                            Box::new(LocalFileSystem),
-                            gl,
-                            video,
-                            window,
-                            &trusted_plugins,
-                            |loaded_project| {
-                                if let Ok(loaded_project) = loaded_project {
-                                    project.replace(Some(loaded_project));
-                                }
-                            },
-                        );
+                *config_store.borrow_mut() = config;
+                if auto_start_project
+                    && let Some(project_path_str) = &config_store.borrow().opened_project_path
+                {
+                    let project_path = PathBuf::from(project_path_str);
+                    let parent = project_path.parent();
+                    if let Some(parent) = parent {
+                        let _ = debouncer
+                            .borrow_mut()
+                            .watch(parent, notify::RecursiveMode::Recursive);
                     }
+
+                    ProjectState::new(
+                        &project_path,
+                        Box::new(LocalFileSystem),
+                        gl,
+                        video,
+                        window,
+                        &trusted_plugins,
+                        |loaded_project| {
+                            if let Ok(loaded_project) = loaded_project {
+                                project.replace(Some(loaded_project));
+                            }
+                        },
+                    );
                 }
             }),
         );
@@ -189,6 +248,7 @@ impl EditorState {
             )),
             editor_want_keyboard: false,
             editor_want_mouse: false,
+            repaint_deadline: Some(Instant::now()),
             plugins: trustedplugin::load_plugins(),
         }
     }
@@ -201,6 +261,8 @@ impl EditorState {
     ) where
         F: FnOnce(anyhow::Result<()>),
     {
+        // Reloads keep the migration report, another project starts a new one.
+        apiversion::clear_deprecated_calls();
         ProjectState::new(
             project_path,
             file_system,
@@ -242,20 +304,32 @@ impl EditorState {
     }
 
     pub fn close_project(&mut self) {
-        if let Some(proj) = &*self.project.borrow() {
+        if let Some(proj) = &mut *self.project.borrow_mut() {
+            proj.save_tool_store();
             let project_path = &proj.project_path;
             let parent = project_path.parent();
             if let Some(parent) = parent {
                 let _ = self.debouncer.borrow_mut().unwatch(parent);
             }
+            proj.game.lua_env.env_state.borrow_mut().rumble.stop_all();
         }
 
+        editorexamples::stop_running_example();
         self.project.borrow_mut().take();
         self.config.borrow_mut().opened_project_path = None;
         console::print_reload();
         self.save_config();
     }
 
+    /// Called when the game accepted to quit after its window was closed. A game drawn in the editor window quits the
+    /// editor with it.
+    pub fn stop_game(&mut self) {
+        if self.config.borrow().window_style == WindowStyle::GameWithEditor {
+            std::process::exit(0);
+        }
+        self.close_project();
+    }
+
     pub fn draw_editor_interface(
         &mut self,
         platform: &mut egui_sdl2_platform::Platform,
@@ -270,32 +344,57 @@ impl EditorState {
         let mut egui_eats_mouse = false;
 
         let full_output = platform.run(self, &mut |ui, editor_state| {
+            apply_theme(ui.ctx(), &editor_state.config.borrow());
             draw_editor_menu(editor_state, ui);
+            // Before the other windows, so that Escape closes the palette first.
+            draw_command_palette(editor_state, ui);
 
             if editor_state.project.borrow().is_none() {
                 draw_empty_screen(editor_state, ui);
             }
+            draw_stats_strip(editor_state, ui);
 
             draw_editor_console(editor_state, ui);
+            draw_editor_script_diff(ui);
             draw_editor_resources(editor_state, painter, ui);
             draw_editor_watcher(editor_state, ui);
+            draw_editor_debugger(editor_state, ui);
+            draw_editor_inspector(editor_state, ui);
             draw_editor_profiler(editor_state, ui);
+            draw_editor_find(editor_state, ui);
+            draw_editor_gamepads(editor_state, ui);
+            draw_editor_collision_layers(editor_state, ui);
+            draw_editor_api_browser(editor_state, ui);
+            draw_editor_examples(editor_state, ui);
             draw_editor_export(editor_state, ui);
             draw_editor_plugin_manager(editor_state, ui);
             draw_editor_plugin_windows(editor_state, ui);
             draw_editor_preferences(editor_state, ui);
+            draw_editor_manifest_conflict(editor_state, ui);
+            draw_tutorial(editor_state, ui);
+            draw_toast(ui);
 
             egui_eats_keyboard = ui.egui_wants_keyboard_input();
             egui_eats_mouse = ui.egui_wants_pointer_input() || ui.is_pointer_over_egui();
         });
 
         self.editor_want_keyboard = egui_eats_keyboard;
+        // The game may have stopped the text input with `Io.stopTextInput`, the fields of the editor still need it.
+        if egui_eats_keyboard && !self.video.text_input().is_active() {
+            self.video.text_input().start();
+        }
         self.editor_want_mouse = egui_eats_mouse;
 
         // Stop drawing the egui frame and get the full output
         // let full_output = platform.end_frame(&self.video);
         match full_output {
             Ok(full_output) => {
+                // The delay is Duration::MAX when egui does not need to be drawn again.
+                self.repaint_deadline = full_output
+                    .viewport_output
+                    .get(&runtime::egui::ViewportId::ROOT)
+                    .and_then(|viewport| Instant::now().checked_add(viewport.repaint_delay));
+
                 // Get the paint jobs
                 let paint_jobs = platform.tessellate(&full_output);
                 let pj = paint_jobs.as_slice();
@@ -317,10 +416,19 @@ impl EditorState {
                     &full_output.textures_delta,
                 );
             }
-            Err(e) => println!("Failed to render debug ui: {e:?}"),
+            Err(e) => {
+                self.repaint_deadline = Some(Instant::now());
+                println!("Failed to render debug ui: {e:?}");
+            }
         };
     }
 
+    /// Whether egui asked to be drawn again by now.
+    pub fn is_repaint_due(&self) -> bool {
+        self.repaint_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+    }
+
     pub fn get_trusted_plugins(&self) -> Vec<TrustedPlugin> {
         self.plugins
             .iter()
@@ -332,6 +440,33 @@ impl EditorState {
     }
 }
 
+/// Closing the game window stops the game, unless the game handles it with `Io.onQuitRequested`. Without a game, it
+/// quits the editor.
+pub fn handle_game_window_close_events(
+    editor: &mut EditorState,
+    game_window_events: &[sdl2::event::Event],
+) {
+    let is_closed = game_window_events.iter().any(|event| {
+        matches!(
+            event,
+            sdl2::event::Event::Window {
+                win_event: sdl2::event::WindowEvent::Close,
+                ..
+            }
+        )
+    });
+    if !is_closed {
+        return;
+    }
+    let is_game_stopped = match editor.project.borrow_mut().as_mut() {
+        Some(project) => request_quit(&mut project.game),
+        None => std::process::exit(0),
+    };
+    if is_game_stopped {
+        editor.stop_game();
+    }
+}
+
 pub fn handle_close_events(latest_events: &[sdl2::event::Event]) {
     for event in latest_events {
         if let sdl2::event::Event::Window { win_event, .. } = event
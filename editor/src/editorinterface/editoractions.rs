@@ -0,0 +1,691 @@
+use std::{cell::RefCell, collections::HashMap, time::Instant};
+
+use runtime::egui::{self, Key, KeyboardShortcut, Modifiers};
+use runtime::{apiversion, console, projectinfo::ProjectInfo};
+
+use crate::{
+    editorconfig::{EditorConfig, EditorTheme},
+    editorinterface::{
+        EditorState, editorcommandpalette, editorfind, editormenu,
+        emptyscreen::open_file_dialog_and_load_project,
+    },
+    editortheme::Palette,
+    export::exportinterface::validate_project_and_print_report,
+    glcontextloss,
+};
+
+const TOAST_DURATION_SECONDS: f32 = 3.0;
+
+/// The name of the key of `Modifiers::COMMAND` in the shortcuts shown to the user.
+const COMMAND_NAME: &str = if cfg!(target_os = "macos") {
+    "Cmd"
+} else {
+    "Ctrl"
+};
+
+/// The actions of the editor. The menus and the command palette both go through this list, so that every action
+/// of the menus can be searched and bound to a shortcut.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditorAction {
+    OpenProject,
+    ReloadProject,
+    CloseProject,
+    Export,
+    ValidateProject,
+    MigrationReport,
+    ReimportAssets,
+    OpenProjectFolder,
+    Exit,
+    PauseGame,
+    StepFrame,
+    ResumeGame,
+    ToggleConsole,
+    ToggleResources,
+    ToggleWatcher,
+    ToggleDebugger,
+    ToggleInspector,
+    ToggleProfiler,
+    ToggleStatsStrip,
+    FindInProject,
+    ToggleGamepads,
+    ToggleCollisionLayers,
+    ToggleApiBrowser,
+    SetTheme(EditorTheme),
+    SimulateContextLoss,
+    TogglePluginManager,
+    TogglePreferences,
+    OpenGuide,
+    ToggleApiExamples,
+    OpenGithub,
+    ShowAbout,
+    ShowCommandPalette,
+}
+
+impl EditorAction {
+    pub const ALL: [EditorAction; 34] = [
+        EditorAction::OpenProject,
+        EditorAction::ReloadProject,
+        EditorAction::CloseProject,
+        EditorAction::Export,
+        EditorAction::ValidateProject,
+        EditorAction::MigrationReport,
+        EditorAction::ReimportAssets,
+        EditorAction::OpenProjectFolder,
+        EditorAction::Exit,
+        EditorAction::PauseGame,
+        EditorAction::StepFrame,
+        EditorAction::ResumeGame,
+        EditorAction::ToggleConsole,
+        EditorAction::ToggleResources,
+        EditorAction::ToggleWatcher,
+        EditorAction::ToggleDebugger,
+        EditorAction::ToggleInspector,
+        EditorAction::ToggleProfiler,
+        EditorAction::ToggleStatsStrip,
+        EditorAction::FindInProject,
+        EditorAction::ToggleGamepads,
+        EditorAction::ToggleCollisionLayers,
+        EditorAction::ToggleApiBrowser,
+        EditorAction::SetTheme(EditorTheme::System),
+        EditorAction::SetTheme(EditorTheme::Dark),
+        EditorAction::SetTheme(EditorTheme::Light),
+        EditorAction::SimulateContextLoss,
+        EditorAction::TogglePluginManager,
+        EditorAction::TogglePreferences,
+        EditorAction::OpenGuide,
+        EditorAction::ToggleApiExamples,
+        EditorAction::OpenGithub,
+        EditorAction::ShowAbout,
+        EditorAction::ShowCommandPalette,
+    ];
+
+    /// The key of the action in the `keybindings` of the editor config.
+    pub fn id(self) -> &'static str {
+        match self {
+            EditorAction::OpenProject => "open_project",
+            EditorAction::ReloadProject => "reload_project",
+            EditorAction::CloseProject => "close_project",
+            EditorAction::Export => "export",
+            EditorAction::ValidateProject => "validate_project",
+            EditorAction::MigrationReport => "migration_report",
+            EditorAction::ReimportAssets => "reimport_assets",
+            EditorAction::OpenProjectFolder => "open_project_folder",
+            EditorAction::Exit => "exit",
+            EditorAction::PauseGame => "pause_game",
+            EditorAction::StepFrame => "step_frame",
+            EditorAction::ResumeGame => "resume_game",
+            EditorAction::ToggleConsole => "toggle_console",
+            EditorAction::ToggleResources => "toggle_resources",
+            EditorAction::ToggleWatcher => "toggle_watcher",
+            EditorAction::ToggleDebugger => "toggle_debugger",
+            EditorAction::ToggleInspector => "toggle_inspector",
+            EditorAction::ToggleProfiler => "toggle_profiler",
+            EditorAction::ToggleStatsStrip => "toggle_stats_strip",
+            EditorAction::FindInProject => "find_in_project",
+            EditorAction::ToggleGamepads => "toggle_gamepads",
+            EditorAction::ToggleCollisionLayers => "toggle_collision_layers",
+            EditorAction::ToggleApiBrowser => "toggle_api_browser",
+            EditorAction::SetTheme(EditorTheme::System) => "theme_system",
+            EditorAction::SetTheme(EditorTheme::Dark) => "theme_dark",
+            EditorAction::SetTheme(EditorTheme::Light) => "theme_light",
+            EditorAction::SimulateContextLoss => "simulate_context_loss",
+            EditorAction::TogglePluginManager => "toggle_plugin_manager",
+            EditorAction::TogglePreferences => "toggle_preferences",
+            EditorAction::OpenGuide => "open_guide",
+            EditorAction::ToggleApiExamples => "toggle_api_examples",
+            EditorAction::OpenGithub => "open_github",
+            EditorAction::ShowAbout => "show_about",
+            EditorAction::ShowCommandPalette => "show_command_palette",
+        }
+    }
+
+    /// The text of the action in the menus.
+    pub fn name(self) -> &'static str {
+        match self {
+            EditorAction::OpenProject => "Open project",
+            EditorAction::ReloadProject => "Reload project",
+            EditorAction::CloseProject => "Close project",
+            EditorAction::Export => "Export...",
+            EditorAction::ValidateProject => "Validate project",
+            EditorAction::MigrationReport => "Migration report",
+            EditorAction::ReimportAssets => "Reimport all assets",
+            EditorAction::OpenProjectFolder => "Open project folder",
+            EditorAction::Exit => "Exit",
+            EditorAction::PauseGame => "Pause",
+            EditorAction::StepFrame => "Step one frame",
+            EditorAction::ResumeGame => "Resume",
+            EditorAction::ToggleConsole => "Console",
+            EditorAction::ToggleResources => "Resources",
+            EditorAction::ToggleWatcher => "Watcher",
+            EditorAction::ToggleDebugger => "Debugger",
+            EditorAction::ToggleInspector => "Inspector",
+            EditorAction::ToggleProfiler => "Profiler",
+            EditorAction::ToggleStatsStrip => "Playtest stats",
+            EditorAction::FindInProject => "Find in project",
+            EditorAction::ToggleGamepads => "Gamepad setup",
+            EditorAction::ToggleCollisionLayers => "Collision layers",
+            EditorAction::ToggleApiBrowser => "API browser",
+            EditorAction::SetTheme(EditorTheme::System) => "System",
+            EditorAction::SetTheme(EditorTheme::Dark) => "Dark",
+            EditorAction::SetTheme(EditorTheme::Light) => "Light",
+            EditorAction::SimulateContextLoss => "Simulate context loss",
+            EditorAction::TogglePluginManager => "Plugin Manager",
+            EditorAction::TogglePreferences => "Preferences",
+            EditorAction::OpenGuide => "Offline Guide",
+            EditorAction::ToggleApiExamples => "API examples",
+            EditorAction::OpenGithub => "Github",
+            EditorAction::ShowAbout => "About",
+            EditorAction::ShowCommandPalette => "Command palette",
+        }
+    }
+
+    /// The text of the action in the command palette, with the menu it comes from.
+    pub fn palette_name(self) -> String {
+        let menu = match self {
+            EditorAction::OpenProject
+            | EditorAction::ReloadProject
+            | EditorAction::CloseProject
+            | EditorAction::Export
+            | EditorAction::ValidateProject
+            | EditorAction::MigrationReport
+            | EditorAction::ReimportAssets
+            | EditorAction::OpenProjectFolder
+            | EditorAction::Exit => "File",
+            EditorAction::PauseGame | EditorAction::StepFrame | EditorAction::ResumeGame => "Game",
+            EditorAction::ToggleConsole
+            | EditorAction::ToggleResources
+            | EditorAction::ToggleWatcher
+            | EditorAction::ToggleDebugger
+            | EditorAction::ToggleInspector
+            | EditorAction::ToggleProfiler
+            | EditorAction::ToggleStatsStrip
+            | EditorAction::FindInProject
+            | EditorAction::ToggleGamepads
+            | EditorAction::ToggleCollisionLayers
+            | EditorAction::ToggleApiBrowser
+            | EditorAction::SimulateContextLoss
+            | EditorAction::ShowCommandPalette => "Tools",
+            EditorAction::SetTheme(_) => "Tools > Theme",
+            EditorAction::TogglePluginManager => "Plugins",
+            EditorAction::TogglePreferences => "Preferences",
+            EditorAction::OpenGuide
+            | EditorAction::ToggleApiExamples
+            | EditorAction::OpenGithub
+            | EditorAction::ShowAbout => "Help",
+        };
+        format!("{menu}: {}", self.name().trim_end_matches("..."))
+    }
+
+    pub fn default_shortcut(self) -> Option<KeyboardShortcut> {
+        match self {
+            EditorAction::ReloadProject => Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::R)),
+            EditorAction::ToggleConsole => {
+                Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Num1))
+            }
+            EditorAction::ToggleResources => {
+                Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Num2))
+            }
+            EditorAction::ToggleWatcher => {
+                Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Num3))
+            }
+            EditorAction::ToggleProfiler => {
+                Some(KeyboardShortcut::new(Modifiers::COMMAND, Key::Num4))
+            }
+            EditorAction::ShowCommandPalette => Some(KeyboardShortcut::new(
+                Modifiers::COMMAND | Modifiers::SHIFT,
+                Key::P,
+            )),
+            EditorAction::FindInProject => Some(KeyboardShortcut::new(
+                Modifiers::COMMAND | Modifiers::SHIFT,
+                Key::F,
+            )),
+            _ => None,
+        }
+    }
+
+    /// The shortcut chosen in the preferences, or the default one.
+    pub fn shortcut(self, config: &EditorConfig) -> Option<KeyboardShortcut> {
+        match config.keybindings.get(self.id()) {
+            Some(shortcut) => parse_shortcut(shortcut),
+            None => self.default_shortcut(),
+        }
+    }
+
+    pub fn needs_project(self) -> bool {
+        matches!(
+            self,
+            EditorAction::ReloadProject
+                | EditorAction::CloseProject
+                | EditorAction::Export
+                | EditorAction::ValidateProject
+                | EditorAction::MigrationReport
+                | EditorAction::ReimportAssets
+                | EditorAction::OpenProjectFolder
+                | EditorAction::FindInProject
+                | EditorAction::PauseGame
+                | EditorAction::StepFrame
+                | EditorAction::ResumeGame
+        )
+    }
+
+    /// Debug actions are only available in debug builds.
+    pub fn is_available(self) -> bool {
+        self != EditorAction::SimulateContextLoss || cfg!(debug_assertions)
+    }
+}
+
+/// Runs the action. Actions needing a project show a toast instead when no project is open.
+pub fn run_action(editor: &mut EditorState, action: EditorAction) {
+    if action.needs_project() && editor.project.borrow().is_none() {
+        show_toast(format!(
+            "\"{}\" needs an open project. Open a project first.",
+            action.name().trim_end_matches("...")
+        ));
+        return;
+    }
+    match action {
+        EditorAction::OpenProject => open_file_dialog_and_load_project(editor),
+        EditorAction::ReloadProject => editor.reload_project(),
+        EditorAction::CloseProject => editor.close_project(),
+        EditorAction::Export => editor.config.borrow_mut().is_export_window_shown = true,
+        EditorAction::ValidateProject => {
+            if let Some(project) = editor.project.borrow().as_ref() {
+                validate_project_and_print_report(&project.project_path);
+            }
+        }
+        EditorAction::MigrationReport => {
+            if let Some(project) = editor.project.borrow().as_ref() {
+                print_migration_report(&project.project_info);
+            }
+        }
+        EditorAction::ReimportAssets => {
+            if let Some(project) = editor.project.borrow_mut().as_mut() {
+                project.import_assets(true);
+            }
+        }
+        EditorAction::OpenProjectFolder => {
+            let project = editor.project.borrow();
+            if let Some(folder) = project.as_ref().and_then(|p| p.project_path.parent())
+                && let Err(error) = open::that(folder)
+            {
+                console::print_err(error.to_string());
+            }
+        }
+        EditorAction::Exit => std::process::exit(0),
+        EditorAction::PauseGame | EditorAction::StepFrame | EditorAction::ResumeGame => {
+            let mut project = editor.project.borrow_mut();
+            let Some(project) = project.as_mut() else {
+                return;
+            };
+            match action {
+                EditorAction::PauseGame => project.game.pause(),
+                EditorAction::StepFrame => project.game.step_frame(),
+                _ => project.game.resume(),
+            }
+        }
+        EditorAction::ToggleConsole => {
+            let mut config = editor.config.borrow_mut();
+            config.is_console_shown = !config.is_console_shown;
+        }
+        EditorAction::ToggleResources => {
+            let mut config = editor.config.borrow_mut();
+            config.is_resources_window_shown = !config.is_resources_window_shown;
+        }
+        EditorAction::ToggleWatcher => {
+            let mut config = editor.config.borrow_mut();
+            config.is_watcher_window_shown = !config.is_watcher_window_shown;
+        }
+        EditorAction::ToggleDebugger => {
+            let mut config = editor.config.borrow_mut();
+            config.is_debugger_window_shown = !config.is_debugger_window_shown;
+        }
+        EditorAction::ToggleInspector => {
+            let mut config = editor.config.borrow_mut();
+            config.is_inspector_window_shown = !config.is_inspector_window_shown;
+        }
+        EditorAction::ToggleProfiler => {
+            let mut config = editor.config.borrow_mut();
+            config.is_profiler_window_shown = !config.is_profiler_window_shown;
+        }
+        EditorAction::ToggleStatsStrip => {
+            let mut config = editor.config.borrow_mut();
+            config.is_stats_strip_shown = !config.is_stats_strip_shown;
+        }
+        EditorAction::FindInProject => {
+            editor.config.borrow_mut().is_find_window_shown = true;
+            editorfind::focus_find_query();
+        }
+        EditorAction::ToggleGamepads => {
+            let mut config = editor.config.borrow_mut();
+            config.is_gamepads_window_shown = !config.is_gamepads_window_shown;
+        }
+        EditorAction::ToggleCollisionLayers => {
+            let mut config = editor.config.borrow_mut();
+            config.is_collision_layers_window_shown = !config.is_collision_layers_window_shown;
+        }
+        EditorAction::ToggleApiBrowser => {
+            let mut config = editor.config.borrow_mut();
+            config.is_api_browser_window_shown = !config.is_api_browser_window_shown;
+        }
+        EditorAction::SetTheme(theme) => {
+            editor.config.borrow_mut().theme = theme;
+            editor.save_config();
+        }
+        EditorAction::SimulateContextLoss => glcontextloss::simulate_context_loss(),
+        EditorAction::TogglePluginManager => {
+            let mut config = editor.config.borrow_mut();
+            config.is_plugins_window_shown = !config.is_plugins_window_shown;
+        }
+        EditorAction::TogglePreferences => {
+            let mut config = editor.config.borrow_mut();
+            config.is_preferences_window_shown = !config.is_preferences_window_shown;
+        }
+        EditorAction::OpenGuide => editormenu::open_offline_guide(),
+        EditorAction::ToggleApiExamples => {
+            let mut config = editor.config.borrow_mut();
+            config.is_examples_window_shown = !config.is_examples_window_shown;
+        }
+        EditorAction::OpenGithub => {
+            if let Err(error) = open::that("https://github.com/vanyle/vectarine") {
+                console::print_err(error.to_string());
+            }
+        }
+        EditorAction::ShowAbout => editormenu::show_about_window(),
+        EditorAction::ShowCommandPalette => editorcommandpalette::toggle_command_palette(),
+    }
+}
+
+/// Prints the deprecated functions the project called since it was opened, with the lines calling them.
+fn print_migration_report(project_info: &ProjectInfo) {
+    let calls = apiversion::get_deprecated_calls();
+    for call in &calls {
+        let call_count = call.call_sites.values().sum::<usize>();
+        console::print_warn(format!(
+            "{} is deprecated, use {} instead. Called {call_count} times from:",
+            call.old_name, call.new_name
+        ));
+        for (call_site, count) in &call.call_sites {
+            console::print_warn(format!("    {call_site} ({count} calls)"));
+        }
+    }
+    console::print_info(format!(
+        "The project uses version {} of the API, the engine version {}. {} deprecated functions were called since the project was opened.",
+        project_info.api_version(),
+        apiversion::CURRENT_API_VERSION,
+        calls.len()
+    ));
+}
+
+/// A button for the action in a menu, with its shortcut.
+pub fn action_button(ui: &mut egui::Ui, editor: &mut EditorState, action: EditorAction) {
+    if !action.is_available() {
+        return;
+    }
+    let mut button = egui::Button::new(action.name());
+    if let Some(shortcut) = action.shortcut(&editor.config.borrow()) {
+        button = button.shortcut_text(shortcut_to_string(&shortcut));
+    }
+    if ui.add(button).clicked() {
+        run_action(editor, action);
+    }
+}
+
+/// Runs the actions whose shortcut was pressed.
+pub fn handle_action_shortcuts(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let shortcuts = EditorAction::ALL
+        .iter()
+        .filter(|action| action.is_available())
+        .filter_map(|action| Some((*action, action.shortcut(&editor.config.borrow())?)))
+        .collect::<Vec<_>>();
+    let pressed_actions = ui.input_mut(|i| {
+        let mut pressed_actions = Vec::new();
+        i.events.retain(|event| {
+            let egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } = event
+            else {
+                return true;
+            };
+            let Some(action) = find_pressed_action(&shortcuts, *modifiers, *key) else {
+                return true;
+            };
+            pressed_actions.push(action);
+            false
+        });
+        pressed_actions
+    });
+    for action in pressed_actions {
+        run_action(editor, action);
+    }
+}
+
+/// The action whose shortcut matches the pressed key. Ctrl+P also matches when Ctrl+Shift+P is pressed, so the
+/// shortcut with the most modifiers wins. When several actions share a shortcut, the first one wins.
+fn find_pressed_action(
+    shortcuts: &[(EditorAction, KeyboardShortcut)],
+    modifiers: Modifiers,
+    key: Key,
+) -> Option<EditorAction> {
+    shortcuts
+        .iter()
+        .filter(|(_, shortcut)| {
+            shortcut.logical_key == key && modifiers.matches_logically(shortcut.modifiers)
+        })
+        .min_by_key(|(_, shortcut)| {
+            let modifiers = shortcut.modifiers;
+            std::cmp::Reverse(
+                [
+                    modifiers.ctrl || modifiers.command,
+                    modifiers.mac_cmd,
+                    modifiers.alt,
+                    modifiers.shift,
+                ]
+                .into_iter()
+                .filter(|is_pressed| *is_pressed)
+                .count(),
+            )
+        })
+        .map(|(action, _)| *action)
+}
+
+/// The shortcuts used by more than one action, with the names of these actions.
+pub fn find_shortcut_conflicts(config: &EditorConfig) -> Vec<(String, Vec<String>)> {
+    let mut actions_by_shortcut = HashMap::<String, Vec<String>>::new();
+    for action in EditorAction::ALL.into_iter().filter(|a| a.is_available()) {
+        if let Some(shortcut) = action.shortcut(config) {
+            actions_by_shortcut
+                .entry(shortcut_to_string(&shortcut))
+                .or_default()
+                .push(action.palette_name());
+        }
+    }
+    let mut conflicts = actions_by_shortcut
+        .into_iter()
+        .filter(|(_, actions)| actions.len() > 1)
+        .collect::<Vec<_>>();
+    conflicts.sort();
+    conflicts
+}
+
+/// Parses shortcuts like "Ctrl+Shift+P". Returns None for an empty text, which means that the action has no shortcut.
+/// "Ctrl" and "Cmd" both stand for Cmd on macOS and Ctrl elsewhere, so that the shortcuts work on every platform.
+pub fn parse_shortcut(text: &str) -> Option<KeyboardShortcut> {
+    let mut modifiers = Modifiers::NONE;
+    let mut parts = text.split('+').map(str::trim).peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            return Some(KeyboardShortcut::new(modifiers, Key::from_name(part)?));
+        }
+        match part.to_lowercase().as_str() {
+            "ctrl" | "cmd" => modifiers = modifiers | Modifiers::COMMAND,
+            "shift" => modifiers = modifiers | Modifiers::SHIFT,
+            "alt" => modifiers = modifiers | Modifiers::ALT,
+            _ => return None,
+        }
+    }
+    None
+}
+
+pub fn shortcut_to_string(shortcut: &KeyboardShortcut) -> String {
+    let mut text = String::new();
+    let modifiers = shortcut.modifiers;
+    if modifiers.ctrl || modifiers.command || modifiers.mac_cmd {
+        text.push_str(COMMAND_NAME);
+        text.push('+');
+    }
+    if shortcut.modifiers.alt {
+        text.push_str("Alt+");
+    }
+    if shortcut.modifiers.shift {
+        text.push_str("Shift+");
+    }
+    text.push_str(shortcut.logical_key.name());
+    text
+}
+
+thread_local! {
+    static TOAST: RefCell<Option<(String, Instant)>> = const { RefCell::new(None) };
+}
+
+/// Shows a short message at the bottom of the editor for a few seconds.
+pub fn show_toast(message: String) {
+    TOAST.set(Some((message, Instant::now())));
+}
+
+pub fn draw_toast(ui: &mut egui::Ui) {
+    let message = TOAST.with_borrow(|toast| {
+        toast
+            .as_ref()
+            .filter(|(_, shown_at)| shown_at.elapsed().as_secs_f32() < TOAST_DURATION_SECONDS)
+            .map(|(message, _)| message.clone())
+    });
+    let Some(message) = message else {
+        TOAST.set(None);
+        return;
+    };
+    egui::Area::new(egui::Id::new("editor_toast"))
+        .anchor(egui::Align2::CENTER_BOTTOM, egui::vec2(0.0, -40.0))
+        .order(egui::Order::Tooltip)
+        .show(ui, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.colored_label(Palette::of(ui).warning, message);
+            });
+        });
+    // Repaint to hide the toast even when nothing else happens.
+    ui.ctx().request_repaint();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use runtime::egui::{Key, KeyboardShortcut, Modifiers};
+
+    use super::{
+        COMMAND_NAME, EditorAction, find_pressed_action, parse_shortcut, shortcut_to_string,
+    };
+
+    /// The modifiers egui reports when the command key (Cmd on macOS, Ctrl elsewhere) is held.
+    fn command_pressed() -> Modifiers {
+        if cfg!(target_os = "macos") {
+            Modifiers::MAC_CMD | Modifiers::COMMAND
+        } else {
+            Modifiers::CTRL | Modifiers::COMMAND
+        }
+    }
+
+    #[test]
+    fn shortcuts_are_parsed_and_written_back() {
+        let shortcut = parse_shortcut("Ctrl+Shift+P").expect("valid shortcut");
+        assert_eq!(
+            shortcut,
+            KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::P)
+        );
+        assert_eq!(
+            shortcut_to_string(&shortcut),
+            format!("{COMMAND_NAME}+Shift+P")
+        );
+        assert_eq!(parse_shortcut("cmd + R"), parse_shortcut("Ctrl+R"));
+        assert_eq!(parse_shortcut("Hyper+R"), None);
+        assert_eq!(parse_shortcut(""), None);
+    }
+
+    #[test]
+    fn the_shortcut_with_the_most_modifiers_wins() {
+        let shortcuts = [
+            (
+                EditorAction::ToggleConsole,
+                KeyboardShortcut::new(Modifiers::COMMAND, Key::P),
+            ),
+            (
+                EditorAction::ShowCommandPalette,
+                KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::P),
+            ),
+            (
+                EditorAction::ToggleWatcher,
+                KeyboardShortcut::new(Modifiers::COMMAND, Key::P),
+            ),
+        ];
+        assert_eq!(
+            find_pressed_action(&shortcuts, command_pressed() | Modifiers::SHIFT, Key::P),
+            Some(EditorAction::ShowCommandPalette)
+        );
+        // The first of the actions sharing a shortcut runs.
+        assert_eq!(
+            find_pressed_action(&shortcuts, command_pressed(), Key::P),
+            Some(EditorAction::ToggleConsole)
+        );
+        assert_eq!(
+            find_pressed_action(&shortcuts, Modifiers::NONE, Key::P),
+            None
+        );
+        assert_eq!(
+            find_pressed_action(&shortcuts, command_pressed(), Key::R),
+            None
+        );
+    }
+
+    #[test]
+    fn default_shortcuts_use_the_command_key() {
+        let shortcuts = EditorAction::ALL
+            .into_iter()
+            .filter_map(|action| Some((action, action.default_shortcut()?)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            find_pressed_action(&shortcuts, command_pressed(), Key::R),
+            Some(EditorAction::ReloadProject)
+        );
+        // Ctrl is not the command key on macOS.
+        let other_key = if cfg!(target_os = "macos") {
+            Modifiers::CTRL
+        } else {
+            Modifiers::MAC_CMD
+        };
+        assert_eq!(find_pressed_action(&shortcuts, other_key, Key::R), None);
+    }
+
+    #[test]
+    fn custom_keybindings_replace_the_defaults() {
+        let mut config = crate::editorconfig::EditorConfig {
+            keybindings: BTreeMap::from([
+                ("reload_project".to_string(), String::new()),
+                ("toggle_console".to_string(), "Ctrl+2".to_string()),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(EditorAction::ReloadProject.shortcut(&config), None);
+        let conflicts = super::find_shortcut_conflicts(&config);
+        assert_eq!(
+            conflicts,
+            vec![(
+                format!("{COMMAND_NAME}+2"),
+                vec!["Tools: Console".to_string(), "Tools: Resources".to_string()]
+            )]
+        );
+        config.keybindings.clear();
+        assert!(super::find_shortcut_conflicts(&config).is_empty());
+    }
+}
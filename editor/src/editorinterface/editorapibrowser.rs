@@ -0,0 +1,104 @@
+use runtime::egui::{self, RichText};
+use runtime::lua_env::RegisteredModule;
+
+use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+
+/// Lists the `@vectarine/*` modules registered by the runtime, with the functions and values they export.
+pub fn draw_editor_api_browser(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_api_browser_window_shown;
+    if !is_shown {
+        return;
+    }
+
+    egui::Window::new("API browser")
+        .default_width(360.0)
+        .default_height(480.0)
+        .open(&mut is_shown)
+        .show(ui, |ui| {
+            let project = editor.project.borrow();
+            let Some(project) = project.as_ref() else {
+                ui.label("No project is currently loaded.");
+                return;
+            };
+            let mut config = editor.config.borrow_mut();
+            ui.add(
+                egui::TextEdit::singleline(&mut config.api_browser_search)
+                    .hint_text("Search a module or a member"),
+            );
+            let search = config.api_browser_search.trim().to_lowercase();
+            let modules = filter_modules(project.game.lua_env.registered_modules(), &search);
+            let muted_text = Palette::of(ui).muted_text;
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                if modules.is_empty() {
+                    ui.label(RichText::new("Nothing matches the search.").color(muted_text));
+                }
+                for module in modules {
+                    egui::CollapsingHeader::new(format!("@vectarine/{}", module.name))
+                        .open((!search.is_empty()).then_some(true))
+                        .show(ui, |ui| {
+                            for member in module.members {
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new(member.name).monospace());
+                                    ui.label(RichText::new(member.type_name).color(muted_text));
+                                });
+                            }
+                        });
+                }
+            });
+        });
+    editor.config.borrow_mut().is_api_browser_window_shown = is_shown;
+}
+
+/// Keeps the modules whose name matches the search with all their members, and the members matching the search of the
+/// other modules.
+fn filter_modules(modules: Vec<RegisteredModule>, search: &str) -> Vec<RegisteredModule> {
+    modules
+        .into_iter()
+        .filter_map(|mut module| {
+            if !module.name.to_lowercase().contains(search) {
+                module
+                    .members
+                    .retain(|member| member.name.to_lowercase().contains(search));
+                if module.members.is_empty() {
+                    return None;
+                }
+            }
+            Some(module)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use runtime::lua_env::{ModuleMember, RegisteredModule};
+
+    use super::filter_modules;
+
+    #[test]
+    fn the_search_matches_modules_and_members() {
+        let module = |name: &str, members: &[&str]| RegisteredModule {
+            name: name.to_string(),
+            members: members
+                .iter()
+                .map(|member| ModuleMember {
+                    name: member.to_string(),
+                    type_name: "function",
+                })
+                .collect(),
+        };
+        let modules = vec![
+            module("graphics", &["drawRect", "drawCircle", "clear"]),
+            module("vec", &["V2", "ZERO2"]),
+        ];
+        assert_eq!(filter_modules(modules.clone(), ""), modules);
+        assert_eq!(
+            filter_modules(modules.clone(), "rect"),
+            vec![module("graphics", &["drawRect"])]
+        );
+        assert_eq!(
+            filter_modules(modules, "vec"),
+            vec![module("vec", &["V2", "ZERO2"])]
+        );
+    }
+}
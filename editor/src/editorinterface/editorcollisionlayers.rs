@@ -0,0 +1,237 @@
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+use runtime::console;
+use runtime::egui::{self, RichText};
+use runtime::lua_env::lua_physics::layers::{CollisionLayers, MAX_COLLISION_LAYERS};
+
+use crate::editorinterface::EditorState;
+use crate::editorinterface::editorwatcher::applytosource::collect_scripts;
+use crate::editortheme::Palette;
+use crate::projectstate::ProjectState;
+
+/// Edits the `[physics.layers]` section of game.vecta: the names of the layers and which of them collide.
+pub fn draw_editor_collision_layers(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_collision_layers_window_shown;
+    if !is_shown {
+        return;
+    }
+
+    egui::Window::new("Collision layers")
+        .default_width(420.0)
+        .default_height(400.0)
+        .open(&mut is_shown)
+        .show(ui, |ui| {
+            let mut project = editor.project.borrow_mut();
+            let Some(project) = project.as_mut() else {
+                ui.label("No project is currently loaded.");
+                return;
+            };
+            ui.label(
+                RichText::new(
+                    "Get a layer in the scripts with Physics.layer(\"name\") and pass it in the layer option of createObject or createPool.",
+                )
+                .color(Palette::of(ui).muted_text),
+            );
+            ui.add_enabled_ui(!project.is_read_only, |ui| {
+                egui::ScrollArea::both().show(ui, |ui| {
+                    draw_collision_layers(ui, project);
+                });
+            });
+        });
+    editor.config.borrow_mut().is_collision_layers_window_shown = is_shown;
+}
+
+fn draw_collision_layers(ui: &mut egui::Ui, project: &mut ProjectState) {
+    let layers = &mut project.project_info.physics.layers;
+    let mut is_edited = false;
+    let mut should_save = false;
+
+    let mut removed_layer = None;
+    let mut renamed_layer = None;
+    for index in 0..layers.names.len() {
+        ui.horizontal(|ui| {
+            let mut name = layers.names[index].clone();
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut name)
+                    .hint_text("Layer name")
+                    .desired_width(160.0),
+            );
+            // The layer is renamed at every key, the scripts only once the name is typed.
+            let name_before_edit_id = response.id.with("name_before_edit");
+            if response.gained_focus() {
+                ui.data_mut(|data| {
+                    data.insert_temp(name_before_edit_id, layers.names[index].clone())
+                });
+            }
+            if response.changed() {
+                layers.rename(index, name.trim());
+                is_edited = true;
+            }
+            if response.lost_focus() {
+                should_save = true;
+                let name_before_edit =
+                    ui.data_mut(|data| data.remove_temp::<String>(name_before_edit_id));
+                if let Some(name_before_edit) = name_before_edit
+                    && name_before_edit != layers.names[index]
+                {
+                    renamed_layer = Some((name_before_edit, layers.names[index].clone()));
+                }
+            }
+            if ui
+                .small_button("✖")
+                .on_hover_text("Remove the layer")
+                .clicked()
+            {
+                removed_layer = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed_layer {
+        layers.remove(index);
+        is_edited = true;
+        should_save = true;
+    }
+    let can_add_layer = layers.names.len() < MAX_COLLISION_LAYERS;
+    if ui
+        .add_enabled(can_add_layer, egui::Button::new("Add layer"))
+        .on_disabled_hover_text(format!(
+            "A project has at most {MAX_COLLISION_LAYERS} layers"
+        ))
+        .clicked()
+    {
+        layers.names.push(new_layer_name(layers));
+        is_edited = true;
+        should_save = true;
+    }
+
+    if !layers.names.is_empty() {
+        ui.add_space(8.0);
+        ui.label(RichText::new("Collisions").heading());
+        should_save |= draw_collision_matrix(ui, layers);
+        is_edited |= should_save;
+    }
+
+    let palette = Palette::of(ui);
+    for error in layers.errors() {
+        ui.colored_label(palette.error, error);
+    }
+    for warning in layers.warnings() {
+        ui.colored_label(palette.warning, warning);
+    }
+
+    if is_edited {
+        project.is_project_info_dirty = true;
+        project.apply_collision_layers();
+    }
+    if should_save && !project.is_read_only {
+        project.save_project_info();
+        project.is_project_info_dirty = false;
+    }
+    if let Some((old_name, new_name)) = renamed_layer
+        && !project.is_read_only
+        && let Some(project_folder) = project.project_path.parent()
+    {
+        rename_layer_in_scripts(project_folder, &old_name, &new_name);
+    }
+}
+
+/// Replaces the calls to `Physics.layer` with the old name of a layer in the scripts of the project. They are then
+/// reloaded like after any other change.
+fn rename_layer_in_scripts(project_folder: &Path, old_name: &str, new_name: &str) {
+    if old_name.is_empty() || new_name.is_empty() {
+        return;
+    }
+    let mut scripts = Vec::new();
+    collect_scripts(project_folder, &mut scripts);
+    for (script, source) in scripts {
+        let Some(renamed) = rename_layer_calls(&source, old_name, new_name) else {
+            continue;
+        };
+        match fs::write(&script, renamed) {
+            Ok(()) => console::print_info(format!(
+                "Renamed the collision layer '{old_name}' to '{new_name}' in {}",
+                script.display()
+            )),
+            Err(error) => console::print_err(format!(
+                "The collision layer '{old_name}' could not be renamed in {}: {error}",
+                script.display()
+            )),
+        }
+    }
+}
+
+/// Returns None when the source has no call to `Physics.layer` with the old name.
+fn rename_layer_calls(source: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let call_regex = Regex::new(&format!(
+        r#"(Physics\.layer\(\s*)(?:"{0}"|'{0}')(\s*\))"#,
+        regex::escape(old_name)
+    ))
+    .ok()?;
+    if !call_regex.is_match(source) {
+        return None;
+    }
+    // `$` starts a group in the replacement.
+    let replacement = format!("${{1}}\"{}\"${{2}}", new_name.replace('$', "$$"));
+    Some(
+        call_regex
+            .replace_all(source, replacement.as_str())
+            .into_owned(),
+    )
+}
+
+/// A checkbox for each pair of layers, in a triangle as the matrix is symmetric. Returns true when a box changed.
+fn draw_collision_matrix(ui: &mut egui::Ui, layers: &mut CollisionLayers) -> bool {
+    let mut is_changed = false;
+    egui::Grid::new("collision_layers_matrix")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label("");
+            for name in layers.names.iter().rev() {
+                ui.label(name);
+            }
+            ui.end_row();
+            let names = layers.names.clone();
+            for (row, first) in names.iter().enumerate() {
+                ui.label(first);
+                for second in names[row..].iter().rev() {
+                    let mut collides = layers.collides(first, second);
+                    if ui
+                        .checkbox(&mut collides, "")
+                        .on_hover_text(format!("{first} and {second} collide"))
+                        .changed()
+                    {
+                        layers.set_collides(first, second, collides);
+                        is_changed = true;
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    is_changed
+}
+
+fn new_layer_name(layers: &CollisionLayers) -> String {
+    (1..)
+        .map(|number| format!("layer{number}"))
+        .find(|name| layers.index_of(name).is_none())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rename_layer_calls;
+
+    #[test]
+    fn only_the_calls_with_the_old_name_are_renamed() {
+        let source = "local a = Physics.layer(\"player\")\nlocal b = Physics.layer( 'player' )\nlocal c = Physics.layer(\"players\")\nprint(\"player\")\n";
+        assert_eq!(
+            rename_layer_calls(source, "player", "hero").as_deref(),
+            Some(
+                "local a = Physics.layer(\"hero\")\nlocal b = Physics.layer( \"hero\" )\nlocal c = Physics.layer(\"players\")\nprint(\"player\")\n"
+            )
+        );
+        assert_eq!(rename_layer_calls(source, "enemy", "foe"), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+};
+
+use runtime::egui;
+
+use crate::editorinterface::{
+    EditorState,
+    editoractions::{EditorAction, run_action, shortcut_to_string},
+};
+
+const MAX_SHOWN_ENTRIES: usize = 12;
+
+thread_local! {
+    static IS_SHOWN: Cell<bool> = const { Cell::new(false) };
+    static QUERY: RefCell<String> = const { RefCell::new(String::new()) };
+    static SELECTED_INDEX: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn toggle_command_palette() {
+    IS_SHOWN.set(!IS_SHOWN.get());
+    QUERY.with_borrow_mut(|query| query.clear());
+    SELECTED_INDEX.set(0);
+}
+
+enum PaletteCommand {
+    Action(EditorAction),
+    /// Plugins appear in the palette as soon as they are trusted and loaded by the project.
+    TogglePluginInterface(PathBuf),
+}
+
+struct PaletteEntry {
+    name: String,
+    shortcut: Option<String>,
+    command: PaletteCommand,
+}
+
+fn get_palette_entries(editor: &EditorState) -> Vec<PaletteEntry> {
+    let config = editor.config.borrow();
+    let mut entries = EditorAction::ALL
+        .into_iter()
+        .filter(|action| action.is_available() && *action != EditorAction::ShowCommandPalette)
+        .map(|action| PaletteEntry {
+            name: action.palette_name(),
+            shortcut: action
+                .shortcut(&config)
+                .map(|shortcut| shortcut_to_string(&shortcut)),
+            command: PaletteCommand::Action(action),
+        })
+        .collect::<Vec<_>>();
+    if let Some(project) = editor.project.borrow_mut().as_mut() {
+        project.for_each_trusted_plugin_mut(|game_plugin| {
+            let Some(trusted_plugin) = &game_plugin.trusted_plugin else {
+                return;
+            };
+            entries.push(PaletteEntry {
+                name: format!("Plugins: Toggle the interface of {}", trusted_plugin.name),
+                shortcut: None,
+                command: PaletteCommand::TogglePluginInterface(game_plugin.path.clone()),
+            });
+        });
+    }
+    entries
+}
+
+/// Scores how well the query matches the text. The characters of the query need to appear in the text in order.
+/// Consecutive characters and characters at the start of a word score higher. None if the query does not match.
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut text_index = 0;
+    let mut previous_match: Option<usize> = None;
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = text[text_index..].iter().position(|c| *c == query_char)? + text_index;
+        score += 1;
+        if previous_match.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous_match = Some(found);
+        text_index = found + 1;
+    }
+    // Shorter texts are closer to what was typed.
+    Some(score * 100 - text.len() as i32)
+}
+
+pub fn draw_command_palette(editor: &mut EditorState, ui: &mut egui::Ui) {
+    if !IS_SHOWN.get() {
+        return;
+    }
+    let entries = get_palette_entries(editor);
+    let query = QUERY.with_borrow(|query| query.clone());
+    let mut matching_entries = entries
+        .iter()
+        .filter_map(|entry| Some((fuzzy_score(&query, &entry.name)?, entry)))
+        .collect::<Vec<_>>();
+    matching_entries.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matching_entries.truncate(MAX_SHOWN_ENTRIES);
+
+    let mut selected_index = SELECTED_INDEX
+        .get()
+        .min(matching_entries.len().saturating_sub(1));
+    if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+        selected_index = (selected_index + 1).min(matching_entries.len().saturating_sub(1));
+    }
+    if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+        selected_index = selected_index.saturating_sub(1);
+    }
+    let mut chosen_index = None;
+    if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) {
+        chosen_index = Some(selected_index);
+    }
+    let mut should_close =
+        ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape));
+
+    egui::Window::new("Command palette")
+        .title_bar(false)
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+        .fixed_size(egui::vec2(420.0, 0.0))
+        .show(ui, |ui| {
+            QUERY.with_borrow_mut(|query| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(query)
+                        .hint_text("Search an action")
+                        .desired_width(f32::INFINITY),
+                );
+                response.request_focus();
+                if response.changed() {
+                    selected_index = 0;
+                }
+            });
+            ui.separator();
+            if matching_entries.is_empty() {
+                ui.weak("No matching action");
+            }
+            for (index, (_, entry)) in matching_entries.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let response = ui.selectable_label(index == selected_index, &entry.name);
+                    if response.clicked() {
+                        chosen_index = Some(index);
+                    }
+                    if response.hovered() {
+                        selected_index = index;
+                    }
+                    if let Some(shortcut) = &entry.shortcut {
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.weak(shortcut);
+                        });
+                    }
+                });
+            }
+        });
+    SELECTED_INDEX.set(selected_index);
+
+    let chosen_command = chosen_index
+        .and_then(|index| matching_entries.get(index))
+        .map(|(_, entry)| &entry.command);
+    if let Some(command) = chosen_command {
+        should_close = true;
+        match command {
+            PaletteCommand::Action(action) => run_action(editor, *action),
+            PaletteCommand::TogglePluginInterface(plugin_path) => {
+                if let Some(project) = editor.project.borrow_mut().as_mut() {
+                    project.for_each_trusted_plugin_mut(|game_plugin| {
+                        if &game_plugin.path == plugin_path {
+                            game_plugin.is_debug_interface_shown =
+                                !game_plugin.is_debug_interface_shown;
+                        }
+                    });
+                }
+            }
+        }
+    }
+    if should_close {
+        IS_SHOWN.set(false);
+    }
+}
@@ -1,27 +1,38 @@
 use std::path::Path;
-use std::sync::{LazyLock, Mutex};
+use std::sync::LazyLock;
+use std::time::SystemTime;
 
 use runtime::console;
 use runtime::console::ConsoleMessage;
 use runtime::console::LuaError;
 use runtime::egui;
 use runtime::egui::{RichText, Widget};
+use runtime::expression::evaluate_expression;
 use runtime::game::Game;
-use runtime::lua_env::to_lua;
+use runtime::lua_env::{stringify_lua_value, to_lua};
 use vectarine_cli::regex::Regex;
 
-use crate::editorconfig::TextEditor;
+use crate::editorconfig::{ProjectDebugState, TextEditor};
 use crate::editorinterface::EditorState;
+use crate::editorinterface::editorscriptdiff::show_script_diff;
 use crate::editorinterface::extra::openfileatline::open_file_at_line;
+use crate::editortheme::Palette;
 
 pub fn draw_editor_console(editor: &mut EditorState, ui: &egui::Ui) {
     let mut project = editor.project.borrow_mut();
     let mut is_shown = editor.config.borrow_mut().is_console_shown;
 
-    let project_dir = project
-        .as_ref()
-        .and_then(|proj| proj.project_path.parent())
+    let project_path = project.as_ref().map(|proj| proj.project_path.clone());
+    let project_dir = project_path
+        .as_deref()
+        .and_then(|path| path.parent())
         .map(|p| p.to_path_buf());
+    // The filters are kept with the project, so that they are the same after a reload.
+    let mut debug_state = editor
+        .config
+        .borrow()
+        .project_debug_state(project_path.as_deref());
+    let previous_debug_state = debug_state.clone();
 
     let game = match project.as_mut() {
         Some(proj) => Some(&mut proj.game),
@@ -37,18 +48,29 @@ pub fn draw_editor_console(editor: &mut EditorState, ui: &egui::Ui) {
             .vscroll(false);
         let response = window.show(ui, |ui| {
                 ui.horizontal(|ui| {
+                    let hint_text = if debug_state.is_console_read_only {
+                        "Enter expression..."
+                    } else {
+                        "Enter command..."
+                    };
                     let response = egui::TextEdit::singleline(&mut editor.text_command)
-                        .hint_text("Enter command...")
+                        .hint_text(hint_text)
                         .ui(ui);
 
                     if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                        try_send_command_to_game(&game, &editor.text_command);
+                        if debug_state.is_console_read_only {
+                            try_evaluate_expression(&game, &editor.text_command);
+                        } else {
+                            try_send_command_to_game(&game, &editor.text_command);
+                        }
                         editor.text_command.clear();
                         response.request_focus();
                     }
                     if egui::Button::new("Clear").ui(ui).clicked() {
                         console::clear_all_logs();
                     }
+                    ui.checkbox(&mut debug_state.is_console_read_only, "Read-only")
+                        .on_hover_text("Evaluate what is typed as an expression that cannot change the game, like `#Enemies.list`. Only math, string and conversion functions can be called.");
                 });
 
                 egui::Panel::bottom("bottom_panel")
@@ -57,7 +79,7 @@ pub fn draw_editor_console(editor: &mut EditorState, ui: &egui::Ui) {
                         ui.label(
                             RichText::new("Frame messages")
                                 .size(14.0)
-                                .color(egui::Color32::LIGHT_BLUE),
+                                .color(Palette::of(ui).info),
                         )
                         .on_hover_text("Frame messages are reset every frame. They are useful to debug things that happen every frame.");
                         egui::ScrollArea::vertical()
@@ -65,17 +87,22 @@ pub fn draw_editor_console(editor: &mut EditorState, ui: &egui::Ui) {
                             .auto_shrink(false)
                             .stick_to_bottom(true)
                             .show(ui, |ui| {
+                                let palette = Palette::of(ui);
                                 console::consume_frame_logs(|msg| {
-                                    ui.label(
-                                        RichText::new(msg).color(egui::Color32::WHITE).monospace(),
-                                    );
+                                    ui.label(RichText::new(msg).color(palette.text).monospace());
                                 });
                             });
                     });
 
                 egui::CentralPanel::default().show_inside(ui, |ui| {
                     let prefered_text_editor = editor.config.borrow().text_editor;
-                    draw_console_content(ui, project_dir.as_deref(), prefered_text_editor);
+                    draw_console_content(
+                        ui,
+                        game.as_deref(),
+                        project_dir.as_deref(),
+                        prefered_text_editor,
+                        &mut debug_state,
+                    );
                 });
         });
         if let Some(response) = response {
@@ -85,6 +112,9 @@ pub fn draw_editor_console(editor: &mut EditorState, ui: &egui::Ui) {
             }
         }
         editor.config.borrow_mut().is_console_shown = is_shown;
+        if debug_state != previous_debug_state {
+            editor.set_project_debug_state(project_path.as_deref(), debug_state);
+        }
     }
 }
 
@@ -97,34 +127,43 @@ pub fn try_send_command_to_game(game: &Option<&mut Game>, command: &str) {
     );
 }
 
+/// Prints the value of the expression, evaluated in the same read-only environment as the expressions of the watcher.
+fn try_evaluate_expression(game: &Option<&mut Game>, expression: &str) {
+    let Some(game) = game else {
+        return;
+    };
+    console::print_info(format!("> {expression}"));
+    match evaluate_expression(&game.lua_env.lua_handle.lua, expression) {
+        Ok(value) => console::print_info(stringify_lua_value(&value)),
+        Err(error) => console::print_err(error.to_string()),
+    }
+}
+
 fn draw_console_content(
     ui: &mut egui::Ui,
+    game: Option<&Game>,
     project_path: Option<&Path>,
     prefered_text_editor: Option<TextEditor>,
+    debug_state: &mut ProjectDebugState,
 ) {
-    static ARE_LOGS_ERROR_SHOWN: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(true));
-    static ARE_LOGS_WARN_SHOWN: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(true));
-    static ARE_LOGS_INFO_SHOWN: LazyLock<Mutex<bool>> = LazyLock::new(|| Mutex::new(true));
-
     ui.horizontal(|ui: &mut egui::Ui| {
-        if let Ok(mut infos) = ARE_LOGS_INFO_SHOWN.lock() {
-            ui.checkbox(&mut infos, "Infos");
-        }
-        if let Ok(mut warnings) = ARE_LOGS_WARN_SHOWN.lock() {
-            ui.checkbox(&mut warnings, "Warnings");
-        }
-        if let Ok(mut errors) = ARE_LOGS_ERROR_SHOWN.lock() {
-            ui.checkbox(&mut errors, "Errors");
-        }
+        let filter = &mut debug_state.console_filter;
+        ui.checkbox(&mut filter.are_infos_shown, "Infos");
+        ui.checkbox(&mut filter.are_warnings_shown, "Warnings");
+        ui.checkbox(&mut filter.are_errors_shown, "Errors");
+        ui.checkbox(&mut debug_state.is_console_scroll_locked, "Lock scroll")
+            .on_hover_text("Do not scroll to the new messages, even when the project reloads");
     });
+    let show_errors = debug_state.console_filter.are_errors_shown;
+    let show_warnings = debug_state.console_filter.are_warnings_shown;
+    let show_infos = debug_state.console_filter.are_infos_shown;
+    // The id does not depend on the project, so the scroll position is kept when it reloads.
     egui::ScrollArea::vertical()
         .id_salt("console")
         .auto_shrink(false)
-        .stick_to_bottom(true)
+        .stick_to_bottom(!debug_state.is_console_scroll_locked)
         .show(ui, |ui| {
-            let show_errors = ARE_LOGS_ERROR_SHOWN.lock().map(|e| *e).unwrap_or_default();
-            let show_warnings = ARE_LOGS_WARN_SHOWN.lock().map(|e| *e).unwrap_or_default();
-            let show_infos = ARE_LOGS_INFO_SHOWN.lock().map(|e| *e).unwrap_or_default();
+            let palette = Palette::of(ui);
 
             console::get_logs(|msg| {
                 if matches!(msg, ConsoleMessage::Info(_)) && !show_infos {
@@ -142,47 +181,56 @@ fn draw_console_content(
                     ConsoleMessage::Info(msg) => {
                         ui.label(
                             RichText::new(format!("{}", msg))
-                                .color(egui::Color32::WHITE)
+                                .color(palette.text)
                                 .monospace(),
                         );
                     }
                     ConsoleMessage::Warning(msg) => {
                         ui.label(
                             RichText::new(format!("{}", msg))
-                                .color(egui::Color32::YELLOW)
+                                .color(palette.warning)
                                 .monospace(),
                         );
                     }
                     ConsoleMessage::Error(msg) => {
                         ui.label(
                             RichText::new(format!("{}", msg))
-                                .color(egui::Color32::RED)
+                                .color(palette.error)
                                 .monospace(),
                         );
                     }
                     ConsoleMessage::LuaError(msg) => {
-                        render_lua_error(ui, msg, project_path, prefered_text_editor)
-                    }
-                    ConsoleMessage::Reload => {
-                        ui.separator();
+                        render_lua_error(ui, msg, game, project_path, prefered_text_editor)
                     }
+                    ConsoleMessage::Reload(time) => draw_reload_divider(ui, *time),
                 };
             });
         });
 }
 
+/// Separates the messages logged before a reload from the ones logged after it.
+fn draw_reload_divider(ui: &mut egui::Ui, time: SystemTime) {
+    let time = chrono::DateTime::<chrono::Local>::from(time).format("%H:%M:%S");
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(format!("Reloaded at {time}"))
+                .color(Palette::of(ui).info)
+                .small(),
+        );
+        ui.add(egui::Separator::default().horizontal());
+    });
+}
+
 fn render_lua_error(
     ui: &mut egui::Ui,
     error: &LuaError,
+    game: Option<&Game>,
     project_path: Option<&Path>,
     prefered_text_editor: Option<TextEditor>,
 ) {
+    let palette = Palette::of(ui);
     error.line_content.iter().enumerate().for_each(|(i, line)| {
-        let line_color = if i == 2 {
-            egui::Color32::RED
-        } else {
-            egui::Color32::WHITE
-        };
+        let line_color = if i == 2 { palette.error } else { palette.text };
         let label = ui
             .label(
                 RichText::new(format!("{}: {}", i + error.line - 2, line))
@@ -207,15 +255,25 @@ fn render_lua_error(
             render_error_line_with_links(ui, first_line, error, project_path, prefered_text_editor);
         }
         for line in lines {
-            ui.label(RichText::new(line).color(egui::Color32::RED).monospace());
+            ui.label(RichText::new(line).color(palette.error).monospace());
         }
     } else {
         ui.label(
             RichText::new(&error.message)
-                .color(egui::Color32::RED)
+                .color(palette.error)
                 .monospace(),
         );
     }
+
+    if let (Some(game), Some(project_path)) = (game, project_path)
+        && !error.file.is_empty()
+        && ui
+            .small_button("Show diff")
+            .on_hover_text("Compare the script with its version that last ran without errors")
+            .clicked()
+    {
+        show_script_diff(game, project_path, &error.file, error.line);
+    }
 }
 
 fn render_error_line_with_links(
@@ -229,9 +287,10 @@ fn render_error_line_with_links(
     static FILE_LINE_RE: LazyLock<Regex> =
         LazyLock::new(|| Regex::new(r"([^\s:]+\.\w+):(\d+)").expect("Regex to be valid"));
 
+    let palette = Palette::of(ui);
     let matches: Vec<_> = FILE_LINE_RE.find_iter(line).collect();
     if matches.is_empty() {
-        ui.label(RichText::new(line).color(egui::Color32::RED).monospace());
+        ui.label(RichText::new(line).color(palette.error).monospace());
         return;
     }
 
@@ -242,17 +301,13 @@ fn render_error_line_with_links(
             if m.start() > last_end {
                 ui.label(
                     RichText::new(&line[last_end..m.start()])
-                        .color(egui::Color32::RED)
+                        .color(palette.error)
                         .monospace(),
                 );
             }
 
             let link = ui
-                .label(
-                    RichText::new(m.as_str())
-                        .color(egui::Color32::LIGHT_BLUE)
-                        .monospace(),
-                )
+                .label(RichText::new(m.as_str()).color(palette.link).monospace())
                 .on_hover_cursor(egui::CursorIcon::PointingHand)
                 .on_hover_text(format!("Open {}", m.as_str()));
 
@@ -273,7 +328,7 @@ fn render_error_line_with_links(
         if last_end < line.len() {
             ui.label(
                 RichText::new(&line[last_end..])
-                    .color(egui::Color32::RED)
+                    .color(palette.error)
                     .monospace(),
             );
         }
@@ -0,0 +1,356 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc, time::Instant};
+
+use runtime::{
+    egui::{self, Key, Modifiers, RichText},
+    egui_glow,
+    game::drawable_screen_size,
+    glow,
+    sdl2::{self, EventPump, event::Event},
+};
+use vectarine_plugin_sdk::glow::HasContext;
+
+use crate::{
+    editorconfig::{Breakpoint, EditorConfig},
+    editorextrawindow::send_window_resize_sync_event,
+    editorinterface::{EditorState, clear_window},
+    editortheme::{Palette, apply_theme},
+    egui_sdl2_platform,
+    framepacing::IDLE_FRAME_INTERVAL,
+    luadebugger::{self, DebuggerCommand, PausedState},
+};
+
+thread_local! {
+    /// The file and the line of the breakpoint being added.
+    static NEW_BREAKPOINT: RefCell<(String, u32)> = const { RefCell::new((String::new(), 1)) };
+}
+
+pub fn draw_editor_debugger(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_debugger_window_shown;
+    if !is_shown {
+        return;
+    }
+
+    egui::Window::new("Debugger")
+        .default_width(380.0)
+        .default_height(300.0)
+        .open(&mut is_shown)
+        .show(ui, |ui| {
+            draw_breakpoints(editor, ui);
+        });
+    editor.config.borrow_mut().is_debugger_window_shown = is_shown;
+}
+
+fn draw_breakpoints(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let Some(project_path) = editor
+        .project
+        .borrow()
+        .as_ref()
+        .map(|project| project.project_path.clone())
+    else {
+        ui.label("No project loaded");
+        return;
+    };
+    // The breakpoints are kept with the project, so that they are still set after a reload.
+    let mut debug_state = editor
+        .config
+        .borrow()
+        .project_debug_state(Some(&project_path));
+    let breakpoints = &mut debug_state.breakpoints;
+    let previous_breakpoints = breakpoints.clone();
+
+    ui.label(
+        RichText::new(
+            "The game stops before running a line with a breakpoint. While it is stopped, the game window shows the call stack and the local variables.",
+        )
+        .color(Palette::of(ui).muted_text),
+    );
+    if ui
+        .button("⏸ Pause")
+        .on_hover_text("Stop on the next line of a script that runs")
+        .clicked()
+    {
+        luadebugger::request_pause();
+    }
+    ui.separator();
+
+    let mut removed_breakpoint = None;
+    for (index, breakpoint) in breakpoints.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui
+                .small_button("✖")
+                .on_hover_text("Remove the breakpoint")
+                .clicked()
+            {
+                removed_breakpoint = Some(index);
+            }
+            ui.label(format!("{}:{}", breakpoint.file, breakpoint.line));
+        });
+    }
+    if let Some(index) = removed_breakpoint {
+        breakpoints.remove(index);
+    }
+    if breakpoints.is_empty() {
+        ui.label(RichText::new("No breakpoint").color(Palette::of(ui).muted_text));
+    }
+
+    NEW_BREAKPOINT.with_borrow_mut(|(file, line)| {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(file)
+                    .hint_text("scripts/main.luau")
+                    .desired_width(180.0),
+            );
+            ui.add(
+                egui::DragValue::new(line)
+                    .range(1..=u32::MAX)
+                    .prefix("line "),
+            );
+            let breakpoint = Breakpoint {
+                file: file.trim().to_string(),
+                line: *line,
+            };
+            let can_add = !breakpoint.file.is_empty() && !breakpoints.contains(&breakpoint);
+            if ui.add_enabled(can_add, egui::Button::new("Add")).clicked() {
+                breakpoints.push(breakpoint);
+            }
+        });
+    });
+
+    if *breakpoints != previous_breakpoints {
+        editor.set_project_debug_state(Some(&project_path), debug_state);
+    }
+}
+
+/// Shows the debugger in the game window while the game is stopped. The rest of the editor is not drawn, as the
+/// game is blocked in the middle of its frame.
+pub struct PausedDebugger {
+    sdl: sdl2::Sdl,
+    video: Rc<sdl2::VideoSubsystem>,
+    window: Rc<RefCell<sdl2::video::Window>>,
+    event_pump: Rc<RefCell<EventPump>>,
+    gl: Arc<glow::Context>,
+    config: Rc<RefCell<EditorConfig>>,
+    platform: egui_sdl2_platform::Platform,
+    painter: egui_glow::Painter,
+    start_time: Instant,
+}
+
+impl PausedDebugger {
+    pub fn new(
+        sdl: &sdl2::Sdl,
+        video: &Rc<sdl2::VideoSubsystem>,
+        window: &Rc<RefCell<sdl2::video::Window>>,
+        event_pump: &Rc<RefCell<EventPump>>,
+        gl: &Arc<glow::Context>,
+        config: &Rc<RefCell<EditorConfig>>,
+    ) -> Self {
+        let painter =
+            egui_glow::Painter::new(gl.clone(), "", None, true).expect("Failed to create painter");
+        let platform = egui_sdl2_platform::Platform::new(drawable_screen_size(&window.borrow()))
+            .expect("Failed to create platform");
+        Self {
+            sdl: sdl.clone(),
+            video: video.clone(),
+            window: window.clone(),
+            event_pump: event_pump.clone(),
+            gl: gl.clone(),
+            config: config.clone(),
+            platform,
+            painter,
+            start_time: Instant::now(),
+        }
+    }
+
+    /// Returns what the user chose to do once the debugger was shown.
+    pub fn run(&mut self, paused_state: &PausedState) -> DebuggerCommand {
+        // The window may have been resized since the last stop, the events went to the editor.
+        send_window_resize_sync_event(
+            &self.sdl,
+            &self.video,
+            &self.window.borrow(),
+            &mut self.platform,
+        );
+        let mut selected_frame = 0;
+        loop {
+            let events = {
+                let mut event_pump = self.event_pump.borrow_mut();
+                let mut events = event_pump
+                    .wait_event_timeout(IDLE_FRAME_INTERVAL.as_millis() as u32)
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                events.extend(event_pump.poll_iter());
+                events
+            };
+            // The editor is closed by its main loop, once the frame of the game is over.
+            if let Some(quit_event) = events
+                .iter()
+                .find(|event| matches!(event, Event::Quit { .. }))
+            {
+                if let Ok(event_subsystem) = self.sdl.event() {
+                    let _ = event_subsystem.push_event(quit_event.clone());
+                }
+                luadebugger::ignore_breakpoints_until_next_frame();
+                return DebuggerCommand::Continue;
+            }
+
+            self.platform
+                .update_time(self.start_time.elapsed().as_secs_f64());
+            self.platform.handle_events(&events, &self.sdl, &self.video);
+            let config = self.config.clone();
+            let mut command = None;
+            let full_output = self.platform.run_ui(&self.video, |ui| {
+                apply_theme(ui.ctx(), &config.borrow());
+                command = draw_paused_debugger(ui, paused_state, &mut selected_frame);
+            });
+            match full_output {
+                Ok(full_output) => self.paint(&full_output),
+                Err(e) => println!("Failed to render the debugger: {e:?}"),
+            }
+            if let Some(command) = command {
+                return command;
+            }
+        }
+    }
+
+    fn paint(&mut self, full_output: &egui::FullOutput) {
+        let paint_jobs = self.platform.tessellate(full_output);
+        let window = self.window.borrow();
+        let size = drawable_screen_size(&window);
+        let pixel_per_point = size.0 as f32 / window.size().0 as f32;
+        let gl = &self.gl;
+        unsafe {
+            // The game can be stopped while it draws on a canvas, with its own state. It is restored after.
+            let framebuffer = gl.get_parameter_framebuffer(glow::FRAMEBUFFER_BINDING);
+            let mut viewport = [0; 4];
+            gl.get_parameter_i32_slice(glow::VIEWPORT, &mut viewport);
+            let is_scissor_enabled = gl.is_enabled(glow::SCISSOR_TEST);
+            let blend_func = [
+                glow::BLEND_SRC_RGB,
+                glow::BLEND_DST_RGB,
+                glow::BLEND_SRC_ALPHA,
+                glow::BLEND_DST_ALPHA,
+            ]
+            .map(|parameter| gl.get_parameter_i32(parameter) as u32);
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.viewport(0, 0, size.0 as i32, size.1 as i32);
+            gl.disable(glow::SCISSOR_TEST);
+            clear_window(gl);
+            self.painter.paint_and_update_textures(
+                [size.0, size.1],
+                pixel_per_point,
+                &paint_jobs,
+                &full_output.textures_delta,
+            );
+            window.gl_swap_window();
+
+            gl.bind_framebuffer(glow::FRAMEBUFFER, framebuffer);
+            gl.viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+            if is_scissor_enabled {
+                gl.enable(glow::SCISSOR_TEST);
+            }
+            gl.enable(glow::BLEND);
+            gl.blend_func_separate(blend_func[0], blend_func[1], blend_func[2], blend_func[3]);
+        }
+    }
+}
+
+fn draw_paused_debugger(
+    ui: &mut egui::Ui,
+    paused_state: &PausedState,
+    selected_frame: &mut usize,
+) -> Option<DebuggerCommand> {
+    let buttons = [
+        (
+            "▶ Continue (F5)",
+            "Run until the next breakpoint",
+            DebuggerCommand::Continue,
+        ),
+        (
+            "Step over (F10)",
+            "Run until the next line of this function",
+            DebuggerCommand::StepOver,
+        ),
+        (
+            "Step into (F11)",
+            "Run until the next line, inside of the functions called",
+            DebuggerCommand::StepInto,
+        ),
+        (
+            "Step out (Shift+F11)",
+            "Run until this function returns",
+            DebuggerCommand::StepOut,
+        ),
+    ];
+    let mut command = ui.input_mut(|input| {
+        if input.consume_key(Modifiers::SHIFT, Key::F11) {
+            Some(DebuggerCommand::StepOut)
+        } else if input.consume_key(Modifiers::NONE, Key::F11) {
+            Some(DebuggerCommand::StepInto)
+        } else if input.consume_key(Modifiers::NONE, Key::F10) {
+            Some(DebuggerCommand::StepOver)
+        } else if input.consume_key(Modifiers::NONE, Key::F5) {
+            Some(DebuggerCommand::Continue)
+        } else {
+            None
+        }
+    });
+
+    egui::Panel::top("debugger_controls").show_inside(ui, |ui| {
+        ui.label(RichText::new(&paused_state.reason).heading());
+        ui.horizontal(|ui| {
+            for (label, hint, button_command) in buttons {
+                if ui.button(label).on_hover_text(hint).clicked() {
+                    command = Some(button_command);
+                }
+            }
+        });
+    });
+
+    egui::Panel::left("debugger_call_stack")
+        .resizable(true)
+        .show_inside(ui, |ui| {
+            ui.label(RichText::new("Call stack").strong());
+            egui::ScrollArea::vertical()
+                .id_salt("call stack")
+                .show(ui, |ui| {
+                    for (index, frame) in paused_state.frames.iter().enumerate() {
+                        let location = match frame.line {
+                            Some(line) => format!("{}:{line}", frame.file),
+                            None => frame.file.clone(),
+                        };
+                        ui.selectable_value(
+                            selected_frame,
+                            index,
+                            format!("{} ({location})", frame.function),
+                        );
+                    }
+                });
+        });
+
+    egui::CentralPanel::default().show_inside(ui, |ui| {
+        ui.label(RichText::new("Local variables").strong());
+        let Some(frame) = paused_state.frames.get(*selected_frame) else {
+            return;
+        };
+        if frame.locals.is_empty() {
+            ui.label(RichText::new("No local variable").color(Palette::of(ui).muted_text));
+            return;
+        }
+        egui::ScrollArea::vertical()
+            .id_salt("local variables")
+            .show(ui, |ui| {
+                egui::Grid::new("debugger_locals")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, value) in &frame.locals {
+                            ui.label(name);
+                            ui.label(value);
+                            ui.end_row();
+                        }
+                    });
+            });
+    });
+
+    command
+}
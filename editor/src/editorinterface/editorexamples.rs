@@ -0,0 +1,207 @@
+use std::{
+    cell::RefCell,
+    time::{Duration, Instant},
+};
+
+use runtime::{
+    egui::{self, RichText},
+    game::Game,
+    lua_env::print_lua_error_from_error,
+    mlua,
+};
+
+use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+
+/// How long the `Draw` function of an example is drawn on top of the game.
+const EXAMPLE_OVERLAY_DURATION: Duration = Duration::from_secs(5);
+
+pub struct ApiExample {
+    pub topic: &'static str,
+    pub name: &'static str,
+    pub code: &'static str,
+}
+
+/// The examples are embedded in the editor, so that they always match the API of the engine.
+pub const API_EXAMPLES: [ApiExample; 6] = [
+    ApiExample {
+        topic: "Drawing",
+        name: "Shapes",
+        code: include_str!("../../../assets/api-examples/drawing-shapes.luau"),
+    },
+    ApiExample {
+        topic: "Drawing",
+        name: "Text",
+        code: include_str!("../../../assets/api-examples/drawing-text.luau"),
+    },
+    ApiExample {
+        topic: "Input",
+        name: "Keyboard",
+        code: include_str!("../../../assets/api-examples/input-keyboard.luau"),
+    },
+    ApiExample {
+        topic: "Input",
+        name: "Mouse",
+        code: include_str!("../../../assets/api-examples/input-mouse.luau"),
+    },
+    ApiExample {
+        topic: "Physics",
+        name: "Falling ball",
+        code: include_str!("../../../assets/api-examples/physics-falling-ball.luau"),
+    },
+    ApiExample {
+        topic: "Audio",
+        name: "Play a sound",
+        code: include_str!("../../../assets/api-examples/audio-play-sound.luau"),
+    },
+];
+
+struct RunningExample {
+    draw_fn: mlua::Function,
+    end_time: Instant,
+}
+
+thread_local! {
+    static SELECTED_EXAMPLE: RefCell<usize> = const { RefCell::new(0) };
+    static RUNNING_EXAMPLE: RefCell<Option<RunningExample>> = const { RefCell::new(None) };
+}
+
+pub fn draw_editor_examples(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_examples_window_shown;
+
+    egui::Window::new("API examples")
+        .default_width(600.0)
+        .default_height(400.0)
+        .open(&mut is_shown)
+        .collapsible(false)
+        .show(ui, |ui| {
+            let mut selected = SELECTED_EXAMPLE.with_borrow(|selected| *selected);
+            egui::Panel::left("api_examples_topics")
+                .resizable(false)
+                .show_inside(ui, |ui| {
+                    let mut previous_topic = None;
+                    for (index, example) in API_EXAMPLES.iter().enumerate() {
+                        if previous_topic != Some(example.topic) {
+                            ui.label(RichText::new(example.topic).strong());
+                            previous_topic = Some(example.topic);
+                        }
+                        ui.selectable_value(&mut selected, index, example.name);
+                    }
+                });
+            SELECTED_EXAMPLE.with_borrow_mut(|selected_example| *selected_example = selected);
+            let Some(example) = API_EXAMPLES.get(selected) else {
+                return;
+            };
+
+            egui::CentralPanel::default().show_inside(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let project = editor.project.borrow();
+                    let run_button = ui.add_enabled(
+                        project.is_some(),
+                        egui::Button::new("Run in current project"),
+                    );
+                    if let Some(project) = project.as_ref()
+                        && run_button
+                            .on_hover_text(
+                                "Run the example in the game, without changing its globals",
+                            )
+                            .clicked()
+                    {
+                        run_example(&project.game, example);
+                    }
+                    if project.is_none() {
+                        ui.label("Open a project to run the examples.");
+                    }
+                    if ui.button("Copy").clicked() {
+                        ui.copy_text(example.code.to_string());
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::both().show(ui, |ui| {
+                    let mut code = example.code;
+                    ui.add(
+                        egui::TextEdit::multiline(&mut code)
+                            .code_editor()
+                            .text_color(Palette::of(ui).code_text)
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            });
+        });
+
+    editor.config.borrow_mut().is_examples_window_shown = is_shown;
+}
+
+/// Runs the example in a sandbox table whose reads fall back to the globals of the game,
+/// so that the variables and functions of the example do not replace the ones of the game.
+fn run_example(game: &Game, example: &ApiExample) {
+    let lua_handle = &game.lua_env.lua_handle;
+    let result = (|| -> mlua::Result<Option<mlua::Function>> {
+        let lua = &lua_handle.lua;
+        let sandbox = lua.create_table()?;
+        let sandbox_metatable = lua.create_table()?;
+        sandbox_metatable.raw_set("__index", lua.globals())?;
+        sandbox.set_metatable(Some(sandbox_metatable))?;
+        lua.load(example.code)
+            .set_name(format!("@api-examples/{}", example.name))
+            .set_environment(sandbox.clone())
+            .exec()?;
+        sandbox.raw_get::<Option<mlua::Function>>("Draw")
+    })();
+    match result {
+        Ok(draw_fn) => RUNNING_EXAMPLE.with_borrow_mut(|running_example| {
+            *running_example = draw_fn.map(|draw_fn| RunningExample {
+                draw_fn,
+                end_time: Instant::now() + EXAMPLE_OVERLAY_DURATION,
+            });
+        }),
+        Err(error) => print_lua_error_from_error(lua_handle, &error),
+    }
+}
+
+/// Draws the example that was run last on top of the game, until its time is up.
+pub fn draw_running_example(game: &Game, delta_time: Duration) {
+    let Some(draw_fn) = RUNNING_EXAMPLE.with_borrow_mut(|running_example| {
+        if running_example
+            .as_ref()
+            .is_some_and(|example| Instant::now() >= example.end_time)
+        {
+            *running_example = None;
+        }
+        running_example
+            .as_ref()
+            .map(|example| example.draw_fn.clone())
+    }) else {
+        return;
+    };
+    if let Err(error) = draw_fn.call::<()>(delta_time.as_secs_f32()) {
+        print_lua_error_from_error(&game.lua_env.lua_handle, &error);
+        stop_running_example();
+    }
+    game.lua_env
+        .batch
+        .borrow_mut()
+        .draw(&game.lua_env.resources, true);
+}
+
+/// Called when the Lua state of the game is replaced, as the example belongs to the previous one.
+pub fn stop_running_example() {
+    RUNNING_EXAMPLE.with_borrow_mut(|running_example| *running_example = None);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::API_EXAMPLES;
+    use vectarine_cli::project::exportproject::bundle_compiler;
+
+    #[test]
+    fn examples_compile() {
+        for example in API_EXAMPLES {
+            assert!(
+                bundle_compiler().compile(example.code).is_ok(),
+                "the {} example does not compile",
+                example.name
+            );
+        }
+    }
+}
@@ -0,0 +1,523 @@
+use std::{
+    cell::RefCell,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+};
+
+use runtime::egui::{self, RichText};
+use runtime::io::paths;
+use vectarine_cli::project::geteditorpaths::get_gallery_path;
+
+use crate::editorinterface::EditorState;
+use crate::editorinterface::extra::openfileatline::open_file_at_line;
+use crate::editortheme::Palette;
+
+const SEARCHED_EXTENSIONS: [&str; 3] = ["luau", "lua", "vecta"];
+/// Only the start of larger files is searched.
+const MAX_SEARCHED_FILE_SIZE: u64 = 1024 * 1024;
+/// Files with a null byte in their first bytes are binary files, even with a searched extension.
+const BINARY_SNIFF_SIZE: usize = 8000;
+/// The search stops after this many matching lines, so that searching for "a" stays fast.
+const MAX_MATCH_COUNT: usize = 2000;
+const MAX_PREVIEW_LENGTH: usize = 200;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+struct FindQuery {
+    text: String,
+    is_case_sensitive: bool,
+    is_whole_word: bool,
+    is_regex: bool,
+    is_gallery_searched: bool,
+}
+
+impl FindQuery {
+    fn to_regex(&self) -> Result<regex::Regex, regex::Error> {
+        let pattern = if self.is_regex {
+            self.text.clone()
+        } else {
+            regex::escape(&self.text)
+        };
+        let pattern = if self.is_whole_word {
+            format!(r"\b(?:{pattern})\b")
+        } else {
+            pattern
+        };
+        regex::RegexBuilder::new(&pattern)
+            .case_insensitive(!self.is_case_sensitive)
+            .build()
+    }
+}
+
+struct LineMatch {
+    line_number: usize,
+    preview: String,
+}
+
+struct FileMatches {
+    path: PathBuf,
+    display_path: String,
+    lines: Vec<LineMatch>,
+    is_truncated: bool,
+}
+
+#[derive(Default)]
+struct FindResults {
+    files: Vec<FileMatches>,
+    skipped_binary_file_count: usize,
+    is_match_limit_reached: bool,
+}
+
+impl FindResults {
+    fn match_count(&self) -> usize {
+        self.files.iter().map(|file| file.lines.len()).sum()
+    }
+}
+
+struct RunningSearch {
+    receiver: mpsc::Receiver<FindResults>,
+    is_cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct FindState {
+    query: FindQuery,
+    /// The query of the results shown. None when the files changed since the last search.
+    searched_query: Option<FindQuery>,
+    running_search: Option<RunningSearch>,
+    results: Option<FindResults>,
+    error: Option<String>,
+    is_focus_requested: bool,
+}
+
+impl FindState {
+    fn cancel_search(&mut self) {
+        if let Some(running_search) = self.running_search.take() {
+            running_search.is_cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn start_search(&mut self, roots: Vec<(String, PathBuf)>) {
+        self.cancel_search();
+        self.searched_query = Some(self.query.clone());
+        self.error = None;
+        let regex = match self.query.to_regex() {
+            Ok(regex) => regex,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                self.results = None;
+                return;
+            }
+        };
+        let (sender, receiver) = mpsc::channel();
+        let is_cancelled = Arc::new(AtomicBool::new(false));
+        std::thread::spawn({
+            let is_cancelled = is_cancelled.clone();
+            move || {
+                let results = search_files(&roots, &regex, &is_cancelled);
+                let _ = sender.send(results);
+            }
+        });
+        self.running_search = Some(RunningSearch {
+            receiver,
+            is_cancelled,
+        });
+    }
+
+    fn receive_results(&mut self) {
+        let Some(running_search) = &self.running_search else {
+            return;
+        };
+        match running_search.receiver.try_recv() {
+            Ok(results) => {
+                self.results = Some(results);
+                self.running_search = None;
+            }
+            Err(mpsc::TryRecvError::Empty) => {}
+            Err(mpsc::TryRecvError::Disconnected) => self.running_search = None,
+        }
+    }
+}
+
+thread_local! {
+    static FIND_STATE: RefCell<FindState> = RefCell::new(FindState::default());
+}
+
+/// Moves the keyboard focus to the query the next time the window is drawn.
+pub fn focus_find_query() {
+    FIND_STATE.with_borrow_mut(|state| state.is_focus_requested = true);
+}
+
+/// Called when files change on disk, so that the next search does not reuse outdated results.
+pub fn invalidate_find_results() {
+    FIND_STATE.with_borrow_mut(|state| state.searched_query = None);
+}
+
+pub fn draw_editor_find(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_find_window_shown;
+    let mut file_to_open = None;
+
+    let maybe_response = egui::Window::new("Find in project")
+        .default_width(500.0)
+        .default_height(400.0)
+        .open(&mut is_shown)
+        .collapsible(false)
+        .show(ui, |ui| {
+            let project = editor.project.borrow();
+            let Some(project_folder) = project.as_ref().and_then(|p| p.project_path.parent())
+            else {
+                ui.label("No project opened to search in");
+                return;
+            };
+
+            FIND_STATE.with_borrow_mut(|state| {
+                let query_response = egui::TextEdit::singleline(&mut state.query.text)
+                    .hint_text("Search in the .luau and .vecta files")
+                    .desired_width(f32::INFINITY)
+                    .show(ui)
+                    .response;
+                if state.is_focus_requested {
+                    query_response.request_focus();
+                    state.is_focus_requested = false;
+                }
+                ui.horizontal(|ui| {
+                    ui.toggle_value(&mut state.query.is_case_sensitive, "Aa")
+                        .on_hover_text("Match case");
+                    ui.toggle_value(&mut state.query.is_whole_word, "Word")
+                        .on_hover_text("Match whole words only");
+                    ui.toggle_value(&mut state.query.is_regex, ".*")
+                        .on_hover_text("Use a regular expression");
+                    ui.checkbox(&mut state.query.is_gallery_searched, "Search gallery too")
+                        .on_hover_text(
+                            "Look for examples of the engine API in the gallery projects",
+                        );
+                });
+
+                state.receive_results();
+                if state.query.text.is_empty() {
+                    state.cancel_search();
+                    state.searched_query = None;
+                    state.results = None;
+                    state.error = None;
+                } else if state.searched_query.as_ref() != Some(&state.query) {
+                    state.start_search(search_roots(project_folder, &state.query));
+                }
+
+                ui.separator();
+                if let Some(error) = &state.error {
+                    ui.colored_label(Palette::of(ui).error, format!("Invalid regex: {error}"));
+                    return;
+                }
+                if state.running_search.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Searching...");
+                    });
+                }
+                let Some(results) = &state.results else {
+                    return;
+                };
+                draw_results(ui, results, &mut file_to_open);
+            });
+        });
+
+    // The file may have been deleted since the search.
+    if let Some((path, line)) = file_to_open.filter(|(path, _)| path.exists()) {
+        let text_editor = editor.config.borrow().text_editor;
+        open_file_at_line(&path, line, text_editor);
+    }
+
+    if let Some(response) = maybe_response {
+        let on_top = Some(response.response.layer_id) == ui.top_layer_id();
+        if on_top && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+            is_shown = false;
+        }
+    }
+    editor.config.borrow_mut().is_find_window_shown = is_shown;
+}
+
+fn draw_results(
+    ui: &mut egui::Ui,
+    results: &FindResults,
+    file_to_open: &mut Option<(PathBuf, usize)>,
+) {
+    let match_count = results.match_count();
+    ui.label(format!(
+        "{} {} in {} {}",
+        match_count,
+        if match_count == 1 { "match" } else { "matches" },
+        results.files.len(),
+        if results.files.len() == 1 {
+            "file"
+        } else {
+            "files"
+        },
+    ));
+    if results.is_match_limit_reached {
+        ui.label(
+            RichText::new(format!(
+                "Only the first {MAX_MATCH_COUNT} matches are shown. Refine the search to see the others."
+            ))
+            .weak(),
+        );
+    }
+    if results.skipped_binary_file_count > 0 {
+        ui.label(
+            RichText::new(format!(
+                "{} binary files were skipped",
+                results.skipped_binary_file_count
+            ))
+            .weak(),
+        );
+    }
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            for file in &results.files {
+                egui::CollapsingHeader::new(format!(
+                    "{} ({})",
+                    file.display_path,
+                    file.lines.len()
+                ))
+                .id_salt(&file.path)
+                .default_open(true)
+                .show(ui, |ui| {
+                    if file.is_truncated {
+                        ui.label(
+                            RichText::new(format!(
+                                "This file is large, only its first {} KB were searched",
+                                MAX_SEARCHED_FILE_SIZE / 1024
+                            ))
+                            .weak(),
+                        );
+                    }
+                    for line in &file.lines {
+                        let link = ui.link(
+                            RichText::new(format!("{:>5}  {}", line.line_number, line.preview))
+                                .monospace(),
+                        );
+                        if link.clicked() {
+                            *file_to_open = Some((file.path.clone(), line.line_number));
+                        }
+                    }
+                });
+            }
+        });
+}
+
+/// The folders to search, with the prefix shown before the paths of their files.
+fn search_roots(project_folder: &Path, query: &FindQuery) -> Vec<(String, PathBuf)> {
+    let mut roots = vec![(String::new(), project_folder.to_path_buf())];
+    if query.is_gallery_searched {
+        let gallery_path = get_gallery_path();
+        let is_project_in_gallery = paths::canonicalize(project_folder)
+            .ok()
+            .zip(paths::canonicalize(&gallery_path).ok())
+            .is_some_and(|(project, gallery)| project.starts_with(gallery));
+        if is_project_in_gallery {
+            roots.clear();
+        }
+        roots.push(("gallery/".to_string(), gallery_path));
+    }
+    roots
+}
+
+fn search_files(
+    roots: &[(String, PathBuf)],
+    regex: &regex::Regex,
+    is_cancelled: &AtomicBool,
+) -> FindResults {
+    let mut results = FindResults::default();
+    let mut match_count = 0;
+    for (prefix, root) in roots {
+        let mut paths = Vec::new();
+        collect_searched_files(root, &mut paths);
+        paths.sort();
+        for path in paths {
+            if is_cancelled.load(Ordering::Relaxed) {
+                return results;
+            }
+            let Some((bytes, is_truncated)) = read_start_of_file(&path) else {
+                continue;
+            };
+            if bytes[..bytes.len().min(BINARY_SNIFF_SIZE)].contains(&0) {
+                results.skipped_binary_file_count += 1;
+                continue;
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            let mut lines = Vec::new();
+            for (index, line) in text.lines().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if match_count >= MAX_MATCH_COUNT {
+                    results.is_match_limit_reached = true;
+                    break;
+                }
+                match_count += 1;
+                lines.push(LineMatch {
+                    line_number: index + 1,
+                    preview: line.trim().chars().take(MAX_PREVIEW_LENGTH).collect(),
+                });
+            }
+            if !lines.is_empty() {
+                let relative_path = path.strip_prefix(root).unwrap_or(&path);
+                results.files.push(FileMatches {
+                    display_path: format!("{prefix}{}", relative_path.display()),
+                    path,
+                    lines,
+                    is_truncated,
+                });
+            }
+            if results.is_match_limit_reached {
+                return results;
+            }
+        }
+    }
+    results
+}
+
+fn collect_searched_files(folder: &Path, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        // Hidden folders contain things like .git or the cache of the editor, which are not part of the project.
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            collect_searched_files(&path, paths);
+        } else if path
+            .extension()
+            .is_some_and(|extension| SEARCHED_EXTENSIONS.iter().any(|e| extension == *e))
+        {
+            paths.push(path);
+        }
+    }
+}
+
+/// Returns the first bytes of the file, and true when the file is longer than that.
+fn read_start_of_file(path: &Path) -> Option<(Vec<u8>, bool)> {
+    let file = fs::File::open(path).ok()?;
+    let is_truncated = file.metadata().ok()?.len() > MAX_SEARCHED_FILE_SIZE;
+    let mut bytes = Vec::new();
+    file.take(MAX_SEARCHED_FILE_SIZE)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    Some((bytes, is_truncated))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf, sync::atomic::AtomicBool};
+
+    use super::{FindQuery, MAX_SEARCHED_FILE_SIZE, search_files};
+
+    fn query(text: &str) -> FindQuery {
+        FindQuery {
+            text: text.to_string(),
+            ..FindQuery::default()
+        }
+    }
+
+    #[test]
+    fn queries_are_turned_into_regexes() {
+        let regex = query("Player.x").to_regex().expect("valid");
+        assert!(regex.is_match("player.x = 1"));
+        assert!(!regex.is_match("playerAx = 1"));
+
+        let case_sensitive = FindQuery {
+            is_case_sensitive: true,
+            ..query("Player")
+        };
+        assert!(!case_sensitive.to_regex().expect("valid").is_match("player"));
+
+        let whole_word = FindQuery {
+            is_whole_word: true,
+            ..query("hp")
+        };
+        let regex = whole_word.to_regex().expect("valid");
+        assert!(regex.is_match("local hp = 3"));
+        assert!(!regex.is_match("local hpMax = 3"));
+
+        let regex = FindQuery {
+            is_regex: true,
+            ..query(r"load\w+\(")
+        };
+        assert!(
+            regex
+                .to_regex()
+                .expect("valid")
+                .is_match("Loader.loadImage(")
+        );
+        assert!(
+            FindQuery {
+                is_regex: true,
+                ..query("(")
+            }
+            .to_regex()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn binary_hidden_and_other_files_are_skipped() {
+        let folder: PathBuf =
+            std::env::temp_dir().join(format!("vectarine-find-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        let files: [(&str, &[u8]); 5] = [
+            ("main.luau", b"local hp = 3\nprint(hp)\n"),
+            ("game.vecta", b"title = \"hp\"\n"),
+            ("notes.txt", b"hp\n"),
+            (".cache/old.luau", b"hp\n"),
+            ("binary.lua", b"hp\0\0"),
+        ];
+        for (path, content) in files {
+            let path = folder.join(path);
+            fs::create_dir_all(path.parent().expect("in a folder")).expect("folder created");
+            fs::write(path, content).expect("file written");
+        }
+        let mut large_file = "hp\n".repeat(MAX_SEARCHED_FILE_SIZE as usize / 3 + 10);
+        large_file.insert_str(0, "-- large\n");
+        // Sorted last, as the search stops at the match limit.
+        fs::write(folder.join("z_large.luau"), large_file).expect("file written");
+
+        let regex = query("hp").to_regex().expect("valid");
+        let results = search_files(
+            &[(String::new(), folder.clone())],
+            &regex,
+            &AtomicBool::new(false),
+        );
+        let found = results
+            .files
+            .iter()
+            .map(|file| {
+                (
+                    file.display_path.as_str(),
+                    file.lines.len(),
+                    file.is_truncated,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(found[0], ("game.vecta", 1, false));
+        assert_eq!(found[1], ("main.luau", 2, false));
+        assert_eq!(found[2].0, "z_large.luau");
+        assert!(found[2].2);
+        assert_eq!(found.len(), 3);
+        assert_eq!(results.skipped_binary_file_count, 1);
+        assert!(results.is_match_limit_reached);
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
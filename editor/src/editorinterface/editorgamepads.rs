@@ -0,0 +1,493 @@
+use std::{cell::RefCell, fs, path::Path};
+
+use runtime::{
+    console,
+    egui::{self, RichText},
+    io::gamepad::{RawJoystick, add_gamepad_mapping, get_raw_joysticks},
+    sdl2,
+};
+use vectarine_cli::project::geteditorpaths::get_gamepad_mappings_path;
+
+use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+
+/// The inputs of a gamepad in the order the wizard asks for them, with their name in the SDL mappings.
+const MAPPING_STEPS: [(&str, &str); 21] = [
+    (
+        "a",
+        "Press the bottom face button (A on Xbox, Cross on PlayStation)",
+    ),
+    ("b", "Press the right face button (B, Circle)"),
+    ("x", "Press the left face button (X, Square)"),
+    ("y", "Press the top face button (Y, Triangle)"),
+    ("back", "Press Back (Select, Share)"),
+    ("guide", "Press the Home button (Guide, PS)"),
+    ("start", "Press Start (Options)"),
+    ("leftstick", "Click the left stick"),
+    ("rightstick", "Click the right stick"),
+    ("leftshoulder", "Press the left shoulder button (LB, L1)"),
+    ("rightshoulder", "Press the right shoulder button (RB, R1)"),
+    ("dpup", "Press up on the D-pad"),
+    ("dpdown", "Press down on the D-pad"),
+    ("dpleft", "Press left on the D-pad"),
+    ("dpright", "Press right on the D-pad"),
+    ("leftx", "Push the left stick to the right"),
+    ("lefty", "Push the left stick down"),
+    ("rightx", "Push the right stick to the right"),
+    ("righty", "Push the right stick down"),
+    ("lefttrigger", "Press the left trigger (LT, L2)"),
+    ("righttrigger", "Press the right trigger (RT, R2)"),
+];
+
+/// An axis moved by more than this from where it rested was moved on purpose.
+const AXIS_PRESS_THRESHOLD: i32 = 16_384;
+
+/// Axes resting closer to the center than this are centered axes, like sticks. The others rest at one end, like
+/// most triggers.
+const AXIS_CENTER_TOLERANCE: i32 = 8_000;
+
+#[derive(Clone, Debug)]
+struct RawInputs {
+    axes: Vec<i16>,
+    buttons: Vec<bool>,
+    hats: Vec<u8>,
+}
+
+impl RawInputs {
+    fn of(joystick: &RawJoystick) -> Self {
+        Self {
+            axes: joystick.axes.clone(),
+            buttons: joystick.buttons.clone(),
+            hats: joystick.hats.clone(),
+        }
+    }
+}
+
+/// Asks for the inputs of the gamepad one after the other ("press the button you use as A") and writes the SDL
+/// mapping of the answers.
+#[derive(Clone, Debug)]
+pub struct MappingWizard {
+    pub instance_id: u32,
+    guid: String,
+    name: String,
+    step: usize,
+    /// The inputs when the step started, so that the axes resting at one end and the buttons held down are not taken
+    /// for an answer.
+    resting_inputs: Option<RawInputs>,
+    is_waiting_for_release: bool,
+    bindings: Vec<(&'static str, String)>,
+}
+
+impl MappingWizard {
+    pub fn new(joystick: &RawJoystick) -> Self {
+        Self {
+            instance_id: joystick.instance_id,
+            guid: joystick.guid.clone(),
+            name: joystick.name.clone(),
+            step: 0,
+            resting_inputs: None,
+            is_waiting_for_release: false,
+            bindings: Vec::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step >= MAPPING_STEPS.len()
+    }
+
+    pub fn prompt(&self) -> &'static str {
+        MAPPING_STEPS
+            .get(self.step)
+            .map_or("Done", |(_, prompt)| prompt)
+    }
+
+    /// For inputs the gamepad does not have.
+    pub fn skip(&mut self) {
+        self.step += 1;
+        self.resting_inputs = None;
+    }
+
+    /// Call every frame with the current values of the joystick.
+    pub fn update(&mut self, joystick: &RawJoystick) {
+        if self.is_done() {
+            return;
+        }
+        let inputs = RawInputs::of(joystick);
+        let Some(resting_inputs) = &self.resting_inputs else {
+            self.resting_inputs = Some(inputs);
+            return;
+        };
+        let binding = self.find_binding(resting_inputs, &inputs);
+        if self.is_waiting_for_release {
+            // The input of the previous step must be released first, or it would answer this step too.
+            self.is_waiting_for_release = binding.is_some();
+            return;
+        }
+        let Some(binding) = binding else {
+            return;
+        };
+        if self.bindings.iter().any(|(_, used)| *used == binding) {
+            return;
+        }
+        let (name, _) = MAPPING_STEPS[self.step];
+        self.bindings.push((name, binding));
+        self.step += 1;
+        self.is_waiting_for_release = true;
+    }
+
+    fn find_binding(&self, resting: &RawInputs, inputs: &RawInputs) -> Option<String> {
+        let (name, _) = MAPPING_STEPS.get(self.step)?;
+        let is_stick_axis = matches!(*name, "leftx" | "lefty" | "rightx" | "righty");
+        for (button, is_pressed) in inputs.buttons.iter().enumerate() {
+            if *is_pressed && !resting.buttons.get(button).copied().unwrap_or(false) {
+                return Some(format!("b{button}"));
+            }
+        }
+        for (hat, bits) in inputs.hats.iter().enumerate() {
+            let resting_bits = resting.hats.get(hat).copied().unwrap_or(0);
+            // Only one direction, so that pressing up-left on the D-pad does not give the up button to left.
+            if *bits != resting_bits && bits.count_ones() == 1 {
+                return Some(format!("h{hat}.{bits}"));
+            }
+        }
+        for (axis, value) in inputs.axes.iter().enumerate() {
+            let value = *value as i32;
+            let resting_value = resting.axes.get(axis).copied().unwrap_or(0) as i32;
+            let movement = value - resting_value;
+            if movement.abs() < AXIS_PRESS_THRESHOLD {
+                continue;
+            }
+            let is_inverted = movement < 0;
+            let binding = if is_stick_axis {
+                // The stick was pushed right or down, which are the positive directions for SDL.
+                format!("a{axis}{}", if is_inverted { "~" } else { "" })
+            } else if resting_value.abs() < AXIS_CENTER_TOLERANCE {
+                // A half of the axis is a button, like a D-pad reported as an axis.
+                format!("{}a{axis}", if is_inverted { "-" } else { "+" })
+            } else {
+                // A trigger resting at one end of the axis uses the whole axis.
+                format!("a{axis}{}", if is_inverted { "~" } else { "" })
+            };
+            return Some(binding);
+        }
+        None
+    }
+
+    /// The mapping in the format of SDL_GameControllerDB.
+    pub fn mapping(&self, platform: &str) -> String {
+        // Commas separate the fields of the mapping.
+        let name = self.name.replace(',', " ");
+        let bindings = self
+            .bindings
+            .iter()
+            .map(|(name, binding)| format!("{name}:{binding},"))
+            .collect::<String>();
+        format!("{},{name},{bindings}platform:{platform},", self.guid)
+    }
+}
+
+/// Replaces the mapping of the same controller in the content of a mappings file, or adds it at the end.
+fn replace_mapping(content: &str, mapping: &str) -> String {
+    let guid = mapping.split(',').next().unwrap_or_default();
+    let mut lines = content
+        .lines()
+        .filter(|line| line.split(',').next() != Some(guid))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    lines.push_str(mapping);
+    lines.push('\n');
+    lines
+}
+
+fn save_mapping(mappings_path: &Path, mapping: &str) -> std::io::Result<()> {
+    let content = fs::read_to_string(mappings_path).unwrap_or_default();
+    if let Some(parent) = mappings_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(mappings_path, replace_mapping(&content, mapping))
+}
+
+thread_local! {
+    static MAPPING_WIZARD: RefCell<Option<MappingWizard>> = const { RefCell::new(None) };
+}
+
+pub fn draw_editor_gamepads(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_gamepads_window_shown;
+    if !is_shown {
+        MAPPING_WIZARD.with_borrow_mut(|wizard| *wizard = None);
+        return;
+    }
+
+    egui::Window::new("Gamepad setup")
+        .default_width(420.0)
+        .default_height(400.0)
+        .open(&mut is_shown)
+        .show(ui, |ui| {
+            let joysticks = get_raw_joysticks();
+            let mappings_path = get_gamepad_mappings_path();
+            ui.label(
+                RichText::new(format!(
+                    "Saved mappings are loaded from {} when the editor starts.",
+                    mappings_path.display()
+                ))
+                .color(Palette::of(ui).muted_text),
+            );
+            if joysticks.is_empty() {
+                ui.label("No controller connected.");
+            }
+
+            let wizard_joystick = MAPPING_WIZARD.with_borrow(|wizard| {
+                let wizard = wizard.as_ref()?;
+                joysticks
+                    .iter()
+                    .find(|joystick| joystick.instance_id == wizard.instance_id)
+            });
+            match wizard_joystick {
+                Some(joystick) => draw_mapping_wizard(editor, ui, joystick, &mappings_path),
+                None => {
+                    MAPPING_WIZARD.with_borrow_mut(|wizard| *wizard = None);
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        for joystick in &joysticks {
+                            draw_joystick(ui, joystick);
+                        }
+                    });
+                }
+            }
+        });
+
+    if !is_shown {
+        editor.config.borrow_mut().is_gamepads_window_shown = false;
+    }
+}
+
+fn draw_joystick(ui: &mut egui::Ui, joystick: &RawJoystick) {
+    let palette = Palette::of(ui);
+    egui::CollapsingHeader::new(&joystick.name)
+        .id_salt(joystick.instance_id)
+        .default_open(true)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("GUID:");
+                ui.label(RichText::new(&joystick.guid).monospace());
+                if ui.small_button("Copy").clicked() {
+                    ui.ctx().copy_text(joystick.guid.clone());
+                }
+            });
+            match &joystick.mapping {
+                Some(mapping) => {
+                    ui.colored_label(palette.info, "SDL knows this controller as a gamepad.")
+                        .on_hover_text(mapping);
+                }
+                None => {
+                    ui.colored_label(
+                        palette.warning,
+                        "SDL does not know this controller: games cannot use it as a gamepad.",
+                    );
+                }
+            }
+            if ui.button("Create a mapping").clicked() {
+                MAPPING_WIZARD
+                    .with_borrow_mut(|wizard| *wizard = Some(MappingWizard::new(joystick)));
+            }
+
+            for (axis, value) in joystick.axes.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Axis {axis}"));
+                    let fraction = (*value as f32 + 32768.0) / 65535.0;
+                    ui.add(
+                        egui::ProgressBar::new(fraction)
+                            .desired_width(200.0)
+                            .text(value.to_string()),
+                    );
+                });
+            }
+            if !joystick.buttons.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Buttons");
+                    for (button, is_pressed) in joystick.buttons.iter().enumerate() {
+                        let text = RichText::new(button.to_string()).monospace();
+                        if *is_pressed {
+                            ui.label(text.strong().color(palette.link));
+                        } else {
+                            ui.label(text.color(palette.muted_text));
+                        }
+                    }
+                });
+            }
+            for (hat, bits) in joystick.hats.iter().enumerate() {
+                let directions = [(1, "up"), (2, "right"), (4, "down"), (8, "left")]
+                    .into_iter()
+                    .filter(|(bit, _)| bits & bit != 0)
+                    .map(|(_, direction)| direction)
+                    .collect::<Vec<_>>();
+                let directions = if directions.is_empty() {
+                    "centered".to_string()
+                } else {
+                    directions.join(" ")
+                };
+                ui.label(format!("Hat {hat}: {directions}"));
+            }
+        });
+}
+
+fn draw_mapping_wizard(
+    editor: &mut EditorState,
+    ui: &mut egui::Ui,
+    joystick: &RawJoystick,
+    mappings_path: &Path,
+) {
+    let Some(mut wizard) = MAPPING_WIZARD.with_borrow(|wizard| wizard.clone()) else {
+        return;
+    };
+    wizard.update(joystick);
+    ui.heading(format!("Mapping {}", joystick.name));
+
+    let mut is_closed = false;
+    if wizard.is_done() {
+        let mapping = wizard.mapping(sdl2::get_platform());
+        ui.label("The mapping of the controller:");
+        ui.label(RichText::new(&mapping).monospace());
+        ui.horizontal(|ui| {
+            if ui
+                .button("Save and apply")
+                .on_hover_text("Save the mapping for every game started by the editor")
+                .clicked()
+            {
+                match save_mapping(mappings_path, &mapping) {
+                    Ok(()) => console::print_info(format!(
+                        "Gamepad mapping saved to {}",
+                        mappings_path.display()
+                    )),
+                    Err(error) => console::print_err(format!(
+                        "Unable to save the gamepad mapping to {}: {error}",
+                        mappings_path.display()
+                    )),
+                }
+                apply_mapping(&mapping);
+                is_closed = true;
+            }
+            let mut project = editor.project.borrow_mut();
+            if let Some(project) = project.as_mut()
+                && ui
+                    .button("Add to the project")
+                    .on_hover_text(
+                        "Store the mapping in gamepad_mappings of game.vecta, so that exported games use it",
+                    )
+                    .clicked()
+            {
+                let guid = mapping.split(',').next().unwrap_or_default();
+                project
+                    .project_info
+                    .gamepad_mappings
+                    .retain(|existing| existing.split(',').next() != Some(guid));
+                project.project_info.gamepad_mappings.push(mapping.clone());
+                project.save_project_info();
+                apply_mapping(&mapping);
+                is_closed = true;
+            }
+            if ui.button("Start again").clicked() {
+                wizard = MappingWizard::new(joystick);
+            }
+        });
+    } else {
+        ui.label(format!(
+            "Step {} of {}",
+            wizard.step + 1,
+            MAPPING_STEPS.len()
+        ));
+        ui.label(RichText::new(wizard.prompt()).size(16.0));
+        if wizard.is_waiting_for_release {
+            ui.label(RichText::new("Release the input").color(Palette::of(ui).muted_text));
+        }
+        ui.horizontal(|ui| {
+            if ui
+                .button("Skip")
+                .on_hover_text("The controller does not have this input")
+                .clicked()
+            {
+                wizard.skip();
+            }
+            if ui.button("Start again").clicked() {
+                wizard = MappingWizard::new(joystick);
+            }
+        });
+    }
+    if ui.button("Cancel").clicked() {
+        is_closed = true;
+    }
+    MAPPING_WIZARD.with_borrow_mut(|current| *current = (!is_closed).then_some(wizard));
+}
+
+fn apply_mapping(mapping: &str) {
+    match add_gamepad_mapping(mapping) {
+        Ok(()) => console::print_info("Gamepad mapping applied".to_string()),
+        Err(error) => console::print_err(format!("Unable to apply the gamepad mapping: {error}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MappingWizard, replace_mapping};
+    use runtime::io::gamepad::RawJoystick;
+
+    fn joystick() -> RawJoystick {
+        RawJoystick {
+            instance_id: 3,
+            name: "Cheap Pad, USB".to_string(),
+            guid: "03000000aaaa0000bbbb000000000000".to_string(),
+            // A stick at rest and a trigger resting at one end.
+            axes: vec![0, 0, -32768],
+            buttons: vec![false; 4],
+            hats: vec![0],
+            mapping: None,
+        }
+    }
+
+    #[test]
+    fn the_wizard_maps_buttons_hats_and_axes() {
+        let pad = joystick();
+        let mut wizard = MappingWizard::new(&pad);
+        let press = |wizard: &mut MappingWizard, change: &dyn Fn(&mut RawJoystick)| {
+            let mut pressed = pad.clone();
+            change(&mut pressed);
+            wizard.update(&pressed);
+            wizard.update(&pad);
+        };
+        wizard.update(&pad);
+        press(&mut wizard, &|pad| pad.buttons[2] = true);
+        // An input already used does not answer the next step.
+        press(&mut wizard, &|pad| pad.buttons[2] = true);
+        assert_eq!(wizard.step, 1);
+        for _ in 1..11 {
+            wizard.skip();
+            wizard.update(&pad);
+        }
+        press(&mut wizard, &|pad| pad.hats[0] = 1);
+        press(&mut wizard, &|pad| pad.hats[0] = 4);
+        press(&mut wizard, &|pad| pad.axes[1] = -32768);
+        press(&mut wizard, &|pad| pad.axes[1] = 32767);
+        // leftx, pushed right but reported as negative.
+        press(&mut wizard, &|pad| pad.axes[0] = -30000);
+        for _ in 16..19 {
+            wizard.skip();
+            wizard.update(&pad);
+        }
+        press(&mut wizard, &|pad| pad.axes[2] = 32767);
+        wizard.skip();
+        assert!(wizard.is_done());
+        assert_eq!(wizard.bindings.len(), 7);
+        assert_eq!(
+            wizard.mapping("Linux"),
+            "03000000aaaa0000bbbb000000000000,Cheap Pad  USB,a:b2,dpup:h0.1,dpdown:h0.4,dpleft:-a1,dpright:+a1,leftx:a0~,lefttrigger:a2,platform:Linux,"
+        );
+    }
+
+    #[test]
+    fn saved_mappings_replace_the_mapping_of_the_same_controller() {
+        let content = "# Comment\n0300aa,Old pad,a:b0,\n0300bb,Other pad,a:b1,";
+        assert_eq!(
+            replace_mapping(content, "0300aa,New pad,a:b3,"),
+            "# Comment\n0300bb,Other pad,a:b1,\n0300aa,New pad,a:b3,\n"
+        );
+    }
+}
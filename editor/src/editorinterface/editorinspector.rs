@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+
+use runtime::{
+    egui::{self, Color32, Pos2, RichText, Sense, Stroke},
+    graphics::glframebuffer::Viewport,
+    lua_env::{
+        lua_camera::Camera2,
+        lua_physics::inspector::{InspectedObject, InspectedWorld},
+        lua_vec2::Vec2,
+    },
+};
+
+use crate::{
+    editorconfig::WindowStyle, editorinterface::EditorState, editortheme::Palette,
+    projectstate::ProjectState,
+};
+
+/// The distance between an object and the handle rotating it, in points.
+const ROTATION_HANDLE_DISTANCE: f32 = 40.0;
+const HANDLE_RADIUS: f32 = 6.0;
+
+/// The world and the handle of an object.
+type Selection = (usize, (u32, u32));
+
+thread_local! {
+    static SELECTION: RefCell<Option<Selection>> = const { RefCell::new(None) };
+    /// The tags of the selected object being edited, separated by commas.
+    static EDITED_TAGS: RefCell<Option<(Selection, String)>> = const { RefCell::new(None) };
+}
+
+pub fn draw_editor_inspector(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let mut is_shown = editor.config.borrow().is_inspector_window_shown;
+    if !is_shown {
+        return;
+    }
+    let project = editor.project.borrow();
+    let worlds = project
+        .as_ref()
+        .map(|project| project.game.lua_env.physics_worlds.borrow().worlds())
+        .unwrap_or_default();
+
+    egui::Window::new("Inspector")
+        .default_width(320.0)
+        .default_height(420.0)
+        .open(&mut is_shown)
+        .show(ui, |ui| {
+            if project.is_none() {
+                ui.label("No project loaded");
+                return;
+            }
+            draw_inspector_window(ui, &worlds);
+        });
+
+    // In a separate window, the game is not under the editor and the gizmos have nowhere to go.
+    if let Some(project) = project.as_ref()
+        && editor.config.borrow().window_style == WindowStyle::GameWithEditor
+    {
+        let transform = ScreenTransform::new(project, &editor.window.borrow());
+        draw_gizmos(ui, &worlds, &transform);
+    }
+    editor.config.borrow_mut().is_inspector_window_shown = is_shown;
+}
+
+fn draw_inspector_window(ui: &mut egui::Ui, worlds: &[InspectedWorld]) {
+    if worlds.is_empty() {
+        ui.label(
+            RichText::new("The game has no physics world. Create one with Physics.newWorld2.")
+                .color(Palette::of(ui).muted_text),
+        );
+        return;
+    }
+    let mut selection = SELECTION.with_borrow(|selection| *selection);
+
+    egui::Panel::bottom("inspector_details")
+        .resizable(true)
+        .show_inside(ui, |ui| {
+            let selected = selection.and_then(|key| {
+                let world = worlds.get(key.0)?;
+                let object = world
+                    .objects()
+                    .into_iter()
+                    .find(|object| object.handle == key.1)?;
+                Some((key, world, object))
+            });
+            match selected {
+                Some((key, world, object)) => draw_object_details(ui, world, &object, key),
+                None => {
+                    ui.label(RichText::new("No object selected").color(Palette::of(ui).muted_text));
+                }
+            }
+        });
+
+    egui::CentralPanel::default().show_inside(ui, |ui| {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for (world_index, world) in worlds.iter().enumerate() {
+                let objects = world.objects();
+                egui::CollapsingHeader::new(format!(
+                    "World {} ({} objects)",
+                    world_index + 1,
+                    objects.len()
+                ))
+                .id_salt(("inspector_world", world_index))
+                .default_open(true)
+                .show(ui, |ui| {
+                    for object in &objects {
+                        let key = (world_index, object.handle);
+                        ui.selectable_value(&mut selection, Some(key), object_label(object));
+                    }
+                });
+            }
+        });
+    });
+
+    SELECTION.set(selection);
+}
+
+fn object_label(object: &InspectedObject) -> String {
+    let mut label = format!("#{} {}", object.handle.0, object.body_type);
+    if !object.tags.is_empty() {
+        label.push_str(&format!(" [{}]", object.tags.join(", ")));
+    }
+    label
+}
+
+fn draw_object_details(
+    ui: &mut egui::Ui,
+    world: &InspectedWorld,
+    object: &InspectedObject,
+    selection: Selection,
+) {
+    let mut position = object.position;
+    let mut rotation = object.rotation.to_degrees();
+    let mut is_moved = false;
+    egui::Grid::new("inspector_object")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Position");
+            ui.horizontal(|ui| {
+                let (mut x, mut y) = (position.x(), position.y());
+                is_moved |= ui
+                    .add(egui::DragValue::new(&mut x).speed(0.05).prefix("x "))
+                    .changed();
+                is_moved |= ui
+                    .add(egui::DragValue::new(&mut y).speed(0.05).prefix("y "))
+                    .changed();
+                position = Vec2::new(x, y);
+            });
+            ui.end_row();
+
+            ui.label("Rotation");
+            is_moved |= ui
+                .add(egui::DragValue::new(&mut rotation).speed(1.0).suffix("°"))
+                .changed();
+            ui.end_row();
+
+            ui.label("Velocity");
+            ui.label(format!(
+                "{:.2}, {:.2}",
+                object.velocity.x(),
+                object.velocity.y()
+            ));
+            ui.end_row();
+
+            ui.label("Type");
+            ui.label(object.body_type);
+            ui.end_row();
+
+            ui.label("Layer");
+            ui.label(object.layer.as_deref().unwrap_or("none"));
+            ui.end_row();
+
+            ui.label("Tags");
+            EDITED_TAGS.with_borrow_mut(|edited_tags| {
+                if edited_tags.as_ref().map(|(key, _)| *key) != Some(selection) {
+                    *edited_tags = Some((selection, object.tags.join(", ")));
+                }
+                let Some((_, text)) = edited_tags.as_mut() else {
+                    return;
+                };
+                let response = ui.add(
+                    egui::TextEdit::singleline(text)
+                        .hint_text("player, enemy")
+                        .desired_width(160.0),
+                );
+                if response.lost_focus() {
+                    let tags = text
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect::<Vec<_>>();
+                    world.set_tags(object.handle, &tags);
+                    *text = tags.join(", ");
+                }
+            });
+            ui.end_row();
+        });
+    if is_moved {
+        world.set_pose(object.handle, position, rotation.to_radians());
+    }
+}
+
+/// Converts between the coordinates of a world and the points of the interface, through the camera of the world and
+/// the viewport of the game.
+struct ScreenTransform {
+    viewport: Viewport,
+    /// The height of the window in pixels, as the viewport starts from the bottom of the window.
+    window_height: f32,
+    pixels_per_point: f32,
+    /// The size the cameras use for the aspect ratio.
+    camera_window_size: Vec2,
+}
+
+impl ScreenTransform {
+    fn new(project: &ProjectState, window: &runtime::sdl2::video::Window) -> Self {
+        let env_state = project.game.lua_env.env_state.borrow();
+        let (window_width, _) = window.size();
+        Self {
+            viewport: env_state.game_viewport,
+            window_height: env_state.window_height as f32,
+            pixels_per_point: env_state.window_width as f32 / window_width.max(1) as f32,
+            camera_window_size: Vec2::new(
+                env_state.window_width as f32 / env_state.px_ratio_x,
+                env_state.window_height as f32 / env_state.px_ratio_y,
+            ),
+        }
+    }
+
+    fn to_screen(&self, camera: Option<&Camera2>, point: Vec2) -> Pos2 {
+        let point = match camera {
+            Some(camera) => camera.world_to_screen(point, self.camera_window_size),
+            None => point,
+        };
+        let viewport = &self.viewport;
+        let x = viewport.x as f32 + (point.x() + 1.0) / 2.0 * viewport.width as f32;
+        let y = viewport.y as f32 + (point.y() + 1.0) / 2.0 * viewport.height as f32;
+        Pos2::new(
+            x / self.pixels_per_point,
+            (self.window_height - y) / self.pixels_per_point,
+        )
+    }
+
+    fn to_world(&self, camera: Option<&Camera2>, point: Pos2) -> Vec2 {
+        let viewport = &self.viewport;
+        let x = point.x * self.pixels_per_point;
+        let y = self.window_height - point.y * self.pixels_per_point;
+        let point = Vec2::new(
+            (x - viewport.x as f32) / viewport.width.max(1) as f32 * 2.0 - 1.0,
+            (y - viewport.y as f32) / viewport.height.max(1) as f32 * 2.0 - 1.0,
+        );
+        match camera {
+            Some(camera) => camera.screen_to_world(point, self.camera_window_size),
+            None => point,
+        }
+    }
+}
+
+/// Draws the outlines of the objects over the game. The selected object has a handle to move it and one to rotate it.
+fn draw_gizmos(ui: &mut egui::Ui, worlds: &[InspectedWorld], transform: &ScreenTransform) {
+    let palette = Palette::of(ui);
+    let mut selection = SELECTION.with_borrow(|selection| *selection);
+
+    for (world_index, world) in worlds.iter().enumerate() {
+        let camera = world.camera();
+        let camera = camera.as_ref();
+        for object in world.objects() {
+            let key = (world_index, object.handle);
+            let is_selected = selection == Some(key);
+            let color = if is_selected {
+                palette.warning
+            } else {
+                palette.info.gamma_multiply(0.6)
+            };
+            for outline in &object.outlines {
+                let points = outline
+                    .iter()
+                    .map(|point| transform.to_screen(camera, *point))
+                    .collect::<Vec<_>>();
+                ui.painter()
+                    .add(egui::Shape::closed_line(points, Stroke::new(1.5, color)));
+            }
+
+            let center = transform.to_screen(camera, object.position);
+            let id = ui.id().with(("inspector_gizmo", key));
+            let response = ui.interact(
+                egui::Rect::from_center_size(center, egui::Vec2::splat(HANDLE_RADIUS * 2.0)),
+                id,
+                Sense::click_and_drag(),
+            );
+            if response.clicked() || response.drag_started() {
+                selection = Some(key);
+            }
+            if response.dragged() {
+                let position = transform.to_world(camera, center + response.drag_delta());
+                world.set_pose(object.handle, position, object.rotation);
+            }
+            ui.painter()
+                .circle_filled(center, HANDLE_RADIUS * 0.6, color);
+            if !is_selected {
+                continue;
+            }
+
+            // The handle points in the direction of the rotation, at the same distance whatever the zoom.
+            let direction = Vec2::new(object.rotation.cos(), object.rotation.sin());
+            let towards = transform.to_screen(camera, object.position + direction) - center;
+            let towards = if towards.length() > f32::EPSILON {
+                towards.normalized()
+            } else {
+                egui::Vec2::X
+            };
+            let handle = center + towards * ROTATION_HANDLE_DISTANCE;
+            let response = ui.interact(
+                egui::Rect::from_center_size(handle, egui::Vec2::splat(HANDLE_RADIUS * 2.0)),
+                id.with("rotation"),
+                Sense::drag(),
+            );
+            if let Some(pointer) = response.interact_pointer_pos()
+                && response.dragged()
+            {
+                let pointer = transform.to_world(camera, pointer);
+                let rotation = (pointer - object.position).angle();
+                world.set_pose(object.handle, object.position, rotation);
+            }
+            let painter = ui.painter();
+            painter.line_segment([center, handle], Stroke::new(1.5, color));
+            painter.circle_stroke(handle, HANDLE_RADIUS, Stroke::new(2.0, color));
+            painter.circle_filled(handle, HANDLE_RADIUS * 0.5, Color32::WHITE);
+        }
+    }
+
+    SELECTION.set(selection);
+}
@@ -0,0 +1,76 @@
+use std::cell::Cell;
+
+use runtime::egui;
+
+use crate::editorinterface::EditorState;
+
+enum ConflictChoice {
+    ReloadFromDisk(String),
+    KeepEditorVersion,
+}
+
+/// Dialog shown when the project manifest was changed on disk while the editor had unsaved changes to it.
+pub fn draw_editor_manifest_conflict(editor: &EditorState, ui: &mut egui::Ui) {
+    thread_local! {
+        static IS_DIFF_SHOWN: Cell<bool> = const { Cell::new(false) };
+    }
+
+    let mut project = editor.project.borrow_mut();
+    let Some(project) = project.as_mut() else {
+        return;
+    };
+    let Some(conflict) = &project.manifest_conflict else {
+        return;
+    };
+
+    let mut choice = None;
+    egui::Window::new("Project file changed on disk")
+        .collapsible(false)
+        .resizable(true)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ui, |ui| {
+            ui.label(format!(
+                "{} was modified by another program, but the editor also has unsaved changes to it.",
+                project.project_path.to_string_lossy()
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("Reload from disk").clicked() {
+                    choice = Some(ConflictChoice::ReloadFromDisk(
+                        conflict.disk_content.clone(),
+                    ));
+                }
+                if ui.button("Keep editor version (overwrite)").clicked() {
+                    choice = Some(ConflictChoice::KeepEditorVersion);
+                }
+                let mut is_diff_shown = IS_DIFF_SHOWN.get();
+                ui.toggle_value(&mut is_diff_shown, "Show diff");
+                IS_DIFF_SHOWN.set(is_diff_shown);
+            });
+
+            if IS_DIFF_SHOWN.get() {
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        ui.columns(2, |columns| {
+                            columns[0].strong("On disk");
+                            columns[0].monospace(&conflict.disk_content);
+                            columns[1].strong("Editor");
+                            columns[1].monospace(&conflict.editor_content);
+                        });
+                    });
+            }
+        });
+
+    match choice {
+        Some(ConflictChoice::ReloadFromDisk(disk_content)) => {
+            project.reload_project_info_from(&disk_content);
+            IS_DIFF_SHOWN.set(false);
+        }
+        Some(ConflictChoice::KeepEditorVersion) => {
+            project.overwrite_manifest_with_editor_version();
+            IS_DIFF_SHOWN.set(false);
+        }
+        None => {}
+    }
+}
@@ -1,39 +1,27 @@
 use std::{cell::Cell, path::PathBuf};
 
 use crate::buildinfo;
+use crate::editorconfig::EditorTheme;
+use crate::editortheme::Palette;
 use runtime::console;
 use runtime::egui;
 use runtime::egui::{Modal, Popup, RichText, UiBuilder};
 
-use crate::editorinterface::{EditorState, emptyscreen::open_file_dialog_and_load_project};
+use crate::editorinterface::{
+    EditorState,
+    editoractions::{EditorAction, action_button, handle_action_shortcuts, run_action},
+};
 
-pub fn draw_editor_menu(editor: &mut EditorState, ui: &mut egui::Ui) {
-    if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::Num1)) {
-        let mut config = editor.config.borrow_mut();
-        config.is_console_shown = !config.is_console_shown;
-    }
-    if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::Num2)) {
-        let mut config = editor.config.borrow_mut();
-        config.is_resources_window_shown = !config.is_resources_window_shown;
-    }
-
-    if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::Num3)) {
-        let mut config = editor.config.borrow_mut();
-        config.is_watcher_window_shown = !config.is_watcher_window_shown;
-    }
-
-    if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::Num4)) {
-        let mut config = editor.config.borrow_mut();
-        config.is_profiler_window_shown = !config.is_profiler_window_shown;
-    }
+thread_local! {
+    static IS_ABOUT_OPEN: Cell<bool> = const { Cell::new(false) };
+}
 
-    if ui.input_mut(|i| i.consume_key(egui::Modifiers::CTRL, egui::Key::R)) {
-        editor.reload_project();
-    }
+pub fn show_about_window() {
+    IS_ABOUT_OPEN.with(|cell| cell.set(true));
+}
 
-    thread_local! {
-        static IS_ABOUT_OPEN: Cell<bool> = const { Cell::new(false) };
-    }
+pub fn draw_editor_menu(editor: &mut EditorState, ui: &mut egui::Ui) {
+    handle_action_shortcuts(editor, ui);
 
     if IS_ABOUT_OPEN.with(|cell| cell.get()) {
         let modal = Modal::new(egui::Id::new("about")).show(ui, |ui| {
@@ -60,14 +48,7 @@ pub fn draw_editor_menu(editor: &mut EditorState, ui: &mut egui::Ui) {
             ui.label(RichText::new("Vectarine Editor").size(18.0));
             egui::MenuBar::new().ui(ui, |ui| {
                 ui.menu_button("File", |ui| {
-                    let exit_text = if cfg!(target_os = "macos") {
-                        "Exit (Cmd+Q)"
-                    } else {
-                        "Exit (Alt+F4)"
-                    };
-                    if ui.button("Open project").clicked() {
-                        open_file_dialog_and_load_project(editor);
-                    }
+                    action_button(ui, editor, EditorAction::OpenProject);
 
                     let is_project_loaded = editor.project.borrow().is_some();
                     let mut ui_builder = UiBuilder::new();
@@ -75,51 +56,47 @@ pub fn draw_editor_menu(editor: &mut EditorState, ui: &mut egui::Ui) {
                         ui_builder = ui_builder.disabled();
                     }
                     ui.scope_builder(ui_builder, |ui| {
-                        if ui.button("Reload project (Ctrl+R)").clicked() {
-                            editor.reload_project();
-                        }
-
-                        if ui.button("Close project").clicked() {
-                            editor.close_project();
-                        }
-
-                        if ui.button("Export...").clicked() {
-                            let mut config = editor.config.borrow_mut();
-                            config.is_export_window_shown = true;
-                        }
+                        action_button(ui, editor, EditorAction::ReloadProject);
+                        action_button(ui, editor, EditorAction::CloseProject);
+                        action_button(ui, editor, EditorAction::Export);
+                        action_button(ui, editor, EditorAction::ValidateProject);
+                        action_button(ui, editor, EditorAction::MigrationReport);
+                        action_button(ui, editor, EditorAction::ReimportAssets);
+                        action_button(ui, editor, EditorAction::OpenProjectFolder);
                     });
 
-                    if ui.button(exit_text).clicked() {
-                        std::process::exit(0);
-                    }
+                    action_button(ui, editor, EditorAction::Exit);
                 });
                 let popup_menu = Popup::menu(&ui.button("Tools"));
                 // .close_behavior(egui::PopupCloseBehavior::CloseOnClickOutside); // not convenient
 
                 popup_menu.show(|ui| {
-                    if ui.button("Console (Ctrl+1)").clicked() {
-                        let mut config = editor.config.borrow_mut();
-                        config.is_console_shown = !config.is_console_shown;
-                    }
-                    if ui.button("Resources (Ctrl+2)").clicked() {
-                        let mut config = editor.config.borrow_mut();
-                        config.is_resources_window_shown = !config.is_resources_window_shown;
-                    }
-                    if ui.button("Watcher (Ctrl+3)").clicked() {
-                        let mut config = editor.config.borrow_mut();
-                        config.is_watcher_window_shown = !config.is_watcher_window_shown;
-                    }
-                    if ui.button("Profiler (Ctrl+4)").clicked() {
-                        let mut config = editor.config.borrow_mut();
-                        config.is_profiler_window_shown = !config.is_profiler_window_shown;
-                    }
+                    action_button(ui, editor, EditorAction::ToggleConsole);
+                    action_button(ui, editor, EditorAction::ToggleResources);
+                    action_button(ui, editor, EditorAction::ToggleWatcher);
+                    action_button(ui, editor, EditorAction::ToggleDebugger);
+                    action_button(ui, editor, EditorAction::ToggleInspector);
+                    action_button(ui, editor, EditorAction::ToggleProfiler);
+                    action_button(ui, editor, EditorAction::ToggleStatsStrip);
+                    action_button(ui, editor, EditorAction::FindInProject);
+                    action_button(ui, editor, EditorAction::ToggleGamepads);
+                    action_button(ui, editor, EditorAction::ToggleCollisionLayers);
+                    action_button(ui, editor, EditorAction::ToggleApiBrowser);
+                    action_button(ui, editor, EditorAction::ShowCommandPalette);
+                    ui.menu_button("Theme", |ui| {
+                        let current_theme = editor.config.borrow().theme;
+                        for theme in [EditorTheme::System, EditorTheme::Dark, EditorTheme::Light] {
+                            let action = EditorAction::SetTheme(theme);
+                            if ui.radio(current_theme == theme, action.name()).clicked() {
+                                run_action(editor, action);
+                            }
+                        }
+                    });
+                    action_button(ui, editor, EditorAction::SimulateContextLoss);
                 });
 
                 ui.menu_button("Plugins", |ui| {
-                    if ui.button("Plugin Manager").clicked() {
-                        let mut config = editor.config.borrow_mut();
-                        config.is_plugins_window_shown = !config.is_plugins_window_shown;
-                    }
+                    action_button(ui, editor, EditorAction::TogglePluginManager);
                     ui.menu_button("Plugins", |ui| {
                         let mut project = editor.project.borrow_mut();
                         if let Some(project) = project.as_mut() {
@@ -139,39 +116,86 @@ pub fn draw_editor_menu(editor: &mut EditorState, ui: &mut egui::Ui) {
                     });
                 });
 
-                if ui.button("Preferences").clicked() {
-                    let mut config = editor.config.borrow_mut();
-                    config.is_preferences_window_shown = !config.is_preferences_window_shown;
+                if ui.button(EditorAction::TogglePreferences.name()).clicked() {
+                    run_action(editor, EditorAction::TogglePreferences);
                 }
 
                 ui.menu_button("Help", |ui| {
-                    if ui.button("Offline Guide").clicked() {
-                        if let Some(manual_path) = get_manual_path() {
-                            let result = open::that(manual_path);
-                            if let Err(result) = result {
-                                console::print_err(result.to_string());
-                            }
-                        } else {
-                            console::print_err(
-                                "PDF Guide not found, maybe it was deleted?".to_string(),
-                            );
-                        }
-                    }
-                    if ui.button("Github").clicked() {
-                        let result = open::that("https://github.com/vanyle/vectarine");
-                        if let Err(result) = result {
-                            console::print_err(result.to_string());
-                        }
-                    }
-                    if ui.button("About").clicked() {
-                        IS_ABOUT_OPEN.with(|cell| cell.set(true));
-                    }
+                    action_button(ui, editor, EditorAction::OpenGuide);
+                    action_button(ui, editor, EditorAction::ToggleApiExamples);
+                    action_button(ui, editor, EditorAction::OpenGithub);
+                    action_button(ui, editor, EditorAction::ShowAbout);
                 });
             });
+            draw_game_controls(editor, ui);
         });
         // let window_handle = editor.window.borrow().raw();
         // sdl2_sys::SDL_SetWindowHitTest(window_handle, callback, callback_data)
     });
+
+    draw_previous_version_banner(editor, ui);
+}
+
+/// Outside of the menus, so that the game can be stepped by clicking the same button several times.
+fn draw_game_controls(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let Some(is_paused) = editor
+        .project
+        .borrow()
+        .as_ref()
+        .map(|project| project.game.is_paused())
+    else {
+        return;
+    };
+    ui.separator();
+    let buttons = if is_paused {
+        [
+            ("▶", EditorAction::ResumeGame),
+            ("⏭", EditorAction::StepFrame),
+        ]
+        .as_slice()
+    } else {
+        [("⏸", EditorAction::PauseGame)].as_slice()
+    };
+    for (icon, action) in buttons {
+        if ui.button(*icon).on_hover_text(action.name()).clicked() {
+            run_action(editor, *action);
+        }
+    }
+    if is_paused {
+        ui.colored_label(Palette::of(ui).warning, "Paused");
+    }
+}
+
+/// Stays visible until the main script is reloaded successfully, so that the user knows that their changes to Update
+/// are not running.
+fn draw_previous_version_banner(editor: &EditorState, ui: &mut egui::Ui) {
+    let project = editor.project.borrow();
+    let Some(project) = project.as_ref() else {
+        return;
+    };
+    if !project.game.is_running_previous_version() {
+        return;
+    }
+    egui::Panel::top("previous_version_banner").show_inside(ui, |ui| {
+        ui.colored_label(
+            Palette::of(ui).warning,
+            format!(
+                "⚠ Running previous version due to errors. Fix the errors in Update and save {} to run your changes.",
+                project.game.main_script_path
+            ),
+        );
+    });
+}
+
+pub fn open_offline_guide() {
+    if let Some(manual_path) = get_manual_path() {
+        let result = open::that(manual_path);
+        if let Err(result) = result {
+            console::print_err(result.to_string());
+        }
+    } else {
+        console::print_err("PDF Guide not found, maybe it was deleted?".to_string());
+    }
 }
 
 fn get_manual_path() -> Option<PathBuf> {
@@ -2,7 +2,7 @@ use std::{borrow::Cow, fs, path::PathBuf};
 
 use egui_extras::{Column, TableBody, TableBuilder};
 use runtime::egui::{self, Label};
-use vectarine_plugin_sdk::plugininterface::{EditorPluginInterface, PluginInterface};
+use vectarine_plugin_sdk::plugininterface::EditorPluginInterface;
 
 use crate::{
     editorinterface::EditorState,
@@ -22,9 +22,7 @@ pub fn draw_editor_plugin_windows(editor: &mut EditorState, ui: &egui::Ui) {
 
     let editor_plugin_interface = EditorPluginInterface {
         gui_context: ui,
-        plugin_interface: PluginInterface {
-            lua: &project.game.lua_env.lua_handle.lua,
-        },
+        plugin_interface: project.game.plugin_interface(),
     };
 
     for plugin in &project.game.plugin_env.loaded_plugins {
@@ -278,7 +276,9 @@ fn draw_editor_plugin_manager_content(editor: &mut EditorState, ui: &mut egui::U
         editor.plugins = trustedplugin::load_plugins();
         let mut project = editor.project.borrow_mut();
         if let Some(project) = project.as_mut() {
+            // Refreshing saves the project info, with the plugins of the last update.
             project.refresh_plugin_list(&editor.get_trusted_plugins());
+            project.is_project_info_dirty = false;
             project.update_plugins_in_project_info();
         }
     }
@@ -1,10 +1,18 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
 use runtime::egui;
+use runtime::graphics::glerror::set_gl_error_checking;
+use runtime::scriptdialect::ScriptDialect;
 
 use crate::editorinterface::EditorState;
+use crate::editorinterface::editoractions::{
+    EditorAction, find_shortcut_conflicts, parse_shortcut, shortcut_to_string,
+};
+use crate::editortheme::Palette;
 
-use crate::editorconfig::{TextEditor, WindowStyle};
+use crate::editorconfig::{
+    DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS, EditorTheme, TextEditor, WindowStyle,
+};
 
 pub fn draw_editor_preferences(editor: &mut EditorState, ui: &mut egui::Ui) {
     let mut is_shown = editor.config.borrow().is_preferences_window_shown;
@@ -43,6 +51,15 @@ pub fn draw_editor_preferences(editor: &mut EditorState, ui: &mut egui::Ui) {
                             .editor_specific_window
                             .set_always_on_top(config.is_editor_always_on_top);
                     }
+
+                    let mut is_throttled = config.unfocused_editor_frame_rate.is_some();
+                    let response = ui
+                        .checkbox(&mut is_throttled, "Limit editor to 30 FPS when unfocused")
+                        .on_hover_text("Leaves more GPU time to the game window.");
+                    if response.changed() {
+                        config.unfocused_editor_frame_rate = is_throttled.then_some(30);
+                        HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                    }
                 }
 
                 {
@@ -61,6 +78,148 @@ pub fn draw_editor_preferences(editor: &mut EditorState, ui: &mut egui::Ui) {
                     }
                 }
 
+                {
+                    let mut config = editor.config.borrow_mut();
+                    let response = ui
+                        .checkbox(
+                            &mut config.is_single_script_reload_enabled,
+                            "Only reload the edited script",
+                        )
+                        .on_hover_text(
+                            "By default, the scripts loading an edited script with Loader.loadScript run again after it.\nCheck this if your scripts must not run twice.",
+                        );
+                    if response.changed() {
+                        HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                    }
+                }
+
+                {
+                    let mut config = editor.config.borrow_mut();
+                    let mut autosave_seconds = config
+                        .tool_store_autosave_seconds
+                        .unwrap_or(DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS);
+                    ui.horizontal(|ui| {
+                        ui.label("Save the tool store every");
+                        let response = ui.add(
+                            egui::DragValue::new(&mut autosave_seconds)
+                                .range(1..=3600)
+                                .suffix(" s"),
+                        );
+                        if response.changed() {
+                            config.tool_store_autosave_seconds = Some(autosave_seconds);
+                            HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                        }
+                    })
+                    .response
+                    .on_hover_text("The values of Debug.getToolStore are also saved when the project is closed and when the editor crashes.");
+                }
+
+                if !cfg!(debug_assertions) {
+                    let mut config = editor.config.borrow_mut();
+                    let response = ui
+                        .checkbox(
+                            &mut config.is_gl_error_checking_enabled,
+                            "Report OpenGL errors",
+                        )
+                        .on_hover_text(
+                            "Check for OpenGL errors after every draw call and print them in the console.\nThis makes rendering slower.",
+                        );
+                    if response.changed() {
+                        set_gl_error_checking(config.is_gl_error_checking_enabled);
+                        HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                    }
+                }
+
+                ui.separator();
+                ui.heading("Appearance");
+
+                {
+                    let mut config = editor.config.borrow_mut();
+                    egui::ComboBox::new("theme_selector", "Theme")
+                        .selected_text(format!("{}", config.theme))
+                        .show_ui(ui, |ui| {
+                            for theme in [EditorTheme::System, EditorTheme::Dark, EditorTheme::Light] {
+                                if ui
+                                    .selectable_value(&mut config.theme, theme, format!("{}", theme))
+                                    .changed()
+                                {
+                                    HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        });
+
+                    ui.horizontal(|ui| {
+                        let mut has_accent_color = config.accent_color.is_some();
+                        if ui
+                            .checkbox(&mut has_accent_color, "Custom accent color")
+                            .on_hover_text("Color of the selected items and of the links.")
+                            .changed()
+                        {
+                            config.accent_color = has_accent_color.then_some([90, 170, 255]);
+                            HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                        }
+                        if let Some(accent_color) = config.accent_color.as_mut()
+                            && ui.color_edit_button_srgb(accent_color).changed()
+                        {
+                            HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                        }
+                    });
+                }
+
+                ui.separator();
+                ui.heading("Keyboard Shortcuts");
+                egui::CollapsingHeader::new("Shortcuts of the menu actions").show(ui, |ui| {
+                    ui.label("Write shortcuts like Ctrl+Shift+P. Leave a shortcut empty to remove it.");
+                    let error_color = Palette::of(ui).error;
+                    let mut config = editor.config.borrow_mut();
+                    egui::Grid::new("keybindings_grid")
+                        .num_columns(3)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for action in EditorAction::ALL.into_iter().filter(|a| a.is_available()) {
+                                let default_text = action
+                                    .default_shortcut()
+                                    .map(|shortcut| shortcut_to_string(&shortcut))
+                                    .unwrap_or_default();
+                                let mut text = config
+                                    .keybindings
+                                    .get(action.id())
+                                    .cloned()
+                                    .unwrap_or_else(|| default_text.clone());
+                                ui.label(action.palette_name());
+                                let response = ui
+                                    .add(egui::TextEdit::singleline(&mut text).desired_width(120.0));
+                                if response.changed() {
+                                    if text == default_text {
+                                        config.keybindings.remove(action.id());
+                                    } else {
+                                        config
+                                            .keybindings
+                                            .insert(action.id().to_string(), text.clone());
+                                    }
+                                    HAS_UNSAVED_CHANGES.store(true, Ordering::Relaxed);
+                                }
+                                if !text.trim().is_empty() && parse_shortcut(&text).is_none() {
+                                    ui.colored_label(error_color, "Invalid shortcut");
+                                } else {
+                                    ui.label("");
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    for (shortcut, actions) in find_shortcut_conflicts(&config) {
+                        ui.colored_label(
+                            error_color,
+                            format!(
+                                "{shortcut} is used by {}, only the first one runs.",
+                                actions.join(", ")
+                            ),
+                        );
+                    }
+                });
+
+                draw_script_dialects(editor, ui);
+
                 ui.separator();
                 ui.heading("External Editor");
                 ui.label("Select the default editor used to open scripts.");
@@ -111,3 +270,36 @@ pub fn draw_editor_preferences(editor: &mut EditorState, ui: &mut egui::Ui) {
         editor.config.borrow_mut().is_preferences_window_shown = is_shown;
     }
 }
+
+/// The scripts of the project still in Lua compatibility mode, to nudge their migration to Luau.
+fn draw_script_dialects(editor: &EditorState, ui: &mut egui::Ui) {
+    let project = editor.project.borrow();
+    let Some(project) = project.as_ref() else {
+        return;
+    };
+    let mut compat_scripts = project
+        .game
+        .lua_env
+        .lua_handle
+        .script_dialects
+        .borrow()
+        .iter()
+        .filter(|(_, dialect)| **dialect == ScriptDialect::LuaCompat)
+        .map(|(path, _)| path.clone())
+        .collect::<Vec<_>>();
+    if compat_scripts.is_empty() {
+        return;
+    }
+    compat_scripts.sort();
+
+    ui.separator();
+    ui.heading("Project");
+    ui.label(format!(
+        "{} script(s) run in Lua compatibility mode.",
+        compat_scripts.len()
+    ))
+    .on_hover_text(compat_scripts.join("\n"));
+    ui.label(
+        "Rename them to .luau and fix the warnings of the console to get the optimizations and the type checking of Luau.",
+    );
+}
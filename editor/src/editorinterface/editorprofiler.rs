@@ -1,12 +1,24 @@
 use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+use crate::luau::ScriptAttribution;
+use crate::projectstate::ProjectState;
+use runtime::console;
 use runtime::egui;
 use runtime::egui::RichText;
-use runtime::metrics::{METRICS_STORAGE_DURATION, Measurable, Metric};
+use runtime::metrics::{
+    GameMetricKind, LUA_ALLOCATED_METRIC_NAME, LUA_GC_TIME_METRIC_NAME, MAX_RECORDED_FRAMES,
+    METRICS_STORAGE_DURATION, Measurable, MemoryCategory, Metric, MetricsHolder,
+    SKIPPED_FRAME_METRIC_NAME,
+};
 use std::cell::{Cell, RefCell};
 
 const AVERAGE_SMOOTHING_WINDOW_SIZE: usize = 5;
 const Y_SCALE_SMOOTHING_FACTOR: f32 = 0.05;
 
+thread_local! {
+    static IS_SCRIPT_ATTRIBUTION_ENABLED: Cell<bool> = const { Cell::new(false) };
+}
+
 pub fn draw_editor_profiler(editor: &mut EditorState, ui: &mut egui::Ui) {
     let mut is_shown = editor.config.borrow().is_profiler_window_shown;
 
@@ -23,18 +35,11 @@ pub fn draw_editor_profiler(editor: &mut EditorState, ui: &mut egui::Ui) {
                 return;
             };
             let metrics = &project.game.metrics_holder;
+            draw_recording_controls(editor, ui, &mut metrics.borrow_mut());
             let metrics_ref = metrics.borrow();
 
             egui::ScrollArea::vertical().show(ui, |ui| {
-                let colors = [
-                    egui::Color32::from_rgb(255, 100, 100),
-                    egui::Color32::from_rgb(100, 255, 100),
-                    egui::Color32::from_rgb(100, 100, 255),
-                    egui::Color32::from_rgb(255, 255, 100),
-                    egui::Color32::from_rgb(100, 255, 255),
-                    egui::Color32::from_rgb(255, 100, 255),
-                    egui::Color32::WHITE,
-                ];
+                let colors = Palette::of(ui).graph_series;
 
                 ui.heading("Timed")
                     .on_hover_text("
@@ -104,11 +109,64 @@ spent executing Lua are shown, but you can add your own metrics using Debug.time
                         &painter,
                         metric,
                         color,
+                        0.0,
                         max_val,
                         metric.frames_since_addition(),
                     );
                 }
 
+                if let Some(skipped_frames) =
+                    metrics_ref.get_numeric_metric_by_name(SKIPPED_FRAME_METRIC_NAME)
+                {
+                    let skipped_count = skipped_frames.values().sum::<usize>();
+                    ui.label(format!(
+                        "Skipped frames: {skipped_count} of the last {}",
+                        skipped_frames.samples()
+                    ))
+                    .on_hover_text(
+                        "
+Frames that players would not see because the game runs slower than the display. The editor still shows them.
+"
+                        .trim(),
+                    );
+                }
+
+                ui.separator();
+
+                ui.heading("Memory").on_hover_text(
+                    "Memory used by each subsystem. The ticks show the peak usage since the game was loaded.",
+                );
+                draw_memory_breakdown(ui, &colors);
+
+                ui.separator();
+
+                ui.heading("Garbage collector").on_hover_text(
+                    "
+Memory allocated by Lua during each frame and time spent collecting it. Frames that allocate a lot
+end up paying for it with long collections, which look like stutters.
+"
+                    .trim(),
+                );
+                draw_gc_summary(ui, &metrics_ref, project);
+
+                ui.separator();
+
+                ui.heading("Scripts").on_hover_text(
+                    "
+Growth of the Lua heap attributed to the script running when it happened. The numbers are sampled: use them
+to find which script allocates the most, not to measure exact sizes.
+"
+                    .trim(),
+                );
+                draw_script_attribution(ui, project);
+
+                ui.separator();
+
+                ui.heading("Game metrics").on_hover_text(
+                    "Numbers recorded by the game with Debug.metricCounter and Debug.metricGauge.",
+                );
+                draw_game_metrics(ui, &metrics_ref);
+
                 ui.separator();
 
                 ui.heading("Metrics");
@@ -118,6 +176,10 @@ spent executing Lua are shown, but you can add your own metrics using Debug.time
                 }
             });
         });
+    if !is_shown && let Some(project) = editor.project.borrow().as_ref() {
+        // The interrupt hook stops inspecting the stack when nobody looks at the numbers.
+        *project.hook_attribution.borrow_mut() = None;
+    }
     if let Some(response) = maybe_response {
         let on_top = Some(response.response.layer_id) == ui.top_layer_id();
         if on_top && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
@@ -127,6 +189,251 @@ spent executing Lua are shown, but you can add your own metrics using Debug.time
     editor.config.borrow_mut().is_profiler_window_shown = is_shown;
 }
 
+fn draw_recording_controls(editor: &EditorState, ui: &mut egui::Ui, metrics: &mut MetricsHolder) {
+    ui.horizontal(|ui| {
+        let Some(recording) = metrics.recording() else {
+            if ui
+                .button("Record")
+                .on_hover_text(
+                    "Keep the values of every metric for each frame, to save them as CSV",
+                )
+                .clicked()
+            {
+                metrics.start_recording();
+            }
+            return;
+        };
+        let frame_count = recording.frame_count();
+        if ui.button("Save as CSV").clicked() {
+            editor.window.borrow_mut().set_always_on_top(false); // prevent editor from being over the file picker.
+            let path = rfd::FileDialog::new()
+                .add_filter("CSV", &["csv"])
+                .set_file_name("metrics.csv")
+                .set_title("Save the recorded metrics")
+                .save_file();
+            editor
+                .window
+                .borrow_mut()
+                .set_always_on_top(editor.config.borrow().is_always_on_top);
+            // The recording goes on when the dialog is cancelled.
+            if let Some(path) = path
+                && let Some(recording) = metrics.stop_recording()
+                && let Err(error) = std::fs::write(&path, recording.to_csv())
+            {
+                console::print_err(format!(
+                    "Unable to save the metrics to {}: {error}",
+                    path.display()
+                ));
+            }
+        }
+        if ui.button("Stop").clicked() {
+            metrics.stop_recording();
+        }
+        if frame_count >= MAX_RECORDED_FRAMES {
+            ui.label(format!("Recording is full ({frame_count} frames)"));
+        } else {
+            ui.label(format!("Recording: {frame_count} frames"));
+        }
+    });
+}
+
+fn draw_game_metrics(ui: &mut egui::Ui, metrics: &MetricsHolder) {
+    let mut game_metrics = metrics.get_game_metrics().peekable();
+    if game_metrics.peek().is_none() {
+        ui.weak("Call Debug.metricCounter or Debug.metricGauge to graph the numbers of your game.");
+        return;
+    }
+    let color = Palette::of(ui).graph_line;
+    for game_metric in game_metrics {
+        let metric = game_metric.metric();
+        let kind = match game_metric.kind() {
+            GameMetricKind::Counter => "per frame",
+            GameMetricKind::Gauge => "gauge",
+        };
+        ui.label(format!(
+            "{} ({kind}): {}",
+            metric.name(),
+            metric.last().map(|value| value.0).unwrap_or_default()
+        ));
+        // The graph fits the values, which can be negative.
+        let min_val = metric.min().into_f32().min(0.0);
+        let max_val = metric.max().into_f32().max(min_val + f32::EPSILON);
+        let (response, painter) = setup_drawing_area(ui, 60.0);
+        draw_graph_impl(
+            &response,
+            &painter,
+            metric,
+            color,
+            min_val,
+            max_val,
+            metric.frames_since_addition(),
+        );
+        ui.add_space(4.0);
+    }
+}
+
+fn draw_script_attribution(ui: &mut egui::Ui, project: &ProjectState) {
+    let mut is_enabled = IS_SCRIPT_ATTRIBUTION_ENABLED.get();
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut is_enabled, "Attribute Lua allocations to scripts")
+            .on_hover_text("Slows down the game a bit while enabled.");
+        if is_enabled && ui.button("Reset").clicked() {
+            *project.hook_attribution.borrow_mut() = None;
+        }
+    });
+    IS_SCRIPT_ATTRIBUTION_ENABLED.set(is_enabled);
+
+    let mut attribution = project.hook_attribution.borrow_mut();
+    if !is_enabled {
+        *attribution = None;
+        return;
+    }
+    // Also recreates the attribution after a reload of the game, as the hooks are set up again.
+    let attribution = attribution
+        .get_or_insert_with(|| ScriptAttribution::new(&project.game.lua_env.lua_handle.lua));
+
+    let mut scripts = attribution.scripts.iter().collect::<Vec<_>>();
+    scripts.sort_by(|(name_a, a), (name_b, b)| {
+        b.allocated_last_second
+            .cmp(&a.allocated_last_second)
+            .then(b.allocated_total.cmp(&a.allocated_total))
+            .then(name_a.cmp(name_b))
+    });
+    if scripts.is_empty() {
+        ui.weak("No script has run since the attribution started");
+        return;
+    }
+    egui::Grid::new("script_attribution")
+        .striped(true)
+        .num_columns(4)
+        .show(ui, |ui| {
+            ui.strong("Script");
+            ui.strong("Allocated / s");
+            ui.strong("Allocated in total");
+            ui.strong("Calls and loops / s").on_hover_text(
+                "Number of function calls and loop iterations, an approximation of the work done by the script.",
+            );
+            ui.end_row();
+            for (script, usage) in scripts {
+                ui.label(script);
+                ui.label(format_bytes(usage.allocated_last_second));
+                ui.label(format_bytes(usage.allocated_total));
+                ui.label(usage.interrupts_last_second.to_string());
+                ui.end_row();
+            }
+        });
+}
+
+fn draw_gc_summary(ui: &mut egui::Ui, metrics: &MetricsHolder, project: &ProjectState) {
+    if let Some(allocated) = metrics.get_numeric_metric_by_name(LUA_ALLOCATED_METRIC_NAME)
+        && allocated.samples() > 0
+    {
+        ui.label(format!(
+            "Allocated per frame: {} on average, {} at most",
+            format_bytes(allocated.avg()),
+            format_bytes(allocated.max())
+        ));
+    }
+    if let Some(gc_time) = metrics.get_duration_metric_by_name(LUA_GC_TIME_METRIC_NAME)
+        && gc_time.samples() > 0
+    {
+        ui.label(format!(
+            "Collection time per frame: {:.2}ms on average, {:.2}ms at most",
+            gc_time.avg().into_f32(),
+            gc_time.max().into_f32()
+        ));
+    }
+    if project.game.lua_env.gc_pacer.borrow().is_step_per_frame() {
+        ui.label("The collector runs a step at the end of every frame (gc_step_per_frame).");
+    } else {
+        ui.weak(
+            "Set gc_step_per_frame = true in game.vecta to spread the collections over the frames.",
+        );
+    }
+
+    let attribution = project.hook_attribution.borrow();
+    let Some(attribution) = attribution.as_ref() else {
+        ui.weak("Enable \"Attribute Lua allocations to scripts\" below to find the scripts that allocate.");
+        return;
+    };
+    let total_last_second: usize = attribution
+        .scripts
+        .values()
+        .map(|usage| usage.allocated_last_second)
+        .sum();
+    let top_script = attribution
+        .scripts
+        .iter()
+        .max_by_key(|(_, usage)| usage.allocated_last_second);
+    if let Some((script, usage)) = top_script
+        && total_last_second > 0
+    {
+        ui.label(format!(
+            "Allocates the most: {script} ({}/s, {:.0}% of the allocations)",
+            format_bytes(usage.allocated_last_second),
+            usage.allocated_last_second as f32 / total_last_second as f32 * 100.0
+        ));
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f32;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1}{}", UNITS[unit])
+}
+
+fn draw_memory_breakdown(ui: &mut egui::Ui, colors: &[egui::Color32]) {
+    let total: usize = MemoryCategory::ALL.iter().map(|c| c.usage()).sum();
+    let scale = MemoryCategory::ALL
+        .iter()
+        .map(|c| c.peak())
+        .sum::<usize>()
+        .max(total)
+        .max(1) as f32;
+
+    ui.label(format!("Total: {}", format_bytes(total)));
+    ui.horizontal_wrapped(|ui| {
+        for (i, category) in MemoryCategory::ALL.iter().enumerate() {
+            ui.label(
+                RichText::new(format!(
+                    "{}: {} (peak {})",
+                    category.name(),
+                    format_bytes(category.usage()),
+                    format_bytes(category.peak())
+                ))
+                .color(colors[i % colors.len()]),
+            );
+        }
+    });
+
+    let (response, painter) = setup_drawing_area(ui, 20.0);
+    let rect = response.rect;
+    let mut x = rect.min.x;
+    for (i, category) in MemoryCategory::ALL.iter().enumerate() {
+        let color = colors[i % colors.len()];
+        let width = category.usage() as f32 / scale * rect.width();
+        let peak_x = x + category.peak() as f32 / scale * rect.width();
+        painter.rect_filled(
+            egui::Rect::from_min_max(egui::pos2(x, rect.min.y), egui::pos2(x + width, rect.max.y)),
+            0.0,
+            color,
+        );
+        painter.line_segment(
+            [
+                egui::pos2(peak_x, rect.min.y),
+                egui::pos2(peak_x, rect.min.y + rect.height() / 3.0),
+            ],
+            egui::Stroke::new(2.0_f32, color),
+        );
+        x += width;
+    }
+}
+
 fn draw_metric_graph<T: Measurable>(ui: &mut egui::Ui, metric: &Metric<T>, unit: &str) {
     ui.label(format!(
         "{}: {:.2}{}",
@@ -140,7 +447,8 @@ fn draw_metric_graph<T: Measurable>(ui: &mut egui::Ui, metric: &Metric<T>, unit:
         &response,
         &painter,
         metric,
-        egui::Color32::WHITE,
+        Palette::of(ui).graph_line,
+        0.0,
         max_val,
         metric.frames_since_addition(),
     );
@@ -159,6 +467,7 @@ fn draw_graph_impl<T: Measurable>(
     painter: &egui::Painter,
     metric: &Metric<T>,
     color: egui::Color32,
+    min_val: f32,
     max_val: f32,
     frames_since_addition: usize,
 ) {
@@ -179,7 +488,7 @@ fn draw_graph_impl<T: Measurable>(
             let x_fraction = 1.0 - (frames_ago as f32 / METRICS_STORAGE_DURATION as f32);
 
             let x = rect.min.x + x_fraction.clamp(0.0, 1.0) * rect.width();
-            let y = rect.max.y - (val.into_f32() / max_val) * rect.height();
+            let y = rect.max.y - ((val.into_f32() - min_val) / (max_val - min_val)) * rect.height();
             egui::pos2(x, y)
         })
         .collect();
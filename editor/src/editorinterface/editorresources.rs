@@ -3,10 +3,17 @@ use std::{cell::RefCell, path::PathBuf, sync::Arc};
 use egui_extras::{Column, TableBuilder};
 use runtime::egui::ScrollArea;
 use runtime::game::Game;
+use runtime::game_resource::Status;
 use runtime::glow;
+use runtime::io::enginefs::{ENGINE_ASSET_PREFIX, ENGINE_ASSETS, is_engine_asset};
 use runtime::{egui, egui_glow};
 
 use crate::editorinterface::EditorState;
+use assetoperations::{
+    draw_asset_operation_window, draw_create_asset_buttons, draw_resource_context_menu,
+};
+
+mod assetoperations;
 
 pub fn draw_editor_resources(
     editor: &EditorState,
@@ -14,14 +21,11 @@ pub fn draw_editor_resources(
     ui: &mut egui::Ui,
 ) {
     let mut project = editor.project.borrow_mut();
-    let game = match project.as_mut() {
-        Some(proj) => Some(&mut proj.game),
-        None => None,
-    };
-
-    let Some(game) = game else {
+    let Some(project) = project.as_mut() else {
         return;
     };
+    let is_read_only = project.is_read_only;
+    let game = &mut project.game;
 
     let mut is_shown = editor.config.borrow().is_resources_window_shown;
     let maybe_response = egui::Window::new("Resources")
@@ -32,8 +36,12 @@ pub fn draw_editor_resources(
         .show(ui, |ui| {
             ScrollArea::vertical()
                 .auto_shrink([true, false])
-                .show(ui, |ui| draw_scroll_area_content(editor, ui, game));
+                .show(ui, |ui| {
+                    draw_scroll_area_content(editor, ui, game, is_read_only)
+                });
         });
+    let project_folder = game.lua_env.resources.get_resource_path();
+    draw_asset_operation_window(ui, &project_folder, is_read_only);
     if let Some(response) = maybe_response {
         let on_top = Some(response.response.layer_id) == ui.top_layer_id();
         if on_top && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
@@ -56,7 +64,12 @@ pub fn draw_editor_resources(
     };
 }
 
-fn draw_scroll_area_content(editor: &EditorState, ui: &mut egui::Ui, game: &mut Game) {
+fn draw_scroll_area_content(
+    editor: &EditorState,
+    ui: &mut egui::Ui,
+    game: &mut Game,
+    is_read_only: bool,
+) {
     thread_local! {
         static RESOURCE_SEARCH: RefCell<String> = const { RefCell::new(String::new()) };
     }
@@ -68,6 +81,11 @@ fn draw_scroll_area_content(editor: &EditorState, ui: &mut egui::Ui, game: &mut
             editor.window.borrow_mut().set_always_on_top(false);
             open::that(absolute_path).ok();
         }
+        draw_create_asset_buttons(ui, is_read_only);
+        ui.checkbox(
+            &mut editor.config.borrow_mut().are_only_resource_errors_shown,
+            "Only errors",
+        );
 
         let resource_count = game.lua_env.resources.enumerate().count();
         // No need to display the search if there are few resources
@@ -82,7 +100,16 @@ fn draw_scroll_area_content(editor: &EditorState, ui: &mut egui::Ui, game: &mut
     });
     let search_query = RESOURCE_SEARCH.with_borrow(|s| s.clone());
 
-    draw_resource_table(editor, ui, game, &search_query);
+    egui::CollapsingHeader::new("Engine assets")
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.label("These assets are built into Vectarine. Load them with their path.");
+            for (path, _) in ENGINE_ASSETS {
+                ui.label(format!("{ENGINE_ASSET_PREFIX}{path}"));
+            }
+            draw_resource_table(editor, ui, game, &search_query, is_read_only, true);
+        });
+    draw_resource_table(editor, ui, game, &search_query, is_read_only, false);
 }
 
 fn draw_resource_table(
@@ -90,12 +117,15 @@ fn draw_resource_table(
     ui: &mut egui::Ui,
     game: &mut Game,
     search_query: &str,
+    is_read_only: bool,
+    is_engine_section: bool,
 ) {
     let available_height = ui.available_height();
     let table = TableBuilder::new(ui)
+        .id_salt(is_engine_section)
         .striped(true)
         .resizable(true)
-        .auto_shrink(false)
+        .auto_shrink([false, is_engine_section])
         .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
         .column(Column::auto()) // id
         .column(Column::auto().clip(true)) // path
@@ -129,12 +159,23 @@ fn draw_resource_table(
         .body(|mut body| {
             for (id, res) in game.lua_env.resources.enumerate() {
                 let resources = game.lua_env.resources.clone();
-                let status_string = res.get_status().to_string();
+                let mut status_string = res.get_status().to_string();
+                if let Some(pack) = res.get_source_pack() {
+                    status_string.push_str(&format!(" (from the content pack {pack})"));
+                }
                 let status_length = status_string.len();
                 let row_height = f32::max(20.0, status_length as f32 / 2.0);
 
+                if is_engine_asset(res.get_path()) != is_engine_section {
+                    continue;
+                }
                 let path = resources.get_absolute_path(res.get_path());
-                if !path.contains(search_query) {
+                if !path.to_string_lossy().contains(search_query) {
+                    continue;
+                }
+                if editor.config.borrow().are_only_resource_errors_shown
+                    && !matches!(res.get_status(), Status::Error(_))
+                {
                     continue;
                 }
 
@@ -143,14 +184,27 @@ fn draw_resource_table(
                         ui.label(id.to_string());
                     });
                     row.col(|ui| {
-                        if ui
+                        if is_engine_section {
+                            // Engine assets are embedded in the binary, there is no file to open or rename.
+                            ui.label(res.get_path().to_string_lossy().to_string());
+                            return;
+                        }
+                        let response = ui
                             .link(res.get_path().to_string_lossy().to_string())
-                            .clicked()
-                        {
+                            .on_hover_text("Right-click to rename, duplicate or delete the file");
+                        if response.clicked() {
                             // Open the file
                             let absolute_path = resources.get_absolute_path(res.get_path());
                             open::that(absolute_path).ok();
                         }
+                        response.context_menu(|ui| {
+                            draw_resource_context_menu(
+                                ui,
+                                &resources.get_resource_path(),
+                                res.get_path(),
+                                is_read_only,
+                            );
+                        });
                     });
                     row.col(|ui| {
                         ui.label(res.get_type_name().to_string());
@@ -0,0 +1,582 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use runtime::console::{print_err, print_info, print_warn};
+use runtime::egui;
+use runtime::io::{
+    fs::{FileSystem, ReadOnlyFileSystem},
+    localfs::LocalFileSystem,
+    paths::describe_path,
+};
+
+const READ_ONLY_MESSAGE: &str =
+    "Gallery projects are read-only. Copy the project folder somewhere else to edit its files.";
+
+const SCRIPT_TEMPLATE: &str = r#"local module = {}
+
+return module
+"#;
+
+const SHADER_TEMPLATE: &str = r#"precision mediump float;
+in vec2 uv;
+uniform sampler2D tex;
+uniform float iTime;
+out vec4 frag_color;
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {
+    fragColor = texture(tex, uv);
+}
+
+void main() {
+    mainImage(frag_color, uv);
+}
+"#;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AssetTemplate {
+    Script,
+    Shader,
+}
+
+impl AssetTemplate {
+    fn extension(self) -> &'static str {
+        match self {
+            AssetTemplate::Script => "luau",
+            AssetTemplate::Shader => "glsl",
+        }
+    }
+
+    fn content(self) -> &'static str {
+        match self {
+            AssetTemplate::Script => SCRIPT_TEMPLATE,
+            AssetTemplate::Shader => SHADER_TEMPLATE,
+        }
+    }
+}
+
+/// A line of a script containing a string literal that references a renamed file.
+struct ScriptReference {
+    file: PathBuf,
+    /// Starts at 0.
+    line_index: usize,
+    line: String,
+    is_selected: bool,
+}
+
+/// Operation waiting for the confirmation of the user. Paths are relative to the project folder.
+enum AssetOperation {
+    Rename {
+        path: String,
+        new_path: String,
+    },
+    Delete {
+        path: String,
+        /// Number of lines of the scripts still referencing the file.
+        reference_count: usize,
+    },
+    Create {
+        folder: String,
+        name: String,
+        template: AssetTemplate,
+    },
+    UpdateReferences {
+        old_path: String,
+        new_path: String,
+        references: Vec<ScriptReference>,
+    },
+}
+
+thread_local! {
+    static ASSET_OPERATION: RefCell<Option<AssetOperation>> = const { RefCell::new(None) };
+}
+
+/// Draws the menu shown when right-clicking a resource.
+pub fn draw_resource_context_menu(
+    ui: &mut egui::Ui,
+    project_folder: &Path,
+    resource_path: &Path,
+    is_read_only: bool,
+) {
+    let path = to_slash_path(resource_path);
+    if is_read_only {
+        ui.label(READ_ONLY_MESSAGE);
+    }
+    ui.add_enabled_ui(!is_read_only, |ui| {
+        if ui.button("Rename…").clicked() {
+            set_operation(AssetOperation::Rename {
+                new_path: path.clone(),
+                path: path.clone(),
+            });
+            ui.close();
+        }
+        if ui.button("Duplicate").clicked() {
+            duplicate_file(project_folder, &path);
+            ui.close();
+        }
+        if ui.button("Move to trash…").clicked() {
+            set_operation(AssetOperation::Delete {
+                reference_count: find_script_references(project_folder, &path).len(),
+                path: path.clone(),
+            });
+            ui.close();
+        }
+    });
+}
+
+/// Draws the buttons to create new files from a template.
+pub fn draw_create_asset_buttons(ui: &mut egui::Ui, is_read_only: bool) {
+    let response = ui.add_enabled_ui(!is_read_only, |ui| {
+        ui.menu_button("New file", |ui| {
+            for (label, folder, template) in [
+                ("Script", "scripts", AssetTemplate::Script),
+                ("Shader", "shaders", AssetTemplate::Shader),
+            ] {
+                if ui.button(label).clicked() {
+                    set_operation(AssetOperation::Create {
+                        folder: folder.to_string(),
+                        name: String::new(),
+                        template,
+                    });
+                    ui.close();
+                }
+            }
+        });
+    });
+    if is_read_only {
+        response.response.on_hover_text(READ_ONLY_MESSAGE);
+    }
+}
+
+/// Draws the window asking for the confirmation of the pending operation, if any.
+pub fn draw_asset_operation_window(ui: &mut egui::Ui, project_folder: &Path, is_read_only: bool) {
+    ASSET_OPERATION.with_borrow_mut(|operation| {
+        let Some(current) = operation.as_mut() else {
+            return;
+        };
+        if is_read_only {
+            // The project may have changed since the operation was requested.
+            print_warn(READ_ONLY_MESSAGE.to_string());
+            *operation = None;
+            return;
+        }
+        let mut is_open = true;
+        let mut next_operation = None;
+        let mut is_done = false;
+        egui::Window::new("File operation")
+            .collapsible(false)
+            .resizable(true)
+            .open(&mut is_open)
+            .show(ui, |ui| match current {
+                AssetOperation::Rename { path, new_path } => {
+                    ui.label(format!("Rename {path} to:"));
+                    ui.text_edit_singleline(new_path);
+                    if ui.button("Rename").clicked() {
+                        is_done = true;
+                        if rename_file(project_folder, path, new_path) {
+                            let references = find_script_references(project_folder, path);
+                            if !references.is_empty() {
+                                next_operation = Some(AssetOperation::UpdateReferences {
+                                    old_path: path.clone(),
+                                    new_path: new_path.clone(),
+                                    references,
+                                });
+                            }
+                        }
+                    }
+                }
+                AssetOperation::Delete {
+                    path,
+                    reference_count,
+                } => {
+                    ui.label(format!("Move {path} to the trash?"));
+                    if *reference_count > 0 {
+                        ui.label(format!(
+                            "{reference_count} line(s) of the scripts still reference this file."
+                        ));
+                    }
+                    if ui.button("Move to trash").clicked() {
+                        is_done = true;
+                        delete_file(project_folder, path);
+                    }
+                }
+                AssetOperation::Create {
+                    folder,
+                    name,
+                    template,
+                } => {
+                    egui::Grid::new("create_asset_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Folder");
+                            ui.text_edit_singleline(folder);
+                            ui.end_row();
+                            ui.label("Name");
+                            ui.text_edit_singleline(name);
+                            ui.end_row();
+                        });
+                    let file_name = if name.ends_with(&format!(".{}", template.extension())) {
+                        name.clone()
+                    } else {
+                        format!("{name}.{}", template.extension())
+                    };
+                    let path = to_slash_path(&Path::new(folder.as_str()).join(file_name));
+                    ui.add_enabled_ui(!name.is_empty(), |ui| {
+                        if ui.button(format!("Create {path}")).clicked() {
+                            is_done = true;
+                            create_file(project_folder, &path, template.content());
+                        }
+                    });
+                }
+                AssetOperation::UpdateReferences {
+                    old_path,
+                    new_path,
+                    references,
+                } => {
+                    ui.label(format!(
+                        "These lines reference {old_path}. Select the ones to change to {new_path}:"
+                    ));
+                    egui::ScrollArea::vertical()
+                        .max_height(300.0)
+                        .show(ui, |ui| {
+                            for reference in references.iter_mut() {
+                                let file = reference
+                                    .file
+                                    .strip_prefix(project_folder)
+                                    .unwrap_or(&reference.file);
+                                ui.checkbox(
+                                    &mut reference.is_selected,
+                                    format!(
+                                        "{}:{}  {}",
+                                        to_slash_path(file),
+                                        reference.line_index + 1,
+                                        reference.line.trim()
+                                    ),
+                                );
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        if ui.button("Update selected lines").clicked() {
+                            is_done = true;
+                            update_references(references, old_path, new_path);
+                        }
+                        if ui.button("Keep the scripts unchanged").clicked() {
+                            is_done = true;
+                        }
+                    });
+                }
+            });
+        if next_operation.is_some() || is_done || !is_open {
+            *operation = next_operation;
+        }
+    });
+}
+
+fn set_operation(operation: AssetOperation) {
+    ASSET_OPERATION.with_borrow_mut(|current| *current = Some(operation));
+}
+
+fn to_slash_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn absolute_path(project_folder: &Path, path: &str) -> PathBuf {
+    project_folder.join(path)
+}
+
+fn rename_file(project_folder: &Path, path: &str, new_path: &str) -> bool {
+    if path == new_path {
+        return false;
+    }
+    // The callback of the local file system is called before rename_file returns.
+    let is_renamed = Rc::new(Cell::new(false));
+    LocalFileSystem.rename_file(
+        &absolute_path(project_folder, path),
+        &absolute_path(project_folder, new_path),
+        Box::new({
+            let is_renamed = is_renamed.clone();
+            move |success| is_renamed.set(success)
+        }),
+    );
+    if is_renamed.get() {
+        print_info(format!("Renamed {path} to {new_path}"));
+    } else {
+        print_err(format!(
+            "Failed to rename {path} to {new_path}. Check that {new_path} does not exist already."
+        ));
+    }
+    is_renamed
+}
+
+fn delete_file(project_folder: &Path, path: &str) {
+    let path = path.to_string();
+    LocalFileSystem.delete_file(
+        &absolute_path(project_folder, &path),
+        Box::new(move |success| {
+            if success {
+                print_info(format!("Moved {path} to the trash"));
+            } else {
+                print_err(format!("Failed to move {path} to the trash"));
+            }
+        }),
+    );
+}
+
+fn create_file(project_folder: &Path, path: &str, content: &str) {
+    let absolute_path = absolute_path(project_folder, path);
+    if absolute_path.exists() {
+        print_err(format!("{path} already exists"));
+        return;
+    }
+    let path = path.to_string();
+    LocalFileSystem.write_file(
+        &absolute_path,
+        content.as_bytes(),
+        Box::new(move |success| {
+            if success {
+                print_info(format!("Created {path}"));
+            } else {
+                print_err(format!(
+                    "Failed to create {path}. Check that the folder exists."
+                ));
+            }
+        }),
+    );
+}
+
+/// Copies the file next to itself, as `name copy.ext`, `name copy 2.ext`, etc.
+fn duplicate_file(project_folder: &Path, path: &str) {
+    let original = Path::new(path);
+    let stem = original
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = original
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let Some(copy_path) = (1..100)
+        .map(|i| {
+            let suffix = if i == 1 {
+                " copy".to_string()
+            } else {
+                format!(" copy {i}")
+            };
+            to_slash_path(&original.with_file_name(format!("{stem}{suffix}{extension}")))
+        })
+        .find(|copy_path| !project_folder.join(copy_path).exists())
+    else {
+        print_err(format!("Failed to find a name for the copy of {path}"));
+        return;
+    };
+    let Some(content) = LocalFileSystem.read_file_sync(&absolute_path(project_folder, path)) else {
+        print_err(format!("Failed to read {path}"));
+        return;
+    };
+    create_file(
+        project_folder,
+        &copy_path,
+        &String::from_utf8_lossy(&content),
+    );
+}
+
+/// Returns the byte ranges of the content of the string literals of a line of Luau.
+/// Long strings (`[[...]]`) are ignored as they can span several lines.
+fn string_literal_ranges(line: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut literal: Option<(char, usize)> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match literal {
+            Some((quote, start)) => {
+                if c == '\\' {
+                    chars.next();
+                } else if c == quote {
+                    ranges.push(start..index);
+                    literal = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' | '`' => literal = Some((c, index + c.len_utf8())),
+                '-' if chars.peek().is_some_and(|(_, next)| *next == '-') => break,
+                _ => {}
+            },
+        }
+    }
+    ranges
+}
+
+/// Replaces the string literals of the line that are exactly the path `old` by `new`. A literal starting with `./`
+/// keeps it. Returns None when no literal is the path, so `"hero.png"` is not changed when renaming `o.png`.
+fn replace_in_string_literals(line: &str, old: &str, new: &str) -> Option<String> {
+    let is_old_path = |literal: &str| literal.strip_prefix("./").unwrap_or(literal) == old;
+    let ranges = string_literal_ranges(line)
+        .into_iter()
+        .filter(|range| is_old_path(&line[range.clone()]))
+        .collect::<Vec<_>>();
+    if ranges.is_empty() {
+        return None;
+    }
+    let mut result = String::with_capacity(line.len());
+    let mut end_of_previous_range = 0;
+    for range in ranges {
+        result.push_str(&line[end_of_previous_range..range.start]);
+        if line[range.clone()].starts_with("./") {
+            result.push_str("./");
+        }
+        result.push_str(new);
+        end_of_previous_range = range.end;
+    }
+    result.push_str(&line[end_of_previous_range..]);
+    Some(result)
+}
+
+fn collect_scripts(folder: &Path, scripts: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if is_hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "luau" || extension == "lua")
+        {
+            scripts.push(path);
+        }
+    }
+}
+
+/// Returns the lines of the scripts of the project with a string literal that is `path`.
+fn find_script_references(project_folder: &Path, path: &str) -> Vec<ScriptReference> {
+    let mut scripts = Vec::new();
+    collect_scripts(project_folder, &mut scripts);
+    let mut references = Vec::new();
+    for script in scripts {
+        let Some(content) = LocalFileSystem.read_file_sync(&script) else {
+            continue;
+        };
+        let content = String::from_utf8_lossy(&content);
+        for (line_index, line) in content.lines().enumerate() {
+            if replace_in_string_literals(line, path, path).is_some() {
+                references.push(ScriptReference {
+                    file: script.clone(),
+                    line_index,
+                    line: line.to_string(),
+                    is_selected: true,
+                });
+            }
+        }
+    }
+    references
+}
+
+/// Rewrites the selected lines. Lines that changed since they were found are left untouched.
+fn update_references(references: &[ScriptReference], old_path: &str, new_path: &str) {
+    let mut files = references
+        .iter()
+        .filter(|reference| reference.is_selected)
+        .map(|reference| &reference.file)
+        .collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+    for file in files {
+        let file_name = describe_path(file);
+        let Some(content) = LocalFileSystem.read_file_sync(file) else {
+            print_err(format!("Failed to read {file_name}"));
+            continue;
+        };
+        let content = String::from_utf8_lossy(&content);
+        let mut updated_line_count = 0;
+        let updated_content = content
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(line_index, line)| {
+                let is_selected = references.iter().any(|reference| {
+                    reference.is_selected
+                        && &reference.file == file
+                        && reference.line_index == line_index
+                        && line.trim_end_matches(['\r', '\n']) == reference.line
+                });
+                match replace_in_string_literals(line, old_path, new_path) {
+                    Some(updated_line) if is_selected => {
+                        updated_line_count += 1;
+                        updated_line
+                    }
+                    _ => line.to_string(),
+                }
+            })
+            .collect::<String>();
+        LocalFileSystem.write_file(
+            file,
+            updated_content.as_bytes(),
+            Box::new(move |success| {
+                if success {
+                    print_info(format!(
+                        "Updated {updated_line_count} line(s) of {file_name}"
+                    ));
+                } else {
+                    print_err(format!("Failed to write {file_name}"));
+                }
+            }),
+        );
+    }
+    let skipped_count = references
+        .iter()
+        .filter(|reference| !reference.is_selected)
+        .count();
+    if skipped_count > 0 {
+        print_warn(format!(
+            "{skipped_count} line(s) still reference {old_path}, which does not exist anymore"
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{replace_in_string_literals, string_literal_ranges};
+
+    #[test]
+    fn string_literals_are_found_outside_of_comments() {
+        let line = r#"local a, b = "x\"y", 'z' -- "comment""#;
+        let literals = string_literal_ranges(line)
+            .into_iter()
+            .map(|range| &line[range])
+            .collect::<Vec<_>>();
+        assert_eq!(literals, vec![r#"x\"y"#, "z"]);
+    }
+
+    #[test]
+    fn only_whole_path_literals_are_replaced() {
+        assert_eq!(
+            replace_in_string_literals(r#"Loader.loadImage("a.png") -- "a.png""#, "a.png", "b.png"),
+            Some(r#"Loader.loadImage("b.png") -- "a.png""#.to_string())
+        );
+        // Other paths containing the old one are kept.
+        assert_eq!(
+            replace_in_string_literals(
+                r#"load("data.png", "./a.png", "textures/a.png")"#,
+                "a.png",
+                "b.png"
+            ),
+            Some(r#"load("data.png", "./b.png", "textures/a.png")"#.to_string())
+        );
+        assert_eq!(
+            replace_in_string_literals(r#"print("a.png is missing")"#, "a.png", "b.png"),
+            None
+        );
+        assert_eq!(
+            replace_in_string_literals("local a_png = 1", "a.png", "b.png"),
+            None
+        );
+    }
+}
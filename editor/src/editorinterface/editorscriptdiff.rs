@@ -0,0 +1,225 @@
+use std::cell::RefCell;
+use std::path::Path;
+
+use runtime::egui::{self, RichText};
+use runtime::game::Game;
+use runtime::game_resource::script_resource::ScriptResource;
+
+use crate::editortheme::Palette;
+
+/// Past this many pairs of changed lines, the diff shows every old line as removed and every new line as added instead
+/// of looking for the lines they have in common.
+const MAX_COMPARED_LINE_PAIRS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLineKind {
+    Unchanged,
+    Removed,
+    Added,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub kind: DiffLineKind,
+    /// The 1-based number of the line in the current version. None for the removed lines.
+    pub current_line: Option<usize>,
+    pub text: String,
+}
+
+/// The difference between the version of a script that last worked and the current one, shown from a Lua error.
+struct ScriptDiff {
+    file: String,
+    error_line: usize,
+    /// None when no working version of the script was kept.
+    lines: Option<Vec<DiffLine>>,
+}
+
+thread_local! {
+    static SCRIPT_DIFF: RefCell<Option<ScriptDiff>> = const { RefCell::new(None) };
+}
+
+/// Computes the diff of the script of an error, and opens the window showing it.
+pub fn show_script_diff(game: &Game, project_dir: &Path, file: &str, error_line: usize) {
+    let last_good_source = game
+        .lua_env
+        .resources
+        .get_id_by_path(Path::new(file))
+        .and_then(|id| game.lua_env.resources.get_by_id::<ScriptResource>(id).ok())
+        .and_then(|script| script.get_last_good_source());
+    let current_source = std::fs::read(project_dir.join(file)).unwrap_or_default();
+    let lines = last_good_source.map(|last_good_source| {
+        diff_lines(
+            &String::from_utf8_lossy(&last_good_source),
+            &String::from_utf8_lossy(&current_source),
+        )
+    });
+    SCRIPT_DIFF.set(Some(ScriptDiff {
+        file: file.to_string(),
+        error_line,
+        lines,
+    }));
+}
+
+pub fn draw_editor_script_diff(ui: &mut egui::Ui) {
+    SCRIPT_DIFF.with_borrow_mut(|script_diff| {
+        let Some(diff) = script_diff.as_ref() else {
+            return;
+        };
+        let mut is_shown = true;
+        egui::Window::new(format!("Changes of {}", diff.file))
+            .id(egui::Id::new("script_diff"))
+            .default_width(560.0)
+            .default_height(420.0)
+            .open(&mut is_shown)
+            .show(ui, |ui| {
+                let Some(lines) = &diff.lines else {
+                    ui.label("No working version of this script was kept: it never ran without errors since the project was opened, or it is too big.");
+                    return;
+                };
+                if lines.iter().all(|line| line.kind == DiffLineKind::Unchanged) {
+                    ui.label("The script did not change since the version that last worked.");
+                }
+                let palette = Palette::of(ui);
+                egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
+                    for line in lines {
+                        let (sign, color) = match line.kind {
+                            DiffLineKind::Unchanged => (' ', palette.muted_text),
+                            DiffLineKind::Removed => ('-', palette.error),
+                            DiffLineKind::Added => ('+', palette.info),
+                        };
+                        let number = line
+                            .current_line
+                            .map(|number| number.to_string())
+                            .unwrap_or_default();
+                        let mut text = RichText::new(format!("{number:>5} {sign} {}", line.text))
+                            .monospace()
+                            .color(color);
+                        if line.current_line == Some(diff.error_line) {
+                            text = text.background_color(palette.error.gamma_multiply(0.25));
+                        }
+                        ui.label(text);
+                    }
+                });
+            });
+        if !is_shown {
+            *script_diff = None;
+        }
+    });
+}
+
+/// A line diff of the two texts, keeping the longest sequence of lines they have in common.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+    let prefix = old
+        .iter()
+        .zip(&new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    let unchanged = |lines: &mut Vec<DiffLine>, new_index: usize| {
+        lines.push(DiffLine {
+            kind: DiffLineKind::Unchanged,
+            current_line: Some(new_index + 1),
+            text: new[new_index].to_string(),
+        });
+    };
+    for index in 0..prefix {
+        unchanged(&mut lines, index);
+    }
+    for (kind, index) in diff_middle(old_middle, new_middle) {
+        lines.push(match kind {
+            DiffLineKind::Removed => DiffLine {
+                kind,
+                current_line: None,
+                text: old_middle[index].to_string(),
+            },
+            _ => DiffLine {
+                kind,
+                current_line: Some(prefix + index + 1),
+                text: new_middle[index].to_string(),
+            },
+        });
+    }
+    for index in new.len() - suffix..new.len() {
+        unchanged(&mut lines, index);
+    }
+    lines
+}
+
+/// The lines of the changed part, with their index in `old` for the removed lines and in `new` for the others.
+fn diff_middle(old: &[&str], new: &[&str]) -> Vec<(DiffLineKind, usize)> {
+    if old.len().saturating_mul(new.len()) > MAX_COMPARED_LINE_PAIRS {
+        return (0..old.len())
+            .map(|index| (DiffLineKind::Removed, index))
+            .chain((0..new.len()).map(|index| (DiffLineKind::Added, index)))
+            .collect();
+    }
+    // common[i][j] is the length of the longest common sequence of old[i..] and new[j..].
+    let width = new.len() + 1;
+    let mut common = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i * width + j] = if old[i] == new[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut result = Vec::with_capacity(old.len() + new.len());
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push((DiffLineKind::Unchanged, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len()
+            && (j == new.len() || common[(i + 1) * width + j] >= common[i * width + j + 1])
+        {
+            // The removed lines come before the added lines that replace them.
+            result.push((DiffLineKind::Removed, i));
+            i += 1;
+        } else {
+            result.push((DiffLineKind::Added, j));
+            j += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffLineKind, diff_lines};
+
+    #[test]
+    fn the_diff_keeps_the_common_lines() {
+        let old = "local a = 1\nlocal b = 2\nprint(a)\nprint(b)\n";
+        let new = "local a = 1\nlocal b = 3\nprint(a)\nprint(c)\nprint(b)\n";
+        let lines = diff_lines(old, new)
+            .into_iter()
+            .map(|line| (line.kind, line.current_line, line.text))
+            .collect::<Vec<_>>();
+        let line = |kind, current_line, text: &str| (kind, current_line, text.to_string());
+        assert_eq!(
+            lines,
+            vec![
+                line(DiffLineKind::Unchanged, Some(1), "local a = 1"),
+                line(DiffLineKind::Removed, None, "local b = 2"),
+                line(DiffLineKind::Added, Some(2), "local b = 3"),
+                line(DiffLineKind::Unchanged, Some(3), "print(a)"),
+                line(DiffLineKind::Added, Some(4), "print(c)"),
+                line(DiffLineKind::Unchanged, Some(5), "print(b)"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use runtime::egui;
+use runtime::game_resource::Status;
+use runtime::metrics::TOTAL_FRAME_TIME_METRIC_NAME;
+
+use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+
+/// The 1% low is computed over the frames of this duration.
+const ONE_PERCENT_LOW_WINDOW: Duration = Duration::from_secs(60);
+/// A longer frame is the editor being suspended, like when its window is dragged, and not time spent playing.
+const MAX_PLAYED_FRAME_TIME: Duration = Duration::from_millis(250);
+/// The current FPS is the average of this many frames.
+const FPS_FRAME_COUNT: usize = 60;
+
+/// The statistics of the playtest since the project was opened.
+#[derive(Default)]
+pub struct SessionStats {
+    play_time: Duration,
+    reload_count: u32,
+    /// The reloads of the whole project, without the scripts reloaded as they are saved.
+    project_reload_count: u32,
+    autosave_count: u32,
+    /// The frame times of the last minute, with the time they ended at.
+    recent_frames: VecDeque<(Instant, Duration)>,
+}
+
+impl SessionStats {
+    /// Called for every frame the game runs. The frames not run, like while an error is shown, do not count as play
+    /// time.
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        self.record_frame_at(Instant::now(), frame_time);
+    }
+
+    fn record_frame_at(&mut self, now: Instant, frame_time: Duration) {
+        self.play_time += frame_time.min(MAX_PLAYED_FRAME_TIME);
+        self.recent_frames.push_back((now, frame_time));
+        while self.recent_frames.front().is_some_and(|(recorded_at, _)| {
+            now.duration_since(*recorded_at) > ONE_PERCENT_LOW_WINDOW
+        }) {
+            self.recent_frames.pop_front();
+        }
+    }
+
+    pub fn count_reload(&mut self) {
+        self.reload_count += 1;
+    }
+
+    pub fn count_project_reload(&mut self) {
+        self.reload_count += 1;
+        self.project_reload_count += 1;
+    }
+
+    pub fn count_autosave(&mut self) {
+        self.autosave_count += 1;
+    }
+
+    pub fn play_time(&self) -> Duration {
+        self.play_time
+    }
+
+    pub fn reload_count(&self) -> u32 {
+        self.reload_count
+    }
+
+    pub fn project_reload_count(&self) -> u32 {
+        self.project_reload_count
+    }
+
+    pub fn autosave_count(&self) -> u32 {
+        self.autosave_count
+    }
+
+    /// The average time of the slowest 1% of the frames of the last minute, at least one frame.
+    pub fn one_percent_low(&self) -> Option<Duration> {
+        let mut frame_times = self
+            .recent_frames
+            .iter()
+            .map(|(_, frame_time)| *frame_time)
+            .collect::<Vec<_>>();
+        if frame_times.is_empty() {
+            return None;
+        }
+        frame_times.sort_unstable_by(|a, b| b.cmp(a));
+        let slowest_count = frame_times.len().div_ceil(100);
+        Some(frame_times[..slowest_count].iter().sum::<Duration>() / slowest_count as u32)
+    }
+}
+
+fn format_play_time(play_time: Duration) -> String {
+    let seconds = play_time.as_secs();
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// A compact line of statistics at the top of the game, to follow a playtest without opening the profiler.
+pub fn draw_stats_strip(editor: &EditorState, ui: &mut egui::Ui) {
+    if !editor.config.borrow().is_stats_strip_shown {
+        return;
+    }
+    let project = editor.project.borrow();
+    let Some(project) = project.as_ref() else {
+        return;
+    };
+    let stats = &project.session_stats;
+    let fps = project
+        .game
+        .metrics_holder
+        .borrow()
+        .get_duration_metric_by_name(TOTAL_FRAME_TIME_METRIC_NAME)
+        .map(|metric| metric.recent_avg(FPS_FRAME_COUNT))
+        .filter(|frame_time| !frame_time.is_zero())
+        .map(|frame_time| 1.0 / frame_time.as_secs_f64());
+    let (mut loaded, mut loading, mut errors) = (0, 0, 0);
+    for (_, resource) in project.game.lua_env.resources.enumerate() {
+        match resource.get_status() {
+            Status::Loaded => loaded += 1,
+            Status::Loading => loading += 1,
+            Status::Error(_) => errors += 1,
+            Status::Unloaded => {}
+        }
+    }
+
+    let mut should_open_profiler = false;
+    let mut should_show_resource_errors = false;
+    egui::Area::new(egui::Id::new("editor_stats_strip"))
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 28.0))
+        .order(egui::Order::Foreground)
+        .show(ui, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let palette = Palette::of(ui);
+                    ui.label(format!("⏱ {}", format_play_time(stats.play_time())))
+                        .on_hover_text("Time played since the project was opened");
+                    ui.label(format!("⟳ {}", stats.reload_count()))
+                        .on_hover_text("Reloads since the project was opened");
+                    ui.label(format!("💾 {}", stats.autosave_count()))
+                        .on_hover_text("Times the changes of the tool store were saved since the project was opened");
+                    ui.separator();
+                    let frame_text = match (fps, stats.one_percent_low()) {
+                        (Some(fps), Some(low)) => {
+                            format!("{fps:.0} FPS, 1% low {:.1} ms", low.as_secs_f64() * 1000.0)
+                        }
+                        _ => "-- FPS".to_string(),
+                    };
+                    should_open_profiler = ui
+                        .link(frame_text)
+                        .on_hover_text("The average frame time of the slowest 1% of the frames of the last minute.\nClick to open the profiler.")
+                        .clicked();
+                    ui.separator();
+                    ui.label(format!("{loaded} loaded"));
+                    if loading > 0 {
+                        ui.label(format!("{loading} loading"));
+                    }
+                    if errors > 0 {
+                        should_show_resource_errors = ui
+                            .link(egui::RichText::new(format!("{errors} errors")).color(palette.error))
+                            .on_hover_text("Show the resources that failed to load")
+                            .clicked();
+                    }
+                });
+            });
+        });
+    // The play time changes every second even when nothing else happens.
+    ui.ctx().request_repaint_after(Duration::from_secs(1));
+
+    let mut config = editor.config.borrow_mut();
+    if should_open_profiler {
+        config.is_profiler_window_shown = true;
+    }
+    if should_show_resource_errors {
+        config.is_resources_window_shown = true;
+        config.are_only_resource_errors_shown = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::SessionStats;
+
+    #[test]
+    fn the_one_percent_low_only_keeps_the_last_minute() {
+        let mut stats = SessionStats::default();
+        let start = Instant::now();
+        let milliseconds = Duration::from_millis;
+        stats.record_frame_at(start, milliseconds(500));
+        for frame in 1..200 {
+            let frame_time = if frame % 100 == 0 { 40 } else { 16 };
+            stats.record_frame_at(
+                start + milliseconds(30_000 + frame),
+                milliseconds(frame_time),
+            );
+        }
+        // The long frame is still in the last minute, and is not counted entirely as play time.
+        assert_eq!(stats.one_percent_low(), Some(milliseconds(270)));
+        assert_eq!(stats.play_time(), milliseconds(250 + 198 * 16 + 40));
+
+        stats.record_frame_at(start + milliseconds(61_000), milliseconds(16));
+        assert_eq!(stats.one_percent_low(), Some(milliseconds(28)));
+        assert_eq!(SessionStats::default().one_percent_low(), None);
+    }
+
+    #[test]
+    fn script_reloads_are_not_project_reloads() {
+        let mut stats = SessionStats::default();
+        stats.count_reload();
+        stats.count_project_reload();
+        stats.count_reload();
+        assert_eq!(stats.reload_count(), 3);
+        assert_eq!(stats.project_reload_count(), 1);
+    }
+}
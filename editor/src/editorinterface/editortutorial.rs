@@ -0,0 +1,415 @@
+//! A tutorial is a project generated from a template embedded in the editor, with steps shown over the editor.
+//! Each step outlines a part of the editor, asks the user to do something and waits for a check to pass.
+//! The steps are written in the `tutorial.toml` of the template, so a tutorial is added without code for its steps.
+
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use runtime::console::print_err;
+use runtime::egui::{self, RichText};
+use runtime::io::localfs::LocalFileSystem;
+use runtime::mlua;
+use runtime::statehash::hash_lua_value;
+use runtime::toml;
+use serde::Deserialize;
+use vectarine_cli::project::createproject::create_game_with_script;
+
+use crate::editorconfig::{EditorConfig, TutorialProgress};
+use crate::editorinterface::EditorState;
+use crate::editorinterface::editoractions::show_toast;
+use crate::editortheme::Palette;
+use crate::projectstate::ProjectState;
+
+pub struct EmbeddedTutorial {
+    pub id: &'static str,
+    pub steps: &'static str,
+    pub main_script: &'static str,
+}
+
+pub const TUTORIALS: &[EmbeddedTutorial] = &[EmbeddedTutorial {
+    id: "basics",
+    steps: include_str!("../../../assets/tutorials/basics/tutorial.toml"),
+    main_script: include_str!("../../../assets/tutorials/basics/game.luau"),
+}];
+
+const TUTORIAL_PROJECT_NAME: &str = "Vectarine Tutorial";
+
+#[derive(Debug, Deserialize)]
+pub struct Tutorial {
+    pub title: String,
+    pub steps: Vec<TutorialStep>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TutorialStep {
+    /// The part of the editor to outline, see `target_window`.
+    #[serde(default)]
+    pub highlight: Option<String>,
+    pub text: String,
+    pub check: TutorialCheck,
+}
+
+/// What completes a step.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TutorialCheck {
+    /// The user clicks Next.
+    Next,
+    WindowShown {
+        target: String,
+    },
+    /// The Lua global has another value than when the step started, like after the script was edited.
+    GlobalChanged {
+        name: String,
+    },
+    /// The global is in the watcher.
+    Watched {
+        name: String,
+    },
+    /// The whole project was reloaded since the step started. Scripts reloaded as they are saved do not count.
+    Reloaded,
+}
+
+impl Tutorial {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tutorial = toml::from_str::<Tutorial>(source).map_err(|error| error.to_string())?;
+        let mut config = EditorConfig::default();
+        for (index, step) in tutorial.steps.iter().enumerate() {
+            let targets = step.highlight.iter().chain(match &step.check {
+                TutorialCheck::WindowShown { target } => Some(target),
+                _ => None,
+            });
+            for target in targets {
+                if target_window(&mut config, target).is_none() {
+                    return Err(format!(
+                        "Step {} uses the target '{target}', which does not exist",
+                        index + 1
+                    ));
+                }
+            }
+        }
+        Ok(tutorial)
+    }
+}
+
+/// The title of the window of a target, and whether it is shown.
+fn target_window<'a>(
+    config: &'a mut EditorConfig,
+    target: &str,
+) -> Option<(&'static str, &'a mut bool)> {
+    match target {
+        "console" => Some(("Console", &mut config.is_console_shown)),
+        "watcher" => Some(("Watcher", &mut config.is_watcher_window_shown)),
+        "resources" => Some(("Resources", &mut config.is_resources_window_shown)),
+        "profiler" => Some(("Profiler", &mut config.is_profiler_window_shown)),
+        _ => None,
+    }
+}
+
+/// What the checks compare with, taken when the step starts. It is not saved, so a step resumed after a restart of the
+/// editor starts over.
+struct StepStart {
+    tutorial_id: String,
+    step: usize,
+    project_reload_count: u32,
+    global_hash: Option<u64>,
+}
+
+thread_local! {
+    static PARSED_TUTORIALS: RefCell<Vec<(&'static str, Rc<Tutorial>)>> = const { RefCell::new(Vec::new()) };
+    static STEP_START: RefCell<Option<StepStart>> = const { RefCell::new(None) };
+}
+
+fn get_tutorial(id: &str) -> Option<Rc<Tutorial>> {
+    PARSED_TUTORIALS.with_borrow_mut(|parsed| {
+        if let Some((_, tutorial)) = parsed.iter().find(|(parsed_id, _)| *parsed_id == id) {
+            return Some(tutorial.clone());
+        }
+        let embedded = TUTORIALS.iter().find(|tutorial| tutorial.id == id)?;
+        match Tutorial::parse(embedded.steps) {
+            Ok(tutorial) => {
+                let tutorial = Rc::new(tutorial);
+                parsed.push((embedded.id, tutorial.clone()));
+                Some(tutorial)
+            }
+            Err(error) => {
+                print_err(format!("The tutorial '{id}' is invalid: {error}"));
+                None
+            }
+        }
+    })
+}
+
+/// Generates the project of the tutorial in a new folder of `parent_folder`, opens it and starts the first step.
+pub fn start_tutorial(editor: &mut EditorState, tutorial: &EmbeddedTutorial, parent_folder: &Path) {
+    let game_name = (1..)
+        .map(|number| match number {
+            1 => TUTORIAL_PROJECT_NAME.to_string(),
+            number => format!("{TUTORIAL_PROJECT_NAME} {number}"),
+        })
+        .find(|name| !parent_folder.join(name).exists())
+        .unwrap_or_default();
+    let project_path =
+        match create_game_with_script(&game_name, parent_folder, tutorial.main_script) {
+            Ok(project_path) => project_path,
+            Err(error) => {
+                print_err(format!("Unable to create the tutorial project: {error}"));
+                return;
+            }
+        };
+    editor.load_project(Box::new(LocalFileSystem), &project_path, |result| {
+        if let Err(error) = result {
+            print_err(format!("Unable to open the tutorial project: {error}"));
+        }
+    });
+    editor.config.borrow_mut().tutorial_progress = Some(TutorialProgress {
+        tutorial_id: tutorial.id.to_string(),
+        project_path: project_path.to_string_lossy().to_string(),
+        step: 0,
+    });
+    editor.save_config();
+}
+
+enum TutorialAction {
+    Show(String),
+    Advance,
+    Quit,
+}
+
+/// The step of the tutorial in progress, over the editor, while its project is opened.
+pub fn draw_tutorial(editor: &mut EditorState, ui: &mut egui::Ui) {
+    let Some(progress) = editor.config.borrow().tutorial_progress.clone() else {
+        return;
+    };
+    let is_tutorial_project = editor
+        .project
+        .borrow()
+        .as_ref()
+        .is_some_and(|project| project.project_path == PathBuf::from(&progress.project_path));
+    if !is_tutorial_project {
+        return;
+    }
+    let Some(tutorial) = get_tutorial(&progress.tutorial_id) else {
+        editor.config.borrow_mut().tutorial_progress = None;
+        editor.save_config();
+        return;
+    };
+    let Some(step) = tutorial.steps.get(progress.step) else {
+        finish_tutorial(editor);
+        return;
+    };
+
+    let is_step_done = {
+        let mut config = editor.config.borrow_mut();
+        if let Some(target) = &step.highlight {
+            highlight_target(ui, &mut config, target);
+        }
+        let project = editor.project.borrow();
+        project
+            .as_ref()
+            .is_some_and(|project| is_step_done(&mut config, project, &progress, step))
+    };
+
+    let mut action = is_step_done.then_some(TutorialAction::Advance);
+    egui::Window::new("Tutorial")
+        .anchor(egui::Align2::RIGHT_BOTTOM, [-16.0, -16.0])
+        .default_width(320.0)
+        .collapsible(false)
+        .resizable(false)
+        .show(ui, |ui| {
+            let muted_text = Palette::of(ui).muted_text;
+            ui.label(RichText::new(&tutorial.title).strong());
+            ui.label(
+                RichText::new(format!(
+                    "Step {} of {}",
+                    progress.step + 1,
+                    tutorial.steps.len()
+                ))
+                .color(muted_text),
+            );
+            ui.add_space(4.0);
+            ui.label(&step.text);
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                let is_last_step = progress.step + 1 == tutorial.steps.len();
+                if step.check == TutorialCheck::Next {
+                    if ui
+                        .button(if is_last_step { "Finish" } else { "Next" })
+                        .clicked()
+                    {
+                        action = Some(TutorialAction::Advance);
+                    }
+                } else if ui
+                    .small_button("Skip")
+                    .on_hover_text("Go to the next step without doing this one")
+                    .clicked()
+                {
+                    action = Some(TutorialAction::Advance);
+                }
+                if let Some(target) = &step.highlight
+                    && ui.small_button("Show me").clicked()
+                {
+                    action = Some(TutorialAction::Show(target.clone()));
+                }
+                if ui.small_button("Quit tutorial").clicked() {
+                    action = Some(TutorialAction::Quit);
+                }
+            });
+        });
+
+    match action {
+        Some(TutorialAction::Show(target)) => {
+            if let Some((_, is_shown)) = target_window(&mut editor.config.borrow_mut(), &target) {
+                *is_shown = true;
+            }
+        }
+        Some(TutorialAction::Advance) => {
+            STEP_START.set(None);
+            if let Some(progress) = editor.config.borrow_mut().tutorial_progress.as_mut() {
+                progress.step += 1;
+            }
+            editor.save_config();
+        }
+        Some(TutorialAction::Quit) => {
+            STEP_START.set(None);
+            editor.config.borrow_mut().tutorial_progress = None;
+            editor.save_config();
+        }
+        None => {}
+    }
+}
+
+fn finish_tutorial(editor: &mut EditorState) {
+    STEP_START.set(None);
+    editor.config.borrow_mut().tutorial_progress = None;
+    editor.save_config();
+    show_toast("Tutorial completed".to_string());
+}
+
+/// Outlines the window of the target when it is shown.
+fn highlight_target(ui: &egui::Ui, config: &mut EditorConfig, target: &str) {
+    let Some((title, is_shown)) = target_window(config, target) else {
+        return;
+    };
+    if !*is_shown {
+        return;
+    }
+    let Some(rect) = ui
+        .ctx()
+        .memory(|memory| memory.area_rect(egui::Id::new(title)))
+    else {
+        return;
+    };
+    // Pulses slowly, to draw the eye without being distracting.
+    let time = ui.input(|input| input.time);
+    let alpha = 0.6 + 0.4 * (time * 3.0).sin() as f32;
+    let color = ui.visuals().selection.stroke.color.gamma_multiply(alpha);
+    ui.ctx()
+        .layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("tutorial_highlight"),
+        ))
+        .rect_stroke(
+            rect.expand(4.0),
+            6.0,
+            egui::Stroke::new(3.0, color),
+            egui::StrokeKind::Outside,
+        );
+    ui.ctx().request_repaint();
+}
+
+fn is_step_done(
+    config: &mut EditorConfig,
+    project: &ProjectState,
+    progress: &TutorialProgress,
+    step: &TutorialStep,
+) -> bool {
+    let global_hash = |name: &str| {
+        let value = project
+            .game
+            .lua_env
+            .lua_handle
+            .lua
+            .globals()
+            .get::<mlua::Value>(name)
+            .ok()
+            .filter(|value| !value.is_nil())?;
+        hash_lua_value(&value).ok()
+    };
+    let project_reload_count = project.session_stats.project_reload_count();
+
+    STEP_START.with_borrow_mut(|start| {
+        let is_current_step = start.as_ref().is_some_and(|start| {
+            start.tutorial_id == progress.tutorial_id && start.step == progress.step
+        });
+        if !is_current_step {
+            *start = Some(StepStart {
+                tutorial_id: progress.tutorial_id.clone(),
+                step: progress.step,
+                project_reload_count,
+                global_hash: None,
+            });
+        }
+        let Some(start) = start.as_mut() else {
+            return false;
+        };
+        match &step.check {
+            TutorialCheck::Next => false,
+            TutorialCheck::WindowShown { target } => {
+                target_window(config, target).is_some_and(|(_, is_shown)| *is_shown)
+            }
+            TutorialCheck::GlobalChanged { name } => {
+                let Some(hash) = global_hash(name) else {
+                    return false;
+                };
+                // The global may not exist yet when the step starts, like when the script has an error.
+                let start_hash = *start.global_hash.get_or_insert(hash);
+                hash != start_hash
+            }
+            TutorialCheck::Watched { name } => config
+                .project_debug_state(Some(&project.project_path))
+                .watched_variables
+                .contains(name),
+            TutorialCheck::Reloaded => project_reload_count > start.project_reload_count,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TUTORIALS, Tutorial, TutorialCheck};
+
+    #[test]
+    fn the_embedded_tutorials_are_valid() {
+        for embedded in TUTORIALS {
+            let tutorial = Tutorial::parse(embedded.steps).expect("valid tutorial");
+            assert!(!tutorial.steps.is_empty());
+        }
+        let error = Tutorial::parse(
+            r#"
+            title = "Broken"
+            [[steps]]
+            text = "Open the inventory"
+            check = { kind = "window_shown", target = "inventory" }
+            "#,
+        )
+        .expect_err("unknown target");
+        assert_eq!(
+            error,
+            "Step 1 uses the target 'inventory', which does not exist"
+        );
+        let tutorial = Tutorial::parse(
+            r#"
+            title = "Reload"
+            [[steps]]
+            text = "Press Ctrl+R"
+            check = { kind = "reloaded" }
+            "#,
+        )
+        .expect("valid tutorial");
+        assert_eq!(tutorial.steps[0].check, TutorialCheck::Reloaded);
+    }
+}
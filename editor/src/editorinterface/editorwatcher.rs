@@ -1,19 +1,40 @@
-use std::{cell::RefCell, thread::LocalKey};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
 
 use egui_extras::{Size, StripBuilder};
+use runtime::console::{print_info, print_warn};
 use runtime::egui;
 use runtime::egui::RichText;
+use runtime::expression::{evaluate_expression, is_global_name};
 use runtime::{
+    lua_env::lua_canvas::RcFramebuffer,
     lua_env::lua_physics::Object2,
     lua_env::{lua_vec2::Vec2, lua_vec4::Vec4, stringify_lua_value},
     mlua,
 };
 
 use crate::editorinterface::EditorState;
+use crate::editortheme::Palette;
+use applytosource::{AppliedEdit, apply_to_source, revert_applied_edit};
+use edithistory::{EditHistory, WatchedValue, resolve_path};
+use vectarine_cli::project::geteditorpaths::get_end_of_path;
+
+pub(crate) mod applytosource;
+mod edithistory;
+mod sourceliteral;
 
 const MAX_WATCHED_VARIABLES: usize = 20;
 const MAX_TABLE_INSPECTION_DEPTH: usize = 2;
 
+thread_local! {
+    static EDIT_HISTORY: RefCell<EditHistory> = RefCell::new(EditHistory::default());
+    /// The values written in the scripts during the session, the most recent last.
+    static APPLIED_EDITS: RefCell<Vec<AppliedEdit>> = const { RefCell::new(Vec::new()) };
+    /// Why the edited variables could not be written in the scripts, until they are edited again.
+    static APPLY_FAILURES: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
 pub fn draw_editor_watcher(editor: &mut EditorState, ui: &mut egui::Ui) {
     let mut is_shown = editor.config.borrow().is_watcher_window_shown;
 
@@ -36,27 +57,39 @@ pub fn draw_editor_watcher(editor: &mut EditorState, ui: &mut egui::Ui) {
 
 fn draw_editor_watcher_window(ui: &mut egui::Ui, editor: &mut EditorState) {
     let mut project = editor.project.borrow_mut();
-    let game = match project.as_mut() {
-        Some(proj) => Some(&mut proj.game),
-        None => None,
-    };
-
-    let Some(game) = game else {
+    let Some(project) = project.as_mut() else {
         ui.label("No project loaded");
         return;
     };
-
+    // Gallery projects are read-only, their scripts are not changed.
+    let scripts_folder = project
+        .project_path
+        .parent()
+        .filter(|_| !project.is_read_only)
+        .map(Path::to_path_buf);
+    let scripts_folder = scripts_folder.as_deref();
+    let game = &mut project.game;
     let globals = game.lua_env.lua_handle.lua.globals();
 
     thread_local! {
         static SEARCH_BOX_CONTENT: RefCell<String> = const { RefCell::new(String::new()) };
-        static WATCHED_VARIABLES_NAMES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
     }
 
-    let watched_vars_len = WATCHED_VARIABLES_NAMES.with_borrow(|vars| vars.len());
+    // The names are kept with the project, so that they are still watched after a reload.
+    let mut debug_state = editor
+        .config
+        .borrow()
+        .project_debug_state(Some(&project.project_path));
+    let watched_vars = &mut debug_state.watched_variables;
+    let previous_watched_vars = watched_vars.clone();
+
+    draw_edit_summary(ui, &game.lua_env.lua_handle.lua, &globals);
+    draw_applied_edits(ui, scripts_folder);
+
+    let watched_vars_len = watched_vars.len();
     if watched_vars_len < MAX_WATCHED_VARIABLES {
         SEARCH_BOX_CONTENT.with_borrow_mut(|content| {
-            draw_search_variable_box(ui, content, &globals, &WATCHED_VARIABLES_NAMES);
+            draw_search_variable_box(ui, content, &globals, watched_vars);
         });
     } else {
         ui.label(
@@ -77,27 +110,30 @@ fn draw_editor_watcher_window(ui: &mut egui::Ui, editor: &mut EditorState) {
                     .max_height(800.0)
                     .auto_shrink([false, true])
                     .show(ui, |ui| {
-                        WATCHED_VARIABLES_NAMES.with_borrow_mut(|vars| {
-                            for idx in 0..vars.len() {
-                                draw_watched_variable(
-                                    ui,
-                                    &game.lua_env.lua_handle.lua,
-                                    &globals,
-                                    vars,
-                                    idx,
-                                );
-                            }
-                        });
+                        for idx in 0..watched_vars.len() {
+                            draw_watched_variable(
+                                ui,
+                                &game.lua_env.lua_handle.lua,
+                                &globals,
+                                watched_vars,
+                                idx,
+                                scripts_folder,
+                            );
+                        }
                     });
             });
         });
+
+    if *watched_vars != previous_watched_vars {
+        editor.set_project_debug_state(Some(&project.project_path), debug_state);
+    }
 }
 
 fn draw_search_variable_box(
     ui: &mut egui::Ui,
     content: &mut String,
     globals: &mlua::Table,
-    watched_variable_names: &'static LocalKey<RefCell<Vec<String>>>,
+    watched_variable_names: &mut Vec<String>,
 ) {
     let search_results = globals
         .pairs::<mlua::Value, mlua::Value>()
@@ -117,22 +153,39 @@ fn draw_search_variable_box(
         .show(ui)
         .response;
 
+    // Anything that is not the name of a global is watched as an expression, like `#Enemies.list`.
+    let expression = content.trim();
+    let is_expression = !expression.is_empty() && !is_global_name(expression);
+
     if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+        let key = if is_expression {
+            Some(expression.to_string())
+        } else {
+            search_results.first().map(stringify_lua_value)
+        };
+        if let Some(key) = key
+            && !watched_variable_names.contains(&key)
+        {
+            watched_variable_names.push(key);
+        }
         // Clear search box on enter
         content.clear();
-        watched_variable_names.with_borrow_mut(|vars| {
-            let Some(first_key) = search_results.first() else {
-                return;
-            };
-            let key = stringify_lua_value(first_key);
-            if !vars.iter().any(|v| v == &key) {
-                vars.push(key);
-            }
-        });
         response.request_focus(); // keep focus on enter
     }
 
-    if !content.is_empty() {
+    if is_expression {
+        ui.horizontal(|ui| {
+            ui.label(format!("Watch the expression {expression}"));
+            if ui
+                .button("+")
+                .on_hover_text("Add to watch list, it is evaluated every frame")
+                .clicked()
+                && !watched_variable_names.iter().any(|v| v == expression)
+            {
+                watched_variable_names.push(expression.to_string());
+            }
+        });
+    } else if !content.is_empty() {
         egui::ScrollArea::vertical()
             .max_height(200.0)
             .auto_shrink([false, true])
@@ -141,12 +194,10 @@ fn draw_search_variable_box(
                     ui.horizontal(|ui| {
                         let key_str = stringify_lua_value(result);
                         ui.label(format!("Watch {}", key_str));
-                        if ui.button("+").on_hover_text("Add to watch list").clicked() {
-                            watched_variable_names.with_borrow_mut(|vars| {
-                                if !vars.contains(&key_str) {
-                                    vars.push(key_str.clone());
-                                }
-                            });
+                        if ui.button("+").on_hover_text("Add to watch list").clicked()
+                            && !watched_variable_names.contains(&key_str)
+                        {
+                            watched_variable_names.push(key_str.clone());
                         }
                     });
                 }
@@ -160,11 +211,16 @@ fn draw_watched_variable(
     globals: &mlua::Table,
     var_keys: &mut Vec<String>,
     idx: usize,
+    scripts_folder: Option<&Path>,
 ) {
     let var = var_keys.get(idx).cloned();
     let Some(var) = var else {
         return; // removed by another watcher
     };
+    if !is_global_name(&var) {
+        draw_watched_expression(ui, lua, var_keys, idx, &var);
+        return;
+    }
     let lua_key = lua.create_string(var.clone());
     let Ok(lua_key) = lua_key else {
         return;
@@ -175,51 +231,327 @@ fn draw_watched_variable(
         return;
     };
     let var_name = &var;
+    if watched_value.is_nil() {
+        // After a reload, the globals only exist once the scripts set them again.
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(format!("{var_name} - not yet defined")).weak())
+                .on_hover_text(
+                    "The scripts did not set this global yet. It is shown as soon as they do.",
+                );
+            if ui
+                .small_button("Remove")
+                .on_hover_text("Remove from watch list")
+                .clicked()
+            {
+                var_keys.remove(idx);
+            }
+        });
+        return;
+    }
     let var_type = watched_value.type_name();
+    let header = edited_label(ui, format!("{} - {}", var_name, var_type), var_name);
 
-    egui::CollapsingHeader::new(format!("{} - {}", var_name, var_type)).show(ui, |ui| {
-        ui.button("Remove")
-            .on_hover_text("Remove from watch list")
-            .clicked()
-            .then(|| {
-                var_keys.remove(idx);
+    egui::CollapsingHeader::new(header)
+        .id_salt(var_name)
+        .show(ui, |ui| {
+            ui.button("Remove")
+                .on_hover_text("Remove from watch list")
+                .clicked()
+                .then(|| {
+                    var_keys.remove(idx);
+                });
+            draw_any_watcher(
+                ui,
+                globals,
+                &lua_key,
+                &watched_value,
+                var_name,
+                MAX_TABLE_INSPECTION_DEPTH,
+                scripts_folder,
+            );
+        });
+}
+
+/// Expressions are evaluated every frame in a read-only environment, so their value cannot be edited.
+/// Their errors are shown under them instead of in the console, which they would flood.
+fn draw_watched_expression(
+    ui: &mut egui::Ui,
+    lua: &mlua::Lua,
+    var_keys: &mut Vec<String>,
+    idx: usize,
+    expression: &str,
+) {
+    let result = evaluate_expression(lua, expression);
+    let type_name = match &result {
+        Ok(value) => value.type_name(),
+        Err(_) => "error",
+    };
+    egui::CollapsingHeader::new(format!("{expression} - {type_name}"))
+        .id_salt(expression)
+        .default_open(true)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                match &result {
+                    Ok(value) => {
+                        ui.label(RichText::new(stringify_lua_value(value)).monospace());
+                    }
+                    Err(error) => {
+                        // The first line is the message, the others are the traceback of the evaluator.
+                        let error = error.to_string();
+                        let message = error.lines().next().unwrap_or_default();
+                        ui.label(
+                            RichText::new(message)
+                                .color(Palette::of(ui).error)
+                                .monospace(),
+                        );
+                    }
+                }
+                if ui
+                    .small_button("Remove")
+                    .on_hover_text("Remove from watch list")
+                    .clicked()
+                {
+                    var_keys.remove(idx);
+                }
             });
-        draw_any_watcher(
-            ui,
-            globals,
-            &lua_key,
-            &watched_value,
-            MAX_TABLE_INSPECTION_DEPTH,
+        });
+}
+
+/// The edits are only reverted when asked: the running game has diverged from the scripts until then.
+fn draw_edit_summary(ui: &mut egui::Ui, lua: &mlua::Lua, globals: &mlua::Table) {
+    if EDIT_HISTORY.with_borrow(|history| history.is_empty()) {
+        return;
+    }
+    let edit_count = EDIT_HISTORY.with_borrow(|history| history.len());
+    ui.horizontal(|ui| {
+        ui.label(
+            RichText::new(format!(
+                "✏ {edit_count} edited variable(s), the game differs from the scripts"
+            ))
+            .color(Palette::of(ui).warning),
         );
+        if ui
+            .button("Revert all edits")
+            .on_hover_text("Set all edited variables back to their value before the first edit")
+            .clicked()
+        {
+            let originals = EDIT_HISTORY.with_borrow_mut(|history| history.take_originals());
+            for (path, original) in originals {
+                if let Some((parent, key)) = resolve_path(lua, globals, &path) {
+                    let _ = original.write_to(&parent, &key);
+                }
+            }
+        }
+    });
+}
+
+/// Marks the label when the variable or one of its fields was edited in the watcher.
+fn edited_label(ui: &egui::Ui, text: String, path: &str) -> RichText {
+    if EDIT_HISTORY.with_borrow(|history| history.has_edits_under(path)) {
+        RichText::new(format!("✏ {text}")).color(Palette::of(ui).warning)
+    } else {
+        RichText::new(text)
+    }
+}
+
+fn record_edit(path: &str, before: WatchedValue, after: WatchedValue) {
+    if before != after {
+        EDIT_HISTORY.with_borrow_mut(|history| history.record_edit(path, before, after));
+        APPLY_FAILURES.with_borrow_mut(|failures| failures.remove(path));
+    }
+}
+
+/// Lists the values written in the scripts during the session, so that they can be reverted one by one.
+fn draw_applied_edits(ui: &mut egui::Ui, scripts_folder: Option<&Path>) {
+    APPLIED_EDITS.with_borrow_mut(|edits| {
+        if edits.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new(format!("Applied to the scripts ({})", edits.len()))
+            .id_salt("applied edits")
+            .show(ui, |ui| {
+                for edit in edits.iter_mut().rev() {
+                    ui.horizontal(|ui| {
+                        let location = format!("{}:{}", get_end_of_path(&edit.script), edit.line);
+                        if edit.is_reverted {
+                            ui.label(RichText::new(edit.describe()).strikethrough().weak())
+                                .on_hover_text(format!("Reverted in {location}"));
+                            return;
+                        }
+                        ui.label(edit.describe()).on_hover_text(location);
+                        if let Some(scripts_folder) = scripts_folder
+                            && ui
+                                .small_button("↺")
+                                .on_hover_text("Write the previous value back in the script")
+                                .clicked()
+                        {
+                            match revert_applied_edit(scripts_folder, edit) {
+                                Ok(()) => edit.is_reverted = true,
+                                Err(reason) => {
+                                    print_warn(format!("Cannot revert {}: {reason}", edit.path))
+                                }
+                            }
+                        }
+                    });
+                }
+            });
     });
 }
 
+/// Writes the edited value in the script that sets it, so that the value is kept after a reload.
+fn draw_apply_to_source(
+    ui: &mut egui::Ui,
+    scripts_folder: Option<&Path>,
+    path: &str,
+    value: WatchedValue,
+) {
+    if let Some(reason) = APPLY_FAILURES.with_borrow(|failures| failures.get(path).cloned()) {
+        ui.label(RichText::new("Cannot apply automatically").color(Palette::of(ui).muted_text))
+            .on_hover_text(reason);
+        return;
+    }
+    let Some(scripts_folder) = scripts_folder else {
+        ui.add_enabled(false, egui::Button::new("Apply to source").small())
+            .on_disabled_hover_text("The scripts of gallery projects cannot be changed");
+        return;
+    };
+    if ui
+        .small_button("Apply to source")
+        .on_hover_text(format!("Write {value} in the script that sets {path}"))
+        .clicked()
+    {
+        match apply_to_source(scripts_folder, path, value) {
+            Ok(edit) => {
+                print_info(format!(
+                    "Applied {} in {}:{}",
+                    edit.describe(),
+                    get_end_of_path(&edit.script),
+                    edit.line
+                ));
+                // The scripts now set the edited value, the game will match them after the reload.
+                EDIT_HISTORY.with_borrow_mut(|history| history.forget(path));
+                APPLIED_EDITS.with_borrow_mut(|edits| edits.push(edit));
+            }
+            Err(reason) => {
+                APPLY_FAILURES
+                    .with_borrow_mut(|failures| failures.insert(path.to_string(), reason));
+            }
+        }
+    }
+}
+
+/// Shows whether the value was edited, with its history, and the buttons to revert or re-apply the edits.
+fn draw_edit_state(
+    ui: &mut egui::Ui,
+    variable_parent: &mlua::Table,
+    value_global_name: &mlua::Value,
+    path: &str,
+    scripts_folder: Option<&Path>,
+) {
+    let Some((original, latest, history_description)) = EDIT_HISTORY.with_borrow(|history| {
+        history
+            .get(path)
+            .map(|record| (record.original, record.latest(), record.describe_history()))
+    }) else {
+        return;
+    };
+    // The value is read again, it may have been edited just before.
+    let current = variable_parent
+        .raw_get::<mlua::Value>(value_global_name)
+        .ok()
+        .and_then(|value| WatchedValue::from_lua(&value));
+    if current == Some(latest) {
+        ui.label(RichText::new("✏").color(Palette::of(ui).warning))
+            .on_hover_text(history_description);
+        if ui
+            .small_button("↺")
+            .on_hover_text(format!("Revert to the original value: {original}"))
+            .clicked()
+        {
+            let _ = original.write_to(variable_parent, value_global_name);
+            EDIT_HISTORY.with_borrow_mut(|history| history.forget(path));
+            return;
+        }
+        draw_apply_to_source(ui, scripts_folder, path, latest);
+    } else {
+        // The value was replaced since the edit, by the game or by a reload of the scripts.
+        ui.label(RichText::new("⚠").color(Palette::of(ui).warning))
+            .on_hover_text(format!(
+                "The value was replaced since it was edited.\n{history_description}"
+            ));
+        if ui
+            .small_button("Re-apply")
+            .on_hover_text(format!("Set the value back to {latest}"))
+            .clicked()
+        {
+            let _ = latest.write_to(variable_parent, value_global_name);
+        }
+        if ui
+            .small_button("Forget")
+            .on_hover_text("Keep the current value and forget the edits")
+            .clicked()
+        {
+            EDIT_HISTORY.with_borrow_mut(|history| history.forget(path));
+        }
+    }
+}
+
 fn draw_any_watcher(
     ui: &mut egui::Ui,
     variable_parent: &mlua::Table,
     value_global_name: &mlua::Value,
     watched_value: &mlua::Value,
+    path: &str,
     max_depth: usize,
+    scripts_folder: Option<&Path>,
 ) {
     if let mlua::Value::Table(table) = watched_value {
-        draw_table_watcher(ui, table, max_depth);
+        draw_table_watcher(ui, table, path, max_depth, scripts_folder);
         return;
     }
+    ui.horizontal(|ui| {
+        draw_value_watcher(ui, variable_parent, value_global_name, watched_value, path);
+        draw_edit_state(ui, variable_parent, value_global_name, path, scripts_folder);
+    });
+}
+
+fn draw_value_watcher(
+    ui: &mut egui::Ui,
+    variable_parent: &mlua::Table,
+    value_global_name: &mlua::Value,
+    watched_value: &mlua::Value,
+    path: &str,
+) {
     if let mlua::Value::Boolean(b) = watched_value {
         draw_boolean_watcher(ui, *b, |new_val| {
             let _ = variable_parent.raw_set(value_global_name, new_val);
+            record_edit(
+                path,
+                WatchedValue::Boolean(*b),
+                WatchedValue::Boolean(new_val),
+            );
         });
         return;
     }
     if let mlua::Value::Integer(n) = watched_value {
         draw_number_watcher(ui, *n as f64, |new_val| {
             let _ = variable_parent.raw_set(value_global_name, new_val);
+            record_edit(
+                path,
+                WatchedValue::Number(*n as f64),
+                WatchedValue::Number(new_val),
+            );
         });
         return;
     }
     if let mlua::Value::Number(n) = watched_value {
         draw_number_watcher(ui, *n, |new_val| {
             let _ = variable_parent.raw_set(value_global_name, new_val);
+            record_edit(
+                path,
+                WatchedValue::Number(*n),
+                WatchedValue::Number(new_val),
+            );
         });
         return;
     }
@@ -229,13 +561,19 @@ fn draw_any_watcher(
     if let mlua::Value::UserData(ud) = watched_value {
         let maybe_vec = ud.borrow_mut::<Vec2>();
         if let Ok(mut vec) = maybe_vec {
-            draw_vec2_watcher(ui, &mut vec);
+            let before = *vec;
+            if draw_vec2_watcher(ui, &mut vec) {
+                record_edit(path, WatchedValue::Vec2(before), WatchedValue::Vec2(*vec));
+            }
             return;
         }
         let maybe_vec = ud.borrow_mut::<Vec4>();
         if let Ok(mut vec) = maybe_vec {
             let var_name = stringify_lua_value(value_global_name);
-            draw_vec4_watcher(ui, &mut vec, var_name.contains("color"));
+            let before = *vec;
+            if draw_vec4_watcher(ui, &mut vec, var_name.contains("color")) {
+                record_edit(path, WatchedValue::Vec4(before), WatchedValue::Vec4(*vec));
+            }
             return;
         }
         let maybe_object = ud.borrow_mut::<Object2>();
@@ -243,6 +581,19 @@ fn draw_any_watcher(
             draw_object_watcher(ui, &mut object);
             return;
         }
+        if let Ok(canvas) = ud.borrow::<RcFramebuffer>() {
+            let framebuffer = canvas.gl();
+            let samples = match framebuffer.sample_count() {
+                1 => "not multisampled".to_string(),
+                samples => format!("multisampled, {samples} samples per pixel"),
+            };
+            ui.label(format!(
+                "Canvas {}x{}, {samples}",
+                framebuffer.width(),
+                framebuffer.height()
+            ));
+            return;
+        }
     }
 
     ui.label(format!(
@@ -251,24 +602,48 @@ fn draw_any_watcher(
     ));
 }
 
-fn draw_table_watcher(ui: &mut egui::Ui, table: &mlua::Table, max_depth: usize) {
+fn draw_table_watcher(
+    ui: &mut egui::Ui,
+    table: &mlua::Table,
+    path: &str,
+    max_depth: usize,
+    scripts_folder: Option<&Path>,
+) {
     let pairs = table.pairs::<mlua::Value, mlua::Value>();
     for pair in pairs.flatten() {
         let (key, value) = pair;
+        let key_name = stringify_lua_value(&key);
+        let field_path = format!("{path}.{key_name}");
         ui.horizontal(|ui| {
+            let label = edited_label(ui, format!("{key_name}:"), &field_path);
             if max_depth == 0 {
-                ui.label(format!("{}:", stringify_lua_value(&key)));
+                ui.label(label);
                 ui.label("...");
             } else if let mlua::Value::Table(_) = value {
-                egui::CollapsingHeader::new(format!("{}:", stringify_lua_value(&key))).show(
+                egui::CollapsingHeader::new(label)
+                    .id_salt(&field_path)
+                    .show(ui, |ui| {
+                        draw_any_watcher(
+                            ui,
+                            table,
+                            &key,
+                            &value,
+                            &field_path,
+                            max_depth - 1,
+                            scripts_folder,
+                        );
+                    });
+            } else {
+                ui.label(label);
+                draw_any_watcher(
                     ui,
-                    |ui| {
-                        draw_any_watcher(ui, table, &key, &value, max_depth - 1);
-                    },
+                    table,
+                    &key,
+                    &value,
+                    &field_path,
+                    max_depth - 1,
+                    scripts_folder,
                 );
-            } else {
-                ui.label(format!("{}:", stringify_lua_value(&key)));
-                draw_any_watcher(ui, table, &key, &value, max_depth - 1);
             }
         });
     }
@@ -298,8 +673,10 @@ where
     });
 }
 
-fn draw_vec2_watcher(ui: &mut egui::Ui, vec: &mut Vec2) {
+/// Returns true when the vector was changed.
+fn draw_vec2_watcher(ui: &mut egui::Ui, vec: &mut Vec2) -> bool {
     ui.horizontal(|ui| {
+        let mut changed = false;
         let mut x = vec.0[0];
         let mut y = vec.0[1];
         if ui
@@ -307,51 +684,40 @@ fn draw_vec2_watcher(ui: &mut egui::Ui, vec: &mut Vec2) {
             .changed()
         {
             vec.0[0] = x;
+            changed = true;
         }
         if ui
             .add(egui::DragValue::new(&mut y).prefix("y: ").speed(0.1))
             .changed()
         {
             vec.0[1] = y;
+            changed = true;
         }
-    });
+        changed
+    })
+    .inner
 }
 
-fn draw_vec4_watcher(ui: &mut egui::Ui, vec: &mut Vec4, is_color: bool) {
+/// Returns true when the vector was changed.
+fn draw_vec4_watcher(ui: &mut egui::Ui, vec: &mut Vec4, is_color: bool) -> bool {
     ui.horizontal(|ui| {
-        let mut x = vec.0[0];
-        let mut y = vec.0[1];
-        let mut z = vec.0[2];
-        let mut w = vec.0[3];
         if is_color {
-            ui.color_edit_button_rgba_unmultiplied(&mut vec.0);
-        } else {
-            if ui
-                .add(egui::DragValue::new(&mut x).prefix("x: ").speed(0.1))
-                .changed()
-            {
-                vec.0[0] = x;
-            }
-            if ui
-                .add(egui::DragValue::new(&mut y).prefix("y: ").speed(0.1))
-                .changed()
-            {
-                vec.0[1] = y;
-            }
-            if ui
-                .add(egui::DragValue::new(&mut z).prefix("z: ").speed(0.1))
-                .changed()
-            {
-                vec.0[2] = z;
-            }
+            return ui.color_edit_button_rgba_unmultiplied(&mut vec.0).changed();
+        }
+        let mut changed = false;
+        for (component, prefix) in vec.0.iter_mut().zip(["x: ", "y: ", "z: ", "w: "]) {
+            let mut value = *component;
             if ui
-                .add(egui::DragValue::new(&mut w).prefix("w: ").speed(0.1))
+                .add(egui::DragValue::new(&mut value).prefix(prefix).speed(0.1))
                 .changed()
             {
-                vec.0[3] = w;
+                *component = value;
+                changed = true;
             }
         }
-    });
+        changed
+    })
+    .inner
 }
 
 fn draw_object_watcher(ui: &mut egui::Ui, object: &mut Object2) {
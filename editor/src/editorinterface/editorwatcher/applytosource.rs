@@ -0,0 +1,191 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::edithistory::WatchedValue;
+use super::sourceliteral::{LiteralKind, SourceLiteral, locate_literal, parse_number};
+
+/// The definitions of the API copied in the projects are not scripts of the game.
+const API_FOLDER_NAME: &str = "luau-api";
+
+/// A value of the watcher written in a script.
+pub struct AppliedEdit {
+    pub path: String,
+    pub script: PathBuf,
+    pub line: usize,
+    /// The texts of the literal, or of the arguments of the call, before and after the edit.
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub is_reverted: bool,
+}
+
+impl AppliedEdit {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}: {} → {}",
+            self.path,
+            self.before.join(", "),
+            self.after.join(", ")
+        )
+    }
+}
+
+pub(crate) fn collect_scripts(folder: &Path, scripts: &mut Vec<(PathBuf, String)>) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') || name == API_FOLDER_NAME {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            collect_scripts(&path, scripts);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "luau" || extension == "lua")
+            && let Ok(source) = fs::read_to_string(&path)
+        {
+            scripts.push((path, source));
+        }
+    }
+}
+
+/// Keeps the text of the script when it already has the value, so that `0x10` is not rewritten as `16`.
+fn number_text(current: &str, value: f64) -> Result<String, String> {
+    if !value.is_finite() {
+        return Err(format!("{value} cannot be written in a script"));
+    }
+    Ok(match parse_number(current) {
+        Some(current_value) if current_value == value => current.to_string(),
+        _ => value.to_string(),
+    })
+}
+
+fn component_texts(current: &[&str], components: &[f32]) -> Result<Vec<String>, String> {
+    if current.len() != components.len() {
+        return Err(format!(
+            "the call in the script has {} arguments, not {}",
+            current.len(),
+            components.len()
+        ));
+    }
+    current
+        .iter()
+        .zip(components)
+        .map(|(current, component)| {
+            if !component.is_finite() {
+                return Err(format!("{component} cannot be written in a script"));
+            }
+            // The components are f32, written without the digits added by a conversion to f64.
+            Ok(match parse_number(current) {
+                Some(current_value) if current_value as f32 == *component => current.to_string(),
+                _ => component.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the new texts of the parts of the literal.
+fn literal_texts(
+    value: WatchedValue,
+    literal: &SourceLiteral,
+    current: &[&str],
+) -> Result<Vec<String>, String> {
+    match (value, literal.kind) {
+        (WatchedValue::Boolean(b), LiteralKind::Boolean) => Ok(vec![b.to_string()]),
+        (WatchedValue::Number(n), LiteralKind::Number) => Ok(vec![number_text(current[0], n)?]),
+        (WatchedValue::Vec2(vec), LiteralKind::Call) => component_texts(current, &vec.0),
+        (WatchedValue::Vec4(vec), LiteralKind::Call) => component_texts(current, &vec.0),
+        (_, kind) => Err(format!(
+            "the script sets it to a {}",
+            match kind {
+                LiteralKind::Boolean => "boolean",
+                LiteralKind::Number => "number",
+                LiteralKind::Call => "call",
+            }
+        )),
+    }
+}
+
+/// Writes the value in the script setting the variable at `path`.
+/// The scripts are then reloaded like after any other change.
+pub fn apply_to_source(
+    project_folder: &Path,
+    path: &str,
+    value: WatchedValue,
+) -> Result<AppliedEdit, String> {
+    let mut scripts = Vec::new();
+    collect_scripts(project_folder, &mut scripts);
+    let (script, source, literal) = locate_literal(&scripts, path)?;
+    let before = literal.part_texts(source);
+    let after = literal_texts(value, &literal, &before)?;
+    if before == after {
+        return Err(format!("{} already has this value", script.display()));
+    }
+    fs::write(script, literal.replace_parts(source, &after))
+        .map_err(|error| format!("{} cannot be written: {error}", script.display()))?;
+    Ok(AppliedEdit {
+        path: path.to_string(),
+        script: script.to_path_buf(),
+        line: literal.line,
+        before: before.into_iter().map(str::to_string).collect(),
+        after,
+        is_reverted: false,
+    })
+}
+
+/// Puts the literal back to its text before the edit, if the script still has the text written by the edit.
+pub fn revert_applied_edit(project_folder: &Path, edit: &AppliedEdit) -> Result<(), String> {
+    let mut scripts = Vec::new();
+    collect_scripts(project_folder, &mut scripts);
+    let (script, source, literal) = locate_literal(&scripts, &edit.path)?;
+    if script != edit.script || literal.part_texts(source) != edit.after {
+        return Err(format!(
+            "{} was changed in the scripts since the edit",
+            edit.path
+        ));
+    }
+    fs::write(script, literal.replace_parts(source, &edit.before))
+        .map_err(|error| format!("{} cannot be written: {error}", script.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use runtime::math::Vect;
+
+    use super::{apply_to_source, revert_applied_edit};
+    use crate::editorinterface::editorwatcher::edithistory::WatchedValue;
+
+    #[test]
+    fn applied_edits_can_be_reverted() {
+        let folder = std::env::temp_dir().join(format!("vectarine apply {}", std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(folder.join("luau-api")).expect("the folders are created");
+        let script = folder.join("main.luau");
+        let source = "Count = 0x10\nSize = V2(1.5, 2) -- in meters\n";
+        fs::write(&script, source).expect("the script is written");
+        fs::write(folder.join("luau-api/vec.luau"), "Size = V2(0, 0)").expect("written");
+
+        let size = apply_to_source(&folder, "Size", WatchedValue::Vec2(Vect([0.1, 2.0])))
+            .expect("the literal is found");
+        assert_eq!(size.after, ["0.1", "2"]);
+        let count = apply_to_source(&folder, "Count", WatchedValue::Number(17.0))
+            .expect("the literal is found");
+        assert_eq!(
+            fs::read_to_string(&script).expect("read"),
+            "Count = 17\nSize = V2(0.1, 2) -- in meters\n"
+        );
+        assert!(apply_to_source(&folder, "Count", WatchedValue::Boolean(true)).is_err());
+
+        revert_applied_edit(&folder, &size).expect("the edit is reverted");
+        assert!(revert_applied_edit(&folder, &size).is_err());
+        revert_applied_edit(&folder, &count).expect("the edit is reverted");
+        assert_eq!(fs::read_to_string(&script).expect("read"), source);
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    time::Instant,
+};
+
+use runtime::{
+    lua_env::{lua_vec2::Vec2, lua_vec4::Vec4},
+    mlua,
+};
+
+const MAX_HISTORY_LENGTH: usize = 10;
+
+/// A copy of a value edited in the watcher.
+/// Vectors are copied component by component, the copy does not change with the live value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchedValue {
+    Boolean(bool),
+    Number(f64),
+    Vec2(Vec2),
+    Vec4(Vec4),
+}
+
+impl WatchedValue {
+    pub fn from_lua(value: &mlua::Value) -> Option<Self> {
+        match value {
+            mlua::Value::Boolean(b) => Some(Self::Boolean(*b)),
+            mlua::Value::Integer(n) => Some(Self::Number(*n as f64)),
+            mlua::Value::Number(n) => Some(Self::Number(*n)),
+            mlua::Value::UserData(ud) => {
+                if let Ok(vec) = ud.borrow::<Vec2>() {
+                    Some(Self::Vec2(*vec))
+                } else if let Ok(vec) = ud.borrow::<Vec4>() {
+                    Some(Self::Vec4(*vec))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes the value into `parent[key]`.
+    /// Vectors are modified in place when possible, like the edits made in the watcher.
+    pub fn write_to(&self, parent: &mlua::Table, key: &mlua::Value) -> mlua::Result<()> {
+        let current = parent.raw_get::<mlua::Value>(key)?;
+        match self {
+            Self::Boolean(b) => parent.raw_set(key, *b),
+            Self::Number(n) => parent.raw_set(key, *n),
+            Self::Vec2(vec) => {
+                if let mlua::Value::UserData(ud) = &current
+                    && let Ok(mut current) = ud.borrow_mut::<Vec2>()
+                {
+                    *current = *vec;
+                    return Ok(());
+                }
+                parent.raw_set(key, *vec)
+            }
+            Self::Vec4(vec) => {
+                if let mlua::Value::UserData(ud) = &current
+                    && let Ok(mut current) = ud.borrow_mut::<Vec4>()
+                {
+                    *current = *vec;
+                    return Ok(());
+                }
+                parent.raw_set(key, *vec)
+            }
+        }
+    }
+}
+
+impl fmt::Display for WatchedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boolean(b) => write!(f, "{b}"),
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Vec2(vec) => write!(f, "V2({}, {})", vec.x(), vec.y()),
+            Self::Vec4(vec) => write!(
+                f,
+                "V4({}, {}, {}, {})",
+                vec.0[0], vec.0[1], vec.0[2], vec.0[3]
+            ),
+        }
+    }
+}
+
+pub struct EditRecord {
+    /// The value before the first edit of the session.
+    pub original: WatchedValue,
+    /// The last values set in the watcher, the most recent last.
+    pub history: VecDeque<(Instant, WatchedValue)>,
+}
+
+impl EditRecord {
+    pub fn latest(&self) -> WatchedValue {
+        self.history
+            .back()
+            .map(|(_, value)| *value)
+            .unwrap_or(self.original)
+    }
+
+    pub fn describe_history(&self) -> String {
+        let now = Instant::now();
+        let mut description = format!("Original: {}", self.original);
+        for (time, value) in self.history.iter().rev() {
+            let seconds = now.duration_since(*time).as_secs();
+            let ago = if seconds < 60 {
+                format!("{seconds}s ago")
+            } else {
+                format!("{} min ago", seconds / 60)
+            };
+            description.push_str(&format!("\n{value} ({ago})"));
+        }
+        description
+    }
+}
+
+/// The edits made in the watcher, by path of the edited variable, like `Player.speed`.
+/// The records are kept when scripts are reloaded so that the edits can be applied again.
+#[derive(Default)]
+pub struct EditHistory {
+    records: BTreeMap<String, EditRecord>,
+}
+
+impl EditHistory {
+    pub fn record_edit(&mut self, path: &str, before: WatchedValue, after: WatchedValue) {
+        let record = self
+            .records
+            .entry(path.to_string())
+            .or_insert_with(|| EditRecord {
+                original: before,
+                history: VecDeque::new(),
+            });
+        // Dragging a value produces an edit per frame, they are merged into a single entry.
+        if let Some((time, value)) = record.history.back_mut()
+            && time.elapsed().as_secs_f32() < 1.0
+        {
+            *time = Instant::now();
+            *value = after;
+            return;
+        }
+        record.history.push_back((Instant::now(), after));
+        if record.history.len() > MAX_HISTORY_LENGTH {
+            record.history.pop_front();
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<&EditRecord> {
+        self.records.get(path)
+    }
+
+    pub fn forget(&mut self, path: &str) {
+        self.records.remove(path);
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns true if the variable or one of its fields was edited.
+    pub fn has_edits_under(&self, path: &str) -> bool {
+        self.records
+            .keys()
+            .any(|edited| edited == path || edited.starts_with(&format!("{path}.")))
+    }
+
+    /// Forgets all the records and returns their paths and original values.
+    pub fn take_originals(&mut self) -> Vec<(String, WatchedValue)> {
+        std::mem::take(&mut self.records)
+            .into_iter()
+            .map(|(path, record)| (path, record.original))
+            .collect()
+    }
+}
+
+/// Finds the table containing the variable at `path` and the key of the variable in it.
+pub fn resolve_path(
+    lua: &mlua::Lua,
+    globals: &mlua::Table,
+    path: &str,
+) -> Option<(mlua::Table, mlua::Value)> {
+    let mut parent = globals.clone();
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        let mut key = mlua::Value::String(lua.create_string(segment).ok()?);
+        if parent.raw_get::<mlua::Value>(&key).ok()?.is_nil()
+            && let Ok(index) = segment.parse::<i64>()
+        {
+            key = mlua::Value::Integer(index);
+        }
+        if segments.peek().is_none() {
+            return Some((parent, key));
+        }
+        let mlua::Value::Table(child) = parent.raw_get::<mlua::Value>(&key).ok()? else {
+            return None;
+        };
+        parent = child;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use runtime::{
+        lua_env::lua_vec2::{self, Vec2},
+        math::Vect,
+        mlua,
+    };
+
+    use super::{EditHistory, MAX_HISTORY_LENGTH, WatchedValue, resolve_path};
+
+    /// Makes the last edit of the path old enough to not be merged with the next one.
+    fn age_last_edit(history: &mut EditHistory, path: &str) {
+        let record = history.records.get_mut(path).expect("the path was edited");
+        if let Some((time, _)) = record.history.back_mut() {
+            *time = Instant::now() - Duration::from_secs(2);
+        }
+    }
+
+    #[test]
+    fn edits_are_recorded_with_the_original_value() {
+        let mut history = EditHistory::default();
+        history.record_edit(
+            "Player.speed",
+            WatchedValue::Number(1.0),
+            WatchedValue::Number(2.0),
+        );
+        // Dragging a value gives an edit per frame, they are merged.
+        history.record_edit(
+            "Player.speed",
+            WatchedValue::Number(2.0),
+            WatchedValue::Number(3.0),
+        );
+        let record = history.get("Player.speed").expect("the edit is recorded");
+        assert_eq!(record.original, WatchedValue::Number(1.0));
+        assert_eq!(record.latest(), WatchedValue::Number(3.0));
+        assert_eq!(record.history.len(), 1);
+
+        age_last_edit(&mut history, "Player.speed");
+        history.record_edit(
+            "Player.speed",
+            WatchedValue::Number(3.0),
+            WatchedValue::Number(4.0),
+        );
+        let record = history.get("Player.speed").expect("the edit is recorded");
+        assert_eq!(record.history.len(), 2);
+        assert_eq!(record.original, WatchedValue::Number(1.0));
+
+        assert!(history.has_edits_under("Player"));
+        assert!(!history.has_edits_under("Play"));
+        history.forget("Player.speed");
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_latest_edits() {
+        let mut history = EditHistory::default();
+        for value in 0..MAX_HISTORY_LENGTH + 5 {
+            history.record_edit(
+                "Count",
+                WatchedValue::Number(-1.0),
+                WatchedValue::Number(value as f64),
+            );
+            age_last_edit(&mut history, "Count");
+        }
+        let record = history.get("Count").expect("the edit is recorded");
+        assert_eq!(record.history.len(), MAX_HISTORY_LENGTH);
+        assert_eq!(record.original, WatchedValue::Number(-1.0));
+        assert_eq!(
+            record.latest(),
+            WatchedValue::Number((MAX_HISTORY_LENGTH + 4) as f64)
+        );
+    }
+
+    #[test]
+    fn edits_can_be_reverted_and_applied_again() {
+        let lua = mlua::Lua::new();
+        lua.globals()
+            .set("Vec", lua_vec2::setup_vec_api(&lua).expect("Vec API"))
+            .expect("Set Vec");
+        lua.load("Player = { speed = 1, position = Vec.V2(1, 2) }")
+            .exec()
+            .expect("Valid script");
+        let globals = lua.globals();
+        let mut history = EditHistory::default();
+
+        let (speed_parent, speed_key) =
+            resolve_path(&lua, &globals, "Player.speed").expect("the path exists");
+        WatchedValue::Number(5.0)
+            .write_to(&speed_parent, &speed_key)
+            .expect("written");
+        history.record_edit(
+            "Player.speed",
+            WatchedValue::Number(1.0),
+            WatchedValue::Number(5.0),
+        );
+
+        let (position_parent, position_key) =
+            resolve_path(&lua, &globals, "Player.position").expect("the path exists");
+        let position = lua
+            .load("return Player.position")
+            .eval::<mlua::AnyUserData>()
+            .expect("a vector");
+        let after = WatchedValue::Vec2(Vect([3.0, 4.0]));
+        after
+            .write_to(&position_parent, &position_key)
+            .expect("written");
+        history.record_edit(
+            "Player.position",
+            WatchedValue::Vec2(Vect([1.0, 2.0])),
+            after,
+        );
+        // Vectors are edited in place, the references held by the game see the edit.
+        assert_eq!(
+            *position.borrow::<Vec2>().expect("a vector"),
+            Vect([3.0, 4.0])
+        );
+
+        // Revert all, like the button of the watcher.
+        for (path, original) in history.take_originals() {
+            let (parent, key) = resolve_path(&lua, &globals, &path).expect("the path exists");
+            original.write_to(&parent, &key).expect("written");
+        }
+        assert!(history.is_empty());
+        assert_eq!(
+            lua.load("return Player.speed")
+                .eval::<f64>()
+                .expect("a number"),
+            1.0
+        );
+        assert_eq!(
+            *position.borrow::<Vec2>().expect("a vector"),
+            Vect([1.0, 2.0])
+        );
+
+        // Applying the latest value again after a reload replaced the variable.
+        history.record_edit(
+            "Player.speed",
+            WatchedValue::Number(1.0),
+            WatchedValue::Number(7.0),
+        );
+        lua.load("Player.speed = 1").exec().expect("Valid script");
+        let latest = history.get("Player.speed").expect("recorded").latest();
+        latest.write_to(&speed_parent, &speed_key).expect("written");
+        assert_eq!(
+            lua.load("return Player.speed")
+                .eval::<f64>()
+                .expect("a number"),
+            7.0
+        );
+    }
+
+    #[test]
+    fn paths_with_indices_are_resolved() {
+        let lua = mlua::Lua::new();
+        lua.load("Enemies = { { hp = 3 } }")
+            .exec()
+            .expect("Valid script");
+        let (parent, key) =
+            resolve_path(&lua, &lua.globals(), "Enemies.1.hp").expect("the path exists");
+        assert_eq!(parent.raw_get::<i64>(key).expect("a number"), 3);
+        assert!(resolve_path(&lua, &lua.globals(), "Enemies.2.hp").is_none());
+    }
+}
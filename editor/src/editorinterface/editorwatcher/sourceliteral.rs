@@ -0,0 +1,780 @@
+//! Finds the literal that sets a global in the scripts, so that a value tuned in the watcher can be written back.
+//!
+//! This is a scanner, not a parser: it splits the scripts into tokens and recognizes a few statement shapes.
+//! It only accepts a value when it is sure of where the value comes from:
+//! - `Speed = 5` and `Player.speed = 5` at the top level of a script,
+//! - the fields of table constructors assigned at the top level, like `speed` in `Player = { speed = 5 }`,
+//! - `true`, `false`, numbers, negative numbers, and calls with only number arguments like `Vec.V2(1, -2)`.
+//!
+//! Limitations:
+//! - Assignments through `_G`, `rawset`, computed keys (`Player["speed"] = 5`) or a local alias of a table are not seen.
+//! - A value set more than once, in a multiple assignment or inside a function or a block is refused, even when
+//!   only one of the assignments can run. A local of a function with the same name as the global also refuses it.
+//! - The order in which the scripts run is unknown: a table constructor of another script without the field is
+//!   assumed not to replace the field.
+//! - An `if` expression right after the `then` or `else` of another `if` expression is taken for an `if` statement.
+//!   Scripts whose blocks do not balance are refused, so this refuses the value instead of finding a wrong one.
+
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiteralKind {
+    Boolean,
+    Number,
+    /// A call with only number arguments, like `Vec.V2(1, 2)`.
+    Call,
+}
+
+/// A literal found in a script.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLiteral {
+    pub line: usize,
+    pub kind: LiteralKind,
+    /// The byte ranges to rewrite: the literal, or the arguments of the call.
+    pub parts: Vec<Range<usize>>,
+}
+
+impl SourceLiteral {
+    pub fn part_texts<'a>(&self, source: &'a str) -> Vec<&'a str> {
+        self.parts
+            .iter()
+            .map(|part| &source[part.clone()])
+            .collect()
+    }
+
+    /// Replaces the parts of the literal, the rest of the source is kept as it is.
+    pub fn replace_parts(&self, source: &str, new_parts: &[String]) -> String {
+        let mut result = String::with_capacity(source.len());
+        let mut copied = 0;
+        for (part, new_part) in self.parts.iter().zip(new_parts) {
+            result.push_str(&source[copied..part.start]);
+            result.push_str(new_part);
+            copied = part.end;
+        }
+        result.push_str(&source[copied..]);
+        result
+    }
+}
+
+/// A statement of a script that may set the searched variable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Assignment {
+    /// A top-level assignment to a literal, the only kind that can be rewritten.
+    Literal(SourceLiteral),
+    /// Any other statement that may set the variable, with why it cannot be rewritten.
+    Other { line: usize, reason: String },
+}
+
+/// Finds the only literal setting the variable at `path`, like `Player.speed`, in the scripts.
+/// Returns the script, its source and the literal, or why the literal cannot be found with confidence.
+pub fn locate_literal<'a>(
+    scripts: &'a [(PathBuf, String)],
+    path: &str,
+) -> Result<(&'a Path, &'a str, SourceLiteral), String> {
+    let root = path.split('.').next().unwrap_or(path);
+    let mut found = Vec::new();
+    for (script_path, source) in scripts {
+        let assignments = match find_assignments(source, path) {
+            Ok(assignments) => assignments,
+            // Scripts that cannot be followed only matter when they may set the variable.
+            Err(reason) if source.contains(root) => {
+                return Err(format!("{reason} in {}", script_path.display()));
+            }
+            Err(_) => continue,
+        };
+        for assignment in assignments {
+            match assignment {
+                Assignment::Literal(literal) => {
+                    found.push((script_path.as_path(), source.as_str(), literal));
+                }
+                Assignment::Other { line, reason } => {
+                    return Err(format!("{reason} ({}:{line})", script_path.display()));
+                }
+            }
+        }
+    }
+    if found.len() > 1 {
+        let places = found
+            .iter()
+            .map(|(script_path, _, literal)| format!("{}:{}", script_path.display(), literal.line))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!("{path} is set in several places ({places})"));
+    }
+    found
+        .pop()
+        .ok_or_else(|| format!("no assignment of {path} to a literal was found in the scripts"))
+}
+
+/// Finds the statements of the script that may set the variable at `path`.
+pub fn find_assignments(source: &str, path: &str) -> Result<Vec<Assignment>, String> {
+    let segments = path.split('.').collect::<Vec<_>>();
+    let tokens = tokenize(source)?;
+    let mut nesting = Vec::new();
+    let mut found = Vec::new();
+    // Once a top-level local has the name of the global, the rest of the script sets the local.
+    let mut is_shadowed = false;
+    for i in 0..tokens.len() {
+        if nesting.is_empty() && tokens[i].is("local") {
+            is_shadowed |= declared_locals(&tokens, i).contains(&segments[0]);
+        }
+        if !is_shadowed
+            && is_assignment_operator(&tokens[i])
+            && matches!(nesting.last(), None | Some(Open::Block))
+            && let Some(targets) = assignment_targets(&tokens, i)
+            && let Some(assignment) =
+                check_assignment(&tokens, i, &targets, &segments, nesting.is_empty())
+        {
+            found.push(assignment);
+        }
+        if !track_nesting(&mut nesting, &tokens, i) {
+            return Err(format!(
+                "the blocks do not match on line {}",
+                tokens[i].line
+            ));
+        }
+    }
+    if !nesting.is_empty() {
+        return Err("the blocks are not all closed".to_string());
+    }
+
+    // A table constructor without the field only replaces the literals found before it.
+    let mut assignments = Vec::new();
+    for assignment in found {
+        match assignment {
+            Found::Absent { line, target } => {
+                if assignments
+                    .iter()
+                    .any(|assignment| matches!(assignment, Assignment::Literal(_)))
+                {
+                    assignments.push(Assignment::Other {
+                        line,
+                        reason: format!("{target} is replaced by a table without {path}"),
+                    });
+                }
+            }
+            Found::Assignment(assignment) => assignments.push(assignment),
+        }
+    }
+    Ok(assignments)
+}
+
+enum Found {
+    Assignment(Assignment),
+    /// A table constructor assigned to a parent of the variable, without the variable.
+    Absent {
+        line: usize,
+        target: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Name,
+    Number,
+    String,
+    Symbol,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+    line: usize,
+}
+
+impl Token<'_> {
+    fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    fn range(&self) -> Range<usize> {
+        self.start..self.end()
+    }
+
+    /// Strings never match: their text includes the quotes.
+    fn is(&self, text: &str) -> bool {
+        self.text == text
+    }
+
+    fn is_identifier(&self) -> bool {
+        self.kind == TokenKind::Name && !KEYWORDS.contains(&self.text)
+    }
+}
+
+const KEYWORDS: [&str; 22] = [
+    "and", "break", "continue", "do", "else", "elseif", "end", "false", "for", "function", "if",
+    "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// The symbols of more than one character, the longest first.
+const SYMBOLS: [&str; 17] = [
+    "...", "..=", "//=", "==", "~=", "<=", ">=", "+=", "-=", "*=", "/=", "%=", "^=", "..", "//",
+    "::", "->",
+];
+
+const COMPOUND_ASSIGNMENTS: [&str; 8] = ["+=", "-=", "*=", "/=", "//=", "%=", "^=", "..="];
+
+/// Returns the level of the long bracket starting the bytes, like 2 for `[==[`.
+fn long_bracket_level(bytes: &[u8]) -> Option<usize> {
+    if bytes.first() != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[1..].iter().take_while(|b| **b == b'=').count();
+    (bytes.get(level + 1) == Some(&b'[')).then_some(level)
+}
+
+/// Returns the index after the long bracket closing the one at `start`.
+fn skip_long_bracket(source: &str, start: usize, level: usize, line: &mut usize) -> Option<usize> {
+    let content_start = start + level + 2;
+    let closing = format!("]{}]", "=".repeat(level));
+    let content_length = source[content_start..].find(&closing)?;
+    let content_end = content_start + content_length;
+    *line += source[start..content_end].matches('\n').count();
+    Some(content_end + closing.len())
+}
+
+/// Returns the index after the string starting at `start`.
+fn skip_quoted_string(bytes: &[u8], start: usize, line: &mut usize) -> Option<usize> {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                if bytes.get(i + 1) == Some(&b'\n') {
+                    *line += 1;
+                }
+                i += 2;
+            }
+            b'\n' => return None,
+            b if b == quote => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+/// Splits the source into tokens, without the comments.
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, String> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let start_line = line;
+        if c == b'\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if bytes[i..].starts_with(b"--") {
+            if let Some(level) = long_bracket_level(&bytes[i + 2..]) {
+                i = skip_long_bracket(source, i + 2, level, &mut line)
+                    .ok_or_else(|| format!("the comment on line {start_line} is not closed"))?;
+            } else {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            continue;
+        }
+        let kind = if matches!(c, b'"' | b'\'' | b'`') {
+            i = skip_quoted_string(bytes, i, &mut line)
+                .ok_or_else(|| format!("the string on line {start_line} is not closed"))?;
+            TokenKind::String
+        } else if let Some(level) = long_bracket_level(&bytes[i..]) {
+            i = skip_long_bracket(source, i, level, &mut line)
+                .ok_or_else(|| format!("the string on line {start_line} is not closed"))?;
+            TokenKind::String
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+        {
+            let is_hexadecimal = bytes[i..].starts_with(b"0x") || bytes[i..].starts_with(b"0X");
+            i += 1;
+            while i < bytes.len() {
+                let is_exponent_sign = matches!(bytes[i], b'+' | b'-')
+                    && matches!(bytes[i - 1], b'e' | b'E')
+                    && !is_hexadecimal;
+                if !(bytes[i].is_ascii_alphanumeric()
+                    || matches!(bytes[i], b'_' | b'.')
+                    || is_exponent_sign)
+                {
+                    break;
+                }
+                i += 1;
+            }
+            TokenKind::Number
+        } else if c.is_ascii_alphabetic() || c == b'_' || !c.is_ascii() {
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || !bytes[i].is_ascii())
+            {
+                i += 1;
+            }
+            TokenKind::Name
+        } else {
+            i += SYMBOLS
+                .iter()
+                .find(|symbol| source[i..].starts_with(**symbol))
+                .map_or(1, |symbol| symbol.len());
+            TokenKind::Symbol
+        };
+        tokens.push(Token {
+            kind,
+            text: &source[start..i],
+            start,
+            line: start_line,
+        });
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Open {
+    Parenthesis,
+    Bracket,
+    Brace,
+    /// A function, a loop, an `if` or a `do` block.
+    Block,
+}
+
+/// An `if` is an expression when it is where a value is expected.
+fn is_if_expression(tokens: &[Token], i: usize) -> bool {
+    let Some(previous) = i.checked_sub(1).map(|previous| &tokens[previous]) else {
+        return false;
+    };
+    match previous.kind {
+        TokenKind::Symbol => !matches!(previous.text, ")" | "]" | "}" | ";"),
+        TokenKind::Name => matches!(previous.text, "return" | "and" | "or" | "not" | "in"),
+        TokenKind::Number | TokenKind::String => false,
+    }
+}
+
+/// Updates the open brackets and blocks with the token.
+/// Returns false when the token closes something that is not open.
+fn track_nesting(nesting: &mut Vec<Open>, tokens: &[Token], i: usize) -> bool {
+    let token = &tokens[i];
+    let closed = match token.text {
+        "(" => Some(Open::Parenthesis),
+        "[" => Some(Open::Bracket),
+        "{" => Some(Open::Brace),
+        "function" | "do" | "repeat" => Some(Open::Block),
+        "if" if !is_if_expression(tokens, i) => Some(Open::Block),
+        _ => None,
+    };
+    if let Some(open) = closed {
+        nesting.push(open);
+        return true;
+    }
+    let closed = match token.text {
+        ")" => Open::Parenthesis,
+        "]" => Open::Bracket,
+        "}" => Open::Brace,
+        "end" | "until" => Open::Block,
+        _ => return true,
+    };
+    nesting.pop() == Some(closed)
+}
+
+fn is_assignment_operator(token: &Token) -> bool {
+    token.kind == TokenKind::Symbol && (token.is("=") || COMPOUND_ASSIGNMENTS.contains(&token.text))
+}
+
+/// Returns the names declared by the `local` statement at `i`.
+fn declared_locals<'a>(tokens: &[Token<'a>], i: usize) -> Vec<&'a str> {
+    let Some(next) = tokens.get(i + 1) else {
+        return Vec::new();
+    };
+    if next.is("function") {
+        return tokens
+            .get(i + 2)
+            .map(|name| vec![name.text])
+            .unwrap_or_default();
+    }
+    // The names are the first one and the ones after a comma, the other names are in the types.
+    let mut names = vec![next.text];
+    let mut depth = 0;
+    for j in i + 2..tokens.len() {
+        let token = &tokens[j];
+        if token.line != tokens[i].line && !tokens[j - 1].is(",") {
+            break;
+        }
+        match token.text {
+            "(" | "[" | "{" | "<" => depth += 1,
+            ")" | "]" | "}" | ">" => depth -= 1,
+            "=" | ";" if depth == 0 => break,
+            _ if depth == 0 && tokens[j - 1].is(",") && token.is_identifier() => {
+                names.push(token.text);
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Returns the variables set by the assignment whose operator is at `operator`, like `["Player", "speed"]`.
+/// Returns `None` when the targets are not all variables or fields, or when the `=` is not an assignment.
+fn assignment_targets<'a>(tokens: &[Token<'a>], operator: usize) -> Option<Vec<Vec<&'a str>>> {
+    let mut targets = Vec::new();
+    let mut j = operator;
+    loop {
+        let mut target = Vec::new();
+        loop {
+            j = j.checked_sub(1)?;
+            if !tokens[j].is_identifier() {
+                return None;
+            }
+            target.push(tokens[j].text);
+            if j > 0 && tokens[j - 1].is(".") {
+                j -= 1;
+            } else {
+                break;
+            }
+        }
+        target.reverse();
+        targets.push(target);
+        if j > 0 && tokens[j - 1].is(",") {
+            j -= 1;
+        } else {
+            break;
+        }
+    }
+    targets.reverse();
+    match j.checked_sub(1).map(|previous| tokens[previous].text) {
+        // A local, a loop variable, a type alias, or a variable with a type annotation.
+        Some("local" | "for" | "type" | ":" | "::") => None,
+        _ => Some(targets),
+    }
+}
+
+/// The result of looking for a value in the tokens of an expression.
+enum ValueSearch {
+    Literal(SourceLiteral),
+    /// The value is in a table constructor that does not have the field.
+    Absent,
+    NotLiteral(String),
+}
+
+fn check_assignment(
+    tokens: &[Token],
+    operator: usize,
+    targets: &[Vec<&str>],
+    segments: &[&str],
+    is_top_level: bool,
+) -> Option<Found> {
+    let path = segments.join(".");
+    let related = targets
+        .iter()
+        .filter(|target| {
+            let common = target.len().min(segments.len());
+            target[..common] == segments[..common]
+        })
+        .collect::<Vec<_>>();
+    let target = *related.first()?;
+    let line = tokens[operator].line;
+    let other = |reason: String| Some(Found::Assignment(Assignment::Other { line, reason }));
+    if targets.len() > 1 {
+        return other(format!("{path} may be set by a multiple assignment"));
+    }
+    if !tokens[operator].is("=") {
+        return other(format!("{path} is changed with {}", tokens[operator].text));
+    }
+    if target.len() > segments.len() {
+        return other(format!("a field of {path} is set"));
+    }
+    match find_value(tokens, operator + 1, &segments[target.len()..], false) {
+        ValueSearch::Literal(_) if !is_top_level => {
+            other(format!("{path} is set inside a function or a block"))
+        }
+        ValueSearch::Literal(literal) => Some(Found::Assignment(Assignment::Literal(literal))),
+        ValueSearch::Absent => Some(Found::Absent {
+            line,
+            target: target.join("."),
+        }),
+        ValueSearch::NotLiteral(reason) => other(reason),
+    }
+}
+
+/// Finds the value of the expression at `start`, or of the field at `fields` of the table constructor at `start`.
+fn find_value(tokens: &[Token], start: usize, fields: &[&str], is_field: bool) -> ValueSearch {
+    let Some(first) = tokens.get(start) else {
+        return ValueSearch::NotLiteral("the value is missing".to_string());
+    };
+    let Some((field, fields)) = fields.split_first() else {
+        let Some((literal, end)) = read_literal(tokens, start) else {
+            return ValueSearch::NotLiteral("the value is not a literal".to_string());
+        };
+        let is_end_of_value = match tokens.get(end) {
+            None => !is_field,
+            Some(next) if is_field => next.is(",") || next.is(";") || next.is("}"),
+            // The next statement starts with a name or a keyword.
+            Some(next) => {
+                next.is(";") || (next.kind == TokenKind::Name && !next.is("and") && !next.is("or"))
+            }
+        };
+        return if is_end_of_value {
+            ValueSearch::Literal(literal)
+        } else {
+            ValueSearch::NotLiteral("the value is an expression, not a literal".to_string())
+        };
+    };
+    if !first.is("{") {
+        return ValueSearch::NotLiteral(format!(
+            "the table containing {field} is not made with a table constructor"
+        ));
+    }
+
+    let mut found = None;
+    let mut position = 0;
+    let mut i = start + 1;
+    while let Some(token) = tokens.get(i) {
+        if token.is("}") {
+            break;
+        }
+        if token.is("[") {
+            return ValueSearch::NotLiteral(format!(
+                "the table containing {field} has computed keys"
+            ));
+        }
+        let (key, value_start) =
+            if token.is_identifier() && tokens.get(i + 1).is_some_and(|t| t.is("=")) {
+                (token.text.to_string(), i + 2)
+            } else {
+                position += 1;
+                (position.to_string(), i)
+            };
+        let Some(value_end) = skip_expression(tokens, value_start) else {
+            return ValueSearch::NotLiteral(format!("the table containing {field} is not closed"));
+        };
+        if key == *field {
+            if found.is_some() {
+                return ValueSearch::NotLiteral(format!("{field} is in the table twice"));
+            }
+            found = Some(find_value(tokens, value_start, fields, true));
+        }
+        i = value_end;
+        if tokens[i].is(",") || tokens[i].is(";") {
+            i += 1;
+        }
+    }
+    found.unwrap_or(ValueSearch::Absent)
+}
+
+/// Returns the index of the `,`, `;` or `}` ending the field of a table constructor starting at `start`.
+fn skip_expression(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut nesting = Vec::new();
+    for i in start..tokens.len() {
+        let token = &tokens[i];
+        if nesting.is_empty() && (token.is(",") || token.is(";") || token.is("}")) {
+            return Some(i);
+        }
+        if !track_nesting(&mut nesting, tokens, i) {
+            return None;
+        }
+    }
+    None
+}
+
+/// Reads a negative or positive number at `start`, and returns its range and the index after it.
+fn read_number(tokens: &[Token], start: usize) -> Option<(Range<usize>, usize)> {
+    let first = tokens.get(start)?;
+    let number_index = if first.is("-") { start + 1 } else { start };
+    let number = tokens.get(number_index)?;
+    (number.kind == TokenKind::Number).then_some((first.start..number.end(), number_index + 1))
+}
+
+/// Reads the literal at `start`, and returns it with the index after it.
+fn read_literal(tokens: &[Token], start: usize) -> Option<(SourceLiteral, usize)> {
+    let first = tokens.get(start)?;
+    let literal = |kind, parts| SourceLiteral {
+        line: first.line,
+        kind,
+        parts,
+    };
+    if first.is("true") || first.is("false") {
+        return Some((
+            literal(LiteralKind::Boolean, vec![first.range()]),
+            start + 1,
+        ));
+    }
+    if let Some((range, end)) = read_number(tokens, start) {
+        return Some((literal(LiteralKind::Number, vec![range]), end));
+    }
+
+    // A call like `Vec.V2(1, 2)`.
+    let mut i = start;
+    while tokens.get(i)?.is_identifier() && tokens.get(i + 1)?.is(".") {
+        i += 2;
+    }
+    if !tokens.get(i)?.is_identifier() || !tokens.get(i + 1)?.is("(") {
+        return None;
+    }
+    i += 2;
+    let mut arguments = Vec::new();
+    loop {
+        let (range, end) = read_number(tokens, i)?;
+        arguments.push(range);
+        i = end;
+        match tokens.get(i)?.text {
+            "," => i += 1,
+            ")" => return Some((literal(LiteralKind::Call, arguments), i + 1)),
+            _ => return None,
+        }
+    }
+}
+
+/// Reads a number written in a script, like `-1.5`, `1_000` or `0xFF`.
+pub fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest.trim_start()),
+        None => (1.0, text),
+    };
+    let text = text.replace('_', "");
+    let value = if let Some(digits) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        u64::from_str_radix(digits, 16).ok()? as f64
+    } else if let Some(digits) = text.strip_prefix("0b").or(text.strip_prefix("0B")) {
+        u64::from_str_radix(digits, 2).ok()? as f64
+    } else {
+        text.parse::<f64>().ok()?
+    };
+    Some(sign * value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Assignment, LiteralKind, find_assignments, locate_literal, parse_number};
+
+    /// Returns the texts of the literal found for the path, or the reason it was not found.
+    fn locate(source: &str, path: &str) -> Result<Vec<String>, String> {
+        let scripts = [(PathBuf::from("main.luau"), source.to_string())];
+        let (_, source, literal) = locate_literal(&scripts, path)?;
+        Ok(literal
+            .part_texts(source)
+            .into_iter()
+            .map(str::to_string)
+            .collect())
+    }
+
+    #[test]
+    fn top_level_literals_are_found() {
+        let source = r#"
+-- Speed = 1 in a comment
+local text = "Speed = 2"
+Speed = 3.5
+Debug = false
+Player = { name = "a", jump = { height = -2 }, 10, color = Vec4.V4(1, 0.5, 0, 1) }
+Player.position = Vec.V2(0, -1e3)
+Enemy = {}
+Enemy.count = 0x10 -- in hexadecimal
+function Update()
+    local Speed = 10
+    print(Speed + 1)
+end
+"#;
+        assert_eq!(locate(source, "Speed"), Ok(vec!["3.5".to_string()]));
+        assert_eq!(locate(source, "Debug"), Ok(vec!["false".to_string()]));
+        assert_eq!(
+            locate(source, "Player.jump.height"),
+            Ok(vec!["-2".to_string()])
+        );
+        assert_eq!(locate(source, "Player.1"), Ok(vec!["10".to_string()]));
+        assert_eq!(
+            locate(source, "Player.color"),
+            Ok(vec!["1", "0.5", "0", "1"]
+                .into_iter()
+                .map(String::from)
+                .collect())
+        );
+        assert_eq!(
+            locate(source, "Player.position"),
+            Ok(vec!["0".to_string(), "-1e3".to_string()])
+        );
+        assert_eq!(locate(source, "Enemy.count"), Ok(vec!["0x10".to_string()]));
+        assert_eq!(parse_number("0x10"), Some(16.0));
+        assert_eq!(parse_number("- 1_000.5"), Some(-1000.5));
+    }
+
+    #[test]
+    fn uncertain_values_are_refused_with_a_reason() {
+        let refused = |source: &str, path: &str, reason: &str| {
+            let error = locate(source, path).expect_err(source);
+            assert!(error.contains(reason), "{error:?} for {source:?}");
+        };
+        refused("Speed = 5 * 2", "Speed", "not a literal");
+        refused("Speed = getSpeed()", "Speed", "not a literal");
+        refused("Speed = 1\nSpeed = 2", "Speed", "several places");
+        refused(
+            "Speed = 1\nfunction f()\n  Speed = 2\nend",
+            "Speed",
+            "inside a function",
+        );
+        refused("Speed = 1\nSpeed += 1", "Speed", "changed with +=");
+        refused("a, Speed = 1, 2", "Speed", "multiple assignment");
+        refused("Player = { speed = 1, speed = 2 }", "Player.speed", "twice");
+        refused(
+            "Player = { [k] = 1, speed = 2 }",
+            "Player.speed",
+            "computed keys",
+        );
+        refused(
+            "Player = makePlayer()",
+            "Player.speed",
+            "not made with a table constructor",
+        );
+        refused(
+            "Player = {}\nPlayer.speed = 1\nPlayer = {}",
+            "Player.speed",
+            "replaced by a table",
+        );
+        refused("Pos = Vec.V2(1, 2)\nPos.x = 3", "Pos", "a field of Pos");
+        refused("Speed = 1\nif x then\n", "Speed", "not all closed");
+        refused("local Speed = 1\nSpeed = 2", "Speed", "no assignment");
+        refused("Other = 1", "Speed", "no assignment");
+    }
+
+    #[test]
+    fn statements_that_look_like_assignments_are_ignored() {
+        let source = r#"
+Speed = 2
+local x: number = 3
+type Speed = number
+for Speed = 1, 10 do
+    print(Speed == 3)
+end
+local f = function() return if Speed == 1 then 1 else 2 end
+local t = { Speed = 4, [[ Speed = 5 ]], `Speed = {6}` }
+"#;
+        let assignments = find_assignments(source, "Speed").expect("the script is followed");
+        assert!(
+            matches!(&assignments[..], [Assignment::Literal(literal)] if literal.kind == LiteralKind::Number && literal.line == 2),
+            "{assignments:?}"
+        );
+    }
+
+    #[test]
+    fn only_the_literal_is_rewritten() {
+        let source = "Player = {\n    speed = 1.5, -- tuned\n    position = V2( 0,  1 ),\n}\n";
+        let scripts = [(PathBuf::from("main.luau"), source.to_string())];
+        let (_, _, speed) = locate_literal(&scripts, "Player.speed").expect("found");
+        let source = speed.replace_parts(source, &["2.25".to_string()]);
+        let scripts = [(PathBuf::from("main.luau"), source.clone())];
+        let (_, _, position) = locate_literal(&scripts, "Player.position").expect("found");
+        let source = position.replace_parts(&source, &["-3".to_string(), "1".to_string()]);
+        assert_eq!(
+            source,
+            "Player = {\n    speed = 2.25, -- tuned\n    position = V2( -3,  1 ),\n}\n"
+        );
+    }
+}
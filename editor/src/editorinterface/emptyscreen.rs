@@ -13,6 +13,8 @@ use runtime::{
 use vectarine_cli::{project::createproject::create_game_and_get_path, regex::Regex};
 
 use crate::editorinterface::EditorState;
+use crate::editorinterface::editortutorial::{TUTORIALS, start_tutorial};
+use crate::editortheme::Palette;
 use vectarine_cli::project::geteditorpaths::{get_end_of_path, get_gallery_path};
 
 pub fn draw_empty_screen(state: &mut EditorState, ui: &mut egui::Ui) {
@@ -76,6 +78,18 @@ pub fn draw_empty_screen_window_content(
             {
                 open_file_dialog_and_load_project(state);
             }
+            ui.add_space(8.0);
+            if let Some(tutorial) = TUTORIALS.first()
+                && ui
+                    .button(RichText::new("Start tutorial").size(18.0))
+                    .on_hover_text_at_pointer(
+                        "Creates a small project in the chosen folder and guides you through the editor, step by step",
+                    )
+                    .clicked()
+                && let Some(parent_folder) = open_folder_dialog_and_create_project(state)
+            {
+                start_tutorial(state, tutorial, &parent_folder);
+            }
             ui.style_mut().spacing.button_padding =
                 egui::Spacing::default().button_padding;
         });
@@ -139,7 +153,7 @@ pub fn draw_new_game_window_content(
     if let Some(error_idx) = error_idx {
         ui.label(
             RichText::new(ERRORS[error_idx])
-                .color(egui::Color32::DARK_RED)
+                .color(Palette::of(ui).error)
                 .size(12.0),
         );
     }
@@ -292,7 +306,7 @@ pub fn draw_gallery(state: &mut EditorState, ui: &mut egui::Ui) {
                                 }
                             };
                             let stroke = if is_hovering {
-                                Stroke::new(2.0_f32, egui::Color32::WHITE)
+                                Stroke::new(2.0_f32, Palette::of(ui).text)
                             } else {
                                 Stroke::new(2.0_f32, egui::Color32::TRANSPARENT)
                             };
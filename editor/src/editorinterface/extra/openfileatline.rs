@@ -1,16 +1,19 @@
 use std::{path::Path, process::Command};
 
+use runtime::io::paths;
+
 use crate::editorconfig::TextEditor;
 
 // There is no standard way to do this, so we try different editors
 // Ideally the user should be able his preferred editor
 // Roughly sorted by popularity (least to most popular)
 pub fn open_file_at_line(file: &Path, line: usize, prefered_text_editor: Option<TextEditor>) {
-    let absolute_path = file
-        .canonicalize()
-        .expect("Failed to canonicalize path. This should not happen because the file exists")
-        .display()
-        .to_string();
+    let absolute_path = paths::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+    // The editors receive the line in the same argument as the path, which needs the path as a string.
+    let Some(absolute_path) = absolute_path.to_str().map(str::to_string) else {
+        let _ = open::that(file);
+        return;
+    };
 
     let opened_successfully = match prefered_text_editor {
         None => false,
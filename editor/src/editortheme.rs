@@ -0,0 +1,158 @@
+use std::sync::OnceLock;
+
+use runtime::egui::{self, Color32};
+
+use crate::editorconfig::{EditorConfig, EditorTheme};
+
+/// The colors used by the editor windows for text that carries a meaning.
+/// All text colors have a contrast ratio of at least 4.5:1 with the window background of their theme.
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    pub text: Color32,
+    pub muted_text: Color32,
+    pub error: Color32,
+    pub warning: Color32,
+    pub info: Color32,
+    pub link: Color32,
+    pub code_text: Color32,
+    pub code_background: Color32,
+    pub graph_line: Color32,
+    /// Colors of the different metrics in the profiler, also used for their names.
+    pub graph_series: [Color32; 7],
+}
+
+impl Palette {
+    pub const DARK: Palette = Palette {
+        text: Color32::from_gray(220),
+        muted_text: Color32::from_gray(150),
+        error: Color32::from_rgb(255, 110, 110),
+        warning: Color32::from_rgb(240, 200, 80),
+        info: Color32::from_rgb(120, 180, 255),
+        link: Color32::from_rgb(120, 180, 255),
+        code_text: Color32::WHITE,
+        code_background: Color32::from_gray(0x22),
+        graph_line: Color32::WHITE,
+        graph_series: [
+            Color32::from_rgb(255, 100, 100),
+            Color32::from_rgb(100, 255, 100),
+            Color32::from_rgb(130, 140, 255),
+            Color32::from_rgb(255, 255, 100),
+            Color32::from_rgb(100, 255, 255),
+            Color32::from_rgb(255, 100, 255),
+            Color32::WHITE,
+        ],
+    };
+
+    pub const LIGHT: Palette = Palette {
+        text: Color32::from_gray(20),
+        muted_text: Color32::from_gray(100),
+        error: Color32::from_rgb(180, 0, 0),
+        warning: Color32::from_rgb(140, 90, 0),
+        info: Color32::from_rgb(0, 90, 180),
+        link: Color32::from_rgb(0, 90, 180),
+        code_text: Color32::from_gray(20),
+        code_background: Color32::from_gray(235),
+        graph_line: Color32::from_gray(40),
+        graph_series: [
+            Color32::from_rgb(200, 40, 40),
+            Color32::from_rgb(30, 130, 30),
+            Color32::from_rgb(40, 60, 200),
+            Color32::from_rgb(140, 110, 0),
+            Color32::from_rgb(0, 120, 130),
+            Color32::from_rgb(160, 40, 160),
+            Color32::from_gray(40),
+        ],
+    };
+
+    /// The palette matching the theme currently used by the ui.
+    pub fn of(ui: &egui::Ui) -> Palette {
+        if ui.visuals().dark_mode {
+            Palette::DARK
+        } else {
+            Palette::LIGHT
+        }
+    }
+}
+
+/// Applies the theme of the config to the egui context. Does nothing when it is already applied.
+pub fn apply_theme(ctx: &egui::Context, config: &EditorConfig) {
+    let is_dark = match config.theme {
+        EditorTheme::Dark => true,
+        EditorTheme::Light => false,
+        EditorTheme::System => is_system_theme_dark(),
+    };
+    let mut visuals = if is_dark {
+        egui::Visuals::dark()
+    } else {
+        egui::Visuals::light()
+    };
+    if let Some([r, g, b]) = config.accent_color {
+        let accent = Color32::from_rgb(r, g, b);
+        visuals.selection.bg_fill = accent;
+        visuals.selection.stroke.color = if is_dark {
+            Color32::WHITE
+        } else {
+            Color32::BLACK
+        };
+        visuals.hyperlink_color = accent;
+    }
+    if ctx.style().visuals != visuals {
+        ctx.set_visuals(visuals);
+    }
+}
+
+/// SDL2 cannot tell us if the system uses a dark theme, so we ask the OS.
+/// The answer is cached: changing the theme of the OS requires restarting the editor.
+pub fn is_system_theme_dark() -> bool {
+    static IS_DARK: OnceLock<bool> = OnceLock::new();
+    *IS_DARK.get_or_init(|| detect_system_theme().unwrap_or(true))
+}
+
+fn detect_system_theme() -> Option<bool> {
+    use std::process::Command;
+
+    if cfg!(target_os = "windows") {
+        let mut command = Command::new("reg");
+        command.args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+            "/v",
+            "AppsUseLightTheme",
+        ]);
+        // Without this, a console window flashes on screen when the editor starts.
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        let output = command.output().ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        if !output.contains("AppsUseLightTheme") {
+            return None;
+        }
+        // The value is printed as "AppsUseLightTheme    REG_DWORD    0x0"
+        Some(output.trim_end().ends_with("0x0"))
+    } else if cfg!(target_os = "macos") {
+        // The key only exists in dark mode.
+        let output = Command::new("defaults")
+            .args(["read", "-g", "AppleInterfaceStyle"])
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).contains("Dark"))
+    } else {
+        let output = Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.interface", "color-scheme"])
+            .output()
+            .ok()?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        if output.contains("dark") {
+            Some(true)
+        } else if output.contains("light") {
+            Some(false)
+        } else {
+            // "default" means that the desktop did not choose, most Linux desktops are dark by default.
+            None
+        }
+    }
+}
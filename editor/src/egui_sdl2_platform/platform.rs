@@ -275,25 +275,31 @@ impl Platform {
     where
         F: FnMut(&mut egui::Ui, &mut EditorState),
     {
+        let video = editor_state.video.clone();
+        self.run_ui(&video, |ui| draw_ui(ui, editor_state))
+    }
+
+    /// Like `run`, for an interface that does not use the state of the editor.
+    pub fn run_ui(
+        &mut self,
+        video: &sdl2::VideoSubsystem,
+        mut draw_ui: impl FnMut(&mut egui::Ui),
+    ) -> anyhow::Result<egui::FullOutput> {
         let output = self.egui_ctx.run_ui(self.raw_input.take(), |ui| {
             ui.input_mut(|input| {
                 input.smooth_scroll_delta = self.smooth_scroll_delta;
             });
 
-            draw_ui(ui, editor_state);
+            draw_ui(ui);
         });
 
         // Update the clipboard
         for cmd in &output.platform_output.commands {
             match cmd {
                 egui::OutputCommand::CopyText(text) => {
-                    editor_state
-                        .video
-                        .clipboard()
-                        .set_clipboard_text(text)
-                        .map_err(|e| {
-                            anyhow::anyhow!("Failed to assign text to clipboard: {}", e)
-                        })?;
+                    video.clipboard().set_clipboard_text(text).map_err(|e| {
+                        anyhow::anyhow!("Failed to assign text to clipboard: {}", e)
+                    })?;
                 }
                 egui::OutputCommand::CopyImage(_) | egui::OutputCommand::OpenUrl(_) => {
                     // ...
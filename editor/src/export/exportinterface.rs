@@ -1,13 +1,23 @@
-use lazy_static::lazy_static;
 use std::cell::RefCell;
-use std::sync::Mutex;
+use std::path::Path;
 use std::thread;
+use std::time::Instant;
 
+use runtime::console::{
+    self, ConsoleMessage, Subsystem, print_err, print_err_from, print_info, print_info_from,
+    print_warn,
+};
 use runtime::egui;
-use runtime::egui::{Color32, RichText, Widget};
+use runtime::egui::{RichText, Widget};
+use runtime::projectinfo::ContentPackInfo;
 
 use crate::editorinterface::EditorState;
-use vectarine_cli::project::exportproject::{ExportPlatform, export_project};
+use crate::editortheme::Palette;
+use crate::projectstate::ProjectState;
+use vectarine_cli::project::exportproject::{
+    ExportPlatform, describe_single_file_export, export_project, export_project_as_single_html_file,
+};
+use vectarine_cli::project::validateproject::{AssetValidationReport, validate_project_assets};
 
 pub fn draw_editor_export(editor: &mut EditorState, ui: &mut egui::Ui) {
     let mut is_shown = editor.config.borrow().is_export_window_shown;
@@ -29,6 +39,144 @@ fn ui_title(ui: &mut egui::Ui, title: &str) {
     ui.label(RichText::new(title).heading());
 }
 
+/// Checks the files loaded by the scripts and prints the problems in the console.
+pub fn validate_project_and_print_report(project_path: &Path) {
+    let report = validate_project_assets(project_path);
+    for reference in &report.missing {
+        print_err(format!("{reference}: file not found"));
+    }
+    for reference in &report.not_exported {
+        print_err(format!(
+            "{reference}: the file is not exported, move it to an exported folder"
+        ));
+    }
+    for reference in &report.unverifiable {
+        print_warn(format!(
+            "{reference}: unverifiable, the path is built at runtime"
+        ));
+    }
+    print_info(format!(
+        "Checked {} asset paths: {} missing, {} unverifiable.",
+        report.checked_count,
+        report.missing.len() + report.not_exported.len(),
+        report.unverifiable.len()
+    ));
+}
+
+fn draw_validation_report(ui: &mut egui::Ui, report: &AssetValidationReport) {
+    let palette = Palette::of(ui);
+    egui::ScrollArea::vertical()
+        .max_height(150.0)
+        .show(ui, |ui| {
+            for reference in &report.missing {
+                ui.colored_label(palette.error, format!("{reference}: file not found"));
+            }
+            for reference in &report.not_exported {
+                ui.colored_label(
+                    palette.error,
+                    format!("{reference}: the file is not exported"),
+                );
+            }
+            for reference in &report.unverifiable {
+                ui.colored_label(
+                    palette.warning,
+                    format!("{reference}: unverifiable, the path is built at runtime"),
+                );
+            }
+        });
+}
+
+/// Edits the version of the game and its content packs, saved in game.vecta.
+fn draw_content_packs(ui: &mut egui::Ui, project: &mut ProjectState) {
+    const CONTENT_PACKS_INFO: &str = "
+The folders of a content pack are exported in their own bundle, named after the pack, instead of bundle.vecta. \
+The game mounts it with Loader.mountBundle. Packs only mount on the version of the game they were exported with, \
+and on the later versions with the same major version.
+    ";
+    let mut is_edited = false;
+    let mut should_save = false;
+    ui.horizontal(|ui| {
+        ui.label("Game version").on_hover_text(CONTENT_PACKS_INFO);
+        let mut version = project.project_info.version.clone().unwrap_or_default();
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut version)
+                .hint_text("1.0.0")
+                .desired_width(80.0),
+        );
+        if response.changed() {
+            let version = version.trim();
+            project.project_info.version = (!version.is_empty()).then(|| version.to_string());
+            is_edited = true;
+        }
+        should_save |= response.lost_focus();
+    });
+
+    let mut removed_pack = None;
+    for (pack_index, pack) in project.project_info.content_packs.iter_mut().enumerate() {
+        ui.horizontal_wrapped(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut pack.name)
+                    .hint_text("Pack name")
+                    .desired_width(120.0),
+            );
+            is_edited |= response.changed();
+            should_save |= response.lost_focus();
+
+            let mut removed_folder = None;
+            for (folder_index, folder) in pack.folders.iter_mut().enumerate() {
+                let response = ui.add(
+                    egui::TextEdit::singleline(folder)
+                        .hint_text("levels/forest")
+                        .desired_width(120.0),
+                );
+                is_edited |= response.changed();
+                should_save |= response.lost_focus();
+                if ui
+                    .small_button("✖")
+                    .on_hover_text("Remove the folder")
+                    .clicked()
+                {
+                    removed_folder = Some(folder_index);
+                }
+            }
+            if let Some(folder_index) = removed_folder {
+                pack.folders.remove(folder_index);
+                should_save = true;
+            }
+            if ui.button("Add folder").clicked() {
+                pack.folders.push(String::new());
+                should_save = true;
+            }
+            if ui.button("Remove pack").clicked() {
+                removed_pack = Some(pack_index);
+            }
+        });
+    }
+    if let Some(pack_index) = removed_pack {
+        project.project_info.content_packs.remove(pack_index);
+        should_save = true;
+    }
+    if ui
+        .button("Add content pack")
+        .on_hover_text(CONTENT_PACKS_INFO)
+        .clicked()
+    {
+        project
+            .project_info
+            .content_packs
+            .push(ContentPackInfo::default());
+        should_save = true;
+    }
+
+    if is_edited {
+        project.is_project_info_dirty = true;
+    }
+    if should_save && !project.is_read_only {
+        project.save_project_info();
+        project.is_project_info_dirty = false;
+    }
+}
+
 fn draw_editor_export_window(ui: &mut egui::Ui, editor: &mut EditorState) {
     let mut project = editor.project.borrow_mut();
     let project = project.as_mut();
@@ -37,14 +185,20 @@ fn draw_editor_export_window(ui: &mut egui::Ui, editor: &mut EditorState) {
         return;
     };
 
-    let project_file_path = &mut project.project_path;
-    let project_folder = project_file_path
+    let project_folder = project
+        .project_path
         .parent()
-        .expect("Failed to get project folder");
+        .expect("Failed to get project folder")
+        .to_path_buf();
 
     thread_local! {
         static OBFUSCATE_GAME_DATA: RefCell<bool> = const { RefCell::new(true) };
         static TARGET_PLATFORM: RefCell<ExportPlatform> = const { RefCell::new(ExportPlatform::Web) };
+        static EXPORT_SINGLE_HTML_FILE: RefCell<bool> = const { RefCell::new(false) };
+        static SAVE_SIZE_REPORT: RefCell<bool> = const { RefCell::new(false) };
+        static VALIDATION_REPORT: RefCell<Option<AssetValidationReport>> = const { RefCell::new(None) };
+        /// The export messages logged after this are shown under the button.
+        static EXPORT_STARTED_AT: RefCell<Option<Instant>> = const { RefCell::new(None) };
     }
 
     ui_title(ui, "Optimization");
@@ -58,6 +212,27 @@ Read the manual section about obfuscation for more details.
         ui.checkbox(obfuscate_game_data, "Obfuscate game data")
             .on_hover_text(OBFUSCATION_INFO);
     });
+    if OBFUSCATE_GAME_DATA.with_borrow(|b| *b) {
+        SAVE_SIZE_REPORT.with_borrow_mut(|save_size_report| {
+            ui.checkbox(save_size_report, "Save size report")
+                .on_hover_text("Also write the size of the game data per file extension to report.json, next to the export.");
+        });
+    }
+    const PACK_IMAGES_INFO: &str = "
+Pack the images of the game in a few large atlases, so that sprites drawn one after the other share their texture \
+and are drawn together. The images drawn with the same atlas share its filtering.
+    ";
+    if ui
+        .checkbox(
+            &mut project.project_info.pack_images,
+            "Pack images into atlases",
+        )
+        .on_hover_text(PACK_IMAGES_INFO)
+        .changed()
+        && !project.is_read_only
+    {
+        project.save_project_info();
+    }
 
     // -----------------
     ui.add_space(8.0);
@@ -69,6 +244,16 @@ Read the manual section about obfuscation for more details.
             }
         });
     });
+    if TARGET_PLATFORM.with_borrow(|p| *p) == ExportPlatform::Web {
+        EXPORT_SINGLE_HTML_FILE.with_borrow_mut(|single_html_file| {
+            const SINGLE_HTML_FILE_INFO: &str = "
+Also export the game as a single html file that can be opened without a web server. \
+The file is about a third bigger than the zip because the game data is embedded as base64.
+            ";
+            ui.checkbox(single_html_file, "Single HTML file")
+                .on_hover_text(SINGLE_HTML_FILE_INFO);
+        });
+    }
 
     // -----------------
     ui.add_space(8.0);
@@ -80,34 +265,74 @@ Read the manual section about obfuscation for more details.
             .on_hover_text("Open the folder where the exported game will be saved.")
             .clicked()
         {
-            let _ = open::that(project_folder);
+            let _ = open::that(&project_folder);
         }
         ui.label(
             RichText::new(project_folder.display().to_string())
                 .monospace()
-                .color(Color32::WHITE)
-                .background_color(Color32::from_gray(0x22)),
+                .color(Palette::of(ui).code_text)
+                .background_color(Palette::of(ui).code_background),
         );
     });
 
     // -----------------
     ui.add_space(8.0);
+    ui_title(ui, "Content packs");
+    draw_content_packs(ui, project);
 
-    let export_button = egui::Button::new(RichText::new("Export").size(20.0));
+    // -----------------
+    ui.add_space(8.0);
 
-    lazy_static! {
-        static ref EXPORT_LOG_BUFFER: Mutex<String> = Mutex::new(String::new());
-    }
+    let export_button = egui::Button::new(RichText::new("Export").size(20.0));
 
+    let mut should_export = false;
     if export_button.ui(ui).clicked() {
+        let report = validate_project_assets(&project.project_path);
+        // Exporting with missing files needs a second click.
+        should_export = !report.has_errors();
+        VALIDATION_REPORT.set(Some(report));
+    }
+    VALIDATION_REPORT.with_borrow(|report| {
+        let Some(report) = report else {
+            return;
+        };
+        if report.missing.is_empty()
+            && report.not_exported.is_empty()
+            && report.unverifiable.is_empty()
         {
-            let mut log_buffer = EXPORT_LOG_BUFFER.lock().expect("Failed to lock log buffer");
-            log_buffer.clear();
+            return;
         }
+        ui.add_space(8.0);
+        draw_validation_report(ui, report);
+        if report.has_errors() {
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(
+                    Palette::of(ui).error,
+                    "Some files loaded by the scripts are missing from the export.",
+                );
+                if ui.button("Export anyway").clicked() {
+                    should_export = true;
+                }
+            });
+        }
+    });
+
+    if should_export {
+        EXPORT_STARTED_AT.set(Some(Instant::now()));
         let project_path = project.project_path.clone();
         let project_info = project.project_info.clone();
         let obfuscate_data = OBFUSCATE_GAME_DATA.with_borrow(|b| *b);
         let target_platform = TARGET_PLATFORM.with_borrow(|p| *p);
+        let export_single_html_file =
+            target_platform == ExportPlatform::Web && EXPORT_SINGLE_HTML_FILE.with_borrow(|b| *b);
+        let save_size_report = obfuscate_data && SAVE_SIZE_REPORT.with_borrow(|b| *b);
+        print_info_from(
+            Subsystem::Export,
+            format!(
+                "Exporting {} for {}...",
+                project_info.title, target_platform
+            ),
+        );
 
         thread::spawn(move || {
             let result = export_project(
@@ -115,22 +340,72 @@ Read the manual section about obfuscation for more details.
                 &project_info,
                 obfuscate_data,
                 target_platform,
-            );
-            if let Err(err_msg) = result {
-                let mut log_buffer = EXPORT_LOG_BUFFER.lock().expect("Failed to lock log buffer");
-                *log_buffer = format!("Export failed: {}\n", err_msg);
-            } else {
-                let mut log_buffer = EXPORT_LOG_BUFFER.lock().expect("Failed to lock log buffer");
-                *log_buffer = "Export completed successfully.\n".into();
+            )
+            .and_then(|exported_project| {
+                if save_size_report && let Some(bundle_report) = &exported_project.bundle_report {
+                    let report_path = exported_project.output_path.with_file_name("report.json");
+                    std::fs::write(&report_path, bundle_report.to_json()).map_err(|e| {
+                        format!(
+                            "Failed to write the size report to {:?}: {}",
+                            report_path, e
+                        )
+                    })?;
+                }
+                if !export_single_html_file {
+                    return Ok((exported_project, None));
+                }
+                export_project_as_single_html_file(&project_path, &project_info, obfuscate_data)
+                    .map(|output_path| {
+                        (
+                            exported_project,
+                            Some(describe_single_file_export(&output_path)),
+                        )
+                    })
+            });
+            match result {
+                Err(err_msg) => {
+                    print_err_from(Subsystem::Export, format!("Export failed: {}", err_msg));
+                }
+                Ok((exported_project, single_file_description)) => {
+                    print_info_from(
+                        Subsystem::Export,
+                        "Export completed successfully.".to_string(),
+                    );
+                    if let Some(description) = single_file_description {
+                        print_info_from(Subsystem::Export, description);
+                    }
+                    for content_pack in &exported_project.content_packs {
+                        print_info_from(Subsystem::Export, format!("Content pack: {content_pack}"));
+                    }
+                    if let Some(bundle_report) = exported_project.bundle_report {
+                        print_info_from(Subsystem::Export, bundle_report.to_string());
+                    }
+                }
             }
         });
     }
-    {
-        if let Ok(log_buffer) = EXPORT_LOG_BUFFER.try_lock()
-            && !log_buffer.is_empty()
+    if let Some(started_at) = EXPORT_STARTED_AT.with_borrow(|started_at| *started_at) {
+        draw_export_log(ui, started_at);
+    }
+}
+
+/// The messages of the console about the last export.
+fn draw_export_log(ui: &mut egui::Ui, started_at: Instant) {
+    let palette = Palette::of(ui);
+    ui.add_space(8.0);
+    console::get_logs(|message| {
+        if message.subsystem() != Some(Subsystem::Export)
+            || message
+                .last_logged_at()
+                .is_none_or(|logged_at| logged_at < started_at)
         {
-            ui.add_space(8.0);
-            ui.label(RichText::new(&*log_buffer).monospace());
+            return;
         }
-    }
+        let color = match message {
+            ConsoleMessage::Error(_) => palette.error,
+            ConsoleMessage::Warning(_) => palette.warning,
+            _ => palette.text,
+        };
+        ui.label(RichText::new(message.message()).color(color).monospace());
+    });
 }
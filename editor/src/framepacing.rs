@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+use runtime::sdl2::{
+    EventPump, VideoSubsystem,
+    event::Event,
+    video::{GLContext, SwapInterval, Window},
+};
+
+/// When the editor is idle, the loop runs at least this often, about 10 frames per second.
+pub const IDLE_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Both windows share the same OpenGL context. When each window swaps with vsync, the loop waits for two vblanks
+/// and the game runs at half the refresh rate. Only the game window waits for vsync, the editor window swaps immediately.
+///
+/// This also remembers which window is current and the swap interval, to skip driver calls that would do nothing.
+#[derive(Default)]
+pub struct FramePacer {
+    current_window_id: Option<u32>,
+    swap_interval: Option<SwapInterval>,
+    last_editor_frame: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn make_current(&mut self, window: &Window, gl_context: &GLContext) {
+        if self.current_window_id == Some(window.id()) {
+            return;
+        }
+        window
+            .gl_make_current(gl_context)
+            .expect("Failed to make context current");
+        self.current_window_id = Some(window.id());
+    }
+
+    /// Needs to be called when the context was replaced, as the cached state belongs to the old context.
+    pub fn forget_context_state(&mut self) {
+        self.current_window_id = None;
+        self.swap_interval = None;
+    }
+
+    /// Swap the game window, waiting for vsync. The window needs to be current.
+    pub fn swap_game_window(&mut self, video: &VideoSubsystem, window: &Window) {
+        self.set_swap_interval(video, SwapInterval::VSync);
+        window.gl_swap_window();
+    }
+
+    /// Swap the editor window without waiting for vsync, the game window already waits for it.
+    /// The window needs to be current.
+    pub fn swap_editor_window(&mut self, video: &VideoSubsystem, window: &Window) {
+        self.set_swap_interval(video, SwapInterval::Immediate);
+        window.gl_swap_window();
+    }
+
+    /// Returns the pending events. When the editor is idle, this first waits for an event instead of returning right
+    /// away, so that an editor with nothing to do does not spin at the refresh rate. The wait ends as soon as an event
+    /// arrives, after `IDLE_FRAME_INTERVAL`, or at `repaint_deadline` when egui wants to be drawn again before that.
+    pub fn wait_for_events(
+        &mut self,
+        event_pump: &mut EventPump,
+        is_idle: bool,
+        repaint_deadline: Option<Instant>,
+    ) -> Vec<Event> {
+        let mut events = Vec::new();
+        if is_idle {
+            let timeout = repaint_deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+                .map_or(IDLE_FRAME_INTERVAL, |until_repaint| {
+                    until_repaint.min(IDLE_FRAME_INTERVAL)
+                });
+            events.extend(event_pump.wait_event_timeout(timeout.as_millis() as u32));
+        }
+        events.extend(event_pump.poll_iter());
+        events
+    }
+
+    /// Returns false when the editor window should skip this frame because it is over its frame rate limit.
+    pub fn should_render_editor_window(&mut self, max_frame_rate: Option<u32>) -> bool {
+        let now = Instant::now();
+        if let Some(max_frame_rate) = max_frame_rate
+            && let Some(last_editor_frame) = self.last_editor_frame
+            && now.duration_since(last_editor_frame)
+                < Duration::from_secs(1) / max_frame_rate.max(1)
+        {
+            return false;
+        }
+        self.last_editor_frame = Some(now);
+        true
+    }
+
+    fn set_swap_interval(&mut self, video: &VideoSubsystem, interval: SwapInterval) {
+        if self.swap_interval == Some(interval) {
+            return;
+        }
+        // Some drivers do not support changing the interval. In that case, we keep whatever the driver does.
+        let _ = video.gl_set_swap_interval(interval);
+        self.swap_interval = Some(interval);
+    }
+}
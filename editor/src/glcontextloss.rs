@@ -0,0 +1,163 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use runtime::{
+    console::print_err,
+    egui_glow,
+    game::drawable_screen_size,
+    glow::{self, HasContext},
+    graphics::{batchdraw::BatchDraw2d, glerror::take_last_checked_gl_error},
+    sdl2::{
+        self,
+        messagebox::{MessageBoxFlag, show_simple_message_box},
+        video::GLContext,
+    },
+};
+
+use crate::{
+    editorextrawindow::EditorInterfaceWithGl, editorinterface::EditorState, egui_sdl2_platform,
+};
+
+/// Some drivers do not report `CONTEXT_LOST` and make every call fail instead.
+/// Errors on every frame for about 2 seconds are treated as a lost context.
+const CONSECUTIVE_ERROR_FRAMES_BEFORE_LOSS: u32 = 120;
+
+/// A recovery is considered successful when the new context works for this many frames.
+const HEALTHY_FRAMES_AFTER_RECOVERY: u32 = 600;
+
+/// After this many failed recoveries in a row, we give up and exit.
+const MAX_FAILED_RECOVERIES: u32 = 2;
+
+static IS_CONTEXT_LOSS_SIMULATED: AtomicBool = AtomicBool::new(false);
+
+/// Make the watchdog report a lost context on the next frame. This is used to test the recovery path.
+pub fn simulate_context_loss() {
+    IS_CONTEXT_LOSS_SIMULATED.store(true, Ordering::Relaxed);
+}
+
+/// Detects when the OpenGL context was lost, for example after a driver update or when switching GPU on a laptop.
+#[derive(Default)]
+pub struct GlContextWatchdog {
+    consecutive_error_frames: u32,
+    frames_since_recovery: Option<u32>,
+    failed_recoveries: u32,
+}
+
+impl GlContextWatchdog {
+    /// Needs to be called once per frame, after swapping the windows.
+    pub fn is_context_lost(&mut self, gl: &glow::Context) -> bool {
+        if let Some(frames) = self.frames_since_recovery.as_mut() {
+            *frames += 1;
+            if *frames >= HEALTHY_FRAMES_AFTER_RECOVERY {
+                self.frames_since_recovery = None;
+                self.failed_recoveries = 0;
+            }
+        }
+
+        if IS_CONTEXT_LOSS_SIMULATED.swap(false, Ordering::Relaxed) {
+            return true;
+        }
+        // The error checks of the renderer may already have consumed the error.
+        let checked_error = take_last_checked_gl_error();
+        let mut error = unsafe { gl.get_error() };
+        if error == glow::NO_ERROR || checked_error == glow::CONTEXT_LOST {
+            error = checked_error;
+        }
+        if error == glow::CONTEXT_LOST {
+            return true;
+        }
+        if error == glow::NO_ERROR {
+            self.consecutive_error_frames = 0;
+        } else {
+            self.consecutive_error_frames += 1;
+        }
+        self.consecutive_error_frames >= CONSECUTIVE_ERROR_FRAMES_BEFORE_LOSS
+    }
+
+    /// Creates a new OpenGL context and recreates everything that lived on the GPU: the egui painters, the batch
+    /// renderers and the resources of the project.
+    /// If the recovery keeps failing, the editor configuration is saved and the editor exits.
+    pub fn recover(
+        &mut self,
+        gl: &Arc<glow::Context>,
+        painter: &mut egui_glow::Painter,
+        platform: &mut egui_sdl2_platform::Platform,
+        editor_interface: &mut EditorInterfaceWithGl,
+        editor_state: &mut EditorState,
+    ) -> GLContext {
+        // Losing the context again right after a recovery means that the recovery did not work.
+        if self.frames_since_recovery.is_some() {
+            self.failed_recoveries += 1;
+        }
+        loop {
+            if self.failed_recoveries >= MAX_FAILED_RECOVERIES {
+                give_up(editor_state);
+            }
+            match recreate_gl_context(gl, painter, platform, editor_interface, editor_state) {
+                Ok(gl_context) => {
+                    self.consecutive_error_frames = 0;
+                    self.frames_since_recovery = Some(0);
+                    return gl_context;
+                }
+                Err(err) => {
+                    print_err(format!(
+                        "Failed to recover from the OpenGL context loss: {err}"
+                    ));
+                    self.failed_recoveries += 1;
+                }
+            }
+        }
+    }
+}
+
+fn recreate_gl_context(
+    gl: &Arc<glow::Context>,
+    painter: &mut egui_glow::Painter,
+    platform: &mut egui_sdl2_platform::Platform,
+    editor_interface: &mut EditorInterfaceWithGl,
+    editor_state: &mut EditorState,
+) -> Result<GLContext, String> {
+    // The function pointers of `gl` stay valid: SDL creates the new context for the same window, with the same driver.
+    let gl_context = editor_state.window.borrow().gl_create_context()?;
+    editor_state.window.borrow().gl_make_current(&gl_context)?;
+
+    // Everything created with the lost context is forgotten instead of dropped.
+    // The names of the old objects can now belong to objects of the new context, so deleting them would corrupt it.
+    let new_painter =
+        egui_glow::Painter::new(gl.clone(), "", None, true).map_err(|err| err.to_string())?;
+    std::mem::forget(std::mem::replace(painter, new_painter));
+    // egui only sends its font atlas once, so the platform is recreated to upload it again.
+    *platform =
+        egui_sdl2_platform::Platform::new(drawable_screen_size(&editor_state.window.borrow()))
+            .map_err(|err| err.to_string())?;
+
+    let new_interface = EditorInterfaceWithGl::new(&editor_state.editor_specific_window, gl)
+        .map_err(|err| err.to_string())?;
+    std::mem::forget(std::mem::replace(editor_interface, new_interface));
+
+    let new_batch_draw = BatchDraw2d::new(gl)?;
+    std::mem::forget(std::mem::replace(
+        &mut editor_state.editor_batch_draw,
+        new_batch_draw,
+    ));
+
+    if let Some(project) = editor_state.project.borrow_mut().as_mut() {
+        project.game.recreate_gpu_objects()?;
+    }
+    Ok(gl_context)
+}
+
+fn give_up(editor_state: &EditorState) -> ! {
+    editor_state.save_config();
+    let message = "The graphics driver stopped responding and the editor could not recover.\nYour editor settings were saved.\nPlease restart Vectarine.";
+    let _ = show_simple_message_box(
+        MessageBoxFlag::ERROR,
+        "Vectarine - Graphics context lost",
+        message,
+        None::<&sdl2::video::Window>,
+    );
+    eprintln!("{message}");
+    std::process::exit(1);
+}
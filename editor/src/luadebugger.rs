@@ -0,0 +1,333 @@
+//! Breakpoints and stepping through the scripts of the game.
+//!
+//! Luau only calls the interrupt hook on function calls and loop iterations, so the debugger turns on the single step
+//! mode of the VM while it needs to see every line, and the `debugstep` callback decides when to stop. Stopping calls
+//! the pause handler of the editor, which keeps showing the debugger until the user continues. The game is blocked
+//! in the middle of its frame meanwhile.
+
+use std::{
+    cell::RefCell,
+    ffi::{CStr, c_char, c_int},
+    time::{Duration, Instant},
+};
+
+use runtime::mlua::{self, ffi};
+
+use crate::editorconfig::Breakpoint;
+
+/// A recursion can be very deep, only the innermost frames are shown.
+const MAX_SHOWN_FRAMES: c_int = 64;
+const MAX_SHOWN_LOCALS: c_int = 200;
+const MAX_SHOWN_STRING_LENGTH: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepMode {
+    /// Only stops on the breakpoints.
+    Run,
+    /// Stops on the next line that runs, at any depth.
+    Into,
+    /// Stops on the next line of the same function or of a caller.
+    Over { depth: usize },
+    /// Stops on the next line of a caller.
+    Out { depth: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebuggerCommand {
+    Continue,
+    StepOver,
+    StepInto,
+    StepOut,
+}
+
+#[derive(Clone, Debug)]
+pub struct StackFrame {
+    pub function: String,
+    pub file: String,
+    /// None for native functions.
+    pub line: Option<u32>,
+    /// The name and the description of the value of the local variables, in the order of their declaration.
+    pub locals: Vec<(String, String)>,
+}
+
+/// Where the game stopped. The innermost frame is the first one.
+#[derive(Clone, Debug)]
+pub struct PausedState {
+    pub reason: String,
+    pub frames: Vec<StackFrame>,
+}
+
+type PauseHandler = Box<dyn FnMut(&PausedState) -> DebuggerCommand>;
+
+struct LuaDebugger {
+    breakpoints: Vec<Breakpoint>,
+    step_mode: StepMode,
+    /// The line running at each depth of the call stack, so that a line is only stopped on when it starts running,
+    /// and not again when a function called from it returns.
+    current_lines: Vec<Option<(String, u32)>>,
+    paused_duration: Duration,
+    last_resume: Option<Instant>,
+}
+
+impl LuaDebugger {
+    const fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            step_mode: StepMode::Run,
+            current_lines: Vec::new(),
+            paused_duration: Duration::ZERO,
+            last_resume: None,
+        }
+    }
+
+    fn needs_single_step(&self) -> bool {
+        !self.breakpoints.is_empty() || self.step_mode != StepMode::Run
+    }
+
+    /// Called for every instruction. Returns why the game should stop when `line` of `source` starts running.
+    fn on_instruction(&mut self, depth: usize, source: &[u8], line: u32) -> Option<String> {
+        self.current_lines.truncate(depth + 1);
+        self.current_lines.resize(depth + 1, None);
+        let current_line = &mut self.current_lines[depth];
+        if current_line
+            .as_ref()
+            .is_some_and(|(current_source, current)| {
+                *current == line && current_source.as_bytes() == source
+            })
+        {
+            return None;
+        }
+        let source = String::from_utf8_lossy(source).into_owned();
+        let file = source.trim_start_matches(['@', '=']).replace('\\', "/");
+        *current_line = Some((source, line));
+
+        if let Some(breakpoint) = self
+            .breakpoints
+            .iter()
+            .find(|breakpoint| breakpoint.matches(&file, line))
+        {
+            return Some(format!("Breakpoint at {}:{}", breakpoint.file, line));
+        }
+        let should_stop = match self.step_mode {
+            StepMode::Run => false,
+            StepMode::Into => true,
+            StepMode::Over { depth: from } => depth <= from,
+            StepMode::Out { depth: from } => depth < from,
+        };
+        should_stop.then(|| format!("Stopped at {file}:{line}"))
+    }
+}
+
+thread_local! {
+    static DEBUGGER: RefCell<LuaDebugger> = const { RefCell::new(LuaDebugger::new()) };
+    static PAUSE_HANDLER: RefCell<Option<PauseHandler>> = const { RefCell::new(None) };
+}
+
+/// Sets what shows the debugger while the game is stopped. It returns when the user wants the game to go on.
+pub fn set_pause_handler(handler: PauseHandler) {
+    PAUSE_HANDLER.with_borrow_mut(|pause_handler| *pause_handler = Some(handler));
+}
+
+/// Called before every frame of the game, as the breakpoints can change and the game can be reloaded.
+pub fn attach(lua: &mlua::Lua, breakpoints: Vec<Breakpoint>) {
+    let needs_single_step = DEBUGGER.with_borrow_mut(|debugger| {
+        debugger.breakpoints = breakpoints;
+        debugger.current_lines.clear();
+        debugger.needs_single_step()
+    });
+    // SAFETY: the callbacks and the single step flag do not use the stack of the state.
+    let _ = unsafe {
+        lua.exec_raw::<()>((), |state| {
+            (*ffi::lua_callbacks(state)).debugstep = Some(debug_step);
+            ffi::lua_singlestep(state, needs_single_step as c_int);
+        })
+    };
+}
+
+/// Stops on the next line that runs.
+pub fn request_pause() {
+    DEBUGGER.with_borrow_mut(|debugger| debugger.step_mode = StepMode::Into);
+}
+
+/// Lets the current frame of the game end without stopping, like when the editor is being closed.
+pub fn ignore_breakpoints_until_next_frame() {
+    DEBUGGER.with_borrow_mut(|debugger| {
+        debugger.breakpoints.clear();
+        debugger.step_mode = StepMode::Run;
+    });
+}
+
+/// The time spent stopped since the last call, so that the game does not see the pause as a very long frame.
+pub fn take_paused_duration() -> Duration {
+    DEBUGGER.with_borrow_mut(|debugger| std::mem::take(&mut debugger.paused_duration))
+}
+
+/// When the game went on after the last stop, so that the time spent stopped is not seen as an infinite loop.
+pub fn last_resume() -> Option<Instant> {
+    DEBUGGER.with_borrow(|debugger| debugger.last_resume)
+}
+
+unsafe extern "C-unwind" fn debug_step(state: *mut ffi::lua_State, _debug: *mut ffi::lua_Debug) {
+    // SAFETY: the state is the one running the instruction, and the functions below leave its stack as it was.
+    unsafe {
+        let Some(reason) = get_pause_reason(state) else {
+            return;
+        };
+        let paused_state = PausedState {
+            reason,
+            frames: get_stack_frames(state),
+        };
+        let start = Instant::now();
+        // The handler is already running when a script runs while the game is stopped. It does not stop again.
+        let Some(command) = PAUSE_HANDLER.with(|handler| {
+            let mut handler = handler.try_borrow_mut().ok()?;
+            handler.as_mut().map(|handler| handler(&paused_state))
+        }) else {
+            return;
+        };
+        let depth = ffi::lua_stackdepth(state).max(0) as usize;
+        let needs_single_step = DEBUGGER.with_borrow_mut(|debugger| {
+            debugger.paused_duration += start.elapsed();
+            debugger.last_resume = Some(Instant::now());
+            debugger.step_mode = match command {
+                DebuggerCommand::Continue => StepMode::Run,
+                DebuggerCommand::StepInto => StepMode::Into,
+                DebuggerCommand::StepOver => StepMode::Over { depth },
+                DebuggerCommand::StepOut => StepMode::Out { depth },
+            };
+            debugger.needs_single_step()
+        });
+        ffi::lua_singlestep(state, needs_single_step as c_int);
+    }
+}
+
+unsafe fn get_pause_reason(state: *mut ffi::lua_State) -> Option<String> {
+    // SAFETY: level 0 is the function running the instruction.
+    unsafe {
+        let mut debug: ffi::lua_Debug = std::mem::zeroed();
+        if ffi::lua_getinfo(state, 0, c"sl".as_ptr(), &mut debug) == 0
+            || debug.currentline < 0
+            || debug.source.is_null()
+        {
+            return None;
+        }
+        let source = CStr::from_ptr(debug.source).to_bytes();
+        let depth = ffi::lua_stackdepth(state).max(0) as usize;
+        DEBUGGER.with(|debugger| {
+            debugger
+                .try_borrow_mut()
+                .ok()?
+                .on_instruction(depth, source, debug.currentline as u32)
+        })
+    }
+}
+
+unsafe fn to_string(text: *const c_char) -> Option<String> {
+    // SAFETY: the strings of the debug information end with a zero.
+    (!text.is_null()).then(|| {
+        unsafe { CStr::from_ptr(text) }
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+unsafe fn get_stack_frames(state: *mut ffi::lua_State) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+    // SAFETY: every value pushed on the stack is popped before the next one.
+    unsafe {
+        for level in 0..MAX_SHOWN_FRAMES {
+            let mut debug: ffi::lua_Debug = std::mem::zeroed();
+            if ffi::lua_getinfo(state, level, c"sln".as_ptr(), &mut debug) == 0 {
+                break;
+            }
+            let mut locals = Vec::new();
+            for index in 1..=MAX_SHOWN_LOCALS {
+                let Some(name) = to_string(ffi::lua_getlocal(state, level, index)) else {
+                    break;
+                };
+                // Names in parentheses are the hidden variables of the compiler, like the state of a loop.
+                if !name.starts_with('(') {
+                    locals.push((name, describe_value(state, -1)));
+                }
+                ffi::lua_pop(state, 1);
+            }
+            frames.push(StackFrame {
+                function: to_string(debug.name).unwrap_or_else(|| "?".to_string()),
+                file: to_string(debug.short_src).unwrap_or_default(),
+                line: (debug.currentline >= 0).then_some(debug.currentline as u32),
+                locals,
+            });
+        }
+    }
+    frames
+}
+
+/// Describes a value without running any script, as a `__tostring` could change the state of the stopped game.
+unsafe fn describe_value(state: *mut ffi::lua_State, index: c_int) -> String {
+    // SAFETY: the value at `index` exists, and the metafield read is popped.
+    unsafe {
+        let index = ffi::lua_absindex(state, index);
+        match ffi::lua_type(state, index) {
+            ffi::LUA_TNIL => "nil".to_string(),
+            ffi::LUA_TBOOLEAN => (ffi::lua_toboolean(state, index) != 0).to_string(),
+            ffi::LUA_TNUMBER => ffi::lua_tonumber(state, index).to_string(),
+            ffi::LUA_TSTRING => {
+                let mut length = 0;
+                let text = ffi::lua_tolstring(state, index, &mut length);
+                let bytes = std::slice::from_raw_parts(text as *const u8, length);
+                let text = String::from_utf8_lossy(bytes);
+                match text.char_indices().nth(MAX_SHOWN_STRING_LENGTH) {
+                    Some((end, _)) => format!("{:?}...", &text[..end]),
+                    None => format!("{text:?}"),
+                }
+            }
+            ffi::LUA_TVECTOR => {
+                let vector = ffi::lua_tovector(state, index);
+                format!(
+                    "vector({}, {}, {})",
+                    *vector,
+                    *vector.add(1),
+                    *vector.add(2)
+                )
+            }
+            ffi::LUA_TTABLE => format!("table, length {}", ffi::lua_objlen(state, index)),
+            ffi::LUA_TUSERDATA if ffi::luaL_getmetafield(state, index, c"__type".as_ptr()) != 0 => {
+                let name = to_string(ffi::lua_tostring(state, -1)).unwrap_or_default();
+                ffi::lua_pop(state, 1);
+                name
+            }
+            value_type => to_string(ffi::lua_typename(state, value_type)).unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LuaDebugger, StepMode};
+    use crate::editorconfig::Breakpoint;
+
+    #[test]
+    fn lines_are_stopped_on_when_they_start_running() {
+        let mut debugger = LuaDebugger::new();
+        debugger.breakpoints = vec![Breakpoint {
+            file: "scripts/player.luau".to_string(),
+            line: 3,
+        }];
+        let source = b"@/games/demo/scripts/player.luau";
+
+        assert_eq!(debugger.on_instruction(1, source, 2), None);
+        assert!(debugger.on_instruction(1, source, 3).is_some());
+        // The other instructions of the line, and the line again after a call made from it returns.
+        assert_eq!(debugger.on_instruction(1, source, 3), None);
+        assert_eq!(debugger.on_instruction(2, b"@scripts/enemy.luau", 10), None);
+        assert_eq!(debugger.on_instruction(1, source, 3), None);
+
+        debugger.step_mode = StepMode::Over { depth: 1 };
+        assert_eq!(debugger.on_instruction(2, b"@scripts/enemy.luau", 11), None);
+        assert!(debugger.on_instruction(1, source, 4).is_some());
+
+        debugger.step_mode = StepMode::Out { depth: 2 };
+        assert_eq!(debugger.on_instruction(2, b"@scripts/enemy.luau", 12), None);
+        assert!(debugger.on_instruction(1, source, 5).is_some());
+    }
+}
@@ -1,52 +1,132 @@
-use std::{cell::RefCell, rc::Rc, time::Instant};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use runtime::mlua;
 
+use crate::luadebugger;
+
 #[derive(Clone, Debug)]
 pub struct InfiniteLoopError {
     pub file: String,
     pub line: usize,
 }
 
+/// What a script did, as seen by the interrupt hook.
+#[derive(Clone, Debug, Default)]
+pub struct ScriptUsage {
+    pub allocated_total: usize,
+    /// Bytes allocated during the last full second. Steadier than per frame values.
+    pub allocated_last_second: usize,
+    /// Luau interrupts happen on function calls and loop iterations, so this approximates the work done by the script.
+    pub interrupts_last_second: u64,
+    allocated_this_second: usize,
+    interrupts_this_second: u64,
+}
+
+/// Attributes the growth of the Lua heap to the script running when the interrupt hook is called.
+/// This is sampling: memory allocated by a script just before a GC step frees more than that is not counted,
+/// and allocations made between two interrupts are attributed to the script running at the second one.
+pub struct ScriptAttribution {
+    pub scripts: HashMap<String, ScriptUsage>,
+    last_used_memory: usize,
+    second_start: Instant,
+}
+
+impl ScriptAttribution {
+    pub fn new(lua: &mlua::Lua) -> Self {
+        Self {
+            scripts: HashMap::new(),
+            last_used_memory: lua.used_memory(),
+            second_start: Instant::now(),
+        }
+    }
+
+    /// Called before the game runs a frame, so that memory allocated by the engine between frames is not attributed
+    /// to a script.
+    pub fn start_frame(&mut self, lua: &mlua::Lua) {
+        self.last_used_memory = lua.used_memory();
+        self.end_second_if_needed();
+    }
+
+    fn record(&mut self, script: String, used_memory: usize) {
+        let allocated = used_memory.saturating_sub(self.last_used_memory);
+        self.last_used_memory = used_memory;
+        self.end_second_if_needed();
+        let usage = self.scripts.entry(script).or_default();
+        usage.allocated_total += allocated;
+        usage.allocated_this_second += allocated;
+        usage.interrupts_this_second += 1;
+    }
+
+    fn end_second_if_needed(&mut self) {
+        if self.second_start.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        self.second_start = Instant::now();
+        for usage in self.scripts.values_mut() {
+            usage.allocated_last_second = std::mem::take(&mut usage.allocated_this_second);
+            usage.interrupts_last_second = std::mem::take(&mut usage.interrupts_this_second);
+        }
+    }
+}
+
 type HookTiming = Rc<RefCell<Option<Instant>>>;
 type HookError = Rc<RefCell<Option<InfiniteLoopError>>>;
+/// None when the attribution is not shown, so that the hook does not inspect the stack.
+type HookAttribution = Rc<RefCell<Option<ScriptAttribution>>>;
 
-pub fn setup_luau_hooks(lua: &mlua::Lua) -> (HookTiming, HookError) {
+/// Returns the file and line of the innermost function of the stack that is not native code.
+fn get_current_script_location(lua: &mlua::Lua) -> Option<(String, usize)> {
+    for level in 0..10 {
+        let mut location = None;
+        lua.inspect_stack(level, |debug| {
+            let source = debug.source();
+            if let Some(src) = source.short_src.or(source.source)
+                && !src.is_empty()
+                && src != "=[C]"
+            {
+                location = Some((src.to_string(), debug.current_line().unwrap_or(0)));
+            }
+        });
+        if location.is_some() {
+            return location;
+        }
+    }
+    None
+}
+
+pub fn setup_luau_hooks(lua: &mlua::Lua) -> (HookTiming, HookError, HookAttribution) {
     let frame_start_time: HookTiming = Rc::new(RefCell::new(None));
     let hook_error: HookError = Rc::new(RefCell::new(None));
+    let hook_attribution: HookAttribution = Rc::new(RefCell::new(None));
 
     let frame_start_time_for_hook = frame_start_time.clone();
     let hook_error_for_hook = hook_error.clone();
+    let hook_attribution_for_hook = hook_attribution.clone();
 
     lua.set_interrupt(move |lua| {
+        runtime::expression::check_expression_budget()?;
+        if let Some(attribution) = hook_attribution_for_hook.borrow_mut().as_mut() {
+            let script = get_current_script_location(lua)
+                .map_or_else(|| "unknown".to_string(), |(file, _)| file);
+            attribution.record(script, lua.used_memory());
+        }
         // 700ms is a bit long, but sometimes, a frame can be long, like when going to fullscreen.
         // It avoid this, we could have 2 thresholds, one for a specific frame (like 1sec), and one for the average of the last 3 frames (like 500ms).
         // But for now, this works fine.
+        // The time spent stopped in the debugger does not count.
         if frame_start_time_for_hook
             .borrow()
+            .map(|start| luadebugger::last_resume().map_or(start, |resume| resume.max(start)))
             .filter(|s| s.elapsed().as_millis() > 700)
             .is_some()
         {
-            let mut file = "unknown".to_string();
-            let mut line = 0usize;
-
-            for level in 0..10 {
-                let mut found = false;
-                lua.inspect_stack(level, |debug| {
-                    let source = debug.source();
-                    if let Some(src) = source.short_src.or(source.source)
-                        && !src.is_empty()
-                        && src != "=[C]"
-                    {
-                        file = src.to_string();
-                        line = debug.current_line().unwrap_or(0);
-                        found = true;
-                    }
-                });
-                if found {
-                    break;
-                }
-            }
+            let (file, line) =
+                get_current_script_location(lua).unwrap_or_else(|| ("unknown".to_string(), 0));
 
             *hook_error_for_hook.borrow_mut() = Some(InfiniteLoopError { file, line });
 
@@ -57,5 +137,5 @@ pub fn setup_luau_hooks(lua: &mlua::Lua) -> (HookTiming, HookError) {
         Ok(mlua::VmState::Continue)
     });
 
-    (frame_start_time, hook_error)
+    (frame_start_time, hook_error, hook_attribution)
 }
@@ -1,41 +1,69 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{path::PathBuf, sync::mpsc::channel};
+use std::{cell::RefCell, mem::ManuallyDrop, path::PathBuf, rc::Rc, sync::mpsc::channel};
 
 use runtime::{
     egui_glow,
     game::drawable_screen_size,
+    graphics::glerror::{GL_ERROR_ENV_VAR, set_gl_error_checking},
     init_sdl,
     inithelpers::RenderingBlock,
-    io::{localfs::LocalFileSystem, time::now_ms},
+    io::{
+        gamepad::{init_gamepad_system, load_gamepad_mappings_file},
+        localfs::LocalFileSystem,
+        time::now_ms,
+    },
+    lua_env::lua_persist::flush_tool_store_on_crash,
+    scriptdialect::keep_local_names,
     sound::init_sound_system,
 };
+use vectarine_cli::project::geteditorpaths::get_gamepad_mappings_path;
 use vectarine_plugin_sdk::glow::HasContext;
 
 use crate::{
-    editorconfig::WindowStyle,
+    editorconfig::{DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS, WindowStyle},
     editorextrawindow::{
         draw_error_in_game_window, draw_info_in_empty_game_window, send_window_resize_sync_event,
     },
-    editorinterface::{EditorState, clear_window},
+    editorinterface::{EditorState, clear_window, editordebugger::PausedDebugger},
+    framepacing::FramePacer,
+    glcontextloss::GlContextWatchdog,
     reload::reload_assets_if_needed,
 };
 
+pub mod assetimport;
 pub mod buildinfo;
 pub mod editorconfig;
 pub mod editorextrawindow;
 pub mod editorinterface;
+pub mod editortheme;
 pub mod egui_sdl2_platform;
 pub mod export;
+pub mod framepacing;
+pub mod glcontextloss;
+pub mod luadebugger;
 pub mod luau;
 pub mod pluginsystem;
 pub mod projectstate;
 pub mod reload;
 
 fn main() {
+    install_tool_store_flush_on_panic();
     gui_main();
 }
 
+/// Saves the changes of the tool store of the running game before the editor dies, so that a save tested in the game
+/// is not lost with the crash. It is a single small write, done before the default report of the panic.
+fn install_tool_store_flush_on_panic() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if flush_tool_store_on_crash() {
+            eprintln!("The changes of the tool store were saved before the crash.");
+        }
+        default_hook(info);
+    }));
+}
+
 fn get_project_to_open_from_args() -> Option<PathBuf> {
     let args: Vec<String> = std::env::args().collect();
     if args.len() == 2 {
@@ -54,15 +82,20 @@ fn gui_main() {
         sdl,
         video,
         window,
-        mut event_pump,
+        event_pump,
         gl,
-        gl_context,
+        mut gl_context,
     } = init_sdl(|video_subsystem| unsafe {
         egui_glow::painter::Context::from_loader_function(|name| {
             video_subsystem.gl_get_proc_address(name) as *const _
         })
     });
     init_sound_system(&sdl);
+    init_gamepad_system(&sdl);
+    let gamepad_mappings_path = get_gamepad_mappings_path();
+    if gamepad_mappings_path.is_file() {
+        load_gamepad_mappings_file(&gamepad_mappings_path);
+    }
 
     let (editor_window, mut editor_interface) =
         editorextrawindow::create_specific_editor_window(&video, &gl);
@@ -83,6 +116,21 @@ fn gui_main() {
         debounce_event_sender,
     );
 
+    // The debugger shows the local variables of the scripts.
+    keep_local_names(true);
+    let event_pump = Rc::new(RefCell::new(event_pump));
+    let mut paused_debugger = PausedDebugger::new(
+        &sdl,
+        &video,
+        &window,
+        &event_pump,
+        &gl,
+        &editor_state.config,
+    );
+    luadebugger::set_pause_handler(Box::new(move |paused_state| {
+        paused_debugger.run(paused_state)
+    }));
+
     let project_to_open = get_project_to_open_from_args();
     if let Some(project_path) = project_to_open {
         editor_state.load_config(false);
@@ -91,6 +139,12 @@ fn gui_main() {
         editor_state.load_config(true);
     }
 
+    if editor_state.config.borrow().is_gl_error_checking_enabled
+        || std::env::var_os(GL_ERROR_ENV_VAR).is_some()
+    {
+        set_gl_error_checking(true);
+    }
+
     window
         .borrow_mut()
         .set_always_on_top(editor_state.config.borrow().is_always_on_top);
@@ -109,21 +163,50 @@ fn gui_main() {
 
     // The main loop
     let mut start_of_frame = now_ms();
+    let mut context_watchdog = GlContextWatchdog::default();
+    let mut frame_pacer = FramePacer::default();
     loop {
-        let latest_events = event_pump.poll_iter().collect::<Vec<_>>();
+        // Without a project, only the interface changes, and only when an event arrives or egui asks for it.
+        let is_project_loaded = editor_state.project.borrow().is_some();
+        let is_idle = !is_project_loaded && !editor_state.is_repaint_due();
+        // A paused game draws the same frame, it does not need to be drawn as often. It still runs its frames to
+        // reload the scripts and the resources that changed.
+        let is_game_frozen = editor_state
+            .project
+            .borrow()
+            .as_ref()
+            .is_some_and(|project| project.game.is_frozen());
+        let latest_events = frame_pacer.wait_for_events(
+            &mut event_pump.borrow_mut(),
+            is_idle || (is_game_frozen && !editor_state.is_repaint_due()),
+            editor_state.repaint_deadline,
+        );
+        if is_idle && latest_events.is_empty() && !editor_state.is_repaint_due() {
+            // Nothing changed, the windows keep showing the last frame.
+            continue;
+        }
+        let game_window_id = editor_state.window.borrow().id();
+        // Controller, joystick and finger events are not attached to a window, they go to the game. Finger events go
+        // to the editor when its window has the focus, and quitting closes the editor.
+        let is_editor_window_focused = editor_state.editor_specific_window.has_input_focus();
         let (game_window_events, editor_window_events): (Vec<_>, Vec<_>) = latest_events
             .into_iter()
-            .partition(|e| e.get_window_id() == Some(editor_state.window.borrow().id()));
-
-        window
-            .borrow_mut()
-            .gl_make_current(&gl_context)
-            .expect("Failed to make context current");
+            .partition(|e| match e.get_window_id() {
+                Some(window_id) => window_id == game_window_id,
+                None if e.is_touch() => !is_editor_window_focused,
+                None => {
+                    !matches!(e, runtime::sdl2::event::Event::Quit { .. })
+                        && !e.is_keyboard()
+                        && !e.is_mouse()
+                }
+            });
+
+        frame_pacer.make_current(&window.borrow(), &gl_context);
 
         if window.borrow().is_minimized() {
             // Preserve CPU when minimized
             clear_window(&gl);
-            window.borrow().gl_swap_window();
+            frame_pacer.swap_game_window(&video, &window.borrow());
             std::thread::sleep(std::time::Duration::from_millis(100));
             continue;
         }
@@ -134,30 +217,31 @@ fn gui_main() {
         start_of_frame = now_instant;
 
         // Handle basic events
-        editorinterface::handle_close_events(&game_window_events);
+        editorinterface::handle_game_window_close_events(&mut editor_state, &game_window_events);
         editorinterface::handle_close_events(&editor_window_events);
 
         let window_style = editor_state.config.borrow().window_style;
 
+        let mut is_game_stopped = false;
         if let Some(project) = editor_state.project.borrow_mut().as_mut() {
             let game = &mut project.game;
 
             game.load_resource_as_needed();
-            let script_reloaded = reload_assets_if_needed(
+            let reload_summary = reload_assets_if_needed(
                 &gl,
                 &game.lua_env.resources,
                 &game.lua_env,
+                &project.project_path,
                 &debounce_receiver,
+                !editor_state.config.borrow().is_single_script_reload_enabled,
             );
 
-            if script_reloaded {
+            if reload_summary.script_reloaded {
                 *project.hook_error.borrow_mut() = None;
+                project.session_stats.count_reload();
             }
 
-            window
-                .borrow_mut()
-                .gl_make_current(&gl_context)
-                .expect("Failed to make context current");
+            frame_pacer.make_current(&window.borrow(), &gl_context);
             unsafe {
                 let (w, h) = drawable_screen_size(&window.borrow());
                 gl.viewport(0, 0, w as i32, h as i32);
@@ -178,16 +262,43 @@ fn gui_main() {
                     editor_state.editor_want_keyboard,
                 );
 
+                if let Some(attribution) = project.hook_attribution.borrow_mut().as_mut() {
+                    attribution.start_frame(&game.lua_env.lua_handle.lua);
+                }
+                let breakpoints = editor_state
+                    .config
+                    .borrow()
+                    .project_debug_state(Some(&project.project_path))
+                    .breakpoints;
+                luadebugger::attach(&game.lua_env.lua_handle.lua, breakpoints);
                 *project.hook_timing.borrow_mut() = Some(std::time::Instant::now());
                 game.main_loop(game_events, &window, delta_duration, true);
+                is_game_stopped = game.take_quit();
+                // The next frame does not see the time spent stopped in the debugger.
+                start_of_frame += luadebugger::take_paused_duration().as_secs_f64() * 1000.0;
+                project.session_stats.record_frame(delta_duration);
+                // Inside the hook timing, so that an example with an infinite loop is stopped too. A paused game keeps
+                // its last frame in the batch, which the example would flush.
+                if !game.is_paused() {
+                    editorinterface::editorexamples::draw_running_example(game, delta_duration);
+                }
                 *project.hook_timing.borrow_mut() = None;
+                let autosave_seconds = editor_state
+                    .config
+                    .borrow()
+                    .tool_store_autosave_seconds
+                    .unwrap_or(DEFAULT_TOOL_STORE_AUTOSAVE_SECONDS);
+                project
+                    .autosave_tool_store(std::time::Duration::from_secs(autosave_seconds.into()));
             }
+
+            if reload_summary.manifest_changed {
+                project.on_manifest_changed_on_disk();
+            }
+            project.import_changed_assets(reload_summary.changed_files);
         } else {
             // Clear the screen when no project is loaded
-            window
-                .borrow()
-                .gl_make_current(&gl_context)
-                .expect("Failed to make context current");
+            frame_pacer.make_current(&window.borrow(), &gl_context);
             clear_window(&gl);
 
             if window_style == WindowStyle::GameSeparateFromEditor {
@@ -198,42 +309,77 @@ fn gui_main() {
                 );
             }
         }
+        // The game called Io.quit after its window was closed.
+        if is_game_stopped {
+            editor_state.stop_game();
+        }
 
         match window_style {
             WindowStyle::GameSeparateFromEditor => {
                 // We finished drawing the game. If it is separate from the editor, we can swap.
-                window.borrow().gl_swap_window();
-
-                editor_state
-                    .editor_specific_window
-                    .gl_make_current(&gl_context)
-                    .expect("Failed to make context current");
-                clear_window(&gl);
-
-                editorextrawindow::render_editor_in_extra_window(
-                    &sdl,
-                    &gl,
-                    &gl_context,
-                    &mut editor_state,
-                    &mut editor_interface,
-                    &editor_window_events,
-                );
-                editor_state.editor_specific_window.gl_swap_window();
+                frame_pacer.swap_game_window(&video, &window.borrow());
+
+                // When the editor is in the background, it does not need to be as smooth as the game.
+                let max_editor_frame_rate = if editor_state.editor_specific_window.has_input_focus()
+                {
+                    None
+                } else {
+                    editor_state.config.borrow().unfocused_editor_frame_rate
+                };
+                if !editor_window_events.is_empty()
+                    || frame_pacer.should_render_editor_window(max_editor_frame_rate)
+                {
+                    frame_pacer.make_current(&editor_state.editor_specific_window, &gl_context);
+                    clear_window(&gl);
+
+                    editorextrawindow::render_editor_in_extra_window(
+                        &sdl,
+                        &gl,
+                        &mut editor_state,
+                        &mut editor_interface,
+                        &editor_window_events,
+                    );
+                    frame_pacer.swap_editor_window(&video, &editor_state.editor_specific_window);
+                }
             }
             WindowStyle::GameWithEditor => {
                 editor_state.editor_specific_window.hide();
-                window
-                    .borrow()
-                    .gl_make_current(&gl_context)
-                    .expect("Failed to make context current");
+                frame_pacer.make_current(&window.borrow(), &gl_context);
                 editor_state.draw_editor_interface(
                     &mut platform,
                     &sdl,
                     &game_window_events,
                     &mut painter,
                 );
-                window.borrow().gl_swap_window();
+                frame_pacer.swap_game_window(&video, &window.borrow());
             }
         }
+
+        // The game window was swapped by both window styles.
+        if let Some(project) = editor_state.project.borrow().as_ref() {
+            project.game.record_presented_frame();
+        }
+
+        if context_watchdog.is_context_lost(&gl) {
+            runtime::console::print_warn(
+                "The OpenGL context was lost, recreating the graphics resources.".to_string(),
+            );
+            // The lost context is not deleted: some drivers crash when deleting a context that is already gone.
+            gl_context = ManuallyDrop::new(context_watchdog.recover(
+                &gl,
+                &mut painter,
+                &mut platform,
+                &mut editor_interface,
+                &mut editor_state,
+            ));
+            frame_pacer.forget_context_state();
+            send_window_resize_sync_event(&sdl, &video, &window.borrow(), &mut platform);
+            send_window_resize_sync_event(
+                &sdl,
+                &video,
+                &editor_state.editor_specific_window,
+                &mut editor_interface.platform,
+            );
+        }
     }
 }
@@ -1,11 +1,12 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashSet,
     fs,
+    hash::{DefaultHasher, Hash, Hasher},
     path::{Path, PathBuf},
     rc::Rc,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use runtime::{
@@ -17,9 +18,14 @@ use runtime::{
     lua_env::BUILT_IN_MODULES,
     projectinfo::{ProjectInfo, get_project_info},
 };
-use runtime::{io::localfs::LocalFileSystem, sdl2};
+use runtime::{
+    io::{localfs::LocalFileSystem, paths},
+    sdl2,
+};
 
 use crate::{
+    assetimport::AssetImporter,
+    editorinterface::editorstats::SessionStats,
     luau,
     pluginsystem::{
         gameplugin::GamePlugin,
@@ -28,7 +34,7 @@ use crate::{
 };
 
 use vectarine_cli::project::geteditorpaths::{
-    PLUGIN_FILE_EXTENSION, does_path_end_with, get_luau_api_path,
+    PLUGIN_FILE_EXTENSION, does_path_end_with, get_gallery_path, get_luau_api_path,
 };
 
 pub struct ProjectState {
@@ -40,12 +46,51 @@ pub struct ProjectState {
     pub window: Rc<RefCell<sdl2::video::Window>>,
     pub hook_timing: Rc<RefCell<Option<Instant>>>,
     pub hook_error: Rc<RefCell<Option<luau::InfiniteLoopError>>>,
+    pub hook_attribution: Rc<RefCell<Option<luau::ScriptAttribution>>>,
     pub plugins: Rc<RefCell<Vec<GamePlugin>>>,
+    /// True for the projects of the gallery, whose files are not modified by the editor.
+    pub is_read_only: bool,
+
+    /// Hash of the manifest content the editor last read or wrote.
+    /// Used to ignore watcher events triggered by the editor itself.
+    manifest_hash: Cell<u64>,
+    /// True when `project_info` was edited in the editor but not saved to the manifest yet.
+    pub is_project_info_dirty: bool,
+    /// Set when the manifest was changed on disk while the editor had unsaved changes.
+    pub manifest_conflict: Option<ManifestConflict>,
+    pub asset_importer: AssetImporter,
+    pub session_stats: SessionStats,
+    last_tool_store_autosave: Instant,
+}
+
+pub struct ManifestConflict {
+    pub disk_content: String,
+    pub editor_content: String,
+}
+
+fn is_gallery_project(project_path: &Path) -> bool {
+    let gallery_path = get_gallery_path();
+    let gallery_path = paths::canonicalize(&gallery_path).unwrap_or(gallery_path);
+    let project_path =
+        paths::canonicalize(project_path).unwrap_or_else(|_| project_path.to_path_buf());
+    project_path.starts_with(gallery_path)
+}
+
+pub fn hash_manifest_content(content: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
 }
 
 impl ProjectState {
     pub fn reload(&mut self) {
         console::print_reload();
+        self.session_stats.count_project_reload();
+        // The reloaded game reads the tool store from the disk.
+        self.save_tool_store();
+        crate::editorinterface::editorexamples::stop_running_example();
+        // The patterns belong to the game being replaced, the gamepads should not keep rumbling.
+        self.game.lua_env.env_state.borrow_mut().rumble.stop_all();
         let gl = self.game.gl.clone();
         Game::from_project(
             &self.project_path,
@@ -58,10 +103,11 @@ impl ProjectState {
                 let Ok(game) = result else {
                     return;
                 };
-                let (hook_timing, hook_error) =
+                let (hook_timing, hook_error, hook_attribution) =
                     luau::setup_luau_hooks(&game.lua_env.lua_handle.lua);
                 self.hook_timing = hook_timing;
                 self.hook_error = hook_error;
+                self.hook_attribution = hook_attribution;
                 self.game = game;
             },
         );
@@ -110,9 +156,9 @@ impl ProjectState {
                     )));
                     return;
                 };
-                let (hook_timing, hook_error) =
+                let (hook_timing, hook_error, hook_attribution) =
                     luau::setup_luau_hooks(&game.lua_env.lua_handle.lua);
-                let result = Self {
+                let mut result = Self {
                     project_path: project_path.to_path_buf(),
                     project_info,
                     game,
@@ -120,14 +166,42 @@ impl ProjectState {
                     window,
                     hook_timing,
                     hook_error,
+                    hook_attribution,
                     plugins: Rc::new(RefCell::new(Vec::new())),
+                    is_read_only: is_gallery_project(project_path),
+                    manifest_hash: Cell::new(hash_manifest_content(&project_manifest_content)),
+                    is_project_info_dirty: false,
+                    manifest_conflict: None,
+                    asset_importer: AssetImporter::new(
+                        project_path.parent().unwrap_or(Path::new(".")),
+                    ),
+                    session_stats: SessionStats::default(),
+                    last_tool_store_autosave: Instant::now(),
                 };
                 result.refresh_plugin_list(trusted_plugins);
+                // The sources may have changed while the editor was closed.
+                result.import_assets(false);
                 callback(Ok(result));
             },
         );
     }
 
+    /// Writes the changes of the tool store of the game when `interval` passed since the last save, so that they
+    /// survive a crash of the editor.
+    pub fn autosave_tool_store(&mut self, interval: Duration) {
+        if self.last_tool_store_autosave.elapsed() >= interval {
+            self.save_tool_store();
+        }
+    }
+
+    pub fn save_tool_store(&mut self) {
+        self.last_tool_store_autosave = Instant::now();
+        if self.game.lua_env.tool_store.flush() {
+            self.session_stats.count_autosave();
+            console::print_info("Saved the changes of the tool store".to_string());
+        }
+    }
+
     pub fn project_folder(&self) -> Option<&Path> {
         self.project_path.parent()
     }
@@ -267,8 +341,9 @@ impl ProjectState {
         let _ = fs::copy(&plugin.path, project_plugins_folder.join(plugin_name));
     }
 
+    /// The new list is written to the manifest by the next save of the project info.
     pub fn update_plugins_in_project_info(&mut self) {
-        self.project_info.plugins = self
+        let plugins = self
             .plugins
             .borrow()
             .iter()
@@ -277,7 +352,11 @@ impl ProjectState {
                 let filename = plugin.dynamic_library_path.file_prefix()?;
                 Some(filename.to_string_lossy().to_string())
             })
-            .collect();
+            .collect::<Vec<_>>();
+        if plugins != self.project_info.plugins {
+            self.project_info.plugins = plugins;
+            self.is_project_info_dirty = true;
+        }
     }
 
     pub fn for_each_trusted_plugin<F>(&self, mut callback: F)
@@ -309,31 +388,118 @@ impl ProjectState {
 
     /// Save the project info from RAM to the project manifest file while trying to preserve comments general order of keys.
     pub fn save_project_info(&self) {
+        self.write_manifest(&self.project_info_as_manifest());
+    }
+
+    /// Serialize the project info into a manifest, keeping the comments and unknown keys of the manifest on disk if possible.
+    pub fn project_info_as_manifest(&self) -> String {
+        let toml_string = vectarine_plugin_sdk::toml::to_string(&self.project_info)
+            .expect("Unable to serialize the ProjectInfo type to toml");
         let Ok(current_project_info) = fs::read_to_string(&self.project_path) else {
-            self.save_project_info_by_overwriting();
-            return;
+            return toml_string;
         };
         let Ok(mut document) = current_project_info.parse::<toml_edit::DocumentMut>() else {
-            self.save_project_info_by_overwriting();
-            return;
+            return toml_string;
         };
-        let toml_string = vectarine_plugin_sdk::toml::to_string(&self.project_info)
-            .expect("Unable to serialize the ProjectInfo type to toml");
         let target_document = toml_string
             .parse::<toml_edit::DocumentMut>()
             .expect("Unable to parse the toml string generated by toml");
         for (key, value) in target_document.iter() {
             document[key] = value.clone();
         }
-        let toml_string = document.to_string();
-        let _ = fs::write(&self.project_path, toml_string);
+        document.to_string()
     }
 
-    /// Save the project info while erasing the existing file and its fields.
-    fn save_project_info_by_overwriting(&self) {
-        let toml_string = vectarine_plugin_sdk::toml::to_string(&self.project_info)
-            .expect("Unable to serialize the ProjectInfo type to toml");
-        let _ = fs::write(&self.project_path, toml_string);
+    fn write_manifest(&self, content: &str) {
+        // The hash is recorded before writing so that the watcher event caused by this write is ignored.
+        self.manifest_hash.set(hash_manifest_content(content));
+        let _ = fs::write(&self.project_path, content);
+    }
+
+    /// Called when the watcher reports a change of the manifest.
+    /// Changes made by the editor itself are ignored. If the editor has no unsaved changes, the project info is
+    /// refreshed from disk, otherwise a conflict is recorded so that the user can choose which version to keep.
+    pub fn on_manifest_changed_on_disk(&mut self) {
+        let Ok(disk_content) = fs::read_to_string(&self.project_path) else {
+            return;
+        };
+        if hash_manifest_content(&disk_content) == self.manifest_hash.get() {
+            return;
+        }
+        if self.is_project_info_dirty {
+            self.manifest_conflict = Some(ManifestConflict {
+                editor_content: self.project_info_as_manifest(),
+                disk_content,
+            });
+            return;
+        }
+        self.reload_project_info_from(&disk_content);
+    }
+
+    /// Replace the project info in RAM by the one described by the given manifest content.
+    pub fn reload_project_info_from(&mut self, disk_content: &str) {
+        self.manifest_hash.set(hash_manifest_content(disk_content));
+        self.manifest_conflict = None;
+        match get_project_info(disk_content) {
+            Ok(project_info) => {
+                self.project_info = project_info;
+                self.is_project_info_dirty = false;
+                // The import rules may have changed.
+                self.import_assets(false);
+                self.apply_collision_layers();
+                self.game
+                    .lua_env
+                    .deterministic_physics
+                    .set(self.project_info.deterministic_physics);
+                console::print_info(format!(
+                    "{} was changed on disk, the project settings were reloaded.",
+                    self.project_path.to_string_lossy()
+                ));
+            }
+            Err(_) => {
+                console::print_warn(format!(
+                    "{} was changed on disk but could not be parsed.",
+                    self.project_path.to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    /// Gives the collision layers of the project info to the running game. The worlds update the objects created with a
+    /// layer on their next step.
+    pub fn apply_collision_layers(&self) {
+        self.game
+            .lua_env
+            .collision_layers
+            .borrow_mut()
+            .set(self.project_info.physics.layers.clone());
+    }
+
+    /// Runs the import rules on the sources that changed since their last conversion, or on all of them with `force`.
+    pub fn import_assets(&mut self, force: bool) {
+        if self.is_read_only {
+            return;
+        }
+        self.asset_importer
+            .import_all(&self.project_path, &self.project_info, force);
+    }
+
+    /// Runs the import rules on the files reported by the watcher, and reports the conversions that finished.
+    pub fn import_changed_assets(&mut self, changed_files: Vec<PathBuf>) {
+        if self.is_read_only {
+            return;
+        }
+        self.asset_importer
+            .import(&self.project_path, &self.project_info, changed_files, false);
+        self.asset_importer
+            .poll(&self.project_path, &self.project_info);
+    }
+
+    /// Resolve a manifest conflict by writing the editor version over the one on disk.
+    pub fn overwrite_manifest_with_editor_version(&mut self) {
+        self.manifest_conflict = None;
+        self.save_project_info();
+        self.is_project_info_dirty = false;
     }
 }
 
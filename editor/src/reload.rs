@@ -1,4 +1,8 @@
-use std::{rc::Rc, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
 
 use notify_debouncer_full::{
     DebouncedEvent,
@@ -9,21 +13,46 @@ use notify_debouncer_full::{
 };
 use runtime::glow;
 use runtime::{
-    game_resource::{ResourceManager, Status, script_resource::ScriptResource},
-    lua_env::LuaEnvironment,
+    console::print_info,
+    game_resource::{
+        ResourceId, ResourceManager, Status, script_graph::ReloadPlan,
+        script_resource::ScriptResource,
+    },
+    io::paths,
+    lua_env::{LuaEnvironment, lua_persist::EDITOR_CACHE_FOLDER, print_lua_error_from_error},
 };
 
+use crate::editorinterface::editorfind;
+
+pub struct ReloadSummary {
+    /// True if any script resource was reloaded
+    pub script_reloaded: bool,
+    /// True if the project manifest (game.vecta) was modified
+    pub manifest_changed: bool,
+    /// The files modified, for the import rules
+    pub changed_files: Vec<PathBuf>,
+}
+
 // Reload assets corresponding to changed file as needed without blocking
-// Returns true if any script resource was reloaded
+// With `reload_dependents`, the scripts loading an edited script run again after it.
 pub fn reload_assets_if_needed(
     gl: &Arc<glow::Context>,
     resources: &Rc<ResourceManager>,
     lua_for_reload: &LuaEnvironment,
+    manifest_path: &Path,
     debounce_receiver: &std::sync::mpsc::Receiver<DebouncedEvent>,
-) -> bool {
-    let mut script_reloaded = false;
+    reload_dependents: bool,
+) -> ReloadSummary {
+    let mut changed_scripts = Vec::new();
+    let mut manifest_changed = false;
+    let mut changed_files = Vec::new();
+    let manifest_path =
+        paths::canonicalize(manifest_path).unwrap_or_else(|_| manifest_path.to_path_buf());
 
     for event in debounce_receiver.try_iter() {
+        // Any change can add or remove search results, even the creation or the deletion of a file.
+        editorfind::invalidate_find_results();
+
         // Only file modification matters, no creation, deletion, etc...
         let EventKind::Modify(modify) = event.kind else {
             continue;
@@ -42,6 +71,21 @@ pub fn reload_assets_if_needed(
         }
 
         for path in event.event.paths {
+            // Files written by the editor itself for its own purposes are never resources.
+            if path
+                .components()
+                .any(|c| c.as_os_str() == EDITOR_CACHE_FOLDER)
+            {
+                continue;
+            }
+            if path == manifest_path || paths::canonicalize(&path).is_ok_and(|p| p == manifest_path)
+            {
+                manifest_changed = true;
+                continue;
+            }
+            if !changed_files.contains(&path) {
+                changed_files.push(path.clone());
+            }
             // Check if a resource is in the list of path
             // If so, and the resource is in an unloaded / loaded state, load it.
             if let Some(res_id) = resources.get_id_by_path(&path) {
@@ -51,9 +95,12 @@ pub fn reload_assets_if_needed(
                     res_status,
                     Status::Unloaded | Status::Loaded | Status::Error(_)
                 ) {
-                    // Check if this is a script resource
+                    // Scripts are run again together once all the events are handled.
                     if resources.get_by_id::<ScriptResource>(res_id).is_ok() {
-                        script_reloaded = true;
+                        if !changed_scripts.contains(&res_id) {
+                            changed_scripts.push(res_id);
+                        }
+                        continue;
                     }
 
                     resources.reload(
@@ -67,5 +114,85 @@ pub fn reload_assets_if_needed(
         }
     }
 
-    script_reloaded
+    let script_reloaded = !changed_scripts.is_empty();
+    if script_reloaded {
+        reload_scripts(
+            gl,
+            resources,
+            lua_for_reload,
+            &changed_scripts,
+            reload_dependents,
+        );
+    }
+
+    ReloadSummary {
+        script_reloaded,
+        manifest_changed,
+        changed_files,
+    }
+}
+
+/// Runs the changed scripts again, then the scripts loading them so that they do not keep values computed with the
+/// previous version. The scripts keep their export tables, and the scriptsReloaded event is fired once at the end.
+fn reload_scripts(
+    gl: &Arc<glow::Context>,
+    resources: &Rc<ResourceManager>,
+    lua_for_reload: &LuaEnvironment,
+    changed_scripts: &[ResourceId],
+    reload_dependents: bool,
+) {
+    let plan = if reload_dependents {
+        resources
+            .script_graph
+            .borrow()
+            .reload_order(changed_scripts)
+    } else {
+        ReloadPlan {
+            order: changed_scripts.to_vec(),
+            cycles: Vec::new(),
+        }
+    };
+    let script_path = |id: ResourceId| {
+        resources
+            .get_holder_by_id(id)
+            .get_path()
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    for cycle in &plan.cycles {
+        let paths = cycle.iter().map(|id| script_path(*id)).collect::<Vec<_>>();
+        print_info(format!(
+            "{} load each other, they all run again in no particular order.",
+            paths.join(", ")
+        ));
+    }
+
+    let mut reloaded_paths = Vec::new();
+    for id in plan.order {
+        // A script still loading for the first time will run with the new content anyway.
+        if resources.get_holder_by_id(id).is_loading() {
+            continue;
+        }
+        resources.reload(
+            id,
+            gl.clone(),
+            lua_for_reload.lua_handle.clone(),
+            lua_for_reload.default_events.resource_loaded_event.clone(),
+        );
+        reloaded_paths.push(script_path(id));
+    }
+
+    let lua_handle = &lua_for_reload.lua_handle;
+    let result = lua_handle
+        .lua
+        .create_sequence_from(reloaded_paths)
+        .and_then(|paths| {
+            lua_for_reload
+                .default_events
+                .scripts_reloaded_event
+                .trigger(runtime::mlua::Value::Table(paths))
+        });
+    if let Err(err) = result {
+        print_lua_error_from_error(lua_handle, &err);
+    }
 }
@@ -0,0 +1,160 @@
+//! Compares the broad phases on the entities of a bullet-hell game: a lot of small entities of similar sizes.
+//!
+//! Run with `cargo bench -p runtime --bench broadphase`.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+use runtime::{
+    math::Vect,
+    space::{Aabb, BroadPhase, Dbvh, EntityId, SpatialHash, suggested_cell_size},
+};
+use std::hint::black_box;
+
+const ENTITY_COUNTS: [usize; 3] = [1_000, 10_000, 100_000];
+
+struct Entity {
+    id: EntityId,
+    position: Vect<2>,
+    radius: f32,
+}
+
+/// The entities are spread so that the density, and so the number of overlapping pairs, does not depend on the count.
+fn entities(count: usize) -> Vec<Entity> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut random = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state >> 40) as f32 / (1u64 << 24) as f32
+    };
+    let side = (count as f32).sqrt() * 10.0;
+    (0..count)
+        .map(|id| Entity {
+            id: id as EntityId,
+            position: Vect([random() * side, random() * side]),
+            radius: 1.0 + random() * 2.0,
+        })
+        .collect()
+}
+
+fn build<B: BroadPhase<2>>(mut broad_phase: B, entities: &[Entity]) -> B {
+    for entity in entities {
+        broad_phase.insert(entity.id, entity.position, entity.radius);
+    }
+    broad_phase
+}
+
+fn new_spatial_hash(entities: &[Entity]) -> SpatialHash<2> {
+    SpatialHash::new(suggested_cell_size(
+        entities.iter().map(|entity| entity.radius),
+    ))
+}
+
+fn bench_insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    group.sample_size(10);
+    for count in ENTITY_COUNTS {
+        let entities = entities(count);
+        group.bench_with_input(
+            BenchmarkId::new("SpatialHash", count),
+            &entities,
+            |b, entities| {
+                b.iter(|| build(new_spatial_hash(entities), entities));
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("Dbvh", count), &entities, |b, entities| {
+            b.iter(|| build(Dbvh::new(), entities));
+        });
+    }
+    group.finish();
+}
+
+/// Moves every entity by a bullet step, as a game does every frame.
+fn bench_update(c: &mut Criterion) {
+    fn step<B: BroadPhase<2>>(broad_phase: &mut B, entities: &[Entity]) {
+        for entity in entities {
+            let position = entity.position + Vect([0.5, 0.25]);
+            broad_phase.update(entity.id, position, entity.radius);
+        }
+    }
+    let mut group = c.benchmark_group("update");
+    group.sample_size(10);
+    for count in ENTITY_COUNTS {
+        let entities = entities(count);
+        group.bench_with_input(
+            BenchmarkId::new("SpatialHash", count),
+            &entities,
+            |b, entities| {
+                b.iter_batched_ref(
+                    || build(new_spatial_hash(entities), entities),
+                    |broad_phase| step(broad_phase, entities),
+                    BatchSize::LargeInput,
+                );
+            },
+        );
+        group.bench_with_input(BenchmarkId::new("Dbvh", count), &entities, |b, entities| {
+            b.iter_batched_ref(
+                || build(Dbvh::new(), entities),
+                |broad_phase| step(broad_phase, entities),
+                BatchSize::LargeInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+fn bench_query(c: &mut Criterion) {
+    fn query<B: BroadPhase<2>>(broad_phase: &B, entities: &[Entity]) -> usize {
+        let mut found = 0;
+        // A circle query around a hundred entities, like the player checking for bullets.
+        for entity in entities.iter().step_by(entities.len() / 100) {
+            broad_phase.query_circle(entity.position, 20.0, |_| found += 1);
+            let min = entity.position - Vect([20.0, 20.0]);
+            broad_phase.query_aabb(Aabb::new(min, min + Vect([40.0, 40.0])), |_| found += 1);
+        }
+        found
+    }
+    let mut group = c.benchmark_group("query");
+    for count in ENTITY_COUNTS {
+        let entities = entities(count);
+        let spatial_hash = build(new_spatial_hash(&entities), &entities);
+        let dbvh = build(Dbvh::new(), &entities);
+        group.bench_function(BenchmarkId::new("SpatialHash", count), |b| {
+            b.iter(|| query(black_box(&spatial_hash), &entities));
+        });
+        group.bench_function(BenchmarkId::new("Dbvh", count), |b| {
+            b.iter(|| query(black_box(&dbvh), &entities));
+        });
+    }
+    group.finish();
+}
+
+fn bench_pairs(c: &mut Criterion) {
+    fn count_pairs<B: BroadPhase<2>>(broad_phase: &B) -> usize {
+        let mut pairs = 0;
+        broad_phase.for_each_pair(|_, _| pairs += 1);
+        pairs
+    }
+    let mut group = c.benchmark_group("for_each_pair");
+    group.sample_size(10);
+    for count in ENTITY_COUNTS {
+        let entities = entities(count);
+        let spatial_hash = build(new_spatial_hash(&entities), &entities);
+        let dbvh = build(Dbvh::new(), &entities);
+        group.bench_function(BenchmarkId::new("SpatialHash", count), |b| {
+            b.iter(|| count_pairs(black_box(&spatial_hash)));
+        });
+        group.bench_function(BenchmarkId::new("Dbvh", count), |b| {
+            b.iter(|| count_pairs(black_box(&dbvh)));
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_insert,
+    bench_update,
+    bench_query,
+    bench_pairs
+);
+criterion_main!(benches);
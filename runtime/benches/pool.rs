@@ -0,0 +1,93 @@
+//! Compares spawning and despawning the bullets of a bullet-hell game from an `ObjectPool2` against creating and
+//! removing them with `createObject` and `removeObject`.
+//!
+//! Run with `cargo bench -p runtime --bench pool`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use runtime::{
+    game_resource::ResourceManager,
+    lua_env::{
+        lua_physics::{
+            inspector::PhysicsWorldRegistry, layers::SharedCollisionLayers, setup_physics_api,
+        },
+        lua_vec2,
+    },
+};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+use vectarine_plugin_sdk::mlua::{Function, Lua};
+
+const BULLET_COUNT: usize = 1_000;
+
+const SCENE: &str = r#"
+    local world = Physics.newWorld2(Vec.V2(0, 0))
+    local collider = Physics.newCircleCollider(0.05)
+    local pool = world:createPool({ collider = collider, tags = { "bullet" }, size = BULLET_COUNT })
+
+    function WithPool()
+        local bullets = {}
+        for i = 1, BULLET_COUNT do
+            bullets[i] = pool:spawn(Vec.V2(i * 0.1, 0), Vec.V2(0, 10))
+        end
+        world:step(1 / 60)
+        for i = 1, BULLET_COUNT do
+            pool:despawn(bullets[i])
+        end
+    end
+
+    function WithoutPool()
+        local bullets = {}
+        for i = 1, BULLET_COUNT do
+            local bullet = world:createObject(Vec.V2(i * 0.1, 0), 1, collider, { "bullet" }, "dynamic")
+            bullet.speed = Vec.V2(0, 10)
+            bullets[i] = bullet
+        end
+        world:step(1 / 60)
+        for i = 1, BULLET_COUNT do
+            world:removeObject(bullets[i])
+        end
+    end
+"#;
+
+fn new_lua() -> Lua {
+    let lua = Lua::new();
+    let vec = lua_vec2::setup_vec_api(&lua).expect("Vec API");
+    let physics = setup_physics_api(
+        &lua,
+        &Rc::new(ResourceManager::dummy_manager()),
+        &Rc::new(RefCell::new(SharedCollisionLayers::default())),
+        &Rc::new(Cell::new(false)),
+        &Rc::new(Cell::new(None)),
+        &Rc::new(RefCell::new(PhysicsWorldRegistry::default())),
+    )
+    .expect("Physics API");
+    lua.globals().set("Vec", vec).expect("Set Vec");
+    lua.globals().set("Physics", physics).expect("Set Physics");
+    lua.globals()
+        .set("BULLET_COUNT", BULLET_COUNT)
+        .expect("Set BULLET_COUNT");
+    lua.load(SCENE).exec().expect("Valid scene");
+    lua
+}
+
+fn bench_spawn(c: &mut Criterion) {
+    let lua = new_lua();
+    let with_pool = lua.globals().get::<Function>("WithPool").expect("WithPool");
+    let without_pool = lua
+        .globals()
+        .get::<Function>("WithoutPool")
+        .expect("WithoutPool");
+    let mut group = c.benchmark_group("spawn_and_despawn");
+    group.bench_function("ObjectPool2", |b| {
+        b.iter(|| with_pool.call::<()>(()).expect("WithPool runs"));
+    });
+    group.bench_function("createObject", |b| {
+        b.iter(|| without_pool.call::<()>(()).expect("WithoutPool runs"));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_spawn);
+criterion_main!(benches);
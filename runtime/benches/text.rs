@@ -0,0 +1,116 @@
+//! Compares the CPU work of submitting text: the quads of the regular text against the instances of `Text.drawFast`.
+//! The strings are 2,000 distinct 8-character strings, like the damage numbers of a busy frame.
+//!
+//! Run with `cargo bench -p runtime --bench text`.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use runtime::{
+    game_resource::font_resource::CharacterInfo,
+    graphics::{
+        affinetransform::AffineTransform,
+        batchdraw::text_vertices,
+        instancedtext::{GlyphInstanceInfo, push_text_instances},
+    },
+};
+use std::{collections::HashMap, hint::black_box};
+
+const STRING_COUNT: usize = 2_000;
+const CHARSET: &str = "abcdefghijklmnopqrstuvwxyz0123456789";
+const ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+fn strings() -> Vec<String> {
+    let charset = CHARSET.chars().collect::<Vec<_>>();
+    (0..STRING_COUNT)
+        .map(|i| {
+            (0..8)
+                .map(|j| charset[(i * 7 + j * 13 + i / 36) % charset.len()])
+                .collect()
+        })
+        .collect()
+}
+
+/// Glyphs with the sizes of a 64px font. Only the metrics matter, nothing is rasterized.
+fn font_cache() -> HashMap<char, CharacterInfo> {
+    CHARSET
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let metrics = fontdue::Metrics {
+                advance_width: 36.0,
+                bounds: fontdue::OutlineBounds {
+                    xmin: 2.0,
+                    ymin: -1.0,
+                    width: 32.0,
+                    height: 46.0,
+                },
+                ..Default::default()
+            };
+            let info = CharacterInfo {
+                metrics,
+                atlas_x: (i % 6) as f32 / 6.0,
+                atlas_y: (i / 6) as f32 / 6.0,
+                atlas_width: 1.0 / 6.0,
+                atlas_height: 1.0 / 6.0,
+            };
+            (c, info)
+        })
+        .collect()
+}
+
+fn bench_text_submission(c: &mut Criterion) {
+    let strings = strings();
+    let font_cache = font_cache();
+    let glyphs = font_cache
+        .iter()
+        .enumerate()
+        .map(|(index, (c, info))| {
+            let glyph = GlyphInstanceInfo {
+                index: index as f32,
+                advance_width: info.metrics.advance_width,
+            };
+            (*c, glyph)
+        })
+        .collect::<HashMap<_, _>>();
+    let transform = AffineTransform::identity();
+    let scale = 0.05 / 64.0;
+
+    let mut group = c.benchmark_group("text_submission");
+    group.bench_function("quads", |b| {
+        b.iter(|| {
+            let mut vertices = Vec::new();
+            let mut indices = Vec::new();
+            for (i, text) in strings.iter().enumerate() {
+                let y = i as f32 / STRING_COUNT as f32;
+                let (text_vertices, text_indices) =
+                    text_vertices(&transform, &font_cache, -0.5, y, text, scale, ASPECT_RATIO);
+                let offset = vertices.len() as u32 / 4;
+                vertices.extend_from_slice(&text_vertices);
+                indices.extend(text_indices.iter().map(|index| index + offset));
+            }
+            black_box((vertices, indices))
+        });
+    });
+    group.bench_function("instances", |b| {
+        b.iter(|| {
+            let mut instances = Vec::new();
+            for (i, text) in strings.iter().enumerate() {
+                let y = i as f32 / STRING_COUNT as f32;
+                push_text_instances(
+                    &mut instances,
+                    &glyphs,
+                    -0.5,
+                    y,
+                    text,
+                    scale,
+                    ASPECT_RATIO,
+                    [1.0; 4],
+                );
+            }
+            black_box(instances)
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_text_submission);
+criterion_main!(benches);
@@ -0,0 +1,187 @@
+use std::{cell::RefCell, collections::BTreeMap};
+
+use vectarine_plugin_sdk::anyhow;
+use vectarine_plugin_sdk::mlua::{self, Lua, MultiValue};
+
+use crate::console::print_warn;
+use crate::projectinfo::ProjectInfo;
+
+/// The version of the Lua API of this engine. Increase it when a function of a `@vectarine` module is renamed or
+/// removed, and add the old name to `DEPRECATED_FUNCTIONS` so that the projects written for older versions still run.
+pub const CURRENT_API_VERSION: u32 = 1;
+
+/// The version of the projects created before `api_version` was added to game.vecta.
+pub const FIRST_API_VERSION: u32 = 1;
+
+/// A function of a `@vectarine` module that was renamed. The names are the name of the module followed by the name of
+/// the function, like "graphics.drawRect".
+#[derive(Debug)]
+pub struct DeprecatedFunction {
+    pub old_name: &'static str,
+    pub new_name: &'static str,
+    /// The first version of the API without the old name. Projects declaring an older version get a shim calling
+    /// the new function.
+    pub removed_in: u32,
+}
+
+pub const DEPRECATED_FUNCTIONS: &[DeprecatedFunction] = &[];
+
+/// A deprecated function called by the project, with the lines calling it and the number of calls from each line.
+#[derive(Debug, Clone)]
+pub struct DeprecatedCall {
+    pub old_name: &'static str,
+    pub new_name: &'static str,
+    pub call_sites: BTreeMap<String, usize>,
+}
+
+thread_local! {
+    static DEPRECATED_CALLS: RefCell<BTreeMap<&'static str, DeprecatedCall>> =
+        const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Refuses the projects written for a version of the API that this engine does not know.
+pub fn check_api_version(project_info: &ProjectInfo) -> anyhow::Result<()> {
+    let api_version = project_info.api_version();
+    if api_version > CURRENT_API_VERSION {
+        return Err(anyhow::anyhow!(
+            "This project uses version {api_version} of the Vectarine API, but this version of Vectarine only supports up to version {CURRENT_API_VERSION}. Update Vectarine to open it."
+        ));
+    }
+    Ok(())
+}
+
+/// Adds the old names of the renamed functions to the modules, for the projects declaring a version of the API older
+/// than their removal. The old names call the new functions and print a warning the first time each line calls them.
+pub fn install_deprecation_shims(
+    lua: &Lua,
+    api_version: u32,
+    deprecations: &'static [DeprecatedFunction],
+) -> mlua::Result<()> {
+    let require = lua.globals().get::<mlua::Function>("require")?;
+    let get_function = |name: &str| -> mlua::Result<(mlua::Table, String)> {
+        let (module_name, function_name) = name.split_once('.').ok_or_else(|| {
+            mlua::Error::runtime(format!("{name} is not a module followed by a function"))
+        })?;
+        let module = require.call::<mlua::Table>(format!("@vectarine/{module_name}"))?;
+        Ok((module, function_name.to_string()))
+    };
+    for deprecation in deprecations {
+        if api_version >= deprecation.removed_in {
+            continue;
+        }
+        let (new_module, new_function_name) = get_function(deprecation.new_name)?;
+        let new_function = new_module.get::<mlua::Function>(new_function_name)?;
+        let (old_module, old_function_name) = get_function(deprecation.old_name)?;
+        let shim = lua.create_function(move |lua, args: MultiValue| {
+            record_deprecated_call(lua, deprecation);
+            new_function.call::<MultiValue>(args)
+        })?;
+        old_module.raw_set(old_function_name, shim)?;
+    }
+    Ok(())
+}
+
+/// Returns the file and line of the innermost function of the stack that is not native code.
+fn get_call_site(lua: &Lua) -> String {
+    for level in 0..10 {
+        let mut location = None;
+        lua.inspect_stack(level, |debug| {
+            let source = debug.source();
+            if let Some(src) = source.short_src.or(source.source)
+                && !src.is_empty()
+                && src != "=[C]"
+            {
+                location = Some(format!("{src}:{}", debug.current_line().unwrap_or(0)));
+            }
+        });
+        if let Some(location) = location {
+            return location;
+        }
+    }
+    "unknown location".to_string()
+}
+
+fn record_deprecated_call(lua: &Lua, deprecation: &'static DeprecatedFunction) {
+    let call_site = get_call_site(lua);
+    let is_new_call_site = DEPRECATED_CALLS.with_borrow_mut(|calls| {
+        let call = calls
+            .entry(deprecation.old_name)
+            .or_insert_with(|| DeprecatedCall {
+                old_name: deprecation.old_name,
+                new_name: deprecation.new_name,
+                call_sites: BTreeMap::new(),
+            });
+        let count = call.call_sites.entry(call_site.clone()).or_insert(0);
+        *count += 1;
+        *count == 1
+    });
+    if is_new_call_site {
+        print_warn(format!(
+            "{call_site}: {} is deprecated, use {} instead. It does not exist in version {} of the API.",
+            deprecation.old_name, deprecation.new_name, deprecation.removed_in
+        ));
+    }
+}
+
+/// The deprecated functions called since the project was opened, sorted by name.
+pub fn get_deprecated_calls() -> Vec<DeprecatedCall> {
+    DEPRECATED_CALLS.with_borrow(|calls| calls.values().cloned().collect())
+}
+
+/// Called when another project is opened. The calls are kept when the project is reloaded.
+pub fn clear_deprecated_calls() {
+    DEPRECATED_CALLS.with_borrow_mut(BTreeMap::clear);
+}
+
+#[cfg(test)]
+mod tests {
+    use vectarine_plugin_sdk::mlua::Lua;
+
+    use super::{DeprecatedFunction, get_deprecated_calls, install_deprecation_shims};
+
+    static RENAMES: &[DeprecatedFunction] = &[DeprecatedFunction {
+        old_name: "graphics.drawRectangle",
+        new_name: "graphics.drawRect",
+        removed_in: 3,
+    }];
+
+    #[test]
+    fn old_names_call_the_new_functions_until_they_are_removed() {
+        let lua = Lua::new();
+        let graphics = lua.create_table().expect("the table is created");
+        let draw_rect = lua
+            .create_function(|_, (width, height): (f64, f64)| Ok(width * height))
+            .expect("the function is created");
+        graphics.raw_set("drawRect", draw_rect).expect("set");
+        lua.register_module("@vectarine/graphics", graphics)
+            .expect("the module is registered");
+
+        install_deprecation_shims(&lua, 3, RENAMES).expect("no shim is needed");
+        let is_shimmed = lua
+            .load("return require('@vectarine/graphics').drawRectangle ~= nil")
+            .eval::<bool>()
+            .expect("the script runs");
+        assert!(!is_shimmed);
+
+        install_deprecation_shims(&lua, 2, RENAMES).expect("the shims are installed");
+        let area = lua
+            .load(
+                "local Graphics = require('@vectarine/graphics')
+                Graphics.drawRectangle(1, 1)
+                for i = 1, 2 do Graphics.drawRectangle(1, 1) end
+                return Graphics.drawRectangle(2, 3)",
+            )
+            .set_name("@game.luau")
+            .eval::<f64>()
+            .expect("the script runs");
+        assert_eq!(area, 6.0);
+
+        let calls = get_deprecated_calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].new_name, "graphics.drawRect");
+        assert_eq!(
+            calls[0].call_sites.values().copied().collect::<Vec<_>>(),
+            [1, 2, 1]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+/// How much of the difference with a new measurement is added to the estimate. Smaller values smooth the jitter of
+/// the callbacks better, but follow a change of the sound card more slowly.
+const SMOOTHING: f64 = 0.05;
+
+/// A measurement further than this from the estimate means that the sound card stopped (an underrun, a new device),
+/// so the estimate starts again from it instead of slowly moving to it.
+const RESYNC_THRESHOLD_SECS: f64 = 0.1;
+
+/// The time of the sound card, in seconds since the first sample it played.
+///
+/// The mixer only knows how many samples the sound card asked for, and at what time it asked. A sample count alone
+/// moves by whole buffers (1024 samples, 21ms at 48kHz), and the time of the requests jitters by a few milliseconds, so
+/// the clock keeps a smoothed estimate of the time at which the first sample played. As the estimate is corrected by
+/// every measurement, the clock follows the sound card even when its rate differs a bit from the system clock.
+#[derive(Debug)]
+pub struct AudioClock {
+    sample_rate: f64,
+    /// The estimated system time, in seconds, at which the sample 0 was played.
+    start_secs: Option<f64>,
+    last_frames_played: Option<u64>,
+    last_reading: f64,
+    /// The mean distance between the measurements and the estimate.
+    jitter_secs: f64,
+}
+
+impl AudioClock {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            start_secs: None,
+            last_frames_played: None,
+            last_reading: 0.0,
+            jitter_secs: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Records that the sound card played `frames_played` frames at the system time `now_secs`.
+    /// Measurements without new frames are ignored, as they only say that no buffer was requested since the last one.
+    pub fn observe(&mut self, now_secs: f64, frames_played: u64) {
+        if self.last_frames_played == Some(frames_played) {
+            return;
+        }
+        self.last_frames_played = Some(frames_played);
+        let measured_start_secs = now_secs - frames_played as f64 / self.sample_rate;
+        let Some(start_secs) = self.start_secs else {
+            self.start_secs = Some(measured_start_secs);
+            return;
+        };
+        let error = measured_start_secs - start_secs;
+        if error.abs() > RESYNC_THRESHOLD_SECS {
+            self.start_secs = Some(measured_start_secs);
+            return;
+        }
+        self.start_secs = Some(start_secs + error * SMOOTHING);
+        self.jitter_secs += (error.abs() - self.jitter_secs) * SMOOTHING;
+    }
+
+    /// Returns the time of the sound card at the system time `now_secs`. The result never decreases, even when the
+    /// estimate is corrected backwards. Returns 0 until the first measurement.
+    pub fn seconds(&mut self, now_secs: f64) -> f64 {
+        let Some(start_secs) = self.start_secs else {
+            return 0.0;
+        };
+        self.last_reading = self.last_reading.max(now_secs - start_secs);
+        self.last_reading
+    }
+
+    pub fn jitter_secs(&self) -> f64 {
+        self.jitter_secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AudioClock;
+
+    #[test]
+    fn the_clock_follows_the_sound_card_despite_late_callbacks() {
+        let sample_rate = 48_000.0;
+        let buffer_frames = 1024;
+        // The sound card runs a bit faster than the system clock says.
+        let card_rate = sample_rate * 1.0005;
+        let mut clock = AudioClock::new(sample_rate);
+        let mut previous_reading = 0.0;
+        for callback in 0..2000u64 {
+            let frames_played = callback * buffer_frames;
+            let true_secs = frames_played as f64 / card_rate;
+            // The callbacks arrive up to 4ms late.
+            let lateness_secs = ((callback * 7919) % 5) as f64 / 1000.0;
+            clock.observe(1.0 + true_secs + lateness_secs, frames_played);
+
+            let reading_time = 1.0 + true_secs + 0.010;
+            let reading = clock.seconds(reading_time);
+            assert!(reading >= previous_reading);
+            previous_reading = reading;
+            if callback > 200 {
+                let heard_secs = (true_secs + 0.010) * card_rate / sample_rate;
+                assert!(
+                    (reading - heard_secs).abs() < 0.004,
+                    "{reading} is too far from {heard_secs}"
+                );
+            }
+        }
+        assert!(clock.jitter_secs() < 0.004);
+    }
+
+    #[test]
+    fn the_clock_starts_again_after_an_underrun() {
+        let mut clock = AudioClock::new(1000.0);
+        assert_eq!(clock.seconds(5.0), 0.0);
+        clock.observe(10.0, 0);
+        clock.observe(11.0, 1000);
+        assert_eq!(clock.seconds(11.5), 1.5);
+        // The sound card was not fed for a second.
+        clock.observe(13.0, 2000);
+        assert_eq!(clock.seconds(13.5), 2.5);
+        // Going backwards is not allowed.
+        clock.observe(14.2, 3000);
+        assert_eq!(clock.seconds(13.5), 2.5);
+    }
+}
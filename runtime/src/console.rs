@@ -1,7 +1,32 @@
+//! The messages of the editor console. Every function can be called from any thread, like the threads of the export
+//! or the ones decoding resources, except the frame messages, which are drawn and cleared on every frame of the game
+//! and are only printed from the main thread.
+
 use std::collections::VecDeque;
 use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 use vectarine_plugin_sdk::lazy_static::lazy_static;
 
+/// The part of the engine a message comes from, for the messages that are not about the game itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsystem {
+    Export,
+    Loader,
+    Audio,
+    Import,
+}
+
+impl Subsystem {
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Export => "export",
+            Subsystem::Loader => "loader",
+            Subsystem::Audio => "audio",
+            Subsystem::Import => "import",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LuaError {
     // Allows for clickable links to the file / showing the line
@@ -10,11 +35,14 @@ pub struct LuaError {
     pub line: usize,
     pub line_content: [String; 5], // 2 lines before, the line itself, and 2 lines after
     pub repeat_count: u32,
+    pub last_logged_at: Instant,
 }
 
 pub struct RepeatableMessage {
     pub message: String,
+    pub subsystem: Option<Subsystem>,
     pub repeat_count: u32,
+    pub last_logged_at: Instant,
 }
 
 impl std::fmt::Display for RepeatableMessage {
@@ -22,6 +50,9 @@ impl std::fmt::Display for RepeatableMessage {
         if self.repeat_count > 1 {
             write!(f, "({}x) ", self.repeat_count)?;
         }
+        if let Some(subsystem) = self.subsystem {
+            write!(f, "[{}] ", subsystem.name())?;
+        }
         write!(f, "{}", self.message)
     }
 }
@@ -40,7 +71,8 @@ pub enum ConsoleMessage {
     Warning(RepeatableMessage),
     Error(RepeatableMessage),
     LuaError(LuaError),
-    Reload,
+    /// The project was reloaded at this time.
+    Reload(SystemTime),
 }
 
 impl std::fmt::Display for ConsoleMessage {
@@ -50,16 +82,18 @@ impl std::fmt::Display for ConsoleMessage {
             ConsoleMessage::Warning(warning) => write!(f, "{}", warning),
             ConsoleMessage::Error(error) => write!(f, "{}", error),
             ConsoleMessage::LuaError(err) => write!(f, "{}", err),
-            ConsoleMessage::Reload => write!(f, "Reloading..."),
+            ConsoleMessage::Reload(_) => write!(f, "Reloading..."),
         }
     }
 }
 
 impl RepeatableMessage {
-    fn new(message: String) -> Self {
+    fn new(message: String, subsystem: Option<Subsystem>) -> Self {
         Self {
             message,
+            subsystem,
             repeat_count: 1,
+            last_logged_at: Instant::now(),
         }
     }
 }
@@ -71,7 +105,16 @@ impl ConsoleMessage {
             ConsoleMessage::Warning(warning) => &warning.message,
             ConsoleMessage::Error(error) => &error.message,
             ConsoleMessage::LuaError(err) => &err.message,
-            ConsoleMessage::Reload => "Reloading...",
+            ConsoleMessage::Reload(_) => "Reloading...",
+        }
+    }
+    /// None for the messages of the game.
+    pub fn subsystem(&self) -> Option<Subsystem> {
+        match self {
+            ConsoleMessage::Info(info) => info.subsystem,
+            ConsoleMessage::Warning(warning) => warning.subsystem,
+            ConsoleMessage::Error(error) => error.subsystem,
+            ConsoleMessage::LuaError(_) | ConsoleMessage::Reload(_) => None,
         }
     }
     pub fn repeat_count(&self) -> u32 {
@@ -80,7 +123,42 @@ impl ConsoleMessage {
             ConsoleMessage::Warning(warning) => warning.repeat_count,
             ConsoleMessage::Error(error) => error.repeat_count,
             ConsoleMessage::LuaError(err) => err.repeat_count,
-            ConsoleMessage::Reload => 1,
+            ConsoleMessage::Reload(_) => 1,
+        }
+    }
+    /// The time of the last repeat of the message. Reload messages are never repeated.
+    pub fn last_logged_at(&self) -> Option<Instant> {
+        match self {
+            ConsoleMessage::Info(info) => Some(info.last_logged_at),
+            ConsoleMessage::Warning(warning) => Some(warning.last_logged_at),
+            ConsoleMessage::Error(error) => Some(error.last_logged_at),
+            ConsoleMessage::LuaError(err) => Some(err.last_logged_at),
+            ConsoleMessage::Reload(_) => None,
+        }
+    }
+    fn count_repeat(&mut self) {
+        let (repeat_count, last_logged_at) = match self {
+            ConsoleMessage::Info(info) => (&mut info.repeat_count, &mut info.last_logged_at),
+            ConsoleMessage::Warning(warning) => {
+                (&mut warning.repeat_count, &mut warning.last_logged_at)
+            }
+            ConsoleMessage::Error(error) => (&mut error.repeat_count, &mut error.last_logged_at),
+            ConsoleMessage::LuaError(err) => (&mut err.repeat_count, &mut err.last_logged_at),
+            ConsoleMessage::Reload(_) => return,
+        };
+        *repeat_count += 1;
+        *last_logged_at = Instant::now();
+    }
+    /// Returns true if both messages are errors with the same content.
+    fn is_same_error(&self, other: &ConsoleMessage) -> bool {
+        match (self, other) {
+            (ConsoleMessage::LuaError(a), ConsoleMessage::LuaError(b)) => {
+                a.message == b.message && a.file == b.file && a.line == b.line
+            }
+            (ConsoleMessage::Error(a), ConsoleMessage::Error(b)) => {
+                a.message == b.message && a.subsystem == b.subsystem
+            }
+            _ => false,
         }
     }
     pub fn is_same_kind(&self, other: &ConsoleMessage) -> bool {
@@ -90,7 +168,7 @@ impl ConsoleMessage {
                 | (ConsoleMessage::Warning(_), ConsoleMessage::Warning(_))
                 | (ConsoleMessage::Error(_), ConsoleMessage::Error(_))
                 | (ConsoleMessage::LuaError(_), ConsoleMessage::LuaError(_))
-                | (ConsoleMessage::Reload, ConsoleMessage::Reload)
+                | (ConsoleMessage::Reload(_), ConsoleMessage::Reload(_))
         )
     }
 }
@@ -102,6 +180,10 @@ pub struct Logger {
 
 pub const MAX_LOGS_COUNT: usize = 300;
 
+/// An error identical to one logged less than this long ago is counted as a repeat of it,
+/// even when other messages were logged in between.
+const ERROR_REPEAT_WINDOW: Duration = Duration::from_secs(1);
+
 impl Logger {
     fn new() -> Self {
         Self {
@@ -116,50 +198,61 @@ impl Logger {
         repeat_candidate_index: usize, // trick to avoid double mutable borrow
     ) {
         let repeat_candidate = &mut self.messages[repeat_candidate_index];
-        match (&message, repeat_candidate) {
-            (ConsoleMessage::LuaError(message), ConsoleMessage::LuaError(candidate)) => {
-                if message.message == candidate.message
-                    && message.file == candidate.file
-                    && message.line == candidate.line
-                {
-                    candidate.repeat_count += 1;
-                    return;
-                }
+        let is_repeat = match (&message, &*repeat_candidate) {
+            (ConsoleMessage::LuaError(_), ConsoleMessage::LuaError(_))
+            | (ConsoleMessage::Error(_), ConsoleMessage::Error(_)) => {
+                message.is_same_error(repeat_candidate)
             }
             (ConsoleMessage::Info(info), ConsoleMessage::Info(candidate)) => {
-                if info.message == candidate.message {
-                    candidate.repeat_count += 1;
-                    return;
-                }
+                info.message == candidate.message && info.subsystem == candidate.subsystem
             }
             (ConsoleMessage::Warning(warning), ConsoleMessage::Warning(candidate)) => {
-                if warning.message == candidate.message {
-                    candidate.repeat_count += 1;
-                    return;
-                }
-            }
-            (ConsoleMessage::Error(error), ConsoleMessage::Error(candidate)) => {
-                if error.message == candidate.message {
-                    candidate.repeat_count += 1;
-                    return;
-                }
+                warning.message == candidate.message && warning.subsystem == candidate.subsystem
             }
-            (ConsoleMessage::Reload, ConsoleMessage::Reload) => {
-                // Never show a reload message twice in a row.
-                return;
-            }
-            _ => {}
+            _ => false,
+        };
+        if is_repeat {
+            repeat_candidate.count_repeat();
+            return;
         }
         self.messages.push_back(message);
     }
 
+    /// Errors thrown on every frame by a broken Update would push everything else out of the console.
+    fn find_recent_same_error(&self, message: &ConsoleMessage) -> Option<usize> {
+        let now = Instant::now();
+        self.messages
+            .iter()
+            .enumerate()
+            .rev()
+            .take_while(|m| !matches!(m.1, ConsoleMessage::Reload(_)))
+            .find(|m| {
+                m.1.is_same_error(message)
+                    && m.1.last_logged_at().is_some_and(|logged_at| {
+                        now.duration_since(logged_at) < ERROR_REPEAT_WINDOW
+                    })
+            })
+            .map(|(index, _)| index)
+    }
+
     fn log(&mut self, message: ConsoleMessage) {
+        if let ConsoleMessage::Reload(time) = &message {
+            // Every reload gets its divider, only the same reload logged twice is skipped.
+            if !matches!(self.messages.back(), Some(ConsoleMessage::Reload(last)) if last == time) {
+                self.messages.push_back(message);
+            }
+            return;
+        }
+        if let Some(index) = self.find_recent_same_error(&message) {
+            self.messages[index].count_repeat();
+            return;
+        }
         let last_log = self
             .messages
             .iter()
             .enumerate()
             .rev()
-            .take_while(|m| !matches!(m.1, ConsoleMessage::Reload)) // skip reload messages when looking for a repeat
+            .take_while(|m| !matches!(m.1, ConsoleMessage::Reload(_))) // skip reload messages when looking for a repeat
             .find(|m| m.1.is_same_kind(&message));
         if let Some((index, _)) = last_log {
             self.add_message_without_repeat(message, index);
@@ -173,16 +266,20 @@ impl Logger {
         self.trim_frame();
     }
 
-    fn log_info(&mut self, msg: String) {
-        self.log(ConsoleMessage::Info(RepeatableMessage::new(msg)));
+    fn log_info(&mut self, msg: String, subsystem: Option<Subsystem>) {
+        self.log(ConsoleMessage::Info(RepeatableMessage::new(msg, subsystem)));
         self.trim();
     }
-    fn log_warning(&mut self, msg: String) {
-        self.log(ConsoleMessage::Warning(RepeatableMessage::new(msg)));
+    fn log_warning(&mut self, msg: String, subsystem: Option<Subsystem>) {
+        self.log(ConsoleMessage::Warning(RepeatableMessage::new(
+            msg, subsystem,
+        )));
         self.trim();
     }
-    fn log_error(&mut self, msg: String) {
-        self.log(ConsoleMessage::Error(RepeatableMessage::new(msg)));
+    fn log_error(&mut self, msg: String, subsystem: Option<Subsystem>) {
+        self.log(ConsoleMessage::Error(RepeatableMessage::new(
+            msg, subsystem,
+        )));
         self.trim();
     }
     fn log_lua_error(
@@ -198,6 +295,7 @@ impl Logger {
             line,
             line_content,
             repeat_count: 1,
+            last_logged_at: Instant::now(),
         }));
         self.trim();
     }
@@ -222,7 +320,7 @@ lazy_static! {
 /// the configuration.
 pub fn print_err(msg: String) {
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log_error(msg);
+        logger.log_error(msg, None);
     }
 }
 
@@ -230,7 +328,7 @@ pub fn print_err(msg: String) {
 /// the configuration.
 pub fn print_warn(msg: String) {
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log_warning(msg);
+        logger.log_warning(msg, None);
     }
 }
 
@@ -238,7 +336,28 @@ pub fn print_warn(msg: String) {
 /// the configuration.
 pub fn print_info(msg: String) {
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log_info(msg);
+        logger.log_info(msg, None);
+    }
+}
+
+/// Print an error of a part of the engine, shown with its name in the console.
+pub fn print_err_from(subsystem: Subsystem, msg: String) {
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.log_error(msg, Some(subsystem));
+    }
+}
+
+/// Print a warning of a part of the engine, shown with its name in the console.
+pub fn print_warn_from(subsystem: Subsystem, msg: String) {
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.log_warning(msg, Some(subsystem));
+    }
+}
+
+/// Print an information of a part of the engine, shown with its name in the console.
+pub fn print_info_from(subsystem: Subsystem, msg: String) {
+    if let Ok(mut logger) = LOGGER.lock() {
+        logger.log_info(msg, Some(subsystem));
     }
 }
 
@@ -254,10 +373,10 @@ pub fn print_frame(msg: String) {
     }
 }
 
-/// Prints an indicator that a project was unloaded. Currently, this is a horizontal separator.
+/// Prints an indicator that a project was unloaded. The editor shows it as a separator with the time of the reload.
 pub fn print_reload() {
     if let Ok(mut logger) = LOGGER.lock() {
-        logger.log(ConsoleMessage::Reload);
+        logger.log(ConsoleMessage::Reload(SystemTime::now()));
     }
 }
 
@@ -298,3 +417,79 @@ pub fn clear_all_logs() {
     logger.messages.clear();
     logger.frame_messages.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    use super::{ConsoleMessage, Logger, Subsystem};
+
+    #[test]
+    fn only_identical_reloads_are_collapsed() {
+        let mut logger = Logger::new();
+        let first_reload = SystemTime::UNIX_EPOCH;
+        let second_reload = first_reload + Duration::from_secs(1);
+        logger.log(ConsoleMessage::Reload(first_reload));
+        logger.log(ConsoleMessage::Reload(first_reload));
+        logger.log(ConsoleMessage::Reload(second_reload));
+        logger.log_info("hello".to_string(), None);
+        logger.log_info("hello".to_string(), None);
+
+        let reload_times = logger
+            .messages
+            .iter()
+            .filter_map(|message| match message {
+                ConsoleMessage::Reload(time) => Some(*time),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reload_times, vec![first_reload, second_reload]);
+        assert_eq!(logger.messages.len(), 3);
+        assert_eq!(logger.messages[2].repeat_count(), 2);
+    }
+
+    #[test]
+    fn messages_of_several_threads_are_all_kept_whole() {
+        let logger = Arc::new(Mutex::new(Logger::new()));
+        let threads = (0..4)
+            .map(|thread_index| {
+                let logger = Arc::clone(&logger);
+                std::thread::spawn(move || {
+                    for message_index in 0..50 {
+                        let message =
+                            format!("thread {thread_index} message {message_index}\nsecond line");
+                        if let Ok(mut logger) = logger.lock() {
+                            logger.log_info(message, Some(Subsystem::Loader));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().expect("the thread does not panic");
+        }
+
+        let logger = logger.lock().expect("the logger is not poisoned");
+        assert_eq!(logger.messages.len(), 200);
+        for thread_index in 0..4 {
+            let prefix = format!("thread {thread_index} message ");
+            let messages = logger
+                .messages
+                .iter()
+                .filter(|message| message.message().starts_with(&prefix))
+                .collect::<Vec<_>>();
+            for (message_index, message) in messages.iter().enumerate() {
+                assert_eq!(
+                    message.message(),
+                    format!("{prefix}{message_index}\nsecond line")
+                );
+                assert_eq!(message.subsystem(), Some(Subsystem::Loader));
+                assert!(matches!(message, ConsoleMessage::Info(_)));
+            }
+            assert_eq!(messages.len(), 50);
+        }
+    }
+}
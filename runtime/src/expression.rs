@@ -0,0 +1,263 @@
+use std::{
+    cell::Cell,
+    time::{Duration, Instant},
+};
+
+use vectarine_plugin_sdk::mlua::{self, Function, Lua, Table, Value};
+
+/// Names of the registry values holding the environment of the expressions, created once per Lua state.
+const EXPRESSION_ENVIRONMENT_KEY: &str = "vectarine_expression_environment";
+const EXPRESSION_UNWRAP_KEY: &str = "vectarine_expression_unwrap";
+
+/// The longest an expression can run. The watcher evaluates its expressions every frame, so an expression like
+/// `(function() while true do end end)()` must not freeze the editor.
+pub const EXPRESSION_TIME_BUDGET: Duration = Duration::from_millis(50);
+
+thread_local! {
+    /// When the expression being evaluated runs out of time, None outside of `evaluate_expression`.
+    static EXPRESSION_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Builds the environment of the expressions from the globals of the game.
+///
+/// Globals are read through proxies: reading a field of a proxy returns a proxy of the live value, so nothing is copied
+/// and the expressions always see the current state of the game. Writing to a proxy is an error, and the functions of
+/// the game are replaced by functions refusing to run. Only the functions of `SAFE_GLOBALS` can be called.
+/// Operators are forwarded to the proxied values, so `Player.pos.x - Boss.pos.x` and `#Enemies.list` work.
+const EXPRESSION_ENVIRONMENT_SOURCE: &str = r#"
+local globals = ...
+local originals = setmetatable({}, { __mode = "k" })
+local proxyMetatable = {}
+
+local function unwrap(value)
+    local original = originals[value]
+    if original ~= nil then
+        return original
+    end
+    return value
+end
+
+local function wrap(value, name)
+    local kind = type(value)
+    if kind == "table" or kind == "userdata" then
+        local proxy = setmetatable({}, proxyMetatable)
+        originals[proxy] = value
+        return proxy
+    elseif kind == "function" then
+        return function()
+            error(`{name} cannot be called in an expression, only math, string and conversion functions can`, 2)
+        end
+    end
+    return value
+end
+
+local function refuseWrite()
+    error("expressions cannot change the game", 2)
+end
+
+proxyMetatable.__index = function(proxy, key)
+    return wrap(originals[proxy][key], tostring(key))
+end
+proxyMetatable.__newindex = refuseWrite
+proxyMetatable.__len = function(proxy) return #originals[proxy] end
+proxyMetatable.__tostring = function(proxy) return tostring(originals[proxy]) end
+proxyMetatable.__unm = function(a) return wrap(-unwrap(a)) end
+proxyMetatable.__add = function(a, b) return wrap(unwrap(a) + unwrap(b)) end
+proxyMetatable.__sub = function(a, b) return wrap(unwrap(a) - unwrap(b)) end
+proxyMetatable.__mul = function(a, b) return wrap(unwrap(a) * unwrap(b)) end
+proxyMetatable.__div = function(a, b) return wrap(unwrap(a) / unwrap(b)) end
+proxyMetatable.__idiv = function(a, b) return wrap(unwrap(a) // unwrap(b)) end
+proxyMetatable.__mod = function(a, b) return wrap(unwrap(a) % unwrap(b)) end
+proxyMetatable.__pow = function(a, b) return wrap(unwrap(a) ^ unwrap(b)) end
+proxyMetatable.__concat = function(a, b) return unwrap(a) .. unwrap(b) end
+proxyMetatable.__eq = function(a, b) return unwrap(a) == unwrap(b) end
+proxyMetatable.__lt = function(a, b) return unwrap(a) < unwrap(b) end
+proxyMetatable.__le = function(a, b) return unwrap(a) <= unwrap(b) end
+proxyMetatable.__metatable = false
+
+-- The random functions change the state of the generator used by the game.
+local safeMath = table.clone(math)
+safeMath.random = nil
+safeMath.randomseed = nil
+
+local SAFE_GLOBALS = {
+    math = table.freeze(safeMath),
+    string = table.freeze({
+        format = string.format,
+        len = function(s) return string.len(unwrap(s)) end,
+        lower = function(s) return string.lower(unwrap(s)) end,
+        rep = function(s, n) return string.rep(unwrap(s), n) end,
+        sub = function(s, i, j) return string.sub(unwrap(s), i, j) end,
+        upper = function(s) return string.upper(unwrap(s)) end,
+    }),
+    tostring = function(value) return tostring(unwrap(value)) end,
+    tonumber = function(value, base) return tonumber(unwrap(value), base) end,
+    type = function(value) return type(unwrap(value)) end,
+    typeof = function(value) return typeof(unwrap(value)) end,
+}
+
+local environment = setmetatable({}, {
+    __index = function(_, name)
+        local safe = SAFE_GLOBALS[name]
+        if safe ~= nil then
+            return safe
+        end
+        return wrap(globals[name], name)
+    end,
+    __newindex = refuseWrite,
+    __metatable = false,
+})
+return environment, unwrap
+"#;
+
+fn get_expression_environment(lua: &Lua) -> mlua::Result<(Table, Function)> {
+    if let Some(environment) =
+        lua.named_registry_value::<Option<Table>>(EXPRESSION_ENVIRONMENT_KEY)?
+    {
+        let unwrap = lua.named_registry_value::<Function>(EXPRESSION_UNWRAP_KEY)?;
+        return Ok((environment, unwrap));
+    }
+    let (environment, unwrap) = lua
+        .load(EXPRESSION_ENVIRONMENT_SOURCE)
+        .set_name("=expression environment")
+        .call::<(Table, Function)>(lua.globals())?;
+    lua.set_named_registry_value(EXPRESSION_ENVIRONMENT_KEY, &environment)?;
+    lua.set_named_registry_value(EXPRESSION_UNWRAP_KEY, &unwrap)?;
+    Ok((environment, unwrap))
+}
+
+/// Evaluates a Luau expression, like `#Enemies.list` or `Player.pos.x - Boss.pos.x`, without letting it change the
+/// game: statements do not compile, the globals are read-only and only math, string and conversion functions can be
+/// called. The tables and userdata of the result are the live values of the game.
+///
+/// The expression is stopped after `EXPRESSION_TIME_BUDGET` when the interrupt of the Lua state calls
+/// `check_expression_budget`.
+pub fn evaluate_expression(lua: &Lua, expression: &str) -> mlua::Result<Value> {
+    let (environment, unwrap) = get_expression_environment(lua)?;
+    EXPRESSION_DEADLINE.set(Some(Instant::now() + EXPRESSION_TIME_BUDGET));
+    // The parentheses refuse a second statement after the expression, like `1) os.exit(`.
+    let value = lua
+        .load(format!("return ({expression}\n)"))
+        .set_name("=expression")
+        .set_environment(environment)
+        .call::<Value>(());
+    EXPRESSION_DEADLINE.set(None);
+    unwrap.call::<Value>(value?)
+}
+
+/// To call from the interrupt of the Lua state. Stops the expression being evaluated when it ran longer than
+/// `EXPRESSION_TIME_BUDGET`, and does nothing outside of `evaluate_expression`.
+pub fn check_expression_budget() -> mlua::Result<()> {
+    let is_over_budget = EXPRESSION_DEADLINE
+        .get()
+        .is_some_and(|deadline| Instant::now() > deadline);
+    if is_over_budget {
+        return Err(mlua::Error::RuntimeError(format!(
+            "The expression took more than {}ms and was stopped.",
+            EXPRESSION_TIME_BUDGET.as_millis()
+        )));
+    }
+    Ok(())
+}
+
+/// Whether the text is the name of a global, which the watcher can edit, rather than an expression.
+pub fn is_global_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use vectarine_plugin_sdk::mlua::{Lua, Value, VmState};
+
+    use super::{check_expression_budget, evaluate_expression, is_global_name};
+
+    fn game_state() -> Lua {
+        let lua = Lua::new();
+        lua.load(
+            "Player = { hp = 10, maxHp = 30, pos = { x = 4 } }
+            Boss = { pos = { x = 1 } }
+            Enemies = { list = { 1, 2, 3 } }
+            function heal() Player.hp = Player.maxHp end",
+        )
+        .exec()
+        .expect("the game state is created");
+        lua
+    }
+
+    fn evaluate_number(lua: &Lua, expression: &str) -> f64 {
+        match evaluate_expression(lua, expression).expect("the expression is evaluated") {
+            Value::Integer(n) => n as f64,
+            Value::Number(n) => n,
+            value => panic!("{expression} is not a number: {value:?}"),
+        }
+    }
+
+    #[test]
+    fn expressions_read_the_live_globals() {
+        let lua = game_state();
+        assert_eq!(evaluate_number(&lua, "#Enemies.list"), 3.0);
+        assert_eq!(evaluate_number(&lua, "Player.pos.x - Boss.pos.x"), 3.0);
+        assert_eq!(evaluate_number(&lua, "math.max(Player.hp, 12)"), 12.0);
+        lua.load("table.insert(Enemies.list, 4)")
+            .exec()
+            .expect("the game changes");
+        assert_eq!(evaluate_number(&lua, "#Enemies.list"), 4.0);
+
+        let list = evaluate_expression(&lua, "Enemies.list").expect("the table is returned");
+        let Value::Table(list) = list else {
+            panic!("the live table is returned, not its proxy");
+        };
+        assert_eq!(list.raw_len(), 4);
+    }
+
+    #[test]
+    fn expressions_cannot_change_the_game() {
+        let lua = game_state();
+        for expression in [
+            "heal()",
+            "rawset(Player, 'hp', 0)",
+            "table.insert(Enemies.list, 4)",
+            "Player.hp = 0",
+            "1) heal(",
+            "setmetatable(Player, nil)",
+            "math.random()",
+            "math.randomseed(4)",
+        ] {
+            assert!(
+                evaluate_expression(&lua, expression).is_err(),
+                "{expression} is refused"
+            );
+        }
+        assert_eq!(evaluate_number(&lua, "Player.hp"), 10.0);
+        assert_eq!(evaluate_number(&lua, "#Enemies.list"), 3.0);
+    }
+
+    #[test]
+    fn expressions_are_stopped_after_their_time_budget() {
+        let lua = game_state();
+        lua.set_interrupt(|_| {
+            check_expression_budget()?;
+            Ok(VmState::Continue)
+        });
+        assert!(evaluate_expression(&lua, "(function() while true do end end)()").is_err());
+        // The budget only applies to expressions.
+        lua.load("local start = os.clock() while os.clock() - start < 0.1 do end")
+            .exec()
+            .expect("the game runs longer than the budget");
+        assert_eq!(evaluate_number(&lua, "Player.hp"), 10.0);
+    }
+
+    #[test]
+    fn global_names_are_told_apart_from_expressions() {
+        assert!(is_global_name("Player"));
+        assert!(is_global_name("_score2"));
+        assert!(!is_global_name("Player.hp"));
+        assert!(!is_global_name("#list"));
+        assert!(!is_global_name("2x"));
+        assert!(!is_global_name(""));
+    }
+}
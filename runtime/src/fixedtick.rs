@@ -0,0 +1,96 @@
+use std::{ops::Range, time::Duration};
+
+/// When a frame takes too long (or the game was paused by a debugger), we drop the time we cannot catch up with
+/// instead of running hundreds of ticks in a row, which would make the next frame even slower.
+pub const MAX_TICKS_PER_FRAME: u64 = 8;
+
+/// Accumulates real time and turns it into a sequence of fixed-duration ticks.
+///
+/// Everything is computed using integer nanoseconds, so the same sequence of frame durations
+/// always produces the same sequence of ticks. This is what makes replays possible.
+pub struct FixedTickClock {
+    tick_duration: Duration,
+    accumulator: Duration,
+    tick_count: u64,
+}
+
+impl FixedTickClock {
+    pub fn new(ticks_per_second: u32) -> Self {
+        let ticks_per_second = ticks_per_second.max(1);
+        Self {
+            tick_duration: Duration::from_nanos(1_000_000_000 / ticks_per_second as u64),
+            accumulator: Duration::ZERO,
+            tick_count: 0,
+        }
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        self.tick_duration
+    }
+
+    /// The number of ticks that were run since the start of the game.
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    /// Adds the duration of a frame and returns the indices of the ticks that need to run this frame.
+    pub fn advance(&mut self, frame_duration: Duration) -> Range<u64> {
+        self.accumulator += frame_duration;
+        let tick_nanos = self.tick_duration.as_nanos();
+        let mut tick_to_run = (self.accumulator.as_nanos() / tick_nanos) as u64;
+        if tick_to_run > MAX_TICKS_PER_FRAME {
+            tick_to_run = MAX_TICKS_PER_FRAME;
+            self.accumulator = self.tick_duration * MAX_TICKS_PER_FRAME as u32
+                + Duration::from_nanos((self.accumulator.as_nanos() % tick_nanos) as u64);
+        }
+        self.accumulator -= self.tick_duration * tick_to_run as u32;
+
+        let first_tick = self.tick_count;
+        self.tick_count += tick_to_run;
+        first_tick..self.tick_count
+    }
+
+    /// How far we are between the last tick and the next one, between 0 and 1.
+    /// Use it to interpolate between the previous and the current state when rendering.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick_duration.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixedTickClock, MAX_TICKS_PER_FRAME};
+    use std::time::Duration;
+
+    #[test]
+    fn same_frame_durations_give_same_ticks() {
+        let frames = [
+            16_600_000, 17_100_000, 8_000_000, 33_300_000, 1_000_000, 16_666_667,
+        ];
+        let run = || {
+            let mut clock = FixedTickClock::new(60);
+            frames
+                .iter()
+                .map(|nanos| clock.advance(Duration::from_nanos(*nanos)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn ticks_are_accumulated_across_frames() {
+        let mut clock = FixedTickClock::new(10);
+        assert_eq!(clock.advance(Duration::from_millis(60)), 0..0);
+        assert_eq!(clock.advance(Duration::from_millis(60)), 0..1);
+        assert!((clock.alpha() - 0.2).abs() < 1e-4);
+        assert_eq!(clock.advance(Duration::from_millis(200)), 1..3);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut clock = FixedTickClock::new(60);
+        let ticks = clock.advance(Duration::from_secs(10));
+        assert_eq!(ticks, 0..MAX_TICKS_PER_FRAME);
+        assert!(clock.alpha() < 1.0);
+    }
+}
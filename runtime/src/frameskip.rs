@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+/// Number of frames in a row that can be skipped when the project does not set `max_consecutive_skips`.
+pub const DEFAULT_MAX_CONSECUTIVE_SKIPS: u32 = 2;
+
+/// The largest accepted `max_consecutive_skips`. Skipping more frames in a row would make the game look frozen.
+pub const MAX_CONSECUTIVE_SKIPS_LIMIT: u32 = 30;
+
+/// We are late when we are behind by more than this fraction of a frame. The margin absorbs the jitter of the timer,
+/// so that a game running at the target rate never skips frames.
+const LATE_FRAME_MARGIN: f64 = 0.25;
+
+/// Decides when to skip rendering a frame so that the game logic keeps running at full speed on slow machines.
+///
+/// Frames are only skipped when the game is behind the target frame rate, so skipping never makes the game update
+/// faster than the display refreshes.
+pub struct FrameSkipper {
+    target_frame_duration: Duration,
+    max_consecutive_skips: u32,
+    consecutive_skips: u32,
+    /// How much time the game is behind the target frame rate.
+    lag: Duration,
+}
+
+impl FrameSkipper {
+    pub fn new(max_consecutive_skips: u32, target_frame_rate: u32) -> Self {
+        Self {
+            target_frame_duration: Duration::from_secs(1) / target_frame_rate.max(1),
+            max_consecutive_skips,
+            consecutive_skips: 0,
+            lag: Duration::ZERO,
+        }
+    }
+
+    pub fn set_target_frame_rate(&mut self, target_frame_rate: u32) {
+        self.target_frame_duration = Duration::from_secs(1) / target_frame_rate.max(1);
+    }
+
+    /// Adds the duration of the last frame and returns false when the current frame should not be rendered.
+    pub fn should_render(&mut self, frame_duration: Duration) -> bool {
+        self.lag = (self.lag + frame_duration).saturating_sub(self.target_frame_duration);
+        let is_late = self.lag > self.target_frame_duration.mul_f64(LATE_FRAME_MARGIN);
+        if is_late && self.consecutive_skips < self.max_consecutive_skips {
+            self.consecutive_skips += 1;
+            return false;
+        }
+        if is_late {
+            // The machine is too slow to catch up. Forgetting the lag avoids skipping every other frame forever.
+            self.lag = Duration::ZERO;
+        }
+        self.consecutive_skips = 0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameSkipper;
+    use std::time::Duration;
+
+    fn run(skipper: &mut FrameSkipper, frame_millis: &[u64]) -> Vec<bool> {
+        frame_millis
+            .iter()
+            .map(|millis| skipper.should_render(Duration::from_millis(*millis)))
+            .collect()
+    }
+
+    #[test]
+    fn frames_on_time_are_always_rendered() {
+        let mut skipper = FrameSkipper::new(2, 60);
+        let rendered = run(&mut skipper, &[16, 17, 17, 16, 20, 14, 17, 16]);
+        assert!(rendered.iter().all(|is_rendered| *is_rendered));
+    }
+
+    #[test]
+    fn slow_frames_skip_at_most_the_limit() {
+        let mut skipper = FrameSkipper::new(2, 60);
+        let rendered = run(&mut skipper, &[50, 50, 50, 50, 50, 50]);
+        assert_eq!(rendered, [false, false, true, false, false, true]);
+
+        let mut skipper = FrameSkipper::new(0, 60);
+        assert!(
+            run(&mut skipper, &[50, 50, 50])
+                .iter()
+                .all(|is_rendered| *is_rendered)
+        );
+    }
+
+    #[test]
+    fn catching_up_stops_the_skipping() {
+        let mut skipper = FrameSkipper::new(3, 60);
+        // A single hitch, then skipped frames are fast because they are not rendered.
+        let rendered = run(&mut skipper, &[40, 5, 5, 16, 16]);
+        assert_eq!(rendered, [false, false, true, true, true]);
+    }
+}
@@ -2,36 +2,77 @@ use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
 
 use vectarine_plugin_sdk::glow;
 use vectarine_plugin_sdk::glow::HasContext;
-use vectarine_plugin_sdk::plugininterface::PluginInterface;
+use vectarine_plugin_sdk::plugininterface::{PluginDrawInterface, PluginInterface};
 use vectarine_plugin_sdk::sdl2;
 use vectarine_plugin_sdk::sdl2::video::WindowPos;
 
 use crate::{
-    console::print_warn,
+    apiversion::{DEPRECATED_FUNCTIONS, check_api_version, install_deprecation_shims},
+    console::{print_info, print_warn},
+    fixedtick::FixedTickClock,
+    frameskip::FrameSkipper,
     game_resource::{
-        Resource, ResourceId, ResourceManager, Status, script_resource::ScriptResource,
+        Resource, ResourceId, ResourceManager, Status, font_resource,
+        script_resource::ScriptResource,
     },
-    graphics::batchdraw::BatchDraw2d,
-    io::{fs::ReadOnlyFileSystem, process_events},
+    graphics::{
+        batchdraw::BatchDraw2d,
+        glframebuffer::Viewport,
+        letterbox::{SafeAreaInsets, game_viewport, pixel_perfect_viewport},
+        pixelperfect::PixelPerfectTarget,
+    },
+    io::{
+        QuitDecision, TextInputRequest, contentpack::GameIdentity, cursor::CursorState,
+        fs::ReadOnlyFileSystem, gamepad::add_gamepad_mappings, process_events, take_quit_decision,
+    },
+    latency,
     lua_env::{LuaEnvironment, print_lua_error_from_error},
     metrics::{
-        DRAW_CALL_METRIC_NAME, LUA_HEAP_SIZE_METRIC_NAME, LUA_SCRIPT_TIME_METRIC_NAME,
-        MetricsHolder, TOTAL_FRAME_TIME_METRIC_NAME,
+        CULLED_DRAW_METRIC_NAME, DRAW_CALL_METRIC_NAME, INSTANCED_GLYPH_METRIC_NAME,
+        LUA_SCRIPT_TIME_METRIC_NAME, MemoryCategory, MetricsHolder, SKIPPED_FRAME_METRIC_NAME,
+        TOTAL_FRAME_TIME_METRIC_NAME, reset_memory_peaks,
     },
-    native_plugin::PluginEnvironment,
+    native_plugin::{PluginEnvironment, plugin_host::PluginHandles},
     projectinfo::ProjectInfo,
     sound,
 };
 
+/// The time that passes in the game during a frame stepped in the editor, as the real time includes the pause.
+const STEPPED_FRAME_DURATION: std::time::Duration = std::time::Duration::from_micros(16_667);
+
 pub struct Game {
     pub gl: Arc<glow::Context>,
     pub lua_env: LuaEnvironment,
     pub was_main_script_executed: bool,
     pub main_script_path: String,
+    pub main_script_id: Option<ResourceId>,
 
     pub metrics_holder: Rc<RefCell<MetricsHolder>>,
 
     pub plugin_env: PluginEnvironment,
+    /// The parts of the game given to the plugins. They are invalidated when the game is dropped.
+    pub plugin_handles: PluginHandles,
+
+    /// Present when the project sets `fixed_tick_rate`.
+    pub fixed_tick: Option<FixedTickClock>,
+
+    /// Present when the project sets `allow_frame_skip`.
+    pub frame_skipper: Option<FrameSkipper>,
+
+    /// Present while the game uses `Graphics.setPixelPerfect`.
+    pixel_perfect_target: Option<PixelPerfectTarget>,
+    /// Whether the window is too small for the internal resolution, so that the note is printed once per fallback.
+    is_pixel_scale_fractional: bool,
+
+    /// Set by the editor to stop calling Update. The last frame is drawn again while the game is paused.
+    is_paused: bool,
+    /// The frames to run while the game is paused, one per click on "Step" in the editor.
+    pending_steps: u32,
+    /// Whether the batch still holds the draw calls of the last frame, to draw them again while paused.
+    is_last_frame_kept: bool,
+
+    /// The cursor set by the game, kept alive while it is shown.
+    cursor: CursorState,
 }
 
 impl Game {
@@ -56,6 +97,10 @@ impl Game {
             )));
             return;
         };
+        if let Err(err) = check_api_version(project_info) {
+            callback(Err(err));
+            return;
+        }
 
         let _ = window.borrow_mut().set_title(&project_info.title);
         let _ = window.borrow_mut().set_size(
@@ -67,38 +112,49 @@ impl Game {
         let batch = BatchDraw2d::new(&gl).expect("Failed to create batch 2d");
         let metrics = Rc::new(RefCell::new(MetricsHolder::new()));
         let resources = Rc::new(ResourceManager::new(file_system, project_dir));
+        apply_max_parallel_loads(&resources, project_info);
+        resources.set_game_identity(GameIdentity {
+            title: project_info.title.clone(),
+            version: project_info.version.clone(),
+        });
 
         PluginEnvironment::load_plugins(
             &project_info.plugins,
             &resources.clone(),
             move |plugin_environment| {
                 let lua_env = LuaEnvironment::new(batch, metrics.clone(), resources);
+                apply_gc_step_per_frame(&lua_env, project_info);
+                apply_api_version(&lua_env, project_info);
+                apply_physics_info(&lua_env, project_info);
 
                 // Make the game!
                 let mut game = Game::from_lua(
                     &gl,
                     lua_env,
                     project_info.main_script_path.clone(),
+                    project_info.fixed_tick_rate,
+                    project_info.frame_skip_limit(),
                     metrics,
                     plugin_environment,
                 );
 
                 game.load(video, window);
-                game.plugin_env.init(PluginInterface {
-                    lua: &game.lua_env.lua_handle.lua,
-                });
+                game.plugin_env.init(game.plugin_interface());
 
                 // Load the starting script
                 let path = Path::new(&game.main_script_path);
-                game.lua_env.resources.load_resource::<ScriptResource>(
+                let main_script_id = game.lua_env.resources.load_resource::<ScriptResource>(
                     path,
                     gl,
                     game.lua_env.lua_handle.clone(),
                     game.lua_env.default_events.resource_loaded_event.clone(),
                 );
+                game.main_script_id = Some(main_script_id);
 
                 // New game means new sounds, so we discard the previous ones (this is useful only for the editor).
+                sound::set_audio_thread(project_info.audio_thread);
                 sound::flush_all_samples();
+                add_gamepad_mappings(&project_info.gamepad_mappings);
 
                 callback(Ok(game));
             },
@@ -123,6 +179,7 @@ impl Game {
                 "Invalid project path"
             ));
         };
+        check_api_version(project_info)?;
 
         let _ = window.borrow_mut().set_title(&project_info.title);
         let _ = window.borrow_mut().set_size(
@@ -134,13 +191,23 @@ impl Game {
         let batch = BatchDraw2d::new(&gl).expect("Failed to create batch 2d");
         let metrics = Rc::new(RefCell::new(MetricsHolder::new()));
         let resources = Rc::new(ResourceManager::new(file_system, project_dir));
+        apply_max_parallel_loads(&resources, project_info);
+        resources.set_game_identity(GameIdentity {
+            title: project_info.title.clone(),
+            version: project_info.version.clone(),
+        });
 
         let lua_env = LuaEnvironment::new(batch, metrics.clone(), resources);
+        apply_gc_step_per_frame(&lua_env, project_info);
+        apply_api_version(&lua_env, project_info);
+        apply_physics_info(&lua_env, project_info);
 
         let mut game = Game::from_lua(
             &gl,
             lua_env,
             project_info.main_script_path.clone(),
+            project_info.fixed_tick_rate,
+            project_info.frame_skip_limit(),
             metrics,
             PluginEnvironment::new_empty_environment(),
         );
@@ -151,21 +218,22 @@ impl Game {
         }
 
         game.load(video, window);
-        game.plugin_env.init(PluginInterface {
-            lua: &game.lua_env.lua_handle.lua,
-        });
+        game.plugin_env.init(game.plugin_interface());
 
         // Load the starting script
         let path = Path::new(&game.main_script_path);
-        game.lua_env.resources.load_resource::<ScriptResource>(
+        let main_script_id = game.lua_env.resources.load_resource::<ScriptResource>(
             path,
             gl,
             game.lua_env.lua_handle.clone(),
             game.lua_env.default_events.resource_loaded_event.clone(),
         );
+        game.main_script_id = Some(main_script_id);
 
         // New game means new sounds, so we discard the previous ones (this is useful only for the editor).
+        sound::set_audio_thread(project_info.audio_thread);
         sound::flush_all_samples();
+        add_gamepad_mappings(&project_info.gamepad_mappings);
 
         Ok(game)
     }
@@ -174,19 +242,83 @@ impl Game {
         gl: &Arc<glow::Context>,
         lua_env: LuaEnvironment,
         main_script_path: String,
+        fixed_tick_rate: Option<u32>,
+        frame_skip_limit: Option<u32>,
         metrics_holder: Rc<RefCell<MetricsHolder>>,
         plugin_env: PluginEnvironment,
     ) -> Self {
+        // Peaks are shown relative to the loading of the game.
+        reset_memory_peaks();
+        let fixed_tick = fixed_tick_rate.map(FixedTickClock::new);
+        lua_env.env_state.borrow_mut().tick_duration =
+            fixed_tick.as_ref().map(FixedTickClock::tick_duration);
+        lua_env.fixed_tick_duration.set(
+            fixed_tick
+                .as_ref()
+                .map(|fixed_tick| fixed_tick.tick_duration().as_secs_f32()),
+        );
+        let plugin_handles = PluginHandles::new(&lua_env.resources, &lua_env.batch);
         Game {
             gl: gl.clone(),
             lua_env,
             was_main_script_executed: false,
             main_script_path,
+            main_script_id: None,
             metrics_holder,
             plugin_env,
+            plugin_handles,
+            fixed_tick,
+            // The target frame rate is the refresh rate of the display, known once the game is loaded.
+            frame_skipper: frame_skip_limit.map(|limit| FrameSkipper::new(limit, 60)),
+            pixel_perfect_target: None,
+            is_pixel_scale_fractional: false,
+            is_paused: false,
+            pending_steps: 0,
+            is_last_frame_kept: false,
+            cursor: CursorState::default(),
         }
     }
 
+    /// Creates the framebuffer of `Graphics.setPixelPerfect` when the internal resolution changes, and binds it for
+    /// the frame.
+    fn update_pixel_perfect_target(&mut self) {
+        let (pixel_perfect, pixel_scale) = {
+            let env_state = self.lua_env.env_state.borrow();
+            (env_state.pixel_perfect, env_state.pixel_scale)
+        };
+        let Some(pixel_perfect) = pixel_perfect else {
+            self.pixel_perfect_target = None;
+            self.is_pixel_scale_fractional = false;
+            return;
+        };
+        let is_fractional = pixel_scale < 1.0;
+        if is_fractional && !self.is_pixel_scale_fractional {
+            print_info(format!(
+                "The window is smaller than the pixel perfect resolution of {}x{}, the game is scaled down by {:.2}.",
+                pixel_perfect.width, pixel_perfect.height, pixel_scale
+            ));
+        }
+        self.is_pixel_scale_fractional = is_fractional;
+        let has_size = self
+            .pixel_perfect_target
+            .as_ref()
+            .is_some_and(|target| target.has_size(pixel_perfect.width, pixel_perfect.height));
+        if !has_size {
+            self.pixel_perfect_target = Some(PixelPerfectTarget::new(
+                &self.gl,
+                pixel_perfect.width,
+                pixel_perfect.height,
+            ));
+        }
+        if let Some(target) = &mut self.pixel_perfect_target {
+            target.begin_frame(&self.gl);
+        }
+    }
+
+    pub fn plugin_interface(&self) -> PluginInterface<'_> {
+        PluginInterface::new(&self.lua_env.lua_handle.lua, &self.plugin_handles.resources)
+    }
+
     /// Initializes the game environment with the current video and window information.
     /// This needs to be called before loading Lua scripts.
     fn load(
@@ -215,6 +347,18 @@ impl Game {
             self.lua_env.env_state.borrow_mut().window_width = width;
             self.lua_env.env_state.borrow_mut().window_height = height;
         }
+
+        if let Ok(display_mode) = window.borrow().display_mode()
+            && display_mode.refresh_rate > 0
+        {
+            if let Some(frame_skipper) = &mut self.frame_skipper {
+                frame_skipper.set_target_frame_rate(display_mode.refresh_rate as u32);
+            }
+            self.lua_env
+                .latency
+                .borrow_mut()
+                .set_target_frame_rate(display_mode.refresh_rate as u32);
+        }
     }
 
     pub fn get_resource_or_print_error<T>(&self, id: ResourceId) -> Option<Rc<T>>
@@ -235,49 +379,95 @@ impl Game {
         Some(res)
     }
 
+    /// Quits when the game called `Io.quit`. In the editor, the game can only quit after the player tried to close its
+    /// window, so that the quit button of a menu does not close the editor, and the editor stops the game instead of
+    /// quitting, see `take_quit`.
+    fn handle_quit_request(&self, in_editor: bool) {
+        let mut env_state = self.lua_env.env_state.borrow_mut();
+        match take_quit_decision(&mut env_state, in_editor) {
+            QuitDecision::KeepRunning => {}
+            QuitDecision::Quit if in_editor => env_state.is_quit_accepted = true,
+            QuitDecision::Quit => std::process::exit(0),
+            QuitDecision::IgnoredInEditor => print_info(
+                "The game called Io.quit(), it quits when it is not run by the editor.".to_string(),
+            ),
+        }
+    }
+
+    /// Whether the game accepted to quit after the player closed its window in the editor.
+    pub fn take_quit(&self) -> bool {
+        std::mem::take(&mut self.lua_env.env_state.borrow_mut().is_quit_accepted)
+    }
+
+    /// Runs a frame of the game. Returns false when the rendering of the frame was skipped, in which case the window
+    /// should not be swapped.
     pub fn main_loop<'a>(
         &mut self,
         events: impl Iterator<Item = &'a sdl2::event::Event>,
         window: &Rc<RefCell<sdl2::video::Window>>,
         delta_time: std::time::Duration,
-        _in_editor: bool,
-    ) {
-        self.lua_env
-            .batch
-            .borrow()
-            .drawing_target
-            .reset_draw_call_counter();
+        in_editor: bool,
+    ) -> bool {
+        let start_of_frame = std::time::Instant::now();
+        {
+            let mut batch = self.lua_env.batch.borrow_mut();
+            batch.drawing_target.reset_draw_call_counter();
+            batch.reset_culling();
+        }
 
-        let framebuffer_width;
-        let framebuffer_height;
         {
             let mut env_state = self.lua_env.env_state.borrow_mut();
             let (width, height) = drawable_screen_size(&window.borrow());
             env_state.window_width = width;
             env_state.window_height = height;
             env_state.is_window_minimized = window.borrow().is_minimized();
-            let aspect_ratio = width as f32 / height as f32;
+            env_state.safe_area_insets =
+                safe_area_insets(env_state.px_ratio_x, env_state.px_ratio_y);
+            let (viewport, pixel_scale) = match env_state.pixel_perfect {
+                Some(pixel_perfect) => pixel_perfect_viewport(width, height, pixel_perfect),
+                None => (game_viewport(width, height, env_state.aspect_policy), 1.0),
+            };
+            env_state.game_viewport = viewport;
+            env_state.pixel_scale = pixel_scale;
             // This works in the editor, but not the runtime.
             // On the web, this is different, the aspect ratio needs to be squared??
             //self.batch.set_aspect_ratio(aspect_ratio * aspect_ratio);
 
-            self.lua_env
-                .batch
-                .borrow_mut()
-                .set_aspect_ratio(aspect_ratio);
+            {
+                let mut batch = self.lua_env.batch.borrow_mut();
+                // The game is drawn at the internal resolution, whose ratio the viewport only approximates.
+                batch.set_aspect_ratio(match env_state.pixel_perfect {
+                    Some(pixel_perfect) => pixel_perfect.width as f32 / pixel_perfect.height as f32,
+                    None => viewport.aspect_ratio(),
+                });
+                batch.set_pixel_snap(
+                    env_state
+                        .pixel_perfect
+                        .filter(|pixel_perfect| pixel_perfect.snap)
+                        .map(|pixel_perfect| [pixel_perfect.width, pixel_perfect.height]),
+                );
+            }
 
-            framebuffer_width = width;
-            framebuffer_height = height;
+            set_viewport(&self.gl, width, height, &viewport);
         }
+        self.update_pixel_perfect_target();
 
         {
-            // This is incorrect on the web.
-            let gl = &self.gl;
-            set_viewport(gl, framebuffer_width, framebuffer_height);
+            sound::update_sound_system()
         }
 
-        {
-            sound::update_sound_system()
+        // A paused game does not see the events nor the time passing, so that stepping continues where it stopped.
+        let is_frozen = self.is_frozen();
+        let delta_time = if self.is_paused {
+            STEPPED_FRAME_DURATION
+        } else {
+            delta_time
+        };
+        if self.is_paused && !is_frozen {
+            self.pending_steps -= 1;
+        }
+        if !is_frozen && std::mem::take(&mut self.is_last_frame_kept) {
+            self.lua_env.batch.borrow_mut().flush();
         }
 
         {
@@ -302,6 +492,24 @@ impl Game {
             if let Some(title) = env_state.window_title.take() {
                 window.borrow_mut().set_title(&title).unwrap_or(());
             }
+            if let Some(text_input_request) = env_state.text_input_request.take() {
+                let text_input = window.borrow().subsystem().text_input();
+                match text_input_request {
+                    TextInputRequest::Start(rect) => {
+                        if let Some(rect) = rect {
+                            text_input.set_rect(rect);
+                        }
+                        text_input.start();
+                    }
+                    TextInputRequest::Stop => text_input.stop(),
+                }
+            }
+            if let Some(cursor_request) = env_state.cursor_request.take() {
+                let mouse = window.borrow().subsystem().sdl().mouse();
+                if let Err(error) = self.cursor.apply(cursor_request, &mouse) {
+                    print_warn(format!("Failed to change the cursor: {error}"));
+                }
+            }
 
             if env_state.center_window_request {
                 window
@@ -309,14 +517,12 @@ impl Game {
                     .set_position(WindowPos::Centered, WindowPos::Centered);
                 env_state.center_window_request = false;
             }
+            env_state.platform_services.update();
         }
 
-        process_events(
-            self,
-            events,
-            framebuffer_width as f32,
-            framebuffer_height as f32,
-        );
+        if !is_frozen {
+            process_events(self, events);
+        }
 
         // 2D Settings
         unsafe {
@@ -328,41 +534,108 @@ impl Game {
             gl.enable(glow::MULTISAMPLE);
         }
 
-        let plugin_interface = PluginInterface {
-            lua: &self.lua_env.lua_handle.lua,
-        };
-        self.plugin_env.pre_lua_hook(plugin_interface);
+        if !is_frozen {
+            let plugin_interface = self.plugin_interface();
+            self.plugin_env.pre_lua_hook(plugin_interface);
+        }
 
         let start_of_lua_update = std::time::Instant::now();
-        if self.was_main_script_executed {
-            let update_fn = self
-                .lua_env
-                .lua_handle
-                .lua
-                .globals()
-                .get::<vectarine_plugin_sdk::mlua::Function>("Update");
-            if let Ok(update_fn) = update_fn {
-                let err = update_fn.call::<()>((delta_time.as_secs_f32(),));
-                if let Err(err) = err {
-                    print_lua_error_from_error(&self.lua_env.lua_handle, &err);
+        if self.was_main_script_executed && !is_frozen {
+            self.lua_env.env_state.borrow_mut().frame_time += delta_time;
+            let alpha = self.run_fixed_ticks(delta_time);
+            let main_script = self.get_main_script();
+            let update_fn = match &main_script {
+                Some(main_script) => main_script.get_update_function(&self.lua_env.lua_handle.lua),
+                None => self
+                    .lua_env
+                    .lua_handle
+                    .lua
+                    .globals()
+                    .get::<vectarine_plugin_sdk::mlua::Function>("Update")
+                    .ok(),
+            };
+            if let Some(update_fn) = update_fn {
+                let result = update_fn.call::<()>((delta_time.as_secs_f32(), alpha));
+                if let Err(err) = &result {
+                    print_lua_error_from_error(&self.lua_env.lua_handle, err);
+                }
+                if let Some(main_script) = &main_script {
+                    main_script.report_update_result(result.is_ok());
                 }
             } else {
                 print_warn("Update() function not found".to_string());
             }
+            self.lua_env.env_state.borrow_mut().frame_index += 1;
         }
         let lua_update_duration = start_of_lua_update.elapsed();
+        {
+            let mut env_state = self.lua_env.env_state.borrow_mut();
+            let frame_time = env_state.frame_time;
+            env_state.rumble.update(frame_time);
+        }
+        self.handle_quit_request(in_editor);
 
+        if !is_frozen {
+            self.plugin_env.draw_hook(PluginDrawInterface {
+                plugin_interface: self.plugin_interface(),
+                canvas: &self.plugin_handles.canvas,
+            });
+        }
+
+        // On the web, the browser presents the canvas after every frame, so skipping would show an unfinished frame.
+        // A game capped with `Io.setTargetFps` is slower than the display on purpose, it is not late.
+        let is_frame_skipped = cfg!(not(target_os = "emscripten"))
+            && self.lua_env.env_state.borrow().target_fps.is_none()
+            && self
+                .frame_skipper
+                .as_mut()
+                .is_some_and(|frame_skipper| !frame_skipper.should_render(delta_time));
+        // The editor still renders the frames that players would not see, so that the game window does not flicker.
+        let is_rendered = !is_frame_skipped || in_editor;
         {
-            self.lua_env
-                .batch
-                .borrow_mut()
-                .draw(&self.lua_env.resources, true);
+            let mut batch = self.lua_env.batch.borrow_mut();
+            if is_rendered {
+                self.lua_env.latency.borrow_mut().begin_gpu_timing(&self.gl);
+                // While paused, the draw calls are kept to draw the same frame until the next step.
+                batch.draw(&self.lua_env.resources, !self.is_paused);
+                self.is_last_frame_kept = self.is_paused;
+            } else {
+                batch.flush();
+            }
+        }
+        if is_rendered && let Some(target) = &self.pixel_perfect_target {
+            let viewport = self.lua_env.env_state.borrow().game_viewport;
+            target.present(&self.gl, &viewport);
+        }
+        if is_rendered {
+            let mut latency = self.lua_env.latency.borrow_mut();
+            // Drawn last, over the pixel perfect target, so that the overlay includes the whole frame. The kept draw
+            // calls would be drawn twice with it.
+            if latency.is_overlay_shown && !self.is_last_frame_kept {
+                let mut batch = self.lua_env.batch.borrow_mut();
+                latency.draw_overlay(&mut batch);
+                batch.draw(&self.lua_env.resources, true);
+            }
+            latency.end_gpu_timing();
+        }
+        // The bars are only protected while the game draws, so that the editor can draw over the whole window.
+        unsafe {
+            self.gl.disable(glow::SCISSOR_TEST);
         }
 
-        let plugin_interface = PluginInterface {
-            lua: &self.lua_env.lua_handle.lua,
-        };
-        self.plugin_env.post_lua_hook(plugin_interface);
+        // The collector runs at the same point of every frame when the project sets `gc_step_per_frame`.
+        let gc_result = self.lua_env.gc_pacer.borrow_mut().end_frame(
+            &self.lua_env.lua_handle.lua,
+            &mut self.metrics_holder.borrow_mut(),
+        );
+        if let Err(err) = gc_result {
+            print_lua_error_from_error(&self.lua_env.lua_handle, &err);
+        }
+
+        if !is_frozen {
+            let plugin_interface = self.plugin_interface();
+            self.plugin_env.post_lua_hook(plugin_interface);
+        }
 
         // Default Duration metrics
         self.metrics_holder
@@ -373,10 +646,12 @@ impl Game {
             .record_duration_metric(TOTAL_FRAME_TIME_METRIC_NAME, delta_time);
 
         // Default Counter metrics
-        self.metrics_holder.borrow_mut().record_number_metric(
-            LUA_HEAP_SIZE_METRIC_NAME,
-            self.lua_env.lua_handle.lua.used_memory(),
-        );
+        MemoryCategory::LuaHeap.set_usage(self.lua_env.lua_handle.lua.used_memory());
+        for category in MemoryCategory::ALL {
+            self.metrics_holder
+                .borrow_mut()
+                .record_number_metric(category.metric_name(), category.usage());
+        }
         self.metrics_holder.borrow_mut().record_number_metric(
             DRAW_CALL_METRIC_NAME,
             self.lua_env
@@ -385,26 +660,151 @@ impl Game {
                 .drawing_target
                 .get_draw_call_counter(),
         );
+        self.metrics_holder.borrow_mut().record_number_metric(
+            CULLED_DRAW_METRIC_NAME,
+            self.lua_env.batch.borrow().get_culled_draw_counter(),
+        );
+        self.metrics_holder.borrow_mut().record_number_metric(
+            INSTANCED_GLYPH_METRIC_NAME,
+            self.lua_env.batch.borrow().get_instanced_glyph_counter(),
+        );
+        if self.frame_skipper.is_some() {
+            self.metrics_holder
+                .borrow_mut()
+                .record_number_metric(SKIPPED_FRAME_METRIC_NAME, is_frame_skipped as usize);
+        }
 
         self.metrics_holder.borrow_mut().flush();
+        self.lua_env
+            .latency
+            .borrow_mut()
+            .end_frame(delta_time, start_of_frame.elapsed());
+        is_rendered
+    }
+
+    /// Needs to be called after the swap of the game window, to measure the input latency.
+    pub fn record_presented_frame(&self) {
+        self.lua_env
+            .latency
+            .borrow_mut()
+            .frame_presented(latency::sdl_ticks_ms());
+    }
+
+    /// Returns the main script resource when it is loaded.
+    fn get_main_script(&self) -> Option<Rc<ScriptResource>> {
+        self.lua_env
+            .resources
+            .get_by_id::<ScriptResource>(self.main_script_id?)
+            .ok()
+    }
+
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+        self.pending_steps = 0;
+    }
+
+    /// Pauses the game if it runs, and runs exactly one more frame.
+    pub fn step_frame(&mut self) {
+        self.is_paused = true;
+        self.pending_steps += 1;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// True when the next frames only draw the last one again, until the game is resumed or stepped.
+    pub fn is_frozen(&self) -> bool {
+        self.is_paused && self.pending_steps == 0
+    }
+
+    /// True when the editor calls the Update of the previous version of the main script because the current one
+    /// keeps failing.
+    pub fn is_running_previous_version(&self) -> bool {
+        self.get_main_script()
+            .is_some_and(|main_script| main_script.is_running_previous_version())
+    }
+
+    /// Calls `Tick(tickIndex)` and `FixedUpdate(dt)` as many times as needed to catch up with the real time when the
+    /// project has a fixed tick rate.
+    /// Returns the interpolation alpha to pass to `Update`, or None when the project does not use fixed ticks.
+    fn run_fixed_ticks(&mut self, delta_time: std::time::Duration) -> Option<f32> {
+        let fixed_tick = self.fixed_tick.as_mut()?;
+        let ticks = fixed_tick.advance(delta_time);
+        let alpha = fixed_tick.alpha();
+        let tick_count = fixed_tick.tick_count();
+        let tick_duration = fixed_tick.tick_duration().as_secs_f32();
+        self.lua_env.env_state.borrow_mut().tick_alpha = alpha;
+        if ticks.is_empty() {
+            return Some(alpha);
+        }
+
+        let globals = self.lua_env.lua_handle.lua.globals();
+        let tick_fn = globals
+            .get::<vectarine_plugin_sdk::mlua::Function>("Tick")
+            .ok();
+        let fixed_update_fn = globals
+            .get::<vectarine_plugin_sdk::mlua::Function>("FixedUpdate")
+            .ok();
+        if tick_fn.is_none() && fixed_update_fn.is_none() {
+            print_warn(
+                "Neither Tick() nor FixedUpdate() was found but fixed_tick_rate is set".to_string(),
+            );
+            return Some(alpha);
+        }
+        for tick_index in ticks {
+            self.lua_env.env_state.borrow_mut().tick_count = tick_index;
+            if let Some(tick_fn) = &tick_fn
+                && let Err(err) = tick_fn.call::<()>((tick_index,))
+            {
+                print_lua_error_from_error(&self.lua_env.lua_handle, &err);
+            }
+            if let Some(fixed_update_fn) = &fixed_update_fn
+                && let Err(err) = fixed_update_fn.call::<()>((tick_duration,))
+            {
+                print_lua_error_from_error(&self.lua_env.lua_handle, &err);
+            }
+        }
+        self.lua_env.env_state.borrow_mut().tick_count = tick_count;
+        Some(alpha)
+    }
+
+    /// Recreates the GPU objects of the game after the OpenGL context was lost and a new one was made current.
+    /// Objects created directly from Lua (like canvases) cannot be recreated and are left invalid.
+    pub fn recreate_gpu_objects(&mut self) -> Result<(), String> {
+        let batch = BatchDraw2d::new(&self.gl)?;
+        // The old objects belong to the lost context, deleting them could delete objects of the new context.
+        std::mem::forget(std::mem::replace(
+            &mut *self.lua_env.batch.borrow_mut(),
+            batch,
+        ));
+        font_resource::recreate_default_font_gpu_objects(&self.gl);
+        self.lua_env.latency.borrow_mut().forget_gpu_queries();
+        self.lua_env.resources.recreate_gpu_objects(
+            self.gl.clone(),
+            self.lua_env.lua_handle.clone(),
+            self.lua_env.default_events.resource_loaded_event.clone(),
+        );
+        Ok(())
     }
 
-    /// Calls reload on all unloaded resource inside the manager.
+    /// Calls reload on the unloaded resources inside the manager, the most urgent first.
     pub fn load_resource_as_needed(&mut self) {
-        let mut to_reload = Vec::new();
-        {
-            let resource_manager = &self.lua_env.resources;
-            for (id, resource) in resource_manager.enumerate() {
-                if resource.get_path().display().to_string() == self.main_script_path {
-                    self.was_main_script_executed = resource.get_status() == Status::Loaded;
-                }
-                if resource.get_status() != Status::Unloaded {
-                    continue;
-                }
-                to_reload.push(id);
+        let was_main_script_executed = self.was_main_script_executed;
+        for resource in self.lua_env.resources.iter() {
+            if resource.get_path().display().to_string() == self.main_script_path {
+                self.was_main_script_executed = resource.get_status() == Status::Loaded;
             }
         }
-        for resource_id in to_reload {
+        if self.was_main_script_executed && !was_main_script_executed {
+            // The resources requested by the main script when it runs are the ones the first frames need.
+            self.lua_env.resources.prioritize_unloaded_resources();
+        }
+        for resource_id in self.lua_env.resources.take_resources_to_load() {
             self.lua_env.resources.clone().reload(
                 resource_id,
                 self.gl.clone(),
@@ -415,6 +815,60 @@ impl Game {
     }
 }
 
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.plugin_env.release_hook(self.plugin_interface());
+        // The plugins can keep clones of the handles, they must not reach the game once it is gone.
+        self.plugin_handles.invalidate();
+    }
+}
+
+fn apply_gc_step_per_frame(lua_env: &LuaEnvironment, project_info: &ProjectInfo) {
+    if !project_info.gc_step_per_frame {
+        return;
+    }
+    let result = lua_env
+        .gc_pacer
+        .borrow_mut()
+        .set_step_per_frame(&lua_env.lua_handle.lua, true);
+    if let Err(err) = result {
+        print_lua_error_from_error(&lua_env.lua_handle, &err);
+    }
+}
+
+/// Keeps the functions renamed since the version of the API of the project.
+fn apply_api_version(lua_env: &LuaEnvironment, project_info: &ProjectInfo) {
+    let result = install_deprecation_shims(
+        &lua_env.lua_handle.lua,
+        project_info.api_version(),
+        DEPRECATED_FUNCTIONS,
+    );
+    if let Err(err) = result {
+        print_lua_error_from_error(&lua_env.lua_handle, &err);
+    }
+}
+
+/// Gives the physics settings of the project to the physics API and prints the problems of the collision layers.
+fn apply_physics_info(lua_env: &LuaEnvironment, project_info: &ProjectInfo) {
+    let layers = &project_info.physics.layers;
+    for problem in layers.errors().into_iter().chain(layers.warnings()) {
+        print_warn(problem);
+    }
+    lua_env.collision_layers.borrow_mut().set(layers.clone());
+    lua_env
+        .deterministic_physics
+        .set(project_info.deterministic_physics);
+}
+
+/// A limit of 0 in the project means no limit. Without a limit in the project, the default of the platform is kept.
+fn apply_max_parallel_loads(resources: &ResourceManager, project_info: &ProjectInfo) {
+    if let Some(max_parallel_loads) = project_info.max_parallel_loads {
+        resources.set_max_parallel_loads(
+            Some(max_parallel_loads as usize).filter(|max_parallel_loads| *max_parallel_loads > 0),
+        );
+    }
+}
+
 #[cfg(not(target_os = "emscripten"))]
 pub fn drawable_screen_size(window: &sdl2::video::Window) -> (u32, u32) {
     window.drawable_size()
@@ -445,15 +899,42 @@ pub fn screen_size(_window: &sdl2::video::Window) -> (u32, u32) {
 }
 
 #[cfg(not(target_os = "emscripten"))]
-pub fn set_viewport(gl: &glow::Context, width: u32, height: u32) {
-    unsafe {
-        gl.viewport(0, 0, width as i32, height as i32);
-    }
+pub fn safe_area_insets(_px_ratio_x: f32, _px_ratio_y: f32) -> SafeAreaInsets {
+    // SDL2 does not know about notches.
+    SafeAreaInsets::default()
 }
 
+/// Reads the `env(safe-area-inset-*)` CSS values, which are in CSS pixels.
 #[cfg(target_os = "emscripten")]
-pub fn set_viewport(gl: &glow::Context, width: u32, height: u32) {
+pub fn safe_area_insets(px_ratio_x: f32, px_ratio_y: f32) -> SafeAreaInsets {
+    use emscripten_val::Val;
+    let insets = Val::global("vectarine").call("getSafeAreaInsets", &[]);
+    let inset = |name: &str| insets.get(&Val::from_str(name)).as_f64() as f32;
+    SafeAreaInsets {
+        top: inset("top") * px_ratio_y,
+        right: inset("right") * px_ratio_x,
+        bottom: inset("bottom") * px_ratio_y,
+        left: inset("left") * px_ratio_x,
+    }
+}
+
+/// Restricts the drawing to the game area. When the game area is smaller than the window, the bars around it are
+/// cleared in black and the scissor test keeps `Graphics.clear` from drawing over them.
+pub fn set_viewport(
+    gl: &glow::Context,
+    window_width: u32,
+    window_height: u32,
+    viewport: &Viewport,
+) {
     unsafe {
-        gl.viewport(0, 0, width as i32, height as i32);
+        gl.disable(glow::SCISSOR_TEST);
+        if *viewport != Viewport::from_size(window_width as i32, window_height as i32) {
+            gl.viewport(0, 0, window_width as i32, window_height as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            gl.enable(glow::SCISSOR_TEST);
+            gl.scissor(viewport.x, viewport.y, viewport.width, viewport.height);
+        }
+        gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
     }
 }
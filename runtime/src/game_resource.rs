@@ -1,6 +1,6 @@
 use std::{
-    cell::RefCell,
-    collections::HashSet,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     path::{Path, PathBuf},
     rc::{Rc, Weak},
     sync::Arc,
@@ -11,18 +11,29 @@ use vectarine_plugin_sdk::mlua::IntoLua;
 use vectarine_plugin_sdk::serde::{Deserialize, Serialize};
 
 use crate::{
-    game_resource::script_resource::ScriptResource,
-    io::{dummyfs::DummyFileSystem, fs::ReadOnlyFileSystem},
+    game_resource::{script_graph::ScriptGraph, script_resource::ScriptResource},
+    io::{
+        contentpack::{self, GameIdentity, MountedPack, PackMounts},
+        dummyfs::DummyFileSystem,
+        enginefs::{self, EngineFileSystem},
+        fs::ReadOnlyFileSystem,
+        paths::{self, describe_path},
+        zipfs::ZipFileSystem,
+    },
     lua_env::{LuaHandle, lua_event::EventType},
 };
 
+pub mod animation_resource;
 pub mod audio_resource;
 pub mod font_resource;
 pub mod image_resource;
+pub mod plugin_resource;
+pub mod script_graph;
 pub mod script_resource;
 pub mod shader_resource;
 pub mod text_resource;
 pub mod tile_resource;
+pub mod video_resource;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Status {
@@ -43,8 +54,66 @@ impl std::fmt::Display for Status {
     }
 }
 
+/// The order in which waiting resources are loaded when the number of resources loading at the same time is limited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoadPriority {
+    High,
+    Normal,
+    Low,
+}
+
+/// A resource waiting for this many frames is loaded as if its priority was one level higher, so that low priority
+/// resources are not delayed forever by higher priority ones.
+const FRAMES_PER_PRIORITY_LEVEL: u32 = 60;
+
+/// Browsers slow down when too many files are fetched at the same time.
+#[cfg(target_os = "emscripten")]
+const DEFAULT_MAX_PARALLEL_LOADS: Option<usize> = Some(4);
+#[cfg(not(target_os = "emscripten"))]
+const DEFAULT_MAX_PARALLEL_LOADS: Option<usize> = None;
+
+impl LoadPriority {
+    pub const ALL: [LoadPriority; 3] =
+        [LoadPriority::High, LoadPriority::Normal, LoadPriority::Low];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "high" => Some(LoadPriority::High),
+            "normal" => Some(LoadPriority::Normal),
+            "low" => Some(LoadPriority::Low),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LoadPriority::High => "high",
+            LoadPriority::Normal => "normal",
+            LoadPriority::Low => "low",
+        }
+    }
+
+    /// Resources with a higher urgency are loaded first.
+    fn urgency(&self, waiting_frames: u32) -> u32 {
+        let level = match self {
+            LoadPriority::High => 2,
+            LoadPriority::Normal => 1,
+            LoadPriority::Low => 0,
+        };
+        level * FRAMES_PER_PRIORITY_LEVEL + waiting_frames
+    }
+}
+
+/// The number of resources of a priority in each state, to display the progress of a loading screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadingProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
 /// Represents a valid identifier for a resource
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(crate = "vectarine_plugin_sdk::serde")]
 pub struct ResourceId(usize);
 
@@ -70,13 +139,19 @@ pub struct ResourceHolder {
     dependencies: RefCell<HashSet<ResourceId>>,
     /// A list of ids of other resources that depend on this resource
     dependent: RefCell<HashSet<ResourceId>>,
+
+    /// None when the game did not choose a priority, the resource is then loaded with the normal priority.
+    priority: Cell<Option<LoadPriority>>,
+    /// Number of frames the resource waited to load because too many resources were already loading.
+    waiting_frames: Cell<u32>,
+    /// The name of the content pack the file of the resource was read from, None for the files of the game.
+    source_pack: RefCell<Option<String>>,
 }
 
 impl ResourceHolder {
     /// Request the resource to be reloaded.
     fn reload(
         self: Rc<Self>,
-        file_system: &dyn ReadOnlyFileSystem,
         assigned_id: ResourceId,
         resource_manager: Rc<ResourceManager>,
         gl: Arc<glow::Context>,
@@ -99,18 +174,31 @@ impl ResourceHolder {
         };
 
         self.status.replace(Status::Loading);
-        let abs_path = get_absolute_path(&resource_manager.base_path, &self.path);
+        let pack = resource_manager.find_pack(&self.path);
+        let (file_system, file_path) = resource_manager.locate_file(&self.path, &pack);
+        self.source_pack
+            .replace(pack.as_ref().map(|(name, _)| name.clone()));
 
         // We pass data to the resource into the closure.
         // As this data needs to be kept alive, every piece of state pass inside needs Rc or Arc.
+        let described_path = describe_path(&file_path);
         file_system.read_file(
-            &abs_path,
+            &file_path,
             Box::new(move |data| {
+                let is_pack_unmounted = self.source_pack.borrow().as_deref().is_some_and(|name| {
+                    dr.resource_manager
+                        .upgrade()
+                        .is_some_and(|resource_manager| !resource_manager.is_pack_mounted(name))
+                });
+                if is_pack_unmounted {
+                    // Loaded again from the files that replace the pack.
+                    self.source_pack.replace(None);
+                    self.status.replace(Status::Unloaded);
+                    return;
+                }
                 let Some(data) = data else {
-                    self.status.replace(Status::Error(format!(
-                        "File not found: {}",
-                        self.path.display()
-                    )));
+                    self.status
+                        .replace(Status::Error(format!("File not found: {described_path}")));
                     return;
                 };
                 let resulting_status = self.resource.clone().load_from_data(
@@ -121,6 +209,7 @@ impl ResourceHolder {
                     &self.path,
                     data.into_boxed_slice(),
                 );
+                let is_done = matches!(resulting_status, Status::Loaded | Status::Error(_));
                 self.status.replace(resulting_status);
                 let _ = resource_event.trigger(
                     assigned_id
@@ -128,6 +217,18 @@ impl ResourceHolder {
                         .into_lua(&lua.lua)
                         .expect("Failed to convert usize to Lua"),
                 );
+                // The resources using this one, like the shaders including it, are loaded again with the new content.
+                if is_done && let Some(resource_manager) = dr.resource_manager.upgrade() {
+                    let dependents = self.dependent.borrow().iter().copied().collect::<Vec<_>>();
+                    for dependent_id in dependents {
+                        resource_manager.reload(
+                            dependent_id,
+                            gl.clone(),
+                            lua.clone(),
+                            resource_event.clone(),
+                        );
+                    }
+                }
             }),
         );
     }
@@ -175,12 +276,29 @@ impl ResourceHolder {
     pub fn is_loaded(&self) -> bool {
         matches!(*self.status.borrow(), Status::Loaded)
     }
+
+    pub fn get_priority(&self) -> LoadPriority {
+        self.priority.get().unwrap_or(LoadPriority::Normal)
+    }
+
+    pub fn get_source_pack(&self) -> Option<String> {
+        self.source_pack.borrow().clone()
+    }
 }
 
 pub struct ResourceManager {
     file_system: Box<dyn ReadOnlyFileSystem>,
     resources: RefCell<Vec<Rc<ResourceHolder>>>,
     base_path: PathBuf,
+    /// None when there is no limit.
+    max_parallel_loads: Cell<Option<usize>>,
+    /// Priorities set for paths that are not loaded yet. They are applied when the resource is created.
+    pending_priorities: RefCell<HashMap<PathBuf, LoadPriority>>,
+    /// Which scripts loaded which, to run the scripts using a module again when the module is edited.
+    pub script_graph: RefCell<ScriptGraph>,
+    packs: RefCell<PackMounts>,
+    /// The content packs mounted must belong to this game.
+    game_identity: RefCell<GameIdentity>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -224,6 +342,18 @@ impl DependencyReporter {
         resource_manager.declare_dependency::<T>(id, path);
     }
 
+    /// Runs `f` with the graph of the scripts loaded by other scripts. Lua must not run inside `f`.
+    pub fn with_script_graph<R>(&self, f: impl FnOnce(&mut ScriptGraph) -> R) -> Option<R> {
+        let resource_manager = self.resource_manager.upgrade()?;
+        let mut script_graph = resource_manager.script_graph.borrow_mut();
+        Some(f(&mut script_graph))
+    }
+
+    pub fn obtain_resource_status(&self, resource_id: ResourceId) -> Option<Status> {
+        let resource_manager = self.resource_manager.upgrade()?;
+        Some(resource_manager.get_holder_by_id(resource_id).get_status())
+    }
+
     /// Obtain a ResourceId to a resource you depend on. If the resource is not loaded yet, return None.
     /// This function runs in O(N) currently.
     /// In that case, you should declare the dependency and return Unloaded to wait for the resource to be loaded.
@@ -250,6 +380,11 @@ impl ResourceManager {
             resources: RefCell::new(Vec::new()),
             base_path: base_path.to_path_buf(),
             file_system,
+            max_parallel_loads: Cell::new(DEFAULT_MAX_PARALLEL_LOADS),
+            pending_priorities: RefCell::new(HashMap::new()),
+            script_graph: RefCell::new(ScriptGraph::default()),
+            packs: RefCell::new(PackMounts::default()),
+            game_identity: RefCell::new(GameIdentity::default()),
         }
     }
 
@@ -262,6 +397,11 @@ impl ResourceManager {
             resources: RefCell::new(Vec::new()),
             base_path: PathBuf::new(),
             file_system: Box::new(DummyFileSystem {}),
+            max_parallel_loads: Cell::new(DEFAULT_MAX_PARALLEL_LOADS),
+            pending_priorities: RefCell::new(HashMap::new()),
+            script_graph: RefCell::new(ScriptGraph::default()),
+            packs: RefCell::new(PackMounts::default()),
+            game_identity: RefCell::new(GameIdentity::default()),
         }
     }
 
@@ -291,6 +431,13 @@ impl ResourceManager {
             .unwrap_or_default()
             .to_string_lossy()
             .to_string();
+        let priority = if self.pending_priorities.borrow().is_empty() {
+            None
+        } else {
+            self.pending_priorities
+                .borrow_mut()
+                .remove(&get_canonical_absolute_path(&self.base_path, path))
+        };
 
         self.resources.borrow_mut().push(Rc::new(ResourceHolder {
             status: RefCell::new(Status::Unloaded),
@@ -298,6 +445,9 @@ impl ResourceManager {
             name,
             dependencies: RefCell::new(HashSet::new()),
             dependent: RefCell::new(HashSet::new()),
+            priority: Cell::new(priority),
+            waiting_frames: Cell::new(0),
+            source_pack: RefCell::new(None),
             resource,
         }));
 
@@ -361,16 +511,25 @@ impl ResourceManager {
             };
             resource.clone()
         };
-        // Check if the dependency is already exists. Create it if not.
-        let holder = &self
+        // Create the dependency if it does not exist yet.
+        let dependency_id = self
             .get_id_by_path(path)
-            .map(|id| self.get_holder_by_id_unchecked(id));
-        if let Some(holder) = holder {
-            holder.dependent.borrow_mut().insert(resource_id);
-            resource.dependent.borrow_mut().insert(resource_id);
-            return;
-        };
-        self.schedule_load_resource::<T>(path);
+            .unwrap_or_else(|| self.schedule_load_resource::<T>(path));
+        resource.dependencies.borrow_mut().insert(dependency_id);
+        self.get_holder_by_id_unchecked(dependency_id)
+            .dependent
+            .borrow_mut()
+            .insert(resource_id);
+    }
+
+    /// Resources whose dependencies are still loading are loaded once the dependencies are done.
+    fn is_waiting_for_dependencies(&self, holder: &ResourceHolder) -> bool {
+        holder.dependencies.borrow().iter().any(|id| {
+            matches!(
+                self.get_holder_by_id_unchecked(*id).get_status(),
+                Status::Unloaded | Status::Loading
+            )
+        })
     }
 
     pub fn reload(
@@ -381,14 +540,136 @@ impl ResourceManager {
         loaded_event: EventType,
     ) {
         let resource = self.get_holder_by_id(id);
-        resource.reload(
-            self.file_system.as_ref(),
-            id,
-            self.clone(),
-            gl,
-            lua,
-            loaded_event,
-        );
+        resource.reload(id, self.clone(), gl, lua, loaded_event);
+    }
+
+    /// Reads the file of a resource again without reloading it, like the pixels of an image that are not kept in memory.
+    pub fn read_resource_file(&self, id: ResourceId, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+        let path = self.get_holder_by_id(id).path.clone();
+        let pack = self.find_pack(&path);
+        let (file_system, file_path) = self.locate_file(&path, &pack);
+        file_system.read_file(&file_path, callback);
+    }
+
+    /// The file system and the path to read the file of the resource at `path` from.
+    fn locate_file<'a>(
+        &'a self,
+        path: &Path,
+        pack: &'a Option<(String, Rc<ZipFileSystem>)>,
+    ) -> (&'a dyn ReadOnlyFileSystem, PathBuf) {
+        // Engine assets are embedded in the binary, they are not read from the project folder.
+        // The files of the mounted content packs hide the files of the game with the same path.
+        match (enginefs::strip_engine_prefix(path), pack) {
+            (Some(engine_path), _) => (
+                &EngineFileSystem as &dyn ReadOnlyFileSystem,
+                PathBuf::from(engine_path),
+            ),
+            (None, Some((_, pack_files))) => (
+                &**pack_files as &dyn ReadOnlyFileSystem,
+                contentpack::path_in_pack(path),
+            ),
+            (None, None) => (
+                self.file_system.as_ref(),
+                get_absolute_path(&self.base_path, path),
+            ),
+        }
+    }
+
+    /// Limits the number of resources loading at the same time. None removes the limit.
+    pub fn set_max_parallel_loads(&self, max_parallel_loads: Option<usize>) {
+        self.max_parallel_loads.set(max_parallel_loads);
+    }
+
+    /// Sets the priority of the resource at the path. The path does not need to be loaded yet.
+    pub fn set_priority(&self, path: &Path, priority: LoadPriority) {
+        match self.get_id_by_path(path) {
+            Some(id) => self.get_holder_by_id(id).priority.set(Some(priority)),
+            None => {
+                self.pending_priorities
+                    .borrow_mut()
+                    .insert(get_canonical_absolute_path(&self.base_path, path), priority);
+            }
+        }
+    }
+
+    /// Gives the high priority to the resources waiting to load whose priority was not chosen by the game.
+    pub fn prioritize_unloaded_resources(&self) {
+        for holder in self.iter() {
+            if holder.priority.get().is_none() && holder.get_status() == Status::Unloaded {
+                holder.priority.set(Some(LoadPriority::High));
+            }
+        }
+    }
+
+    /// Returns the resources to load this frame, the most urgent first.
+    /// When the number of parallel loads is limited, the resources left waiting become more urgent.
+    pub fn take_resources_to_load(&self) -> Vec<ResourceId> {
+        let mut loading_count = 0;
+        let mut waiting = Vec::new();
+        for (id, holder) in self.enumerate() {
+            match holder.get_status() {
+                Status::Loading => loading_count += 1,
+                Status::Unloaded if self.is_waiting_for_dependencies(&holder) => {}
+                Status::Unloaded => waiting.push((id, holder)),
+                Status::Loaded | Status::Error(_) => {}
+            }
+        }
+        // Among resources as urgent as each other, the ones requested first are loaded first.
+        waiting.sort_by_key(|(id, holder)| {
+            (
+                std::cmp::Reverse(holder.get_priority().urgency(holder.waiting_frames.get())),
+                id.0,
+            )
+        });
+        let available_slots = match self.max_parallel_loads.get() {
+            Some(max_parallel_loads) => max_parallel_loads.saturating_sub(loading_count),
+            None => usize::MAX,
+        };
+        for (_, holder) in waiting.iter().skip(available_slots) {
+            holder.waiting_frames.set(holder.waiting_frames.get() + 1);
+        }
+        waiting
+            .into_iter()
+            .take(available_slots)
+            .map(|(id, holder)| {
+                holder.waiting_frames.set(0);
+                id
+            })
+            .collect()
+    }
+
+    pub fn get_loading_progress(&self, priority: LoadPriority) -> LoadingProgress {
+        let mut progress = LoadingProgress::default();
+        for holder in self
+            .iter()
+            .filter(|holder| holder.get_priority() == priority)
+        {
+            progress.total += 1;
+            match holder.get_status() {
+                Status::Loaded => progress.loaded += 1,
+                Status::Error(_) => progress.failed += 1,
+                Status::Unloaded | Status::Loading => {}
+            }
+        }
+        progress
+    }
+
+    /// Recreates the GPU objects of every loaded resource after the OpenGL context was lost.
+    /// Resources that cannot rebuild their GPU objects from memory are reloaded from their file.
+    pub fn recreate_gpu_objects(
+        self: &Rc<Self>,
+        gl: Arc<glow::Context>,
+        lua: Rc<LuaHandle>,
+        loaded_event: EventType,
+    ) {
+        let to_reload = self
+            .enumerate()
+            .filter(|(_, holder)| holder.is_loaded() && holder.resource.recreate_gpu_objects(&gl))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        for id in to_reload {
+            self.reload(id, gl.clone(), lua.clone(), loaded_event.clone());
+        }
     }
 
     /// Performance: O(n) for now. Store the ID and use instead get_by_id if you already have the id.
@@ -412,6 +693,17 @@ impl ResourceManager {
         resource.get_underlying_resource::<T>()
     }
 
+    /// Ids are always created valid, but an id deserialized from a previous session can point past the end of the list.
+    pub fn is_valid_id(&self, id: ResourceId) -> bool {
+        id.0 < self.resources.borrow().len()
+    }
+
+    /// Returns the id of the resource with this index, as given by `getId` in Lua or to plugins.
+    pub fn get_id_by_index(&self, index: usize) -> Option<ResourceId> {
+        self.is_valid_id(ResourceId(index))
+            .then_some(ResourceId(index))
+    }
+
     pub fn get_holder_by_id(&self, id: ResourceId) -> Rc<ResourceHolder> {
         let resources = self.resources.borrow();
         match resources.get(id.0) {
@@ -470,9 +762,86 @@ impl ResourceManager {
         None
     }
 
-    pub fn get_absolute_path(&self, resource_path: &Path) -> String {
+    pub fn get_absolute_path(&self, resource_path: &Path) -> PathBuf {
         get_absolute_path(&self.base_path, resource_path)
     }
+
+    pub fn set_game_identity(&self, game_identity: GameIdentity) {
+        self.game_identity.replace(game_identity);
+    }
+
+    /// Mounts a content pack from the content of its bundle and returns its name.
+    /// The loaded resources whose file is in the pack are loaded again from the pack.
+    pub fn mount_pack(&self, bundle: Vec<u8>, priority: i32) -> Result<String, String> {
+        let pack = MountedPack::open(bundle, priority)?;
+        pack.manifest
+            .check_compatibility(&self.game_identity.borrow())?;
+        let name = pack.name().to_string();
+        self.packs.borrow_mut().mount(pack)?;
+        for holder in self.iter() {
+            if !holder.is_loading()
+                && holder.get_status() != Status::Unloaded
+                && self
+                    .find_pack(&holder.path)
+                    .is_some_and(|(pack_name, _)| pack_name == name)
+            {
+                holder.status.replace(Status::Unloaded);
+            }
+        }
+        Ok(name)
+    }
+
+    /// Unmounts a content pack and releases its files. The resources read from it, and the ones using them, are loaded
+    /// again from the other packs or from the files of the game, and fail to load when their file is only in the pack.
+    /// The resources loading from the pack are loaded again once their file is read. Returns false if the pack was not
+    /// mounted.
+    pub fn unmount_pack(&self, name: &str) -> bool {
+        if self.packs.borrow_mut().unmount(name).is_none() {
+            return false;
+        }
+        let mut invalidated = self
+            .enumerate()
+            .filter(|(_, holder)| {
+                holder.source_pack.borrow().as_deref() == Some(name) && !holder.is_loading()
+            })
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        let mut visited = HashSet::new();
+        while let Some(id) = invalidated.pop() {
+            if !visited.insert(id) {
+                continue;
+            }
+            let holder = self.get_holder_by_id(id);
+            if holder.is_loading() {
+                continue;
+            }
+            holder.source_pack.replace(None);
+            holder.status.replace(Status::Unloaded);
+            invalidated.extend(holder.dependent.borrow().iter().copied());
+        }
+        true
+    }
+
+    /// Whether the content pack is still mounted, to drop what a resource read from a pack unmounted meanwhile.
+    fn is_pack_mounted(&self, name: &str) -> bool {
+        self.packs.borrow().iter().any(|pack| pack.name() == name)
+    }
+
+    /// The names of the mounted content packs, from the highest priority to the lowest.
+    pub fn get_mounted_packs(&self) -> Vec<String> {
+        self.packs
+            .borrow()
+            .iter()
+            .map(|pack| pack.name().to_string())
+            .collect()
+    }
+
+    /// The name and the files of the pack the resource at `path` is read from, if any.
+    fn find_pack(&self, path: &Path) -> Option<(String, Rc<ZipFileSystem>)> {
+        let packs = self.packs.borrow();
+        let pack = packs.find(path)?;
+        Some((pack.name().to_string(), pack.files.clone()))
+    }
     pub fn get_resource_path(&self) -> PathBuf {
         self.base_path.clone()
     }
@@ -509,21 +878,25 @@ pub trait Resource: ResourceToAny {
     /// This is usually the name of the struct implementing the trait.
     fn get_type_name(&self) -> &'static str;
 
+    /// Called after the OpenGL context was lost and a new one was created.
+    /// The GPU objects of the resource are invalid and must be forgotten, not deleted, as their names can now belong to
+    /// objects of the new context. Return true if the resource needs to be reloaded from its file to upload its data again.
+    fn recreate_gpu_objects(&self, _gl: &Arc<glow::Context>) -> bool {
+        false
+    }
+
     /// Create an empty instance of a resource
     fn default() -> Self
     where
         Self: Sized;
 }
 
-pub fn get_absolute_path(current_base_path: &Path, resource_path: &Path) -> String {
-    let abs_path = current_base_path.join(resource_path);
-    // let abs_path = abs_path.canonicalize().unwrap_or(abs_path);
-    abs_path.to_string_lossy().replace("\\", "/")
+/// The path is kept as is: converting it to a string would break the paths that are not valid Unicode.
+pub fn get_absolute_path(current_base_path: &Path, resource_path: &Path) -> PathBuf {
+    current_base_path.join(resource_path)
 }
 pub fn get_canonical_absolute_path(current_base_path: &Path, resource_path: &Path) -> PathBuf {
-    current_base_path
-        .join(resource_path)
-        .canonicalize()
+    paths::canonicalize(&current_base_path.join(resource_path))
         .unwrap_or_else(|_| current_base_path.join(resource_path))
 }
 
@@ -540,3 +913,151 @@ impl<T: Resource + 'static> ResourceToAny for T {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        path::Path,
+    };
+
+    use zip::write::SimpleFileOptions;
+
+    use super::{
+        FRAMES_PER_PRIORITY_LEVEL, LoadPriority, ResourceId, ResourceManager, Status,
+        text_resource::TextResource,
+    };
+    use crate::io::contentpack::GameIdentity;
+
+    fn schedule(resources: &ResourceManager, path: &str) -> ResourceId {
+        resources.schedule_load_resource::<TextResource>(Path::new(path))
+    }
+
+    /// Marks the resources as loading, like `ResourceHolder::reload` does.
+    fn start_loading(resources: &ResourceManager) -> Vec<ResourceId> {
+        let ids = resources.take_resources_to_load();
+        for id in &ids {
+            resources
+                .get_holder_by_id(*id)
+                .status
+                .replace(Status::Loading);
+        }
+        ids
+    }
+
+    fn finish_loading(resources: &ResourceManager, ids: &[ResourceId]) {
+        for id in ids {
+            resources
+                .get_holder_by_id(*id)
+                .status
+                .replace(Status::Loaded);
+        }
+    }
+
+    #[test]
+    fn high_priority_resources_load_first_within_the_limit() {
+        let resources = ResourceManager::dummy_manager();
+        resources.set_max_parallel_loads(Some(2));
+        let tiles = (0..5)
+            .map(|i| schedule(&resources, &format!("tiles/{i}.png")))
+            .collect::<Vec<_>>();
+        // The priority can be set before the resource is requested.
+        resources.set_priority(Path::new("fonts/title.ttf"), LoadPriority::High);
+        let font = schedule(&resources, "fonts/title.ttf");
+
+        let first = start_loading(&resources);
+        assert_eq!(first, vec![font, tiles[0]]);
+        // Nothing else starts while two resources are loading.
+        assert!(start_loading(&resources).is_empty());
+        finish_loading(&resources, &first);
+        assert_eq!(start_loading(&resources), vec![tiles[1], tiles[2]]);
+
+        let high = resources.get_loading_progress(LoadPriority::High);
+        assert_eq!((high.loaded, high.total), (1, 1));
+        let normal = resources.get_loading_progress(LoadPriority::Normal);
+        assert_eq!((normal.loaded, normal.total), (1, 5));
+    }
+
+    #[test]
+    fn low_priority_resources_are_not_starved() {
+        let resources = ResourceManager::dummy_manager();
+        resources.set_max_parallel_loads(Some(1));
+        let background = schedule(&resources, "music/background.ogg");
+        resources.set_priority(Path::new("music/background.ogg"), LoadPriority::Low);
+
+        // The game keeps requesting normal priority resources faster than they load.
+        let mut frame = 0;
+        loop {
+            schedule(&resources, &format!("levels/{frame}.txt"));
+            schedule(&resources, &format!("levels/{frame}.json"));
+            let loading = start_loading(&resources);
+            finish_loading(&resources, &loading);
+            if loading.contains(&background) {
+                break;
+            }
+            frame += 1;
+            assert!(
+                frame <= 2 * FRAMES_PER_PRIORITY_LEVEL,
+                "The low priority resource never loaded"
+            );
+        }
+    }
+
+    #[test]
+    fn unmounting_a_pack_unloads_its_resources_and_the_ones_using_them() {
+        let resources = ResourceManager::dummy_manager();
+        resources.set_game_identity(GameIdentity {
+            title: "Test".to_string(),
+            version: Some("1.0".to_string()),
+        });
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in [
+            ("pack.toml", "name = \"levels\"\ngame = \"Test\"\n"),
+            ("gamedata/levels/1.txt", "level 1"),
+        ] {
+            zip.start_file(path, SimpleFileOptions::default())
+                .expect("the entry is created");
+            zip.write_all(content.as_bytes())
+                .expect("the entry is written");
+        }
+        let bundle = zip.finish().expect("the zip is written").into_inner();
+        let name = resources.mount_pack(bundle, 0).expect("the pack mounts");
+
+        let level = schedule(&resources, "levels/1.txt");
+        let script = schedule(&resources, "main.luau");
+        let loading_level = schedule(&resources, "levels/2.txt");
+        let menu = schedule(&resources, "menu.luau");
+        for id in [level, script, menu] {
+            resources
+                .get_holder_by_id(id)
+                .status
+                .replace(Status::Loaded);
+        }
+        for id in [level, loading_level] {
+            resources
+                .get_holder_by_id(id)
+                .source_pack
+                .replace(Some(name.clone()));
+        }
+        resources
+            .get_holder_by_id(loading_level)
+            .status
+            .replace(Status::Loading);
+        resources
+            .get_holder_by_id(level)
+            .dependent
+            .borrow_mut()
+            .insert(script);
+
+        assert!(resources.unmount_pack(&name));
+        assert!(!resources.unmount_pack(&name));
+        assert!(!resources.is_pack_mounted(&name));
+        let status = |id| resources.get_holder_by_id(id).get_status();
+        assert_eq!(status(level), Status::Unloaded);
+        assert_eq!(resources.get_holder_by_id(level).get_source_pack(), None);
+        assert_eq!(status(script), Status::Unloaded);
+        assert_eq!(status(menu), Status::Loaded);
+        // Dropped once its file is read.
+        assert_eq!(status(loading_level), Status::Loading);
+    }
+}
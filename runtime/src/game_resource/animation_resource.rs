@@ -0,0 +1,288 @@
+use std::{cell::RefCell, collections::BTreeMap, path::Path, rc::Rc};
+
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::serde::Deserialize;
+
+use crate::{
+    game_resource::{Resource, ResourceId, Status, image_resource::ImageResource},
+    lua_env::LuaHandle,
+};
+
+/// The duration of the frames of an animation that gives none, in seconds.
+pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde", rename_all = "lowercase")]
+pub enum LoopMode {
+    /// Starts over from the first frame after the last one.
+    #[default]
+    Loop,
+    /// Stays on the last frame.
+    Once,
+    /// Plays the frames backwards after the last one, then forwards again.
+    PingPong,
+}
+
+/// A frame is either its rectangle, or its rectangle with its own duration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde", untagged)]
+enum FrameDefinition {
+    Rect([f32; 4]),
+    Detailed {
+        rect: [f32; 4],
+        duration: Option<f32>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde", rename_all = "camelCase")]
+struct AnimationDefinition {
+    frames: Vec<FrameDefinition>,
+    frame_duration: Option<f32>,
+    #[serde(default, rename = "loop")]
+    loop_mode: LoopMode,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde", deny_unknown_fields)]
+struct AnimationFileDefinition {
+    image: String,
+    animations: BTreeMap<String, AnimationDefinition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    /// The position and the size of the frame in the image, in pixels from the top left corner.
+    pub rect: [f32; 4],
+    /// In seconds.
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteAnimation {
+    pub frames: Vec<Frame>,
+    pub loop_mode: LoopMode,
+    /// The indices of the frames in the order they are shown during a cycle of the animation.
+    order: Vec<usize>,
+    cycle_duration: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(frames: Vec<Frame>, loop_mode: LoopMode) -> Result<Self, String> {
+        if frames.is_empty() {
+            return Err("an animation needs at least one frame".to_string());
+        }
+        if let Some(frame) = frames
+            .iter()
+            .find(|frame| !frame.duration.is_finite() || frame.duration <= 0.0)
+        {
+            return Err(format!(
+                "the duration of a frame is {}, it should be more than 0",
+                frame.duration
+            ));
+        }
+        let mut order = (0..frames.len()).collect::<Vec<_>>();
+        if loop_mode == LoopMode::PingPong {
+            order.extend((1..frames.len().saturating_sub(1)).rev());
+        }
+        let cycle_duration = order.iter().map(|index| frames[*index].duration).sum();
+        Ok(Self {
+            frames,
+            loop_mode,
+            order,
+            cycle_duration,
+        })
+    }
+
+    /// The duration of a cycle: every frame once, or there and back for ping-pong animations.
+    pub fn cycle_duration(&self) -> f32 {
+        self.cycle_duration
+    }
+
+    /// The number of cycles completed after `time` seconds. Animations playing once only complete one.
+    pub fn completed_cycles(&self, time: f32) -> u64 {
+        let cycles = (time / self.cycle_duration).max(0.0) as u64;
+        if self.loop_mode == LoopMode::Once {
+            cycles.min(1)
+        } else {
+            cycles
+        }
+    }
+
+    /// The index of the frame shown after `time` seconds.
+    pub fn frame_at(&self, time: f32) -> usize {
+        let last = self.order[self.order.len() - 1];
+        if self.loop_mode == LoopMode::Once && time >= self.cycle_duration {
+            return last;
+        }
+        let mut remaining = time.max(0.0) % self.cycle_duration;
+        for index in &self.order {
+            remaining -= self.frames[*index].duration;
+            if remaining < 0.0 {
+                return *index;
+            }
+        }
+        last
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationContent {
+    /// The image containing the frames, loaded with the animations.
+    pub image: ResourceId,
+    pub image_path: String,
+    pub animations: BTreeMap<String, SpriteAnimation>,
+}
+
+/// Parses the definition of the animations. The image is the path of the file in the project.
+fn parse_animation_file(
+    data: &[u8],
+) -> Result<(String, BTreeMap<String, SpriteAnimation>), String> {
+    let definition = serde_json::from_slice::<AnimationFileDefinition>(data)
+        .map_err(|error| error.to_string())?;
+    let mut animations = BTreeMap::new();
+    for (name, animation) in definition.animations {
+        let default_duration = animation.frame_duration.unwrap_or(DEFAULT_FRAME_DURATION);
+        let frames = animation
+            .frames
+            .iter()
+            .map(|frame| match *frame {
+                FrameDefinition::Rect(rect) => Frame {
+                    rect,
+                    duration: default_duration,
+                },
+                FrameDefinition::Detailed { rect, duration } => Frame {
+                    rect,
+                    duration: duration.unwrap_or(default_duration),
+                },
+            })
+            .collect();
+        let animation = SpriteAnimation::new(frames, animation.loop_mode)
+            .map_err(|error| format!("animations.{name}: {error}"))?;
+        animations.insert(name, animation);
+    }
+    Ok((definition.image, animations))
+}
+
+/// Sprite-sheet animations described by a JSON file, like:
+///
+/// ```json
+/// {
+///     "image": "textures/hero.png",
+///     "animations": {
+///         "idle": { "frames": [[0, 0, 32, 32], [32, 0, 32, 32]], "frameDuration": 0.2 },
+///         "jump": { "frames": [[0, 32, 32, 32], { "rect": [32, 32, 32, 32], "duration": 0.3 }], "loop": "once" }
+///     }
+/// }
+/// ```
+pub struct AnimationResource {
+    pub content: RefCell<Option<AnimationContent>>,
+}
+
+impl Resource for AnimationResource {
+    fn load_from_data(
+        self: std::rc::Rc<Self>,
+        assigned_id: ResourceId,
+        dependency_reporter: &super::DependencyReporter,
+        _lua: &Rc<LuaHandle>,
+        _gl: std::sync::Arc<glow::Context>,
+        _path: &Path,
+        data: Box<[u8]>,
+    ) -> Status {
+        let (image_path, animations) = match parse_animation_file(&data) {
+            Ok(definition) => definition,
+            Err(error) => return Status::Error(error),
+        };
+        // The image does not need to be loaded for the animations to load: nothing is drawn until it is.
+        dependency_reporter
+            .declare_dependency::<ImageResource>(assigned_id, Path::new(&image_path));
+        let Some(image) = dependency_reporter.obtain_resource_id(Path::new(&image_path)) else {
+            return Status::Error(format!("The image {image_path} cannot be loaded"));
+        };
+        self.content.replace(Some(AnimationContent {
+            image,
+            image_path,
+            animations,
+        }));
+        Status::Loaded
+    }
+
+    fn draw_debug_gui(
+        &self,
+        _painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
+        ui: &mut vectarine_plugin_sdk::egui::Ui,
+    ) {
+        ui.label("Animation Resource");
+        let content = self.content.borrow();
+        let Some(content) = &*content else {
+            ui.label("<No content loaded>");
+            return;
+        };
+        ui.label(format!("image: {}", content.image_path));
+        for (name, animation) in &content.animations {
+            ui.label(format!(
+                "{name}: {} frames, {:.2} s, {:?}",
+                animation.frames.len(),
+                animation.cycle_duration(),
+                animation.loop_mode
+            ));
+        }
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        "Animation"
+    }
+
+    fn default() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            content: RefCell::new(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoopMode, parse_animation_file};
+
+    #[test]
+    fn frames_are_shown_in_the_order_of_the_loop_mode() {
+        let (image, animations) = parse_animation_file(
+            br#"{
+                "image": "hero.png",
+                "animations": {
+                    "walk": { "frames": [[0, 0, 8, 8], [8, 0, 8, 8], { "rect": [16, 0, 8, 8], "duration": 0.2 }] },
+                    "bounce": { "frames": [[0, 0, 8, 8], [8, 0, 8, 8], [16, 0, 8, 8]], "loop": "pingpong" },
+                    "jump": { "frames": [[0, 8, 8, 8], [8, 8, 8, 8]], "frameDuration": 0.5, "loop": "once" }
+                }
+            }"#,
+        )
+        .expect("valid definition");
+        assert_eq!(image, "hero.png");
+
+        let walk = &animations["walk"];
+        assert_eq!(walk.loop_mode, LoopMode::Loop);
+        let frames = [0.05, 0.15, 0.25, 0.35, 0.45].map(|time| walk.frame_at(time));
+        assert_eq!(frames, [0, 1, 2, 2, 0]);
+        assert_eq!(walk.completed_cycles(0.45), 1);
+
+        let bounce = &animations["bounce"];
+        let frames = [0.05, 0.15, 0.25, 0.35, 0.45].map(|time| bounce.frame_at(time));
+        assert_eq!(frames, [0, 1, 2, 1, 0]);
+
+        let jump = &animations["jump"];
+        assert_eq!(jump.frame_at(0.7), 1);
+        assert_eq!(jump.frame_at(100.0), 1);
+        assert_eq!(jump.completed_cycles(100.0), 1);
+
+        assert!(
+            parse_animation_file(
+                br#"{ "image": "a.png", "animations": { "a": { "frames": [] } } }"#
+            )
+            .is_err()
+        );
+        assert!(parse_animation_file(br#"{ "image": "a.png", "animatons": {} }"#).is_err());
+    }
+}
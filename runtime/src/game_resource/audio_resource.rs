@@ -4,9 +4,10 @@ use symphonia::core::audio::SampleBuffer;
 use symphonia::core::io::MediaSourceStream;
 
 use crate::{
+    console::{Subsystem, print_err_from},
     game_resource::{Resource, ResourceId, Status},
     lua_env::LuaHandle,
-    sound::{self, ChannelId},
+    sound::{self, ChannelId, DecodedSound, PlaySound, SoundData},
 };
 use vectarine_plugin_sdk::glow;
 
@@ -15,7 +16,7 @@ pub static AUDIO_CHANNELS: i32 = 2; // Stereo
 pub static BYTES_PER_SAMPLE: u32 = 2; // 16-bit audio
 
 pub struct AudioResource {
-    pub chunk: RefCell<Option<Box<[f32]>>>,
+    pub sound: RefCell<Option<SoundData>>,
     pub duration: RefCell<f32>,
     pub currently_used_channel: RefCell<Option<ChannelId>>,
 }
@@ -39,6 +40,80 @@ impl std::io::Read for ReadableBytes {
     }
 }
 
+/// Decodes the whole file to interleaved samples.
+fn decode_audio(data: Box<[u8]>) -> Result<Vec<f32>, String> {
+    decode_audio_with_format(data, &symphonia::core::probe::Hint::new())
+        .map(|(samples, _, _)| samples)
+}
+
+/// Decodes the whole file to interleaved samples, and returns them with their sample rate and number of channels.
+/// The hint helps recognizing formats without a header, like the audio of a video.
+pub fn decode_audio_with_format(
+    data: Box<[u8]>,
+    hint: &symphonia::core::probe::Hint,
+) -> Result<(Vec<f32>, u32, usize), String> {
+    let readable_data = ReadableBytes::new(data);
+    let read_only_source = Box::new(symphonia::core::io::ReadOnlySource::new(readable_data));
+    let mss = MediaSourceStream::new(read_only_source, Default::default());
+
+    let format_opts: symphonia::core::formats::FormatOptions = Default::default();
+    let metadata_opts: symphonia::core::meta::MetadataOptions = Default::default();
+    let decoder_opts: symphonia::core::codecs::DecoderOptions = Default::default();
+    let probed = symphonia::default::get_probe()
+        .format(hint, mss, &format_opts, &metadata_opts)
+        .map_err(|err| format!("Unknown audio format: {err}"))?;
+    let mut format = probed.format;
+    let track = format.default_track().ok_or("No default track")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &decoder_opts)
+        .map_err(|err| format!("Failed to create decoder: {err}"))?;
+
+    let mut result = Vec::new();
+    let mut sample_rate = track
+        .codec_params
+        .sample_rate
+        .unwrap_or(AUDIO_SAMPLE_FREQUENCY as u32);
+    let mut channel_count = track
+        .codec_params
+        .channels
+        .map_or(AUDIO_CHANNELS as usize, |channels| channels.count());
+    loop {
+        let maybe_packet = format.next_packet();
+        let Ok(packet) = maybe_packet else {
+            break;
+        };
+
+        let decoded = decoder
+            .decode(&packet)
+            .map_err(|err| format!("Failed to decode packet: {err}"))?;
+
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        channel_count = spec.channels.count();
+        let duration = decoded.capacity() as u64;
+        let mut sample_buf = SampleBuffer::<f32>::new(duration, spec);
+        sample_buf.copy_interleaved_ref(decoded);
+        result.extend_from_slice(sample_buf.samples());
+    }
+    Ok((result, sample_rate, channel_count))
+}
+
+/// Decodes the sound without blocking the main thread, on the decoder worker of the sound system.
+fn decode_audio_in_background(path: &Path, data: Box<[u8]>, sound: SoundData) {
+    let path = path.to_path_buf();
+    let decode = move || {
+        let samples = decode_audio(data).unwrap_or_else(|err| {
+            print_err_from(
+                Subsystem::Audio,
+                format!("Failed to decode {}: {}", path.display(), err),
+            );
+            Vec::new()
+        });
+        let _ = sound.set(DecodedSound::new(samples));
+    };
+    sound::decode_in_background(decode);
+}
+
 impl Resource for AudioResource {
     fn load_from_data(
         self: std::rc::Rc<Self>,
@@ -46,46 +121,15 @@ impl Resource for AudioResource {
         _dependency_reporter: &super::DependencyReporter,
         _lua: &Rc<LuaHandle>,
         _gl: std::sync::Arc<glow::Context>,
-        _path: &Path,
+        path: &Path,
         data: Box<[u8]>,
     ) -> Status {
         let data_length = data.len();
 
-        // Decode audio
-        let readable_data = ReadableBytes::new(data);
-        let read_only_source = Box::new(symphonia::core::io::ReadOnlySource::new(readable_data));
-        let mss = MediaSourceStream::new(read_only_source, Default::default());
-
-        let hint = symphonia::core::probe::Hint::new();
-        let format_opts: symphonia::core::formats::FormatOptions = Default::default();
-        let metadata_opts: symphonia::core::meta::MetadataOptions = Default::default();
-        let decoder_opts: symphonia::core::codecs::DecoderOptions = Default::default();
-        let probed = symphonia::default::get_probe()
-            .format(&hint, mss, &format_opts, &metadata_opts)
-            .expect("Probe to work");
-        let mut format = probed.format;
-        let track = format.default_track().expect("No default track");
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &decoder_opts)
-            .expect("Failed to create decoder");
-
-        let mut result = Vec::new();
-        loop {
-            let maybe_packet = format.next_packet();
-            let Ok(packet) = maybe_packet else {
-                break;
-            };
-
-            let decoded = decoder.decode(&packet).expect("Failed to decode packet");
-
-            let spec = *decoded.spec();
-            let duration = decoded.capacity() as u64;
-            let mut sample_buf = SampleBuffer::<f32>::new(duration, spec);
-            sample_buf.copy_interleaved_ref(decoded);
-            result.extend_from_slice(sample_buf.samples());
-        }
-
-        self.chunk.replace(Some(result.into_boxed_slice()));
+        // The channel keeps playing the previous sound until the new one is played.
+        let sound = SoundData::default();
+        self.sound.replace(Some(sound.clone()));
+        decode_audio_in_background(path, data, sound);
 
         let duration_secs = data_length as f32
             / (AUDIO_SAMPLE_FREQUENCY as f32 * AUDIO_CHANNELS as f32 * BYTES_PER_SAMPLE as f32);
@@ -105,7 +149,19 @@ impl Resource for AudioResource {
         _painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
         ui: &mut vectarine_plugin_sdk::egui::Ui,
     ) {
-        ui.label("[TODO] Audio Resource");
+        ui.label("Audio Resource");
+        let is_decoded = self
+            .sound
+            .borrow()
+            .as_ref()
+            .is_some_and(|sound| sound.get().is_some());
+        ui.label(if is_decoded { "Decoded" } else { "Decoding..." });
+        ui.label(if sound::is_audio_threaded() {
+            "Mixed on the audio thread"
+        } else {
+            "Mixed on the main thread"
+        });
+        ui.label(format!("Underruns: {}", sound::underrun_count()));
         let c = self.currently_used_channel.borrow();
         let c = c.as_ref();
         let Some(c) = c else {
@@ -124,7 +180,7 @@ impl Resource for AudioResource {
         Self: Sized,
     {
         Self {
-            chunk: RefCell::new(None),
+            sound: RefCell::new(None),
             currently_used_channel: RefCell::new(None),
             duration: RefCell::new(0.0),
         }
@@ -141,20 +197,45 @@ impl AudioResource {
             println!("No available audio channels to play sound.");
             return;
         };
-        let chunk = self.chunk.borrow();
-        let Some(chunk) = chunk.as_ref() else {
+        let sound = self.sound.borrow();
+        let Some(sound) = sound.as_ref() else {
             println!("No audio chunk loaded to play.");
             return;
         };
         sound::resume_audio(channel);
-        sound::add_sound_data_to_channel(
+        sound::play_sound(
             channel,
-            chunk,
-            fade_in_ms.unwrap_or(100) as f32,
-            100.0,
-            looped,
+            PlaySound {
+                sound: sound.clone(),
+                fade_in_ms: fade_in_ms.unwrap_or(100) as f32,
+                fade_out_ms: 100.0,
+                looped,
+                start_ms: 0.0,
+            },
         );
     }
+
+    /// Starts playing the audio from the beginning when the audio clock reaches `clock_seconds`, replacing what the
+    /// resource was playing. There is no fade, so that the sound starts on the exact sample.
+    /// Returns how late the sound starts, in seconds, or None when the audio cannot be played.
+    pub fn play_at(&self, clock_seconds: f64, looped: bool) -> Option<f64> {
+        let channel = self.get_channel()?;
+        let sound = self.sound.borrow();
+        let sound = sound.as_ref()?;
+        sound::resume_audio(channel);
+        Some(sound::play_sound_at(
+            channel,
+            PlaySound {
+                sound: sound.clone(),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped,
+                start_ms: 0.0,
+            },
+            clock_seconds,
+        ))
+    }
+
     pub fn pause(&self) {
         let channel = self.currently_used_channel.borrow();
         let Some(channel) = channel.as_ref() else {
@@ -186,6 +267,14 @@ impl AudioResource {
         Some(())
     }
 
+    /// Moves the audio between the speakers, from -1.0 for the left one to 1.0 for the right one.
+    pub fn set_pan(&self, pan: f32) -> Option<()> {
+        let channel = self.currently_used_channel.borrow();
+        let channel = channel.as_ref()?;
+        sound::set_pan(*channel, pan);
+        Some(())
+    }
+
     // Get the volume of the audio resource. Volume is a float between 0.0 and 1.0.
     // If no audio is loaded, returns 0.0.
     pub fn get_volume(&self) -> f32 {
@@ -196,8 +285,13 @@ impl AudioResource {
         sound::get_volume(*channel)
     }
 
-    pub fn current_position(&self) -> f32 {
-        todo!("AudioResource.current_position() is not implemented yet");
+    /// Get the position heard in the audio, in seconds, from the audio clock.
+    /// Returns 0.0 if the audio was never played.
+    pub fn current_position(&self) -> f64 {
+        let Some(channel) = self.get_channel() else {
+            return 0.0;
+        };
+        sound::get_position(channel)
     }
     /// Get the duration of the audio in seconds.
     /// Returns 0.0 if no audio is loaded or if the audio failed to load.
@@ -10,7 +10,12 @@ use vectarine_plugin_sdk::lazy_static::lazy_static;
 
 use crate::{
     game_resource::{DependencyReporter, Resource, ResourceId, Status},
-    graphics::gltexture,
+    graphics::{
+        gltexture,
+        instancedtext::GlyphTable,
+        textlayout::{self, TextLayout, TextLayoutOptions, TextSpan},
+    },
+    io::enginefs,
     lua_env::LuaHandle,
 };
 
@@ -29,20 +34,22 @@ pub struct FontRenderingData {
     pub font_loader: fontdue::Font,
     pub font_size: f32,
     max_baseline_height: f32, // The maximum distance from the bottom to the baseline.
+    /// The metrics of the glyphs for `Text.drawFast`, uploaded the first time it is used and after the atlas changes.
+    glyph_table: Option<GlyphTable>,
 }
 
 pub struct FontResource {
     pub font_rendering: RefCell<Option<FontRenderingData>>,
 }
 
+lazy_static! {
+    static ref DEFAULT_FONT: Mutex<Option<FontRenderingData>> = Mutex::new(None);
+}
+
 pub fn use_default_font<F, R>(gl: &Arc<glow::Context>, f: F) -> R
 where
     F: FnOnce(&mut FontRenderingData) -> R,
 {
-    lazy_static! {
-        static ref DEFAULT_FONT: Mutex<Option<FontRenderingData>> = Mutex::new(None);
-    }
-
     let mut default_font = DEFAULT_FONT
         .lock()
         .expect("Failed to acquire lock on the default font.");
@@ -51,8 +58,9 @@ where
         return f(default_font);
     }
 
-    let font_bytes = include_bytes!("../../../assets/Roboto-Regular.ttf");
-    let font = fontdue::Font::from_bytes(font_bytes.as_ref(), fontdue::FontSettings::default())
+    let font_bytes = enginefs::get_engine_asset("fonts/default.ttf")
+        .expect("The default font is embedded in the engine.");
+    let font = fontdue::Font::from_bytes(font_bytes, fontdue::FontSettings::default())
         .expect("The default font file contains a valid font.");
     let chars: Vec<char> = CHARSET.chars().collect();
     let (atlas_texture, font_cache, max_baseline_height) =
@@ -63,12 +71,37 @@ where
         font_loader: font,
         font_size: FONT_DETAIL,
         max_baseline_height,
+        glyph_table: None,
     };
     let result = f(&mut font);
     *default_font = Some(font);
     result
 }
 
+/// Rebuilds the atlas of the default font after the OpenGL context was lost.
+pub fn recreate_default_font_gpu_objects(gl: &Arc<glow::Context>) {
+    let mut default_font = DEFAULT_FONT
+        .lock()
+        .expect("Failed to acquire lock on the default font.");
+    if let Some(default_font) = default_font.as_mut() {
+        default_font.recreate_atlas(gl);
+    }
+}
+
+impl FontRenderingData {
+    /// Rasterizes again all the characters of the cache into a new atlas.
+    /// The previous atlas is forgotten as it belongs to a lost OpenGL context.
+    fn recreate_atlas(&mut self, gl: &Arc<glow::Context>) {
+        let chars = self.font_cache.keys().copied().collect::<Vec<_>>();
+        let (atlas_texture, font_cache, max_baseline_height) =
+            initialize_cache_and_texture(gl, &self.font_loader, chars);
+        std::mem::forget(std::mem::replace(&mut self.font_atlas, atlas_texture));
+        std::mem::forget(self.glyph_table.take());
+        self.font_cache = font_cache;
+        self.max_baseline_height = max_baseline_height;
+    }
+}
+
 const CHARSET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!@#$%^&*()-_=+[]{}|;:'\",.<>?/\\`~ \n";
 const FONT_DETAIL: f32 = 64.0; // Base font size for rasterization
 
@@ -102,10 +135,18 @@ impl Resource for FontResource {
             font_loader: font,
             font_size: FONT_DETAIL,
             max_baseline_height,
+            glyph_table: None,
         }));
         Status::Loaded
     }
 
+    fn recreate_gpu_objects(&self, gl: &Arc<glow::Context>) -> bool {
+        if let Some(font_rendering) = self.font_rendering.borrow_mut().as_mut() {
+            font_rendering.recreate_atlas(gl);
+        }
+        false
+    }
+
     fn draw_debug_gui(
         &self,
         _painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
@@ -172,6 +213,33 @@ impl FontRenderingData {
         )
     }
 
+    /// Places the characters of the spans on lines, see `textlayout::layout_text`. The characters need to be in the atlas.
+    pub fn layout_text(
+        &self,
+        spans: &[TextSpan],
+        font_size: f32,
+        aspect_ratio: f32,
+        options: &TextLayoutOptions,
+    ) -> TextLayout {
+        let scale = font_size.abs() / self.font_size;
+        textlayout::layout_text(
+            spans,
+            font_size,
+            options,
+            |c| {
+                let char_info = self.font_cache.get(&c)?;
+                Some(char_info.metrics.advance_width * scale / aspect_ratio)
+            },
+            |left, right| {
+                let kerning = self
+                    .font_loader
+                    .horizontal_kern(left, right, self.font_size)
+                    .unwrap_or(0.0);
+                kerning * scale / aspect_ratio
+            },
+        )
+    }
+
     pub fn get_max_baseline_height(&self, font_size: f32) -> f32 {
         self.max_baseline_height * (font_size / self.font_size)
     }
@@ -201,6 +269,13 @@ impl FontRenderingData {
         self.font_atlas = atlas_texture;
         self.font_cache = font_cache;
         self.max_baseline_height = max_baseline_height;
+        self.glyph_table = None;
+    }
+
+    /// The glyph metrics of the characters of the atlas, in a texture for the instanced text.
+    pub fn glyph_table(&mut self, gl: &Arc<glow::Context>) -> &GlyphTable {
+        self.glyph_table
+            .get_or_insert_with(|| GlyphTable::new(gl, &self.font_cache))
     }
 }
 
@@ -1,16 +1,188 @@
-use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     game_resource::{DependencyReporter, Resource, ResourceId, Status},
-    graphics::gltexture::{self, ImageAntialiasing, Texture},
+    graphics::{
+        gltexture::{self, ImageAntialiasing, Texture},
+        imagepacking::PackedImage,
+        tileatlas::{self, TileAtlas, TileLayout},
+    },
     lua_env::LuaHandle,
+    sound,
 };
 use vectarine_plugin_sdk::glow;
 
+/// The pixels of the tile atlases built on the decoder worker, with the generation of the atlases they were built for.
+type BuiltTileAtlases = Arc<Mutex<Vec<(u64, TileLayout, Vec<u8>)>>>;
+
 pub struct ImageResource {
     pub texture: RefCell<Option<Arc<gltexture::Texture>>>,
     pub egui_id: RefCell<Option<vectarine_plugin_sdk::egui::TextureId>>,
     pub antialiasing: Option<ImageAntialiasing>,
+    /// The layouts of the tilesets the image is drawn with, by tileset. Their atlases are built again when the image is
+    /// reloaded, and replaced when the tileset changes.
+    pub tile_layouts: RefCell<Vec<(ResourceId, TileLayout)>>,
+    pub tile_atlases: RefCell<Vec<TileAtlas>>,
+    /// Increased when the pixels of the image change, so that the atlases built from the previous pixels are dropped.
+    pub tile_atlas_generation: Cell<u64>,
+    pub built_tile_atlases: BuiltTileAtlases,
+}
+
+impl ImageResource {
+    /// Records that the image is drawn with the tiles of the layout of a tileset. Returns true when no atlas was built
+    /// for the layout yet, and it needs to be built with `build_tile_atlas_from_data`.
+    pub fn use_tile_layout(&self, tileset_id: ResourceId, layout: TileLayout) -> bool {
+        // Without filtering, the texels of neighboring tiles are never mixed and the atlas is not needed.
+        if self.antialiasing == Some(ImageAntialiasing::Nearest) {
+            return false;
+        }
+        let mut tile_layouts = self.tile_layouts.borrow_mut();
+        match tile_layouts.iter_mut().find(|(id, _)| *id == tileset_id) {
+            Some((_, used_layout)) if *used_layout == layout => return false,
+            // The tileset was reloaded with other tile sizes or gutters.
+            Some((_, used_layout)) => *used_layout = layout,
+            None => tile_layouts.push((tileset_id, layout)),
+        }
+        // The atlases of the layouts no tileset uses anymore are dropped.
+        self.tile_atlases.borrow_mut().retain(|atlas| {
+            tile_layouts
+                .iter()
+                .any(|(_, used_layout)| *used_layout == atlas.layout)
+        });
+        !self
+            .tile_atlases
+            .borrow()
+            .iter()
+            .any(|atlas| atlas.layout == layout)
+    }
+
+    /// Decodes the image and builds the missing atlases on the decoder worker. They are uploaded by
+    /// `upload_built_tile_atlases`.
+    pub fn build_tile_atlas_from_data(&self, data: Vec<u8>) {
+        let layouts = self.tile_layouts_to_build(false);
+        if layouts.is_empty() {
+            return;
+        }
+        let generation = self.tile_atlas_generation.get();
+        let built_tile_atlases = self.built_tile_atlases.clone();
+        sound::decode_in_background(move || {
+            if let Ok(image) = image::load_from_memory(&data) {
+                build_tile_atlases(&image.to_rgba8(), layouts, generation, &built_tile_atlases);
+            }
+        });
+    }
+
+    /// Builds the atlases of the new pixels of the image on the decoder worker.
+    fn build_tile_atlases_in_background(&self, image: image::RgbaImage) {
+        self.tile_atlas_generation
+            .set(self.tile_atlas_generation.get() + 1);
+        let layouts = self.tile_layouts_to_build(true);
+        if layouts.is_empty() {
+            return;
+        }
+        let generation = self.tile_atlas_generation.get();
+        let built_tile_atlases = self.built_tile_atlases.clone();
+        sound::decode_in_background(move || {
+            build_tile_atlases(&image, layouts, generation, &built_tile_atlases);
+        });
+    }
+
+    /// The layouts used by the tilesets, without the ones already built unless `is_rebuilt`.
+    fn tile_layouts_to_build(&self, is_rebuilt: bool) -> Vec<TileLayout> {
+        let tile_atlases = self.tile_atlases.borrow();
+        let mut layouts = Vec::new();
+        for (_, layout) in self.tile_layouts.borrow().iter() {
+            let is_built = tile_atlases.iter().any(|atlas| atlas.layout == *layout);
+            if !layouts.contains(layout) && (is_rebuilt || !is_built) {
+                layouts.push(*layout);
+            }
+        }
+        layouts
+    }
+
+    /// Creates the textures of the atlases built on the decoder worker. The textures can only be created on the main
+    /// thread.
+    pub fn upload_built_tile_atlases(&self, gl: &Arc<glow::Context>) {
+        let Ok(mut built_tile_atlases) = self.built_tile_atlases.try_lock() else {
+            return;
+        };
+        if built_tile_atlases.is_empty() {
+            return;
+        }
+        let generation = self.tile_atlas_generation.get();
+        let tile_layouts = self.tile_layouts.borrow();
+        let mut tile_atlases = self.tile_atlases.borrow_mut();
+        for (built_generation, layout, pixels) in built_tile_atlases.drain(..) {
+            let is_used = tile_layouts
+                .iter()
+                .any(|(_, used_layout)| *used_layout == layout);
+            if built_generation != generation || !is_used {
+                continue;
+            }
+            let (width, height) = layout.atlas_size();
+            tile_atlases.retain(|atlas| atlas.layout != layout);
+            tile_atlases.push(TileAtlas {
+                layout,
+                texture: Texture::new_rgba(
+                    gl,
+                    Some(&pixels),
+                    width,
+                    height,
+                    self.antialiasing.unwrap_or(ImageAntialiasing::Linear),
+                ),
+            });
+        }
+    }
+
+    /// The image was packed in a page at export. The page is loaded as an image too, and this one draws a part of it.
+    fn load_from_page(
+        &self,
+        assigned_id: ResourceId,
+        dependency_reporter: &DependencyReporter,
+        packed: &PackedImage,
+    ) -> Status {
+        let page_path = Path::new(&packed.page);
+        dependency_reporter.declare_dependency::<ImageResource>(assigned_id, page_path);
+        let Some(page_id) = dependency_reporter.obtain_resource_id(page_path) else {
+            return Status::Unloaded;
+        };
+        match dependency_reporter.obtain_resource_status(page_id) {
+            None | Some(Status::Unloaded | Status::Loading) => Status::Unloaded,
+            Some(Status::Error(error)) => {
+                Status::Error(format!("Cannot load the atlas {}: {error}", packed.page))
+            }
+            Some(Status::Loaded) => {
+                let page = match dependency_reporter.obtain_resource::<ImageResource>(&page_id) {
+                    Ok(page) => page,
+                    Err(error) => return Status::Error(error),
+                };
+                let Some(page_texture) = page.texture.borrow().clone() else {
+                    return Status::Unloaded;
+                };
+                // The images of a page share its filtering, the last one loaded decides.
+                if let Some(antialiasing) = self.antialiasing {
+                    page_texture.set_filter(antialiasing);
+                }
+                self.texture.replace(Some(Texture::new_region(
+                    &page_texture,
+                    packed.x,
+                    packed.y,
+                    packed.width,
+                    packed.height,
+                )));
+                self.tile_atlases.replace(Vec::new());
+                self.tile_atlas_generation
+                    .set(self.tile_atlas_generation.get() + 1);
+                self.egui_id.replace(None);
+                Status::Loaded
+            }
+        }
+    }
 }
 
 impl Resource for ImageResource {
@@ -19,30 +191,44 @@ impl Resource for ImageResource {
     }
     fn load_from_data(
         self: Rc<Self>,
-        _assigned_id: ResourceId,
-        _dependency_reporter: &DependencyReporter,
+        assigned_id: ResourceId,
+        dependency_reporter: &DependencyReporter,
         _lua: &Rc<LuaHandle>,
         gl: Arc<glow::Context>,
         _path: &Path,
         data: Box<[u8]>,
     ) -> Status {
+        if let Some(packed) = PackedImage::parse(&data) {
+            return self.load_from_page(assigned_id, dependency_reporter, &packed);
+        }
         let result = image::load_from_memory(&data);
         let image = match result {
             Err(err) => return Status::Error(format!("{}", err)),
             Ok(image) => image,
         };
 
+        let image = image.to_rgba8();
         self.texture.replace(Some(Texture::new_rgba(
             &gl,
-            Some(image.to_rgba8().as_raw().as_slice()),
+            Some(image.as_raw().as_slice()),
             image.width(),
             image.height(),
             self.antialiasing.unwrap_or(ImageAntialiasing::Linear),
         )));
+        // The previous atlases are drawn until the new ones are built.
+        self.build_tile_atlases_in_background(image);
         self.egui_id.replace(None);
         Status::Loaded
     }
 
+    fn recreate_gpu_objects(&self, _gl: &Arc<glow::Context>) -> bool {
+        // We do not keep the pixels in memory, so we need to read the file again.
+        std::mem::forget(self.texture.take());
+        std::mem::forget(self.tile_atlases.take());
+        self.egui_id.replace(None);
+        true
+    }
+
     fn draw_debug_gui(
         &self,
         painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
@@ -58,6 +244,21 @@ impl Resource for ImageResource {
         ui.label(format!("Height: {}", tex.height()));
         ui.label(format!("Antialiasing: {:?}", self.antialiasing));
         ui.label(format!("OpenGL ID: {}", tex.id().0));
+        let [uv_x, uv_y, uv_width, uv_height] = tex.uv_rect();
+        if let Some(atlas) = tex.atlas() {
+            ui.label(format!(
+                "Packed in a {}x{} atlas",
+                atlas.width(),
+                atlas.height()
+            ));
+        }
+        for atlas in self.tile_atlases.borrow().iter() {
+            let (width, height) = atlas.layout.atlas_size();
+            ui.label(format!(
+                "Tile atlas: {} tiles, {width}x{height}, gutter of {}",
+                atlas.layout.tile_count, atlas.layout.gutter
+            ));
+        }
 
         let mut egui_id = self.egui_id.borrow_mut();
         let texture_id = match egui_id.as_mut() {
@@ -82,6 +283,10 @@ impl Resource for ImageResource {
         );
 
         let image = vectarine_plugin_sdk::egui::Image::from_texture(sized_texture)
+            .uv(vectarine_plugin_sdk::egui::Rect::from_min_size(
+                vectarine_plugin_sdk::egui::pos2(uv_x, uv_y),
+                vectarine_plugin_sdk::egui::vec2(uv_width, uv_height),
+            ))
             .max_size(size)
             .corner_radius(5);
         ui.add(image);
@@ -95,6 +300,26 @@ impl Resource for ImageResource {
             texture: RefCell::new(None),
             egui_id: RefCell::new(None),
             antialiasing: None,
+            tile_layouts: RefCell::new(Vec::new()),
+            tile_atlases: RefCell::new(Vec::new()),
+            tile_atlas_generation: Cell::new(0),
+            built_tile_atlases: BuiltTileAtlases::default(),
+        }
+    }
+}
+
+/// Runs on the decoder worker.
+fn build_tile_atlases(
+    image: &image::RgbaImage,
+    layouts: Vec<TileLayout>,
+    generation: u64,
+    built_tile_atlases: &BuiltTileAtlases,
+) {
+    for layout in layouts {
+        let pixels =
+            tileatlas::build_tile_atlas(image.as_raw(), image.width(), image.height(), &layout);
+        if let Ok(mut built_tile_atlases) = built_tile_atlases.lock() {
+            built_tile_atlases.push((generation, layout, pixels));
         }
     }
 }
@@ -110,3 +335,37 @@ fn get_desired_size(
     let scale = width_scale.min(height_scale);
     vectarine_plugin_sdk::egui::Vec2::new(actual_size.x * scale, actual_size.y * scale)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ImageResource;
+    use crate::game_resource::{Resource, ResourceId};
+    use crate::graphics::tileatlas::TileLayout;
+
+    #[test]
+    fn a_reloaded_tileset_replaces_its_layout() {
+        let image = <ImageResource as Resource>::default();
+        let layout = TileLayout {
+            tile_width: 16,
+            tile_height: 16,
+            spacing: 0,
+            margin: 0,
+            columns: 4,
+            tile_count: 16,
+            gutter: 2,
+        };
+        assert!(image.use_tile_layout(ResourceId(1), layout));
+        assert!(!image.use_tile_layout(ResourceId(1), layout));
+
+        let reloaded_layout = TileLayout {
+            gutter: 4,
+            ..layout
+        };
+        assert!(image.use_tile_layout(ResourceId(1), reloaded_layout));
+        assert!(image.use_tile_layout(ResourceId(2), layout));
+        assert_eq!(
+            *image.tile_layouts.borrow(),
+            vec![(ResourceId(1), reloaded_layout), (ResourceId(2), layout)]
+        );
+    }
+}
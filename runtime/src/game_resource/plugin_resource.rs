@@ -0,0 +1,74 @@
+use std::{any::Any, cell::RefCell, path::Path, rc::Rc};
+
+use crate::{
+    game_resource::{Resource, ResourceId, Status},
+    lua_env::LuaHandle,
+};
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::plugininterface::{ImportResult, Importer};
+
+/// A file loaded with an importer provided by a plugin, for formats the engine does not know.
+pub struct PluginResource {
+    importer: Option<Importer>,
+    pub payload: RefCell<Option<Box<dyn Any>>>,
+}
+
+impl PluginResource {
+    pub fn new(importer: Importer) -> Self {
+        Self {
+            importer: Some(importer),
+            payload: RefCell::new(None),
+        }
+    }
+}
+
+impl Resource for PluginResource {
+    fn load_from_data(
+        self: Rc<Self>,
+        _assigned_id: ResourceId,
+        _dependency_reporter: &super::DependencyReporter,
+        _lua: &Rc<LuaHandle>,
+        _gl: std::sync::Arc<glow::Context>,
+        path: &Path,
+        data: Box<[u8]>,
+    ) -> Status {
+        let Some(importer) = &self.importer else {
+            return Status::Error("No importer was provided for this file".to_string());
+        };
+        match importer(path, &data) {
+            ImportResult::Loaded(payload) => {
+                self.payload.replace(Some(payload));
+                Status::Loaded
+            }
+            ImportResult::Error(message) => {
+                self.payload.replace(None);
+                Status::Error(message)
+            }
+        }
+    }
+
+    fn draw_debug_gui(
+        &self,
+        _painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
+        ui: &mut vectarine_plugin_sdk::egui::Ui,
+    ) {
+        ui.label("Imported by a plugin");
+        if self.payload.borrow().is_none() {
+            ui.label("<No content loaded>");
+        }
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        "Plugin"
+    }
+
+    fn default() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            importer: None,
+            payload: RefCell::new(None),
+        }
+    }
+}
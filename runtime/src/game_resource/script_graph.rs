@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::game_resource::ResourceId;
+
+/// Remembers which scripts each script loaded with `Loader.loadScript` the last time it ran, so that the editor can
+/// run the scripts using a module again after the module changes.
+#[derive(Default, Debug)]
+pub struct ScriptGraph {
+    /// For each script, the scripts it loaded.
+    loads: BTreeMap<ResourceId, BTreeSet<ResourceId>>,
+    /// The scripts being run, the last one is the one calling `loadScript`.
+    running: Vec<ResourceId>,
+}
+
+/// The scripts to run again after some of them changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReloadPlan {
+    /// Every script appears after the scripts it loads, except inside cycles.
+    pub order: Vec<ResourceId>,
+    /// Scripts loading each other. They are all in `order`, but their order between them is arbitrary.
+    pub cycles: Vec<Vec<ResourceId>>,
+}
+
+impl ScriptGraph {
+    /// Called before running a script. What it loaded during its previous run is forgotten.
+    pub fn start_running(&mut self, script: ResourceId) {
+        self.loads.remove(&script);
+        self.running.push(script);
+    }
+
+    pub fn finish_running(&mut self, script: ResourceId) {
+        if let Some(index) = self.running.iter().rposition(|id| *id == script) {
+            self.running.remove(index);
+        }
+    }
+
+    /// Called when `loadScript` is called. Loads made outside of the top level of a script, like in `Update`, are
+    /// not recorded as they do not run again when the script is run again.
+    pub fn record_load(&mut self, loaded: ResourceId) {
+        if let Some(script) = self.running.last() {
+            self.loads.entry(*script).or_default().insert(loaded);
+        }
+    }
+
+    pub fn loaded_by(&self, script: ResourceId) -> impl Iterator<Item = ResourceId> + '_ {
+        self.loads.get(&script).into_iter().flatten().copied()
+    }
+
+    /// Returns the changed scripts and all the scripts loading them, directly or not, in the order in which they
+    /// should run again.
+    pub fn reload_order(&self, changed: &[ResourceId]) -> ReloadPlan {
+        let mut loaded_by: HashMap<ResourceId, Vec<ResourceId>> = HashMap::new();
+        for (script, loads) in &self.loads {
+            for loaded in loads {
+                loaded_by.entry(*loaded).or_default().push(*script);
+            }
+        }
+        let mut affected = BTreeSet::new();
+        let mut to_visit = changed.to_vec();
+        while let Some(script) = to_visit.pop() {
+            if affected.insert(script) {
+                to_visit.extend(loaded_by.get(&script).into_iter().flatten().copied());
+            }
+        }
+
+        let mut search = ComponentSearch {
+            graph: self,
+            affected: &affected,
+            states: HashMap::new(),
+            stack: Vec::new(),
+            next_index: 0,
+            plan: ReloadPlan::default(),
+        };
+        for script in &affected {
+            if !search.states.contains_key(script) {
+                search.visit(*script);
+            }
+        }
+        search.plan
+    }
+}
+
+#[derive(Clone, Copy)]
+struct VisitState {
+    index: usize,
+    lowest_reachable: usize,
+    is_on_stack: bool,
+}
+
+/// Tarjan's algorithm. A group of scripts is complete once all the scripts it loads are, so the groups come out with
+/// the loaded scripts first.
+struct ComponentSearch<'a> {
+    graph: &'a ScriptGraph,
+    affected: &'a BTreeSet<ResourceId>,
+    states: HashMap<ResourceId, VisitState>,
+    stack: Vec<ResourceId>,
+    next_index: usize,
+    plan: ReloadPlan,
+}
+
+impl ComponentSearch<'_> {
+    fn visit(&mut self, script: ResourceId) {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.states.insert(
+            script,
+            VisitState {
+                index,
+                lowest_reachable: index,
+                is_on_stack: true,
+            },
+        );
+        self.stack.push(script);
+
+        let loads = self
+            .graph
+            .loaded_by(script)
+            .filter(|loaded| self.affected.contains(loaded))
+            .collect::<Vec<_>>();
+        for loaded in loads {
+            let reachable = match self.states.get(&loaded) {
+                None => {
+                    self.visit(loaded);
+                    self.states[&loaded].lowest_reachable
+                }
+                Some(state) if state.is_on_stack => state.index,
+                Some(_) => continue,
+            };
+            let state = self
+                .states
+                .get_mut(&script)
+                .expect("the script was visited");
+            state.lowest_reachable = state.lowest_reachable.min(reachable);
+        }
+
+        let state = self.states[&script];
+        if state.lowest_reachable != state.index {
+            return;
+        }
+        let mut component = Vec::new();
+        while let Some(member) = self.stack.pop() {
+            if let Some(member_state) = self.states.get_mut(&member) {
+                member_state.is_on_stack = false;
+            }
+            component.push(member);
+            if member == script {
+                break;
+            }
+        }
+        component.sort();
+        let is_cycle = component.len() > 1 || self.graph.loaded_by(script).any(|id| id == script);
+        self.plan.order.extend(component.iter().copied());
+        if is_cycle {
+            self.plan.cycles.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScriptGraph;
+    use crate::game_resource::ResourceId;
+
+    fn run(graph: &mut ScriptGraph, script: usize, loads: &[usize]) {
+        graph.start_running(ResourceId(script));
+        for loaded in loads {
+            graph.record_load(ResourceId(*loaded));
+        }
+        graph.finish_running(ResourceId(script));
+    }
+
+    fn ids(ids: &[usize]) -> Vec<ResourceId> {
+        ids.iter().map(|id| ResourceId(*id)).collect()
+    }
+
+    #[test]
+    fn dependents_run_after_the_modules_they_load() {
+        let mut graph = ScriptGraph::default();
+        // main (0) loads player (1) and enemy (2), which both load utils (3). hud (4) loads nothing.
+        run(&mut graph, 0, &[1, 2]);
+        run(&mut graph, 1, &[3]);
+        run(&mut graph, 2, &[3, 1]);
+        run(&mut graph, 4, &[]);
+
+        let plan = graph.reload_order(&ids(&[3]));
+        assert_eq!(plan.order, ids(&[3, 1, 2, 0]));
+        assert!(plan.cycles.is_empty());
+
+        assert_eq!(graph.reload_order(&ids(&[4])).order, ids(&[4]));
+
+        // Running a script again replaces what it loads.
+        run(&mut graph, 2, &[]);
+        assert_eq!(graph.reload_order(&ids(&[3])).order, ids(&[3, 1, 0]));
+    }
+
+    #[test]
+    fn cycles_are_reloaded_together() {
+        let mut graph = ScriptGraph::default();
+        run(&mut graph, 0, &[1]);
+        run(&mut graph, 1, &[2]);
+        run(&mut graph, 2, &[1, 3]);
+        run(&mut graph, 3, &[]);
+
+        let plan = graph.reload_order(&ids(&[3]));
+        assert_eq!(plan.order, ids(&[3, 1, 2, 0]));
+        assert_eq!(plan.cycles, vec![ids(&[1, 2])]);
+    }
+}
@@ -1,28 +1,100 @@
 use std::{cell::RefCell, path::Path, rc::Rc};
 
 use crate::{
+    console::print_warn,
     game_resource::{Resource, ResourceId, Status},
-    lua_env::{LuaHandle, run_file_and_display_error_from_lua_handle},
+    lua_env::{
+        LuaHandle, rerun_main_script_transactionally, run_file_and_display_error_from_lua_handle,
+    },
+    metrics::{MemoryCategory, MemoryGuard},
 };
 use vectarine_plugin_sdk::glow;
 
+/// When the Update of a reloaded main script fails this many frames in a row, the editor calls the Update of the
+/// previous version instead.
+pub const FAILED_UPDATES_BEFORE_FALLBACK: u32 = 30;
+/// The source of the version that last ran is only kept for smaller scripts.
+pub const MAX_LAST_GOOD_SOURCE_BYTES: usize = 256 * 1024;
+
 pub struct ScriptResource {
     pub script: RefCell<Option<Vec<u8>>>,
     /// If provided when the script is created, the return table of the script will be merged into this table.
     pub target_table: Option<vectarine_plugin_sdk::mlua::Table>,
+    memory: RefCell<MemoryGuard>,
+    update_recovery: RefCell<UpdateRecovery>,
+}
+
+/// Keeps the game running in the editor when a hot reload of the main script breaks Update, and the source of the
+/// version that last worked, to compare it with a version that fails.
+#[derive(Default)]
+struct UpdateRecovery {
+    /// The Update function from before the last successful reload.
+    previous_update: Option<vectarine_plugin_sdk::mlua::Function>,
+    /// The source of the version `previous_update` comes from.
+    previous_source: Option<Rc<[u8]>>,
+    consecutive_failures: u32,
+    is_using_previous_update: bool,
+    /// The source of the last version that ran without errors. To bound the memory used, it is only kept for the main
+    /// script and for the scripts that failed at least once.
+    last_good_source: Option<Rc<[u8]>>,
+    has_failed: bool,
+    did_last_run_fail: bool,
 }
 
 impl Resource for ScriptResource {
     fn load_from_data(
         self: std::rc::Rc<Self>,
-        _assigned_id: ResourceId,
-        _dependency_reporter: &super::DependencyReporter,
+        assigned_id: ResourceId,
+        dependency_reporter: &super::DependencyReporter,
         lua: &Rc<LuaHandle>,
         _gl: std::sync::Arc<glow::Context>,
         path: &Path,
         data: Box<[u8]>,
     ) -> Status {
-        run_file_and_display_error_from_lua_handle(lua, &data, path, self.target_table.as_ref());
+        let is_main_script = self.target_table.is_none();
+        let is_main_script_reload = is_main_script && self.script.borrow().is_some();
+        // The scripts loaded while this one runs are the ones to run again after this one when they change.
+        dependency_reporter
+            .with_script_graph(|script_graph| script_graph.start_running(assigned_id));
+        if cfg!(feature = "editor") && is_main_script_reload {
+            let previous_update = {
+                let recovery = self.update_recovery.borrow();
+                if recovery.is_using_previous_update {
+                    // The current Update is the broken one, the working version is kept.
+                    recovery.previous_update.clone()
+                } else {
+                    lua.lua
+                        .globals()
+                        .get::<vectarine_plugin_sdk::mlua::Function>("Update")
+                        .ok()
+                }
+            };
+            let is_success = rerun_main_script_transactionally(lua, &data, path);
+            if is_success {
+                let mut recovery = self.update_recovery.borrow_mut();
+                let previous_source = if recovery.is_using_previous_update {
+                    recovery.previous_source.take()
+                } else {
+                    recovery.last_good_source.take()
+                };
+                recovery.previous_update = previous_update;
+                recovery.previous_source = previous_source;
+                recovery.consecutive_failures = 0;
+                recovery.is_using_previous_update = false;
+            }
+            self.record_run(&data, is_success, is_main_script);
+        } else {
+            let is_success = run_file_and_display_error_from_lua_handle(
+                lua,
+                &data,
+                path,
+                self.target_table.as_ref(),
+            );
+            self.record_run(&data, is_success, is_main_script);
+        }
+        dependency_reporter
+            .with_script_graph(|script_graph| script_graph.finish_running(assigned_id));
+        self.memory.borrow_mut().set_bytes(data.len());
         self.script.replace(Some(data.to_vec()));
         Status::Loaded
     }
@@ -47,6 +119,8 @@ impl Resource for ScriptResource {
         Self {
             script: RefCell::new(None),
             target_table: None,
+            memory: RefCell::new(MemoryGuard::new(MemoryCategory::ResourceData, 0)),
+            update_recovery: RefCell::new(UpdateRecovery::default()),
         }
     }
 }
@@ -56,10 +130,151 @@ impl ScriptResource {
         Self {
             script: RefCell::new(None),
             target_table: Some(target_table),
+            memory: RefCell::new(MemoryGuard::new(MemoryCategory::ResourceData, 0)),
+            update_recovery: RefCell::new(UpdateRecovery::default()),
         }
     }
 
     pub fn get_exports(&self) -> Option<&vectarine_plugin_sdk::mlua::Table> {
         self.target_table.as_ref()
     }
+
+    /// Called after the script ran, before `script` is replaced with `data`.
+    fn record_run(&self, data: &[u8], is_success: bool, is_main_script: bool) {
+        if !cfg!(feature = "editor") {
+            return;
+        }
+        let mut recovery = self.update_recovery.borrow_mut();
+        if is_success {
+            recovery.did_last_run_fail = false;
+            if is_main_script || recovery.has_failed {
+                recovery.last_good_source =
+                    (data.len() <= MAX_LAST_GOOD_SOURCE_BYTES).then(|| Rc::from(data));
+            }
+            return;
+        }
+        if !recovery.has_failed {
+            // The previous version is still there, it becomes the last good one if it ran.
+            recovery.has_failed = true;
+            if !recovery.did_last_run_fail {
+                recovery.last_good_source = self
+                    .script
+                    .borrow()
+                    .as_deref()
+                    .filter(|source| source.len() <= MAX_LAST_GOOD_SOURCE_BYTES)
+                    .map(Rc::from);
+            }
+        }
+        recovery.did_last_run_fail = true;
+    }
+
+    /// The source of the last version of the script that ran without errors, or of the version whose Update is called
+    /// when the current one keeps failing. None when the script never ran, or is too big to be kept.
+    pub fn get_last_good_source(&self) -> Option<Rc<[u8]>> {
+        let recovery = self.update_recovery.borrow();
+        if recovery.is_using_previous_update {
+            return recovery.previous_source.clone();
+        }
+        recovery.last_good_source.clone()
+    }
+
+    /// The Update function to call for this frame. This is the global Update, unless it kept failing after a reload.
+    pub fn get_update_function(
+        &self,
+        lua: &vectarine_plugin_sdk::mlua::Lua,
+    ) -> Option<vectarine_plugin_sdk::mlua::Function> {
+        let recovery = self.update_recovery.borrow();
+        if recovery.is_using_previous_update {
+            return recovery.previous_update.clone();
+        }
+        lua.globals()
+            .get::<vectarine_plugin_sdk::mlua::Function>("Update")
+            .ok()
+    }
+
+    /// Needs to be called after each call to the function returned by `get_update_function`.
+    pub fn report_update_result(&self, is_success: bool) {
+        let mut recovery = self.update_recovery.borrow_mut();
+        if is_success {
+            recovery.consecutive_failures = 0;
+            return;
+        }
+        recovery.consecutive_failures += 1;
+        if recovery.consecutive_failures >= FAILED_UPDATES_BEFORE_FALLBACK
+            && !recovery.is_using_previous_update
+            && recovery.previous_update.is_some()
+        {
+            recovery.is_using_previous_update = true;
+            print_warn(format!(
+                "Update failed {FAILED_UPDATES_BEFORE_FALLBACK} frames in a row since the last reload. Running the previous version of Update until the script is fixed."
+            ));
+        }
+    }
+
+    /// True when the Update of the previous version is called because the current one keeps failing.
+    pub fn is_running_previous_version(&self) -> bool {
+        self.update_recovery.borrow().is_using_previous_update
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use vectarine_plugin_sdk::mlua::{Function, Lua};
+
+    use super::{FAILED_UPDATES_BEFORE_FALLBACK, ScriptResource, UpdateRecovery};
+    use crate::game_resource::Resource;
+
+    #[test]
+    fn failing_updates_fall_back_to_the_previous_version() {
+        let lua = Lua::new();
+        lua.load("function Update() return 'new' end")
+            .exec()
+            .unwrap();
+        let previous_update: Function = lua
+            .load("return function() return 'old' end")
+            .eval()
+            .unwrap();
+        let script = ScriptResource::default();
+        script.update_recovery.replace(UpdateRecovery {
+            previous_update: Some(previous_update),
+            ..Default::default()
+        });
+
+        let call_update = || {
+            script
+                .get_update_function(&lua)
+                .unwrap()
+                .call::<String>(())
+                .unwrap()
+        };
+        for _ in 1..FAILED_UPDATES_BEFORE_FALLBACK {
+            script.report_update_result(false);
+        }
+        // A success resets the count of failures.
+        script.report_update_result(true);
+        for _ in 1..FAILED_UPDATES_BEFORE_FALLBACK {
+            script.report_update_result(false);
+        }
+        assert_eq!(call_update(), "new");
+        assert!(!script.is_running_previous_version());
+
+        script.report_update_result(false);
+        assert!(script.is_running_previous_version());
+        assert_eq!(call_update(), "old");
+    }
+
+    #[test]
+    fn without_a_previous_version_the_current_update_is_kept() {
+        let lua = Lua::new();
+        lua.load("function Update() return 'new' end")
+            .exec()
+            .unwrap();
+        let script = ScriptResource::default();
+        for _ in 0..FAILED_UPDATES_BEFORE_FALLBACK * 2 {
+            script.report_update_result(false);
+        }
+        assert!(!script.is_running_previous_version());
+        let update = script.get_update_function(&lua).unwrap();
+        assert_eq!(update.call::<String>(()).unwrap(), "new");
+    }
 }
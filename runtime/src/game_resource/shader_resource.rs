@@ -1,17 +1,51 @@
-use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+use regex::Regex;
 
 use crate::{
-    game_resource::{DependencyReporter, Resource, ResourceId, Status},
+    game_resource::{
+        DependencyReporter, Resource, ResourceId, ResourceManager, Status,
+        text_resource::TextResource,
+    },
     graphics::{
         glprogram,
         gltypes::{DataLayout, GLTypes, UsageHint},
+        gluniforms::Uniforms,
+        shadersources::COLOR_VERTEX_SHADER_SOURCE,
     },
+    io::enginefs::ENGINE_ASSET_PREFIX,
     lua_env::LuaHandle,
 };
 use vectarine_plugin_sdk::glow;
 
+/// The vertex data a custom shader receives, chosen with `#pragma layout texture` or `#pragma layout color`
+/// in the fragment shader. Shaders without the pragma use the texture layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderLayout {
+    /// `in vec2 uv`, for canvases, images and text.
+    Texture,
+    /// `in vec4 color`, for shapes.
+    Color,
+}
+
+impl fmt::Display for ShaderLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderLayout::Texture => write!(f, "texture"),
+            ShaderLayout::Color => write!(f, "color"),
+        }
+    }
+}
+
 pub struct Shader {
     pub shader: glprogram::GLProgram,
+    pub layout: ShaderLayout,
 }
 
 // Fragment-shader is user-provided.
@@ -25,7 +59,334 @@ void main() {
 }"#;
 
 pub struct ShaderResource {
+    /// The last version of the shader that compiled. It is only replaced once a new version compiled, so that
+    /// editing a shader never leaves a frame without a program to draw with.
     pub shader: RefCell<Option<Shader>>,
+    /// Uniforms set from Lua, sent with every draw call using the shader.
+    pub uniforms: RefCell<Uniforms>,
+}
+
+/// Unlike `ResourceManager::get_by_id`, the shader is returned while a new version of it is loading or when the new
+/// version failed to compile, as the previous program is still usable.
+pub fn get_shader_resource(
+    resources: &ResourceManager,
+    id: ResourceId,
+) -> Option<Rc<ShaderResource>> {
+    resources
+        .get_holder_by_id(id)
+        .get_underlying_resource::<ShaderResource>()
+        .ok()
+}
+
+/// Swaps the program in use with the new one when it compiled. Otherwise, the previous program stays in use.
+fn replace_if_compiled<T>(current: &RefCell<Option<T>>, compiled: Result<T, String>) -> Status {
+    match compiled {
+        Ok(program) => {
+            current.replace(Some(program));
+            Status::Loaded
+        }
+        Err(error) if current.borrow().is_some() => Status::Error(format!(
+            "{error}\nThe previous version of the shader is used until this is fixed."
+        )),
+        Err(error) => Status::Error(error),
+    }
+}
+
+/// Reads the layout pragma of a fragment shader and checks that the inputs of the shader exist in this layout.
+fn parse_shader_layout(frag_src: &str) -> Result<ShaderLayout, String> {
+    let pragma_regex =
+        Regex::new(r"(?m)^\s*#\s*pragma\s+layout\s+(\w+)").expect("The pragma regex is valid");
+    let layout = match pragma_regex.captures(frag_src).and_then(|c| c.get(1)) {
+        None => ShaderLayout::Texture,
+        Some(name) => match name.as_str() {
+            "texture" => ShaderLayout::Texture,
+            "color" => ShaderLayout::Color,
+            other => {
+                return Err(format!(
+                    "Unknown layout '{other}' in '#pragma layout', expected 'texture' or 'color'"
+                ));
+            }
+        },
+    };
+    let (available_input, other_layout) = match layout {
+        ShaderLayout::Texture => ("uv", ShaderLayout::Color),
+        ShaderLayout::Color => ("color", ShaderLayout::Texture),
+    };
+    let input_regex =
+        Regex::new(r"(?m)^\s*in\s+\w+\s+(\w+)\s*;").expect("The input regex is valid");
+    for input in input_regex.captures_iter(frag_src).filter_map(|c| c.get(1)) {
+        if input.as_str() != available_input {
+            return Err(format!(
+                "The shader uses the {layout} layout, which only provides '{available_input}', not '{}'. \
+                 Use '#pragma layout {other_layout}' if the shader is meant for the {other_layout} layout",
+                input.as_str()
+            ));
+        }
+    }
+    Ok(layout)
+}
+
+/// Reads the path of the vertex shader given with `#pragma vertex "path"` in a fragment shader. Shaders without the
+/// pragma use the vertex shader of their layout.
+fn parse_vertex_pragma(frag_src: &str) -> Option<String> {
+    let pragma_regex = Regex::new(r#"(?m)^\s*#\s*pragma\s+vertex\s+"([^"]+)"\s*$"#)
+        .expect("The vertex pragma regex is valid");
+    pragma_regex
+        .captures(frag_src)
+        .and_then(|c| c.get(1))
+        .map(|path| path.as_str().to_string())
+}
+
+/// Includes nested deeper than this are an error.
+const MAX_INCLUDE_DEPTH: usize = 16;
+/// The maximum size of a shader once its includes are inlined, so that a file included in a loop stays an error
+/// instead of filling the memory.
+const MAX_EXPANDED_SHADER_SIZE: usize = 1024 * 1024;
+
+/// The content of a file included by a shader, as far as the resource manager knows.
+#[derive(Debug, Clone, PartialEq)]
+enum IncludeContent {
+    Loaded(String),
+    Loading,
+    Failed(String),
+}
+
+/// A shader with its `#include` lines replaced by the content of the included files.
+#[derive(Debug)]
+struct ExpandedShader {
+    source: String,
+    /// The files of the shader. The index of a file is the source string number of its `#line` directives.
+    files: Vec<PathBuf>,
+    /// False when an included file is not loaded yet.
+    is_complete: bool,
+}
+
+/// Resolves the path of an include relative to the folder of the file including it.
+/// Paths starting with `@engine/` are the shaders embedded in the engine.
+fn resolve_include_path(including_file: &Path, include: &str) -> PathBuf {
+    if include.starts_with(ENGINE_ASSET_PREFIX) {
+        return PathBuf::from(include);
+    }
+    let including_file = including_file.to_string_lossy().replace('\\', "/");
+    let mut components = including_file.split('/').collect::<Vec<_>>();
+    components.pop();
+    for component in include.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    PathBuf::from(components.join("/"))
+}
+
+/// Inlines the files included with `#include "path"`, with `#line` directives so that compile errors point to the
+/// right file and line. `read_include` gives the content of an included file.
+fn expand_includes(
+    path: &Path,
+    source: &str,
+    read_include: &mut dyn FnMut(&Path) -> IncludeContent,
+) -> Result<ExpandedShader, String> {
+    expand_includes_after(&[], path, source, read_include)
+}
+
+/// Like `expand_includes`, with the source string numbers following the files of another stage of the same program,
+/// so that the errors of both stages can be mapped with the same list of files.
+fn expand_includes_after(
+    previous_files: &[PathBuf],
+    path: &Path,
+    source: &str,
+    read_include: &mut dyn FnMut(&Path) -> IncludeContent,
+) -> Result<ExpandedShader, String> {
+    let file_index = previous_files.len();
+    let mut expanded = ExpandedShader {
+        source: String::new(),
+        files: previous_files
+            .iter()
+            .cloned()
+            .chain(std::iter::once(path.to_path_buf()))
+            .collect(),
+        is_complete: true,
+    };
+    let mut include_stack = vec![path.to_path_buf()];
+    expanded.source.push_str(&format!("#line 1 {file_index}\n"));
+    expand_file(
+        source,
+        file_index,
+        &mut include_stack,
+        &mut expanded,
+        read_include,
+    )?;
+    Ok(expanded)
+}
+
+fn expand_file(
+    source: &str,
+    file_index: usize,
+    include_stack: &mut Vec<PathBuf>,
+    expanded: &mut ExpandedShader,
+    read_include: &mut dyn FnMut(&Path) -> IncludeContent,
+) -> Result<(), String> {
+    let include_regex =
+        Regex::new(r#"^\s*#\s*include\s+"([^"]+)"\s*$"#).expect("The include regex is valid");
+    let path = include_stack.last().cloned().unwrap_or_default();
+    for (line_index, line) in source.lines().enumerate() {
+        let Some(include) = include_regex.captures(line).and_then(|c| c.get(1)) else {
+            expanded.source.push_str(line);
+            expanded.source.push('\n');
+            continue;
+        };
+        let location = format!("{}:{}", path.display(), line_index + 1);
+        let include_path = resolve_include_path(&path, include.as_str());
+        if include_stack.contains(&include_path) {
+            let cycle = include_stack
+                .iter()
+                .chain(std::iter::once(&include_path))
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(format!("{location}: Include cycle: {cycle}"));
+        }
+        if include_stack.len() > MAX_INCLUDE_DEPTH {
+            return Err(format!(
+                "{location}: Includes are nested more than {MAX_INCLUDE_DEPTH} times"
+            ));
+        }
+        let included_source = match read_include(&include_path) {
+            IncludeContent::Loaded(included_source) => included_source,
+            IncludeContent::Loading => {
+                // The other includes are still read, so that they all start loading.
+                expanded.is_complete = false;
+                continue;
+            }
+            IncludeContent::Failed(error) => {
+                return Err(format!(
+                    "{location}: Cannot include {}: {error}",
+                    include_path.display()
+                ));
+            }
+        };
+        let included_index = match expanded.files.iter().position(|file| *file == include_path) {
+            Some(index) => index,
+            None => {
+                expanded.files.push(include_path.clone());
+                expanded.files.len() - 1
+            }
+        };
+        expanded
+            .source
+            .push_str(&format!("#line 1 {included_index}\n"));
+        include_stack.push(include_path);
+        expand_file(
+            &included_source,
+            included_index,
+            include_stack,
+            expanded,
+            read_include,
+        )?;
+        include_stack.pop();
+        expanded
+            .source
+            .push_str(&format!("#line {} {file_index}\n", line_index + 2));
+        if expanded.source.len() > MAX_EXPANDED_SHADER_SIZE {
+            return Err(format!(
+                "{location}: The shader is bigger than {MAX_EXPANDED_SHADER_SIZE} bytes once its includes are inlined"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Reads a file included by a shader, loading it as a text resource the first time.
+/// The shader is loaded again when the file changes.
+fn read_include(
+    dependency_reporter: &DependencyReporter,
+    shader_id: ResourceId,
+    path: &Path,
+) -> IncludeContent {
+    dependency_reporter.declare_dependency::<TextResource>(shader_id, path);
+    let Some(id) = dependency_reporter.obtain_resource_id(path) else {
+        return IncludeContent::Loading;
+    };
+    match dependency_reporter.obtain_resource_status(id) {
+        None | Some(Status::Unloaded | Status::Loading) => IncludeContent::Loading,
+        Some(Status::Error(error)) => IncludeContent::Failed(error),
+        Some(Status::Loaded) => {
+            let text = match dependency_reporter.obtain_resource::<TextResource>(&id) {
+                Ok(text) => text,
+                Err(error) => return IncludeContent::Failed(error),
+            };
+            let content = text.content.borrow();
+            match content.as_deref().map(std::str::from_utf8) {
+                Some(Ok(content)) => IncludeContent::Loaded(content.to_string()),
+                Some(Err(error)) => IncludeContent::Failed(format!("not valid UTF-8: {error}")),
+                None => IncludeContent::Loading,
+            }
+        }
+    }
+}
+
+/// Replaces the source string numbers in the errors of the compiler, like `0:12` or `0(12)`, with the path of the file.
+fn map_error_locations(error: &str, files: &[PathBuf]) -> String {
+    let location_regex =
+        Regex::new(r"\b(\d+)(?::(\d+)|\((\d+)\))").expect("The location regex is valid");
+    location_regex
+        .replace_all(error, |captures: &regex::Captures| {
+            let file = captures[1]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| files.get(index));
+            let line = captures.get(2).or_else(|| captures.get(3));
+            match (file, line) {
+                (Some(file), Some(line)) => format!("{}:{}", file.display(), line.as_str()),
+                _ => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Compiles the fragment shader with the vertex shader of its layout, or with `vertex` when the shader gives one,
+/// without touching the shader in use.
+fn compile_shader(
+    gl: &Arc<glow::Context>,
+    expanded: &ExpandedShader,
+    vertex: Option<&ExpandedShader>,
+) -> Result<Shader, String> {
+    let frag_src = expanded.source.as_str();
+    let layout = parse_shader_layout(frag_src)?;
+    let vertex_src = match (vertex, layout) {
+        (Some(vertex), _) => vertex.source.as_str(),
+        (None, ShaderLayout::Texture) => BASE_VERTEX_SHADER,
+        (None, ShaderLayout::Color) => COLOR_VERTEX_SHADER_SOURCE,
+    };
+    // The files of the vertex shader come after the ones of the fragment shader.
+    let files = vertex.map_or(&expanded.files, |vertex| &vertex.files);
+    let program = glprogram::GLProgram::from_source(gl, vertex_src, frag_src);
+    let mut program = match program {
+        Ok(p) => p,
+        Err(e) => {
+            let e = map_error_locations(&e, files);
+            println!("Shader compilation error: {}", e);
+            return Err(format!("Failed to compile shader: {e}"));
+        }
+    };
+    let mut vertex_layout = DataLayout::new();
+    vertex_layout.add_field("in_vert", GLTypes::Vec2, Some(UsageHint::Position));
+    match layout {
+        ShaderLayout::Texture => {
+            vertex_layout.add_field("in_uv", GLTypes::Vec2, Some(UsageHint::TexCoord));
+        }
+        ShaderLayout::Color => {
+            vertex_layout.add_field("in_color", GLTypes::Vec4, Some(UsageHint::Color));
+        }
+    }
+    program.vertex_layout = vertex_layout;
+    Ok(Shader {
+        shader: program,
+        layout,
+    })
 }
 
 impl Resource for ShaderResource {
@@ -34,34 +395,66 @@ impl Resource for ShaderResource {
     }
     fn load_from_data(
         self: Rc<Self>,
-        _assigned_id: ResourceId,
-        _dependency_reporter: &DependencyReporter,
+        assigned_id: ResourceId,
+        dependency_reporter: &DependencyReporter,
         _lua: &Rc<LuaHandle>,
         gl: Arc<glow::Context>,
-        _path: &Path,
+        path: &Path,
         data: Box<[u8]>,
     ) -> Status {
-        let frag_src = match std::str::from_utf8(&data) {
-            Ok(src) => src,
-            Err(e) => {
-                return Status::Error(format!("Shader is not valid UTF-8: {e}"));
-            }
+        let expanded = std::str::from_utf8(&data)
+            .map_err(|e| format!("Shader is not valid UTF-8: {e}"))
+            .and_then(|source| {
+                expand_includes(path, source, &mut |include_path| {
+                    read_include(dependency_reporter, assigned_id, include_path)
+                })
+            });
+        let expanded = match expanded {
+            // The shader is loaded again once the included files are loaded.
+            Ok(expanded) if !expanded.is_complete => return Status::Unloaded,
+            expanded => expanded,
         };
-        let program = glprogram::GLProgram::from_source(&gl, BASE_VERTEX_SHADER, frag_src);
-        let mut program = match program {
-            Ok(p) => p,
-            Err(e) => {
-                println!("Shader compilation error: {}", e);
-                return Status::Error(format!("Failed to compile shader: {e}"));
-            }
+        // The vertex shader is a dependency like the includes, so that saving it compiles the program again.
+        let vertex = match &expanded {
+            Ok(expanded) => match parse_vertex_pragma(&expanded.source) {
+                None => Ok(None),
+                Some(vertex) => {
+                    let vertex_path = resolve_include_path(path, &vertex);
+                    match read_include(dependency_reporter, assigned_id, &vertex_path) {
+                        IncludeContent::Loaded(vertex_source) => expand_includes_after(
+                            &expanded.files,
+                            &vertex_path,
+                            &vertex_source,
+                            &mut |include_path| {
+                                read_include(dependency_reporter, assigned_id, include_path)
+                            },
+                        )
+                        .map(Some),
+                        IncludeContent::Loading => return Status::Unloaded,
+                        IncludeContent::Failed(error) => Err(format!(
+                            "Cannot read the vertex shader {}: {error}",
+                            vertex_path.display()
+                        )),
+                    }
+                }
+            },
+            Err(_) => Ok(None),
         };
-        let mut layout = DataLayout::new();
-        layout.add_field("in_vert", GLTypes::Vec2, Some(UsageHint::Position));
-        layout.add_field("in_uv", GLTypes::Vec2, Some(UsageHint::TexCoord));
-        program.vertex_layout = layout;
-        self.shader.replace(Some(Shader { shader: program }));
+        if let Ok(Some(vertex)) = &vertex
+            && !vertex.is_complete
+        {
+            return Status::Unloaded;
+        }
+        // The uniforms are stored in the resource, not in the program, so they are kept by the new program.
+        replace_if_compiled(
+            &self.shader,
+            expanded.and_then(|expanded| compile_shader(&gl, &expanded, vertex?.as_ref())),
+        )
+    }
 
-        Status::Loaded
+    fn recreate_gpu_objects(&self, _gl: &Arc<glow::Context>) -> bool {
+        std::mem::forget(self.shader.take());
+        true
     }
 
     fn draw_debug_gui(
@@ -75,7 +468,10 @@ impl Resource for ShaderResource {
             ui.label("No texture loaded.");
             return;
         };
-        ui.label(format!("Layout: {}", shader.shader.vertex_layout));
+        ui.label(format!(
+            "Layout: {} ({})",
+            shader.layout, shader.shader.vertex_layout
+        ));
     }
 
     fn default() -> Self
@@ -84,6 +480,227 @@ impl Resource for ShaderResource {
     {
         Self {
             shader: RefCell::new(None),
+            uniforms: RefCell::new(Uniforms::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        path::{Path, PathBuf},
+    };
+
+    use super::{
+        IncludeContent, ShaderLayout, ShaderResource, expand_includes, expand_includes_after,
+        get_shader_resource, map_error_locations, parse_shader_layout, parse_vertex_pragma,
+        replace_if_compiled, resolve_include_path,
+    };
+    use crate::game_resource::{ResourceManager, Status};
+
+    fn read_from(files: &[(&str, &str)]) -> impl FnMut(&Path) -> IncludeContent {
+        move |path| {
+            files
+                .iter()
+                .find(|(file, _)| Path::new(file) == path)
+                .map(|(_, content)| IncludeContent::Loaded(content.to_string()))
+                .unwrap_or_else(|| IncludeContent::Failed("not found".to_string()))
+        }
+    }
+
+    /// Stands for the shader compiler: sources being typed are missing their closing brace.
+    fn compile(source: &'static str) -> Result<&'static str, String> {
+        if source.ends_with('}') {
+            Ok(source)
+        } else {
+            Err(format!("Unexpected end of file: {source}"))
         }
     }
+
+    #[test]
+    fn failed_compilations_keep_the_previous_program() {
+        let program = RefCell::new(None);
+        let status = replace_if_compiled(&program, compile("void main() {"));
+        assert!(matches!(status, Status::Error(_)));
+        assert_eq!(*program.borrow(), None);
+
+        let sources = [
+            "void main() { color = vec4(1.0); }",
+            "void main() { color = vec4(0.5",
+            "void main() { color = vec4(0.5); }",
+            "void main() { color = ",
+            "void main() { color = vec4(",
+            "void main() { color = vec4(0.2); }",
+        ];
+        let mut last_valid_source = None;
+        for source in sources {
+            let status = replace_if_compiled(&program, compile(source));
+            if compile(source).is_ok() {
+                last_valid_source = Some(source);
+                assert!(matches!(status, Status::Loaded));
+            } else {
+                assert!(
+                    matches!(&status, Status::Error(error) if error.contains("previous version"))
+                );
+            }
+            // The batch always has a program to draw with.
+            assert_eq!(*program.borrow(), last_valid_source);
+        }
+    }
+
+    #[test]
+    fn shaders_are_available_while_reloading() {
+        let resources = ResourceManager::dummy_manager();
+        let id = resources.schedule_load_resource::<ShaderResource>(Path::new("effect.frag"));
+        for status in [
+            Status::Loaded,
+            Status::Loading,
+            Status::Error("Failed to compile shader".to_string()),
+        ] {
+            resources.get_holder_by_id(id).status.replace(status);
+            assert!(get_shader_resource(&resources, id).is_some());
+        }
+    }
+
+    #[test]
+    fn include_paths_are_relative_to_the_including_file() {
+        let shader = Path::new("shaders/effects/wave.glsl");
+        assert_eq!(
+            resolve_include_path(shader, "common/noise.glsl"),
+            PathBuf::from("shaders/effects/common/noise.glsl")
+        );
+        assert_eq!(
+            resolve_include_path(shader, "../common/./noise.glsl"),
+            PathBuf::from("shaders/common/noise.glsl")
+        );
+        assert_eq!(
+            resolve_include_path(shader, "@engine/shaders/lib/noise.glsl"),
+            PathBuf::from("@engine/shaders/lib/noise.glsl")
+        );
+    }
+
+    #[test]
+    fn includes_are_inlined_with_line_directives() {
+        let mut read = read_from(&[
+            ("shaders/common.glsl", "float a;\n#include \"deep.glsl\""),
+            ("shaders/deep.glsl", "float b;"),
+        ]);
+        let expanded = expand_includes(
+            Path::new("shaders/main.glsl"),
+            "#version 300 es\n#include \"common.glsl\"\nvoid main() {}",
+            &mut read,
+        )
+        .expect("the includes exist");
+        assert!(expanded.is_complete);
+        assert_eq!(
+            expanded.files,
+            [
+                PathBuf::from("shaders/main.glsl"),
+                PathBuf::from("shaders/common.glsl"),
+                PathBuf::from("shaders/deep.glsl"),
+            ]
+        );
+        assert_eq!(
+            expanded.source,
+            "#line 1 0\n#version 300 es\n#line 1 1\nfloat a;\n#line 1 2\nfloat b;\n#line 3 1\n#line 3 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn include_errors_name_the_file() {
+        let mut read = read_from(&[
+            ("a.glsl", "#include \"b.glsl\""),
+            ("b.glsl", "#include \"a.glsl\""),
+        ]);
+        let error = expand_includes(Path::new("a.glsl"), "#include \"b.glsl\"", &mut read)
+            .expect_err("the includes form a cycle");
+        assert!(error.contains("b.glsl:1"), "{error}");
+        assert!(error.contains("a.glsl -> b.glsl -> a.glsl"), "{error}");
+
+        let error = expand_includes(
+            Path::new("main.glsl"),
+            "void f();\n#include \"missing.glsl\"",
+            &mut read,
+        )
+        .expect_err("the include is missing");
+        assert!(error.starts_with("main.glsl:2"), "{error}");
+    }
+
+    #[test]
+    fn vertex_shaders_are_numbered_after_the_fragment_shader() {
+        let mut read = read_from(&[("shaders/common.glsl", "float a;")]);
+        let fragment = expand_includes(
+            Path::new("shaders/wave.frag"),
+            "#pragma vertex \"wave.vert\"\n#include \"common.glsl\"",
+            &mut read,
+        )
+        .expect("the includes exist");
+        assert_eq!(
+            parse_vertex_pragma(&fragment.source).as_deref(),
+            Some("wave.vert")
+        );
+        let vertex = expand_includes_after(
+            &fragment.files,
+            Path::new("shaders/wave.vert"),
+            "void main() {}",
+            &mut read,
+        )
+        .expect("the vertex shader has no includes");
+        assert_eq!(vertex.source, "#line 1 2\nvoid main() {}\n");
+        assert_eq!(
+            map_error_locations("ERROR: 2:1: 'main' : syntax error", &vertex.files),
+            "ERROR: shaders/wave.vert:1: 'main' : syntax error"
+        );
+        assert_eq!(parse_vertex_pragma("#pragma layout color"), None);
+    }
+
+    #[test]
+    fn shaders_wait_for_their_includes() {
+        let expanded = expand_includes(
+            Path::new("main.glsl"),
+            "#include \"noise.glsl\"\nvoid main() {}",
+            &mut |_| IncludeContent::Loading,
+        )
+        .expect("loading includes are not an error");
+        assert!(!expanded.is_complete);
+    }
+
+    #[test]
+    fn error_locations_point_to_the_included_files() {
+        let files = [PathBuf::from("main.glsl"), PathBuf::from("lib/noise.glsl")];
+        assert_eq!(
+            map_error_locations("ERROR: 1:12: 'x' : undeclared identifier", &files),
+            "ERROR: lib/noise.glsl:12: 'x' : undeclared identifier"
+        );
+        assert_eq!(
+            map_error_locations("0(3) : error C0000: syntax error", &files),
+            "main.glsl:3 : error C0000: syntax error"
+        );
+    }
+
+    #[test]
+    fn the_layout_pragma_chooses_the_inputs_of_the_shader() {
+        assert_eq!(
+            parse_shader_layout("in vec2 uv;\nvoid main() {}"),
+            Ok(ShaderLayout::Texture)
+        );
+        assert_eq!(
+            parse_shader_layout("#pragma layout color\nin vec4 color;\nvoid main() {}"),
+            Ok(ShaderLayout::Color)
+        );
+        assert_eq!(
+            parse_shader_layout("  # pragma layout texture\nin vec2 uv;"),
+            Ok(ShaderLayout::Texture)
+        );
+
+        let error = parse_shader_layout("#pragma layout sprite\nvoid main() {}")
+            .expect_err("unknown layouts are rejected");
+        assert!(error.contains("Unknown layout 'sprite'"));
+        // A shape shader without the pragma is loaded with the texture layout, where there is no color.
+        let error =
+            parse_shader_layout("in vec4 color;\nvoid main() {}").expect_err("color is missing");
+        assert!(error.contains("not 'color'"));
+        assert!(error.contains("'#pragma layout color'"));
+    }
 }
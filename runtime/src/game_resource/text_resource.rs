@@ -3,12 +3,14 @@ use std::{cell::RefCell, path::Path, rc::Rc};
 use crate::{
     game_resource::{Resource, ResourceId, Status},
     lua_env::LuaHandle,
+    metrics::{MemoryCategory, MemoryGuard},
 };
 use vectarine_plugin_sdk::glow;
 
 /// The most simple resource, a .txt file with some content.
 pub struct TextResource {
     pub content: RefCell<Option<Vec<u8>>>,
+    memory: RefCell<MemoryGuard>,
 }
 
 impl Resource for TextResource {
@@ -21,6 +23,7 @@ impl Resource for TextResource {
         _path: &Path,
         data: Box<[u8]>,
     ) -> Status {
+        self.memory.borrow_mut().set_bytes(data.len());
         self.content.replace(Some(Vec::from(data)));
         Status::Loaded
     }
@@ -53,6 +56,7 @@ impl Resource for TextResource {
     {
         Self {
             content: RefCell::new(None),
+            memory: RefCell::new(MemoryGuard::new(MemoryCategory::ResourceData, 0)),
         }
     }
 }
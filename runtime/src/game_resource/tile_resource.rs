@@ -1,10 +1,17 @@
-use std::{cell::RefCell, collections::HashMap, path::Path, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+};
 
 use tiled::{DefaultResourceCache, Loader, ResourceCache};
 
 use crate::{
     game_resource::{Resource, ResourceId, Status},
-    lua_env::LuaHandle,
+    graphics::tileatlas::{DEFAULT_TILE_GUTTER, MAX_TILE_GUTTER, TileLayout},
+    lua_env::{LuaHandle, lua_vec2::Vec2},
 };
 use vectarine_plugin_sdk::glow;
 
@@ -30,6 +37,26 @@ impl TilesetContent {
             type_mapping,
         }
     }
+
+    /// The width of the gutter around the tiles in the atlas can be changed with an int `gutter` property of the
+    /// tileset in Tiled.
+    pub fn tile_layout(&self) -> TileLayout {
+        let gutter = match self.tiled.properties.get("gutter") {
+            Some(tiled::PropertyValue::IntValue(gutter)) => {
+                (*gutter).clamp(0, MAX_TILE_GUTTER as i32) as u32
+            }
+            _ => DEFAULT_TILE_GUTTER,
+        };
+        TileLayout {
+            tile_width: self.tiled.tile_width,
+            tile_height: self.tiled.tile_height,
+            spacing: self.tiled.spacing,
+            margin: self.tiled.margin,
+            columns: self.tiled.columns,
+            tile_count: self.tiled.tilecount,
+            gutter,
+        }
+    }
 }
 
 pub struct TilesetResource {
@@ -186,6 +213,218 @@ impl ResourceCache for VectarineResourceCache<'_> {
 
 pub struct TilemapResource {
     pub content: RefCell<Option<tiled::Map>>,
+    /// Changes made to the map at runtime. They are applied on top of `content`.
+    pub edits: RefCell<TilemapEdits>,
+}
+
+#[derive(Default)]
+pub struct TilemapEdits {
+    /// Tiles changed at runtime, indexed by (layer, x, y). `None` means the tile was erased.
+    pub tiles: HashMap<(i32, i32, i32), Option<u32>>,
+    pub autotile_rules: HashMap<i32, Rc<AutotileRules>>,
+    /// Layers marked as solid or not at runtime, overriding the `solid` property of the layer in Tiled.
+    pub solid_layers: HashMap<i32, bool>,
+    /// The size of a tile in the world, set at runtime. Defaults to the size of the tiles in Tiled, in pixels.
+    pub tile_size: Option<Vec2>,
+}
+
+/// A rectangle of tiles, in tile coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Covers the tiles with as few rectangles as possible by merging them greedily: each rectangle starts at the first
+/// uncovered tile, row by row, and grows to the right then downwards while every tile it would cover is filled.
+/// The rectangles do not overlap.
+pub fn merge_tiles_into_rectangles(tiles: &HashSet<(i32, i32)>) -> Vec<TileRect> {
+    let mut sorted_tiles = tiles.iter().copied().collect::<Vec<_>>();
+    sorted_tiles.sort_by_key(|&(x, y)| (y, x));
+    let mut remaining = tiles.clone();
+    let mut rectangles = Vec::new();
+    for (x, y) in sorted_tiles {
+        if !remaining.contains(&(x, y)) {
+            continue;
+        }
+        let mut width = 1;
+        while remaining.contains(&(x + width, y)) {
+            width += 1;
+        }
+        let mut height = 1;
+        while (x..x + width).all(|cx| remaining.contains(&(cx, y + height))) {
+            height += 1;
+        }
+        for cy in y..y + height {
+            for cx in x..x + width {
+                remaining.remove(&(cx, cy));
+            }
+        }
+        rectangles.push(TileRect {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+    rectangles
+}
+
+/// Rules used to pick the variant of a terrain tile based on which of its neighbors are also terrain.
+pub struct AutotileRules {
+    /// If true, the diagonal neighbors are part of the mask (8-bit mask), otherwise only the 4 direct neighbors are (4-bit mask).
+    pub use_diagonals: bool,
+    /// Maps a neighbor mask to the tile to use.
+    pub variants: HashMap<u32, u32>,
+    /// Tiles that are considered terrain. Contains at least all the variants.
+    pub terrain: HashSet<u32>,
+}
+
+impl AutotileRules {
+    /// Compute the neighbor mask of the tile at (x, y).
+    /// The bits are, starting from the lowest: up, right, down, left for 4-bit masks and
+    /// up, up-right, right, down-right, down, down-left, left, up-left for 8-bit masks.
+    /// Up is towards lower y values, like in Tiled.
+    pub fn neighbor_mask(&self, x: i32, y: i32, is_terrain: impl Fn(i32, i32) -> bool) -> u32 {
+        let neighbors: &[(i32, i32)] = if self.use_diagonals {
+            &[
+                (0, -1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+                (0, 1),
+                (-1, 1),
+                (-1, 0),
+                (-1, -1),
+            ]
+        } else {
+            &[(0, -1), (1, 0), (0, 1), (-1, 0)]
+        };
+        neighbors
+            .iter()
+            .enumerate()
+            .filter(|(_, (dx, dy))| is_terrain(x + dx, y + dy))
+            .fold(0, |mask, (bit, _)| mask | (1 << bit))
+    }
+
+    /// The variants to use for the terrain tiles around (x, y), after the tile at (x, y) was edited.
+    /// Returns the tiles to change as (x, y, variant).
+    pub fn updated_tiles(
+        &self,
+        x: i32,
+        y: i32,
+        get_tile: impl Fn(i32, i32) -> Option<u32>,
+    ) -> Vec<(i32, i32, u32)> {
+        let is_terrain =
+            |x: i32, y: i32| get_tile(x, y).is_some_and(|tile| self.terrain.contains(&tile));
+        let mut updates = Vec::new();
+        for cy in (y - 1)..=(y + 1) {
+            for cx in (x - 1)..=(x + 1) {
+                let Some(current) = get_tile(cx, cy) else {
+                    continue;
+                };
+                if !self.terrain.contains(&current) {
+                    continue;
+                }
+                // The variants are terrain too, so changing a tile does not change the masks of its neighbors.
+                let mask = self.neighbor_mask(cx, cy, is_terrain);
+                if let Some(&variant) = self.variants.get(&mask)
+                    && variant != current
+                {
+                    updates.push((cx, cy, variant));
+                }
+            }
+        }
+        updates
+    }
+}
+
+impl TilemapResource {
+    /// The size of a tile in the world: the one set at runtime, or the size of the tiles in Tiled, in pixels.
+    pub fn tile_size(&self) -> Vec2 {
+        if let Some(tile_size) = self.edits.borrow().tile_size {
+            return tile_size;
+        }
+        self.content
+            .borrow()
+            .as_ref()
+            .map(|content| Vec2::new(content.tile_width as f32, content.tile_height as f32))
+            .unwrap_or(Vec2::new(1.0, 1.0))
+    }
+
+    /// A layer is solid when it has a `solid` bool property set to true in Tiled, unless it was changed at runtime.
+    pub fn is_layer_solid(&self, layer: i32) -> bool {
+        if let Some(&solid) = self.edits.borrow().solid_layers.get(&layer) {
+            return solid;
+        }
+        let content = self.content.borrow();
+        let Some(tiled_layer) = content
+            .as_ref()
+            .and_then(|content| content.get_layer(layer as usize))
+        else {
+            return false;
+        };
+        matches!(
+            tiled_layer.properties.get("solid"),
+            Some(tiled::PropertyValue::BoolValue(true))
+        )
+    }
+
+    /// The positions of the tiles of a tile layer, with the runtime edits. Returns nothing when the layer is not a tile
+    /// layer.
+    pub fn filled_tiles(&self, layer: i32) -> HashSet<(i32, i32)> {
+        let mut tiles = HashSet::new();
+        let content = self.content.borrow();
+        let Some(tile_layer) = content
+            .as_ref()
+            .and_then(|content| content.get_layer(layer as usize))
+            .and_then(|tiled_layer| tiled_layer.as_tile_layer())
+        else {
+            return tiles;
+        };
+        match tile_layer {
+            tiled::TileLayer::Finite(finite_layer) => {
+                for y in 0..finite_layer.height() as i32 {
+                    for x in 0..finite_layer.width() as i32 {
+                        if finite_layer.get_tile_data(x, y).is_some() {
+                            tiles.insert((x, y));
+                        }
+                    }
+                }
+            }
+            tiled::TileLayer::Infinite(infinite_layer) => {
+                let (width, height) = (
+                    tiled::ChunkData::WIDTH as i32,
+                    tiled::ChunkData::HEIGHT as i32,
+                );
+                for ((chunk_x, chunk_y), chunk) in infinite_layer.chunks() {
+                    for local_y in 0..height {
+                        for local_x in 0..width {
+                            if chunk.get_tile_data(local_x, local_y).is_some() {
+                                tiles.insert((
+                                    chunk_x * width + local_x,
+                                    chunk_y * height + local_y,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for (&(edited_layer, x, y), tile) in &self.edits.borrow().tiles {
+            if edited_layer != layer {
+                continue;
+            }
+            if tile.is_some() {
+                tiles.insert((x, y));
+            } else {
+                tiles.remove(&(x, y));
+            }
+        }
+        tiles
+    }
 }
 
 impl Resource for TilemapResource {
@@ -220,6 +459,8 @@ impl Resource for TilemapResource {
         match tmx {
             Err(err) => Status::Error(err.to_string()),
             Ok(tilemap) => {
+                // The map was changed on disk, so the runtime edits are discarded. The autotiling rules are kept.
+                self.edits.borrow_mut().tiles.clear();
                 self.content.replace(Some(tilemap));
                 Status::Loaded
             }
@@ -237,6 +478,10 @@ impl Resource for TilemapResource {
             ui.label(format!("width: {}", data.width));
             ui.label(format!("height: {}", data.height));
             ui.label(format!("Layer count: {}", data.layers().len()));
+            ui.label(format!(
+                "Tiles edited at runtime: {}",
+                self.edits.borrow().tiles.len()
+            ));
         } else {
             ui.label("<No content loaded>");
         }
@@ -252,6 +497,107 @@ impl Resource for TilemapResource {
     {
         Self {
             content: RefCell::new(None),
+            edits: RefCell::new(TilemapEdits::default()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{AutotileRules, TileRect, merge_tiles_into_rectangles};
+
+    fn rules(use_diagonals: bool, variants: &[(u32, u32)]) -> AutotileRules {
+        let variants = variants.iter().copied().collect::<HashMap<_, _>>();
+        AutotileRules {
+            use_diagonals,
+            terrain: variants.values().copied().collect(),
+            variants,
+        }
+    }
+
+    #[test]
+    fn neighbor_masks_start_with_the_tile_above() {
+        // .#.
+        // ##.
+        // ..#
+        let terrain = [(1, 0), (0, 1), (1, 1), (2, 2)]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let is_terrain = |x: i32, y: i32| terrain.contains(&(x, y));
+        // Up and left.
+        assert_eq!(rules(false, &[]).neighbor_mask(1, 1, is_terrain), 0b1001);
+        // Up, down-right and left.
+        assert_eq!(
+            rules(true, &[]).neighbor_mask(1, 1, is_terrain),
+            0b0100_1001
+        );
+    }
+
+    #[test]
+    fn edited_tiles_and_their_neighbors_use_the_matching_variant() {
+        // 10 is a lone tile, 11 has a neighbor on the right, 12 on the left and 13 on both sides.
+        let rules = rules(
+            false,
+            &[(0b0000, 10), (0b0010, 11), (0b1000, 12), (0b1010, 13)],
+        );
+        // A tile was just placed at (1, 0), between two lone tiles: 10 10 10.
+        let row = [Some(10), Some(10), Some(10), None];
+        let get_tile = |x: i32, y: i32| {
+            if y != 0 || !(0..4).contains(&x) {
+                return None;
+            }
+            row[x as usize]
+        };
+        let mut updates = rules.updated_tiles(1, 0, get_tile);
+        updates.sort();
+        assert_eq!(updates, vec![(0, 0, 11), (1, 0, 13), (2, 0, 12)]);
+        // Tiles that are not terrain are left alone.
+        assert!(rules.updated_tiles(1, 0, |_, _| Some(99)).is_empty());
+    }
+
+    #[test]
+    fn tiles_are_merged_into_rectangles_covering_them_once() {
+        // ###.
+        // ###.
+        // #..#
+        let tiles = [
+            (0, 0),
+            (1, 0),
+            (2, 0),
+            (0, 1),
+            (1, 1),
+            (2, 1),
+            (0, 2),
+            (3, 2),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>();
+        let rectangles = merge_tiles_into_rectangles(&tiles);
+        assert_eq!(
+            rectangles,
+            vec![
+                TileRect {
+                    x: 0,
+                    y: 0,
+                    width: 3,
+                    height: 2
+                },
+                TileRect {
+                    x: 0,
+                    y: 2,
+                    width: 1,
+                    height: 1
+                },
+                TileRect {
+                    x: 3,
+                    y: 2,
+                    width: 1,
+                    height: 1
+                },
+            ]
+        );
+        assert!(merge_tiles_into_rectangles(&HashSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use vectarine_plugin_sdk::glow;
+
+use crate::{
+    console::{Subsystem, print_err_from},
+    game_resource::{DependencyReporter, Resource, ResourceId, Status},
+    graphics::gltexture::{ImageAntialiasing, Texture},
+    lua_env::LuaHandle,
+    sound::{self, ChannelId, DecodedSound, PlaySound, SoundData},
+    video::{VideoInfo, VideoStream, decode_soundtrack, mpegps},
+};
+
+/// Where the video is. The position advances with `playback_clock` while the video plays.
+#[derive(Clone, Copy, Debug, Default)]
+struct Playback {
+    is_playing: bool,
+    /// In seconds, at `started_at`.
+    position: f64,
+    /// The time of `playback_clock`, in seconds.
+    started_at: f64,
+}
+
+impl Playback {
+    fn position_at(&self, clock: f64, duration: f64) -> f64 {
+        let position = if self.is_playing {
+            self.position + (clock - self.started_at).max(0.0)
+        } else {
+            self.position
+        };
+        position.min(duration)
+    }
+}
+
+/// The clock of the videos, in seconds. With a sound card, it is the audio clock, so that the frames follow the
+/// soundtrack heard. Otherwise, it is the frame clock of the game, `now`.
+fn playback_clock(now: Duration) -> f64 {
+    if sound::has_audio_output() {
+        sound::get_audio_clock()
+    } else {
+        now.as_secs_f64()
+    }
+}
+
+pub struct VideoResource {
+    pub stream: RefCell<Option<VideoStream>>,
+    /// Holds the frame shown, replaced as the video plays.
+    pub texture: RefCell<Option<Arc<Texture>>>,
+    /// The whole soundtrack, in the format of the mixer. None for a silent video.
+    soundtrack: RefCell<Option<SoundData>>,
+    channel: RefCell<Option<ChannelId>>,
+    playback: Cell<Playback>,
+    /// The soundtrack was not decoded when the video started, so it starts once it is.
+    is_soundtrack_start_pending: Cell<bool>,
+    /// The index of the frame in the texture.
+    shown_frame: Cell<Option<u64>>,
+}
+
+/// Decodes the soundtrack without blocking the main thread, on the decoder worker of the sound system.
+fn decode_soundtrack_in_background(path: &Path, audio_data: Vec<u8>, sound: SoundData) {
+    let path = path.to_path_buf();
+    let decode = move || {
+        let samples = decode_soundtrack(audio_data).unwrap_or_else(|err| {
+            print_err_from(
+                Subsystem::Audio,
+                format!("Failed to decode the audio of {}: {}", path.display(), err),
+            );
+            Vec::new()
+        });
+        let _ = sound.set(DecodedSound::new(samples));
+    };
+    sound::decode_in_background(decode);
+}
+
+impl Resource for VideoResource {
+    fn load_from_data(
+        self: Rc<Self>,
+        _assigned_id: ResourceId,
+        _dependency_reporter: &DependencyReporter,
+        _lua: &Rc<LuaHandle>,
+        gl: Arc<glow::Context>,
+        path: &Path,
+        data: Box<[u8]>,
+    ) -> Status {
+        let streams = match mpegps::demux(&data) {
+            Ok(streams) => streams,
+            Err(err) => return Status::Error(err),
+        };
+        let stream = match VideoStream::open(Arc::from(streams.video)) {
+            Ok(stream) => stream,
+            Err(err) => return Status::Error(err),
+        };
+        let info = stream.info();
+
+        self.texture.replace(Some(Texture::new_rgba(
+            &gl,
+            None,
+            info.width,
+            info.height,
+            ImageAntialiasing::Linear,
+        )));
+        self.stream.replace(Some(stream));
+        self.playback.set(Playback::default());
+        self.shown_frame.set(None);
+        self.is_soundtrack_start_pending.set(false);
+
+        if let Some(channel) = *self.channel.borrow() {
+            sound::pause_audio(channel);
+        }
+        if streams.audio.is_empty() {
+            self.soundtrack.replace(None);
+        } else {
+            let sound = SoundData::default();
+            self.soundtrack.replace(Some(sound.clone()));
+            decode_soundtrack_in_background(path, streams.audio, sound);
+            if self.channel.borrow().is_none() {
+                self.channel.replace(Some(sound::get_available_channel()));
+            }
+        }
+        Status::Loaded
+    }
+
+    fn recreate_gpu_objects(&self, gl: &Arc<glow::Context>) -> bool {
+        std::mem::forget(self.texture.take());
+        let Some(info) = self.info() else {
+            return false;
+        };
+        self.texture.replace(Some(Texture::new_rgba(
+            gl,
+            None,
+            info.width,
+            info.height,
+            ImageAntialiasing::Linear,
+        )));
+        // The frame of the texture is gone, so the next one is fetched again from the position.
+        self.shown_frame.set(None);
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            stream.seek(info.frame_at(self.playback.get().position));
+        }
+        false
+    }
+
+    fn draw_debug_gui(
+        &self,
+        _painter: &mut vectarine_plugin_sdk::egui_glow::Painter,
+        ui: &mut vectarine_plugin_sdk::egui::Ui,
+    ) {
+        ui.label("Video Resource");
+        let Some(info) = self.info() else {
+            ui.label("No video loaded");
+            return;
+        };
+        ui.label(format!("Size: {}x{}", info.width, info.height));
+        ui.label(format!(
+            "{} frames at {:.2} fps ({:.1}s)",
+            info.frame_count,
+            info.frame_rate,
+            info.duration()
+        ));
+        let playback = self.playback.get();
+        ui.label(if playback.is_playing {
+            "Playing"
+        } else {
+            "Paused"
+        });
+        match self.shown_frame.get() {
+            Some(frame) => ui.label(format!("Showing frame {frame}")),
+            None => ui.label("No frame shown yet"),
+        };
+        let soundtrack = self.soundtrack.borrow();
+        ui.label(match soundtrack.as_ref() {
+            None => "No audio",
+            Some(sound) if sound.get().is_some() => "Audio decoded",
+            Some(_) => "Decoding the audio...",
+        });
+    }
+
+    fn get_type_name(&self) -> &'static str {
+        "Video"
+    }
+
+    fn default() -> Self
+    where
+        Self: Sized,
+    {
+        Self {
+            stream: RefCell::new(None),
+            texture: RefCell::new(None),
+            soundtrack: RefCell::new(None),
+            channel: RefCell::new(None),
+            playback: Cell::new(Playback::default()),
+            is_soundtrack_start_pending: Cell::new(false),
+            shown_frame: Cell::new(None),
+        }
+    }
+}
+
+impl VideoResource {
+    pub fn info(&self) -> Option<VideoInfo> {
+        self.stream.borrow().as_ref().map(VideoStream::info)
+    }
+
+    /// The position in seconds at the time `now` of the frame clock.
+    pub fn position(&self, now: Duration) -> f64 {
+        let Some(info) = self.info() else {
+            return 0.0;
+        };
+        self.playback
+            .get()
+            .position_at(playback_clock(now), info.duration())
+    }
+
+    pub fn is_finished(&self, now: Duration) -> bool {
+        self.info()
+            .is_some_and(|info| self.position(now) >= info.duration())
+    }
+
+    /// Plays from the current position, or from the start when the video is finished.
+    pub fn play(&self, now: Duration) {
+        if self.is_finished(now) {
+            self.seek(0.0, now);
+        }
+        let position = self.position(now);
+        self.playback.set(Playback {
+            is_playing: true,
+            position,
+            started_at: playback_clock(now),
+        });
+        self.start_soundtrack(position);
+    }
+
+    pub fn pause(&self, now: Duration) {
+        let position = self.position(now);
+        self.playback.set(Playback {
+            is_playing: false,
+            position,
+            started_at: playback_clock(now),
+        });
+        self.is_soundtrack_start_pending.set(false);
+        if let Some(channel) = *self.channel.borrow() {
+            sound::pause_audio(channel);
+        }
+    }
+
+    /// Moves to `position` seconds. The video keeps playing if it was.
+    pub fn seek(&self, position: f64, now: Duration) {
+        let Some(info) = self.info() else {
+            return;
+        };
+        let position = position.clamp(0.0, info.duration());
+        let is_playing = self.playback.get().is_playing;
+        self.playback.set(Playback {
+            is_playing,
+            position,
+            started_at: playback_clock(now),
+        });
+        if let Some(stream) = self.stream.borrow_mut().as_mut() {
+            stream.seek(info.frame_at(position));
+        }
+        self.shown_frame.set(None);
+        if is_playing {
+            self.start_soundtrack(position);
+        }
+    }
+
+    /// Puts the frame of the current position in the texture. Called before drawing the video.
+    pub fn update(&self, now: Duration) {
+        let Some(info) = self.info() else {
+            return;
+        };
+        let position = self.position(now);
+        if self.is_soundtrack_start_pending.get() && self.playback.get().is_playing {
+            self.start_soundtrack(position);
+        }
+        let index = info.frame_at(position);
+        if self.shown_frame.get() == Some(index) {
+            return;
+        }
+        let frame = self
+            .stream
+            .borrow_mut()
+            .as_mut()
+            .and_then(|stream| stream.take_frame(index));
+        let Some(frame) = frame else {
+            // The decoder is late: the previous frame stays until the next one is ready.
+            return;
+        };
+        if let Some(texture) = self.texture.borrow().as_ref() {
+            texture.update_rgba(&frame.rgba);
+        }
+        self.shown_frame.set(Some(frame.index));
+    }
+
+    /// Plays the soundtrack from `position` on the audio clock now. The samples are shared with the mixer, which
+    /// starts reading them at the position.
+    fn start_soundtrack(&self, position: f64) {
+        let Some(channel) = *self.channel.borrow() else {
+            return;
+        };
+        let soundtrack = self.soundtrack.borrow();
+        let Some(soundtrack) = soundtrack.as_ref() else {
+            self.is_soundtrack_start_pending.set(false);
+            return;
+        };
+        if soundtrack.get().is_none() {
+            self.is_soundtrack_start_pending.set(true);
+            return;
+        }
+        self.is_soundtrack_start_pending.set(false);
+        sound::resume_audio(channel);
+        sound::play_sound_at(
+            channel,
+            PlaySound {
+                sound: soundtrack.clone(),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: false,
+                start_ms: (position * 1000.0) as f32,
+            },
+            sound::get_audio_clock(),
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    ffi::c_int,
+    time::{Duration, Instant},
+};
+
+use vectarine_plugin_sdk::mlua::{self, Lua, ffi};
+
+use crate::metrics::{LUA_ALLOCATED_METRIC_NAME, LUA_GC_TIME_METRIC_NAME, MetricsHolder};
+
+/// The amount of allocations, in KB, that a step of the collector pays for when the game did not set it.
+pub const DEFAULT_GC_STEP_SIZE_KB: u32 = 64;
+
+/// The most allocations, in KB, that the step of a frame pays for. A frame allocating more, like one loading a level,
+/// would otherwise make the next frame long. The rest is paid by the next frames.
+pub const MAX_FRAME_STEP_SIZE_KB: u32 = 8 * 1024;
+
+/// Controls when the Luau garbage collector runs and measures what it costs.
+///
+/// With `gc_step_per_frame`, the collector is stopped while the game runs and a step is made at the end of every
+/// frame instead, as big as the allocations of the frame. Collections then cost a bit every frame instead of making
+/// some frames much longer than the others.
+pub struct GcPacer {
+    is_step_per_frame: bool,
+    step_size_kb: u32,
+    /// The size of the heap at the end of the previous frame, to measure the allocations of the current one.
+    heap_at_frame_start: usize,
+    /// The allocations, in KB, that the steps of the previous frames did not pay for.
+    debt_kb: u32,
+}
+
+impl GcPacer {
+    pub fn new(lua: &Lua) -> Self {
+        Self {
+            is_step_per_frame: false,
+            step_size_kb: DEFAULT_GC_STEP_SIZE_KB,
+            heap_at_frame_start: lua.used_memory(),
+            debt_kb: 0,
+        }
+    }
+
+    pub fn is_step_per_frame(&self) -> bool {
+        self.is_step_per_frame
+    }
+
+    pub fn set_step_per_frame(&mut self, lua: &Lua, is_step_per_frame: bool) -> mlua::Result<()> {
+        self.is_step_per_frame = is_step_per_frame;
+        let what = if is_step_per_frame {
+            ffi::LUA_GCSTOP
+        } else {
+            ffi::LUA_GCRESTART
+        };
+        lua_gc(lua, what, 0)?;
+        Ok(())
+    }
+
+    /// Sets the amount of allocations, in KB, that a step of the collector pays for. Bigger steps are less frequent.
+    pub fn set_step_size(&mut self, lua: &Lua, step_size_kb: u32) -> mlua::Result<()> {
+        self.step_size_kb = step_size_kb.max(1);
+        lua_gc(lua, ffi::LUA_GCSETSTEPSIZE, to_c_int(self.step_size_kb))?;
+        Ok(())
+    }
+
+    /// Sets how much the heap can grow, in percent of the heap in use after a collection, before the next one starts.
+    /// Luau calls it the goal of the collector. It defaults to 200.
+    pub fn set_pause(&self, lua: &Lua, percent: u32) -> mlua::Result<()> {
+        lua_gc(lua, ffi::LUA_GCSETGOAL, to_c_int(percent.max(100)))?;
+        Ok(())
+    }
+
+    /// Runs a full collection.
+    pub fn collect(&self, lua: &Lua) -> mlua::Result<()> {
+        lua.gc_collect()?;
+        if self.is_step_per_frame {
+            // The end of a collection starts the collector again.
+            lua_gc(lua, ffi::LUA_GCSTOP, 0)?;
+        }
+        Ok(())
+    }
+
+    pub fn step_size_kb(&self) -> u32 {
+        self.step_size_kb
+    }
+
+    /// Runs a step of the collector that pays for `step_size_kb` of allocations. Returns the time it took.
+    pub fn step(&self, lua: &Lua, step_size_kb: u32) -> mlua::Result<Duration> {
+        let start = Instant::now();
+        let step_size_kb = to_c_int(step_size_kb.max(1));
+        if self.is_step_per_frame {
+            // A stopped collector does not do any work, even when asked for a step.
+            lua_gc(lua, ffi::LUA_GCRESTART, 0)?;
+        }
+        // Luau does an amount of work proportional to the step size, whatever the size asked for the step.
+        let previous_step_size_kb = lua_gc(lua, ffi::LUA_GCSETSTEPSIZE, step_size_kb)?;
+        lua_gc(lua, ffi::LUA_GCSTEP, step_size_kb)?;
+        lua_gc(lua, ffi::LUA_GCSETSTEPSIZE, previous_step_size_kb)?;
+        if self.is_step_per_frame {
+            lua_gc(lua, ffi::LUA_GCSTOP, 0)?;
+        }
+        Ok(start.elapsed())
+    }
+
+    /// Called at the end of every frame. Runs the step of the frame when `gc_step_per_frame` is set and records the
+    /// allocations and the time of the collector.
+    ///
+    /// Without `gc_step_per_frame`, the collections made during the frame are subtracted from the allocations, so
+    /// they are only an approximation.
+    pub fn end_frame(&mut self, lua: &Lua, metrics: &mut MetricsHolder) -> mlua::Result<()> {
+        let allocated = lua.used_memory().saturating_sub(self.heap_at_frame_start);
+        metrics.record_number_metric(LUA_ALLOCATED_METRIC_NAME, allocated);
+        let gc_time = if self.is_step_per_frame {
+            let allocated_kb = u32::try_from(allocated / 1024).unwrap_or(u32::MAX);
+            let step_size_kb = self.frame_step_size_kb(allocated_kb);
+            self.step(lua, step_size_kb)?
+        } else {
+            Duration::ZERO
+        };
+        // Also makes the metric exist when the game never runs the collector itself.
+        metrics.record_duration_metric(LUA_GC_TIME_METRIC_NAME, gc_time);
+        self.heap_at_frame_start = lua.used_memory();
+        Ok(())
+    }
+
+    /// The size of the step of a frame: the allocations of the frame and the debt of the previous frames, at least a
+    /// normal step and at most `MAX_FRAME_STEP_SIZE_KB`.
+    fn frame_step_size_kb(&mut self, allocated_kb: u32) -> u32 {
+        let owed_kb = self.debt_kb.saturating_add(allocated_kb);
+        let step_size_kb = owed_kb.clamp(
+            self.step_size_kb,
+            MAX_FRAME_STEP_SIZE_KB.max(self.step_size_kb),
+        );
+        self.debt_kb = owed_kb.saturating_sub(step_size_kb);
+        step_size_kb
+    }
+}
+
+fn to_c_int(value: u32) -> c_int {
+    value.min(c_int::MAX as u32) as c_int
+}
+
+fn lua_gc(lua: &Lua, what: c_int, data: c_int) -> mlua::Result<c_int> {
+    let mut result = 0;
+    // SAFETY: lua_gc does not use the stack of the state.
+    unsafe {
+        lua.exec_raw::<()>((), |state| {
+            result = ffi::lua_gc(state, what, data);
+        })?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_GC_STEP_SIZE_KB, GcPacer, MAX_FRAME_STEP_SIZE_KB};
+    use crate::metrics::{LUA_ALLOCATED_METRIC_NAME, MetricsHolder};
+    use vectarine_plugin_sdk::mlua::Lua;
+
+    #[test]
+    fn steps_per_frame_collect_the_garbage_of_the_frame() {
+        let lua = Lua::new();
+        let mut pacer = GcPacer::new(&lua);
+        pacer
+            .set_step_per_frame(&lua, true)
+            .expect("the collector can be stopped");
+        let mut metrics = MetricsHolder::new();
+        let allocate_garbage = "for i = 1, 10000 do local t = { i, tostring(i) } end";
+
+        let heap_before = lua.used_memory();
+        for _ in 0..100 {
+            lua.load(allocate_garbage)
+                .exec()
+                .expect("the test code is valid");
+            pacer
+                .end_frame(&lua, &mut metrics)
+                .expect("the collector can run");
+            metrics.flush();
+        }
+        let allocated = metrics
+            .get_numeric_metric_by_name(LUA_ALLOCATED_METRIC_NAME)
+            .expect("the allocations are recorded");
+        assert!(allocated.max() > 100 * 1024);
+        // The steps keep up with the allocations, even though the collector only runs at the end of the frames.
+        assert!(lua.used_memory() < heap_before + 16 * allocated.max());
+    }
+
+    #[test]
+    fn large_allocations_are_paid_over_several_frames() {
+        let lua = Lua::new();
+        let mut pacer = GcPacer::new(&lua);
+        assert_eq!(pacer.frame_step_size_kb(1), DEFAULT_GC_STEP_SIZE_KB);
+        assert_eq!(pacer.frame_step_size_kb(100), 100);
+
+        // A frame loading a level.
+        let level_kb = MAX_FRAME_STEP_SIZE_KB * 2 + 10;
+        assert_eq!(pacer.frame_step_size_kb(level_kb), MAX_FRAME_STEP_SIZE_KB);
+        assert_eq!(pacer.frame_step_size_kb(0), MAX_FRAME_STEP_SIZE_KB);
+        assert_eq!(pacer.frame_step_size_kb(0), DEFAULT_GC_STEP_SIZE_KB);
+        // The debt is paid, the steps are back to normal.
+        assert_eq!(pacer.frame_step_size_kb(0), DEFAULT_GC_STEP_SIZE_KB);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod glbuffer;
 pub mod gldraw;
+pub mod glerror;
 pub mod glframebuffer;
 pub mod glprogram;
 pub mod gltypes;
@@ -8,8 +9,16 @@ pub mod batchdraw;
 pub mod glstencil;
 pub mod gltexture;
 pub mod gluniforms;
+pub mod instancedtext;
 
+pub mod letterbox;
+pub mod particles;
+pub mod pixelperfect;
+
+pub mod imagepacking;
 pub mod shadersources;
 pub mod shape;
+pub mod textlayout;
+pub mod tileatlas;
 
 pub mod affinetransform;
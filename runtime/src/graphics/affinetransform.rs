@@ -48,6 +48,15 @@ impl AffineTransform {
         )
     }
 
+    /// The transform as a column-major matrix, for the `mat3` uniforms of shaders.
+    pub fn to_mat3(&self) -> [[f32; 3]; 3] {
+        [
+            [self.a, self.b, 0.0],
+            [self.c, self.d, 0.0],
+            [self.tx, self.ty, 1.0],
+        ]
+    }
+
     /// Returns the translation component of the affine transform.
     pub fn translation(&self) -> Vec2 {
         let det = self.a * self.d - self.b * self.c;
@@ -1,27 +1,33 @@
-use std::{sync::Arc, time::Instant};
+use std::{collections::HashMap, sync::Arc, time::Instant};
 
 use crate::{
+    console::print_info,
     game_resource::{
-        ResourceId, ResourceManager, font_resource::FontRenderingData,
-        shader_resource::ShaderResource,
+        ResourceId, ResourceManager,
+        font_resource::{CharacterInfo, FontRenderingData},
+        image_resource::ImageResource,
+        shader_resource::get_shader_resource,
     },
     graphics::{
         affinetransform::AffineTransform,
         glbuffer::{BufferUsageHint, SharedGPUCPUBuffer},
         gldraw::DrawingTarget,
+        glerror::check_gl_error,
         glframebuffer::Framebuffer,
         glprogram::GLProgram,
         gltexture::Texture,
         gltypes::{DataLayout, GLTypes, UsageHint},
         gluniforms::{UniformValue, Uniforms},
+        instancedtext::{GLYPH_INSTANCE_SIZE, create_instanced_text_program, push_text_instances},
         shadersources::{
             COLOR_FRAG_SHADER_SOURCE, COLOR_VERTEX_SHADER_SOURCE, FONT_FRAG_SHADER_SOURCE,
             FONT_VERTEX_SHADER_SOURCE, TEX_FRAG_SHADER_SOURCE, TEX_VERTEX_SHADER_SOURCE,
         },
         shape::Quad,
+        textlayout::TextLayout,
     },
     io::IoEnvState,
-    lua_env::lua_vec2::Vec2,
+    lua_env::{lua_transform::Transform2, lua_vec2::Vec2},
 };
 use vectarine_plugin_sdk::glow;
 
@@ -30,9 +36,18 @@ pub enum BatchShader {
     Color,
     Texture,
     Font,
+    /// One glyph per row of the buffer, drawn with instancing.
+    InstancedText,
     Custom(ResourceId), // Id of the custom shader
 }
 
+/// A shader resource replacing the built-in shaders of the primitives drawn inside `BatchDraw2d::with_custom_shader`.
+pub struct CustomShader {
+    pub id: ResourceId,
+    /// Added to the uniforms of each draw call, like `tex` and `tint_color` for images.
+    pub uniforms: Uniforms,
+}
+
 /// A simple structure to get quickly start drawing shapes.
 /// Batches OpenGL calls together when possible.
 /// Designed for immediate drawing
@@ -40,12 +55,37 @@ pub struct BatchDraw2d {
     color_program: GLProgram,
     texture_program: GLProgram,
     text_program: GLProgram,
+    /// None when the driver cannot read textures in vertex shaders. `draw_text_instanced` then uses `draw_text`.
+    instanced_text_program: Option<GLProgram>,
+    has_reported_instancing_fallback: bool,
+    instanced_glyph_counter: usize,
     aspect_ratio: f32,
 
     pub affine_transform: AffineTransform,
 
     vertex_data: Vec<(SharedGPUCPUBuffer, Uniforms, BatchShader)>,
     pub drawing_target: DrawingTarget,
+
+    /// Draw calls entirely outside of this rectangle are skipped before their vertices are generated.
+    /// The corners are the minimum and the maximum, after the affine transform.
+    cull_rect: Option<(Vec2, Vec2)>,
+    culled_draw_counter: usize,
+
+    custom_shader: Option<CustomShader>,
+
+    /// The canvases being painted, the innermost last. The batched entries are drawn on the innermost canvas, or on
+    /// the screen when there is none.
+    painted_canvases: Vec<PaintedCanvas>,
+
+    /// The internal resolution of the pixel perfect mode when it snaps the images to whole pixels.
+    pixel_snap: Option<[u32; 2]>,
+}
+
+struct PaintedCanvas {
+    framebuffer: glow::NativeFramebuffer,
+    /// The culling rectangle of the target the canvas is painted from. It is in the coordinates of that target, so it
+    /// is cleared while painting the canvas and restored after.
+    outer_cull_rect: Option<(Vec2, Vec2)>,
 }
 
 impl BatchDraw2d {
@@ -81,10 +121,18 @@ impl BatchDraw2d {
             color_program,
             texture_program,
             text_program,
+            instanced_text_program: create_instanced_text_program(gl),
+            has_reported_instancing_fallback: false,
+            instanced_glyph_counter: 0,
             vertex_data: Vec::new(),
             aspect_ratio: 1.0,
             affine_transform: AffineTransform::identity(),
             drawing_target,
+            cull_rect: None,
+            culled_draw_counter: 0,
+            custom_shader: None,
+            painted_canvases: Vec::new(),
+            pixel_snap: None,
         })
     }
 
@@ -92,6 +140,118 @@ impl BatchDraw2d {
         self.aspect_ratio = aspect_ratio;
     }
 
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn set_pixel_snap(&mut self, resolution: Option<[u32; 2]>) {
+        self.pixel_snap = resolution;
+    }
+
+    /// Moves the transformed corners of an image so that the first one is on a whole pixel of the internal resolution.
+    /// Canvases have their own resolution, so the images painted on them are not moved.
+    fn snap_to_pixels(&self, points: [Vec2; 4]) -> [Vec2; 4] {
+        match self.pixel_snap {
+            Some(resolution) if self.painted_canvases.is_empty() => snap_quad(points, resolution),
+            _ => points,
+        }
+    }
+
+    /// Skip the draw calls that are entirely outside of the rectangle going from `min` to `max` until the end of the frame.
+    /// The rectangle is transformed by the current affine transform. None draws everything.
+    pub fn set_cull_rect(&mut self, rect: Option<(Vec2, Vec2)>) {
+        self.cull_rect = rect.map(|(min, max)| {
+            let quad = self.affine_transform.apply_quad(&make_rect(
+                min.x(),
+                min.y(),
+                max.x() - min.x(),
+                max.y() - min.y(),
+            ));
+            bounding_box(&[quad.p1, quad.p2, quad.p3, quad.p4])
+        });
+    }
+
+    /// Disable culling and return the previous rectangle, to restore it with `restore_cull_rect`.
+    pub fn take_cull_rect(&mut self) -> Option<(Vec2, Vec2)> {
+        self.cull_rect.take()
+    }
+
+    pub fn restore_cull_rect(&mut self, rect: Option<(Vec2, Vec2)>) {
+        self.cull_rect = rect;
+    }
+
+    /// Number of draw calls skipped by culling since the last reset.
+    pub fn get_culled_draw_counter(&self) -> usize {
+        self.culled_draw_counter
+    }
+
+    /// Called at the start of each frame: the culling rectangle only lasts for one frame.
+    pub fn reset_culling(&mut self) {
+        self.cull_rect = None;
+        self.culled_draw_counter = 0;
+        self.instanced_glyph_counter = 0;
+    }
+
+    /// Number of glyphs drawn with `draw_text_instanced` since the last reset.
+    pub fn get_instanced_glyph_counter(&self) -> usize {
+        self.instanced_glyph_counter
+    }
+
+    /// Returns true and counts the draw call when the points, already transformed, are all outside of the culling rectangle.
+    fn is_culled(&mut self, points: &[Vec2]) -> bool {
+        let Some(cull_rect) = self.cull_rect else {
+            return false;
+        };
+        let is_culled = !do_rects_overlap(cull_rect, bounding_box(points));
+        if is_culled {
+            self.culled_draw_counter += 1;
+        }
+        is_culled
+    }
+
+    /// Draws the primitives submitted by `draw_fn` with the custom shader instead of the built-in ones.
+    /// The caller checks that the layout of the shader matches the vertices of the primitives.
+    pub fn with_custom_shader<R>(
+        &mut self,
+        shader: CustomShader,
+        draw_fn: impl FnOnce(&mut Self) -> R,
+    ) -> R {
+        let previous_shader = self.custom_shader.replace(shader);
+        let result = draw_fn(self);
+        self.custom_shader = previous_shader;
+        result
+    }
+
+    /// Call before binding a canvas to paint on it. The entries batched for the previous target are drawn first, so
+    /// that the draw calls are executed in the order they were made from Lua.
+    pub fn begin_canvas(&mut self, resources: &ResourceManager, canvas: &Framebuffer) {
+        self.draw(resources, true);
+        self.painted_canvases.push(PaintedCanvas {
+            framebuffer: canvas.id(),
+            outer_cull_rect: self.cull_rect.take(),
+        });
+    }
+
+    /// Call before unbinding the canvas, to draw the entries batched for it while it is still bound.
+    pub fn end_canvas(&mut self, resources: &ResourceManager) {
+        self.draw(resources, true);
+        if let Some(painted_canvas) = self.painted_canvases.pop() {
+            self.cull_rect = painted_canvas.outer_cull_rect;
+        }
+    }
+
+    /// Draws the entries batched for the canvas, so that drawing the canvas shows them.
+    /// Entries are only pending for a canvas when it is drawn inside of its own paint function.
+    pub fn flush_canvas(&mut self, resources: &ResourceManager, canvas: &Framebuffer) {
+        if self
+            .painted_canvases
+            .last()
+            .is_some_and(|painted_canvas| painted_canvas.framebuffer == canvas.id())
+        {
+            self.draw(resources, true);
+        }
+    }
+
     pub fn draw(&mut self, resources: &ResourceManager, auto_flush: bool) {
         // This is probably a dubious optimization, it needs to be benchmarked.
         let hint = if auto_flush {
@@ -113,9 +273,18 @@ impl BatchDraw2d {
                 BatchShader::Color => draw(vertex, &self.color_program, uniforms),
                 BatchShader::Texture => draw(vertex, &self.texture_program, uniforms),
                 BatchShader::Font => draw(vertex, &self.text_program, uniforms),
+                BatchShader::InstancedText => {
+                    let Some(program) = &self.instanced_text_program else {
+                        continue;
+                    };
+                    self.drawing_target.draw_instanced(
+                        vertex.send_to_gpu_with_usage(self.drawing_target.gl(), &hint),
+                        program,
+                        uniforms,
+                    );
+                }
                 BatchShader::Custom(id) => {
-                    let shader = resources.get_by_id::<ShaderResource>(id.to_owned());
-                    let Ok(shader) = shader else {
+                    let Some(shader) = get_shader_resource(resources, *id) else {
                         continue;
                     };
                     let shader = &shader.shader;
@@ -124,9 +293,11 @@ impl BatchDraw2d {
                         continue;
                     };
                     draw(vertex, &shader.shader, uniforms);
-                    continue;
                 }
             };
+            check_gl_error(self.drawing_target.gl(), || {
+                describe_batch_entry(resources, vertex, uniforms, *shader)
+            });
         }
         if auto_flush {
             self.flush();
@@ -144,16 +315,37 @@ impl BatchDraw2d {
         if vertices.is_empty() || indices.is_empty() {
             return;
         }
+        // The vertices are laid out for the built-in shader, custom shaders declare which layout they expect.
+        let layout_shader = shader_to_use;
+        let (uniforms, shader_to_use) = match &self.custom_shader {
+            Some(custom_shader) => (
+                add_custom_uniforms(uniforms, &custom_shader.uniforms),
+                BatchShader::Custom(custom_shader.id),
+            ),
+            None => (uniforms, shader_to_use),
+        };
 
         let last_item = self.vertex_data.last_mut();
         let Some(last_item) = last_item else {
-            self.add_to_batch_as_new_entry(vertices, indices, uniforms, shader_to_use);
+            self.add_to_batch_as_new_entry(
+                vertices,
+                indices,
+                uniforms,
+                shader_to_use,
+                layout_shader,
+            );
             return;
         };
         let (last_vertex_buffer, last_uniforms, last_shader) = last_item;
         // Merging is not possible if the uniforms are not the same / the shader is different.
         if *last_shader != shader_to_use || !last_uniforms.similar(&uniforms) {
-            self.add_to_batch_as_new_entry(vertices, indices, uniforms, shader_to_use);
+            self.add_to_batch_as_new_entry(
+                vertices,
+                indices,
+                uniforms,
+                shader_to_use,
+                layout_shader,
+            );
             return;
         }
 
@@ -166,11 +358,15 @@ impl BatchDraw2d {
         indices: &[u32],
         uniforms: Uniforms,
         shader_to_use: BatchShader,
+        layout_shader: BatchShader,
     ) {
-        let layout = (match shader_to_use {
+        let layout = (match layout_shader {
             BatchShader::Color => &self.color_program,
             BatchShader::Texture => &self.texture_program,
             BatchShader::Font => &self.text_program,
+            BatchShader::InstancedText => {
+                unreachable!("Instanced text is added by draw_text_instanced")
+            }
             BatchShader::Custom(_) => {
                 &self.texture_program // Custom shaders have the same layout as texture shaders
             }
@@ -186,21 +382,26 @@ impl BatchDraw2d {
     }
 
     pub fn draw_polygon(&mut self, points: impl Iterator<Item = Vec2>, color: [f32; 4]) {
-        let mut points_len = 0;
+        let points = points
+            .map(|p| self.affine_transform.apply(&p))
+            .collect::<Vec<_>>();
+        let points_len = points.len();
+
+        if points_len < 3 {
+            return; // Not enough points to form a polygon
+        }
+        if self.is_culled(&points) {
+            return;
+        }
+
         #[rustfmt::skip]
-        let vertices: Vec<f32> = points.flat_map(|p| {
-            points_len += 1;
-            let p = self.affine_transform.apply(&p);
-            vec![
+        let vertices: Vec<f32> = points.iter().flat_map(|p| {
+            [
                 p.x(), p.y(), // position
                 color[0], color[1], color[2], color[3], // color
             ]
         }).collect();
 
-        if points_len < 3 {
-            return; // Not enough points to form a polygon
-        }
-
         // Triangulate the polygon using a triangle fan
         let mut indices: Vec<u32> = Vec::with_capacity((points_len - 2) * 3);
         for i in 1..(points_len - 1) {
@@ -218,19 +419,30 @@ impl BatchDraw2d {
     }
 
     pub fn draw_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.draw_rect_gradient(x, y, width, height, [color; 4]);
+    }
+
+    /// Draws a rectangle whose colors are interpolated between the corners.
+    /// The colors are in this order: bottom left, bottom right, top right, top left.
+    pub fn draw_rect_gradient(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        colors: [[f32; 4]; 4],
+    ) {
+        let corners = self
+            .affine_transform
+            .apply_quad(&make_rect(x, y, width, height));
+        if self.is_culled(&[corners.p1, corners.p2, corners.p3, corners.p4]) {
+            return;
+        }
         let p = self.affine_transform.apply(&Vec2::new(x, y));
         let q = self
             .affine_transform
             .apply(&Vec2::new(x + width, y + height));
-
-        #[rustfmt::skip]
-        let vertices: [f32; 4 * 6] = [
-            // positions       // colors
-            p.x(), p.y(), color[0], color[1], color[2], color[3], // bottom left
-            q.x(), p.y(), color[0], color[1], color[2], color[3], // bottom right
-            q.x(), q.y(), color[0], color[1], color[2], color[3], // top right
-            p.x(), q.y(), color[0], color[1], color[2], color[3], // top left
-        ];
+        let vertices = rect_vertices(p, q, colors);
 
         self.add_to_batch_by_trying_to_merge(
             &vertices,
@@ -246,36 +458,54 @@ impl BatchDraw2d {
     }
 
     pub fn draw_ellipse(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
-        let circle_segment_count: usize = if (width.abs() + height.abs()) < 0.05 {
-            32
-        } else {
-            64
-        };
+        self.draw_ellipse_gradient(x, y, width, height, color, color);
+    }
 
-        let mut vertices: Vec<f32> = Vec::with_capacity((circle_segment_count + 1) * (2 + 4));
-        let mut indices: Vec<u32> = Vec::with_capacity(circle_segment_count * 3);
+    /// Draws a circle whose color goes from `inner_color` at the center to `outer_color` on the edge.
+    #[inline]
+    pub fn draw_circle_gradient(
+        &mut self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        inner_color: [f32; 4],
+        outer_color: [f32; 4],
+    ) {
+        self.draw_ellipse_gradient(
+            x,
+            y,
+            radius / self.aspect_ratio,
+            radius,
+            inner_color,
+            outer_color,
+        );
+    }
 
-        // Center vertex
-        let p = self.affine_transform.apply(&Vec2::new(x, y));
-        vertices.push(p.x());
-        vertices.push(p.y());
-        vertices.extend_from_slice(&color);
-
-        for i in 0..=circle_segment_count {
-            let theta = (i as f32 / circle_segment_count as f32) * std::f32::consts::TAU;
-            let vx = x + (width * theta.cos());
-            let vy = y + height * theta.sin();
-            let p = self.affine_transform.apply(&Vec2::new(vx, vy));
-            vertices.push(p.x());
-            vertices.push(p.y());
-            vertices.extend_from_slice(&color);
-
-            if i < circle_segment_count {
-                indices.push(0);
-                indices.push(i as u32 + 1);
-                indices.push(i as u32 + 2);
-            }
+    pub fn draw_ellipse_gradient(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        inner_color: [f32; 4],
+        outer_color: [f32; 4],
+    ) {
+        let corners = self.affine_transform.apply_quad(&make_rect(
+            x - width,
+            y - height,
+            width * 2.0,
+            height * 2.0,
+        ));
+        if self.is_culled(&[corners.p1, corners.p2, corners.p3, corners.p4]) {
+            return;
         }
+        let (vertices, indices) = ellipse_vertices(
+            &self.affine_transform,
+            Vec2::new(x, y),
+            Vec2::new(width, height),
+            inner_color,
+            outer_color,
+        );
 
         self.add_to_batch_by_trying_to_merge(
             &vertices,
@@ -303,10 +533,36 @@ impl BatchDraw2d {
         self.draw_image_part(q, texture, uv_pos, uv_size, color);
     }
 
+    /// Draws the image with the given size centered on the origin of the transform.
+    pub fn draw_image_transformed(
+        &mut self,
+        transform: &Transform2,
+        width: f32,
+        height: f32,
+        texture: &Arc<Texture>,
+        color: [f32; 4],
+    ) {
+        let rect = make_rect(-width / 2.0, -height / 2.0, width, height);
+        let quad = Quad {
+            p1: transform.apply(rect.p1),
+            p2: transform.apply(rect.p2),
+            p3: transform.apply(rect.p3),
+            p4: transform.apply(rect.p4),
+        };
+        self.draw_image_part(
+            quad,
+            texture,
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            color,
+        );
+    }
+
     #[rustfmt::skip]
     pub fn draw_image_part(
         &mut self, pos_size: Quad, texture: &Arc<Texture>, uv_pos: Vec2, uv_size: Vec2, color: [f32; 4]
     ) {
+        let (uv_pos, uv_size) = uv_in_atlas(texture, uv_pos, uv_size);
         let uv_x1 = uv_pos.x();
         let uv_y1 = uv_pos.y();
         let uv_x2 = uv_pos.x() + uv_size.x();
@@ -316,6 +572,10 @@ impl BatchDraw2d {
         let p2 = self.affine_transform.apply(&pos_size.p2);
         let p3 = self.affine_transform.apply(&pos_size.p3);
         let p4 = self.affine_transform.apply(&pos_size.p4);
+        if self.is_culled(&[p1, p2, p3, p4]) {
+            return;
+        }
+        let [p1, p2, p3, p4] = self.snap_to_pixels([p1, p2, p3, p4]);
 
         #[rustfmt::skip]
         let vertices: [f32; 4 * 4] = [
@@ -343,19 +603,30 @@ impl BatchDraw2d {
         uv_pos_size: &[(Vec2, Vec2)],
         color: [f32; 4],
     ) {
+        let cull_rect = self.cull_rect;
+        let mut culled_count = 0;
         let vertices: Box<[f32]> = quads
             .iter()
             .zip(uv_pos_size)
-            .flat_map(|(pos_size, (uv_pos, uv_size))| {
-                let uv_x1 = uv_pos.x();
-                let uv_y1 = uv_pos.y();
-                let uv_x2 = uv_pos.x() + uv_size.x();
-                let uv_y2 = uv_pos.y() + uv_size.y();
-
+            .filter_map(|(pos_size, uv)| {
                 let p1 = self.affine_transform.apply(&pos_size.p1);
                 let p2 = self.affine_transform.apply(&pos_size.p2);
                 let p3 = self.affine_transform.apply(&pos_size.p3);
                 let p4 = self.affine_transform.apply(&pos_size.p4);
+                if let Some(cull_rect) = cull_rect
+                    && !do_rects_overlap(cull_rect, bounding_box(&[p1, p2, p3, p4]))
+                {
+                    culled_count += 1;
+                    return None;
+                }
+                Some((self.snap_to_pixels([p1, p2, p3, p4]), uv))
+            })
+            .flat_map(|([p1, p2, p3, p4], (uv_pos, uv_size))| {
+                let (uv_pos, uv_size) = uv_in_atlas(texture, *uv_pos, *uv_size);
+                let uv_x1 = uv_pos.x();
+                let uv_y1 = uv_pos.y();
+                let uv_x2 = uv_pos.x() + uv_size.x();
+                let uv_y2 = uv_pos.y() + uv_size.y();
 
                 #[rustfmt::skip]
                 let vertices = [
@@ -368,6 +639,7 @@ impl BatchDraw2d {
                 vertices
             })
             .collect::<Box<[f32]>>();
+        self.culled_draw_counter += culled_count;
         let mut uniforms = Uniforms::new();
 
         uniforms.add("tex", UniformValue::Sampler2D(texture.id()));
@@ -411,6 +683,9 @@ impl BatchDraw2d {
         &mut self, pos_size: Quad, canvas: &Framebuffer, uv_pos: Vec2, uv_size: Vec2,
         custom_shader: Option<ResourceId>, env: &IoEnvState
     ) {
+        // The multisampled pixels need to be copied to the texture before it is sampled.
+        canvas.resolve();
+
         let uv_x1 = uv_pos.x();
         let uv_y1 = uv_pos.y();
         let uv_x2 = uv_pos.x() + uv_size.x();
@@ -420,6 +695,9 @@ impl BatchDraw2d {
         let p2 = self.affine_transform.apply(&pos_size.p2);
         let p3 = self.affine_transform.apply(&pos_size.p3);
         let p4 = self.affine_transform.apply(&pos_size.p4);
+        if self.is_culled(&[p1, p2, p3, p4]) {
+            return;
+        }
 
         // Weird that we need to flip the y coordinates in canvas, but not image.
         #[rustfmt::skip]
@@ -457,64 +735,143 @@ impl BatchDraw2d {
         font_resource: &FontRenderingData,
     ) {
         let scale = font_size.abs() / font_resource.font_size;
-        let mut vertices = Vec::<f32>::new();
-        let mut indices = Vec::<u32>::new();
-        let mut x_pos = 0.0;
-        let mut y_pos = 0.0;
-
-        for c in text.chars() {
-            if let Some(char_info) = font_resource.font_cache.get(&c) {
-                let bounds = char_info.metrics.bounds.scale(scale);
-                let x0 = x + (x_pos + bounds.xmin) / self.aspect_ratio;
-                let y0 = y + y_pos + bounds.ymin;
-                let x1 = x0 + bounds.width / self.aspect_ratio;
-                let y1 = y0 + bounds.height;
-
-                x_pos += char_info.metrics.advance_width * scale;
-                y_pos += char_info.metrics.advance_height * scale;
-
-                // Use the stored atlas coordinates instead of calculating from metrics
-                let s0 = char_info.atlas_x;
-                let t0 = char_info.atlas_y;
-                let s1 = char_info.atlas_x + char_info.atlas_width;
-                let t1 = char_info.atlas_y + char_info.atlas_height + 0.04;
-
-                let p1 = self.affine_transform.apply(&Vec2::new(x0, y0));
-                let p2 = self.affine_transform.apply(&Vec2::new(x1, y0));
-                let p3 = self.affine_transform.apply(&Vec2::new(x1, y1));
-                let p4 = self.affine_transform.apply(&Vec2::new(x0, y1));
-
-                #[rustfmt::skip]
-                let s = &[
-                    // positions       // tex coords
-                    p1.x(), p1.y(), s0, t1, // bottom left
-                    p2.x(), p2.y(), s1, t1, // bottom right
-                    p3.x(), p3.y(), s1, t0, // top right
-                    p4.x(), p4.y(), s0, t0, // top left
-                ];
+        let (vertices, indices) = text_vertices(
+            &self.affine_transform,
+            &font_resource.font_cache,
+            x,
+            y,
+            text,
+            scale,
+            self.aspect_ratio,
+        );
 
-                vertices.extend_from_slice(s);
+        let mut uniforms = Uniforms::new();
+        uniforms.add(
+            "tex",
+            UniformValue::Sampler2D(font_resource.font_atlas.id()),
+        );
+        uniforms.add("text_color", UniformValue::Vec4(color));
+        self.add_to_batch_by_trying_to_merge(&vertices, &indices, uniforms, BatchShader::Font);
+    }
 
-                let base_index = (vertices.len() / 4 - 4) as u32; // Each vertex has 4 components
+    /// Draws the characters placed by `FontRenderingData::layout_text`, with the first baseline starting at `x, y`. The
+    /// characters of the same color are drawn together.
+    pub fn draw_text_layout(
+        &mut self,
+        x: f32,
+        y: f32,
+        layout: &TextLayout,
+        font_size: f32,
+        font_resource: &FontRenderingData,
+    ) {
+        let scale = font_size.abs() / font_resource.font_size;
+        for run in layout
+            .glyphs
+            .chunk_by(|glyph, next_glyph| glyph.color == next_glyph.color)
+        {
+            let mut vertices = Vec::with_capacity(run.len() * 16);
+            let mut indices = Vec::with_capacity(run.len() * 6);
+            for glyph in run {
+                let Some(char_info) = font_resource.font_cache.get(&glyph.character) else {
+                    continue;
+                };
+                push_glyph_vertices(
+                    &mut vertices,
+                    &mut indices,
+                    &self.affine_transform,
+                    char_info,
+                    x + glyph.x,
+                    y + glyph.y,
+                    scale,
+                    self.aspect_ratio,
+                );
+            }
+            if vertices.is_empty() {
+                continue;
+            }
+            let mut uniforms = Uniforms::new();
+            uniforms.add(
+                "tex",
+                UniformValue::Sampler2D(font_resource.font_atlas.id()),
+            );
+            uniforms.add("text_color", UniformValue::Vec4(run[0].color));
+            self.add_to_batch_by_trying_to_merge(&vertices, &indices, uniforms, BatchShader::Font);
+        }
+    }
 
-                indices.extend_from_slice(&[
-                    base_index,
-                    base_index + 1,
-                    base_index + 2, // first triangle
-                    base_index + 2,
-                    base_index + 3,
-                    base_index, // second triangle
-                ]);
+    /// Draws the text on one line with one instance per glyph: the glyphs are placed by the GPU from the glyph table of
+    /// the font. Consecutive calls with the same font and transform are drawn together, whatever their color.
+    /// Custom shaders and culling do not apply.
+    pub fn draw_text_instanced(
+        &mut self,
+        x: f32,
+        y: f32,
+        text: &str,
+        color: [f32; 4],
+        font_size: f32,
+        font_resource: &mut FontRenderingData,
+    ) {
+        if self.instanced_text_program.is_none() {
+            if !self.has_reported_instancing_fallback {
+                self.has_reported_instancing_fallback = true;
+                print_info(
+                    "This graphics driver cannot read textures in vertex shaders, Text.drawFast uses the regular text rendering."
+                        .to_string(),
+                );
             }
+            self.draw_text(x, y, text, color, font_size, font_resource);
+            return;
         }
 
+        let scale = font_size.abs() / font_resource.font_size;
+        let atlas = font_resource.font_atlas.id();
+        let glyph_table = font_resource.glyph_table(self.drawing_target.gl());
+        let mut instances = Vec::with_capacity(text.len() * GLYPH_INSTANCE_SIZE);
+        push_text_instances(
+            &mut instances,
+            &glyph_table.glyphs,
+            x,
+            y,
+            text,
+            scale,
+            self.aspect_ratio,
+            color,
+        );
+        if instances.is_empty() {
+            return;
+        }
+        self.instanced_glyph_counter += instances.len() / GLYPH_INSTANCE_SIZE;
+
         let mut uniforms = Uniforms::new();
+        uniforms.add("tex", UniformValue::Sampler2D(atlas));
         uniforms.add(
-            "tex",
-            UniformValue::Sampler2D(font_resource.font_atlas.id()),
+            "glyph_table",
+            UniformValue::Sampler2D(glyph_table.texture.id()),
         );
-        uniforms.add("text_color", UniformValue::Vec4(color));
-        self.add_to_batch_by_trying_to_merge(&vertices, &indices, uniforms, BatchShader::Font);
+        uniforms.add(
+            "transform",
+            UniformValue::Mat3(self.affine_transform.to_mat3()),
+        );
+        uniforms.add("aspect_ratio", UniformValue::Float(self.aspect_ratio));
+
+        if let Some((last_vertex_buffer, last_uniforms, BatchShader::InstancedText)) =
+            self.vertex_data.last_mut()
+            && last_uniforms.similar(&uniforms)
+        {
+            last_vertex_buffer.append_from(&instances, &[]);
+            return;
+        }
+        let layout = self
+            .instanced_text_program
+            .as_ref()
+            .expect("The instanced text program exists")
+            .vertex_layout
+            .clone();
+        self.vertex_data.push((
+            SharedGPUCPUBuffer::from_data(layout, &instances, &[]),
+            uniforms,
+            BatchShader::InstancedText,
+        ));
     }
 
     pub fn flush(&mut self) {
@@ -527,11 +884,252 @@ impl BatchDraw2d {
     }
 }
 
+/// Maps texture coordinates of a texture to the GPU texture it is drawn from, for the images packed in an atlas.
+fn uv_in_atlas(texture: &Texture, uv_pos: Vec2, uv_size: Vec2) -> (Vec2, Vec2) {
+    let [x, y, width, height] = texture.uv_rect();
+    (
+        Vec2::new(x + uv_pos.x() * width, y + uv_pos.y() * height),
+        Vec2::new(uv_size.x() * width, uv_size.y() * height),
+    )
+}
+
+/// Moves the corners, in OpenGL coordinates, so that the first one is on a whole pixel of the resolution.
+/// The whole quad is moved, so its size in pixels is kept.
+fn snap_quad(points: [Vec2; 4], resolution: [u32; 2]) -> [Vec2; 4] {
+    let snap = |coordinate: f32, pixels: u32| {
+        let half = pixels as f32 / 2.0;
+        ((coordinate + 1.0) * half).round() / half - 1.0
+    };
+    let corner = points[0];
+    let offset = Vec2::new(
+        snap(corner.x(), resolution[0]) - corner.x(),
+        snap(corner.y(), resolution[1]) - corner.y(),
+    );
+    points.map(|point| point + offset)
+}
+
 const INDICES_FOR_QUAD: [u32; 6] = [
     0, 1, 2, // first triangle
     2, 3, 0, // second triangle
 ];
 
+/// Everything we know about a batch entry, to find the Lua call that produced an OpenGL error.
+fn describe_batch_entry(
+    resources: &ResourceManager,
+    vertex: &SharedGPUCPUBuffer,
+    uniforms: &Uniforms,
+    shader: BatchShader,
+) -> String {
+    let shader_description = match shader {
+        BatchShader::Custom(id) => format!(
+            "custom shader {}",
+            resources.get_holder_by_id(id).get_path().display()
+        ),
+        shader => format!("{:?} shader", shader),
+    };
+    let vertex_count = vertex
+        .gpu_buffer()
+        .map(|buffer| buffer.buffer_row_count)
+        .unwrap_or_default();
+    let uniform_names = uniforms
+        .data
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut description = format!(
+        "drawing {} vertices with the {} (uniforms: {})",
+        vertex_count, shader_description, uniform_names
+    );
+    for (name, value) in &uniforms.data {
+        let UniformValue::Sampler2D(texture) = value else {
+            continue;
+        };
+        let image_path = resources.iter().find_map(|holder| {
+            let image = holder.get_underlying_resource::<ImageResource>().ok()?;
+            let image_texture = image.texture.borrow();
+            (image_texture.as_ref()?.id() == *texture).then(|| holder.get_path().to_path_buf())
+        });
+        if let Some(image_path) = image_path {
+            description.push_str(&format!(", {} is {}", name, image_path.display()));
+        }
+    }
+    description
+}
+
+/// The uniforms of a draw call made with a custom shader: the ones of the built-in shader and the ones set from Lua.
+/// The uniforms set from Lua win, so that a shader can override `tint_color` for example.
+fn add_custom_uniforms(mut uniforms: Uniforms, custom_uniforms: &Uniforms) -> Uniforms {
+    for (name, value) in &custom_uniforms.data {
+        uniforms.set(name, value.clone());
+    }
+    uniforms
+}
+
+/// Vertices of a rectangle for the color shader. `p` is the bottom left corner and `q` the top right one.
+/// The colors are in this order: bottom left, bottom right, top right, top left.
+fn rect_vertices(p: Vec2, q: Vec2, colors: [[f32; 4]; 4]) -> [f32; 4 * 6] {
+    let [bl, br, tr, tl] = colors;
+    #[rustfmt::skip]
+    let vertices = [
+        // positions       // colors
+        p.x(), p.y(), bl[0], bl[1], bl[2], bl[3], // bottom left
+        q.x(), p.y(), br[0], br[1], br[2], br[3], // bottom right
+        q.x(), q.y(), tr[0], tr[1], tr[2], tr[3], // top right
+        p.x(), q.y(), tl[0], tl[1], tl[2], tl[3], // top left
+    ];
+    vertices
+}
+
+/// Vertices and indices of the glyphs of a line of text for the font shader, placed on the CPU.
+/// `scale` is the font size divided by the size of the font in the atlas.
+pub fn text_vertices(
+    transform: &AffineTransform,
+    font_cache: &HashMap<char, CharacterInfo>,
+    x: f32,
+    y: f32,
+    text: &str,
+    scale: f32,
+    aspect_ratio: f32,
+) -> (Vec<f32>, Vec<u32>) {
+    let mut vertices = Vec::<f32>::new();
+    let mut indices = Vec::<u32>::new();
+    let mut x_pos = 0.0;
+    let mut y_pos = 0.0;
+
+    for c in text.chars() {
+        if let Some(char_info) = font_cache.get(&c) {
+            push_glyph_vertices(
+                &mut vertices,
+                &mut indices,
+                transform,
+                char_info,
+                x + x_pos / aspect_ratio,
+                y + y_pos,
+                scale,
+                aspect_ratio,
+            );
+            x_pos += char_info.metrics.advance_width * scale;
+            y_pos += char_info.metrics.advance_height * scale;
+        }
+    }
+    (vertices, indices)
+}
+
+/// Adds the quad of a character whose origin is at `x, y`.
+#[allow(clippy::too_many_arguments)]
+fn push_glyph_vertices(
+    vertices: &mut Vec<f32>,
+    indices: &mut Vec<u32>,
+    transform: &AffineTransform,
+    char_info: &CharacterInfo,
+    x: f32,
+    y: f32,
+    scale: f32,
+    aspect_ratio: f32,
+) {
+    let bounds = char_info.metrics.bounds.scale(scale);
+    let x0 = x + bounds.xmin / aspect_ratio;
+    let y0 = y + bounds.ymin;
+    let x1 = x0 + bounds.width / aspect_ratio;
+    let y1 = y0 + bounds.height;
+
+    // Use the stored atlas coordinates instead of calculating from metrics
+    let s0 = char_info.atlas_x;
+    let t0 = char_info.atlas_y;
+    let s1 = char_info.atlas_x + char_info.atlas_width;
+    let t1 = char_info.atlas_y + char_info.atlas_height + 0.04;
+
+    let p1 = transform.apply(&Vec2::new(x0, y0));
+    let p2 = transform.apply(&Vec2::new(x1, y0));
+    let p3 = transform.apply(&Vec2::new(x1, y1));
+    let p4 = transform.apply(&Vec2::new(x0, y1));
+
+    #[rustfmt::skip]
+    let s = &[
+        // positions       // tex coords
+        p1.x(), p1.y(), s0, t1, // bottom left
+        p2.x(), p2.y(), s1, t1, // bottom right
+        p3.x(), p3.y(), s1, t0, // top right
+        p4.x(), p4.y(), s0, t0, // top left
+    ];
+
+    vertices.extend_from_slice(s);
+
+    let base_index = (vertices.len() / 4 - 4) as u32; // Each vertex has 4 components
+
+    indices.extend_from_slice(&[
+        base_index,
+        base_index + 1,
+        base_index + 2, // first triangle
+        base_index + 2,
+        base_index + 3,
+        base_index, // second triangle
+    ]);
+}
+
+/// Vertices and indices of a triangle fan covering an ellipse for the color shader.
+/// The first vertex is the center, the others are on the edge.
+fn ellipse_vertices(
+    transform: &AffineTransform,
+    center: Vec2,
+    size: Vec2,
+    inner_color: [f32; 4],
+    outer_color: [f32; 4],
+) -> (Vec<f32>, Vec<u32>) {
+    let circle_segment_count: usize = if (size.x().abs() + size.y().abs()) < 0.05 {
+        32
+    } else {
+        64
+    };
+
+    let mut vertices: Vec<f32> = Vec::with_capacity((circle_segment_count + 2) * (2 + 4));
+    let mut indices: Vec<u32> = Vec::with_capacity(circle_segment_count * 3);
+
+    // Center vertex
+    let p = transform.apply(&center);
+    vertices.push(p.x());
+    vertices.push(p.y());
+    vertices.extend_from_slice(&inner_color);
+
+    for i in 0..=circle_segment_count {
+        let theta = (i as f32 / circle_segment_count as f32) * std::f32::consts::TAU;
+        let vx = center.x() + size.x() * theta.cos();
+        let vy = center.y() + size.y() * theta.sin();
+        let p = transform.apply(&Vec2::new(vx, vy));
+        vertices.push(p.x());
+        vertices.push(p.y());
+        vertices.extend_from_slice(&outer_color);
+
+        if i < circle_segment_count {
+            indices.push(0);
+            indices.push(i as u32 + 1);
+            indices.push(i as u32 + 2);
+        }
+    }
+    (vertices, indices)
+}
+
+/// The smallest axis aligned rectangle containing the points, as (min, max).
+fn bounding_box(points: &[Vec2]) -> (Vec2, Vec2) {
+    let mut min = Vec2::new(f32::INFINITY, f32::INFINITY);
+    let mut max = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for p in points {
+        min = min.min(*p);
+        max = max.max(*p);
+    }
+    (min, max)
+}
+
+/// Rectangles touching on an edge overlap, so that culling stays conservative.
+fn do_rects_overlap(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> bool {
+    let ((a_min, a_max), (b_min, b_max)) = (a, b);
+    a_min.x() <= b_max.x()
+        && b_min.x() <= a_max.x()
+        && a_min.y() <= b_max.y()
+        && b_min.y() <= a_max.y()
+}
+
 pub fn make_rect(x: f32, y: f32, width: f32, height: f32) -> Quad {
     let x_μ = f32::min(x, x + width);
     let x_ω = f32::max(x, x + width);
@@ -545,3 +1143,114 @@ pub fn make_rect(x: f32, y: f32, width: f32, height: f32) -> Quad {
         p4: Vec2::new(x_μ, y_ω),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_custom_uniforms, bounding_box, do_rects_overlap, ellipse_vertices, rect_vertices,
+        snap_quad,
+    };
+    use crate::{
+        graphics::{
+            affinetransform::AffineTransform,
+            gluniforms::{UniformValue, Uniforms},
+        },
+        lua_env::lua_vec2::Vec2,
+    };
+
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+    const GREEN: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+    const BLUE: [f32; 4] = [0.0, 0.0, 1.0, 0.5];
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 0.0];
+
+    #[test]
+    fn rect_gradient_corners_have_the_input_colors() {
+        let vertices = rect_vertices(
+            Vec2::new(-0.5, -0.5),
+            Vec2::new(0.5, 0.5),
+            [RED, GREEN, BLUE, WHITE],
+        );
+        let corners = vertices.chunks_exact(6).collect::<Vec<_>>();
+        assert_eq!(corners[0], [-0.5, -0.5, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(corners[1], [0.5, -0.5, 0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(corners[2], [0.5, 0.5, 0.0, 0.0, 1.0, 0.5]);
+        assert_eq!(corners[3], [-0.5, 0.5, 1.0, 1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn circle_gradient_has_inner_color_at_center_and_outer_color_on_edge() {
+        let (vertices, indices) = ellipse_vertices(
+            &AffineTransform::identity(),
+            Vec2::new(0.25, 0.0),
+            Vec2::new(0.5, 0.5),
+            RED,
+            BLUE,
+        );
+        let mut fan = vertices.chunks_exact(6);
+        assert_eq!(fan.next(), Some([0.25, 0.0, 1.0, 0.0, 0.0, 1.0].as_slice()));
+        for vertex in fan {
+            assert_eq!(vertex[2..], BLUE);
+        }
+        assert!(indices.chunks_exact(3).all(|triangle| triangle[0] == 0));
+    }
+
+    #[test]
+    fn draws_with_the_same_custom_uniforms_can_be_merged() {
+        let dissolve = |threshold: f32| {
+            let mut uniforms = Uniforms::new();
+            uniforms.add("threshold", UniformValue::Float(threshold));
+            uniforms
+        };
+        let image_uniforms = || {
+            let mut uniforms = Uniforms::new();
+            uniforms.add("tint_color", UniformValue::Vec4(WHITE));
+            uniforms
+        };
+
+        let first = add_custom_uniforms(image_uniforms(), &dissolve(0.5));
+        let second = add_custom_uniforms(image_uniforms(), &dissolve(0.5));
+        let third = add_custom_uniforms(image_uniforms(), &dissolve(0.6));
+        assert!(first.similar(&second));
+        assert!(!second.similar(&third));
+
+        let mut tint = Uniforms::new();
+        tint.add("tint_color", UniformValue::Vec4(RED));
+        let tinted = add_custom_uniforms(image_uniforms(), &tint);
+        assert_eq!(tinted.data.len(), 1);
+        assert_eq!(tinted.get("tint_color"), Some(&UniformValue::Vec4(RED)));
+    }
+
+    #[test]
+    fn snapped_images_keep_their_size_and_start_on_a_pixel() {
+        // 4 pixels wide, so a pixel is 0.5 wide in OpenGL coordinates.
+        let quad = [
+            Vec2::new(-0.6, 0.1),
+            Vec2::new(0.4, 0.1),
+            Vec2::new(0.4, 0.35),
+            Vec2::new(-0.6, 0.35),
+        ];
+        let snapped = snap_quad(quad, [4, 8]);
+        assert!((snapped[0].x() - -0.5).abs() < 1e-6);
+        assert!((snapped[0].y() - 0.0).abs() < 1e-6);
+        assert!((snapped[2].x() - snapped[0].x() - 1.0).abs() < 1e-6);
+        assert!((snapped[2].y() - snapped[0].y() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn culling_uses_the_bounding_box_of_rotated_shapes() {
+        let screen = (Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0));
+        // A square rotated by 45 degrees, its left corner is on the right edge of the screen.
+        let rotated_square = [
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.3, -0.3),
+            Vec2::new(1.6, 0.0),
+            Vec2::new(1.3, 0.3),
+        ];
+        let bounds = bounding_box(&rotated_square);
+        assert_eq!(bounds, (Vec2::new(1.0, -0.3), Vec2::new(1.6, 0.3)));
+        assert!(do_rects_overlap(screen, bounds));
+
+        let off_screen_square = rotated_square.map(|p| Vec2::new(p.x() + 0.01, p.y()));
+        assert!(!do_rects_overlap(screen, bounding_box(&off_screen_square)));
+    }
+}
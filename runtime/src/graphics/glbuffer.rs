@@ -3,7 +3,9 @@ use std::{hint::unreachable_unchecked, sync::Arc};
 use vectarine_plugin_sdk::glow;
 use vectarine_plugin_sdk::glow::HasContext;
 
+use crate::graphics::glerror::check_gl_error;
 use crate::graphics::gltypes::DataLayout;
+use crate::metrics::{MemoryCategory, MemoryGuard};
 
 /// Represents a buffer stored in a GPU that can be drawn.
 /// We don"t store the associate CPU data here.
@@ -16,6 +18,7 @@ pub struct GpuVertexData {
     pub drawn_point_count: usize,
     pub buffer_row_count: usize,
     gl: Arc<glow::Context>,
+    memory: MemoryGuard,
 }
 
 /// Give a hint to the driver on how you intent to use the data.
@@ -50,6 +53,7 @@ impl GpuVertexData {
             drawn_point_count: 0,
             buffer_row_count: 0,
             gl: gl.clone(),
+            memory: MemoryGuard::new(MemoryCategory::VertexBuffers, 0),
         }
     }
 
@@ -90,6 +94,8 @@ impl GpuVertexData {
         let stride = self.layout.stride();
         let point_count = vertex_data_byte_count / stride;
         self.buffer_row_count = point_count;
+        self.memory
+            .set_bytes(vertex_data_byte_count + std::mem::size_of_val(index_data));
 
         unsafe {
             let gl = self.gl.as_ref();
@@ -112,6 +118,13 @@ impl GpuVertexData {
             gl.bind_buffer(glow::ARRAY_BUFFER, None);
             gl.bind_buffer(glow::ELEMENT_ARRAY_BUFFER, None);
         }
+        check_gl_error(&self.gl, || {
+            format!(
+                "uploading a vertex buffer with {} vertices and {} indices",
+                point_count,
+                index_data.len()
+            )
+        });
         Ok(())
     }
 
@@ -50,6 +50,30 @@ impl DrawingTarget {
         }
     }
 
+    /// Draws a quad for each row of the buffer. The rows are instance attributes, so the vertex shader builds the
+    /// corners of the quad from `gl_VertexID`. The index buffer is not used.
+    pub fn draw_instanced(
+        &self,
+        vertex_buffer: &GpuVertexData,
+        program: &GLProgram,
+        uniforms: &Uniforms,
+    ) {
+        let gl = self.gl.as_ref();
+        program.use_program();
+        program.set_uniforms(uniforms);
+        vertex_buffer.bind_for_drawing();
+
+        *self.draw_call_counter.borrow_mut() += 1;
+        let instances = vertex_buffer.buffer_row_count as i32;
+        unsafe {
+            // The divisor is stored in the VAO, which is created when the batch is sent to the GPU.
+            for i in 0..vertex_buffer.layout.fields.len() {
+                gl.vertex_attrib_divisor(i as u32, 1);
+            }
+            gl.draw_arrays_instanced(glow::TRIANGLES, 0, 6, instances);
+        }
+    }
+
     pub fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
         let gl = self.gl.as_ref();
         unsafe {
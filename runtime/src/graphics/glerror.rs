@@ -0,0 +1,93 @@
+//! OpenGL errors are silent: the only symptom is something not being drawn.
+//! These checks call glGetError after the operations that can fail and log what was being done.
+//!
+//! glGetError makes the CPU wait for the GPU, so the checks only exist with the `gl-error-checks` feature, which is
+//! enabled by the editor and the debug tasks of the runtime but never by exported games.
+//! They are enabled by default in debug builds. In release builds of the editor, they can be enabled in the preferences
+//! or by setting the `VECTARINE_GL_ERRORS` environment variable.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use vectarine_plugin_sdk::glow;
+
+static IS_GL_ERROR_CHECKING_ENABLED: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+/// glGetError clears the error, so errors found by the checks are kept for the context loss detection of the editor.
+static LAST_CHECKED_ERROR: AtomicU32 = AtomicU32::new(glow::NO_ERROR);
+
+pub const GL_ERROR_ENV_VAR: &str = "VECTARINE_GL_ERRORS";
+
+pub fn set_gl_error_checking(enabled: bool) {
+    IS_GL_ERROR_CHECKING_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_gl_error_checking_enabled() -> bool {
+    cfg!(feature = "gl-error-checks") && IS_GL_ERROR_CHECKING_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Returns the last error found by `check_gl_error` since the previous call, or `NO_ERROR`.
+pub fn take_last_checked_gl_error() -> u32 {
+    LAST_CHECKED_ERROR.swap(glow::NO_ERROR, Ordering::Relaxed)
+}
+
+/// Logs the pending OpenGL errors. `describe_operation` is only called when there is an error.
+#[inline]
+#[allow(unused_variables)]
+pub fn check_gl_error(gl: &glow::Context, describe_operation: impl FnOnce() -> String) {
+    #[cfg(feature = "gl-error-checks")]
+    {
+        use crate::console::print_err;
+        use vectarine_plugin_sdk::glow::HasContext;
+
+        if !IS_GL_ERROR_CHECKING_ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut errors = Vec::new();
+        // Several error flags can be set at the same time. A lost context keeps returning errors, hence the limit.
+        for _ in 0..8 {
+            let error = unsafe { gl.get_error() };
+            if error == glow::NO_ERROR {
+                break;
+            }
+            errors.push(error);
+        }
+        let Some(&first_error) = errors.first() else {
+            return;
+        };
+        let is_context_lost = errors.contains(&glow::CONTEXT_LOST);
+        LAST_CHECKED_ERROR.store(
+            if is_context_lost {
+                glow::CONTEXT_LOST
+            } else {
+                first_error
+            },
+            Ordering::Relaxed,
+        );
+        if is_context_lost {
+            // Everything fails after that, the editor will recreate the context.
+            return;
+        }
+        let names = errors
+            .iter()
+            .map(|error| gl_error_name(*error))
+            .collect::<Vec<_>>()
+            .join(", ");
+        print_err(format!(
+            "OpenGL error ({}) while {}",
+            names,
+            describe_operation()
+        ));
+    }
+}
+
+pub fn gl_error_name(error: u32) -> &'static str {
+    match error {
+        glow::INVALID_ENUM => "INVALID_ENUM",
+        glow::INVALID_VALUE => "INVALID_VALUE",
+        glow::INVALID_OPERATION => "INVALID_OPERATION",
+        glow::INVALID_FRAMEBUFFER_OPERATION => "INVALID_FRAMEBUFFER_OPERATION",
+        glow::OUT_OF_MEMORY => "OUT_OF_MEMORY",
+        glow::CONTEXT_LOST => "CONTEXT_LOST",
+        _ => "UNKNOWN_ERROR",
+    }
+}
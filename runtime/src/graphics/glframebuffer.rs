@@ -1,9 +1,11 @@
-use std::sync::Arc;
+use std::{cell::Cell, sync::Arc};
 
 use vectarine_plugin_sdk::glow;
 use vectarine_plugin_sdk::glow::HasContext;
 
+use crate::console::{print_info, print_warn};
 use crate::graphics::gltexture::ImageAntialiasing;
+use crate::metrics::{MemoryCategory, MemoryGuard};
 
 pub struct Framebuffer {
     id: glow::Framebuffer,
@@ -13,6 +15,21 @@ pub struct Framebuffer {
     width: u32,
     height: u32,
     gl: Arc<glow::Context>,
+    _memory: MemoryGuard,
+    multisample: Option<MultisampleTarget>,
+    /// The mipmaps of the color texture are generated again after each resolve.
+    has_mipmaps: bool,
+}
+
+/// Textures cannot be multisampled in OpenGL ES 3 / WebGL 2, so multisampled canvases are drawn into renderbuffers
+/// and the result is copied (resolved) into the color texture of the framebuffer before it is used.
+struct MultisampleTarget {
+    id: glow::Framebuffer,
+    color_buffer: glow::Renderbuffer,
+    depth_stencil_buffer: glow::Renderbuffer,
+    samples: u32,
+    needs_resolve: Cell<bool>,
+    _memory: MemoryGuard,
 }
 
 impl Framebuffer {
@@ -31,7 +48,8 @@ impl Framebuffer {
             gl.tex_image_2d(
                 glow::TEXTURE_2D,
                 0,
-                glow::RGBA as i32,
+                // A sized format, because resolving a multisampled buffer requires the formats to match exactly.
+                glow::RGBA8 as i32,
                 width as i32,
                 height as i32,
                 0,
@@ -94,6 +112,99 @@ impl Framebuffer {
                 gl: gl.clone(),
                 color_tex,
                 depth_stencil_tex,
+                // 4 bytes of color and 4 bytes of depth + stencil per pixel
+                _memory: MemoryGuard::new(
+                    MemoryCategory::Textures,
+                    width as usize * height as usize * 8,
+                ),
+                multisample: None,
+                has_mipmaps: filter.has_mipmaps(),
+            }
+        }
+    }
+
+    /// Create a framebuffer whose drawings are antialiased using `samples` samples per pixel.
+    /// When multisampling is not available, this creates a regular framebuffer and prints a warning.
+    pub fn new_rgba_multisampled(
+        gl: &Arc<glow::Context>,
+        width: u32,
+        height: u32,
+        filter: ImageAntialiasing,
+        samples: u32,
+    ) -> Self {
+        let mut framebuffer = Self::new_rgba(gl, width, height, filter);
+        if samples <= 1 {
+            return framebuffer;
+        }
+        let max_samples = unsafe { gl.get_parameter_i32(glow::MAX_SAMPLES) }.max(0) as u32;
+        let samples = if samples > max_samples {
+            print_info(format!(
+                "The graphics driver supports at most {max_samples} samples per pixel, the canvas uses {max_samples} instead of {samples}."
+            ));
+            max_samples
+        } else {
+            samples
+        };
+        if samples <= 1 {
+            print_warn("Multisampling is not supported by the graphics driver, the canvas will not be antialiased.".to_string());
+            return framebuffer;
+        }
+        match MultisampleTarget::new(gl, width, height, samples) {
+            Ok(target) => framebuffer.multisample = Some(target),
+            Err(err) => print_warn(format!(
+                "Unable to create a multisampled canvas ({err}), the canvas will not be antialiased."
+            )),
+        }
+        framebuffer
+    }
+
+    /// The number of samples per pixel, 1 when the framebuffer is not multisampled.
+    pub fn sample_count(&self) -> u32 {
+        self.multisample
+            .as_ref()
+            .map(|target| target.samples)
+            .unwrap_or(1)
+    }
+
+    /// Copy what was drawn in the multisampled buffer into the color texture.
+    /// This does nothing when the framebuffer is not multisampled or when nothing was drawn since the last resolve.
+    pub fn resolve(&self) {
+        let Some(target) = &self.multisample else {
+            return;
+        };
+        if !target.needs_resolve.replace(false) {
+            return;
+        }
+        let (width, height) = (self.width as i32, self.height as i32);
+        unsafe {
+            let gl = self.gl.as_ref();
+            // We can be inside the paint function of another canvas, so the bindings are restored afterwards.
+            let previous_read = gl.get_parameter_framebuffer(glow::READ_FRAMEBUFFER_BINDING);
+            let previous_draw = gl.get_parameter_framebuffer(glow::DRAW_FRAMEBUFFER_BINDING);
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(target.id));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(self.id));
+            // WebGL 2 requires the same size for both rectangles and NEAREST filtering when resolving.
+            gl.blit_framebuffer(
+                0,
+                0,
+                width,
+                height,
+                0,
+                0,
+                width,
+                height,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, previous_read);
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, previous_draw);
+
+            // The blit only writes the base level, the smaller levels would still show the previous drawing.
+            if self.has_mipmaps {
+                let previous_texture = gl.get_parameter_texture(glow::TEXTURE_BINDING_2D);
+                gl.bind_texture(glow::TEXTURE_2D, Some(self.color_tex));
+                gl.generate_mipmap(glow::TEXTURE_2D);
+                gl.bind_texture(glow::TEXTURE_2D, previous_texture);
             }
         }
     }
@@ -109,20 +220,39 @@ impl Framebuffer {
         get_viewport(&self.gl)
     }
 
-    /// Bind the framebuffer, execute the closure, then unbind the framebuffer.
+    /// Bind the framebuffer, execute the closure, then bind the framebuffer that was bound before.
     /// The viewport is adjusted to match the framebuffer size during the execution of the closure.
     /// This means that any rendering done in the closure will be rendered to the framebuffer.
     pub fn using(&self, f: impl FnOnce()) {
         // Store current viewport
         let viewport = self.get_viewport();
+        // Calls can be nested when a canvas is painted inside the paint function of another canvas.
+        let previous_framebuffer = unsafe {
+            self.gl
+                .get_parameter_framebuffer(glow::DRAW_FRAMEBUFFER_BINDING)
+        };
         unsafe {
             let gl = self.gl.as_ref();
-            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.id));
+            let id = match &self.multisample {
+                Some(target) => {
+                    target.needs_resolve.set(true);
+                    target.id
+                }
+                None => self.id,
+            };
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(id));
             gl.viewport(0, 0, self.width as i32, self.height as i32);
         }
+        // The scissor test keeps the game out of the letterbox bars of the window, it does not apply to canvases.
+        let is_scissor_enabled = unsafe { self.gl.is_enabled(glow::SCISSOR_TEST) };
+        unsafe { self.gl.disable(glow::SCISSOR_TEST) };
         f();
         unsafe {
-            self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            if is_scissor_enabled {
+                self.gl.enable(glow::SCISSOR_TEST);
+            }
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, previous_framebuffer);
             // Restore previous viewport
             self.gl
                 .viewport(viewport.x, viewport.y, viewport.width, viewport.height);
@@ -152,6 +282,79 @@ impl Drop for Framebuffer {
             self.gl.delete_texture(self.color_tex);
             self.gl.delete_texture(self.depth_stencil_tex);
             self.gl.delete_framebuffer(self.id);
+            if let Some(target) = &self.multisample {
+                target.delete(&self.gl);
+            }
+        }
+    }
+}
+
+impl MultisampleTarget {
+    fn new(gl: &Arc<glow::Context>, width: u32, height: u32, samples: u32) -> Result<Self, String> {
+        unsafe {
+            let id = gl.create_framebuffer()?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(id));
+
+            let color_buffer = gl.create_renderbuffer()?;
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(color_buffer));
+            gl.renderbuffer_storage_multisample(
+                glow::RENDERBUFFER,
+                samples as i32,
+                glow::RGBA8,
+                width as i32,
+                height as i32,
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::RENDERBUFFER,
+                Some(color_buffer),
+            );
+
+            let depth_stencil_buffer = gl.create_renderbuffer()?;
+            gl.bind_renderbuffer(glow::RENDERBUFFER, Some(depth_stencil_buffer));
+            gl.renderbuffer_storage_multisample(
+                glow::RENDERBUFFER,
+                samples as i32,
+                glow::DEPTH24_STENCIL8,
+                width as i32,
+                height as i32,
+            );
+            gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                glow::DEPTH_STENCIL_ATTACHMENT,
+                glow::RENDERBUFFER,
+                Some(depth_stencil_buffer),
+            );
+            gl.bind_renderbuffer(glow::RENDERBUFFER, None);
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+
+            let target = Self {
+                id,
+                color_buffer,
+                depth_stencil_buffer,
+                samples,
+                needs_resolve: Cell::new(false),
+                _memory: MemoryGuard::new(
+                    MemoryCategory::Textures,
+                    width as usize * height as usize * 8 * samples as usize,
+                ),
+            };
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                target.delete(gl);
+                return Err(format!("framebuffer status {status}"));
+            }
+            Ok(target)
+        }
+    }
+
+    fn delete(&self, gl: &glow::Context) {
+        unsafe {
+            gl.delete_renderbuffer(self.color_buffer);
+            gl.delete_renderbuffer(self.depth_stencil_buffer);
+            gl.delete_framebuffer(self.id);
         }
     }
 }
@@ -101,6 +101,7 @@ impl GLProgram {
     pub fn set_uniforms(&self, uniforms: &Uniforms) -> Vec<UniformNotFoundWarning> {
         let gl = self.gl.as_ref();
         let mut warnings = Vec::new();
+        let mut texture_slot = 0;
         for (uniform_name, uniform_value) in &uniforms.data {
             unsafe {
                 let location = gl.get_uniform_location(self.program, uniform_name.as_str());
@@ -138,8 +139,11 @@ impl GLProgram {
                         gl.uniform_1_i32(Some(&location), *v as i32);
                     }
                     UniformValue::Sampler2D(tex) => {
-                        gl.active_texture(glow::TEXTURE0); // We assume the texture is bound to slot 0
+                        // Each sampler gets the next texture slot, so the first one is in slot 0.
+                        gl.active_texture(glow::TEXTURE0 + texture_slot);
                         gl.bind_texture(glow::TEXTURE_2D, Some(*tex));
+                        gl.uniform_1_i32(Some(&location), texture_slot as i32);
+                        texture_slot += 1;
                     }
                     UniformValue::SamplerCube(tex_id) => {
                         todo!("Implement cubemap texture binding. Tried to bind {tex_id}");
@@ -147,6 +151,9 @@ impl GLProgram {
                 }
             }
         }
+        if texture_slot > 1 {
+            unsafe { gl.active_texture(glow::TEXTURE0) };
+        }
         warnings
     }
 }
@@ -3,6 +3,12 @@ use std::sync::Arc;
 use vectarine_plugin_sdk::glow;
 use vectarine_plugin_sdk::glow::{HasContext, PixelUnpackData};
 
+use crate::graphics::glerror::check_gl_error;
+use crate::metrics::{MemoryCategory, MemoryGuard};
+
+/// The texture coordinates of a texture that is not part of an atlas.
+const FULL_UV_RECT: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
 /// Represents a texture on the GPU
 #[derive(Debug, Clone)]
 pub struct Texture {
@@ -10,6 +16,11 @@ pub struct Texture {
     width: u32,
     height: u32,
     gl: Arc<glow::Context>,
+    /// The atlas the texture is a region of, for the images packed at export time. The GPU texture belongs to the atlas.
+    atlas: Option<Arc<Texture>>,
+    /// The position and the size of the region in the GPU texture, in texture coordinates.
+    uv_rect: [f32; 4],
+    _memory: MemoryGuard,
 }
 
 impl Texture {
@@ -54,15 +65,79 @@ impl Texture {
                 PixelUnpackData::Slice(data),
             );
 
+            let mut byte_count = width as usize * height as usize * 4;
             if filter.has_mipmaps() {
                 glref.generate_mipmap(glow::TEXTURE_2D);
+                // The full mipmap chain adds about a third of the base level.
+                byte_count += byte_count / 3;
             }
+            check_gl_error(glref, || {
+                format!("creating a {width}x{height} RGBA texture")
+            });
 
             Arc::new(Self {
                 tex,
                 width,
                 height,
                 gl: gl.clone(),
+                atlas: None,
+                uv_rect: FULL_UV_RECT,
+                _memory: MemoryGuard::new(MemoryCategory::Textures, byte_count),
+            })
+        }
+    }
+
+    /// Create a texture of 4 floats per pixel, read with texelFetch. It is not filtered, as float textures are not
+    /// filterable in OpenGL ES 3.
+    pub fn new_float_rgba(
+        gl: &Arc<glow::Context>,
+        data: &[f32],
+        width: u32,
+        height: u32,
+    ) -> Arc<Self> {
+        assert!(data.len() as u32 == width * height * 4);
+
+        unsafe {
+            let glref = gl.as_ref();
+            let tex = glref.create_texture().expect("Cannot create texture");
+
+            glref.bind_texture(glow::TEXTURE_2D, Some(tex));
+            glref.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+            glref.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            glref.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+
+            let bytes = std::slice::from_raw_parts(data.as_ptr() as *const u8, size_of_val(data));
+            glref.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA32F as i32,
+                width as i32,
+                height as i32,
+                0,
+                glow::RGBA,
+                glow::FLOAT,
+                PixelUnpackData::Slice(Some(bytes)),
+            );
+            check_gl_error(glref, || {
+                format!("creating a {width}x{height} float texture")
+            });
+
+            Arc::new(Self {
+                tex,
+                width,
+                height,
+                gl: gl.clone(),
+                atlas: None,
+                uv_rect: FULL_UV_RECT,
+                _memory: MemoryGuard::new(MemoryCategory::Textures, bytes.len()),
             })
         }
     }
@@ -118,16 +193,83 @@ impl Texture {
                 glow::UNSIGNED_BYTE,
                 PixelUnpackData::Slice(Some(data)),
             );
+            check_gl_error(glref, || {
+                format!("creating a {width}x{height} grayscale texture")
+            });
 
             Arc::new(Self {
                 tex,
                 width,
                 height,
                 gl: gl.clone(),
+                atlas: None,
+                uv_rect: FULL_UV_RECT,
+                _memory: MemoryGuard::new(MemoryCategory::Textures, data.len()),
             })
         }
     }
 
+    /// A texture drawn from a part of an atlas. Its size is the size of the part, and the texture coordinates used to
+    /// draw it are mapped to the part by `BatchDraw2d`.
+    pub fn new_region(atlas: &Arc<Texture>, x: u32, y: u32, width: u32, height: u32) -> Arc<Self> {
+        let atlas_width = atlas.width.max(1) as f32;
+        let atlas_height = atlas.height.max(1) as f32;
+        Arc::new(Self {
+            tex: atlas.tex,
+            width,
+            height,
+            gl: atlas.gl.clone(),
+            atlas: Some(atlas.clone()),
+            uv_rect: [
+                x as f32 / atlas_width,
+                y as f32 / atlas_height,
+                width as f32 / atlas_width,
+                height as f32 / atlas_height,
+            ],
+            // The pixels are counted with the atlas.
+            _memory: MemoryGuard::new(MemoryCategory::Textures, 0),
+        })
+    }
+
+    /// Change how the texture is filtered. For a region, the whole atlas changes.
+    pub fn set_filter(&self, filter: ImageAntialiasing) {
+        unsafe {
+            let glref = self.gl.as_ref();
+            glref.bind_texture(glow::TEXTURE_2D, Some(self.tex));
+            let gl_filter = filter.to_tex_parameter();
+            glref.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, gl_filter);
+            glref.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, gl_filter);
+            if filter.has_mipmaps() {
+                glref.generate_mipmap(glow::TEXTURE_2D);
+            }
+        }
+    }
+
+    /// Replace the pixels of an RGBA texture, for textures that change every frame like the ones of videos.
+    pub fn update_rgba(&self, data: &[u8]) {
+        assert!(data.len() as u32 == self.width * self.height * 4);
+
+        unsafe {
+            let glref = self.gl.as_ref();
+            glref.bind_texture(glow::TEXTURE_2D, Some(self.tex));
+            glref.pixel_store_i32(glow::UNPACK_ALIGNMENT, 4);
+            glref.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                0,
+                0,
+                self.width as i32,
+                self.height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                PixelUnpackData::Slice(Some(data)),
+            );
+            check_gl_error(glref, || {
+                format!("updating a {}x{} RGBA texture", self.width, self.height)
+            });
+        }
+    }
+
     pub fn bind(&self, slot: u32) {
         unsafe {
             let gl = self.gl.as_ref();
@@ -147,10 +289,23 @@ impl Texture {
     pub fn id(&self) -> glow::NativeTexture {
         self.tex
     }
+
+    /// The `[x, y, width, height]` of the texture in the GPU texture, in texture coordinates.
+    pub fn uv_rect(&self) -> [f32; 4] {
+        self.uv_rect
+    }
+
+    /// The atlas the texture is a region of, if any.
+    pub fn atlas(&self) -> Option<&Arc<Texture>> {
+        self.atlas.as_ref()
+    }
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
+        if self.atlas.is_some() {
+            return;
+        }
         unsafe {
             self.gl.delete_texture(self.tex);
         }
@@ -33,7 +33,7 @@ impl PartialEq for UniformValue {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Uniforms {
     pub data: Vec<(String, UniformValue)>,
 }
@@ -0,0 +1,239 @@
+//! Exports can pack the images of a game in a few large pages, so that the sprites are drawn from the same texture and
+//! their draw calls are merged. Every packed image is replaced by a small file pointing to its region of a page, and the
+//! page is loaded instead when the image is.
+
+use image::RgbaImage;
+
+/// The first line of the files that replace the packed images.
+pub const PACKED_IMAGE_HEADER: &str = "vectarine-packed-image";
+/// The folder of the pages in the game data.
+pub const ATLAS_FOLDER: &str = "vectarine-atlas";
+/// Textures of this size are supported by every GPU able to run the engine.
+pub const MAX_PAGE_SIZE: u32 = 2048;
+/// The images are surrounded by copies of their edge pixels, so that the filtering does not read the neighbors.
+pub const PACKING_GUTTER: u32 = 2;
+
+/// Where an image is in its page, in pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedImage {
+    /// The path of the page, like the paths of the resources.
+    pub page: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PackedImage {
+    pub fn to_file_content(&self) -> String {
+        format!(
+            "{PACKED_IMAGE_HEADER}\n{}\n{} {} {} {}\n",
+            self.page, self.x, self.y, self.width, self.height
+        )
+    }
+
+    /// Returns None when the data is not the file of a packed image, like the data of a regular image.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let content = data.strip_prefix(PACKED_IMAGE_HEADER.as_bytes())?;
+        let content = std::str::from_utf8(content).ok()?;
+        let mut lines = content.trim().lines();
+        let page = lines.next()?.trim().to_string();
+        let mut numbers = lines
+            .next()?
+            .split_whitespace()
+            .map(|number| number.parse::<u32>().ok());
+        let mut next_number = || numbers.next().flatten();
+        Some(Self {
+            page,
+            x: next_number()?,
+            y: next_number()?,
+            width: next_number()?,
+            height: next_number()?,
+        })
+    }
+}
+
+/// The page and the top left corner of a rectangle placed by `pack_rectangles`, inside of its gutter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Places rectangles on pages of at most `max_size` pixels, in rows of decreasing height. Returns the placement of every
+/// rectangle, None for the ones too large for a page, and the size of every page.
+pub fn pack_rectangles(
+    sizes: &[(u32, u32)],
+    max_size: u32,
+    gutter: u32,
+) -> (Vec<Option<Placement>>, Vec<(u32, u32)>) {
+    let mut order = (0..sizes.len()).collect::<Vec<_>>();
+    order.sort_by_key(|index| {
+        let (width, height) = sizes[*index];
+        (std::cmp::Reverse(height), std::cmp::Reverse(width))
+    });
+
+    let mut placements = vec![None; sizes.len()];
+    let mut page_sizes: Vec<(u32, u32)> = Vec::new();
+    // The row being filled on the last page: its top, its height and the left of the free space.
+    let (mut row_y, mut row_height, mut row_x) = (0, 0, 0);
+    for index in order {
+        let (width, height) = sizes[index];
+        let (cell_width, cell_height) = (width + 2 * gutter, height + 2 * gutter);
+        if cell_width > max_size || cell_height > max_size {
+            continue;
+        }
+        if page_sizes.is_empty() || row_x + cell_width > max_size {
+            row_y += row_height;
+            row_height = 0;
+            row_x = 0;
+        }
+        if page_sizes.is_empty() || row_y + cell_height > max_size {
+            page_sizes.push((0, 0));
+            row_y = 0;
+            row_height = 0;
+            row_x = 0;
+        }
+        let page = page_sizes.len() - 1;
+        placements[index] = Some(Placement {
+            page,
+            x: row_x + gutter,
+            y: row_y + gutter,
+        });
+        row_x += cell_width;
+        row_height = row_height.max(cell_height);
+        let page_size = &mut page_sizes[page];
+        *page_size = (page_size.0.max(row_x), page_size.1.max(row_y + row_height));
+    }
+    (placements, page_sizes)
+}
+
+/// The pages built by `pack_images`, and the region of every image that was packed.
+pub struct PackedPages {
+    pub pages: Vec<RgbaImage>,
+    pub images: Vec<Option<PackedImage>>,
+}
+
+/// Packs the images in pages. `page_path` gives the path of a page from its index.
+pub fn pack_images(images: &[RgbaImage], page_path: impl Fn(usize) -> String) -> PackedPages {
+    let sizes = images
+        .iter()
+        .map(|image| image.dimensions())
+        .collect::<Vec<_>>();
+    let (placements, page_sizes) = pack_rectangles(&sizes, MAX_PAGE_SIZE, PACKING_GUTTER);
+    let mut pages = page_sizes
+        .iter()
+        .map(|(width, height)| RgbaImage::new(*width, *height))
+        .collect::<Vec<_>>();
+    let images = images
+        .iter()
+        .zip(placements)
+        .map(|(image, placement)| {
+            let placement = placement?;
+            copy_with_gutter(
+                &mut pages[placement.page],
+                image,
+                placement.x,
+                placement.y,
+                PACKING_GUTTER,
+            );
+            Some(PackedImage {
+                page: page_path(placement.page),
+                x: placement.x,
+                y: placement.y,
+                width: image.width(),
+                height: image.height(),
+            })
+        })
+        .collect();
+    PackedPages { pages, images }
+}
+
+/// Copies the image with its top left corner at `x, y`. The pixels of the gutter repeat the nearest edge pixel.
+fn copy_with_gutter(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, gutter: u32) {
+    if image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    let gutter = gutter as i64;
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    for row in -gutter..height + gutter {
+        for column in -gutter..width + gutter {
+            let (page_x, page_y) = (x as i64 + column, y as i64 + row);
+            let is_outside = page_x < 0
+                || page_y < 0
+                || page_x >= page.width() as i64
+                || page_y >= page.height() as i64;
+            if is_outside {
+                continue;
+            }
+            let pixel = image.get_pixel(
+                column.clamp(0, width - 1) as u32,
+                row.clamp(0, height - 1) as u32,
+            );
+            page.put_pixel(page_x as u32, page_y as u32, *pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PackedImage, Placement, pack_images, pack_rectangles};
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn rectangles_do_not_overlap_and_stay_in_their_page() {
+        let sizes = [(30, 10), (50, 20), (10, 10), (60, 40), (200, 5), (20, 30)];
+        let (placements, page_sizes) = pack_rectangles(&sizes, 100, 1);
+        // The 200 pixels wide rectangle does not fit in a page.
+        assert_eq!(placements[4], None);
+        let cells = placements
+            .iter()
+            .zip(sizes)
+            .filter_map(|(placement, size)| Some(((*placement)?, size)))
+            .collect::<Vec<_>>();
+        assert_eq!(cells.len(), 5);
+        for (index, (Placement { page, x, y }, (width, height))) in cells.iter().enumerate() {
+            let (page_width, page_height) = page_sizes[*page];
+            assert!(x + width < page_width && y + height < page_height);
+            for (
+                Placement {
+                    page: other_page,
+                    x: other_x,
+                    y: other_y,
+                },
+                (other_width, other_height),
+            ) in &cells[index + 1..]
+            {
+                let is_apart = page != other_page
+                    || x + width + 2 <= *other_x
+                    || other_x + other_width + 2 <= *x
+                    || y + height + 2 <= *other_y
+                    || other_y + other_height + 2 <= *y;
+                assert!(is_apart);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_images_are_copied_with_their_gutter() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([1, 1, 1, 255]));
+        image.put_pixel(1, 0, Rgba([2, 2, 2, 255]));
+        let packed = pack_images(&[image], |page| format!("atlas/{page}.png"));
+        assert_eq!(packed.pages.len(), 1);
+        let region = packed.images[0].clone().expect("The image is packed");
+        assert_eq!(
+            (region.x, region.y, region.width, region.height),
+            (2, 2, 2, 1)
+        );
+        let page = &packed.pages[0];
+        assert_eq!(page.dimensions(), (6, 5));
+        assert_eq!(page.get_pixel(0, 0), &Rgba([1, 1, 1, 255]));
+        assert_eq!(page.get_pixel(5, 4), &Rgba([2, 2, 2, 255]));
+
+        let parsed = PackedImage::parse(region.to_file_content().as_bytes());
+        assert_eq!(parsed, Some(region));
+        assert_eq!(PackedImage::parse(b"\x89PNG"), None);
+    }
+}
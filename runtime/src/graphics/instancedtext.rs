@@ -0,0 +1,164 @@
+use std::{collections::HashMap, sync::Arc};
+
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::glow::HasContext;
+
+use crate::{
+    game_resource::font_resource::CharacterInfo,
+    graphics::{
+        glprogram::GLProgram,
+        gltexture::Texture,
+        gltypes::{DataLayout, GLTypes, UsageHint},
+        shadersources::{INSTANCED_TEXT_FRAG_SHADER_SOURCE, INSTANCED_TEXT_VERTEX_SHADER_SOURCE},
+    },
+};
+
+/// The number of floats of a glyph instance: position (2), glyph index, scale and color (4).
+pub const GLYPH_INSTANCE_SIZE: usize = 8;
+
+/// Where a character is in the glyph table, and how far it moves the pen.
+#[derive(Debug, Clone, Copy)]
+pub struct GlyphInstanceInfo {
+    pub index: f32,
+    pub advance_width: f32,
+}
+
+/// The metrics of the glyphs of a font, uploaded once so that the vertex shader of the instanced text places the
+/// glyphs. Each glyph is a row of 2 texels: its bounds, then its rectangle in the atlas.
+pub struct GlyphTable {
+    pub texture: Arc<Texture>,
+    pub glyphs: HashMap<char, GlyphInstanceInfo>,
+}
+
+impl GlyphTable {
+    pub fn new(gl: &Arc<glow::Context>, font_cache: &HashMap<char, CharacterInfo>) -> Self {
+        let mut data = Vec::with_capacity(font_cache.len() * 8);
+        let mut glyphs = HashMap::with_capacity(font_cache.len());
+        for (index, (c, char_info)) in font_cache.iter().enumerate() {
+            let bounds = char_info.metrics.bounds;
+            data.extend_from_slice(&[bounds.xmin, bounds.ymin, bounds.width, bounds.height]);
+            // The same rectangle as the regular text, which samples a bit below the glyph.
+            data.extend_from_slice(&[
+                char_info.atlas_x,
+                char_info.atlas_y,
+                char_info.atlas_width,
+                char_info.atlas_height + 0.04,
+            ]);
+            glyphs.insert(
+                *c,
+                GlyphInstanceInfo {
+                    index: index as f32,
+                    advance_width: char_info.metrics.advance_width,
+                },
+            );
+        }
+        // Textures cannot be empty.
+        if data.is_empty() {
+            data.resize(8, 0.0);
+        }
+        let texture = Texture::new_float_rgba(gl, &data, 2, (data.len() / 8) as u32);
+        Self { texture, glyphs }
+    }
+}
+
+/// Creates the program drawing the glyph instances, or returns None when the driver cannot read textures in vertex
+/// shaders. The text is then drawn with the regular path.
+pub fn create_instanced_text_program(gl: &Arc<glow::Context>) -> Option<GLProgram> {
+    let vertex_texture_units =
+        unsafe { gl.get_parameter_i32(glow::MAX_VERTEX_TEXTURE_IMAGE_UNITS) };
+    if vertex_texture_units <= 0 {
+        return None;
+    }
+    let mut program = GLProgram::from_source(
+        gl,
+        INSTANCED_TEXT_VERTEX_SHADER_SOURCE,
+        INSTANCED_TEXT_FRAG_SHADER_SOURCE,
+    )
+    .ok()?;
+    let mut layout = DataLayout::new();
+    layout
+        .add_field("in_pos", GLTypes::Vec2, Some(UsageHint::Position))
+        .add_field("in_glyph", GLTypes::Float, Some(UsageHint::Custom))
+        .add_field("in_scale", GLTypes::Float, Some(UsageHint::Custom))
+        .add_field("in_color", GLTypes::Vec4, Some(UsageHint::Color));
+    program.vertex_layout = layout;
+    Some(program)
+}
+
+/// Appends one instance per glyph of the text to `instances`. Characters missing from the table are skipped, like in
+/// the regular text. The positions are before the affine transform, which is applied by the shader.
+#[allow(clippy::too_many_arguments)]
+pub fn push_text_instances(
+    instances: &mut Vec<f32>,
+    glyphs: &HashMap<char, GlyphInstanceInfo>,
+    x: f32,
+    y: f32,
+    text: &str,
+    scale: f32,
+    aspect_ratio: f32,
+    color: [f32; 4],
+) {
+    let mut pen_x = x;
+    for c in text.chars() {
+        let Some(glyph) = glyphs.get(&c) else {
+            continue;
+        };
+        instances.extend_from_slice(&[
+            pen_x,
+            y,
+            glyph.index,
+            scale,
+            color[0],
+            color[1],
+            color[2],
+            color[3],
+        ]);
+        pen_x += glyph.advance_width * scale / aspect_ratio;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{GLYPH_INSTANCE_SIZE, GlyphInstanceInfo, push_text_instances};
+
+    #[test]
+    fn glyphs_are_placed_along_the_advance_widths() {
+        let glyphs = HashMap::from([
+            (
+                'a',
+                GlyphInstanceInfo {
+                    index: 0.0,
+                    advance_width: 10.0,
+                },
+            ),
+            (
+                'b',
+                GlyphInstanceInfo {
+                    index: 1.0,
+                    advance_width: 20.0,
+                },
+            ),
+        ]);
+        let mut instances = Vec::new();
+        push_text_instances(
+            &mut instances,
+            &glyphs,
+            0.5,
+            0.25,
+            "ab?a",
+            0.1,
+            2.0,
+            [1.0; 4],
+        );
+        let instances = instances
+            .chunks_exact(GLYPH_INSTANCE_SIZE)
+            .collect::<Vec<_>>();
+        // The unknown character is skipped without moving the pen.
+        assert_eq!(instances.len(), 3);
+        assert_eq!(instances[0][..4], [0.5, 0.25, 0.0, 0.1]);
+        assert_eq!(instances[1][..3], [1.0, 0.25, 1.0]);
+        assert_eq!(instances[2][..3], [2.0, 0.25, 0.0]);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::graphics::glframebuffer::Viewport;
+
+/// How the game area fits in the window, set with `Io.setAspectPolicy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AspectPolicy {
+    /// The game is drawn on the whole window.
+    #[default]
+    Free,
+    /// The game keeps this aspect ratio (width / height), with black bars on the sides or at the top and bottom.
+    Letterbox(f32),
+}
+
+impl AspectPolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "free" => Some(AspectPolicy::Free),
+            "letterbox_16_9" => Some(AspectPolicy::Letterbox(16.0 / 9.0)),
+            "letterbox_4_3" => Some(AspectPolicy::Letterbox(4.0 / 3.0)),
+            _ => None,
+        }
+    }
+
+    pub fn from_size(width: f32, height: f32) -> Option<Self> {
+        let ratio = width / height;
+        (ratio.is_finite() && ratio > 0.0).then_some(AspectPolicy::Letterbox(ratio))
+    }
+}
+
+/// The borders of the window hidden by notches or rounded corners, in drawable pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SafeAreaInsets {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+/// Returns the part of the window the game is drawn in, as given to `gl.viewport` (the origin is the bottom-left corner).
+/// The game area is centered in the window.
+pub fn game_viewport(window_width: u32, window_height: u32, policy: AspectPolicy) -> Viewport {
+    let (width, height) = (window_width as f32, window_height as f32);
+    let AspectPolicy::Letterbox(ratio) = policy else {
+        return Viewport::from_size(window_width as i32, window_height as i32);
+    };
+    if height <= 0.0 {
+        return Viewport::from_size(window_width as i32, window_height as i32);
+    }
+    let (game_width, game_height) = if width / height > ratio {
+        ((height * ratio).round(), height)
+    } else {
+        (width, (width / ratio).round())
+    };
+    Viewport {
+        x: ((width - game_width) / 2.0).floor() as i32,
+        y: ((height - game_height) / 2.0).floor() as i32,
+        width: game_width as i32,
+        height: game_height as i32,
+    }
+}
+
+/// The internal resolution set with `Graphics.setPixelPerfect`. The game is drawn at this size and scaled up by a
+/// whole number, so that every pixel of the game covers the same number of pixels of the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelPerfect {
+    pub width: u32,
+    pub height: u32,
+    /// Images are moved to whole internal pixels, so that moving sprites do not shimmer.
+    pub snap: bool,
+}
+
+/// Returns the part of the window the internal resolution is shown in, and its scale: the largest whole number that
+/// fits the window. When the window is smaller than the internal resolution, the game is letterboxed with a fractional
+/// scale below 1 instead.
+pub fn pixel_perfect_viewport(
+    window_width: u32,
+    window_height: u32,
+    pixel_perfect: PixelPerfect,
+) -> (Viewport, f32) {
+    let (width, height) = (pixel_perfect.width.max(1), pixel_perfect.height.max(1));
+    let scale = (window_width / width).min(window_height / height);
+    if scale == 0 {
+        let policy = AspectPolicy::Letterbox(width as f32 / height as f32);
+        let viewport = game_viewport(window_width, window_height, policy);
+        return (viewport, viewport.width as f32 / width as f32);
+    }
+    let (game_width, game_height) = (width * scale, height * scale);
+    let viewport = Viewport {
+        x: ((window_width - game_width) / 2) as i32,
+        y: ((window_height - game_height) / 2) as i32,
+        width: game_width as i32,
+        height: game_height as i32,
+    };
+    (viewport, scale as f32)
+}
+
+/// Converts a position in drawable pixels from the top-left corner of the window to the OpenGL coordinates of the
+/// game area. Positions in the bars are outside of [-1, 1].
+pub fn window_px_to_gl(viewport: &Viewport, window_height: u32, x: f32, y: f32) -> (f32, f32) {
+    let y_from_bottom = window_height as f32 - y;
+    (
+        (x - viewport.x as f32) / viewport.width.max(1) as f32 * 2.0 - 1.0,
+        (y_from_bottom - viewport.y as f32) / viewport.height.max(1) as f32 * 2.0 - 1.0,
+    )
+}
+
+/// The inverse of `window_px_to_gl`.
+pub fn gl_to_window_px(viewport: &Viewport, window_height: u32, x: f32, y: f32) -> (f32, f32) {
+    let y_from_bottom = viewport.y as f32 + (y + 1.0) / 2.0 * viewport.height as f32;
+    (
+        viewport.x as f32 + (x + 1.0) / 2.0 * viewport.width as f32,
+        window_height as f32 - y_from_bottom,
+    )
+}
+
+/// Returns the part of the game area that is not hidden by the insets, as its bottom-left corner and its size in the
+/// OpenGL coordinates of the game area.
+pub fn safe_rect(
+    viewport: &Viewport,
+    window_width: u32,
+    window_height: u32,
+    insets: SafeAreaInsets,
+) -> ([f32; 2], [f32; 2]) {
+    let (width, height) = (window_width as f32, window_height as f32);
+    let left = (viewport.x as f32).max(insets.left);
+    let right = ((viewport.x + viewport.width) as f32).min(width - insets.right);
+    let bottom = (viewport.y as f32).max(insets.bottom);
+    let top = ((viewport.y + viewport.height) as f32).min(height - insets.top);
+    let (min_x, min_y) = window_px_to_gl(viewport, window_height, left, height - bottom);
+    let (max_x, max_y) = window_px_to_gl(viewport, window_height, right, height - top);
+    (
+        [min_x, min_y],
+        [(max_x - min_x).max(0.0), (max_y - min_y).max(0.0)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AspectPolicy, PixelPerfect, SafeAreaInsets, game_viewport, gl_to_window_px,
+        pixel_perfect_viewport, safe_rect, window_px_to_gl,
+    };
+    use crate::graphics::glframebuffer::Viewport;
+
+    #[test]
+    fn wide_windows_get_bars_on_the_sides() {
+        let policy = AspectPolicy::from_name("letterbox_16_9").expect("the policy exists");
+        // An ultrawide monitor.
+        assert_eq!(
+            game_viewport(3440, 1440, policy),
+            Viewport {
+                x: 440,
+                y: 0,
+                width: 2560,
+                height: 1440,
+            }
+        );
+        // A phone held in portrait.
+        assert_eq!(
+            game_viewport(1080, 2400, policy),
+            Viewport {
+                x: 0,
+                y: 896,
+                width: 1080,
+                height: 608,
+            }
+        );
+        assert_eq!(
+            game_viewport(800, 600, AspectPolicy::Free),
+            Viewport::from_size(800, 600)
+        );
+        assert_eq!(AspectPolicy::from_size(4.0, 0.0), None);
+    }
+
+    #[test]
+    fn window_positions_map_to_the_game_area() {
+        let viewport = game_viewport(3440, 1440, AspectPolicy::Letterbox(16.0 / 9.0));
+        assert_eq!(window_px_to_gl(&viewport, 1440, 440.0, 0.0), (-1.0, 1.0));
+        assert_eq!(
+            window_px_to_gl(&viewport, 1440, 3000.0, 1440.0),
+            (1.0, -1.0)
+        );
+        assert_eq!(window_px_to_gl(&viewport, 1440, 1720.0, 720.0), (0.0, 0.0));
+        // The left bar.
+        assert!(window_px_to_gl(&viewport, 1440, 0.0, 720.0).0 < -1.0);
+        assert_eq!(gl_to_window_px(&viewport, 1440, -1.0, 1.0), (440.0, 0.0));
+        assert_eq!(gl_to_window_px(&viewport, 1440, 0.0, 0.0), (1720.0, 720.0));
+    }
+
+    #[test]
+    fn pixel_perfect_scales_are_whole_numbers_when_the_window_is_large_enough() {
+        let low_res = PixelPerfect {
+            width: 320,
+            height: 180,
+            snap: false,
+        };
+        assert_eq!(
+            pixel_perfect_viewport(1920, 1080, low_res),
+            (Viewport::from_size(1920, 1080), 6.0)
+        );
+        assert_eq!(
+            pixel_perfect_viewport(1366, 768, low_res),
+            (
+                Viewport {
+                    x: 43,
+                    y: 24,
+                    width: 1280,
+                    height: 720,
+                },
+                4.0
+            )
+        );
+        // Smaller than the internal resolution: fractional scaling.
+        let (viewport, scale) = pixel_perfect_viewport(160, 200, low_res);
+        assert_eq!(viewport.width, 160);
+        assert_eq!(scale, 0.5);
+    }
+
+    #[test]
+    fn insets_only_shrink_the_safe_rect_where_they_overlap_the_game() {
+        let window = (1000, 500);
+        let notch = SafeAreaInsets {
+            left: 100.0,
+            ..Default::default()
+        };
+        let free = game_viewport(window.0, window.1, AspectPolicy::Free);
+        assert_eq!(
+            safe_rect(&free, window.0, window.1, notch),
+            ([-0.8, -1.0], [1.8, 2.0])
+        );
+        // The bar is wider than the notch.
+        let square = game_viewport(window.0, window.1, AspectPolicy::Letterbox(1.0));
+        assert_eq!(
+            safe_rect(&square, window.0, window.1, notch),
+            ([-1.0, -1.0], [2.0, 2.0])
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Emitters spawning, moving and drawing particles in native code, for effects like sparks, smoke or rain that would
+//! be too slow to simulate one particle at a time in Lua.
+
+use std::sync::Arc;
+
+use crate::{
+    graphics::{batchdraw::BatchDraw2d, gltexture::Texture},
+    lua_env::{lua_vec2::Vec2, lua_vec4::Vec4},
+    math::Vect,
+};
+
+/// An emitter holds at most this many particles, so that a typo in a rate does not allocate gigabytes.
+pub const MAX_PARTICLES_PER_EMITTER: usize = 100_000;
+
+/// A value that can change over the lifetime of a particle.
+pub trait Lerp: Copy {
+    fn lerp_to(self, other: Self, k: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp_to(self, other: Self, k: f32) -> Self {
+        self + (other - self) * k
+    }
+}
+
+impl<const N: usize> Lerp for Vect<N> {
+    fn lerp_to(self, other: Self, k: f32) -> Self {
+        self.lerp(other, k)
+    }
+}
+
+/// Values evenly spaced over the lifetime of a particle, from its birth to its death. The value between two keys is
+/// interpolated linearly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T: Lerp> {
+    keys: Vec<T>,
+}
+
+impl<T: Lerp> Curve<T> {
+    pub fn new(keys: Vec<T>) -> Result<Self, String> {
+        if keys.is_empty() {
+            return Err("a curve needs at least one value".to_string());
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self { keys: vec![value] }
+    }
+
+    /// The value at `progress`, between 0 at the birth of the particle and 1 at its death.
+    pub fn sample(&self, progress: f32) -> T {
+        let last = self.keys.len() - 1;
+        let position = progress.clamp(0.0, 1.0) * last as f32;
+        let index = (position as usize).min(last);
+        let next = (index + 1).min(last);
+        self.keys[index].lerp_to(self.keys[next], position - index as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleShape {
+    Square,
+    Circle,
+}
+
+impl ParticleShape {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(ParticleShape::Square),
+            "circle" => Some(ParticleShape::Circle),
+            _ => None,
+        }
+    }
+}
+
+/// How an emitter spawns its particles and how they change during their life. The ranges are `(min, max)`, each
+/// particle picks a random value inside of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterConfig {
+    /// Particles spawned per second.
+    pub rate: f32,
+    /// In seconds.
+    pub lifetime: (f32, f32),
+    /// The speed at the birth of the particles.
+    pub speed: (f32, f32),
+    /// The angle of the direction in which the particles leave the emitter, in radians.
+    pub direction: f32,
+    /// The angle around `direction` inside of which the particles leave, in radians. `TAU` spawns them in every
+    /// direction.
+    pub spread: f32,
+    /// Added to the velocity of every particle each second.
+    pub gravity: Vec2,
+    /// Multiplies the velocity of the particles, to slow them down as they age.
+    pub speed_curve: Curve<f32>,
+    pub size_curve: Curve<f32>,
+    pub color_curve: Curve<Vec4>,
+    pub shape: ParticleShape,
+    pub max_particles: usize,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        Self {
+            rate: 10.0,
+            lifetime: (1.0, 1.0),
+            speed: (0.1, 0.1),
+            direction: std::f32::consts::FRAC_PI_2,
+            spread: 0.0,
+            gravity: Vec2::zero(),
+            speed_curve: Curve::constant(1.0),
+            size_curve: Curve::constant(0.02),
+            color_curve: Curve::constant(Vec4::new(1.0, 1.0, 1.0, 1.0)),
+            shape: ParticleShape::Square,
+            max_particles: 1000,
+        }
+    }
+}
+
+impl EmitterConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        let numbers = [
+            ("rate", self.rate),
+            ("lifetime", self.lifetime.0),
+            ("lifetime", self.lifetime.1),
+            ("speed", self.speed.0),
+            ("speed", self.speed.1),
+        ];
+        for (name, value) in numbers {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{name} is {value}, it should be a positive number"));
+            }
+        }
+        if self.lifetime.0 > self.lifetime.1 || self.speed.0 > self.speed.1 {
+            return Err("the minimum of a range should not be more than its maximum".to_string());
+        }
+        if self.max_particles > MAX_PARTICLES_PER_EMITTER {
+            return Err(format!(
+                "maxParticles is {}, the maximum is {MAX_PARTICLES_PER_EMITTER}",
+                self.max_particles
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+pub struct ParticleEmitter {
+    pub config: EmitterConfig,
+    /// Where the next particles are spawned. The particles already spawned do not follow the emitter.
+    pub position: Vec2,
+    /// From the oldest to the newest, which is also the order in which they are drawn.
+    particles: Vec<Particle>,
+    /// The fraction of a particle left over by the previous updates, so that low rates still spawn particles.
+    spawn_debt: f32,
+    random_state: u64,
+}
+
+impl ParticleEmitter {
+    /// Two emitters with the same seed spawn the same particles.
+    pub fn new(config: EmitterConfig, position: Vec2, seed: u64) -> Self {
+        Self {
+            config,
+            position,
+            particles: Vec::new(),
+            spawn_debt: 0.0,
+            random_state: seed,
+        }
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_debt = 0.0;
+    }
+
+    /// A random number between 0 and 1, from splitmix64.
+    fn random(&mut self) -> f32 {
+        self.random_state = self.random_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut hash = self.random_state;
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        hash ^= hash >> 31;
+        (hash >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn random_in(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.random()
+    }
+
+    /// Spawns `count` particles at once, on top of the ones spawned by the rate. Particles past `max_particles` are
+    /// not spawned.
+    pub fn emit(&mut self, count: usize) {
+        let count = count.min(
+            self.config
+                .max_particles
+                .saturating_sub(self.particles.len()),
+        );
+        self.particles.reserve(count);
+        for _ in 0..count {
+            let angle = self.config.direction + (self.random() - 0.5) * self.config.spread;
+            let speed = self.random_in(self.config.speed);
+            let lifetime = self.random_in(self.config.lifetime);
+            self.particles.push(Particle {
+                position: self.position,
+                velocity: Vec2::from_angle(angle) * speed,
+                age: 0.0,
+                lifetime,
+            });
+        }
+    }
+
+    /// Ages and moves the particles, removes the dead ones and spawns new ones according to the rate.
+    pub fn update(&mut self, delta_time: f32) {
+        let gravity = self.config.gravity * delta_time;
+        let speed_curve = &self.config.speed_curve;
+        self.particles.retain_mut(|particle| {
+            particle.age += delta_time;
+            if particle.age >= particle.lifetime {
+                return false;
+            }
+            let progress = particle.age / particle.lifetime;
+            particle.velocity = particle.velocity + gravity;
+            particle.position =
+                particle.position + particle.velocity * (speed_curve.sample(progress) * delta_time);
+            true
+        });
+
+        self.spawn_debt += self.config.rate * delta_time.max(0.0);
+        let count = self.spawn_debt.floor();
+        self.spawn_debt -= count;
+        self.emit(count as usize);
+    }
+
+    /// Draws every particle, centered on its position. The size of the particles is their height, like the radius of
+    /// circles. With an image, the color of the particle tints the image.
+    pub fn draw(&self, batch: &mut BatchDraw2d, image: Option<&Arc<Texture>>) {
+        let aspect_ratio = batch.aspect_ratio();
+        for particle in &self.particles {
+            // Particles with no lifetime are drawn once with their final look.
+            let progress = (particle.age / particle.lifetime).min(1.0);
+            let size = self.config.size_curve.sample(progress);
+            let color = self.config.color_curve.sample(progress).0;
+            let (width, height) = (size / aspect_ratio, size);
+            let (x, y) = (
+                particle.position.x() - width / 2.0,
+                particle.position.y() - height / 2.0,
+            );
+            match (image, self.config.shape) {
+                (Some(texture), _) => batch.draw_image(x, y, width, height, texture, color),
+                (None, ParticleShape::Square) => batch.draw_rect(x, y, width, height, color),
+                (None, ParticleShape::Circle) => batch.draw_circle(
+                    particle.position.x(),
+                    particle.position.y(),
+                    size / 2.0,
+                    color,
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Curve, EmitterConfig, ParticleEmitter};
+    use crate::lua_env::lua_vec2::Vec2;
+
+    #[test]
+    fn particles_spawn_at_the_rate_and_die_at_the_end_of_their_life() {
+        let curve = Curve::new(vec![0.0, 1.0, 0.0]).expect("not empty");
+        assert_eq!(curve.sample(0.25), 0.5);
+        assert_eq!(curve.sample(0.5), 1.0);
+        assert_eq!(curve.sample(2.0), 0.0);
+        assert!(Curve::<f32>::new(Vec::new()).is_err());
+
+        let config = EmitterConfig {
+            rate: 10.0,
+            lifetime: (1.0, 1.0),
+            speed: (1.0, 1.0),
+            direction: 0.0,
+            max_particles: 15,
+            ..EmitterConfig::default()
+        };
+        assert!(config.validate().is_ok());
+        let mut emitter = ParticleEmitter::new(config, Vec2::new(1.0, 2.0), 7);
+        // 2.5 particles per update: the fractions add up.
+        for _ in 0..4 {
+            emitter.update(0.25);
+        }
+        assert_eq!(emitter.particle_count(), 10);
+        let oldest = emitter.particles[0];
+        assert!((oldest.position - Vec2::new(1.75, 2.0)).length() < 1e-5);
+
+        emitter.emit(100);
+        assert_eq!(emitter.particle_count(), 15);
+        // The particles spawned by the first update reach the end of their life.
+        emitter.config.rate = 0.0;
+        emitter.update(0.25);
+        assert_eq!(emitter.particle_count(), 15 - 2);
+
+        let invalid = EmitterConfig {
+            lifetime: (2.0, 1.0),
+            ..EmitterConfig::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::glow::HasContext;
+
+use crate::graphics::{
+    glframebuffer::{Framebuffer, Viewport},
+    gltexture::ImageAntialiasing,
+};
+
+/// The framebuffer the game is drawn in when `Graphics.setPixelPerfect` is used.
+/// At the end of the frame, it is copied to the game area of the window with nearest filtering.
+pub struct PixelPerfectTarget {
+    framebuffer: Framebuffer,
+    /// The framebuffer bound at the start of the frame: the window, or the game view of the editor.
+    window_framebuffer: Option<glow::NativeFramebuffer>,
+}
+
+impl PixelPerfectTarget {
+    pub fn new(gl: &Arc<glow::Context>, width: u32, height: u32) -> Self {
+        Self {
+            framebuffer: Framebuffer::new_rgba(gl, width, height, ImageAntialiasing::Nearest),
+            window_framebuffer: None,
+        }
+    }
+
+    pub fn has_size(&self, width: u32, height: u32) -> bool {
+        self.framebuffer.width() == width && self.framebuffer.height() == height
+    }
+
+    /// Binds the framebuffer so that the frame is drawn at the internal resolution.
+    /// The pixel conversions of `Coord` read the viewport, so they give internal pixels.
+    pub fn begin_frame(&mut self, gl: &glow::Context) {
+        unsafe {
+            self.window_framebuffer = gl.get_parameter_framebuffer(glow::DRAW_FRAMEBUFFER_BINDING);
+            // The bars are cleared in the window, the internal resolution has none.
+            gl.disable(glow::SCISSOR_TEST);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer.id()));
+            gl.viewport(
+                0,
+                0,
+                self.framebuffer.width() as i32,
+                self.framebuffer.height() as i32,
+            );
+        }
+    }
+
+    /// Copies the frame to the game area of the window and binds the window again.
+    pub fn present(&self, gl: &glow::Context, viewport: &Viewport) {
+        unsafe {
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer.id()));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, self.window_framebuffer);
+            gl.blit_framebuffer(
+                0,
+                0,
+                self.framebuffer.width() as i32,
+                self.framebuffer.height() as i32,
+                viewport.x,
+                viewport.y,
+                viewport.x + viewport.width,
+                viewport.y + viewport.height,
+                glow::COLOR_BUFFER_BIT,
+                glow::NEAREST,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, self.window_framebuffer);
+            gl.viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        }
+    }
+}
@@ -54,3 +54,44 @@ pub const FONT_FRAG_SHADER_SOURCE: &str = r#"precision mediump float;
         }
         frag_color = vec4(text_color.rgb, r * text_color.a);
     }"#;
+
+/// Draws one glyph per instance. The 6 vertices of a glyph have no attributes, the corner comes from gl_VertexID.
+pub const INSTANCED_TEXT_VERTEX_SHADER_SOURCE: &str = r#"
+    layout (location = 0) in vec2 in_pos;
+    layout (location = 1) in float in_glyph;
+    layout (location = 2) in float in_scale;
+    layout (location = 3) in vec4 in_color;
+    uniform highp sampler2D glyph_table;
+    uniform mat3 transform;
+    uniform float aspect_ratio;
+    out vec2 uv;
+    out vec4 color;
+    const vec2 CORNERS[6] = vec2[6](
+        vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0),
+        vec2(1.0, 1.0), vec2(0.0, 1.0), vec2(0.0, 0.0)
+    );
+    void main() {
+        vec2 corner = CORNERS[gl_VertexID];
+        int glyph = int(in_glyph);
+        vec4 bounds = texelFetch(glyph_table, ivec2(0, glyph), 0);
+        vec4 atlas = texelFetch(glyph_table, ivec2(1, glyph), 0);
+        vec2 offset = (bounds.xy + corner * bounds.zw) * in_scale;
+        vec2 pos = in_pos + vec2(offset.x / aspect_ratio, offset.y);
+        uv = vec2(atlas.x + corner.x * atlas.z, atlas.y + (1.0 - corner.y) * atlas.w);
+        color = in_color;
+        gl_Position = vec4((transform * vec3(pos, 1.0)).xy, 0.0, 1.0);
+    }
+"#;
+
+pub const INSTANCED_TEXT_FRAG_SHADER_SOURCE: &str = r#"precision mediump float;
+    in vec2 uv;
+    in vec4 color;
+    uniform sampler2D tex;
+    out vec4 frag_color;
+    void main() {
+        float r = texture(tex, uv).r;
+        if (r < 0.01) {
+            discard;
+        }
+        frag_color = vec4(color.rgb, r * color.a);
+    }"#;
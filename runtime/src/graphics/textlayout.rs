@@ -0,0 +1,338 @@
+//! Places the characters of a text on several lines: wrapping at a maximum width, alignment, line spacing and colors
+//! given inline with `[color=#ff0000]...[/color]`. The positions take the kerning of the font into account.
+
+/// How the lines are placed horizontally, in the maximum width or in the width of the longest line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "left" => Some(Self::Left),
+            "center" => Some(Self::Center),
+            "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    fn factor(self) -> f32 {
+        match self {
+            Self::Left => 0.0,
+            Self::Center => 0.5,
+            Self::Right => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextLayoutOptions {
+    /// The lines longer than this are wrapped between words, or between characters for words that do not fit.
+    pub max_width: Option<f32>,
+    pub align: TextAlign,
+    /// The distance between the baselines of two lines, as a multiple of the font size.
+    pub line_spacing: f32,
+}
+
+impl Default for TextLayoutOptions {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            align: TextAlign::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+/// A part of a text drawn with the same color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: [f32; 4],
+}
+
+/// Splits the text at the `[color=#rrggbb]` and `[/color]` tags. Colors can also have an alpha: `#rrggbbaa`. Tags can
+/// be nested, and brackets that are not tags are kept in the text.
+pub fn parse_markup(text: &str, color: [f32; 4]) -> Vec<TextSpan> {
+    let mut spans: Vec<TextSpan> = Vec::new();
+    let mut colors = vec![color];
+    let mut rest = text;
+    let mut current = String::new();
+    while let Some(start) = rest.find('[') {
+        current.push_str(&rest[..start]);
+        let tag_and_rest = &rest[start..];
+        let tag = tag_and_rest
+            .find(']')
+            .map(|end| (&tag_and_rest[1..end], end + 1));
+        let change = tag.and_then(|(tag, length)| {
+            if tag == "/color" {
+                return Some((None, length));
+            }
+            let color = parse_hex_color(tag.strip_prefix("color=")?)?;
+            Some((Some(color), length))
+        });
+        let Some((new_color, tag_length)) = change else {
+            current.push('[');
+            rest = &tag_and_rest[1..];
+            continue;
+        };
+        let span_color = *colors.last().unwrap_or(&color);
+        push_span(&mut spans, std::mem::take(&mut current), span_color);
+        match new_color {
+            Some(new_color) => colors.push(new_color),
+            // The color given to the text is never removed.
+            None if colors.len() > 1 => {
+                colors.pop();
+            }
+            None => {}
+        }
+        rest = &tag_and_rest[tag_length..];
+    }
+    current.push_str(rest);
+    push_span(&mut spans, current, *colors.last().unwrap_or(&color));
+    spans
+}
+
+fn push_span(spans: &mut Vec<TextSpan>, text: String, color: [f32; 4]) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        Some(last) if last.color == color => last.text.push_str(&text),
+        _ => spans.push(TextSpan { text, color }),
+    }
+}
+
+fn parse_hex_color(color: &str) -> Option<[f32; 4]> {
+    let digits = color.strip_prefix('#')?;
+    if (digits.len() != 6 && digits.len() != 8) || !digits.is_ascii() {
+        return None;
+    }
+    let mut result = [1.0; 4];
+    for (index, channel) in result.iter_mut().enumerate().take(digits.len() / 2) {
+        let value = u8::from_str_radix(&digits[index * 2..index * 2 + 2], 16).ok()?;
+        *channel = value as f32 / 255.0;
+    }
+    Some(result)
+}
+
+/// A character placed by `layout_text`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedGlyph {
+    pub character: char,
+    /// The position of the origin of the character, from the start of the first baseline.
+    pub x: f32,
+    pub y: f32,
+    pub color: [f32; 4],
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PlacedGlyph>,
+    /// The width of the longest line, without the spaces at its end.
+    pub width: f32,
+    pub line_count: usize,
+}
+
+impl TextLayout {
+    /// The distance from the top of the first line to the bottom of the last line.
+    pub fn height(&self, font_size: f32, options: &TextLayoutOptions) -> f32 {
+        font_size + self.line_count.saturating_sub(1) as f32 * font_size * options.line_spacing
+    }
+}
+
+/// Places the characters of the spans. `advance` gives the distance to the next character and `kerning` the adjustment
+/// between two characters, both in the units of the layout. Characters without an advance are not in the font and are
+/// skipped.
+pub fn layout_text(
+    spans: &[TextSpan],
+    font_size: f32,
+    options: &TextLayoutOptions,
+    advance: impl Fn(char) -> Option<f32>,
+    kerning: impl Fn(char, char) -> f32,
+) -> TextLayout {
+    let characters = spans
+        .iter()
+        .flat_map(|span| span.text.chars().map(|character| (character, span.color)));
+
+    let mut lines: Vec<Vec<(char, [f32; 4])>> = Vec::new();
+    let mut line: Vec<(char, [f32; 4])> = Vec::new();
+    for (character, color) in characters {
+        if character == '\n' {
+            lines.push(std::mem::take(&mut line));
+            continue;
+        }
+        if advance(character).is_none() {
+            continue;
+        }
+        line.push((character, color));
+        let Some(max_width) = options.max_width else {
+            continue;
+        };
+        if character.is_whitespace() || line.len() < 2 {
+            continue;
+        }
+        let (_, width) = place_line(&line, &advance, &kerning);
+        if width <= max_width {
+            continue;
+        }
+        // Wrap after the last space, or before the character when the word is longer than a line.
+        let wrap_index = line
+            .iter()
+            .rposition(|(character, _)| character.is_whitespace())
+            .map(|index| index + 1)
+            .unwrap_or(line.len() - 1);
+        let next_line = line.split_off(wrap_index);
+        lines.push(std::mem::replace(&mut line, next_line));
+    }
+    lines.push(line);
+
+    let placed_lines = lines
+        .iter()
+        .map(|line| place_line(line, &advance, &kerning))
+        .collect::<Vec<_>>();
+    let width = placed_lines
+        .iter()
+        .map(|(_, width)| *width)
+        .fold(0.0, f32::max);
+    let container_width = options.max_width.unwrap_or(width);
+
+    let mut glyphs = Vec::new();
+    for (index, (line, (positions, line_width))) in lines.iter().zip(&placed_lines).enumerate() {
+        let offset = (container_width - line_width) * options.align.factor();
+        let y = -(index as f32) * font_size * options.line_spacing;
+        for ((character, color), x) in line.iter().zip(positions) {
+            if character.is_whitespace() {
+                continue;
+            }
+            glyphs.push(PlacedGlyph {
+                character: *character,
+                x: offset + x,
+                y,
+                color: *color,
+            });
+        }
+    }
+    TextLayout {
+        glyphs,
+        width,
+        line_count: lines.len(),
+    }
+}
+
+/// Returns the position of every character of the line and the width of the line without the spaces at its end.
+fn place_line(
+    line: &[(char, [f32; 4])],
+    advance: &impl Fn(char) -> Option<f32>,
+    kerning: &impl Fn(char, char) -> f32,
+) -> (Vec<f32>, f32) {
+    let mut positions = Vec::with_capacity(line.len());
+    let mut pen = 0.0;
+    let mut width: f32 = 0.0;
+    let mut previous = None;
+    for (character, _) in line {
+        if let Some(previous) = previous {
+            pen += kerning(previous, *character);
+        }
+        positions.push(pen);
+        pen += advance(*character).unwrap_or(0.0);
+        if !character.is_whitespace() {
+            width = pen;
+        }
+        previous = Some(*character);
+    }
+    (positions, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextAlign, TextLayoutOptions, TextSpan, layout_text, parse_markup};
+
+    const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const RED: [f32; 4] = [1.0, 0.0, 0.0, 1.0];
+
+    fn lines_of(text: &str, options: &TextLayoutOptions) -> Vec<String> {
+        let spans = parse_markup(text, WHITE);
+        let layout = layout_text(&spans, 1.0, options, |_| Some(1.0), |_, _| 0.0);
+        let mut lines = vec![String::new(); layout.line_count];
+        for glyph in &layout.glyphs {
+            lines[(-glyph.y).round() as usize].push(glyph.character);
+        }
+        lines
+    }
+
+    #[test]
+    fn markup_changes_the_color_of_the_text_inside_the_tags() {
+        let spans = parse_markup(
+            "a [color=#ff0000]b[color=#00ff0080]c[/color][/color] [d]",
+            WHITE,
+        );
+        assert_eq!(
+            spans,
+            vec![
+                TextSpan {
+                    text: "a ".to_string(),
+                    color: WHITE,
+                },
+                TextSpan {
+                    text: "b".to_string(),
+                    color: RED,
+                },
+                TextSpan {
+                    text: "c".to_string(),
+                    color: [0.0, 1.0, 0.0, 128.0 / 255.0],
+                },
+                TextSpan {
+                    text: " [d]".to_string(),
+                    color: WHITE,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn long_lines_are_wrapped_between_words() {
+        let wrapped_at = |max_width: f32| TextLayoutOptions {
+            max_width: Some(max_width),
+            ..TextLayoutOptions::default()
+        };
+        // The spaces are not drawn.
+        assert_eq!(
+            lines_of("the quick brown fox", &wrapped_at(11.0)),
+            vec!["thequick", "brownfox"]
+        );
+        // A word longer than a line is cut.
+        assert_eq!(
+            lines_of("abcdefghij", &wrapped_at(7.0)),
+            vec!["abcdefg", "hij"]
+        );
+        assert_eq!(lines_of("a\n\nb", &wrapped_at(7.0)), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn lines_are_aligned_in_the_maximum_width() {
+        let options = TextLayoutOptions {
+            max_width: Some(10.0),
+            align: TextAlign::Right,
+            line_spacing: 1.5,
+        };
+        let spans = parse_markup("ab\nc", WHITE);
+        let layout = layout_text(&spans, 2.0, &options, |_| Some(1.0), |_, _| -0.5);
+        let positions = layout
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.character, glyph.x, glyph.y))
+            .collect::<Vec<_>>();
+        // The kerning brings "b" closer to "a".
+        assert_eq!(
+            positions,
+            vec![('a', 8.5, 0.0), ('b', 9.0, 0.0), ('c', 9.0, -3.0)]
+        );
+        assert_eq!(layout.width, 1.5);
+        assert_eq!(layout.height(2.0, &options), 5.0);
+    }
+}
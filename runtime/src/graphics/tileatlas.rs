@@ -0,0 +1,142 @@
+//! The images drawn with a tileset are repacked in an atlas where every tile is surrounded by a gutter of copies of its
+//! edge pixels. When a tile is drawn at a scale that is not a whole number, the texture filtering reads the gutter
+//! instead of the neighboring tile, which would otherwise show as lines of the wrong color between the tiles.
+
+use std::sync::Arc;
+
+use crate::graphics::gltexture::Texture;
+
+/// The width of the gutter when the tileset does not have a `gutter` property.
+pub const DEFAULT_TILE_GUTTER: u32 = 2;
+pub const MAX_TILE_GUTTER: u32 = 16;
+
+/// How a tileset cuts its image in tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileLayout {
+    pub tile_width: u32,
+    pub tile_height: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub gutter: u32,
+}
+
+impl TileLayout {
+    /// The top left corner of the tile in the image of the tileset.
+    pub fn source_position(&self, id: u32) -> (u32, u32) {
+        let columns = self.columns.max(1);
+        (
+            id % columns * (self.tile_width + self.spacing) + self.margin,
+            id / columns * (self.tile_height + self.spacing) + self.margin,
+        )
+    }
+
+    fn cell_size(&self) -> (u32, u32) {
+        (
+            self.tile_width + 2 * self.gutter,
+            self.tile_height + 2 * self.gutter,
+        )
+    }
+
+    /// The atlas is about square, so that it fits in the maximum texture size whenever the tileset image does.
+    fn atlas_columns(&self) -> u32 {
+        ((self.tile_count as f64).sqrt().ceil() as u32).max(1)
+    }
+
+    pub fn atlas_size(&self) -> (u32, u32) {
+        let (cell_width, cell_height) = self.cell_size();
+        let columns = self.atlas_columns();
+        let rows = self.tile_count.div_ceil(columns).max(1);
+        (columns * cell_width, rows * cell_height)
+    }
+
+    /// The top left corner of the tile in the atlas, inside of its gutter.
+    pub fn atlas_position(&self, id: u32) -> (u32, u32) {
+        let (cell_width, cell_height) = self.cell_size();
+        let columns = self.atlas_columns();
+        (
+            id % columns * cell_width + self.gutter,
+            id / columns * cell_height + self.gutter,
+        )
+    }
+}
+
+/// The texture of the tiles of an image, for one layout.
+pub struct TileAtlas {
+    pub layout: TileLayout,
+    pub texture: Arc<Texture>,
+}
+
+/// Copies the tiles of the RGBA pixels of a tileset image into an atlas of `layout.atlas_size()`. The pixels of the
+/// gutters repeat the nearest edge pixel of their tile. The parts of the tiles past the image repeat its last pixels.
+pub fn build_tile_atlas(pixels: &[u8], width: u32, height: u32, layout: &TileLayout) -> Vec<u8> {
+    let (atlas_width, atlas_height) = layout.atlas_size();
+    let mut atlas = vec![0; atlas_width as usize * atlas_height as usize * 4];
+    if width == 0 || height == 0 || layout.tile_width == 0 || layout.tile_height == 0 {
+        return atlas;
+    }
+    let gutter = layout.gutter as i64;
+    let (tile_width, tile_height) = (layout.tile_width as i64, layout.tile_height as i64);
+    for id in 0..layout.tile_count {
+        let (source_x, source_y) = layout.source_position(id);
+        let (atlas_x, atlas_y) = layout.atlas_position(id);
+        for y in -gutter..tile_height + gutter {
+            let source_row = (source_y as i64 + y.clamp(0, tile_height - 1)).min(height as i64 - 1);
+            let atlas_row = atlas_y as i64 + y;
+            for x in -gutter..tile_width + gutter {
+                let source_column =
+                    (source_x as i64 + x.clamp(0, tile_width - 1)).min(width as i64 - 1);
+                let atlas_column = atlas_x as i64 + x;
+                let source = (source_row as usize * width as usize + source_column as usize) * 4;
+                let target =
+                    (atlas_row as usize * atlas_width as usize + atlas_column as usize) * 4;
+                if let (Some(target), Some(source)) = (
+                    atlas.get_mut(target..target + 4),
+                    pixels.get(source..source + 4),
+                ) {
+                    target.copy_from_slice(source);
+                }
+            }
+        }
+    }
+    atlas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TileLayout, build_tile_atlas};
+
+    #[test]
+    fn gutters_repeat_the_edges_of_their_tile() {
+        // Two tiles of 2x1 pixels separated by 1 pixel of spacing, after a margin of 1 pixel.
+        let pixel = |value: u8| [value, value, value, 255];
+        let row = [0, 1, 2, 0, 3, 4];
+        let pixels = [[0; 6], row]
+            .iter()
+            .flatten()
+            .flat_map(|value| pixel(*value))
+            .collect::<Vec<_>>();
+        let layout = TileLayout {
+            tile_width: 2,
+            tile_height: 1,
+            spacing: 1,
+            margin: 1,
+            columns: 2,
+            tile_count: 2,
+            gutter: 1,
+        };
+        assert_eq!(layout.source_position(1), (4, 1));
+        assert_eq!(layout.atlas_size(), (8, 3));
+        assert_eq!(layout.atlas_position(1), (5, 1));
+
+        let atlas = build_tile_atlas(&pixels, 6, 2, &layout);
+        let atlas_row = [1, 1, 2, 2, 3, 3, 4, 4]
+            .iter()
+            .flat_map(|value| pixel(*value))
+            .collect::<Vec<_>>();
+        for row in atlas.chunks_exact(8 * 4) {
+            assert_eq!(row, atlas_row.as_slice());
+        }
+    }
+}
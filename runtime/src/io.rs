@@ -1,11 +1,39 @@
-use crate::{game::Game, lua_env::print_lua_error_from_error};
+use crate::{
+    game::Game,
+    graphics::{
+        glframebuffer::Viewport,
+        letterbox::{AspectPolicy, PixelPerfect, SafeAreaInsets, window_px_to_gl},
+    },
+    io::{
+        cursor::CursorRequest,
+        inputkind::{InputKind, InputKindTracker},
+        platformservices::PlatformServices,
+        rumble::RumblePlayer,
+    },
+    lua_env::{
+        lua_event::EventType,
+        lua_io::{input_kind_to_lua, text_composition_to_lua, touch_to_lua},
+        print_lua_error_from_error,
+    },
+};
 use std::collections::HashMap;
 use vectarine_plugin_sdk::mlua::IntoLua;
 use vectarine_plugin_sdk::sdl2::{self, event::Event, keyboard::Scancode, video::FullscreenType};
 
+pub mod clipboard;
+pub mod contentpack;
+pub mod cursor;
 pub mod dummyfs;
+pub mod enginefs;
+pub mod framepacing;
 pub mod fs;
+pub mod gamepad;
+pub mod inputkind;
 pub mod localfs;
+pub mod messagebox;
+pub mod paths;
+pub mod platformservices;
+pub mod rumble;
 pub mod time;
 pub mod zipfs;
 
@@ -29,11 +57,51 @@ pub struct TouchState {
     pub pressure: f32,
 }
 
+/// How the two fingers on the screen moved since the previous frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pinch {
+    /// Ratio between the current and the previous distance between the fingers.
+    pub scale: f32,
+    /// Angle in radians, counter-clockwise.
+    pub rotation: f32,
+}
+
+/// The text being composed with an input method, like the syllables of a Japanese word before it is converted. It is
+/// typed once the player confirms it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextComposition {
+    pub text: String,
+    /// The position of the cursor in the text, in characters.
+    pub cursor: i32,
+    /// The number of characters selected after the cursor.
+    pub selection_length: i32,
+}
+
+/// Set with `Io.startTextInput` and `Io.stopTextInput`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextInputRequest {
+    /// The rectangle of the text field in window pixels, so that the input method shows its candidates next to it.
+    Start(Option<sdl2::rect::Rect>),
+    Stop,
+}
+
+/// SDL also sends mouse events for touches, with this as the mouse id. We ignore them and handle the fingers ourselves.
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+/// Mouse movements smaller than this, in pixels, are noise from a bumped desk and do not change the active input kind.
+const MOUSE_MOTION_NOISE: i32 = 3;
+
 #[derive(Debug)]
 pub struct IoEnvState {
     // Inputs
     pub window_width: u32,
     pub window_height: u32,
+    /// The part of the window the game is drawn in. It is smaller than the window when the aspect policy adds bars.
+    pub game_viewport: Viewport,
+    /// The number of window pixels per game pixel, a whole number in pixel perfect mode when the window is large enough.
+    pub pixel_scale: f32,
+    /// The borders of the window hidden by notches, in drawable pixels. Only known on the web.
+    pub safe_area_insets: SafeAreaInsets,
     pub is_window_minimized: bool,
     pub screen_width: u32,
     pub screen_height: u32,
@@ -41,19 +109,63 @@ pub struct IoEnvState {
     pub px_ratio_y: f32,
     pub mouse_state: MouseState,
     pub current_touches: HashMap<(i64, i64), TouchState>,
+    /// The touches at the start of the frame, used to compute how the fingers moved.
+    pub previous_touches: HashMap<(i64, i64), TouchState>,
+    /// The first finger that touched the screen drives the mouse, so that games made for the mouse work with a touch screen.
+    pub primary_touch: Option<(i64, i64)>,
     pub keyboard_state: HashMap<Scancode, bool>,
     pub keyboard_just_pressed_state: HashMap<Scancode, bool>,
     // The text typed since the last frame.
     pub text_input: String,
+    /// The text being composed with an input method, None when the player is not composing.
+    pub text_composition: Option<TextComposition>,
+    /// Whether the keyboard types text. It shows the keyboard of phones and enables the input methods.
+    pub is_text_input_active: bool,
+    /// The kind of device the player used last.
+    pub input_kind: InputKindTracker,
 
     pub start_time: std::time::Instant,
+    /// Index of the current fixed tick, only used when the project has a `fixed_tick_rate`.
+    pub tick_count: u64,
+    pub tick_duration: Option<std::time::Duration>,
+    /// How far the current frame is between the last tick and the next one, between 0 and 1.
+    pub tick_alpha: f32,
+    /// The number of frames that were updated before the current one.
+    pub frame_index: u64,
+    /// The sum of the durations passed to `Update`, which stops when the game is paused by the editor.
+    pub frame_time: std::time::Duration,
 
     // Outputs
+    pub aspect_policy: AspectPolicy,
+    /// Set with `Graphics.setPixelPerfect`, it replaces the aspect policy.
+    pub pixel_perfect: Option<PixelPerfect>,
     pub is_window_resizeable: bool,
     pub center_window_request: bool,
+    /// Set with `Io.setTargetFps`. The runtime waits between frames so that the game runs at most this fast.
+    pub target_fps: Option<u32>,
+    /// Set with `Io.setVsync`, applied by the runtime before the next frame is shown.
+    pub vsync_mode: framepacing::VsyncMode,
     pub fullscreen_state_request: Option<FullscreenType>,
     pub window_target_size: Option<(u32, u32)>,
     pub window_title: Option<String>,
+    /// Set with `Io.setCursor`, `Io.setSystemCursor` and `Io.hideCursor`, applied by the runtime before the next frame.
+    pub cursor_request: Option<CursorRequest>,
+    /// Set with `Io.startTextInput` and `Io.stopTextInput`, applied by the runtime before the next frame.
+    pub text_input_request: Option<TextInputRequest>,
+    /// Set with `Io.onTextInput`. Called with the text typed, like the `textInput` event.
+    pub text_input_handler: Option<vectarine_plugin_sdk::mlua::Function>,
+    /// Text-to-speech and notifications, used by `Io.speak` and `Io.notify`.
+    pub platform_services: PlatformServices,
+    /// Set with `Io.onQuitRequested`. Closing the window calls it instead of quitting.
+    pub quit_handler: Option<vectarine_plugin_sdk::mlua::Function>,
+    /// Set during the frame in which the player tried to close the window and the quit handler was called.
+    pub is_quit_deferred: bool,
+    /// Set by `Io.quit`, the game quits at the end of the frame.
+    pub is_quit_requested: bool,
+    /// Set in the editor when the game quit, the editor stops it then.
+    pub is_quit_accepted: bool,
+    /// The patterns of `Io.defineRumble` and the ones playing on each gamepad.
+    pub rumble: RumblePlayer,
 }
 
 impl Default for IoEnvState {
@@ -61,6 +173,9 @@ impl Default for IoEnvState {
         Self {
             window_width: 800,
             window_height: 600,
+            game_viewport: Viewport::from_size(800, 600),
+            pixel_scale: 1.0,
+            safe_area_insets: SafeAreaInsets::default(),
             screen_width: 0,
             screen_height: 0,
             is_window_minimized: false,
@@ -68,27 +183,103 @@ impl Default for IoEnvState {
             px_ratio_y: 1.0,
             mouse_state: MouseState::default(),
             current_touches: HashMap::new(),
+            previous_touches: HashMap::new(),
+            primary_touch: None,
             keyboard_state: HashMap::new(),
             keyboard_just_pressed_state: HashMap::new(),
             text_input: String::new(),
+            text_composition: None,
+            is_text_input_active: true,
+            input_kind: InputKindTracker::default(),
 
             start_time: std::time::Instant::now(),
+            tick_count: 0,
+            tick_duration: None,
+            tick_alpha: 0.0,
+            frame_index: 0,
+            frame_time: std::time::Duration::ZERO,
 
+            pixel_perfect: None,
             is_window_resizeable: false,
             window_target_size: None,
             fullscreen_state_request: None,
             center_window_request: false,
+            target_fps: None,
+            vsync_mode: framepacing::VsyncMode::default(),
             window_title: None,
+            cursor_request: None,
+            text_input_request: None,
+            text_input_handler: None,
+            platform_services: PlatformServices::default(),
+            quit_handler: None,
+            is_quit_deferred: false,
+            is_quit_requested: false,
+            is_quit_accepted: false,
+            rumble: RumblePlayer::default(),
         }
     }
 }
 
-pub fn process_events<'a>(
-    game: &mut Game,
-    events: impl Iterator<Item = &'a sdl2::event::Event>,
-    framebuffer_width: f32,
-    framebuffer_height: f32,
-) {
+/// What a game does when the player closes its window, see `defer_quit`.
+#[derive(Debug)]
+pub enum QuitRequest {
+    /// The game has no quit handler.
+    QuitNow,
+    CallHandler(vectarine_plugin_sdk::mlua::Function),
+    /// The handler was already called during this frame.
+    AlreadyDeferred,
+}
+
+/// What a game does at the end of a frame, see `take_quit_decision`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuitDecision {
+    KeepRunning,
+    Quit,
+    /// The game called `Io.quit` in the editor while the player did not try to close its window.
+    IgnoredInEditor,
+}
+
+/// Called when the player closes the window. Returns true when the game quits right away, because it did not set a
+/// handler with `Io.onQuitRequested`. Otherwise the handler is called instead, at most once per frame, and the game
+/// quits when it calls `Io.quit` during the frame.
+pub fn request_quit(game: &mut Game) -> bool {
+    let request = defer_quit(&mut game.lua_env.env_state.borrow_mut());
+    match request {
+        QuitRequest::QuitNow => true,
+        QuitRequest::CallHandler(quit_handler) => {
+            if let Err(err) = quit_handler.call::<()>(()) {
+                print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+            }
+            false
+        }
+        QuitRequest::AlreadyDeferred => false,
+    }
+}
+
+pub fn defer_quit(state: &mut IoEnvState) -> QuitRequest {
+    let Some(quit_handler) = state.quit_handler.clone() else {
+        return QuitRequest::QuitNow;
+    };
+    if std::mem::replace(&mut state.is_quit_deferred, true) {
+        return QuitRequest::AlreadyDeferred;
+    }
+    QuitRequest::CallHandler(quit_handler)
+}
+
+/// Called at the end of every frame. The request of the player only lasts for the frame in which the handler was
+/// called: a handler that did not call `Io.quit`, like after "Cancel" was pressed, canceled it.
+pub fn take_quit_decision(state: &mut IoEnvState, in_editor: bool) -> QuitDecision {
+    let is_quit_deferred = std::mem::take(&mut state.is_quit_deferred);
+    if !std::mem::take(&mut state.is_quit_requested) {
+        return QuitDecision::KeepRunning;
+    }
+    if in_editor && !is_quit_deferred {
+        return QuitDecision::IgnoredInEditor;
+    }
+    QuitDecision::Quit
+}
+
+pub fn process_events<'a>(game: &mut Game, events: impl Iterator<Item = &'a sdl2::event::Event>) {
     {
         let mut env_state = game.lua_env.env_state.borrow_mut();
         env_state.keyboard_just_pressed_state.clear();
@@ -97,13 +288,35 @@ pub fn process_events<'a>(
         env_state.mouse_state.wheel_x = 0.0;
         env_state.mouse_state.wheel_y = 0.0;
         env_state.text_input.clear();
+        env_state.previous_touches = env_state.current_touches.clone();
     }
+    gamepad::start_gamepad_frame();
 
     for event in events {
+        if let Some(input_kind) = get_input_kind(event) {
+            report_input_kind(game, input_kind);
+            // The frame consuming the event is the one whose swap shows its effect.
+            game.lua_env
+                .latency
+                .borrow_mut()
+                .record_input_event(event.get_timestamp());
+        }
+        if let Event::ControllerDeviceRemoved { which, .. } = event {
+            game.lua_env.env_state.borrow_mut().rumble.forget(*which);
+        }
+        if gamepad::process_gamepad_event(event) {
+            continue;
+        }
         match event {
             Event::Quit { .. } => {
-                std::process::exit(0);
+                if request_quit(game) {
+                    std::process::exit(0);
+                }
             }
+            Event::MouseButtonDown { which, .. }
+            | Event::MouseButtonUp { which, .. }
+            | Event::MouseMotion { which, .. }
+                if *which == TOUCH_MOUSE_ID => {}
             Event::KeyUp { scancode, .. } => {
                 let Some(scancode) = scancode else {
                     return;
@@ -147,10 +360,13 @@ pub fn process_events<'a>(
             }
             Event::TextInput { text, .. } => {
                 let lua = &game.lua_env.lua_handle.lua;
-                {
+                let text_input_handler = {
                     let mut env_state = game.lua_env.env_state.borrow_mut();
                     env_state.text_input.push_str(text);
-                }
+                    // The composed text was confirmed and is the text typed.
+                    env_state.text_composition = None;
+                    env_state.text_input_handler.clone()
+                };
                 let lua_res = game.lua_env.default_events.text_input_event.trigger(
                     text.clone()
                         .into_lua(lua)
@@ -159,6 +375,35 @@ pub fn process_events<'a>(
                 if let Err(err) = lua_res {
                     print_lua_error_from_error(&game.lua_env.lua_handle, &err);
                 }
+                if let Some(text_input_handler) = text_input_handler
+                    && let Err(err) = text_input_handler.call::<()>(text.clone())
+                {
+                    print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+                }
+            }
+            Event::TextEditing {
+                text,
+                start,
+                length,
+                ..
+            } => {
+                let lua = &game.lua_env.lua_handle.lua;
+                let composition = (!text.is_empty()).then(|| TextComposition {
+                    text: text.clone(),
+                    cursor: *start,
+                    selection_length: *length,
+                });
+                game.lua_env.env_state.borrow_mut().text_composition = composition.clone();
+                let lua_res =
+                    text_composition_to_lua(lua, composition.as_ref()).and_then(|composition| {
+                        game.lua_env
+                            .default_events
+                            .text_editing_event
+                            .trigger(composition)
+                    });
+                if let Err(err) = lua_res {
+                    print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+                }
             }
             Event::MouseButtonUp { mouse_btn, .. } => {
                 {
@@ -232,10 +477,17 @@ pub fn process_events<'a>(
                 let mut env_state = game.lua_env.env_state.borrow_mut();
                 let px_ratio_x = env_state.px_ratio_x; // convert between real and fake pixels
                 let px_ratio_y = env_state.px_ratio_y;
+                // The position is relative to the game area, so that it matches what is drawn when there are bars.
+                let (mouse_x, mouse_y) = window_px_to_gl(
+                    &env_state.game_viewport,
+                    env_state.window_height,
+                    *x as f32 * px_ratio_x,
+                    *y as f32 * px_ratio_y,
+                );
                 let mouse_state = &mut env_state.mouse_state;
 
-                mouse_state.x = (*x as f32) * px_ratio_x / framebuffer_width * 2.0 - 1.0;
-                mouse_state.y = -((*y as f32) * px_ratio_y / framebuffer_height * 2.0 - 1.0);
+                mouse_state.x = mouse_x;
+                mouse_state.y = mouse_y;
                 mouse_state.is_left_down = mousestate.left();
                 mouse_state.is_right_down = mousestate.right();
             }
@@ -246,8 +498,21 @@ pub fn process_events<'a>(
                 y,
                 pressure,
                 ..
+            } => {
+                let (touch, is_primary) = {
+                    let mut env_state = game.lua_env.env_state.borrow_mut();
+                    press_touch(&mut env_state, *touch_id, *finger_id, *x, *y, *pressure)
+                };
+                if is_primary {
+                    trigger_left_button_event(game, &game.lua_env.default_events.mouse_down_event);
+                }
+                trigger_touch_event(
+                    game,
+                    &game.lua_env.default_events.touch_started_event,
+                    &touch,
+                );
             }
-            | Event::FingerMotion {
+            Event::FingerMotion {
                 touch_id,
                 finger_id,
                 x,
@@ -255,25 +520,145 @@ pub fn process_events<'a>(
                 pressure,
                 ..
             } => {
-                let mut env_state = game.lua_env.env_state.borrow_mut();
-                update_touch(&mut env_state, *touch_id, *finger_id, *x, *y, *pressure);
+                let touch = {
+                    let mut env_state = game.lua_env.env_state.borrow_mut();
+                    update_touch(&mut env_state, *touch_id, *finger_id, *x, *y, *pressure)
+                };
+                trigger_touch_event(game, &game.lua_env.default_events.touch_moved_event, &touch);
             }
             Event::FingerUp {
                 touch_id,
                 finger_id,
+                x,
+                y,
+                pressure,
                 ..
             } => {
-                remove_touch(
-                    &mut game.lua_env.env_state.borrow_mut(),
-                    *touch_id,
-                    *finger_id,
-                );
+                let (touch, is_primary) = {
+                    let mut env_state = game.lua_env.env_state.borrow_mut();
+                    release_touch(&mut env_state, *touch_id, *finger_id, *x, *y, *pressure)
+                };
+                if is_primary {
+                    trigger_left_button_event(game, &game.lua_env.default_events.mouse_up_event);
+                }
+                trigger_touch_event(game, &game.lua_env.default_events.touch_ended_event, &touch);
             }
             _ => {}
         }
     }
 }
 
+/// Returns the kind of device that sent the event, when the event shows that the player is using it.
+fn get_input_kind(event: &Event) -> Option<InputKind> {
+    match event {
+        Event::MouseButtonDown { which, .. }
+        | Event::MouseMotion { which, .. }
+        | Event::MouseWheel { which, .. }
+            if *which == TOUCH_MOUSE_ID =>
+        {
+            None
+        }
+        Event::MouseMotion { xrel, yrel, .. } if xrel.abs() + yrel.abs() < MOUSE_MOTION_NOISE => {
+            None
+        }
+        Event::KeyDown { .. }
+        | Event::MouseButtonDown { .. }
+        | Event::MouseMotion { .. }
+        | Event::MouseWheel { .. } => Some(InputKind::KeyboardMouse),
+        Event::FingerDown { .. } | Event::FingerMotion { .. } => Some(InputKind::Touch),
+        Event::ControllerAxisMotion { value, .. }
+            if (*value as f32 / i16::MAX as f32).abs() < gamepad::STICK_DEAD_ZONE =>
+        {
+            None
+        }
+        Event::ControllerButtonDown { which, .. } | Event::ControllerAxisMotion { which, .. } => {
+            Some(InputKind::Gamepad {
+                id: *which,
+                gamepad_type: gamepad::get_gamepad_type(*which).unwrap_or_default(),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn report_input_kind(game: &Game, input_kind: InputKind) {
+    let has_changed = game
+        .lua_env
+        .env_state
+        .borrow_mut()
+        .input_kind
+        .report_input(input_kind, std::time::Instant::now());
+    if !has_changed {
+        return;
+    }
+    let lua_res = input_kind_to_lua(&game.lua_env.lua_handle.lua, input_kind).and_then(|kind| {
+        game.lua_env
+            .default_events
+            .input_kind_changed_event
+            .trigger(vectarine_plugin_sdk::mlua::Value::Table(kind))
+    });
+    if let Err(err) = lua_res {
+        print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+    }
+}
+
+fn trigger_left_button_event(game: &Game, event: &EventType) {
+    let lua_res = event.trigger(
+        "left"
+            .into_lua(&game.lua_env.lua_handle.lua)
+            .expect("Failed to convert mouse button to Lua"),
+    );
+    if let Err(err) = lua_res {
+        print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+    }
+}
+
+fn trigger_touch_event(game: &Game, event: &EventType, touch: &TouchState) {
+    let lua_res = touch_to_lua(&game.lua_env.lua_handle.lua, touch)
+        .and_then(|touch| event.trigger(vectarine_plugin_sdk::mlua::Value::Table(touch)));
+    if let Err(err) = lua_res {
+        print_lua_error_from_error(&game.lua_env.lua_handle, &err);
+    }
+}
+
+/// Returns the new touch and whether it became the primary touch.
+fn press_touch(
+    env_state: &mut IoEnvState,
+    touch_id: i64,
+    finger_id: i64,
+    x: f32,
+    y: f32,
+    pressure: f32,
+) -> (TouchState, bool) {
+    let is_primary = env_state.primary_touch.is_none();
+    if is_primary {
+        env_state.primary_touch = Some((touch_id, finger_id));
+        env_state.mouse_state.is_left_down = true;
+        env_state.mouse_state.is_left_just_pressed = true;
+    }
+    let touch = update_touch(env_state, touch_id, finger_id, x, y, pressure);
+    (touch, is_primary)
+}
+
+/// Returns the removed touch and whether it was the primary touch.
+fn release_touch(
+    env_state: &mut IoEnvState,
+    touch_id: i64,
+    finger_id: i64,
+    x: f32,
+    y: f32,
+    pressure: f32,
+) -> (TouchState, bool) {
+    let touch = update_touch(env_state, touch_id, finger_id, x, y, pressure);
+    env_state.current_touches.remove(&(touch_id, finger_id));
+    let is_primary = env_state.primary_touch == Some((touch_id, finger_id));
+    if is_primary {
+        env_state.primary_touch = None;
+        env_state.mouse_state.is_left_down = false;
+    }
+    (touch, is_primary)
+}
+
 fn update_touch(
     env_state: &mut IoEnvState,
     touch_id: i64,
@@ -281,20 +666,70 @@ fn update_touch(
     x: f32,
     y: f32,
     pressure: f32,
-) {
-    env_state.current_touches.insert(
-        (touch_id, finger_id),
-        TouchState {
-            id: finger_id,
-            x: x * 2.0 - 1.0,
-            y: 1.0 - y * 2.0,
-            pressure,
-        },
+) -> TouchState {
+    // SDL gives the position of fingers relative to the size of the window.
+    let (x, y) = window_px_to_gl(
+        &env_state.game_viewport,
+        env_state.window_height,
+        x * env_state.window_width as f32,
+        y * env_state.window_height as f32,
     );
+    let touch = TouchState {
+        id: finger_id,
+        x,
+        y,
+        pressure,
+    };
+    if env_state.primary_touch == Some((touch_id, finger_id)) {
+        env_state.mouse_state.x = touch.x;
+        env_state.mouse_state.y = touch.y;
+    }
+    env_state
+        .current_touches
+        .insert((touch_id, finger_id), touch.clone());
+    touch
 }
 
-fn remove_touch(env_state: &mut IoEnvState, touch_id: i64, finger_id: i64) {
-    env_state.current_touches.remove(&(touch_id, finger_id));
+/// Returns how the fingers moved since the start of the frame, when exactly two fingers are on the screen.
+pub fn get_pinch(env_state: &IoEnvState) -> Option<Pinch> {
+    let mut touches = env_state.current_touches.iter();
+    let (Some((first_key, first)), Some((second_key, second)), None) =
+        (touches.next(), touches.next(), touches.next())
+    else {
+        return None;
+    };
+    let (Some(previous_first), Some(previous_second)) = (
+        env_state.previous_touches.get(first_key),
+        env_state.previous_touches.get(second_key),
+    ) else {
+        // A finger just landed, it did not move yet.
+        return Some(Pinch {
+            scale: 1.0,
+            rotation: 0.0,
+        });
+    };
+    // The coordinates are stretched by the aspect ratio of the window, we measure in pixels instead.
+    let half_width = env_state.game_viewport.width as f32 / 2.0;
+    let half_height = env_state.game_viewport.height as f32 / 2.0;
+    let to_pixels =
+        |a: &TouchState, b: &TouchState| ((b.x - a.x) * half_width, (b.y - a.y) * half_height);
+    let (dx, dy) = to_pixels(first, second);
+    let (previous_dx, previous_dy) = to_pixels(previous_first, previous_second);
+
+    let distance = dx.hypot(dy);
+    let previous_distance = previous_dx.hypot(previous_dy);
+    let scale = if previous_distance > f32::EPSILON {
+        distance / previous_distance
+    } else {
+        1.0
+    };
+    let mut rotation = dy.atan2(dx) - previous_dy.atan2(previous_dx);
+    if rotation > std::f32::consts::PI {
+        rotation -= std::f32::consts::TAU;
+    } else if rotation <= -std::f32::consts::PI {
+        rotation += std::f32::consts::TAU;
+    }
+    Some(Pinch { scale, rotation })
 }
 
 fn mouse_button_to_str(mouse_btn: sdl2::mouse::MouseButton) -> &'static str {
@@ -311,7 +746,72 @@ fn mouse_button_to_str(mouse_btn: sdl2::mouse::MouseButton) -> &'static str {
 
 #[cfg(test)]
 mod tests {
-    use super::{IoEnvState, remove_touch, update_touch};
+    use vectarine_plugin_sdk::mlua::Lua;
+
+    use super::{
+        IoEnvState, QuitDecision, QuitRequest, defer_quit, get_pinch, press_touch, release_touch,
+        take_quit_decision, update_touch,
+    };
+    use crate::graphics::glframebuffer::Viewport;
+
+    #[test]
+    fn closing_the_window_calls_the_quit_handler_once_per_frame() {
+        let lua = Lua::new();
+        let mut state = IoEnvState::default();
+        assert!(matches!(defer_quit(&mut state), QuitRequest::QuitNow));
+
+        state.quit_handler = Some(
+            lua.create_function(|_, ()| Ok(()))
+                .expect("the handler is created"),
+        );
+        assert!(matches!(
+            defer_quit(&mut state),
+            QuitRequest::CallHandler(_)
+        ));
+        assert!(matches!(
+            defer_quit(&mut state),
+            QuitRequest::AlreadyDeferred
+        ));
+        // The handler calls Io.quit.
+        state.is_quit_requested = true;
+        assert_eq!(take_quit_decision(&mut state, true), QuitDecision::Quit);
+    }
+
+    #[test]
+    fn a_canceled_quit_does_not_let_the_game_quit_the_editor_later() {
+        let lua = Lua::new();
+        let mut state = IoEnvState::default();
+        state.quit_handler = Some(
+            lua.create_function(|_, ()| Ok(()))
+                .expect("the handler is created"),
+        );
+        assert!(matches!(
+            defer_quit(&mut state),
+            QuitRequest::CallHandler(_)
+        ));
+        // The player pressed "Cancel", the handler did not call Io.quit.
+        assert_eq!(
+            take_quit_decision(&mut state, true),
+            QuitDecision::KeepRunning
+        );
+
+        // The quit button of a menu of the game.
+        state.is_quit_requested = true;
+        assert_eq!(
+            take_quit_decision(&mut state, true),
+            QuitDecision::IgnoredInEditor
+        );
+        state.is_quit_requested = true;
+        assert_eq!(take_quit_decision(&mut state, false), QuitDecision::Quit);
+
+        // Closing the window again calls the handler again.
+        assert!(matches!(
+            defer_quit(&mut state),
+            QuitRequest::CallHandler(_)
+        ));
+        state.is_quit_requested = true;
+        assert_eq!(take_quit_decision(&mut state, true), QuitDecision::Quit);
+    }
 
     #[test]
     fn touch_positions_use_opengl_coordinates() {
@@ -354,10 +854,54 @@ mod tests {
             20
         );
 
-        remove_touch(&mut state, 1, 10);
+        release_touch(&mut state, 1, 10, 0.5, 0.5, 0.0);
 
         assert_eq!(state.current_touches.len(), 1);
         assert!(!state.current_touches.contains_key(&(1, 10)));
         assert!(state.current_touches.contains_key(&(1, 20)));
     }
+
+    #[test]
+    fn first_finger_drives_the_mouse() {
+        let mut state = IoEnvState::default();
+
+        let (_, is_primary) = press_touch(&mut state, 1, 10, 0.25, 0.25, 1.0);
+        assert!(is_primary);
+        let (_, is_primary) = press_touch(&mut state, 1, 20, 1.0, 1.0, 1.0);
+        assert!(!is_primary);
+        update_touch(&mut state, 1, 20, 0.0, 0.0, 1.0);
+        assert!(state.mouse_state.is_left_down);
+        assert_eq!(state.mouse_state.x, -0.5);
+        assert_eq!(state.mouse_state.y, 0.5);
+
+        let (_, was_primary) = release_touch(&mut state, 1, 10, 0.75, 0.75, 0.0);
+        assert!(was_primary);
+        assert!(!state.mouse_state.is_left_down);
+        assert_eq!(state.mouse_state.x, 0.5);
+        assert_eq!(state.primary_touch, None);
+    }
+
+    #[test]
+    fn pinch_measures_scale_and_rotation_in_pixels() {
+        let mut state = IoEnvState {
+            window_width: 200,
+            window_height: 100,
+            game_viewport: Viewport::from_size(200, 100),
+            ..Default::default()
+        };
+        assert_eq!(get_pinch(&state), None);
+
+        press_touch(&mut state, 1, 10, 0.5, 0.5, 1.0);
+        press_touch(&mut state, 1, 20, 0.625, 0.5, 1.0);
+        let pinch = get_pinch(&state).expect("two fingers are down");
+        assert_eq!(pinch.scale, 1.0);
+        assert_eq!(pinch.rotation, 0.0);
+
+        state.previous_touches = state.current_touches.clone();
+        // The second finger moves from 25px to the right of the first one to 50px above it.
+        update_touch(&mut state, 1, 20, 0.5, 0.0, 1.0);
+        let pinch = get_pinch(&state).expect("two fingers are down");
+        assert!((pinch.scale - 2.0).abs() < 1e-5);
+        assert!((pinch.rotation - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+    }
 }
@@ -0,0 +1,74 @@
+//! The clipboard, for `Io.getClipboardText` and `Io.setClipboardText`.
+//!
+//! The clipboard of SDL does not reach the browser on the web, so the page keeps the text that was last pasted in it
+//! or copied by the game.
+
+/// Returns None when the clipboard is empty or does not contain text.
+pub fn get_clipboard_text() -> Option<String> {
+    #[cfg(target_os = "emscripten")]
+    {
+        web::get_clipboard_text().filter(|text| !text.is_empty())
+    }
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        desktop::get_clipboard_text().filter(|text| !text.is_empty())
+    }
+}
+
+pub fn set_clipboard_text(text: &str) -> Result<(), String> {
+    #[cfg(target_os = "emscripten")]
+    {
+        web::set_clipboard_text(text)
+    }
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        desktop::set_clipboard_text(text)
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+mod desktop {
+    use std::ffi::{CStr, CString};
+
+    use vectarine_plugin_sdk::sdl2::sys;
+
+    pub fn get_clipboard_text() -> Option<String> {
+        unsafe {
+            if sys::SDL_HasClipboardText() != sys::SDL_bool::SDL_TRUE {
+                return None;
+            }
+            let text = sys::SDL_GetClipboardText();
+            if text.is_null() {
+                return None;
+            }
+            let result = CStr::from_ptr(text).to_string_lossy().into_owned();
+            sys::SDL_free(text.cast());
+            Some(result)
+        }
+    }
+
+    pub fn set_clipboard_text(text: &str) -> Result<(), String> {
+        let text = CString::new(text)
+            .map_err(|_| "The text contains a null character, it cannot be copied".to_string())?;
+        let result = unsafe { sys::SDL_SetClipboardText(text.as_ptr()) };
+        if result != 0 {
+            return Err(vectarine_plugin_sdk::sdl2::get_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+mod web {
+    use emscripten_functions::emscripten::{run_script, run_script_string};
+
+    pub fn get_clipboard_text() -> Option<String> {
+        run_script_string("vectarine.getClipboardText()")
+    }
+
+    pub fn set_clipboard_text(text: &str) -> Result<(), String> {
+        let text = serde_json::to_string(text).map_err(|e| e.to_string())?;
+        run_script(&format!("vectarine.setClipboardText({text});"));
+        Ok(())
+    }
+}
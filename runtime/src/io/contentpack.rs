@@ -0,0 +1,244 @@
+//! Content packs are bundles mounted over the files of the game while it runs, like downloadable content or the levels
+//! of a game split in several downloads. They are zip files like bundle.vecta, with their files in `gamedata/` and a
+//! `pack.toml` manifest naming the game they belong to.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use vectarine_plugin_sdk::serde::{Deserialize, Serialize};
+
+use crate::io::zipfs::ZipFileSystem;
+
+/// The path of the manifest inside a content pack.
+pub const PACK_MANIFEST_PATH: &str = "pack.toml";
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde")]
+pub struct PackManifest {
+    pub name: String,
+    /// The title of the game the pack was exported from.
+    pub game: String,
+    /// The version of the game the pack was exported from. The pack mounts on this version and on the later ones with
+    /// the same major version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+}
+
+/// The game running, to check that the packs mounted belong to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameIdentity {
+    pub title: String,
+    pub version: Option<String>,
+}
+
+impl PackManifest {
+    pub fn check_compatibility(&self, game: &GameIdentity) -> Result<(), String> {
+        if self.game != game.title {
+            return Err(format!(
+                "The pack {} is for the game '{}', not '{}'",
+                self.name, self.game, game.title
+            ));
+        }
+        let Some(pack_game_version) = &self.game_version else {
+            return Ok(());
+        };
+        let game_version = game.version.as_deref().unwrap_or("0");
+        if !is_compatible_version(game_version, pack_game_version) {
+            return Err(format!(
+                "The pack {} needs version {} of the game, the game is version {}",
+                self.name, pack_game_version, game_version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parses a version like "1.4.2". Missing numbers count as 0, so "1.4" is "1.4.0".
+fn parse_version(version: &str) -> Option<[u64; 3]> {
+    let mut numbers = [0; 3];
+    for (index, part) in version
+        .trim()
+        .trim_start_matches('v')
+        .split('.')
+        .enumerate()
+    {
+        *numbers.get_mut(index)? = part.parse().ok()?;
+    }
+    Some(numbers)
+}
+
+/// A pack exported for `pack_game_version` works with the same version and the later ones until the major version
+/// changes. Versions that are not numbers must be equal.
+pub fn is_compatible_version(game_version: &str, pack_game_version: &str) -> bool {
+    match (
+        parse_version(game_version),
+        parse_version(pack_game_version),
+    ) {
+        (Some(game), Some(pack)) => game[0] == pack[0] && game >= pack,
+        _ => game_version.trim() == pack_game_version.trim(),
+    }
+}
+
+/// Where the file of a resource is inside a pack. The path of the resource is relative to game.vecta.
+pub fn path_in_pack(resource_path: &Path) -> PathBuf {
+    let relative_path = resource_path
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect::<PathBuf>();
+    Path::new("gamedata").join(relative_path)
+}
+
+pub struct MountedPack {
+    pub manifest: PackManifest,
+    /// The packs with a higher priority hide the files of the others. The files of the game have the lowest priority.
+    pub priority: i32,
+    pub files: Rc<ZipFileSystem>,
+}
+
+impl MountedPack {
+    pub fn open(data: Vec<u8>, priority: i32) -> Result<Self, String> {
+        let files = ZipFileSystem::new(data).map_err(|_| "Not a valid bundle".to_string())?;
+        let manifest = files
+            .read_file_sync(PACK_MANIFEST_PATH)
+            .ok_or_else(|| format!("The bundle has no {PACK_MANIFEST_PATH}"))?;
+        let manifest = vectarine_plugin_sdk::toml::from_str::<PackManifest>(
+            String::from_utf8_lossy(&manifest).as_ref(),
+        )
+        .map_err(|err| format!("Malformed {PACK_MANIFEST_PATH}: {err}"))?;
+        Ok(Self {
+            manifest,
+            priority,
+            files: Rc::new(files),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    pub fn contains(&self, resource_path: &Path) -> bool {
+        self.files.contains(&path_in_pack(resource_path))
+    }
+}
+
+/// The packs mounted, from the highest priority to the lowest. Among packs with the same priority, the last mounted
+/// comes first.
+#[derive(Default)]
+pub struct PackMounts {
+    packs: Vec<MountedPack>,
+}
+
+impl PackMounts {
+    pub fn mount(&mut self, pack: MountedPack) -> Result<(), String> {
+        if self
+            .packs
+            .iter()
+            .any(|mounted| mounted.name() == pack.name())
+        {
+            return Err(format!("The pack {} is already mounted", pack.name()));
+        }
+        let index = self
+            .packs
+            .iter()
+            .position(|mounted| mounted.priority <= pack.priority)
+            .unwrap_or(self.packs.len());
+        self.packs.insert(index, pack);
+        Ok(())
+    }
+
+    pub fn unmount(&mut self, name: &str) -> Option<MountedPack> {
+        let index = self.packs.iter().position(|pack| pack.name() == name)?;
+        Some(self.packs.remove(index))
+    }
+
+    /// The pack the file of the resource is read from, or None when it is read from the files of the game.
+    pub fn find(&self, resource_path: &Path) -> Option<&MountedPack> {
+        self.packs.iter().find(|pack| pack.contains(resource_path))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MountedPack> {
+        self.packs.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Cursor, Write},
+        path::Path,
+    };
+
+    use zip::write::SimpleFileOptions;
+
+    use super::{GameIdentity, MountedPack, PackMounts, is_compatible_version};
+
+    fn make_pack(name: &str, priority: i32, files: &[&str]) -> MountedPack {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let manifest = format!("name = \"{name}\"\ngame = \"Test\"\ngame_version = \"1.2\"\n");
+        let entries = [("pack.toml", manifest.as_str())]
+            .into_iter()
+            .chain(files.iter().map(|file| (*file, name)));
+        for (path, content) in entries {
+            zip.start_file(path, SimpleFileOptions::default())
+                .expect("the entry is created");
+            zip.write_all(content.as_bytes())
+                .expect("the entry is written");
+        }
+        let data = zip.finish().expect("the zip is written").into_inner();
+        MountedPack::open(data, priority).expect("the pack is valid")
+    }
+
+    #[test]
+    fn packs_with_a_higher_priority_hide_the_others() {
+        let mut mounts = PackMounts::default();
+        mounts
+            .mount(make_pack("base", 0, &["gamedata/a.png", "gamedata/b.png"]))
+            .expect("the pack mounts");
+        mounts
+            .mount(make_pack("patch", 0, &["gamedata/b.png"]))
+            .expect("the pack mounts");
+        mounts
+            .mount(make_pack("mod", 5, &["gamedata/a.png"]))
+            .expect("the pack mounts");
+        assert!(mounts.mount(make_pack("mod", 0, &[])).is_err());
+
+        let source = |path: &str| mounts.find(Path::new(path)).map(MountedPack::name);
+        assert_eq!(source("a.png"), Some("mod"));
+        assert_eq!(source("./b.png"), Some("patch"));
+        assert_eq!(source("c.png"), None);
+
+        mounts.unmount("mod");
+        mounts.unmount("patch");
+        let source = |path: &str| mounts.find(Path::new(path)).map(MountedPack::name);
+        assert_eq!(source("a.png"), Some("base"));
+        assert_eq!(source("b.png"), Some("base"));
+    }
+
+    #[test]
+    fn packs_mount_on_later_versions_of_the_game() {
+        assert!(is_compatible_version("1.2.0", "1.2"));
+        assert!(is_compatible_version("1.10", "1.2.3"));
+        assert!(!is_compatible_version("1.1.9", "1.2"));
+        assert!(!is_compatible_version("2.0", "1.2"));
+        assert!(is_compatible_version("beta", "beta"));
+
+        let manifest = make_pack("dlc", 0, &[]).manifest;
+        let game = |title: &str, version: Option<&str>| GameIdentity {
+            title: title.to_string(),
+            version: version.map(str::to_string),
+        };
+        assert!(
+            manifest
+                .check_compatibility(&game("Test", Some("1.3")))
+                .is_ok()
+        );
+        assert!(manifest.check_compatibility(&game("Test", None)).is_err());
+        assert!(
+            manifest
+                .check_compatibility(&game("Other", Some("1.3")))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! The cursor of the game, set with `Io.setCursor`, `Io.setSystemCursor` and `Io.hideCursor`.
+
+use std::{
+    path::Path,
+    rc::{Rc, Weak},
+};
+
+use vectarine_plugin_sdk::sdl2::{
+    mouse::{Cursor, MouseUtil, SystemCursor},
+    pixels::PixelFormatEnum,
+    surface::Surface,
+};
+
+use crate::{
+    game_resource::{ResourceId, ResourceManager},
+    graphics::imagepacking::PackedImage,
+};
+
+/// The names accepted by `Io.setSystemCursor`, for the error messages.
+pub const SYSTEM_CURSOR_NAMES: &str = "\"arrow\", \"text\", \"wait\", \"progress\", \"crosshair\", \"hand\", \"move\", \"not_allowed\", \"resize_horizontal\", \"resize_vertical\", \"resize_nwse\" or \"resize_nesw\"";
+
+/// A change of the cursor asked by the game, applied by the runtime before the next frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CursorRequest {
+    /// RGBA pixels, and the point of the image at the position of the mouse.
+    Image {
+        pixels: Vec<u8>,
+        width: u32,
+        height: u32,
+        hotspot: (i32, i32),
+    },
+    System(SystemCursor),
+    Hidden,
+}
+
+pub fn system_cursor_from_name(name: &str) -> Option<SystemCursor> {
+    Some(match name {
+        "arrow" => SystemCursor::Arrow,
+        "text" => SystemCursor::IBeam,
+        "wait" => SystemCursor::Wait,
+        "progress" => SystemCursor::WaitArrow,
+        "crosshair" => SystemCursor::Crosshair,
+        "hand" => SystemCursor::Hand,
+        "move" => SystemCursor::SizeAll,
+        "not_allowed" => SystemCursor::No,
+        "resize_horizontal" => SystemCursor::SizeWE,
+        "resize_vertical" => SystemCursor::SizeNS,
+        "resize_nwse" => SystemCursor::SizeNWSE,
+        "resize_nesw" => SystemCursor::SizeNESW,
+        _ => return None,
+    })
+}
+
+/// SDL does not copy the cursors, so the current one is kept until it is replaced.
+#[derive(Default)]
+pub struct CursorState {
+    cursor: Option<Cursor>,
+}
+
+impl CursorState {
+    pub fn apply(&mut self, request: CursorRequest, mouse: &MouseUtil) -> Result<(), String> {
+        let cursor = match request {
+            CursorRequest::Hidden => {
+                mouse.show_cursor(false);
+                return Ok(());
+            }
+            CursorRequest::System(system_cursor) => Cursor::from_system(system_cursor)?,
+            CursorRequest::Image {
+                mut pixels,
+                width,
+                height,
+                hotspot,
+            } => {
+                let surface = Surface::from_data(
+                    &mut pixels,
+                    width,
+                    height,
+                    width * 4,
+                    PixelFormatEnum::RGBA32,
+                )?;
+                Cursor::from_surface(surface, hotspot.0, hotspot.1)?
+            }
+        };
+        cursor.set();
+        mouse.show_cursor(true);
+        self.cursor = Some(cursor);
+        Ok(())
+    }
+}
+
+/// Reads the pixels of an image for a cursor. They are not kept in memory once the image is loaded, so its file is read
+/// again. For the images packed at export, the region is cut from the page.
+pub fn read_cursor_image(
+    resources: &Rc<ResourceManager>,
+    image_id: ResourceId,
+    callback: impl FnOnce(Option<image::RgbaImage>) + 'static,
+) {
+    let weak_resources: Weak<ResourceManager> = Rc::downgrade(resources);
+    resources.read_resource_file(
+        image_id,
+        Box::new(move |data| {
+            let Some(data) = data else {
+                callback(None);
+                return;
+            };
+            let Some(packed) = PackedImage::parse(&data) else {
+                callback(
+                    image::load_from_memory(&data)
+                        .ok()
+                        .map(|image| image.to_rgba8()),
+                );
+                return;
+            };
+            let page = weak_resources.upgrade().and_then(|resources| {
+                let page_id = resources.get_id_by_path(Path::new(&packed.page))?;
+                Some((resources, page_id))
+            });
+            let Some((resources, page_id)) = page else {
+                callback(None);
+                return;
+            };
+            resources.read_resource_file(
+                page_id,
+                Box::new(move |data| {
+                    let page = data.and_then(|data| image::load_from_memory(&data).ok());
+                    callback(page.map(|page| {
+                        page.crop_imm(packed.x, packed.y, packed.width, packed.height)
+                            .to_rgba8()
+                    }));
+                }),
+            );
+        }),
+    );
+}
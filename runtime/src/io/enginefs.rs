@@ -0,0 +1,101 @@
+use std::path::Path;
+
+use crate::io::{fs::ReadOnlyFileSystem, paths::to_slash_string};
+
+/// Resources whose path starts with this prefix are read from the assets embedded in the engine instead of the
+/// project folder, so that they are available to every game, including exported ones.
+pub const ENGINE_ASSET_PREFIX: &str = "@engine/";
+
+/// The assets embedded in the engine, with their path relative to `@engine/`.
+pub const ENGINE_ASSETS: &[(&str, &[u8])] = &[
+    (
+        "fonts/default.ttf",
+        include_bytes!("../../../assets/Roboto-Regular.ttf"),
+    ),
+    (
+        "textures/white.png",
+        include_bytes!("../../../assets/engine/textures/white.png"),
+    ),
+    (
+        "textures/checkerboard.png",
+        include_bytes!("../../../assets/engine/textures/checkerboard.png"),
+    ),
+    (
+        "shaders/texture.glsl",
+        include_bytes!("../../../assets/engine/shaders/texture.glsl"),
+    ),
+    (
+        "shaders/color.glsl",
+        include_bytes!("../../../assets/engine/shaders/color.glsl"),
+    ),
+    (
+        "shaders/grayscale.glsl",
+        include_bytes!("../../../assets/engine/shaders/grayscale.glsl"),
+    ),
+    (
+        "shaders/lib/noise.glsl",
+        include_bytes!("../../../assets/engine/shaders/lib/noise.glsl"),
+    ),
+    (
+        "shaders/lib/colorspace.glsl",
+        include_bytes!("../../../assets/engine/shaders/lib/colorspace.glsl"),
+    ),
+];
+
+/// Path of the font used to draw text when the game did not load one.
+pub const DEFAULT_FONT_PATH: &str = "@engine/fonts/default.ttf";
+
+pub fn get_engine_asset(path: &str) -> Option<&'static [u8]> {
+    ENGINE_ASSETS
+        .iter()
+        .find(|(asset_path, _)| *asset_path == path)
+        .map(|(_, data)| *data)
+}
+
+/// Returns the path of the asset inside the engine assets if the path starts with `@engine/`.
+pub fn strip_engine_prefix(path: &Path) -> Option<String> {
+    let path = to_slash_string(path)?;
+    path.strip_prefix(ENGINE_ASSET_PREFIX).map(str::to_string)
+}
+
+pub fn is_engine_asset(path: &Path) -> bool {
+    strip_engine_prefix(path).is_some()
+}
+
+/// A read-only file system over the assets embedded in the engine. Paths are relative to `@engine/`.
+pub struct EngineFileSystem;
+impl ReadOnlyFileSystem for EngineFileSystem {
+    fn read_file(&self, path: &Path, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+        let asset = to_slash_string(path).and_then(|path| get_engine_asset(&path));
+        callback(asset.map(<[u8]>::to_vec));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DEFAULT_FONT_PATH, ENGINE_ASSETS, get_engine_asset, strip_engine_prefix};
+    use std::path::Path;
+
+    #[test]
+    fn engine_paths_resolve_to_embedded_assets() {
+        let font_path =
+            strip_engine_prefix(Path::new(DEFAULT_FONT_PATH)).expect("the prefix is stripped");
+        assert_eq!(font_path, "fonts/default.ttf");
+        assert!(get_engine_asset(&font_path).is_some_and(|data| !data.is_empty()));
+
+        assert_eq!(strip_engine_prefix(Path::new("fonts/default.ttf")), None);
+        assert_eq!(get_engine_asset("fonts/missing.ttf"), None);
+    }
+
+    #[test]
+    fn engine_asset_paths_are_unique() {
+        for (i, (path, _)) in ENGINE_ASSETS.iter().enumerate() {
+            assert!(
+                ENGINE_ASSETS[i + 1..]
+                    .iter()
+                    .all(|(other, _)| other != path),
+                "{path} is embedded twice"
+            );
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! The frame rate cap and the vsync mode chosen by the game with `Io.setTargetFps` and `Io.setVsync`.
+
+use vectarine_plugin_sdk::sdl2::video::SwapInterval;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VsyncMode {
+    /// Waits for the display before showing a frame, so the game runs at the refresh rate without tearing.
+    #[default]
+    On,
+    /// Shows the frames as soon as they are drawn.
+    Off,
+    /// Waits for the display, except when the frame is late, which tears a bit instead of halving the frame rate.
+    Adaptive,
+}
+
+impl VsyncMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "on" => Some(VsyncMode::On),
+            "off" => Some(VsyncMode::Off),
+            "adaptive" => Some(VsyncMode::Adaptive),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            VsyncMode::On => "on",
+            VsyncMode::Off => "off",
+            VsyncMode::Adaptive => "adaptive",
+        }
+    }
+
+    pub fn swap_interval(self) -> SwapInterval {
+        match self {
+            VsyncMode::On => SwapInterval::VSync,
+            VsyncMode::Off => SwapInterval::Immediate,
+            VsyncMode::Adaptive => SwapInterval::LateSwapTearing,
+        }
+    }
+}
+
+/// Decides when the next frame can start so that the game does not run faster than its target frame rate.
+#[derive(Debug, Default)]
+pub struct FrameLimiter {
+    /// In the milliseconds of `now_ms`.
+    next_frame_ms: Option<f64>,
+}
+
+impl FrameLimiter {
+    /// How many milliseconds to wait before starting a frame at `now_ms`, 0 when the frame can start.
+    pub fn wait_time_ms(&self, now_ms: f64, target_fps: Option<u32>) -> f64 {
+        match (self.next_frame_ms, target_fps) {
+            (Some(next_frame_ms), Some(_)) => (next_frame_ms - now_ms).max(0.0),
+            _ => 0.0,
+        }
+    }
+
+    /// Schedules the frame after the one starting at `now_ms`. A frame that started late only makes up for one frame
+    /// of delay, so a long pause does not make the next frames run uncapped.
+    pub fn start_frame(&mut self, now_ms: f64, target_fps: Option<u32>) {
+        self.next_frame_ms = target_fps.map(|target_fps| {
+            let frame_duration_ms = 1000.0 / target_fps.max(1) as f64;
+            let scheduled_ms = self.next_frame_ms.map_or(now_ms, |next_frame_ms| {
+                next_frame_ms.max(now_ms - frame_duration_ms)
+            });
+            scheduled_ms + frame_duration_ms
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameLimiter;
+
+    #[test]
+    fn frames_start_at_the_target_rate() {
+        let mut limiter = FrameLimiter::default();
+        assert_eq!(limiter.wait_time_ms(0.0, Some(50)), 0.0);
+        limiter.start_frame(0.0, Some(50));
+        // The frame took 5 ms, the next one starts 20 ms after the previous one.
+        assert_eq!(limiter.wait_time_ms(5.0, Some(50)), 15.0);
+        limiter.start_frame(20.0, Some(50));
+        assert_eq!(limiter.wait_time_ms(30.0, Some(50)), 10.0);
+        // After a long frame, the next frames are not run back to back to catch up.
+        limiter.start_frame(200.0, Some(50));
+        assert_eq!(limiter.wait_time_ms(200.0, Some(50)), 0.0);
+        limiter.start_frame(200.0, Some(50));
+        assert_eq!(limiter.wait_time_ms(200.0, Some(50)), 20.0);
+        assert_eq!(limiter.wait_time_ms(200.0, None), 0.0);
+    }
+}
@@ -1,9 +1,11 @@
+use std::path::Path;
+
 pub trait ReadOnlyFileSystem {
-    fn read_file(&self, path: &str, callback: Box<dyn FnOnce(Option<Vec<u8>>)>);
+    fn read_file(&self, path: &Path, callback: Box<dyn FnOnce(Option<Vec<u8>>)>);
 
     /// Synchronously reads a file from the filesystem.
     /// Use is not recommended in a browser environment, as it may block the main thread.
-    fn read_file_sync(&self, path: &str) -> Option<Vec<u8>> {
+    fn read_file_sync(&self, path: &Path) -> Option<Vec<u8>> {
         let (sender, receiver) = std::sync::mpsc::channel();
         self.read_file(
             path,
@@ -20,7 +22,13 @@ pub trait ReadOnlyFileSystem {
 }
 
 pub trait FileSystem: ReadOnlyFileSystem {
-    fn write_file(&self, path: &str, data: &[u8], callback: Box<dyn FnOnce(bool)>);
+    fn write_file(&self, path: &Path, data: &[u8], callback: Box<dyn FnOnce(bool)>);
+
+    /// Moves a file, creating the missing folders of the destination. Fails if the destination already exists.
+    fn rename_file(&self, from: &Path, to: &Path, callback: Box<dyn FnOnce(bool)>);
+
+    /// Removes a file. In the editor, the file is moved to the trash of the OS so that it can be recovered.
+    fn delete_file(&self, path: &Path, callback: Box<dyn FnOnce(bool)>);
 }
 
 pub fn init_fs() {
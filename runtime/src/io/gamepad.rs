@@ -0,0 +1,335 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+
+use vectarine_plugin_sdk::sdl2::{
+    GameControllerSubsystem, JoystickSubsystem, Sdl,
+    controller::{Axis, Button, GameController},
+    event::Event,
+    joystick::{HatState, Joystick},
+};
+
+use crate::{
+    console::{print_err, print_info},
+    io::{inputkind::GamepadType, paths::describe_path},
+};
+
+/// Stick positions closer to the center than this are considered to be at rest.
+pub const STICK_DEAD_ZONE: f32 = 0.2;
+
+/// The SDL_GameControllerDB file loaded at startup from the folder of the executable, so that a game can ship the
+/// mappings of the controllers that SDL does not know.
+pub const GAMEPAD_MAPPINGS_FILE_NAME: &str = "gamecontrollerdb.txt";
+
+thread_local! {
+    static GAME_CONTROLLER_SUBSYSTEM: RefCell<Option<GameControllerSubsystem>> = const { RefCell::new(None) };
+    static JOYSTICK_SUBSYSTEM: RefCell<Option<JoystickSubsystem>> = const { RefCell::new(None) };
+    /// The joysticks opened by `get_raw_joysticks`, by instance id, so that SDL keeps updating their values.
+    static OPENED_JOYSTICKS: RefCell<HashMap<u32, Joystick>> = RefCell::new(HashMap::new());
+    /// SDL only sends the events of the gamepads that are opened. They are closed when dropped.
+    static OPENED_GAMEPADS: RefCell<HashMap<u32, GameController>> = RefCell::new(HashMap::new());
+    /// Stored here instead of in `IoEnvState` because SDL only announces a gamepad once, and the state of the game is
+    /// recreated when the game is reloaded in the editor.
+    static GAMEPAD_STATES: RefCell<BTreeMap<u32, GamepadState>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GamepadState {
+    pub name: String,
+    pub gamepad_type: GamepadType,
+    pub buttons_down: HashSet<Button>,
+    pub buttons_just_pressed: HashSet<Button>,
+    /// Sticks are between -1 and 1, triggers between 0 and 1.
+    pub axes: HashMap<Axis, f32>,
+}
+
+impl GamepadState {
+    /// Returns the value of the axis, or 0 when it is inside of the dead zone.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let value = self.axes.get(&axis).copied().unwrap_or(0.0);
+        if value.abs() < STICK_DEAD_ZONE {
+            0.0
+        } else {
+            value
+        }
+    }
+}
+
+/// The values of a joystick as SDL reads them, before any gamepad mapping is applied.
+#[derive(Clone, Debug)]
+pub struct RawJoystick {
+    pub instance_id: u32,
+    pub name: String,
+    pub guid: String,
+    pub axes: Vec<i16>,
+    pub buttons: Vec<bool>,
+    /// The hats use the bits of SDL: 1 for up, 2 for right, 4 for down and 8 for left.
+    pub hats: Vec<u8>,
+    /// The mapping SDL uses to turn the joystick into a gamepad, or None when SDL does not know the joystick.
+    pub mapping: Option<String>,
+}
+
+pub fn init_gamepad_system(sdl: &Sdl) {
+    match sdl.game_controller() {
+        Ok(subsystem) => {
+            GAME_CONTROLLER_SUBSYSTEM.with_borrow_mut(|global_subsystem| {
+                *global_subsystem = Some(subsystem);
+            });
+        }
+        Err(err) => {
+            println!(
+                "Failed to initialize gamepad subsystem: {:?}. Gamepads will be disabled.",
+                err
+            );
+        }
+    }
+    if let Ok(subsystem) = sdl.joystick() {
+        JOYSTICK_SUBSYSTEM.with_borrow_mut(|global_subsystem| *global_subsystem = Some(subsystem));
+    }
+    let next_to_executable = std::env::current_exe()
+        .ok()
+        .and_then(|executable| Some(executable.parent()?.join(GAMEPAD_MAPPINGS_FILE_NAME)));
+    if let Some(path) = next_to_executable
+        && path.is_file()
+    {
+        load_gamepad_mappings_file(&path);
+    }
+}
+
+/// Adds the mappings of an SDL_GameControllerDB file. The controllers they describe can be used right away.
+pub fn load_gamepad_mappings_file(path: &Path) {
+    let result = GAME_CONTROLLER_SUBSYSTEM.with_borrow(|subsystem| {
+        subsystem
+            .as_ref()
+            .map(|subsystem| subsystem.load_mappings(path))
+    });
+    match result {
+        Some(Ok(count)) => {
+            print_info(format!(
+                "Loaded {count} gamepad mappings from {}",
+                describe_path(path)
+            ));
+            open_new_gamepads();
+        }
+        Some(Err(error)) => print_err(format!(
+            "Unable to load the gamepad mappings of {}: {error}",
+            describe_path(path)
+        )),
+        None => {}
+    }
+}
+
+/// Adds a mapping in the format of SDL_GameControllerDB. A joystick it describes can be used as a gamepad right away,
+/// and a gamepad already connected uses the new mapping.
+pub fn add_gamepad_mapping(mapping: &str) -> Result<(), String> {
+    GAME_CONTROLLER_SUBSYSTEM.with_borrow(|subsystem| {
+        let subsystem = subsystem
+            .as_ref()
+            .ok_or("Gamepads are disabled".to_string())?;
+        subsystem
+            .add_mapping(mapping)
+            .map_err(|error| error.to_string())
+    })?;
+    open_new_gamepads();
+    Ok(())
+}
+
+/// Adds the mappings of the project. The invalid ones are reported in the console.
+pub fn add_gamepad_mappings(mappings: &[String]) {
+    for mapping in mappings {
+        if let Err(error) = add_gamepad_mapping(mapping) {
+            print_err(format!("Invalid gamepad mapping \"{mapping}\": {error}"));
+        }
+    }
+}
+
+/// SDL only announces the joysticks it recognizes as gamepads when they are connected, so the joysticks that became
+/// gamepads because of a new mapping are opened here.
+fn open_new_gamepads() {
+    let gamepads = GAME_CONTROLLER_SUBSYSTEM.with_borrow(|subsystem| {
+        let Some(subsystem) = subsystem.as_ref() else {
+            return Vec::new();
+        };
+        let joystick_count = subsystem.num_joysticks().unwrap_or(0);
+        (0..joystick_count)
+            .filter(|index| subsystem.is_game_controller(*index))
+            .filter_map(|index| subsystem.open(index).ok())
+            .collect::<Vec<_>>()
+    });
+    for gamepad in gamepads {
+        let instance_id = gamepad.instance_id();
+        if OPENED_GAMEPADS.with_borrow(|gamepads| gamepads.contains_key(&instance_id)) {
+            continue;
+        }
+        add_gamepad(gamepad);
+    }
+}
+
+fn add_gamepad(gamepad: GameController) {
+    let instance_id = gamepad.instance_id();
+    let name = gamepad.name();
+    let state = GamepadState {
+        gamepad_type: GamepadType::from_controller_name(&name),
+        name,
+        ..GamepadState::default()
+    };
+    GAMEPAD_STATES.with_borrow_mut(|states| states.insert(instance_id, state));
+    OPENED_GAMEPADS.with_borrow_mut(|gamepads| gamepads.insert(instance_id, gamepad));
+}
+
+/// Returns the connected joysticks, including the ones SDL does not recognize as gamepads.
+/// The joysticks are kept open afterwards, so call it every frame to see their values change.
+pub fn get_raw_joysticks() -> Vec<RawJoystick> {
+    let joysticks = JOYSTICK_SUBSYSTEM.with_borrow(|subsystem| {
+        let Some(subsystem) = subsystem.as_ref() else {
+            return Vec::new();
+        };
+        let joystick_count = subsystem.num_joysticks().unwrap_or(0);
+        (0..joystick_count)
+            .filter_map(|index| subsystem.open(index).ok())
+            .collect::<Vec<_>>()
+    });
+    let raw_joysticks = joysticks
+        .iter()
+        .map(|joystick| {
+            let guid = joystick.guid();
+            let mapping = GAME_CONTROLLER_SUBSYSTEM
+                .with_borrow(|subsystem| subsystem.as_ref()?.mapping_for_guid(guid).ok());
+            RawJoystick {
+                instance_id: joystick.instance_id(),
+                name: joystick.name(),
+                guid: guid.string(),
+                axes: (0..joystick.num_axes())
+                    .map(|axis| joystick.axis(axis).unwrap_or(0))
+                    .collect(),
+                buttons: (0..joystick.num_buttons())
+                    .map(|button| joystick.button(button).unwrap_or(false))
+                    .collect(),
+                hats: (0..joystick.num_hats())
+                    .map(|hat| joystick.hat(hat).map(hat_bits).unwrap_or(0))
+                    .collect(),
+                mapping,
+            }
+        })
+        .collect();
+    // Replacing the handles closes the joysticks that were disconnected.
+    OPENED_JOYSTICKS.with_borrow_mut(|opened| {
+        *opened = joysticks
+            .into_iter()
+            .map(|joystick| (joystick.instance_id(), joystick))
+            .collect();
+    });
+    raw_joysticks
+}
+
+fn hat_bits(hat: HatState) -> u8 {
+    match hat {
+        HatState::Centered => 0,
+        HatState::Up => 1,
+        HatState::Right => 2,
+        HatState::Down => 4,
+        HatState::Left => 8,
+        HatState::RightUp => 3,
+        HatState::RightDown => 6,
+        HatState::LeftUp => 9,
+        HatState::LeftDown => 12,
+    }
+}
+
+/// Call at the start of a frame, before `process_gamepad_event`.
+pub fn start_gamepad_frame() {
+    GAMEPAD_STATES.with_borrow_mut(|states| {
+        for state in states.values_mut() {
+            state.buttons_just_pressed.clear();
+        }
+    });
+}
+
+/// Updates the state of the gamepads. Returns false if the event is not about gamepads.
+pub fn process_gamepad_event(event: &Event) -> bool {
+    match event {
+        Event::ControllerDeviceAdded { which, .. } => {
+            let gamepad = GAME_CONTROLLER_SUBSYSTEM
+                .with_borrow(|subsystem| subsystem.as_ref()?.open(*which).ok());
+            if let Some(gamepad) = gamepad {
+                add_gamepad(gamepad);
+            }
+        }
+        Event::ControllerDeviceRemapped { which, .. } => {
+            // The name comes from the mapping, and the type from the name.
+            let name = OPENED_GAMEPADS.with_borrow(|gamepads| Some(gamepads.get(which)?.name()));
+            GAMEPAD_STATES.with_borrow_mut(|states| {
+                if let (Some(state), Some(name)) = (states.get_mut(which), name) {
+                    state.gamepad_type = GamepadType::from_controller_name(&name);
+                    state.name = name;
+                }
+            });
+        }
+        Event::ControllerDeviceRemoved { which, .. } => {
+            GAMEPAD_STATES.with_borrow_mut(|states| states.remove(which));
+            OPENED_GAMEPADS.with_borrow_mut(|gamepads| gamepads.remove(which));
+        }
+        Event::ControllerButtonDown { which, button, .. } => {
+            GAMEPAD_STATES.with_borrow_mut(|states| {
+                if let Some(state) = states.get_mut(which) {
+                    state.buttons_down.insert(*button);
+                    state.buttons_just_pressed.insert(*button);
+                }
+            });
+        }
+        Event::ControllerButtonUp { which, button, .. } => {
+            GAMEPAD_STATES.with_borrow_mut(|states| {
+                if let Some(state) = states.get_mut(which) {
+                    state.buttons_down.remove(button);
+                }
+            });
+        }
+        Event::ControllerAxisMotion {
+            which, axis, value, ..
+        } => {
+            GAMEPAD_STATES.with_borrow_mut(|states| {
+                if let Some(state) = states.get_mut(which) {
+                    let value = (*value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+                    state.axes.insert(*axis, value);
+                }
+            });
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Rumbles the gamepad for the duration, replacing its previous rumble. The strengths are between 0 and 1, and 0 for
+/// both stops the rumble. Returns false when the gamepad is not connected or cannot rumble.
+pub fn set_gamepad_rumble(
+    instance_id: u32,
+    low_frequency: f32,
+    high_frequency: f32,
+    duration: Duration,
+) -> bool {
+    let to_strength = |value: f32| (value.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+    OPENED_GAMEPADS.with_borrow_mut(|gamepads| {
+        gamepads.get_mut(&instance_id).is_some_and(|gamepad| {
+            gamepad
+                .set_rumble(
+                    to_strength(low_frequency),
+                    to_strength(high_frequency),
+                    duration.as_millis().min(u32::MAX as u128) as u32,
+                )
+                .is_ok()
+        })
+    })
+}
+
+/// Returns the type of the gamepad with the instance id, if it is connected.
+pub fn get_gamepad_type(instance_id: u32) -> Option<GamepadType> {
+    GAMEPAD_STATES.with_borrow(|states| states.get(&instance_id).map(|state| state.gamepad_type))
+}
+
+/// Gives access to the connected gamepads, by instance id.
+pub fn with_gamepads<R>(f: impl FnOnce(&BTreeMap<u32, GamepadState>) -> R) -> R {
+    GAMEPAD_STATES.with_borrow(f)
+}
@@ -0,0 +1,207 @@
+use std::time::{Duration, Instant};
+
+/// How long the active input device must stay unused before another device can become active. This prevents the
+/// button prompts of the game from flickering when the player touches the mouse while holding a gamepad.
+pub const DEFAULT_INPUT_KIND_SWITCH_DELAY: Duration = Duration::from_millis(500);
+
+/// The family of a gamepad, guessed from the name SDL gives to its mapping.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GamepadType {
+    Xbox,
+    PlayStation,
+    Switch,
+    #[default]
+    Unknown,
+}
+
+impl GamepadType {
+    pub fn from_controller_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        let contains_any = |words: &[&str]| words.iter().any(|word| name.contains(word));
+        if contains_any(&[
+            "playstation",
+            "ps3",
+            "ps4",
+            "ps5",
+            "dualshock",
+            "dualsense",
+            "sony",
+        ]) {
+            GamepadType::PlayStation
+        } else if contains_any(&["nintendo", "switch", "joy-con", "joycon"]) {
+            GamepadType::Switch
+        } else if contains_any(&["xbox", "xinput", "x-box"]) {
+            GamepadType::Xbox
+        } else {
+            GamepadType::Unknown
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GamepadType::Xbox => "xbox",
+            GamepadType::PlayStation => "playstation",
+            GamepadType::Switch => "switch",
+            GamepadType::Unknown => "unknown",
+        }
+    }
+
+    /// Returns the name of the glyph printed on a button, from its SDL name, so that games can pick the right image
+    /// from their button prompt atlas. Unknown gamepads use the Xbox names, as most gamepads copy its layout.
+    /// Nintendo gamepads already use the printed labels in SDL, so "a" is the right face button.
+    pub fn button_glyph_name<'a>(&self, button: &'a str) -> &'a str {
+        match (self, button) {
+            (GamepadType::PlayStation, "a") => "cross",
+            (GamepadType::PlayStation, "b") => "circle",
+            (GamepadType::PlayStation, "x") => "square",
+            (GamepadType::PlayStation, "y") => "triangle",
+            (GamepadType::PlayStation, "back") => "share",
+            (GamepadType::PlayStation, "start") => "options",
+            (GamepadType::PlayStation, "guide") => "ps",
+            (GamepadType::PlayStation, "leftshoulder") => "l1",
+            (GamepadType::PlayStation, "rightshoulder") => "r1",
+            (GamepadType::PlayStation, "leftstick") => "l3",
+            (GamepadType::PlayStation, "rightstick") => "r3",
+            (GamepadType::Switch, "back") => "minus",
+            (GamepadType::Switch, "start") => "plus",
+            (GamepadType::Switch, "guide") => "home",
+            (GamepadType::Switch, "leftshoulder") => "l",
+            (GamepadType::Switch, "rightshoulder") => "r",
+            (GamepadType::Switch, "leftstick") => "ls",
+            (GamepadType::Switch, "rightstick") => "rs",
+            (_, "back") => "view",
+            (_, "start") => "menu",
+            (_, "guide") => "xbox",
+            (_, "leftshoulder") => "lb",
+            (_, "rightshoulder") => "rb",
+            (_, "leftstick") => "ls",
+            (_, "rightstick") => "rs",
+            (_, other) => other,
+        }
+    }
+}
+
+/// The kind of device the player used last, to show "Press A" or "Press Space".
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputKind {
+    #[default]
+    KeyboardMouse,
+    Touch,
+    Gamepad {
+        id: u32,
+        gamepad_type: GamepadType,
+    },
+}
+
+impl InputKind {
+    pub fn device_name(&self) -> &'static str {
+        match self {
+            InputKind::KeyboardMouse => "keyboardMouse",
+            InputKind::Touch => "touch",
+            InputKind::Gamepad { .. } => "gamepad",
+        }
+    }
+}
+
+/// Remembers the last used input device, with some hysteresis.
+#[derive(Clone, Debug)]
+pub struct InputKindTracker {
+    active: InputKind,
+    /// When the active device was last used. None until the player used a device.
+    last_active_use: Option<Instant>,
+    pub switch_delay: Duration,
+}
+
+impl Default for InputKindTracker {
+    fn default() -> Self {
+        Self {
+            active: InputKind::default(),
+            last_active_use: None,
+            switch_delay: DEFAULT_INPUT_KIND_SWITCH_DELAY,
+        }
+    }
+}
+
+impl InputKindTracker {
+    pub fn active(&self) -> InputKind {
+        self.active
+    }
+
+    /// Records a meaningful input from a device. Returns true when the active device changed.
+    /// Another device only becomes active once the active one was left unused for the switch delay.
+    pub fn report_input(&mut self, kind: InputKind, now: Instant) -> bool {
+        if kind == self.active {
+            self.last_active_use = Some(now);
+            return false;
+        }
+        let is_active_idle = self
+            .last_active_use
+            .is_none_or(|last_use| now.saturating_duration_since(last_use) >= self.switch_delay);
+        if !is_active_idle {
+            return false;
+        }
+        self.active = kind;
+        self.last_active_use = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GamepadType, InputKind, InputKindTracker};
+    use std::time::{Duration, Instant};
+
+    const PLAYSTATION_PAD: InputKind = InputKind::Gamepad {
+        id: 0,
+        gamepad_type: GamepadType::PlayStation,
+    };
+
+    #[test]
+    fn gamepad_types_are_guessed_from_their_name() {
+        assert_eq!(
+            GamepadType::from_controller_name("PS4 Controller"),
+            GamepadType::PlayStation
+        );
+        assert_eq!(
+            GamepadType::from_controller_name("Nintendo Switch Pro Controller"),
+            GamepadType::Switch
+        );
+        assert_eq!(
+            GamepadType::from_controller_name("Xbox Series X Controller"),
+            GamepadType::Xbox
+        );
+        assert_eq!(
+            GamepadType::from_controller_name("Generic USB Joystick"),
+            GamepadType::Unknown
+        );
+        assert_eq!(GamepadType::PlayStation.button_glyph_name("a"), "cross");
+        assert_eq!(GamepadType::Xbox.button_glyph_name("a"), "a");
+        assert_eq!(GamepadType::Switch.button_glyph_name("start"), "plus");
+        assert_eq!(GamepadType::Unknown.button_glyph_name("dpup"), "dpup");
+    }
+
+    #[test]
+    fn the_first_input_switches_immediately() {
+        let mut tracker = InputKindTracker::default();
+        assert!(tracker.report_input(PLAYSTATION_PAD, Instant::now()));
+        assert_eq!(tracker.active(), PLAYSTATION_PAD);
+    }
+
+    #[test]
+    fn simultaneous_use_does_not_flip_flop() {
+        let mut tracker = InputKindTracker {
+            switch_delay: Duration::from_millis(500),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        assert!(tracker.report_input(PLAYSTATION_PAD, at(0)));
+        // The mouse is bumped while the gamepad is in use.
+        assert!(!tracker.report_input(InputKind::KeyboardMouse, at(100)));
+        assert!(!tracker.report_input(PLAYSTATION_PAD, at(400)));
+        assert!(!tracker.report_input(InputKind::KeyboardMouse, at(800)));
+        // The gamepad was put down.
+        assert!(tracker.report_input(InputKind::KeyboardMouse, at(1000)));
+        assert_eq!(tracker.active(), InputKind::KeyboardMouse);
+    }
+}
@@ -5,54 +5,55 @@ use std::cell::RefCell;
 #[cfg(target_os = "emscripten")]
 use std::collections::HashMap;
 
+use std::path::Path;
+
+#[cfg(not(target_os = "emscripten"))]
+use crate::console::{Subsystem, print_warn_from};
 use crate::io::fs::FileSystem;
 use crate::io::fs::ReadOnlyFileSystem;
+#[cfg(not(target_os = "emscripten"))]
+use crate::io::paths::{canonicalize, with_long_path_prefix};
 
 pub struct LocalFileSystem;
 #[cfg(not(target_os = "emscripten"))]
 impl ReadOnlyFileSystem for LocalFileSystem {
     /// Returns the content of the file at `path`
     /// Depending on your platform, this function can query the file system or perform an HTTP request to get the content.
-    fn read_file(&self, filename: &str, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
-        use std::{
-            fs::{self},
-            path::Path,
-        };
+    fn read_file(&self, path: &Path, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+        use std::fs;
 
-        let path = Path::new(filename);
         if path.is_relative() // Only perform this check for relative paths.
-            && let Ok(canonical) = path.canonicalize()
+            && let Ok(canonical) = canonicalize(path)
         {
-            let canonical_with_slash = canonical.to_string_lossy().replace("\\", "/");
-            let ends_with = canonical_with_slash.ends_with(filename);
+            // The components are compared, so that the separators and the encoding of the path do not matter.
+            let ends_with = canonical.ends_with(path);
             if !ends_with {
                 // Access might work on MacOS or Windows, but not on the web (path is case-sensitive + you might be accessing a file outside the bundle)
                 // We fail on all platforms for consistency and to catch errors early.
                 // TODO: It would be nice to also this kind of path issues in the editor instead of the runtime.
-                #[cfg(debug_assertions)]
-                {
-                    println!(
+                print_warn_from(
+                    Subsystem::Loader,
+                    format!(
                         "The path provided is not canonicalized correctly: {} instead of {}",
-                        filename,
+                        path.display(),
                         canonical.display(),
-                    );
-                }
+                    ),
+                );
                 callback(None);
                 return;
             }
         }
 
-        let content = fs::read(Path::new(filename)).ok();
+        let content = fs::read(with_long_path_prefix(path)).ok();
         callback(content);
     }
 }
 
 #[cfg(not(target_os = "emscripten"))]
 impl FileSystem for LocalFileSystem {
-    fn write_file(&self, path: &str, data: &[u8], callback: Box<dyn FnOnce(bool)>) {
+    fn write_file(&self, path: &Path, data: &[u8], callback: Box<dyn FnOnce(bool)>) {
         use std::fs;
-        use std::path::Path;
-        let result = fs::write(Path::new(path), data);
+        let result = fs::write(with_long_path_prefix(path), data);
         callback(result.is_ok());
         #[cfg(debug_assertions)]
         {
@@ -61,6 +62,41 @@ impl FileSystem for LocalFileSystem {
             }
         }
     }
+
+    fn rename_file(&self, from: &Path, to: &Path, callback: Box<dyn FnOnce(bool)>) {
+        use std::fs;
+        let to = with_long_path_prefix(to);
+        if to.exists() {
+            callback(false);
+            return;
+        }
+        let result = to
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::rename(with_long_path_prefix(from), &to));
+        callback(result.is_ok());
+        #[cfg(debug_assertions)]
+        {
+            if let Err(e) = result {
+                println!("Failed to rename file: {}", e);
+            }
+        }
+    }
+
+    fn delete_file(&self, path: &Path, callback: Box<dyn FnOnce(bool)>) {
+        let path = with_long_path_prefix(path);
+        #[cfg(feature = "editor")]
+        let result = trash::delete(&path).map_err(|e| e.to_string());
+        #[cfg(not(feature = "editor"))]
+        let result = std::fs::remove_file(&path).map_err(|e| e.to_string());
+        callback(result.is_ok());
+        #[cfg(debug_assertions)]
+        {
+            if let Err(e) = result {
+                println!("Failed to delete file: {}", e);
+            }
+        }
+    }
 }
 
 #[cfg(target_os = "emscripten")]
@@ -112,9 +148,18 @@ pub unsafe extern "C" fn read_rust_callback_from_js(
 
 #[cfg(target_os = "emscripten")]
 impl ReadOnlyFileSystem for LocalFileSystem {
-    fn read_file(&self, filename: &str, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+    fn read_file(&self, path: &Path, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+        use crate::io::paths::to_slash_string;
         use emscripten_functions::emscripten;
 
+        // Escaped as a JS string, as file names can contain quotes.
+        let Some(filename) =
+            to_slash_string(path).and_then(|path| serde_json::to_string(&path).ok())
+        else {
+            callback(None);
+            return;
+        };
+
         let callback_id = NEXT_CALLBACK_ID.with(|id_cell| {
             let id = id_cell.get();
             id_cell.set(id.wrapping_add(1));
@@ -126,14 +171,22 @@ impl ReadOnlyFileSystem for LocalFileSystem {
         });
 
         emscripten::run_script_string(format!(
-            "vectarine.read_file_for_rust({callback_id}, \"{filename}\")"
+            "vectarine.read_file_for_rust({callback_id}, {filename})"
         ));
     }
 }
 
 #[cfg(target_os = "emscripten")]
 impl FileSystem for LocalFileSystem {
-    fn write_file(&self, _path: &str, _data: &[u8], callback: Box<dyn FnOnce(bool)>) {
+    fn write_file(&self, _path: &Path, _data: &[u8], callback: Box<dyn FnOnce(bool)>) {
+        callback(false);
+    }
+
+    fn rename_file(&self, _from: &Path, _to: &Path, callback: Box<dyn FnOnce(bool)>) {
+        callback(false);
+    }
+
+    fn delete_file(&self, _path: &Path, callback: Box<dyn FnOnce(bool)>) {
         callback(false);
     }
 }
@@ -0,0 +1,127 @@
+//! Blocking dialogs for `Io.alert` and `Io.confirm`, shown by SDL or by the browser.
+//!
+//! The game is frozen while a dialog is open, so the events received meanwhile are handled when it closes. The quit
+//! events and the close events of the windows are dropped then: the player who closed the window again while a
+//! "Quit anyway?" dialog was open should not get a second dialog.
+
+/// The buttons of `Io.confirm` when the game does not give any.
+pub const DEFAULT_CONFIRM_BUTTONS: [&str; 2] = ["OK", "Cancel"];
+
+pub fn show_alert(title: &str, message: &str) {
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        use vectarine_plugin_sdk::sdl2::messagebox::{MessageBoxFlag, show_simple_message_box};
+        let _ = show_simple_message_box(
+            MessageBoxFlag::INFORMATION,
+            title,
+            message,
+            None::<&vectarine_plugin_sdk::sdl2::video::Window>,
+        );
+    }
+
+    #[cfg(target_os = "emscripten")]
+    {
+        use emscripten_functions::emscripten::run_script;
+        // The browser dialogs have no title.
+        let Ok(text) = serde_json::to_string(&format!("{title}\n\n{message}")) else {
+            return;
+        };
+        run_script(&format!("window.alert({text});"));
+    }
+    drop_quit_events();
+}
+
+/// Returns the index of the pressed button. Closing the dialog presses the last button, which is usually "Cancel".
+///
+/// The browser only has OK and Cancel: OK presses the first button and Cancel the last one, the others cannot be pressed.
+pub fn show_confirm(title: &str, message: &str, buttons: &[String]) -> usize {
+    let last_button = buttons.len().saturating_sub(1);
+
+    #[cfg(not(target_os = "emscripten"))]
+    let pressed = {
+        use vectarine_plugin_sdk::sdl2::messagebox::{
+            ButtonData, ClickedButton, MessageBoxButtonFlag, MessageBoxFlag, show_message_box,
+        };
+        let button_data = buttons
+            .iter()
+            .enumerate()
+            .map(|(index, text)| ButtonData {
+                flags: if index == 0 {
+                    MessageBoxButtonFlag::RETURNKEY_DEFAULT
+                } else if index == last_button {
+                    MessageBoxButtonFlag::ESCAPEKEY_DEFAULT
+                } else {
+                    MessageBoxButtonFlag::NOTHING
+                },
+                button_id: index as i32,
+                text,
+            })
+            .collect::<Vec<_>>();
+        match show_message_box(
+            MessageBoxFlag::WARNING,
+            &button_data,
+            title,
+            message,
+            None::<&vectarine_plugin_sdk::sdl2::video::Window>,
+            None,
+        ) {
+            Ok(ClickedButton::CustomButton(button)) => button.button_id as usize,
+            Ok(ClickedButton::CloseButton) | Err(_) => last_button,
+        }
+    };
+
+    #[cfg(target_os = "emscripten")]
+    let pressed = {
+        use emscripten_functions::emscripten::run_script_int;
+        let Ok(text) = serde_json::to_string(&format!("{title}\n\n{message}")) else {
+            return last_button;
+        };
+        let is_confirmed = run_script_int(&format!("window.confirm({text}) ? 1 : 0")) != 0;
+        web_button_index(is_confirmed, buttons.len())
+    };
+
+    drop_quit_events();
+    pressed
+}
+
+/// The button pressed by the OK and Cancel buttons of the browser.
+#[cfg_attr(not(target_os = "emscripten"), allow(dead_code))]
+fn web_button_index(is_confirmed: bool, button_count: usize) -> usize {
+    if is_confirmed {
+        0
+    } else {
+        button_count.saturating_sub(1)
+    }
+}
+
+fn drop_quit_events() {
+    use std::ffi::{c_int, c_void};
+    use vectarine_plugin_sdk::sdl2::sys;
+
+    unsafe extern "C" fn keep_event(_: *mut c_void, event: *mut sys::SDL_Event) -> c_int {
+        // SAFETY: SDL gives the events of its queue, the union field read matches the type of the event.
+        let is_quit = unsafe {
+            let event_type = (*event).type_;
+            event_type == sys::SDL_EventType::SDL_QUIT as u32
+                || (event_type == sys::SDL_EventType::SDL_WINDOWEVENT as u32
+                    && (*event).window.event == sys::SDL_WindowEventID::SDL_WINDOWEVENT_CLOSE as u8)
+        };
+        c_int::from(!is_quit)
+    }
+
+    unsafe {
+        sys::SDL_FilterEvents(Some(keep_event), std::ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::web_button_index;
+
+    #[test]
+    fn browser_buttons_press_the_first_or_the_last_button() {
+        assert_eq!(web_button_index(true, 3), 0);
+        assert_eq!(web_button_index(false, 3), 2);
+        assert_eq!(web_button_index(false, 1), 0);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    borrow::Cow,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Paths at least this long need the `\\?\` prefix to be used with the Windows API.
+#[cfg(windows)]
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Returns the path with `/` separators, as used in zip archives and URLs.
+/// Returns None when the path is not valid Unicode, as such paths cannot be written there.
+pub fn to_slash_string(path: &Path) -> Option<String> {
+    path.to_str().map(|path| path.replace('\\', "/"))
+}
+
+/// Formats the path for error messages. Unlike `Path::display`, the bytes that are not valid Unicode are shown
+/// escaped instead of being replaced, so that the user can find the file.
+pub fn describe_path(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) => path.to_string(),
+        None => format!("{path:?}"),
+    }
+}
+
+/// Adds the `\\?\` prefix to long absolute paths on Windows. Without it, the Windows API fails on paths longer than
+/// MAX_PATH, which happens in deeply nested folders. Other paths, and paths on other platforms, are returned unchanged.
+pub fn with_long_path_prefix(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        windows::with_long_path_prefix(path)
+    }
+    #[cfg(not(windows))]
+    {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Removes the `\\?\` prefix when the path is short enough to be used without it, so that the path can be shown to
+/// the user and compared with paths built by joining.
+pub fn without_long_path_prefix(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        windows::without_long_path_prefix(path)
+    }
+    #[cfg(not(windows))]
+    {
+        Cow::Borrowed(path)
+    }
+}
+
+/// `fs::canonicalize` that also works with paths longer than MAX_PATH on Windows.
+/// Unlike `fs::canonicalize`, the result only has the `\\?\` prefix when it needs it.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let canonical = std::fs::canonicalize(with_long_path_prefix(path))?;
+    Ok(without_long_path_prefix(&canonical).into_owned())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{
+        borrow::Cow,
+        ffi::OsString,
+        path::{Component, Path, PathBuf, Prefix},
+    };
+
+    use super::WINDOWS_MAX_PATH;
+
+    pub fn with_long_path_prefix(path: &Path) -> Cow<'_, Path> {
+        if path.as_os_str().len() < WINDOWS_MAX_PATH {
+            return Cow::Borrowed(path);
+        }
+        let mut components = path.components();
+        let mut root = OsString::from(r"\\?\");
+        match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) => root.push(format!("{}:\\", letter as char)),
+                Prefix::UNC(server, share) => {
+                    root.push(r"UNC\");
+                    root.push(server);
+                    root.push(r"\");
+                    root.push(share);
+                    root.push(r"\");
+                }
+                // Verbatim paths already have the prefix and device paths cannot have it.
+                _ => return Cow::Borrowed(path),
+            },
+            // Relative paths cannot have the prefix.
+            _ => return Cow::Borrowed(path),
+        }
+        let mut long_path = PathBuf::from(root);
+        // Windows does not resolve `.` and `..` in paths with the prefix.
+        for component in components {
+            match component {
+                Component::ParentDir => {
+                    long_path.pop();
+                }
+                Component::Normal(name) => long_path.push(name),
+                Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+            }
+        }
+        Cow::Owned(long_path)
+    }
+
+    pub fn without_long_path_prefix(path: &Path) -> Cow<'_, Path> {
+        let mut components = path.components();
+        let root = match components.next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::VerbatimDisk(letter) => OsString::from(format!("{}:\\", letter as char)),
+                Prefix::VerbatimUNC(server, share) => {
+                    let mut root = OsString::from(r"\\");
+                    root.push(server);
+                    root.push(r"\");
+                    root.push(share);
+                    root.push(r"\");
+                    root
+                }
+                _ => return Cow::Borrowed(path),
+            },
+            _ => return Cow::Borrowed(path),
+        };
+        let mut short_path = PathBuf::from(root);
+        for component in components {
+            if let Component::Normal(name) = component {
+                short_path.push(name);
+            }
+        }
+        if short_path.as_os_str().len() >= WINDOWS_MAX_PATH {
+            return Cow::Borrowed(path);
+        }
+        Cow::Owned(short_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, to_slash_string, with_long_path_prefix, without_long_path_prefix};
+    use crate::io::{fs::ReadOnlyFileSystem, localfs::LocalFileSystem};
+    use std::{fs, path::Path};
+
+    #[test]
+    fn slash_strings_keep_unicode_names() {
+        assert_eq!(
+            to_slash_string(Path::new("textures/my sprites/猫.png")).as_deref(),
+            Some("textures/my sprites/猫.png")
+        );
+        assert_eq!(
+            to_slash_string(Path::new(r"gamedata\Пользователь\main.luau")).as_deref(),
+            Some("gamedata/Пользователь/main.luau")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_are_not_unicode_are_kept_intact() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let path = Path::new(OsStr::from_bytes(b"caf\xe9/main.luau"));
+        assert_eq!(to_slash_string(path), None);
+        assert_eq!(with_long_path_prefix(path), path);
+        assert_eq!(without_long_path_prefix(path), path);
+    }
+
+    #[test]
+    fn deeply_nested_files_with_spaces_and_cjk_names_can_be_read() {
+        let root = std::env::temp_dir().join(format!("vectarine paths {}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let mut folder = root.clone();
+        while folder.as_os_str().len() <= 300 {
+            folder.push("ゲーム の フォルダ");
+        }
+        fs::create_dir_all(with_long_path_prefix(&folder)).expect("the folders are created");
+        let file = folder.join("メイン script.luau");
+        fs::write(with_long_path_prefix(&file), "return 1").expect("the file is written");
+
+        let canonical = canonicalize(&file).expect("long paths can be canonicalized");
+        assert!(canonical.ends_with("ゲーム の フォルダ/メイン script.luau"));
+        assert_eq!(
+            LocalFileSystem.read_file_sync(&file).as_deref(),
+            Some(b"return 1".as_slice())
+        );
+        let _ = fs::remove_dir_all(with_long_path_prefix(&root));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn long_windows_paths_get_the_prefix() {
+        let long_name = "a".repeat(300);
+        let path = format!(r"C:\Users\me\..\{long_name}\.\game.vecta");
+        let long_path = with_long_path_prefix(Path::new(&path));
+        assert_eq!(
+            long_path.as_os_str(),
+            format!(r"\\?\C:\Users\{long_name}\game.vecta").as_str()
+        );
+        assert_eq!(without_long_path_prefix(&long_path), long_path);
+
+        let short_path = Path::new(r"C:\Users\me\game.vecta");
+        assert_eq!(with_long_path_prefix(short_path), short_path);
+        assert_eq!(
+            without_long_path_prefix(Path::new(r"\\?\C:\Users\me\game.vecta")),
+            short_path
+        );
+        assert_eq!(
+            without_long_path_prefix(Path::new(r"\\?\UNC\server\share\game.vecta")),
+            Path::new(r"\\server\share\game.vecta")
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::VecDeque;
+
+use crate::{
+    console::{print_info, print_warn},
+    io::time::now_ms,
+};
+
+/// How many times per minute a game can call each service when it did not set it with `Io.setServiceRateLimit`.
+pub const DEFAULT_SERVICE_CALLS_PER_MINUTE: u32 = 10;
+
+const MINUTE_MS: f64 = 60_000.0;
+
+/// Refuses calls once `calls_per_minute` calls were made during the last minute,
+/// so that a call made every frame by mistake does not flood the OS.
+#[derive(Debug)]
+pub struct RateLimiter {
+    calls_per_minute: u32,
+    call_times_ms: VecDeque<f64>,
+    /// The calls refused since the last allowed one, to warn once instead of on every refused call.
+    refused_calls: u32,
+}
+
+impl RateLimiter {
+    pub fn new(calls_per_minute: u32) -> Self {
+        Self {
+            calls_per_minute,
+            call_times_ms: VecDeque::new(),
+            refused_calls: 0,
+        }
+    }
+
+    pub fn set_calls_per_minute(&mut self, calls_per_minute: u32) {
+        self.calls_per_minute = calls_per_minute;
+    }
+
+    /// Returns whether a call made at `now_ms` is allowed, and counts it if it is.
+    pub fn try_call(&mut self, now_ms: f64) -> bool {
+        while self
+            .call_times_ms
+            .front()
+            .is_some_and(|call_time| now_ms - call_time >= MINUTE_MS)
+        {
+            self.call_times_ms.pop_front();
+        }
+        if self.call_times_ms.len() >= self.calls_per_minute as usize {
+            self.refused_calls = self.refused_calls.saturating_add(1);
+            return false;
+        }
+        self.call_times_ms.push_back(now_ms);
+        self.refused_calls = 0;
+        true
+    }
+
+    /// The number of calls refused in a row, 1 for the first refused call.
+    pub fn refused_calls(&self) -> u32 {
+        self.refused_calls
+    }
+}
+
+/// Text-to-speech and desktop notifications, using what the OS provides.
+///
+/// The calls are fire-and-forget: the services run in other processes (or in the browser) and their failures are
+/// only logged. Every call is logged in the console, so that a call made by mistake is noticed while testing.
+#[derive(Debug)]
+pub struct PlatformServices {
+    speech_limiter: RateLimiter,
+    notification_limiter: RateLimiter,
+    #[cfg(not(target_os = "emscripten"))]
+    desktop: desktop::DesktopServices,
+}
+
+impl Default for PlatformServices {
+    fn default() -> Self {
+        Self {
+            speech_limiter: RateLimiter::new(DEFAULT_SERVICE_CALLS_PER_MINUTE),
+            notification_limiter: RateLimiter::new(DEFAULT_SERVICE_CALLS_PER_MINUTE),
+            #[cfg(not(target_os = "emscripten"))]
+            desktop: desktop::DesktopServices::default(),
+        }
+    }
+}
+
+impl PlatformServices {
+    pub fn set_calls_per_minute(&mut self, calls_per_minute: u32) {
+        self.speech_limiter.set_calls_per_minute(calls_per_minute);
+        self.notification_limiter
+            .set_calls_per_minute(calls_per_minute);
+    }
+
+    pub fn can_speak(&self) -> bool {
+        #[cfg(target_os = "emscripten")]
+        {
+            web::can_speak()
+        }
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            desktop::can_speak()
+        }
+    }
+
+    pub fn can_notify(&self) -> bool {
+        #[cfg(target_os = "emscripten")]
+        {
+            web::can_notify()
+        }
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            desktop::can_notify()
+        }
+    }
+
+    /// Reads the text aloud. With `interrupt`, the text being read is stopped, otherwise the text is read after it.
+    pub fn speak(&mut self, text: &str, interrupt: bool) {
+        if !self.speech_limiter.try_call(now_ms()) {
+            if self.speech_limiter.refused_calls() == 1 {
+                print_warn(format!(
+                    "Io.speak was called too often, \"{text}\" and the next texts are not read until a minute passed. Use Io.setServiceRateLimit to allow more calls."
+                ));
+            }
+            return;
+        }
+        if !self.can_speak() {
+            print_info(format!(
+                "Io.speak: \"{text}\" (no text-to-speech available)"
+            ));
+            return;
+        }
+        print_info(format!("Io.speak: \"{text}\""));
+        #[cfg(target_os = "emscripten")]
+        {
+            web::speak(text, interrupt);
+        }
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            self.desktop.speak(text, interrupt);
+        }
+    }
+
+    pub fn notify(&mut self, title: &str, body: &str) {
+        if !self.notification_limiter.try_call(now_ms()) {
+            if self.notification_limiter.refused_calls() == 1 {
+                print_warn(format!(
+                    "Io.notify was called too often, \"{title}\" and the next notifications are not shown until a minute passed. Use Io.setServiceRateLimit to allow more calls."
+                ));
+            }
+            return;
+        }
+        if !self.can_notify() {
+            print_info(format!(
+                "Io.notify: \"{title}\" (no notifications available)"
+            ));
+            return;
+        }
+        print_info(format!("Io.notify: \"{title}\": \"{body}\""));
+        #[cfg(target_os = "emscripten")]
+        {
+            web::notify(title, body);
+        }
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            self.desktop.notify(title, body);
+        }
+    }
+
+    /// Called every frame to start the speech that was waiting for the previous one to finish.
+    pub fn update(&mut self) {
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            self.desktop.update();
+        }
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+mod desktop {
+    use std::{
+        collections::VecDeque,
+        path::Path,
+        process::{Child, Command, Stdio},
+        sync::OnceLock,
+    };
+
+    use crate::console::print_warn;
+
+    /// The OS speaks one text at a time, so the texts are read by one process after the other.
+    #[derive(Debug, Default)]
+    pub struct DesktopServices {
+        speech_process: Option<Child>,
+        /// `spd-say --cancel`, that has to end before the next text is read or it would cancel it too.
+        cancel_process: Option<Child>,
+        pending_speech: VecDeque<String>,
+        /// The notification processes and the other short ones, kept to wait for them when they end.
+        background_processes: Vec<Child>,
+    }
+
+    impl DesktopServices {
+        pub fn speak(&mut self, text: &str, interrupt: bool) {
+            if interrupt {
+                self.pending_speech.clear();
+                if let Some(mut process) = self.speech_process.take() {
+                    let _ = process.kill();
+                    let _ = process.wait();
+                }
+                if cfg!(target_os = "linux") && self.cancel_process.is_none() {
+                    // Killing spd-say does not stop the speech-dispatcher daemon.
+                    self.cancel_process =
+                        run_silently(Command::new("spd-say").arg("--cancel")).ok();
+                }
+            }
+            self.pending_speech.push_back(text.to_string());
+            self.update();
+        }
+
+        pub fn notify(&mut self, title: &str, body: &str) {
+            let mut command = if cfg!(target_os = "windows") {
+                let mut command = Command::new("powershell");
+                command
+                    .args(["-NoProfile", "-NonInteractive", "-Command"])
+                    .arg(concat!(
+                        "Add-Type -AssemblyName System.Windows.Forms;",
+                        "$icon = New-Object System.Windows.Forms.NotifyIcon;",
+                        "$icon.Icon = [System.Drawing.SystemIcons]::Information;",
+                        "$icon.Visible = $true;",
+                        "$icon.ShowBalloonTip(5000, $env:VECTARINE_NOTIFICATION_TITLE, $env:VECTARINE_NOTIFICATION_BODY, 'None');",
+                        "Start-Sleep -Seconds 5;",
+                        "$icon.Dispose()"
+                    ))
+                    // Passed through the environment so that the text is never parsed as code.
+                    .env("VECTARINE_NOTIFICATION_TITLE", title)
+                    .env("VECTARINE_NOTIFICATION_BODY", body);
+                command
+            } else if cfg!(target_os = "macos") {
+                let mut command = Command::new("osascript");
+                command
+                    .args([
+                        "-e",
+                        "on run argv",
+                        "-e",
+                        "display notification (item 2 of argv) with title (item 1 of argv)",
+                        "-e",
+                        "end run",
+                    ])
+                    .args([title, body]);
+                command
+            } else {
+                let mut command = Command::new("notify-send");
+                command.args(["--", title, body]);
+                command
+            };
+            match run_silently(&mut command) {
+                Ok(process) => self.background_processes.push(process),
+                Err(error) => print_warn(format!("Unable to show the notification: {error}")),
+            }
+        }
+
+        pub fn update(&mut self) {
+            self.background_processes
+                .retain_mut(|process| matches!(process.try_wait(), Ok(None)));
+            let is_running = |process: &mut Option<Child>| {
+                process
+                    .as_mut()
+                    .is_some_and(|process| matches!(process.try_wait(), Ok(None)))
+            };
+            if is_running(&mut self.cancel_process) || is_running(&mut self.speech_process) {
+                return;
+            }
+            self.cancel_process = None;
+            self.speech_process = None;
+            let Some(text) = self.pending_speech.pop_front() else {
+                return;
+            };
+            match run_silently(&mut speech_command(&text)) {
+                Ok(process) => self.speech_process = Some(process),
+                Err(error) => print_warn(format!("Unable to read the text aloud: {error}")),
+            }
+        }
+    }
+
+    impl Drop for DesktopServices {
+        /// The speech and the notifications do not outlive the game.
+        fn drop(&mut self) {
+            let is_speaking = self.speech_process.is_some();
+            let processes = self
+                .speech_process
+                .iter_mut()
+                .chain(&mut self.cancel_process)
+                .chain(&mut self.background_processes);
+            for process in processes {
+                let _ = process.kill();
+                let _ = process.wait();
+            }
+            if cfg!(target_os = "linux") && is_speaking {
+                let _ = run_silently(Command::new("spd-say").arg("--cancel"));
+            }
+        }
+    }
+
+    fn speech_command(text: &str) -> Command {
+        if cfg!(target_os = "windows") {
+            let mut command = Command::new("powershell");
+            command
+                .args(["-NoProfile", "-NonInteractive", "-Command"])
+                .arg(concat!(
+                    "Add-Type -AssemblyName System.Speech;",
+                    "(New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak($env:VECTARINE_SPEECH_TEXT)"
+                ))
+                .env("VECTARINE_SPEECH_TEXT", text);
+            command
+        } else if cfg!(target_os = "macos") {
+            let mut command = Command::new("say");
+            command.args(["--", text]);
+            command
+        } else {
+            // --wait makes the process last as long as the speech, so that the next text waits for it.
+            let mut command = Command::new("spd-say");
+            command.args(["--wait", "--", text]);
+            command
+        }
+    }
+
+    fn run_silently(command: &mut Command) -> std::io::Result<Child> {
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    }
+
+    /// Windows and macOS always come with their speech synthesizer, Linux needs speech-dispatcher.
+    pub fn can_speak() -> bool {
+        static CAN_SPEAK: OnceLock<bool> = OnceLock::new();
+        *CAN_SPEAK.get_or_init(|| {
+            cfg!(any(target_os = "windows", target_os = "macos")) || is_in_path("spd-say")
+        })
+    }
+
+    pub fn can_notify() -> bool {
+        static CAN_NOTIFY: OnceLock<bool> = OnceLock::new();
+        *CAN_NOTIFY.get_or_init(|| {
+            cfg!(any(target_os = "windows", target_os = "macos")) || is_in_path("notify-send")
+        })
+    }
+
+    fn is_in_path(program: &str) -> bool {
+        let Some(path) = std::env::var_os("PATH") else {
+            return false;
+        };
+        std::env::split_paths(&path).any(|folder| Path::new(&folder).join(program).is_file())
+    }
+}
+
+/// The browser services are called through the `vectarine` object of the page, see `js-rust-interaction.md`.
+#[cfg(target_os = "emscripten")]
+mod web {
+    use emscripten_functions::emscripten::{run_script, run_script_int};
+
+    pub fn can_speak() -> bool {
+        run_script_int("vectarine.canSpeak() ? 1 : 0") != 0
+    }
+
+    pub fn can_notify() -> bool {
+        run_script_int("vectarine.canNotify() ? 1 : 0") != 0
+    }
+
+    pub fn speak(text: &str, interrupt: bool) {
+        let Ok(text) = serde_json::to_string(text) else {
+            return;
+        };
+        run_script(&format!("vectarine.speak({text}, {interrupt});"));
+    }
+
+    pub fn notify(title: &str, body: &str) {
+        let (Ok(title), Ok(body)) = (serde_json::to_string(title), serde_json::to_string(body))
+        else {
+            return;
+        };
+        run_script(&format!("vectarine.notify({title}, {body});"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+
+    #[test]
+    fn calls_are_allowed_again_a_minute_later() {
+        let mut limiter = RateLimiter::new(3);
+        assert!(limiter.try_call(0.0));
+        assert!(limiter.try_call(10.0));
+        assert!(limiter.try_call(20.0));
+        // A call every frame is refused once the limit is reached.
+        assert!(!limiter.try_call(30.0));
+        assert_eq!(limiter.refused_calls(), 1);
+        assert!(!limiter.try_call(59_999.0));
+        assert_eq!(limiter.refused_calls(), 2);
+        assert!(limiter.try_call(60_000.0));
+        assert_eq!(limiter.refused_calls(), 0);
+        assert!(!limiter.try_call(60_005.0));
+
+        limiter.set_calls_per_minute(0);
+        assert!(!limiter.try_call(1_000_000.0));
+    }
+}
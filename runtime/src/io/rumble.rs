@@ -0,0 +1,179 @@
+//! Rumble patterns played by the engine, like a heartbeat or a double tap on hit.
+//!
+//! The segments advance with the frame clock of the game, so a pattern pauses when the game does not update. The
+//! gamepad is told to rumble a bit longer than the current segment, so that a late frame does not leave a gap
+//! between two segments.
+
+use std::{collections::HashMap, rc::Rc, time::Duration};
+
+use crate::io::gamepad::set_gamepad_rumble;
+
+/// How much longer than its segment a gamepad rumbles, in case the next frame is late.
+const RUMBLE_OVERLAP: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RumbleSegment {
+    /// Between 0 and 1, the strength of the heavy motor.
+    pub low_frequency: f32,
+    /// Between 0 and 1, the strength of the light motor.
+    pub high_frequency: f32,
+    pub duration: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RumblePattern {
+    pub segments: Vec<RumbleSegment>,
+    pub looped: bool,
+}
+
+impl RumblePattern {
+    pub fn total_duration(&self) -> Duration {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Returns the segment playing after `elapsed`, as the number of the loop, the index of the segment and the time
+    /// left in it. Returns None when the pattern is over.
+    pub fn segment_at(&self, elapsed: Duration) -> Option<(u64, usize, Duration)> {
+        let total = self.total_duration();
+        if total.is_zero() {
+            return None;
+        }
+        let (cycle, mut elapsed) = if self.looped {
+            let cycle = (elapsed.as_nanos() / total.as_nanos()) as u64;
+            let in_cycle = Duration::from_nanos((elapsed.as_nanos() % total.as_nanos()) as u64);
+            (cycle, in_cycle)
+        } else {
+            (0, elapsed)
+        };
+        for (index, segment) in self.segments.iter().enumerate() {
+            if elapsed < segment.duration {
+                return Some((cycle, index, segment.duration - elapsed));
+            }
+            elapsed -= segment.duration;
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+struct PlayingRumble {
+    pattern: Rc<RumblePattern>,
+    started_at: Duration,
+    /// The loop and the segment sent to the gamepad last, so that each segment is only sent once.
+    applied_segment: Option<(u64, usize)>,
+}
+
+/// The patterns defined by the game and the ones playing on each gamepad.
+#[derive(Debug, Default)]
+pub struct RumblePlayer {
+    defined: HashMap<String, Rc<RumblePattern>>,
+    playing: HashMap<u32, PlayingRumble>,
+}
+
+impl RumblePlayer {
+    pub fn define(&mut self, name: String, pattern: RumblePattern) {
+        self.defined.insert(name, Rc::new(pattern));
+    }
+
+    pub fn get_defined(&self, name: &str) -> Option<Rc<RumblePattern>> {
+        self.defined.get(name).cloned()
+    }
+
+    /// Replaces the pattern playing on the gamepad. It starts at the next call to `update`.
+    pub fn play(&mut self, gamepad_id: u32, pattern: Rc<RumblePattern>, now: Duration) {
+        self.playing.insert(
+            gamepad_id,
+            PlayingRumble {
+                pattern,
+                started_at: now,
+                applied_segment: None,
+            },
+        );
+    }
+
+    /// Stops the pattern of the gamepad without touching the motors, for a gamepad that was disconnected or that the
+    /// game rumbles directly.
+    pub fn forget(&mut self, gamepad_id: u32) {
+        self.playing.remove(&gamepad_id);
+    }
+
+    /// Stops the pattern and the motors of the gamepad.
+    pub fn stop(&mut self, gamepad_id: u32) {
+        self.forget(gamepad_id);
+        set_gamepad_rumble(gamepad_id, 0.0, 0.0, Duration::ZERO);
+    }
+
+    /// Stops the motors of the gamepads with a pattern, used when the game is reloaded or closed in the editor.
+    pub fn stop_all(&mut self) {
+        for gamepad_id in self.playing.keys() {
+            set_gamepad_rumble(*gamepad_id, 0.0, 0.0, Duration::ZERO);
+        }
+        self.playing.clear();
+    }
+
+    /// Sends the segments that started since the previous frame to the gamepads. Called once per frame.
+    pub fn update(&mut self, now: Duration) {
+        self.playing.retain(|gamepad_id, playing| {
+            let elapsed = now.saturating_sub(playing.started_at);
+            let Some((cycle, index, remaining)) = playing.pattern.segment_at(elapsed) else {
+                set_gamepad_rumble(*gamepad_id, 0.0, 0.0, Duration::ZERO);
+                return false;
+            };
+            if playing.applied_segment == Some((cycle, index)) {
+                return true;
+            }
+            playing.applied_segment = Some((cycle, index));
+            let segment = playing.pattern.segments[index];
+            // The pattern is dropped when the gamepad was disconnected or cannot rumble.
+            set_gamepad_rumble(
+                *gamepad_id,
+                segment.low_frequency,
+                segment.high_frequency,
+                remaining + RUMBLE_OVERLAP,
+            )
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RumblePattern, RumbleSegment};
+
+    fn heartbeat(looped: bool) -> RumblePattern {
+        let segment = |strength: f32, ms: u64| RumbleSegment {
+            low_frequency: strength,
+            high_frequency: 0.0,
+            duration: Duration::from_millis(ms),
+        };
+        RumblePattern {
+            segments: vec![segment(1.0, 100), segment(0.0, 50), segment(0.6, 100)],
+            looped,
+        }
+    }
+
+    #[test]
+    fn segments_follow_each_other_then_the_pattern_ends() {
+        let pattern = heartbeat(false);
+        let ms = Duration::from_millis;
+        assert_eq!(pattern.segment_at(ms(0)), Some((0, 0, ms(100))));
+        assert_eq!(pattern.segment_at(ms(120)), Some((0, 1, ms(30))));
+        assert_eq!(pattern.segment_at(ms(150)), Some((0, 2, ms(100))));
+        assert_eq!(pattern.segment_at(ms(250)), None);
+    }
+
+    #[test]
+    fn looped_patterns_count_their_loops() {
+        let pattern = heartbeat(true);
+        let ms = Duration::from_millis;
+        assert_eq!(pattern.segment_at(ms(260)), Some((1, 0, ms(90))));
+        assert_eq!(pattern.segment_at(ms(2 * 250 + 160)), Some((2, 2, ms(90))));
+
+        let empty = RumblePattern {
+            segments: Vec::new(),
+            looped: true,
+        };
+        assert_eq!(empty.segment_at(ms(10)), None);
+    }
+}
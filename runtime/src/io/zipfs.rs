@@ -1,9 +1,10 @@
 use std::cell::RefCell;
 use std::io::{Cursor, Read};
+use std::path::Path;
 use vectarine_plugin_sdk::anyhow::Result;
 use zip::ZipArchive;
 
-use crate::io::fs::ReadOnlyFileSystem;
+use crate::io::{fs::ReadOnlyFileSystem, paths::to_slash_string};
 
 pub struct ZipFileSystem {
     archive: RefCell<ZipArchive<Cursor<Vec<u8>>>>,
@@ -26,20 +27,24 @@ impl ZipFileSystem {
         file.read_to_end(&mut contents).ok()?;
         Some(contents)
     }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        to_slash_string(path)
+            .is_some_and(|filename| self.archive.borrow().index_for_name(&filename).is_some())
+    }
 }
 
 impl ReadOnlyFileSystem for ZipFileSystem {
     /// Returns the content of the file at `path`
     /// Reads the file from the zip archive and calls the callback with the file contents.
-    fn read_file(&self, filename: &str, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
-        let mut archive = self.archive.borrow_mut();
-
-        // Try to find and read the file from the zip archive
-        let result = archive.by_name(filename).ok().and_then(|mut file| {
-            let mut contents = Vec::new();
-            file.read_to_end(&mut contents).ok()?;
-            Some(contents)
-        });
+    fn read_file(&self, path: &Path, callback: Box<dyn FnOnce(Option<Vec<u8>>)>) {
+        // The names of the entries are Unicode, so other paths cannot be in the archive.
+        let Some(filename) = to_slash_string(path) else {
+            callback(None);
+            return;
+        };
+        // The archive is released before the callback, which can read other files of the archive.
+        let result = self.read_file_sync(&filename);
         callback(result);
     }
 }
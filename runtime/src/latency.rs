@@ -0,0 +1,384 @@
+//! Measures the frame pacing and the input latency of the game, for `Debug.showLatencyOverlay` and
+//! `Debug.getLatencyStats`. Nothing is measured until one of them is used.
+//!
+//! The input latency of a frame goes from the oldest input event the frame consumed, using the timestamp SDL gave to
+//! the event, to the swap that first showed the frame. The GPU time is measured with timer queries, which are only
+//! available on desktop OpenGL 3.3 or with the `GL_ARB_timer_query` extension.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use vectarine_plugin_sdk::glow::{self, HasContext};
+
+use crate::graphics::batchdraw::BatchDraw2d;
+
+/// The number of frames kept for the overlay and the statistics.
+pub const LATENCY_FRAME_COUNT: usize = 120;
+/// A frame hits the target interval when it is at most this much longer, to absorb the jitter of the timer.
+const TARGET_INTERVAL_MARGIN: f64 = 1.2;
+/// Timer queries in flight. Their results are read a few frames later, without waiting for the GPU.
+const MAX_PENDING_GPU_QUERIES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTiming {
+    index: u64,
+    pub frame_time: Duration,
+    /// The time spent in the main loop of the game, from the events to the end of the drawing.
+    pub cpu_time: Duration,
+    pub gpu_time: Option<Duration>,
+    /// None when the frame consumed no input event, or was not shown.
+    pub input_latency: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub frame_count: usize,
+    pub target_interval: Duration,
+    /// The frames longer than the target interval.
+    pub missed_frame_count: usize,
+    pub average_frame_time: Duration,
+    pub worst_frame_time: Duration,
+    pub average_cpu_time: Duration,
+    pub average_gpu_time: Option<Duration>,
+    pub average_input_latency: Option<Duration>,
+    pub worst_input_latency: Option<Duration>,
+}
+
+struct GpuTimer {
+    gl: Arc<glow::Context>,
+    /// False when the driver has no timer queries. The GPU time of the frames is then None.
+    is_supported: bool,
+    pending: VecDeque<(u64, glow::Query)>,
+    free: Vec<glow::Query>,
+    is_running: bool,
+}
+
+fn are_timer_queries_supported(gl: &glow::Context) -> bool {
+    let version = gl.version();
+    (!version.is_embedded && (version.major, version.minor) >= (3, 3))
+        || gl.supported_extensions().contains("GL_ARB_timer_query")
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            for query in self.free.drain(..) {
+                self.gl.delete_query(query);
+            }
+            for (_, query) in self.pending.drain(..) {
+                self.gl.delete_query(query);
+            }
+        }
+    }
+}
+
+pub struct LatencyTracker {
+    is_enabled: bool,
+    pub is_overlay_shown: bool,
+    target_interval: Duration,
+    frames: VecDeque<FrameTiming>,
+    frame_index: u64,
+    /// The SDL timestamp, in milliseconds, of the oldest input event consumed since the last frame was shown.
+    oldest_input_timestamp: Option<u32>,
+    gpu_timer: Option<GpuTimer>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self {
+            is_enabled: false,
+            is_overlay_shown: false,
+            target_interval: Duration::from_secs(1) / 60,
+            frames: VecDeque::with_capacity(LATENCY_FRAME_COUNT),
+            frame_index: 0,
+            oldest_input_timestamp: None,
+            gpu_timer: None,
+        }
+    }
+}
+
+/// The SDL clock the timestamps of the events use, in milliseconds.
+pub fn sdl_ticks_ms() -> u32 {
+    unsafe { vectarine_plugin_sdk::sdl2::sys::SDL_GetTicks() }
+}
+
+impl LatencyTracker {
+    pub fn is_enabled(&self) -> bool {
+        self.is_enabled
+    }
+
+    /// Starts measuring. The statistics cover the frames run after this call.
+    pub fn enable(&mut self) {
+        self.is_enabled = true;
+    }
+
+    /// The refresh interval of the display.
+    pub fn set_target_frame_rate(&mut self, frame_rate: u32) {
+        self.target_interval = Duration::from_secs(1) / frame_rate.max(1);
+    }
+
+    pub fn is_on_target(&self, frame_time: Duration) -> bool {
+        frame_time.as_secs_f64() <= self.target_interval.as_secs_f64() * TARGET_INTERVAL_MARGIN
+    }
+
+    pub fn record_input_event(&mut self, timestamp_ms: u32) {
+        if !self.is_enabled {
+            return;
+        }
+        let oldest = self.oldest_input_timestamp.get_or_insert(timestamp_ms);
+        *oldest = (*oldest).min(timestamp_ms);
+    }
+
+    /// Called around the drawing of the game, to measure the time the GPU spends on it.
+    pub fn begin_gpu_timing(&mut self, gl: &Arc<glow::Context>) {
+        if !self.is_enabled || cfg!(target_os = "emscripten") {
+            return;
+        }
+        let gpu_timer = self.gpu_timer.get_or_insert_with(|| GpuTimer {
+            gl: gl.clone(),
+            is_supported: are_timer_queries_supported(gl),
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            is_running: false,
+        });
+        if !gpu_timer.is_supported || gpu_timer.pending.len() >= MAX_PENDING_GPU_QUERIES {
+            return;
+        }
+        let query = match gpu_timer.free.pop() {
+            Some(query) => query,
+            None => match unsafe { gl.create_query() } {
+                Ok(query) => query,
+                Err(_) => return,
+            },
+        };
+        unsafe { gl.begin_query(glow::TIME_ELAPSED, query) };
+        gpu_timer.pending.push_back((self.frame_index, query));
+        gpu_timer.is_running = true;
+    }
+
+    pub fn end_gpu_timing(&mut self) {
+        let Some(gpu_timer) = &mut self.gpu_timer else {
+            return;
+        };
+        if gpu_timer.is_running {
+            unsafe { gpu_timer.gl.end_query(glow::TIME_ELAPSED) };
+            gpu_timer.is_running = false;
+        }
+    }
+
+    /// The queries belong to a lost context.
+    pub fn forget_gpu_queries(&mut self) {
+        if let Some(mut gpu_timer) = self.gpu_timer.take() {
+            gpu_timer.pending.clear();
+            gpu_timer.free.clear();
+        }
+    }
+
+    /// Records the frame that just ran, and the GPU times that are available.
+    pub fn end_frame(&mut self, frame_time: Duration, cpu_time: Duration) {
+        if !self.is_enabled {
+            return;
+        }
+        if self.frames.len() == LATENCY_FRAME_COUNT {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(FrameTiming {
+            index: self.frame_index,
+            frame_time,
+            cpu_time,
+            gpu_time: None,
+            input_latency: None,
+        });
+        self.frame_index += 1;
+        self.read_gpu_times();
+    }
+
+    fn read_gpu_times(&mut self) {
+        let Some(gpu_timer) = &mut self.gpu_timer else {
+            return;
+        };
+        while let Some((index, query)) = gpu_timer.pending.front().copied() {
+            if gpu_timer.is_running && gpu_timer.pending.len() == 1 {
+                break;
+            }
+            let is_available = unsafe {
+                gpu_timer
+                    .gl
+                    .get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE)
+            } != 0;
+            if !is_available {
+                break;
+            }
+            let nanoseconds = unsafe {
+                gpu_timer
+                    .gl
+                    .get_query_parameter_u32(query, glow::QUERY_RESULT)
+            };
+            gpu_timer.pending.pop_front();
+            gpu_timer.free.push(query);
+            if let Some(frame) = self.frames.iter_mut().find(|frame| frame.index == index) {
+                frame.gpu_time = Some(Duration::from_nanos(nanoseconds.into()));
+            }
+        }
+    }
+
+    /// Called after the swap that shows the last frame. `now_ms` is the SDL clock, see `sdl_ticks_ms`.
+    pub fn frame_presented(&mut self, now_ms: u32) {
+        let Some(oldest_input_timestamp) = self.oldest_input_timestamp.take() else {
+            return;
+        };
+        if let Some(frame) = self.frames.back_mut() {
+            frame.input_latency = Some(Duration::from_millis(
+                now_ms.saturating_sub(oldest_input_timestamp).into(),
+            ));
+        }
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &FrameTiming> {
+        self.frames.iter()
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        fn average(durations: &[Duration]) -> Option<Duration> {
+            (!durations.is_empty())
+                .then(|| durations.iter().sum::<Duration>() / durations.len() as u32)
+        }
+        let frame_times = self
+            .frames
+            .iter()
+            .map(|frame| frame.frame_time)
+            .collect::<Vec<_>>();
+        let cpu_times = self
+            .frames
+            .iter()
+            .map(|frame| frame.cpu_time)
+            .collect::<Vec<_>>();
+        let gpu_times = self
+            .frames
+            .iter()
+            .filter_map(|frame| frame.gpu_time)
+            .collect::<Vec<_>>();
+        let input_latencies = self
+            .frames
+            .iter()
+            .filter_map(|frame| frame.input_latency)
+            .collect::<Vec<_>>();
+        LatencyStats {
+            frame_count: self.frames.len(),
+            target_interval: self.target_interval,
+            missed_frame_count: frame_times
+                .iter()
+                .filter(|frame_time| !self.is_on_target(**frame_time))
+                .count(),
+            average_frame_time: average(&frame_times).unwrap_or_default(),
+            worst_frame_time: frame_times.iter().max().copied().unwrap_or_default(),
+            average_cpu_time: average(&cpu_times).unwrap_or_default(),
+            average_gpu_time: average(&gpu_times),
+            average_input_latency: average(&input_latencies),
+            worst_input_latency: input_latencies.iter().max().copied(),
+        }
+    }
+
+    /// Draws a bar per recent frame at the bottom left of the game: its height is the frame time, green when the frame
+    /// hit the target interval and red otherwise. Below, the CPU time in blue with the GPU time in orange above it, and
+    /// the input latency in purple. The white line is the target interval.
+    pub fn draw_overlay(&self, batch: &mut BatchDraw2d) {
+        const LEFT: f32 = -0.98;
+        const BOTTOM: f32 = -0.98;
+        const WIDTH: f32 = 0.9;
+        const ROW_HEIGHT: f32 = 0.15;
+        let bar_width = WIDTH / LATENCY_FRAME_COUNT as f32;
+        let target = self.target_interval.as_secs_f32();
+        // The target interval is at half of the height of a row.
+        let height_of = |duration: Duration| {
+            (duration.as_secs_f32() / target * ROW_HEIGHT / 2.0).min(ROW_HEIGHT)
+        };
+        let rows_bottom = [BOTTOM + 2.0 * ROW_HEIGHT, BOTTOM + ROW_HEIGHT, BOTTOM];
+        batch.draw_rect(LEFT, BOTTOM, WIDTH, 3.0 * ROW_HEIGHT, [0.0, 0.0, 0.0, 0.6]);
+        for (position, frame) in self.frames.iter().enumerate() {
+            let x = LEFT + position as f32 * bar_width;
+            let color = if self.is_on_target(frame.frame_time) {
+                [0.3, 0.9, 0.3, 0.9]
+            } else {
+                [1.0, 0.3, 0.3, 0.9]
+            };
+            batch.draw_rect(
+                x,
+                rows_bottom[0],
+                bar_width,
+                height_of(frame.frame_time),
+                color,
+            );
+            let cpu_height = height_of(frame.cpu_time);
+            batch.draw_rect(
+                x,
+                rows_bottom[1],
+                bar_width,
+                cpu_height,
+                [0.4, 0.6, 1.0, 0.9],
+            );
+            if let Some(gpu_time) = frame.gpu_time {
+                let gpu_height = height_of(gpu_time).min(ROW_HEIGHT - cpu_height);
+                batch.draw_rect(
+                    x,
+                    rows_bottom[1] + cpu_height,
+                    bar_width,
+                    gpu_height,
+                    [1.0, 0.6, 0.2, 0.9],
+                );
+            }
+            if let Some(input_latency) = frame.input_latency {
+                batch.draw_rect(
+                    x,
+                    rows_bottom[2],
+                    bar_width,
+                    height_of(input_latency),
+                    [0.7, 0.4, 1.0, 0.9],
+                );
+            }
+        }
+        for row_bottom in rows_bottom {
+            batch.draw_rect(
+                LEFT,
+                row_bottom + ROW_HEIGHT / 2.0,
+                WIDTH,
+                0.004,
+                [1.0, 1.0, 1.0, 0.7],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::LatencyTracker;
+
+    #[test]
+    fn input_latency_goes_to_the_frame_that_shows_the_input() {
+        let milliseconds = Duration::from_millis;
+        let mut tracker = LatencyTracker::default();
+        tracker.record_input_event(100);
+        assert_eq!(tracker.stats().frame_count, 0);
+
+        tracker.enable();
+        tracker.set_target_frame_rate(50);
+        tracker.record_input_event(1000);
+        tracker.record_input_event(990);
+        // A skipped frame consumes the events, but only the next shown frame displays them.
+        tracker.end_frame(milliseconds(20), milliseconds(5));
+        tracker.end_frame(milliseconds(40), milliseconds(30));
+        tracker.frame_presented(1050);
+        tracker.end_frame(milliseconds(20), milliseconds(7));
+        tracker.frame_presented(1070);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.frame_count, 3);
+        assert_eq!(stats.missed_frame_count, 1);
+        assert_eq!(stats.worst_frame_time, milliseconds(40));
+        assert_eq!(stats.average_cpu_time, milliseconds(14));
+        assert_eq!(stats.average_gpu_time, None);
+        assert_eq!(stats.average_input_latency, Some(milliseconds(60)));
+        assert_eq!(stats.worst_input_latency, Some(milliseconds(60)));
+    }
+}
@@ -1,16 +1,28 @@
+pub mod apiversion;
+pub mod audioclock;
 pub mod console;
+pub mod expression;
+pub mod fixedtick;
+pub mod frameskip;
 pub mod game;
 pub mod game_resource;
+pub mod gcpacing;
 pub mod graphics;
 pub mod inithelpers;
 pub mod io;
+pub mod latency;
 pub mod loader;
 pub mod lua_env;
 pub mod math;
 pub mod metrics;
 pub mod native_plugin;
+pub mod panichandler;
 pub mod projectinfo;
+pub mod scriptdialect;
 pub mod sound;
+pub mod space;
+pub mod statehash;
+pub mod video;
 
 // Re-export commonly used crates for the editor
 use crate::inithelpers::RenderingBlock;
@@ -36,6 +48,7 @@ use sdl2::{
 
 use crate::{
     game_resource::audio_resource::{AUDIO_CHANNELS, AUDIO_SAMPLE_FREQUENCY},
+    io::gamepad::init_gamepad_system,
     sound::init_sound_system,
 };
 
@@ -121,9 +134,13 @@ where
 /// This can be called from main.rs or other binaries like the editor
 pub fn lib_main() {
     use crate::game::Game;
+    use crate::io::framepacing::FrameLimiter;
     use crate::io::fs::init_fs;
     use crate::io::time::now_ms;
     use crate::loader::loader;
+    use crate::panichandler::{install_panic_handler, set_panic_game_info};
+
+    install_panic_handler();
 
     let RenderingBlock {
         sdl,
@@ -138,11 +155,14 @@ pub fn lib_main() {
         })
     });
     init_sound_system(&sdl);
+    init_gamepad_system(&sdl);
 
     // Initialize IDBFS for persistent storage on Emscripten
     init_fs();
 
     loader(move |(project_path, project_info, fs)| {
+        set_panic_game_info(&project_info.title, project_info.version.as_deref());
+        let loop_video = video.clone();
         Game::from_project(
             &project_path,
             &project_info,
@@ -155,15 +175,44 @@ pub fn lib_main() {
                     panic!("Failed to load the game project at {:?}", project_path);
                 };
                 let mut now = now_ms();
+                let mut frame_limiter = FrameLimiter::default();
+                let mut applied_vsync_mode = None;
 
                 set_main_loop_wrapper(move || {
+                    let (target_fps, vsync_mode) = {
+                        let env_state = game.lua_env.env_state.borrow();
+                        (env_state.target_fps, env_state.vsync_mode)
+                    };
+                    let wait_time_ms = frame_limiter.wait_time_ms(now_ms(), target_fps);
+                    if wait_time_ms > 0.0 {
+                        // The browser calls the loop at its own pace and cannot sleep: the frame is skipped instead.
+                        if cfg!(target_os = "emscripten") {
+                            return;
+                        }
+                        std::thread::sleep(std::time::Duration::from_secs_f64(
+                            wait_time_ms / 1000.0,
+                        ));
+                    }
+                    frame_limiter.start_frame(now_ms(), target_fps);
+                    if applied_vsync_mode != Some(vsync_mode) {
+                        // Some drivers do not support adaptive vsync, regular vsync is the closest.
+                        if loop_video
+                            .gl_set_swap_interval(vsync_mode.swap_interval())
+                            .is_err()
+                        {
+                            let _ = loop_video.gl_set_swap_interval(SwapInterval::VSync);
+                        }
+                        applied_vsync_mode = Some(vsync_mode);
+                    }
+
                     let latest_events = event_pump.poll_iter().collect::<Vec<_>>();
                     game.load_resource_as_needed();
                     let now_instant = now_ms();
                     let delta_duration =
                         std::time::Duration::from_micros(((now_instant - now) * 1000.0) as u64);
                     now = now_instant;
-                    game.main_loop(latest_events.iter(), &window, delta_duration, false);
+                    let is_rendered =
+                        game.main_loop(latest_events.iter(), &window, delta_duration, false);
 
                     // These are for debug and are never displayed in the runtime.
                     // We still need to clear them to avoid memory leaks.
@@ -180,7 +229,10 @@ pub fn lib_main() {
                     }
                     console::clear_all_logs();
 
-                    window.borrow().gl_swap_window();
+                    if is_rendered {
+                        window.borrow().gl_swap_window();
+                        game.record_presented_frame();
+                    }
                 });
             },
         );
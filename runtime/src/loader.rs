@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     io::{fs::ReadOnlyFileSystem, localfs::LocalFileSystem, zipfs::ZipFileSystem},
@@ -12,7 +12,7 @@ where
 {
     // Implementation goes here
     LocalFileSystem.read_file(
-        "bundle.vecta",
+        Path::new("bundle.vecta"),
         Box::new(move |result| {
             match result {
                 Some(data) => {
@@ -44,7 +44,7 @@ where
                     // Local filesystem.
                     let path = PathBuf::from("gamedata/game.vecta");
                     LocalFileSystem.read_file(
-                        "gamedata/game.vecta",
+                        Path::new("gamedata/game.vecta"),
                         Box::new(move |result| {
                             let Some(data) = result else {
                                 println!("game.vecta not found in local filesystem");
@@ -1,39 +1,81 @@
 use regex::Regex;
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::{cell::RefCell, path::Path, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    path::Path,
+    rc::Rc,
+};
 
 use vectarine_plugin_sdk::mlua::ObjectLike;
 
+pub mod lua_anim;
+pub mod lua_animation;
 pub mod lua_audio;
 pub mod lua_camera;
 pub mod lua_canvas;
 pub mod lua_coord;
 pub mod lua_debug;
+pub mod lua_ease;
 pub mod lua_event;
 pub mod lua_fastlist;
 pub mod lua_graphics;
 pub mod lua_image;
 pub mod lua_io;
 pub mod lua_loader;
+pub mod lua_particles;
 pub mod lua_persist;
 pub mod lua_physics;
 pub mod lua_resource;
+pub mod lua_snapshot;
 pub mod lua_text;
 pub mod lua_tile;
+pub mod lua_transform;
 pub mod lua_ui;
 pub mod lua_vec2;
 pub mod lua_vec4;
+pub mod lua_video;
 
 use crate::console::{print_lua_error, print_warn};
 use crate::game_resource::ResourceManager;
+use crate::gcpacing::GcPacer;
 use crate::graphics::batchdraw::BatchDraw2d;
 use crate::io::IoEnvState;
+use crate::latency::LatencyTracker;
+use crate::lua_env::lua_physics::inspector::PhysicsWorldRegistry;
+use crate::lua_env::lua_physics::layers::SharedCollisionLayers;
 
 use crate::metrics::MetricsHolder;
+use crate::scriptdialect::{ScriptDialect, find_unsupported_constructs};
 
 pub const BUILT_IN_MODULES: &[&str] = &[
-    "vec", "vec4", "event", "fastlist", "camera", "audio", "tile", "loader", "image", "text",
-    "graphics", "io", "debug", "persist", "resource", "physics", "color", "coord", "canvas", "ui",
+    "vec",
+    "vec4",
+    "event",
+    "fastlist",
+    "camera",
+    "audio",
+    "tile",
+    "loader",
+    "image",
+    "text",
+    "graphics",
+    "io",
+    "debug",
+    "persist",
+    "resource",
+    "physics",
+    "color",
+    "coord",
+    "canvas",
+    "ui",
+    "anim",
+    "animation",
+    "particles",
+    "snapshot",
+    "transform",
+    "ease",
+    "video",
 ];
 
 pub const DEPRECATED_MODULES: &[(&str, &str)] = &[];
@@ -41,6 +83,8 @@ pub const DEPRECATED_MODULES: &[(&str, &str)] = &[];
 pub struct LuaHandle {
     pub lua: vectarine_plugin_sdk::mlua::Lua,
     pub project_path: PathBuf,
+    /// The dialect each script was compiled as, by chunk name, to name it in the errors of the script.
+    pub script_dialects: RefCell<HashMap<String, ScriptDialect>>,
 }
 
 pub struct LuaEnvironment {
@@ -53,6 +97,16 @@ pub struct LuaEnvironment {
 
     pub metrics: Rc<RefCell<MetricsHolder>>,
     pub resources: Rc<ResourceManager>,
+    pub gc_pacer: Rc<RefCell<GcPacer>>,
+    pub collision_layers: Rc<RefCell<SharedCollisionLayers>>,
+    /// Set from `deterministic_physics` in game.vecta.
+    pub deterministic_physics: Rc<Cell<bool>>,
+    /// The duration of a fixed tick in seconds when the project sets `fixed_tick_rate`, used by `World2:step()`.
+    pub fixed_tick_duration: Rc<Cell<Option<f32>>>,
+    /// The physics worlds of the game, shown in the inspector of the editor.
+    pub physics_worlds: Rc<RefCell<PhysicsWorldRegistry>>,
+    pub tool_store: lua_persist::LuaToolStore,
+    pub latency: Rc<RefCell<LatencyTracker>>,
 }
 
 impl LuaEnvironment {
@@ -80,15 +134,12 @@ impl LuaEnvironment {
 
         let lua = vectarine_plugin_sdk::mlua::Lua::new_with(lua_libs, lua_options)
             .expect("Failed to create Lua");
-        lua.set_compiler(
-            vectarine_plugin_sdk::mlua::chunk::Compiler::new()
-                .set_optimization_level(2)
-                .set_type_info_level(1),
-        );
+        lua.set_compiler(ScriptDialect::Luau.compiler());
         let _ = lua.sandbox(false);
         let lua_handle = Rc::new(LuaHandle {
             lua,
             project_path: resources.get_resource_path(),
+            script_dialects: RefCell::new(HashMap::new()),
         });
 
         // We create a table used to store rust state that is tied to the lua environment, for internal use.
@@ -137,6 +188,17 @@ impl LuaEnvironment {
             lua_image::setup_image_api(&lua_handle.lua, &batch, &env_state, &resources).unwrap();
         register_vectarine_module(&lua_handle.lua, "image", image_module);
 
+        let anim_module = lua_anim::setup_anim_api(&lua_handle.lua, &batch, &resources).unwrap();
+        register_vectarine_module(&lua_handle.lua, "anim", anim_module);
+
+        let animation_module =
+            lua_animation::setup_animation_api(&lua_handle.lua, &batch, &resources).unwrap();
+        register_vectarine_module(&lua_handle.lua, "animation", animation_module);
+
+        let particles_module =
+            lua_particles::setup_particles_api(&lua_handle.lua, &batch, &resources).unwrap();
+        register_vectarine_module(&lua_handle.lua, "particles", particles_module);
+
         let text_module =
             lua_text::setup_text_api(&lua_handle.lua, &batch, &env_state, &resources).unwrap();
         register_vectarine_module(&lua_handle.lua, "text", text_module);
@@ -146,20 +208,54 @@ impl LuaEnvironment {
                 .unwrap();
         register_vectarine_module(&lua_handle.lua, "graphics", graphics_module);
 
-        let io_module = lua_io::setup_io_api(&lua_handle.lua, &env_state).unwrap();
+        let io_module = lua_io::setup_io_api(&lua_handle.lua, &env_state, &resources).unwrap();
         register_vectarine_module(&lua_handle.lua, "io", io_module);
 
         let camera_module = lua_camera::setup_camera_api(&lua_handle.lua, &env_state).unwrap();
         register_vectarine_module(&lua_handle.lua, "camera", camera_module);
 
-        let debug_module = lua_debug::setup_debug_api(&lua_handle.lua, &metrics).unwrap();
+        let transform_module = lua_transform::setup_transform_api(&lua_handle.lua).unwrap();
+        register_vectarine_module(&lua_handle.lua, "transform", transform_module);
+
+        let ease_module = lua_ease::setup_ease_api(&lua_handle.lua).unwrap();
+        register_vectarine_module(&lua_handle.lua, "ease", ease_module);
+
+        let snapshot_module = lua_snapshot::setup_snapshot_api(&lua_handle.lua).unwrap();
+        register_vectarine_module(&lua_handle.lua, "snapshot", snapshot_module);
+
+        let gc_pacer = Rc::new(RefCell::new(GcPacer::new(&lua_handle.lua)));
+        let tool_store =
+            lua_persist::setup_tool_store(&lua_handle.lua, &lua_handle.project_path).unwrap();
+        // The editor saves the tool store of the game it runs when it crashes, which is the last one created.
+        #[cfg(feature = "editor")]
+        tool_store.flush_on_crash();
+        let latency = Rc::new(RefCell::new(LatencyTracker::default()));
+        let debug_module =
+            lua_debug::setup_debug_api(&lua_handle.lua, &metrics, &gc_pacer, &tool_store, &latency)
+                .unwrap();
         register_vectarine_module(&lua_handle.lua, "debug", debug_module);
 
         let audio_module =
             lua_audio::setup_audio_api(&lua_handle.lua, &env_state, &resources).unwrap();
         register_vectarine_module(&lua_handle.lua, "audio", audio_module);
 
-        let physics_module = lua_physics::setup_physics_api(&lua_handle.lua, &resources).unwrap();
+        let video_module =
+            lua_video::setup_video_api(&lua_handle.lua, &batch, &env_state, &resources).unwrap();
+        register_vectarine_module(&lua_handle.lua, "video", video_module);
+
+        let collision_layers = Rc::new(RefCell::new(SharedCollisionLayers::default()));
+        let deterministic_physics = Rc::new(Cell::new(false));
+        let fixed_tick_duration = Rc::new(Cell::new(None));
+        let physics_worlds = Rc::new(RefCell::new(PhysicsWorldRegistry::default()));
+        let physics_module = lua_physics::setup_physics_api(
+            &lua_handle.lua,
+            &resources,
+            &collision_layers,
+            &deterministic_physics,
+            &fixed_tick_duration,
+            &physics_worlds,
+        )
+        .unwrap();
         register_vectarine_module(&lua_handle.lua, "physics", physics_module);
 
         let tile_module = lua_tile::setup_tile_api(&lua_handle.lua, &resources).unwrap();
@@ -222,9 +318,20 @@ impl LuaEnvironment {
             default_events,
             resources,
             metrics,
+            gc_pacer,
+            collision_layers,
+            deterministic_physics,
+            fixed_tick_duration,
+            physics_worlds,
+            tool_store,
+            latency,
         }
     }
 
+    pub fn registered_modules(&self) -> Vec<RegisteredModule> {
+        get_registered_modules(&self.lua_handle.lua)
+    }
+
     pub fn run_file_and_display_error(&self, file_content: &[u8], file_path: &Path) {
         run_file_and_display_error_from_lua_handle(&self.lua_handle, file_content, file_path, None);
     }
@@ -256,29 +363,59 @@ pub fn add_fn_to_table<F, A, R>(
     table.set(name, lua.create_function(func).unwrap()).unwrap();
 }
 
+/// Prepares a script to run, compiled for its dialect. In the editor, the constructs of Lua that Luau does not support
+/// are reported for the scripts in Lua compatibility mode.
+fn load_script_chunk<'a>(
+    lua_handle: &'a LuaHandle,
+    file_content: &'a [u8],
+    file_path: &Path,
+) -> vectarine_plugin_sdk::mlua::Chunk<'a> {
+    let dialect = ScriptDialect::detect(file_path, file_content);
+    let chunk_name = file_path.to_string_lossy().to_string();
+    // Exported scripts are bytecode, which is not scanned.
+    if cfg!(feature = "editor")
+        && dialect == ScriptDialect::LuaCompat
+        && let Ok(source) = std::str::from_utf8(file_content)
+    {
+        for construct in find_unsupported_constructs(source) {
+            print_warn(format!(
+                "{chunk_name}:{}: {}",
+                construct.line, construct.message
+            ));
+        }
+    }
+    lua_handle
+        .script_dialects
+        .borrow_mut()
+        .insert(chunk_name.clone(), dialect);
+    lua_handle
+        .lua
+        .load(file_content)
+        .set_name(format!("@{chunk_name}"))
+        .set_compiler(dialect.compiler())
+}
+
 /// Run the given Lua file content assuming it is at the given path.
 /// If the file returns a table, and a target_table is provided, the table will be merged into the target_table.
+/// Returns true if the script ran without errors.
 pub fn run_file_and_display_error_from_lua_handle(
     lua_handle: &LuaHandle,
     file_content: &[u8],
     file_path: &Path,
     target_table: Option<&vectarine_plugin_sdk::mlua::Table>,
-) {
-    // lua.set_compiler(compiler);
-    let lua_chunk = lua_handle.lua.load(file_content);
-    // Note: We could change the optimization level of the chunk here (for example, inside the runtime)
-    let result = lua_chunk
-        .set_name(format!("@{}", file_path.to_string_lossy()))
+) -> bool {
+    let result = load_script_chunk(lua_handle, file_content, file_path)
         .eval::<vectarine_plugin_sdk::mlua::Value>();
 
     match result {
         Err(error) => {
             print_lua_error_from_error(lua_handle, &error);
+            false
         }
         Ok(value) => {
             // Merge the table with the argument table if provided.
             let Some(target_table) = target_table else {
-                return;
+                return true;
             };
             let table = value.as_table();
             let Some(table) = table else {
@@ -286,19 +423,155 @@ pub fn run_file_and_display_error_from_lua_handle(
                     "Script {} did not return a table, so we cannot put its exports into the table provided when calling LoadScript.",
                     file_path.to_string_lossy()
                 ));
-                return;
+                return true;
             };
 
-            for pair in table
-                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
-            {
-                let Ok((key, value)) = pair else { continue };
-                let _ = target_table.raw_set(key, value);
+            if let Err(error) = merge_exports_preserving_identity(target_table, table) {
+                print_lua_error_from_error(lua_handle, &error);
+                return false;
             }
+            true
         }
     }
 }
 
+/// Runs the main script again after it was modified, leaving the globals untouched if it fails.
+///
+/// The chunk runs in a scratch environment whose reads fall back to the globals. When it succeeds, the globals it
+/// defined are moved into the real globals and the scratch environment starts forwarding its writes to them, so the
+/// functions of the chunk keep sharing their globals with the other scripts.
+/// Changes made by a failing chunk to existing tables (like `Player.speed = 3`) cannot be undone.
+/// Returns true if the chunk ran without errors.
+pub fn rerun_main_script_transactionally(
+    lua_handle: &LuaHandle,
+    file_content: &[u8],
+    file_path: &Path,
+) -> bool {
+    let result = run_in_scratch_environment(lua_handle, file_content, file_path);
+    if let Err(error) = &result {
+        print_lua_error_from_error(lua_handle, error);
+    }
+    result.is_ok()
+}
+
+fn run_in_scratch_environment(
+    lua_handle: &LuaHandle,
+    file_content: &[u8],
+    file_path: &Path,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    let lua = &lua_handle.lua;
+    let globals = lua.globals();
+    let scratch_env = lua.create_table()?;
+    let scratch_metatable = lua.create_table()?;
+    scratch_metatable.raw_set("__index", globals.clone())?;
+    scratch_env.set_metatable(Some(scratch_metatable.clone()))?;
+
+    load_script_chunk(lua_handle, file_content, file_path)
+        .set_environment(scratch_env.clone())
+        .exec()?;
+
+    let defined_globals = scratch_env
+        .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+        .collect::<vectarine_plugin_sdk::mlua::Result<Vec<_>>>()?;
+    for (key, value) in defined_globals {
+        globals.raw_set(key, value)?;
+    }
+    scratch_env.clear()?;
+    scratch_metatable.raw_set("__newindex", globals)?;
+    Ok(())
+}
+
+/// Merges the exports of a (re)loaded script into the table holding the previous exports.
+///
+/// Nested tables that exist in both the old and the new exports are merged in place instead of being replaced,
+/// so that anything holding a reference to them (metatables of existing instances, other modules) sees the new values.
+/// References to a new nested table (like `Enemy.__index = Enemy`) are re-pointed to the old table it was merged into.
+/// Keys that are no longer exported are removed.
+///
+/// What cannot be updated are references captured before the reload by the new chunk itself or by `local x = Module.x`
+/// statements in other scripts: those still point to the previous values.
+fn merge_exports_preserving_identity(
+    target_table: &vectarine_plugin_sdk::mlua::Table,
+    exports: &vectarine_plugin_sdk::mlua::Table,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    // Pairs of (old table, new table) to merge, and the mapping from the new tables to the old ones.
+    let mut merged_pairs = Vec::new();
+    let mut replacements = HashMap::new();
+    collect_tables_to_merge(target_table, exports, &mut merged_pairs, &mut replacements)?;
+
+    let substitute = |value: vectarine_plugin_sdk::mlua::Value| match &value {
+        vectarine_plugin_sdk::mlua::Value::Table(table) => replacements
+            .get(&table.to_pointer())
+            .map(|old| vectarine_plugin_sdk::mlua::Value::Table(old.clone()))
+            .unwrap_or(value),
+        _ => value,
+    };
+
+    for (old, new) in &merged_pairs {
+        let stale_keys = old
+            .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+            .filter_map(|pair| pair.ok())
+            .map(|(key, _)| key)
+            .filter(|key| {
+                new.raw_get::<vectarine_plugin_sdk::mlua::Value>(key.clone())
+                    .is_ok_and(|value| value.is_nil())
+            })
+            .collect::<Vec<_>>();
+        for key in stale_keys {
+            old.raw_set(key, vectarine_plugin_sdk::mlua::Value::Nil)?;
+        }
+
+        let new_pairs = new
+            .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+            .collect::<vectarine_plugin_sdk::mlua::Result<Vec<_>>>()?;
+        for (key, value) in new_pairs {
+            let value = substitute(value);
+            // The new table is also re-pointed, so that instances created by the new code share the old tables.
+            new.raw_set(key.clone(), value.clone())?;
+            old.raw_set(key, value)?;
+        }
+
+        let metatable = new.metatable().and_then(|metatable| {
+            substitute(vectarine_plugin_sdk::mlua::Value::Table(metatable))
+                .as_table()
+                .cloned()
+        });
+        old.set_metatable(metatable)?;
+    }
+    Ok(())
+}
+
+fn collect_tables_to_merge(
+    old: &vectarine_plugin_sdk::mlua::Table,
+    new: &vectarine_plugin_sdk::mlua::Table,
+    merged_pairs: &mut Vec<(
+        vectarine_plugin_sdk::mlua::Table,
+        vectarine_plugin_sdk::mlua::Table,
+    )>,
+    replacements: &mut HashMap<*const std::ffi::c_void, vectarine_plugin_sdk::mlua::Table>,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    if old.to_pointer() == new.to_pointer() || replacements.contains_key(&new.to_pointer()) {
+        return Ok(());
+    }
+    replacements.insert(new.to_pointer(), old.clone());
+    merged_pairs.push((old.clone(), new.clone()));
+
+    for pair in new.pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+    {
+        let (key, value) = pair?;
+        let vectarine_plugin_sdk::mlua::Value::Table(new_nested) = value else {
+            continue;
+        };
+        let Ok(vectarine_plugin_sdk::mlua::Value::Table(old_nested)) =
+            old.raw_get::<vectarine_plugin_sdk::mlua::Value>(key)
+        else {
+            continue;
+        };
+        collect_tables_to_merge(&old_nested, &new_nested, merged_pairs, replacements)?;
+    }
+    Ok(())
+}
+
 pub fn register_vectarine_module(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     name: &'static str,
@@ -310,10 +583,71 @@ pub fn register_vectarine_module(
             name
         );
     }
+    get_registered_modules_table(lua)
+        .and_then(|modules| modules.raw_set(name, module.clone()))
+        .expect("Failed to record vectarine module");
     lua.register_module(&format!("@vectarine/{}", name), module)
         .expect("Failed to register vectarine module");
 }
 
+const REGISTERED_MODULES_KEY: &str = "registered_modules";
+
+fn get_registered_modules_table(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let internals = get_internals(lua);
+    if let Some(modules) =
+        internals.raw_get::<Option<vectarine_plugin_sdk::mlua::Table>>(REGISTERED_MODULES_KEY)?
+    {
+        return Ok(modules);
+    }
+    let modules = lua.create_table()?;
+    internals.raw_set(REGISTERED_MODULES_KEY, modules.clone())?;
+    Ok(modules)
+}
+
+/// A value exported by a module of the Lua API.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleMember {
+    pub name: String,
+    /// The Lua type of the value, like "function" or "number".
+    pub type_name: &'static str,
+}
+
+/// A `@vectarine/*` module with its members sorted by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisteredModule {
+    pub name: String,
+    pub members: Vec<ModuleMember>,
+}
+
+/// The modules registered with `register_vectarine_module`, sorted by name, for the tools documenting the API.
+pub fn get_registered_modules(lua: &vectarine_plugin_sdk::mlua::Lua) -> Vec<RegisteredModule> {
+    let Ok(modules) = get_registered_modules_table(lua) else {
+        return Vec::new();
+    };
+    let mut modules = modules
+        .pairs::<String, vectarine_plugin_sdk::mlua::Table>()
+        .flatten()
+        .map(|(name, module)| {
+            let mut members = module
+                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+                .flatten()
+                .filter_map(|(key, value)| {
+                    Some(ModuleMember {
+                        name: key.as_string()?.to_string_lossy(),
+                        type_name: value.type_name(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            members.sort_by(|a, b| a.name.cmp(&b.name));
+            RegisteredModule { name, members }
+        })
+        .collect::<Vec<_>>();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+    modules
+}
+
 pub fn stringify_lua_value(value: &vectarine_plugin_sdk::mlua::Value) -> String {
     let mut seen = Vec::new();
     stringify_lua_value_helper(value, &mut seen)
@@ -439,7 +773,7 @@ fn stringify_lua_value_helper(
     }
 }
 
-const UNSAFE_INTERNALS_KEY: &str = "Vectarine_Unsafe_Internal";
+pub const UNSAFE_INTERNALS_KEY: &str = "Vectarine_Unsafe_Internal";
 
 pub fn get_internals(lua: &vectarine_plugin_sdk::mlua::Lua) -> vectarine_plugin_sdk::mlua::Table {
     let globals = lua.globals();
@@ -488,6 +822,192 @@ pub fn print_lua_error_from_error(
 
     let error_msg = error.to_string();
     let (line, file_path) = get_line_and_file_of_error(error);
+    let dialect = lua_handle.script_dialects.borrow().get(&file_path).copied();
+    let error_msg = match dialect {
+        Some(dialect) if dialect.is_shown_in_errors(Path::new(&file_path)) => {
+            format!("[{}] {error_msg}", dialect.name())
+        }
+        _ => error_msg,
+    };
     let line_content = extract_file_lines_from_error(lua_handle, &file_path, line);
     print_lua_error(error_msg, file_path, line, line_content);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::Path, path::PathBuf};
+
+    use super::{
+        LuaHandle, ModuleMember, UNSAFE_INTERNALS_KEY, get_registered_modules,
+        merge_exports_preserving_identity, register_vectarine_module,
+        rerun_main_script_transactionally,
+    };
+    use vectarine_plugin_sdk::mlua::{Function, Lua, Table};
+
+    const ENEMY_MODULE: &str = r#"
+        local module = {}
+        local Enemy = {}
+        Enemy.__index = Enemy
+        module.Enemy = Enemy
+        function Enemy.new() return setmetatable({ health = 3 }, Enemy) end
+        function Enemy:describe() return "old " .. self.health end
+        module.removed = true
+        return module
+    "#;
+
+    const EDITED_ENEMY_MODULE: &str = r#"
+        local module = {}
+        local Enemy = {}
+        Enemy.__index = Enemy
+        module.Enemy = Enemy
+        function Enemy.new() return setmetatable({ health = 5 }, Enemy) end
+        function Enemy:describe() return "new " .. self.health end
+        return module
+    "#;
+
+    #[test]
+    fn reloaded_classes_keep_their_identity() {
+        let lua = Lua::new();
+        let exports: Table = lua.load(ENEMY_MODULE).eval().unwrap();
+        let old_enemy_class: Table = exports.get("Enemy").unwrap();
+        let new_function: Function = old_enemy_class.get("new").unwrap();
+        let instance: Table = new_function.call(()).unwrap();
+
+        let reloaded: Table = lua.load(EDITED_ENEMY_MODULE).eval().unwrap();
+        merge_exports_preserving_identity(&exports, &reloaded).unwrap();
+
+        let enemy_class: Table = exports.get("Enemy").unwrap();
+        assert_eq!(enemy_class.to_pointer(), old_enemy_class.to_pointer());
+        // The instance created before the reload uses the new methods.
+        let description: String = instance.call_method("describe", ()).unwrap();
+        assert_eq!(description, "new 3");
+        // `Enemy.__index = Enemy` points to the preserved table.
+        let index: Table = enemy_class.get("__index").unwrap();
+        assert_eq!(index.to_pointer(), old_enemy_class.to_pointer());
+        assert!(!exports.contains_key("removed").unwrap());
+    }
+
+    #[test]
+    fn instances_created_after_the_reload_share_the_old_class() {
+        let lua = Lua::new();
+        let exports: Table = lua.load(ENEMY_MODULE).eval().unwrap();
+        let old_enemy_class: Table = exports.get("Enemy").unwrap();
+
+        let reloaded: Table = lua.load(EDITED_ENEMY_MODULE).eval().unwrap();
+        merge_exports_preserving_identity(&exports, &reloaded).unwrap();
+
+        let new_function: Function = old_enemy_class.get("new").unwrap();
+        let instance: Table = new_function.call(()).unwrap();
+        // The new chunk captured its own Enemy table, but its `__index` is re-pointed to the old one.
+        let metatable = instance.metatable().unwrap();
+        let index: Table = metatable.get("__index").unwrap();
+        assert_eq!(index.to_pointer(), old_enemy_class.to_pointer());
+        let description: String = instance.call_method("describe", ()).unwrap();
+        assert_eq!(description, "new 5");
+    }
+
+    fn new_lua_handle() -> LuaHandle {
+        LuaHandle {
+            lua: Lua::new(),
+            project_path: PathBuf::new(),
+            script_dialects: RefCell::new(Default::default()),
+        }
+    }
+
+    #[test]
+    fn failing_reloads_leave_the_globals_untouched() {
+        let lua_handle = new_lua_handle();
+        let path = Path::new("scripts/game.luau");
+        lua_handle
+            .lua
+            .load("Score = 1\nfunction Update() Score = Score + 1 end")
+            .exec()
+            .unwrap();
+
+        let is_success = rerun_main_script_transactionally(
+            &lua_handle,
+            b"Score = 100\nfunction Update() error('broken') end\nerror('boom')",
+            path,
+        );
+        assert!(!is_success);
+        let globals = lua_handle.lua.globals();
+        assert_eq!(globals.get::<i64>("Score").unwrap(), 1);
+        globals
+            .get::<Function>("Update")
+            .unwrap()
+            .call::<()>(())
+            .unwrap();
+        assert_eq!(globals.get::<i64>("Score").unwrap(), 2);
+    }
+
+    #[test]
+    fn successful_reloads_share_the_globals_with_the_other_scripts() {
+        let lua_handle = new_lua_handle();
+        let path = Path::new("scripts/game.luau");
+        lua_handle.lua.load("Score = 1").exec().unwrap();
+
+        let is_success = rerun_main_script_transactionally(
+            &lua_handle,
+            b"Bonus = 10\nfunction Update() Score = Score + Bonus end",
+            path,
+        );
+        assert!(is_success);
+        let globals = lua_handle.lua.globals();
+        assert_eq!(globals.get::<i64>("Bonus").unwrap(), 10);
+        globals
+            .get::<Function>("Update")
+            .unwrap()
+            .call::<()>(())
+            .unwrap();
+        // The write made by the reloaded Update is seen by the other scripts.
+        assert_eq!(globals.get::<i64>("Score").unwrap(), 11);
+        lua_handle.lua.load("Bonus = 20").exec().unwrap();
+        globals
+            .get::<Function>("Update")
+            .unwrap()
+            .call::<()>(())
+            .unwrap();
+        assert_eq!(globals.get::<i64>("Score").unwrap(), 31);
+    }
+
+    #[test]
+    fn registered_modules_are_listed_with_their_members() {
+        let lua = Lua::new();
+        lua.globals()
+            .raw_set(UNSAFE_INTERNALS_KEY, lua.create_table().unwrap())
+            .unwrap();
+        let vec_module: Table = lua
+            .load("return { half = 0.5, new = function() end, [1] = 'not a name' }")
+            .eval()
+            .unwrap();
+        let camera_module: Table = lua
+            .load("return { shake = function() end }")
+            .eval()
+            .unwrap();
+        register_vectarine_module(&lua, "vec", vec_module);
+        register_vectarine_module(&lua, "camera", camera_module);
+
+        let modules = get_registered_modules(&lua);
+        let names = modules
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["camera", "vec"]);
+        assert_eq!(
+            modules[1].members,
+            vec![
+                ModuleMember {
+                    name: "half".to_string(),
+                    type_name: "number",
+                },
+                ModuleMember {
+                    name: "new".to_string(),
+                    type_name: "function",
+                },
+            ]
+        );
+        // The scripts require the same table.
+        let required: Table = lua.load("return require('@vectarine/vec')").eval().unwrap();
+        assert!(required.contains_key("half").unwrap());
+    }
+}
@@ -0,0 +1,1005 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
+
+use crate::{
+    auto_impl_lua_clone,
+    game_resource::{ResourceManager, image_resource::ImageResource},
+    graphics::{batchdraw, shape::Quad},
+    lua_env::{
+        add_fn_to_table,
+        lua_coord::{get_pos_as_vec2, get_size_as_vec2},
+        lua_image::ImageResourceId,
+        lua_vec2::Vec2,
+        lua_vec4::{Vec4, WHITE},
+        stringify_lua_value,
+    },
+};
+
+// MARK: Definition
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParamValue {
+    Number(f64),
+    Boolean(bool),
+}
+
+impl ParamValue {
+    fn from_lua_value(value: &vectarine_plugin_sdk::mlua::Value) -> Option<Self> {
+        match value {
+            vectarine_plugin_sdk::mlua::Value::Boolean(b) => Some(Self::Boolean(*b)),
+            vectarine_plugin_sdk::mlua::Value::Integer(n) => Some(Self::Number(*n as f64)),
+            vectarine_plugin_sdk::mlua::Value::Number(n) => Some(Self::Number(*n)),
+            _ => None,
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "number",
+            Self::Boolean(_) => "boolean",
+        }
+    }
+}
+
+impl IntoLua for ParamValue {
+    fn into_lua(
+        self,
+        _lua: &vectarine_plugin_sdk::mlua::Lua,
+    ) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Value> {
+        Ok(match self {
+            Self::Number(n) => vectarine_plugin_sdk::mlua::Value::Number(n),
+            Self::Boolean(b) => vectarine_plugin_sdk::mlua::Value::Boolean(b),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+}
+
+impl Operator {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "eq" => Some(Self::Eq),
+            "ne" => Some(Self::Ne),
+            "gt" => Some(Self::Gt),
+            "lt" => Some(Self::Lt),
+            "ge" => Some(Self::Ge),
+            "le" => Some(Self::Le),
+            _ => None,
+        }
+    }
+
+    fn is_ordering(&self) -> bool {
+        !matches!(self, Self::Eq | Self::Ne)
+    }
+
+    fn compare(&self, value: ParamValue, target: ParamValue) -> bool {
+        match (value, target) {
+            (ParamValue::Number(value), ParamValue::Number(target)) => match self {
+                Self::Eq => value == target,
+                Self::Ne => value != target,
+                Self::Gt => value > target,
+                Self::Lt => value < target,
+                Self::Ge => value >= target,
+                Self::Le => value <= target,
+            },
+            (ParamValue::Boolean(value), ParamValue::Boolean(target)) => match self {
+                Self::Eq => value == target,
+                Self::Ne => value != target,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    param: String,
+    operator: Operator,
+    target: ParamValue,
+}
+
+#[derive(Debug, Clone)]
+struct Transition {
+    to: usize,
+    /// All the conditions need to be true for the transition to happen.
+    conditions: Vec<Condition>,
+    /// Duration in seconds during which the previous animation fades out.
+    blend_time: f32,
+}
+
+/// A transition from the `transitions` list of the definition. `from` is None for transitions from any state.
+#[derive(Debug, Clone)]
+struct GlobalTransition {
+    from: Option<usize>,
+    transition: Transition,
+}
+
+/// An animation made of frames placed from left to right in an image.
+#[derive(Debug, Clone)]
+struct AnimationState {
+    name: String,
+    image: ImageResourceId,
+    /// Position and size of the first frame, in uv coordinates like `drawPart`.
+    frame_pos: Vec2,
+    frame_size: Vec2,
+    frame_count: u32,
+    fps: f32,
+    is_looping: bool,
+    /// Transitions out of the state are only evaluated after this many seconds in the state.
+    min_time: f32,
+    transitions: Vec<Transition>,
+}
+
+impl AnimationState {
+    fn frame_index(&self, time_in_state: f32) -> u32 {
+        let frame = (time_in_state * self.fps).max(0.0) as u32;
+        if self.is_looping {
+            frame % self.frame_count
+        } else {
+            frame.min(self.frame_count - 1)
+        }
+    }
+
+    fn is_finished(&self, time_in_state: f32) -> bool {
+        !self.is_looping && time_in_state * self.fps >= self.frame_count as f32
+    }
+}
+
+fn key_to_string(key: &vectarine_plugin_sdk::mlua::Value) -> String {
+    match key {
+        vectarine_plugin_sdk::mlua::Value::String(key) => key.to_string_lossy(),
+        key => stringify_lua_value(key),
+    }
+}
+
+pub fn definition_error(
+    path: &str,
+    message: impl fmt::Display,
+) -> vectarine_plugin_sdk::mlua::Error {
+    vectarine_plugin_sdk::mlua::Error::RuntimeError(format!("{path}: {message}"))
+}
+
+pub fn check_known_fields(
+    table: &vectarine_plugin_sdk::mlua::Table,
+    known_fields: &[&str],
+    path: &str,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    for pair in
+        table.pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+    {
+        let (key, _) = pair?;
+        let key = key_to_string(&key);
+        if !known_fields.contains(&key.as_str()) {
+            return Err(definition_error(path, format!("unknown field '{key}'")));
+        }
+    }
+    Ok(())
+}
+
+/// Reads an optional field, reporting the path of the field when it has the wrong type.
+pub fn get_field<T: FromLua>(
+    table: &vectarine_plugin_sdk::mlua::Table,
+    key: &str,
+    path: &str,
+    expected: &str,
+) -> vectarine_plugin_sdk::mlua::Result<Option<T>> {
+    table
+        .raw_get::<Option<T>>(key)
+        .map_err(|_| definition_error(&join_path(path, key), format!("expected {expected}")))
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn parse_conditions(
+    when: &vectarine_plugin_sdk::mlua::Table,
+    params: &HashMap<String, ParamValue>,
+    path: &str,
+) -> vectarine_plugin_sdk::mlua::Result<Vec<Condition>> {
+    let mut conditions = Vec::new();
+    for pair in when.pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+    {
+        let (param, comparison) = pair?;
+        let param = key_to_string(&param);
+        let Some(declared) = params.get(&param) else {
+            return Err(definition_error(
+                path,
+                format!("unknown parameter '{param}', parameters need to be declared in 'params'"),
+            ));
+        };
+
+        // `{grounded = true}` is a shorthand for `{grounded = {eq = true}}`
+        let comparisons = match comparison {
+            vectarine_plugin_sdk::mlua::Value::Table(comparisons) => comparisons
+                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+                .map(|pair| {
+                    let (operator, target) = pair?;
+                    Ok((key_to_string(&operator), target))
+                })
+                .collect::<vectarine_plugin_sdk::mlua::Result<Vec<_>>>()?,
+            target => vec![("eq".to_string(), target)],
+        };
+        for (operator_name, target) in comparisons {
+            let Some(operator) = Operator::from_name(&operator_name) else {
+                return Err(definition_error(
+                    path,
+                    format!("unknown operator '{operator_name}'"),
+                ));
+            };
+            let Some(target) = ParamValue::from_lua_value(&target) else {
+                return Err(definition_error(
+                    path,
+                    format!(
+                        "'{param}' is compared to a {}, expected a number or a boolean",
+                        target.type_name()
+                    ),
+                ));
+            };
+            if target.type_name() != declared.type_name() {
+                return Err(definition_error(
+                    path,
+                    format!(
+                        "'{param}' is a {} but is compared to a {}",
+                        declared.type_name(),
+                        target.type_name()
+                    ),
+                ));
+            }
+            if operator.is_ordering() && matches!(target, ParamValue::Boolean(_)) {
+                return Err(definition_error(
+                    path,
+                    format!("operator '{operator_name}' cannot compare booleans"),
+                ));
+            }
+            conditions.push(Condition {
+                param: param.clone(),
+                operator,
+                target,
+            });
+        }
+    }
+    Ok(conditions)
+}
+
+fn parse_state_name(
+    value: Option<String>,
+    state_names: &[String],
+    path: &str,
+) -> vectarine_plugin_sdk::mlua::Result<usize> {
+    let Some(name) = value else {
+        return Err(definition_error(path, "missing state name"));
+    };
+    state_names
+        .iter()
+        .position(|state_name| *state_name == name)
+        .ok_or_else(|| definition_error(path, format!("unknown state '{name}'")))
+}
+
+/// Parses a transition. Returns the index of the `from` state when it is given, `usize::MAX` meaning any state.
+fn parse_transition(
+    table: &vectarine_plugin_sdk::mlua::Table,
+    state_names: &[String],
+    params: &HashMap<String, ParamValue>,
+    path: &str,
+) -> vectarine_plugin_sdk::mlua::Result<(Option<usize>, Transition)> {
+    check_known_fields(table, &["from", "to", "when", "blend"], path)?;
+    let from = match get_field::<String>(table, "from", path, "a state name")? {
+        Some(from) if from == "*" => Some(usize::MAX),
+        Some(from) => Some(parse_state_name(
+            Some(from),
+            state_names,
+            &format!("{path}.from"),
+        )?),
+        None => None,
+    };
+    let to = parse_state_name(
+        get_field::<String>(table, "to", path, "a state name")?,
+        state_names,
+        &format!("{path}.to"),
+    )?;
+    let conditions =
+        match get_field::<vectarine_plugin_sdk::mlua::Table>(table, "when", path, "a table")? {
+            Some(when) => parse_conditions(&when, params, &format!("{path}.when"))?,
+            None => Vec::new(),
+        };
+    let blend_time = get_field::<f32>(table, "blend", path, "a number")?.unwrap_or(0.0);
+    if blend_time < 0.0 {
+        return Err(definition_error(
+            &format!("{path}.blend"),
+            "needs to be positive",
+        ));
+    }
+    Ok((
+        from,
+        Transition {
+            to,
+            conditions,
+            blend_time,
+        },
+    ))
+}
+
+fn parse_transition_list(
+    table: Option<vectarine_plugin_sdk::mlua::Table>,
+    state_names: &[String],
+    params: &HashMap<String, ParamValue>,
+    path: &str,
+) -> vectarine_plugin_sdk::mlua::Result<Vec<(Option<usize>, Transition)>> {
+    let Some(table) = table else {
+        return Ok(Vec::new());
+    };
+    let mut transitions = Vec::new();
+    for (index, transition) in table
+        .sequence_values::<vectarine_plugin_sdk::mlua::Value>()
+        .enumerate()
+    {
+        let item_path = format!("{path}[{}]", index + 1);
+        let vectarine_plugin_sdk::mlua::Value::Table(transition) = transition? else {
+            return Err(definition_error(&item_path, "expected a table"));
+        };
+        transitions.push(parse_transition(
+            &transition,
+            state_names,
+            params,
+            &item_path,
+        )?);
+    }
+    Ok(transitions)
+}
+
+fn parse_state(
+    table: &vectarine_plugin_sdk::mlua::Table,
+    name: &str,
+    state_names: &[String],
+    params: &HashMap<String, ParamValue>,
+    default_min_time: f32,
+) -> vectarine_plugin_sdk::mlua::Result<AnimationState> {
+    let path = format!("states.{name}");
+    check_known_fields(
+        table,
+        &[
+            "image",
+            "framePos",
+            "frameSize",
+            "frameCount",
+            "fps",
+            "loop",
+            "minTime",
+            "transitions",
+        ],
+        &path,
+    )?;
+    let Some(image) = get_field::<ImageResourceId>(table, "image", &path, "an ImageResource")?
+    else {
+        return Err(definition_error(&format!("{path}.image"), "missing image"));
+    };
+    let frame_count = get_field::<u32>(table, "frameCount", &path, "a number")?.unwrap_or(1);
+    if frame_count == 0 {
+        return Err(definition_error(
+            &format!("{path}.frameCount"),
+            "needs at least one frame",
+        ));
+    }
+    let fps = get_field::<f32>(table, "fps", &path, "a number")?.unwrap_or(10.0);
+    if fps <= 0.0 {
+        return Err(definition_error(
+            &format!("{path}.fps"),
+            "needs to be greater than 0",
+        ));
+    }
+
+    let transitions_path = format!("{path}.transitions");
+    let mut transitions = Vec::new();
+    for (index, (from, transition)) in parse_transition_list(
+        get_field(table, "transitions", &path, "a list of transitions")?,
+        state_names,
+        params,
+        &transitions_path,
+    )?
+    .into_iter()
+    .enumerate()
+    {
+        if from.is_some_and(|from| state_names.get(from).is_none_or(|from| from != name)) {
+            return Err(definition_error(
+                &format!("{transitions_path}[{}].from", index + 1),
+                format!("transitions of the state '{name}' can only start from '{name}'"),
+            ));
+        }
+        transitions.push(transition);
+    }
+
+    Ok(AnimationState {
+        name: name.to_string(),
+        image,
+        frame_pos: get_field(table, "framePos", &path, "a Vec2")?.unwrap_or(Vec2::new(0.0, 0.0)),
+        frame_size: get_field(table, "frameSize", &path, "a Vec2")?.unwrap_or(Vec2::new(1.0, 1.0)),
+        frame_count,
+        fps,
+        is_looping: get_field(table, "loop", &path, "a boolean")?.unwrap_or(true),
+        min_time: get_field(table, "minTime", &path, "a number")?.unwrap_or(default_min_time),
+        transitions,
+    })
+}
+
+// MARK: State machine
+
+#[derive(Debug, Clone, Copy)]
+struct Blend {
+    previous: usize,
+    previous_time_in_state: f32,
+    remaining: f32,
+    duration: f32,
+}
+
+pub struct StateMachine {
+    states: Vec<AnimationState>,
+    global_transitions: Vec<GlobalTransition>,
+    params: HashMap<String, ParamValue>,
+    current: usize,
+    time_in_state: f32,
+    blend: Option<Blend>,
+    on_enter: HashMap<usize, Vec<vectarine_plugin_sdk::mlua::Function>>,
+}
+
+impl StateMachine {
+    /// Validates the whole definition, so that mistakes are reported when the state machine is created instead of
+    /// when a transition happens.
+    fn from_definition(
+        definition: &vectarine_plugin_sdk::mlua::Table,
+    ) -> vectarine_plugin_sdk::mlua::Result<Self> {
+        check_known_fields(
+            definition,
+            &[
+                "initial",
+                "params",
+                "states",
+                "transitions",
+                "minTimeInState",
+            ],
+            "definition",
+        )?;
+
+        let mut params = HashMap::new();
+        if let Some(params_table) =
+            get_field::<vectarine_plugin_sdk::mlua::Table>(definition, "params", "", "a table")?
+        {
+            for pair in params_table
+                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+            {
+                let (name, value) = pair?;
+                let name = key_to_string(&name);
+                let Some(value) = ParamValue::from_lua_value(&value) else {
+                    return Err(definition_error(
+                        &format!("params.{name}"),
+                        format!(
+                            "expected a number or a boolean, got a {}",
+                            value.type_name()
+                        ),
+                    ));
+                };
+                params.insert(name, value);
+            }
+        }
+
+        let Some(states_table) =
+            get_field::<vectarine_plugin_sdk::mlua::Table>(definition, "states", "", "a table")?
+        else {
+            return Err(definition_error("states", "missing states"));
+        };
+        let mut state_tables = Vec::new();
+        for pair in states_table
+            .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+        {
+            let (name, state) = pair?;
+            let name = key_to_string(&name);
+            let vectarine_plugin_sdk::mlua::Value::Table(state) = state else {
+                return Err(definition_error(
+                    &format!("states.{name}"),
+                    "expected a table",
+                ));
+            };
+            state_tables.push((name, state));
+        }
+        // The iteration order of Lua tables is not specified, sorting keeps the indices stable.
+        state_tables.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let state_names = state_tables
+            .iter()
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if state_names.is_empty() {
+            return Err(definition_error("states", "needs at least one state"));
+        }
+
+        let default_min_time =
+            get_field::<f32>(definition, "minTimeInState", "", "a number")?.unwrap_or(0.0);
+        let states = state_tables
+            .iter()
+            .map(|(name, state)| parse_state(state, name, &state_names, &params, default_min_time))
+            .collect::<vectarine_plugin_sdk::mlua::Result<Vec<_>>>()?;
+
+        let mut global_transitions = Vec::new();
+        for (index, (from, transition)) in parse_transition_list(
+            get_field(definition, "transitions", "", "a list of transitions")?,
+            &state_names,
+            &params,
+            "transitions",
+        )?
+        .into_iter()
+        .enumerate()
+        {
+            let Some(from) = from else {
+                return Err(definition_error(
+                    &format!("transitions[{}].from", index + 1),
+                    "missing state name, use '*' to allow the transition from any state",
+                ));
+            };
+            global_transitions.push(GlobalTransition {
+                from: (from != usize::MAX).then_some(from),
+                transition,
+            });
+        }
+
+        let current = parse_state_name(
+            get_field::<String>(definition, "initial", "", "a state name")?,
+            &state_names,
+            "initial",
+        )?;
+
+        Ok(Self {
+            states,
+            global_transitions,
+            params,
+            current,
+            time_in_state: 0.0,
+            blend: None,
+            on_enter: HashMap::new(),
+        })
+    }
+
+    fn get_state_index(&self, name: &str) -> vectarine_plugin_sdk::mlua::Result<usize> {
+        self.states
+            .iter()
+            .position(|state| state.name == name)
+            .ok_or_else(|| {
+                vectarine_plugin_sdk::mlua::Error::RuntimeError(format!("Unknown state '{name}'"))
+            })
+    }
+
+    fn set_param(
+        &mut self,
+        name: &str,
+        value: &vectarine_plugin_sdk::mlua::Value,
+    ) -> vectarine_plugin_sdk::mlua::Result<()> {
+        let Some(param) = self.params.get_mut(name) else {
+            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "Unknown parameter '{name}', parameters need to be declared in 'params'"
+            )));
+        };
+        let Some(value) = ParamValue::from_lua_value(value)
+            .filter(|value| value.type_name() == param.type_name())
+        else {
+            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "Parameter '{name}' is a {}, got a {}",
+                param.type_name(),
+                value.type_name()
+            )));
+        };
+        *param = value;
+        Ok(())
+    }
+
+    fn are_conditions_met(&self, transition: &Transition) -> bool {
+        transition.conditions.iter().all(|condition| {
+            self.params
+                .get(&condition.param)
+                .is_some_and(|value| condition.operator.compare(*value, condition.target))
+        })
+    }
+
+    fn find_transition(&self) -> Option<Transition> {
+        let state = &self.states[self.current];
+        if self.time_in_state < state.min_time {
+            return None;
+        }
+        let global_transitions = self
+            .global_transitions
+            .iter()
+            .filter(|global| global.from.is_none_or(|from| from == self.current))
+            .map(|global| &global.transition)
+            // A transition from any state does not restart the animation it leads to.
+            .filter(|transition| transition.to != self.current);
+        state
+            .transitions
+            .iter()
+            .chain(global_transitions)
+            .find(|transition| self.are_conditions_met(transition))
+            .cloned()
+    }
+
+    fn enter(&mut self, to: usize, blend_time: f32) {
+        self.blend = (blend_time > 0.0).then_some(Blend {
+            previous: self.current,
+            previous_time_in_state: self.time_in_state,
+            remaining: blend_time,
+            duration: blend_time,
+        });
+        self.current = to;
+        self.time_in_state = 0.0;
+    }
+
+    /// Advances the animations and takes at most one transition. Returns the previous state when a transition was taken.
+    fn update(&mut self, delta_time: f32) -> Option<usize> {
+        self.time_in_state += delta_time;
+        if let Some(blend) = self.blend.as_mut() {
+            blend.remaining -= delta_time;
+            blend.previous_time_in_state += delta_time;
+            if blend.remaining <= 0.0 {
+                self.blend = None;
+            }
+        }
+        let transition = self.find_transition()?;
+        let previous = self.current;
+        self.enter(transition.to, transition.blend_time);
+        Some(previous)
+    }
+
+    fn get_on_enter_callbacks(&self) -> Vec<vectarine_plugin_sdk::mlua::Function> {
+        self.on_enter
+            .get(&self.current)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+pub struct LuaStateMachine(Rc<RefCell<StateMachine>>);
+auto_impl_lua_clone!(LuaStateMachine, LuaStateMachine);
+
+impl LuaStateMachine {
+    /// The callbacks are called without borrowing the state machine, so that they can use it.
+    fn call_on_enter_callbacks(&self, previous: usize) -> vectarine_plugin_sdk::mlua::Result<()> {
+        let (callbacks, previous_name) = {
+            let state_machine = self.0.borrow();
+            (
+                state_machine.get_on_enter_callbacks(),
+                state_machine.states[previous].name.clone(),
+            )
+        };
+        for callback in callbacks {
+            callback.call::<()>(previous_name.clone())?;
+        }
+        Ok(())
+    }
+}
+
+fn draw_frame(
+    batch: &mut batchdraw::BatchDraw2d,
+    resources: &ResourceManager,
+    state: &AnimationState,
+    time_in_state: f32,
+    quad: Quad,
+    color: [f32; 4],
+) {
+    let Ok(image) = resources.get_by_id::<ImageResource>(state.image.0) else {
+        return;
+    };
+    let texture = image.texture.borrow();
+    let Some(texture) = texture.as_ref() else {
+        return;
+    };
+    let frame = state.frame_index(time_in_state);
+    let src_pos = Vec2::new(
+        state.frame_pos.x() + state.frame_size.x() * frame as f32,
+        state.frame_pos.y(),
+    );
+    batch.draw_image_part(quad, texture, src_pos, state.frame_size, color);
+}
+
+pub fn setup_anim_api(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
+    resources: &Rc<ResourceManager>,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let anim_module = lua.create_table()?;
+
+    add_fn_to_table(
+        lua,
+        &anim_module,
+        "newStateMachine",
+        |_, definition: vectarine_plugin_sdk::mlua::Table| {
+            let state_machine = StateMachine::from_definition(&definition)?;
+            Ok(LuaStateMachine(Rc::new(RefCell::new(state_machine))))
+        },
+    );
+
+    lua.register_userdata_type::<LuaStateMachine>(|registry| {
+        registry.add_method(
+            "setParam",
+            |_, state_machine, (name, value): (String, vectarine_plugin_sdk::mlua::Value)| {
+                state_machine.0.borrow_mut().set_param(&name, &value)
+            },
+        );
+
+        registry.add_method("getParam", |_, state_machine, name: String| {
+            Ok(state_machine.0.borrow().params.get(&name).copied())
+        });
+
+        registry.add_method("getState", |_, state_machine, (): ()| {
+            let state_machine = state_machine.0.borrow();
+            Ok(state_machine.states[state_machine.current].name.clone())
+        });
+
+        registry.add_method("getTimeInState", |_, state_machine, (): ()| {
+            Ok(state_machine.0.borrow().time_in_state)
+        });
+
+        registry.add_method("isFinished", |_, state_machine, (): ()| {
+            let state_machine = state_machine.0.borrow();
+            Ok(
+                state_machine.states[state_machine.current]
+                    .is_finished(state_machine.time_in_state),
+            )
+        });
+
+        registry.add_method("setState", |_, state_machine, name: String| {
+            let previous = {
+                let mut inner = state_machine.0.borrow_mut();
+                let to = inner.get_state_index(&name)?;
+                let previous = inner.current;
+                inner.enter(to, 0.0);
+                previous
+            };
+            state_machine.call_on_enter_callbacks(previous)
+        });
+
+        registry.add_method(
+            "onEnter",
+            |_, state_machine, (name, callback): (String, vectarine_plugin_sdk::mlua::Function)| {
+                let mut state_machine = state_machine.0.borrow_mut();
+                let state = state_machine.get_state_index(&name)?;
+                state_machine
+                    .on_enter
+                    .entry(state)
+                    .or_default()
+                    .push(callback);
+                Ok(())
+            },
+        );
+
+        registry.add_method("update", |_, state_machine, delta_time: f32| {
+            let previous = state_machine.0.borrow_mut().update(delta_time);
+            if let Some(previous) = previous {
+                state_machine.call_on_enter_callbacks(previous)?;
+            }
+            Ok(())
+        });
+
+        registry.add_method("draw", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            move |_,
+                  state_machine,
+                  (mpos, msize, flip_x, color): (
+                AnyUserData,
+                AnyUserData,
+                Option<bool>,
+                Option<Vec4>,
+            )| {
+                let pos = get_pos_as_vec2(mpos)?;
+                let size = get_size_as_vec2(msize)?;
+                let left = pos.x();
+                let right = pos.x() + size.x();
+                let (left, right) = if flip_x.unwrap_or(false) {
+                    (right, left)
+                } else {
+                    (left, right)
+                };
+                let quad = Quad {
+                    p1: Vec2::new(left, pos.y()),
+                    p2: Vec2::new(right, pos.y()),
+                    p3: Vec2::new(right, pos.y() + size.y()),
+                    p4: Vec2::new(left, pos.y() + size.y()),
+                };
+                let color = color.unwrap_or(WHITE).0;
+
+                let state_machine = state_machine.0.borrow();
+                let mut batch = batch.borrow_mut();
+                draw_frame(
+                    &mut batch,
+                    &resources,
+                    &state_machine.states[state_machine.current],
+                    state_machine.time_in_state,
+                    quad,
+                    color,
+                );
+                // The previous animation fades out on top of the new one.
+                if let Some(blend) = state_machine.blend {
+                    let [r, g, b, a] = color;
+                    let fade = (blend.remaining / blend.duration).clamp(0.0, 1.0);
+                    draw_frame(
+                        &mut batch,
+                        &resources,
+                        &state_machine.states[blend.previous],
+                        blend.previous_time_in_state,
+                        quad,
+                        [r, g, b, a * fade],
+                    );
+                }
+                Ok(())
+            }
+        });
+    })?;
+
+    Ok(anim_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::lua_env::lua_resource::ResourceIdWrapper;
+
+    /// Creates a state machine with an idle, a run and a jump state.
+    fn new_hero_state_machine(lua: &vectarine_plugin_sdk::mlua::Lua) -> StateMachine {
+        let resources = ResourceManager::dummy_manager();
+        let image = ImageResourceId::from_id(
+            resources.schedule_load_resource::<ImageResource>(Path::new("hero.png")),
+        );
+        lua.globals().set("heroImage", image).expect("Image set");
+        let definition = lua
+            .load(
+                r#"return {
+                    initial = "idle",
+                    params = { speed = 0, jumping = false },
+                    minTimeInState = 0.5,
+                    states = {
+                        idle = { image = heroImage, transitions = { { to = "run", when = { speed = { gt = 0.1 } } } } },
+                        run = { image = heroImage, transitions = { { to = "idle", when = { speed = { le = 0.1 } }, blend = 0.2 } } },
+                        jump = { image = heroImage, minTime = 0 },
+                    },
+                    transitions = {
+                        { from = "*", to = "jump", when = { jumping = true } },
+                        { from = "jump", to = "idle", when = { jumping = false } },
+                    },
+                }"#,
+            )
+            .eval::<vectarine_plugin_sdk::mlua::Table>()
+            .expect("Valid table");
+        StateMachine::from_definition(&definition).expect("Valid definition")
+    }
+
+    fn current_name(state_machine: &StateMachine) -> &str {
+        &state_machine.states[state_machine.current].name
+    }
+
+    #[test]
+    fn operators_compare_numbers_and_booleans() {
+        let speed = ParamValue::Number(0.5);
+        assert!(Operator::Gt.compare(speed, ParamValue::Number(0.1)));
+        assert!(!Operator::Lt.compare(speed, ParamValue::Number(0.1)));
+        assert!(Operator::Ge.compare(speed, ParamValue::Number(0.5)));
+        assert!(Operator::Ne.compare(ParamValue::Boolean(true), ParamValue::Boolean(false)));
+        // Mismatched types never match, the definition is validated to avoid them.
+        assert!(!Operator::Eq.compare(speed, ParamValue::Boolean(true)));
+    }
+
+    #[test]
+    fn conditions_report_the_path_of_mistakes() {
+        let lua = vectarine_plugin_sdk::mlua::Lua::new();
+        let params = HashMap::from([
+            ("speed".to_string(), ParamValue::Number(0.0)),
+            ("grounded".to_string(), ParamValue::Boolean(true)),
+        ]);
+        let parse = |code: &str| {
+            let when = lua
+                .load(code)
+                .eval::<vectarine_plugin_sdk::mlua::Table>()
+                .expect("Valid table");
+            parse_conditions(&when, &params, "states.run.transitions[2].when")
+        };
+
+        let error = parse("return { speed = { gte = 0.1 } }").expect_err("Unknown operator");
+        assert!(
+            error
+                .to_string()
+                .contains("states.run.transitions[2].when: unknown operator 'gte'")
+        );
+        assert!(parse("return { sped = { gt = 0.1 } }").is_err());
+        assert!(parse("return { grounded = { gt = 1 } }").is_err());
+
+        let conditions =
+            parse("return { speed = { gt = 0.1, le = 3 }, grounded = true }").expect("Valid");
+        assert_eq!(conditions.len(), 3);
+    }
+
+    #[test]
+    fn update_takes_transitions_after_the_minimum_time_in_state() {
+        let lua = vectarine_plugin_sdk::mlua::Lua::new();
+        let mut state_machine = new_hero_state_machine(&lua);
+        assert_eq!(current_name(&state_machine), "idle");
+        assert_eq!(state_machine.update(0.1), None);
+
+        state_machine
+            .set_param("speed", &vectarine_plugin_sdk::mlua::Value::Number(1.0))
+            .expect("Valid param");
+        // The guard prevents leaving the state too early.
+        assert_eq!(state_machine.update(0.1), None);
+        assert_eq!(current_name(&state_machine), "idle");
+
+        let previous = state_machine.update(0.4).expect("Transition taken");
+        assert_eq!(state_machine.states[previous].name, "idle");
+        assert_eq!(current_name(&state_machine), "run");
+        assert_eq!(state_machine.time_in_state, 0.0);
+        // The conditions are still true, but only one transition is taken per update.
+        assert_eq!(state_machine.update(0.1), None);
+    }
+
+    #[test]
+    fn update_blends_out_the_previous_state() {
+        let lua = vectarine_plugin_sdk::mlua::Lua::new();
+        let mut state_machine = new_hero_state_machine(&lua);
+        state_machine
+            .set_param("speed", &vectarine_plugin_sdk::mlua::Value::Number(1.0))
+            .expect("Valid param");
+        state_machine.update(0.5).expect("To run");
+        // The transition to run has no blend time.
+        assert!(state_machine.blend.is_none());
+
+        state_machine
+            .set_param("speed", &vectarine_plugin_sdk::mlua::Value::Number(0.0))
+            .expect("Valid param");
+        state_machine.update(0.5).expect("To idle");
+        let blend = state_machine.blend.expect("Blending from run");
+        assert_eq!(state_machine.states[blend.previous].name, "run");
+        assert_eq!(blend.previous_time_in_state, 0.5);
+
+        state_machine.update(0.1);
+        assert!(state_machine.blend.is_some());
+        state_machine.update(0.15);
+        assert!(state_machine.blend.is_none());
+    }
+
+    #[test]
+    fn transitions_from_any_state_do_not_restart_their_target() {
+        let lua = vectarine_plugin_sdk::mlua::Lua::new();
+        let mut state_machine = new_hero_state_machine(&lua);
+        state_machine
+            .set_param("jumping", &vectarine_plugin_sdk::mlua::Value::Boolean(true))
+            .expect("Valid param");
+        state_machine.update(0.5).expect("To jump");
+        assert_eq!(current_name(&state_machine), "jump");
+
+        assert_eq!(state_machine.update(0.1), None);
+        assert_eq!(state_machine.time_in_state, 0.1);
+
+        assert!(
+            state_machine
+                .set_param("jumping", &vectarine_plugin_sdk::mlua::Value::Number(1.0))
+                .is_err()
+        );
+        state_machine
+            .set_param(
+                "jumping",
+                &vectarine_plugin_sdk::mlua::Value::Boolean(false),
+            )
+            .expect("Valid param");
+        state_machine.update(0.1).expect("Back to idle");
+        assert_eq!(current_name(&state_machine), "idle");
+    }
+}
@@ -0,0 +1,282 @@
+use std::{cell::RefCell, rc::Rc};
+
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
+
+use crate::{
+    auto_impl_lua_clone,
+    game_resource::{
+        ResourceId, ResourceManager,
+        animation_resource::{AnimationContent, AnimationResource, LoopMode, SpriteAnimation},
+        image_resource::ImageResource,
+    },
+    graphics::{batchdraw, shape::Quad},
+    lua_env::{
+        add_fn_to_table,
+        lua_coord::{get_pos_as_vec2, get_size_as_vec2},
+        lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
+        lua_vec2::Vec2,
+        lua_vec4::{Vec4, WHITE},
+    },
+    make_resource_lua_compatible,
+};
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct AnimationResourceId(ResourceId);
+make_resource_lua_compatible!(AnimationResourceId);
+
+/// Runs `f` with the animation of the resource, when the resource is loaded and has it.
+fn with_animation<R>(
+    resources: &ResourceManager,
+    resource_id: AnimationResourceId,
+    name: &str,
+    f: impl FnOnce(&AnimationContent, &SpriteAnimation) -> R,
+) -> Option<R> {
+    let resource = resources
+        .get_by_id::<AnimationResource>(resource_id.0)
+        .ok()?;
+    let content = resource.content.borrow();
+    let content = content.as_ref()?;
+    let animation = content.animations.get(name)?;
+    Some(f(content, animation))
+}
+
+/// The playback of the animations of a resource, one at a time.
+struct Player {
+    resource: AnimationResourceId,
+    animation: Option<String>,
+    time: f32,
+    speed: f32,
+    is_playing: bool,
+    on_finished: Vec<vectarine_plugin_sdk::mlua::Function>,
+}
+
+#[derive(Clone)]
+pub struct AnimationPlayer(Rc<RefCell<Player>>);
+auto_impl_lua_clone!(AnimationPlayer, AnimationPlayer);
+
+impl AnimationPlayer {
+    /// The callbacks are called without borrowing the player, so that they can use it.
+    fn call_on_finished_callbacks(&self, name: &str) -> vectarine_plugin_sdk::mlua::Result<()> {
+        let callbacks = self.0.borrow().on_finished.clone();
+        for callback in callbacks {
+            callback.call::<()>(name.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn setup_animation_api(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
+    resources: &Rc<ResourceManager>,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let animation_module = lua.create_table()?;
+
+    lua.register_userdata_type::<AnimationResourceId>(|registry| {
+        register_resource_id_methods_on_type(resources, registry);
+
+        registry.add_method("getAnimationNames", {
+            let resources = resources.clone();
+            move |_, resource_id, (): ()| {
+                let Ok(resource) = resources.get_by_id::<AnimationResource>(resource_id.0) else {
+                    return Ok(Vec::new());
+                };
+                let content = resource.content.borrow();
+                Ok(content
+                    .as_ref()
+                    .map(|content| content.animations.keys().cloned().collect())
+                    .unwrap_or_default())
+            }
+        });
+    })?;
+
+    add_fn_to_table(
+        lua,
+        &animation_module,
+        "newPlayer",
+        |_, resource: AnimationResourceId| {
+            Ok(AnimationPlayer(Rc::new(RefCell::new(Player {
+                resource,
+                animation: None,
+                time: 0.0,
+                speed: 1.0,
+                is_playing: false,
+                on_finished: Vec::new(),
+            }))))
+        },
+    );
+
+    lua.register_userdata_type::<AnimationPlayer>(|registry| {
+        registry.add_method(
+            "play",
+            |_, player, (name, from_start): (Option<String>, Option<bool>)| {
+                let mut player = player.0.borrow_mut();
+                let from_start = from_start.unwrap_or(false);
+                match name {
+                    // Playing the current animation again continues it, so that `play` can be called every frame.
+                    Some(name) => {
+                        if from_start || player.animation.as_ref() != Some(&name) {
+                            player.time = 0.0;
+                        }
+                        player.animation = Some(name);
+                    }
+                    None if player.animation.is_none() => {
+                        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                            "No animation to resume, give the name of the animation to play"
+                                .to_string(),
+                        ));
+                    }
+                    None => {
+                        if from_start {
+                            player.time = 0.0;
+                        }
+                    }
+                }
+                player.is_playing = true;
+                Ok(())
+            },
+        );
+
+        registry.add_method("pause", |_, player, (): ()| {
+            player.0.borrow_mut().is_playing = false;
+            Ok(())
+        });
+
+        registry.add_method("isPlaying", |_, player, (): ()| {
+            Ok(player.0.borrow().is_playing)
+        });
+
+        registry.add_method("getAnimation", |_, player, (): ()| {
+            Ok(player.0.borrow().animation.clone())
+        });
+
+        registry.add_method("setSpeed", |_, player, speed: f32| {
+            if !speed.is_finite() || speed < 0.0 {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "The speed is {speed}, it should be a positive number"
+                )));
+            }
+            player.0.borrow_mut().speed = speed;
+            Ok(())
+        });
+
+        registry.add_method("getSpeed", |_, player, (): ()| Ok(player.0.borrow().speed));
+
+        registry.add_method("getFrame", {
+            let resources = resources.clone();
+            move |_, player, (): ()| {
+                let player = player.0.borrow();
+                let Some(name) = &player.animation else {
+                    return Ok(None);
+                };
+                // Frames are numbered from 1, like the items of Lua lists.
+                Ok(with_animation(
+                    &resources,
+                    player.resource,
+                    name,
+                    |_, animation| animation.frame_at(player.time) + 1,
+                ))
+            }
+        });
+
+        registry.add_method(
+            "onFinished",
+            |_, player, callback: vectarine_plugin_sdk::mlua::Function| {
+                player.0.borrow_mut().on_finished.push(callback);
+                Ok(())
+            },
+        );
+
+        registry.add_method("update", {
+            let resources = resources.clone();
+            move |_, player, delta_time: f32| {
+                let finished = {
+                    let mut inner = player.0.borrow_mut();
+                    let Some(name) = inner.animation.clone() else {
+                        return Ok(());
+                    };
+                    if !inner.is_playing {
+                        return Ok(());
+                    }
+                    let previous_time = inner.time;
+                    let time = previous_time + delta_time.max(0.0) * inner.speed;
+                    // The time does not advance until the resource is loaded.
+                    let Some((completed, loop_mode)) =
+                        with_animation(&resources, inner.resource, &name, |_, animation| {
+                            (
+                                animation.completed_cycles(time)
+                                    > animation.completed_cycles(previous_time),
+                                animation.loop_mode,
+                            )
+                        })
+                    else {
+                        return Ok(());
+                    };
+                    inner.time = time;
+                    if completed && loop_mode == LoopMode::Once {
+                        inner.is_playing = false;
+                    }
+                    completed.then_some(name)
+                };
+                if let Some(name) = finished {
+                    player.call_on_finished_callbacks(&name)?;
+                }
+                Ok(())
+            }
+        });
+
+        registry.add_method("draw", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            move |_,
+                  player,
+                  (mpos, msize, flip_x, color): (
+                AnyUserData,
+                AnyUserData,
+                Option<bool>,
+                Option<Vec4>,
+            )| {
+                let pos = get_pos_as_vec2(mpos)?;
+                let size = get_size_as_vec2(msize)?;
+                let (left, right) = if flip_x.unwrap_or(false) {
+                    (pos.x() + size.x(), pos.x())
+                } else {
+                    (pos.x(), pos.x() + size.x())
+                };
+                let quad = Quad {
+                    p1: Vec2::new(left, pos.y()),
+                    p2: Vec2::new(right, pos.y()),
+                    p3: Vec2::new(right, pos.y() + size.y()),
+                    p4: Vec2::new(left, pos.y() + size.y()),
+                };
+                let player = player.0.borrow();
+                let Some(name) = &player.animation else {
+                    return Ok(());
+                };
+                with_animation(&resources, player.resource, name, |content, animation| {
+                    let Ok(image) = resources.get_by_id::<ImageResource>(content.image) else {
+                        return;
+                    };
+                    let texture = image.texture.borrow();
+                    let Some(texture) = texture.as_ref() else {
+                        return;
+                    };
+                    // The frames are in pixels, the batch takes parts of the image between 0 and 1.
+                    let [x, y, width, height] =
+                        animation.frames[animation.frame_at(player.time)].rect;
+                    let image_size = Vec2::new(texture.width() as f32, texture.height() as f32);
+                    batch.borrow_mut().draw_image_part(
+                        quad,
+                        texture,
+                        Vec2::new(x, y) / image_size,
+                        Vec2::new(width, height) / image_size,
+                        color.unwrap_or(WHITE).0,
+                    );
+                });
+                Ok(())
+            }
+        });
+    })?;
+
+    Ok(animation_module)
+}
@@ -1,18 +1,164 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
 
-use vectarine_plugin_sdk::mlua::{FromLua, IntoLua, UserDataMethods};
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
 
 use crate::{
     game_resource::{self, ResourceId, audio_resource::AudioResource},
     io,
-    lua_env::lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
-    make_resource_lua_compatible,
+    lua_env::{
+        add_fn_to_table,
+        lua_audio::{
+            buffer::{AudioBuffer, AudioBufferData, decode_pcm16, samples_from_fastlist},
+            spatial::{Spatialization, spatialize, spatialize_with_camera},
+            synth::{Tone, Wave},
+        },
+        lua_camera::Camera2,
+        lua_fastlist::FastList,
+        lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
+        lua_vec2::Vec2,
+    },
+    make_resource_lua_compatible, sound,
 };
 
+pub mod buffer;
+pub mod spatial;
+pub mod synth;
+
+/// The tones rendered by `Audio.tone` are kept to be played again. The oldest are forgotten past this count.
+const MAX_CACHED_TONES: usize = 256;
+
+#[derive(Default)]
+struct ToneCache {
+    buffers: HashMap<(Wave, [u32; 7]), AudioBufferData>,
+    order: VecDeque<(Wave, [u32; 7])>,
+}
+
+impl ToneCache {
+    /// A copy of the rendered tone, so that writing the buffer returned does not change the next tones.
+    fn get_or_render(&mut self, tone: &Tone) -> AudioBuffer {
+        let key = tone.cache_key();
+        if let Some(buffer) = self.buffers.get_mut(&key) {
+            return AudioBuffer::new(buffer.copy());
+        }
+        if self.order.len() >= MAX_CACHED_TONES
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.buffers.remove(&oldest);
+        }
+        let mut buffer = AudioBufferData::from_mixer_samples(tone.render());
+        let copy = buffer.copy();
+        self.buffers.insert(key, buffer);
+        self.order.push_back(key);
+        AudioBuffer::new(copy)
+    }
+}
+
+fn tone_from_table(
+    options: &vectarine_plugin_sdk::mlua::Table,
+) -> vectarine_plugin_sdk::mlua::Result<Tone> {
+    let default = Tone::default();
+    let wave = match options.get::<Option<String>>("wave")? {
+        Some(name) => Wave::from_name(&name).ok_or_else(|| {
+            vectarine_plugin_sdk::mlua::Error::runtime(format!(
+                "Unknown wave '{name}', expected square, sine, saw or noise"
+            ))
+        })?,
+        None => default.wave,
+    };
+    let number = |name: &str, default: f32| {
+        options
+            .get::<Option<f32>>(name)
+            .map(|value| value.unwrap_or(default))
+    };
+    let tone = Tone {
+        wave,
+        frequency: number("freq", default.frequency)?,
+        duration_ms: number("durationMs", default.duration_ms)?,
+        attack_ms: number("attack", default.attack_ms)?,
+        decay_ms: number("decay", default.decay_ms)?,
+        sustain: number("sustain", default.sustain)?,
+        release_ms: number("release", default.release_ms)?,
+        volume: number("volume", default.volume)?,
+    };
+    tone.validate().map_err(|error| {
+        vectarine_plugin_sdk::mlua::Error::runtime(format!("Audio.tone: {error}"))
+    })?;
+    Ok(tone)
+}
+
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
 pub struct AudioResourceId(ResourceId);
 make_resource_lua_compatible!(AudioResourceId);
 
+/// The volume and the pan of a sound at `position`, heard from `listener`, or from the center of the camera when no
+/// listener is given.
+fn spatialization_from_lua(
+    function_name: &str,
+    position: Vec2,
+    listener: Option<Vec2>,
+    falloff: f32,
+    camera: Option<AnyUserData>,
+) -> vectarine_plugin_sdk::mlua::Result<Spatialization> {
+    if !falloff.is_finite() || falloff <= 0.0 {
+        return Err(vectarine_plugin_sdk::mlua::Error::runtime(format!(
+            "{function_name}: the falloff is {falloff}, it should be more than 0"
+        )));
+    }
+    let camera = camera
+        .map(|camera| camera.borrow::<Camera2>().map(|camera| (*camera).clone()))
+        .transpose()?;
+    match (listener, camera) {
+        (listener, Some(camera)) => Ok(spatialize_with_camera(
+            position,
+            listener.unwrap_or(camera.position),
+            falloff,
+            &camera,
+        )),
+        (Some(listener), None) => Ok(spatialize(position, listener, falloff)),
+        (None, None) => Err(vectarine_plugin_sdk::mlua::Error::runtime(format!(
+            "{function_name}: give the position of the listener or a camera"
+        ))),
+    }
+}
+
+/// Sets the volume and the pan of the channel of an audio resource or an audio buffer, after playing it when `play`
+/// holds whether it loops.
+fn apply_spatialization(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    resources: &game_resource::ResourceManager,
+    audio: vectarine_plugin_sdk::mlua::Value,
+    spatialization: Spatialization,
+    play: Option<bool>,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    if let vectarine_plugin_sdk::mlua::Value::UserData(buffer) = &audio
+        && let Ok(buffer) = buffer.borrow::<AudioBuffer>()
+    {
+        let mut buffer = buffer.0.borrow_mut();
+        if let Some(is_loop) = play {
+            buffer.play(is_loop, 0.0);
+        }
+        if let Some(channel) = buffer.played_channel() {
+            sound::set_volume(channel, spatialization.volume);
+            sound::set_pan(channel, spatialization.pan);
+        }
+        return Ok(());
+    }
+    let audio_resource_id = AudioResourceId::from_lua(audio, lua)?;
+    let Ok(audio_res) = resources.get_by_id::<AudioResource>(audio_resource_id.0) else {
+        return Ok(());
+    };
+    if let Some(is_loop) = play {
+        audio_res.play(is_loop, None);
+    }
+    let _ = audio_res.set_volume(spatialization.volume);
+    let _ = audio_res.set_pan(spatialization.pan);
+    Ok(())
+}
+
 pub fn setup_audio_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     _env_state: &Rc<RefCell<io::IoEnvState>>,
@@ -68,6 +214,27 @@ pub fn setup_audio_api(
                 Ok(())
             }
         });
+        registry.add_method("setPan", {
+            let resources = Rc::clone(resources);
+            move |_lua, audio_resource_id, (pan,): (f32,)| {
+                let audio_res = resources.get_by_id::<AudioResource>(audio_resource_id.0);
+                let Ok(audio_res) = audio_res else {
+                    return Ok(());
+                };
+                let _ = audio_res.set_pan(pan);
+                Ok(())
+            }
+        });
+        registry.add_method("getPosition", {
+            let resources = Rc::clone(resources);
+            move |_lua, audio_resource_id, (): ()| {
+                let audio_res = resources.get_by_id::<AudioResource>(audio_resource_id.0);
+                let Ok(audio_res) = audio_res else {
+                    return Ok(0.0);
+                };
+                Ok(audio_res.current_position())
+            }
+        });
         registry.add_method("getVolume", {
             let resources = Rc::clone(resources);
             move |_lua, audio_resource_id, (): ()| {
@@ -80,5 +247,204 @@ pub fn setup_audio_api(
         });
     })?;
 
+    lua.register_userdata_type::<AudioBuffer>(|registry| {
+        registry.add_method(
+            "setSamples",
+            |_lua, buffer, (offset, samples): (usize, vectarine_plugin_sdk::mlua::Value)| {
+                let mut buffer = buffer.0.borrow_mut();
+                let values = match &samples {
+                    vectarine_plugin_sdk::mlua::Value::String(bytes) => {
+                        decode_pcm16(&bytes.as_bytes())
+                    }
+                    vectarine_plugin_sdk::mlua::Value::UserData(list) => {
+                        let list = list.borrow::<FastList>()?;
+                        Ok(samples_from_fastlist(&list, buffer.channels()))
+                    }
+                    _ => Err(format!(
+                        "Expected a string of 16-bit samples or a FastList, got {}",
+                        samples.type_name()
+                    )),
+                };
+                values
+                    .and_then(|values| buffer.write(offset, &values))
+                    .map_err(|error| {
+                        vectarine_plugin_sdk::mlua::Error::runtime(format!("setSamples: {error}"))
+                    })
+            },
+        );
+        registry.add_method("getSampleCount", |_lua, buffer, (): ()| {
+            Ok(buffer.0.borrow().frame_count())
+        });
+        registry.add_method("getChannels", |_lua, buffer, (): ()| {
+            Ok(buffer.0.borrow().channels())
+        });
+        registry.add_method("getDuration", |_lua, buffer, (): ()| {
+            Ok(buffer.0.borrow().duration())
+        });
+        registry.add_method(
+            "play",
+            |_lua, buffer, (is_loop, fade_in): (Option<bool>, Option<f32>)| {
+                buffer
+                    .0
+                    .borrow_mut()
+                    .play(is_loop.unwrap_or(false), fade_in.unwrap_or(0.0));
+                Ok(())
+            },
+        );
+        registry.add_method("pause", |_lua, buffer, (): ()| {
+            if let Some(channel) = buffer.0.borrow().played_channel() {
+                sound::pause_audio(channel);
+            }
+            Ok(())
+        });
+        registry.add_method("resume", |_lua, buffer, (): ()| {
+            if let Some(channel) = buffer.0.borrow().played_channel() {
+                sound::resume_audio(channel);
+            }
+            Ok(())
+        });
+        registry.add_method("setVolume", |_lua, buffer, (volume,): (f32,)| {
+            if let Some(channel) = buffer.0.borrow().played_channel() {
+                sound::set_volume(channel, volume);
+            }
+            Ok(())
+        });
+        registry.add_method("setPan", |_lua, buffer, (pan,): (f32,)| {
+            if let Some(channel) = buffer.0.borrow().played_channel() {
+                sound::set_pan(channel, pan);
+            }
+            Ok(())
+        });
+        registry.add_method("getVolume", |_lua, buffer, (): ()| {
+            Ok(buffer
+                .0
+                .borrow()
+                .played_channel()
+                .map_or(1.0, sound::get_volume))
+        });
+        registry.add_method("getPosition", |_lua, buffer, (): ()| {
+            Ok(buffer
+                .0
+                .borrow()
+                .played_channel()
+                .map_or(0.0, sound::get_position))
+        });
+    })?;
+
+    add_fn_to_table(lua, &audio_module, "createBuffer", {
+        move |_lua, (sample_count, channels): (usize, Option<usize>)| {
+            AudioBufferData::new(sample_count, channels.unwrap_or(1))
+                .map(AudioBuffer::new)
+                .map_err(|error| {
+                    vectarine_plugin_sdk::mlua::Error::runtime(format!(
+                        "Audio.createBuffer: {error}"
+                    ))
+                })
+        }
+    });
+
+    add_fn_to_table(lua, &audio_module, "tone", {
+        let tone_cache = Rc::new(RefCell::new(ToneCache::default()));
+        move |_lua, options: vectarine_plugin_sdk::mlua::Table| {
+            let tone = tone_from_table(&options)?;
+            Ok(tone_cache.borrow_mut().get_or_render(&tone))
+        }
+    });
+
+    add_fn_to_table(lua, &audio_module, "getClock", |_lua, (): ()| {
+        Ok(sound::get_audio_clock())
+    });
+
+    add_fn_to_table(lua, &audio_module, "playAt", {
+        let resources = Rc::clone(resources);
+        move |lua,
+              (audio, time, is_loop): (
+            vectarine_plugin_sdk::mlua::Value,
+            f64,
+            Option<bool>,
+        )| {
+            let is_loop = is_loop.unwrap_or(false);
+            if let vectarine_plugin_sdk::mlua::Value::UserData(buffer) = &audio
+                && let Ok(buffer) = buffer.borrow::<AudioBuffer>()
+            {
+                return Ok(Some(buffer.0.borrow_mut().play_at(time, is_loop)));
+            }
+            let audio_resource_id = AudioResourceId::from_lua(audio, lua)?;
+            let audio_res = resources.get_by_id::<AudioResource>(audio_resource_id.0);
+            let Ok(audio_res) = audio_res else {
+                return Ok(None);
+            };
+            Ok(audio_res.play_at(time, is_loop))
+        }
+    });
+
+    add_fn_to_table(lua, &audio_module, "playSpatial", {
+        let resources = Rc::clone(resources);
+        move |lua,
+              (audio, position, listener, falloff, camera, is_loop): (
+            vectarine_plugin_sdk::mlua::Value,
+            Vec2,
+            Option<Vec2>,
+            f32,
+            Option<AnyUserData>,
+            Option<bool>,
+        )| {
+            let spatialization =
+                spatialization_from_lua("Audio.playSpatial", position, listener, falloff, camera)?;
+            apply_spatialization(
+                lua,
+                &resources,
+                audio,
+                spatialization,
+                Some(is_loop.unwrap_or(false)),
+            )
+        }
+    });
+
+    add_fn_to_table(lua, &audio_module, "updateSpatial", {
+        let resources = Rc::clone(resources);
+        move |lua,
+              (audio, position, listener, falloff, camera): (
+            vectarine_plugin_sdk::mlua::Value,
+            Vec2,
+            Option<Vec2>,
+            f32,
+            Option<AnyUserData>,
+        )| {
+            let spatialization = spatialization_from_lua(
+                "Audio.updateSpatial",
+                position,
+                listener,
+                falloff,
+                camera,
+            )?;
+            apply_spatialization(lua, &resources, audio, spatialization, None)
+        }
+    });
+
+    add_fn_to_table(lua, &audio_module, "getLatencyReport", |lua, (): ()| {
+        let Some(report) = sound::get_latency_report() else {
+            return Ok(None);
+        };
+        let latency_report = lua.create_table()?;
+        latency_report.set("threaded", report.is_threaded)?;
+        latency_report.set("sampleRate", report.sample_rate)?;
+        latency_report.set("bufferSamples", report.buffer_frames)?;
+        latency_report.set(
+            "bufferDuration",
+            report.buffer_frames as f64 / report.sample_rate as f64,
+        )?;
+        latency_report.set("lead", report.lead_seconds)?;
+        latency_report.set("clockJitter", report.clock_jitter_seconds)?;
+        Ok(Some(latency_report))
+    });
+
+    add_fn_to_table(lua, &audio_module, "getDebugInfo", |lua, (): ()| {
+        let debug_info = lua.create_table()?;
+        debug_info.set("threaded", sound::is_audio_threaded())?;
+        debug_info.set("underruns", sound::underrun_count())?;
+        Ok(debug_info)
+    });
+
     Ok(audio_module)
 }
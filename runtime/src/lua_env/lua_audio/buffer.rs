@@ -0,0 +1,249 @@
+//! Sound samples written from Lua, or rendered by the synthesizer, and played like an audio resource.
+
+use std::{cell::RefCell, rc::Rc};
+
+use vectarine_plugin_sdk::mlua::{FromLua, IntoLua};
+
+use crate::{
+    auto_impl_lua_clone,
+    game_resource::audio_resource::{AUDIO_CHANNELS, AUDIO_SAMPLE_FREQUENCY},
+    lua_env::lua_fastlist::FastList,
+    sound::{self, ChannelId, DecodedSound, PlaySound, SoundData},
+};
+
+/// A buffer holds at most a minute of sound, so that a typo in a size does not allocate gigabytes.
+pub const MAX_AUDIO_BUFFER_FRAMES: usize = 60 * AUDIO_SAMPLE_FREQUENCY as usize;
+
+pub struct AudioBufferData {
+    /// Interleaved, with `AUDIO_CHANNELS` samples per frame, as the mixer plays them.
+    samples: Vec<f32>,
+    /// The number of channels of the samples written from Lua. Mono samples are played on every speaker.
+    channels: usize,
+    /// What the mixer played last. A write makes a new sound at the next play, so the sounds already playing keep the
+    /// samples they started with.
+    sound: Option<SoundData>,
+    /// Allocated at the first play, and released when the buffer is collected.
+    channel: Option<ChannelId>,
+}
+
+impl Drop for AudioBufferData {
+    fn drop(&mut self) {
+        if let Some(channel) = self.channel {
+            sound::release_channel(channel);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AudioBuffer(pub Rc<RefCell<AudioBufferData>>);
+auto_impl_lua_clone!(AudioBuffer, AudioBuffer);
+
+impl AudioBuffer {
+    pub fn new(data: AudioBufferData) -> Self {
+        Self(Rc::new(RefCell::new(data)))
+    }
+}
+
+impl AudioBufferData {
+    /// A silent buffer of `frame_count` frames of `channels` samples.
+    pub fn new(frame_count: usize, channels: usize) -> Result<Self, String> {
+        if channels != 1 && channels != 2 {
+            return Err(format!(
+                "An audio buffer has 1 or 2 channels, not {channels}"
+            ));
+        }
+        if frame_count > MAX_AUDIO_BUFFER_FRAMES {
+            return Err(format!(
+                "An audio buffer has at most {MAX_AUDIO_BUFFER_FRAMES} samples (a minute), not {frame_count}"
+            ));
+        }
+        Ok(Self {
+            samples: vec![0.0; frame_count * AUDIO_CHANNELS as usize],
+            channels,
+            sound: None,
+            channel: None,
+        })
+    }
+
+    /// A buffer of samples already interleaved for the mixer.
+    pub fn from_mixer_samples(samples: Vec<f32>) -> Self {
+        Self {
+            samples,
+            channels: AUDIO_CHANNELS as usize,
+            sound: None,
+            channel: None,
+        }
+    }
+
+    /// Another buffer with the same samples and its own channel. The mixer plays the same sound for both until one of
+    /// them is written.
+    pub fn copy(&mut self) -> Self {
+        Self {
+            samples: self.samples.clone(),
+            channels: self.channels,
+            sound: Some(self.sound()),
+            channel: None,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / AUDIO_CHANNELS as usize
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frame_count() as f64 / AUDIO_SAMPLE_FREQUENCY as f64
+    }
+
+    /// Replaces the frames starting at `offset` with the values, `channels` values per frame, between -1 and 1.
+    pub fn write(&mut self, offset: usize, values: &[f32]) -> Result<(), String> {
+        if !values.len().is_multiple_of(self.channels) {
+            return Err(format!(
+                "{} samples do not make whole frames of {} channels",
+                values.len(),
+                self.channels
+            ));
+        }
+        let frame_count = values.len() / self.channels;
+        if offset + frame_count > self.frame_count() {
+            return Err(format!(
+                "Writing {frame_count} samples at {offset} goes past the end of the buffer of {} samples",
+                self.frame_count()
+            ));
+        }
+        if let Some(value) = values.iter().find(|value| !(-1.0..=1.0).contains(*value)) {
+            return Err(format!("The sample {value} is not between -1 and 1"));
+        }
+        let mixer_channels = AUDIO_CHANNELS as usize;
+        for (index, frame) in values.chunks_exact(self.channels).enumerate() {
+            let start = (offset + index) * mixer_channels;
+            for (channel, sample) in self.samples[start..start + mixer_channels]
+                .iter_mut()
+                .enumerate()
+            {
+                *sample = frame[channel.min(frame.len() - 1)];
+            }
+        }
+        self.sound = None;
+        Ok(())
+    }
+
+    fn sound(&mut self) -> SoundData {
+        self.sound
+            .get_or_insert_with(|| {
+                let sound = SoundData::default();
+                let _ = sound.set(DecodedSound::new(self.samples.clone()));
+                sound
+            })
+            .clone()
+    }
+
+    fn channel(&mut self) -> ChannelId {
+        *self
+            .channel
+            .get_or_insert_with(sound::get_available_channel)
+    }
+
+    /// Plays the buffer after what it plays already, like `AudioResource::play`.
+    pub fn play(&mut self, looped: bool, fade_in_ms: f32) {
+        let channel = self.channel();
+        let sound = self.sound();
+        sound::resume_audio(channel);
+        sound::play_sound(
+            channel,
+            PlaySound {
+                sound,
+                fade_in_ms,
+                fade_out_ms: 0.0,
+                looped,
+                start_ms: 0.0,
+            },
+        );
+    }
+
+    /// Plays the buffer when the audio clock reaches `clock_seconds`, like `AudioResource::play_at`.
+    pub fn play_at(&mut self, clock_seconds: f64, looped: bool) -> f64 {
+        let channel = self.channel();
+        let sound = self.sound();
+        sound::resume_audio(channel);
+        sound::play_sound_at(
+            channel,
+            PlaySound {
+                sound,
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped,
+                start_ms: 0.0,
+            },
+            clock_seconds,
+        )
+    }
+
+    pub fn played_channel(&self) -> Option<ChannelId> {
+        self.channel
+    }
+}
+
+/// Reads 16-bit signed little-endian samples.
+pub fn decode_pcm16(bytes: &[u8]) -> Result<Vec<f32>, String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(format!(
+            "The string has {} bytes, 16-bit samples need an even number",
+            bytes.len()
+        ));
+    }
+    Ok(bytes
+        .chunks_exact(2)
+        .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0)
+        .collect())
+}
+
+/// Every vector of the list is a frame: `x` is the left channel and `y` the right one. Mono buffers only use `x`.
+pub fn samples_from_fastlist(list: &FastList, channels: usize) -> Vec<f32> {
+    list.data
+        .iter()
+        .flat_map(|frame| [frame.x(), frame.y()].into_iter().take(channels))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AudioBufferData, MAX_AUDIO_BUFFER_FRAMES, decode_pcm16};
+
+    #[test]
+    fn writes_are_checked_and_converted_to_the_mixer_layout() {
+        let mut mono = AudioBufferData::new(4, 1).expect("valid size");
+        let samples = decode_pcm16(&[0x00, 0x40, 0x00, 0xC0]).expect("even length");
+        assert_eq!(samples, vec![0.5, -0.5]);
+        mono.write(1, &samples).expect("inside of the buffer");
+        assert_eq!(mono.samples, vec![0.0, 0.0, 0.5, 0.5, -0.5, -0.5, 0.0, 0.0]);
+        assert!(mono.write(3, &samples).is_err());
+        assert!(mono.write(0, &[1.5]).is_err());
+        assert!(mono.write(0, &[f32::NAN]).is_err());
+
+        let mut stereo = AudioBufferData::new(2, 2).expect("valid size");
+        stereo.write(1, &[0.25, -0.25]).expect("a whole frame");
+        assert_eq!(stereo.samples, vec![0.0, 0.0, 0.25, -0.25]);
+        assert!(stereo.write(0, &[0.25]).is_err());
+
+        assert!(decode_pcm16(&[0]).is_err());
+        assert!(AudioBufferData::new(MAX_AUDIO_BUFFER_FRAMES + 1, 1).is_err());
+        assert!(AudioBufferData::new(16, 3).is_err());
+    }
+
+    #[test]
+    fn writing_a_copy_keeps_the_original() {
+        let mut original = AudioBufferData::new(2, 1).expect("valid size");
+        original
+            .write(0, &[0.5, 0.5])
+            .expect("inside of the buffer");
+        let mut copy = original.copy();
+        copy.write(0, &[-0.5]).expect("inside of the buffer");
+        assert_eq!(original.samples, vec![0.5; 4]);
+        assert_eq!(copy.samples, vec![-0.5, -0.5, 0.5, 0.5]);
+        assert_eq!(copy.channels(), 1);
+    }
+}
@@ -0,0 +1,59 @@
+//! How loud a sound in the world is, and on which side it is heard, from where the listener is.
+
+use crate::lua_env::{lua_camera::Camera2, lua_vec2::Vec2};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spatialization {
+    /// 1 at the listener, down to 0 at the falloff distance.
+    pub volume: f32,
+    /// From -1 when the sound is on the left of the listener to 1 when it is on the right.
+    pub pan: f32,
+}
+
+/// The volume decreases linearly with the distance and the sound is silent past `falloff`. The sound is fully on one
+/// side when it is half of the falloff away horizontally, so that close sounds are not heard in one ear only.
+pub fn spatialize(source: Vec2, listener: Vec2, falloff: f32) -> Spatialization {
+    let offset = source - listener;
+    Spatialization {
+        volume: (1.0 - offset.length() / falloff).clamp(0.0, 1.0),
+        pan: (offset.x() / (falloff / 2.0)).clamp(-1.0, 1.0),
+    }
+}
+
+/// Like `spatialize`, with the left and the right of the screen when the camera is rotated. The zoom of the camera
+/// does not change the volume, the distances stay in world units.
+pub fn spatialize_with_camera(
+    source: Vec2,
+    listener: Vec2,
+    falloff: f32,
+    camera: &Camera2,
+) -> Spatialization {
+    let offset = (source - listener).rotated(-camera.rotation);
+    spatialize(offset, Vec2::zero(), falloff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spatialize, spatialize_with_camera};
+    use crate::lua_env::{lua_camera::Camera2, lua_vec2::Vec2};
+
+    #[test]
+    fn sounds_fade_with_the_distance_and_pan_to_their_side() {
+        let listener = Vec2::new(1.0, 1.0);
+        let close = spatialize(listener, listener, 10.0);
+        assert_eq!((close.volume, close.pan), (1.0, 0.0));
+
+        let right = spatialize(Vec2::new(3.5, 1.0), listener, 10.0);
+        assert!((right.volume - 0.75).abs() < 1e-6);
+        assert!((right.pan - 0.5).abs() < 1e-6);
+
+        let far_left = spatialize(Vec2::new(-20.0, 1.0), listener, 10.0);
+        assert_eq!((far_left.volume, far_left.pan), (0.0, -1.0));
+
+        // Upside down, the right of the world is on the left of the screen.
+        let mut camera = Camera2::new();
+        camera.rotation = std::f32::consts::PI;
+        let flipped = spatialize_with_camera(Vec2::new(3.5, 1.0), listener, 10.0, &camera);
+        assert!((flipped.pan + 0.5).abs() < 1e-5);
+    }
+}
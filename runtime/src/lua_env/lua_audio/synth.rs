@@ -0,0 +1,235 @@
+//! A tiny synthesizer for chip-tune sounds: a wave shaped by an attack, decay, sustain and release envelope, rendered
+//! to the samples the mixer plays.
+
+use crate::game_resource::audio_resource::{AUDIO_CHANNELS, AUDIO_SAMPLE_FREQUENCY};
+
+/// A tone lasts at most this long, release included, so that a typo in a duration does not allocate gigabytes.
+pub const MAX_TONE_DURATION_MS: f32 = 10_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Wave {
+    Square,
+    Sine,
+    Saw,
+    Noise,
+}
+
+impl Wave {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Wave::Square),
+            "sine" => Some(Wave::Sine),
+            "saw" => Some(Wave::Saw),
+            "noise" => Some(Wave::Noise),
+            _ => None,
+        }
+    }
+
+    /// The value of the wave at a phase between 0 and 1. `period` is the number of the period, for the noise.
+    fn sample(self, phase: f32, period: u64) -> f32 {
+        match self {
+            Wave::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Wave::Sine => (phase * std::f32::consts::TAU).sin(),
+            Wave::Saw => 2.0 * phase - 1.0,
+            // A random value held for half a period, like the noise channel of old consoles. It only depends on the
+            // period, so a tone sounds the same every time it is rendered.
+            Wave::Noise => {
+                let half_period = period * 2 + u64::from(phase >= 0.5);
+                let mut hash = half_period.wrapping_add(0x9E37_79B9_7F4A_7C15);
+                hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                hash ^= hash >> 31;
+                (hash >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+            }
+        }
+    }
+}
+
+/// The parameters of `Audio.tone`. The durations are in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub wave: Wave,
+    pub frequency: f32,
+    /// How long the note is held, before its release.
+    pub duration_ms: f32,
+    pub attack_ms: f32,
+    pub decay_ms: f32,
+    /// The level of the envelope after the decay, between 0 and 1.
+    pub sustain: f32,
+    pub release_ms: f32,
+    pub volume: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            wave: Wave::Square,
+            frequency: 440.0,
+            duration_ms: 200.0,
+            attack_ms: 5.0,
+            decay_ms: 50.0,
+            sustain: 0.7,
+            release_ms: 50.0,
+            volume: 0.5,
+        }
+    }
+}
+
+impl Tone {
+    pub fn validate(&self) -> Result<(), String> {
+        let numbers = [
+            ("freq", self.frequency),
+            ("durationMs", self.duration_ms),
+            ("attack", self.attack_ms),
+            ("decay", self.decay_ms),
+            ("sustain", self.sustain),
+            ("release", self.release_ms),
+            ("volume", self.volume),
+        ];
+        for (name, value) in numbers {
+            if !value.is_finite() || value < 0.0 {
+                return Err(format!("{name} is {value}, it should be a positive number"));
+            }
+        }
+        if self.frequency <= 0.0 || self.frequency > AUDIO_SAMPLE_FREQUENCY as f32 / 2.0 {
+            return Err(format!(
+                "freq is {}, it should be between 0 and {} Hz",
+                self.frequency,
+                AUDIO_SAMPLE_FREQUENCY / 2
+            ));
+        }
+        if self.sustain > 1.0 || self.volume > 1.0 {
+            return Err("sustain and volume should be between 0 and 1".to_string());
+        }
+        if self.duration_ms + self.release_ms > MAX_TONE_DURATION_MS {
+            return Err(format!(
+                "the tone lasts {} ms with its release, the maximum is {MAX_TONE_DURATION_MS} ms",
+                self.duration_ms + self.release_ms
+            ));
+        }
+        Ok(())
+    }
+
+    /// The parameters as bits, to find an already rendered tone.
+    pub fn cache_key(&self) -> (Wave, [u32; 7]) {
+        (
+            self.wave,
+            [
+                self.frequency,
+                self.duration_ms,
+                self.attack_ms,
+                self.decay_ms,
+                self.sustain,
+                self.release_ms,
+                self.volume,
+            ]
+            .map(f32::to_bits),
+        )
+    }
+
+    pub fn frame_count(&self) -> usize {
+        ((self.duration_ms + self.release_ms) * AUDIO_SAMPLE_FREQUENCY as f32 / 1000.0).round()
+            as usize
+    }
+
+    /// The level of the envelope while the note is held.
+    fn held_level(&self, time_ms: f32) -> f32 {
+        if time_ms < self.attack_ms {
+            time_ms / self.attack_ms
+        } else if time_ms < self.attack_ms + self.decay_ms {
+            1.0 - (1.0 - self.sustain) * (time_ms - self.attack_ms) / self.decay_ms
+        } else {
+            self.sustain
+        }
+    }
+
+    fn envelope(&self, time_ms: f32) -> f32 {
+        if time_ms < self.duration_ms {
+            return self.held_level(time_ms);
+        }
+        if self.release_ms <= 0.0 {
+            return 0.0;
+        }
+        let release_progress = ((time_ms - self.duration_ms) / self.release_ms).min(1.0);
+        self.held_level(self.duration_ms) * (1.0 - release_progress)
+    }
+
+    /// The interleaved samples of the tone, with the same sound on every channel.
+    pub fn render(&self) -> Vec<f32> {
+        let sample_rate = AUDIO_SAMPLE_FREQUENCY as f64;
+        let channels = AUDIO_CHANNELS as usize;
+        let mut samples = Vec::with_capacity(self.frame_count() * channels);
+        for frame in 0..self.frame_count() {
+            let cycles = frame as f64 * self.frequency as f64 / sample_rate;
+            let time_ms = (frame as f64 * 1000.0 / sample_rate) as f32;
+            let value = self.wave.sample(cycles.fract() as f32, cycles as u64)
+                * self.envelope(time_ms)
+                * self.volume;
+            samples.extend(std::iter::repeat_n(value, channels));
+        }
+        samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tone, Wave};
+
+    #[test]
+    fn the_envelope_shapes_the_wave() {
+        let tone = Tone {
+            wave: Wave::Square,
+            frequency: 1000.0,
+            duration_ms: 20.0,
+            attack_ms: 10.0,
+            decay_ms: 5.0,
+            sustain: 0.5,
+            release_ms: 10.0,
+            volume: 1.0,
+        };
+        assert!(tone.validate().is_ok());
+        assert_eq!(tone.frame_count(), 1440);
+        assert_eq!(tone.envelope(5.0), 0.5);
+        assert_eq!(tone.envelope(10.0), 1.0);
+        assert_eq!(tone.envelope(17.0), 0.5);
+        assert_eq!(tone.envelope(25.0), 0.25);
+        assert_eq!(tone.envelope(30.0), 0.0);
+
+        let samples = tone.render();
+        assert_eq!(samples.len(), 1440 * 2);
+        // 48 frames per period: the first half is high and the second half is low.
+        let frame = |index: usize| samples[index * 2];
+        assert!(frame(480 + 10) > 0.0);
+        assert!(frame(480 + 30) < 0.0);
+        // During the sustain, every period is the same.
+        assert_eq!(frame(730), 0.5);
+        assert_eq!(frame(730 + 48), 0.5);
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+    }
+
+    #[test]
+    fn mistakes_in_the_parameters_are_reported() {
+        let too_long = Tone {
+            duration_ms: 2_000_000.0,
+            ..Tone::default()
+        };
+        assert!(too_long.validate().is_err());
+        let negative = Tone {
+            release_ms: -1.0,
+            ..Tone::default()
+        };
+        assert!(negative.validate().is_err());
+        let silent = Tone {
+            frequency: 0.0,
+            ..Tone::default()
+        };
+        assert!(silent.validate().is_err());
+        assert_eq!(Tone::default().cache_key(), Tone::default().cache_key());
+    }
+}
@@ -1,13 +1,17 @@
 use std::{cell::RefCell, ops::Deref, rc::Rc};
 
-use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, Table, UserDataMethods};
 
 use crate::{
     auto_impl_lua_clone,
     console::print_warn,
-    game_resource::{self, ResourceId, shader_resource::ShaderResource},
+    game_resource::{
+        self, ResourceId, ResourceManager,
+        shader_resource::{ShaderLayout, get_shader_resource},
+    },
     graphics::{
-        batchdraw, glframebuffer,
+        batchdraw::{self, BatchDraw2d, CustomShader},
+        glframebuffer,
         gltexture::ImageAntialiasing,
         gluniforms::{UniformValue, Uniforms},
         shape::Quad,
@@ -18,6 +22,7 @@ use crate::{
         lua_coord::{get_pos_as_vec2, get_size_as_vec2},
         lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
         lua_vec2::Vec2,
+        lua_vec4::Vec4,
     },
     make_resource_lua_compatible,
 };
@@ -48,6 +53,80 @@ impl RcFramebuffer {
     }
 }
 
+/// The shader and its uniforms, or None while the shader is not compiled.
+/// Errors when the shader was written for another vertex layout than the one of the primitive drawn.
+fn get_custom_shader(
+    resources: &ResourceManager,
+    shader: ShaderResourceId,
+    layout: ShaderLayout,
+) -> vectarine_plugin_sdk::mlua::Result<Option<CustomShader>> {
+    let id = shader.to_resource_id();
+    let Some(shader_resource) = get_shader_resource(resources, id) else {
+        return Ok(None);
+    };
+    let compiled_shader = shader_resource.shader.borrow();
+    let Some(compiled_shader) = compiled_shader.as_ref() else {
+        return Ok(None);
+    };
+    if compiled_shader.layout != layout {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+            "The shader {} uses the {} layout but this function needs the {layout} layout. Add '#pragma layout {layout}' to the shader.",
+            resources.get_holder_by_id(id).get_path().display(),
+            compiled_shader.layout,
+        )));
+    }
+    Ok(Some(CustomShader {
+        id,
+        uniforms: shader_resource.uniforms.borrow().clone(),
+    }))
+}
+
+/// Calls `draw_fn` with the shader instead of the built-in shader of the primitive when a shader is given.
+/// The built-in shader is used while the shader is loading.
+pub fn draw_with_shader(
+    resources: &ResourceManager,
+    batch: &RefCell<BatchDraw2d>,
+    shader: Option<ShaderResourceId>,
+    layout: ShaderLayout,
+    draw_fn: impl FnOnce(&mut BatchDraw2d),
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    let custom_shader = match shader {
+        Some(shader) => get_custom_shader(resources, shader, layout)?,
+        None => None,
+    };
+    let mut batch = batch.borrow_mut();
+    match custom_shader {
+        Some(custom_shader) => batch.with_custom_shader(custom_shader, draw_fn),
+        None => draw_fn(&mut batch),
+    }
+    Ok(())
+}
+
+fn lua_value_to_uniform(
+    value: &vectarine_plugin_sdk::mlua::Value,
+) -> vectarine_plugin_sdk::mlua::Result<UniformValue> {
+    match value {
+        vectarine_plugin_sdk::mlua::Value::Integer(n) => Ok(UniformValue::Float(*n as f32)),
+        vectarine_plugin_sdk::mlua::Value::Number(n) => Ok(UniformValue::Float(*n as f32)),
+        vectarine_plugin_sdk::mlua::Value::Boolean(b) => Ok(UniformValue::Bool(*b)),
+        vectarine_plugin_sdk::mlua::Value::UserData(ud) => {
+            if let Ok(vec) = ud.borrow::<Vec2>() {
+                Ok(UniformValue::Vec2([vec.x(), vec.y()]))
+            } else if let Ok(vec) = ud.borrow::<Vec4>() {
+                Ok(UniformValue::Vec4(vec.0))
+            } else {
+                Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "A uniform needs to be a number, a boolean, a Vec2 or a Vec4".to_string(),
+                ))
+            }
+        }
+        _ => Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+            "A uniform needs to be a number, a boolean, a Vec2 or a Vec4, not a {}",
+            value.type_name()
+        ))),
+    }
+}
+
 pub fn setup_canvas_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
@@ -69,8 +148,38 @@ pub fn setup_canvas_api(
         }
     });
 
+    add_fn_to_table(lua, &canvas_module, "new", {
+        let batch = batch.clone();
+        move |_lua, (width, height, options): (u32, u32, Option<Table>)| {
+            let msaa = match options {
+                Some(options) => options.get::<Option<u32>>("msaa")?,
+                None => None,
+            };
+            let canvas = RcFramebuffer::new(glframebuffer::Framebuffer::new_rgba_multisampled(
+                batch.borrow().drawing_target.gl(),
+                width,
+                height,
+                ImageAntialiasing::LinearWithMipmaps,
+                msaa.unwrap_or(1),
+            ));
+            Ok(canvas)
+        }
+    });
+
     lua.register_userdata_type::<ShaderResourceId>(|registry| {
         register_resource_id_methods_on_type(resources, registry);
+
+        registry.add_method("setUniform", {
+            let resources = resources.clone();
+            move |_lua, shader, (name, value): (String, vectarine_plugin_sdk::mlua::Value)| {
+                let value = lua_value_to_uniform(&value)?;
+                // The uniforms can be set before the shader is loaded.
+                if let Some(shader) = get_shader_resource(&resources, shader.to_resource_id()) {
+                    shader.uniforms.borrow_mut().set(&name, value);
+                }
+                Ok(())
+            }
+        });
     })?;
 
     lua.register_userdata_type::<RcFramebuffer>(|registry| {
@@ -86,10 +195,10 @@ pub fn setup_canvas_api(
             let resources = resources.clone();
             move |_, canvas, (func,): (vectarine_plugin_sdk::mlua::Function,)| {
                 let mut result = Ok(());
-                batch.borrow_mut().draw(&resources, true); // flush before changing framebuffer
+                batch.borrow_mut().begin_canvas(&resources, canvas.gl());
                 canvas.gl().using(|| {
                     result = func.call::<()>(());
-                    batch.borrow_mut().draw(&resources, true);
+                    batch.borrow_mut().end_canvas(&resources);
                 });
                 result
             }
@@ -102,10 +211,14 @@ pub fn setup_canvas_api(
                 let Some(shader_id) = shader_id else {
                     return Ok(()); // no op if no shader is set
                 };
-                let shader = resources.get_by_id::<ShaderResource>(shader_id);
-                let Ok(shader) = shader else {
+                let shader = get_shader_resource(&resources, shader_id);
+                let Some(shader) = shader else {
                     return Ok(()); // no op if shader resource is not loaded
                 };
+                shader
+                    .uniforms
+                    .borrow_mut()
+                    .set(&name, UniformValue::Float(value));
                 let mut shader = shader.shader.borrow_mut();
                 let shader = shader.as_mut();
                 let Some(shader) = shader else {
@@ -125,6 +238,35 @@ pub fn setup_canvas_api(
             }
         });
 
+        registry.add_method("resolve", {
+            move |_lua, canvas, (): ()| {
+                canvas.gl().resolve();
+                Ok(())
+            }
+        });
+
+        registry.add_method("getSampleCount", {
+            move |_lua, canvas, (): ()| Ok(canvas.gl().sample_count())
+        });
+
+        registry.add_meta_method(
+            vectarine_plugin_sdk::mlua::MetaMethod::ToString,
+            |_lua, canvas, (): ()| {
+                let framebuffer = canvas.gl();
+                let samples = framebuffer.sample_count();
+                Ok(if samples > 1 {
+                    format!(
+                        "Canvas({}x{}, {}x MSAA)",
+                        framebuffer.width(),
+                        framebuffer.height(),
+                        samples
+                    )
+                } else {
+                    format!("Canvas({}x{})", framebuffer.width(), framebuffer.height())
+                })
+            },
+        );
+
         registry.add_method("getSize", {
             move |_lua, canvas, (): ()| {
                 let size = Vec2::new(canvas.gl().width() as f32, canvas.gl().height() as f32);
@@ -135,21 +277,28 @@ pub fn setup_canvas_api(
         registry.add_method("draw", {
             let batch = batch.clone();
             let env = env_state.clone();
+            let resources = resources.clone();
             move |_, canvas, (mpos, msize): (AnyUserData, AnyUserData)| {
                 let pos = get_pos_as_vec2(mpos)?;
                 let size = get_size_as_vec2(msize)?;
                 let framebuffer = canvas.gl();
+                batch.borrow_mut().flush_canvas(&resources, framebuffer);
                 let shader = canvas.current_shader();
-                batch
-                    .borrow_mut()
-                    .draw_canvas(pos, size, framebuffer, shader, &env.borrow());
-                Ok(())
+                let shader_id = *canvas.shader.borrow();
+                draw_with_shader(
+                    &resources,
+                    &batch,
+                    shader_id,
+                    ShaderLayout::Texture,
+                    |batch| batch.draw_canvas(pos, size, framebuffer, shader, &env.borrow()),
+                )
             }
         });
 
         registry.add_method("drawPart", {
             let batch = batch.clone();
             let env_state = env_state.clone();
+            let resources = resources.clone();
             move |_,
                   canvas,
                   (mp1, mp2, mp3, mp4, src_pos, src_size): (
@@ -165,16 +314,25 @@ pub fn setup_canvas_api(
                 let p3 = get_pos_as_vec2(mp3)?;
                 let p4 = get_pos_as_vec2(mp4)?;
                 let framebuffer = canvas.gl();
+                batch.borrow_mut().flush_canvas(&resources, framebuffer);
                 let shader = canvas.current_shader();
-                batch.borrow_mut().draw_canvas_part(
-                    Quad { p1, p2, p3, p4 },
-                    framebuffer,
-                    src_pos,
-                    src_size,
-                    shader,
-                    &env_state.borrow(),
-                );
-                Ok(())
+                let shader_id = *canvas.shader.borrow();
+                draw_with_shader(
+                    &resources,
+                    &batch,
+                    shader_id,
+                    ShaderLayout::Texture,
+                    |batch| {
+                        batch.draw_canvas_part(
+                            Quad { p1, p2, p3, p4 },
+                            framebuffer,
+                            src_pos,
+                            src_size,
+                            shader,
+                            &env_state.borrow(),
+                        )
+                    },
+                )
             }
         });
     })?;
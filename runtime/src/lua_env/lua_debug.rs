@@ -1,13 +1,20 @@
 use std::{cell::RefCell, rc::Rc};
 
 use crate::console::{print_frame, print_info};
-use crate::lua_env::{add_fn_to_table, stringify_lua_value};
+use crate::gcpacing::GcPacer;
+use crate::latency::LatencyTracker;
+use crate::lua_env::lua_persist::LuaToolStore;
+use crate::lua_env::{add_fn_to_table, get_registered_modules, stringify_lua_value};
 
-use crate::metrics::MetricsHolder;
+use crate::metrics::{GameMetricKind, LUA_GC_TIME_METRIC_NAME, MemoryCategory, MetricsHolder};
+use crate::statehash::hash_lua_value;
 
 pub fn setup_debug_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     metrics: &Rc<RefCell<MetricsHolder>>,
+    gc_pacer: &Rc<RefCell<GcPacer>>,
+    tool_store: &LuaToolStore,
+    latency: &Rc<RefCell<LatencyTracker>>,
 ) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
     let debug_module = lua.create_table()?;
 
@@ -46,5 +53,252 @@ pub fn setup_debug_api(
         }
     });
 
+    add_fn_to_table(lua, &debug_module, "metricCounter", {
+        let metrics = metrics.clone();
+        move |_, (name, value): (String, f64)| {
+            metrics
+                .borrow_mut()
+                .record_game_metric(&name, GameMetricKind::Counter, value)
+                .map_err(vectarine_plugin_sdk::mlua::Error::runtime)
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "metricGauge", {
+        let metrics = metrics.clone();
+        move |_, (name, value): (String, f64)| {
+            metrics
+                .borrow_mut()
+                .record_game_metric(&name, GameMetricKind::Gauge, value)
+                .map_err(vectarine_plugin_sdk::mlua::Error::runtime)
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "getTimedAverage", {
+        let metrics = metrics.clone();
+        move |_, name: String| {
+            let metrics = metrics.borrow();
+            Ok(metrics
+                .get_duration_metric_by_name(&name)
+                .map(|metric| metric.avg().as_secs_f64()))
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "getMemoryStats", {
+        move |lua, ()| {
+            let stats = lua.create_table()?;
+            let mut total = 0;
+            for category in MemoryCategory::ALL {
+                let category_stats = lua.create_table()?;
+                category_stats.raw_set("bytes", category.usage())?;
+                category_stats.raw_set("peak", category.peak())?;
+                stats.raw_set(category.name(), category_stats)?;
+                total += category.usage();
+            }
+            stats.raw_set("total", total)?;
+            Ok(stats)
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "setGcStepSize", {
+        let gc_pacer = gc_pacer.clone();
+        move |lua, step_size_kb: u32| gc_pacer.borrow_mut().set_step_size(lua, step_size_kb)
+    });
+
+    add_fn_to_table(lua, &debug_module, "setGcPause", {
+        let gc_pacer = gc_pacer.clone();
+        move |lua, percent: u32| gc_pacer.borrow().set_pause(lua, percent)
+    });
+
+    add_fn_to_table(lua, &debug_module, "gcCollect", {
+        let gc_pacer = gc_pacer.clone();
+        let metrics = metrics.clone();
+        move |lua, kind: Option<String>| {
+            let start = std::time::Instant::now();
+            let gc_pacer = gc_pacer.borrow();
+            match kind.as_deref().unwrap_or("full") {
+                "full" => gc_pacer.collect(lua)?,
+                "step" => {
+                    gc_pacer.step(lua, gc_pacer.step_size_kb())?;
+                }
+                other => {
+                    return Err(vectarine_plugin_sdk::mlua::Error::runtime(format!(
+                        "Unknown collection '{other}', expected \"step\" or \"full\""
+                    )));
+                }
+            }
+            metrics
+                .borrow_mut()
+                .record_duration_metric(LUA_GC_TIME_METRIC_NAME, start.elapsed());
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "showLatencyOverlay", {
+        let latency = latency.clone();
+        move |_, is_shown: bool| {
+            let mut latency = latency.borrow_mut();
+            if is_shown {
+                latency.enable();
+            }
+            latency.is_overlay_shown = is_shown;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "getLatencyStats", {
+        let latency = latency.clone();
+        move |lua, ()| {
+            let mut latency = latency.borrow_mut();
+            // The first call starts the measures, the next ones cover the frames run since.
+            latency.enable();
+            let stats = latency.stats();
+            let milliseconds = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+            let table = lua.create_table()?;
+            table.raw_set("frameCount", stats.frame_count)?;
+            table.raw_set("targetFrameMs", milliseconds(stats.target_interval))?;
+            table.raw_set("missedFrames", stats.missed_frame_count)?;
+            table.raw_set("averageFrameMs", milliseconds(stats.average_frame_time))?;
+            table.raw_set("worstFrameMs", milliseconds(stats.worst_frame_time))?;
+            table.raw_set("averageCpuMs", milliseconds(stats.average_cpu_time))?;
+            table.raw_set("averageGpuMs", stats.average_gpu_time.map(milliseconds))?;
+            table.raw_set(
+                "averageInputLatencyMs",
+                stats.average_input_latency.map(milliseconds),
+            )?;
+            table.raw_set(
+                "worstInputLatencyMs",
+                stats.worst_input_latency.map(milliseconds),
+            )?;
+            Ok(table)
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "getModules", {
+        move |lua, ()| {
+            let modules = lua.create_table()?;
+            for module in get_registered_modules(lua) {
+                let members = lua.create_table()?;
+                for member in module.members {
+                    let member_table = lua.create_table()?;
+                    member_table.raw_set("name", member.name)?;
+                    member_table.raw_set("type", member.type_name)?;
+                    members.raw_set(members.raw_len() + 1, member_table)?;
+                }
+                let module_table = lua.create_table()?;
+                module_table.raw_set("name", format!("@vectarine/{}", module.name))?;
+                module_table.raw_set("members", members)?;
+                modules.raw_set(modules.raw_len() + 1, module_table)?;
+            }
+            Ok(modules)
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "hashState", {
+        move |_, state: vectarine_plugin_sdk::mlua::Value| {
+            // Lua numbers cannot hold 64 bits, so the hash is returned as hexadecimal.
+            Ok(format!("{:016x}", hash_lua_value(&state)?))
+        }
+    });
+
+    // Only available in development builds, to test what players see when the runtime crashes.
+    #[cfg(any(debug_assertions, feature = "editor"))]
+    add_fn_to_table(lua, &debug_module, "panic", {
+        move |_, message: Option<String>| -> vectarine_plugin_sdk::mlua::Result<()> {
+            panic!(
+                "{}",
+                message.unwrap_or_else(|| "Debug.panic was called".to_string())
+            );
+        }
+    });
+
+    add_fn_to_table(lua, &debug_module, "getToolStore", {
+        let tool_store = tool_store.clone();
+        move |_, ()| Ok(tool_store.clone())
+    });
+
     Ok(debug_module)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use vectarine_plugin_sdk::mlua::Lua;
+
+    use super::setup_debug_api;
+    use crate::{
+        gcpacing::GcPacer,
+        latency::LatencyTracker,
+        lua_env::{UNSAFE_INTERNALS_KEY, lua_persist::setup_tool_store, register_vectarine_module},
+        metrics::MetricsHolder,
+    };
+
+    fn new_lua() -> Lua {
+        let lua = Lua::new();
+        lua.globals()
+            .raw_set(UNSAFE_INTERNALS_KEY, lua.create_table().expect("Internals"))
+            .expect("Set internals");
+        let tool_store = setup_tool_store(&lua, &std::env::temp_dir()).expect("Tool store");
+        let debug = setup_debug_api(
+            &lua,
+            &Rc::new(RefCell::new(MetricsHolder::default())),
+            &Rc::new(RefCell::new(GcPacer::new(&lua))),
+            &tool_store,
+            &Rc::new(RefCell::new(LatencyTracker::default())),
+        )
+        .expect("Debug API");
+        lua.globals().set("Debug", debug).expect("Set Debug");
+        lua
+    }
+
+    #[cfg(any(debug_assertions, feature = "editor"))]
+    #[test]
+    fn panic_crashes_the_runtime_with_the_message() {
+        let lua = new_lua();
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = lua.load(r#"Debug.panic("the hero fell")"#).exec();
+        }))
+        .expect_err("Debug.panic panics");
+        assert_eq!(
+            payload.downcast_ref::<String>().map(String::as_str),
+            Some("the hero fell")
+        );
+
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = lua.load("Debug.panic()").exec();
+        }))
+        .expect_err("Debug.panic panics");
+        assert_eq!(
+            payload.downcast_ref::<String>().map(String::as_str),
+            Some("Debug.panic was called")
+        );
+    }
+
+    #[test]
+    fn get_modules_lists_the_registered_modules() {
+        let lua = new_lua();
+        let module = lua
+            .load("return { play = function() end, volume = 1.5 }")
+            .eval()
+            .expect("Module");
+        register_vectarine_module(&lua, "audio", module);
+        let description: String = lua
+            .load(
+                r#"
+                local lines = {}
+                for _, module in Debug.getModules() do
+                    for _, member in module.members do
+                        table.insert(lines, module.name .. "." .. member.name .. ": " .. member.type)
+                    end
+                end
+                return table.concat(lines, ", ")
+                "#,
+            )
+            .eval()
+            .expect("Debug.getModules runs");
+        assert_eq!(
+            description,
+            "@vectarine/audio.play: function, @vectarine/audio.volume: number"
+        );
+    }
+}
@@ -0,0 +1,454 @@
+use std::f32::consts::PI;
+
+use vectarine_plugin_sdk::mlua;
+
+use crate::lua_env::add_fn_to_table;
+
+/// Maps a progress between 0 and 1 to an eased progress. Every easing maps 0 to 0 and 1 to 1, but `back` and
+/// `elastic` go outside of [0, 1] in between.
+pub type EasingFunction = fn(f32) -> f32;
+
+/// Every easing available by name, both from the `ease` module and from the options of the engine taking an easing.
+pub const EASINGS: &[(&str, EasingFunction)] = &[
+    ("linear", linear),
+    ("sineIn", sine_in),
+    ("sineOut", sine_out),
+    ("sineInOut", sine_in_out),
+    ("quadIn", quad_in),
+    ("quadOut", quad_out),
+    ("quadInOut", quad_in_out),
+    ("cubicIn", cubic_in),
+    ("cubicOut", cubic_out),
+    ("cubicInOut", cubic_in_out),
+    ("quartIn", quart_in),
+    ("quartOut", quart_out),
+    ("quartInOut", quart_in_out),
+    ("expoIn", expo_in),
+    ("expoOut", expo_out),
+    ("expoInOut", expo_in_out),
+    ("circIn", circ_in),
+    ("circOut", circ_out),
+    ("circInOut", circ_in_out),
+    ("backIn", back_in),
+    ("backOut", back_out),
+    ("backInOut", back_in_out),
+    ("elasticIn", elastic_in),
+    ("elasticOut", elastic_out),
+    ("elasticInOut", elastic_in_out),
+    ("bounceIn", bounce_in),
+    ("bounceOut", bounce_out),
+    ("bounceInOut", bounce_in_out),
+];
+
+pub fn get_easing(name: &str) -> Option<EasingFunction> {
+    EASINGS
+        .iter()
+        .find(|(easing_name, _)| *easing_name == name)
+        .map(|(_, easing)| *easing)
+}
+
+/// The easing functions only make sense between 0 and 1. Clamping means that a timer running a bit late ends exactly
+/// on the final value instead of overshooting it.
+fn ease_clamped(easing: EasingFunction, t: f32) -> f32 {
+    if t.is_nan() {
+        return 0.0;
+    }
+    easing(t.clamp(0.0, 1.0))
+}
+
+fn linear(t: f32) -> f32 {
+    t
+}
+
+fn sine_in(t: f32) -> f32 {
+    1.0 - (t * PI / 2.0).cos()
+}
+
+fn sine_out(t: f32) -> f32 {
+    (t * PI / 2.0).sin()
+}
+
+fn sine_in_out(t: f32) -> f32 {
+    -((PI * t).cos() - 1.0) / 2.0
+}
+
+fn quad_in(t: f32) -> f32 {
+    t * t
+}
+
+fn quad_out(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+fn quad_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+    }
+}
+
+fn cubic_in(t: f32) -> f32 {
+    t * t * t
+}
+
+fn cubic_out(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+fn cubic_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+fn quart_in(t: f32) -> f32 {
+    t.powi(4)
+}
+
+fn quart_out(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(4)
+}
+
+fn quart_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        8.0 * t.powi(4)
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(4) / 2.0
+    }
+}
+
+// The exponential easings do not reach 0 and 1 on their own, so the ends are special cased.
+fn expo_in(t: f32) -> f32 {
+    if t == 0.0 {
+        0.0
+    } else {
+        2f32.powf(10.0 * t - 10.0)
+    }
+}
+
+fn expo_out(t: f32) -> f32 {
+    if t == 1.0 {
+        1.0
+    } else {
+        1.0 - 2f32.powf(-10.0 * t)
+    }
+}
+
+fn expo_in_out(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 {
+        t
+    } else if t < 0.5 {
+        2f32.powf(20.0 * t - 10.0) / 2.0
+    } else {
+        (2.0 - 2f32.powf(-20.0 * t + 10.0)) / 2.0
+    }
+}
+
+fn circ_in(t: f32) -> f32 {
+    1.0 - (1.0 - t * t).sqrt()
+}
+
+fn circ_out(t: f32) -> f32 {
+    (1.0 - (t - 1.0).powi(2)).sqrt()
+}
+
+fn circ_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        (1.0 - (1.0 - (2.0 * t).powi(2)).sqrt()) / 2.0
+    } else {
+        ((1.0 - (-2.0 * t + 2.0).powi(2)).sqrt() + 1.0) / 2.0
+    }
+}
+
+/// How far `back` goes past the ends, about 10% of the distance.
+const BACK_OVERSHOOT: f32 = 1.70158;
+
+fn back_in(t: f32) -> f32 {
+    (BACK_OVERSHOOT + 1.0) * t * t * t - BACK_OVERSHOOT * t * t
+}
+
+fn back_out(t: f32) -> f32 {
+    1.0 - back_in(1.0 - t)
+}
+
+fn back_in_out(t: f32) -> f32 {
+    let overshoot = BACK_OVERSHOOT * 1.525;
+    if t < 0.5 {
+        (2.0 * t).powi(2) * ((overshoot + 1.0) * 2.0 * t - overshoot) / 2.0
+    } else {
+        ((2.0 * t - 2.0).powi(2) * ((overshoot + 1.0) * (t * 2.0 - 2.0) + overshoot) + 2.0) / 2.0
+    }
+}
+
+fn elastic_in(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 {
+        return t;
+    }
+    -(2f32.powf(10.0 * t - 10.0)) * ((t * 10.0 - 10.75) * (2.0 * PI / 3.0)).sin()
+}
+
+fn elastic_out(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 {
+        return t;
+    }
+    2f32.powf(-10.0 * t) * ((t * 10.0 - 0.75) * (2.0 * PI / 3.0)).sin() + 1.0
+}
+
+fn elastic_in_out(t: f32) -> f32 {
+    if t == 0.0 || t == 1.0 {
+        return t;
+    }
+    let wave = ((20.0 * t - 11.125) * (2.0 * PI / 4.5)).sin();
+    if t < 0.5 {
+        -(2f32.powf(20.0 * t - 10.0) * wave) / 2.0
+    } else {
+        2f32.powf(-20.0 * t + 10.0) * wave / 2.0 + 1.0
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+fn bounce_in(t: f32) -> f32 {
+    1.0 - bounce_out(1.0 - t)
+}
+
+fn bounce_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+    } else {
+        (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+    }
+}
+
+/// The easing curves of CSS, going from (0, 0) to (1, 1) with the control points (x1, y1) and (x2, y2).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CubicBezier {
+    x1: f32,
+    y1: f32,
+    x2: f32,
+    y2: f32,
+}
+
+impl CubicBezier {
+    /// The x coordinates are clamped to [0, 1] so that the curve is a function of x, like in CSS.
+    pub fn new(x1: f32, y1: f32, x2: f32, y2: f32) -> Self {
+        Self {
+            x1: x1.clamp(0.0, 1.0),
+            y1,
+            x2: x2.clamp(0.0, 1.0),
+            y2,
+        }
+    }
+
+    fn sample(a1: f32, a2: f32, s: f32) -> f32 {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * s * a1 + 3.0 * inv * s * s * a2 + s * s * s
+    }
+
+    fn sample_derivative(a1: f32, a2: f32, s: f32) -> f32 {
+        let inv = 1.0 - s;
+        3.0 * inv * inv * a1 + 6.0 * inv * s * (a2 - a1) + 3.0 * s * s * (1.0 - a2)
+    }
+
+    pub fn ease(&self, t: f32) -> f32 {
+        if t.is_nan() {
+            return 0.0;
+        }
+        let x = t.clamp(0.0, 1.0);
+        // Find the parameter of the curve at x. Newton converges in a few steps for most curves. When the slope is
+        // too flat for it, bisection always works because x grows with the parameter.
+        let mut s = x;
+        for _ in 0..8 {
+            let error = Self::sample(self.x1, self.x2, s) - x;
+            if error.abs() < 1e-6 {
+                return Self::sample(self.y1, self.y2, s);
+            }
+            let slope = Self::sample_derivative(self.x1, self.x2, s);
+            if slope.abs() < 1e-6 {
+                break;
+            }
+            s = (s - error / slope).clamp(0.0, 1.0);
+        }
+        let (mut low, mut high) = (0.0f32, 1.0f32);
+        s = x;
+        for _ in 0..32 {
+            let sampled = Self::sample(self.x1, self.x2, s);
+            if (sampled - x).abs() < 1e-6 {
+                break;
+            }
+            if sampled < x {
+                low = s;
+            } else {
+                high = s;
+            }
+            s = (low + high) / 2.0;
+        }
+        Self::sample(self.y1, self.y2, s)
+    }
+}
+
+/// An easing given by the user, either as the name of a built-in easing or as a function.
+#[derive(Clone, Debug)]
+pub enum Easing {
+    BuiltIn(EasingFunction),
+    Custom(mlua::Function),
+}
+
+impl Easing {
+    pub fn ease(&self, t: f32) -> mlua::Result<f32> {
+        match self {
+            Easing::BuiltIn(easing) => Ok(ease_clamped(*easing, t)),
+            Easing::Custom(function) => function.call(t.clamp(0.0, 1.0)),
+        }
+    }
+}
+
+impl mlua::FromLua for Easing {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        match value {
+            mlua::Value::String(name) => {
+                let name = name.to_str()?;
+                get_easing(&name)
+                    .map(Easing::BuiltIn)
+                    .ok_or_else(|| mlua::Error::external(format!("Unknown easing: {}", &*name)))
+            }
+            mlua::Value::Function(function) => Ok(Easing::Custom(function)),
+            _ => Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Easing".to_string(),
+                message: Some("Expected the name of an easing or a function".to_string()),
+            }),
+        }
+    }
+}
+
+pub fn setup_ease_api(lua: &mlua::Lua) -> mlua::Result<mlua::Table> {
+    let ease_module = lua.create_table()?;
+
+    for (name, easing) in EASINGS {
+        let easing = *easing;
+        add_fn_to_table(lua, &ease_module, name, move |_, t: f32| {
+            Ok(ease_clamped(easing, t))
+        });
+    }
+
+    add_fn_to_table(lua, &ease_module, "get", |lua, name: String| {
+        let Some(easing) = get_easing(&name) else {
+            return Err(mlua::Error::external(format!("Unknown easing: {}", name)));
+        };
+        lua.create_function(move |_, t: f32| Ok(ease_clamped(easing, t)))
+    });
+
+    add_fn_to_table(
+        lua,
+        &ease_module,
+        "cubicBezier",
+        |lua, (x1, y1, x2, y2): (f32, f32, f32, f32)| {
+            let curve = CubicBezier::new(x1, y1, x2, y2);
+            lua.create_function(move |_, t: f32| Ok(curve.ease(t)))
+        },
+    );
+
+    Ok(ease_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, what: &str) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{what} gave {actual}, expected {expected}"
+        );
+    }
+
+    fn easing(name: &str) -> EasingFunction {
+        get_easing(name).expect("the easing exists")
+    }
+
+    #[test]
+    fn every_easing_starts_at_0_and_ends_at_1() {
+        for (name, easing) in EASINGS {
+            assert_close(easing(0.0), 0.0, name);
+            assert_close(easing(1.0), 1.0, name);
+        }
+    }
+
+    #[test]
+    fn in_out_easings_are_symmetric() {
+        for (name, easing) in EASINGS.iter().filter(|(name, _)| name.ends_with("InOut")) {
+            assert_close(easing(0.5), 0.5, name);
+            for t in [0.1, 0.25, 0.4] {
+                assert_close(easing(t) + easing(1.0 - t), 1.0, name);
+            }
+        }
+    }
+
+    #[test]
+    fn easings_match_reference_values() {
+        let references = [
+            ("sineIn", 0.5, 1.0 - std::f32::consts::FRAC_1_SQRT_2),
+            ("sineOut", 0.5, std::f32::consts::FRAC_1_SQRT_2),
+            ("quadIn", 0.5, 0.25),
+            ("quadInOut", 0.25, 0.125),
+            ("cubicOut", 0.5, 0.875),
+            ("quartIn", 0.5, 0.0625),
+            ("expoIn", 0.5, 0.031_25),
+            ("expoOut", 0.5, 0.968_75),
+            ("circOut", 0.5, 0.866_025),
+            ("backIn", 0.5, -0.087_698),
+            ("backOut", 0.5, 1.087_698),
+            ("elasticOut", 0.5, 1.015_625),
+            ("elasticIn", 0.5, -0.015_625),
+            ("bounceOut", 0.5, 0.765_625),
+            ("bounceIn", 0.5, 0.234_375),
+        ];
+        for (name, t, expected) in references {
+            assert_close(easing(name)(t), expected, name);
+        }
+    }
+
+    #[test]
+    fn progress_outside_of_the_range_is_clamped() {
+        assert_close(ease_clamped(easing("quadIn"), -1.0), 0.0, "quadIn(-1)");
+        assert_close(ease_clamped(easing("backOut"), 2.0), 1.0, "backOut(2)");
+        assert_close(ease_clamped(easing("linear"), f32::NAN), 0.0, "linear(NaN)");
+    }
+
+    #[test]
+    fn cubic_bezier_matches_css() {
+        let linear = CubicBezier::new(0.0, 0.0, 1.0, 1.0);
+        for t in [0.0, 0.2, 0.5, 0.9, 1.0] {
+            assert_close(linear.ease(t), t, "linear bezier");
+        }
+        // The "ease" timing function of CSS.
+        let ease = CubicBezier::new(0.25, 0.1, 0.25, 1.0);
+        assert_close(ease.ease(0.5), 0.802_403, "ease(0.5)");
+        assert_close(ease.ease(0.0), 0.0, "ease(0)");
+        assert_close(ease.ease(1.0), 1.0, "ease(1)");
+        let ease_in_out = CubicBezier::new(0.42, 0.0, 0.58, 1.0);
+        assert_close(ease_in_out.ease(0.5), 0.5, "ease-in-out(0.5)");
+        // A flat slope at the end, where Newton's method alone does not converge.
+        let flat = CubicBezier::new(1.0, 0.0, 1.0, 1.0);
+        assert_close(flat.ease(0.875), 0.5, "flat(0.875)");
+    }
+}
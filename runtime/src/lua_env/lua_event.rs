@@ -166,12 +166,20 @@ pub struct DefaultEvents {
     pub keydown_event: EventType,
     pub keyup_event: EventType,
     pub text_input_event: EventType,
+    pub text_editing_event: EventType,
 
     pub mouse_down_event: EventType,
     pub mouse_up_event: EventType,
     pub mouse_click_event: EventType,
 
+    pub touch_started_event: EventType,
+    pub touch_moved_event: EventType,
+    pub touch_ended_event: EventType,
+
+    pub input_kind_changed_event: EventType,
+
     pub resource_loaded_event: EventType,
+    pub scripts_reloaded_event: EventType,
     pub console_command_event: EventType,
 }
 
@@ -256,6 +264,8 @@ pub fn setup_event_api(
         create_event_constant_in_event_module(&event_manager, lua, "keyUp", &event_module)?;
     let text_input_event =
         create_event_constant_in_event_module(&event_manager, lua, "textInput", &event_module)?;
+    let text_editing_event =
+        create_event_constant_in_event_module(&event_manager, lua, "textEditing", &event_module)?;
 
     let mouse_down_event =
         create_event_constant_in_event_module(&event_manager, lua, "mouseDown", &event_module)?;
@@ -263,12 +273,30 @@ pub fn setup_event_api(
         create_event_constant_in_event_module(&event_manager, lua, "mouseUp", &event_module)?;
     let mouse_click_event =
         create_event_constant_in_event_module(&event_manager, lua, "mouseClick", &event_module)?;
+    let touch_started_event =
+        create_event_constant_in_event_module(&event_manager, lua, "touchStarted", &event_module)?;
+    let touch_moved_event =
+        create_event_constant_in_event_module(&event_manager, lua, "touchMoved", &event_module)?;
+    let touch_ended_event =
+        create_event_constant_in_event_module(&event_manager, lua, "touchEnded", &event_module)?;
+    let input_kind_changed_event = create_event_constant_in_event_module(
+        &event_manager,
+        lua,
+        "inputKindChanged",
+        &event_module,
+    )?;
     let resource_loaded_event = create_event_constant_in_event_module(
         &event_manager,
         lua,
         "resourceLoaded",
         &event_module,
     )?;
+    let scripts_reloaded_event = create_event_constant_in_event_module(
+        &event_manager,
+        lua,
+        "scriptsReloaded",
+        &event_module,
+    )?;
     let console_command_event = create_event_constant_in_event_module(
         &event_manager,
         lua,
@@ -282,9 +310,15 @@ pub fn setup_event_api(
         mouse_down_event,
         mouse_up_event,
         mouse_click_event,
+        touch_started_event,
+        touch_moved_event,
+        touch_ended_event,
+        input_kind_changed_event,
         resource_loaded_event,
+        scripts_reloaded_event,
         console_command_event,
         text_input_event,
+        text_editing_event,
     };
 
     Ok((event_module, default_events, event_manager))
@@ -3,22 +3,92 @@ use std::{cell::RefCell, rc::Rc, sync::Arc};
 use vectarine_plugin_sdk::mlua::{AnyUserData, ObjectLike};
 
 use crate::{
-    game_resource::{self, font_resource::use_default_font},
+    game_resource::{
+        self, font_resource::use_default_font, image_resource::ImageResource,
+        shader_resource::ShaderLayout,
+    },
     graphics::{
         affinetransform::AffineTransform,
-        batchdraw,
+        batchdraw::{self, make_rect},
         glstencil::draw_with_mask,
         gltexture::{ImageAntialiasing, Texture},
+        letterbox::PixelPerfect,
     },
     io,
     lua_env::{
         add_fn_to_table,
+        lua_canvas::{ShaderResourceId, draw_with_shader},
         lua_coord::{get_pos_as_vec2, get_size_as_vec2},
+        lua_fastlist::FastList,
+        lua_image::ImageResourceId,
         lua_vec2::Vec2,
         lua_vec4::{BLACK, Vec4, WHITE},
     },
 };
 
+/// Number of floats in a record of `drawRectBatch`: x, y, w, h, r, g, b, a.
+const RECT_RECORD_SIZE: usize = 8;
+/// Number of floats in a record of `drawImageBatch`: x, y, w, h, u0, v0, u1, v1, r, g, b, a.
+const IMAGE_RECORD_SIZE: usize = 12;
+
+/// Calls `f` on every record of `N` floats. The records are either a FastList, where every Vec2 holds 2 floats,
+/// or a string or a buffer of little-endian 32-bit floats.
+/// The length is checked once, so that a truncated record is an error instead of a silently missing quad.
+fn for_each_batch_record<const N: usize>(
+    function_name: &str,
+    records: &vectarine_plugin_sdk::mlua::Value,
+    mut f: impl FnMut([f32; N]),
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    let for_each_packed_record = |bytes: &[u8], f: &mut dyn FnMut([f32; N])| {
+        if !bytes.len().is_multiple_of(N * 4) {
+            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "{function_name}: the records have {} bytes, which is not a multiple of the {} bytes of a record",
+                bytes.len(),
+                N * 4
+            )));
+        }
+        for chunk in bytes.chunks_exact(N * 4) {
+            let mut record = [0.0; N];
+            for (value, bytes) in record.iter_mut().zip(chunk.chunks_exact(4)) {
+                *value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+            f(record);
+        }
+        Ok(())
+    };
+    match records {
+        vectarine_plugin_sdk::mlua::Value::UserData(records) => {
+            let list = records.borrow::<FastList>()?;
+            if !list.data.len().is_multiple_of(N / 2) {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "{function_name}: the FastList has {} elements, which is not a multiple of the {} elements of a record",
+                    list.data.len(),
+                    N / 2
+                )));
+            }
+            for chunk in list.data.chunks_exact(N / 2) {
+                let mut record = [0.0; N];
+                for (values, vec) in record.chunks_exact_mut(2).zip(chunk) {
+                    values[0] = vec.x();
+                    values[1] = vec.y();
+                }
+                f(record);
+            }
+            Ok(())
+        }
+        vectarine_plugin_sdk::mlua::Value::String(records) => {
+            for_each_packed_record(&records.as_bytes()[..], &mut f)
+        }
+        vectarine_plugin_sdk::mlua::Value::Buffer(records) => {
+            for_each_packed_record(&records.to_vec(), &mut f)
+        }
+        other => Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+            "{function_name}: expected a FastList, a string or a buffer, got {}",
+            other.type_name()
+        ))),
+    }
+}
+
 pub fn setup_graphics_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
@@ -29,15 +99,47 @@ pub fn setup_graphics_api(
 
     add_fn_to_table(lua, &graphics_module, "drawRect", {
         let batch = batch.clone();
-        move |_, (mpos, msize, color): (AnyUserData, AnyUserData, Option<Vec4>)| {
+        let resources = resources.clone();
+        move |_,
+              (mpos, msize, color, shader): (
+            AnyUserData,
+            AnyUserData,
+            Option<Vec4>,
+            Option<ShaderResourceId>,
+        )| {
             let pos = get_pos_as_vec2(mpos)?;
             let size = get_size_as_vec2(msize)?;
-            batch.borrow_mut().draw_rect(
+            draw_with_shader(&resources, &batch, shader, ShaderLayout::Color, |batch| {
+                batch.draw_rect(
+                    pos.x(),
+                    pos.y(),
+                    size.x(),
+                    size.y(),
+                    color.unwrap_or(BLACK).0,
+                )
+            })
+        }
+    });
+
+    add_fn_to_table(lua, &graphics_module, "drawRectGradient", {
+        let batch = batch.clone();
+        move |_,
+              (mpos, msize, top_left, top_right, bottom_right, bottom_left): (
+            AnyUserData,
+            AnyUserData,
+            Vec4,
+            Vec4,
+            Vec4,
+            Vec4,
+        )| {
+            let pos = get_pos_as_vec2(mpos)?;
+            let size = get_size_as_vec2(msize)?;
+            batch.borrow_mut().draw_rect_gradient(
                 pos.x(),
                 pos.y(),
                 size.x(),
                 size.y(),
-                color.unwrap_or(BLACK).0,
+                [bottom_left.0, bottom_right.0, top_right.0, top_left.0],
             );
             Ok(())
         }
@@ -45,14 +147,19 @@ pub fn setup_graphics_api(
 
     add_fn_to_table(lua, &graphics_module, "drawPolygon", {
         let batch = batch.clone();
-        move |_, (points, color): (Vec<AnyUserData>, Option<Vec4>)| {
+        let resources = resources.clone();
+        move |_,
+              (points, color, shader): (
+            Vec<AnyUserData>,
+            Option<Vec4>,
+            Option<ShaderResourceId>,
+        )| {
             let points = points
                 .into_iter()
                 .map(|p| get_pos_as_vec2(p).unwrap_or_default());
-            batch
-                .borrow_mut()
-                .draw_polygon(points, color.unwrap_or(BLACK).0);
-            Ok(())
+            draw_with_shader(&resources, &batch, shader, ShaderLayout::Color, |batch| {
+                batch.draw_polygon(points, color.unwrap_or(BLACK).0)
+            })
         }
     });
 
@@ -126,6 +233,21 @@ pub fn setup_graphics_api(
         }
     });
 
+    add_fn_to_table(lua, &graphics_module, "drawCircleGradient", {
+        let batch = batch.clone();
+        move |_, (mpos, radius, inner_color, outer_color): (AnyUserData, f32, Vec4, Vec4)| {
+            let pos = get_pos_as_vec2(mpos)?;
+            batch.borrow_mut().draw_circle_gradient(
+                pos.x(),
+                pos.y(),
+                radius,
+                inner_color.0,
+                outer_color.0,
+            );
+            Ok(())
+        }
+    });
+
     add_fn_to_table(lua, &graphics_module, "drawEllipse", {
         let batch = batch.clone();
         move |_, (mpos, size, color): (AnyUserData, AnyUserData, Option<Vec4>)| {
@@ -142,6 +264,42 @@ pub fn setup_graphics_api(
         }
     });
 
+    add_fn_to_table(lua, &graphics_module, "drawRectBatch", {
+        let batch = batch.clone();
+        move |_, records: vectarine_plugin_sdk::mlua::Value| {
+            let mut batch = batch.borrow_mut();
+            for_each_batch_record::<RECT_RECORD_SIZE>("drawRectBatch", &records, |record| {
+                let [x, y, w, h, r, g, b, a] = record;
+                batch.draw_rect(x, y, w, h, [r, g, b, a]);
+            })
+        }
+    });
+
+    add_fn_to_table(lua, &graphics_module, "drawImageBatch", {
+        let batch = batch.clone();
+        let resources = resources.clone();
+        move |_, (image_id, records): (ImageResourceId, vectarine_plugin_sdk::mlua::Value)| {
+            let Ok(image) = resources.get_by_id::<ImageResource>(image_id.0) else {
+                return Ok(());
+            };
+            let texture = image.texture.borrow();
+            let Some(texture) = texture.as_ref() else {
+                return Ok(());
+            };
+            let mut batch = batch.borrow_mut();
+            for_each_batch_record::<IMAGE_RECORD_SIZE>("drawImageBatch", &records, |record| {
+                let [x, y, w, h, u0, v0, u1, v1, r, g, b, a] = record;
+                batch.draw_image_part(
+                    make_rect(x, y, w, h),
+                    texture,
+                    Vec2::new(u0, v0),
+                    Vec2::new(u1 - u0, v1 - v0),
+                    [r, g, b, a],
+                );
+            })
+        }
+    });
+
     add_fn_to_table(lua, &graphics_module, "drawWithMask", {
         let batch = batch.clone();
         let resources = resources.clone();
@@ -240,6 +398,63 @@ pub fn setup_graphics_api(
         }
     });
 
+    add_fn_to_table(lua, &graphics_module, "setCullRect", {
+        let batch = batch.clone();
+        move |_lua, (min, max): (Option<Vec2>, Option<Vec2>)| {
+            let rect = match (min, max) {
+                (Some(min), Some(max)) => Some((min, max)),
+                (None, None) => None,
+                _ => {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                        "setCullRect expects both min and max, or nothing to disable culling"
+                            .to_string(),
+                    ));
+                }
+            };
+            batch.borrow_mut().set_cull_rect(rect);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &graphics_module, "withoutCulling", {
+        let batch = batch.clone();
+        move |_lua, draw_fn: vectarine_plugin_sdk::mlua::Function| {
+            let cull_rect = batch.borrow_mut().take_cull_rect();
+            let result = draw_fn.call::<()>(());
+            batch.borrow_mut().restore_cull_rect(cull_rect);
+            result
+        }
+    });
+
+    add_fn_to_table(lua, &graphics_module, "setPixelPerfect", {
+        let env_state = env_state.clone();
+        move |_lua,
+              (width, height, options): (
+            Option<u32>,
+            Option<u32>,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            let pixel_perfect = match (width, height) {
+                (Some(width), Some(height)) if width > 0 && height > 0 => Some(PixelPerfect {
+                    width,
+                    height,
+                    snap: match &options {
+                        Some(options) => options.get::<Option<bool>>("snap")?.unwrap_or(false),
+                        None => false,
+                    },
+                }),
+                (None, None) => None,
+                _ => {
+                    return Err(vectarine_plugin_sdk::mlua::Error::external(
+                        "setPixelPerfect expects a width and a height above 0, or nothing to disable it",
+                    ));
+                }
+            };
+            env_state.borrow_mut().pixel_perfect = pixel_perfect;
+            Ok(())
+        }
+    });
+
     add_fn_to_table(lua, &graphics_module, "clear", {
         let batch = batch.clone();
         move |_, (color,): (Option<Vec4>,)| {
@@ -273,7 +488,7 @@ pub fn setup_graphics_api(
         move |loading_text: Option<String>, progress: Option<f32>| {
             batch.borrow_mut().clear(BLACK.0);
             let env = env_state.borrow();
-            let aspect = env.window_width as f32 / env.window_height as f32;
+            let aspect = env.game_viewport.aspect_ratio();
             let pos = Vec2::new(-scale, -scale * aspect);
             let size = Vec2::new(scale * 2.0, scale * 2.0 * aspect);
             {
@@ -6,14 +6,16 @@ use crate::{
     auto_impl_lua_copy, console,
     game_resource::{
         self, ResourceId, ResourceManager, image_resource::ImageResource,
-        tile_resource::TilesetContent,
+        shader_resource::ShaderLayout, tile_resource::TilesetContent,
     },
     graphics::{batchdraw, shape::Quad},
     io,
     lua_env::{
+        lua_canvas::{ShaderResourceId, draw_with_shader},
         lua_coord::{get_pos_as_vec2, get_size_as_vec2},
         lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
         lua_tile::{TilesetResourceId, get_tileset_from_resource_id},
+        lua_transform::Transform2,
         lua_vec2::Vec2,
         lua_vec4::{Vec4, WHITE},
         stringify_lua_value,
@@ -69,7 +71,12 @@ pub fn setup_image_api(
             let resources = resources.clone();
             move |_lua,
                   image_resource_id,
-                  (mpos, msize, color): (AnyUserData, AnyUserData, Option<Vec4>)| {
+                  (mpos, msize, color, shader): (
+                AnyUserData,
+                AnyUserData,
+                Option<Vec4>,
+                Option<ShaderResourceId>,
+            )| {
                 let pos = get_pos_as_vec2(mpos)?;
                 let size = get_size_as_vec2(msize)?;
                 let tex = resources.get_by_id::<ImageResource>(image_resource_id.0);
@@ -80,15 +87,48 @@ pub fn setup_image_api(
                 let Some(tex) = tex.as_ref() else {
                     return Ok(());
                 };
-                batch.borrow_mut().draw_image(
-                    pos.x(),
-                    pos.y(),
-                    size.x(),
-                    size.y(),
-                    tex,
-                    color.unwrap_or(WHITE).0,
-                );
-                Ok(())
+                draw_with_shader(&resources, &batch, shader, ShaderLayout::Texture, |batch| {
+                    batch.draw_image(
+                        pos.x(),
+                        pos.y(),
+                        size.x(),
+                        size.y(),
+                        tex,
+                        color.unwrap_or(WHITE).0,
+                    )
+                })
+            }
+        });
+
+        registry.add_method("drawTransformed", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            move |_lua,
+                  image_resource_id,
+                  (transform, msize, color, shader): (
+                Transform2,
+                AnyUserData,
+                Option<Vec4>,
+                Option<ShaderResourceId>,
+            )| {
+                let size = get_size_as_vec2(msize)?;
+                let tex = resources.get_by_id::<ImageResource>(image_resource_id.0);
+                let Ok(tex) = tex else {
+                    return Ok(());
+                };
+                let tex = tex.texture.borrow();
+                let Some(tex) = tex.as_ref() else {
+                    return Ok(());
+                };
+                draw_with_shader(&resources, &batch, shader, ShaderLayout::Texture, |batch| {
+                    batch.draw_image_transformed(
+                        &transform,
+                        size.x(),
+                        size.y(),
+                        tex,
+                        color.unwrap_or(WHITE).0,
+                    )
+                })
             }
         });
 
@@ -241,11 +281,23 @@ pub fn draw_tile_part<T>(
     t_to_i64: impl Fn(&T, &TilesetContent) -> Option<i64>,
     color: Option<Vec4>,
 ) {
-    let tex = resources.get_by_id::<ImageResource>(image_with_tileset.image_id.0);
+    let image = resources.get_by_id::<ImageResource>(image_with_tileset.image_id.0);
     get_tileset_from_resource_id(resources, image_with_tileset.tileset_id, |tileset| {
-        let tex = tex.ok()?;
-        let tex = tex.texture.borrow();
-        let tex = tex.as_ref()?;
+        let image = image.ok()?;
+        let layout = tileset.tile_layout();
+        image.upload_built_tile_atlases(batch.borrow().drawing_target.gl());
+        if image.use_tile_layout(image_with_tileset.tileset_id.to_resource_id(), layout)
+            && image.texture.borrow().is_some()
+        {
+            request_tile_atlas(resources, image_with_tileset.image_id, &image);
+        }
+        let atlases = image.tile_atlases.borrow();
+        let atlas = atlases.iter().find(|atlas| atlas.layout == layout);
+        let texture = image.texture.borrow();
+        let tex = match atlas {
+            Some(atlas) => &atlas.texture,
+            None => texture.as_ref()?,
+        };
 
         let column_count = tileset.tiled.columns as i64;
         let tile_width = tileset.tiled.tile_width as i64;
@@ -258,11 +310,22 @@ pub fn draw_tile_part<T>(
             .filter_map(|(tile_id, quad)| {
                 // tile_id can be a number or a string. It it's a string, we need to find the corresponding tile id in the tileset
                 let id = t_to_i64(tile_id, tileset)?;
-                let x = id % column_count * (tile_width + spacing) + margin;
-                let y = id / column_count * (tile_height + spacing) + margin;
-
-                // To make everything pixel perfect and avoid sampling from neighboring pixels, we need to add an epsilon to the uvs.
-                let epsilon = 0.2;
+                // The tiles of the atlas are surrounded by copies of their edges, so the filtering never reads another
+                // tile. Until the atlas is built, the uvs are inset by half a texel, which avoids most of the bleeding.
+                let (x, y, epsilon) = match atlas {
+                    Some(atlas) => {
+                        let id = u32::try_from(id)
+                            .ok()
+                            .filter(|id| *id < atlas.layout.tile_count)?;
+                        let (x, y) = atlas.layout.atlas_position(id);
+                        (x as i64, y as i64, 0.0)
+                    }
+                    None => (
+                        id % column_count * (tile_width + spacing) + margin,
+                        id / column_count * (tile_height + spacing) + margin,
+                        0.5,
+                    ),
+                };
                 let src_pos = Vec2::new(
                     (x as f32 + epsilon) / tex.width() as f32,
                     (y as f32 + epsilon) / tex.height() as f32,
@@ -281,3 +344,20 @@ pub fn draw_tile_part<T>(
         Some(())
     });
 }
+
+/// The pixels of the images are not kept in memory, so the file of the image is read again to build the atlas.
+fn request_tile_atlas(
+    resources: &Rc<ResourceManager>,
+    image_id: ImageResourceId,
+    image: &Rc<ImageResource>,
+) {
+    let image = image.clone();
+    resources.read_resource_file(
+        image_id.0,
+        Box::new(move |data| {
+            if let Some(data) = data {
+                image.build_tile_atlas_from_data(data);
+            }
+        }),
+    );
+}
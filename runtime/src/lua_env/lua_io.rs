@@ -1,20 +1,143 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, rc::Rc, time::Duration};
 
-use vectarine_plugin_sdk::mlua::{Result, Table};
+use vectarine_plugin_sdk::mlua::{IntoLua, Result, Table, Value};
 use vectarine_plugin_sdk::sdl2;
 use vectarine_plugin_sdk::sdl2::keyboard::Scancode;
 
 use crate::{
-    io::IoEnvState,
-    lua_env::{add_fn_to_table, lua_vec2::Vec2},
+    console::print_warn,
+    game_resource::ResourceManager,
+    graphics::letterbox::{AspectPolicy, gl_to_window_px, safe_rect},
+    io::{
+        IoEnvState, TextComposition, TextInputRequest, TouchState, clipboard,
+        cursor::{CursorRequest, SYSTEM_CURSOR_NAMES, read_cursor_image, system_cursor_from_name},
+        framepacing::VsyncMode,
+        gamepad::{self, GamepadState},
+        get_pinch,
+        inputkind::{GamepadType, InputKind},
+        messagebox::{DEFAULT_CONFIRM_BUTTONS, show_alert, show_confirm},
+        rumble::{RumblePattern, RumbleSegment},
+    },
+    lua_env::{add_fn_to_table, lua_image::ImageResourceId, lua_vec2::Vec2, lua_vec4::Vec4},
 };
 
+pub fn touch_to_lua(lua: &vectarine_plugin_sdk::mlua::Lua, touch: &TouchState) -> Result<Table> {
+    let touch_table = lua.create_table()?;
+    touch_table.raw_set("id", touch.id)?;
+    touch_table.raw_set("position", Vec2::new(touch.x, touch.y))?;
+    touch_table.raw_set("pressure", touch.pressure)?;
+    Ok(touch_table)
+}
+
+/// Returns nil when the player is not composing text.
+pub fn text_composition_to_lua(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    composition: Option<&TextComposition>,
+) -> Result<Value> {
+    let Some(composition) = composition else {
+        return Ok(Value::Nil);
+    };
+    let composition_table = lua.create_table()?;
+    composition_table.raw_set("text", composition.text.as_str())?;
+    composition_table.raw_set("cursor", composition.cursor)?;
+    composition_table.raw_set("selectionLength", composition.selection_length)?;
+    Ok(Value::Table(composition_table))
+}
+
+pub fn input_kind_to_lua(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    input_kind: InputKind,
+) -> Result<Table> {
+    let kind_table = lua.create_table()?;
+    kind_table.raw_set("device", input_kind.device_name())?;
+    if let InputKind::Gamepad { id, gamepad_type } = input_kind {
+        kind_table.raw_set("gamepadId", id)?;
+        kind_table.raw_set("gamepadType", gamepad_type.name())?;
+    }
+    Ok(kind_table)
+}
+
+/// Returns true if the condition is true for the gamepad with the id, or for any gamepad when there is no id.
+fn any_gamepad(gamepad_id: Option<u32>, condition: impl Fn(&GamepadState) -> bool) -> bool {
+    gamepad::with_gamepads(|gamepads| match gamepad_id {
+        Some(id) => gamepads.get(&id).is_some_and(&condition),
+        None => gamepads.values().any(&condition),
+    })
+}
+
+/// Converts seconds given by the game to a duration, negative values being 0. `Duration` cannot hold infinite, NaN or
+/// huge values, they are reported as errors of `what`.
+fn seconds_to_duration(seconds: f64, what: &str) -> Result<Duration> {
+    // `max` would turn NaN into 0.
+    let seconds_or_zero = if seconds < 0.0 { 0.0 } else { seconds };
+    Duration::try_from_secs_f64(seconds_or_zero).map_err(|_| {
+        vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+            "{what} needs to be a finite number, got {seconds}"
+        ))
+    })
+}
+
+/// Reads a pattern like `{ { lowFreq = 1, highFreq = 0, durationMs = 100 }, { durationMs = 50 }, looped = true }`.
+fn rumble_pattern_from_lua(pattern: &Table) -> Result<RumblePattern> {
+    let segments = pattern
+        .sequence_values::<Table>()
+        .map(|segment| {
+            let segment = segment?;
+            Ok(RumbleSegment {
+                low_frequency: segment.get::<Option<f32>>("lowFreq")?.unwrap_or(0.0),
+                high_frequency: segment.get::<Option<f32>>("highFreq")?.unwrap_or(0.0),
+                duration: seconds_to_duration(
+                    segment.get::<f64>("durationMs")? / 1000.0,
+                    "The durationMs of a rumble segment",
+                )?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if segments.is_empty() {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "A rumble pattern needs at least one segment".to_string(),
+        ));
+    }
+    Ok(RumblePattern {
+        segments,
+        looped: pattern.get::<Option<bool>>("looped")?.unwrap_or(false),
+    })
+}
+
+/// Interpolates between the state of the previous tick and the one of the current tick, for numbers and vectors.
+fn interpolate_values(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    previous: &Value,
+    current: &Value,
+    alpha: f32,
+) -> Result<Value> {
+    if let (Some(previous), Some(current)) = (previous.as_f64(), current.as_f64()) {
+        return Ok(Value::Number(
+            previous + (current - previous) * alpha as f64,
+        ));
+    }
+    if let (Value::UserData(previous), Value::UserData(current)) = (previous, current) {
+        if let (Ok(previous), Ok(current)) = (previous.borrow::<Vec2>(), current.borrow::<Vec2>()) {
+            return previous.lerp(*current, alpha).into_lua(lua);
+        }
+        if let (Ok(previous), Ok(current)) = (previous.borrow::<Vec4>(), current.borrow::<Vec4>()) {
+            return previous.lerp(*current, alpha).into_lua(lua);
+        }
+    }
+    Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+        "Cannot interpolate between a {} and a {}, expected two numbers, two Vec2 or two Vec4",
+        previous.type_name(),
+        current.type_name()
+    )))
+}
+
 /// Adds to the Lua environment functions to interact with the outside environment
 /// For example, the keyboard, the mouse, the window, etc...
 /// This is called the IO API.
 pub fn setup_io_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     env_state: &Rc<RefCell<IoEnvState>>,
+    resources: &Rc<ResourceManager>,
 ) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
     let io_module = lua.create_table()?;
 
@@ -89,6 +212,249 @@ pub fn setup_io_api(
         }
     });
 
+    add_fn_to_table(lua, &io_module, "getTextComposition", {
+        let env_state = env_state.clone();
+        move |lua, ()| text_composition_to_lua(lua, env_state.borrow().text_composition.as_ref())
+    });
+
+    add_fn_to_table(lua, &io_module, "startTextInput", {
+        let env_state = env_state.clone();
+        move |_, (area,): (Option<Table>,)| {
+            let mut state = env_state.borrow_mut();
+            let rect = match area {
+                Some(area) => {
+                    let position = area.get::<Vec2>("position")?;
+                    let size = area.get::<Vec2>("size")?;
+                    // The position is the bottom-left corner and SDL wants the top-left one, in window pixels.
+                    let (left, top) = gl_to_window_px(
+                        &state.game_viewport,
+                        state.window_height,
+                        position.x(),
+                        position.y() + size.y(),
+                    );
+                    let (right, bottom) = gl_to_window_px(
+                        &state.game_viewport,
+                        state.window_height,
+                        position.x() + size.x(),
+                        position.y(),
+                    );
+                    let (px_ratio_x, px_ratio_y) = (state.px_ratio_x, state.px_ratio_y);
+                    Some(sdl2::rect::Rect::new(
+                        (left / px_ratio_x) as i32,
+                        (top / px_ratio_y) as i32,
+                        ((right - left) / px_ratio_x).max(1.0) as u32,
+                        ((bottom - top) / px_ratio_y).max(1.0) as u32,
+                    ))
+                }
+                None => None,
+            };
+            state.text_input_request = Some(TextInputRequest::Start(rect));
+            state.is_text_input_active = true;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "stopTextInput", {
+        let env_state = env_state.clone();
+        move |_, ()| {
+            let mut state = env_state.borrow_mut();
+            state.text_input_request = Some(TextInputRequest::Stop);
+            state.is_text_input_active = false;
+            state.text_composition = None;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "isTextInputActive", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().is_text_input_active)
+    });
+
+    add_fn_to_table(lua, &io_module, "onTextInput", {
+        let env_state = env_state.clone();
+        move |_, (handler,): (Option<vectarine_plugin_sdk::mlua::Function>,)| {
+            env_state.borrow_mut().text_input_handler = handler;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getClipboardText", |_, ()| {
+        Ok(clipboard::get_clipboard_text())
+    });
+
+    add_fn_to_table(lua, &io_module, "setClipboardText", |_, text: String| {
+        clipboard::set_clipboard_text(&text).map_err(|error| {
+            vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "Failed to copy the text: {error}"
+            ))
+        })
+    });
+
+    add_fn_to_table(lua, &io_module, "getGamepads", |lua, ()| {
+        gamepad::with_gamepads(|gamepads| {
+            let table = lua.create_table()?;
+            for (id, state) in gamepads {
+                let gamepad_table = lua.create_table()?;
+                gamepad_table.raw_set("id", *id)?;
+                gamepad_table.raw_set("name", state.name.as_str())?;
+                table.raw_set(table.raw_len() + 1, gamepad_table)?;
+            }
+            Ok(table)
+        })
+    });
+
+    add_fn_to_table(
+        lua,
+        &io_module,
+        "isGamepadButtonDown",
+        |_, (button_name, gamepad_id): (String, Option<u32>)| {
+            let Some(button) = sdl2::controller::Button::from_string(&button_name) else {
+                return Ok(false);
+            };
+            Ok(any_gamepad(gamepad_id, |state| {
+                state.buttons_down.contains(&button)
+            }))
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &io_module,
+        "isGamepadButtonJustPressed",
+        |_, (button_name, gamepad_id): (String, Option<u32>)| {
+            let Some(button) = sdl2::controller::Button::from_string(&button_name) else {
+                return Ok(false);
+            };
+            Ok(any_gamepad(gamepad_id, |state| {
+                state.buttons_just_pressed.contains(&button)
+            }))
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &io_module,
+        "getGamepadAxis",
+        |_, (axis_name, gamepad_id): (String, Option<u32>)| {
+            let Some(axis) = sdl2::controller::Axis::from_string(&axis_name) else {
+                return Ok(0.0);
+            };
+            // Without an id, the gamepad pushed the furthest is used.
+            let value = gamepad::with_gamepads(|gamepads| {
+                gamepads
+                    .iter()
+                    .filter(|(id, _)| gamepad_id.is_none_or(|gamepad_id| **id == gamepad_id))
+                    .map(|(_, state)| state.axis(axis))
+                    .fold(0.0, |a: f32, b: f32| if b.abs() > a.abs() { b } else { a })
+            });
+            Ok(value)
+        },
+    );
+
+    add_fn_to_table(lua, &io_module, "rumble", {
+        let env_state = env_state.clone();
+        move |_, (gamepad_id, low_frequency, high_frequency, duration_ms): (u32, f32, f32, f64)| {
+            // A direct rumble replaces the pattern of the gamepad.
+            env_state.borrow_mut().rumble.forget(gamepad_id);
+            let duration = seconds_to_duration(duration_ms / 1000.0, "The duration of Io.rumble")?;
+            Ok(gamepad::set_gamepad_rumble(
+                gamepad_id,
+                low_frequency,
+                high_frequency,
+                duration,
+            ))
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "defineRumble", {
+        let env_state = env_state.clone();
+        move |_, (name, pattern): (String, Table)| {
+            let pattern = rumble_pattern_from_lua(&pattern)?;
+            env_state.borrow_mut().rumble.define(name, pattern);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "playRumblePattern", {
+        let env_state = env_state.clone();
+        move |_, (gamepad_id, pattern): (u32, Value)| {
+            let mut env_state = env_state.borrow_mut();
+            let pattern = match pattern {
+                Value::String(name) => {
+                    let name = name.to_string_lossy();
+                    env_state.rumble.get_defined(&name).ok_or_else(|| {
+                        vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                            "No rumble pattern named \"{name}\", define it with Io.defineRumble"
+                        ))
+                    })?
+                }
+                Value::Table(pattern) => Rc::new(rumble_pattern_from_lua(&pattern)?),
+                _ => {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                        "The rumble pattern must be a name or a list of segments".to_string(),
+                    ));
+                }
+            };
+            let now = env_state.frame_time;
+            env_state.rumble.play(gamepad_id, pattern, now);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "stopRumble", {
+        let env_state = env_state.clone();
+        move |_, gamepad_id: Option<u32>| {
+            let mut env_state = env_state.borrow_mut();
+            match gamepad_id {
+                Some(gamepad_id) => env_state.rumble.stop(gamepad_id),
+                None => {
+                    env_state.rumble.stop_all();
+                    let ids = gamepad::with_gamepads(|gamepads| {
+                        gamepads.keys().copied().collect::<Vec<_>>()
+                    });
+                    for gamepad_id in ids {
+                        gamepad::set_gamepad_rumble(gamepad_id, 0.0, 0.0, Duration::ZERO);
+                    }
+                }
+            }
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getActiveInputKind", {
+        let env_state = env_state.clone();
+        move |lua, ()| input_kind_to_lua(lua, env_state.borrow().input_kind.active())
+    });
+
+    add_fn_to_table(lua, &io_module, "setInputKindSwitchDelay", {
+        let env_state = env_state.clone();
+        move |_, seconds: f64| {
+            env_state.borrow_mut().input_kind.switch_delay =
+                seconds_to_duration(seconds, "The delay of Io.setInputKindSwitchDelay")?;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getGamepadButtonGlyphName", {
+        let env_state = env_state.clone();
+        move |_, (button_name, gamepad_id): (String, Option<u32>)| {
+            // Without an id, the gamepad used last is used, or the first one connected.
+            let gamepad_id = gamepad_id.or(match env_state.borrow().input_kind.active() {
+                InputKind::Gamepad { id, .. } => Some(id),
+                _ => None,
+            });
+            let gamepad_type = gamepad::with_gamepads(|gamepads| {
+                match gamepad_id {
+                    Some(id) => gamepads.get(&id),
+                    None => gamepads.values().next(),
+                }
+                .map(|state| state.gamepad_type)
+                .unwrap_or(GamepadType::Unknown)
+            });
+            Ok(gamepad_type.button_glyph_name(&button_name).to_string())
+        }
+    });
+
     add_fn_to_table(lua, &io_module, "getMouse", {
         let env_state = env_state.clone();
         move |_, ()| {
@@ -97,6 +463,49 @@ pub fn setup_io_api(
         }
     });
 
+    add_fn_to_table(lua, &io_module, "setCursor", {
+        let env_state = env_state.clone();
+        let resources = resources.clone();
+        move |_, (image_id, hotspot_x, hotspot_y): (ImageResourceId, Option<i32>, Option<i32>)| {
+            let hotspot = (hotspot_x.unwrap_or(0), hotspot_y.unwrap_or(0));
+            let env_state = env_state.clone();
+            read_cursor_image(&resources, image_id.0, move |image| {
+                let Some(image) = image else {
+                    print_warn("The image of the cursor cannot be read".to_string());
+                    return;
+                };
+                env_state.borrow_mut().cursor_request = Some(CursorRequest::Image {
+                    width: image.width(),
+                    height: image.height(),
+                    pixels: image.into_raw(),
+                    hotspot,
+                });
+            });
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "setSystemCursor", {
+        let env_state = env_state.clone();
+        move |_, name: String| {
+            let Some(system_cursor) = system_cursor_from_name(&name) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "Unknown cursor '{name}', expected {SYSTEM_CURSOR_NAMES}"
+                )));
+            };
+            env_state.borrow_mut().cursor_request = Some(CursorRequest::System(system_cursor));
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "hideCursor", {
+        let env_state = env_state.clone();
+        move |_, ()| {
+            env_state.borrow_mut().cursor_request = Some(CursorRequest::Hidden);
+            Ok(())
+        }
+    });
+
     add_fn_to_table(lua, &io_module, "getMouseWheel", {
         let env_state = env_state.clone();
         move |_, ()| {
@@ -118,22 +527,32 @@ pub fn setup_io_api(
         }
     });
 
-    add_fn_to_table(lua, &io_module, "getCurrentTouches", {
+    let get_touches = {
         let env_state = env_state.clone();
-        move |lua, ()| -> Result<Vec<Table>> {
-            env_state
-                .borrow()
-                .current_touches
-                .values()
-                .map(|touch| {
-                    let touch_table = lua.create_table()?;
-                    touch_table.raw_set("id", touch.id)?;
-                    touch_table.raw_set("position", Vec2::new(touch.x, touch.y))?;
-                    touch_table.raw_set("pressure", touch.pressure)?;
-                    Ok(touch_table)
-                })
+        move |lua: &vectarine_plugin_sdk::mlua::Lua, ()| -> Result<Vec<Table>> {
+            let env_state = env_state.borrow();
+            let mut touches = env_state.current_touches.values().collect::<Vec<_>>();
+            touches.sort_by_key(|touch| touch.id);
+            touches
+                .into_iter()
+                .map(|touch| touch_to_lua(lua, touch))
                 .collect()
         }
+    };
+    add_fn_to_table(lua, &io_module, "getTouches", get_touches.clone());
+    add_fn_to_table(lua, &io_module, "getCurrentTouches", get_touches);
+
+    add_fn_to_table(lua, &io_module, "getPinch", {
+        let env_state = env_state.clone();
+        move |lua, ()| {
+            let Some(pinch) = get_pinch(&env_state.borrow()) else {
+                return Ok(None);
+            };
+            let table = lua.create_table()?;
+            table.raw_set("scale", pinch.scale)?;
+            table.raw_set("rotation", pinch.rotation)?;
+            Ok(Some(table))
+        }
     });
 
     add_fn_to_table(lua, &io_module, "getWindowSize", {
@@ -147,6 +566,50 @@ pub fn setup_io_api(
         }
     });
 
+    add_fn_to_table(lua, &io_module, "setAspectPolicy", {
+        let env_state = env_state.clone();
+        move |_, (policy,): (vectarine_plugin_sdk::mlua::Value,)| {
+            let aspect_policy = match &policy {
+                vectarine_plugin_sdk::mlua::Value::String(name) => {
+                    AspectPolicy::from_name(&name.to_string_lossy())
+                }
+                vectarine_plugin_sdk::mlua::Value::Table(size) => {
+                    AspectPolicy::from_size(size.raw_get::<f32>(1)?, size.raw_get::<f32>(2)?)
+                }
+                _ => None,
+            };
+            let Some(aspect_policy) = aspect_policy else {
+                return Err(vectarine_plugin_sdk::mlua::Error::runtime(
+                    "Invalid aspect policy, expected \"free\", \"letterbox_16_9\", \"letterbox_4_3\" or { width, height }",
+                ));
+            };
+            env_state.borrow_mut().aspect_policy = aspect_policy;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getPixelScale", {
+        let env_state = env_state.clone();
+        move |_lua, ()| Ok(env_state.borrow().pixel_scale)
+    });
+
+    add_fn_to_table(lua, &io_module, "getSafeRect", {
+        let env_state = env_state.clone();
+        move |lua, ()| {
+            let state = env_state.borrow();
+            let (position, size) = safe_rect(
+                &state.game_viewport,
+                state.window_width,
+                state.window_height,
+                state.safe_area_insets,
+            );
+            let rect = lua.create_table()?;
+            rect.raw_set("position", Vec2::new(position[0], position[1]))?;
+            rect.raw_set("size", Vec2::new(size[0], size[1]))?;
+            Ok(rect)
+        }
+    });
+
     add_fn_to_table(lua, &io_module, "getScreenSize", {
         let env_state = env_state.clone();
         move |_lua, ()| {
@@ -187,6 +650,94 @@ pub fn setup_io_api(
         move |_, ()| Ok(env_state.borrow_mut().is_window_minimized)
     });
 
+    add_fn_to_table(lua, &io_module, "getTickCount", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().tick_count)
+    });
+
+    add_fn_to_table(lua, &io_module, "getTickDuration", {
+        let env_state = env_state.clone();
+        move |_, ()| {
+            Ok(env_state
+                .borrow()
+                .tick_duration
+                .map(|duration| duration.as_secs_f32()))
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getTickAlpha", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().tick_alpha)
+    });
+
+    add_fn_to_table(lua, &io_module, "interpolateTick", {
+        let env_state = env_state.clone();
+        move |lua, (previous, current): (Value, Value)| {
+            let alpha = env_state.borrow().tick_alpha;
+            interpolate_values(lua, &previous, &current, alpha)
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getMonotonicFrameClock", {
+        let env_state = env_state.clone();
+        move |lua, ()| {
+            let env_state = env_state.borrow();
+            // With fixed ticks, the time is computed from the tick count so that it is the same on every machine.
+            let time = match env_state.tick_duration {
+                Some(tick_duration) => {
+                    (tick_duration.as_nanos() * env_state.tick_count as u128) as f64 / 1e9
+                }
+                None => env_state.frame_time.as_secs_f64(),
+            };
+            let clock = lua.create_table()?;
+            clock.raw_set("frame", env_state.frame_index)?;
+            clock.raw_set("tick", env_state.tick_count)?;
+            clock.raw_set("timeSeconds", time)?;
+            Ok(clock)
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "setTargetFps", {
+        let env_state = env_state.clone();
+        move |_, fps: Option<f64>| {
+            let target_fps = match fps {
+                None => None,
+                Some(fps) if fps == 0.0 => None,
+                Some(fps) if fps.is_finite() && fps >= 1.0 => Some(fps.round() as u32),
+                Some(fps) => {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                        "The target frame rate is {fps}, it should be at least 1, or nil for no limit"
+                    )));
+                }
+            };
+            env_state.borrow_mut().target_fps = target_fps;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getTargetFps", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().target_fps)
+    });
+
+    add_fn_to_table(lua, &io_module, "setVsync", {
+        let env_state = env_state.clone();
+        move |_, mode: String| {
+            let Some(mode) = VsyncMode::from_name(&mode) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "Unknown vsync mode '{mode}', expected \"on\", \"off\" or \"adaptive\""
+                )));
+            };
+            env_state.borrow_mut().vsync_mode = mode;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "getVsync", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().vsync_mode.name())
+    });
+
     add_fn_to_table(lua, &io_module, "centerWindow", {
         let env_state = env_state.clone();
         move |_, ()| {
@@ -220,5 +771,148 @@ pub fn setup_io_api(
         }
     });
 
+    add_fn_to_table(lua, &io_module, "canSpeak", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().platform_services.can_speak())
+    });
+
+    add_fn_to_table(lua, &io_module, "speak", {
+        let env_state = env_state.clone();
+        move |_, (text, options): (String, Option<Table>)| {
+            let interrupt = match options {
+                Some(options) => options.get::<Option<bool>>("interrupt")?.unwrap_or(false),
+                None => false,
+            };
+            env_state
+                .borrow_mut()
+                .platform_services
+                .speak(&text, interrupt);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "canNotify", {
+        let env_state = env_state.clone();
+        move |_, ()| Ok(env_state.borrow().platform_services.can_notify())
+    });
+
+    add_fn_to_table(lua, &io_module, "notify", {
+        let env_state = env_state.clone();
+        move |_, (title, body): (String, String)| {
+            env_state
+                .borrow_mut()
+                .platform_services
+                .notify(&title, &body);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(
+        lua,
+        &io_module,
+        "alert",
+        |_, (title, message): (String, String)| {
+            show_alert(&title, &message);
+            Ok(())
+        },
+    );
+
+    add_fn_to_table(lua, &io_module, "confirm", {
+        move |_,
+              (title, message, options, callback): (
+            String,
+            String,
+            Option<Table>,
+            Option<vectarine_plugin_sdk::mlua::Function>,
+        )| {
+            let buttons = match &options {
+                Some(options) => options.get::<Option<Vec<String>>>("buttons")?,
+                None => None,
+            };
+            let buttons = buttons
+                .filter(|buttons| !buttons.is_empty())
+                .unwrap_or_else(|| DEFAULT_CONFIRM_BUTTONS.map(String::from).to_vec());
+            let pressed = show_confirm(&title, &message, &buttons);
+            if let Some(callback) = callback {
+                callback.call::<()>((pressed + 1, buttons[pressed].clone()))?;
+            }
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "onQuitRequested", {
+        let env_state = env_state.clone();
+        move |_, (handler,): (Option<vectarine_plugin_sdk::mlua::Function>,)| {
+            env_state.borrow_mut().quit_handler = handler;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "quit", {
+        let env_state = env_state.clone();
+        move |_, ()| {
+            env_state.borrow_mut().is_quit_requested = true;
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &io_module, "setServiceRateLimit", {
+        let env_state = env_state.clone();
+        move |_, (calls_per_minute,): (u32,)| {
+            env_state
+                .borrow_mut()
+                .platform_services
+                .set_calls_per_minute(calls_per_minute);
+            Ok(())
+        }
+    });
+
     Ok(io_module)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use vectarine_plugin_sdk::mlua::{Lua, Table};
+
+    use super::{rumble_pattern_from_lua, seconds_to_duration};
+
+    #[test]
+    fn invalid_durations_are_errors_instead_of_panics() {
+        assert_eq!(
+            seconds_to_duration(-1.0, "The delay").expect("valid"),
+            Duration::ZERO
+        );
+        assert_eq!(
+            seconds_to_duration(0.25, "The delay").expect("valid"),
+            Duration::from_millis(250)
+        );
+        let error = seconds_to_duration(f64::INFINITY, "The delay").expect_err("infinite");
+        assert!(
+            error
+                .to_string()
+                .contains("The delay needs to be a finite number, got inf")
+        );
+        assert!(seconds_to_duration(f64::NAN, "The delay").is_err());
+        assert!(seconds_to_duration(1e30, "The delay").is_err());
+    }
+
+    #[test]
+    fn rumble_patterns_with_infinite_durations_are_errors() {
+        let lua = Lua::new();
+        let pattern = |source: &str| {
+            let pattern = lua
+                .load(source)
+                .eval::<Table>()
+                .expect("the pattern is created");
+            rumble_pattern_from_lua(&pattern)
+        };
+        let valid = pattern("{ { lowFreq = 1, durationMs = 100 }, { durationMs = -5 } }")
+            .expect("the pattern is valid");
+        assert_eq!(valid.segments[0].duration, Duration::from_millis(100));
+        assert_eq!(valid.segments[1].duration, Duration::ZERO);
+        assert!(pattern("{ { durationMs = math.huge } }").is_err());
+        assert!(pattern("{ { durationMs = 0/0 } }").is_err());
+    }
+}
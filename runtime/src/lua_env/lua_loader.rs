@@ -1,4 +1,8 @@
-use std::{cell::RefCell, path::Path, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use vectarine_plugin_sdk::mlua::UserDataMethods;
 use vectarine_plugin_sdk::mlua::{FromLua, IntoLua};
@@ -6,14 +10,18 @@ use vectarine_plugin_sdk::mlua::{FromLua, IntoLua};
 use crate::game_resource::tile_resource::TilemapResource;
 use crate::lua_env::lua_tile::TilemapResourceId;
 use crate::{
+    console,
     game_resource::{
-        ResourceId, ResourceManager, audio_resource::AudioResource, font_resource::FontResource,
-        image_resource::ImageResource, shader_resource::ShaderResource,
-        text_resource::TextResource, tile_resource::TilesetResource,
+        LoadPriority, ResourceId, ResourceManager, animation_resource::AnimationResource,
+        audio_resource::AudioResource, font_resource::FontResource, image_resource::ImageResource,
+        shader_resource::ShaderResource, text_resource::TextResource,
+        tile_resource::TilesetResource,
     },
     graphics::gltexture::ImageAntialiasing,
+    io::{fs::ReadOnlyFileSystem, localfs::LocalFileSystem},
     lua_env::{
         add_fn_to_table,
+        lua_animation::AnimationResourceId,
         lua_audio::AudioResourceId,
         lua_canvas::ShaderResourceId,
         lua_image::ImageResourceId,
@@ -28,6 +36,53 @@ use crate::{
 pub struct TextResourceId(ResourceId);
 make_resource_lua_compatible!(TextResourceId);
 
+/// Where to read a bundle given to `Loader.mountBundle`. URLs are fetched as is, the other paths are relative to the
+/// folder of the game, where bundle.vecta is.
+fn get_bundle_location(resources: &ResourceManager, path_or_url: &str) -> PathBuf {
+    if path_or_url.contains("://") {
+        return PathBuf::from(path_or_url);
+    }
+    let game_folder = resources.get_resource_path();
+    let game_folder = game_folder.parent().unwrap_or(Path::new(""));
+    game_folder.join(path_or_url)
+}
+
+/// Schedules the load of a file given to `Loader.preload`, with the resource type matching its extension.
+/// Returns false for the extensions shared by several types, like .json, which are only loaded by the `load` functions.
+fn schedule_preload(resources: &ResourceManager, path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    let Some(extension) = extension else {
+        return false;
+    };
+    match extension.as_str() {
+        "png" | "jpg" | "jpeg" | "bmp" | "gif" | "webp" | "ico" => {
+            resources.schedule_load_resource::<ImageResource>(path);
+        }
+        "ttf" | "otf" => {
+            resources.schedule_load_resource::<FontResource>(path);
+        }
+        "wav" | "ogg" | "mp3" | "mp2" | "flac" => {
+            resources.schedule_load_resource::<AudioResource>(path);
+        }
+        "glsl" => {
+            resources.schedule_load_resource::<ShaderResource>(path);
+        }
+        "tsx" => {
+            resources.schedule_load_resource::<TilesetResource>(path);
+        }
+        "tmx" => {
+            resources.schedule_load_resource::<TilemapResource>(path);
+        }
+        "txt" => {
+            resources.schedule_load_resource::<TextResource>(path);
+        }
+        _ => return false,
+    }
+    true
+}
+
 pub fn setup_loader_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     resources: &Rc<ResourceManager>,
@@ -82,6 +137,10 @@ pub fn setup_loader_api(
                             ImageAntialiasing::Nearest
                         }
                     }),
+                    tile_layouts: RefCell::new(Vec::new()),
+                    tile_atlases: RefCell::new(Vec::new()),
+                    tile_atlas_generation: Cell::new(0),
+                    built_tile_atlases: Default::default(),
                 },
             );
             vectarine_plugin_sdk::mlua::Result::Ok(ImageResourceId::from_id(id))
@@ -128,12 +187,21 @@ pub fn setup_loader_api(
         }
     });
 
+    add_fn_to_table(lua, &loader_module, "loadAnimation", {
+        let resources = resources.clone();
+        move |_, path: String| {
+            let id = resources.schedule_load_resource::<AnimationResource>(Path::new(&path));
+            Ok(AnimationResourceId::from_id(id))
+        }
+    });
+
     add_fn_to_table(lua, &loader_module, "loadScript", {
         let resources = resources.clone();
         move |lua, (path, results): (String, Option<vectarine_plugin_sdk::mlua::Table>)| {
             if let Some(target_table) = results {
                 let (id, table) =
                     resources.schedule_load_script_resource(Path::new(&path), target_table);
+                resources.script_graph.borrow_mut().record_load(id);
                 return Ok((
                     ScriptResourceId::from_id(id),
                     vectarine_plugin_sdk::mlua::Value::Table(table),
@@ -142,6 +210,7 @@ pub fn setup_loader_api(
             let dummy_table = lua.create_table()?;
             let (id, table) =
                 resources.schedule_load_script_resource(Path::new(&path), dummy_table);
+            resources.script_graph.borrow_mut().record_load(id);
             Ok((
                 ScriptResourceId::from_id(id),
                 vectarine_plugin_sdk::mlua::Value::Table(table),
@@ -149,5 +218,95 @@ pub fn setup_loader_api(
         }
     });
 
+    add_fn_to_table(lua, &loader_module, "preload", {
+        let resources = resources.clone();
+        move |_, (paths, options): (Vec<String>, Option<vectarine_plugin_sdk::mlua::Table>)| {
+            let priority = match &options {
+                Some(options) => options.get::<Option<String>>("priority")?,
+                None => None,
+            };
+            let priority = match priority {
+                Some(name) => LoadPriority::from_name(&name).ok_or_else(|| {
+                    vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                        "Unknown priority '{name}', expected 'high', 'normal' or 'low'"
+                    ))
+                })?,
+                None => LoadPriority::High,
+            };
+            for path in paths {
+                // The priority is set first so that it applies to the load scheduled just after.
+                resources.set_priority(Path::new(&path), priority);
+                schedule_preload(&resources, Path::new(&path));
+            }
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &loader_module, "getLoadingProgress", {
+        let resources = resources.clone();
+        move |lua, (): ()| {
+            let progress_table = lua.create_table()?;
+            for priority in LoadPriority::ALL {
+                let progress = resources.get_loading_progress(priority);
+                let table = lua.create_table()?;
+                table.raw_set("loaded", progress.loaded)?;
+                table.raw_set("failed", progress.failed)?;
+                table.raw_set("total", progress.total)?;
+                progress_table.raw_set(priority.name(), table)?;
+            }
+            Ok(progress_table)
+        }
+    });
+
+    add_fn_to_table(lua, &loader_module, "mountBundle", {
+        let resources = resources.clone();
+        move |_,
+              (path, callback, options): (
+            String,
+            Option<vectarine_plugin_sdk::mlua::Function>,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            let priority = match &options {
+                Some(options) => options.get::<Option<i32>>("priority")?.unwrap_or(0),
+                None => 0,
+            };
+            let resources = resources.clone();
+            // On the web, the bundle is downloaded, so the callback runs after this function returns.
+            LocalFileSystem.read_file(
+                &get_bundle_location(&resources, &path),
+                Box::new(move |data| {
+                    let result = match data {
+                        Some(data) => resources.mount_pack(data, priority),
+                        None => Err(format!("Bundle not found: {path}")),
+                    };
+                    if let Err(err) = &result {
+                        console::print_warn(format!("Unable to mount {path}: {err}"));
+                    }
+                    let Some(callback) = callback else {
+                        return;
+                    };
+                    let call_result = match result {
+                        Ok(name) => callback.call::<()>((true, name)),
+                        Err(err) => callback.call::<()>((false, err)),
+                    };
+                    if let Err(err) = call_result {
+                        console::print_err(format!("Error in the callback of mountBundle: {err}"));
+                    }
+                }),
+            );
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &loader_module, "unmountBundle", {
+        let resources = resources.clone();
+        move |_, name: String| Ok(resources.unmount_pack(&name))
+    });
+
+    add_fn_to_table(lua, &loader_module, "getMountedBundles", {
+        let resources = resources.clone();
+        move |_, (): ()| Ok(resources.get_mounted_packs())
+    });
+
     Ok(loader_module)
 }
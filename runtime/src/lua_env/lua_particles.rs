@@ -0,0 +1,242 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
+
+use crate::{
+    auto_impl_lua_clone,
+    game_resource::{ResourceManager, image_resource::ImageResource},
+    graphics::{
+        batchdraw,
+        particles::{Curve, EmitterConfig, Lerp, ParticleEmitter, ParticleShape},
+    },
+    lua_env::{
+        add_fn_to_table,
+        lua_anim::{check_known_fields, definition_error, get_field},
+        lua_coord::get_pos_as_vec2,
+        lua_image::ImageResourceId,
+        lua_vec2::Vec2,
+    },
+};
+
+thread_local! {
+    /// Emitters created without a seed get different seeds, in the order of their creation.
+    static NEXT_SEED: Cell<u64> = const { Cell::new(0) };
+}
+
+struct Emitter {
+    emitter: ParticleEmitter,
+    image: Option<ImageResourceId>,
+}
+
+#[derive(Clone)]
+pub struct LuaParticleEmitter(Rc<RefCell<Emitter>>);
+auto_impl_lua_clone!(LuaParticleEmitter, LuaParticleEmitter);
+
+/// A range is either a number or a `{min, max}` table.
+fn parse_range(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    definition: &vectarine_plugin_sdk::mlua::Table,
+    key: &str,
+    default: (f32, f32),
+) -> vectarine_plugin_sdk::mlua::Result<(f32, f32)> {
+    match definition.raw_get::<vectarine_plugin_sdk::mlua::Value>(key)? {
+        vectarine_plugin_sdk::mlua::Value::Nil => Ok(default),
+        vectarine_plugin_sdk::mlua::Value::Table(range) => {
+            let bounds = range
+                .sequence_values::<f32>()
+                .collect::<Result<Vec<_>, _>>();
+            match bounds.as_deref() {
+                Ok([min, max]) => Ok((*min, *max)),
+                _ => Err(definition_error(key, "expected a number or {min, max}")),
+            }
+        }
+        value => f32::from_lua(value, lua)
+            .map(|value| (value, value))
+            .map_err(|_| definition_error(key, "expected a number or {min, max}")),
+    }
+}
+
+/// A curve is either a single value or a list of values spread over the lifetime of the particles.
+fn parse_curve<T: Lerp + FromLua>(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    definition: &vectarine_plugin_sdk::mlua::Table,
+    key: &str,
+    default: Curve<T>,
+    expected: &str,
+) -> vectarine_plugin_sdk::mlua::Result<Curve<T>> {
+    let error = || definition_error(key, format!("expected {expected} or a list of them"));
+    match definition.raw_get::<vectarine_plugin_sdk::mlua::Value>(key)? {
+        vectarine_plugin_sdk::mlua::Value::Nil => Ok(default),
+        vectarine_plugin_sdk::mlua::Value::Table(keys) => {
+            let keys = keys
+                .sequence_values::<T>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| error())?;
+            Curve::new(keys).map_err(|message| definition_error(key, message))
+        }
+        value => T::from_lua(value, lua)
+            .map(Curve::constant)
+            .map_err(|_| error()),
+    }
+}
+
+fn parse_definition(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    definition: &vectarine_plugin_sdk::mlua::Table,
+) -> vectarine_plugin_sdk::mlua::Result<(EmitterConfig, Option<ImageResourceId>, u64)> {
+    check_known_fields(
+        definition,
+        &[
+            "rate",
+            "lifetime",
+            "speed",
+            "direction",
+            "spread",
+            "gravity",
+            "speedCurve",
+            "sizes",
+            "colors",
+            "shape",
+            "image",
+            "maxParticles",
+            "seed",
+        ],
+        "definition",
+    )?;
+    let default = EmitterConfig::default();
+    let shape = match get_field::<String>(definition, "shape", "", "\"square\" or \"circle\"")? {
+        Some(name) => ParticleShape::from_name(&name).ok_or_else(|| {
+            definition_error(
+                "shape",
+                format!("unknown shape '{name}', expected \"square\" or \"circle\""),
+            )
+        })?,
+        None => default.shape,
+    };
+    let config = EmitterConfig {
+        rate: get_field(definition, "rate", "", "a number")?.unwrap_or(default.rate),
+        lifetime: parse_range(lua, definition, "lifetime", default.lifetime)?,
+        speed: parse_range(lua, definition, "speed", default.speed)?,
+        direction: get_field(definition, "direction", "", "a number")?.unwrap_or(default.direction),
+        spread: get_field(definition, "spread", "", "a number")?.unwrap_or(default.spread),
+        gravity: get_field(definition, "gravity", "", "a Vec2")?.unwrap_or(default.gravity),
+        speed_curve: parse_curve(
+            lua,
+            definition,
+            "speedCurve",
+            default.speed_curve,
+            "a number",
+        )?,
+        size_curve: parse_curve(lua, definition, "sizes", default.size_curve, "a number")?,
+        color_curve: parse_curve(lua, definition, "colors", default.color_curve, "a Vec4")?,
+        shape,
+        max_particles: get_field(definition, "maxParticles", "", "a number")?
+            .unwrap_or(default.max_particles),
+    };
+    config
+        .validate()
+        .map_err(vectarine_plugin_sdk::mlua::Error::RuntimeError)?;
+    let image = get_field::<ImageResourceId>(definition, "image", "", "an image")?;
+    let seed = match get_field::<u64>(definition, "seed", "", "a number")? {
+        Some(seed) => seed,
+        None => NEXT_SEED.replace(NEXT_SEED.get().wrapping_add(1)),
+    };
+    Ok((config, image, seed))
+}
+
+pub fn setup_particles_api(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
+    resources: &Rc<ResourceManager>,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let particles_module = lua.create_table()?;
+
+    add_fn_to_table(
+        lua,
+        &particles_module,
+        "newEmitter",
+        |lua, (definition, mpos): (vectarine_plugin_sdk::mlua::Table, Option<AnyUserData>)| {
+            let (config, image, seed) = parse_definition(lua, &definition)?;
+            let position = match mpos {
+                Some(mpos) => get_pos_as_vec2(mpos)?,
+                None => Vec2::zero(),
+            };
+            Ok(LuaParticleEmitter(Rc::new(RefCell::new(Emitter {
+                emitter: ParticleEmitter::new(config, position, seed),
+                image,
+            }))))
+        },
+    );
+
+    lua.register_userdata_type::<LuaParticleEmitter>(|registry| {
+        registry.add_method("setPosition", |_, emitter, mpos: AnyUserData| {
+            emitter.0.borrow_mut().emitter.position = get_pos_as_vec2(mpos)?;
+            Ok(())
+        });
+
+        registry.add_method("getPosition", |_, emitter, (): ()| {
+            Ok(emitter.0.borrow().emitter.position)
+        });
+
+        registry.add_method("setRate", |_, emitter, rate: f32| {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "The rate is {rate}, it should be a positive number"
+                )));
+            }
+            emitter.0.borrow_mut().emitter.config.rate = rate;
+            Ok(())
+        });
+
+        registry.add_method("setDirection", |_, emitter, direction: f32| {
+            emitter.0.borrow_mut().emitter.config.direction = direction;
+            Ok(())
+        });
+
+        registry.add_method("emit", |_, emitter, count: usize| {
+            emitter.0.borrow_mut().emitter.emit(count);
+            Ok(())
+        });
+
+        registry.add_method("update", |_, emitter, delta_time: f32| {
+            emitter.0.borrow_mut().emitter.update(delta_time);
+            Ok(())
+        });
+
+        registry.add_method("getParticleCount", |_, emitter, (): ()| {
+            Ok(emitter.0.borrow().emitter.particle_count())
+        });
+
+        registry.add_method("clear", |_, emitter, (): ()| {
+            emitter.0.borrow_mut().emitter.clear();
+            Ok(())
+        });
+
+        registry.add_method("draw", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            move |_, emitter, (): ()| {
+                let emitter = emitter.0.borrow();
+                let mut batch = batch.borrow_mut();
+                let Some(image) = emitter.image else {
+                    emitter.emitter.draw(&mut batch, None);
+                    return Ok(());
+                };
+                // Like the other draw functions, nothing is drawn until the image is loaded.
+                let Ok(image) = resources.get_by_id::<ImageResource>(image.0) else {
+                    return Ok(());
+                };
+                let texture = image.texture.borrow();
+                if let Some(texture) = texture.as_ref() {
+                    emitter.emitter.draw(&mut batch, Some(texture));
+                }
+                Ok(())
+            }
+        });
+    })?;
+
+    Ok(particles_module)
+}
@@ -1,9 +1,20 @@
-use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use std::{
+    cell::RefCell,
+    io::Write,
+    path::{Path, PathBuf},
+    rc::{Rc, Weak},
+};
 
 use serde_json;
-use vectarine_plugin_sdk::mlua::LuaSerdeExt;
+use vectarine_plugin_sdk::mlua::{FromLua, IntoLua, LuaSerdeExt, UserDataMethods};
 
-use crate::lua_env::add_fn_to_table;
+use crate::{auto_impl_lua_clone, console::print_warn, lua_env::add_fn_to_table};
+
+/// Folder inside the project where the editor stores data that is not part of the game.
+/// It is not exported and not watched for resource changes.
+pub const EDITOR_CACHE_FOLDER: &str = ".vectarine-cache";
+#[cfg(feature = "editor")]
+const TOOL_STORE_FILE_NAME: &str = "tool_store.json";
 
 fn serialize_lua(
     lua: &vectarine_plugin_sdk::mlua::Lua,
@@ -27,6 +38,21 @@ fn deserialize_lua(
     lua.to_value(&json_value)
 }
 
+/// Writes the data next to the file, then replaces the file with it. A crash in the middle of the write leaves the
+/// previous content of the file instead of half of the new one.
+fn write_file_atomically(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary_path = path.with_file_name(format!("{file_name}.tmp"));
+    let mut file = std::fs::File::create(&temporary_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temporary_path, path)
+}
+
 fn get_kv_store_path() -> std::path::PathBuf {
     let exec_path = std::env::current_exe().ok();
     let data_folder = exec_path.and_then(|p| p.parent().map(|p| p.join("data")));
@@ -36,24 +62,15 @@ fn get_kv_store_path() -> std::path::PathBuf {
     PathBuf::from("data")
 }
 
-fn save_data_in_kv_store(key: String, value: Box<[u8]>) {
+pub fn save_data_in_kv_store(key: String, value: Box<[u8]>) {
     let path = get_kv_store_path();
     let path = path.join(format!("{}.bin", key));
     let prefix = path.parent().expect("No parent");
     std::fs::create_dir_all(prefix).expect("Unable to create directory");
-    let mut file = match OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(&path)
-    {
-        Ok(file) => file,
-        Err(err) => {
-            println!("Unable to create file: {}", err);
-            return;
-        }
-    };
-    let _ = file.write_all(&value);
+    if let Err(err) = write_file_atomically(&path, &value) {
+        println!("Unable to save {}: {}", path.display(), err);
+        return;
+    }
 
     #[cfg(target_os = "emscripten")]
     {
@@ -68,7 +85,7 @@ fn save_data_in_kv_store(key: String, value: Box<[u8]>) {
     }
 }
 
-fn load_data_from_kv_store(key: String) -> Option<Box<[u8]>> {
+pub fn load_data_from_kv_store(key: String) -> Option<Box<[u8]>> {
     let path = get_kv_store_path();
     let path = path.join(format!("{}.bin", key));
     std::fs::read(&path).ok().map(|v| v.into_boxed_slice())
@@ -129,6 +146,142 @@ pub fn setup_persist_api(
     Ok(persist_module)
 }
 
+// MARK: Tool store
+
+/// A key-value store for the state of development tools (debug panels, spawners, etc.).
+/// It is separate from the store of the player, which is accessed with `Persist.save` and `Persist.load`.
+/// In the editor, it is saved inside the project, in the editor cache folder.
+/// In exported games, it only lives in memory and is never saved.
+pub struct ToolStore {
+    values: serde_json::Map<String, serde_json::Value>,
+    path: Option<PathBuf>,
+    is_dirty: bool,
+}
+
+impl ToolStore {
+    #[cfg(feature = "editor")]
+    fn new(project_path: &Path) -> Self {
+        let path = project_path
+            .join(EDITOR_CACHE_FOLDER)
+            .join(TOOL_STORE_FILE_NAME);
+        let values = std::fs::read(&path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        Self {
+            values,
+            path: Some(path),
+            is_dirty: false,
+        }
+    }
+
+    #[cfg(not(feature = "editor"))]
+    fn new(_project_path: &Path) -> Self {
+        Self {
+            values: serde_json::Map::new(),
+            path: None,
+            is_dirty: false,
+        }
+    }
+
+    /// Returns true when the store had changes and was written.
+    fn flush(&mut self) -> bool {
+        if !self.is_dirty {
+            return false;
+        }
+        self.is_dirty = false;
+        let Some(path) = &self.path else {
+            return false;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let data = serde_json::to_vec_pretty(&self.values).unwrap_or_default();
+        if let Err(err) = write_file_atomically(path, &data) {
+            print_warn(format!("Unable to save the tool store: {err}"));
+            return false;
+        }
+        true
+    }
+}
+
+impl Drop for ToolStore {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+#[derive(Clone)]
+pub struct LuaToolStore(Rc<RefCell<ToolStore>>);
+auto_impl_lua_clone!(LuaToolStore, ToolStore);
+
+thread_local! {
+    /// The store written by `flush_tool_store_on_crash`.
+    static CRASH_FLUSHED_STORE: RefCell<Weak<RefCell<ToolStore>>> = RefCell::new(Weak::new());
+}
+
+impl LuaToolStore {
+    /// Writes the changes of the store. Returns true when there were changes.
+    pub fn flush(&self) -> bool {
+        self.0.try_borrow_mut().is_ok_and(|mut store| store.flush())
+    }
+
+    /// Makes this store the one written by `flush_tool_store_on_crash`.
+    pub fn flush_on_crash(&self) {
+        CRASH_FLUSHED_STORE.set(Rc::downgrade(&self.0));
+    }
+}
+
+/// Writes the changes of the store chosen with `flush_on_crash`, from a panic hook. This is a best effort: nothing is
+/// written when the panic happened on another thread or while the store was being changed.
+pub fn flush_tool_store_on_crash() -> bool {
+    CRASH_FLUSHED_STORE
+        .try_with(|store| {
+            let store = store.try_borrow().ok()?.upgrade()?;
+            let mut store = store.try_borrow_mut().ok()?;
+            Some(store.flush())
+        })
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
+
+/// Create the tool store of a project and register its methods.
+pub fn setup_tool_store(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    project_path: &Path,
+) -> vectarine_plugin_sdk::mlua::Result<LuaToolStore> {
+    lua.register_userdata_type::<LuaToolStore>(|registry| {
+        registry.add_method("get", |lua, store, key: String| {
+            match store.0.borrow().values.get(&key) {
+                Some(value) => lua.to_value(value),
+                None => Ok(vectarine_plugin_sdk::mlua::Nil),
+            }
+        });
+        registry.add_method(
+            "set",
+            |lua, store, (key, value): (String, vectarine_plugin_sdk::mlua::Value)| {
+                let mut store = store.0.borrow_mut();
+                if value.is_nil() {
+                    store.values.remove(&key);
+                } else {
+                    let json_value: serde_json::Value = lua.from_value(value)?;
+                    store.values.insert(key, json_value);
+                }
+                store.is_dirty = true;
+                Ok(())
+            },
+        );
+        registry.add_method("flush", |_, store, ()| {
+            store.0.borrow_mut().flush();
+            Ok(())
+        });
+    })?;
+    Ok(LuaToolStore(Rc::new(RefCell::new(ToolStore::new(
+        project_path,
+    )))))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
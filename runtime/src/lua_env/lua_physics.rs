@@ -1,6 +1,6 @@
 use std::{
-    cell::RefCell,
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
     rc::{Rc, Weak},
 };
 
@@ -9,19 +9,30 @@ use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataFields,
 use vectarine_plugin_sdk::rapier2d::{
     math::Vector,
     prelude::{
-        CCDSolver, Collider, ColliderBuilder, ColliderSet, DefaultBroadPhase, ImpulseJointHandle,
-        ImpulseJointSet, IntegrationParameters, IslandManager, MultibodyJointSet, NarrowPhase,
-        PhysicsPipeline, QueryFilter, RevoluteJointBuilder, RigidBody, RigidBodyBuilder,
-        RigidBodyHandle, RigidBodySet,
+        CCDSolver, Collider, ColliderBuilder, ColliderSet, DefaultBroadPhase, FixedJointBuilder,
+        GenericJoint, ImpulseJointHandle, ImpulseJointSet, IntegrationParameters,
+        InteractionGroups, IslandManager, JointAxesMask, JointAxis, MultibodyJointSet, NarrowPhase,
+        PhysicsPipeline, PrismaticJointBuilder, QueryFilter, RevoluteJointBuilder, RigidBody,
+        RigidBodyBuilder, RigidBodyHandle, RigidBodySet, RopeJointBuilder, SharedShape,
     },
 };
 
 use crate::{
-    auto_impl_lua_take,
-    game_resource::ResourceManager,
+    auto_impl_lua_clone, auto_impl_lua_take,
+    game_resource::{
+        ResourceManager,
+        tile_resource::{TilemapResource, merge_tiles_into_rectangles},
+    },
     lua_env::{
         add_fn_to_table, is_valid_data_type,
         lua_camera::Camera2,
+        lua_physics::{
+            attachments::{Attachment, Attachments},
+            inspector::PhysicsWorldRegistry,
+            layers::SharedCollisionLayers,
+            visibility::compute_visibility_polygon,
+        },
+        lua_resource::ResourceIdWrapper,
         lua_tile::{
             TilemapResourceId,
             tilemap::{GeneratedTilemap, Tilemap},
@@ -30,6 +41,11 @@ use crate::{
     },
 };
 
+pub mod attachments;
+pub mod inspector;
+pub mod layers;
+pub mod visibility;
+
 // MARK: World2
 
 /// Lua wrapper around a rapier physics world
@@ -48,6 +64,20 @@ pub struct PhysicsWorld2 {
     camera: Option<vectarine_plugin_sdk::mlua::Value>,
 
     extras: HashMap<RigidBodyHandle, ExtraObjectData>,
+    /// Only the objects with a speed limit are stored here so that clamping does not need to iterate over every body.
+    max_linear_speeds: HashMap<RigidBodyHandle, f32>,
+    /// Only the joints that can break are stored here, with the force that breaks them.
+    break_forces: HashMap<ImpulseJointHandle, f32>,
+    attachments: Attachments,
+    /// The tilemap and the tile size of the objects created by `addTilemapColliders`, to rebuild their colliders.
+    tilemap_colliders: HashMap<RigidBodyHandle, (TilemapResourceId, Vec2)>,
+    /// The objects owned by a pool, and the ones that were removed with `removeObject` since their pool last looked.
+    pooled_objects: HashSet<RigidBodyHandle>,
+    removed_pooled_objects: Vec<RigidBodyHandle>,
+    collision_layers: Rc<RefCell<SharedCollisionLayers>>,
+    /// The version of the collision layers the groups of the objects were computed with.
+    collision_layers_version: u64,
+    deterministic_physics: Rc<Cell<bool>>,
 }
 
 pub fn ensure_camera_is_valid(
@@ -67,6 +97,8 @@ impl PhysicsWorld2 {
     fn new(
         camera: Option<vectarine_plugin_sdk::mlua::Value>,
         gravity: Vec2,
+        collision_layers: &Rc<RefCell<SharedCollisionLayers>>,
+        deterministic_physics: &Rc<Cell<bool>>,
     ) -> vectarine_plugin_sdk::mlua::Result<Self> {
         let camera = if let Some(camera) = camera {
             ensure_camera_is_valid(&camera)?;
@@ -89,14 +121,169 @@ impl PhysicsWorld2 {
             ccd_solver: CCDSolver::new(),
             camera,
             extras: HashMap::new(),
+            max_linear_speeds: HashMap::new(),
+            break_forces: HashMap::new(),
+            attachments: Attachments::default(),
+            tilemap_colliders: HashMap::new(),
+            pooled_objects: HashSet::new(),
+            removed_pooled_objects: Vec::new(),
+            collision_layers: collision_layers.clone(),
+            collision_layers_version: collision_layers.borrow().version(),
+            deterministic_physics: deterministic_physics.clone(),
         })
     }
+
+    /// With `deterministic_physics`, the objects returned to Lua are sorted by their slot in the world, which only
+    /// depends on the order in which the objects were created and removed. Otherwise, they are in the order of a hash
+    /// map, which changes between runs.
+    fn sort_objects(&self, objects: &mut [Object2]) {
+        if self.deterministic_physics.get() {
+            objects.sort_by_key(|object| object.rigid_body_handle.into_raw_parts());
+        }
+    }
+
+    /// The groups of the objects of a layer. Objects without a layer, or with a layer that was removed, collide with
+    /// everything.
+    fn layer_groups(&self, layer: Option<&str>) -> InteractionGroups {
+        layer
+            .and_then(|layer| self.collision_layers.borrow().groups(layer))
+            .unwrap_or(InteractionGroups::all())
+    }
+
+    /// Applies the collision layers changed since the last step, in the editor, to the objects created with a layer.
+    fn update_layer_groups(&mut self) {
+        let collision_layers = self.collision_layers.borrow();
+        if collision_layers.version() == self.collision_layers_version {
+            return;
+        }
+        self.collision_layers_version = collision_layers.version();
+        for (handle, extra) in &mut self.extras {
+            let Some(layer) = &mut extra.layer else {
+                continue;
+            };
+            // The objects of a renamed layer take its new name.
+            let current_name = collision_layers.current_name(layer).to_string();
+            if current_name != *layer {
+                *layer = current_name;
+            }
+            let groups = collision_layers
+                .get()
+                .groups(layer)
+                .unwrap_or(InteractionGroups::all());
+            let Some(rigid_body) = self.rigid_body_set.get(*handle) else {
+                continue;
+            };
+            for collider_handle in rigid_body.colliders() {
+                if let Some(collider) = self.collider_set.get_mut(*collider_handle) {
+                    collider.set_collision_groups(groups);
+                }
+            }
+        }
+    }
+
+    fn clamp_linear_speeds(&mut self) {
+        for (handle, max_speed) in &self.max_linear_speeds {
+            let Some(rigid_body) = self.rigid_body_set.get_mut(*handle) else {
+                continue;
+            };
+            let linvel = *rigid_body.linvel();
+            let speed = linvel.norm();
+            if speed > *max_speed {
+                rigid_body.set_linvel(linvel * (*max_speed / speed), false);
+            }
+        }
+    }
+
+    /// Removes the joints that had to pull harder than their break force during the last step of `dt` seconds.
+    fn break_overloaded_joints(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        let impulse_joint_set = &self.impulse_joint_set;
+        let mut broken = self
+            .break_forces
+            .iter()
+            .filter(
+                |(handle, break_force)| match impulse_joint_set.get(**handle) {
+                    Some(joint) => joint.impulses.x.hypot(joint.impulses.y) / dt > **break_force,
+                    // Joints removed with their objects are forgotten.
+                    None => true,
+                },
+            )
+            .map(|(handle, _)| *handle)
+            .collect::<Vec<_>>();
+        // The removal order changes the slots reused by the next joints, so it does not depend on the hash map.
+        broken.sort_by_key(|handle| handle.into_raw_parts());
+        for handle in broken {
+            self.break_forces.remove(&handle);
+            self.impulse_joint_set.remove(handle, true);
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct LuaPhysicsWorld2(Rc<RefCell<PhysicsWorld2>>);
 auto_impl_lua_take!(LuaPhysicsWorld2, LuaPhysicsWorld2);
 
+/// The motion of a body, saved in the snapshots of the game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BodyState {
+    /// The slot and the generation of the body in the world.
+    pub handle: (u32, u32),
+    pub position: Vec2,
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub angular_velocity: f32,
+}
+
+impl LuaPhysicsWorld2 {
+    /// The bodies are sorted by their slot, so that snapshots of the same state are the same.
+    pub fn body_states(&self) -> Vec<BodyState> {
+        let world = self.0.borrow();
+        let mut states = world
+            .rigid_body_set
+            .iter()
+            .map(|(handle, rigid_body)| {
+                let position = rigid_body.translation();
+                let velocity = rigid_body.linvel();
+                BodyState {
+                    handle: handle.into_raw_parts(),
+                    position: Vec2::new(position.x, position.y),
+                    rotation: rigid_body.rotation().angle(),
+                    velocity: Vec2::new(velocity.x, velocity.y),
+                    angular_velocity: rigid_body.angvel(),
+                }
+            })
+            .collect::<Vec<_>>();
+        states.sort_by_key(|state| state.handle);
+        states
+    }
+
+    /// Puts the bodies that still exist back in their saved state. The bodies created after the snapshot stay where
+    /// they are.
+    pub fn restore_body_states(&self, states: &[BodyState]) {
+        let mut world = self.0.borrow_mut();
+        for state in states {
+            let handle = RigidBodyHandle::from_raw_parts(state.handle.0, state.handle.1);
+            let Some(rigid_body) = world.rigid_body_set.get_mut(handle) else {
+                continue;
+            };
+            rigid_body.set_position(
+                Isometry2::new(
+                    nalgebra::vector![state.position.x(), state.position.y()],
+                    state.rotation,
+                ),
+                true,
+            );
+            rigid_body.set_linvel(
+                nalgebra::vector![state.velocity.x(), state.velocity.y()],
+                true,
+            );
+            rigid_body.set_angvel(state.angular_velocity, true);
+        }
+    }
+}
+
 // MARK: Collider2
 
 struct Collider2 {
@@ -104,6 +291,16 @@ struct Collider2 {
 }
 auto_impl_lua_take!(Collider2, Collider2);
 
+// MARK: CollisionLayer2
+
+/// A layer declared in the project, returned by `Physics.layer`. Objects remember the name of their layer, so that
+/// their groups follow the changes of the layers.
+#[derive(Clone)]
+struct CollisionLayer2 {
+    name: String,
+}
+auto_impl_lua_clone!(CollisionLayer2, CollisionLayer2);
+
 // MARK: Joint2
 
 struct Joint2 {
@@ -112,6 +309,157 @@ struct Joint2 {
 }
 auto_impl_lua_take!(Joint2, Joint2);
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JointKind {
+    Revolute,
+    Prismatic,
+    Fixed,
+    Rope,
+    Other,
+}
+
+impl JointKind {
+    fn of(joint: &GenericJoint) -> Self {
+        if joint.as_revolute().is_some() {
+            JointKind::Revolute
+        } else if joint.as_prismatic().is_some() {
+            JointKind::Prismatic
+        } else if joint.as_fixed().is_some() {
+            JointKind::Fixed
+        } else if joint.as_rope().is_some() {
+            JointKind::Rope
+        } else {
+            JointKind::Other
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            JointKind::Revolute => "revolute",
+            JointKind::Prismatic => "prismatic",
+            JointKind::Fixed => "fixed",
+            JointKind::Rope => "rope",
+            JointKind::Other => "other",
+        }
+    }
+
+    /// The axis along which the joint moves, which is the one its limits and its motor apply to.
+    fn free_axis(self) -> vectarine_plugin_sdk::mlua::Result<(JointAxis, JointAxesMask)> {
+        match self {
+            JointKind::Revolute => Ok((JointAxis::AngX, JointAxesMask::ANG_X)),
+            JointKind::Prismatic => Ok((JointAxis::LinX, JointAxesMask::LIN_X)),
+            other => Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "Only revolute and prismatic joints have limits and motors, this joint is {}",
+                other.name()
+            ))),
+        }
+    }
+}
+
+/// The options shared by the `newXJoint` functions.
+struct JointOptions {
+    anchor1: Vec2,
+    anchor2: Vec2,
+    limits: Option<[f32; 2]>,
+    break_force: Option<f32>,
+}
+
+fn parse_joint_options(
+    options: Option<vectarine_plugin_sdk::mlua::Table>,
+) -> vectarine_plugin_sdk::mlua::Result<JointOptions> {
+    let Some(options) = options else {
+        return Ok(JointOptions {
+            anchor1: Vec2::zero(),
+            anchor2: Vec2::zero(),
+            limits: None,
+            break_force: None,
+        });
+    };
+    let limits = match options.get::<Option<vectarine_plugin_sdk::mlua::Table>>("limits")? {
+        Some(limits) => Some([limits.get::<f32>(1)?, limits.get::<f32>(2)?]),
+        None => None,
+    };
+    let break_force = options.get::<Option<f32>>("breakForce")?;
+    check_break_force(break_force)?;
+    Ok(JointOptions {
+        anchor1: options.get::<Option<Vec2>>("anchor1")?.unwrap_or_default(),
+        anchor2: options.get::<Option<Vec2>>("anchor2")?.unwrap_or_default(),
+        limits,
+        break_force,
+    })
+}
+
+fn check_break_force(break_force: Option<f32>) -> vectarine_plugin_sdk::mlua::Result<()> {
+    match break_force {
+        Some(force) if force.is_nan() || force <= 0.0 => {
+            Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "The break force is {force}, it should be more than 0"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Adds the joint between two objects of the same world. The objects are borrowed so that they stay usable in Lua.
+fn insert_joint(
+    object1: &AnyUserData,
+    object2: &AnyUserData,
+    joint: GenericJoint,
+    break_force: Option<f32>,
+) -> vectarine_plugin_sdk::mlua::Result<Joint2> {
+    let object1 = object1.borrow::<Object2>()?;
+    let object2 = object2.borrow::<Object2>()?;
+    if !Weak::ptr_eq(&object1.world, &object2.world) {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "The objects of a joint should be in the same world".to_string(),
+        ));
+    }
+    let Some(world) = object1.world.upgrade() else {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Object is invalid".to_string(),
+        ));
+    };
+    let mut world = world.borrow_mut();
+    let removed = |object: &Object2| world.rigid_body_set.get(object.rigid_body_handle).is_none();
+    if removed(&object1) || removed(&object2) {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Object is invalid".to_string(),
+        ));
+    }
+    let handle = world.impulse_joint_set.insert(
+        object1.rigid_body_handle,
+        object2.rigid_body_handle,
+        joint,
+        true,
+    );
+    if let Some(break_force) = break_force {
+        world.break_forces.insert(handle, break_force);
+    }
+    Ok(Joint2 {
+        joint: handle,
+        world: object1.world.clone(),
+    })
+}
+
+/// Runs `f` with the joint, waking up its objects so that the change is applied at the next step.
+fn with_joint_mut<R>(
+    joint: &Joint2,
+    f: impl FnOnce(&mut PhysicsWorld2, ImpulseJointHandle) -> vectarine_plugin_sdk::mlua::Result<R>,
+) -> vectarine_plugin_sdk::mlua::Result<R> {
+    let Some(world) = joint.world.upgrade() else {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Joint is invalid".to_string(),
+        ));
+    };
+    let mut world = world.borrow_mut();
+    if world.impulse_joint_set.get_mut(joint.joint, true).is_none() {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Joint is invalid".to_string(),
+        ));
+    }
+    f(&mut world, joint.joint)
+}
+
 // MARK: Object2
 
 pub struct Object2 {
@@ -131,6 +479,13 @@ impl Object2 {
         let position = rigid_body.position();
         Some(Vec2::new(position.translation.x, position.translation.y))
     }
+    pub fn rotation(&self) -> Option<f32> {
+        let world = self.world.upgrade()?;
+        let world = world.borrow();
+        let world = &*world;
+        let rigid_body = world.rigid_body_set.get(self.rigid_body_handle)?;
+        Some(rigid_body.rotation().angle())
+    }
     pub fn velocity(&self) -> Option<Vec2> {
         let world = self.world.upgrade()?;
         let world = world.borrow();
@@ -160,25 +515,268 @@ impl Object2 {
 struct ExtraObjectData {
     tags: vectarine_plugin_sdk::mlua::Table,
     extra_custom: vectarine_plugin_sdk::mlua::Value,
+    layer: Option<String>,
+}
+
+impl ExtraObjectData {
+    fn has_all_tags(&self, queried_tags: &[vectarine_plugin_sdk::mlua::Value]) -> bool {
+        queried_tags.iter().all(|queried_tag| {
+            self.tags
+                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
+                .filter_map(|o| o.ok())
+                .any(|(_, object_tag)| object_tag == *queried_tag)
+        })
+    }
 }
 
 auto_impl_lua_take!(Object2, Object2);
 
+fn body_builder_from_type(body_type: &str) -> vectarine_plugin_sdk::mlua::Result<RigidBodyBuilder> {
+    match body_type {
+        "dynamic" => Ok(RigidBodyBuilder::dynamic()),
+        "static" => Ok(RigidBodyBuilder::fixed()),
+        "kinematic" => Ok(RigidBodyBuilder::kinematic_velocity_based()),
+        _ => Err(vectarine_plugin_sdk::mlua::Error::FromLuaConversionError {
+            from: "string",
+            to: "RigidBodyType".to_string(),
+            message: Some(
+                "Invalid body type, expected 'dynamic', 'static' or 'kinematic'".to_string(),
+            ),
+        }),
+    }
+}
+
+// MARK: ObjectPool2
+
+const DEFAULT_POOL_SIZE: usize = 100;
+/// Every object of a pool is created upfront, so a huge size would exhaust the memory instead of raising an error.
+const MAX_POOL_SIZE: usize = 100_000;
+
+enum PoolOverflow {
+    Grow,
+    RecycleOldest,
+}
+
+struct PoolSlot {
+    handle: RigidBodyHandle,
+    /// The same userdata is returned every time the slot is spawned to avoid allocations.
+    object: AnyUserData,
+    /// Increases with every spawn. None when the slot is not in use.
+    spawn_index: Option<u64>,
+}
+
+/// A set of objects created in advance and disabled, that can be activated and deactivated cheaply.
+struct ObjectPool2 {
+    world: Weak<RefCell<PhysicsWorld2>>,
+    collider: Collider,
+    tags: vectarine_plugin_sdk::mlua::Table,
+    layer: Option<String>,
+    body_type: String,
+    mass: f32,
+    ccd_enabled: bool,
+    when_full: PoolOverflow,
+
+    slots: Vec<PoolSlot>,
+    free_slots: Vec<usize>,
+    slot_by_handle: HashMap<RigidBodyHandle, usize>,
+    /// Spawned slots, oldest first. Despawned slots are removed lazily.
+    spawn_order: VecDeque<(usize, u64)>,
+    spawn_counter: u64,
+}
+auto_impl_lua_take!(ObjectPool2, ObjectPool2);
+
+impl ObjectPool2 {
+    fn world(&self) -> vectarine_plugin_sdk::mlua::Result<Rc<RefCell<PhysicsWorld2>>> {
+        self.world.upgrade().ok_or_else(|| {
+            vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                "ObjectPool2 is out of this world".to_string(),
+            )
+        })
+    }
+
+    /// Adds a disabled object to the pool and returns the index of its slot. The slot is not marked as free.
+    fn add_slot(
+        &mut self,
+        lua: &vectarine_plugin_sdk::mlua::Lua,
+        world_rc: &Rc<RefCell<PhysicsWorld2>>,
+    ) -> vectarine_plugin_sdk::mlua::Result<usize> {
+        let (handle, object) = self.create_object(lua, world_rc)?;
+        let index = self.slots.len();
+        self.slots.push(PoolSlot {
+            handle,
+            object,
+            spawn_index: None,
+        });
+        self.slot_by_handle.insert(handle, index);
+        Ok(index)
+    }
+
+    /// Creates the disabled object of a slot.
+    fn create_object(
+        &self,
+        lua: &vectarine_plugin_sdk::mlua::Lua,
+        world_rc: &Rc<RefCell<PhysicsWorld2>>,
+    ) -> vectarine_plugin_sdk::mlua::Result<(RigidBodyHandle, AnyUserData)> {
+        let mut world = world_rc.borrow_mut();
+        let world = &mut *world;
+        let body = body_builder_from_type(&self.body_type)?
+            .additional_mass(self.mass)
+            .ccd_enabled(self.ccd_enabled)
+            .enabled(false)
+            .build();
+        let handle = world.rigid_body_set.insert(body);
+        let mut collider = self.collider.clone();
+        collider.set_collision_groups(world.layer_groups(self.layer.as_deref()));
+        world
+            .collider_set
+            .insert_with_parent(collider, handle, &mut world.rigid_body_set);
+        world.extras.insert(
+            handle,
+            ExtraObjectData {
+                tags: self.tags.clone(),
+                extra_custom: vectarine_plugin_sdk::mlua::Nil,
+                layer: self.layer.clone(),
+            },
+        );
+        world.pooled_objects.insert(handle);
+        let object = lua.create_any_userdata(Object2 {
+            rigid_body_handle: handle,
+            world: Rc::downgrade(world_rc),
+        })?;
+        Ok((handle, object))
+    }
+
+    /// Gives a new object to the slots whose object was removed from the world with `removeObject`, and frees them.
+    fn replace_removed_objects(
+        &mut self,
+        lua: &vectarine_plugin_sdk::mlua::Lua,
+        world_rc: &Rc<RefCell<PhysicsWorld2>>,
+    ) -> vectarine_plugin_sdk::mlua::Result<()> {
+        let mut removed_slots = Vec::new();
+        world_rc
+            .borrow_mut()
+            .removed_pooled_objects
+            .retain(|handle| match self.slot_by_handle.remove(handle) {
+                Some(index) => {
+                    removed_slots.push(index);
+                    false
+                }
+                // The object belongs to another pool of the world.
+                None => true,
+            });
+        for index in removed_slots {
+            let (handle, object) = self.create_object(lua, world_rc)?;
+            let slot = &mut self.slots[index];
+            slot.handle = handle;
+            slot.object = object;
+            self.slot_by_handle.insert(handle, index);
+            // Slots that were spawned are removed lazily from spawn_order because their spawn index no longer matches.
+            if slot.spawn_index.take().is_some() {
+                self.free_slots.push(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn deactivate(&mut self, world: &mut PhysicsWorld2, index: usize) {
+        let Some(slot) = self.slots.get_mut(index) else {
+            return;
+        };
+        slot.spawn_index = None;
+        if let Some(rigid_body) = world.rigid_body_set.get_mut(slot.handle) {
+            rigid_body.set_linvel(nalgebra::vector![0.0, 0.0], false);
+            rigid_body.set_angvel(0.0, false);
+            rigid_body.set_enabled(false);
+        }
+    }
+
+    /// Deactivates the object that was spawned the earliest and returns its slot.
+    fn recycle_oldest(&mut self, world: &mut PhysicsWorld2) -> Option<usize> {
+        while let Some((index, spawn_index)) = self.spawn_order.pop_front() {
+            let is_still_active = self
+                .slots
+                .get(index)
+                .is_some_and(|slot| slot.spawn_index == Some(spawn_index));
+            if is_still_active {
+                self.deactivate(world, index);
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn take_slot(
+        &mut self,
+        lua: &vectarine_plugin_sdk::mlua::Lua,
+        world_rc: &Rc<RefCell<PhysicsWorld2>>,
+    ) -> vectarine_plugin_sdk::mlua::Result<usize> {
+        if let Some(index) = self.free_slots.pop() {
+            return Ok(index);
+        }
+        match self.when_full {
+            PoolOverflow::Grow => self.add_slot(lua, world_rc),
+            PoolOverflow::RecycleOldest => {
+                let mut world = world_rc.borrow_mut();
+                self.recycle_oldest(&mut world).ok_or_else(|| {
+                    vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                        "Cannot spawn from an empty pool".to_string(),
+                    )
+                })
+            }
+        }
+    }
+
+    fn active_count(&self) -> usize {
+        self.slots.len() - self.free_slots.len()
+    }
+}
+
 pub fn setup_physics_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     resources: &Rc<ResourceManager>,
+    collision_layers: &Rc<RefCell<SharedCollisionLayers>>,
+    deterministic_physics: &Rc<Cell<bool>>,
+    fixed_tick_duration: &Rc<Cell<Option<f32>>>,
+    physics_worlds: &Rc<RefCell<PhysicsWorldRegistry>>,
 ) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
     let physics_module = lua.create_table()?;
 
     // MARK: World2 fn
     add_fn_to_table(lua, &physics_module, "newWorld2", {
+        let collision_layers = collision_layers.clone();
+        let deterministic_physics = deterministic_physics.clone();
+        let physics_worlds = physics_worlds.clone();
         move |_, (gravity, camera): (Option<Vec2>, vectarine_plugin_sdk::mlua::Value)| {
             let camera = if camera.is_nil() { None } else { Some(camera) };
-            let world = PhysicsWorld2::new(camera, gravity.unwrap_or(Vec2::new(0.0, 0.0)))?;
-            Ok(LuaPhysicsWorld2(Rc::new(RefCell::new(world))))
+            let world = PhysicsWorld2::new(
+                camera,
+                gravity.unwrap_or(Vec2::new(0.0, 0.0)),
+                &collision_layers,
+                &deterministic_physics,
+            )?;
+            let world = Rc::new(RefCell::new(world));
+            physics_worlds.borrow_mut().register(&world);
+            Ok(LuaPhysicsWorld2(world))
+        }
+    });
+
+    // MARK: CollisionLayer2 fn
+    add_fn_to_table(lua, &physics_module, "layer", {
+        let collision_layers = collision_layers.clone();
+        move |_, name: String| {
+            if collision_layers.borrow().groups(&name).is_none() {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "Unknown collision layer '{name}', declare it in [physics.layers] of game.vecta"
+                )));
+            }
+            Ok(CollisionLayer2 { name })
         }
     });
 
+    lua.register_userdata_type::<CollisionLayer2>(|registry| {
+        registry.add_field_method_get("name", |_, layer| Ok(layer.name.clone()));
+    })?;
+
     lua.register_userdata_type::<LuaPhysicsWorld2>(|registry| {
         registry.add_field_method_get("camera", |_, world| {
             let cam = world.0.borrow().camera.clone();
@@ -202,7 +800,14 @@ pub fn setup_physics_api(
             Ok(())
         });
 
-        registry.add_method_mut("step", |_, world, dt: f32| {
+        let fixed_tick_duration = fixed_tick_duration.clone();
+        registry.add_method_mut("step", move |_, world, dt: Option<f32>| {
+            // Without a delta time, the world steps by one fixed tick, so that the physics stays on the tick boundaries.
+            let Some(dt) = dt.or(fixed_tick_duration.get()) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "World2:step needs a delta time when fixed_tick_rate is not set".to_string(),
+                ));
+            };
             let mut world = world.0.borrow_mut();
             let world = &mut *world;
             let physics_hooks = ();
@@ -210,6 +815,7 @@ pub fn setup_physics_api(
 
             let rapier_gravity = vectarine_plugin_sdk::rapier2d::prelude::vector![world.gravity.x(), world.gravity.y()];
             world.integration_parameters.dt = dt;
+            world.update_layer_groups();
 
             world.physics_pipeline.step(
                 &rapier_gravity,
@@ -225,44 +831,49 @@ pub fn setup_physics_api(
                 &physics_hooks,
                 &event_handler,
             );
+            world.clamp_linear_speeds();
+            world.break_overloaded_joints(dt);
+            world.attachments.apply(&mut world.rigid_body_set);
             Ok(())
         });
 
         registry.add_method_mut("createObject", {
             move |_,
                   lua_world,
-                  (position, mass, maybe_collider, tags, body_type): (
+                  (position, mass, maybe_collider, tags, body_type, options): (
                 Vec2,
                 f32,
                 vectarine_plugin_sdk::mlua::AnyUserData,
                 vectarine_plugin_sdk::mlua::Table,
                 String,
+                Option<vectarine_plugin_sdk::mlua::Table>,
             )| {
                 let collider = maybe_collider.borrow::<Collider2>()?;
                 let mut world = lua_world.0.borrow_mut();
                 let world = &mut *world;
 
-                let body_builder = match body_type.as_str() {
-                    "dynamic" => RigidBodyBuilder::dynamic(),
-                    "static" => RigidBodyBuilder::fixed(),
-                    "kinematic" => RigidBodyBuilder::kinematic_velocity_based(),
-                    _ => {
-                        return Err(vectarine_plugin_sdk::mlua::Error::FromLuaConversionError {
-                            from: "string",
-                            to: "RigidBodyType".to_string(),
-                            message: Some(
-                                "Invalid body type, expected 'dynamic', 'static' or 'kinematic'"
-                                    .to_string(),
-                            ),
-                        });
-                    }
+                let body_builder = body_builder_from_type(&body_type)?;
+                let (ccd_enabled, max_linear_speed, layer) = match &options {
+                    Some(options) => (
+                        options.get::<Option<bool>>("ccd")?.unwrap_or(false),
+                        options
+                            .get::<Option<f32>>("maxLinearSpeed")?
+                            .map(check_max_linear_speed)
+                            .transpose()?,
+                        options
+                            .get::<Option<CollisionLayer2>>("layer")?
+                            .map(|layer| layer.name),
+                    ),
+                    None => (false, None, None),
                 };
                 let body = body_builder
                     .pose(Isometry2::translation(position.x(), position.y()))
                     .additional_mass(mass)
+                    .ccd_enabled(ccd_enabled)
                     .build();
                 let body_handle = world.rigid_body_set.insert(body);
-                let collider = collider.collider.clone();
+                let mut collider = collider.collider.clone();
+                collider.set_collision_groups(world.layer_groups(layer.as_deref()));
                 world.collider_set.insert_with_parent(
                     collider,
                     body_handle,
@@ -278,17 +889,85 @@ pub fn setup_physics_api(
                     ExtraObjectData {
                         tags,
                         extra_custom: vectarine_plugin_sdk::mlua::Nil,
+                        layer,
                     },
                 );
+                if let Some(max_linear_speed) = max_linear_speed {
+                    world.max_linear_speeds.insert(body_handle, max_linear_speed);
+                }
                 Ok(object)
             }
         });
 
+        registry.add_method_mut("addTilemapColliders", {
+            let resources = resources.clone();
+            move |lua,
+                  lua_world,
+                  (tilemap, tile_size, options): (
+                TilemapResourceId,
+                Option<Vec2>,
+                Option<vectarine_plugin_sdk::mlua::Table>,
+            )| {
+                let tilemap_res = get_tilemap_resource(&resources, tilemap)?;
+                let tile_size = tile_size.unwrap_or_else(|| tilemap_res.tile_size());
+                let mut collider = tilemap_collider(&tilemap_res, tile_size)?;
+                let (position, tags, layer) = match &options {
+                    Some(options) => (
+                        options.get::<Option<Vec2>>("position")?,
+                        options.get::<Option<vectarine_plugin_sdk::mlua::Table>>("tags")?,
+                        options
+                            .get::<Option<CollisionLayer2>>("layer")?
+                            .map(|layer| layer.name),
+                    ),
+                    None => (None, None, None),
+                };
+                let position = position.unwrap_or(Vec2::zero());
+                let tags = match tags {
+                    Some(tags) => tags,
+                    None => lua.create_table()?,
+                };
+
+                let mut world = lua_world.0.borrow_mut();
+                let world = &mut *world;
+                let body = RigidBodyBuilder::fixed()
+                    .pose(Isometry2::translation(position.x(), position.y()))
+                    .build();
+                let body_handle = world.rigid_body_set.insert(body);
+                collider.set_collision_groups(world.layer_groups(layer.as_deref()));
+                world.collider_set.insert_with_parent(
+                    collider,
+                    body_handle,
+                    &mut world.rigid_body_set,
+                );
+                world.extras.insert(
+                    body_handle,
+                    ExtraObjectData {
+                        tags,
+                        extra_custom: vectarine_plugin_sdk::mlua::Nil,
+                        layer,
+                    },
+                );
+                world
+                    .tilemap_colliders
+                    .insert(body_handle, (tilemap, tile_size));
+                Ok(Object2 {
+                    rigid_body_handle: body_handle,
+                    world: Rc::downgrade(&lua_world.0),
+                })
+            }
+        });
+
         // We pass object directly here because we WANT to take ownership (the object is invalid afterwards)
         registry.add_method_mut("removeObject", |_, world, object: Object2| {
             let mut world = world.0.borrow_mut();
             let world = &mut *world;
             world.extras.remove(&object.rigid_body_handle);
+            world.max_linear_speeds.remove(&object.rigid_body_handle);
+            world.attachments.remove_object(object.rigid_body_handle);
+            world.tilemap_colliders.remove(&object.rigid_body_handle);
+            if world.pooled_objects.remove(&object.rigid_body_handle) {
+                world.removed_pooled_objects.push(object.rigid_body_handle);
+            }
             world.rigid_body_set.remove(
                 object.rigid_body_handle,
                 &mut world.island_manager,
@@ -306,23 +985,23 @@ pub fn setup_physics_api(
                 let tags = tags.unwrap_or_default();
                 let mut world = lua_world.0.borrow_mut();
                 let world = &mut *world;
-                let objects = world
+                let mut objects = world
                     .extras
                     .iter()
-                    .filter(|(_, extra)| {
-                        tags.iter().all(|queried_tag| {
-                            extra
-                                .tags
-                                .pairs::<vectarine_plugin_sdk::mlua::Value, vectarine_plugin_sdk::mlua::Value>()
-                                .filter_map(|o| o.ok())
-                                .any(|(_, object_tag)| object_tag == *queried_tag)
-                        })
+                    .filter(|(handle, _)| {
+                        // Pooled objects that are not spawned are disabled and should not be visible.
+                        world
+                            .rigid_body_set
+                            .get(**handle)
+                            .is_some_and(|rigid_body| rigid_body.is_enabled())
                     })
+                    .filter(|(_, extra)| extra.has_all_tags(&tags))
                     .map(|(&handle, _)| Object2 {
                         rigid_body_handle: handle,
                         world: Rc::downgrade(&lua_world.0),
                     })
                     .collect::<Vec<_>>();
+                world.sort_objects(&mut objects);
                 Ok(objects)
             },
         );
@@ -340,13 +1019,15 @@ pub fn setup_physics_api(
                 );
                 let matches =
                     query_pipeline.intersect_point(vectarine_plugin_sdk::rapier2d::prelude::point![point.x(), point.y()]);
-                Ok(matches
+                let mut objects = matches
                     .filter_map(|m| m.1.parent())
                     .map(|parent| Object2 {
                         rigid_body_handle: parent,
                         world: Rc::downgrade(&lua_world.0),
                     })
-                    .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                world.sort_objects(&mut objects);
+                Ok(objects)
             }
         });
 
@@ -372,13 +1053,15 @@ pub fn setup_physics_api(
                 );
                 let matches = query_pipeline.intersect_shape(shape_pos, &shape);
 
-                Ok(matches
+                let mut objects = matches
                     .filter_map(|m| m.1.parent())
                     .map(|parent| Object2 {
                         rigid_body_handle: parent,
                         world: Rc::downgrade(&lua_world.0),
                     })
-                    .collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                world.sort_objects(&mut objects);
+                Ok(objects)
             }
         });
 
@@ -416,6 +1099,32 @@ pub fn setup_physics_api(
             }
         });
 
+        registry.add_method(
+            "computeVisibility",
+            |_,
+             lua_world,
+             (origin, radius, tags): (
+                Vec2,
+                f32,
+                Option<Vec<vectarine_plugin_sdk::mlua::Value>>,
+            )| {
+                let tags = tags.unwrap_or_default();
+                let world = lua_world.0.borrow();
+                let occluders = world
+                    .extras
+                    .iter()
+                    .filter(|(_, extra)| extra.has_all_tags(&tags))
+                    .filter_map(|(handle, _)| world.rigid_body_set.get(*handle))
+                    .filter(|rigid_body| rigid_body.is_enabled())
+                    .flat_map(|rigid_body| rigid_body.colliders())
+                    .filter_map(|collider| world.collider_set.get(*collider))
+                    .filter(|collider| !collider.is_sensor())
+                    .flat_map(get_edges_of_collider)
+                    .collect::<Vec<_>>();
+                Ok(compute_visibility_polygon(origin, radius, &occluders))
+            },
+        );
+
         registry.add_method_mut("getJoints", {
             move |_, lua_world, (): ()| {
                 let world = lua_world.0.borrow();
@@ -432,6 +1141,64 @@ pub fn setup_physics_api(
             }
         });
 
+        registry.add_method_mut(
+            "createPool",
+            |lua, lua_world, options: vectarine_plugin_sdk::mlua::Table| {
+                let collider = options.get::<AnyUserData>("collider")?;
+                let collider = collider.borrow::<Collider2>()?.collider.clone();
+                let tags = match options.get::<Option<vectarine_plugin_sdk::mlua::Table>>("tags")? {
+                    Some(tags) => tags,
+                    None => lua.create_table()?,
+                };
+                let body_type = options
+                    .get::<Option<String>>("bodyType")?
+                    .unwrap_or_else(|| "dynamic".to_string());
+                body_builder_from_type(&body_type)?;
+                let when_full = match options.get::<Option<String>>("whenFull")?.as_deref() {
+                    None | Some("grow") => PoolOverflow::Grow,
+                    Some("recycle") => PoolOverflow::RecycleOldest,
+                    Some(other) => {
+                        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                            "Invalid whenFull option '{other}', expected 'grow' or 'recycle'"
+                        )));
+                    }
+                };
+                let size = options
+                    .get::<Option<usize>>("size")?
+                    .unwrap_or(DEFAULT_POOL_SIZE);
+                if size > MAX_POOL_SIZE {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                        "Invalid pool size {size}, the maximum is {MAX_POOL_SIZE}"
+                    )));
+                }
+
+                let mut pool = ObjectPool2 {
+                    world: Rc::downgrade(&lua_world.0),
+                    collider,
+                    tags,
+                    layer: options
+                        .get::<Option<CollisionLayer2>>("layer")?
+                        .map(|layer| layer.name),
+                    body_type,
+                    mass: options.get::<Option<f32>>("mass")?.unwrap_or(1.0),
+                    ccd_enabled: options.get::<Option<bool>>("ccd")?.unwrap_or(false),
+                    when_full,
+                    slots: Vec::with_capacity(size),
+                    free_slots: Vec::with_capacity(size),
+                    slot_by_handle: HashMap::with_capacity(size),
+                    spawn_order: VecDeque::with_capacity(size),
+                    spawn_counter: 0,
+                };
+                for _ in 0..size {
+                    let index = pool.add_slot(lua, &lua_world.0)?;
+                    pool.free_slots.push(index);
+                }
+                // Free slots are popped from the end, so we reverse them to spawn the first slots first.
+                pool.free_slots.reverse();
+                Ok(pool)
+            },
+        );
+
         // MARK: Joint2 fn
         registry.add_method_mut("createDistanceJoint", {
             move |_, lua_world, (object1, object2): (Object2, Object2)| {
@@ -502,8 +1269,179 @@ pub fn setup_physics_api(
                 world: joint.world.clone(),
             })
         });
+        registry.add_method("isValid", |_, joint, (): ()| {
+            Ok(joint.world.upgrade().is_some_and(|world| {
+                world.borrow().impulse_joint_set.get(joint.joint).is_some()
+            }))
+        });
+        registry.add_method("getType", |_, joint, (): ()| {
+            with_joint_mut(joint, |world, handle| {
+                let data = &world.impulse_joint_set[handle].data;
+                Ok(JointKind::of(data).name())
+            })
+        });
+        registry.add_method(
+            "setLimits",
+            |_, joint, (min, max): (Option<f32>, Option<f32>)| {
+                with_joint_mut(joint, |world, handle| {
+                    let data = &mut world.impulse_joint_set[handle].data;
+                    let (axis, mask) = JointKind::of(data).free_axis()?;
+                    match (min, max) {
+                        (Some(min), Some(max)) if min <= max => {
+                            data.set_limits(axis, [min, max]);
+                        }
+                        (None, None) => data.limit_axes.remove(mask),
+                        _ => {
+                            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                                "The limits should be a minimum and a maximum above it, or nil to remove them"
+                                    .to_string(),
+                            ));
+                        }
+                    }
+                    Ok(())
+                })
+            },
+        );
+        registry.add_method(
+            "setMotorVelocity",
+            |_, joint, (velocity, factor): (f32, Option<f32>)| {
+                with_joint_mut(joint, |world, handle| {
+                    let data = &mut world.impulse_joint_set[handle].data;
+                    let (axis, _) = JointKind::of(data).free_axis()?;
+                    data.set_motor_velocity(axis, velocity, factor.unwrap_or(1.0));
+                    Ok(())
+                })
+            },
+        );
+        registry.add_method(
+            "setMotorPosition",
+            |_, joint, (target, stiffness, damping): (f32, f32, f32)| {
+                with_joint_mut(joint, |world, handle| {
+                    let data = &mut world.impulse_joint_set[handle].data;
+                    let (axis, _) = JointKind::of(data).free_axis()?;
+                    data.set_motor_position(axis, target, stiffness, damping);
+                    Ok(())
+                })
+            },
+        );
+        registry.add_method("setMotorMaxForce", |_, joint, max_force: f32| {
+            with_joint_mut(joint, |world, handle| {
+                let data = &mut world.impulse_joint_set[handle].data;
+                let (axis, _) = JointKind::of(data).free_axis()?;
+                data.set_motor_max_force(axis, max_force);
+                Ok(())
+            })
+        });
+        registry.add_method("setBreakForce", |_, joint, break_force: Option<f32>| {
+            check_break_force(break_force)?;
+            with_joint_mut(joint, |world, handle| {
+                match break_force {
+                    Some(break_force) => world.break_forces.insert(handle, break_force),
+                    None => world.break_forces.remove(&handle),
+                };
+                Ok(())
+            })
+        });
     })?;
 
+    add_fn_to_table(lua, &physics_module, "newRevoluteJoint", {
+        move |_,
+              (object1, object2, options): (
+            AnyUserData,
+            AnyUserData,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            let options = parse_joint_options(options)?;
+            let mut joint = RevoluteJointBuilder::new()
+                .local_anchor1(nalgebra::point![options.anchor1.x(), options.anchor1.y()])
+                .local_anchor2(nalgebra::point![options.anchor2.x(), options.anchor2.y()]);
+            if let Some(limits) = options.limits {
+                joint = joint.limits(limits);
+            }
+            insert_joint(
+                &object1,
+                &object2,
+                joint.build().into(),
+                options.break_force,
+            )
+        }
+    });
+
+    add_fn_to_table(lua, &physics_module, "newPrismaticJoint", {
+        move |_,
+              (object1, object2, axis, options): (
+            AnyUserData,
+            AnyUserData,
+            Vec2,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            let options = parse_joint_options(options)?;
+            let Some(axis) = nalgebra::Unit::try_new(Vector::new(axis.x(), axis.y()), 1e-6) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "The axis of a prismatic joint should not be V2(0, 0)".to_string(),
+                ));
+            };
+            let mut joint = PrismaticJointBuilder::new(axis)
+                .local_anchor1(nalgebra::point![options.anchor1.x(), options.anchor1.y()])
+                .local_anchor2(nalgebra::point![options.anchor2.x(), options.anchor2.y()]);
+            if let Some(limits) = options.limits {
+                joint = joint.limits(limits);
+            }
+            insert_joint(
+                &object1,
+                &object2,
+                joint.build().into(),
+                options.break_force,
+            )
+        }
+    });
+
+    add_fn_to_table(lua, &physics_module, "newFixedJoint", {
+        move |_,
+              (object1, object2, options): (
+            AnyUserData,
+            AnyUserData,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            let options = parse_joint_options(options)?;
+            let joint = FixedJointBuilder::new()
+                .local_anchor1(nalgebra::point![options.anchor1.x(), options.anchor1.y()])
+                .local_anchor2(nalgebra::point![options.anchor2.x(), options.anchor2.y()]);
+            insert_joint(
+                &object1,
+                &object2,
+                joint.build().into(),
+                options.break_force,
+            )
+        }
+    });
+
+    add_fn_to_table(lua, &physics_module, "newRopeJoint", {
+        move |_,
+              (object1, object2, max_length, options): (
+            AnyUserData,
+            AnyUserData,
+            f32,
+            Option<vectarine_plugin_sdk::mlua::Table>,
+        )| {
+            if max_length.is_nan() || max_length < 0.0 {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                    "The length of a rope is {max_length}, it should be a positive number"
+                )));
+            }
+            let options = parse_joint_options(options)?;
+            let joint = RopeJointBuilder::new(max_length)
+                .local_anchor1(nalgebra::point![options.anchor1.x(), options.anchor1.y()])
+                .local_anchor2(nalgebra::point![options.anchor2.x(), options.anchor2.y()]);
+            insert_joint(
+                &object1,
+                &object2,
+                joint.build().into(),
+                options.break_force,
+            )
+        }
+    });
+
     // MARK: Collider2 fn
     add_fn_to_table(lua, &physics_module, "newRectangleCollider", {
         move |_, size: Vec2| {
@@ -519,6 +1457,33 @@ pub fn setup_physics_api(
         }
     });
 
+    add_fn_to_table(lua, &physics_module, "newCapsuleCollider", {
+        move |_, (half_height, radius): (f32, f32)| {
+            let collider = ColliderBuilder::capsule_y(half_height, radius).build();
+            Ok(Collider2 { collider })
+        }
+    });
+
+    add_fn_to_table(
+        lua,
+        &physics_module,
+        "newCompoundCollider",
+        |_, parts: Vec<vectarine_plugin_sdk::mlua::Table>| {
+            let shapes = parts
+                .iter()
+                .map(compound_part)
+                .collect::<vectarine_plugin_sdk::mlua::Result<Vec<_>>>()?;
+            if shapes.is_empty() {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "A compound collider needs at least one part".to_string(),
+                ));
+            }
+            Ok(Collider2 {
+                collider: ColliderBuilder::compound(shapes).build(),
+            })
+        },
+    );
+
     add_fn_to_table(lua, &physics_module, "newPolygonCollider", {
         move |_, points: Vec<Vec2>| {
             let mut converted_points = points // We could probably transmute here, but we won't.
@@ -616,6 +1581,63 @@ pub fn setup_physics_api(
         }
     });
 
+    // MARK: ObjectPool2 fn
+    lua.register_userdata_type::<ObjectPool2>(|registry| {
+        registry.add_method_mut(
+            "spawn",
+            |lua, pool, (position, velocity): (Vec2, Option<Vec2>)| {
+                let world_rc = pool.world()?;
+                pool.replace_removed_objects(lua, &world_rc)?;
+                let index = pool.take_slot(lua, &world_rc)?;
+
+                pool.spawn_counter += 1;
+                let spawn_index = pool.spawn_counter;
+                pool.spawn_order.push_back((index, spawn_index));
+                pool.slots[index].spawn_index = Some(spawn_index);
+                // Despawned slots stay in spawn_order until they are popped, so we clean it up from time to time.
+                if pool.spawn_order.len() > pool.slots.len() * 2 {
+                    let slots = &pool.slots;
+                    pool.spawn_order.retain(|(index, spawn_index)| {
+                        slots[*index].spawn_index == Some(*spawn_index)
+                    });
+                }
+
+                let slot = &pool.slots[index];
+                let mut world = world_rc.borrow_mut();
+                if let Some(rigid_body) = world.rigid_body_set.get_mut(slot.handle) {
+                    rigid_body.set_enabled(true);
+                    rigid_body
+                        .set_position(Isometry2::translation(position.x(), position.y()), true);
+                    let velocity = velocity.unwrap_or(Vec2::new(0.0, 0.0));
+                    rigid_body.set_linvel(nalgebra::vector![velocity.x(), velocity.y()], true);
+                    rigid_body.set_angvel(0.0, true);
+                }
+                Ok(slot.object.clone())
+            },
+        );
+        registry.add_method_mut("despawn", |_, pool, object: AnyUserData| {
+            let handle = object.borrow::<Object2>()?.rigid_body_handle;
+            let Some(&index) = pool.slot_by_handle.get(&handle) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "Object2 does not belong to this pool".to_string(),
+                ));
+            };
+            if pool.slots[index].spawn_index.is_none() {
+                return Ok(());
+            }
+            let world_rc = pool.world()?;
+            pool.deactivate(&mut world_rc.borrow_mut(), index);
+            pool.free_slots.push(index);
+            Ok(())
+        });
+        registry.add_method_mut("activeCount", |lua, pool, (): ()| {
+            let world_rc = pool.world()?;
+            pool.replace_removed_objects(lua, &world_rc)?;
+            Ok(pool.active_count())
+        });
+        registry.add_method("capacity", |_, pool, (): ()| Ok(pool.slots.len()));
+    })?;
+
     // MARK: Object2 fn
     lua.register_userdata_type::<Object2>(|registry| {
         registry.add_field_method_get("position", |_, object| {
@@ -679,6 +1701,36 @@ pub fn setup_physics_api(
         registry.add_field_method_get("angularDamping", |_, object| {
             access_rigid_body_mut(object, |_, rigid_body| rigid_body.angular_damping())
         });
+        registry.add_field_method_get("ccdEnabled", |_, object| {
+            access_rigid_body(object, |_, rigid_body| rigid_body.is_ccd_enabled())
+        });
+        registry.add_field_method_set("ccdEnabled", |_, object, enabled: bool| {
+            access_rigid_body_mut(object, |_, rigid_body| {
+                rigid_body.enable_ccd(enabled);
+            })?;
+            Ok(())
+        });
+        registry.add_field_method_get("maxLinearSpeed", |_, object| {
+            access_rigid_body(object, |world, _| {
+                world
+                    .max_linear_speeds
+                    .get(&object.rigid_body_handle)
+                    .copied()
+            })
+        });
+        registry.add_field_method_set("maxLinearSpeed", |_, object, max_speed: Option<f32>| {
+            let max_speed = max_speed.map(check_max_linear_speed).transpose()?;
+            access_world_mut(object, |world| match max_speed {
+                Some(max_speed) => {
+                    world
+                        .max_linear_speeds
+                        .insert(object.rigid_body_handle, max_speed);
+                }
+                None => {
+                    world.max_linear_speeds.remove(&object.rigid_body_handle);
+                }
+            })
+        });
 
         registry.add_method_mut("setRestitution", |_, object, restitution: f32| {
             access_rigid_body_mut(object, |collider_set, rigid_body| {
@@ -694,12 +1746,13 @@ pub fn setup_physics_api(
 
         registry.add_method_mut("setMass", |_, object, mass: f32| {
             access_rigid_body_mut(object, |collider_set, rigid_body| {
+                let collider_count = rigid_body.colliders().len().max(1) as f32;
                 rigid_body.colliders().iter().for_each(|collider_handle| {
                     let Some(collider) = collider_set.get_mut(*collider_handle) else {
                         return;
                     };
-                    // Under the assumption one collider per body
-                    collider.set_mass(mass);
+                    // The mass is shared between the colliders added with addCollider.
+                    collider.set_mass(mass / collider_count);
                 });
             })?;
             Ok(())
@@ -717,6 +1770,109 @@ pub fn setup_physics_api(
             Ok(())
         });
 
+        registry.add_method("setCollider", |_, object, collider: AnyUserData| {
+            let mut collider = collider.borrow::<Collider2>()?.collider.clone();
+            let maybe_world = object.world.upgrade();
+            let Some(world) = maybe_world else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "Object2 is out of this world".to_string(),
+                ));
+            };
+            let world = &mut *world.borrow_mut();
+            let Some(rigid_body) = world.rigid_body_set.get(object.rigid_body_handle) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "Object2 is out of this world".to_string(),
+                ));
+            };
+            let previous_colliders = rigid_body.colliders().to_vec();
+            let layer = world
+                .extras
+                .get(&object.rigid_body_handle)
+                .and_then(|extra| extra.layer.as_deref());
+            collider.set_collision_groups(world.layer_groups(layer));
+            for collider_handle in previous_colliders {
+                world.collider_set.remove(
+                    collider_handle,
+                    &mut world.island_manager,
+                    &mut world.rigid_body_set,
+                    true,
+                );
+            }
+            world.collider_set.insert_with_parent(
+                collider,
+                object.rigid_body_handle,
+                &mut world.rigid_body_set,
+            );
+            Ok(())
+        });
+
+        registry.add_method("rebuildColliders", {
+            let resources = resources.clone();
+            move |_, object, (): ()| {
+                let maybe_world = object.world.upgrade();
+                let Some(world) = maybe_world else {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                        "Object2 is out of this world".to_string(),
+                    ));
+                };
+                let world = &mut *world.borrow_mut();
+                let Some(&(tilemap, tile_size)) =
+                    world.tilemap_colliders.get(&object.rigid_body_handle)
+                else {
+                    return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                        "Only objects created with addTilemapColliders can rebuild their colliders"
+                            .to_string(),
+                    ));
+                };
+                let tilemap_res = get_tilemap_resource(&resources, tilemap)?;
+                let mut collider = tilemap_collider(&tilemap_res, tile_size)?;
+                let layer = world
+                    .extras
+                    .get(&object.rigid_body_handle)
+                    .and_then(|extra| extra.layer.as_deref());
+                collider.set_collision_groups(world.layer_groups(layer));
+                let previous_colliders = world
+                    .rigid_body_set
+                    .get(object.rigid_body_handle)
+                    .map(|rigid_body| rigid_body.colliders().to_vec())
+                    .unwrap_or_default();
+                for collider_handle in previous_colliders {
+                    world.collider_set.remove(
+                        collider_handle,
+                        &mut world.island_manager,
+                        &mut world.rigid_body_set,
+                        true,
+                    );
+                }
+                world.collider_set.insert_with_parent(
+                    collider,
+                    object.rigid_body_handle,
+                    &mut world.rigid_body_set,
+                );
+                Ok(())
+            }
+        });
+
+        registry.add_method(
+            "addCollider",
+            |_, object, (collider, offset, rotation): (AnyUserData, Option<Vec2>, Option<f32>)| {
+                let mut collider = collider.borrow::<Collider2>()?.collider.clone();
+                collider.set_position_wrt_parent(part_pose(offset, rotation));
+                access_world_mut(object, |world| {
+                    let layer = world
+                        .extras
+                        .get(&object.rigid_body_handle)
+                        .and_then(|extra| extra.layer.as_deref());
+                    collider.set_collision_groups(world.layer_groups(layer));
+                    world.collider_set.insert_with_parent(
+                        collider,
+                        object.rigid_body_handle,
+                        &mut world.rigid_body_set,
+                    );
+                })
+            },
+        );
+
         registry.add_method("setLockRotation", |_, object, lock: bool| {
             access_rigid_body_mut(object, |_, rigid_body| {
                 rigid_body.lock_rotations(lock, true)
@@ -730,6 +1886,45 @@ pub fn setup_physics_api(
             Ok(())
         });
 
+        registry.add_method(
+            "attach",
+            |_, object, (child, offset, rotation): (AnyUserData, Option<Vec2>, Option<f32>)| {
+                attach_object(
+                    object,
+                    &child,
+                    offset.unwrap_or(Vec2::zero()),
+                    Some(rotation.unwrap_or(0.0)),
+                )
+            },
+        );
+        registry.add_method(
+            "attachOffsetOnly",
+            |_, object, (child, offset): (AnyUserData, Option<Vec2>)| {
+                attach_object(object, &child, offset.unwrap_or(Vec2::zero()), None)
+            },
+        );
+        registry.add_method("detach", |_, object, child: AnyUserData| {
+            let child = child.borrow::<Object2>()?;
+            access_world_mut(object, |world| {
+                world
+                    .attachments
+                    .detach(object.rigid_body_handle, child.rigid_body_handle)
+            })
+        });
+        registry.add_method("getAttachments", |_, object, (): ()| {
+            access_world_mut(object, |world| {
+                world
+                    .attachments
+                    .children_of(object.rigid_body_handle)
+                    .into_iter()
+                    .map(|handle| Object2 {
+                        rigid_body_handle: handle,
+                        world: object.world.clone(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+        });
+
         // ---
 
         registry.add_field_method_get("tags", |_lua, object| {
@@ -746,6 +1941,9 @@ pub fn setup_physics_api(
                 })
             },
         );
+        registry.add_field_method_get("layer", |_lua, object| {
+            access_extras(object, |extra_object_data| extra_object_data.layer.clone())
+        });
         registry.add_field_method_get("extra", |_lua, object| {
             access_extras(object, |extra_object_data| {
                 Ok(extra_object_data.extra_custom.clone())
@@ -833,6 +2031,13 @@ fn get_points_of_collider(collider: &Collider) -> Vec<Vec2> {
             .map(|p| collider.position() * p)
             .map(|p| Vec2::new(p.x, p.y))
             .collect()
+    } else if let Some(shape) = shape.as_capsule() {
+        shape
+            .to_polyline(16)
+            .iter()
+            .map(|p| collider.position() * p)
+            .map(|p| Vec2::new(p.x, p.y))
+            .collect()
     } else if let Some(shape) = shape.as_polyline() {
         shape
             .vertices()
@@ -868,6 +2073,22 @@ fn get_points_of_collider(collider: &Collider) -> Vec<Vec2> {
             })
             .map(|p| Vec2::new(p.x, p.y))
             .collect()
+    } else if let Some(shape) = shape.as_compound() {
+        // Like voxels, 4 points per part: the corners of the rectangles of the tilemap colliders, or the bounding boxes
+        // of the parts of compound colliders.
+        shape
+            .shapes()
+            .iter()
+            .flat_map(|(part_position, part)| {
+                let aabb = part.compute_aabb(&(collider.position() * part_position));
+                vec![
+                    Vec2::new(aabb.mins.x, aabb.mins.y),
+                    Vec2::new(aabb.maxs.x, aabb.mins.y),
+                    Vec2::new(aabb.maxs.x, aabb.maxs.y),
+                    Vec2::new(aabb.mins.x, aabb.maxs.y),
+                ]
+            })
+            .collect()
     } else {
         // As a fallback, we use the AABB
         let aabb = shape.compute_aabb(collider.position());
@@ -880,6 +2101,171 @@ fn get_points_of_collider(collider: &Collider) -> Vec<Vec2> {
     }
 }
 
+/// A `{collider, offset?, rotation?}` part of a compound collider. Compound shapes cannot contain shapes made of
+/// several parts themselves.
+fn compound_part(
+    part: &vectarine_plugin_sdk::mlua::Table,
+) -> vectarine_plugin_sdk::mlua::Result<(Isometry2<f32>, SharedShape)> {
+    let collider = part.get::<AnyUserData>("collider")?;
+    let shape = collider
+        .borrow::<Collider2>()?
+        .collider
+        .shared_shape()
+        .clone();
+    if shape.as_compound().is_some()
+        || shape.as_polyline().is_some()
+        || shape.as_voxels().is_some()
+        || shape.as_trimesh().is_some()
+        || shape.as_heightfield().is_some()
+    {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Compound colliders can only contain rectangles, circles and capsules".to_string(),
+        ));
+    }
+    Ok((part_pose(part.get("offset")?, part.get("rotation")?), shape))
+}
+
+/// The position of a part of an object relative to the center of the object.
+fn part_pose(offset: Option<Vec2>, rotation: Option<f32>) -> Isometry2<f32> {
+    let offset = offset.unwrap_or(Vec2::zero());
+    Isometry2::new(
+        nalgebra::vector![offset.x(), offset.y()],
+        rotation.unwrap_or(0.0),
+    )
+}
+
+/// Returns the edges of the outline of the collider. Polylines are open, the other shapes are closed.
+fn get_edges_of_collider(collider: &Collider) -> Vec<(Vec2, Vec2)> {
+    let points = get_points_of_collider(collider);
+    let closed_loop_edges = |points: &[Vec2]| {
+        let count = points.len();
+        (0..count)
+            .map(|i| (points[i], points[(i + 1) % count]))
+            .collect::<Vec<_>>()
+    };
+    let shape = collider.shape();
+    if let Some(shape) = shape.as_polyline() {
+        shape
+            .indices()
+            .iter()
+            .filter_map(|[start, end]| {
+                Some((*points.get(*start as usize)?, *points.get(*end as usize)?))
+            })
+            .collect()
+    } else if shape.as_voxels().is_some() || shape.as_compound().is_some() {
+        points.chunks_exact(4).flat_map(closed_loop_edges).collect()
+    } else {
+        closed_loop_edges(&points)
+    }
+}
+
+/// Attaches `child` to `parent`, see `Attachments`. Only kinematic objects can be moved by their parent.
+fn attach_object(
+    parent: &Object2,
+    child: &AnyUserData,
+    offset: Vec2,
+    rotation: Option<f32>,
+) -> vectarine_plugin_sdk::mlua::Result<()> {
+    let child = child.borrow::<Object2>()?;
+    if !Weak::ptr_eq(&parent.world, &child.world) {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Only objects of the same world can be attached".to_string(),
+        ));
+    }
+    access_world_mut(parent, |world| {
+        let Some(child_body) = world.rigid_body_set.get(child.rigid_body_handle) else {
+            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                "Object2 is out of this world".to_string(),
+            ));
+        };
+        if !child_body.body_type().is_kinematic() {
+            return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                "Only kinematic objects can be attached, create the child with the \"kinematic\" body type. To connect objects moved by the physics, use a joint like world:createDistanceJoint".to_string(),
+            ));
+        }
+        world
+            .attachments
+            .attach(
+                child.rigid_body_handle,
+                Attachment {
+                    parent: parent.rigid_body_handle,
+                    offset,
+                    rotation,
+                },
+            )
+            .map_err(vectarine_plugin_sdk::mlua::Error::RuntimeError)?;
+        world
+            .attachments
+            .apply_to(&mut world.rigid_body_set, child.rigid_body_handle);
+        Ok(())
+    })?
+}
+
+fn check_max_linear_speed(max_speed: f32) -> vectarine_plugin_sdk::mlua::Result<f32> {
+    if max_speed.is_nan() || max_speed < 0.0 {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+            "maxLinearSpeed must be a positive number or nil, got {max_speed}"
+        )));
+    }
+    Ok(max_speed)
+}
+
+fn get_tilemap_resource(
+    resources: &Rc<ResourceManager>,
+    tilemap: TilemapResourceId,
+) -> vectarine_plugin_sdk::mlua::Result<Rc<TilemapResource>> {
+    resources
+        .get_by_id::<TilemapResource>(tilemap.to_resource_id())
+        .map_err(|_| {
+            vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                "Tilemap resource not found".to_string(),
+            )
+        })
+}
+
+/// A collider covering the tiles of the solid layers of the tilemap, with the tile (x, y) from `(x, y) * tile_size` to
+/// `(x + 1, y + 1) * tile_size`.
+fn tilemap_collider(
+    tilemap_res: &TilemapResource,
+    tile_size: Vec2,
+) -> vectarine_plugin_sdk::mlua::Result<Collider> {
+    let Some(layer_count) = tilemap_res
+        .content
+        .borrow()
+        .as_ref()
+        .map(|content| content.layers().len())
+    else {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Tilemap not loaded".to_string(),
+        ));
+    };
+
+    // Every solid layer is merged into rectangles on its own, the layers can overlap.
+    let mut shapes = Vec::new();
+    for tile_layer in 0..layer_count as i32 {
+        if !tilemap_res.is_layer_solid(tile_layer) {
+            continue;
+        }
+        let tiles = tilemap_res.filled_tiles(tile_layer);
+        for rect in merge_tiles_into_rectangles(&tiles) {
+            let half_width = rect.width as f32 * tile_size.x() / 2.0;
+            let half_height = rect.height as f32 * tile_size.y() / 2.0;
+            shapes.push((
+                Isometry2::translation(
+                    rect.x as f32 * tile_size.x() + half_width,
+                    rect.y as f32 * tile_size.y() + half_height,
+                ),
+                SharedShape::cuboid(half_width, half_height),
+            ));
+        }
+    }
+    if shapes.is_empty() {
+        // Like voxel colliders, a compound shape with no parts has a NaN AABB.
+        return Ok(ColliderBuilder::ball(0.0).build());
+    }
+    Ok(ColliderBuilder::compound(shapes).build())
+}
+
 fn access_rigid_body_mut<F, T>(object: &Object2, f: F) -> vectarine_plugin_sdk::mlua::Result<T>
 where
     F: FnOnce(&mut ColliderSet, &mut RigidBody) -> T,
@@ -918,6 +2304,25 @@ where
     Ok(f(world, rigid_body))
 }
 
+fn access_world_mut<F, T>(object: &Object2, f: F) -> vectarine_plugin_sdk::mlua::Result<T>
+where
+    F: FnOnce(&mut PhysicsWorld2) -> T,
+{
+    let maybe_world = object.world.upgrade();
+    let Some(world) = maybe_world else {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Object2 is out of this world".to_string(),
+        ));
+    };
+    let world = &mut *world.borrow_mut();
+    if !world.rigid_body_set.contains(object.rigid_body_handle) {
+        return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+            "Object2 is out of this world".to_string(),
+        ));
+    }
+    Ok(f(world))
+}
+
 fn access_extras<F, T>(object: &Object2, f: F) -> vectarine_plugin_sdk::mlua::Result<T>
 where
     F: FnOnce(&mut ExtraObjectData) -> T,
@@ -937,3 +2342,275 @@ where
     };
     Ok(f(extras))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+        time::Duration,
+    };
+
+    use vectarine_plugin_sdk::mlua::{Function, Lua, Value};
+
+    use super::{
+        inspector::PhysicsWorldRegistry, layers::SharedCollisionLayers, setup_physics_api,
+    };
+    use crate::{
+        fixedtick::FixedTickClock, game_resource::ResourceManager, lua_env::lua_vec2,
+        statehash::hash_lua_value,
+    };
+
+    const SCENE: &str = r#"
+        local world = Physics.newWorld2(Vec.V2(0, -9.81))
+        world:createObject(Vec.V2(0, -1), 0, Physics.newRectangleCollider(Vec.V2(40, 1)), {}, "static")
+        for i = 0, 29 do
+            local collider = if i % 2 == 0
+                then Physics.newRectangleCollider(Vec.V2(0.4 + i % 3 * 0.1, 0.3))
+                else Physics.newCircleCollider(0.25 + i % 4 * 0.05)
+            world:createObject(Vec.V2(i % 6 * 0.55 - 1.5, 1 + i * 0.7), 1, collider, { "body" }, "dynamic")
+        end
+        for _ = 1, 1000 do
+            world:step(1 / 60)
+        end
+        local state = {}
+        for i, object in world:getObjects({ "body" }) do
+            state[i] = { object.position.x, object.position.y, object.rotation }
+        end
+        return state
+    "#;
+
+    fn new_lua() -> Lua {
+        new_lua_with_worlds(&Rc::default(), None)
+    }
+
+    fn new_lua_with_worlds(
+        physics_worlds: &Rc<RefCell<PhysicsWorldRegistry>>,
+        fixed_tick_duration: Option<f32>,
+    ) -> Lua {
+        let lua = Lua::new();
+        let vec = lua_vec2::setup_vec_api(&lua).expect("Vec API");
+        let physics = setup_physics_api(
+            &lua,
+            &Rc::new(ResourceManager::dummy_manager()),
+            &Rc::new(RefCell::new(SharedCollisionLayers::default())),
+            &Rc::new(Cell::new(true)),
+            &Rc::new(Cell::new(fixed_tick_duration)),
+            physics_worlds,
+        )
+        .expect("Physics API");
+        lua.globals().set("Vec", vec).expect("Set Vec");
+        lua.globals().set("Physics", physics).expect("Set Physics");
+        lua
+    }
+
+    fn run_scene() -> u64 {
+        let lua = new_lua();
+        let state = lua.load(SCENE).eval::<Value>().expect("Valid scene");
+        hash_lua_value(&state).expect("Hashable state")
+    }
+
+    /// The CI compares the printed hash between the native and the web builds.
+    #[test]
+    fn deterministic_physics_gives_the_same_state() {
+        let hash = run_scene();
+        assert_eq!(hash, run_scene());
+        println!("physics state hash: {hash:016x}");
+    }
+
+    /// Replays the same scene at 30 and at 144 frames per second, stepping the world in `Tick` like a game with a
+    /// `fixed_tick_rate` of 60 does.
+    fn run_fixed_tick_scene(frame_duration: Duration, frame_count: usize) -> (u64, u64) {
+        let lua = new_lua_with_worlds(&Rc::default(), Some(1.0 / 60.0));
+        lua.load(
+            r#"
+            local world = Physics.newWorld2(Vec.V2(0, -9.81))
+            world:createObject(Vec.V2(0, -1), 0, Physics.newRectangleCollider(Vec.V2(40, 1)), {}, "static")
+            for i = 0, 9 do
+                world:createObject(Vec.V2(i % 4 * 0.45 - 0.7, 1 + i * 0.6), 1, Physics.newCircleCollider(0.2), { "body" }, "dynamic")
+            end
+            function Tick()
+                world:step()
+            end
+            function State()
+                local state = {}
+                for i, object in world:getObjects({ "body" }) do
+                    state[i] = { object.position.x, object.position.y, object.rotation }
+                end
+                return state
+            end
+            "#,
+        )
+        .exec()
+        .expect("Valid scene");
+        let tick_fn = lua.globals().get::<Function>("Tick").expect("Tick");
+        let mut clock = FixedTickClock::new(60);
+        for _ in 0..frame_count {
+            for _ in clock.advance(frame_duration) {
+                tick_fn.call::<()>(()).expect("Tick runs");
+            }
+        }
+        let state_fn = lua.globals().get::<Function>("State").expect("State");
+        let state = state_fn.call::<Value>(()).expect("State runs");
+        (
+            clock.tick_count(),
+            hash_lua_value(&state).expect("Hashable state"),
+        )
+    }
+
+    #[test]
+    fn fixed_ticks_give_the_same_physics_at_any_frame_rate() {
+        // Both last a bit more than 2 seconds, which is 120 ticks.
+        let at_30_fps = run_fixed_tick_scene(Duration::from_nanos(33_333_334), 60);
+        let at_144_fps = run_fixed_tick_scene(Duration::from_nanos(6_944_445), 288);
+        assert_eq!(at_30_fps.0, 120);
+        assert_eq!(at_30_fps, at_144_fps);
+
+        let lua = new_lua();
+        let result = lua
+            .load("Physics.newWorld2(Vec.V2(0, -9.81)):step()")
+            .exec();
+        assert!(
+            result.is_err(),
+            "step needs a delta time without fixed ticks"
+        );
+    }
+
+    #[test]
+    fn max_linear_speed_limits_the_speed_and_rejects_negative_values() {
+        let lua = new_lua();
+        let speed = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, -9.81))
+                local object = world:createObject(Vec.V2(0, 0), 1, Physics.newCircleCollider(0.1), {}, "dynamic", { maxLinearSpeed = 2 })
+                object.speed = Vec.V2(10, 0)
+                world:step(1 / 60)
+                return object.speed:length()
+                "#,
+            )
+            .eval::<f32>()
+            .expect("Valid scene");
+        assert!(speed <= 2.0 + 1e-4, "speed {speed} is above the limit");
+
+        let created = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, 0))
+                world:createObject(Vec.V2(0, 0), 1, Physics.newCircleCollider(0.1), {}, "dynamic", { maxLinearSpeed = -1 })
+                "#,
+            )
+            .exec();
+        assert!(created.is_err());
+        let set = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, 0))
+                local object = world:createObject(Vec.V2(0, 0), 1, Physics.newCircleCollider(0.1), {}, "dynamic")
+                object.maxLinearSpeed = -1
+                "#,
+            )
+            .exec();
+        assert!(set.is_err());
+    }
+
+    #[test]
+    fn joints_break_when_pulled_harder_than_their_break_force() {
+        let lua = new_lua();
+        let (strong, weak, kind) = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, -9.81))
+                local function hang(x, breakForce)
+                    local anchor = world:createObject(Vec.V2(x, 0), 0, Physics.newCircleCollider(0.1), {}, "static")
+                    local weight = world:createObject(Vec.V2(x, -1), 0, Physics.newCircleCollider(0.1), {}, "dynamic")
+                    return Physics.newRopeJoint(anchor, weight, 1, { breakForce = breakForce })
+                end
+                local strong = hang(0, 1000)
+                local weak = hang(2, 0.01)
+                for _ = 1, 10 do
+                    world:step(1 / 60)
+                end
+                return strong:isValid(), weak:isValid(), strong:getType()
+                "#,
+            )
+            .eval::<(bool, bool, String)>()
+            .expect("Valid scene");
+        assert!(strong);
+        assert!(!weak);
+        assert_eq!(kind, "rope");
+    }
+
+    #[test]
+    fn too_large_pools_are_rejected() {
+        let lua = new_lua();
+        let result = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, 0))
+                world:createPool({ collider = Physics.newCircleCollider(0.1), size = 1e12 })
+                "#,
+            )
+            .exec();
+        let error = result.expect_err("The pool is too large").to_string();
+        assert!(error.contains("maximum"), "{error}");
+    }
+
+    #[test]
+    fn removed_pool_objects_free_their_slot() {
+        let lua = new_lua();
+        let (active_after_remove, capacity, reused, removed_is_gone) = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, 0))
+                local pool = world:createPool({ collider = Physics.newCircleCollider(0.1), size = 2, whenFull = "recycle" })
+                local first = pool:spawn(Vec.V2(0, 0))
+                local second = pool:spawn(Vec.V2(1, 0))
+                world:removeObject(first)
+                local activeAfterRemove = pool:activeCount()
+                -- The slot of the removed object gets a new object instead of recycling the second one.
+                local third = pool:spawn(Vec.V2(2, 0))
+                local reused = third ~= second and third.position.x == 2 and second.position.x == 1
+                return activeAfterRemove, pool:capacity(), reused, #world:getObjects() == 2
+                "#,
+            )
+            .eval::<(usize, usize, bool, bool)>()
+            .expect("Valid scene");
+        assert_eq!(active_after_remove, 1);
+        assert_eq!(capacity, 2);
+        assert!(reused);
+        assert!(removed_is_gone);
+    }
+
+    #[test]
+    fn inspector_moves_and_tags_the_objects_of_the_worlds() {
+        let physics_worlds = Rc::default();
+        let lua = new_lua_with_worlds(&physics_worlds, None);
+        let tags = lua
+            .load(
+                r#"
+                local world = Physics.newWorld2(Vec.V2(0, -9.81))
+                local tags = { "crate" }
+                world:createObject(Vec.V2(1, 2), 0, Physics.newCircleCollider(0.5), tags, "dynamic")
+                return tags
+                "#,
+            )
+            .eval::<vectarine_plugin_sdk::mlua::Table>()
+            .expect("Valid scene");
+
+        let worlds = physics_worlds.borrow().worlds();
+        assert_eq!(worlds.len(), 1);
+        let objects = worlds[0].objects();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].tags, ["crate"]);
+        assert_eq!(objects[0].body_type, "dynamic");
+        assert!(!objects[0].outlines[0].is_empty());
+
+        worlds[0].set_pose(objects[0].handle, lua_vec2::Vec2::new(3.0, 4.0), 1.0);
+        worlds[0].set_tags(objects[0].handle, &["box".to_string(), "wood".to_string()]);
+        let object = &worlds[0].objects()[0];
+        assert_eq!(object.position, lua_vec2::Vec2::new(3.0, 4.0));
+        assert!((object.rotation - 1.0).abs() < 1e-5);
+        assert_eq!(tags.sequence_values::<String>().count(), 2);
+    }
+}
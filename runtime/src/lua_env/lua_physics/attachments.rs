@@ -0,0 +1,190 @@
+//! Kinematic objects attached to a parent object follow it, like a turret on a tank or a health bar above a character.
+//! The poses of the children are updated after every step of the world, parents before their children, so that a deep
+//! hierarchy costs no Lua calls.
+
+use std::collections::HashMap;
+
+use nalgebra::{Isometry2, vector};
+use vectarine_plugin_sdk::rapier2d::prelude::{RigidBodyHandle, RigidBodySet};
+
+use crate::lua_env::lua_vec2::Vec2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Attachment {
+    pub parent: RigidBodyHandle,
+    /// In the space of the parent, or in the world space when `rotation` is None.
+    pub offset: Vec2,
+    /// The rotation of the child relative to its parent. None leaves the rotation of the child free and does not turn
+    /// the offset with the parent.
+    pub rotation: Option<f32>,
+}
+
+/// The attachments of a world, by child.
+#[derive(Default)]
+pub struct Attachments {
+    by_child: HashMap<RigidBodyHandle, Attachment>,
+}
+
+impl Attachments {
+    /// Attaches the child, replacing its previous parent. A child cannot be attached to itself or to one of its
+    /// descendants.
+    pub fn attach(&mut self, child: RigidBodyHandle, attachment: Attachment) -> Result<(), String> {
+        let mut ancestor = Some(attachment.parent);
+        while let Some(handle) = ancestor {
+            if handle == child {
+                return Err(
+                    "Attaching this object would make it its own parent, detach it from its children first"
+                        .to_string(),
+                );
+            }
+            ancestor = self
+                .by_child
+                .get(&handle)
+                .map(|attachment| attachment.parent);
+        }
+        self.by_child.insert(child, attachment);
+        Ok(())
+    }
+
+    /// Returns false when the child was not attached to this parent.
+    pub fn detach(&mut self, parent: RigidBodyHandle, child: RigidBodyHandle) -> bool {
+        if self.parent_of(child) != Some(parent) {
+            return false;
+        }
+        self.by_child.remove(&child);
+        true
+    }
+
+    pub fn parent_of(&self, child: RigidBodyHandle) -> Option<RigidBodyHandle> {
+        self.by_child
+            .get(&child)
+            .map(|attachment| attachment.parent)
+    }
+
+    /// The direct children, in the order of their slot in the world.
+    pub fn children_of(&self, parent: RigidBodyHandle) -> Vec<RigidBodyHandle> {
+        let mut children = self
+            .by_child
+            .iter()
+            .filter(|(_, attachment)| attachment.parent == parent)
+            .map(|(child, _)| *child)
+            .collect::<Vec<_>>();
+        children.sort_by_key(|child| child.into_raw_parts());
+        children
+    }
+
+    /// Called when an object is removed from the world: it is detached from its parent and its children are detached
+    /// from it. The children stay in the world where they are.
+    pub fn remove_object(&mut self, handle: RigidBodyHandle) {
+        self.by_child.remove(&handle);
+        self.by_child
+            .retain(|_, attachment| attachment.parent != handle);
+    }
+
+    /// The children with their attachment, each one after its parent.
+    fn update_order(&self) -> Vec<(RigidBodyHandle, Attachment)> {
+        let depth = |mut handle: RigidBodyHandle| {
+            let mut depth = 0;
+            while let Some(parent) = self.parent_of(handle) {
+                depth += 1;
+                handle = parent;
+            }
+            depth
+        };
+        let mut children = self
+            .by_child
+            .iter()
+            .map(|(child, attachment)| (depth(*child), *child, *attachment))
+            .collect::<Vec<_>>();
+        children.sort_by_key(|(depth, child, _)| (*depth, child.into_raw_parts()));
+        children
+            .into_iter()
+            .map(|(_, child, attachment)| (child, attachment))
+            .collect()
+    }
+
+    /// Moves every child to its place relative to its parent.
+    pub fn apply(&self, rigid_body_set: &mut RigidBodySet) {
+        for (child, attachment) in self.update_order() {
+            apply_attachment(rigid_body_set, child, &attachment);
+        }
+    }
+
+    /// Moves the child and its descendants, when they were just attached.
+    pub fn apply_to(&self, rigid_body_set: &mut RigidBodySet, handle: RigidBodyHandle) {
+        if let Some(attachment) = self.by_child.get(&handle) {
+            apply_attachment(rigid_body_set, handle, attachment);
+        }
+        for child in self.children_of(handle) {
+            self.apply_to(rigid_body_set, child);
+        }
+    }
+}
+
+fn apply_attachment(
+    rigid_body_set: &mut RigidBodySet,
+    child: RigidBodyHandle,
+    attachment: &Attachment,
+) {
+    let Some(parent) = rigid_body_set.get(attachment.parent) else {
+        return;
+    };
+    let parent_pose = *parent.position();
+    let Some(child) = rigid_body_set.get_mut(child) else {
+        return;
+    };
+    let offset = vector![attachment.offset.x(), attachment.offset.y()];
+    let pose = match attachment.rotation {
+        Some(rotation) => parent_pose * Isometry2::new(offset, rotation),
+        None => Isometry2::new(
+            parent_pose.translation.vector + offset,
+            child.rotation().angle(),
+        ),
+    };
+    child.set_position(pose, true);
+}
+
+#[cfg(test)]
+mod tests {
+    use vectarine_plugin_sdk::rapier2d::prelude::{RigidBodyBuilder, RigidBodySet};
+
+    use super::{Attachment, Attachments};
+    use crate::lua_env::lua_vec2::Vec2;
+
+    #[test]
+    fn children_follow_their_parent_without_cycles() {
+        let mut rigid_body_set = RigidBodySet::new();
+        let mut insert = |x: f32| {
+            rigid_body_set.insert(
+                RigidBodyBuilder::kinematic_velocity_based()
+                    .pose(nalgebra::Isometry2::translation(x, 0.0)),
+            )
+        };
+        let (tank, turret, barrel) = (insert(10.0), insert(0.0), insert(0.0));
+        let attachment = |parent, rotation| Attachment {
+            parent,
+            offset: Vec2::new(1.0, 0.0),
+            rotation,
+        };
+        let mut attachments = Attachments::default();
+        attachments
+            .attach(barrel, attachment(turret, Some(0.0)))
+            .expect("no cycle");
+        attachments
+            .attach(turret, attachment(tank, Some(std::f32::consts::FRAC_PI_2)))
+            .expect("no cycle");
+        assert!(attachments.attach(tank, attachment(barrel, None)).is_err());
+        assert!(attachments.attach(tank, attachment(tank, None)).is_err());
+
+        attachments.apply(&mut rigid_body_set);
+        let position = |handle| *rigid_body_set[handle].translation();
+        assert_eq!(position(turret), nalgebra::vector![11.0, 0.0]);
+        // The turret is turned a quarter, so the offset of the barrel points up.
+        assert!((position(barrel) - nalgebra::vector![11.0, 1.0]).norm() < 1e-5);
+
+        attachments.remove_object(turret);
+        assert_eq!(attachments.parent_of(barrel), None);
+        assert!(attachments.children_of(tank).is_empty());
+        assert!(!attachments.detach(tank, barrel));
+    }
+}
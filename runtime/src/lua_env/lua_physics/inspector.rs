@@ -0,0 +1,154 @@
+//! Gives the editor access to the physics worlds of the game, to list their objects and move them while the game runs.
+
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+};
+
+use nalgebra::Isometry2;
+use vectarine_plugin_sdk::rapier2d::prelude::{RigidBodyHandle, RigidBodyType};
+
+use crate::lua_env::{
+    lua_camera::Camera2,
+    lua_physics::{PhysicsWorld2, get_points_of_collider},
+    lua_vec2::Vec2,
+    stringify_lua_value,
+};
+
+/// The worlds created with `Physics.newWorld2`. Worlds collected by Lua are removed when the next one is created.
+#[derive(Default)]
+pub struct PhysicsWorldRegistry {
+    worlds: Vec<Weak<RefCell<PhysicsWorld2>>>,
+}
+
+impl PhysicsWorldRegistry {
+    pub fn register(&mut self, world: &Rc<RefCell<PhysicsWorld2>>) {
+        self.worlds.retain(|world| world.strong_count() > 0);
+        self.worlds.push(Rc::downgrade(world));
+    }
+
+    /// The worlds still in use, in the order they were created.
+    pub fn worlds(&self) -> Vec<InspectedWorld> {
+        self.worlds
+            .iter()
+            .filter_map(|world| world.upgrade())
+            .map(InspectedWorld)
+            .collect()
+    }
+}
+
+/// An object of a world, as shown in the inspector of the editor.
+#[derive(Debug, Clone)]
+pub struct InspectedObject {
+    /// The slot and the generation of the body in the world.
+    pub handle: (u32, u32),
+    pub position: Vec2,
+    /// In radians.
+    pub rotation: f32,
+    pub velocity: Vec2,
+    pub body_type: &'static str,
+    pub tags: Vec<String>,
+    pub layer: Option<String>,
+    /// The outlines of the colliders of the object, in world coordinates.
+    pub outlines: Vec<Vec<Vec2>>,
+}
+
+pub struct InspectedWorld(Rc<RefCell<PhysicsWorld2>>);
+
+impl InspectedWorld {
+    /// Without a camera, the coordinates of the world are the coordinates of the screen.
+    pub fn camera(&self) -> Option<Camera2> {
+        let world = self.0.try_borrow().ok()?;
+        let vectarine_plugin_sdk::mlua::Value::UserData(camera) = world.camera.as_ref()? else {
+            return None;
+        };
+        camera.borrow::<Camera2>().ok().map(|camera| camera.clone())
+    }
+
+    /// The objects are sorted by their slot, so that they keep their place in the list. The list is empty while the
+    /// world is being used by a script.
+    pub fn objects(&self) -> Vec<InspectedObject> {
+        let Ok(world) = self.0.try_borrow() else {
+            return Vec::new();
+        };
+        let mut objects = world
+            .rigid_body_set
+            .iter()
+            .map(|(handle, rigid_body)| {
+                let position = rigid_body.translation();
+                let velocity = rigid_body.linvel();
+                let extra = world.extras.get(&handle);
+                let tags = extra
+                    .map(|extra| {
+                        extra
+                            .tags
+                            .sequence_values::<vectarine_plugin_sdk::mlua::Value>()
+                            .filter_map(|tag| tag.ok())
+                            .map(|tag| match tag.as_string() {
+                                Some(tag) => tag.to_string_lossy(),
+                                None => stringify_lua_value(&tag),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let outlines = rigid_body
+                    .colliders()
+                    .iter()
+                    .filter_map(|collider| world.collider_set.get(*collider))
+                    .map(get_points_of_collider)
+                    .collect();
+                InspectedObject {
+                    handle: handle.into_raw_parts(),
+                    position: Vec2::new(position.x, position.y),
+                    rotation: rigid_body.rotation().angle(),
+                    velocity: Vec2::new(velocity.x, velocity.y),
+                    body_type: match rigid_body.body_type() {
+                        RigidBodyType::Dynamic => "dynamic",
+                        RigidBodyType::Fixed => "static",
+                        RigidBodyType::KinematicPositionBased
+                        | RigidBodyType::KinematicVelocityBased => "kinematic",
+                    },
+                    tags,
+                    layer: extra.and_then(|extra| extra.layer.clone()),
+                    outlines,
+                }
+            })
+            .collect::<Vec<_>>();
+        objects.sort_by_key(|object| object.handle);
+        objects
+    }
+
+    /// Moves the object and stops it, so that it does not fly away when it is dropped.
+    pub fn set_pose(&self, handle: (u32, u32), position: Vec2, rotation: f32) {
+        let Ok(mut world) = self.0.try_borrow_mut() else {
+            return;
+        };
+        let handle = RigidBodyHandle::from_raw_parts(handle.0, handle.1);
+        let Some(rigid_body) = world.rigid_body_set.get_mut(handle) else {
+            return;
+        };
+        rigid_body.set_position(
+            Isometry2::new(nalgebra::vector![position.x(), position.y()], rotation),
+            true,
+        );
+        rigid_body.set_linvel(nalgebra::vector![0.0, 0.0], true);
+        rigid_body.set_angvel(0.0, true);
+    }
+
+    /// The tags table of the object is changed in place, as the scripts may hold it.
+    pub fn set_tags(&self, handle: (u32, u32), tags: &[String]) {
+        let Ok(world) = self.0.try_borrow() else {
+            return;
+        };
+        let handle = RigidBodyHandle::from_raw_parts(handle.0, handle.1);
+        let Some(extra) = world.extras.get(&handle) else {
+            return;
+        };
+        if extra.tags.clear().is_err() {
+            return;
+        }
+        for tag in tags {
+            let _ = extra.tags.raw_push(tag.as_str());
+        }
+    }
+}
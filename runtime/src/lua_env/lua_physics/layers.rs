@@ -0,0 +1,285 @@
+//! Collision layers name the groups of physics objects, like "player" or "terrain", and list which layers collide.
+//! They are written in the `[physics.layers]` section of game.vecta:
+//!
+//! ```toml
+//! [physics.layers]
+//! names = ["player", "enemy", "terrain"]
+//! collisions = [["player", "enemy"], ["player", "terrain"], ["enemy", "terrain"]]
+//! ```
+//!
+//! Objects created without a layer collide with every layer that collides with something.
+
+use vectarine_plugin_sdk::rapier2d::prelude::{Group, InteractionGroups};
+use vectarine_plugin_sdk::serde::{Deserialize, Serialize};
+
+/// Rapier stores the groups of a collider in 32 bits.
+pub const MAX_COLLISION_LAYERS: usize = 32;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde")]
+pub struct CollisionLayers {
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// The pairs of layers that collide. A layer collides with its own objects only when it is paired with itself.
+    #[serde(default)]
+    pub collisions: Vec<[String; 2]>,
+}
+
+impl CollisionLayers {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.collisions.is_empty()
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|layer| layer == name)
+    }
+
+    pub fn collides(&self, first: &str, second: &str) -> bool {
+        self.collisions
+            .iter()
+            .any(|[a, b]| (a == first && b == second) || (a == second && b == first))
+    }
+
+    pub fn set_collides(&mut self, first: &str, second: &str, collides: bool) {
+        self.collisions
+            .retain(|[a, b]| !((a == first && b == second) || (a == second && b == first)));
+        if collides {
+            self.collisions
+                .push([first.to_string(), second.to_string()]);
+        }
+    }
+
+    /// Renames a layer in its pairs too, so that the matrix is kept.
+    pub fn rename(&mut self, index: usize, new_name: &str) {
+        let Some(name) = self.names.get_mut(index) else {
+            return;
+        };
+        let old_name = std::mem::replace(name, new_name.to_string());
+        for layer in self.collisions.iter_mut().flatten() {
+            if *layer == old_name {
+                *layer = new_name.to_string();
+            }
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.names.len() {
+            return;
+        }
+        let name = self.names.remove(index);
+        self.collisions.retain(|pair| !pair.contains(&name));
+    }
+
+    /// The groups of the objects of the layer, or None when the layer does not exist or is past the 32 layers.
+    pub fn groups(&self, name: &str) -> Option<InteractionGroups> {
+        let index = self.index_of(name)?;
+        if index >= MAX_COLLISION_LAYERS {
+            return None;
+        }
+        let filter = self
+            .names
+            .iter()
+            .take(MAX_COLLISION_LAYERS)
+            .enumerate()
+            .filter(|(_, other)| self.collides(name, other))
+            .fold(0u32, |filter, (other_index, _)| filter | (1 << other_index));
+        Some(
+            InteractionGroups::all()
+                .with_memberships(Group::from_bits_truncate(1 << index))
+                .with_filter(Group::from_bits_truncate(filter)),
+        )
+    }
+
+    /// The problems preventing the layers from working.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.names.len() > MAX_COLLISION_LAYERS {
+            errors.push(format!(
+                "There are {} collision layers, the maximum is {MAX_COLLISION_LAYERS}",
+                self.names.len()
+            ));
+        }
+        for (index, name) in self.names.iter().enumerate() {
+            if name.trim().is_empty() {
+                errors.push(format!("The collision layer {} has no name", index + 1));
+            } else if self.names[..index].contains(name) {
+                errors.push(format!("The collision layer '{name}' is declared twice"));
+            }
+        }
+        for layer in self.collisions.iter().flatten() {
+            if self.index_of(layer).is_none() {
+                errors.push(format!(
+                    "The collisions use the layer '{layer}', which is not declared"
+                ));
+            }
+        }
+        errors.dedup();
+        errors
+    }
+
+    /// The layers that look like mistakes but work.
+    pub fn warnings(&self) -> Vec<String> {
+        self.names
+            .iter()
+            .filter(|name| !name.trim().is_empty())
+            .filter(|name| !self.names.iter().any(|other| self.collides(name, other)))
+            .map(|name| format!("The collision layer '{name}' collides with nothing"))
+            .collect()
+    }
+}
+
+/// The collision layers of the project, shared by the physics worlds. The editor replaces them when game.vecta
+/// changes, and the worlds update the groups of their objects on their next step.
+#[derive(Debug, Default)]
+pub struct SharedCollisionLayers {
+    layers: CollisionLayers,
+    version: u64,
+    /// The old and new names of the renamed layers, in order, so that the objects and the values of `Physics.layer`
+    /// created with an old name keep their layer.
+    renames: Vec<(String, String)>,
+}
+
+impl SharedCollisionLayers {
+    pub fn get(&self) -> &CollisionLayers {
+        &self.layers
+    }
+
+    /// Increases with every change of the layers.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn set(&mut self, layers: CollisionLayers) {
+        if self.layers == layers {
+            return;
+        }
+        // A layer replaced by a new name at the same place was renamed. Adding or removing layers moves the others, so
+        // nothing is renamed then.
+        if self.layers.names.len() == layers.names.len() {
+            for (old_name, new_name) in self.layers.names.iter().zip(&layers.names) {
+                if old_name != new_name
+                    && layers.index_of(old_name).is_none()
+                    && self.layers.index_of(new_name).is_none()
+                {
+                    self.renames.push((old_name.clone(), new_name.clone()));
+                }
+            }
+        }
+        self.layers = layers;
+        self.version += 1;
+    }
+
+    /// The name of a layer after the renames done since an object was created with it.
+    pub fn current_name<'a>(&'a self, name: &'a str) -> &'a str {
+        if self.layers.index_of(name).is_some() {
+            return name;
+        }
+        self.renames
+            .iter()
+            .fold(name, |name, (old_name, new_name)| {
+                if name == old_name {
+                    new_name.as_str()
+                } else {
+                    name
+                }
+            })
+    }
+
+    /// The groups of the objects of a layer, following its renames.
+    pub fn groups(&self, name: &str) -> Option<InteractionGroups> {
+        self.layers.groups(self.current_name(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CollisionLayers, SharedCollisionLayers};
+
+    fn layers(names: &[&str], collisions: &[[&str; 2]]) -> CollisionLayers {
+        CollisionLayers {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            collisions: collisions
+                .iter()
+                .map(|[a, b]| [a.to_string(), b.to_string()])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn the_groups_follow_the_matrix() {
+        let mut layers = layers(
+            &["player", "enemy", "projectile", "terrain"],
+            &[
+                ["player", "enemy"],
+                ["terrain", "player"],
+                ["enemy", "terrain"],
+            ],
+        );
+        let groups = |layers: &CollisionLayers, name: &str| {
+            layers
+                .groups(name)
+                .map(|groups| (groups.memberships.bits(), groups.filter.bits()))
+        };
+        assert_eq!(groups(&layers, "player"), Some((0b0001, 0b1010)));
+        assert_eq!(groups(&layers, "terrain"), Some((0b1000, 0b0011)));
+        assert_eq!(groups(&layers, "projectile"), Some((0b0100, 0)));
+        assert_eq!(groups(&layers, "boss"), None);
+        assert!(layers.errors().is_empty());
+        assert_eq!(
+            layers.warnings(),
+            vec!["The collision layer 'projectile' collides with nothing"]
+        );
+
+        layers.set_collides("projectile", "enemy", true);
+        layers.rename(1, "foe");
+        assert_eq!(groups(&layers, "projectile"), Some((0b0100, 0b0010)));
+        layers.remove(0);
+        assert_eq!(groups(&layers, "foe"), Some((0b0001, 0b0110)));
+        assert!(layers.warnings().is_empty());
+    }
+
+    #[test]
+    fn invalid_layers_are_reported() {
+        let too_many = (0..33).map(|index| index.to_string()).collect::<Vec<_>>();
+        let too_many = too_many.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(layers(&too_many, &[]).errors().len(), 1);
+        assert_eq!(layers(&too_many, &[]).groups("32"), None);
+
+        let errors = layers(&["player", "player", ""], &[["player", "ghost"]]).errors();
+        assert_eq!(
+            errors,
+            vec![
+                "The collision layer 'player' is declared twice",
+                "The collision layer 3 has no name",
+                "The collisions use the layer 'ghost', which is not declared",
+            ]
+        );
+    }
+
+    #[test]
+    fn renamed_layers_keep_their_objects() {
+        let mut shared = SharedCollisionLayers::default();
+        shared.set(layers(&["player", "enemy"], &[["player", "enemy"]]));
+        let player_groups = shared.groups("player");
+
+        // Renamed one character at a time, like in the editor.
+        let mut renamed = shared.get().clone();
+        for name in ["playe", "play", "hero"] {
+            renamed.rename(0, name);
+            shared.set(renamed.clone());
+        }
+        assert_eq!(shared.current_name("player"), "hero");
+        assert_eq!(shared.groups("player"), player_groups);
+
+        // Adding a layer named like the old one does not take the objects of the renamed layer.
+        renamed.names.push("player".to_string());
+        shared.set(renamed.clone());
+        assert_eq!(shared.current_name("player"), "player");
+        assert_eq!(shared.current_name("play"), "hero");
+        // Removing a layer moves the others, which is not a rename.
+        renamed.remove(0);
+        shared.set(renamed);
+        assert_eq!(shared.current_name("enemy"), "enemy");
+        assert_eq!(shared.groups("hero"), None);
+    }
+}
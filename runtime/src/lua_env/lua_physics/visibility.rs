@@ -0,0 +1,313 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::lua_env::lua_vec2::Vec2;
+
+/// Number of rays cast around the origin so that the polygon follows the circle where nothing blocks the view.
+const CIRCLE_RAY_COUNT: usize = 64;
+/// Angle in radians between a corner and the two rays cast next to it, to see what is behind the corner.
+const CORNER_ANGLE_OFFSET: f32 = 1e-4;
+/// Hits closer than this to the origin are ignored, so that a segment touching the origin does not hide everything.
+const MIN_HIT_DISTANCE: f32 = 1e-5;
+/// Rays hitting a segment slightly outside of its ends still count as hits, so that rays cast exactly at a corner
+/// are not lost to rounding errors.
+const SEGMENT_END_TOLERANCE: f32 = 1e-5;
+
+/// Returns the polygon of the area visible from `origin` up to `radius` when the segments block the view.
+/// The points are ordered by increasing angle around the origin, so the polygon is star-shaped and never intersects
+/// itself, even when the origin is inside an occluder or when corners are aligned with the origin.
+pub fn compute_visibility_polygon(
+    origin: Vec2,
+    radius: f32,
+    segments: &[(Vec2, Vec2)],
+) -> Vec<Vec2> {
+    if !radius.is_finite() || radius <= 0.0 {
+        return Vec::new();
+    }
+    // Segments are moved so that the origin is at (0, 0). Parts outside of the circle cannot block the view.
+    let segments = segments
+        .iter()
+        .filter_map(|(start, end)| clip_segment_to_circle(*start - origin, *end - origin, radius))
+        .collect::<Vec<_>>();
+
+    let mut angles = Vec::with_capacity(CIRCLE_RAY_COUNT + segments.len() * 6);
+    angles.extend((0..CIRCLE_RAY_COUNT).map(|i| i as f32 / CIRCLE_RAY_COUNT as f32 * TAU - PI));
+    for (start, end) in &segments {
+        for corner in [start, end] {
+            if corner.length_sq() < MIN_HIT_DISTANCE * MIN_HIT_DISTANCE {
+                continue;
+            }
+            let angle = corner.angle();
+            angles.extend([
+                angle - CORNER_ANGLE_OFFSET,
+                angle,
+                angle + CORNER_ANGLE_OFFSET,
+            ]);
+        }
+    }
+    for angle in &mut angles {
+        if *angle < -PI {
+            *angle += TAU;
+        } else if *angle >= PI {
+            *angle -= TAU;
+        }
+    }
+    angles.sort_by(f32::total_cmp);
+    // One point per angle: aligned corners produce a single point instead of a back and forth along the ray.
+    angles.dedup_by(|a, b| (*a - *b).abs() < 1e-7);
+
+    let mut polygon = angles
+        .into_iter()
+        .map(|angle| {
+            let direction = Vec2::from_angle(angle);
+            let distance = segments
+                .iter()
+                .filter_map(|(start, end)| ray_segment_distance(direction, *start, *end))
+                .fold(radius, f32::min);
+            origin + direction * distance
+        })
+        .collect::<Vec<_>>();
+    remove_redundant_points(&mut polygon);
+    polygon
+}
+
+/// Returns the part of the segment inside the circle of the given radius centered on (0, 0).
+fn clip_segment_to_circle(start: Vec2, end: Vec2, radius: f32) -> Option<(Vec2, Vec2)> {
+    let direction = end - start;
+    let a = direction.length_sq();
+    if a <= f32::EPSILON {
+        // A segment of length 0 does not block anything.
+        return None;
+    }
+    let b = 2.0 * start.dot(&direction);
+    let c = start.length_sq() - radius * radius;
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant <= 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let enter = ((-b - root) / (2.0 * a)).max(0.0);
+    let exit = ((-b + root) / (2.0 * a)).min(1.0);
+    if enter >= exit {
+        return None;
+    }
+    Some((start + direction * enter, start + direction * exit))
+}
+
+/// Returns the distance from (0, 0) at which the ray going in the direction hits the segment.
+fn ray_segment_distance(direction: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
+    let segment = end - start;
+    let denominator = direction.cross(segment);
+    if denominator.abs() <= f32::EPSILON * segment.length() {
+        // A segment aligned with the ray is seen from its side and does not block it.
+        return None;
+    }
+    let distance = start.cross(segment) / denominator;
+    let position_on_segment = start.cross(direction) / denominator;
+    let is_on_segment =
+        (-SEGMENT_END_TOLERANCE..=1.0 + SEGMENT_END_TOLERANCE).contains(&position_on_segment);
+    (is_on_segment && distance > MIN_HIT_DISTANCE).then_some(distance)
+}
+
+/// Removes duplicated points and points in the middle of straight edges, like the ones of the rays cast next to the
+/// corners when the corner does not hide anything.
+fn remove_redundant_points(polygon: &mut Vec<Vec2>) {
+    polygon.dedup_by(|a, b| (*a - *b).length_sq() < 1e-10);
+    while polygon.len() > 1
+        && polygon
+            .first()
+            .zip(polygon.last())
+            .is_some_and(|(a, b)| (*a - *b).length_sq() < 1e-10)
+    {
+        polygon.pop();
+    }
+    let mut index = 0;
+    while polygon.len() > 3 && index < polygon.len() {
+        let count = polygon.len();
+        let previous = polygon[(index + count - 1) % count];
+        let current = polygon[index];
+        let next = polygon[(index + 1) % count];
+        let to_current = current - previous;
+        let to_next = next - current;
+        let is_straight = to_current.dot(&to_next) > 0.0
+            && to_current.cross(to_next).abs() <= 1e-5 * to_current.length() * to_next.length();
+        if is_straight {
+            polygon.remove(index);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon_area(polygon: &[Vec2]) -> f32 {
+        let count = polygon.len();
+        (0..count)
+            .map(|i| polygon[i].cross(polygon[(i + 1) % count]))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    fn box_segments(min: Vec2, max: Vec2) -> Vec<(Vec2, Vec2)> {
+        let corners = [
+            min,
+            Vec2::new(max.x(), min.y()),
+            max,
+            Vec2::new(min.x(), max.y()),
+        ];
+        (0..4).map(|i| (corners[i], corners[(i + 1) % 4])).collect()
+    }
+
+    fn is_inside(polygon: &[Vec2], point: Vec2) -> bool {
+        let count = polygon.len();
+        let mut is_inside = false;
+        for i in 0..count {
+            let (a, b) = (polygon[i], polygon[(i + 1) % count]);
+            if (a.y() > point.y()) != (b.y() > point.y()) {
+                let crossing_x = a.x() + (point.y() - a.y()) / (b.y() - a.y()) * (b.x() - a.x());
+                if point.x() < crossing_x {
+                    is_inside = !is_inside;
+                }
+            }
+        }
+        is_inside
+    }
+
+    fn segments_intersect(a: (Vec2, Vec2), b: (Vec2, Vec2)) -> bool {
+        let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).cross(r - p);
+        let d1 = side(b.0, b.1, a.0);
+        let d2 = side(b.0, b.1, a.1);
+        let d3 = side(a.0, a.1, b.0);
+        let d4 = side(a.0, a.1, b.1);
+        d1 * d2 < -1e-9 && d3 * d4 < -1e-9
+    }
+
+    fn assert_is_simple(polygon: &[Vec2]) {
+        let count = polygon.len();
+        let edge = |i: usize| (polygon[i], polygon[(i + 1) % count]);
+        for i in 0..count {
+            for j in i + 2..count {
+                if i == 0 && j == count - 1 {
+                    continue;
+                }
+                assert!(
+                    !segments_intersect(edge(i), edge(j)),
+                    "Edges {i} and {j} of the polygon intersect"
+                );
+            }
+        }
+    }
+
+    /// Visible area computed by casting many rays, without the sweep.
+    fn brute_force_area(origin: Vec2, radius: f32, segments: &[(Vec2, Vec2)]) -> f32 {
+        const RAY_COUNT: usize = 4096;
+        let segments = segments
+            .iter()
+            .map(|(start, end)| (*start - origin, *end - origin))
+            .collect::<Vec<_>>();
+        let step = TAU / RAY_COUNT as f32;
+        (0..RAY_COUNT)
+            .map(|i| {
+                let direction = Vec2::from_angle(i as f32 * step);
+                let distance = segments
+                    .iter()
+                    .filter_map(|(start, end)| ray_segment_distance(direction, *start, *end))
+                    .fold(radius, f32::min);
+                distance * distance * step / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn nothing_in_view_gives_a_circle() {
+        let polygon = compute_visibility_polygon(Vec2::new(3.0, 4.0), 2.0, &[]);
+        assert_eq!(polygon.len(), CIRCLE_RAY_COUNT);
+        let area = polygon_area(&polygon);
+        assert!(
+            (area - PI * 4.0).abs() < 0.05,
+            "Area of the circle is {area}"
+        );
+    }
+
+    #[test]
+    fn box_hides_what_is_behind_it() {
+        let segments = box_segments(Vec2::new(1.0, -0.5), Vec2::new(2.0, 0.5));
+        let polygon = compute_visibility_polygon(Vec2::zero(), 5.0, &segments);
+        assert_is_simple(&polygon);
+        assert!(is_inside(&polygon, Vec2::new(0.5, 0.0)));
+        assert!(is_inside(&polygon, Vec2::new(0.0, 3.0)));
+        assert!(is_inside(&polygon, Vec2::new(3.0, 2.0)));
+        assert!(!is_inside(&polygon, Vec2::new(1.5, 0.0)));
+        assert!(!is_inside(&polygon, Vec2::new(3.0, 0.0)));
+        assert!(!is_inside(&polygon, Vec2::new(4.0, 1.5)));
+    }
+
+    #[test]
+    fn origin_inside_an_occluder_sees_its_inside() {
+        let segments = box_segments(Vec2::new(-1.0, -1.0), Vec2::new(1.0, 1.0));
+        let polygon = compute_visibility_polygon(Vec2::new(0.2, 0.1), 5.0, &segments);
+        assert_is_simple(&polygon);
+        assert!((polygon_area(&polygon) - 4.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn aligned_corners_do_not_create_self_intersections() {
+        // The corners of the boxes are on the same lines going through the origin.
+        let mut segments = Vec::new();
+        for i in 1..5 {
+            let offset = i as f32;
+            segments.extend(box_segments(
+                Vec2::new(offset, 0.0),
+                Vec2::new(offset + 0.5, 0.5),
+            ));
+            segments.extend(box_segments(
+                Vec2::new(offset, offset),
+                Vec2::new(offset + 0.5, offset + 0.5),
+            ));
+        }
+        // A segment going through the origin.
+        segments.push((Vec2::new(-2.0, 0.0), Vec2::new(2.0, 0.0)));
+        let polygon = compute_visibility_polygon(Vec2::zero(), 10.0, &segments);
+        assert!(polygon.len() >= 3);
+        assert_is_simple(&polygon);
+    }
+
+    #[test]
+    fn random_boxes_match_brute_force_and_only_reduce_the_area() {
+        // Xorshift, to get the same scenes on every run.
+        let mut state = 0x2545_f491_u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        let radius = 10.0;
+        for _ in 0..10 {
+            let origin = Vec2::new(random() * 4.0 - 2.0, random() * 4.0 - 2.0);
+            let mut segments = Vec::new();
+            let mut previous_area = PI * radius * radius;
+            for _ in 0..10 {
+                let min = Vec2::new(random() * 20.0 - 10.0, random() * 20.0 - 10.0);
+                let size = Vec2::new(random() * 3.0 + 0.1, random() * 3.0 + 0.1);
+                segments.extend(box_segments(min, min + size));
+
+                let polygon = compute_visibility_polygon(origin, radius, &segments);
+                assert_is_simple(&polygon);
+                let area = polygon_area(&polygon);
+                let expected_area = brute_force_area(origin, radius, &segments);
+                assert!(
+                    (area - expected_area).abs() <= 0.02 * expected_area + 0.05,
+                    "Area is {area}, brute force gives {expected_area}"
+                );
+                assert!(
+                    area <= previous_area + 0.05,
+                    "Adding a box increased the area"
+                );
+                previous_area = area;
+            }
+        }
+    }
+}
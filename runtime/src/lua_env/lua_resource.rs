@@ -13,10 +13,10 @@ pub fn register_resource_id_methods_on_type<T: ResourceIdWrapper>(
     resources: &Rc<ResourceManager>,
     registry: &mut UserDataRegistry<T>,
 ) {
-    registry.add_meta_function(
-        vectarine_plugin_sdk::mlua::MetaMethod::ToString,
-        |_lua, (id,): (T,)| Ok(format!("Resource({})", id.to_resource_id().get_id())),
-    );
+    registry.add_meta_function(vectarine_plugin_sdk::mlua::MetaMethod::ToString, {
+        let resources = resources.clone();
+        move |_lua, (id,): (T,)| Ok(describe_resource_id(&resources, id.to_resource_id()))
+    });
     registry.add_meta_function(
         vectarine_plugin_sdk::mlua::MetaMethod::Eq,
         |_lua, (id1, id2): (T, T)| {
@@ -38,6 +38,36 @@ pub fn register_resource_id_methods_on_type<T: ResourceIdWrapper>(
     registry.add_method("getId", move |_, id: &T, (): ()| {
         Ok(id.to_resource_id().get_id())
     });
+    registry.add_method("getPath", {
+        let resources = resources.clone();
+        move |_, id: &T, (): ()| Ok(get_resource_path_string(&resources, id.to_resource_id()))
+    });
+    registry.add_method("isValid", {
+        let resources = resources.clone();
+        move |_, id: &T, (): ()| Ok(resources.is_valid_id(id.to_resource_id()))
+    });
+}
+
+/// The text shown when printing a resource id from Lua, like `ResourceId(7): textures/player.png`
+pub fn describe_resource_id(resources: &ResourceManager, id: ResourceId) -> String {
+    match get_resource_path_string(resources, id) {
+        Some(path) => format!("{}: {}", id, path),
+        None => id.to_string(),
+    }
+}
+
+/// The path of the resource, relative to the project, or None if the id does not refer to a resource.
+pub fn get_resource_path_string(resources: &ResourceManager, id: ResourceId) -> Option<String> {
+    if !resources.is_valid_id(id) {
+        return None;
+    }
+    Some(
+        resources
+            .get_holder_by_id(id)
+            .get_path()
+            .to_string_lossy()
+            .replace('\\', "/"),
+    )
 }
 
 /// This macro takes a struct like ScriptResourceId and generates the ResourceIdWrapper, IntoLua and FromLua implementations for it.
@@ -0,0 +1,550 @@
+//! Snapshots of the state of a game: the tables reachable from the globals, with their numbers, strings, vectors and
+//! the motion of the bodies of the physics worlds, saved in a binary blob that can be restored later to rewind the
+//! game or resume it.
+
+use std::{collections::HashMap, ffi::c_void};
+
+use vectarine_plugin_sdk::mlua::{self, IntoLua, Table, Value};
+
+use crate::lua_env::{
+    UNSAFE_INTERNALS_KEY, add_fn_to_table,
+    lua_persist::{load_data_from_kv_store, save_data_in_kv_store},
+    lua_physics::{BodyState, LuaPhysicsWorld2},
+    lua_vec2::Vec2,
+    lua_vec4::Vec4,
+};
+
+/// Tables nested deeper than this cannot be saved, as reading them back would overflow the stack.
+pub const MAX_SNAPSHOT_DEPTH: usize = 256;
+
+const MAGIC: &[u8; 4] = b"VSNP";
+/// Snapshots written by another version of the format are refused instead of being misread.
+const FORMAT_VERSION: u8 = 1;
+
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_TABLE: u8 = 5;
+/// A table already written, by its number. Tables referenced from several places are restored as one table.
+const TAG_TABLE_REF: u8 = 6;
+const TAG_VEC2: u8 = 7;
+const TAG_VEC4: u8 = 8;
+const TAG_VECTOR: u8 = 9;
+const TAG_WORLD: u8 = 10;
+
+/// Whether the value is written in snapshots. Functions, coroutines, read-only tables and the userdata that are not
+/// listed here are not: restoring a snapshot leaves them as they are.
+fn is_saved(value: &Value, excluded: &[*const c_void]) -> bool {
+    match value {
+        Value::Nil
+        | Value::Boolean(_)
+        | Value::Integer(_)
+        | Value::Number(_)
+        | Value::String(_)
+        | Value::Vector(_) => true,
+        Value::Table(table) => !table.is_readonly() && !excluded.contains(&table.to_pointer()),
+        Value::UserData(userdata) => {
+            userdata.is::<Vec2>() || userdata.is::<Vec4>() || userdata.is::<LuaPhysicsWorld2>()
+        }
+        _ => false,
+    }
+}
+
+fn is_saved_key(key: &Value) -> bool {
+    matches!(
+        key,
+        Value::Boolean(_) | Value::Integer(_) | Value::Number(_) | Value::String(_)
+    )
+}
+
+fn corrupted() -> mlua::Error {
+    mlua::Error::runtime("the snapshot is corrupted")
+}
+
+// MARK: Writing
+
+struct Writer<'a> {
+    bytes: Vec<u8>,
+    table_ids: HashMap<*const c_void, u32>,
+    excluded: &'a [*const c_void],
+}
+
+impl Writer<'_> {
+    fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn write_f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn write_value(&mut self, value: &Value, depth: usize) -> mlua::Result<()> {
+        match value {
+            Value::Boolean(false) => self.bytes.push(TAG_FALSE),
+            Value::Boolean(true) => self.bytes.push(TAG_TRUE),
+            Value::Integer(number) => {
+                self.bytes.push(TAG_NUMBER);
+                self.bytes
+                    .extend_from_slice(&(*number as f64).to_le_bytes());
+            }
+            Value::Number(number) => {
+                self.bytes.push(TAG_NUMBER);
+                self.bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            Value::String(string) => {
+                let bytes = string.as_bytes();
+                self.bytes.push(TAG_STRING);
+                self.write_u64(bytes.len() as u64);
+                self.bytes.extend_from_slice(&bytes);
+            }
+            Value::Vector(vector) => {
+                self.bytes.push(TAG_VECTOR);
+                self.write_f32s(&[vector.x(), vector.y(), vector.z()]);
+            }
+            Value::Table(table) => self.write_table(table, depth)?,
+            Value::UserData(userdata) => {
+                if let Ok(vec) = userdata.borrow::<Vec2>() {
+                    self.bytes.push(TAG_VEC2);
+                    self.write_f32s(&vec.0);
+                } else if let Ok(vec) = userdata.borrow::<Vec4>() {
+                    self.bytes.push(TAG_VEC4);
+                    self.write_f32s(&vec.0);
+                } else if let Ok(world) = userdata.borrow::<LuaPhysicsWorld2>() {
+                    let states = world.body_states();
+                    self.bytes.push(TAG_WORLD);
+                    self.write_u64(states.len() as u64);
+                    for state in states {
+                        self.write_u32(state.handle.0);
+                        self.write_u32(state.handle.1);
+                        self.write_f32s(&state.position.0);
+                        self.write_f32s(&[state.rotation]);
+                        self.write_f32s(&state.velocity.0);
+                        self.write_f32s(&[state.angular_velocity]);
+                    }
+                } else {
+                    self.bytes.push(TAG_NIL);
+                }
+            }
+            _ => self.bytes.push(TAG_NIL),
+        }
+        Ok(())
+    }
+
+    fn write_table(&mut self, table: &Table, depth: usize) -> mlua::Result<()> {
+        if let Some(id) = self.table_ids.get(&table.to_pointer()) {
+            let id = *id;
+            self.bytes.push(TAG_TABLE_REF);
+            self.write_u32(id);
+            return Ok(());
+        }
+        if depth >= MAX_SNAPSHOT_DEPTH {
+            return Err(mlua::Error::runtime(format!(
+                "the state is nested deeper than {MAX_SNAPSHOT_DEPTH} tables"
+            )));
+        }
+        let id = self.table_ids.len() as u32;
+        self.table_ids.insert(table.to_pointer(), id);
+        let mut entries = Vec::new();
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            if is_saved_key(&key) && is_saved(&value, self.excluded) {
+                entries.push((key, value));
+            }
+        }
+        self.bytes.push(TAG_TABLE);
+        self.write_u64(entries.len() as u64);
+        for (key, value) in &entries {
+            self.write_value(key, depth + 1)?;
+            self.write_value(value, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Saves the state reachable from `root`. The tables in `excluded`, like the internals of the engine, are not saved.
+pub fn capture_snapshot(root: &Table, excluded: &[*const c_void]) -> mlua::Result<Vec<u8>> {
+    let mut writer = Writer {
+        bytes: MAGIC.to_vec(),
+        table_ids: HashMap::new(),
+        excluded,
+    };
+    writer.bytes.push(FORMAT_VERSION);
+    writer.write_table(root, 0)?;
+    Ok(writer.bytes)
+}
+
+// MARK: Reading
+
+/// A value read back from a snapshot. Tables are numbered in the order in which they were written.
+#[derive(Debug, Clone, PartialEq)]
+enum Saved {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Vec<u8>),
+    Table(usize),
+    Vec2([f32; 2]),
+    Vec4([f32; 4]),
+    Vector([f32; 3]),
+    World(Vec<BodyState>),
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    tables: Vec<Vec<(Saved, Saved)>>,
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, count: usize) -> mlua::Result<&'a [u8]> {
+        let end = self.position.checked_add(count).ok_or_else(corrupted)?;
+        let bytes = self.bytes.get(self.position..end).ok_or_else(corrupted)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> mlua::Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes(
+            bytes.try_into().map_err(|_| corrupted())?,
+        ))
+    }
+
+    /// A count of items, which cannot be more than the bytes left so that a corrupted count does not allocate
+    /// gigabytes.
+    fn read_count(&mut self) -> mlua::Result<usize> {
+        let bytes = self.read_bytes(8)?;
+        let count = u64::from_le_bytes(bytes.try_into().map_err(|_| corrupted())?);
+        usize::try_from(count)
+            .ok()
+            .filter(|count| *count <= self.bytes.len() - self.position)
+            .ok_or_else(corrupted)
+    }
+
+    fn read_f32s<const N: usize>(&mut self) -> mlua::Result<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            let bytes = self.read_bytes(4)?;
+            *value = f32::from_le_bytes(bytes.try_into().map_err(|_| corrupted())?);
+        }
+        Ok(values)
+    }
+
+    fn read_value(&mut self, depth: usize) -> mlua::Result<Saved> {
+        let tag = self.read_bytes(1)?[0];
+        Ok(match tag {
+            TAG_NIL => Saved::Nil,
+            TAG_FALSE => Saved::Boolean(false),
+            TAG_TRUE => Saved::Boolean(true),
+            TAG_NUMBER => {
+                let bytes = self.read_bytes(8)?;
+                Saved::Number(f64::from_le_bytes(
+                    bytes.try_into().map_err(|_| corrupted())?,
+                ))
+            }
+            TAG_STRING => {
+                let length = self.read_count()?;
+                Saved::String(self.read_bytes(length)?.to_vec())
+            }
+            TAG_TABLE => {
+                if depth >= MAX_SNAPSHOT_DEPTH {
+                    return Err(corrupted());
+                }
+                let id = self.tables.len();
+                self.tables.push(Vec::new());
+                let count = self.read_count()?;
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let key = self.read_value(depth + 1)?;
+                    let value = self.read_value(depth + 1)?;
+                    entries.push((key, value));
+                }
+                self.tables[id] = entries;
+                Saved::Table(id)
+            }
+            TAG_TABLE_REF => {
+                let id = self.read_u32()? as usize;
+                if id >= self.tables.len() {
+                    return Err(corrupted());
+                }
+                Saved::Table(id)
+            }
+            TAG_VEC2 => Saved::Vec2(self.read_f32s()?),
+            TAG_VEC4 => Saved::Vec4(self.read_f32s()?),
+            TAG_VECTOR => Saved::Vector(self.read_f32s()?),
+            TAG_WORLD => {
+                let count = self.read_count()?;
+                let mut states = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let handle = (self.read_u32()?, self.read_u32()?);
+                    let [x, y, rotation, vx, vy, angular_velocity] = self.read_f32s()?;
+                    states.push(BodyState {
+                        handle,
+                        position: Vec2::new(x, y),
+                        rotation,
+                        velocity: Vec2::new(vx, vy),
+                        angular_velocity,
+                    });
+                }
+                Saved::World(states)
+            }
+            _ => return Err(corrupted()),
+        })
+    }
+}
+
+struct Restorer<'a> {
+    lua: &'a mlua::Lua,
+    tables: &'a [Vec<(Saved, Saved)>],
+    restored: HashMap<usize, Table>,
+    excluded: &'a [*const c_void],
+}
+
+impl Restorer<'_> {
+    /// Restores `saved` in place of `existing`. Tables are restored inside of the existing tables, so that they keep
+    /// their metatables, their functions and the references to them that the snapshot does not know about.
+    fn restore(&mut self, existing: Value, saved: &Saved) -> mlua::Result<Value> {
+        Ok(match saved {
+            Saved::Nil => Value::Nil,
+            Saved::Boolean(boolean) => Value::Boolean(*boolean),
+            Saved::Number(number) => Value::Number(*number),
+            Saved::String(bytes) => Value::String(self.lua.create_string(bytes)?),
+            Saved::Vec2([x, y]) => Vec2::new(*x, *y).into_lua(self.lua)?,
+            Saved::Vec4([x, y, z, w]) => Vec4::new(*x, *y, *z, *w).into_lua(self.lua)?,
+            Saved::Vector([x, y, z]) => Value::Vector(mlua::Vector::new(*x, *y, *z)),
+            Saved::World(states) => {
+                // Worlds cannot be created from a snapshot, only the bodies of the existing ones are moved back.
+                if let Value::UserData(userdata) = &existing
+                    && let Ok(world) = userdata.borrow::<LuaPhysicsWorld2>()
+                {
+                    world.restore_body_states(states);
+                }
+                existing
+            }
+            Saved::Table(id) => Value::Table(self.restore_table(existing, *id)?),
+        })
+    }
+
+    fn restore_table(&mut self, existing: Value, id: usize) -> mlua::Result<Table> {
+        if let Some(table) = self.restored.get(&id) {
+            return Ok(table.clone());
+        }
+        let table = match existing {
+            Value::Table(table)
+                if !table.is_readonly() && !self.excluded.contains(&table.to_pointer()) =>
+            {
+                table
+            }
+            _ => self.lua.create_table()?,
+        };
+        self.restored.insert(id, table.clone());
+
+        let tables = self.tables;
+        let mut entries = Vec::with_capacity(tables[id].len());
+        for (key, value) in &tables[id] {
+            let key = self.restore(Value::Nil, key)?;
+            let existing_value = table.raw_get::<Value>(key.clone())?;
+            entries.push((key, existing_value, value));
+        }
+        // The values that are not in the snapshot were set after it.
+        let mut stale_keys = Vec::new();
+        for pair in table.pairs::<Value, Value>() {
+            let (key, value) = pair?;
+            if is_saved_key(&key) && is_saved(&value, self.excluded) {
+                stale_keys.push(key);
+            }
+        }
+        for key in stale_keys {
+            table.raw_set(key, Value::Nil)?;
+        }
+        for (key, existing_value, value) in entries {
+            let value = self.restore(existing_value, value)?;
+            table.raw_set(key, value)?;
+        }
+        Ok(table)
+    }
+}
+
+/// Puts the state reachable from `root` back to the one of the snapshot. The values that are not saved in snapshots,
+/// like functions, are kept.
+pub fn restore_snapshot(
+    lua: &mlua::Lua,
+    root: &Table,
+    snapshot: &[u8],
+    excluded: &[*const c_void],
+) -> mlua::Result<()> {
+    let Some(content) = snapshot.strip_prefix(MAGIC.as_slice()) else {
+        return Err(mlua::Error::runtime("this is not a snapshot"));
+    };
+    if content.first() != Some(&FORMAT_VERSION) {
+        return Err(mlua::Error::runtime(
+            "the snapshot was made by another version of Vectarine",
+        ));
+    }
+    let mut reader = Reader {
+        bytes: &content[1..],
+        position: 0,
+        tables: Vec::new(),
+    };
+    let saved_root = reader.read_value(0)?;
+    if !matches!(saved_root, Saved::Table(_)) || reader.position != reader.bytes.len() {
+        return Err(corrupted());
+    }
+    let mut restorer = Restorer {
+        lua,
+        tables: &reader.tables,
+        restored: HashMap::new(),
+        excluded,
+    };
+    restorer.restore(Value::Table(root.clone()), &saved_root)?;
+    Ok(())
+}
+
+// MARK: Lua API
+
+/// The root of the snapshots is the globals, unless a table is given.
+fn snapshot_root(lua: &mlua::Lua, root: Option<Table>) -> (Table, Vec<*const c_void>) {
+    let excluded = lua
+        .globals()
+        .raw_get::<Option<Table>>(UNSAFE_INTERNALS_KEY)
+        .ok()
+        .flatten()
+        .map(|internals| internals.to_pointer())
+        .into_iter()
+        .collect();
+    (root.unwrap_or_else(|| lua.globals()), excluded)
+}
+
+/// Snapshots saved on disk are stored next to the values of `Persist.save`, with a prefix so that their keys do not
+/// collide.
+fn snapshot_store_key(key: &str) -> String {
+    format!("snapshot-{key}")
+}
+
+pub fn setup_snapshot_api(lua: &mlua::Lua) -> mlua::Result<Table> {
+    let snapshot_module = lua.create_table()?;
+
+    add_fn_to_table(
+        lua,
+        &snapshot_module,
+        "capture",
+        |lua, root: Option<Table>| {
+            let (root, excluded) = snapshot_root(lua, root);
+            lua.create_string(capture_snapshot(&root, &excluded)?)
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &snapshot_module,
+        "restore",
+        |lua, (snapshot, root): (mlua::String, Option<Table>)| {
+            let (root, excluded) = snapshot_root(lua, root);
+            restore_snapshot(lua, &root, &snapshot.as_bytes(), &excluded)
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &snapshot_module,
+        "save",
+        |lua, (key, root): (String, Option<Table>)| {
+            let (root, excluded) = snapshot_root(lua, root);
+            let snapshot = capture_snapshot(&root, &excluded)?;
+            save_data_in_kv_store(snapshot_store_key(&key), snapshot.into_boxed_slice());
+            Ok(())
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &snapshot_module,
+        "load",
+        |lua, (key, root): (String, Option<Table>)| {
+            let Some(snapshot) = load_data_from_kv_store(snapshot_store_key(&key)) else {
+                return Ok(false);
+            };
+            let (root, excluded) = snapshot_root(lua, root);
+            restore_snapshot(lua, &root, &snapshot, &excluded)?;
+            Ok(true)
+        },
+    );
+
+    Ok(snapshot_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{capture_snapshot, restore_snapshot};
+    use crate::lua_env::lua_vec2::{self, Vec2};
+    use vectarine_plugin_sdk::mlua::{Lua, Table};
+
+    #[test]
+    fn restoring_a_snapshot_brings_back_the_saved_state() {
+        let lua = Lua::new();
+        let vec = lua_vec2::setup_vec_api(&lua).expect("Vec API");
+        lua.globals().set("Vec", vec).expect("Set Vec");
+        let state = lua
+            .load(
+                r#"
+                local shared = { hits = 1 }
+                local state = {
+                    player = setmetatable({ name = "hero", position = Vec.V2(1, 2), first = shared }, { kind = "player" }),
+                    second = shared,
+                    list = { 1, 2, 3 },
+                    greet = function() return "hello" end,
+                }
+                state.self = state
+                return state
+                "#,
+            )
+            .eval::<Table>()
+            .expect("Valid state");
+        let snapshot = capture_snapshot(&state, &[]).expect("The state can be saved");
+
+        lua.load(
+            r#"
+            local state = ...
+            state.player.name = "villain"
+            state.player.position = Vec.V2(5, 5)
+            state.player.first.hits = 10
+            state.list = nil
+            state.extra = true
+            "#,
+        )
+        .call::<()>(state.clone())
+        .expect("Valid changes");
+        restore_snapshot(&lua, &state, &snapshot, &[]).expect("The snapshot can be restored");
+
+        let (name, position, hits, shared, kind, list, extra, greeting) = lua
+            .load(
+                r#"
+                local state = ...
+                return state.player.name, state.player.position, state.second.hits,
+                    rawequal(state.player.first, state.second), getmetatable(state.player).kind,
+                    #state.list, state.extra, state.greet()
+                "#,
+            )
+            .call::<(String, Vec2, f64, bool, String, usize, Option<bool>, String)>(state.clone())
+            .expect("Valid checks");
+        assert_eq!(name, "hero");
+        assert_eq!(position, Vec2::new(1.0, 2.0));
+        assert_eq!(hits, 1.0);
+        assert!(shared);
+        assert_eq!(kind, "player");
+        assert_eq!(list, 3);
+        assert_eq!(extra, None);
+        assert_eq!(greeting, "hello");
+
+        assert!(restore_snapshot(&lua, &state, &snapshot[..snapshot.len() - 1], &[]).is_err());
+        assert!(restore_snapshot(&lua, &state, b"hello", &[]).is_err());
+    }
+}
@@ -1,19 +1,34 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use vectarine_plugin_sdk::{
     glow::Context,
-    mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods, Value},
+    mlua::{AnyUserData, FromLua, IntoLua, Table, UserDataMethods, Value},
 };
 
 use crate::{
+    console,
     game_resource::{
         self, ResourceId, ResourceManager, Status,
         font_resource::{self, FontRenderingData, FontResource},
+        shader_resource::ShaderLayout,
     },
-    graphics::batchdraw,
-    io,
+    graphics::{
+        batchdraw,
+        textlayout::{TextAlign, TextLayoutOptions, TextSpan, parse_markup},
+    },
+    io::{self, enginefs::DEFAULT_FONT_PATH},
     lua_env::{
+        add_fn_to_table,
+        lua_canvas::{ShaderResourceId, draw_with_shader},
         lua_coord::{ScreenVec, get_pos_as_vec2},
+        lua_resource::{describe_resource_id, get_resource_path_string},
         lua_vec4::{BLACK, Vec4},
     },
 };
@@ -97,63 +112,91 @@ pub fn setup_text_api(
     let default_font_handle = FontResourceId(None);
 
     lua.register_userdata_type::<FontResourceId>(|registry| {
-        registry.add_meta_function(vectarine_plugin_sdk::mlua::MetaMethod::ToString, |_lua, (id,): (FontResourceId,)| {
-            if let Some(id) = id.0{
-                Ok(format!("Resource({})", id.get_id()))
-            }else{
-                Ok("FontResource(default)".to_string())
+        registry.add_meta_function(vectarine_plugin_sdk::mlua::MetaMethod::ToString, {
+            let resources = resources.clone();
+            move |_lua, (id,): (FontResourceId,)| {
+                if let Some(id) = id.0 {
+                    Ok(describe_resource_id(&resources, id))
+                } else {
+                    Ok("FontResource(default)".to_string())
+                }
             }
         });
-        registry.add_meta_function(vectarine_plugin_sdk::mlua::MetaMethod::Eq, |_lua, (id1, id2): (FontResourceId, FontResourceId)| {
-            Ok(id1 == id2)
-        });
+        registry.add_meta_function(
+            vectarine_plugin_sdk::mlua::MetaMethod::Eq,
+            |_lua, (id1, id2): (FontResourceId, FontResourceId)| Ok(id1 == id2),
+        );
         registry.add_method("getStatus", {
             let resources = resources.clone();
             move |_, id: &FontResourceId, (): ()| {
-                if let Some(id) = id.0{
+                if let Some(id) = id.0 {
                     let status = resources.get_holder_by_id(id).get_status();
                     Ok(status.to_string())
-                }else{
+                } else {
                     Ok(Status::Loaded.to_string())
                 }
             }
         });
         registry.add_method("isReady", {
             let resources = resources.clone();
-            move |_, id: &FontResourceId, (): ()|{
+            move |_, id: &FontResourceId, (): ()| {
                 if let Some(id) = id.0 {
                     Ok(resources.get_holder_by_id(id).is_loaded())
-                }else{
+                } else {
                     Ok(true)
                 }
             }
         });
 
         registry.add_method("getId", move |_, id: &FontResourceId, (): ()| {
-            if let Some(id) = id.0{
+            if let Some(id) = id.0 {
                 Ok(id.get_id() as i64)
-            }else{
+            } else {
                 Ok(-1)
             }
         });
+        registry.add_method("getPath", {
+            let resources = resources.clone();
+            move |_, id: &FontResourceId, (): ()| match id.0 {
+                Some(id) => Ok(get_resource_path_string(&resources, id)),
+                None => Ok(Some(DEFAULT_FONT_PATH.to_string())),
+            }
+        });
+        registry.add_method("isValid", {
+            let resources = resources.clone();
+            move |_, id: &FontResourceId, (): ()| {
+                // The default font is built into the engine and is always valid.
+                Ok(id.0.is_none_or(|id| resources.is_valid_id(id)))
+            }
+        });
 
         registry.add_method("drawText", {
             let batch = batch.clone();
             let resources = resources.clone();
-            move |_, font, (text, mpos, lua_size, color): (String, AnyUserData, Value, Option<Vec4>)| {
+            move |_,
+                  font,
+                  (text, mpos, lua_size, color, shader): (
+                String,
+                AnyUserData,
+                Value,
+                Option<Vec4>,
+                Option<ShaderResourceId>,
+            )| {
                 let font_size = value_to_text_size(&lua_size)?;
                 let pos = get_pos_as_vec2(mpos)?;
                 let color = color.unwrap_or(BLACK);
-                let draw_with_renderer = |font_renderer: &mut FontRenderingData|{
+                let draw_with_renderer = |font_renderer: &mut FontRenderingData| {
                     {
                         font_renderer.enrich_atlas(batch.borrow().drawing_target.gl(), &text);
                     }
-                    batch
-                        .borrow_mut()
-                        .draw_text(pos.x(), pos.y(), &text, color.0, font_size, font_renderer);
+                    draw_with_shader(&resources, &batch, shader, ShaderLayout::Texture, |batch| {
+                        batch.draw_text(pos.x(), pos.y(), &text, color.0, font_size, font_renderer)
+                    })
                 };
 
-                if let Some(font_id) = font.0 {
+                if let Some(font_id) = font.0
+                    && !falls_back_to_default_font(&resources, font_id)
+                {
                     let font_resource = resources.get_by_id::<FontResource>(font_id);
                     let Ok(font_resource) = font_resource else {
                         return Ok(());
@@ -162,12 +205,11 @@ pub fn setup_text_api(
                     let Some(font_resource) = font_resource.as_mut() else {
                         return Ok(());
                     };
-                    draw_with_renderer(font_resource);
-                }else{
+                    draw_with_renderer(font_resource)
+                } else {
                     let gl = batch.borrow().drawing_target.gl().clone();
-                    font_resource::use_default_font(&gl, draw_with_renderer);
-                };
-                Ok(())
+                    font_resource::use_default_font(&gl, draw_with_renderer)
+                }
             }
         });
         registry.add_method("measureText", {
@@ -176,24 +218,23 @@ pub fn setup_text_api(
             let batch = batch.clone();
             move |lua, font_resource_id, (text, lua_font_size): (String, Value)| {
                 let font_size = value_to_text_size(&lua_font_size)?;
-                let make_failure_result = ||{
-                    let result = match lua.create_table(){
+                let make_failure_result = || {
+                    let result = match lua.create_table() {
                         Ok(result) => result,
-                        Err(e) => return Err(e)
+                        Err(e) => return Err(e),
                     };
                     result.raw_set("width", 0.0)?;
                     result.raw_set("height", 0.0)?;
                     result.raw_set("bearingY", 0.0)?;
                     Ok(result)
                 };
-                let make_measurement = |font_renderer: &mut FontRenderingData|{
-                    let env_state = env_state.borrow();
-                    let ratio = env_state.window_width as f32 / env_state.window_height as f32;
+                let make_measurement = |font_renderer: &mut FontRenderingData| {
+                    let ratio = env_state.borrow().game_viewport.aspect_ratio();
                     let (width, height, max_ascent) =
                         font_renderer.measure_text(&text, font_size, ratio);
-                    let result = match lua.create_table(){
+                    let result = match lua.create_table() {
                         Ok(result) => result,
-                        Err(e) => return Err(e)
+                        Err(e) => return Err(e),
                     };
                     result.raw_set("width", width)?;
                     result.raw_set("height", height)?;
@@ -201,7 +242,9 @@ pub fn setup_text_api(
                     Ok(result)
                 };
 
-                if let Some(font_id) = font_resource_id.0 {
+                if let Some(font_id) = font_resource_id.0
+                    && !falls_back_to_default_font(&resources, font_id)
+                {
                     let font_resource = resources.get_by_id::<FontResource>(font_id);
                     let Ok(font_resource) = font_resource else {
                         return make_failure_result();
@@ -211,18 +254,191 @@ pub fn setup_text_api(
                         return make_failure_result();
                     };
                     make_measurement(font_resource)
-                }else{
-                    font_resource::use_default_font(batch.borrow().drawing_target.gl(), make_measurement)
+                } else {
+                    font_resource::use_default_font(
+                        batch.borrow().drawing_target.gl(),
+                        make_measurement,
+                    )
                 }
             }
         });
+        registry.add_method("drawRichText", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            move |_, font, (text, mpos, lua_size, options): (String, AnyUserData, Value, Option<Table>)| {
+                let font_size = value_to_text_size(&lua_size)?;
+                let pos = get_pos_as_vec2(mpos)?;
+                let (layout_options, color) = rich_text_options_from_lua(options.as_ref())?;
+                let shader = match &options {
+                    Some(options) => options.get::<Option<ShaderResourceId>>("shader")?,
+                    None => None,
+                };
+                let spans = parse_markup(&text, color.0);
+                with_font_renderer(&resources, &batch, *font, Ok(()), |font_renderer| {
+                    let gl = batch.borrow().drawing_target.gl().clone();
+                    font_renderer.enrich_atlas(&gl, &spans_text(&spans));
+                    let aspect_ratio = batch.borrow().aspect_ratio();
+                    let layout =
+                        font_renderer.layout_text(&spans, font_size, aspect_ratio, &layout_options);
+                    draw_with_shader(&resources, &batch, shader, ShaderLayout::Texture, |batch| {
+                        batch.draw_text_layout(pos.x(), pos.y(), &layout, font_size, font_renderer)
+                    })
+                })
+            }
+        });
+        registry.add_method("measureRichText", {
+            let batch = batch.clone();
+            let resources = resources.clone();
+            let env_state = env_state.clone();
+            move |lua, font, (text, lua_size, options): (String, Value, Option<Table>)| {
+                let font_size = value_to_text_size(&lua_size)?;
+                let (layout_options, color) = rich_text_options_from_lua(options.as_ref())?;
+                let spans = parse_markup(&text, color.0);
+                let (width, height, line_count) =
+                    with_font_renderer(&resources, &batch, *font, (0.0, 0.0, 0), |font_renderer| {
+                        let gl = batch.borrow().drawing_target.gl().clone();
+                        font_renderer.enrich_atlas(&gl, &spans_text(&spans));
+                        let env_state = env_state.borrow();
+                        let ratio = env_state.window_width as f32 / env_state.window_height as f32;
+                        let layout =
+                            font_renderer.layout_text(&spans, font_size, ratio, &layout_options);
+                        (
+                            layout.width,
+                            layout.height(font_size, &layout_options),
+                            layout.line_count,
+                        )
+                    });
+                let result = lua.create_table()?;
+                result.raw_set("width", width)?;
+                result.raw_set("height", height)?;
+                result.raw_set("lineCount", line_count)?;
+                Ok(result)
+            }
+        });
     })?;
 
     text_module.set("font", default_font_handle)?;
 
+    add_fn_to_table(lua, &text_module, "drawFast", {
+        let batch = batch.clone();
+        let resources = resources.clone();
+        move |_,
+              (font, text, mpos, lua_size, color): (
+            FontResourceId,
+            String,
+            AnyUserData,
+            Value,
+            Option<Vec4>,
+        )| {
+            let font_size = value_to_text_size(&lua_size)?;
+            let pos = get_pos_as_vec2(mpos)?;
+            let color = color.unwrap_or(BLACK);
+            let draw_with_renderer = |font_renderer: &mut FontRenderingData| {
+                let mut batch = batch.borrow_mut();
+                font_renderer.enrich_atlas(batch.drawing_target.gl(), &text);
+                batch.draw_text_instanced(
+                    pos.x(),
+                    pos.y(),
+                    &text,
+                    color.0,
+                    font_size,
+                    font_renderer,
+                );
+                Ok(())
+            };
+
+            if let Some(font_id) = font.0
+                && !falls_back_to_default_font(&resources, font_id)
+            {
+                let font_resource = resources.get_by_id::<FontResource>(font_id);
+                let Ok(font_resource) = font_resource else {
+                    return Ok(());
+                };
+                let mut font_resource = font_resource.font_rendering.borrow_mut();
+                let Some(font_resource) = font_resource.as_mut() else {
+                    return Ok(());
+                };
+                draw_with_renderer(font_resource)
+            } else {
+                let gl = batch.borrow().drawing_target.gl().clone();
+                font_resource::use_default_font(&gl, draw_with_renderer)
+            }
+        }
+    });
+
     Ok(text_module)
 }
 
+/// Calls the callback with the font, or with the default font when it failed to load. Returns `not_loaded` while the
+/// font is loading.
+fn with_font_renderer<R>(
+    resources: &ResourceManager,
+    batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
+    font: FontResourceId,
+    not_loaded: R,
+    callback: impl FnOnce(&mut FontRenderingData) -> R,
+) -> R {
+    if let Some(font_id) = font.0
+        && !falls_back_to_default_font(resources, font_id)
+    {
+        let Ok(font_resource) = resources.get_by_id::<FontResource>(font_id) else {
+            return not_loaded;
+        };
+        let mut font_resource = font_resource.font_rendering.borrow_mut();
+        let Some(font_resource) = font_resource.as_mut() else {
+            return not_loaded;
+        };
+        callback(font_resource)
+    } else {
+        let gl = batch.borrow().drawing_target.gl().clone();
+        font_resource::use_default_font(&gl, callback)
+    }
+}
+
+/// Reads the options of `drawRichText` and `measureRichText`, and the color of the text outside of the color tags.
+fn rich_text_options_from_lua(
+    options: Option<&Table>,
+) -> vectarine_plugin_sdk::mlua::Result<(TextLayoutOptions, Vec4)> {
+    let Some(options) = options else {
+        return Ok((TextLayoutOptions::default(), BLACK));
+    };
+    let align = match options.get::<Option<String>>("align")? {
+        Some(name) => TextAlign::from_name(&name).ok_or_else(|| {
+            vectarine_plugin_sdk::mlua::Error::RuntimeError(format!(
+                "Invalid text alignment \"{name}\", expected \"left\", \"center\" or \"right\""
+            ))
+        })?,
+        None => TextAlign::Left,
+    };
+    let layout_options = TextLayoutOptions {
+        max_width: options.get::<Option<f32>>("maxWidth")?,
+        align,
+        line_spacing: options.get::<Option<f32>>("lineSpacing")?.unwrap_or(1.0),
+    };
+    let color = options.get::<Option<Vec4>>("color")?.unwrap_or(BLACK);
+    Ok((layout_options, color))
+}
+
+fn spans_text(spans: &[TextSpan]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+/// Text drawn with a font that failed to load uses the default font instead, so that error messages stay readable
+/// when the assets of the game are missing.
+fn falls_back_to_default_font(resources: &ResourceManager, font_id: ResourceId) -> bool {
+    static IS_FALLBACK_NOTE_SHOWN: AtomicBool = AtomicBool::new(false);
+    let Status::Error(_) = resources.get_holder_by_id(font_id).get_status() else {
+        return false;
+    };
+    if !IS_FALLBACK_NOTE_SHOWN.swap(true, Ordering::Relaxed) {
+        console::print_info(format!(
+            "The font {} failed to load, text is drawn with {DEFAULT_FONT_PATH} instead.",
+            describe_resource_id(resources, font_id)
+        ));
+    }
+    true
+}
+
 fn value_to_text_size(
     value: &vectarine_plugin_sdk::mlua::Value,
 ) -> vectarine_plugin_sdk::mlua::Result<f32> {
@@ -1,6 +1,9 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
-use vectarine_plugin_sdk::mlua::{FromLua, IntoLua, UserDataMethods};
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataMethods};
 
 use crate::{
     game_resource::{
@@ -8,8 +11,9 @@ use crate::{
         tile_resource::{TilemapResource, TilesetContent, TilesetResource},
     },
     lua_env::{
+        add_fn_to_table,
         lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
-        lua_tile::tilemap::GeneratedTilemap,
+        lua_tile::tilemap::{GeneratedTilemap, set_tile_with_autotiling, with_any_tilemap},
         lua_vec2::Vec2,
     },
     make_resource_lua_compatible,
@@ -163,6 +167,33 @@ pub fn setup_tile_api(
     lua.register_userdata_type::<TilemapResourceId>(|registry| {
         register_resource_id_methods_on_type(resources, registry);
         tilemap::register_tilemap_methods_on_type(resources, registry);
+
+        registry.add_method("isLayerSolid", {
+            let resources = resources.clone();
+            move |_lua, tilemap_resource_id, (layer,): (i32,)| {
+                let tilemap_res = resources.get_by_id::<TilemapResource>(tilemap_resource_id.0);
+                Ok(tilemap_res.is_ok_and(|tilemap_res| tilemap_res.is_layer_solid(layer)))
+            }
+        });
+
+        registry.add_method("setLayerSolid", {
+            let resources = resources.clone();
+            move |_lua, tilemap_resource_id, (layer, solid): (i32, bool)| {
+                let tilemap_res = resources
+                    .get_by_id::<TilemapResource>(tilemap_resource_id.0)
+                    .map_err(|_| {
+                        vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                            "Tilemap resource not found".to_string(),
+                        )
+                    })?;
+                tilemap_res
+                    .edits
+                    .borrow_mut()
+                    .solid_layers
+                    .insert(layer, solid);
+                Ok(())
+            }
+        });
     })?;
 
     lua.register_userdata_type::<GeneratedTilemap>(|registry| {
@@ -183,10 +214,56 @@ pub fn setup_tile_api(
             let tilemap = GeneratedTilemap {
                 get_chunk_fn: generator,
                 cache: RefCell::new(std::collections::HashMap::new()),
+                autotile_rules: RefCell::new(std::collections::HashMap::new()),
+                tile_size: Cell::new(Vec2::new(1.0, 1.0)),
             };
             lua.create_any_userdata(tilemap)
         })?,
     )?;
 
+    add_fn_to_table(lua, &tile_module, "getTileAt", {
+        let resources = resources.clone();
+        move |_, (tilemap, layer, x, y): (AnyUserData, i32, i32, i32)| {
+            with_any_tilemap(&tilemap, |tilemap| {
+                Ok(tilemap.get_tile(&resources, layer, x, y).unwrap_or(0))
+            })
+        }
+    });
+
+    add_fn_to_table(lua, &tile_module, "setTileAt", {
+        let resources = resources.clone();
+        move |_, (tilemap, layer, x, y, tile): (AnyUserData, i32, i32, i32, Option<u32>)| {
+            with_any_tilemap(&tilemap, |tilemap| {
+                set_tile_with_autotiling(tilemap, &resources, layer, x, y, tile)
+            })
+        }
+    });
+
+    add_fn_to_table(lua, &tile_module, "worldToTile", {
+        let resources = resources.clone();
+        move |_, (tilemap, position): (AnyUserData, Vec2)| {
+            with_any_tilemap(&tilemap, |tilemap| {
+                let tile_size = tilemap.tile_size(&resources);
+                Ok(Vec2::new(
+                    (position.x() / tile_size.x()).floor(),
+                    (position.y() / tile_size.y()).floor(),
+                ))
+            })
+        }
+    });
+
+    add_fn_to_table(lua, &tile_module, "tileToWorld", {
+        let resources = resources.clone();
+        move |_, (tilemap, tile): (AnyUserData, Vec2)| {
+            with_any_tilemap(&tilemap, |tilemap| {
+                let tile_size = tilemap.tile_size(&resources);
+                Ok(Vec2::new(
+                    tile.x() * tile_size.x(),
+                    tile.y() * tile_size.y(),
+                ))
+            })
+        }
+    });
+
     Ok(tile_module)
 }
@@ -1,11 +1,17 @@
 use std::rc::Rc;
-use std::{cell::RefCell, collections::HashMap};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+};
 
 use vectarine_plugin_sdk::mlua::{self, FromLua, IntoLua, UserDataMethods, UserDataRegistry};
 
 use crate::{
-    game_resource::{ResourceManager, tile_resource::TilemapResource},
-    lua_env::lua_tile::TilemapResourceId,
+    game_resource::{
+        ResourceManager,
+        tile_resource::{AutotileRules, TilemapResource},
+    },
+    lua_env::{lua_tile::TilemapResourceId, lua_vec2::Vec2},
 };
 
 const CHUNK_SIZE: i32 = 16;
@@ -21,7 +27,85 @@ pub trait Tilemap {
         hx: i32,
         hy: i32,
         callback: impl FnMut(u32, i32, i32) -> mlua::Result<()>,
+    ) -> mlua::Result<()>
+    where
+        Self: Sized;
+    /// Change a single tile. `None` erases the tile.
+    fn set_tile(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+        x: i32,
+        y: i32,
+        tile: Option<u32>,
+    ) -> mlua::Result<()>;
+    fn get_autotile_rules(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+    ) -> Option<Rc<AutotileRules>>;
+    fn set_autotile_rules(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+        rules: Option<AutotileRules>,
     ) -> mlua::Result<()>;
+    /// The size of a tile in the world, used to convert world positions to tile coordinates.
+    fn tile_size(&self, resources: &Rc<ResourceManager>) -> Vec2;
+    fn set_tile_size(&self, resources: &Rc<ResourceManager>, tile_size: Vec2) -> mlua::Result<()>;
+}
+
+/// Change a tile, then update the variants of the edited tile and its neighbors if the layer has autotiling rules.
+pub fn set_tile_with_autotiling<T: Tilemap + ?Sized>(
+    tilemap: &T,
+    resources: &Rc<ResourceManager>,
+    layer: i32,
+    x: i32,
+    y: i32,
+    tile: Option<u32>,
+) -> mlua::Result<()> {
+    tilemap.set_tile(resources, layer, x, y, tile)?;
+    let Some(rules) = tilemap.get_autotile_rules(resources, layer) else {
+        return Ok(());
+    };
+    let updates = rules.updated_tiles(x, y, |x, y| tilemap.get_tile(resources, layer, x, y));
+    for (x, y, variant) in updates {
+        tilemap.set_tile(resources, layer, x, y, Some(variant))?;
+    }
+    Ok(())
+}
+
+impl FromLua for AutotileRules {
+    fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+        let mlua::Value::Table(table) = value else {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "AutotileRules".to_string(),
+                message: Some("Expected a table with a variants field".to_string()),
+            });
+        };
+        let use_diagonals = match table.get::<Option<String>>("mode")?.as_deref() {
+            None | Some("4bit") => false,
+            Some("8bit") => true,
+            Some(_) => {
+                return Err(mlua::Error::RuntimeError(
+                    "Invalid autotile mode, expected '4bit' or '8bit'".to_string(),
+                ));
+            }
+        };
+        let variants = table.get::<HashMap<u32, u32>>("variants")?;
+        let mut terrain = table
+            .get::<Option<Vec<u32>>>("terrain")?
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<HashSet<_>>();
+        terrain.extend(variants.values().copied());
+        Ok(AutotileRules {
+            use_diagonals,
+            variants,
+            terrain,
+        })
+    }
 }
 
 /// A generated tilemap is a tilemap that is generated dynamically by a Lua function
@@ -29,6 +113,8 @@ pub trait Tilemap {
 pub struct GeneratedTilemap {
     pub get_chunk_fn: mlua::Function,
     pub cache: RefCell<HashMap<(i32, i32, i32), Vec<u32>>>,
+    pub autotile_rules: RefCell<HashMap<i32, Rc<AutotileRules>>>,
+    pub tile_size: Cell<Vec2>,
 }
 
 impl IntoLua for GeneratedTilemap {
@@ -96,6 +182,64 @@ impl Tilemap for GeneratedTilemap {
         }
         Ok(())
     }
+
+    /// Edits are stored in the chunk cache, so they are lost when the chunk is invalidated.
+    fn set_tile(
+        &self,
+        _resources: &Rc<ResourceManager>,
+        layer: i32,
+        x: i32,
+        y: i32,
+        tile: Option<u32>,
+    ) -> mlua::Result<()> {
+        let chunk_x = x.div_euclid(CHUNK_SIZE);
+        let chunk_y = y.div_euclid(CHUNK_SIZE);
+        if self.ensure_chunk(layer, chunk_x, chunk_y).is_none() {
+            return Err(mlua::Error::RuntimeError(
+                "Unable to generate the chunk containing the tile".to_string(),
+            ));
+        }
+        let mut cache = self.cache.borrow_mut();
+        if let Some(chunk) = cache.get_mut(&(layer, chunk_x, chunk_y)) {
+            let local_x = x.rem_euclid(CHUNK_SIZE) as usize;
+            let local_y = y.rem_euclid(CHUNK_SIZE) as usize;
+            if let Some(cell) = chunk.get_mut(local_y * CHUNK_SIZE as usize + local_x) {
+                *cell = tile.unwrap_or(0);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_autotile_rules(
+        &self,
+        _resources: &Rc<ResourceManager>,
+        layer: i32,
+    ) -> Option<Rc<AutotileRules>> {
+        self.autotile_rules.borrow().get(&layer).cloned()
+    }
+
+    fn set_autotile_rules(
+        &self,
+        _resources: &Rc<ResourceManager>,
+        layer: i32,
+        rules: Option<AutotileRules>,
+    ) -> mlua::Result<()> {
+        let mut autotile_rules = self.autotile_rules.borrow_mut();
+        match rules {
+            Some(rules) => autotile_rules.insert(layer, Rc::new(rules)),
+            None => autotile_rules.remove(&layer),
+        };
+        Ok(())
+    }
+
+    fn tile_size(&self, _resources: &Rc<ResourceManager>) -> Vec2 {
+        self.tile_size.get()
+    }
+
+    fn set_tile_size(&self, _resources: &Rc<ResourceManager>, tile_size: Vec2) -> mlua::Result<()> {
+        self.tile_size.set(tile_size);
+        Ok(())
+    }
 }
 
 impl GeneratedTilemap {
@@ -129,6 +273,9 @@ impl GeneratedTilemap {
 impl Tilemap for TilemapResourceId {
     fn get_tile(&self, resources: &Rc<ResourceManager>, layer: i32, x: i32, y: i32) -> Option<u32> {
         let tilemap_res = resources.get_by_id::<TilemapResource>(self.0).ok()?;
+        if let Some(&edited_tile) = tilemap_res.edits.borrow().tiles.get(&(layer, x, y)) {
+            return edited_tile;
+        }
         let content = tilemap_res.content.borrow();
         let content = content.as_ref()?;
         content
@@ -166,28 +313,104 @@ impl Tilemap for TilemapResourceId {
             ));
         };
 
-        match tile_layer {
+        let edits = tilemap_res.edits.borrow();
+        let base_tile = |x: i32, y: i32| match &tile_layer {
             tiled::TileLayer::Finite(finite_layer) => {
-                for x in lx..hx {
-                    for y in ly..hy {
-                        if let Some(tile) = finite_layer.get_tile_data(x, y) {
-                            callback(tile.id(), x, y)?;
-                        }
-                    }
-                }
+                finite_layer.get_tile_data(x, y).map(|t| t.id())
             }
             tiled::TileLayer::Infinite(infinite_layer) => {
-                for x in lx..hx {
-                    for y in ly..hy {
-                        if let Some(tile) = infinite_layer.get_tile_data(x, y) {
-                            callback(tile.id(), x, y)?;
-                        }
-                    }
+                infinite_layer.get_tile_data(x, y).map(|t| t.id())
+            }
+        };
+        for x in lx..hx {
+            for y in ly..hy {
+                let tile = match edits.tiles.get(&(layer, x, y)) {
+                    Some(&edited_tile) => edited_tile,
+                    None => base_tile(x, y),
+                };
+                if let Some(tile) = tile {
+                    callback(tile, x, y)?;
                 }
             }
         }
         Ok(())
     }
+
+    fn set_tile(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+        x: i32,
+        y: i32,
+        tile: Option<u32>,
+    ) -> mlua::Result<()> {
+        let tilemap_res = resources
+            .get_by_id::<TilemapResource>(self.0)
+            .map_err(|_| mlua::Error::RuntimeError("Tilemap resource not found".to_string()))?;
+        tilemap_res
+            .edits
+            .borrow_mut()
+            .tiles
+            .insert((layer, x, y), tile);
+        Ok(())
+    }
+
+    fn get_autotile_rules(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+    ) -> Option<Rc<AutotileRules>> {
+        let tilemap_res = resources.get_by_id::<TilemapResource>(self.0).ok()?;
+        let edits = tilemap_res.edits.borrow();
+        edits.autotile_rules.get(&layer).cloned()
+    }
+
+    fn set_autotile_rules(
+        &self,
+        resources: &Rc<ResourceManager>,
+        layer: i32,
+        rules: Option<AutotileRules>,
+    ) -> mlua::Result<()> {
+        let tilemap_res = resources
+            .get_by_id::<TilemapResource>(self.0)
+            .map_err(|_| mlua::Error::RuntimeError("Tilemap resource not found".to_string()))?;
+        let autotile_rules = &mut tilemap_res.edits.borrow_mut().autotile_rules;
+        match rules {
+            Some(rules) => autotile_rules.insert(layer, Rc::new(rules)),
+            None => autotile_rules.remove(&layer),
+        };
+        Ok(())
+    }
+
+    fn tile_size(&self, resources: &Rc<ResourceManager>) -> Vec2 {
+        resources
+            .get_by_id::<TilemapResource>(self.0)
+            .map(|tilemap_res| tilemap_res.tile_size())
+            .unwrap_or(Vec2::new(1.0, 1.0))
+    }
+
+    fn set_tile_size(&self, resources: &Rc<ResourceManager>, tile_size: Vec2) -> mlua::Result<()> {
+        let tilemap_res = resources
+            .get_by_id::<TilemapResource>(self.0)
+            .map_err(|_| mlua::Error::RuntimeError("Tilemap resource not found".to_string()))?;
+        tilemap_res.edits.borrow_mut().tile_size = Some(tile_size);
+        Ok(())
+    }
+}
+
+/// Calls `f` with the tilemap resource or the generated tilemap inside of the userdata.
+pub fn with_any_tilemap<R>(
+    tilemap: &mlua::AnyUserData,
+    f: impl FnOnce(&dyn Tilemap) -> mlua::Result<R>,
+) -> mlua::Result<R> {
+    if let Ok(tilemap_id) = tilemap.borrow::<TilemapResourceId>() {
+        return f(&*tilemap_id);
+    }
+    tilemap
+        .borrow_scoped::<GeneratedTilemap, mlua::Result<R>>(|generated_tilemap| {
+            f(generated_tilemap)
+        })
+        .flatten()
 }
 
 pub fn register_tilemap_methods_on_type<T: Tilemap + 'static>(
@@ -218,4 +441,35 @@ pub fn register_tilemap_methods_on_type<T: Tilemap + 'static>(
             })
         }
     });
+
+    registry.add_method("setTile", {
+        let resources = resources.clone();
+        move |_lua, this, (layer, x, y, tile): (i32, i32, i32, Option<u32>)| {
+            set_tile_with_autotiling(this, &resources, layer, x, y, tile)
+        }
+    });
+
+    registry.add_method("setAutotileRules", {
+        let resources = resources.clone();
+        move |_lua, this, (layer, rules): (i32, Option<AutotileRules>)| {
+            this.set_autotile_rules(&resources, layer, rules)
+        }
+    });
+
+    registry.add_method("getTileSize", {
+        let resources = resources.clone();
+        move |_lua, this, (): ()| Ok(this.tile_size(&resources))
+    });
+
+    registry.add_method("setTileSize", {
+        let resources = resources.clone();
+        move |_lua, this, tile_size: Vec2| {
+            if !(tile_size.x() > 0.0 && tile_size.y() > 0.0) {
+                return Err(mlua::Error::RuntimeError(
+                    "The tile size must be positive".to_string(),
+                ));
+            }
+            this.set_tile_size(&resources, tile_size)
+        }
+    });
 }
@@ -0,0 +1,265 @@
+use std::f32::consts::{PI, TAU};
+
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua, UserDataFields, UserDataMethods};
+
+use crate::{
+    auto_impl_lua_copy,
+    lua_env::{add_fn_to_table, lua_physics::Object2, lua_vec2::Vec2},
+};
+
+/// A position, a rotation and a scale, applied in the order scale, rotation, translation.
+/// This is enough to place sprites relative to each other without building matrices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform2 {
+    pub position: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+auto_impl_lua_copy!(Transform2, Transform2);
+
+impl Transform2 {
+    pub fn identity() -> Self {
+        Self {
+            position: Vec2::zero(),
+            rotation: 0.0,
+            scale: Vec2::new(1.0, 1.0),
+        }
+    }
+
+    /// Maps a point from the local space of the transform to its parent space.
+    pub fn apply(&self, point: Vec2) -> Vec2 {
+        self.position + (point * self.scale).rotated(self.rotation)
+    }
+
+    /// Returns the transform applying `other` first and then `self`, like a child `other` attached to a parent `self`.
+    /// A rotation followed by a non-uniform scale cannot be represented by a `Transform2`, so the result is only exact
+    /// when the scale of `self` is uniform or when `other` is not rotated.
+    pub fn compose(&self, other: &Self) -> Self {
+        Self {
+            position: self.apply(other.position),
+            rotation: self.rotation + other.rotation,
+            scale: self.scale * other.scale,
+        }
+    }
+
+    /// Returns the transform mapping points back to the local space. Exact when the scale is uniform.
+    /// The components of the scale equal to 0 give infinite values, as the transform cannot be inverted.
+    pub fn inverse(&self) -> Self {
+        let scale = Vec2::new(1.0, 1.0) / self.scale;
+        Self {
+            position: self.position.rotated(-self.rotation) * scale * -1.0,
+            rotation: -self.rotation,
+            scale,
+        }
+    }
+
+    /// Interpolates the position and the scale linearly and the rotation along the shortest way around the circle.
+    pub fn lerp(&self, other: &Self, k: f32) -> Self {
+        let rotation_delta = (other.rotation - self.rotation + PI).rem_euclid(TAU) - PI;
+        Self {
+            position: self.position.lerp(other.position, k),
+            rotation: self.rotation + rotation_delta * k,
+            scale: self.scale.lerp(other.scale, k),
+        }
+    }
+}
+
+impl Default for Transform2 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl vectarine_plugin_sdk::mlua::UserData for Transform2 {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("position", |_, transform| Ok(transform.position));
+        fields.add_field_method_set("position", |_, transform, position: Vec2| {
+            transform.position = position;
+            Ok(())
+        });
+        fields.add_field_method_get("rotation", |_, transform| Ok(transform.rotation));
+        fields.add_field_method_set("rotation", |_, transform, rotation: f32| {
+            transform.rotation = rotation;
+            Ok(())
+        });
+        fields.add_field_method_get("scale", |_, transform| Ok(transform.scale));
+        fields.add_field_method_set("scale", |_, transform, scale: Vec2| {
+            transform.scale = scale;
+            Ok(())
+        });
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("apply", |_, transform, (point,): (Vec2,)| {
+            Ok(transform.apply(point))
+        });
+        methods.add_method("compose", |_, transform, (other,): (Transform2,)| {
+            Ok(transform.compose(&other))
+        });
+        methods.add_method("inverse", |_, transform, ()| Ok(transform.inverse()));
+        methods.add_method("lerp", |_, transform, (other, k): (Transform2, f32)| {
+            Ok(transform.lerp(&other, k))
+        });
+        methods.add_meta_method(
+            vectarine_plugin_sdk::mlua::MetaMethod::ToString,
+            |_, transform, _any: vectarine_plugin_sdk::mlua::Value| {
+                Ok(format!(
+                    "Transform2(position = V2({}, {}), rotation = {}, scale = V2({}, {}))",
+                    transform.position.x(),
+                    transform.position.y(),
+                    transform.rotation,
+                    transform.scale.x(),
+                    transform.scale.y()
+                ))
+            },
+        );
+    }
+}
+
+pub fn setup_transform_api(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let transform_module = lua.create_table()?;
+
+    add_fn_to_table(
+        lua,
+        &transform_module,
+        "new",
+        |_, (position, rotation, scale): (Option<Vec2>, Option<f32>, Option<Vec2>)| {
+            Ok(Transform2 {
+                position: position.unwrap_or(Vec2::zero()),
+                rotation: rotation.unwrap_or(0.0),
+                scale: scale.unwrap_or(Vec2::new(1.0, 1.0)),
+            })
+        },
+    );
+
+    add_fn_to_table(
+        lua,
+        &transform_module,
+        "fromObject",
+        |_, object: AnyUserData| {
+            let object = object.borrow::<Object2>()?;
+            let (Some(position), Some(rotation)) = (object.position(), object.rotation()) else {
+                return Err(vectarine_plugin_sdk::mlua::Error::RuntimeError(
+                    "Object2 is out of this world".to_string(),
+                ));
+            };
+            Ok(Transform2 {
+                position,
+                rotation,
+                scale: Vec2::new(1.0, 1.0),
+            })
+        },
+    );
+
+    Ok(transform_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(
+            (a - b).length() <= 1e-3 * (1.0 + b.length()),
+            "V2({}, {}) is not close to V2({}, {})",
+            a.x(),
+            a.y(),
+            b.x(),
+            b.y()
+        );
+    }
+
+    /// Returns random transforms with a uniform scale, for which compose and inverse are exact.
+    fn random_transforms(count: usize) -> Vec<(Transform2, Vec2)> {
+        // Xorshift, to get the same transforms on every run.
+        let mut state = 0x1234_5678_u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+        (0..count)
+            .map(|_| {
+                let scale = random() * 4.0 + 0.25;
+                let transform = Transform2 {
+                    position: Vec2::new(random() * 20.0 - 10.0, random() * 20.0 - 10.0),
+                    rotation: random() * 4.0 * PI - 2.0 * PI,
+                    scale: Vec2::new(scale, scale),
+                };
+                let point = Vec2::new(random() * 20.0 - 10.0, random() * 20.0 - 10.0);
+                (transform, point)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn apply_scales_then_rotates_then_translates() {
+        let transform = Transform2 {
+            position: Vec2::new(10.0, 0.0),
+            rotation: PI / 2.0,
+            scale: Vec2::new(2.0, 3.0),
+        };
+        assert_close(
+            transform.apply(Vec2::new(1.0, 1.0)),
+            Vec2::new(10.0 - 3.0, 2.0),
+        );
+    }
+
+    #[test]
+    fn composing_with_the_inverse_gives_the_identity() {
+        for (transform, point) in random_transforms(200) {
+            assert_close(transform.compose(&transform.inverse()).apply(point), point);
+            assert_close(transform.inverse().compose(&transform).apply(point), point);
+        }
+    }
+
+    #[test]
+    fn apply_distributes_over_compose() {
+        let transforms = random_transforms(201);
+        for pair in transforms.windows(2) {
+            let (parent, point) = pair[0];
+            let (child, _) = pair[1];
+            assert_close(
+                parent.compose(&child).apply(point),
+                parent.apply(child.apply(point)),
+            );
+        }
+    }
+
+    #[test]
+    fn unrotated_children_compose_exactly_with_non_uniform_scale() {
+        let parent = Transform2 {
+            position: Vec2::new(1.0, 2.0),
+            rotation: 0.7,
+            scale: Vec2::new(2.0, 0.5),
+        };
+        let child = Transform2 {
+            position: Vec2::new(-3.0, 4.0),
+            rotation: 0.0,
+            scale: Vec2::new(1.5, 3.0),
+        };
+        let point = Vec2::new(0.3, -2.0);
+        assert_close(
+            parent.compose(&child).apply(point),
+            parent.apply(child.apply(point)),
+        );
+    }
+
+    #[test]
+    fn lerp_takes_the_shortest_rotation() {
+        let start = Transform2 {
+            rotation: PI - 0.1,
+            ..Transform2::identity()
+        };
+        let end = Transform2 {
+            rotation: -PI + 0.1,
+            ..Transform2::identity()
+        };
+        let middle = start.lerp(&end, 0.5);
+        assert!((middle.rotation - PI).abs() < 1e-4);
+        assert_eq!(start.lerp(&end, 0.0), start);
+    }
+}
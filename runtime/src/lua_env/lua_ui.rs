@@ -1,11 +1,13 @@
 mod column_widget;
 mod generic_widget;
 mod image_widget;
+mod immediate_menu;
 mod row_widget;
 mod scrollable_area_widget;
 mod slider_widget;
 mod stack_widget;
 mod tab_widget;
+mod text_entry;
 mod text_widget;
 
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
@@ -14,6 +16,7 @@ use crate::auto_impl_lua_clone;
 use crate::graphics::batchdraw;
 use crate::graphics::shape::Quad;
 use crate::io::IoEnvState;
+use crate::lua_env::lua_ease::Easing;
 use vectarine_plugin_sdk::mlua::{self, userdata::UserDataMethods};
 use vectarine_plugin_sdk::mlua::{FromLua, IntoLua};
 
@@ -22,11 +25,15 @@ use crate::{game_resource, io, lua_env::lua_vec2::Vec2};
 use column_widget::Column;
 use generic_widget::GenericWidget;
 use image_widget::ImageWidget;
+use immediate_menu::{Direction, MenuFrame, MenuHandle, MenuState, MenuStyle};
 use row_widget::Row;
 use scrollable_area_widget::ScrollableArea;
 use slider_widget::Slider;
 use stack_widget::Stack;
 use tab_widget::{TabTransitionStyle, TabWidget};
+use text_entry::{
+    KeyboardLayout, TextEntryFrame, TextEntryState, TextEntryStyle, update_and_draw_text_entry,
+};
 use text_widget::TextWidget;
 
 // MARK: Widget Trait
@@ -241,6 +248,59 @@ fn parse_alignment_from_table(table: &mlua::Table) -> Alignment {
     }
 }
 
+/// Reads the `pages`, `pageNames` and `labels` options of a text entry. Rows are strings with a key per character,
+/// or lists of strings for keys with several characters.
+fn parse_keyboard_layout_from_table(table: &mlua::Table) -> mlua::Result<KeyboardLayout> {
+    let default_layout = KeyboardLayout::default_layout();
+    let layout = match table.raw_get::<Option<mlua::Table>>("pages")? {
+        None => default_layout,
+        Some(pages_table) => {
+            let mut pages = Vec::new();
+            for page in pages_table.sequence_values::<mlua::Table>() {
+                let mut rows = Vec::new();
+                for row in page?.sequence_values::<mlua::Value>() {
+                    let keys = match row? {
+                        mlua::Value::String(row) => {
+                            row.to_str()?.chars().map(|c| c.to_string()).collect()
+                        }
+                        mlua::Value::Table(row) => row
+                            .sequence_values::<String>()
+                            .collect::<mlua::Result<Vec<_>>>()?,
+                        other => {
+                            return Err(mlua::Error::external(format!(
+                                "textEntry: the rows of pages must be strings or lists of strings, got {}",
+                                other.type_name()
+                            )));
+                        }
+                    };
+                    rows.push(keys);
+                }
+                pages.push(rows);
+            }
+            let page_names = match table.raw_get::<Option<mlua::Table>>("pageNames")? {
+                Some(names) => names
+                    .sequence_values::<String>()
+                    .collect::<mlua::Result<Vec<_>>>()?,
+                None => (1..=pages.len()).map(|page| page.to_string()).collect(),
+            };
+            KeyboardLayout::new(pages, page_names)
+        }
+    };
+    let Some(labels) = table.raw_get::<Option<mlua::Table>>("labels")? else {
+        return Ok(layout);
+    };
+    let label = |name: &str, default: &str| {
+        labels
+            .raw_get::<Option<String>>(name)
+            .map(|label| label.unwrap_or_else(|| default.to_string()))
+    };
+    Ok(layout.with_labels(
+        label("space", "Space")?,
+        label("backspace", "Delete")?,
+        label("done", "Done")?,
+    ))
+}
+
 pub fn setup_ui_api(
     lua: &vectarine_plugin_sdk::mlua::Lua,
     batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
@@ -343,7 +403,8 @@ pub fn setup_ui_api(
                             ));
                         }
                     };
-                    Some((duration, style))
+                    let easing: Option<Easing> = anim_table.raw_get("easing")?;
+                    Some((duration, style, easing))
                 } else {
                     None
                 };
@@ -588,5 +649,157 @@ pub fn setup_ui_api(
         })?
     })?;
 
+    ui_module.raw_set("textEntry", {
+        let batch = batch.clone();
+        let env_state = env_state.clone();
+        let resources = _resources.clone();
+        let gl = batch.borrow().drawing_target.gl().clone();
+        let states = RefCell::new(HashMap::<String, TextEntryState>::new());
+        lua.create_function(
+            move |_lua, (id, pos, options): (String, Vec2, Option<mlua::Table>)| {
+                let options = match options {
+                    Some(options) => options,
+                    None => _lua.create_table()?,
+                };
+                let color = |name: &str, default: [f32; 4]| {
+                    options
+                        .raw_get::<Option<crate::lua_env::lua_vec4::Vec4>>(name)
+                        .map(|color| color.map_or(default, |color| color.0))
+                };
+                let layout = parse_keyboard_layout_from_table(&options)?;
+                let frame = TextEntryFrame {
+                    pos,
+                    size: options
+                        .raw_get::<Option<Vec2>>("size")?
+                        .unwrap_or(Vec2::new(1.2, 0.8)),
+                    max_length: options.raw_get::<Option<usize>>("maxLength")?,
+                    font_id: options
+                        .raw_get::<Option<crate::lua_env::lua_text::FontResourceId>>("font")?
+                        .unwrap_or_else(crate::lua_env::lua_text::FontResourceId::default_font),
+                    style: TextEntryStyle {
+                        background_color: color("backgroundColor", [0.1, 0.1, 0.1, 0.9])?,
+                        key_color: color("keyColor", [0.25, 0.25, 0.25, 1.0])?,
+                        selected_key_color: color("selectedKeyColor", [0.2, 0.45, 0.8, 1.0])?,
+                        text_color: color("textColor", [1.0, 1.0, 1.0, 1.0])?,
+                    },
+                    layout: &layout,
+                };
+
+                let mut states = states.borrow_mut();
+                let state = match states.entry(id.clone()) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        entry.insert(TextEntryState::new(
+                            options
+                                .raw_get::<Option<String>>("initialText")?
+                                .unwrap_or_default(),
+                        ))
+                    }
+                };
+                let committed =
+                    update_and_draw_text_entry(state, &frame, &batch, &env_state, &gl, &resources)?;
+                if committed.is_some() {
+                    // The next text entry with this id starts from the initial text again.
+                    states.remove(&id);
+                }
+                Ok(committed)
+            },
+        )?
+    })?;
+
+    lua.register_userdata_type::<MenuHandle>(|registry| {
+        registry.add_method("button", |_, menu, label: String| {
+            menu.with_frame(|frame| frame.button(&label))
+        });
+        registry.add_method(
+            "checkbox",
+            |_, menu, (label, is_checked): (String, bool)| {
+                menu.with_frame(|frame| frame.checkbox(&label, is_checked))
+            },
+        );
+        registry.add_method(
+            "slider",
+            |_, menu, (label, value, min, max, step): (String, f32, f32, f32, Option<f32>)| {
+                menu.with_frame(|frame| frame.slider(&label, value, min, max, step))
+            },
+        );
+        registry.add_method(
+            "textField",
+            |_, menu, (text, placeholder): (String, Option<String>)| {
+                menu.with_frame(|frame| frame.text_field(&text, placeholder.as_deref()))
+            },
+        );
+        registry.add_method("label", |_, menu, text: String| {
+            menu.with_frame(|frame| frame.label(&text))
+        });
+        for (name, direction) in [("row", Direction::Row), ("column", Direction::Column)] {
+            registry.add_method(name, move |_, menu, build: mlua::Function| {
+                menu.with_frame(|frame| frame.layout.begin(direction))?;
+                let result = build.call::<()>(());
+                menu.with_frame(|frame| frame.layout.end())?;
+                result
+            });
+        }
+    })?;
+
+    ui_module.raw_set("menu", {
+        let batch = batch.clone();
+        let env_state = env_state.clone();
+        let resources = _resources.clone();
+        let gl = batch.borrow().drawing_target.gl().clone();
+        let states = RefCell::new(HashMap::<String, MenuState>::new());
+        lua.create_function(
+            move |lua,
+                  (id, pos, build, options): (
+                String,
+                Vec2,
+                mlua::Function,
+                Option<mlua::Table>,
+            )| {
+                let options = match options {
+                    Some(options) => options,
+                    None => lua.create_table()?,
+                };
+                let color = |name: &str, default: [f32; 4]| {
+                    options
+                        .raw_get::<Option<crate::lua_env::lua_vec4::Vec4>>(name)
+                        .map(|color| color.map_or(default, |color| color.0))
+                };
+                let style = MenuStyle {
+                    control_size: options
+                        .raw_get::<Option<Vec2>>("size")?
+                        .unwrap_or(Vec2::new(0.6, 0.1)),
+                    gap: options.raw_get::<Option<f32>>("gap")?.unwrap_or(0.02),
+                    font_id: options
+                        .raw_get::<Option<crate::lua_env::lua_text::FontResourceId>>("font")?
+                        .unwrap_or_else(crate::lua_env::lua_text::FontResourceId::default_font),
+                    control_color: color("controlColor", [0.2, 0.2, 0.2, 0.9])?,
+                    focused_color: color("focusedColor", [0.2, 0.45, 0.8, 1.0])?,
+                    accent_color: color("accentColor", [0.95, 0.65, 0.2, 1.0])?,
+                    text_color: color("textColor", [1.0, 1.0, 1.0, 1.0])?,
+                };
+
+                let state = states.borrow_mut().remove(&id).unwrap_or_default();
+                let menu = MenuHandle(Rc::new(RefCell::new(Some(MenuFrame::new(
+                    state,
+                    pos,
+                    style,
+                    batch.clone(),
+                    env_state.clone(),
+                    gl.clone(),
+                    resources.clone(),
+                )))));
+                let result = build.call::<()>(lua.create_any_userdata(menu.clone())?);
+                // The menu cannot be used once its function returned.
+                let Some(frame) = menu.0.borrow_mut().take() else {
+                    return result.map(|()| Vec2::new(0.0, 0.0));
+                };
+                let (state, size) = frame.finish();
+                states.borrow_mut().insert(id, state);
+                result.map(|()| size)
+            },
+        )?
+    })?;
+
     Ok(ui_module)
 }
@@ -60,7 +60,7 @@ impl ImageWidget {
         let widget_h = self.size.y();
 
         let io = io_env.borrow();
-        let window_ratio = io.window_width as f32 / io.window_height as f32;
+        let window_ratio = io.game_viewport.aspect_ratio();
         drop(io);
 
         let image_ratio = img_width / img_height;
@@ -216,7 +216,7 @@ impl VectarineWidget for ImageWidget {
         let (draw_w, draw_h, draw_x, draw_y) = if self.preserve_aspect_ratio {
             let img_ratio = img_w / img_h;
             let io = io_env.borrow();
-            let window_ratio = io.window_width as f32 / io.window_height as f32;
+            let window_ratio = io.game_viewport.aspect_ratio();
             drop(io);
 
             // The widget size is in screen-ratio coords, so we need to account for window ratio
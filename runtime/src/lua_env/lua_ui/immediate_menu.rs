@@ -0,0 +1,731 @@
+//! `Ui.menu`: menus whose controls are declared every frame inside of a function. The controls are placed in rows and
+//! columns, drawn right away and return what the player did with them. The focus moves with the mouse, the keyboard
+//! and the gamepads.
+
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::mlua;
+use vectarine_plugin_sdk::sdl2::{controller::Axis, controller::Button, keyboard::Scancode};
+
+use crate::game_resource::ResourceManager;
+use crate::graphics::{batchdraw, textlayout::TextAlign};
+use crate::io::{IoEnvState, TextInputRequest, gamepad};
+use crate::lua_env::lua_text::FontResourceId;
+use crate::lua_env::lua_vec2::Vec2;
+
+/// How far the stick needs to be pushed to move the focus.
+const STICK_THRESHOLD: f32 = 0.5;
+/// Sliders without a step move by this fraction of their range with the keyboard and the gamepads.
+const DEFAULT_SLIDER_STEPS: f32 = 20.0;
+
+/// What the keyboard and the gamepads do to a menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuInput {
+    Previous,
+    Next,
+    /// Moves the focused slider, or the focus when the focused control is not a slider.
+    Decrease,
+    Increase,
+    Confirm,
+    /// Stops editing a text field.
+    Cancel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Column,
+    Row,
+}
+
+struct Container {
+    direction: Direction,
+    top_left: Vec2,
+    /// The size taken by the controls placed so far.
+    extent: Vec2,
+    is_empty: bool,
+}
+
+impl Container {
+    fn new(direction: Direction, top_left: Vec2) -> Self {
+        Self {
+            direction,
+            top_left,
+            extent: Vec2::new(0.0, 0.0),
+            is_empty: true,
+        }
+    }
+
+    /// The top-left corner of the next control.
+    fn next_top_left(&self, gap: f32) -> Vec2 {
+        let gap = if self.is_empty { 0.0 } else { gap };
+        match self.direction {
+            Direction::Column => {
+                Vec2::new(self.top_left.x(), self.top_left.y() - self.extent.y() - gap)
+            }
+            Direction::Row => {
+                Vec2::new(self.top_left.x() + self.extent.x() + gap, self.top_left.y())
+            }
+        }
+    }
+}
+
+/// Places the controls of a menu from its top-left corner, in nested rows and columns.
+pub struct MenuLayout {
+    gap: f32,
+    /// The containers being filled, the innermost last. The first one is the menu.
+    containers: Vec<Container>,
+}
+
+impl MenuLayout {
+    pub fn new(top_left: Vec2, gap: f32) -> Self {
+        Self {
+            gap,
+            containers: vec![Container::new(Direction::Column, top_left)],
+        }
+    }
+
+    /// Returns the bottom-left corner of a control of this size, the corner used to draw it.
+    pub fn place(&mut self, size: Vec2) -> Vec2 {
+        let gap = self.gap;
+        let container = self
+            .containers
+            .last_mut()
+            .expect("The menu is always a container");
+        let top_left = container.next_top_left(gap);
+        let used_gap = if container.is_empty { 0.0 } else { gap };
+        let extent = container.extent;
+        container.extent = match container.direction {
+            Direction::Column => {
+                Vec2::new(extent.x().max(size.x()), extent.y() + used_gap + size.y())
+            }
+            Direction::Row => Vec2::new(extent.x() + used_gap + size.x(), extent.y().max(size.y())),
+        };
+        container.is_empty = false;
+        Vec2::new(top_left.x(), top_left.y() - size.y())
+    }
+
+    /// The controls placed until `end` are in a new row or column, at the position of the next control.
+    pub fn begin(&mut self, direction: Direction) {
+        let top_left = self
+            .containers
+            .last()
+            .expect("The menu is always a container")
+            .next_top_left(self.gap);
+        self.containers.push(Container::new(direction, top_left));
+    }
+
+    pub fn end(&mut self) {
+        if self.containers.len() < 2 {
+            return;
+        }
+        let Some(container) = self.containers.pop() else {
+            return;
+        };
+        if !container.is_empty {
+            self.place(container.extent);
+        }
+    }
+
+    /// The size of the controls placed in the menu.
+    pub fn size(&self) -> Vec2 {
+        self.containers[0].extent
+    }
+}
+
+/// The state of a menu, kept between frames by its id.
+#[derive(Clone, Debug, Default)]
+pub struct MenuState {
+    /// The index of the focused control, in the order they are declared.
+    pub focused: Option<usize>,
+    /// The number of controls at the last frame, for the focus to wrap around.
+    control_count: usize,
+    /// The index of the text field being edited.
+    editing: Option<usize>,
+    dragged_slider: Option<usize>,
+    /// The mouse only moves the focus when it moves, so that it does not fight with the keyboard.
+    last_mouse: Option<(f32, f32)>,
+    /// The direction of the stick at the last frame, so that holding the stick moves the focus only once.
+    held_stick_direction: Option<MenuInput>,
+    /// Whether the text input was active before a text field was edited, to leave it as it was.
+    was_text_input_active: bool,
+}
+
+impl MenuState {
+    /// Moves the focus to the previous or next control, wrapping around.
+    pub fn move_focus(&mut self, input: MenuInput) {
+        let count = self.control_count;
+        if count == 0 {
+            self.focused = None;
+            return;
+        }
+        self.focused = Some(match (self.focused, input) {
+            (None, MenuInput::Previous) => count - 1,
+            (None, MenuInput::Next) => 0,
+            (Some(focused), MenuInput::Previous) => (focused.min(count - 1) + count - 1) % count,
+            (Some(focused), MenuInput::Next) => (focused + 1) % count,
+            _ => return,
+        });
+    }
+}
+
+/// Snaps the value of a slider to its steps, inside of its range.
+pub fn snap_slider_value(value: f32, min: f32, max: f32, step: Option<f32>) -> f32 {
+    let (low, high) = (min.min(max), min.max(max));
+    let Some(step) = step.filter(|step| *step > 0.0) else {
+        return value.clamp(low, high);
+    };
+    let steps = ((value - min) / step).round();
+    (min + steps * step).clamp(low, high)
+}
+
+pub struct MenuStyle {
+    pub control_size: Vec2,
+    pub gap: f32,
+    pub font_id: FontResourceId,
+    pub control_color: [f32; 4],
+    pub focused_color: [f32; 4],
+    pub accent_color: [f32; 4],
+    pub text_color: [f32; 4],
+}
+
+/// A menu while its function runs. The controls read the inputs of the frame and draw themselves.
+pub struct MenuFrame {
+    state: MenuState,
+    pub layout: MenuLayout,
+    style: MenuStyle,
+    /// The inputs not used yet by a control.
+    inputs: Vec<MenuInput>,
+    mouse: Vec2,
+    is_mouse_moved: bool,
+    is_left_just_pressed: bool,
+    is_left_down: bool,
+    typed_text: String,
+    composed_text: String,
+    is_backspace_pressed: bool,
+    /// The index of the next control.
+    control_index: usize,
+    batch: Rc<RefCell<batchdraw::BatchDraw2d>>,
+    env_state: Rc<RefCell<IoEnvState>>,
+    gl: Arc<glow::Context>,
+    resources: Rc<ResourceManager>,
+}
+
+/// A control placed by `MenuFrame::next_control`.
+struct Control {
+    index: usize,
+    pos: Vec2,
+    size: Vec2,
+    is_focused: bool,
+    is_mouse_inside: bool,
+    is_clicked: bool,
+}
+
+impl MenuFrame {
+    pub fn new(
+        mut state: MenuState,
+        top_left: Vec2,
+        style: MenuStyle,
+        batch: Rc<RefCell<batchdraw::BatchDraw2d>>,
+        env_state: Rc<RefCell<IoEnvState>>,
+        gl: Arc<glow::Context>,
+        resources: Rc<ResourceManager>,
+    ) -> Self {
+        let io = env_state.borrow();
+        let mut inputs = collect_inputs(&mut state, &io);
+        inputs.retain(|input| match input {
+            MenuInput::Previous | MenuInput::Next => {
+                state.move_focus(*input);
+                false
+            }
+            _ => true,
+        });
+        let mouse = Vec2::new(io.mouse_state.x, io.mouse_state.y);
+        let is_mouse_moved = state.last_mouse != Some((mouse.x(), mouse.y()));
+        state.last_mouse = Some((mouse.x(), mouse.y()));
+        let is_backspace_pressed = io
+            .keyboard_just_pressed_state
+            .get(&Scancode::Backspace)
+            .copied()
+            == Some(true);
+        let frame = Self {
+            layout: MenuLayout::new(top_left, style.gap),
+            inputs,
+            mouse,
+            is_mouse_moved,
+            is_left_just_pressed: io.mouse_state.is_left_just_pressed,
+            is_left_down: io.mouse_state.is_left_down,
+            typed_text: io.text_input.clone(),
+            composed_text: io
+                .text_composition
+                .as_ref()
+                .map(|composition| composition.text.clone())
+                .unwrap_or_default(),
+            is_backspace_pressed,
+            control_index: 0,
+            state,
+            style,
+            batch,
+            env_state: env_state.clone(),
+            gl,
+            resources,
+        };
+        drop(io);
+        frame
+    }
+
+    /// Returns the state to keep for the next frame and the size of the menu.
+    pub fn finish(mut self) -> (MenuState, Vec2) {
+        // Left and right move the focus when no slider used them.
+        for input in std::mem::take(&mut self.inputs) {
+            match input {
+                MenuInput::Decrease => self.state.move_focus(MenuInput::Previous),
+                MenuInput::Increase => self.state.move_focus(MenuInput::Next),
+                _ => {}
+            }
+        }
+        let count = self.control_index;
+        self.state.control_count = count;
+        self.state.focused = self.state.focused.filter(|focused| *focused < count);
+        if !self.is_left_down {
+            self.state.dragged_slider = None;
+        }
+        if self.state.editing.is_some_and(|editing| editing >= count) {
+            self.stop_editing();
+        }
+        (self.state, self.layout.size())
+    }
+
+    fn take_input(&mut self, input: MenuInput) -> bool {
+        let Some(index) = self.inputs.iter().position(|other| *other == input) else {
+            return false;
+        };
+        self.inputs.remove(index);
+        true
+    }
+
+    fn next_control(&mut self) -> Control {
+        let size = self.style.control_size;
+        let pos = self.layout.place(size);
+        let index = self.control_index;
+        self.control_index += 1;
+        let offset = self.mouse - pos;
+        let is_mouse_inside =
+            (0.0..=size.x()).contains(&offset.x()) && (0.0..=size.y()).contains(&offset.y());
+        let is_clicked = is_mouse_inside && self.is_left_just_pressed;
+        if is_mouse_inside && (self.is_mouse_moved || is_clicked) {
+            self.state.focused = Some(index);
+        }
+        Control {
+            index,
+            pos,
+            size,
+            is_focused: self.state.focused == Some(index),
+            is_mouse_inside,
+            is_clicked,
+        }
+    }
+
+    fn background_color(&self, control: &Control) -> [f32; 4] {
+        if control.is_focused {
+            self.style.focused_color
+        } else {
+            self.style.control_color
+        }
+    }
+
+    fn draw_rect(&self, pos: Vec2, size: Vec2, color: [f32; 4]) {
+        self.batch
+            .borrow_mut()
+            .draw_rect(pos.x(), pos.y(), size.x(), size.y(), color);
+    }
+
+    /// Draws text in the middle of the height of the area, at half of its height.
+    fn draw_text(&self, text: &str, pos: Vec2, size: Vec2, align: TextAlign, color: [f32; 4]) {
+        let font_size = size.y() * 0.5;
+        let padding = size.y() * 0.25;
+        let aspect_ratio = {
+            let io = self.env_state.borrow();
+            io.window_width as f32 / io.window_height as f32
+        };
+        self.style
+            .font_id
+            .get_font_resource(&self.gl, &self.resources, |font| {
+                font.enrich_atlas(&self.gl, text);
+                let baseline = font.get_max_baseline_height(font_size);
+                let x = match align {
+                    TextAlign::Left => pos.x() + padding,
+                    TextAlign::Center => {
+                        let (width, _, _) = font.measure_text(text, font_size, aspect_ratio);
+                        pos.x() + (size.x() - width) / 2.0
+                    }
+                    TextAlign::Right => {
+                        let (width, _, _) = font.measure_text(text, font_size, aspect_ratio);
+                        pos.x() + size.x() - padding - width
+                    }
+                };
+                self.batch.borrow_mut().draw_text(
+                    x,
+                    pos.y() + (size.y() - font_size) / 2.0 + baseline,
+                    text,
+                    color,
+                    font_size,
+                    font,
+                );
+            });
+    }
+
+    /// Returns true when the button is clicked or confirmed.
+    pub fn button(&mut self, label: &str) -> bool {
+        let control = self.next_control();
+        let is_pressed =
+            control.is_clicked || (control.is_focused && self.take_input(MenuInput::Confirm));
+        let color = if is_pressed || (control.is_mouse_inside && self.is_left_down) {
+            self.style.accent_color
+        } else {
+            self.background_color(&control)
+        };
+        self.draw_rect(control.pos, control.size, color);
+        self.draw_text(
+            label,
+            control.pos,
+            control.size,
+            TextAlign::Center,
+            self.style.text_color,
+        );
+        is_pressed
+    }
+
+    /// Returns the new state of the checkbox.
+    pub fn checkbox(&mut self, label: &str, is_checked: bool) -> bool {
+        let control = self.next_control();
+        let is_toggled =
+            control.is_clicked || (control.is_focused && self.take_input(MenuInput::Confirm));
+        let is_checked = is_checked != is_toggled;
+        self.draw_rect(control.pos, control.size, self.background_color(&control));
+
+        let box_size = control.size.y() * 0.5;
+        let box_pos = control.pos + Vec2::new(control.size.y() * 0.25, control.size.y() * 0.25);
+        let mut box_color = self.style.text_color;
+        if is_checked {
+            box_color = self.style.accent_color;
+        } else {
+            box_color[3] *= 0.3;
+        }
+        self.draw_rect(box_pos, Vec2::new(box_size, box_size), box_color);
+        let label_offset = Vec2::new(control.size.y() * 0.5, 0.0);
+        self.draw_text(
+            label,
+            control.pos + label_offset,
+            control.size - label_offset,
+            TextAlign::Left,
+            self.style.text_color,
+        );
+        is_checked
+    }
+
+    /// Returns the new value of the slider. It is dragged with the mouse, and moved with left and right when focused.
+    pub fn slider(
+        &mut self,
+        label: &str,
+        value: f32,
+        min: f32,
+        max: f32,
+        step: Option<f32>,
+    ) -> f32 {
+        let control = self.next_control();
+        if control.is_clicked {
+            self.state.dragged_slider = Some(control.index);
+        }
+        let mut value = value;
+        if self.state.dragged_slider == Some(control.index) && self.is_left_down {
+            let ratio = ((self.mouse.x() - control.pos.x()) / control.size.x()).clamp(0.0, 1.0);
+            value = min + ratio * (max - min);
+        }
+        if control.is_focused {
+            let key_step = step
+                .filter(|step| *step > 0.0)
+                .unwrap_or((max - min) / DEFAULT_SLIDER_STEPS);
+            if self.take_input(MenuInput::Decrease) {
+                value -= key_step;
+            }
+            if self.take_input(MenuInput::Increase) {
+                value += key_step;
+            }
+        }
+        let value = snap_slider_value(value, min, max, step);
+
+        self.draw_rect(control.pos, control.size, self.background_color(&control));
+        let ratio = if max != min {
+            ((value - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let mut fill_color = self.style.accent_color;
+        fill_color[3] *= 0.6;
+        self.draw_rect(
+            control.pos,
+            Vec2::new(control.size.x() * ratio, control.size.y()),
+            fill_color,
+        );
+        self.draw_text(
+            label,
+            control.pos,
+            control.size,
+            TextAlign::Left,
+            self.style.text_color,
+        );
+        value
+    }
+
+    /// Returns the new text of the field. Clicking it or confirming it starts editing it, with the keyboard.
+    pub fn text_field(&mut self, text: &str, placeholder: Option<&str>) -> String {
+        let control = self.next_control();
+        let mut text = text.to_string();
+        let mut is_editing = self.state.editing == Some(control.index);
+        let is_confirmed = control.is_focused && self.take_input(MenuInput::Confirm);
+        if is_editing {
+            let is_clicked_outside = self.is_left_just_pressed && !control.is_mouse_inside;
+            let is_cancelled = self.take_input(MenuInput::Cancel);
+            if is_confirmed || is_cancelled || is_clicked_outside || !control.is_focused {
+                self.stop_editing();
+                is_editing = false;
+            } else {
+                text.extend(self.typed_text.chars().filter(|c| !c.is_control()));
+                if self.is_backspace_pressed {
+                    text.pop();
+                }
+            }
+        } else if control.is_clicked || is_confirmed {
+            self.start_editing(control.index);
+            is_editing = true;
+        }
+
+        self.draw_rect(control.pos, control.size, self.background_color(&control));
+        if is_editing {
+            let shown = format!("{text}{}_", self.composed_text);
+            self.draw_text(
+                &shown,
+                control.pos,
+                control.size,
+                TextAlign::Left,
+                self.style.text_color,
+            );
+        } else if text.is_empty()
+            && let Some(placeholder) = placeholder
+        {
+            let mut color = self.style.text_color;
+            color[3] *= 0.5;
+            self.draw_text(
+                placeholder,
+                control.pos,
+                control.size,
+                TextAlign::Left,
+                color,
+            );
+        } else {
+            self.draw_text(
+                &text,
+                control.pos,
+                control.size,
+                TextAlign::Left,
+                self.style.text_color,
+            );
+        }
+        text
+    }
+
+    /// Text that cannot be focused.
+    pub fn label(&mut self, text: &str) {
+        let pos = self.layout.place(self.style.control_size);
+        self.draw_text(
+            text,
+            pos,
+            self.style.control_size,
+            TextAlign::Left,
+            self.style.text_color,
+        );
+    }
+
+    /// Starts the text input of the platform, which shows the keyboard of phones.
+    fn start_editing(&mut self, index: usize) {
+        let mut io = self.env_state.borrow_mut();
+        self.state.editing = Some(index);
+        self.state.was_text_input_active = io.is_text_input_active;
+        io.text_input_request = Some(TextInputRequest::Start(None));
+        io.is_text_input_active = true;
+    }
+
+    fn stop_editing(&mut self) {
+        self.state.editing = None;
+        if self.state.was_text_input_active {
+            return;
+        }
+        let mut io = self.env_state.borrow_mut();
+        io.text_input_request = Some(TextInputRequest::Stop);
+        io.is_text_input_active = false;
+    }
+}
+
+/// The menu given to the function of `Ui.menu`. It can only be used while the function runs.
+#[derive(Clone)]
+pub struct MenuHandle(pub Rc<RefCell<Option<MenuFrame>>>);
+
+impl MenuHandle {
+    pub fn with_frame<R>(&self, f: impl FnOnce(&mut MenuFrame) -> R) -> mlua::Result<R> {
+        let mut frame = self.0.borrow_mut();
+        let frame = frame.as_mut().ok_or_else(|| {
+            mlua::Error::RuntimeError(
+                "The menu can only be used inside of the function given to Ui.menu".to_string(),
+            )
+        })?;
+        Ok(f(frame))
+    }
+}
+
+/// Reads the inputs of the frame that move the focus and use the controls. Only confirming and cancelling are read
+/// from the keyboard while a text field is edited.
+fn collect_inputs(state: &mut MenuState, io: &IoEnvState) -> Vec<MenuInput> {
+    let mut inputs = Vec::new();
+    let stick = gamepad::with_gamepads(|gamepads| {
+        const BUTTONS: [(Button, MenuInput); 6] = [
+            (Button::DPadUp, MenuInput::Previous),
+            (Button::DPadDown, MenuInput::Next),
+            (Button::DPadLeft, MenuInput::Decrease),
+            (Button::DPadRight, MenuInput::Increase),
+            (Button::A, MenuInput::Confirm),
+            (Button::B, MenuInput::Cancel),
+        ];
+        for gamepad in gamepads.values() {
+            for (button, input) in BUTTONS {
+                if gamepad.buttons_just_pressed.contains(&button) {
+                    inputs.push(input);
+                }
+            }
+        }
+        gamepads
+            .values()
+            .map(|gamepad| Vec2::new(gamepad.axis(Axis::LeftX), gamepad.axis(Axis::LeftY)))
+            .max_by(|a, b| a.length_sq().total_cmp(&b.length_sq()))
+    });
+    inputs.extend(stick_input(&mut state.held_stick_direction, stick));
+
+    const EDITING_KEYS: [(Scancode, MenuInput); 3] = [
+        (Scancode::Return, MenuInput::Confirm),
+        (Scancode::KpEnter, MenuInput::Confirm),
+        (Scancode::Escape, MenuInput::Cancel),
+    ];
+    const KEYS: [(Scancode, MenuInput); 9] = [
+        (Scancode::Up, MenuInput::Previous),
+        (Scancode::Down, MenuInput::Next),
+        (Scancode::Tab, MenuInput::Next),
+        (Scancode::Left, MenuInput::Decrease),
+        (Scancode::Right, MenuInput::Increase),
+        (Scancode::Return, MenuInput::Confirm),
+        (Scancode::KpEnter, MenuInput::Confirm),
+        (Scancode::Space, MenuInput::Confirm),
+        (Scancode::Escape, MenuInput::Cancel),
+    ];
+    let keys: &[(Scancode, MenuInput)] = if state.editing.is_some() {
+        &EDITING_KEYS
+    } else {
+        &KEYS
+    };
+    for (key, input) in keys {
+        if io.keyboard_just_pressed_state.get(key).copied() == Some(true) {
+            inputs.push(*input);
+        }
+    }
+    inputs
+}
+
+/// Converts the position of the stick to an input, only when the stick starts being pushed in a direction.
+fn stick_input(held_direction: &mut Option<MenuInput>, stick: Option<Vec2>) -> Option<MenuInput> {
+    let direction = stick.and_then(|stick| {
+        if stick.x().abs().max(stick.y().abs()) < STICK_THRESHOLD {
+            None
+        } else if stick.x().abs() > stick.y().abs() {
+            Some(if stick.x() > 0.0 {
+                MenuInput::Increase
+            } else {
+                MenuInput::Decrease
+            })
+        } else {
+            // Down is positive on the sticks.
+            Some(if stick.y() > 0.0 {
+                MenuInput::Next
+            } else {
+                MenuInput::Previous
+            })
+        }
+    });
+    let is_new_direction = direction != *held_direction;
+    *held_direction = direction;
+    direction.filter(|_| is_new_direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner(pos: Vec2) -> (f32, f32) {
+        (pos.x(), pos.y())
+    }
+
+    #[test]
+    fn controls_are_placed_in_rows_and_columns() {
+        let mut layout = MenuLayout::new(Vec2::new(0.0, 1.0), 0.5);
+        let size = Vec2::new(2.0, 1.0);
+        // The returned corner is the bottom-left one.
+        assert_eq!(corner(layout.place(size)), (0.0, 0.0));
+        layout.begin(Direction::Row);
+        assert_eq!(corner(layout.place(size)), (0.0, -1.5));
+        assert_eq!(corner(layout.place(size)), (2.5, -1.5));
+        layout.begin(Direction::Column);
+        assert_eq!(corner(layout.place(size)), (5.0, -1.5));
+        assert_eq!(corner(layout.place(size)), (5.0, -3.0));
+        layout.end();
+        layout.end();
+        // The row is as tall as its column.
+        assert_eq!(corner(layout.place(size)), (0.0, -4.5));
+        assert_eq!(corner(layout.size()), (7.0, 5.5));
+    }
+
+    #[test]
+    fn focus_wraps_around_the_controls() {
+        let mut state = MenuState {
+            control_count: 3,
+            ..MenuState::default()
+        };
+        state.move_focus(MenuInput::Previous);
+        assert_eq!(state.focused, Some(2));
+        state.move_focus(MenuInput::Next);
+        assert_eq!(state.focused, Some(0));
+        state.move_focus(MenuInput::Confirm);
+        assert_eq!(state.focused, Some(0));
+        state.control_count = 0;
+        state.move_focus(MenuInput::Next);
+        assert_eq!(state.focused, None);
+    }
+
+    #[test]
+    fn slider_values_snap_to_their_steps() {
+        assert_eq!(snap_slider_value(0.37, 0.0, 1.0, Some(0.25)), 0.25);
+        assert_eq!(snap_slider_value(7.0, 0.0, 5.0, Some(2.0)), 5.0);
+        assert_eq!(snap_slider_value(-1.0, 0.0, 1.0, None), 0.0);
+        assert_eq!(snap_slider_value(0.5, 0.0, 1.0, Some(0.0)), 0.5);
+    }
+
+    #[test]
+    fn holding_the_stick_moves_once() {
+        let mut held = None;
+        let down = Some(Vec2::new(0.1, 0.9));
+        assert_eq!(stick_input(&mut held, down), Some(MenuInput::Next));
+        assert_eq!(stick_input(&mut held, down), None);
+        assert_eq!(stick_input(&mut held, None), None);
+        assert_eq!(
+            stick_input(&mut held, Some(Vec2::new(-0.8, 0.0))),
+            Some(MenuInput::Decrease)
+        );
+    }
+}
@@ -4,6 +4,7 @@ use crate::game_resource::ResourceManager;
 use crate::graphics::batchdraw;
 use crate::graphics::glstencil::draw_with_mask;
 use crate::io::IoEnvState;
+use crate::lua_env::lua_ease::Easing;
 use crate::lua_env::lua_vec2::Vec2;
 use vectarine_plugin_sdk::glow;
 use vectarine_plugin_sdk::mlua;
@@ -26,6 +27,7 @@ pub struct TabTransitionState {
     pub duration: f32,
     pub start_time: Instant,
     pub style: TabTransitionStyle,
+    pub easing: Option<Easing>,
 }
 
 pub struct TabWidget {
@@ -41,18 +43,19 @@ impl TabWidget {
     pub fn set_active_tab(
         &mut self,
         tab_name: String,
-        transition: Option<(f32, TabTransitionStyle)>,
+        transition: Option<(f32, TabTransitionStyle, Option<Easing>)>,
     ) {
         if tab_name == self.current_tab {
             return;
         }
         let old_tab = self.current_tab.clone();
         self.current_tab = tab_name;
-        self.transition = transition.map(|(duration, style)| TabTransitionState {
+        self.transition = transition.map(|(duration, style, easing)| TabTransitionState {
             old_tab,
             duration,
             start_time: Instant::now(),
             style,
+            easing,
         });
     }
 }
@@ -86,17 +89,26 @@ impl VectarineWidget for TabWidget {
         // Compute transition progress from wall-clock time
         let transition_info = self.transition.as_ref().map(|t| {
             let progress = (t.start_time.elapsed().as_secs_f32() / t.duration).min(1.0);
-            (t.old_tab.clone(), t.style.clone(), progress)
+            (
+                t.old_tab.clone(),
+                t.style.clone(),
+                t.easing.clone(),
+                progress,
+            )
         });
 
         // Clear completed transitions
-        if matches!(&transition_info, Some((_, _, p)) if *p >= 1.0) {
+        if matches!(&transition_info, Some((_, _, _, p)) if *p >= 1.0) {
             self.transition = None;
         }
 
-        if let Some((old_tab_key, style, progress)) = transition_info
+        if let Some((old_tab_key, style, easing, progress)) = transition_info
             && progress < 1.0
         {
+            let progress = match easing {
+                Some(easing) => easing.ease(progress)?,
+                None => progress,
+            };
             let widget_size = self.size();
             let w = widget_size.x();
             let h = widget_size.y();
@@ -0,0 +1,602 @@
+use std::{cell::RefCell, sync::Arc};
+
+use vectarine_plugin_sdk::glow;
+use vectarine_plugin_sdk::mlua;
+use vectarine_plugin_sdk::sdl2::{controller::Axis, controller::Button, keyboard::Scancode};
+
+use crate::game_resource::ResourceManager;
+use crate::graphics::batchdraw;
+use crate::io::{IoEnvState, gamepad};
+use crate::lua_env::lua_text::FontResourceId;
+use crate::lua_env::lua_vec2::Vec2;
+
+/// How far the stick needs to be pushed to move the selection.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// What a key of the on-screen keyboard does.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryKey {
+    Character(String),
+    /// Shows the page with this index.
+    Page(usize),
+    Space,
+    Backspace,
+    Done,
+}
+
+pub struct KeyboardLayout {
+    /// The rows of keys of each page. The last row of each page contains the keys to change page, space,
+    /// backspace and done.
+    pages: Vec<Vec<Vec<EntryKey>>>,
+    page_names: Vec<String>,
+    space_label: String,
+    backspace_label: String,
+    done_label: String,
+}
+
+impl KeyboardLayout {
+    /// `pages` contains the characters of the keys of each row of each page.
+    pub fn new(pages: Vec<Vec<Vec<String>>>, page_names: Vec<String>) -> Self {
+        let page_count = pages.len();
+        let pages = pages
+            .into_iter()
+            .enumerate()
+            .map(|(page_index, rows)| {
+                let mut rows = rows
+                    .into_iter()
+                    .filter(|row| !row.is_empty())
+                    .map(|row| row.into_iter().map(EntryKey::Character).collect())
+                    .collect::<Vec<Vec<_>>>();
+                let mut control_row = (0..page_count)
+                    .filter(|other_page| *other_page != page_index)
+                    .map(EntryKey::Page)
+                    .collect::<Vec<_>>();
+                control_row.extend([EntryKey::Space, EntryKey::Backspace, EntryKey::Done]);
+                rows.push(control_row);
+                rows
+            })
+            .collect();
+        Self {
+            pages,
+            page_names,
+            space_label: "Space".to_string(),
+            backspace_label: "Delete".to_string(),
+            done_label: "Done".to_string(),
+        }
+    }
+
+    /// Lowercase letters, uppercase letters and symbols, with a QWERTY layout.
+    pub fn default_layout() -> Self {
+        let rows = |rows: &[&str]| {
+            rows.iter()
+                .map(|row| row.chars().map(|c| c.to_string()).collect())
+                .collect()
+        };
+        Self::new(
+            vec![
+                rows(&["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm-'"]),
+                rows(&["1234567890", "QWERTYUIOP", "ASDFGHJKL", "ZXCVBNM-'"]),
+                rows(&["!?@#$%&*()", "_+=/\\|~^<>", ".,:;\"[]{}"]),
+            ],
+            vec!["abc".to_string(), "ABC".to_string(), "#+=".to_string()],
+        )
+    }
+
+    pub fn with_labels(mut self, space: String, backspace: String, done: String) -> Self {
+        self.space_label = space;
+        self.backspace_label = backspace;
+        self.done_label = done;
+        self
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn rows(&self, page: usize) -> &[Vec<EntryKey>] {
+        self.pages.get(page).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn label<'a>(&'a self, key: &'a EntryKey) -> &'a str {
+        match key {
+            EntryKey::Character(character) => character,
+            EntryKey::Page(page) => self
+                .page_names
+                .get(*page)
+                .map(String::as_str)
+                .unwrap_or("?"),
+            EntryKey::Space => &self.space_label,
+            EntryKey::Backspace => &self.backspace_label,
+            EntryKey::Done => &self.done_label,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NavigationInput {
+    Up,
+    Down,
+    Left,
+    Right,
+    /// Presses the selected key.
+    Press,
+    Backspace,
+    NextPage,
+    PreviousPage,
+    Done,
+}
+
+/// The state of a text entry, kept between frames by its id.
+#[derive(Clone, Debug, Default)]
+pub struct TextEntryState {
+    pub text: String,
+    pub page: usize,
+    pub row: usize,
+    pub column: usize,
+    /// The direction of the stick at the last frame, so that holding the stick moves the selection only once.
+    held_stick_direction: Option<NavigationInput>,
+}
+
+impl TextEntryState {
+    pub fn new(text: String) -> Self {
+        Self {
+            text,
+            ..Self::default()
+        }
+    }
+
+    /// Applies the input. Returns the text when the user selects "done".
+    pub fn handle_input(
+        &mut self,
+        layout: &KeyboardLayout,
+        input: NavigationInput,
+        max_length: Option<usize>,
+    ) -> Option<String> {
+        let rows = layout.rows(self.page);
+        if rows.is_empty() {
+            return None;
+        }
+        match input {
+            NavigationInput::Up => self.move_to_row(rows, (self.row + rows.len() - 1) % rows.len()),
+            NavigationInput::Down => self.move_to_row(rows, (self.row + 1) % rows.len()),
+            NavigationInput::Left => {
+                let row_length = rows[self.row].len();
+                self.column = (self.column + row_length - 1) % row_length;
+            }
+            NavigationInput::Right => {
+                self.column = (self.column + 1) % rows[self.row].len();
+            }
+            NavigationInput::Press => {
+                let key = rows[self.row][self.column].clone();
+                return self.press(layout, &key, max_length);
+            }
+            NavigationInput::Backspace => {
+                self.text.pop();
+            }
+            NavigationInput::NextPage => {
+                self.set_page(layout, (self.page + 1) % layout.page_count());
+            }
+            NavigationInput::PreviousPage => {
+                let page_count = layout.page_count();
+                self.set_page(layout, (self.page + page_count - 1) % page_count);
+            }
+            NavigationInput::Done => return Some(self.text.clone()),
+        }
+        None
+    }
+
+    /// Adds text typed on a physical keyboard. Control characters are ignored.
+    pub fn type_text(&mut self, text: &str, max_length: Option<usize>) {
+        for character in text.chars().filter(|c| !c.is_control()) {
+            if max_length.is_some_and(|max_length| self.text.chars().count() >= max_length) {
+                return;
+            }
+            self.text.push(character);
+        }
+    }
+
+    fn press(
+        &mut self,
+        layout: &KeyboardLayout,
+        key: &EntryKey,
+        max_length: Option<usize>,
+    ) -> Option<String> {
+        match key {
+            EntryKey::Character(character) => self.type_text(character, max_length),
+            EntryKey::Space => self.type_text(" ", max_length),
+            EntryKey::Backspace => {
+                self.text.pop();
+            }
+            EntryKey::Page(page) => {
+                // The selection stays on the control row, where the key to come back is.
+                let column = self.column;
+                self.set_page(layout, *page);
+                let rows = layout.rows(self.page);
+                self.row = rows.len().saturating_sub(1);
+                self.column = column.min(rows.last().map_or(0, |row| row.len().saturating_sub(1)));
+            }
+            EntryKey::Done => return Some(self.text.clone()),
+        }
+        None
+    }
+
+    fn set_page(&mut self, layout: &KeyboardLayout, page: usize) {
+        self.page = page;
+        let rows = layout.rows(page);
+        self.row = self.row.min(rows.len().saturating_sub(1));
+        self.column = self.column.min(
+            rows.get(self.row)
+                .map_or(0, |row| row.len().saturating_sub(1)),
+        );
+    }
+
+    /// Rows have different lengths, so the column is moved to the key at the same horizontal position.
+    fn move_to_row(&mut self, rows: &[Vec<EntryKey>], row: usize) {
+        let previous_length = rows[self.row].len().max(1);
+        let new_length = rows[row].len();
+        let center = (self.column as f32 + 0.5) / previous_length as f32;
+        self.column = ((center * new_length as f32) as usize).min(new_length.saturating_sub(1));
+        self.row = row;
+    }
+
+    /// Converts the position of the stick to a move, only when the stick starts being pushed in a direction.
+    fn stick_input(&mut self, stick: Option<Vec2>) -> Option<NavigationInput> {
+        let direction = stick.and_then(|stick| {
+            if stick.x().abs().max(stick.y().abs()) < STICK_THRESHOLD {
+                None
+            } else if stick.x().abs() > stick.y().abs() {
+                Some(if stick.x() > 0.0 {
+                    NavigationInput::Right
+                } else {
+                    NavigationInput::Left
+                })
+            } else {
+                // Down is positive on the sticks.
+                Some(if stick.y() > 0.0 {
+                    NavigationInput::Down
+                } else {
+                    NavigationInput::Up
+                })
+            }
+        });
+        let is_new_direction = direction != self.held_stick_direction;
+        self.held_stick_direction = direction;
+        direction.filter(|_| is_new_direction)
+    }
+
+    pub fn selected_key<'a>(&self, layout: &'a KeyboardLayout) -> Option<&'a EntryKey> {
+        layout.rows(self.page).get(self.row)?.get(self.column)
+    }
+}
+
+/// Reads the inputs of the frame that control the text entry: gamepads, arrow keys and the typed text.
+fn collect_inputs(
+    state: &mut TextEntryState,
+    io: &IoEnvState,
+) -> (Vec<NavigationInput>, Option<Vec2>) {
+    let mut inputs = Vec::new();
+    let stick = gamepad::with_gamepads(|gamepads| {
+        const BUTTONS: [(Button, NavigationInput); 9] = [
+            (Button::DPadUp, NavigationInput::Up),
+            (Button::DPadDown, NavigationInput::Down),
+            (Button::DPadLeft, NavigationInput::Left),
+            (Button::DPadRight, NavigationInput::Right),
+            (Button::A, NavigationInput::Press),
+            (Button::B, NavigationInput::Backspace),
+            (Button::RightShoulder, NavigationInput::NextPage),
+            (Button::LeftShoulder, NavigationInput::PreviousPage),
+            (Button::Start, NavigationInput::Done),
+        ];
+        for gamepad in gamepads.values() {
+            for (button, input) in BUTTONS {
+                if gamepad.buttons_just_pressed.contains(&button) {
+                    inputs.push(input);
+                }
+            }
+        }
+        gamepads
+            .values()
+            .map(|gamepad| Vec2::new(gamepad.axis(Axis::LeftX), gamepad.axis(Axis::LeftY)))
+            .max_by(|a, b| a.length_sq().total_cmp(&b.length_sq()))
+    });
+
+    inputs.extend(state.stick_input(stick));
+
+    const KEYS: [(Scancode, NavigationInput); 6] = [
+        (Scancode::Up, NavigationInput::Up),
+        (Scancode::Down, NavigationInput::Down),
+        (Scancode::Left, NavigationInput::Left),
+        (Scancode::Right, NavigationInput::Right),
+        (Scancode::Backspace, NavigationInput::Backspace),
+        (Scancode::Return, NavigationInput::Done),
+    ];
+    for (key, input) in KEYS {
+        if io.keyboard_just_pressed_state.get(&key).copied() == Some(true) {
+            inputs.push(input);
+        }
+    }
+
+    let click = io
+        .mouse_state
+        .is_left_just_pressed
+        .then(|| Vec2::new(io.mouse_state.x, io.mouse_state.y));
+    (inputs, click)
+}
+
+/// Returns the position and size of each key of each row of the page, the row at the top first.
+/// The top of the area is used to show the text.
+fn get_key_rects(
+    layout: &KeyboardLayout,
+    page: usize,
+    pos: Vec2,
+    size: Vec2,
+) -> Vec<Vec<(Vec2, Vec2)>> {
+    let rows = layout.rows(page);
+    let row_height = size.y() / (rows.len() + 1) as f32;
+    let margin = row_height * 0.05;
+    let widest_row = rows
+        .iter()
+        .take(rows.len().saturating_sub(1))
+        .map(Vec::len)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    rows.iter()
+        .enumerate()
+        .map(|(row_index, row)| {
+            let is_control_row = row_index + 1 == rows.len();
+            let key_width = if is_control_row {
+                size.x() / row.len().max(1) as f32
+            } else {
+                size.x() / widest_row as f32
+            };
+            let row_start = pos.x() + (size.x() - key_width * row.len() as f32) / 2.0;
+            let y = pos.y() + size.y() - (row_index + 2) as f32 * row_height;
+            (0..row.len())
+                .map(|column| {
+                    (
+                        Vec2::new(row_start + column as f32 * key_width + margin, y + margin),
+                        Vec2::new(key_width - 2.0 * margin, row_height - 2.0 * margin),
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub struct TextEntryStyle {
+    pub background_color: [f32; 4],
+    pub key_color: [f32; 4],
+    pub selected_key_color: [f32; 4],
+    pub text_color: [f32; 4],
+}
+
+pub struct TextEntryFrame<'a> {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub max_length: Option<usize>,
+    pub font_id: FontResourceId,
+    pub style: TextEntryStyle,
+    pub layout: &'a KeyboardLayout,
+}
+
+/// Handles the inputs of the frame and draws the text entry. Returns the text when the user selects "done".
+pub fn update_and_draw_text_entry(
+    state: &mut TextEntryState,
+    frame: &TextEntryFrame,
+    batch: &RefCell<batchdraw::BatchDraw2d>,
+    io_env: &RefCell<IoEnvState>,
+    gl: &Arc<glow::Context>,
+    resources: &ResourceManager,
+) -> mlua::Result<Option<String>> {
+    let layout = frame.layout;
+    if layout.page_count() == 0 {
+        return Err(mlua::Error::external(
+            "textEntry needs at least one page of keys",
+        ));
+    }
+    state.page = state.page.min(layout.page_count() - 1);
+
+    let io = io_env.borrow();
+    let (inputs, click) = collect_inputs(state, &io);
+    let mut committed = None;
+    state.type_text(&io.text_input, frame.max_length);
+    for input in inputs {
+        committed = committed.or(state.handle_input(layout, input, frame.max_length));
+    }
+    if let Some(click) = click {
+        let rects = get_key_rects(layout, state.page, frame.pos, frame.size);
+        let clicked_key = rects.iter().enumerate().find_map(|(row, keys)| {
+            let column = keys.iter().position(|(key_pos, key_size)| {
+                let offset = click - *key_pos;
+                (0.0..=key_size.x()).contains(&offset.x())
+                    && (0.0..=key_size.y()).contains(&offset.y())
+            })?;
+            Some((row, column))
+        });
+        if let Some((row, column)) = clicked_key {
+            state.row = row;
+            state.column = column;
+            committed =
+                committed.or(state.handle_input(layout, NavigationInput::Press, frame.max_length));
+        }
+    }
+
+    let aspect_ratio = io.game_viewport.aspect_ratio();
+    drop(io);
+
+    let style = &frame.style;
+    let row_height = frame.size.y() / (layout.rows(state.page).len() + 1) as f32;
+    let font_size = row_height * 0.5;
+    let text_field_pos = Vec2::new(frame.pos.x(), frame.pos.y() + frame.size.y() - row_height);
+    {
+        let mut batch = batch.borrow_mut();
+        batch.draw_rect(
+            frame.pos.x(),
+            frame.pos.y(),
+            frame.size.x(),
+            frame.size.y(),
+            style.background_color,
+        );
+        batch.draw_rect(
+            text_field_pos.x(),
+            text_field_pos.y(),
+            frame.size.x(),
+            row_height,
+            style.key_color,
+        );
+    }
+    let key_rects = get_key_rects(layout, state.page, frame.pos, frame.size);
+    for (row_index, (row, rects)) in layout.rows(state.page).iter().zip(&key_rects).enumerate() {
+        for (column, (_key, (key_pos, key_size))) in row.iter().zip(rects).enumerate() {
+            let is_selected = row_index == state.row && column == state.column;
+            let color = if is_selected {
+                style.selected_key_color
+            } else {
+                style.key_color
+            };
+            batch.borrow_mut().draw_rect(
+                key_pos.x(),
+                key_pos.y(),
+                key_size.x(),
+                key_size.y(),
+                color,
+            );
+        }
+    }
+
+    let text = format!("{}_", state.text);
+    frame.font_id.get_font_resource(gl, resources, |font| {
+        font.enrich_atlas(gl, &text);
+        let baseline = font.get_max_baseline_height(font_size);
+        let mut batch = batch.borrow_mut();
+        batch.draw_text(
+            text_field_pos.x() + row_height * 0.25,
+            text_field_pos.y() + (row_height - font_size) / 2.0 + baseline,
+            &text,
+            style.text_color,
+            font_size,
+            font,
+        );
+        for (row, rects) in layout.rows(state.page).iter().zip(&key_rects) {
+            for (key, (key_pos, key_size)) in row.iter().zip(rects) {
+                let label = layout.label(key);
+                font.enrich_atlas(gl, label);
+                let (label_width, _, _) = font.measure_text(label, font_size, aspect_ratio);
+                batch.draw_text(
+                    key_pos.x() + (key_size.x() - label_width) / 2.0,
+                    key_pos.y() + (key_size.y() - font_size) / 2.0 + baseline,
+                    label,
+                    style.text_color,
+                    font_size,
+                    font,
+                );
+            }
+        }
+    });
+    Ok(committed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_layout() -> KeyboardLayout {
+        let rows = |rows: &[&str]| {
+            rows.iter()
+                .map(|row| row.chars().map(|c| c.to_string()).collect())
+                .collect()
+        };
+        KeyboardLayout::new(
+            vec![rows(&["abc", "de"]), rows(&["ABC", "DE", "FGHIJ"])],
+            vec!["abc".to_string(), "ABC".to_string()],
+        )
+    }
+
+    #[test]
+    fn navigation_wraps_around_the_rows_and_columns() {
+        let layout = small_layout();
+        let mut state = TextEntryState::default();
+        state.handle_input(&layout, NavigationInput::Left, None);
+        assert_eq!((state.row, state.column), (0, 2));
+        state.handle_input(&layout, NavigationInput::Right, None);
+        assert_eq!((state.row, state.column), (0, 0));
+        // The control row of the first page has the key of the second page, space, backspace and done.
+        state.handle_input(&layout, NavigationInput::Up, None);
+        assert_eq!(state.row, 2);
+        assert_eq!(state.selected_key(&layout), Some(&EntryKey::Page(1)));
+        state.handle_input(&layout, NavigationInput::Down, None);
+        assert_eq!((state.row, state.column), (0, 0));
+    }
+
+    #[test]
+    fn moving_between_rows_keeps_the_horizontal_position() {
+        let layout = small_layout();
+        let mut state = TextEntryState::default();
+        state.handle_input(&layout, NavigationInput::Left, None);
+        state.handle_input(&layout, NavigationInput::Down, None);
+        assert_eq!(
+            state.selected_key(&layout),
+            Some(&EntryKey::Character("e".into()))
+        );
+        state.handle_input(&layout, NavigationInput::Down, None);
+        assert_eq!(state.selected_key(&layout), Some(&EntryKey::Done));
+    }
+
+    #[test]
+    fn pressing_keys_types_and_respects_the_limit() {
+        let layout = small_layout();
+        let mut state = TextEntryState::default();
+        for _ in 0..3 {
+            state.handle_input(&layout, NavigationInput::Press, Some(2));
+        }
+        assert_eq!(state.text, "aa");
+        state.handle_input(&layout, NavigationInput::Backspace, Some(2));
+        state.type_text("b\nc", Some(2));
+        assert_eq!(state.text, "ab");
+        let committed = state.handle_input(&layout, NavigationInput::Done, Some(2));
+        assert_eq!(committed.as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn switching_pages_keeps_the_selection_valid() {
+        let layout = small_layout();
+        let mut state = TextEntryState::default();
+        state.handle_input(&layout, NavigationInput::Up, None);
+        state.handle_input(&layout, NavigationInput::Press, None);
+        assert_eq!(state.page, 1);
+        // Still on the control row, on the key going back to the first page.
+        assert_eq!(state.row, 3);
+        assert_eq!(state.selected_key(&layout), Some(&EntryKey::Page(0)));
+
+        state.handle_input(&layout, NavigationInput::Up, None);
+        state.handle_input(&layout, NavigationInput::Right, None);
+        state.handle_input(&layout, NavigationInput::Right, None);
+        state.handle_input(&layout, NavigationInput::Right, None);
+        assert_eq!(
+            state.selected_key(&layout),
+            Some(&EntryKey::Character("I".into()))
+        );
+        // The first page has fewer rows and keys, the selection is moved to the closest key.
+        state.handle_input(&layout, NavigationInput::NextPage, None);
+        assert_eq!(state.page, 0);
+        assert_eq!((state.row, state.column), (2, 3));
+        state.handle_input(&layout, NavigationInput::PreviousPage, None);
+        assert_eq!(state.page, 1);
+    }
+
+    #[test]
+    fn holding_the_stick_moves_once() {
+        let mut state = TextEntryState::default();
+        let right = Some(Vec2::new(0.9, 0.1));
+        assert_eq!(state.stick_input(right), Some(NavigationInput::Right));
+        assert_eq!(state.stick_input(right), None);
+        assert_eq!(state.stick_input(Some(Vec2::new(0.2, 0.0))), None);
+        assert_eq!(state.stick_input(right), Some(NavigationInput::Right));
+        assert_eq!(
+            state.stick_input(Some(Vec2::new(0.3, 0.8))),
+            Some(NavigationInput::Down)
+        );
+        assert_eq!(state.stick_input(None), None);
+    }
+}
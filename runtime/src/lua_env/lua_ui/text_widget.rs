@@ -70,7 +70,7 @@ impl VectarineWidget for TextWidget {
         };
 
         let io = io_env.borrow();
-        let aspect_ratio = io.window_width as f32 / io.window_height as f32;
+        let aspect_ratio = io.game_viewport.aspect_ratio();
 
         let align = self.align;
         let fitting = self.fitting;
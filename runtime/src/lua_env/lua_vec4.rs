@@ -207,6 +207,40 @@ impl vectarine_plugin_sdk::mlua::UserData for Vec4 {
                 ))
             },
         );
+
+        // In-place methods
+        methods.add_method_mut(
+            "add",
+            #[inline(always)]
+            |_, vec, (other,): (Vec4,)| {
+                *vec = *vec + other;
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "sub",
+            #[inline(always)]
+            |_, vec, (other,): (Vec4,)| {
+                *vec = *vec - other;
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "mul",
+            #[inline(always)]
+            |_, vec, (other,): (Vec4,)| {
+                *vec = *vec * other;
+                Ok(())
+            },
+        );
+        methods.add_method_mut(
+            "rescale",
+            #[inline(always)]
+            |_, vec, (k,): (f32,)| {
+                *vec = *vec * k;
+                Ok(())
+            },
+        );
     }
 }
 
@@ -0,0 +1,144 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use vectarine_plugin_sdk::mlua::{AnyUserData, FromLua, IntoLua};
+
+use crate::{
+    game_resource::{self, ResourceId, video_resource::VideoResource},
+    graphics::batchdraw,
+    io,
+    lua_env::{
+        add_fn_to_table,
+        lua_coord::{get_pos_as_vec2, get_size_as_vec2},
+        lua_resource::{ResourceIdWrapper, register_resource_id_methods_on_type},
+        lua_vec4::{Vec4, WHITE},
+    },
+    make_resource_lua_compatible,
+};
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+pub struct VideoResourceId(ResourceId);
+make_resource_lua_compatible!(VideoResourceId);
+
+pub fn setup_video_api(
+    lua: &vectarine_plugin_sdk::mlua::Lua,
+    batch: &Rc<RefCell<batchdraw::BatchDraw2d>>,
+    env_state: &Rc<RefCell<io::IoEnvState>>,
+    resources: &Rc<game_resource::ResourceManager>,
+) -> vectarine_plugin_sdk::mlua::Result<vectarine_plugin_sdk::mlua::Table> {
+    let video_module = lua.create_table()?;
+
+    lua.register_userdata_type::<VideoResourceId>(|registry| {
+        register_resource_id_methods_on_type(resources, registry);
+    })?;
+
+    add_fn_to_table(lua, &video_module, "load", {
+        let resources = resources.clone();
+        move |_, path: String| {
+            let id = resources.schedule_load_resource::<VideoResource>(Path::new(&path));
+            Ok(VideoResourceId::from_id(id))
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "play", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        move |_, video_id: VideoResourceId| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(());
+            };
+            video.play(env_state.borrow().frame_time);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "pause", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        move |_, video_id: VideoResourceId| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(());
+            };
+            video.pause(env_state.borrow().frame_time);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "seek", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        move |_, (video_id, seconds): (VideoResourceId, f64)| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(());
+            };
+            video.seek(seconds, env_state.borrow().frame_time);
+            Ok(())
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "isFinished", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        move |_, video_id: VideoResourceId| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(false);
+            };
+            Ok(video.is_finished(env_state.borrow().frame_time))
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "getPosition", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        move |_, video_id: VideoResourceId| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(0.0);
+            };
+            Ok(video.position(env_state.borrow().frame_time))
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "getDuration", {
+        let resources = resources.clone();
+        move |_, video_id: VideoResourceId| {
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(0.0);
+            };
+            Ok(video.info().map_or(0.0, |info| info.duration()))
+        }
+    });
+
+    add_fn_to_table(lua, &video_module, "draw", {
+        let resources = resources.clone();
+        let env_state = env_state.clone();
+        let batch = batch.clone();
+        move |_,
+              (video_id, mpos, msize, color): (
+            VideoResourceId,
+            AnyUserData,
+            AnyUserData,
+            Option<Vec4>,
+        )| {
+            let pos = get_pos_as_vec2(mpos)?;
+            let size = get_size_as_vec2(msize)?;
+            let Ok(video) = resources.get_by_id::<VideoResource>(video_id.0) else {
+                return Ok(());
+            };
+            video.update(env_state.borrow().frame_time);
+            let texture = video.texture.borrow();
+            let Some(texture) = texture.as_ref() else {
+                return Ok(());
+            };
+            batch.borrow_mut().draw_image(
+                pos.x(),
+                pos.y(),
+                size.x(),
+                size.y(),
+                texture,
+                color.unwrap_or(WHITE).0,
+            );
+            Ok(())
+        }
+    });
+
+    Ok(video_module)
+}
@@ -1,7 +1,18 @@
-use std::{collections::VecDeque, time::Duration};
+use std::{
+    cmp,
+    collections::VecDeque,
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 // For a 60 FPS game, we store metrics for about 6 seconds.
 pub const METRICS_STORAGE_DURATION: usize = 60 * 6;
+/// The number of different metrics a game can create with `Debug.metricCounter` and `Debug.metricGauge`.
+/// Names made from changing values, like ids, would otherwise create new metrics forever.
+pub const MAX_GAME_METRICS: usize = 64;
+/// A recording stops growing after an hour at 60 FPS.
+pub const MAX_RECORDED_FRAMES: usize = 60 * 60 * 60;
 
 pub trait Measurable: Copy + Ord + Default + std::iter::Sum<Self> {
     fn div_count(self, count: usize) -> Self;
@@ -24,6 +35,40 @@ impl Measurable for usize {
     }
 }
 
+/// A number recorded by the game. Unlike `f64`, it can be sorted, so that it can be used in a [`Metric`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GameValue(pub f64);
+
+impl PartialEq for GameValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == cmp::Ordering::Equal
+    }
+}
+impl Eq for GameValue {}
+impl PartialOrd for GameValue {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for GameValue {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+impl std::iter::Sum<GameValue> for GameValue {
+    fn sum<I: Iterator<Item = GameValue>>(iter: I) -> Self {
+        GameValue(iter.map(|value| value.0).sum())
+    }
+}
+impl Measurable for GameValue {
+    fn div_count(self, count: usize) -> Self {
+        GameValue(self.0 / count as f64)
+    }
+    fn into_f32(self) -> f32 {
+        self.0 as f32
+    }
+}
+
 /// A Metric<T> is a value of type T that is recorded for each frame.
 pub struct Metric<T: Copy> {
     name: String,
@@ -82,6 +127,18 @@ impl<T: Copy> Metric<T> {
         self.values.iter().copied().max().unwrap_or_default()
     }
 
+    pub fn min(&self) -> T
+    where
+        T: Ord + Default,
+    {
+        self.values.iter().copied().min().unwrap_or_default()
+    }
+
+    /// The value of the last frame recorded.
+    pub fn last(&self) -> Option<T> {
+        self.values.back().copied()
+    }
+
     pub fn values(&self) -> impl Iterator<Item = T> + '_ {
         self.values.iter().copied()
     }
@@ -122,17 +179,126 @@ where
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMetricKind {
+    /// Set with `Debug.metricCounter`. The values given during a frame are added, and the counter starts again from 0
+    /// on the next frame.
+    Counter,
+    /// Set with `Debug.metricGauge`. Keeps its value until it is set again.
+    Gauge,
+}
+
+impl GameMetricKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMetricKind::Counter => "counter",
+            GameMetricKind::Gauge => "gauge",
+        }
+    }
+}
+
+/// A metric created by the game, which records a value every frame, even when the game did not set it.
+pub struct GameMetric {
+    metric: Metric<GameValue>,
+    kind: GameMetricKind,
+    /// The value of the current frame, recorded by `MetricsHolder::flush`.
+    current: f64,
+}
+
+impl GameMetric {
+    pub fn metric(&self) -> &Metric<GameValue> {
+        &self.metric
+    }
+
+    pub fn kind(&self) -> GameMetricKind {
+        self.kind
+    }
+}
+
+/// The values of every metric at the end of each frame since the recording started, to export them as CSV.
+#[derive(Default)]
+pub struct MetricsRecording {
+    columns: Vec<String>,
+    /// One row per frame, with a value for each column that had one during the frame.
+    rows: Vec<Vec<Option<f64>>>,
+}
+
+impl MetricsRecording {
+    pub fn frame_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn record(&mut self, column: String, value: f64) {
+        let index = match self.columns.iter().position(|name| *name == column) {
+            Some(index) => index,
+            None => {
+                self.columns.push(column);
+                self.columns.len() - 1
+            }
+        };
+        let Some(row) = self.rows.last_mut() else {
+            return;
+        };
+        if row.len() <= index {
+            row.resize(index + 1, None);
+        }
+        row[index] = Some(value);
+    }
+
+    /// One line per frame. The durations are in milliseconds, and metrics without a value for a frame are left empty.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame");
+        for column in &self.columns {
+            csv.push(',');
+            csv.push_str(&csv_field(column));
+        }
+        csv.push('\n');
+        for (frame, row) in self.rows.iter().enumerate() {
+            let _ = write!(csv, "{frame}");
+            for index in 0..self.columns.len() {
+                csv.push(',');
+                if let Some(Some(value)) = row.get(index) {
+                    let _ = write!(csv, "{value}");
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 /// Stores the metrics for all frames and produces stats about them.
 pub struct MetricsHolder {
     duration_historical_metrics: Vec<Metric<Duration>>,
     number_historical_metrics: Vec<Metric<usize>>,
+    game_metrics: Vec<GameMetric>,
+    recording: Option<MetricsRecording>,
 }
 
 // Name of some default metrics.
 pub const TOTAL_FRAME_TIME_METRIC_NAME: &str = "total_frame_time";
 pub const DRAW_CALL_METRIC_NAME: &str = "draw_call";
+pub const CULLED_DRAW_METRIC_NAME: &str = "culled_draw";
+/// Glyphs drawn with `Text.drawFast` during the frame. They are drawn with a single draw call per font and transform.
+pub const INSTANCED_GLYPH_METRIC_NAME: &str = "instanced_glyph";
 pub const LUA_HEAP_SIZE_METRIC_NAME: &str = "lua_heap_size";
 pub const LUA_SCRIPT_TIME_METRIC_NAME: &str = "total_lua_script_time";
+/// Bytes allocated by Lua during the frame. Collections made during the frame are subtracted unless the project sets
+/// `gc_step_per_frame`.
+pub const LUA_ALLOCATED_METRIC_NAME: &str = "lua_allocated";
+/// Time spent in the garbage collector steps made by the engine and by `Debug.gcCollect`.
+pub const LUA_GC_TIME_METRIC_NAME: &str = "lua_gc_time";
+/// 1 when the rendering of the frame was skipped because the game is behind, 0 otherwise.
+/// Only recorded when the project allows frame skipping.
+pub const SKIPPED_FRAME_METRIC_NAME: &str = "skipped_frame";
 // pub const ENGINE_FRAME_TIME_METRIC_NAME: &str = "engine_frame_time";
 
 impl MetricsHolder {
@@ -140,6 +306,8 @@ impl MetricsHolder {
         MetricsHolder {
             duration_historical_metrics: Vec::new(),
             number_historical_metrics: Vec::new(),
+            game_metrics: Vec::new(),
+            recording: None,
         }
     }
     pub fn record_number_metric(&mut self, name: &str, value: usize) {
@@ -186,7 +354,57 @@ impl MetricsHolder {
             });
         }
     }
+    /// Adds to a counter or sets a gauge created by the game. Fails when the metric has another kind, or when it does
+    /// not exist and the game already has [`MAX_GAME_METRICS`] metrics.
+    pub fn record_game_metric(
+        &mut self,
+        name: &str,
+        kind: GameMetricKind,
+        value: f64,
+    ) -> Result<(), String> {
+        let index = match self.game_metrics.iter().position(|m| m.metric.name == name) {
+            Some(index) => index,
+            None if self.game_metrics.len() >= MAX_GAME_METRICS => {
+                return Err(format!(
+                    "Unable to create the metric {name}, a game can only have {MAX_GAME_METRICS} metrics"
+                ));
+            }
+            None => {
+                self.game_metrics.push(GameMetric {
+                    metric: Metric::new(name.to_string()),
+                    kind,
+                    current: 0.0,
+                });
+                self.game_metrics.len() - 1
+            }
+        };
+        let game_metric = &mut self.game_metrics[index];
+        if game_metric.kind != kind {
+            return Err(format!(
+                "The metric {name} is a {}, not a {}",
+                game_metric.kind.name(),
+                kind.name()
+            ));
+        }
+        match kind {
+            GameMetricKind::Counter => game_metric.current += value,
+            GameMetricKind::Gauge => game_metric.current = value,
+        }
+        Ok(())
+    }
     pub fn flush(&mut self) {
+        for game_metric in &mut self.game_metrics {
+            let values = &mut game_metric.metric.values;
+            values.push_back(GameValue(game_metric.current));
+            if values.len() > METRICS_STORAGE_DURATION {
+                values.pop_front();
+            }
+            if game_metric.kind == GameMetricKind::Counter {
+                game_metric.current = 0.0;
+            }
+        }
+        self.record_frame();
+
         for metric in &mut self.number_historical_metrics {
             let metric_has_too_many_values = metric.values.len() > METRICS_STORAGE_DURATION;
             let metric_is_outdated = metric.frames_since_addition > 0;
@@ -228,6 +446,46 @@ impl MetricsHolder {
     pub fn get_duration_metrics(&self) -> impl Iterator<Item = &Metric<Duration>> {
         self.duration_historical_metrics.iter()
     }
+    pub fn get_game_metrics(&self) -> impl Iterator<Item = &GameMetric> {
+        self.game_metrics.iter()
+    }
+
+    /// Starts keeping the values of every frame, until `stop_recording` is called.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(MetricsRecording::default());
+    }
+    pub fn stop_recording(&mut self) -> Option<MetricsRecording> {
+        self.recording.take()
+    }
+    pub fn recording(&self) -> Option<&MetricsRecording> {
+        self.recording.as_ref()
+    }
+
+    /// Adds the values of the frame to the recording. Called before the values of the frame are flushed.
+    fn record_frame(&mut self) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if recording.rows.len() >= MAX_RECORDED_FRAMES {
+            return;
+        }
+        recording.rows.push(Vec::new());
+        for metric in &self.duration_historical_metrics {
+            if let (0, Some(value)) = (metric.frames_since_addition, metric.last()) {
+                recording.record(format!("{} (ms)", metric.name), value.into_f32() as f64);
+            }
+        }
+        for metric in &self.number_historical_metrics {
+            if let (0, Some(value)) = (metric.frames_since_addition, metric.last()) {
+                recording.record(metric.name.clone(), value as f64);
+            }
+        }
+        for game_metric in &self.game_metrics {
+            if let Some(value) = game_metric.metric.last() {
+                recording.record(format!("game.{}", game_metric.metric.name), value.0);
+            }
+        }
+    }
 }
 
 impl Default for MetricsHolder {
@@ -235,3 +493,194 @@ impl Default for MetricsHolder {
         Self::new()
     }
 }
+
+/// Subsystems for which memory usage is tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCategory {
+    Textures,
+    VertexBuffers,
+    Audio,
+    ResourceData,
+    LuaHeap,
+}
+
+const MEMORY_CATEGORY_COUNT: usize = 5;
+
+static MEMORY_USAGE: [AtomicUsize; MEMORY_CATEGORY_COUNT] =
+    [const { AtomicUsize::new(0) }; MEMORY_CATEGORY_COUNT];
+static MEMORY_PEAK: [AtomicUsize; MEMORY_CATEGORY_COUNT] =
+    [const { AtomicUsize::new(0) }; MEMORY_CATEGORY_COUNT];
+
+impl MemoryCategory {
+    pub const ALL: [MemoryCategory; MEMORY_CATEGORY_COUNT] = [
+        MemoryCategory::Textures,
+        MemoryCategory::VertexBuffers,
+        MemoryCategory::Audio,
+        MemoryCategory::ResourceData,
+        MemoryCategory::LuaHeap,
+    ];
+
+    /// Name used for the category in Lua and in the profiler.
+    pub fn name(self) -> &'static str {
+        match self {
+            MemoryCategory::Textures => "textures",
+            MemoryCategory::VertexBuffers => "vertexBuffers",
+            MemoryCategory::Audio => "audio",
+            MemoryCategory::ResourceData => "resourceData",
+            MemoryCategory::LuaHeap => "luaHeap",
+        }
+    }
+
+    /// Name of the numeric metric recorded every frame for this category.
+    pub fn metric_name(self) -> &'static str {
+        match self {
+            MemoryCategory::Textures => "memory_textures",
+            MemoryCategory::VertexBuffers => "memory_vertex_buffers",
+            MemoryCategory::Audio => "memory_audio",
+            MemoryCategory::ResourceData => "memory_resource_data",
+            MemoryCategory::LuaHeap => LUA_HEAP_SIZE_METRIC_NAME,
+        }
+    }
+
+    /// Number of bytes currently used by this category.
+    pub fn usage(self) -> usize {
+        MEMORY_USAGE[self as usize].load(Ordering::Relaxed)
+    }
+
+    /// Highest number of bytes used by this category since the last call to `reset_memory_peaks`.
+    pub fn peak(self) -> usize {
+        MEMORY_PEAK[self as usize].load(Ordering::Relaxed)
+    }
+
+    /// Overwrite the usage of a category. Used for memory that is not allocated by us, like the Lua heap.
+    pub fn set_usage(self, bytes: usize) {
+        MEMORY_USAGE[self as usize].store(bytes, Ordering::Relaxed);
+        MEMORY_PEAK[self as usize].fetch_max(bytes, Ordering::Relaxed);
+    }
+
+    fn add(self, bytes: usize) {
+        let total = MEMORY_USAGE[self as usize].fetch_add(bytes, Ordering::Relaxed) + bytes;
+        MEMORY_PEAK[self as usize].fetch_max(total, Ordering::Relaxed);
+    }
+
+    fn sub(self, bytes: usize) {
+        MEMORY_USAGE[self as usize].fetch_sub(bytes, Ordering::Relaxed);
+    }
+}
+
+/// Reset the peaks of all categories to their current usage, for example when a game is loaded.
+pub fn reset_memory_peaks() {
+    for category in MemoryCategory::ALL {
+        MEMORY_PEAK[category as usize].store(category.usage(), Ordering::Relaxed);
+    }
+}
+
+/// Accounts for some bytes in a memory category for as long as the guard is alive.
+/// Store it next to the allocation it describes so that the bytes are released when the allocation is freed.
+#[derive(Debug)]
+pub struct MemoryGuard {
+    category: MemoryCategory,
+    bytes: usize,
+}
+
+impl MemoryGuard {
+    pub fn new(category: MemoryCategory, bytes: usize) -> Self {
+        category.add(bytes);
+        Self { category, bytes }
+    }
+
+    /// Update the number of bytes accounted for, when the allocation is resized.
+    pub fn set_bytes(&mut self, bytes: usize) {
+        if bytes > self.bytes {
+            self.category.add(bytes - self.bytes);
+        } else {
+            self.category.sub(self.bytes - bytes);
+        }
+        self.bytes = bytes;
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
+
+impl Clone for MemoryGuard {
+    fn clone(&self) -> Self {
+        Self::new(self.category, self.bytes)
+    }
+}
+
+impl Drop for MemoryGuard {
+    fn drop(&mut self) {
+        self.category.sub(self.bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameMetricKind, MAX_GAME_METRICS, MetricsHolder};
+
+    #[test]
+    fn counters_restart_every_frame_and_gauges_keep_their_value() {
+        let mut metrics = MetricsHolder::new();
+        metrics.start_recording();
+        for frame in 0..3 {
+            for _ in 0..frame {
+                metrics
+                    .record_game_metric("enemies killed", GameMetricKind::Counter, 1.0)
+                    .expect("the counter exists");
+            }
+            if frame == 0 {
+                metrics
+                    .record_game_metric("level, area", GameMetricKind::Gauge, 2.5)
+                    .expect("the gauge can be created");
+            }
+            metrics.flush();
+        }
+
+        let values = |name: &str| {
+            metrics
+                .get_game_metrics()
+                .find(|m| m.metric().name() == name)
+                .expect("the metric exists")
+                .metric()
+                .values()
+                .map(|value| value.0)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values("level, area"), [2.5, 2.5, 2.5]);
+        // The counter only exists from the second frame.
+        assert_eq!(values("enemies killed"), [1.0, 2.0]);
+
+        let recording = metrics.stop_recording().expect("the recording started");
+        assert_eq!(
+            recording.to_csv(),
+            "frame,\"game.level, area\",game.enemies killed\n0,2.5,\n1,2.5,1\n2,2.5,2\n"
+        );
+    }
+
+    #[test]
+    fn game_metrics_are_limited() {
+        let mut metrics = MetricsHolder::new();
+        for i in 0..MAX_GAME_METRICS {
+            metrics
+                .record_game_metric(&format!("metric {i}"), GameMetricKind::Gauge, 0.0)
+                .expect("the limit is not reached");
+        }
+        assert!(
+            metrics
+                .record_game_metric("one too many", GameMetricKind::Gauge, 0.0)
+                .is_err()
+        );
+        assert!(
+            metrics
+                .record_game_metric("metric 0", GameMetricKind::Counter, 1.0)
+                .is_err()
+        );
+        assert!(
+            metrics
+                .record_game_metric("metric 0", GameMetricKind::Gauge, 1.0)
+                .is_ok()
+        );
+    }
+}
@@ -1,8 +1,14 @@
 pub mod native_plugin_impl;
+pub mod plugin_host;
 
-use std::rc::Rc;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use vectarine_plugin_sdk::plugininterface::{EditorPluginInterface, PluginInterface};
+use vectarine_plugin_sdk::plugininterface::{
+    EditorPluginInterface, PluginDrawInterface, PluginInterface,
+};
 
 use crate::game_resource::ResourceManager;
 
@@ -21,25 +27,25 @@ pub enum Platform {
 
 pub struct NativePlugin {
     native_handle: imp::NativePlugin,
-    name: String,     // as specified in the .vecta file of the game
-    location: String, // the path/url used to access the plugin data. Usually, it is the name concatenated with something.
+    name: String,      // as specified in the .vecta file of the game
+    location: PathBuf, // the path/url used to access the plugin data. Usually, it is the name concatenated with something.
 }
 
 impl NativePlugin {
     /// Load a native vectarine plugin from a path.
-    pub fn load(name: &str, location: &str) -> vectarine_plugin_sdk::anyhow::Result<Self> {
+    pub fn load(name: &str, location: &Path) -> vectarine_plugin_sdk::anyhow::Result<Self> {
         let native_handle = unsafe { imp::NativePlugin::load(location) }?;
         Ok(Self {
             native_handle,
             name: name.to_string(),
-            location: location.to_string(),
+            location: location.to_path_buf(),
         })
     }
     pub fn get_name(&self) -> String {
         self.name.clone()
     }
 
-    pub fn get_location(&self) -> String {
+    pub fn get_location(&self) -> PathBuf {
         self.location.clone()
     }
 
@@ -59,6 +65,10 @@ impl NativePlugin {
         self.native_handle.call_post_lua_hook(plugin_interface)
     }
 
+    pub fn call_draw_hook(&self, plugin_interface: PluginDrawInterface) {
+        self.native_handle.call_draw_hook(plugin_interface)
+    }
+
     pub fn call_draw_debug_menu_hook(&self, plugin_interface: EditorPluginInterface) -> bool {
         self.native_handle
             .call_draw_debug_menu_hook(plugin_interface)
@@ -115,7 +125,7 @@ impl PluginEnvironment {
                     .join("plugins")
                     .join(&full_name);
 
-                fs.read_file(&format!("gamedata/plugins/{}", full_name), {
+                fs.read_file(&Path::new("gamedata/plugins").join(&full_name), {
                     let full_name = full_name.clone();
                     Box::new(move |result| {
                         // Copy the content to the true file system so that we can load it as a native library.
@@ -141,8 +151,7 @@ impl PluginEnvironment {
                 if !plugin_path.exists() {
                     return None;
                 }
-                let plugin = match NativePlugin::load(name, plugin_path.to_string_lossy().as_ref())
-                {
+                let plugin = match NativePlugin::load(name, &plugin_path) {
                     Ok(plugin) => plugin,
                     Err(e) => {
                         println!("Failed to load plugin {}: {}", full_name, e);
@@ -181,6 +190,13 @@ impl PluginEnvironment {
         }
     }
 
+    /// Call the draw hook of all the loaded plugins, so that they can draw on top of the game.
+    pub fn draw_hook(&self, plugin_interface: PluginDrawInterface) {
+        for plugin in &self.loaded_plugins {
+            plugin.call_draw_hook(plugin_interface);
+        }
+    }
+
     /// Call the release hook of all the loaded plugins
     pub fn release_hook(&self, plugin_interface: PluginInterface) {
         for plugin in &self.loaded_plugins {
@@ -1,8 +1,11 @@
+use std::path::Path;
+
 use libloading::{Library, Symbol};
 
+use crate::io::paths::with_long_path_prefix;
 use vectarine_plugin_sdk::{
     anyhow,
-    plugininterface::{EditorPluginInterface, PluginInterface},
+    plugininterface::{EditorPluginInterface, PluginDrawInterface, PluginInterface},
 };
 
 pub(crate) struct NativePlugin {
@@ -17,6 +20,7 @@ pub(crate) struct NativePlugin {
     release_hook: Option<Symbol<'static, unsafe extern "C" fn(PluginInterface)>>,
     pre_lua_hook: Option<Symbol<'static, unsafe extern "C" fn(PluginInterface)>>,
     post_lua_hook: Option<Symbol<'static, unsafe extern "C" fn(PluginInterface)>>,
+    draw_hook: Option<Symbol<'static, unsafe extern "C" fn(PluginDrawInterface)>>,
 
     draw_debug_menu_hook:
         Option<Symbol<'static, unsafe extern "C" fn(EditorPluginInterface) -> bool>>,
@@ -27,13 +31,14 @@ impl NativePlugin {
     ///
     /// This function is unsafe because it loads a native module. On some platforms, native module can run code when loaded.
     /// Such a module can run any code and is inherently unsafe.
-    pub unsafe fn load(path: &str) -> vectarine_plugin_sdk::anyhow::Result<Self> {
-        let lib = unsafe { Library::new(path) };
+    pub unsafe fn load(path: &Path) -> vectarine_plugin_sdk::anyhow::Result<Self> {
+        let lib = unsafe { Library::new(with_long_path_prefix(path).as_os_str()) };
         let lib = match lib {
             Ok(lib) => lib,
             Err(err) => {
                 return Err(vectarine_plugin_sdk::anyhow::anyhow!(
-                    "Failed to load library at {path}: {err}"
+                    "Failed to load library at {}: {err}",
+                    path.display()
                 ));
             }
         };
@@ -44,6 +49,8 @@ impl NativePlugin {
             load_symbol::<unsafe extern "C" fn(PluginInterface)>(&lib, "pre_lua_hook").ok();
         let post_lua_hook =
             load_symbol::<unsafe extern "C" fn(PluginInterface)>(&lib, "post_lua_hook").ok();
+        let draw_hook =
+            load_symbol::<unsafe extern "C" fn(PluginDrawInterface)>(&lib, "draw_hook").ok();
 
         let draw_debug_menu_hook =
             load_symbol::<unsafe extern "C" fn(EditorPluginInterface) -> bool>(
@@ -58,6 +65,7 @@ impl NativePlugin {
             release_hook,
             pre_lua_hook,
             post_lua_hook,
+            draw_hook,
             draw_debug_menu_hook,
         })
     }
@@ -88,6 +96,13 @@ impl NativePlugin {
         }
     }
 
+    pub fn call_draw_hook(&self, plugin_interface: PluginDrawInterface) {
+        let draw_hook = &self.draw_hook;
+        if let Some(draw_hook) = draw_hook {
+            unsafe { draw_hook(plugin_interface) }
+        }
+    }
+
     pub fn call_draw_debug_menu_hook(
         &self,
         editor_plugin_interface: EditorPluginInterface,
@@ -1,4 +1,6 @@
-use vectarine_plugin_sdk::plugininterface::{EditorPluginInterface, PluginInterface};
+use vectarine_plugin_sdk::plugininterface::{
+    EditorPluginInterface, PluginDrawInterface, PluginInterface,
+};
 
 pub(crate) struct NativePlugin {}
 
@@ -7,7 +9,7 @@ impl NativePlugin {
     ///
     /// This function is unsafe because it loads a native module. On some platforms, native module can run code when loaded.
     /// Such a module can run any code and is inherently unsafe.
-    pub unsafe fn load(_path: &str) -> vectarine_plugin_sdk::anyhow::Result<Self> {
+    pub unsafe fn load(_path: &std::path::Path) -> vectarine_plugin_sdk::anyhow::Result<Self> {
         Err(vectarine_plugin_sdk::anyhow::Error::msg(
             "Native plugins are not supported on WebAssembly yet",
         ))
@@ -21,6 +23,8 @@ impl NativePlugin {
 
     pub fn call_post_lua_hook(&self, _plugin_interface: PluginInterface) {}
 
+    pub fn call_draw_hook(&self, _plugin_interface: PluginDrawInterface) {}
+
     pub fn call_draw_debug_menu_hook(&self, _plugin_interface: EditorPluginInterface) -> bool {
         false
     }
@@ -0,0 +1,166 @@
+use std::{any::Any, cell::RefCell, path::Path, rc::Rc};
+
+use vectarine_plugin_sdk::plugininterface::{
+    DrawHandle, DrawHost, Importer, PluginError, ResourceHandle, ResourceHost,
+};
+
+use crate::{
+    game_resource::{
+        ResourceId, ResourceManager, font_resource::use_default_font,
+        image_resource::ImageResource, plugin_resource::PluginResource,
+    },
+    graphics::batchdraw::BatchDraw2d,
+};
+
+/// The handles given to the plugins of a game. They are invalidated when the game is unloaded.
+pub struct PluginHandles {
+    pub resources: ResourceHandle,
+    pub canvas: DrawHandle,
+}
+
+impl PluginHandles {
+    pub fn new(resources: &Rc<ResourceManager>, batch: &Rc<RefCell<BatchDraw2d>>) -> Self {
+        Self {
+            resources: ResourceHandle::new(Box::new(GameResourceHost {
+                resources: resources.clone(),
+            })),
+            canvas: DrawHandle::new(Box::new(GameDrawHost {
+                resources: resources.clone(),
+                batch: batch.clone(),
+            })),
+        }
+    }
+
+    /// Called after the release hook of the plugins. The handles the plugins kept then return an error.
+    pub fn invalidate(&self) {
+        self.resources.invalidate();
+        self.canvas.invalidate();
+    }
+}
+
+struct GameResourceHost {
+    resources: Rc<ResourceManager>,
+}
+
+impl GameResourceHost {
+    fn get_plugin_resource(
+        &self,
+        id: usize,
+    ) -> Result<(ResourceId, Rc<PluginResource>), PluginError> {
+        let resource_id = self
+            .resources
+            .get_id_by_index(id)
+            .ok_or(PluginError::InvalidResource(id))?;
+        let holder = self.resources.get_holder_by_id(resource_id);
+        let resource = holder
+            .get_underlying_resource::<PluginResource>()
+            .map_err(|_| PluginError::InvalidResource(id))?;
+        Ok((resource_id, resource))
+    }
+}
+
+impl ResourceHost for GameResourceHost {
+    fn load_with_importer(&self, path: &Path, importer: Importer) -> usize {
+        self.resources
+            .schedule_load_resource_with_builder(path, || PluginResource::new(importer))
+            .get_id()
+    }
+
+    fn is_loaded(&self, id: usize) -> Result<bool, PluginError> {
+        let (resource_id, _) = self.get_plugin_resource(id)?;
+        Ok(self.resources.get_holder_by_id(resource_id).is_loaded())
+    }
+
+    fn with_payload(&self, id: usize, f: &mut dyn FnMut(&dyn Any)) -> Result<(), PluginError> {
+        let (_, resource) = self.get_plugin_resource(id)?;
+        let payload = resource.payload.borrow();
+        let payload = payload.as_ref().ok_or(PluginError::NotLoaded(id))?;
+        f(payload.as_ref());
+        Ok(())
+    }
+}
+
+struct GameDrawHost {
+    resources: Rc<ResourceManager>,
+    batch: Rc<RefCell<BatchDraw2d>>,
+}
+
+impl DrawHost for GameDrawHost {
+    fn draw_rect(&self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.batch
+            .borrow_mut()
+            .draw_rect(x, y, width, height, color);
+    }
+
+    fn draw_image(
+        &self,
+        image_id: usize,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [f32; 4],
+    ) -> Result<(), PluginError> {
+        let resource_id = self
+            .resources
+            .get_id_by_index(image_id)
+            .ok_or(PluginError::InvalidResource(image_id))?;
+        let image = self
+            .resources
+            .get_by_id::<ImageResource>(resource_id)
+            .map_err(|_| PluginError::NotLoaded(image_id))?;
+        let texture = image.texture.borrow();
+        let texture = texture.as_ref().ok_or(PluginError::NotLoaded(image_id))?;
+        self.batch
+            .borrow_mut()
+            .draw_image(x, y, width, height, texture, color);
+        Ok(())
+    }
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]) {
+        let gl = self.batch.borrow().drawing_target.gl().clone();
+        use_default_font(&gl, |font_renderer| {
+            font_renderer.enrich_atlas(&gl, text);
+            self.batch
+                .borrow_mut()
+                .draw_text(x, y, text, color, font_size, font_renderer);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, rc::Rc};
+
+    use vectarine_plugin_sdk::plugininterface::{
+        ImportResult, PluginError, ResourceHandle, ResourceHost,
+    };
+
+    use super::GameResourceHost;
+    use crate::game_resource::ResourceManager;
+
+    #[test]
+    fn handles_return_an_error_once_invalidated() {
+        let resources = Rc::new(ResourceManager::dummy_manager());
+        let handle: ResourceHandle = ResourceHandle::new(Box::new(GameResourceHost { resources }));
+        let kept_by_the_plugin = handle.clone();
+        let importer = Rc::new(|_: &Path, data: &[u8]| ImportResult::Loaded(Box::new(data.len())));
+
+        let id = kept_by_the_plugin
+            .load_with_importer(Path::new("sprites/hero.aseprite"), importer.clone())
+            .expect("the handle is valid");
+        assert_eq!(kept_by_the_plugin.is_loaded(id), Ok(false));
+        assert_eq!(
+            kept_by_the_plugin.with_payload(id, |size: &usize| *size),
+            Err(PluginError::NotLoaded(id))
+        );
+
+        handle.invalidate();
+        assert!(!kept_by_the_plugin.is_valid());
+        assert_eq!(
+            kept_by_the_plugin.load_with_importer(Path::new("sprites/hero.aseprite"), importer),
+            Err(PluginError::Released)
+        );
+        assert_eq!(kept_by_the_plugin.is_loaded(id), Err(PluginError::Released));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use vectarine_plugin_sdk::lazy_static::lazy_static;
+
+static IS_HANDLING_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// The game shown in the crash report.
+#[derive(Clone, Default)]
+struct PanicGameInfo {
+    title: String,
+    version: Option<String>,
+}
+
+lazy_static! {
+    static ref GAME_INFO: Mutex<PanicGameInfo> = Mutex::new(PanicGameInfo::default());
+}
+
+/// Set the name and the version of the game shown in the crash report.
+pub fn set_panic_game_info(title: &str, version: Option<&str>) {
+    if let Ok(mut game_info) = GAME_INFO.lock() {
+        *game_info = PanicGameInfo {
+            title: title.to_string(),
+            version: version.map(str::to_string),
+        };
+    }
+}
+
+/// Exported games do not have a console on Windows, so without this, a panic closes the window without any explanation.
+/// The hook writes the panic to a log file, shows it to the player and aborts the process.
+pub fn install_panic_handler() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if IS_HANDLING_PANIC.swap(true, Ordering::SeqCst) {
+            // Something in the handler panicked, trying to report it again could recurse forever.
+            std::process::abort();
+        }
+        default_hook(info);
+
+        // try_lock: the panic could have happened while the game info was being set.
+        let game_info = GAME_INFO
+            .try_lock()
+            .map(|game_info| game_info.clone())
+            .unwrap_or_default();
+        let log_path = get_crash_log_path();
+        let report = format_panic_report(&panic_message(info), &game_info, log_path.as_deref());
+        if let Some(log_path) = &log_path {
+            let _ = std::fs::write(log_path, &report);
+        }
+        show_panic_report(&report);
+
+        // Abort so that launchers and CI see a non-zero exit code.
+        std::process::abort();
+    }));
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    };
+    match info.location() {
+        Some(location) => format!("{} ({}:{})", message, location.file(), location.line()),
+        None => message,
+    }
+}
+
+fn format_panic_report(
+    message: &str,
+    game_info: &PanicGameInfo,
+    log_path: Option<&Path>,
+) -> String {
+    let mut report = String::from("The game crashed.\n\n");
+    report.push_str(message);
+    report.push_str("\n\n");
+    if !game_info.title.is_empty() {
+        report.push_str(&format!("Game: {}\n", game_info.title));
+    }
+    if let Some(version) = &game_info.version {
+        report.push_str(&format!("Game version: {}\n", version));
+    }
+    report.push_str(&format!(
+        "Vectarine version: {}\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    if let Some(log_path) = log_path {
+        report.push_str(&format!(
+            "This report was saved to {}\n",
+            log_path.display()
+        ));
+    }
+    report
+}
+
+fn get_crash_log_path() -> Option<PathBuf> {
+    if cfg!(target_os = "emscripten") {
+        // The emscripten file system lives in memory, the player could not open the file anyway.
+        return None;
+    }
+    let exec_path = std::env::current_exe().ok();
+    let folder = exec_path.and_then(|p| p.parent().map(|p| p.to_path_buf()));
+    Some(folder.unwrap_or_default().join("crash.log"))
+}
+
+fn show_panic_report(report: &str) {
+    #[cfg(target_os = "emscripten")]
+    {
+        use emscripten_functions::emscripten::run_script;
+        let Ok(report) = serde_json::to_string(report) else {
+            return;
+        };
+        let script = format!(
+            r#"
+            (function () {{
+                const overlay = document.createElement("pre");
+                overlay.textContent = {report};
+                overlay.style.cssText = "position: fixed; inset: 0; z-index: 10; margin: 0; padding: 32px; overflow: auto; background-color: rgba(0, 0, 0, 0.9); color: white; font-size: 16px; white-space: pre-wrap;";
+                document.body.appendChild(overlay);
+            }})();
+        "#
+        );
+        run_script(&script);
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        use vectarine_plugin_sdk::sdl2::messagebox::{MessageBoxFlag, show_simple_message_box};
+        // SDL can show message boxes even when it is not initialized or when the window is gone.
+        let _ = show_simple_message_box(
+            MessageBoxFlag::ERROR,
+            "Vectarine - Crash",
+            report,
+            None::<&vectarine_plugin_sdk::sdl2::video::Window>,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicGameInfo, format_panic_report};
+    use std::path::Path;
+
+    #[test]
+    fn report_contains_versions_and_log_path() {
+        let log_path = Path::new("games/snake/crash.log");
+        let game_info = PanicGameInfo {
+            title: "Snake".to_string(),
+            version: Some("1.2.0".to_string()),
+        };
+        let report = format_panic_report("index out of bounds", &game_info, Some(log_path));
+        assert!(report.contains("index out of bounds"));
+        assert!(report.contains("Game: Snake"));
+        assert!(report.contains("Game version: 1.2.0"));
+        assert!(report.contains(env!("CARGO_PKG_VERSION")));
+        assert!(report.contains(&log_path.display().to_string()));
+    }
+}
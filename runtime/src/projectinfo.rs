@@ -1,6 +1,10 @@
+use std::collections::BTreeMap;
+
 use vectarine_plugin_sdk::anyhow::Result;
 use vectarine_plugin_sdk::serde::{Deserialize, Serialize};
 
+use crate::lua_env::lua_physics::layers::CollisionLayers;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(crate = "vectarine_plugin_sdk::serde")]
 pub struct ProjectInfo {
@@ -13,6 +17,122 @@ pub struct ProjectInfo {
     pub default_screen_width: u32,
     pub default_screen_height: u32,
     pub plugins: Vec<String>,
+    /// When set, the engine calls `Tick(tickIndex)` and `FixedUpdate(dt)` this many times per second, independently of
+    /// the frame rate. `fixed_update_rate` is accepted as another name.
+    #[serde(
+        default,
+        alias = "fixed_update_rate",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub fixed_tick_rate: Option<u32>,
+    /// The maximum number of resources loading at the same time. Defaults to 4 on the web and to no limit on desktop.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_parallel_loads: Option<u32>,
+    /// When true, the engine skips rendering some frames when the game runs slower than the display.
+    /// Update and Tick still run for every frame, so the gameplay does not slow down.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub allow_frame_skip: bool,
+    /// The maximum number of frames skipped in a row when `allow_frame_skip` is set. Defaults to 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_consecutive_skips: Option<u32>,
+    /// When true, the sounds are mixed on the audio thread instead of the main thread, so that long frames do not
+    /// make the sound crackle.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub audio_thread: bool,
+    /// The zstd level, from 1 to 22, of the scripts and text files in the bundle of obfuscated exports. Defaults to 19.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_zstd_level: Option<i64>,
+    /// When true, the files of the bundle are stored without compression, which makes exports faster while iterating.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub fast_export: bool,
+    /// When true, exports pack the images of the game in a few large atlases, so that sprites share their texture.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pack_images: bool,
+    /// When true, the Lua garbage collector only runs at the end of every frame, with a step as big as the allocations
+    /// of the frame, instead of during Update. This evens out the duration of the frames.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub gc_step_per_frame: bool,
+    /// SDL_GameControllerDB mappings of the controllers that SDL does not recognize, added when the game starts.
+    /// The "Gamepad setup" window of the editor creates them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gamepad_mappings: Vec<String>,
+    /// The version of the Lua API the project is written for, set when the project is created. The functions renamed
+    /// since this version keep working, with a warning.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<u32>,
+    /// The version of the game, like "1.2.0". Content packs record it when they are exported, and only mount on the
+    /// versions of the game they are compatible with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Parts of the game exported in their own bundle instead of bundle.vecta, to be mounted with `Loader.mountBundle`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content_packs: Vec<ContentPackInfo>,
+    /// The import rules of the editor: a glob pattern of source files, like "*.aseprite", and the command converting
+    /// them, like "aseprite --batch {in} --sheet {out}.png". The sources are not exported.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub import: BTreeMap<String, String>,
+    /// When true, the lists of physics objects returned to Lua are in the same order on every run and every platform.
+    /// The simulation itself is always deterministic across platforms.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deterministic_physics: bool,
+    #[serde(default, skip_serializing_if = "PhysicsInfo::is_empty")]
+    pub physics: PhysicsInfo,
+}
+
+/// The `[physics]` section of game.vecta.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde")]
+pub struct PhysicsInfo {
+    /// The named collision layers returned by `Physics.layer`, and which of them collide.
+    #[serde(default)]
+    pub layers: CollisionLayers,
+}
+
+impl PhysicsInfo {
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+}
+
+/// A content pack of the project, made of the files of some folders.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(crate = "vectarine_plugin_sdk::serde")]
+pub struct ContentPackInfo {
+    pub name: String,
+    /// The folders in the pack, relative to game.vecta, like "levels/forest".
+    pub folders: Vec<String>,
+}
+
+impl ContentPackInfo {
+    /// True when the file at `resource_path`, relative to game.vecta, is in one of the folders of the pack.
+    pub fn contains(&self, resource_path: &str) -> bool {
+        let resource_path = resource_path.replace('\\', "/");
+        let resource_path = resource_path.trim_start_matches("./");
+        self.folders.iter().any(|folder| {
+            let folder = folder.replace('\\', "/");
+            let folder = folder.trim_start_matches("./").trim_matches('/');
+            !folder.is_empty()
+                && resource_path
+                    .strip_prefix(folder)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+impl ProjectInfo {
+    /// The version of the Lua API of the project. Projects created before the versions existed use the first one.
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+            .unwrap_or(crate::apiversion::FIRST_API_VERSION)
+    }
+
+    /// The maximum number of frames skipped in a row, or None when frames are never skipped.
+    pub fn frame_skip_limit(&self) -> Option<u32> {
+        self.allow_frame_skip.then(|| {
+            self.max_consecutive_skips
+                .unwrap_or(crate::frameskip::DEFAULT_MAX_CONSECUTIVE_SKIPS)
+        })
+    }
 }
 
 impl Default for ProjectInfo {
@@ -27,6 +147,22 @@ impl Default for ProjectInfo {
             default_screen_width: 800,
             default_screen_height: 600,
             loading_animation: "pixel".to_string(),
+            fixed_tick_rate: None,
+            max_parallel_loads: None,
+            allow_frame_skip: false,
+            max_consecutive_skips: None,
+            audio_thread: false,
+            bundle_zstd_level: None,
+            fast_export: false,
+            pack_images: false,
+            gc_step_per_frame: false,
+            gamepad_mappings: vec![],
+            api_version: None,
+            version: None,
+            content_packs: vec![],
+            import: BTreeMap::new(),
+            deterministic_physics: false,
+            physics: PhysicsInfo::default(),
         }
     }
 }
@@ -34,7 +170,7 @@ impl Default for ProjectInfo {
 pub fn get_project_info(project_manifest_content: &str) -> Result<ProjectInfo> {
     let r = vectarine_plugin_sdk::toml::from_str::<ProjectInfo>(project_manifest_content);
     if let Ok(r) = r {
-        return Ok(r);
+        return check_project_info(r);
     }
     let manifest = project_manifest_content.parse::<vectarine_plugin_sdk::toml::Table>()?;
 
@@ -52,6 +188,21 @@ pub fn get_project_info(project_manifest_content: &str) -> Result<ProjectInfo> {
             .map(|v| v as u32)
             .unwrap_or(default)
     };
+    // Out of range values are rejected instead of wrapping around, a negative tick rate would become a huge one.
+    let get_optional_u32 = |key: &str| -> Result<Option<u32>> {
+        manifest
+            .get(key)
+            .and_then(|v| v.as_integer())
+            .map(|v| {
+                u32::try_from(v).map_err(|_| {
+                    vectarine_plugin_sdk::anyhow::anyhow!(
+                        "{key} must be between 0 and {}, got {v}",
+                        u32::MAX
+                    )
+                })
+            })
+            .transpose()
+    };
     let tags = manifest.get("tags").and_then(|v| v.as_array()).map(|arr| {
         arr.iter()
             .filter_map(|v| v.as_str())
@@ -69,7 +220,17 @@ pub fn get_project_info(project_manifest_content: &str) -> Result<ProjectInfo> {
                 .collect::<Vec<_>>()
         });
 
-    Ok(ProjectInfo {
+    let gamepad_mappings = manifest
+        .get("gamepad_mappings")
+        .and_then(|v| v.as_array())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        });
+
+    check_project_info(ProjectInfo {
         title: get_str_or_default("title", "Untitled Vectarine Game"),
         default_screen_width: get_u32_or_default("default_screen_width", 800),
         default_screen_height: get_u32_or_default("default_screen_height", 600),
@@ -79,5 +240,122 @@ pub fn get_project_info(project_manifest_content: &str) -> Result<ProjectInfo> {
         logo_path: get_str_or_default("logo_path", "assets/logo.png"),
         plugins: plugins.unwrap_or_else(std::vec::Vec::new),
         loading_animation: get_str_or_default("loading_animation", "default"),
+        fixed_tick_rate: if manifest.contains_key("fixed_tick_rate") {
+            get_optional_u32("fixed_tick_rate")?
+        } else {
+            get_optional_u32("fixed_update_rate")?
+        },
+        max_parallel_loads: get_optional_u32("max_parallel_loads")?,
+        allow_frame_skip: manifest
+            .get("allow_frame_skip")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        max_consecutive_skips: get_optional_u32("max_consecutive_skips")?,
+        audio_thread: manifest
+            .get("audio_thread")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        bundle_zstd_level: manifest
+            .get("bundle_zstd_level")
+            .and_then(|v| v.as_integer()),
+        fast_export: manifest
+            .get("fast_export")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        pack_images: manifest
+            .get("pack_images")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        gc_step_per_frame: manifest
+            .get("gc_step_per_frame")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        gamepad_mappings: gamepad_mappings.unwrap_or_default(),
+        api_version: get_optional_u32("api_version")?,
+        version: manifest
+            .get("version")
+            .and_then(|v| v.as_str())
+            .map(|v| v.to_string()),
+        content_packs: manifest
+            .get("content_packs")
+            .and_then(|v| v.clone().try_into::<Vec<ContentPackInfo>>().ok())
+            .unwrap_or_default(),
+        import: manifest
+            .get("import")
+            .and_then(|v| v.as_table())
+            .map(|table| {
+                table
+                    .iter()
+                    .filter_map(|(pattern, command)| {
+                        Some((pattern.clone(), command.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+        deterministic_physics: manifest
+            .get("deterministic_physics")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false),
+        physics: manifest
+            .get("physics")
+            .and_then(|v| v.clone().try_into::<PhysicsInfo>().ok())
+            .unwrap_or_default(),
     })
 }
+
+/// Rejects the values that parse but cannot be used.
+fn check_project_info(project_info: ProjectInfo) -> Result<ProjectInfo> {
+    if project_info.fixed_tick_rate == Some(0) {
+        return Err(vectarine_plugin_sdk::anyhow::anyhow!(
+            "fixed_tick_rate must be at least 1"
+        ));
+    }
+    if let Some(max_consecutive_skips) = project_info.max_consecutive_skips
+        && max_consecutive_skips > crate::frameskip::MAX_CONSECUTIVE_SKIPS_LIMIT
+    {
+        return Err(vectarine_plugin_sdk::anyhow::anyhow!(
+            "max_consecutive_skips must be at most {}, got {max_consecutive_skips}",
+            crate::frameskip::MAX_CONSECUTIVE_SKIPS_LIMIT
+        ));
+    }
+    Ok(project_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::get_project_info;
+
+    #[test]
+    fn fixed_tick_rate_is_parsed_and_bad_values_are_rejected() {
+        let project_info = get_project_info("fixed_tick_rate = 60").expect("Valid project");
+        assert_eq!(project_info.fixed_tick_rate, Some(60));
+        let project_info = get_project_info("fixed_update_rate = 30").expect("Valid project");
+        assert_eq!(project_info.fixed_tick_rate, Some(30));
+
+        assert!(get_project_info("fixed_tick_rate = -1").is_err());
+        assert!(get_project_info("fixed_tick_rate = 4294967296").is_err());
+        assert!(get_project_info("fixed_tick_rate = 0").is_err());
+    }
+
+    #[test]
+    fn api_version_out_of_range_is_rejected() {
+        let project_info = get_project_info("api_version = 2").expect("Valid project");
+        assert_eq!(project_info.api_version, Some(2));
+        assert!(get_project_info("api_version = -1").is_err());
+    }
+
+    #[test]
+    fn max_consecutive_skips_out_of_range_is_rejected() {
+        let project_info = get_project_info("max_consecutive_skips = 3").expect("Valid project");
+        assert_eq!(project_info.max_consecutive_skips, Some(3));
+        assert!(get_project_info("max_consecutive_skips = -1").is_err());
+        assert!(get_project_info("max_consecutive_skips = 1000").is_err());
+    }
+
+    #[test]
+    fn negative_max_parallel_loads_is_rejected() {
+        let project_info = get_project_info("max_parallel_loads = 8").expect("Valid project");
+        assert_eq!(project_info.max_parallel_loads, Some(8));
+        assert!(get_project_info("max_parallel_loads = -1").is_err());
+    }
+}
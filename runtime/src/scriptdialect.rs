@@ -0,0 +1,342 @@
+//! Scripts are written in Luau. To try the engine with an existing Lua 5.x game, like a LÖVE game, `.lua` files are
+//! compiled in a compatibility mode, closer to what Lua allows. The first lines of a script can choose the mode with a
+//! `--!strict` (Luau) or `--!compat` comment.
+//!
+//! The compatibility mode cannot change the syntax accepted by Luau, so the constructs of Lua that do not exist in
+//! Luau are detected before the script runs, to print what to use instead.
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use vectarine_plugin_sdk::mlua::chunk::Compiler;
+
+static ARE_LOCAL_NAMES_KEPT: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the names of the local variables in the compiled Luau scripts, so that a debugger can show them.
+/// Only affects the scripts compiled afterwards.
+pub fn keep_local_names(are_kept: bool) {
+    ARE_LOCAL_NAMES_KEPT.store(are_kept, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptDialect {
+    Luau,
+    /// Compiled without optimizations, so that the scripts replacing the functions of the standard libraries see their
+    /// replacements like in Lua, and without type information.
+    LuaCompat,
+}
+
+impl ScriptDialect {
+    /// The dialect of a script from its pragma, or from its extension when it has none.
+    pub fn detect(path: &Path, source: &[u8]) -> Self {
+        // Pragmas are only read before the first line of code, like the ones of Luau.
+        let pragmas = source
+            .split(|byte| *byte == b'\n')
+            .map(|line| line.trim_ascii())
+            .take_while(|line| line.is_empty() || line.starts_with(b"--"));
+        for line in pragmas {
+            match line {
+                b"--!strict" => return Self::Luau,
+                b"--!compat" => return Self::LuaCompat,
+                _ => {}
+            }
+        }
+        if path.extension().is_some_and(|extension| extension == "lua") {
+            Self::LuaCompat
+        } else {
+            Self::Luau
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Luau => "Luau",
+            Self::LuaCompat => "Lua compatibility mode",
+        }
+    }
+
+    /// The compiler of the scripts run from their source.
+    pub fn compiler(&self) -> Compiler {
+        match self {
+            Self::Luau => Compiler::new()
+                .set_optimization_level(2)
+                .set_debug_level(if ARE_LOCAL_NAMES_KEPT.load(Ordering::Relaxed) {
+                    2
+                } else {
+                    1
+                })
+                .set_type_info_level(1),
+            Self::LuaCompat => Compiler::new()
+                .set_optimization_level(0)
+                .set_debug_level(2)
+                .set_type_info_level(0),
+        }
+    }
+
+    /// True when the errors of the script name its dialect: the `.lua` files and the scripts in compatibility mode,
+    /// as the other scripts are Luau without surprise.
+    pub fn is_shown_in_errors(&self, path: &Path) -> bool {
+        *self == Self::LuaCompat || path.extension().is_some_and(|extension| extension == "lua")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedConstruct {
+    /// 1-indexed.
+    pub line: usize,
+    pub message: &'static str,
+}
+
+const GOTO_MESSAGE: &str =
+    "goto and labels do not exist in Luau: use break, continue or a function instead";
+const BITWISE_MESSAGE: &str = "the bitwise operators of Lua 5.3 do not exist in Luau: use bit32.band, bit32.bor, bit32.bxor and bit32.lshift";
+const ARG_MESSAGE: &str = "the implicit arg table of the varargs of Lua 5.0 does not exist: write local arg = table.pack(...)";
+
+/// The globals of Lua that do not exist or behave differently in Luau, with what to use instead.
+const UNSUPPORTED_GLOBALS: [(&str, &str); 6] = [
+    (
+        "setfenv",
+        "setfenv: Luau keeps it, but it disables the optimizations of the whole script; pass the values as arguments instead",
+    ),
+    (
+        "getfenv",
+        "getfenv: Luau keeps it, but it disables the optimizations of the whole script; use globals or upvalues instead",
+    ),
+    (
+        "loadstring",
+        "loadstring: the code it runs is not reloaded by the editor and is not compiled in exports; use Loader.loadScript",
+    ),
+    (
+        "load",
+        "load does not exist in Luau: use Loader.loadScript to run another file",
+    ),
+    (
+        "module",
+        "module() was removed after Lua 5.1: return a table from the script and load it with Loader.loadScript",
+    ),
+    (
+        "dofile",
+        "dofile does not exist in Luau: use Loader.loadScript",
+    ),
+];
+
+/// Finds the constructs of Lua that Luau rejects or runs differently. The strings and the comments are skipped, but
+/// the names are matched without knowing the scope, so a local variable named like a global of Lua is reported too.
+pub fn find_unsupported_constructs(source: &str) -> Vec<UnsupportedConstruct> {
+    let mut constructs = Vec::new();
+    let mut report = |line: usize, message: &'static str| {
+        if !constructs.iter().any(|construct: &UnsupportedConstruct| {
+            construct.line == line && construct.message == message
+        }) {
+            constructs.push(UnsupportedConstruct { line, message });
+        }
+    };
+    let bytes = source.as_bytes();
+    let mut line = 1;
+    let mut index = 0;
+    // The last character of the previous token, to tell the fields and the methods from the globals.
+    let mut previous = b' ';
+    while let Some(&byte) = bytes.get(index) {
+        match byte {
+            b'\n' => {
+                line += 1;
+                index += 1;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => {
+                index += 1;
+                continue;
+            }
+            b'-' if bytes.get(index + 1) == Some(&b'-') => {
+                index += 2;
+                match long_bracket_level(bytes, index) {
+                    Some(level) => index = skip_long_bracket(bytes, index, level, &mut line),
+                    None => {
+                        while bytes.get(index).is_some_and(|byte| *byte != b'\n') {
+                            index += 1;
+                        }
+                    }
+                }
+                continue;
+            }
+            b'[' if long_bracket_level(bytes, index).is_some() => {
+                let level = long_bracket_level(bytes, index).unwrap_or_default();
+                index = skip_long_bracket(bytes, index, level, &mut line);
+                previous = b'"';
+                continue;
+            }
+            b'"' | b'\'' | b'`' => {
+                index += 1;
+                while let Some(&next) = bytes.get(index) {
+                    index += 1;
+                    match next {
+                        b'\\' => index += 1,
+                        b'\n' => line += 1,
+                        _ if next == byte => break,
+                        _ => {}
+                    }
+                }
+                previous = b'"';
+                continue;
+            }
+            b':' if bytes.get(index + 1) == Some(&b':') => {
+                report(line, GOTO_MESSAGE);
+                index += 2;
+            }
+            b'&' | b'|' => {
+                report(line, BITWISE_MESSAGE);
+                index += 1;
+            }
+            b'<' | b'>' if bytes.get(index + 1) == Some(&byte) => {
+                report(line, BITWISE_MESSAGE);
+                index += 2;
+            }
+            // `~=` is the inequality, the other ~ are the bitwise xor and not of Lua 5.3.
+            b'~' if bytes.get(index + 1) != Some(&b'=') => {
+                report(line, BITWISE_MESSAGE);
+                index += 1;
+            }
+            _ if byte.is_ascii_alphabetic() || byte == b'_' => {
+                let start = index;
+                while bytes
+                    .get(index)
+                    .is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+                {
+                    index += 1;
+                }
+                let name = &source[start..index];
+                if name == "goto" && next_token_is_name(bytes, index) {
+                    report(line, GOTO_MESSAGE);
+                } else if name == "arg"
+                    && source[index..].starts_with(".n")
+                    && !bytes
+                        .get(index + 2)
+                        .is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+                {
+                    report(line, ARG_MESSAGE);
+                } else if !matches!(previous, b'.' | b':')
+                    && let Some((_, message)) = UNSUPPORTED_GLOBALS
+                        .iter()
+                        .find(|(global, _)| *global == name)
+                {
+                    report(line, message);
+                }
+                previous = b'a';
+                continue;
+            }
+            _ => index += 1,
+        }
+        previous = byte;
+    }
+    constructs
+}
+
+fn next_token_is_name(bytes: &[u8], mut index: usize) -> bool {
+    while bytes
+        .get(index)
+        .is_some_and(|byte| *byte == b' ' || *byte == b'\t')
+    {
+        index += 1;
+    }
+    bytes
+        .get(index)
+        .is_some_and(|byte| byte.is_ascii_alphabetic() || *byte == b'_')
+}
+
+/// The level of the long bracket starting at `index`, like 2 for `[==[`.
+fn long_bracket_level(bytes: &[u8], index: usize) -> Option<usize> {
+    if bytes.get(index) != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[index + 1..]
+        .iter()
+        .take_while(|byte| **byte == b'=')
+        .count();
+    (bytes.get(index + 1 + level) == Some(&b'[')).then_some(level)
+}
+
+/// Returns the index after the long bracket closing the one at `index`.
+fn skip_long_bracket(bytes: &[u8], mut index: usize, level: usize, line: &mut usize) -> usize {
+    index += level + 2;
+    while let Some(&byte) = bytes.get(index) {
+        if byte == b'\n' {
+            *line += 1;
+        }
+        if byte == b']'
+            && bytes[index + 1..]
+                .iter()
+                .take(level)
+                .all(|byte| *byte == b'=')
+            && bytes.get(index + 1 + level) == Some(&b']')
+        {
+            return index + level + 2;
+        }
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{ScriptDialect, find_unsupported_constructs};
+
+    #[test]
+    fn the_pragma_overrides_the_extension() {
+        let detect =
+            |path: &str, source: &str| ScriptDialect::detect(Path::new(path), source.as_bytes());
+        assert_eq!(detect("main.luau", "print(1)"), ScriptDialect::Luau);
+        assert_eq!(detect("conf.lua", "print(1)"), ScriptDialect::LuaCompat);
+        assert_eq!(
+            detect("conf.lua", "-- Config\n--!strict\nprint(1)"),
+            ScriptDialect::Luau
+        );
+        assert_eq!(
+            detect("main.luau", "\n--!compat\n"),
+            ScriptDialect::LuaCompat
+        );
+        // Too late to be a pragma.
+        assert_eq!(
+            detect("main.luau", "print(1)\n--!compat\n"),
+            ScriptDialect::Luau
+        );
+    }
+
+    #[test]
+    fn lua_constructs_are_found_outside_of_strings_and_comments() {
+        let source = r#"
+local x = a & b
+if x ~= 1 then goto done end
+::done::
+-- setfenv(1, {}) in a comment
+local s = "loadstring(s) in a string" .. [==[
+setfenv ]] still in the string
+]==]
+local f = loadstring(s)
+local y = ~x
+print(string.load, self:module())
+"#;
+        let lines = find_unsupported_constructs(source)
+            .iter()
+            .map(|construct| {
+                (
+                    construct.line,
+                    construct.message.split(' ').next().unwrap_or_default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                (2, "the"),
+                (3, "goto"),
+                (4, "goto"),
+                (9, "loadstring:"),
+                (10, "the"),
+            ]
+        );
+    }
+}
@@ -1,71 +1,646 @@
-// We use a global audio queue to manage mixing.
+// We mix the sounds ourselves. By default, the mixing happens on the main thread, which fills an SDL queue at every frame.
+// When the project sets `audio_thread`, the mixing happens on the audio thread of SDL instead, so that a long frame does
+// not starve the sound card. The main thread then only sends commands to the mixer.
+//
+// The mixer counts the frames it mixed (a frame is a sample for each speaker). This count is the time of the audio
+// clock: `play_sound_at` places a sound at an exact frame, and the `AudioClock` tells which frame is heard now.
+//
+// The channels read the decoded samples where they are instead of copying them, so mixing never allocates, which
+// matters on the audio thread. The sounds are decoded one at a time by a single worker thread.
 
 use std::cell::RefCell;
 use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use vectarine_plugin_sdk::sdl2;
 use vectarine_plugin_sdk::sdl2::Sdl;
+use vectarine_plugin_sdk::sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use crate::audioclock::AudioClock;
+use crate::io::time::now_ms;
+use crate::metrics::{MemoryCategory, MemoryGuard};
 
 static DURATION_OF_BUFFER_IN_MS: f32 = 150.0;
 
+/// The number of sounds a channel can hold without allocating. Playing more sounds after the current one than this
+/// allocates, which is fine on the main thread but not on the audio thread.
+const QUEUED_SOUNDS_CAPACITY: usize = 8;
+
+/// Number of times the sound card ran out of samples since the start. Each underrun is heard as a crackle.
+static UNDERRUN_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The samples of a sound, interleaved.
+pub struct DecodedSound {
+    pub samples: Box<[f32]>,
+    _memory: MemoryGuard,
+}
+
+impl DecodedSound {
+    pub fn new(samples: Vec<f32>) -> Self {
+        let memory = MemoryGuard::new(
+            MemoryCategory::Audio,
+            std::mem::size_of_val(samples.as_slice()),
+        );
+        Self {
+            samples: samples.into_boxed_slice(),
+            _memory: memory,
+        }
+    }
+}
+
+/// Sounds are decoded in the background. A sound played before the end of its decoding starts when it is decoded.
+pub type SoundData = Arc<OnceLock<DecodedSound>>;
+
+#[derive(Clone)]
+pub struct PlaySound {
+    pub sound: SoundData,
+    pub fade_in_ms: f32,
+    pub fade_out_ms: f32,
+    pub looped: bool,
+    /// Where the sound starts, from its beginning. A looped sound loops from its beginning.
+    pub start_ms: f32,
+}
+
+/// A sound in a channel, read from its decoded samples.
+pub struct QueuedSound {
+    pub sound: SoundData,
+    /// The index of the next sample to mix.
+    pub position: usize,
+    pub fade_in_samples: usize,
+    pub fade_out_samples: usize,
+    pub is_looped: bool,
+}
+
+impl QueuedSound {
+    /// The gain of the fades at a sample of a sound of `length` samples. The fades apply at every loop.
+    fn fade_gain(&self, length: usize) -> f32 {
+        let mut gain = 1.0;
+        if self.position < self.fade_in_samples {
+            gain *= self.position as f32 / self.fade_in_samples as f32;
+        }
+        let remaining = length - self.position;
+        if remaining < self.fade_out_samples {
+            gain *= remaining as f32 / self.fade_out_samples as f32;
+        }
+        gain
+    }
+
+    fn remaining_samples(&self) -> usize {
+        self.sound.get().map_or(0, |decoded| {
+            decoded.samples.len().saturating_sub(self.position)
+        })
+    }
+}
+
 pub struct AudioResourceBuffer {
-    pub buffer: VecDeque<f32>,
+    /// The sounds played one after the other. The first one is playing.
+    pub sounds: VecDeque<QueuedSound>,
     pub is_playing: bool,
     pub volume: f32,
+    /// From -1 for the left speaker only to 1 for the right speaker only.
+    pub pan: f32,
+    /// Whether the last sound added loops.
     pub is_looped: bool,
+    /// A sound waiting for its decoding to be done.
+    pub pending_play: Option<PlaySound>,
+    /// A sound waiting for the frame it should start at, or for its decoding to be done.
+    pub scheduled_play: Option<(PlaySound, u64)>,
+    /// The frame at which the last sound started, or will start if it was added after other sounds.
+    pub sound_start_frame: Option<u64>,
+    /// The frame of the last sound at which it started, see `PlaySound::start_ms`.
+    pub sound_start_offset_frames: u64,
+    pub sound_frame_count: u64,
+    /// Nothing can play the channel again: it is removed after its last sound.
+    pub is_released: bool,
+}
+
+impl AudioResourceBuffer {
+    fn has_sounds_to_play(&self) -> bool {
+        !self.sounds.is_empty() || self.pending_play.is_some() || self.scheduled_play.is_some()
+    }
 }
 
 impl Default for AudioResourceBuffer {
     fn default() -> Self {
         Self {
-            buffer: VecDeque::new(),
+            sounds: VecDeque::with_capacity(QUEUED_SOUNDS_CAPACITY),
             is_playing: true,
             is_looped: false,
             volume: 1.0,
+            pan: 0.0,
+            pending_play: None,
+            scheduled_play: None,
+            sound_start_frame: None,
+            sound_start_offset_frames: 0,
+            sound_frame_count: 0,
+            is_released: false,
         }
     }
 }
 
-// Invariant: ChannelId refers to an index in the audio_buffers vector of the AudioQueue.
+// Invariant: ChannelId refers to an index in the audio_buffers of the Mixer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChannelId(usize);
 
-pub struct AudioQueue {
-    pub audio_queue: sdl2::audio::AudioQueue<f32>,
-    pub audio_buffers: HashMap<ChannelId, AudioResourceBuffer>,
+/// Everything the main thread can ask the mixer. Commands are applied in the order they are sent.
+pub enum AudioCommand {
+    AddChannel(ChannelId),
+    Play(ChannelId, PlaySound),
+    /// Replaces what the channel plays with the sound, starting at the given frame of the mixer.
+    PlayAt(ChannelId, PlaySound, u64),
+    Resume(ChannelId),
+    Pause(ChannelId),
+    SetVolume(ChannelId, f32),
+    SetPan(ChannelId, f32),
+    /// The channel is removed once what it plays ends. Its looped sounds stop at the end of their loop.
+    ReleaseChannel(ChannelId),
+    FlushAll,
 }
 
-impl AudioQueue {
-    pub fn new(audio_queue: sdl2::audio::AudioQueue<f32>) -> Self {
+/// The state of a channel, as seen by the main thread.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelSnapshot {
+    pub is_playing: bool,
+    pub volume: f32,
+    pub is_looped: bool,
+    pub sound_start_frame: Option<u64>,
+    pub sound_start_offset_frames: u64,
+    pub sound_frame_count: u64,
+}
+
+impl Default for ChannelSnapshot {
+    fn default() -> Self {
+        let buffer = AudioResourceBuffer::default();
         Self {
-            audio_queue,
-            audio_buffers: HashMap::new(),
+            is_playing: buffer.is_playing,
+            volume: buffer.volume,
+            is_looped: buffer.is_looped,
+            sound_start_frame: buffer.sound_start_frame,
+            sound_start_offset_frames: buffer.sound_start_offset_frames,
+            sound_frame_count: buffer.sound_frame_count,
         }
     }
+}
+
+#[derive(Default)]
+pub struct Mixer {
+    pub audio_buffers: HashMap<ChannelId, AudioResourceBuffer>,
+    /// The number of frames mixed since the mixer was created. The next frame mixed has this index.
+    pub frames_mixed: u64,
+}
+
+impl Mixer {
+    pub fn apply(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::AddChannel(channel_id) => {
+                self.audio_buffers
+                    .insert(channel_id, AudioResourceBuffer::default());
+            }
+            AudioCommand::Play(channel_id, play) => {
+                self.update_buffer(channel_id, |audio_buffer| {
+                    audio_buffer.pending_play = Some(play);
+                });
+            }
+            AudioCommand::PlayAt(channel_id, play, start_frame) => {
+                self.update_buffer(channel_id, |audio_buffer| {
+                    audio_buffer.pending_play = None;
+                    audio_buffer.scheduled_play = Some((play, start_frame));
+                });
+            }
+            AudioCommand::Resume(channel_id) => {
+                self.update_buffer(channel_id, |audio_buffer| audio_buffer.is_playing = true);
+            }
+            AudioCommand::Pause(channel_id) => {
+                self.update_buffer(channel_id, |audio_buffer| audio_buffer.is_playing = false);
+            }
+            AudioCommand::SetVolume(channel_id, volume) => {
+                self.update_buffer(channel_id, |audio_buffer| audio_buffer.volume = volume);
+            }
+            AudioCommand::SetPan(channel_id, pan) => {
+                self.update_buffer(channel_id, |audio_buffer| {
+                    audio_buffer.pan = pan.clamp(-1.0, 1.0);
+                });
+            }
+            AudioCommand::ReleaseChannel(channel_id) => {
+                self.update_buffer(channel_id, |audio_buffer| {
+                    audio_buffer.is_released = true;
+                    for queued in &mut audio_buffer.sounds {
+                        queued.is_looped = false;
+                    }
+                    if let Some(play) = &mut audio_buffer.pending_play {
+                        play.looped = false;
+                    }
+                    if let Some((play, _)) = &mut audio_buffer.scheduled_play {
+                        play.looped = false;
+                    }
+                });
+                self.remove_released_channels();
+            }
+            AudioCommand::FlushAll => self.audio_buffers.clear(),
+        }
+        self.start_decoded_sounds();
+    }
+
+    fn remove_released_channels(&mut self) {
+        self.audio_buffers.retain(|_, audio_buffer| {
+            !audio_buffer.is_released || audio_buffer.has_sounds_to_play()
+        });
+    }
+
+    fn update_buffer<F>(&mut self, channel_id: ChannelId, f: F)
+    where
+        F: FnOnce(&mut AudioResourceBuffer),
+    {
+        // The channel can be missing when the command was sent before a flush.
+        if let Some(audio_buffer) = self.audio_buffers.get_mut(&channel_id) {
+            f(audio_buffer);
+        }
+    }
+
+    fn start_decoded_sounds(&mut self) {
+        for audio_buffer in self.audio_buffers.values_mut() {
+            let Some(play) = audio_buffer.pending_play.take() else {
+                continue;
+            };
+            if play.sound.get().is_none() {
+                audio_buffer.pending_play = Some(play);
+                continue;
+            }
+            // The sound starts after what the channel already plays.
+            let queued_samples = audio_buffer
+                .sounds
+                .iter()
+                .map(QueuedSound::remaining_samples)
+                .sum::<usize>();
+            let start_frame =
+                self.frames_mixed + (queued_samples / crate::AUDIO_CHANNELS as usize) as u64;
+            start_sound(audio_buffer, &play, start_frame);
+        }
+    }
+
     pub fn mix_audio(&mut self, bytes_to_advance: usize) -> Vec<f32> {
         let mut output = vec![0.0; bytes_to_advance * size_of::<f32>()];
+        self.mix_into(&mut output);
+        output
+    }
 
+    /// Adds the sound of every channel to the output.
+    pub fn mix_into(&mut self, output: &mut [f32]) {
+        self.start_decoded_sounds();
+        let channels = crate::AUDIO_CHANNELS as usize;
+        let first_frame = self.frames_mixed;
+        let frame_count = (output.len() / channels) as u64;
         for buffer in self.audio_buffers.values_mut() {
-            for output_sample in output.iter_mut() {
-                let sample = buffer.buffer.pop_front().unwrap_or(0.0);
-                if buffer.is_looped {
-                    buffer.buffer.push_back(sample);
-                }
-                *output_sample += sample * buffer.volume;
+            // A scheduled sound starts at its exact frame, so the output is mixed in two parts around that frame.
+            // Sounds scheduled before the output start at its first frame.
+            let start_offset = buffer
+                .scheduled_play
+                .as_ref()
+                .filter(|(play, _)| play.sound.get().is_some())
+                .map(|(_, start_frame)| start_frame.saturating_sub(first_frame))
+                .filter(|start_offset| *start_offset < frame_count);
+            let Some(start_offset) = start_offset else {
+                mix_channel(buffer, output);
+                continue;
+            };
+            let (before, after) = output.split_at_mut(start_offset as usize * channels);
+            mix_channel(buffer, before);
+            if let Some((play, _)) = buffer.scheduled_play.take() {
+                buffer.sounds.clear();
+                start_sound(buffer, &play, first_frame + start_offset);
             }
+            mix_channel(buffer, after);
         }
+        self.frames_mixed += frame_count;
+        self.remove_released_channels();
+    }
+
+    fn snapshot(&self, channel_id: ChannelId) -> ChannelSnapshot {
+        self.audio_buffers
+            .get(&channel_id)
+            .map(|audio_buffer| ChannelSnapshot {
+                is_playing: audio_buffer.is_playing,
+                volume: audio_buffer.volume,
+                is_looped: audio_buffer.is_looped,
+                sound_start_frame: audio_buffer.sound_start_frame,
+                sound_start_offset_frames: audio_buffer.sound_start_offset_frames,
+                sound_frame_count: audio_buffer.sound_frame_count,
+            })
+            .unwrap_or_default()
+    }
 
-        // Pad with zeros if needed.
-        if output.len() < bytes_to_advance {
-            let res = bytes_to_advance.saturating_sub(output.len());
-            output.extend_from_slice(&vec![0.0; res]);
+    fn write_snapshots(&self, snapshots: &mut HashMap<ChannelId, ChannelSnapshot>) {
+        snapshots.clear();
+        for channel_id in self.audio_buffers.keys() {
+            snapshots.insert(*channel_id, self.snapshot(*channel_id));
         }
-        output
     }
 }
 
+/// The output starts on a frame, so the even samples are for the left speaker and the odd ones for the right one.
+fn mix_channel(buffer: &mut AudioResourceBuffer, output: &mut [f32]) {
+    // A centered sound keeps its volume on both sides.
+    let left_gain = buffer.volume * (1.0 - buffer.pan).min(1.0);
+    let right_gain = buffer.volume * (1.0 + buffer.pan).min(1.0);
+    let mut index = 0;
+    while index < output.len() {
+        let Some(queued) = buffer.sounds.front_mut() else {
+            return;
+        };
+        let samples = queued
+            .sound
+            .get()
+            .map_or(&[][..], |decoded| &decoded.samples[..]);
+        while index < output.len() && queued.position < samples.len() {
+            let gain = if index % 2 == 0 {
+                left_gain
+            } else {
+                right_gain
+            };
+            output[index] += samples[queued.position] * gain * queued.fade_gain(samples.len());
+            queued.position += 1;
+            index += 1;
+        }
+        if queued.position < samples.len() {
+            return;
+        }
+        if queued.is_looped && !samples.is_empty() {
+            queued.position = 0;
+        } else {
+            buffer.sounds.pop_front();
+        }
+    }
+}
+
+/// Adds a decoded sound at the end of the channel, which is reached at `start_frame`.
+fn start_sound(audio_buffer: &mut AudioResourceBuffer, play: &PlaySound, start_frame: u64) {
+    let Some(decoded) = play.sound.get() else {
+        return;
+    };
+    let samples_per_ms =
+        (crate::AUDIO_CHANNELS as f32 * crate::AUDIO_SAMPLE_FREQUENCY as f32) / 1000.0;
+    let frame_count = decoded.samples.len() / crate::AUDIO_CHANNELS as usize;
+    let start_offset_frames = ((play.start_ms.max(0.0) * crate::AUDIO_SAMPLE_FREQUENCY as f32
+        / 1000.0) as usize)
+        .min(frame_count);
+    audio_buffer.sounds.push_back(QueuedSound {
+        sound: play.sound.clone(),
+        position: start_offset_frames * crate::AUDIO_CHANNELS as usize,
+        fade_in_samples: (play.fade_in_ms * samples_per_ms) as usize,
+        fade_out_samples: (play.fade_out_ms * samples_per_ms) as usize,
+        is_looped: play.looped,
+    });
+    audio_buffer.is_looped = play.looped;
+    audio_buffer.sound_start_frame = Some(start_frame);
+    audio_buffer.sound_start_offset_frames = start_offset_frames as u64;
+    audio_buffer.sound_frame_count = frame_count as u64;
+}
+
+/// A job of the decoder worker.
+#[cfg(not(target_os = "emscripten"))]
+type DecodeJob = Box<dyn FnOnce() + Send>;
+
+/// The decoder worker is started with the first sound to decode.
+#[cfg(not(target_os = "emscripten"))]
+static DECODE_JOBS: LazyLock<Mutex<Sender<DecodeJob>>> = LazyLock::new(|| {
+    let (sender, receiver) = std::sync::mpsc::channel::<DecodeJob>();
+    std::thread::Builder::new()
+        .name("audio decoder".to_string())
+        .spawn(move || {
+            for decode in receiver {
+                decode();
+            }
+        })
+        .expect("The audio decoder thread can be started");
+    Mutex::new(sender)
+});
+
+/// Runs `decode` on the decoder worker, after the sounds and the tile atlases already waiting. On the web, there are no
+/// threads, so this blocks.
+pub fn decode_in_background(decode: impl FnOnce() + Send + 'static) {
+    #[cfg(target_os = "emscripten")]
+    decode();
+    #[cfg(not(target_os = "emscripten"))]
+    {
+        let Ok(jobs) = DECODE_JOBS.lock() else {
+            return;
+        };
+        // The worker never stops, so the receiver is always there.
+        let _ = jobs.send(Box::new(decode));
+    }
+}
+
+/// Detects the calls of the audio callback arriving so late that the sound card ran out of samples.
+#[derive(Default)]
+struct UnderrunDetector {
+    last_callback: Option<Instant>,
+}
+
+impl UnderrunDetector {
+    /// SDL asks for the next buffer while the current one plays, so the sound card only runs dry when a buffer is
+    /// requested more than two buffers after the previous one.
+    fn is_underrun(&mut self, now: Instant, buffer_duration: Duration) -> bool {
+        let is_underrun = self
+            .last_callback
+            .is_some_and(|last_callback| now.duration_since(last_callback) > buffer_duration * 2);
+        self.last_callback = Some(now);
+        is_underrun
+    }
+}
+
+/// The mixer running on the audio thread of SDL.
+pub struct AudioThreadMixer {
+    mixer: Mixer,
+    commands: Receiver<AudioCommand>,
+    snapshots: Arc<Mutex<HashMap<ChannelId, ChannelSnapshot>>>,
+    clock: Arc<Mutex<AudioClock>>,
+    frames_mixed: Arc<AtomicU64>,
+    buffer_frames: u64,
+    samples_per_second: f32,
+    underrun_detector: UnderrunDetector,
+}
+
+impl AudioCallback for AudioThreadMixer {
+    type Channel = f32;
+
+    fn callback(&mut self, output: &mut [f32]) {
+        let buffer_duration =
+            Duration::from_secs_f32(output.len() as f32 / self.samples_per_second);
+        if self
+            .underrun_detector
+            .is_underrun(Instant::now(), buffer_duration)
+        {
+            UNDERRUN_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Ok(mut clock) = self.clock.try_lock() {
+            // SDL asks for a buffer when the previous one starts playing.
+            let frames_played = self.mixer.frames_mixed.saturating_sub(self.buffer_frames);
+            clock.observe(now_secs(), frames_played);
+        }
+
+        for command in self.commands.try_iter() {
+            self.mixer.apply(command);
+        }
+        output.fill(0.0);
+        self.mixer.mix_into(output);
+        self.frames_mixed
+            .store(self.mixer.frames_mixed, Ordering::Relaxed);
+
+        // Never wait for the main thread here. When it is reading the snapshots, the next callback updates them.
+        if let Ok(mut snapshots) = self.snapshots.try_lock() {
+            self.mixer.write_snapshots(&mut snapshots);
+        }
+    }
+}
+
+enum AudioOutput {
+    /// Mixed on the main thread and queued at every frame.
+    Queue {
+        audio_queue: sdl2::audio::AudioQueue<f32>,
+        mixer: Mixer,
+        has_queued_audio: bool,
+        buffer_frames: u64,
+    },
+    /// Mixed on the audio thread of SDL.
+    Thread {
+        _device: AudioDevice<AudioThreadMixer>,
+        commands: Sender<AudioCommand>,
+        snapshots: Arc<Mutex<HashMap<ChannelId, ChannelSnapshot>>>,
+        frames_mixed: Arc<AtomicU64>,
+        buffer_frames: u64,
+    },
+}
+
+impl AudioOutput {
+    /// The frame the next sound sent to the mixer can start at.
+    fn frames_mixed(&self) -> u64 {
+        match self {
+            AudioOutput::Queue { mixer, .. } => mixer.frames_mixed,
+            AudioOutput::Thread { frames_mixed, .. } => frames_mixed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The number of frames in a buffer of the sound card.
+    fn buffer_frames(&self) -> u64 {
+        match self {
+            AudioOutput::Queue { buffer_frames, .. }
+            | AudioOutput::Thread { buffer_frames, .. } => *buffer_frames,
+        }
+    }
+}
+
+struct AudioSystem {
+    audio: sdl2::AudioSubsystem,
+    output: AudioOutput,
+    /// The channels are never numbered again, even after a flush, so that releasing an old channel cannot remove a
+    /// new one.
+    channel_count: usize,
+    /// Kept when the output changes, so that the audio clock never goes back.
+    clock: Arc<Mutex<AudioClock>>,
+}
+
+impl AudioSystem {
+    fn send(&mut self, command: AudioCommand) {
+        match &mut self.output {
+            AudioOutput::Queue { mixer, .. } => mixer.apply(command),
+            AudioOutput::Thread { commands, .. } => {
+                // The receiver only disappears with the device, which lives as long as the sender.
+                let _ = commands.send(command);
+            }
+        }
+    }
+
+    fn snapshot(&self, channel_id: ChannelId) -> ChannelSnapshot {
+        match &self.output {
+            AudioOutput::Queue { mixer, .. } => mixer.snapshot(channel_id),
+            AudioOutput::Thread { snapshots, .. } => {
+                let Ok(snapshots) = snapshots.lock() else {
+                    return ChannelSnapshot::default();
+                };
+                snapshots.get(&channel_id).copied().unwrap_or_default()
+            }
+        }
+    }
+
+    fn clock_seconds(&self) -> f64 {
+        let Ok(mut clock) = self.clock.lock() else {
+            return 0.0;
+        };
+        clock.seconds(now_secs())
+    }
+
+    /// The frame heard now, which is where the mixer of a new output starts.
+    fn clock_frame(&self) -> u64 {
+        (self.clock_seconds() * crate::AUDIO_SAMPLE_FREQUENCY as f64) as u64
+    }
+}
+
+fn now_secs() -> f64 {
+    now_ms() / 1000.0
+}
+
 thread_local! {
-    static AUDIO_QUEUE: RefCell<Option<AudioQueue>> = const { RefCell::new(None) };
+    static AUDIO_SYSTEM: RefCell<Option<AudioSystem>> = const { RefCell::new(None) };
+}
+
+fn desired_spec() -> AudioSpecDesired {
+    AudioSpecDesired {
+        freq: Some(crate::AUDIO_SAMPLE_FREQUENCY),
+        channels: Some(crate::AUDIO_CHANNELS as u8), // stereo
+        samples: None,                               // default sample size
+    }
+}
+
+/// The mixer of the output starts at `first_frame`, so that its frames continue the audio clock.
+fn open_queue_output(audio: &sdl2::AudioSubsystem, first_frame: u64) -> AudioOutput {
+    let audio_queue = audio
+        .open_queue::<f32, Option<&str>>(None, &desired_spec())
+        .expect("Queue to be available");
+    let buffer_frames = audio_queue.spec().samples as u64;
+    AudioOutput::Queue {
+        audio_queue,
+        mixer: Mixer {
+            frames_mixed: first_frame,
+            ..Mixer::default()
+        },
+        has_queued_audio: false,
+        buffer_frames,
+    }
+}
+
+fn open_thread_output(
+    audio: &sdl2::AudioSubsystem,
+    clock: &Arc<Mutex<AudioClock>>,
+    first_frame: u64,
+) -> Result<AudioOutput, String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let snapshots = Arc::new(Mutex::new(HashMap::new()));
+    let frames_mixed = Arc::new(AtomicU64::new(first_frame));
+    let device = audio.open_playback(None::<&str>, &desired_spec(), |spec| AudioThreadMixer {
+        mixer: Mixer {
+            frames_mixed: first_frame,
+            ..Mixer::default()
+        },
+        commands: receiver,
+        snapshots: snapshots.clone(),
+        clock: clock.clone(),
+        frames_mixed: frames_mixed.clone(),
+        buffer_frames: spec.samples as u64,
+        samples_per_second: spec.freq as f32 * spec.channels as f32,
+        underrun_detector: UnderrunDetector::default(),
+    })?;
+    let buffer_frames = device.spec().samples as u64;
+    device.resume();
+    Ok(AudioOutput::Thread {
+        _device: device,
+        commands: sender,
+        snapshots,
+        frames_mixed,
+        buffer_frames,
+    })
 }
 
 pub fn init_sound_system(sdl: &Sdl) {
@@ -81,149 +656,518 @@ pub fn init_sound_system(sdl: &Sdl) {
         }
     };
 
-    let desired_spec = sdl2::audio::AudioSpecDesired {
-        freq: Some(crate::AUDIO_SAMPLE_FREQUENCY),
-        channels: Some(crate::AUDIO_CHANNELS as u8), // stereo
-        samples: None,                               // default sample size
-    };
-
-    let audio_queue = audio
-        .open_queue::<f32, Option<&str>>(None, &desired_spec)
-        .expect("Queue to be available");
+    let output = open_queue_output(&audio, 0);
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        *global_audio_system = Some(AudioSystem {
+            audio,
+            output,
+            channel_count: 0,
+            clock: Arc::new(Mutex::new(AudioClock::new(
+                crate::AUDIO_SAMPLE_FREQUENCY as f64,
+            ))),
+        });
+    });
+}
 
-    AUDIO_QUEUE.with_borrow_mut(|global_audio_queue| {
-        *global_audio_queue = Some(AudioQueue::new(audio_queue));
+/// Moves the mixing to the audio thread of SDL, or back to the main thread. The sounds playing are stopped.
+pub fn set_audio_thread(enabled: bool) {
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        let Some(audio_system) = global_audio_system else {
+            return;
+        };
+        if enabled == matches!(audio_system.output, AudioOutput::Thread { .. }) {
+            return;
+        }
+        let first_frame = audio_system.clock_frame();
+        audio_system.output = if enabled {
+            match open_thread_output(&audio_system.audio, &audio_system.clock, first_frame) {
+                Ok(output) => output,
+                Err(err) => {
+                    println!(
+                        "Failed to open the audio device: {:?}. Audio will be mixed on the main thread.",
+                        err
+                    );
+                    return;
+                }
+            }
+        } else {
+            open_queue_output(&audio_system.audio, first_frame)
+        };
     });
 }
 
-pub fn get_available_channel() -> ChannelId {
-    let mut channel_id = ChannelId(0);
-    AUDIO_QUEUE.with_borrow_mut(|global_audio_queue| {
-        let buffers = &mut global_audio_queue
+pub fn is_audio_threaded() -> bool {
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        global_audio_system
+            .as_ref()
+            .is_some_and(|audio_system| matches!(audio_system.output, AudioOutput::Thread { .. }))
+    })
+}
+
+pub fn underrun_count() -> u64 {
+    UNDERRUN_COUNT.load(Ordering::Relaxed)
+}
+
+fn send_command(command: AudioCommand) {
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        global_audio_system
             .as_mut()
             .expect("Audio system should be initialized")
-            .audio_buffers;
-        channel_id = ChannelId(buffers.len());
-        buffers.insert(channel_id, AudioResourceBuffer::default());
+            .send(command);
     });
-    channel_id
 }
 
-pub fn get_audio_buffer<F>(channel_id: ChannelId, f: F)
-where
-    F: FnOnce(&mut AudioResourceBuffer),
-{
-    AUDIO_QUEUE.with_borrow_mut(|global_audio_queue| {
-        let audio_queue = global_audio_queue
+pub fn get_available_channel() -> ChannelId {
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        let audio_system = global_audio_system
             .as_mut()
             .expect("Audio system should be initialized");
-        let audio_buffer = audio_queue
-            .audio_buffers
-            .get_mut(&channel_id)
-            .expect("Channel id refers to a channel in the buffers");
-        f(audio_buffer);
+        let channel_id = ChannelId(audio_system.channel_count);
+        audio_system.channel_count += 1;
+        audio_system.send(AudioCommand::AddChannel(channel_id));
+        channel_id
+    })
+}
+
+/// Frees the channel once what it plays ends, for the sounds that cannot be played again, like an audio buffer
+/// collected by Lua.
+pub fn release_channel(channel_id: ChannelId) {
+    // The channels of the buffers still alive when the program ends are released after the audio system is gone.
+    let _ = AUDIO_SYSTEM.try_with(|global_audio_system| {
+        if let Ok(mut global_audio_system) = global_audio_system.try_borrow_mut()
+            && let Some(audio_system) = global_audio_system.as_mut()
+        {
+            audio_system.send(AudioCommand::ReleaseChannel(channel_id));
+        }
     });
 }
 
-pub fn add_sound_data_to_channel(
-    channel_id: ChannelId,
-    data_to_play: &[f32],
-    fade_in_ms: f32,
-    fade_out_ms: f32,
-    looped: bool,
-) {
-    let byte_count_needed_for_a_ms =
-        (crate::AUDIO_CHANNELS as f32 * crate::AUDIO_SAMPLE_FREQUENCY as f32) / 1000.0;
+/// Adds the sound at the end of what the channel plays.
+pub fn play_sound(channel_id: ChannelId, play: PlaySound) {
+    send_command(AudioCommand::Play(channel_id, play));
+}
+
+/// Replaces what the channel plays with the sound, starting when the audio clock reaches `clock_seconds`.
+/// Returns how late the sound starts, in seconds: a sound scheduled before the frames already sent to the sound card
+/// starts with the next frame instead.
+pub fn play_sound_at(channel_id: ChannelId, play: PlaySound, clock_seconds: f64) -> f64 {
+    let sample_rate = crate::AUDIO_SAMPLE_FREQUENCY as f64;
+    let start_frame = (clock_seconds.max(0.0) * sample_rate).round() as u64;
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        let audio_system = global_audio_system
+            .as_mut()
+            .expect("Audio system should be initialized");
+        // With the audio thread, the next buffer can be mixed before the command arrives, so this is a lower bound.
+        let lateness_frames = audio_system
+            .output
+            .frames_mixed()
+            .saturating_sub(start_frame);
+        audio_system.send(AudioCommand::PlayAt(channel_id, play, start_frame));
+        lateness_frames as f64 / sample_rate
+    })
+}
 
-    let samples_to_fade_in = (fade_in_ms * byte_count_needed_for_a_ms) as usize;
-    let samples_to_fade_out = (fade_out_ms * byte_count_needed_for_a_ms) as usize;
+/// Whether the sounds are played by a sound card, and so whether the audio clock moves.
+pub fn has_audio_output() -> bool {
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| global_audio_system.is_some())
+}
 
-    // For loop is clearer in this context
-    #[allow(clippy::needless_range_loop)]
-    get_audio_buffer(channel_id, |audio_buffer| {
-        let mut sample_copy = data_to_play.to_vec();
-        // Linear fade
-        for i in 0..std::cmp::min(sample_copy.len(), samples_to_fade_in) {
-            sample_copy[i] *= i as f32 / samples_to_fade_in as f32;
-        }
-        for i in 0..std::cmp::min(sample_copy.len(), samples_to_fade_out) {
-            sample_copy[i] =
-                sample_copy[sample_copy.len() - i - 1] * (i as f32 / samples_to_fade_out as f32);
-        }
+/// Returns the time of the audio clock, in seconds. It starts with the sound card and follows the sounds heard, not
+/// the time of the frames. Returns 0 when there is no sound card.
+pub fn get_audio_clock() -> f64 {
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        global_audio_system
+            .as_ref()
+            .map_or(0.0, |audio_system| audio_system.clock_seconds())
+    })
+}
 
-        audio_buffer.buffer.extend(sample_copy);
-        audio_buffer.is_looped = looped;
-    });
+/// Returns the position heard in the last sound played by the channel, in seconds.
+pub fn get_position(channel_id: ChannelId) -> f64 {
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        let Some(audio_system) = global_audio_system.as_ref() else {
+            return 0.0;
+        };
+        let snapshot = audio_system.snapshot(channel_id);
+        let Some(sound_start_frame) = snapshot.sound_start_frame else {
+            return 0.0;
+        };
+        let sample_rate = crate::AUDIO_SAMPLE_FREQUENCY as f64;
+        let sound_frame_count = snapshot.sound_frame_count as f64;
+        let elapsed_frames =
+            (audio_system.clock_seconds() * sample_rate - sound_start_frame as f64).max(0.0)
+                + snapshot.sound_start_offset_frames as f64;
+        let position_frames = if snapshot.is_looped && sound_frame_count > 0.0 {
+            elapsed_frames % sound_frame_count
+        } else {
+            elapsed_frames.min(sound_frame_count)
+        };
+        position_frames / sample_rate
+    })
+}
+
+/// What limits the precision of the audio clock and of the scheduled sounds.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyReport {
+    pub is_threaded: bool,
+    pub sample_rate: u32,
+    /// The number of frames the sound card asks for at once.
+    pub buffer_frames: u64,
+    /// How far ahead of the audio clock the mixer is. Sounds scheduled closer than this to the clock start late.
+    pub lead_seconds: f64,
+    /// The mean error of the measurements of the audio clock.
+    pub clock_jitter_seconds: f64,
+}
+
+pub fn get_latency_report() -> Option<LatencyReport> {
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        let audio_system = global_audio_system.as_ref()?;
+        let sample_rate = crate::AUDIO_SAMPLE_FREQUENCY as f64;
+        let clock_jitter_seconds = audio_system
+            .clock
+            .lock()
+            .map_or(0.0, |clock| clock.jitter_secs());
+        let lead_frames =
+            audio_system.output.frames_mixed() as f64 - audio_system.clock_seconds() * sample_rate;
+        Some(LatencyReport {
+            is_threaded: matches!(audio_system.output, AudioOutput::Thread { .. }),
+            sample_rate: crate::AUDIO_SAMPLE_FREQUENCY as u32,
+            buffer_frames: audio_system.output.buffer_frames(),
+            lead_seconds: lead_frames.max(0.0) / sample_rate,
+            clock_jitter_seconds,
+        })
+    })
 }
 
 pub fn resume_audio(channel_id: ChannelId) {
-    get_audio_buffer(channel_id, |audio_buffer| {
-        audio_buffer.is_playing = true;
-    });
+    send_command(AudioCommand::Resume(channel_id));
 }
 
 pub fn pause_audio(channel_id: ChannelId) {
-    get_audio_buffer(channel_id, |audio_buffer| {
-        audio_buffer.is_playing = false;
-    });
+    send_command(AudioCommand::Pause(channel_id));
 }
 
 pub fn set_volume(channel_id: ChannelId, volume: f32) {
-    get_audio_buffer(channel_id, |audio_buffer| {
-        audio_buffer.volume = volume;
-    });
+    send_command(AudioCommand::SetVolume(channel_id, volume));
+}
+
+/// Moves the sound of the channel between the speakers, from -1 for the left one to 1 for the right one.
+pub fn set_pan(channel_id: ChannelId, pan: f32) {
+    send_command(AudioCommand::SetPan(channel_id, pan));
 }
 
+/// When the audio thread is used, the state of the channels is a snapshot taken by the audio thread, so the changes
+/// made during this frame are only visible a few milliseconds later.
 pub fn get_volume(channel_id: ChannelId) -> f32 {
-    let mut volume = 0.0;
-    get_audio_buffer(channel_id, |audio_buffer| {
-        volume = audio_buffer.volume;
-    });
-    volume
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        global_audio_system
+            .as_ref()
+            .expect("Audio system should be initialized")
+            .snapshot(channel_id)
+            .volume
+    })
 }
 
+/// Like `get_volume`, this is a snapshot when the audio thread is used.
 pub fn is_playing(channel_id: ChannelId) -> bool {
-    let mut is_playing = false;
-    get_audio_buffer(channel_id, |audio_buffer| {
-        is_playing = audio_buffer.is_playing;
-    });
-    is_playing
+    AUDIO_SYSTEM.with_borrow(|global_audio_system| {
+        global_audio_system
+            .as_ref()
+            .expect("Audio system should be initialized")
+            .snapshot(channel_id)
+            .is_playing
+    })
 }
 
 pub fn flush_all_samples() {
-    AUDIO_QUEUE.with_borrow_mut(|global_audio_queue| {
-        let Some(global_audio_queue) = global_audio_queue else {
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        let Some(audio_system) = global_audio_system else {
             return;
         };
-        global_audio_queue.audio_queue.clear();
-        global_audio_queue.audio_buffers.clear();
+        if let AudioOutput::Queue { audio_queue, .. } = &audio_system.output {
+            audio_queue.clear();
+        }
+        audio_system.send(AudioCommand::FlushAll);
     });
 }
 
 /// You need to call this regularly for the sound system to work.
-/// At least once every 150ms
+/// At least once every 150ms, unless the audio thread is used.
 pub fn update_sound_system() {
     // How long does a byte take to play (in ms)
     let byte_count_needed_for_a_ms =
         (crate::AUDIO_CHANNELS as f32 * crate::AUDIO_SAMPLE_FREQUENCY as f32) / 1000.0;
     let desired_size = (byte_count_needed_for_a_ms * DURATION_OF_BUFFER_IN_MS) as usize;
 
-    AUDIO_QUEUE.with_borrow_mut(|global_audio_queue| {
-        let Some(global_audio_queue) = global_audio_queue else {
+    AUDIO_SYSTEM.with_borrow_mut(|global_audio_system| {
+        let Some(AudioSystem {
+            output:
+                AudioOutput::Queue {
+                    audio_queue,
+                    mixer,
+                    has_queued_audio,
+                    buffer_frames,
+                },
+            clock,
+            ..
+        }) = global_audio_system
+        else {
             return;
         };
 
-        let size = global_audio_queue.audio_queue.size() as usize;
+        let size = audio_queue.size() as usize;
+        // The queue always contains something, even silence, so an empty queue means that the sound card ran dry.
+        if size == 0 && *has_queued_audio {
+            UNDERRUN_COUNT.fetch_add(1, Ordering::Relaxed);
+        }
+        if *has_queued_audio && let Ok(mut clock) = clock.lock() {
+            // The queue is read by whole buffers, and only seen at every frame, so this is less precise than the
+            // measurements made by the audio thread.
+            let queued_frames = (size / (size_of::<f32>() * crate::AUDIO_CHANNELS as usize)) as u64;
+            let frames_played = mixer
+                .frames_mixed
+                .saturating_sub(queued_frames + *buffer_frames);
+            clock.observe(now_secs(), frames_played);
+        }
         // We append to the queue enough bytes to be able to play for at least 150ms
         let number_of_bytes_to_append = desired_size.saturating_sub(size);
         if number_of_bytes_to_append > 0 {
-            let bytes_to_queue = global_audio_queue.mix_audio(number_of_bytes_to_append);
-            let result = global_audio_queue.audio_queue.queue_audio(&bytes_to_queue);
+            let bytes_to_queue = mixer.mix_audio(number_of_bytes_to_append);
+            let result = audio_queue.queue_audio(&bytes_to_queue);
             if let Err(result) = result {
                 println!("Failed to queue audio: {:?}", result);
             }
+            *has_queued_audio = true;
             // Resume all the time, we manage the bytes ourselves.
-            global_audio_queue.audio_queue.resume();
+            audio_queue.resume();
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(samples: Vec<f32>) -> SoundData {
+        let sound = SoundData::default();
+        let _ = sound.set(DecodedSound::new(samples));
+        sound
+    }
+
+    #[test]
+    fn sounds_start_when_they_are_decoded() {
+        let mut mixer = Mixer::default();
+        let channel_id = ChannelId(0);
+        let sound = SoundData::default();
+        mixer.apply(AudioCommand::AddChannel(channel_id));
+        mixer.apply(AudioCommand::Play(
+            channel_id,
+            PlaySound {
+                sound: sound.clone(),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: false,
+                start_ms: 0.0,
+            },
+        ));
+        assert_eq!(mixer.mix_audio(1), vec![0.0; 4]);
+
+        let _ = sound.set(DecodedSound::new(vec![0.5; 8]));
+        mixer.apply(AudioCommand::SetVolume(channel_id, 2.0));
+        assert_eq!(mixer.mix_audio(1), vec![1.0; 4]);
+
+        // Panned to the right, the left speaker is silent.
+        mixer.apply(AudioCommand::SetVolume(channel_id, 1.0));
+        mixer.apply(AudioCommand::SetPan(channel_id, 1.0));
+        assert_eq!(mixer.mix_audio(1), vec![0.0, 0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn scheduled_sounds_start_at_their_frame() {
+        let mut mixer = Mixer::default();
+        let channel_id = ChannelId(0);
+        let play = PlaySound {
+            sound: decoded(vec![1.0, 1.0, 0.5, 0.5]),
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            looped: false,
+            start_ms: 0.0,
+        };
+        mixer.apply(AudioCommand::AddChannel(channel_id));
+        mixer.apply(AudioCommand::PlayAt(channel_id, play.clone(), 3));
+        let mut output = vec![0.0; 8];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0]);
+        let mut output = vec![0.0; 8];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [0.5, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(mixer.snapshot(channel_id).sound_start_frame, Some(3));
+
+        // Too late: the sound starts with the next frame mixed.
+        mixer.apply(AudioCommand::PlayAt(channel_id, play, 5));
+        let mut output = vec![0.0; 4];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [1.0, 1.0, 0.5, 0.5]);
+        assert_eq!(mixer.snapshot(channel_id).sound_start_frame, Some(8));
+        assert_eq!(mixer.frames_mixed, 10);
+    }
+
+    #[test]
+    fn sounds_can_start_after_their_beginning() {
+        let mut mixer = Mixer::default();
+        let channel_id = ChannelId(0);
+        // One second of silence, then the part played.
+        let mut samples =
+            vec![0.0; (crate::AUDIO_SAMPLE_FREQUENCY * crate::AUDIO_CHANNELS) as usize];
+        samples.extend([1.0, 1.0, 0.5, 0.5]);
+        mixer.apply(AudioCommand::AddChannel(channel_id));
+        mixer.apply(AudioCommand::Play(
+            channel_id,
+            PlaySound {
+                sound: decoded(samples),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: false,
+                start_ms: 1000.0,
+            },
+        ));
+        let mut output = vec![0.0; 6];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [1.0, 1.0, 0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(
+            mixer.snapshot(channel_id).sound_start_offset_frames,
+            crate::AUDIO_SAMPLE_FREQUENCY as u64
+        );
+    }
+
+    #[test]
+    fn commands_for_flushed_channels_are_ignored() {
+        let mut mixer = Mixer::default();
+        mixer.apply(AudioCommand::AddChannel(ChannelId(0)));
+        mixer.apply(AudioCommand::FlushAll);
+        mixer.apply(AudioCommand::Play(
+            ChannelId(0),
+            PlaySound {
+                sound: decoded(vec![1.0; 4]),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: false,
+                start_ms: 0.0,
+            },
+        ));
+        assert!(mixer.audio_buffers.is_empty());
+    }
+
+    #[test]
+    fn released_channels_are_removed_after_their_last_sound() {
+        let mut mixer = Mixer::default();
+        let play = |looped| PlaySound {
+            sound: decoded(vec![1.0; 4]),
+            fade_in_ms: 0.0,
+            fade_out_ms: 0.0,
+            looped,
+            start_ms: 0.0,
+        };
+        mixer.apply(AudioCommand::AddChannel(ChannelId(0)));
+        mixer.apply(AudioCommand::Play(ChannelId(0), play(true)));
+        mixer.apply(AudioCommand::AddChannel(ChannelId(1)));
+
+        assert_eq!(mixer.mix_audio(1), vec![1.0; 4]);
+        mixer.apply(AudioCommand::ReleaseChannel(ChannelId(0)));
+        mixer.apply(AudioCommand::ReleaseChannel(ChannelId(1)));
+        // The silent channel is removed at once, the looped sound ends its loop first.
+        assert_eq!(
+            mixer.audio_buffers.keys().copied().collect::<Vec<_>>(),
+            [ChannelId(0)]
+        );
+        assert_eq!(mixer.mix_audio(1), vec![1.0; 4]);
+        assert!(mixer.audio_buffers.is_empty());
+    }
+
+    #[test]
+    fn a_stalled_callback_is_an_underrun() {
+        let buffer_duration = Duration::from_millis(10);
+        let mut detector = UnderrunDetector::default();
+        let start = Instant::now();
+        let callback_times_ms = [0, 10, 20, 30, 40];
+        for time_ms in callback_times_ms {
+            let now = start + Duration::from_millis(time_ms);
+            assert!(!detector.is_underrun(now, buffer_duration));
+        }
+        // A 50ms stall of whoever fills the sound card.
+        assert!(detector.is_underrun(start + Duration::from_millis(90), buffer_duration));
+        assert!(!detector.is_underrun(start + Duration::from_millis(100), buffer_duration));
+    }
+
+    #[test]
+    fn looped_sounds_restart_without_copying_their_samples() {
+        let mut mixer = Mixer::default();
+        let channel_id = ChannelId(0);
+        mixer.apply(AudioCommand::AddChannel(channel_id));
+        mixer.apply(AudioCommand::Play(
+            channel_id,
+            PlaySound {
+                sound: decoded(vec![1.0, 1.0, 0.5, 0.5]),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: true,
+                start_ms: 0.0,
+            },
+        ));
+        let capacity = mixer.audio_buffers[&channel_id].sounds.capacity();
+        let mut output = vec![0.0; 10];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [1.0, 1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, 1.0, 1.0]);
+        assert_eq!(mixer.audio_buffers[&channel_id].sounds.capacity(), capacity);
+
+        // A sound played after the looped one is never reached.
+        mixer.apply(AudioCommand::Play(
+            channel_id,
+            PlaySound {
+                sound: decoded(vec![0.25; 4]),
+                fade_in_ms: 0.0,
+                fade_out_ms: 0.0,
+                looped: false,
+                start_ms: 0.0,
+            },
+        ));
+        let mut output = vec![0.0; 4];
+        mixer.mix_into(&mut output);
+        assert_eq!(output, [0.5, 0.5, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn fades_are_applied_at_both_ends_of_the_sound() {
+        let mut queued = QueuedSound {
+            sound: decoded(vec![1.0; 8]),
+            position: 0,
+            fade_in_samples: 4,
+            fade_out_samples: 2,
+            is_looped: false,
+        };
+        let gains = (0..8)
+            .map(|position| {
+                queued.position = position;
+                queued.fade_gain(8)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(gains, [0.0, 0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn sounds_are_decoded_in_order_by_one_worker() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for index in 0..4 {
+            let sender = sender.clone();
+            decode_in_background(move || {
+                let _ = sender.send((index, std::thread::current().id()));
+            });
+        }
+        let jobs = receiver.iter().take(4).collect::<Vec<_>>();
+        assert_eq!(
+            jobs.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert!(jobs.iter().all(|(_, thread)| *thread == jobs[0].1));
+    }
+}
@@ -0,0 +1,275 @@
+//! Broad phases: structures that find which entities are close to each other without testing every pair.
+//!
+//! Two structures implement [`BroadPhase`]:
+//! - [`Dbvh`], a tree of bounding boxes that adapts to entities of any size and to empty areas.
+//! - [`SpatialHash`], a grid of cells of one size. It is faster to build and to query when there are a lot of entities
+//!   of similar sizes, like the bullets of a bullet-hell game, but slow when some entities span many cells.
+//!
+//! Entities are circles (spheres in 3D). The boxes only select candidates that are then tested exactly,
+//! so both structures return the same entities for the same queries.
+//!
+//! Run `cargo bench -p runtime --bench broadphase` to compare them.
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use crate::math::Vect;
+
+pub mod dbvh;
+pub mod spatialhash;
+
+pub use dbvh::Dbvh;
+pub use spatialhash::{SpatialHash, suggested_cell_size};
+
+pub type EntityId = u32;
+
+/// The hash of rustc. The default hash, SipHash, is several times slower for small keys like ids and cells, and its
+/// protection against keys chosen by an attacker is not needed here.
+#[derive(Clone, Copy, Debug, Default)]
+struct FxHasher(u64);
+
+impl Hasher for FxHasher {
+    fn finish(&self) -> u64 {
+        // The multiplication only mixes the low bits into the high bits, and the hash map uses the low bits.
+        self.0.rotate_left(26)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.0 = (self.0.rotate_left(5) ^ u64::from_le_bytes(word))
+                .wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+        }
+    }
+}
+
+type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb<const N: usize> {
+    pub min: Vect<N>,
+    pub max: Vect<N>,
+}
+
+impl<const N: usize> Aabb<N> {
+    pub fn new(min: Vect<N>, max: Vect<N>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..N).all(|i| self.min.0[i] <= other.max.0[i] && other.min.0[i] <= self.max.0[i])
+    }
+
+    pub fn contains(&self, other: &Self) -> bool {
+        (0..N).all(|i| self.min.0[i] <= other.min.0[i] && other.max.0[i] <= self.max.0[i])
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, margin: f32) -> Self {
+        let margin = Vect([margin; N]);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+}
+
+/// The shape of the entities of a broad phase.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Circle<const N: usize> {
+    pub center: Vect<N>,
+    pub radius: f32,
+}
+
+impl<const N: usize> Circle<N> {
+    pub fn new(center: Vect<N>, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn aabb(&self) -> Aabb<N> {
+        Aabb {
+            min: self.center,
+            max: self.center,
+        }
+        .grow(self.radius)
+    }
+
+    /// Circles that touch overlap.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        let radii = self.radius + other.radius;
+        (self.center - other.center).length_sq() <= radii * radii
+    }
+
+    pub fn overlaps_aabb(&self, aabb: &Aabb<N>) -> bool {
+        let closest = self.center.max(aabb.min).min(aabb.max);
+        (self.center - closest).length_sq() <= self.radius * self.radius
+    }
+}
+
+/// The operations shared by the broad phases, so that the code using them can choose the structure.
+pub trait BroadPhase<const N: usize> {
+    /// Adds the entity. An entity that is already there is moved instead.
+    fn insert(&mut self, entity: EntityId, position: Vect<N>, radius: f32);
+
+    /// Returns false when the entity was not there.
+    fn remove(&mut self, entity: EntityId) -> bool;
+
+    /// Moves the entity. Returns false, without adding it, when the entity was not there.
+    fn update(&mut self, entity: EntityId, position: Vect<N>, radius: f32) -> bool;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `f` once with every entity overlapping the circle, in no particular order.
+    fn query_circle(&self, center: Vect<N>, radius: f32, f: impl FnMut(EntityId));
+
+    /// Calls `f` once with every entity overlapping the box, in no particular order.
+    fn query_aabb(&self, aabb: Aabb<N>, f: impl FnMut(EntityId));
+
+    /// Calls `f` once with every pair of entities overlapping each other, in no particular order.
+    fn for_each_pair(&self, f: impl FnMut(EntityId, EntityId));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aabb, BroadPhase, Dbvh, EntityId, SpatialHash, suggested_cell_size};
+    use crate::math::Vect;
+    use std::collections::BTreeSet;
+
+    /// Deterministic random numbers, so that a failing seed can be run again.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn range(&mut self, min: f32, max: f32) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            let unit = (self.0 >> 40) as f32 / (1u64 << 24) as f32;
+            min + (max - min) * unit
+        }
+
+        fn point(&mut self) -> Vect<2> {
+            Vect([self.range(-100.0, 100.0), self.range(-100.0, 100.0)])
+        }
+    }
+
+    fn pairs(broad_phase: &impl BroadPhase<2>) -> BTreeSet<(EntityId, EntityId)> {
+        let mut pairs = BTreeSet::new();
+        broad_phase.for_each_pair(|a, b| {
+            assert!(
+                pairs.insert((a.min(b), a.max(b))),
+                "({a}, {b}) reported twice"
+            );
+        });
+        pairs
+    }
+
+    fn query_aabb(broad_phase: &impl BroadPhase<2>, aabb: Aabb<2>) -> BTreeSet<EntityId> {
+        let mut entities = BTreeSet::new();
+        broad_phase.query_aabb(aabb, |entity| {
+            assert!(entities.insert(entity), "{entity} reported twice");
+        });
+        entities
+    }
+
+    fn query_circle(
+        broad_phase: &impl BroadPhase<2>,
+        center: Vect<2>,
+        radius: f32,
+    ) -> BTreeSet<EntityId> {
+        let mut entities = BTreeSet::new();
+        broad_phase.query_circle(center, radius, |entity| {
+            assert!(entities.insert(entity), "{entity} reported twice");
+        });
+        entities
+    }
+
+    #[test]
+    fn both_structures_find_the_same_entities_on_random_data() {
+        for seed in 1..=20u64 {
+            let mut random = XorShift(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            // Some seeds have entities of very different sizes, which span many cells.
+            let max_radius = if seed % 4 == 0 { 30.0 } else { 3.0 };
+            let mut entities = (0..50 + seed as EntityId * 20)
+                .map(|entity| (entity, random.point(), random.range(0.5, max_radius)))
+                .collect::<Vec<_>>();
+            let mut spatial_hash = SpatialHash::new(suggested_cell_size(
+                entities.iter().map(|(_, _, radius)| *radius),
+            ));
+            let mut dbvh = Dbvh::new();
+            for (entity, position, radius) in &entities {
+                spatial_hash.insert(*entity, *position, *radius);
+                dbvh.insert(*entity, *position, *radius);
+            }
+
+            for step in 0..3 {
+                let mut expected_pairs = BTreeSet::new();
+                for (i, (a, a_position, a_radius)) in entities.iter().enumerate() {
+                    for (b, b_position, b_radius) in &entities[i + 1..] {
+                        let radii = a_radius + b_radius;
+                        if (*a_position - *b_position).length_sq() <= radii * radii {
+                            expected_pairs.insert((*a.min(b), *a.max(b)));
+                        }
+                    }
+                }
+                assert_eq!(pairs(&dbvh), expected_pairs, "seed {seed}, step {step}");
+                assert_eq!(
+                    pairs(&spatial_hash),
+                    expected_pairs,
+                    "seed {seed}, step {step}"
+                );
+
+                for _ in 0..10 {
+                    let min = random.point() - Vect([20.0, 20.0]);
+                    let aabb = Aabb::new(
+                        min,
+                        min + Vect([random.range(0.0, 60.0), random.range(0.0, 60.0)]),
+                    );
+                    assert_eq!(query_aabb(&spatial_hash, aabb), query_aabb(&dbvh, aabb));
+                    let center = random.point();
+                    let radius = random.range(0.0, 40.0);
+                    assert_eq!(
+                        query_circle(&spatial_hash, center, radius),
+                        query_circle(&dbvh, center, radius)
+                    );
+                }
+
+                // Move some entities a bit, some far away, resize some and remove others.
+                for (entity, position, radius) in entities.iter_mut() {
+                    match *entity % 5 {
+                        0 => {
+                            *position =
+                                *position + Vect([random.range(-2.0, 2.0), random.range(-2.0, 2.0)])
+                        }
+                        1 => *position = random.point(),
+                        2 => *radius = random.range(0.5, max_radius),
+                        _ => continue,
+                    }
+                    assert!(spatial_hash.update(*entity, *position, *radius));
+                    assert!(dbvh.update(*entity, *position, *radius));
+                }
+                for (entity, _, _) in entities.split_off(entities.len() - 10) {
+                    assert!(spatial_hash.remove(entity));
+                    assert!(dbvh.remove(entity));
+                    assert!(!spatial_hash.remove(entity));
+                    assert!(!dbvh.update(entity, Vect::zero(), 1.0));
+                }
+                assert_eq!(spatial_hash.len(), entities.len());
+                assert_eq!(dbvh.len(), entities.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use crate::{
+    math::Vect,
+    space::{Aabb, BroadPhase, Circle, EntityId, FxHashMap},
+};
+
+const NO_NODE: usize = usize::MAX;
+
+/// The boxes of the leaves are larger than their entity by this fraction of the radius, so that an entity moving a bit
+/// stays in its box and the tree is not changed.
+const FAT_MARGIN_RATIO: f32 = 0.25;
+
+#[derive(Debug)]
+struct Node<const N: usize> {
+    aabb: Aabb<N>,
+    parent: usize,
+    children: [usize; 2],
+    /// 0 for leaves.
+    height: u32,
+    /// Only leaves have an entity.
+    entity: Option<(EntityId, Circle<N>)>,
+}
+
+impl<const N: usize> Node<N> {
+    fn is_leaf(&self) -> bool {
+        self.children[0] == NO_NODE
+    }
+}
+
+/// A dynamic bounding volume hierarchy: a balanced binary tree of boxes whose leaves are the entities.
+///
+/// Entities are inserted where they make the boxes of the tree grow the least, so the tree adapts to entities of any
+/// size and to the empty areas of the level. The boxes of the leaves are a bit larger than their entity, so small moves
+/// only update the leaf.
+#[derive(Debug)]
+pub struct Dbvh<const N: usize> {
+    nodes: Vec<Node<N>>,
+    free_nodes: Vec<usize>,
+    root: usize,
+    leaf_of_entity: FxHashMap<EntityId, usize>,
+}
+
+impl<const N: usize> Default for Dbvh<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The cost of a box when building the tree: its perimeter in 2D. Queries visit fewer nodes when the boxes are small.
+fn cost<const N: usize>(aabb: &Aabb<N>) -> f32 {
+    (aabb.max - aabb.min).0.iter().sum()
+}
+
+impl<const N: usize> Dbvh<N> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: NO_NODE,
+            leaf_of_entity: FxHashMap::default(),
+        }
+    }
+
+    /// The height of the tree, 0 when it has one entity. Queries visit about this many levels.
+    pub fn height(&self) -> u32 {
+        if self.root == NO_NODE {
+            0
+        } else {
+            self.nodes[self.root].height
+        }
+    }
+
+    fn allocate_node(&mut self, node: Node<N>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn fat_aabb(circle: &Circle<N>) -> Aabb<N> {
+        circle.aabb().grow(circle.radius * FAT_MARGIN_RATIO)
+    }
+
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        let children = &mut self.nodes[parent].children;
+        if children[0] == old_child {
+            children[0] = new_child;
+        } else {
+            children[1] = new_child;
+        }
+    }
+
+    /// Recomputes the box and the height of an inner node from its children.
+    fn refresh(&mut self, index: usize) {
+        let [a, b] = self.nodes[index].children;
+        let aabb = self.nodes[a].aabb.union(&self.nodes[b].aabb);
+        let height = 1 + self.nodes[a].height.max(self.nodes[b].height);
+        let node = &mut self.nodes[index];
+        node.aabb = aabb;
+        node.height = height;
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NO_NODE {
+            self.root = leaf;
+            self.nodes[leaf].parent = NO_NODE;
+            return;
+        }
+
+        // Go down to the sibling that makes the boxes grow the least.
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut sibling = self.root;
+        while !self.nodes[sibling].is_leaf() {
+            let node = &self.nodes[sibling];
+            let combined_cost = cost(&node.aabb.union(&leaf_aabb));
+            // Making a new parent for this node and the leaf.
+            let cost_here = 2.0 * combined_cost;
+            // Every ancestor of the children grows by this much if the leaf goes further down.
+            let inherited_cost = 2.0 * (combined_cost - cost(&node.aabb));
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let grown = cost(&child.aabb.union(&leaf_aabb));
+                if child.is_leaf() {
+                    grown + inherited_cost
+                } else {
+                    grown - cost(&child.aabb) + inherited_cost
+                }
+            };
+            let [a, b] = node.children;
+            let (cost_a, cost_b) = (child_cost(a), child_cost(b));
+            if cost_here < cost_a && cost_here < cost_b {
+                break;
+            }
+            sibling = if cost_a < cost_b { a } else { b };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate_node(Node {
+            aabb: leaf_aabb.union(&self.nodes[sibling].aabb),
+            parent: old_parent,
+            children: [sibling, leaf],
+            height: self.nodes[sibling].height + 1,
+            entity: None,
+        });
+        if old_parent == NO_NODE {
+            self.root = new_parent;
+        } else {
+            self.replace_child(old_parent, sibling, new_parent);
+        }
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+        self.fix_upwards(old_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NO_NODE;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let [a, b] = self.nodes[parent].children;
+        let sibling = if a == leaf { b } else { a };
+        self.nodes[sibling].parent = grandparent;
+        if grandparent == NO_NODE {
+            self.root = sibling;
+        } else {
+            self.replace_child(grandparent, parent, sibling);
+        }
+        self.free_nodes.push(parent);
+        self.fix_upwards(grandparent);
+    }
+
+    /// Balances and refreshes the ancestors after a leaf was added or removed below `index`.
+    fn fix_upwards(&mut self, mut index: usize) {
+        while index != NO_NODE {
+            index = self.balance(index);
+            self.refresh(index);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    /// Rotates the higher child up when the heights of the children differ by more than one.
+    /// Returns the node now at the place of `index`.
+    fn balance(&mut self, index: usize) -> usize {
+        let node = &self.nodes[index];
+        if node.is_leaf() {
+            return index;
+        }
+        let [a, b] = node.children;
+        let (height_a, height_b) = (self.nodes[a].height, self.nodes[b].height);
+        if height_a > height_b + 1 {
+            self.rotate_up(index, a)
+        } else if height_b > height_a + 1 {
+            self.rotate_up(index, b)
+        } else {
+            index
+        }
+    }
+
+    /// Puts `child` at the place of its parent `index`. The parent takes the lower child of `child`.
+    fn rotate_up(&mut self, index: usize, child: usize) -> usize {
+        let parent = self.nodes[index].parent;
+        self.nodes[child].parent = parent;
+        if parent == NO_NODE {
+            self.root = child;
+        } else {
+            self.replace_child(parent, index, child);
+        }
+
+        let [f, g] = self.nodes[child].children;
+        let (kept, moved) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+        self.nodes[child].children = [index, kept];
+        self.nodes[index].parent = child;
+        self.replace_child(index, child, moved);
+        self.nodes[moved].parent = index;
+        self.refresh(index);
+        self.refresh(child);
+        child
+    }
+
+    /// Calls `f` with the leaves whose box intersects `aabb`.
+    fn for_each_leaf_in(&self, aabb: &Aabb<N>, mut f: impl FnMut(EntityId, &Circle<N>)) {
+        if self.root == NO_NODE {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.intersects(aabb) {
+                continue;
+            }
+            match &node.entity {
+                Some((entity, circle)) => f(*entity, circle),
+                None => stack.extend(node.children),
+            }
+        }
+    }
+}
+
+impl<const N: usize> BroadPhase<N> for Dbvh<N> {
+    fn insert(&mut self, entity: EntityId, position: Vect<N>, radius: f32) {
+        if self.update(entity, position, radius) {
+            return;
+        }
+        let circle = Circle::new(position, radius);
+        let leaf = self.allocate_node(Node {
+            aabb: Self::fat_aabb(&circle),
+            parent: NO_NODE,
+            children: [NO_NODE; 2],
+            height: 0,
+            entity: Some((entity, circle)),
+        });
+        self.leaf_of_entity.insert(entity, leaf);
+        self.insert_leaf(leaf);
+    }
+
+    fn remove(&mut self, entity: EntityId) -> bool {
+        let Some(leaf) = self.leaf_of_entity.remove(&entity) else {
+            return false;
+        };
+        self.remove_leaf(leaf);
+        self.nodes[leaf].entity = None;
+        self.free_nodes.push(leaf);
+        true
+    }
+
+    fn update(&mut self, entity: EntityId, position: Vect<N>, radius: f32) -> bool {
+        let Some(&leaf) = self.leaf_of_entity.get(&entity) else {
+            return false;
+        };
+        let circle = Circle::new(position, radius);
+        self.nodes[leaf].entity = Some((entity, circle));
+        if self.nodes[leaf].aabb.contains(&circle.aabb()) {
+            return true;
+        }
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = Self::fat_aabb(&circle);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.leaf_of_entity.len()
+    }
+
+    fn query_circle(&self, center: Vect<N>, radius: f32, mut f: impl FnMut(EntityId)) {
+        let query = Circle::new(center, radius);
+        self.for_each_leaf_in(&query.aabb(), |entity, circle| {
+            if circle.overlaps(&query) {
+                f(entity);
+            }
+        });
+    }
+
+    fn query_aabb(&self, aabb: Aabb<N>, mut f: impl FnMut(EntityId)) {
+        self.for_each_leaf_in(&aabb, |entity, circle| {
+            if circle.overlaps_aabb(&aabb) {
+                f(entity);
+            }
+        });
+    }
+
+    fn for_each_pair(&self, mut f: impl FnMut(EntityId, EntityId)) {
+        if self.root == NO_NODE {
+            return;
+        }
+        // The pairs of a subtree are the pairs of each of its children, and the pairs between its two children.
+        // Comparing the subtrees with each other skips most of the tree, unlike a query for every leaf.
+        let mut subtrees = vec![self.root];
+        let mut subtree_pairs = Vec::new();
+        while let Some(index) = subtrees.pop() {
+            let node = &self.nodes[index];
+            if node.is_leaf() {
+                continue;
+            }
+            subtrees.extend(node.children);
+            subtree_pairs.push((node.children[0], node.children[1]));
+            while let Some((a, b)) = subtree_pairs.pop() {
+                let (node_a, node_b) = (&self.nodes[a], &self.nodes[b]);
+                if !node_a.aabb.intersects(&node_b.aabb) {
+                    continue;
+                }
+                match (&node_a.entity, &node_b.entity) {
+                    (Some((entity_a, circle_a)), Some((entity_b, circle_b))) => {
+                        if circle_a.overlaps(circle_b) {
+                            f(*entity_a, *entity_b);
+                        }
+                    }
+                    // Go down the larger of the two subtrees.
+                    _ if node_b.is_leaf()
+                        || (!node_a.is_leaf() && node_a.height >= node_b.height) =>
+                    {
+                        let [c, d] = node_a.children;
+                        subtree_pairs.extend([(c, b), (d, b)]);
+                    }
+                    _ => {
+                        let [c, d] = node_b.children;
+                        subtree_pairs.extend([(a, c), (a, d)]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dbvh;
+    use crate::{math::Vect, space::BroadPhase};
+
+    #[test]
+    fn the_tree_stays_balanced_when_entities_are_added_in_order() {
+        let mut dbvh = Dbvh::new();
+        for entity in 0..1024 {
+            dbvh.insert(entity, Vect([entity as f32 * 10.0, 0.0]), 1.0);
+        }
+        // A perfectly balanced tree of 1024 leaves has a height of 10.
+        assert!(dbvh.height() <= 20, "height {}", dbvh.height());
+        for entity in 0..1000 {
+            assert!(dbvh.remove(entity));
+        }
+        assert_eq!(dbvh.len(), 24);
+        assert!(dbvh.height() <= 10, "height {}", dbvh.height());
+    }
+}
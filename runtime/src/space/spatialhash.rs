@@ -0,0 +1,299 @@
+use crate::{
+    math::Vect,
+    space::{Aabb, BroadPhase, Circle, EntityId, FxHashMap},
+};
+
+/// Returns a cell size for a [`SpatialHash`] of entities of these radii: twice their average diameter.
+/// Most entities are then in a single cell, and the cells hold few entities unless the entities are packed together.
+pub fn suggested_cell_size(radii: impl IntoIterator<Item = f32>) -> f32 {
+    let (count, total) = radii
+        .into_iter()
+        .fold((0usize, 0.0f32), |(count, total), radius| {
+            (count + 1, total + radius)
+        });
+    if count == 0 || total <= 0.0 {
+        return 1.0;
+    }
+    4.0 * total / count as f32
+}
+
+/// The cells covered by a box, bounds included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CellRange<const N: usize> {
+    min: [i32; N],
+    max: [i32; N],
+}
+
+impl<const N: usize> CellRange<N> {
+    fn cell_count(&self) -> usize {
+        (0..N)
+            .map(|i| (self.max[i] as i64 - self.min[i] as i64 + 1) as usize)
+            .fold(1, usize::saturating_mul)
+    }
+
+    fn contains(&self, cell: &[i32; N]) -> bool {
+        (0..N).all(|i| self.min[i] <= cell[i] && cell[i] <= self.max[i])
+    }
+
+    fn for_each_cell(&self, mut f: impl FnMut([i32; N])) {
+        let mut cell = self.min;
+        loop {
+            f(cell);
+            let mut axis = 0;
+            loop {
+                if axis == N {
+                    return;
+                }
+                if cell[axis] < self.max[axis] {
+                    cell[axis] += 1;
+                    break;
+                }
+                cell[axis] = self.min[axis];
+                axis += 1;
+            }
+        }
+    }
+
+    /// An entity or a pair found in several cells is only reported in the first cell shared by both ranges.
+    fn first_shared_cell(&self, other: &Self) -> [i32; N] {
+        std::array::from_fn(|i| self.min[i].max(other.min[i]))
+    }
+}
+
+#[derive(Debug)]
+struct Slot<const N: usize> {
+    entity: EntityId,
+    circle: Circle<N>,
+    cells: CellRange<N>,
+}
+
+/// A grid of cells of `cell_size`, only storing the cells that contain entities.
+///
+/// Entities are stored in every cell they overlap, so the structure is fast when the entities are about the size of a
+/// cell, and slow when an entity covers many cells. Use [`suggested_cell_size`] to pick the size.
+#[derive(Debug)]
+pub struct SpatialHash<const N: usize> {
+    cell_size: f32,
+    cells: FxHashMap<[i32; N], Vec<usize>>,
+    slots: Vec<Option<Slot<N>>>,
+    free_slots: Vec<usize>,
+    slot_of_entity: FxHashMap<EntityId, usize>,
+}
+
+impl<const N: usize> SpatialHash<N> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: Self::valid_cell_size(cell_size),
+            cells: FxHashMap::default(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            slot_of_entity: FxHashMap::default(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Changes the size of the cells and puts the entities in the new cells.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = Self::valid_cell_size(cell_size);
+        self.cells.clear();
+        for (slot_index, slot) in self.slots.iter_mut().enumerate() {
+            let Some(slot) = slot else {
+                continue;
+            };
+            slot.cells = cell_range(self.cell_size, &slot.circle.aabb());
+            slot.cells.for_each_cell(|cell| {
+                self.cells.entry(cell).or_default().push(slot_index);
+            });
+        }
+    }
+
+    fn valid_cell_size(cell_size: f32) -> f32 {
+        if cell_size.is_finite() && cell_size > 0.0 {
+            cell_size
+        } else {
+            1.0
+        }
+    }
+
+    fn add_to_cells(&mut self, slot_index: usize, cells: CellRange<N>) {
+        cells.for_each_cell(|cell| {
+            self.cells.entry(cell).or_default().push(slot_index);
+        });
+    }
+
+    fn remove_from_cells(&mut self, slot_index: usize, cells: CellRange<N>) {
+        cells.for_each_cell(|cell| {
+            let Some(slots) = self.cells.get_mut(&cell) else {
+                return;
+            };
+            if let Some(position) = slots.iter().position(|slot| *slot == slot_index) {
+                slots.swap_remove(position);
+            }
+            if slots.is_empty() {
+                self.cells.remove(&cell);
+            }
+        });
+    }
+
+    /// Calls `f` with the slots of the cells of `range`. Large ranges go through the cells that are not empty instead
+    /// of every cell of the range.
+    fn for_each_slot_in(&self, range: &CellRange<N>, mut f: impl FnMut(&[i32; N], &Slot<N>)) {
+        let mut visit = |cell: &[i32; N], slots: &Vec<usize>| {
+            for slot_index in slots {
+                if let Some(slot) = &self.slots[*slot_index] {
+                    f(cell, slot);
+                }
+            }
+        };
+        if range.cell_count() > self.cells.len() {
+            for (cell, slots) in &self.cells {
+                if range.contains(cell) {
+                    visit(cell, slots);
+                }
+            }
+        } else {
+            range.for_each_cell(|cell| {
+                if let Some(slots) = self.cells.get(&cell) {
+                    visit(&cell, slots);
+                }
+            });
+        }
+    }
+}
+
+fn cell_range<const N: usize>(cell_size: f32, aabb: &Aabb<N>) -> CellRange<N> {
+    let cell_of = |point: Vect<N>| -> [i32; N] {
+        // `as` saturates, so points far away end in the border cells instead of wrapping around.
+        std::array::from_fn(|i| (point.0[i] / cell_size).floor() as i32)
+    };
+    CellRange {
+        min: cell_of(aabb.min),
+        max: cell_of(aabb.max),
+    }
+}
+
+impl<const N: usize> BroadPhase<N> for SpatialHash<N> {
+    fn insert(&mut self, entity: EntityId, position: Vect<N>, radius: f32) {
+        if self.update(entity, position, radius) {
+            return;
+        }
+        let circle = Circle::new(position, radius);
+        let cells = cell_range(self.cell_size, &circle.aabb());
+        let slot = Slot {
+            entity,
+            circle,
+            cells,
+        };
+        let slot_index = match self.free_slots.pop() {
+            Some(slot_index) => {
+                self.slots[slot_index] = Some(slot);
+                slot_index
+            }
+            None => {
+                self.slots.push(Some(slot));
+                self.slots.len() - 1
+            }
+        };
+        self.slot_of_entity.insert(entity, slot_index);
+        self.add_to_cells(slot_index, cells);
+    }
+
+    fn remove(&mut self, entity: EntityId) -> bool {
+        let Some(slot_index) = self.slot_of_entity.remove(&entity) else {
+            return false;
+        };
+        if let Some(slot) = self.slots[slot_index].take() {
+            self.remove_from_cells(slot_index, slot.cells);
+        }
+        self.free_slots.push(slot_index);
+        true
+    }
+
+    fn update(&mut self, entity: EntityId, position: Vect<N>, radius: f32) -> bool {
+        let Some(&slot_index) = self.slot_of_entity.get(&entity) else {
+            return false;
+        };
+        let circle = Circle::new(position, radius);
+        let cells = cell_range(self.cell_size, &circle.aabb());
+        let Some(slot) = &mut self.slots[slot_index] else {
+            return false;
+        };
+        slot.circle = circle;
+        let old_cells = slot.cells;
+        // Most moves stay in the same cells.
+        if old_cells != cells {
+            slot.cells = cells;
+            self.remove_from_cells(slot_index, old_cells);
+            self.add_to_cells(slot_index, cells);
+        }
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.slot_of_entity.len()
+    }
+
+    fn query_circle(&self, center: Vect<N>, radius: f32, mut f: impl FnMut(EntityId)) {
+        let circle = Circle::new(center, radius);
+        let range = cell_range(self.cell_size, &circle.aabb());
+        self.for_each_slot_in(&range, |cell, slot| {
+            if *cell == range.first_shared_cell(&slot.cells) && slot.circle.overlaps(&circle) {
+                f(slot.entity);
+            }
+        });
+    }
+
+    fn query_aabb(&self, aabb: Aabb<N>, mut f: impl FnMut(EntityId)) {
+        let range = cell_range(self.cell_size, &aabb);
+        self.for_each_slot_in(&range, |cell, slot| {
+            if *cell == range.first_shared_cell(&slot.cells) && slot.circle.overlaps_aabb(&aabb) {
+                f(slot.entity);
+            }
+        });
+    }
+
+    fn for_each_pair(&self, mut f: impl FnMut(EntityId, EntityId)) {
+        for (cell, slots) in &self.cells {
+            for (i, a) in slots.iter().enumerate() {
+                let Some(a) = &self.slots[*a] else {
+                    continue;
+                };
+                for b in &slots[i + 1..] {
+                    let Some(b) = &self.slots[*b] else {
+                        continue;
+                    };
+                    if *cell == a.cells.first_shared_cell(&b.cells) && a.circle.overlaps(&b.circle)
+                    {
+                        f(a.entity, b.entity);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CellRange, suggested_cell_size};
+
+    #[test]
+    fn cell_ranges_visit_every_cell_once() {
+        let range = CellRange {
+            min: [-1, 2, 0],
+            max: [1, 3, 0],
+        };
+        let mut cells = Vec::new();
+        range.for_each_cell(|cell| cells.push(cell));
+        assert_eq!(cells.len(), range.cell_count());
+        cells.sort();
+        cells.dedup();
+        assert_eq!(cells.len(), 6);
+        assert!(cells.iter().all(|cell| range.contains(cell)));
+
+        assert_eq!(suggested_cell_size([1.0, 3.0]), 8.0);
+        assert_eq!(suggested_cell_size([]), 1.0);
+    }
+}
@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+
+use vectarine_plugin_sdk::mlua::{self, Value};
+
+use crate::lua_env::{lua_vec2::Vec2, lua_vec4::Vec4};
+
+/// Tables nested deeper than this cannot be hashed. It also stops tables that contain themselves.
+pub const MAX_STATE_HASH_DEPTH: usize = 64;
+/// The maximum number of values hashed in one call, so that hashing a huge table by mistake does not freeze the game.
+pub const MAX_STATE_HASH_VALUES: usize = 1_000_000;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Every value starts with a tag, so that values of different types never produce the same bytes.
+const TAG_NIL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_TABLE: u8 = 5;
+const TAG_VEC2: u8 = 6;
+const TAG_VEC4: u8 = 7;
+const TAG_VECTOR: u8 = 8;
+const TAG_OTHER: u8 = 9;
+
+/// A 64-bit FNV-1a hash, which only depends on the bytes written, unlike the hashers of the standard library.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher {
+    state: u64,
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self {
+            state: FNV_OFFSET_BASIS,
+        }
+    }
+}
+
+impl StateHasher {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// Writes a number so that equal numbers give the same bytes: -0 is written as 0 and every NaN is written the same.
+    pub fn write_number(&mut self, value: f64) {
+        self.write_u64(canonical_number(value).to_bits());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+fn canonical_number(value: f64) -> f64 {
+    if value == 0.0 {
+        0.0
+    } else if value.is_nan() {
+        f64::NAN
+    } else {
+        value
+    }
+}
+
+/// Computes a hash of a Lua value that is the same on every platform, to check that two runs of a deterministic game
+/// are in the same state. The keys of tables are sorted before being hashed, so the order in which they were inserted
+/// does not matter. Metatables are ignored, and functions and unknown userdata only contribute their type.
+///
+/// The hash is not cryptographic and can change between versions of the engine.
+pub fn hash_lua_value(value: &Value) -> mlua::Result<u64> {
+    let mut hasher = StateHasher::default();
+    let mut value_count = 0;
+    write_lua_value(&mut hasher, value, 0, &mut value_count)?;
+    Ok(hasher.finish())
+}
+
+fn write_lua_value(
+    hasher: &mut StateHasher,
+    value: &Value,
+    depth: usize,
+    value_count: &mut usize,
+) -> mlua::Result<()> {
+    *value_count += 1;
+    if *value_count > MAX_STATE_HASH_VALUES {
+        return Err(mlua::Error::runtime(format!(
+            "the state contains more than {MAX_STATE_HASH_VALUES} values"
+        )));
+    }
+    match value {
+        Value::Nil => hasher.write_bytes(&[TAG_NIL]),
+        Value::Boolean(false) => hasher.write_bytes(&[TAG_FALSE]),
+        Value::Boolean(true) => hasher.write_bytes(&[TAG_TRUE]),
+        Value::Integer(number) => {
+            hasher.write_bytes(&[TAG_NUMBER]);
+            hasher.write_number(*number as f64);
+        }
+        Value::Number(number) => {
+            hasher.write_bytes(&[TAG_NUMBER]);
+            hasher.write_number(*number);
+        }
+        Value::String(string) => {
+            let bytes = string.as_bytes();
+            hasher.write_bytes(&[TAG_STRING]);
+            hasher.write_u64(bytes.len() as u64);
+            hasher.write_bytes(&bytes);
+        }
+        Value::Vector(vector) => {
+            hasher.write_bytes(&[TAG_VECTOR]);
+            for component in [vector.x(), vector.y(), vector.z()] {
+                hasher.write_number(component as f64);
+            }
+        }
+        Value::Table(table) => {
+            if depth >= MAX_STATE_HASH_DEPTH {
+                return Err(mlua::Error::runtime(format!(
+                    "the state is nested deeper than {MAX_STATE_HASH_DEPTH} tables, or contains itself"
+                )));
+            }
+            let mut entries = Vec::new();
+            for pair in table.pairs::<Value, Value>() {
+                let (key, value) = pair?;
+                if key_rank(&key).is_none() {
+                    return Err(mlua::Error::runtime(format!(
+                        "the keys of the state must be booleans, numbers or strings, not {}",
+                        key.type_name()
+                    )));
+                }
+                entries.push((key, value));
+            }
+            entries.sort_by(|(a, _), (b, _)| compare_keys(a, b));
+            hasher.write_bytes(&[TAG_TABLE]);
+            hasher.write_u64(entries.len() as u64);
+            for (key, value) in &entries {
+                write_lua_value(hasher, key, depth + 1, value_count)?;
+                write_lua_value(hasher, value, depth + 1, value_count)?;
+            }
+        }
+        Value::UserData(userdata) => {
+            if let Ok(vec) = userdata.borrow::<Vec2>() {
+                hasher.write_bytes(&[TAG_VEC2]);
+                for component in vec.0 {
+                    hasher.write_number(component as f64);
+                }
+            } else if let Ok(vec) = userdata.borrow::<Vec4>() {
+                hasher.write_bytes(&[TAG_VEC4]);
+                for component in vec.0 {
+                    hasher.write_number(component as f64);
+                }
+            } else {
+                write_other_value(hasher, value);
+            }
+        }
+        _ => write_other_value(hasher, value),
+    }
+    Ok(())
+}
+
+/// Values that cannot be compared between runs, like functions, are only hashed by type.
+fn write_other_value(hasher: &mut StateHasher, value: &Value) {
+    let type_name = value.type_name();
+    hasher.write_bytes(&[TAG_OTHER]);
+    hasher.write_u64(type_name.len() as u64);
+    hasher.write_bytes(type_name.as_bytes());
+}
+
+fn key_rank(key: &Value) -> Option<u8> {
+    match key {
+        Value::Boolean(_) => Some(0),
+        Value::Integer(_) | Value::Number(_) => Some(1),
+        Value::String(_) => Some(2),
+        _ => None,
+    }
+}
+
+fn key_number(key: &Value) -> f64 {
+    match key {
+        Value::Integer(number) => *number as f64,
+        Value::Number(number) => canonical_number(*number),
+        _ => 0.0,
+    }
+}
+
+/// Booleans come first, then numbers, then strings, compared byte by byte.
+fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    key_rank(a).cmp(&key_rank(b)).then_with(|| match (a, b) {
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => {
+            let (a, b) = (a.as_bytes(), b.as_bytes());
+            (*a).cmp(&*b)
+        }
+        _ => key_number(a).total_cmp(&key_number(b)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateHasher, hash_lua_value};
+    use crate::lua_env::lua_vec2::Vec2;
+    use vectarine_plugin_sdk::mlua::{Lua, Value};
+
+    /// Reference hashes. They must be the same on every platform, so a failure here means that hashes computed on
+    /// other machines, like the ones of a replay, will no longer match.
+    const RECORDED_HASHES: [(&str, u64); 6] = [
+        ("nil", 0xaf63bd4c8601b7df),
+        ("true", 0xaf63bf4c8601bb45),
+        ("0.1", 0xfc90ac3ac8dd444f),
+        ("'hello'", 0xff84a729414aa086),
+        ("{}", 0x04f0d7663d895b60),
+        (
+            "{ player = { x = 1.5, y = -2 }, score = 120, [1] = 'first', [true] = false }",
+            0xf35d0eff0fc3b874,
+        ),
+    ];
+
+    fn hash_code(lua: &Lua, code: &str) -> u64 {
+        let value = lua
+            .load(format!("return {code}"))
+            .eval::<Value>()
+            .expect("the test code is valid");
+        hash_lua_value(&value).expect("the value can be hashed")
+    }
+
+    #[test]
+    fn hashes_match_the_recorded_vectors() {
+        let lua = Lua::new();
+        for (code, expected) in RECORDED_HASHES {
+            assert_eq!(
+                hash_code(&lua, code),
+                expected,
+                "the hash of {code} changed"
+            );
+        }
+    }
+
+    #[test]
+    fn equal_states_have_equal_hashes() {
+        let lua = Lua::new();
+        assert_eq!(
+            hash_code(&lua, "{ a = 1, b = 2, c = { 1, 2, 3 } }"),
+            hash_code(&lua, "{ c = { 1, 2, 3 }, b = 2, a = 1 }")
+        );
+        assert_eq!(hash_code(&lua, "-0"), hash_code(&lua, "0"));
+        assert_eq!(hash_code(&lua, "0/0"), hash_code(&lua, "-(0/0)"));
+        assert_ne!(hash_code(&lua, "{ 1, 2 }"), hash_code(&lua, "{ 2, 1 }"));
+        assert_ne!(hash_code(&lua, "'1'"), hash_code(&lua, "1"));
+        assert_ne!(
+            hash_code(&lua, "{ a = 1 }"),
+            hash_code(&lua, "{ a = 1, b = {} }")
+        );
+
+        let vec = lua
+            .create_userdata(Vec2::new(1.0, 2.0))
+            .expect("Vec2 is a userdata");
+        let hash_vec = hash_lua_value(&Value::UserData(vec)).expect("a Vec2 can be hashed");
+        let mut hasher = StateHasher::default();
+        hasher.write_bytes(&[super::TAG_VEC2]);
+        hasher.write_number(1.0);
+        hasher.write_number(2.0);
+        assert_eq!(hash_vec, hasher.finish());
+    }
+
+    #[test]
+    fn tables_containing_themselves_are_rejected() {
+        let lua = Lua::new();
+        let value = lua
+            .load("local t = {} t.self = t return t")
+            .eval::<Value>()
+            .expect("the test code is valid");
+        assert!(hash_lua_value(&value).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Video playback for cutscenes.
+//!
+//! Videos are MPEG-1 program streams (`.mpg`), with MPEG-1 video and MP2 audio. The format is old and its files are
+//! bigger than with recent codecs, but it is free of patents, and its decoder is small enough to live in the engine
+//! and fast enough to run on the CPU at the resolution of a cutscene, on every platform including the web. Convert a
+//! video with `ffmpeg -i input.mp4 -c:v mpeg1video -q:v 4 -c:a mp2 output.mpg`.
+//!
+//! The pictures are decoded on a thread, a few frames ahead of the playback. When the decoder falls behind, the late
+//! frames are dropped so that the video keeps its pace. On the web, there are no threads, so the frames are decoded
+//! when they are drawn.
+
+use std::sync::Arc;
+
+use crate::{
+    game_resource::audio_resource::{
+        AUDIO_CHANNELS, AUDIO_SAMPLE_FREQUENCY, decode_audio_with_format,
+    },
+    video::mpeg1::Mpeg1Video,
+};
+
+pub mod mpeg1;
+pub mod mpegps;
+
+/// How many decoded frames wait for their turn. Each one is a full RGBA picture.
+#[cfg(not(target_os = "emscripten"))]
+const LOOKAHEAD_FRAMES: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: f64,
+    pub frame_count: u64,
+}
+
+impl VideoInfo {
+    /// In seconds.
+    pub fn duration(&self) -> f64 {
+        self.frame_count as f64 / self.frame_rate
+    }
+
+    /// The frame shown at `position` seconds.
+    pub fn frame_at(&self, position: f64) -> u64 {
+        ((position.max(0.0) * self.frame_rate) as u64).min(self.frame_count.saturating_sub(1))
+    }
+}
+
+pub struct VideoFrame {
+    pub index: u64,
+    pub rgba: Vec<u8>,
+}
+
+#[cfg(not(target_os = "emscripten"))]
+struct DecoderThread {
+    frames: std::sync::mpsc::Receiver<(u64, VideoFrame)>,
+    seeks: std::sync::mpsc::Sender<u64>,
+    /// Incremented at each seek, so that the frames decoded before the seek are ignored.
+    generation: u64,
+    /// The frame the playback needs. The thread does not convert the frames before it, so that a late decoder catches
+    /// up instead of slowing the video down.
+    wanted_index: Arc<std::sync::atomic::AtomicU64>,
+}
+
+/// The frames of a video, decoded ahead of the playback.
+pub struct VideoStream {
+    info: VideoInfo,
+    /// The first frame received that is not shown yet.
+    next_frame: Option<VideoFrame>,
+    #[cfg(not(target_os = "emscripten"))]
+    decoder: DecoderThread,
+    #[cfg(target_os = "emscripten")]
+    video: Mpeg1Video,
+}
+
+impl VideoStream {
+    /// Starts decoding an MPEG-1 video elementary stream.
+    pub fn open(video_data: Arc<[u8]>) -> Result<Self, String> {
+        let video = Mpeg1Video::new(video_data)?;
+        let info = VideoInfo {
+            width: video.width() as u32,
+            height: video.height() as u32,
+            frame_rate: video.frame_rate(),
+            frame_count: video.picture_count(),
+        };
+        if info.frame_count == 0 {
+            return Err("The video has no pictures".to_string());
+        }
+        Ok(Self {
+            info,
+            next_frame: None,
+            #[cfg(not(target_os = "emscripten"))]
+            decoder: spawn_decoder_thread(video),
+            #[cfg(target_os = "emscripten")]
+            video,
+        })
+    }
+
+    pub fn info(&self) -> VideoInfo {
+        self.info
+    }
+
+    /// The next frames returned start at `index`.
+    pub fn seek(&mut self, index: u64) {
+        self.next_frame = None;
+        #[cfg(not(target_os = "emscripten"))]
+        {
+            self.decoder.generation += 1;
+            self.decoder
+                .wanted_index
+                .store(index, std::sync::atomic::Ordering::Relaxed);
+            let _ = self.decoder.seeks.send(index);
+        }
+        #[cfg(target_os = "emscripten")]
+        self.video.seek(index);
+    }
+
+    /// Returns the last frame to show at `index` or before, if it was not returned yet. The frames before it are
+    /// dropped.
+    pub fn take_frame(&mut self, index: u64) -> Option<VideoFrame> {
+        let mut latest = None;
+        loop {
+            let frame = match self.next_frame.take() {
+                Some(frame) => frame,
+                None => match self.receive_frame(index) {
+                    Some(frame) => frame,
+                    None => return latest,
+                },
+            };
+            if frame.index > index {
+                self.next_frame = Some(frame);
+                return latest;
+            }
+            latest = Some(frame);
+        }
+    }
+
+    #[cfg(not(target_os = "emscripten"))]
+    fn receive_frame(&mut self, index: u64) -> Option<VideoFrame> {
+        self.decoder
+            .wanted_index
+            .store(index, std::sync::atomic::Ordering::Relaxed);
+        loop {
+            let (generation, frame) = self.decoder.frames.try_recv().ok()?;
+            if generation == self.decoder.generation {
+                return Some(frame);
+            }
+        }
+    }
+
+    #[cfg(target_os = "emscripten")]
+    fn receive_frame(&mut self, index: u64) -> Option<VideoFrame> {
+        loop {
+            let (frame_index, picture) = self.video.next_picture()?;
+            if frame_index >= index {
+                return Some(VideoFrame {
+                    index: frame_index,
+                    rgba: picture.to_rgba(self.info.width as usize, self.info.height as usize),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn spawn_decoder_thread(mut video: Mpeg1Video) -> DecoderThread {
+    use std::sync::{atomic::AtomicU64, atomic::Ordering, mpsc};
+
+    let (frame_sender, frames) = mpsc::sync_channel(LOOKAHEAD_FRAMES);
+    let (seeks, seek_receiver) = mpsc::channel::<u64>();
+    let wanted_index = Arc::new(AtomicU64::new(0));
+    std::thread::spawn({
+        let wanted_index = Arc::clone(&wanted_index);
+        move || {
+            let (width, height) = (video.width(), video.height());
+            let mut generation = 0;
+            loop {
+                for index in seek_receiver.try_iter() {
+                    generation += 1;
+                    video.seek(index);
+                }
+                let Some((index, picture)) = video.next_picture() else {
+                    // The end of the video: wait for a seek, or for the stream to be dropped.
+                    let Ok(index) = seek_receiver.recv() else {
+                        return;
+                    };
+                    generation += 1;
+                    video.seek(index);
+                    continue;
+                };
+                if index < wanted_index.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let frame = VideoFrame {
+                    index,
+                    rgba: picture.to_rgba(width, height),
+                };
+                // Blocks while the lookahead is full. The stream drains the frames of old generations after a seek.
+                if frame_sender.send((generation, frame)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    DecoderThread {
+        frames,
+        seeks,
+        generation: 0,
+        wanted_index,
+    }
+}
+
+/// Decodes the MP2 track of a video, in the format of the mixer.
+pub fn decode_soundtrack(audio_data: Vec<u8>) -> Result<Vec<f32>, String> {
+    let mut hint = symphonia::core::probe::Hint::new();
+    hint.with_extension("mp2");
+    let (samples, sample_rate, channel_count) =
+        decode_audio_with_format(audio_data.into_boxed_slice(), &hint)?;
+    Ok(convert_to_mixer_format(
+        &samples,
+        sample_rate,
+        channel_count,
+    ))
+}
+
+/// Resamples interleaved samples to the rate and the channels of the mixer, with a linear interpolation. Mono is
+/// played on both sides, and the channels after the first two are dropped.
+pub fn convert_to_mixer_format(
+    samples: &[f32],
+    sample_rate: u32,
+    channel_count: usize,
+) -> Vec<f32> {
+    let output_channels = AUDIO_CHANNELS as usize;
+    if channel_count == 0 || sample_rate == 0 {
+        return Vec::new();
+    }
+    let input_frames = samples.len() / channel_count;
+    if input_frames == 0 {
+        return Vec::new();
+    }
+    let sample = |frame: usize, channel: usize| -> f32 {
+        samples[frame.min(input_frames - 1) * channel_count + channel.min(channel_count - 1)]
+    };
+    let step = sample_rate as f64 / AUDIO_SAMPLE_FREQUENCY as f64;
+    let output_frames = (input_frames as f64 / step) as usize;
+    let mut output = Vec::with_capacity(output_frames * output_channels);
+    for output_frame in 0..output_frames {
+        let position = output_frame as f64 * step;
+        let frame = position as usize;
+        let fraction = (position - frame as f64) as f32;
+        for channel in 0..output_channels {
+            let value =
+                sample(frame, channel) * (1.0 - fraction) + sample(frame + 1, channel) * fraction;
+            output.push(value);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VideoInfo, convert_to_mixer_format};
+
+    #[test]
+    fn mono_audio_is_resampled_to_stereo() {
+        let samples = [0.0, 1.0, 0.0, -1.0];
+        let converted = convert_to_mixer_format(&samples, 24000, 1);
+        assert_eq!(
+            converted,
+            vec![
+                0.0, 0.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, 0.0, 0.0, -0.5, -0.5, -1.0, -1.0, -1.0,
+                -1.0
+            ]
+        );
+
+        let stereo = [0.25, -0.25, 0.5, -0.5];
+        assert_eq!(convert_to_mixer_format(&stereo, 48000, 2), stereo.to_vec());
+        assert!(convert_to_mixer_format(&[], 44100, 2).is_empty());
+    }
+
+    #[test]
+    fn the_last_frame_stays_until_the_end() {
+        let info = VideoInfo {
+            width: 320,
+            height: 240,
+            frame_rate: 25.0,
+            frame_count: 50,
+        };
+        assert_eq!(info.duration(), 2.0);
+        assert_eq!(info.frame_at(0.0), 0);
+        assert_eq!(info.frame_at(0.1), 2);
+        assert_eq!(info.frame_at(5.0), 49);
+    }
+}
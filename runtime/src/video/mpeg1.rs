@@ -0,0 +1,1519 @@
+//! A decoder for MPEG-1 video (ISO/IEC 11172-2).
+//!
+//! The decoder follows the layers of the standard: a sequence holds groups of pictures, a picture is cut in slices, a
+//! slice in macroblocks of 16x16 pixels, and a macroblock in six 8x8 blocks (four of luma, one of each chroma).
+//! Pictures are decoded in the order of the stream and returned in the order they are shown.
+
+use std::sync::{Arc, OnceLock};
+
+const PICTURE_START_CODE: u8 = 0x00;
+const SLICE_FIRST_START_CODE: u8 = 0x01;
+const SLICE_LAST_START_CODE: u8 = 0xAF;
+const USER_DATA_START_CODE: u8 = 0xB2;
+const SEQUENCE_HEADER_CODE: u8 = 0xB3;
+const EXTENSION_START_CODE: u8 = 0xB5;
+const SEQUENCE_END_CODE: u8 = 0xB7;
+const GROUP_START_CODE: u8 = 0xB8;
+
+const PICTURE_TYPE_INTRA: u8 = 1;
+const PICTURE_TYPE_PREDICTED: u8 = 2;
+const PICTURE_TYPE_BIDIRECTIONAL: u8 = 3;
+
+const MACROBLOCK_QUANT: u8 = 1;
+const MACROBLOCK_FORWARD: u8 = 2;
+const MACROBLOCK_BACKWARD: u8 = 4;
+const MACROBLOCK_PATTERN: u8 = 8;
+const MACROBLOCK_INTRA: u8 = 16;
+
+/// The DC predictors start at 128 (mid gray) times 8 at the start of each slice and after non-intra macroblocks.
+const DC_PREDICTOR_RESET: i32 = 1024;
+
+/// Position in the block of each coefficient of the stream.
+const ZIGZAG: [u8; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const DEFAULT_INTRA_QUANTIZER_MATRIX: [u8; 64] = [
+    8, 16, 19, 22, 26, 27, 29, 34, 16, 16, 22, 24, 27, 29, 34, 37, 19, 22, 26, 27, 29, 34, 34, 38,
+    22, 22, 26, 27, 29, 34, 37, 40, 22, 26, 27, 29, 32, 35, 40, 48, 26, 27, 29, 32, 35, 40, 48, 58,
+    26, 27, 29, 34, 38, 46, 56, 69, 27, 29, 35, 38, 46, 56, 69, 83,
+];
+
+const DEFAULT_NON_INTRA_QUANTIZER_MATRIX: [u8; 64] = [16; 64];
+
+/// Reads the stream bit by bit, most significant bit first. Reading past the end returns zeros.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    /// In bits.
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8], byte_position: usize) -> Self {
+        Self {
+            data,
+            position: byte_position * 8,
+        }
+    }
+
+    /// The position of the next whole byte.
+    pub fn byte_position(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position >= self.data.len() * 8
+    }
+
+    /// Returns the next `count` bits without moving. `count` is at most 32.
+    pub fn peek(&self, count: u32) -> u32 {
+        if count == 0 {
+            return 0;
+        }
+        let byte_index = self.position / 8;
+        let window = (0..5).fold(0u64, |window, offset| {
+            let byte = self.data.get(byte_index + offset).copied().unwrap_or(0);
+            (window << 8) | byte as u64
+        });
+        let shift = 40 - (self.position % 8) as u32 - count;
+        ((window >> shift) & ((1u64 << count) - 1)) as u32
+    }
+
+    pub fn read(&mut self, count: u32) -> u32 {
+        let value = self.peek(count);
+        self.skip(count);
+        value
+    }
+
+    pub fn read_bool(&mut self) -> bool {
+        self.read(1) == 1
+    }
+
+    pub fn skip(&mut self, count: u32) {
+        self.position += count as usize;
+    }
+
+    /// Moves after the next start code (`00 00 01 xx`) and returns its last byte, or None at the end of the stream.
+    pub fn next_start_code(&mut self) -> Option<u8> {
+        let mut index = self.byte_position();
+        while index + 3 < self.data.len() {
+            if self.data[index] == 0 && self.data[index + 1] == 0 && self.data[index + 2] == 1 {
+                self.position = (index + 4) * 8;
+                return Some(self.data[index + 3]);
+            }
+            index += 1;
+        }
+        self.position = self.data.len() * 8;
+        None
+    }
+
+    /// Goes back before the start code returned by `next_start_code`, so that another reader finds it again.
+    fn rewind_start_code(&mut self) {
+        self.position -= 32;
+    }
+
+    /// A start code follows the last macroblock of a slice, after zeros up to the next byte.
+    fn is_start_code_next(&self) -> bool {
+        self.is_at_end() || self.peek(23) == 0
+    }
+}
+
+/// A table of variable length codes, decoded by walking a binary tree one bit at a time.
+struct VlcTable<T> {
+    /// The two children of each node. 0 is a missing child, a positive number is a node and a negative number is
+    /// `!index` of a value.
+    nodes: Vec<[i32; 2]>,
+    values: Vec<T>,
+}
+
+impl<T: Copy> VlcTable<T> {
+    /// The codes are written with '0' and '1', as in the tables of the standard.
+    fn new(codes: &[(&str, T)]) -> Self {
+        let mut table = Self {
+            nodes: vec![[0, 0]],
+            values: Vec::with_capacity(codes.len()),
+        };
+        for (code, value) in codes {
+            let value_index = table.values.len() as i32;
+            table.values.push(*value);
+            let mut node = 0;
+            let bit_count = code.len();
+            for (bit_index, bit) in code.bytes().enumerate() {
+                let bit = usize::from(bit == b'1');
+                let child = table.nodes[node][bit];
+                if bit_index + 1 == bit_count {
+                    assert!(child == 0, "{code} is the prefix of another code");
+                    table.nodes[node][bit] = !value_index;
+                } else if child == 0 {
+                    table.nodes.push([0, 0]);
+                    let new_node = table.nodes.len() - 1;
+                    table.nodes[node][bit] = new_node as i32;
+                    node = new_node;
+                } else {
+                    assert!(child > 0, "{code} starts with another code");
+                    node = child as usize;
+                }
+            }
+        }
+        table
+    }
+
+    /// Returns None when the bits are not a code of the table, in a damaged stream.
+    fn decode(&self, reader: &mut BitReader) -> Option<T> {
+        let mut node = 0;
+        loop {
+            let child = self.nodes[node][reader.read(1) as usize];
+            if child < 0 {
+                return Some(self.values[!child as usize]);
+            }
+            if child == 0 {
+                return None;
+            }
+            node = child as usize;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AddressIncrement {
+    Increment(u8),
+    Escape,
+    Stuffing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DctCoefficient {
+    EndOfBlock,
+    Escape,
+    /// A number of zeros, then a coefficient of this magnitude. A sign bit follows the code.
+    RunLevel(u8, u8),
+}
+
+fn macroblock_address_increment_table() -> &'static VlcTable<AddressIncrement> {
+    static TABLE: OnceLock<VlcTable<AddressIncrement>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        use AddressIncrement::Increment as I;
+        VlcTable::new(&[
+            ("1", I(1)),
+            ("011", I(2)),
+            ("010", I(3)),
+            ("0011", I(4)),
+            ("0010", I(5)),
+            ("00011", I(6)),
+            ("00010", I(7)),
+            ("0000111", I(8)),
+            ("0000110", I(9)),
+            ("00001011", I(10)),
+            ("00001010", I(11)),
+            ("00001001", I(12)),
+            ("00001000", I(13)),
+            ("00000111", I(14)),
+            ("00000110", I(15)),
+            ("0000010111", I(16)),
+            ("0000010110", I(17)),
+            ("0000010101", I(18)),
+            ("0000010100", I(19)),
+            ("0000010011", I(20)),
+            ("0000010010", I(21)),
+            ("00000100011", I(22)),
+            ("00000100010", I(23)),
+            ("00000100001", I(24)),
+            ("00000100000", I(25)),
+            ("00000011111", I(26)),
+            ("00000011110", I(27)),
+            ("00000011101", I(28)),
+            ("00000011100", I(29)),
+            ("00000011011", I(30)),
+            ("00000011010", I(31)),
+            ("00000011001", I(32)),
+            ("00000011000", I(33)),
+            ("00000001111", AddressIncrement::Stuffing),
+            ("00000001000", AddressIncrement::Escape),
+        ])
+    })
+}
+
+fn macroblock_type_table(picture_type: u8) -> &'static VlcTable<u8> {
+    static INTRA: OnceLock<VlcTable<u8>> = OnceLock::new();
+    static PREDICTED: OnceLock<VlcTable<u8>> = OnceLock::new();
+    static BIDIRECTIONAL: OnceLock<VlcTable<u8>> = OnceLock::new();
+    const Q: u8 = MACROBLOCK_QUANT;
+    const F: u8 = MACROBLOCK_FORWARD;
+    const B: u8 = MACROBLOCK_BACKWARD;
+    const P: u8 = MACROBLOCK_PATTERN;
+    const I: u8 = MACROBLOCK_INTRA;
+    match picture_type {
+        PICTURE_TYPE_PREDICTED => PREDICTED.get_or_init(|| {
+            VlcTable::new(&[
+                ("1", F | P),
+                ("01", P),
+                ("001", F),
+                ("00011", I),
+                ("00010", F | P | Q),
+                ("00001", P | Q),
+                ("000001", I | Q),
+            ])
+        }),
+        PICTURE_TYPE_BIDIRECTIONAL => BIDIRECTIONAL.get_or_init(|| {
+            VlcTable::new(&[
+                ("10", F | B),
+                ("11", F | B | P),
+                ("010", B),
+                ("011", B | P),
+                ("0010", F),
+                ("0011", F | P),
+                ("00011", I),
+                ("00010", F | B | P | Q),
+                ("000011", F | P | Q),
+                ("000010", B | P | Q),
+                ("000001", I | Q),
+            ])
+        }),
+        _ => INTRA.get_or_init(|| VlcTable::new(&[("1", I), ("01", I | Q)])),
+    }
+}
+
+/// Which of the six blocks of the macroblock are coded, the first block being the highest bit.
+fn coded_block_pattern_table() -> &'static VlcTable<u8> {
+    static TABLE: OnceLock<VlcTable<u8>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        VlcTable::new(&[
+            ("111", 60),
+            ("1101", 4),
+            ("1100", 8),
+            ("1011", 16),
+            ("1010", 32),
+            ("10011", 12),
+            ("10010", 48),
+            ("10001", 20),
+            ("10000", 40),
+            ("01111", 28),
+            ("01110", 44),
+            ("01101", 52),
+            ("01100", 56),
+            ("01011", 1),
+            ("01010", 61),
+            ("01001", 2),
+            ("01000", 62),
+            ("001111", 24),
+            ("001110", 36),
+            ("001101", 3),
+            ("001100", 63),
+            ("0010111", 5),
+            ("0010110", 9),
+            ("0010101", 17),
+            ("0010100", 33),
+            ("0010011", 6),
+            ("0010010", 10),
+            ("0010001", 18),
+            ("0010000", 34),
+            ("00011111", 7),
+            ("00011110", 11),
+            ("00011101", 19),
+            ("00011100", 35),
+            ("00011011", 13),
+            ("00011010", 49),
+            ("00011001", 21),
+            ("00011000", 41),
+            ("00010111", 14),
+            ("00010110", 50),
+            ("00010101", 22),
+            ("00010100", 42),
+            ("00010011", 15),
+            ("00010010", 51),
+            ("00010001", 23),
+            ("00010000", 43),
+            ("00001111", 25),
+            ("00001110", 37),
+            ("00001101", 26),
+            ("00001100", 38),
+            ("00001011", 29),
+            ("00001010", 45),
+            ("00001001", 53),
+            ("00001000", 57),
+            ("00000111", 30),
+            ("00000110", 46),
+            ("00000101", 54),
+            ("00000100", 58),
+            ("000000111", 31),
+            ("000000110", 47),
+            ("000000101", 55),
+            ("000000100", 59),
+            ("000000011", 27),
+            ("000000010", 39),
+        ])
+    })
+}
+
+/// The magnitude of a motion code. A sign bit follows the codes other than 0.
+fn motion_code_table() -> &'static VlcTable<u8> {
+    static TABLE: OnceLock<VlcTable<u8>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        VlcTable::new(&[
+            ("1", 0),
+            ("01", 1),
+            ("001", 2),
+            ("0001", 3),
+            ("000011", 4),
+            ("0000101", 5),
+            ("0000100", 6),
+            ("0000011", 7),
+            ("000001011", 8),
+            ("000001010", 9),
+            ("000001001", 10),
+            ("0000010001", 11),
+            ("0000010000", 12),
+            ("0000001111", 13),
+            ("0000001110", 14),
+            ("0000001101", 15),
+            ("0000001100", 16),
+        ])
+    })
+}
+
+/// The number of bits of the DC difference of intra blocks, for the luma and the chroma blocks.
+fn dc_size_table(is_luma: bool) -> &'static VlcTable<u8> {
+    static LUMA: OnceLock<VlcTable<u8>> = OnceLock::new();
+    static CHROMA: OnceLock<VlcTable<u8>> = OnceLock::new();
+    if is_luma {
+        LUMA.get_or_init(|| {
+            VlcTable::new(&[
+                ("100", 0),
+                ("00", 1),
+                ("01", 2),
+                ("101", 3),
+                ("110", 4),
+                ("1110", 5),
+                ("11110", 6),
+                ("111110", 7),
+                ("1111110", 8),
+            ])
+        })
+    } else {
+        CHROMA.get_or_init(|| {
+            VlcTable::new(&[
+                ("00", 0),
+                ("01", 1),
+                ("10", 2),
+                ("110", 3),
+                ("1110", 4),
+                ("11110", 5),
+                ("111110", 6),
+                ("1111110", 7),
+                ("11111110", 8),
+            ])
+        })
+    }
+}
+
+/// The coefficients after the DC of intra blocks, and all the coefficients of the other blocks except a first
+/// coefficient of magnitude 1, which is coded with a single bit.
+fn dct_coefficient_table() -> &'static VlcTable<DctCoefficient> {
+    static TABLE: OnceLock<VlcTable<DctCoefficient>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        use DctCoefficient::RunLevel as R;
+        VlcTable::new(&[
+            ("10", DctCoefficient::EndOfBlock),
+            ("000001", DctCoefficient::Escape),
+            ("11", R(0, 1)),
+            ("011", R(1, 1)),
+            ("0100", R(0, 2)),
+            ("0101", R(2, 1)),
+            ("00101", R(0, 3)),
+            ("00111", R(3, 1)),
+            ("00110", R(4, 1)),
+            ("000110", R(1, 2)),
+            ("000111", R(5, 1)),
+            ("000101", R(6, 1)),
+            ("000100", R(7, 1)),
+            ("0000110", R(0, 4)),
+            ("0000100", R(2, 2)),
+            ("0000111", R(8, 1)),
+            ("0000101", R(9, 1)),
+            ("00100110", R(0, 5)),
+            ("00100001", R(0, 6)),
+            ("00100101", R(1, 3)),
+            ("00100100", R(3, 2)),
+            ("00100111", R(10, 1)),
+            ("00100011", R(11, 1)),
+            ("00100010", R(12, 1)),
+            ("00100000", R(13, 1)),
+            ("0000001010", R(0, 7)),
+            ("0000001100", R(1, 4)),
+            ("0000001011", R(2, 3)),
+            ("0000001111", R(4, 2)),
+            ("0000001001", R(5, 2)),
+            ("0000001110", R(14, 1)),
+            ("0000001101", R(15, 1)),
+            ("0000001000", R(16, 1)),
+            ("000000011101", R(0, 8)),
+            ("000000011000", R(0, 9)),
+            ("000000010011", R(0, 10)),
+            ("000000010000", R(0, 11)),
+            ("000000011011", R(1, 5)),
+            ("000000010100", R(2, 4)),
+            ("000000011100", R(3, 3)),
+            ("000000010010", R(4, 3)),
+            ("000000011110", R(6, 2)),
+            ("000000010101", R(7, 2)),
+            ("000000010001", R(8, 2)),
+            ("000000011111", R(17, 1)),
+            ("000000011010", R(18, 1)),
+            ("000000011001", R(19, 1)),
+            ("000000010111", R(20, 1)),
+            ("000000010110", R(21, 1)),
+            ("0000000011010", R(0, 12)),
+            ("0000000011001", R(0, 13)),
+            ("0000000011000", R(0, 14)),
+            ("0000000010111", R(0, 15)),
+            ("0000000010110", R(1, 6)),
+            ("0000000010101", R(1, 7)),
+            ("0000000010100", R(2, 5)),
+            ("0000000010011", R(3, 4)),
+            ("0000000010010", R(5, 3)),
+            ("0000000010001", R(9, 2)),
+            ("0000000010000", R(10, 2)),
+            ("0000000011111", R(22, 1)),
+            ("0000000011110", R(23, 1)),
+            ("0000000011101", R(24, 1)),
+            ("0000000011100", R(25, 1)),
+            ("0000000011011", R(26, 1)),
+            ("00000000011111", R(0, 16)),
+            ("00000000011110", R(0, 17)),
+            ("00000000011101", R(0, 18)),
+            ("00000000011100", R(0, 19)),
+            ("00000000011011", R(0, 20)),
+            ("00000000011010", R(0, 21)),
+            ("00000000011001", R(0, 22)),
+            ("00000000011000", R(0, 23)),
+            ("00000000010111", R(0, 24)),
+            ("00000000010110", R(0, 25)),
+            ("00000000010101", R(0, 26)),
+            ("00000000010100", R(0, 27)),
+            ("00000000010011", R(0, 28)),
+            ("00000000010010", R(0, 29)),
+            ("00000000010001", R(0, 30)),
+            ("00000000010000", R(0, 31)),
+            ("000000000011000", R(0, 32)),
+            ("000000000010111", R(0, 33)),
+            ("000000000010110", R(0, 34)),
+            ("000000000010101", R(0, 35)),
+            ("000000000010100", R(0, 36)),
+            ("000000000010011", R(0, 37)),
+            ("000000000010010", R(0, 38)),
+            ("000000000010001", R(0, 39)),
+            ("000000000010000", R(0, 40)),
+            ("000000000011111", R(1, 8)),
+            ("000000000011110", R(1, 9)),
+            ("000000000011101", R(1, 10)),
+            ("000000000011100", R(1, 11)),
+            ("000000000011011", R(1, 12)),
+            ("000000000011010", R(1, 13)),
+            ("000000000011001", R(1, 14)),
+            ("0000000000010011", R(1, 15)),
+            ("0000000000010010", R(1, 16)),
+            ("0000000000010001", R(1, 17)),
+            ("0000000000010000", R(1, 18)),
+            ("0000000000010100", R(6, 3)),
+            ("0000000000011010", R(11, 2)),
+            ("0000000000011001", R(12, 2)),
+            ("0000000000011000", R(13, 2)),
+            ("0000000000010111", R(14, 2)),
+            ("0000000000010110", R(15, 2)),
+            ("0000000000010101", R(16, 2)),
+            ("0000000000011111", R(27, 1)),
+            ("0000000000011110", R(28, 1)),
+            ("0000000000011101", R(29, 1)),
+            ("0000000000011100", R(30, 1)),
+            ("0000000000011011", R(31, 1)),
+        ])
+    })
+}
+
+/// `IDCT_TABLE[u][x]` is the weight of the frequency `u` in the pixel `x`, with the normalization of the standard.
+fn idct_table() -> &'static [[f32; 8]; 8] {
+    static TABLE: OnceLock<[[f32; 8]; 8]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut table = [[0.0; 8]; 8];
+        for (u, row) in table.iter_mut().enumerate() {
+            let scale = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+            for (x, weight) in row.iter_mut().enumerate() {
+                let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+                *weight = scale * angle.cos();
+            }
+        }
+        table
+    })
+}
+
+/// Turns the coefficients of a block into pixel values, in place.
+fn inverse_dct(block: &mut [i32; 64]) {
+    if block[1..].iter().all(|coefficient| *coefficient == 0) {
+        let value = (block[0] as f32 / 8.0).round() as i32;
+        block.fill(value);
+        return;
+    }
+    let table = idct_table();
+    let mut rows = [0.0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8).map(|u| table[u][x] * block[v * 8 + u] as f32).sum();
+        }
+    }
+    for x in 0..8 {
+        for y in 0..8 {
+            let value: f32 = (0..8).map(|v| table[v][y] * rows[v * 8 + x]).sum();
+            block[y * 8 + x] = value.round() as i32;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SequenceHeader {
+    pub width: usize,
+    pub height: usize,
+    pub frame_rate: f64,
+    intra_quantizer_matrix: [u8; 64],
+    non_intra_quantizer_matrix: [u8; 64],
+}
+
+impl SequenceHeader {
+    /// Reads the header after its start code.
+    fn read(reader: &mut BitReader) -> Result<Self, String> {
+        let width = reader.read(12) as usize;
+        let height = reader.read(12) as usize;
+        reader.skip(4); // Aspect ratio
+        let frame_rate = match reader.read(4) {
+            1 => 24000.0 / 1001.0,
+            2 => 24.0,
+            3 => 25.0,
+            4 => 30000.0 / 1001.0,
+            5 => 30.0,
+            6 => 50.0,
+            7 => 60000.0 / 1001.0,
+            8 => 60.0,
+            code => return Err(format!("Unknown frame rate code {code}")),
+        };
+        // Bit rate, marker, buffer size and constrained parameters flag.
+        reader.skip(18 + 1 + 10 + 1);
+        if width == 0 || height == 0 {
+            return Err("The video has no pixels".to_string());
+        }
+        let mut header = Self {
+            width,
+            height,
+            frame_rate,
+            intra_quantizer_matrix: DEFAULT_INTRA_QUANTIZER_MATRIX,
+            non_intra_quantizer_matrix: DEFAULT_NON_INTRA_QUANTIZER_MATRIX,
+        };
+        if reader.read_bool() {
+            for position in ZIGZAG {
+                header.intra_quantizer_matrix[position as usize] = reader.read(8) as u8;
+            }
+        }
+        if reader.read_bool() {
+            for position in ZIGZAG {
+                header.non_intra_quantizer_matrix[position as usize] = reader.read(8) as u8;
+            }
+        }
+        Ok(header)
+    }
+}
+
+/// A decoded picture in YCbCr, with the chroma at half the resolution. The planes are padded to whole macroblocks.
+#[derive(Clone, Debug)]
+pub struct Picture {
+    stride: usize,
+    coded_height: usize,
+    luma: Vec<u8>,
+    blue_chroma: Vec<u8>,
+    red_chroma: Vec<u8>,
+}
+
+impl Picture {
+    /// A black picture, used in place of a missing reference after a seek.
+    fn new(mb_width: usize, mb_height: usize) -> Self {
+        let stride = mb_width * 16;
+        let coded_height = mb_height * 16;
+        let chroma_size = stride / 2 * coded_height / 2;
+        Self {
+            stride,
+            coded_height,
+            luma: vec![16; stride * coded_height],
+            blue_chroma: vec![128; chroma_size],
+            red_chroma: vec![128; chroma_size],
+        }
+    }
+
+    /// Converts the top left `width` x `height` pixels to RGBA, with the BT.601 coefficients used by MPEG-1.
+    pub fn to_rgba(&self, width: usize, height: usize) -> Vec<u8> {
+        let chroma_stride = self.stride / 2;
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for row in 0..height {
+            for column in 0..width {
+                let luma = (self.luma[row * self.stride + column] as i32 - 16) * 298;
+                let chroma_index = (row / 2) * chroma_stride + column / 2;
+                let blue = self.blue_chroma[chroma_index] as i32 - 128;
+                let red = self.red_chroma[chroma_index] as i32 - 128;
+                rgba.extend_from_slice(&[
+                    ((luma + 409 * red + 128) >> 8).clamp(0, 255) as u8,
+                    ((luma - 100 * blue - 208 * red + 128) >> 8).clamp(0, 255) as u8,
+                    ((luma + 516 * blue + 128) >> 8).clamp(0, 255) as u8,
+                    255,
+                ]);
+            }
+        }
+        rgba
+    }
+
+    /// The plane, its stride and the position of a block of the macroblock.
+    fn block_target(
+        &mut self,
+        block_index: usize,
+        mb_x: usize,
+        mb_y: usize,
+    ) -> (&mut [u8], usize, usize, usize) {
+        match block_index {
+            0..=3 => (
+                &mut self.luma,
+                self.stride,
+                mb_x * 16 + (block_index & 1) * 8,
+                mb_y * 16 + (block_index >> 1) * 8,
+            ),
+            4 => (&mut self.blue_chroma, self.stride / 2, mb_x * 8, mb_y * 8),
+            _ => (&mut self.red_chroma, self.stride / 2, mb_x * 8, mb_y * 8),
+        }
+    }
+}
+
+/// A motion vector in half pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MotionVector {
+    horizontal: i32,
+    vertical: i32,
+}
+
+/// The motion parameters of the picture in one direction, and the prediction of the next vector.
+#[derive(Clone, Copy, Debug, Default)]
+struct MotionState {
+    is_full_pixel: bool,
+    r_size: u32,
+    /// The previous vector, in the unit of the stream: whole pixels when `is_full_pixel` is set.
+    predictor: MotionVector,
+}
+
+impl MotionState {
+    fn read_parameters(&mut self, reader: &mut BitReader) -> Option<()> {
+        self.is_full_pixel = reader.read_bool();
+        let f_code = reader.read(3);
+        if f_code == 0 {
+            return None;
+        }
+        self.r_size = f_code - 1;
+        Some(())
+    }
+
+    fn read_vector(&mut self, reader: &mut BitReader) -> Option<MotionVector> {
+        self.predictor.horizontal = self.read_component(reader, self.predictor.horizontal)?;
+        self.predictor.vertical = self.read_component(reader, self.predictor.vertical)?;
+        let shift = u32::from(self.is_full_pixel);
+        Some(MotionVector {
+            horizontal: self.predictor.horizontal << shift,
+            vertical: self.predictor.vertical << shift,
+        })
+    }
+
+    fn read_component(&self, reader: &mut BitReader, predictor: i32) -> Option<i32> {
+        let magnitude = motion_code_table().decode(reader)? as i32;
+        let code = if magnitude != 0 && reader.read_bool() {
+            -magnitude
+        } else {
+            magnitude
+        };
+        let scale = 1 << self.r_size;
+        let mut delta = code;
+        if scale != 1 && code != 0 {
+            let residual = reader.read(self.r_size) as i32;
+            delta = ((code.abs() - 1) << self.r_size) + residual + 1;
+            if code < 0 {
+                delta = -delta;
+            }
+        }
+        // The vectors wrap around, so that a large change can be coded as a small one.
+        let mut vector = predictor + delta;
+        if vector > (scale << 4) - 1 {
+            vector -= scale << 5;
+        } else if vector < -(scale << 4) {
+            vector += scale << 5;
+        }
+        Some(vector)
+    }
+
+    fn reset_predictor(&mut self) {
+        self.predictor = MotionVector::default();
+    }
+}
+
+/// The pictures predicted from, for the picture being decoded.
+struct References {
+    forward: Arc<Picture>,
+    backward: Arc<Picture>,
+}
+
+/// Copies a block of the reference moved by the vector into the picture. With `average`, the result is averaged with
+/// the pixels already there, for the macroblocks predicted from both directions.
+#[allow(clippy::too_many_arguments)]
+fn predict_block(
+    target: &mut [u8],
+    source: &[u8],
+    stride: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    size: usize,
+    vector: MotionVector,
+    average: bool,
+) {
+    let offset_x = (x as i32) + (vector.horizontal >> 1);
+    let offset_y = (y as i32) + (vector.vertical >> 1);
+    let is_half_x = vector.horizontal & 1 != 0;
+    let is_half_y = vector.vertical & 1 != 0;
+    // A vector can point slightly out of the picture. The border pixels are repeated.
+    let sample = |column: i32, row: i32| -> i32 {
+        let column = column.clamp(0, stride as i32 - 1) as usize;
+        let row = row.clamp(0, height as i32 - 1) as usize;
+        source[row * stride + column] as i32
+    };
+    for row in 0..size {
+        for column in 0..size {
+            let source_x = offset_x + column as i32;
+            let source_y = offset_y + row as i32;
+            let value = match (is_half_x, is_half_y) {
+                (false, false) => sample(source_x, source_y),
+                (true, false) => {
+                    (sample(source_x, source_y) + sample(source_x + 1, source_y) + 1) >> 1
+                }
+                (false, true) => {
+                    (sample(source_x, source_y) + sample(source_x, source_y + 1) + 1) >> 1
+                }
+                (true, true) => {
+                    (sample(source_x, source_y)
+                        + sample(source_x + 1, source_y)
+                        + sample(source_x, source_y + 1)
+                        + sample(source_x + 1, source_y + 1)
+                        + 2)
+                        >> 2
+                }
+            };
+            let pixel = &mut target[(y + row) * stride + x + column];
+            *pixel = if average {
+                ((*pixel as i32 + value + 1) >> 1) as u8
+            } else {
+                value as u8
+            };
+        }
+    }
+}
+
+/// Predicts the three planes of the macroblock from a reference picture.
+fn predict_macroblock(
+    picture: &mut Picture,
+    reference: &Picture,
+    mb_x: usize,
+    mb_y: usize,
+    vector: MotionVector,
+    average: bool,
+) {
+    let (stride, height) = (picture.stride, picture.coded_height);
+    predict_block(
+        &mut picture.luma,
+        &reference.luma,
+        stride,
+        height,
+        mb_x * 16,
+        mb_y * 16,
+        16,
+        vector,
+        average,
+    );
+    let chroma_vector = MotionVector {
+        horizontal: vector.horizontal / 2,
+        vertical: vector.vertical / 2,
+    };
+    for (target, source) in [
+        (&mut picture.blue_chroma, &reference.blue_chroma),
+        (&mut picture.red_chroma, &reference.red_chroma),
+    ] {
+        predict_block(
+            target,
+            source,
+            stride / 2,
+            height / 2,
+            mb_x * 8,
+            mb_y * 8,
+            8,
+            chroma_vector,
+            average,
+        );
+    }
+}
+
+/// Decodes the pictures of a sequence and puts them back in display order.
+pub struct Mpeg1Decoder {
+    sequence: SequenceHeader,
+    mb_width: usize,
+    mb_height: usize,
+    /// The reference before `newer_reference`, used by the B pictures to predict forward.
+    older_reference: Option<Arc<Picture>>,
+    /// The last I or P picture. It is shown after the B pictures that follow it in the stream.
+    newer_reference: Option<Arc<Picture>>,
+    is_newer_reference_shown: bool,
+
+    picture_type: u8,
+    quantizer_scale: i32,
+    /// For the luma, blue chroma and red chroma blocks.
+    dc_predictors: [i32; 3],
+    forward: MotionState,
+    backward: MotionState,
+    /// The vectors of the previous macroblock, which the skipped macroblocks of B pictures reuse.
+    previous_vectors: (Option<MotionVector>, Option<MotionVector>),
+    block: [i32; 64],
+}
+
+impl Mpeg1Decoder {
+    pub fn new(sequence: SequenceHeader) -> Self {
+        Self {
+            mb_width: sequence.width.div_ceil(16),
+            mb_height: sequence.height.div_ceil(16),
+            sequence,
+            older_reference: None,
+            newer_reference: None,
+            is_newer_reference_shown: false,
+            picture_type: PICTURE_TYPE_INTRA,
+            quantizer_scale: 1,
+            dc_predictors: [DC_PREDICTOR_RESET; 3],
+            forward: MotionState::default(),
+            backward: MotionState::default(),
+            previous_vectors: (None, None),
+            block: [0; 64],
+        }
+    }
+
+    pub fn sequence(&self) -> &SequenceHeader {
+        &self.sequence
+    }
+
+    /// Forgets the reference pictures, before decoding from another place in the stream.
+    pub fn reset(&mut self) {
+        self.older_reference = None;
+        self.newer_reference = None;
+        self.is_newer_reference_shown = false;
+    }
+
+    /// Returns the last reference picture when the stream has ended, as nothing comes after it to show it.
+    pub fn flush(&mut self) -> Option<Arc<Picture>> {
+        if self.is_newer_reference_shown {
+            return None;
+        }
+        self.is_newer_reference_shown = true;
+        self.newer_reference.clone()
+    }
+
+    /// Decodes the picture after its start code, and returns the picture to show next, if any. The reader stops
+    /// before the start code that follows the picture.
+    pub fn decode_picture(&mut self, reader: &mut BitReader) -> Option<Arc<Picture>> {
+        reader.skip(10); // Temporal reference, as the pictures are shown in the order of the references.
+        self.picture_type = reader.read(3) as u8;
+        reader.skip(16); // Buffer delay
+        if !matches!(
+            self.picture_type,
+            PICTURE_TYPE_INTRA | PICTURE_TYPE_PREDICTED | PICTURE_TYPE_BIDIRECTIONAL
+        ) {
+            return None;
+        }
+        if self.picture_type != PICTURE_TYPE_INTRA {
+            self.forward.read_parameters(reader)?;
+        }
+        if self.picture_type == PICTURE_TYPE_BIDIRECTIONAL {
+            self.backward.read_parameters(reader)?;
+        }
+        while reader.read_bool() {
+            reader.skip(8); // Extra information
+        }
+
+        let blank = || Arc::new(Picture::new(self.mb_width, self.mb_height));
+        let newer = self.newer_reference.clone().unwrap_or_else(blank);
+        let references = match self.picture_type {
+            PICTURE_TYPE_BIDIRECTIONAL => References {
+                // After a seek, the first B pictures can miss their forward reference.
+                forward: self
+                    .older_reference
+                    .clone()
+                    .unwrap_or_else(|| newer.clone()),
+                backward: newer,
+            },
+            _ => References {
+                forward: newer.clone(),
+                backward: newer,
+            },
+        };
+
+        let mut picture = Picture::new(self.mb_width, self.mb_height);
+        while let Some(code) = reader.next_start_code() {
+            match code {
+                SLICE_FIRST_START_CODE..=SLICE_LAST_START_CODE => {
+                    self.decode_slice(reader, code, &mut picture, &references);
+                }
+                USER_DATA_START_CODE | EXTENSION_START_CODE => {}
+                _ => {
+                    reader.rewind_start_code();
+                    break;
+                }
+            }
+        }
+        let picture = Arc::new(picture);
+
+        if self.picture_type == PICTURE_TYPE_BIDIRECTIONAL {
+            return Some(picture);
+        }
+        let shown = (!self.is_newer_reference_shown)
+            .then(|| self.newer_reference.clone())
+            .flatten();
+        self.older_reference = self.newer_reference.replace(picture);
+        self.is_newer_reference_shown = false;
+        shown
+    }
+
+    fn decode_slice(
+        &mut self,
+        reader: &mut BitReader,
+        slice_code: u8,
+        picture: &mut Picture,
+        references: &References,
+    ) {
+        let row = slice_code as usize - 1;
+        if row >= self.mb_height {
+            return;
+        }
+        self.quantizer_scale = reader.read(5) as i32;
+        while reader.read_bool() {
+            reader.skip(8); // Extra information
+        }
+        self.dc_predictors = [DC_PREDICTOR_RESET; 3];
+        self.forward.reset_predictor();
+        self.backward.reset_predictor();
+        self.previous_vectors = (None, None);
+
+        let macroblock_count = self.mb_width * self.mb_height;
+        // The first increment of a slice is counted from the end of the previous row.
+        let mut address = (row * self.mb_width) as isize - 1;
+        let mut is_first_macroblock = true;
+        loop {
+            let Some(increment) = read_address_increment(reader) else {
+                return;
+            };
+            if !is_first_macroblock {
+                for skipped in address + 1..address + increment as isize {
+                    if skipped as usize >= macroblock_count {
+                        return;
+                    }
+                    self.predict_skipped_macroblock(skipped as usize, picture, references);
+                }
+            }
+            is_first_macroblock = false;
+            address += increment as isize;
+            if address as usize >= macroblock_count {
+                return;
+            }
+            if self
+                .decode_macroblock(reader, address as usize, picture, references)
+                .is_none()
+                || reader.is_start_code_next()
+            {
+                return;
+            }
+        }
+    }
+
+    /// Skipped macroblocks are copied from the forward reference in P pictures, and predicted like the previous
+    /// macroblock in B pictures.
+    fn predict_skipped_macroblock(
+        &mut self,
+        address: usize,
+        picture: &mut Picture,
+        references: &References,
+    ) {
+        self.dc_predictors = [DC_PREDICTOR_RESET; 3];
+        let (mb_x, mb_y) = (address % self.mb_width, address / self.mb_width);
+        let vectors = if self.picture_type == PICTURE_TYPE_PREDICTED {
+            self.forward.reset_predictor();
+            (Some(MotionVector::default()), None)
+        } else {
+            self.previous_vectors
+        };
+        self.predict(picture, references, mb_x, mb_y, vectors);
+    }
+
+    fn predict(
+        &self,
+        picture: &mut Picture,
+        references: &References,
+        mb_x: usize,
+        mb_y: usize,
+        (forward, backward): (Option<MotionVector>, Option<MotionVector>),
+    ) {
+        if let Some(vector) = forward {
+            predict_macroblock(picture, &references.forward, mb_x, mb_y, vector, false);
+        }
+        if let Some(vector) = backward {
+            let average = forward.is_some();
+            predict_macroblock(picture, &references.backward, mb_x, mb_y, vector, average);
+        }
+    }
+
+    fn decode_macroblock(
+        &mut self,
+        reader: &mut BitReader,
+        address: usize,
+        picture: &mut Picture,
+        references: &References,
+    ) -> Option<()> {
+        let (mb_x, mb_y) = (address % self.mb_width, address / self.mb_width);
+        let flags = macroblock_type_table(self.picture_type).decode(reader)?;
+        if flags & MACROBLOCK_QUANT != 0 {
+            self.quantizer_scale = reader.read(5) as i32;
+        }
+
+        if flags & MACROBLOCK_INTRA != 0 {
+            self.forward.reset_predictor();
+            self.backward.reset_predictor();
+            self.previous_vectors = (None, None);
+            for block_index in 0..6 {
+                self.decode_block(reader, block_index, true)?;
+                let (plane, stride, x, y) = picture.block_target(block_index, mb_x, mb_y);
+                for (index, value) in self.block.iter().enumerate() {
+                    plane[(y + index / 8) * stride + x + index % 8] = (*value).clamp(0, 255) as u8;
+                }
+            }
+            return Some(());
+        }
+
+        self.dc_predictors = [DC_PREDICTOR_RESET; 3];
+        let forward = if flags & MACROBLOCK_FORWARD != 0 {
+            Some(self.forward.read_vector(reader)?)
+        } else if self.picture_type == PICTURE_TYPE_PREDICTED {
+            // The macroblocks of P pictures without a vector are predicted without motion.
+            self.forward.reset_predictor();
+            Some(MotionVector::default())
+        } else {
+            None
+        };
+        let backward = if flags & MACROBLOCK_BACKWARD != 0 {
+            Some(self.backward.read_vector(reader)?)
+        } else {
+            None
+        };
+        self.previous_vectors = (forward, backward);
+        self.predict(picture, references, mb_x, mb_y, (forward, backward));
+
+        let coded_block_pattern = if flags & MACROBLOCK_PATTERN != 0 {
+            coded_block_pattern_table().decode(reader)?
+        } else {
+            0
+        };
+        for block_index in 0..6 {
+            if coded_block_pattern & (0b100000 >> block_index) == 0 {
+                continue;
+            }
+            self.decode_block(reader, block_index, false)?;
+            let (plane, stride, x, y) = picture.block_target(block_index, mb_x, mb_y);
+            for (index, value) in self.block.iter().enumerate() {
+                let pixel = &mut plane[(y + index / 8) * stride + x + index % 8];
+                *pixel = (*pixel as i32 + value).clamp(0, 255) as u8;
+            }
+        }
+        Some(())
+    }
+
+    /// Reads the coefficients of a block, dequantizes them and puts the pixel values in `self.block`.
+    fn decode_block(
+        &mut self,
+        reader: &mut BitReader,
+        block_index: usize,
+        is_intra: bool,
+    ) -> Option<()> {
+        self.block = [0; 64];
+        let mut scan_index = 0;
+        if is_intra {
+            let size = dc_size_table(block_index < 4).decode(reader)? as u32;
+            let differential = if size == 0 {
+                0
+            } else {
+                let bits = reader.read(size) as i32;
+                if bits & (1 << (size - 1)) != 0 {
+                    bits
+                } else {
+                    bits - (1 << size) + 1
+                }
+            };
+            let predictor = &mut self.dc_predictors[block_index.saturating_sub(3)];
+            *predictor += differential << 3;
+            self.block[0] = *predictor;
+            scan_index = 1;
+        }
+
+        let matrix = if is_intra {
+            &self.sequence.intra_quantizer_matrix
+        } else {
+            &self.sequence.non_intra_quantizer_matrix
+        };
+        loop {
+            let (run, level) = if !is_intra && scan_index == 0 && reader.peek(1) == 1 {
+                reader.skip(1);
+                (0, if reader.read_bool() { -1 } else { 1 })
+            } else {
+                match dct_coefficient_table().decode(reader)? {
+                    DctCoefficient::EndOfBlock => break,
+                    DctCoefficient::Escape => {
+                        let run = reader.read(6) as usize;
+                        (run, read_escaped_level(reader))
+                    }
+                    DctCoefficient::RunLevel(run, level) => {
+                        let level = level as i32;
+                        (
+                            run as usize,
+                            if reader.read_bool() { -level } else { level },
+                        )
+                    }
+                }
+            };
+            scan_index += run;
+            if scan_index >= 64 {
+                return None;
+            }
+            let position = ZIGZAG[scan_index] as usize;
+            let scaled_level = if is_intra {
+                2 * level
+            } else {
+                2 * level + level.signum()
+            };
+            let mut value = scaled_level * self.quantizer_scale * matrix[position] as i32 / 16;
+            // Mismatch control: the values are made odd toward zero.
+            if value & 1 == 0 {
+                value -= value.signum();
+            }
+            self.block[position] = value.clamp(-2048, 2047);
+            scan_index += 1;
+        }
+        inverse_dct(&mut self.block);
+        Some(())
+    }
+}
+
+fn read_address_increment(reader: &mut BitReader) -> Option<usize> {
+    let mut increment = 0;
+    loop {
+        match macroblock_address_increment_table().decode(reader)? {
+            AddressIncrement::Stuffing => {}
+            AddressIncrement::Escape => increment += 33,
+            AddressIncrement::Increment(value) => return Some(increment + value as usize),
+        }
+    }
+}
+
+/// The level after an escape code: 8 bits, or 16 when the first 8 are 0 or -128.
+fn read_escaped_level(reader: &mut BitReader) -> i32 {
+    match reader.read(8) as i32 {
+        0 => reader.read(8) as i32,
+        128 => reader.read(8) as i32 - 256,
+        level if level > 128 => level - 256,
+        level => level,
+    }
+}
+
+/// A group of pictures starts with an I picture, so decoding can start there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GroupOfPictures {
+    pub byte_offset: usize,
+    /// The number of pictures before the group, which is also the display index of its first picture.
+    pub first_index: u64,
+}
+
+/// An MPEG-1 video stream, decoded one picture at a time.
+pub struct Mpeg1Video {
+    data: Arc<[u8]>,
+    position: usize,
+    decoder: Mpeg1Decoder,
+    groups: Vec<GroupOfPictures>,
+    picture_count: u64,
+    /// The display index of the next picture returned.
+    next_index: u64,
+    has_ended: bool,
+}
+
+impl Mpeg1Video {
+    /// Reads the sequence header and indexes the groups of pictures of a video elementary stream.
+    pub fn new(data: Arc<[u8]>) -> Result<Self, String> {
+        let mut reader = BitReader::new(&data, 0);
+        let mut sequence = None;
+        let mut groups = Vec::new();
+        let mut picture_count = 0;
+        while let Some(code) = reader.next_start_code() {
+            match code {
+                SEQUENCE_HEADER_CODE if sequence.is_none() => {
+                    sequence = Some(SequenceHeader::read(&mut reader)?);
+                }
+                GROUP_START_CODE => groups.push(GroupOfPictures {
+                    byte_offset: reader.byte_position() - 4,
+                    first_index: picture_count,
+                }),
+                PICTURE_START_CODE => picture_count += 1,
+                _ => {}
+            }
+        }
+        let sequence = sequence.ok_or("No MPEG-1 sequence header found")?;
+        Ok(Self {
+            data,
+            position: 0,
+            decoder: Mpeg1Decoder::new(sequence),
+            groups,
+            picture_count,
+            next_index: 0,
+            has_ended: false,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.decoder.sequence().width
+    }
+
+    pub fn height(&self) -> usize {
+        self.decoder.sequence().height
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        self.decoder.sequence().frame_rate
+    }
+
+    pub fn picture_count(&self) -> u64 {
+        self.picture_count
+    }
+
+    /// Decodes until the next picture in display order. Returns its display index and the picture, or None at the end.
+    pub fn next_picture(&mut self) -> Option<(u64, Arc<Picture>)> {
+        let data = Arc::clone(&self.data);
+        loop {
+            if self.has_ended {
+                let picture = self.decoder.flush()?;
+                return Some((self.take_index(), picture));
+            }
+            let mut reader = BitReader::new(&data, self.position);
+            let code = reader.next_start_code();
+            match code {
+                None | Some(SEQUENCE_END_CODE) => self.has_ended = true,
+                Some(SEQUENCE_HEADER_CODE) => {
+                    // Repeated headers can change the matrices, not the size.
+                    if let Ok(sequence) = SequenceHeader::read(&mut reader)
+                        && sequence.width == self.width()
+                        && sequence.height == self.height()
+                    {
+                        self.decoder.sequence = sequence;
+                    }
+                }
+                Some(PICTURE_START_CODE) => {
+                    let shown = self.decoder.decode_picture(&mut reader);
+                    self.position = reader.byte_position();
+                    if let Some(picture) = shown {
+                        return Some((self.take_index(), picture));
+                    }
+                    continue;
+                }
+                Some(_) => {}
+            }
+            self.position = reader.byte_position();
+        }
+    }
+
+    fn take_index(&mut self) -> u64 {
+        let index = self.next_index;
+        self.next_index += 1;
+        index
+    }
+
+    /// Moves to a group of pictures before the picture `index`. The next pictures returned start before `index`, and
+    /// the caller skips them.
+    pub fn seek(&mut self, index: u64) {
+        let group = self
+            .groups
+            .iter()
+            .rposition(|group| group.first_index <= index)
+            .unwrap_or(0);
+        // The first B pictures of a group are predicted from the last picture of the previous group.
+        let (byte_offset, first_index) = group
+            .checked_sub(1)
+            .and_then(|previous| self.groups.get(previous))
+            .map_or((0, 0), |group| (group.byte_offset, group.first_index));
+        self.position = byte_offset;
+        self.next_index = first_index;
+        self.has_ended = false;
+        self.decoder.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{
+        BitReader, Mpeg1Video, dc_size_table, dct_coefficient_table, inverse_dct,
+        macroblock_address_increment_table, macroblock_type_table, motion_code_table,
+    };
+
+    /// Writes the bits of a hand made stream.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bit_count: usize,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u32, count: u32) -> &mut Self {
+            for shift in (0..count).rev() {
+                if self.bit_count % 8 == 0 {
+                    self.bytes.push(0);
+                }
+                let bit = ((value >> shift) & 1) as u8;
+                let last = self.bytes.len() - 1;
+                self.bytes[last] |= bit << (7 - self.bit_count % 8);
+                self.bit_count += 1;
+            }
+            self
+        }
+
+        fn code(&mut self, code: &str) -> &mut Self {
+            for bit in code.bytes() {
+                self.bits(u32::from(bit == b'1'), 1);
+            }
+            self
+        }
+
+        fn start_code(&mut self, code: u8) -> &mut Self {
+            self.bit_count = self.bytes.len() * 8;
+            self.bits(1, 24).bits(code as u32, 8)
+        }
+    }
+
+    #[test]
+    fn bits_are_read_across_bytes_and_start_codes_are_found() {
+        let data = [0b1010_1100, 0b0101_0011, 0x00, 0x00, 0x01, 0xB3, 0xFF];
+        let mut reader = BitReader::new(&data, 0);
+        assert_eq!(reader.read(3), 0b101);
+        assert_eq!(reader.read(9), 0b0_1100_0101);
+        assert_eq!(reader.peek(4), 0b0011);
+        assert_eq!(reader.next_start_code(), Some(0xB3));
+        assert_eq!(reader.read(8), 0xFF);
+        assert_eq!(reader.read(8), 0, "reading past the end returns zeros");
+        assert_eq!(reader.next_start_code(), None);
+    }
+
+    #[test]
+    fn code_tables_are_prefix_free() {
+        // Building a table panics when a code is the prefix of another.
+        macroblock_address_increment_table();
+        for picture_type in 1..=3 {
+            macroblock_type_table(picture_type);
+        }
+        motion_code_table();
+        dc_size_table(true);
+        dc_size_table(false);
+
+        let mut writer = BitWriter::default();
+        writer.code("0000001100").code("1").code("000000000010011");
+        let mut reader = BitReader::new(&writer.bytes, 0);
+        assert_eq!(motion_code_table().decode(&mut reader), Some(16));
+        assert_eq!(motion_code_table().decode(&mut reader), Some(0));
+        assert_eq!(
+            dct_coefficient_table().decode(&mut reader),
+            Some(super::DctCoefficient::RunLevel(0, 37))
+        );
+    }
+
+    #[test]
+    fn a_block_with_only_a_dc_coefficient_is_flat() {
+        let mut block = [0; 64];
+        block[0] = 1024;
+        inverse_dct(&mut block);
+        assert!(block.iter().all(|value| *value == 128));
+
+        let mut block = [0; 64];
+        block[0] = 1024;
+        block[1] = 80;
+        inverse_dct(&mut block);
+        assert!(
+            block[0] > block[7],
+            "a horizontal frequency makes a gradient"
+        );
+        assert_eq!(block[0], block[56]);
+    }
+
+    /// One intra picture of 16x16 pixels where every block only has a DC coefficient.
+    fn flat_intra_picture() -> Vec<u8> {
+        let mut writer = BitWriter::default();
+        writer
+            .start_code(0xB3)
+            .bits(16, 12)
+            .bits(16, 12)
+            .bits(1, 4) // Square pixels
+            .bits(3, 4) // 25 frames per second
+            .bits(0x3FFFF, 18)
+            .bits(1, 1)
+            .bits(20, 10)
+            .bits(0, 3); // Not constrained, default matrices
+        writer.start_code(0xB8).bits(0, 25).bits(1, 1).bits(0, 1);
+        writer
+            .start_code(0x00)
+            .bits(0, 10)
+            .bits(1, 3) // I picture
+            .bits(0xFFFF, 16)
+            .bits(0, 1);
+        writer
+            .start_code(0x01)
+            .bits(1, 5) // Quantizer scale
+            .bits(0, 1)
+            .code("1") // First macroblock
+            .code("1"); // Intra
+        // The first luma block is 3 above the mid gray, the second 1 below the first.
+        writer.code("01").code("11").code("10");
+        writer.code("00").code("0").code("10");
+        // The other luma blocks and the chroma have no difference.
+        writer.code("100").code("10").code("100").code("10");
+        writer.code("00").code("10").code("00").code("10");
+        writer.start_code(0xB7);
+        writer.bytes
+    }
+
+    #[test]
+    fn an_intra_picture_is_decoded_and_shown_at_the_end_of_the_stream() {
+        let mut video =
+            Mpeg1Video::new(Arc::from(flat_intra_picture())).expect("the header is valid");
+        assert_eq!((video.width(), video.height()), (16, 16));
+        assert_eq!(video.frame_rate(), 25.0);
+        assert_eq!(video.picture_count(), 1);
+
+        let (index, picture) = video.next_picture().expect("the picture is shown");
+        assert_eq!(index, 0);
+        // (1024 + 3 * 8) / 8 and (1048 - 8) / 8
+        assert_eq!(picture.luma[0], 131);
+        assert_eq!(picture.luma[8], 130);
+        assert_eq!(picture.luma[8 * 16], 130);
+        let rgba = picture.to_rgba(16, 16);
+        assert_eq!(rgba.len(), 16 * 16 * 4);
+        assert_eq!(&rgba[0..4], &[134, 134, 134, 255]);
+        assert!(video.next_picture().is_none());
+
+        video.seek(0);
+        assert_eq!(video.next_picture().map(|(index, _)| index), Some(0));
+    }
+}
@@ -0,0 +1,156 @@
+//! Splits an MPEG-1 program stream (the `.mpg` container) into its video and audio streams.
+//!
+//! The container is a list of packs, each holding packets of one stream. Only the first video stream and the first
+//! audio stream are kept. The timestamps are ignored: the pictures are timed by the frame rate and the audio plays
+//! from its first sample.
+
+const PACK_START_CODE: u8 = 0xBA;
+const END_CODE: u8 = 0xB9;
+const VIDEO_SEQUENCE_HEADER_CODE: u8 = 0xB3;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DemuxedStreams {
+    /// An MPEG-1 video elementary stream.
+    pub video: Vec<u8>,
+    /// An MPEG audio elementary stream, usually MP2. Empty for a silent video.
+    pub audio: Vec<u8>,
+}
+
+fn start_code_at(data: &[u8], index: usize) -> Option<u8> {
+    match data.get(index..index + 4) {
+        Some([0, 0, 1, code]) => Some(*code),
+        _ => None,
+    }
+}
+
+/// Accepts a program stream, or a bare video stream (`.m1v`), which is returned as is.
+pub fn demux(data: &[u8]) -> Result<DemuxedStreams, String> {
+    match start_code_at(data, 0) {
+        Some(VIDEO_SEQUENCE_HEADER_CODE) => {
+            return Ok(DemuxedStreams {
+                video: data.to_vec(),
+                audio: Vec::new(),
+            });
+        }
+        Some(PACK_START_CODE) => {}
+        _ => return Err("Not an MPEG-1 video: convert it to .mpg with MPEG-1 video".to_string()),
+    }
+
+    let mut streams = DemuxedStreams::default();
+    let mut video_stream_id = None;
+    let mut audio_stream_id = None;
+    let mut index = 0;
+    while index + 4 <= data.len() {
+        let Some(code) = start_code_at(data, index) else {
+            // Garbage between packets: look for the next start code.
+            index += 1;
+            continue;
+        };
+        match code {
+            END_CODE => break,
+            PACK_START_CODE => {
+                // MPEG-1 packs have 8 bytes of clock and bit rate after their start code, starting with 0010.
+                let Some(first_byte) = data.get(index + 4) else {
+                    break;
+                };
+                if first_byte >> 4 != 0b0010 {
+                    return Err(
+                        "This is an MPEG-2 file, only MPEG-1 videos are supported".to_string()
+                    );
+                }
+                index += 12;
+            }
+            // The system header, padding and the other packets have their length after the start code.
+            0xBB..=0xFF => {
+                let Some(length) = data.get(index + 4..index + 6) else {
+                    break;
+                };
+                let length = u16::from_be_bytes([length[0], length[1]]) as usize;
+                let packet_end = (index + 6 + length).min(data.len());
+                let packet = &data[index + 6..packet_end];
+                let target = if (0xC0..=0xDF).contains(&code)
+                    && *audio_stream_id.get_or_insert(code) == code
+                {
+                    Some(&mut streams.audio)
+                } else if (0xE0..=0xEF).contains(&code)
+                    && *video_stream_id.get_or_insert(code) == code
+                {
+                    Some(&mut streams.video)
+                } else {
+                    None
+                };
+                if let Some(target) = target
+                    && let Some(payload) = packet_payload(packet)
+                {
+                    target.extend_from_slice(payload);
+                }
+                index = packet_end;
+            }
+            _ => index += 4,
+        }
+    }
+    if streams.video.is_empty() {
+        return Err("The file has no video stream".to_string());
+    }
+    Ok(streams)
+}
+
+/// Skips the header of an MPEG-1 packet: stuffing, buffer size and timestamps.
+fn packet_payload(packet: &[u8]) -> Option<&[u8]> {
+    let mut index = 0;
+    while packet.get(index) == Some(&0xFF) {
+        index += 1;
+    }
+    if packet.get(index)? & 0xC0 == 0x40 {
+        index += 2;
+    }
+    index += match packet.get(index)? & 0xF0 {
+        0x20 => 5,  // Presentation timestamp
+        0x30 => 10, // Presentation and decoding timestamps
+        _ if packet[index] == 0x0F => 1,
+        _ => return None,
+    };
+    packet.get(index..)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DemuxedStreams, demux};
+
+    fn packet(stream_id: u8, header: &[u8], payload: &[u8]) -> Vec<u8> {
+        let length = (header.len() + payload.len()) as u16;
+        let mut packet = vec![0, 0, 1, stream_id];
+        packet.extend_from_slice(&length.to_be_bytes());
+        packet.extend_from_slice(header);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    #[test]
+    fn packets_are_gathered_by_stream() {
+        let pack_header = [0, 0, 1, 0xBA, 0x21, 0, 1, 0, 1, 0x80, 0, 1];
+        let mut file = pack_header.to_vec();
+        file.extend(packet(0xBB, &[0; 6], &[]));
+        file.extend(packet(0xE0, &[0x0F], &[0, 0, 1, 0xB3]));
+        // Stuffing, a buffer size and a timestamp before the payload.
+        file.extend(packet(
+            0xC0,
+            &[0xFF, 0xFF, 0x40, 0, 0x21, 0, 1, 0, 1],
+            &[1, 2],
+        ));
+        file.extend(packet(0xE1, &[0x0F], &[9, 9]));
+        file.extend(pack_header);
+        file.extend(packet(0xE0, &[0x21, 0, 1, 0, 1], &[4, 5]));
+        file.extend(packet(0xBE, &[], &[0xFF; 3]));
+        file.extend([0, 0, 1, 0xB9]);
+
+        assert_eq!(
+            demux(&file),
+            Ok(DemuxedStreams {
+                video: vec![0, 0, 1, 0xB3, 4, 5],
+                audio: vec![1, 2],
+            })
+        );
+        assert!(demux(b"RIFF....WAVE").is_err());
+    }
+}
@@ -0,0 +1,4 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.8" tiledversion="1.8.2" name="checker" tilewidth="4" tileheight="4" tilecount="16" columns="4">
+ <image source="./checker.png" width="16" height="16"/>
+</tileset>
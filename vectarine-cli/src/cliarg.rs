@@ -9,6 +9,7 @@ pub enum VectarineCliFeatures {
     New(NewArgs),
     Export(ExportArgs),
     Test(TestArgs),
+    Check(CheckArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -42,6 +43,23 @@ pub struct ExportArgs {
     pub project: PathBuf,
     #[arg(long, short, value_enum)]
     pub target: ExportTarget,
+    /// Also export the web build as a single html file, next to the zip. Only valid with the web target.
+    #[arg(long)]
+    pub single_file: bool,
+    /// Export even if the scripts load files that are missing from the export.
+    #[arg(long)]
+    pub ignore_missing_assets: bool,
+    /// Write the size of the bundle per file extension to this path, as JSON.
+    #[arg(long)]
+    pub size_report: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+pub struct CheckArgs {
+    /// Path to the project manifest (the .vecta file) to check.
+    /// The files loaded by the scripts are checked without running the game.
+    #[arg(long, short)]
+    pub project: PathBuf,
 }
 
 #[derive(Parser, Debug)]
@@ -7,13 +7,22 @@ use runtime::{anyhow, projectinfo::get_project_info};
 
 use crate::{
     cliarg::ExportTarget,
-    project::exportproject::{ExportPlatform, export_project},
+    project::{
+        exportproject::{
+            ExportPlatform, describe_single_file_export, export_project,
+            export_project_as_single_html_file,
+        },
+        validateproject::validate_project_assets,
+    },
 };
 
 pub fn export(
     project_path: &Path,
     output_path: Option<&Path>,
     export_target: ExportTarget,
+    single_file: bool,
+    ignore_missing_assets: bool,
+    size_report_path: Option<&Path>,
 ) -> anyhow::Result<PathBuf> {
     let Ok(project_manifest_content) = fs::read_to_string(project_path) else {
         return Err(anyhow::anyhow!(
@@ -36,10 +45,50 @@ pub fn export(
         ExportTarget::Web => ExportPlatform::Web,
     };
 
-    let project_path = match export_project(project_path, &project_info, true, platform) {
-        Ok(path) => path,
+    if single_file && platform != ExportPlatform::Web {
+        return Err(anyhow::anyhow!(
+            "Single file exports are only available for the web target"
+        ));
+    }
+
+    let report = validate_project_assets(project_path);
+    print!("{report}");
+    if report.has_errors() && !ignore_missing_assets {
+        return Err(anyhow::anyhow!(
+            "Some files loaded by the scripts are missing from the export. Use --ignore-missing-assets to export anyway."
+        ));
+    }
+
+    let exported_project = match export_project(project_path, &project_info, true, platform) {
+        Ok(exported_project) => exported_project,
         Err(e) => Err(anyhow::anyhow!("{:?}", e))?,
     };
+    for content_pack in &exported_project.content_packs {
+        println!("Content pack: {content_pack}");
+    }
+    // The single html file is an extra artifact, the zip is still exported.
+    let single_html_file_path = if single_file {
+        let html_path = export_project_as_single_html_file(project_path, &project_info, true)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        println!("{}", describe_single_file_export(&html_path));
+        Some(html_path)
+    } else {
+        None
+    };
+    let project_path = exported_project.output_path;
+
+    if let Some(bundle_report) = &exported_project.bundle_report {
+        print!("{bundle_report}");
+        if let Some(size_report_path) = size_report_path {
+            fs::write(size_report_path, bundle_report.to_json()).map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to write the size report to {:?}: {:?}",
+                    size_report_path,
+                    e
+                )
+            })?;
+        }
+    }
 
     if let Some(output_path) = output_path {
         let output_path = output_path.to_path_buf();
@@ -51,6 +100,23 @@ pub fn export(
                 e
             )
         })?;
+        // Keep the html file next to the zip.
+        if let Some(html_path) = single_html_file_path
+            && let Some(output_folder) = output_path.parent()
+            && let Some(html_file_name) = html_path.file_name()
+        {
+            let html_output_path = output_folder.join(html_file_name);
+            if html_output_path != html_path {
+                fs::rename(&html_path, &html_output_path).map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to move the html file from {:?} to {:?}: {:?}",
+                        html_path,
+                        html_output_path,
+                        e
+                    )
+                })?;
+            }
+        }
         return Ok(output_path);
     }
 
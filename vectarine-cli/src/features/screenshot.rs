@@ -2,6 +2,8 @@ use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+use runtime::io::paths;
+
 use crate::headless::GameHeadlessRunner;
 
 /// Takes a screenshot of the game at the given project path, after running it for a few frames to let it initialize.
@@ -10,7 +12,7 @@ pub fn take_screenshot(
     output_path: Option<&Path>,
     initialization_frames: usize,
 ) -> vectarine_plugin_sdk::anyhow::Result<PathBuf> {
-    let canonicalized_project_path = project_path.canonicalize()?;
+    let canonicalized_project_path = paths::canonicalize(project_path)?;
 
     let game_runner = GameHeadlessRunner::new(&canonicalized_project_path);
     let mut game_runner = game_runner?;
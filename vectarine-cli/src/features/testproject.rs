@@ -1,6 +1,7 @@
 use runtime::{
     anyhow::{self, Result, anyhow},
     image::{DynamicImage, RgbaImage},
+    io::paths::{self, describe_path},
     sdl2::{self, event::Event},
     toml,
 };
@@ -62,13 +63,13 @@ mod testfileparsing {
 fn make_path_absolute(relative_file_path: &Path, anchor_file_path: &Path) -> PathBuf {
     if let Some(parent_dir) = anchor_file_path.parent() {
         let joined = parent_dir.join(relative_file_path);
-        if let Ok(path) = joined.canonicalize() {
+        if let Ok(path) = paths::canonicalize(&joined) {
             return path;
         }
         return joined;
     }
 
-    if let Ok(path) = relative_file_path.canonicalize() {
+    if let Ok(path) = paths::canonicalize(relative_file_path) {
         return path;
     }
 
@@ -105,9 +106,10 @@ pub fn run_test_file(test_file: &Path, overwrite: bool, acceptable_pixel_diff: u
     let test_manifest = toml::from_slice::<TestFile>(&test_file_content)?;
 
     let canonicalized_project_path = make_path_absolute(&test_manifest.project.path, test_file);
-    let canon_string = canonicalized_project_path.to_string_lossy().into_owned();
-
-    println!("Testing: {} ...", canon_string);
+    println!(
+        "Testing: {} ...",
+        describe_path(&canonicalized_project_path)
+    );
     if let Some(description) = &test_manifest.project.description {
         println!("➡️ {}", description);
     }
@@ -215,7 +217,7 @@ pub fn run_test_file(test_file: &Path, overwrite: bool, acceptable_pixel_diff: u
                         runtime::console::ConsoleMessage::LuaError(msg) => {
                             format!("Lua Error: {}", msg)
                         }
-                        runtime::console::ConsoleMessage::Reload => "--- Reload ---".to_string(),
+                        runtime::console::ConsoleMessage::Reload(_) => "--- Reload ---".to_string(),
                     })
                     .collect();
 
@@ -91,7 +91,7 @@ impl Display for FrameResult {
                 ConsoleMessage::Warning(msg) => writeln!(f, "[WARN] {}", msg)?,
                 ConsoleMessage::Error(msg) => writeln!(f, "[ERROR] {}", msg)?,
                 ConsoleMessage::LuaError(msg) => writeln!(f, "[ERROR] {}", msg)?,
-                ConsoleMessage::Reload => writeln!(f, "--- Reload ---")?,
+                ConsoleMessage::Reload(_) => writeln!(f, "--- Reload ---")?,
             }
         }
         writeln!(f, "--- Frame logs ---")?;
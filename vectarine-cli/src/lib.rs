@@ -43,6 +43,9 @@ pub fn lib_main() {
                 &export_args.project,
                 export_args.output.as_deref(),
                 export_args.target,
+                export_args.single_file,
+                export_args.ignore_missing_assets,
+                export_args.size_report.as_deref(),
             ) {
                 Ok(output_path) => {
                     println!("Exported project to {:?}", output_path);
@@ -52,6 +55,15 @@ pub fn lib_main() {
                 }
             }
         }
+        cliarg::VectarineCliFeatures::Check(check_args) => {
+            let report = project::validateproject::validate_project_assets(&check_args.project);
+            print!("{report}");
+            if report.has_errors() {
+                eprintln!("❌ Some files loaded by the scripts are missing from the export.");
+                std::process::exit(1);
+            }
+            println!("✅ Check passed.");
+        }
         cliarg::VectarineCliFeatures::Test(test_args) => {
             match features::testproject::test_project(
                 &test_args.path,
@@ -2,3 +2,5 @@ pub mod copydirall;
 pub mod createproject;
 pub mod exportproject;
 pub mod geteditorpaths;
+pub mod importassets;
+pub mod validateproject;
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 use std::{fs, path::Path};
 
 use runtime::anyhow;
-use runtime::{projectinfo::ProjectInfo, toml};
+use runtime::{apiversion::CURRENT_API_VERSION, projectinfo::ProjectInfo, toml};
 
 use crate::project::copydirall::copy_dir_all;
 use crate::project::geteditorpaths::get_luau_api_path;
@@ -43,11 +43,21 @@ fn copy_default_luau_api(project_folder: &Path) -> Result<(), std::io::Error> {
 }
 
 pub fn create_game_and_get_path(game_name: &str, game_path: &Path) -> anyhow::Result<PathBuf> {
+    create_game_with_script(game_name, game_path, DEFAULT_CODE)
+}
+
+/// Creates a project like `create_game_and_get_path`, with another main script.
+pub fn create_game_with_script(
+    game_name: &str,
+    game_path: &Path,
+    main_script: &str,
+) -> anyhow::Result<PathBuf> {
     let project_folder = game_path.join(game_name);
     let project_file_path = project_folder.join("game.vecta");
     let script_folder = project_folder.join("scripts");
     let project_info = ProjectInfo {
         title: game_name.to_string(),
+        api_version: Some(CURRENT_API_VERSION),
         ..ProjectInfo::default()
     };
 
@@ -65,7 +75,7 @@ pub fn create_game_and_get_path(game_name: &str, game_path: &Path) -> anyhow::Re
         setup_failed = setup_failed.or(fs::write(&project_file_path, serialized).err());
     }
 
-    setup_failed = setup_failed.or(fs::write(&main_script_path, DEFAULT_CODE).err());
+    setup_failed = setup_failed.or(fs::write(&main_script_path, main_script).err());
     setup_failed = setup_failed.or(copy_default_luau_api(&project_folder).err());
     setup_failed = setup_failed.or(fs::write(project_folder.join(".luaurc"), DEFAULT_LUAURC).err());
 
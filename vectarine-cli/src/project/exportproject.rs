@@ -1,17 +1,26 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
 use regex::Regex;
+use runtime::graphics::imagepacking::{ATLAS_FOLDER, pack_images};
+use runtime::io::contentpack::{PACK_MANIFEST_PATH, PackManifest};
+use runtime::io::paths::describe_path;
+use runtime::lua_env::lua_persist::EDITOR_CACHE_FOLDER;
 use runtime::mlua;
-use runtime::projectinfo::ProjectInfo;
+use runtime::projectinfo::{ContentPackInfo, ProjectInfo};
+use runtime::scriptdialect::ScriptDialect;
+use runtime::toml;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
-use runtime::egui::TextBuffer;
 use zip::write::SimpleFileOptions;
 
 use crate::project::geteditorpaths::{
     get_runtime_file_for_linux, get_runtime_file_for_macos, get_runtime_file_for_windows,
     get_runtime_file_paths_for_web,
 };
+use crate::project::importassets::is_import_source;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
 pub enum ExportPlatform {
@@ -44,12 +53,127 @@ impl std::fmt::Display for ExportPlatform {
     }
 }
 
+/// The zstd level of the scripts and text files of the bundle when the project does not set `bundle_zstd_level`.
+pub const DEFAULT_BUNDLE_ZSTD_LEVEL: i64 = 19;
+
+/// Formats that are already compressed. Compressing them again makes the export slower for almost no gain.
+const COMPRESSED_EXTENSIONS: [&str; 8] = ["ogg", "mp3", "png", "jpg", "jpeg", "gif", "webp", "mpg"];
+
+/// The images packed in atlases when the project sets `pack_images`.
+const PACKED_IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+pub struct ExportedProject {
+    pub output_path: PathBuf,
+    /// The sizes of the files in bundle.vecta, only for obfuscated exports.
+    pub bundle_report: Option<BundleSizeReport>,
+    /// The file names of the content packs exported next to the game.
+    pub content_packs: Vec<String>,
+}
+
+/// The size of the files of an extension in bundle.vecta, before and after compression.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtensionSize {
+    pub file_count: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+}
+
+impl ExtensionSize {
+    /// The output size divided by the input size, lower is better.
+    pub fn ratio(&self) -> f64 {
+        if self.input_bytes == 0 {
+            return 1.0;
+        }
+        self.output_bytes as f64 / self.input_bytes as f64
+    }
+
+    fn add(&mut self, other: &ExtensionSize) {
+        self.file_count += other.file_count;
+        self.input_bytes += other.input_bytes;
+        self.output_bytes += other.output_bytes;
+    }
+}
+
+/// How well each kind of file was compressed in bundle.vecta. The input size of scripts is the size of their source.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BundleSizeReport {
+    pub extensions: BTreeMap<String, ExtensionSize>,
+}
+
+impl BundleSizeReport {
+    pub fn total(&self) -> ExtensionSize {
+        let mut total = ExtensionSize::default();
+        for size in self.extensions.values() {
+            total.add(size);
+        }
+        total
+    }
+
+    pub fn to_json(&self) -> String {
+        let describe = |size: &ExtensionSize| {
+            format!(
+                "{{ \"files\": {}, \"inputBytes\": {}, \"outputBytes\": {}, \"ratio\": {:.4} }}",
+                size.file_count,
+                size.input_bytes,
+                size.output_bytes,
+                size.ratio()
+            )
+        };
+        let mut json = String::from("{\n  \"extensions\": {\n");
+        let entries = self
+            .extensions
+            .iter()
+            .map(|(extension, size)| {
+                format!(
+                    "    {}: {}",
+                    to_js_string_literal(extension),
+                    describe(size)
+                )
+            })
+            .collect::<Vec<_>>();
+        json.push_str(&entries.join(",\n"));
+        let _ = write!(
+            json,
+            "\n  }},\n  \"total\": {}\n}}\n",
+            describe(&self.total())
+        );
+        json
+    }
+}
+
+impl std::fmt::Display for BundleSizeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>6} {:>12} {:>12} {:>7}",
+            "Extension", "Files", "Input", "Output", "Ratio"
+        )?;
+        let rows = self
+            .extensions
+            .iter()
+            .map(|(extension, size)| (extension.as_str(), *size))
+            .chain(std::iter::once(("total", self.total())));
+        for (extension, size) in rows {
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>12} {:>12} {:>6.1}%",
+                extension,
+                size.file_count,
+                size.input_bytes,
+                size.output_bytes,
+                size.ratio() * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
 pub fn export_project(
     project_path: &Path,
     project_info: &ProjectInfo,
     obfuscate: bool,
     platform: ExportPlatform,
-) -> Result<PathBuf, String> {
+) -> Result<ExportedProject, String> {
     let game_data_folder = project_path
         .parent()
         .expect("Failed to get game data folder");
@@ -80,12 +204,7 @@ pub fn export_project(
                 let _ = fs::remove_file(&output_path);
             }
 
-            let index_html_content =
-                fs::read_to_string(&index_html_path).map_err(|e| e.to_string())?;
-            let re = Regex::new(r"target/[a-zA-Z0-9\-/]+/runtime.js").map_err(|e| e.to_string())?;
-            let index_html_content = re.replace_all(&index_html_content, "runtime.js");
-            let index_html_content =
-                index_html_content.replace("Vectarine Web Build", &project_info.title);
+            let index_html_content = prepare_index_html(&index_html_path, project_info)?;
             add_file_content_to_zip(
                 &mut zip,
                 index_html_content.as_bytes(),
@@ -94,16 +213,31 @@ pub fn export_project(
             )
             .map_err(|e| e.to_string())?;
 
-            add_file_to_zip_from_path(&mut zip, &runtime_js_path, "runtime.js", false, false)
-                .map_err(|e| e.to_string())?;
-            add_file_to_zip_from_path(&mut zip, &runtime_wasm_path, "runtime.wasm", false, false)
-                .map_err(|e| e.to_string())?;
+            add_file_to_zip_from_path(
+                &mut zip,
+                &runtime_js_path,
+                "runtime.js",
+                SimpleFileOptions::default(),
+            )
+            .map_err(|e| e.to_string())?;
+            add_file_to_zip_from_path(
+                &mut zip,
+                &runtime_wasm_path,
+                "runtime.wasm",
+                SimpleFileOptions::default(),
+            )
+            .map_err(|e| e.to_string())?;
         }
         ExportPlatform::Windows => {
             let runtime_path = get_runtime_file_for_windows();
             if let Some(runtime_path) = runtime_path {
-                add_file_to_zip_from_path(&mut zip, &runtime_path, "game.exe", true, false)
-                    .map_err(|e| e.to_string())?;
+                add_file_to_zip_from_path(
+                    &mut zip,
+                    &runtime_path,
+                    "game.exe",
+                    SimpleFileOptions::default().unix_permissions(0o755),
+                )
+                .map_err(|e| e.to_string())?;
             } else {
                 return Err("Failed to locate runtime.exe".into());
             }
@@ -111,8 +245,13 @@ pub fn export_project(
         ExportPlatform::Linux => {
             let runtime_path = get_runtime_file_for_linux();
             if let Some(runtime_path) = runtime_path {
-                add_file_to_zip_from_path(&mut zip, &runtime_path, "game", true, false)
-                    .map_err(|e| e.to_string())?;
+                add_file_to_zip_from_path(
+                    &mut zip,
+                    &runtime_path,
+                    "game",
+                    SimpleFileOptions::default().unix_permissions(0o755),
+                )
+                .map_err(|e| e.to_string())?;
             } else {
                 return Err("Failed to locate runtime executable".into());
             }
@@ -120,103 +259,484 @@ pub fn export_project(
         ExportPlatform::MacOS => {
             let runtime_path = get_runtime_file_for_macos();
             if let Some(runtime_path) = runtime_path {
-                add_file_to_zip_from_path(&mut zip, &runtime_path, "game", true, false)
-                    .map_err(|e| e.to_string())?;
+                add_file_to_zip_from_path(
+                    &mut zip,
+                    &runtime_path,
+                    "game",
+                    SimpleFileOptions::default().unix_permissions(0o755),
+                )
+                .map_err(|e| e.to_string())?;
             } else {
                 return Err("Failed to locate runtime executable".into());
             }
         }
     }
 
+    let (game_data_files, content_packs) =
+        split_content_packs(get_project_files(project_path), project_info)?;
+    let mut bundle_report = None;
     if !obfuscate {
         // Add game data folder
         // Adding .vecta file as executable as you can run it using a shebang.
-        let game_data_files = get_project_files(project_path);
-        for (file_path, zip_path) in game_data_files {
-            add_file_to_zip_from_path(&mut zip, &file_path, &zip_path, false, false)
-                .map_err(|e| e.to_string())?;
+        for (file_path, zip_path) in pack_game_images(project_path, project_info, game_data_files)?
+        {
+            add_file_to_zip_from_path(
+                &mut zip,
+                &file_path,
+                &zip_path,
+                SimpleFileOptions::default(),
+            )
+            .map_err(|e| e.to_string())?;
         }
     } else {
         // Compress game data into bundle.vecta (a zip with zstd compression)
         // then, put the bundle.vecta file into the exported zip
         let inner_zip_path = game_data_folder.join("bundle.vecta");
-        let inner_zip_file = fs::File::create(&inner_zip_path).map_err(|e| e.to_string())?;
-        let mut inner_zip = zip::ZipWriter::new(inner_zip_file);
-        let game_data_files = get_project_files(project_path);
-        for (file_path, zip_path) in game_data_files {
-            if file_path.extension() == Some(std::ffi::OsStr::new("luau")) {
-                // Compile into bytecode
-                let script_content = fs::read_to_string(&file_path).map_err(|e| e.to_string())?;
-                let compiler = mlua::chunk::Compiler::new()
-                    .set_optimization_level(2)
-                    .set_debug_level(0)
-                    .set_type_info_level(1);
-                let result = compiler.compile(script_content);
-                match result {
-                    Ok(bytecode) => {
-                        add_file_content_to_zip(
-                            &mut inner_zip,
-                            &bytecode,
-                            &zip_path,
-                            SimpleFileOptions::default(),
-                        )
-                        .map_err(|e| e.to_string())?;
-                    }
-                    Err(err) => {
-                        println!("Failed to compile {}: {}", file_path.display(), err);
-                        add_file_to_zip_from_path(
-                            &mut inner_zip,
-                            &file_path,
-                            &zip_path,
-                            false,
-                            false,
-                        )
-                        .map_err(|e| e.to_string())?;
-                    }
-                }
-            } else {
-                add_file_to_zip_from_path(&mut inner_zip, &file_path, &zip_path, false, false)
-                    .map_err(|e| e.to_string())?;
-            }
-        }
-        inner_zip.finish().map_err(|e| e.to_string())?;
+        bundle_report = Some(create_bundle(project_path, project_info, &inner_zip_path)?);
 
         add_file_to_zip_from_path(
             &mut zip,
             &inner_zip_path,
             "bundle.vecta",
-            false,
-            false, // avoid double compression
+            // avoid double compression
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
         )
         .map_err(|e| e.to_string())?;
         let _ = fs::remove_file(&inner_zip_path);
     }
 
+    // Content packs are bundles even when the game data is not obfuscated, as they are mounted from a single file.
+    let mut content_pack_names = Vec::new();
+    for (pack, files) in &content_packs {
+        let pack_filename = get_content_pack_filename(pack);
+        let pack_path = game_data_folder.join(&pack_filename);
+        create_content_pack(pack, files, project_info, &pack_path)?;
+        add_file_to_zip_from_path(
+            &mut zip,
+            &pack_path,
+            &pack_filename,
+            SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored),
+        )
+        .map_err(|e| e.to_string())?;
+        let _ = fs::remove_file(&pack_path);
+        content_pack_names.push(pack_filename);
+    }
+
     zip.finish().map_err(|e| e.to_string())?;
+    Ok(ExportedProject {
+        output_path,
+        bundle_report,
+        content_packs: content_pack_names,
+    })
+}
+
+/// A content pack of the project with the files of its folders.
+type ContentPackFiles<'a> = (&'a ContentPackInfo, Vec<(PathBuf, String)>);
+
+/// Separates the files of the content packs from the files of the game. A file in the folders of several packs goes in
+/// the first one. The sources of the import rules are left out, as the game reads their outputs.
+fn split_content_packs(
+    files: impl Iterator<Item = (PathBuf, String)>,
+    project_info: &ProjectInfo,
+) -> Result<(Vec<(PathBuf, String)>, Vec<ContentPackFiles<'_>>), String> {
+    let mut names = Vec::new();
+    for pack in &project_info.content_packs {
+        let filename = get_content_pack_filename(pack);
+        if pack.name.trim().is_empty() {
+            return Err("A content pack has no name".into());
+        }
+        if filename == "bundle.vecta" || names.contains(&filename) {
+            return Err(format!(
+                "The content pack name '{}' is used twice or is reserved",
+                pack.name
+            ));
+        }
+        names.push(filename);
+    }
+
+    let mut game_files = Vec::new();
+    let mut packs = project_info
+        .content_packs
+        .iter()
+        .map(|pack| (pack, Vec::new()))
+        .collect::<Vec<_>>();
+    for (file_path, zip_path) in files {
+        let resource_path = zip_path.strip_prefix("gamedata/").unwrap_or(&zip_path);
+        if is_import_source(project_info, resource_path) {
+            continue;
+        }
+        match packs
+            .iter_mut()
+            .find(|(pack, _)| pack.contains(resource_path))
+        {
+            Some((_, pack_files)) => pack_files.push((file_path, zip_path)),
+            None => game_files.push((file_path, zip_path)),
+        }
+    }
+    Ok((game_files, packs))
+}
+
+/// The name of the pack comes from the manifest of the project, so only letters, digits, `_` and `-` are kept to
+/// write the bundle next to the game and not elsewhere.
+fn get_content_pack_filename(pack: &ContentPackInfo) -> String {
+    // Example: forest_levels.vecta
+    let name = pack
+        .name
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if name.is_empty() {
+        return "pack.vecta".to_string();
+    }
+    format!("{name}.vecta")
+}
+
+/// Writes the bundle of a content pack, with the manifest checked when the game mounts it.
+fn create_content_pack(
+    pack: &ContentPackInfo,
+    files: &[(PathBuf, String)],
+    project_info: &ProjectInfo,
+    pack_path: &Path,
+) -> Result<BundleSizeReport, String> {
+    let manifest = PackManifest {
+        name: pack.name.trim().to_string(),
+        game: project_info.title.clone(),
+        game_version: project_info.version.clone(),
+    };
+    let manifest = toml::to_string(&manifest).map_err(|e| e.to_string())?;
+    write_bundle(files, Some(&manifest), project_info, pack_path)
+}
+
+/// Files that are already compressed are stored as-is, the others are compressed with zstd.
+fn bundle_file_options(zip_path: &str, project_info: &ProjectInfo) -> SimpleFileOptions {
+    let extension = get_extension(zip_path);
+    if project_info.fast_export || COMPRESSED_EXTENSIONS.contains(&extension.as_str()) {
+        return SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    }
+    let level = project_info
+        .bundle_zstd_level
+        .unwrap_or(DEFAULT_BUNDLE_ZSTD_LEVEL)
+        .clamp(1, 22);
+    SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Zstd)
+        .compression_level(Some(level))
+}
+
+fn get_extension(zip_path: &str) -> String {
+    Path::new(zip_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// The settings used to compile the scripts of obfuscated exports.
+pub fn bundle_compiler() -> mlua::chunk::Compiler {
+    mlua::chunk::Compiler::new()
+        .set_optimization_level(2)
+        .set_debug_level(0)
+        .set_type_info_level(1)
+}
+
+/// The compiler of an exported script. The scripts in Lua compatibility mode keep the options of their mode.
+pub fn bundle_compiler_for(dialect: ScriptDialect) -> mlua::chunk::Compiler {
+    match dialect {
+        ScriptDialect::Luau => bundle_compiler(),
+        ScriptDialect::LuaCompat => dialect.compiler().set_debug_level(0),
+    }
+}
+
+/// Compiles the scripts and compresses the game data into a single bundle.vecta file.
+pub(crate) fn create_bundle(
+    project_path: &Path,
+    project_info: &ProjectInfo,
+    bundle_path: &Path,
+) -> Result<BundleSizeReport, String> {
+    let (game_data_files, _) = split_content_packs(get_project_files(project_path), project_info)?;
+    let game_data_files = pack_game_images(project_path, project_info, game_data_files)?;
+    write_bundle(&game_data_files, None, project_info, bundle_path)
+}
+
+/// Replaces the images of the game with their region in atlas pages, written in the cache folder of the project.
+/// The images that cannot be read or are too large for a page are exported as they are.
+fn pack_game_images(
+    project_path: &Path,
+    project_info: &ProjectInfo,
+    files: Vec<(PathBuf, String)>,
+) -> Result<Vec<(PathBuf, String)>, String> {
+    if !project_info.pack_images {
+        return Ok(files);
+    }
+    let game_data_folder = project_path
+        .parent()
+        .expect("Failed to get game data folder");
+    let atlas_folder = game_data_folder.join(EDITOR_CACHE_FOLDER).join("atlas");
+    let _ = fs::remove_dir_all(&atlas_folder);
+    fs::create_dir_all(&atlas_folder).map_err(|e| e.to_string())?;
+
+    let mut exported_files = Vec::new();
+    let mut images = Vec::new();
+    let mut image_files = Vec::new();
+    for (file_path, zip_path) in files {
+        let is_image = PACKED_IMAGE_EXTENSIONS.contains(&get_extension(&zip_path).as_str());
+        match is_image.then(|| runtime::image::open(&file_path)) {
+            Some(Ok(image)) => {
+                images.push(image.to_rgba8());
+                image_files.push((file_path, zip_path));
+            }
+            Some(Err(error)) => {
+                println!("Not packing {}: {error}", describe_path(&file_path));
+                exported_files.push((file_path, zip_path));
+            }
+            None => exported_files.push((file_path, zip_path)),
+        }
+    }
+
+    let packed = pack_images(&images, |page| format!("{ATLAS_FOLDER}/page-{page}.png"));
+    for (page_index, page) in packed.pages.iter().enumerate() {
+        let page_path = atlas_folder.join(format!("page-{page_index}.png"));
+        page.save_with_format(&page_path, runtime::image::ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        exported_files.push((
+            page_path,
+            format!("gamedata/{ATLAS_FOLDER}/page-{page_index}.png"),
+        ));
+    }
+    // The packed images keep their path, so the scripts load them the same way.
+    for (index, ((file_path, zip_path), region)) in
+        image_files.into_iter().zip(packed.images).enumerate()
+    {
+        let Some(region) = region else {
+            exported_files.push((file_path, zip_path));
+            continue;
+        };
+        let region_path = atlas_folder.join(format!("image-{index}.txt"));
+        fs::write(&region_path, region.to_file_content()).map_err(|e| e.to_string())?;
+        exported_files.push((region_path, zip_path));
+    }
+    Ok(exported_files)
+}
+
+/// Writes the files in a bundle. Content packs also have a manifest.
+fn write_bundle(
+    files: &[(PathBuf, String)],
+    pack_manifest: Option<&str>,
+    project_info: &ProjectInfo,
+    bundle_path: &Path,
+) -> Result<BundleSizeReport, String> {
+    let inner_zip_file = fs::File::create(bundle_path).map_err(|e| e.to_string())?;
+    let mut inner_zip = zip::ZipWriter::new(inner_zip_file);
+    if let Some(pack_manifest) = pack_manifest {
+        add_file_content_to_zip(
+            &mut inner_zip,
+            pack_manifest.as_bytes(),
+            PACK_MANIFEST_PATH,
+            SimpleFileOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let mut input_sizes = Vec::new();
+    for (file_path, zip_path) in files {
+        let (file_path, zip_path) = (file_path.as_path(), zip_path.as_str());
+        let options = bundle_file_options(zip_path, project_info);
+        let input_bytes = fs::metadata(file_path).map_err(|e| e.to_string())?.len();
+        input_sizes.push((zip_path, input_bytes));
+        if file_path
+            .extension()
+            .is_some_and(|extension| extension == "luau" || extension == "lua")
+        {
+            // Compile into bytecode
+            let script_content = fs::read_to_string(file_path).map_err(|e| e.to_string())?;
+            let dialect = ScriptDialect::detect(file_path, script_content.as_bytes());
+            let result = bundle_compiler_for(dialect).compile(&script_content);
+            match result {
+                Ok(bytecode) => {
+                    add_file_content_to_zip(&mut inner_zip, &bytecode, zip_path, options)
+                        .map_err(|e| e.to_string())?;
+                }
+                Err(err) => {
+                    println!("Failed to compile {}: {}", file_path.display(), err);
+                    add_file_content_to_zip(
+                        &mut inner_zip,
+                        script_content.as_bytes(),
+                        zip_path,
+                        options,
+                    )
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+        } else {
+            add_file_to_zip_from_path(&mut inner_zip, file_path, zip_path, options)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    inner_zip.finish().map_err(|e| e.to_string())?;
+
+    // The compressed sizes are only known once the files are written.
+    let bundle = fs::File::open(bundle_path).map_err(|e| e.to_string())?;
+    let mut archive = zip::ZipArchive::new(bundle).map_err(|e| e.to_string())?;
+    let mut report = BundleSizeReport::default();
+    for (zip_path, input_bytes) in input_sizes {
+        let entry = archive.by_name(zip_path).map_err(|e| e.to_string())?;
+        let size = report
+            .extensions
+            .entry(get_extension(zip_path))
+            .or_default();
+        size.add(&ExtensionSize {
+            file_count: 1,
+            input_bytes,
+            output_bytes: entry.compressed_size(),
+        });
+    }
+    Ok(report)
+}
+
+/// Itch.io refuses files bigger than this in HTML games.
+const ITCH_IO_MAX_FILE_SIZE: u64 = 200 * 1024 * 1024;
+
+/// Exports the web version of the game as one html file containing the runtime, the WebAssembly module and the game data.
+/// The files are embedded as base64, so this is about a third bigger than the zip export.
+pub fn export_project_as_single_html_file(
+    project_path: &Path,
+    project_info: &ProjectInfo,
+    obfuscate: bool,
+) -> Result<PathBuf, String> {
+    let game_data_folder = project_path
+        .parent()
+        .expect("Failed to get game data folder");
+    let Some((runtime_js_path, runtime_wasm_path, index_html_path)) =
+        get_runtime_file_paths_for_web()
+    else {
+        return Err(
+            "Failed to locate runtime files (runtime.js, runtime.wasm, index.html). \
+            Make sure they are located next to the executable."
+                .into(),
+        );
+    };
+
+    let index_html_content = prepare_index_html(&index_html_path, project_info)?;
+    let runtime_js = fs::read_to_string(&runtime_js_path).map_err(|e| e.to_string())?;
+    let runtime_wasm = fs::read(&runtime_wasm_path).map_err(|e| e.to_string())?;
+
+    let (game_data_files, content_packs) =
+        split_content_packs(get_project_files(project_path), project_info)?;
+    let mut embedded_files = Vec::new();
+    if obfuscate {
+        let bundle_path = game_data_folder.join("bundle.vecta");
+        create_bundle(project_path, project_info, &bundle_path)?;
+        let bundle = fs::read(&bundle_path).map_err(|e| e.to_string());
+        let _ = fs::remove_file(&bundle_path);
+        embedded_files.push(("bundle.vecta".to_string(), bundle?));
+    } else {
+        for (file_path, zip_path) in pack_game_images(project_path, project_info, game_data_files)?
+        {
+            let content = fs::read(&file_path).map_err(|e| e.to_string())?;
+            embedded_files.push((zip_path, content));
+        }
+    }
+    // The packs are embedded too, so that Loader.mountBundle reads them without a web server.
+    for (pack, files) in &content_packs {
+        let pack_filename = get_content_pack_filename(pack);
+        let pack_path = game_data_folder.join(&pack_filename);
+        create_content_pack(pack, files, project_info, &pack_path)?;
+        let pack_content = fs::read(&pack_path).map_err(|e| e.to_string());
+        let _ = fs::remove_file(&pack_path);
+        embedded_files.push((pack_filename, pack_content?));
+    }
+
+    // The page reads window.vectarineEmbedded instead of fetching the files when it is defined.
+    let mut embedded_script = String::from("<script>\nwindow.vectarineEmbedded = {\nwasm: \"");
+    embedded_script.push_str(&BASE64_STANDARD.encode(&runtime_wasm));
+    embedded_script.push_str("\",\nfiles: {\n");
+    for (path, content) in embedded_files {
+        let _ = writeln!(
+            embedded_script,
+            "{}: \"{}\",",
+            to_js_string_literal(&path),
+            BASE64_STANDARD.encode(content)
+        );
+    }
+    embedded_script.push_str("},\n};\n</script>\n");
+
+    // The runtime is inlined after the loader script, so that the Module object already exists when it runs.
+    let runtime_script = format!(
+        "<script>\n{}\n</script>\n",
+        runtime_js.replace("</script", "<\\/script")
+    );
+
+    let mut html = index_html_content;
+    if let Some(head_end) = html.find("</head>") {
+        html.insert_str(head_end, &embedded_script);
+    }
+    if let Some(body_end) = html.rfind("</body>") {
+        html.insert_str(body_end, &runtime_script);
+    }
+
+    let output_path = game_data_folder.join(get_single_file_export_filename(project_info));
+    fs::write(&output_path, html).map_err(|e| e.to_string())?;
     Ok(output_path)
 }
 
+/// A message to show to the user after a single file export, with the final size and the hosting limits.
+pub fn describe_single_file_export(output_path: &Path) -> String {
+    let Ok(metadata) = fs::metadata(output_path) else {
+        return format!("Exported {}", output_path.display());
+    };
+    let size = metadata.len();
+    let mut description = format!(
+        "Exported {} ({:.2} MB).\nEmbedded files are encoded in base64, which makes them about 33% bigger than in the zip export.",
+        output_path.display(),
+        size as f64 / (1024.0 * 1024.0)
+    );
+    if size > ITCH_IO_MAX_FILE_SIZE {
+        description.push_str("\nWarning: itch.io does not accept files bigger than 200 MB, use the zip export instead.");
+    }
+    description
+}
+
+fn to_js_string_literal(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            // Avoid closing the script tag from inside a string.
+            '<' => literal.push_str("\\u003c"),
+            c if c.is_control() => {
+                let _ = write!(literal, "\\u{:04x}", c as u32);
+            }
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn prepare_index_html(
+    index_html_path: &Path,
+    project_info: &ProjectInfo,
+) -> Result<String, String> {
+    let index_html_content = fs::read_to_string(index_html_path).map_err(|e| e.to_string())?;
+    let re = Regex::new(r"target/[a-zA-Z0-9\-/]+/runtime.js").map_err(|e| e.to_string())?;
+    let index_html_content = re.replace_all(&index_html_content, "runtime.js");
+    Ok(index_html_content.replace("Vectarine Web Build", &project_info.title))
+}
+
 fn add_file_to_zip_from_path(
     zip: &mut zip::ZipWriter<fs::File>,
     file_path: &Path,
     zip_path: &str,
-    as_executable: bool,
-    as_zstd: bool,
+    options: SimpleFileOptions,
 ) -> std::io::Result<()> {
-    let options = SimpleFileOptions::default();
-    let options = if as_executable {
-        options.unix_permissions(0o755)
-    } else {
-        options
-    };
-    let options = if as_zstd {
-        options.compression_method(zip::CompressionMethod::Zstd)
-    } else {
-        options
-    };
-
     // Note: itch like tar files for web games, maybe this should be an option.
     // zip + zstd are smaller though.
+    // Names that are not ASCII get the UTF-8 flag (bit 11) from zip-rs, so they are read back the same everywhere.
     zip.start_file(zip_path, options)?;
     let mut f = fs::File::open(file_path)?;
     io::copy(&mut f, zip)?;
@@ -244,6 +764,11 @@ fn get_export_filename(project_info: &ProjectInfo, platform: ExportPlatform) ->
     )
 }
 
+fn get_single_file_export_filename(project_info: &ProjectInfo) -> String {
+    // Example: my_snake_web.html
+    format!("{}_web.html", project_info.title.replace(" ", "_"))
+}
+
 fn get_files_in_folder(folder_path: &Path, zip_base_path: &str) -> Vec<(PathBuf, String)> {
     let mut files = Vec::new();
     let Ok(entries) = fs::read_dir(folder_path) else {
@@ -254,8 +779,9 @@ fn get_files_in_folder(folder_path: &Path, zip_base_path: &str) -> Vec<(PathBuf,
             continue;
         };
         let path = entry.path();
-        let file_name = entry.file_name();
-        let file_name = file_name.to_string_lossy();
+        let Some(file_name) = exported_name(&path) else {
+            continue;
+        };
         if path.is_file() {
             let zip_path = format!("{}/{}", zip_base_path, file_name);
             files.push((path, zip_path));
@@ -268,12 +794,20 @@ fn get_files_in_folder(folder_path: &Path, zip_base_path: &str) -> Vec<(PathBuf,
     files
 }
 
-fn get_project_files(project_path: &Path) -> impl Iterator<Item = (PathBuf, String)> {
+pub(crate) fn get_project_files(project_path: &Path) -> impl Iterator<Item = (PathBuf, String)> {
     let game_data_folder = project_path
         .parent()
         .expect("Failed to get game data folder");
     let unexported_folder_names = [
-        "release", "game", "output", "build", "debug", "export", "private", "luau-api",
+        "release",
+        "game",
+        "output",
+        "build",
+        "debug",
+        "export",
+        "private",
+        "luau-api",
+        EDITOR_CACHE_FOLDER,
     ];
     // Add game data folder
     // Adding .vecta file as executable as you can run it using a shebang.
@@ -299,13 +833,259 @@ fn get_project_files(project_path: &Path) -> impl Iterator<Item = (PathBuf, Stri
                 game_data_folder.display()
             );
         };
-        let folder_name = folder_name.to_string_lossy();
-        let folder_name = folder_name.as_str();
-        if unexported_folder_names.contains(&folder_name) {
+        if unexported_folder_names
+            .iter()
+            .any(|unexported_folder_name| folder_name == *unexported_folder_name)
+        {
             continue;
         }
+        let Some(folder_name) = exported_name(&path) else {
+            continue;
+        };
         let sub_iter = get_files_in_folder(&path, &format!("gamedata/{}", folder_name));
         iter.extend(sub_iter);
     }
     iter.into_iter()
 }
+
+/// Returns the name of the file as written in the archive. Names that are not valid Unicode cannot be written in a zip
+/// archive and would not match the paths used by the scripts, so the file is skipped with a warning.
+fn exported_name(path: &Path) -> Option<&str> {
+    let file_name = path.file_name()?;
+    let file_name = file_name.to_str();
+    if file_name.is_none() {
+        println!(
+            "Skipping {}: its name is not valid Unicode, rename it to export it.",
+            describe_path(path)
+        );
+    }
+    file_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BundleSizeReport, create_bundle, create_content_pack, get_content_pack_filename,
+        get_project_files, split_content_packs,
+    };
+    use runtime::graphics::imagepacking::{ATLAS_FOLDER, PackedImage};
+    use runtime::image::{Rgba, RgbaImage};
+    use runtime::io::contentpack::MountedPack;
+    use runtime::io::zipfs::ZipFileSystem;
+    use runtime::projectinfo::{ContentPackInfo, ProjectInfo};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const LEVEL_FILE: (&str, &str) = ("data/level.txt", "gamedata/data/level.txt");
+    const TEXTURE_FILE: (&str, &str) = ("textures/a.png", "gamedata/textures/a.png");
+    const SCRIPT_FILE: (&str, &str) = ("scripts/game.luau", "gamedata/scripts/game.luau");
+
+    /// Writes a small project in a temporary folder and returns the path of its manifest.
+    fn write_test_project(name: &str) -> PathBuf {
+        let project_folder =
+            std::env::temp_dir().join(format!("vectarine-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_folder);
+        // Bytes that do not compress, like a real png.
+        let texture = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        let files: [(&str, Vec<u8>); 4] = [
+            ("game.vecta", b"title = \"Bundle test\"\n".to_vec()),
+            (SCRIPT_FILE.0, b"local x = 1 + 1\nprint(x)\n".to_vec()),
+            (TEXTURE_FILE.0, texture),
+            (
+                LEVEL_FILE.0,
+                "wall wall floor floor\n".repeat(200).into_bytes(),
+            ),
+        ];
+        for (path, content) in files {
+            let path = project_folder.join(path);
+            fs::create_dir_all(path.parent().expect("the file is in a folder"))
+                .expect("the test project folder is created");
+            fs::write(path, content).expect("the test project file is written");
+        }
+        project_folder.join("game.vecta")
+    }
+
+    fn bundle_and_read_back(
+        project_path: &Path,
+        project_info: &ProjectInfo,
+    ) -> (BundleSizeReport, ZipFileSystem) {
+        let bundle_path = project_path.with_file_name("bundle.vecta");
+        let report =
+            create_bundle(project_path, project_info, &bundle_path).expect("the bundle is created");
+        let bundle = fs::read(&bundle_path).expect("the bundle is readable");
+        let bundle_fs = ZipFileSystem::new(bundle).expect("the bundle is a valid zip");
+        (report, bundle_fs)
+    }
+
+    fn assert_entries_round_trip(project_path: &Path, bundle_fs: &ZipFileSystem) {
+        let project_folder = project_path.parent().expect("the manifest is in a folder");
+        for (path, zip_path) in [LEVEL_FILE, TEXTURE_FILE] {
+            let original = fs::read(project_folder.join(path)).expect("the file is readable");
+            assert_eq!(
+                bundle_fs.read_file_sync(zip_path),
+                Some(original),
+                "{zip_path} changed in the bundle"
+            );
+        }
+        let manifest = fs::read(project_path).expect("the manifest is readable");
+        assert_eq!(
+            bundle_fs.read_file_sync("gamedata/game.vecta"),
+            Some(manifest)
+        );
+        // Scripts are compiled to bytecode, so only check that they are present.
+        assert!(
+            bundle_fs
+                .read_file_sync(SCRIPT_FILE.1)
+                .is_some_and(|bytecode| !bytecode.is_empty())
+        );
+    }
+
+    #[test]
+    fn bundle_entries_are_compressed_and_read_back() {
+        let project_path = write_test_project("bundle-compressed");
+        let (report, bundle_fs) = bundle_and_read_back(&project_path, &ProjectInfo::default());
+        assert_entries_round_trip(&project_path, &bundle_fs);
+
+        let png = report.extensions["png"];
+        assert_eq!(png.file_count, 1);
+        assert_eq!(png.output_bytes, png.input_bytes, "png files are stored");
+        let txt = report.extensions["txt"];
+        assert!(
+            txt.output_bytes < txt.input_bytes / 4,
+            "text files use zstd"
+        );
+        assert_eq!(report.total().file_count, 4);
+
+        let _ = fs::remove_dir_all(project_path.parent().expect("the manifest is in a folder"));
+    }
+
+    #[test]
+    fn fast_export_stores_every_entry() {
+        let project_path = write_test_project("bundle-fast");
+        let project_info = ProjectInfo {
+            fast_export: true,
+            ..ProjectInfo::default()
+        };
+        let (report, bundle_fs) = bundle_and_read_back(&project_path, &project_info);
+        assert_entries_round_trip(&project_path, &bundle_fs);
+
+        let txt = report.extensions["txt"];
+        assert_eq!(txt.output_bytes, txt.input_bytes);
+
+        let _ = fs::remove_dir_all(project_path.parent().expect("the manifest is in a folder"));
+    }
+
+    #[test]
+    fn packed_images_point_to_their_atlas() {
+        let project_path = write_test_project("bundle-atlas");
+        let project_folder = project_path.parent().expect("the manifest is in a folder");
+        for (name, size) in [("hero.png", 16), ("coin.png", 8)] {
+            RgbaImage::from_pixel(size, size, Rgba([200, 100, 50, 255]))
+                .save(project_folder.join("textures").join(name))
+                .expect("the image is written");
+        }
+        let project_info = ProjectInfo {
+            pack_images: true,
+            ..ProjectInfo::default()
+        };
+        let (_, bundle_fs) = bundle_and_read_back(&project_path, &project_info);
+
+        let page_path = format!("{ATLAS_FOLDER}/page-0.png");
+        let hero = bundle_fs
+            .read_file_sync("gamedata/textures/hero.png")
+            .and_then(|data| PackedImage::parse(&data))
+            .expect("the image is packed");
+        assert_eq!((hero.page.as_str(), hero.width), (page_path.as_str(), 16));
+        assert!(
+            bundle_fs
+                .read_file_sync("gamedata/textures/coin.png")
+                .and_then(|data| PackedImage::parse(&data))
+                .is_some()
+        );
+        assert!(
+            bundle_fs
+                .read_file_sync(&format!("gamedata/{page_path}"))
+                .is_some()
+        );
+        // The file that is not a valid image is left as it is.
+        assert_entries_round_trip(&project_path, &bundle_fs);
+
+        let _ = fs::remove_dir_all(project_folder);
+    }
+
+    #[test]
+    fn content_pack_folders_leave_the_bundle() {
+        let project_path = write_test_project("bundle-packs");
+        let project_info = ProjectInfo {
+            title: "Bundle test".to_string(),
+            version: Some("1.0".to_string()),
+            content_packs: vec![ContentPackInfo {
+                name: "levels".to_string(),
+                folders: vec!["data/".to_string()],
+            }],
+            ..ProjectInfo::default()
+        };
+        let (_, bundle_fs) = bundle_and_read_back(&project_path, &project_info);
+        assert_eq!(bundle_fs.read_file_sync(LEVEL_FILE.1), None);
+        assert!(bundle_fs.read_file_sync(TEXTURE_FILE.1).is_some());
+
+        let (_, packs) = split_content_packs(get_project_files(&project_path), &project_info)
+            .expect("the packs are valid");
+        let [(pack, files)] = packs.as_slice() else {
+            panic!("one pack is exported");
+        };
+        let pack_path = project_path.with_file_name("levels.vecta");
+        create_content_pack(pack, files, &project_info, &pack_path).expect("the pack is created");
+        let pack = MountedPack::open(fs::read(&pack_path).expect("the pack is readable"), 0)
+            .expect("the pack has a manifest");
+        assert_eq!(pack.name(), "levels");
+        assert_eq!(pack.manifest.game_version.as_deref(), Some("1.0"));
+        assert!(pack.contains(Path::new(LEVEL_FILE.0)));
+        assert!(!pack.contains(Path::new(TEXTURE_FILE.0)));
+
+        let _ = fs::remove_dir_all(project_path.parent().expect("the manifest is in a folder"));
+    }
+
+    #[test]
+    fn unicode_names_round_trip() {
+        let project_path = write_test_project("bundle-unicode");
+        let project_folder = project_path.parent().expect("the manifest is in a folder");
+        let unicode_folder = project_folder.join("レベル データ");
+        fs::create_dir_all(&unicode_folder).expect("the folder is created");
+        fs::write(unicode_folder.join("Уровень 1.txt"), "floor").expect("the file is written");
+
+        // macOS refuses names that are not valid Unicode.
+        #[cfg(target_os = "linux")]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+            let name = OsStr::from_bytes(b"caf\xe9.txt");
+            fs::write(unicode_folder.join(name), "skipped").expect("the file is written");
+        }
+
+        let (report, bundle_fs) = bundle_and_read_back(&project_path, &ProjectInfo::default());
+        assert_eq!(
+            bundle_fs.read_file_sync("gamedata/レベル データ/Уровень 1.txt"),
+            Some(b"floor".to_vec())
+        );
+        // Files with names that are not valid Unicode are skipped.
+        assert_eq!(report.total().file_count, 5);
+
+        let _ = fs::remove_dir_all(project_folder);
+    }
+
+    #[test]
+    fn content_pack_filenames_stay_next_to_the_game() {
+        let filename = |name: &str| {
+            get_content_pack_filename(&ContentPackInfo {
+                name: name.to_string(),
+                folders: Vec::new(),
+            })
+        };
+        assert_eq!(filename(" Forest levels "), "Forest_levels.vecta");
+        assert_eq!(filename("../../evil"), "______evil.vecta");
+        assert_eq!(filename("C:\\dlc-2"), "C__dlc-2.vecta");
+        assert_eq!(filename(""), "pack.vecta");
+    }
+}
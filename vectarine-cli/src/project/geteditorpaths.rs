@@ -117,6 +117,18 @@ pub fn get_editor_config_path() -> PathBuf {
     base_dirs.config_dir().join(EDITOR_CONFIG_FILE)
 }
 
+/// The gamepad mappings made in the "Gamepad setup" window, in the format of SDL_GameControllerDB.
+pub fn get_gamepad_mappings_path() -> PathBuf {
+    let base_dirs = get_base_dir();
+    base_dirs.config_dir().join("gamecontrollerdb.txt")
+}
+
+/// The answers of the user to running the commands of the import rules of projects.
+pub fn get_trusted_import_commands_path() -> PathBuf {
+    let base_dirs = get_base_dir();
+    base_dirs.config_dir().join("trusted-imports.toml")
+}
+
 pub fn get_editor_plugins_path() -> PathBuf {
     let base_dirs = get_base_dir();
     base_dirs.data_dir().join("plugins")
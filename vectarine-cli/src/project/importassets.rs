@@ -0,0 +1,473 @@
+//! Import rules convert the source files of the artists, like `.aseprite` files, into files the engine reads. They are
+//! written in the `[import]` section of game.vecta:
+//!
+//! ```toml
+//! [import]
+//! "*.aseprite" = "aseprite --batch {in} --sheet {out}.png --data {out}.json"
+//! ```
+//!
+//! `{in}` is the path of the source and `{out}` the path of the output without extension, in the `generated` folder,
+//! with the same folders as the source. A command starting with `@`, like `@svg`, is one of the `builtinconverters`.
+//! The editor runs the rules when a source changes, and the sources are not exported.
+//!
+//! The commands come with the project, so the editor asks the user before running them. The answer is kept, by
+//! project and by set of commands, in `ImportTrust`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use runtime::io::paths;
+use runtime::lua_env::lua_persist::EDITOR_CACHE_FOLDER;
+use runtime::projectinfo::ProjectInfo;
+use runtime::serde::{Deserialize, Serialize};
+use runtime::toml;
+
+use crate::project::exportproject::get_project_files;
+use crate::project::geteditorpaths::get_trusted_import_commands_path;
+use crate::project::importassets::builtinconverters::run_built_in_converter;
+
+pub mod builtinconverters;
+
+/// The folder of the outputs of the import rules, next to game.vecta. It is exported like the other folders.
+pub const IMPORT_OUTPUT_FOLDER: &str = "generated";
+const IMPORT_CACHE_FILE_NAME: &str = "imports.toml";
+
+/// Matches a path relative to game.vecta, with `/` separators, against the pattern of an import rule. `*` matches any
+/// characters but `/`, `**/` any number of folders and `?` one character. A pattern without `/` matches the file name,
+/// in any folder.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim().trim_start_matches("./");
+    let path = path.trim_start_matches("./");
+    let path = if pattern.contains('/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let path = path.chars().collect::<Vec<_>>();
+    matches_from(&pattern, &path)
+}
+
+fn matches_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            matches_from(rest, path)
+                || path.iter().enumerate().any(|(index, character)| {
+                    *character == '/' && matches_from(rest, &path[index + 1..])
+                })
+        }
+        ['*', '*'] => true,
+        ['*', rest @ ..] => {
+            let segment_end = path
+                .iter()
+                .position(|character| *character == '/')
+                .unwrap_or(path.len());
+            (0..=segment_end).any(|index| matches_from(rest, &path[index..]))
+        }
+        ['?', rest @ ..] => {
+            path.first().is_some_and(|character| *character != '/')
+                && matches_from(rest, &path[1..])
+        }
+        [character, rest @ ..] => path.first() == Some(character) && matches_from(rest, &path[1..]),
+    }
+}
+
+/// The command converting the file at `resource_path`, relative to game.vecta. When several patterns match, the first
+/// one in alphabetical order is used. The outputs are never sources, so that a rule cannot convert its own outputs.
+pub fn find_import_command<'a>(
+    project_info: &'a ProjectInfo,
+    resource_path: &str,
+) -> Option<&'a str> {
+    let resource_path = resource_path.replace('\\', "/");
+    let resource_path = resource_path.trim_start_matches("./");
+    if resource_path.starts_with(&format!("{IMPORT_OUTPUT_FOLDER}/")) {
+        return None;
+    }
+    project_info
+        .import
+        .iter()
+        .find(|(pattern, _)| glob_matches(pattern, resource_path))
+        .map(|(_, command)| command.as_str())
+}
+
+pub fn is_import_source(project_info: &ProjectInfo, resource_path: &str) -> bool {
+    find_import_command(project_info, resource_path).is_some()
+}
+
+/// The path of the outputs of a source, without extension, like "generated/art/hero" for "art/hero.aseprite".
+pub fn get_import_output_stem(resource_path: &str) -> String {
+    let resource_path = resource_path.trim_start_matches("./");
+    let stem = resource_path
+        .rsplit_once('.')
+        .filter(|(_, extension)| !extension.contains('/'))
+        .map_or(resource_path, |(stem, _)| stem);
+    format!("{IMPORT_OUTPUT_FOLDER}/{stem}")
+}
+
+/// A source to convert.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportJob {
+    /// The path of the source relative to game.vecta, with `/` separators.
+    pub resource_path: String,
+    pub command: String,
+    /// The hash of the content of the source and of the command, to skip the conversions already done.
+    pub hash: String,
+}
+
+impl ImportJob {
+    /// Returns None when the file is not the source of a rule or cannot be read.
+    pub fn new(
+        project_folder: &Path,
+        project_info: &ProjectInfo,
+        source_path: &Path,
+    ) -> Option<Self> {
+        // The watcher reports canonical paths, which the path of the project may not be.
+        let resource_path = match source_path.strip_prefix(project_folder) {
+            Ok(resource_path) => resource_path.to_path_buf(),
+            Err(_) => paths::canonicalize(source_path)
+                .ok()?
+                .strip_prefix(paths::canonicalize(project_folder).ok()?)
+                .ok()?
+                .to_path_buf(),
+        };
+        let resource_path = resource_path.to_string_lossy().replace('\\', "/");
+        let command = find_import_command(project_info, &resource_path)?.to_string();
+        let content = fs::read(source_path).ok()?;
+        // The hash is kept on disk, so it must not change between versions of Rust like the hasher of std can.
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+        hasher.update(command.as_bytes());
+        Some(Self {
+            resource_path,
+            command,
+            hash: hasher.finalize().to_hex().to_string(),
+        })
+    }
+
+    /// Built-in converters run without asking the user, as they come with the engine.
+    pub fn is_built_in(&self) -> bool {
+        is_built_in_command(&self.command)
+    }
+
+    /// The arguments of the command, with `{in}` and `{out}` replaced.
+    pub fn arguments(&self) -> Vec<String> {
+        let output_stem = get_import_output_stem(&self.resource_path);
+        split_command(&self.command)
+            .into_iter()
+            .map(|argument| {
+                argument
+                    .replace("{in}", &self.resource_path)
+                    .replace("{out}", &output_stem)
+            })
+            .collect()
+    }
+
+    /// Runs the command in the folder of the project. On failure, the error contains what the command printed.
+    pub fn run(&self, project_folder: &Path) -> Result<(), String> {
+        let output_folder = project_folder.join(get_import_output_stem(&self.resource_path));
+        if let Some(output_folder) = output_folder.parent() {
+            fs::create_dir_all(output_folder)
+                .map_err(|err| format!("Could not create {}: {err}", output_folder.display()))?;
+        }
+        if self.is_built_in() {
+            let converter = split_command(self.command.trim_start().trim_start_matches('@'));
+            return run_built_in_converter(
+                &converter,
+                project_folder,
+                &self.resource_path,
+                &get_import_output_stem(&self.resource_path),
+            );
+        }
+        let arguments = self.arguments();
+        let Some((program, arguments)) = arguments.split_first() else {
+            return Err(format!(
+                "The import rule of {} has no command",
+                self.resource_path
+            ));
+        };
+        let mut command = Command::new(program);
+        command.args(arguments).current_dir(project_folder);
+        // Without this, a console window flashes on screen for every conversion.
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            command.creation_flags(CREATE_NO_WINDOW);
+        }
+        let output = command.output().map_err(|err| {
+            format!(
+                "Could not run {program} to import {}: {err}",
+                self.resource_path
+            )
+        })?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let mut message = format!(
+            "{program} failed to import {} ({})",
+            self.resource_path, output.status
+        );
+        for stream in [&output.stdout, &output.stderr] {
+            let text = String::from_utf8_lossy(stream);
+            if !text.trim().is_empty() {
+                message.push('\n');
+                message.push_str(text.trim_end());
+            }
+        }
+        Err(message)
+    }
+}
+
+fn is_built_in_command(command: &str) -> bool {
+    command.trim_start().starts_with('@')
+}
+
+/// The commands of the import rules that run programs of the computer, sorted and without duplicates.
+pub fn get_external_import_commands(project_info: &ProjectInfo) -> Vec<&str> {
+    let mut commands = project_info
+        .import
+        .values()
+        .map(|command| command.trim())
+        .filter(|command| !is_built_in_command(command))
+        .collect::<Vec<_>>();
+    commands.sort_unstable();
+    commands.dedup();
+    commands
+}
+
+/// Splits a command in arguments like a shell: spaces separate the arguments, except inside quotes.
+fn split_command(command: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut argument = String::new();
+    let mut is_in_argument = false;
+    let mut quote = None;
+    for character in command.chars() {
+        match quote {
+            Some(quote_character) if character == quote_character => quote = None,
+            Some(_) => argument.push(character),
+            None if character == '"' || character == '\'' => {
+                quote = Some(character);
+                is_in_argument = true;
+            }
+            None if character.is_whitespace() => {
+                if is_in_argument {
+                    arguments.push(std::mem::take(&mut argument));
+                    is_in_argument = false;
+                }
+            }
+            None => {
+                argument.push(character);
+                is_in_argument = true;
+            }
+        }
+    }
+    if is_in_argument {
+        arguments.push(argument);
+    }
+    arguments
+}
+
+/// The sources of the import rules in the project.
+pub fn find_import_sources(project_path: &Path, project_info: &ProjectInfo) -> Vec<PathBuf> {
+    if project_info.import.is_empty() {
+        return Vec::new();
+    }
+    get_project_files(project_path)
+        .filter(|(_, zip_path)| {
+            let resource_path = zip_path.strip_prefix("gamedata/").unwrap_or(zip_path);
+            is_import_source(project_info, resource_path)
+        })
+        .map(|(file_path, _)| file_path)
+        .collect()
+}
+
+/// The hashes of the sources at their last successful conversion, kept in the cache folder of the editor.
+pub struct ImportCache {
+    path: PathBuf,
+    hashes: BTreeMap<String, String>,
+}
+
+impl ImportCache {
+    pub fn load(project_folder: &Path) -> Self {
+        let path = project_folder
+            .join(EDITOR_CACHE_FOLDER)
+            .join(IMPORT_CACHE_FILE_NAME);
+        let hashes = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, hashes }
+    }
+
+    pub fn is_up_to_date(&self, job: &ImportJob) -> bool {
+        self.hashes.get(&job.resource_path) == Some(&job.hash)
+    }
+
+    pub fn record(&mut self, job: &ImportJob) {
+        self.hashes
+            .insert(job.resource_path.clone(), job.hash.clone());
+        self.save();
+    }
+
+    pub fn forget(&mut self, resource_path: &str) {
+        if self.hashes.remove(resource_path).is_some() {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Ok(content) = toml::to_string(&self.hashes) else {
+            return;
+        };
+        if let Some(folder) = self.path.parent() {
+            let _ = fs::create_dir_all(folder);
+        }
+        let _ = fs::write(&self.path, content);
+    }
+}
+
+/// The answer of the user to running the import commands of a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(crate = "runtime::serde")]
+pub struct ImportTrustDecision {
+    /// The hash of the commands the user was asked about. The user is asked again when they change.
+    pub commands_hash: String,
+    pub is_allowed: bool,
+}
+
+/// The answers of the user to running the import commands, by path of the project manifest. They are kept with the
+/// settings of the editor and not in the project, so that a project cannot allow its own commands.
+pub struct ImportTrust {
+    path: PathBuf,
+    decisions: BTreeMap<String, ImportTrustDecision>,
+}
+
+impl ImportTrust {
+    pub fn load() -> Self {
+        Self::load_from(get_trusted_import_commands_path())
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        let decisions = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, decisions }
+    }
+
+    /// Returns None when the user was never asked about these commands for this project.
+    pub fn is_allowed(&self, project_path: &Path, commands: &[&str]) -> Option<bool> {
+        let decision = self.decisions.get(&trust_key(project_path))?;
+        (decision.commands_hash == hash_commands(commands)).then_some(decision.is_allowed)
+    }
+
+    pub fn record(&mut self, project_path: &Path, commands: &[&str], is_allowed: bool) {
+        self.decisions.insert(
+            trust_key(project_path),
+            ImportTrustDecision {
+                commands_hash: hash_commands(commands),
+                is_allowed,
+            },
+        );
+        let Ok(content) = toml::to_string(&self.decisions) else {
+            return;
+        };
+        if let Some(folder) = self.path.parent() {
+            let _ = fs::create_dir_all(folder);
+        }
+        let _ = fs::write(&self.path, content);
+    }
+}
+
+fn trust_key(project_path: &Path) -> String {
+    paths::canonicalize(project_path)
+        .unwrap_or_else(|_| project_path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+fn hash_commands(commands: &[&str]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for command in commands {
+        hasher.update(&(command.len() as u64).to_le_bytes());
+        hasher.update(command.as_bytes());
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{ImportJob, ImportTrust, get_import_output_stem, glob_matches};
+
+    #[test]
+    fn patterns_match_like_gitignore() {
+        assert!(glob_matches("*.aseprite", "art/characters/hero.aseprite"));
+        assert!(!glob_matches("*.aseprite", "art/hero.aseprite.png"));
+        assert!(glob_matches("art/*.svg", "art/logo.svg"));
+        assert!(!glob_matches("art/*.svg", "art/icons/logo.svg"));
+        assert!(glob_matches("art/**/*.svg", "art/logo.svg"));
+        assert!(glob_matches("art/**/*.svg", "art/icons/big/logo.svg"));
+        assert!(glob_matches("level?.tmx", "maps/level1.tmx"));
+        assert!(!glob_matches("level?.tmx", "maps/level10.tmx"));
+    }
+
+    #[test]
+    fn commands_receive_the_paths_of_the_job() {
+        assert_eq!(
+            get_import_output_stem("art/hero.aseprite"),
+            "generated/art/hero"
+        );
+        assert_eq!(
+            get_import_output_stem("art.v2/README"),
+            "generated/art.v2/README"
+        );
+        let job = ImportJob {
+            resource_path: "art/my hero.aseprite".to_string(),
+            command: "aseprite --batch {in} --sheet \"{out}.png\" --format 'json-array'"
+                .to_string(),
+            hash: String::new(),
+        };
+        assert_eq!(
+            job.arguments(),
+            vec![
+                "aseprite",
+                "--batch",
+                "art/my hero.aseprite",
+                "--sheet",
+                "generated/art/my hero.png",
+                "--format",
+                "json-array",
+            ]
+        );
+    }
+
+    #[test]
+    fn trust_is_kept_by_project_and_by_commands() {
+        let trust_path = std::env::temp_dir().join(format!(
+            "vectarine-import-trust-{}.toml",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&trust_path);
+        let game = Path::new("/projects/game/game.vecta");
+        let other_game = Path::new("/projects/other/game.vecta");
+        let commands = ["aseprite --batch {in} --sheet {out}.png"];
+
+        let mut trust = ImportTrust::load_from(trust_path.clone());
+        assert_eq!(trust.is_allowed(game, &commands), None);
+        trust.record(game, &commands, true);
+
+        let trust = ImportTrust::load_from(trust_path.clone());
+        assert_eq!(trust.is_allowed(game, &commands), Some(true));
+        assert_eq!(trust.is_allowed(other_game, &commands), None);
+        // A project changing its commands is asked about again.
+        assert_eq!(trust.is_allowed(game, &["rm -rf {in}"]), None);
+        let _ = fs::remove_file(&trust_path);
+    }
+}
@@ -0,0 +1,285 @@
+//! The converters of the engine, used in import rules with a command starting with `@`, like `"*.svg" = "@svg"`. They
+//! do not need any tool to be installed, and they run without asking the user, as they cannot do anything else than
+//! writing their output.
+
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+use resvg::{tiny_skia, usvg};
+
+/// The names of the built-in converters, without their `@`.
+pub const BUILT_IN_CONVERTERS: [&str; 2] = ["svg", "tiled"];
+
+/// Matches the external tilesets of a Tiled map, and the other attributes holding a path.
+static TILED_PATH_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"<tileset\s+firstgid="(\d+)"\s+source="([^"]*)"\s*/>|\b(source|template)="([^"]*)""#,
+    )
+    .expect("The Tiled path regex is valid")
+});
+
+/// Runs the converter named in `arguments`, like `["svg", "2"]`, on the source at `resource_path`. The outputs are
+/// written at `output_stem`, which has no extension. Both paths are relative to the project folder.
+pub fn run_built_in_converter(
+    arguments: &[String],
+    project_folder: &Path,
+    resource_path: &str,
+    output_stem: &str,
+) -> Result<(), String> {
+    let Some((name, options)) = arguments.split_first() else {
+        return Err(format!(
+            "The import rule of {resource_path} has no converter after @"
+        ));
+    };
+    match name.as_str() {
+        "svg" => {
+            let scale = match options.first() {
+                Some(scale) => scale
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|scale| scale.is_finite() && *scale > 0.0)
+                    .ok_or_else(|| {
+                        format!("The scale of @svg must be a positive number, not {scale}")
+                    })?,
+                None => 1.0,
+            };
+            convert_svg(
+                &project_folder.join(resource_path),
+                &project_folder.join(format!("{output_stem}.png")),
+                scale,
+            )
+        }
+        "tiled" => convert_tiled_map(project_folder, resource_path, output_stem),
+        _ => Err(format!(
+            "Unknown converter @{name} in the import rule of {resource_path}. The built-in converters are @{}",
+            BUILT_IN_CONVERTERS.join(", @")
+        )),
+    }
+}
+
+/// Draws an SVG file into a PNG file, `scale` times bigger than the size given in the SVG file.
+fn convert_svg(source_path: &Path, output_path: &Path, scale: f32) -> Result<(), String> {
+    let data = fs::read(source_path)
+        .map_err(|err| format!("Could not read {}: {err}", source_path.display()))?;
+    // The images used by the SVG file are relative to it.
+    let options = usvg::Options {
+        resources_dir: source_path.parent().map(Path::to_path_buf),
+        ..usvg::Options::default()
+    };
+    let tree = usvg::Tree::from_data(&data, &options)
+        .map_err(|err| format!("{} is not a valid SVG file: {err}", source_path.display()))?;
+    let size = tree
+        .size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or_else(|| format!("{} is too big to be drawn", source_path.display()))?;
+    let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())
+        .ok_or_else(|| format!("{} has an empty size", source_path.display()))?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    pixmap
+        .save_png(output_path)
+        .map_err(|err| format!("Could not write {}: {err}", output_path.display()))
+}
+
+/// Writes a Tiled map as a single file: the external tilesets are copied inside it, and the other paths are changed
+/// to stay valid from the generated folder. The map is loaded first, so that a missing tileset is reported when the
+/// map is saved instead of when the game loads it.
+fn convert_tiled_map(
+    project_folder: &Path,
+    resource_path: &str,
+    output_stem: &str,
+) -> Result<(), String> {
+    if !resource_path.ends_with(".tmx") {
+        return Err(format!(
+            "@tiled converts Tiled maps (.tmx), {resource_path} is not one"
+        ));
+    }
+    let source_path = project_folder.join(resource_path);
+    tiled::Loader::new()
+        .load_tmx_map(&source_path)
+        .map_err(|err| format!("{resource_path} is not a valid Tiled map: {err}"))?;
+    let map = fs::read_to_string(&source_path)
+        .map_err(|err| format!("Could not read {resource_path}: {err}"))?;
+
+    let map_folder = parent_folder(resource_path);
+    let output_folder = parent_folder(output_stem);
+    let mut error = None;
+    let map = TILED_PATH_REGEX.replace_all(&map, |captures: &Captures| {
+        if let (Some(first_gid), Some(tileset_path)) = (captures.get(1), captures.get(2)) {
+            let tileset_path = join_path(map_folder, tileset_path.as_str());
+            match embed_tileset(
+                project_folder,
+                &tileset_path,
+                first_gid.as_str(),
+                output_folder,
+            ) {
+                Ok(tileset) => tileset,
+                Err(err) => {
+                    error.get_or_insert(err);
+                    String::new()
+                }
+            }
+        } else {
+            rebase_attribute(captures, map_folder, output_folder)
+        }
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    let output_path = project_folder.join(format!("{output_stem}.tmx"));
+    fs::write(&output_path, map.as_bytes())
+        .map_err(|err| format!("Could not write {}: {err}", output_path.display()))
+}
+
+/// The content of a `.tsx` file as a `<tileset>` element of a map.
+fn embed_tileset(
+    project_folder: &Path,
+    tileset_path: &str,
+    first_gid: &str,
+    output_folder: &str,
+) -> Result<String, String> {
+    let tileset = fs::read_to_string(project_folder.join(tileset_path))
+        .map_err(|err| format!("Could not read the tileset {tileset_path}: {err}"))?;
+    let start = tileset
+        .find("<tileset")
+        .ok_or_else(|| format!("{tileset_path} is not a Tiled tileset"))?;
+    let tileset = format!(
+        "<tileset firstgid=\"{first_gid}\"{}",
+        tileset[start + "<tileset".len()..].trim_end()
+    );
+    let tileset_folder = parent_folder(tileset_path);
+    Ok(TILED_PATH_REGEX
+        .replace_all(&tileset, |captures: &Captures| {
+            if captures.get(3).is_some() {
+                rebase_attribute(captures, tileset_folder, output_folder)
+            } else {
+                captures[0].to_string()
+            }
+        })
+        .into_owned())
+}
+
+fn rebase_attribute(captures: &Captures, from_folder: &str, to_folder: &str) -> String {
+    let path = join_path(from_folder, &captures[4]);
+    format!("{}=\"{}\"", &captures[3], relative_path(to_folder, &path))
+}
+
+fn parent_folder(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(folder, _)| folder)
+}
+
+/// The path of `path`, relative to `folder`, relative to the project. `..` are resolved. Absolute paths and URLs are
+/// kept.
+fn join_path(folder: &str, path: &str) -> String {
+    if path.starts_with('/') || path.contains(':') {
+        return path.to_string();
+    }
+    let mut components = Vec::new();
+    for component in folder.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." if components.last().is_some_and(|last| *last != "..") => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    components.join("/")
+}
+
+/// The path leading from `folder` to `path`, both relative to the project.
+fn relative_path(folder: &str, path: &str) -> String {
+    if path.starts_with('/') || path.contains(':') {
+        return path.to_string();
+    }
+    let folder = folder.split('/').filter(|component| !component.is_empty());
+    let path = path.split('/').collect::<Vec<_>>();
+    let mut common = 0;
+    let mut parents = 0;
+    for component in folder {
+        if parents == 0 && path.get(common) == Some(&component) && common + 1 < path.len() {
+            common += 1;
+        } else {
+            parents += 1;
+        }
+    }
+    let mut components = vec![".."; parents];
+    components.extend(&path[common..]);
+    components.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{join_path, relative_path, run_built_in_converter};
+
+    #[test]
+    fn paths_are_rebased_from_the_generated_folder() {
+        assert_eq!(join_path("maps", "../tiles/ground.tsx"), "tiles/ground.tsx");
+        assert_eq!(join_path("", "./ground.png"), "ground.png");
+        assert_eq!(
+            relative_path("generated/maps", "tiles/ground.png"),
+            "../../tiles/ground.png"
+        );
+        assert_eq!(
+            relative_path("maps/forest", "maps/ground.png"),
+            "../ground.png"
+        );
+        assert_eq!(relative_path("", "ground.png"), "ground.png");
+    }
+
+    #[test]
+    fn tiled_maps_are_written_with_their_tilesets() {
+        let project_folder =
+            std::env::temp_dir().join(format!("vectarine-import-tiled-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_folder);
+        fs::create_dir_all(project_folder.join("maps")).expect("the folder is created");
+        fs::create_dir_all(project_folder.join("generated/maps")).expect("the folder is created");
+        fs::write(
+            project_folder.join("maps/ground.tsx"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tileset version=\"1.10\" name=\"ground\" tilewidth=\"16\" tileheight=\"16\" tilecount=\"4\" columns=\"2\">\n <image source=\"ground.png\" width=\"32\" height=\"32\"/>\n</tileset>\n",
+        )
+        .expect("the tileset is written");
+        fs::write(
+            project_folder.join("maps/level.tmx"),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" width=\"2\" height=\"1\" tilewidth=\"16\" tileheight=\"16\" infinite=\"0\" nextlayerid=\"2\" nextobjectid=\"1\">\n <tileset firstgid=\"1\" source=\"ground.tsx\"/>\n <layer id=\"1\" name=\"Ground\" width=\"2\" height=\"1\">\n  <data encoding=\"csv\">\n1,2\n</data>\n </layer>\n</map>\n",
+        )
+        .expect("the map is written");
+
+        run_built_in_converter(
+            &["tiled".to_string()],
+            &project_folder,
+            "maps/level.tmx",
+            "generated/maps/level",
+        )
+        .expect("the map is converted");
+        let map = fs::read_to_string(project_folder.join("generated/maps/level.tmx"))
+            .expect("the map is written");
+        assert!(!map.contains("ground.tsx"));
+        assert!(map.contains("<tileset firstgid=\"1\" version=\"1.10\" name=\"ground\""));
+        assert!(map.contains("<image source=\"../../maps/ground.png\""));
+        tiled::Loader::new()
+            .load_tmx_map(project_folder.join("generated/maps/level.tmx"))
+            .expect("the converted map is valid");
+        let _ = fs::remove_dir_all(&project_folder);
+    }
+
+    #[test]
+    fn unknown_converters_are_errors() {
+        let error = run_built_in_converter(
+            &["png".to_string()],
+            &std::env::temp_dir(),
+            "logo.svg",
+            "generated/logo",
+        )
+        .expect_err("there is no png converter");
+        assert!(error.contains("@svg, @tiled"));
+    }
+}
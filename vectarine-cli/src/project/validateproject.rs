@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use runtime::io::enginefs::{ENGINE_ASSET_PREFIX, get_engine_asset};
+
+use crate::project::exportproject::get_project_files;
+
+/// The functions of `@vectarine/loader` taking the path of a file of the project as their first argument.
+const LOADER_FUNCTIONS: &[&str] = &[
+    "loadText",
+    "loadImage",
+    "loadFont",
+    "loadAudio",
+    "loadShader",
+    "loadTileset",
+    "loadTilemap",
+    "loadAnimation",
+    "loadScript",
+];
+
+/// Matches the calls to the loader functions and to `preload`, which takes a list of paths.
+static LOADER_CALL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"\b({}|preload)\s*\(", LOADER_FUNCTIONS.join("|")))
+        .expect("The loader call regex is valid")
+});
+
+/// A call to a loader function found in a script.
+pub struct AssetReference {
+    /// Path of the script, relative to the project folder.
+    pub script: String,
+    pub line: usize,
+    pub function: String,
+    /// The path passed to the function, or the expression building it when it is only known at runtime.
+    pub argument: String,
+}
+
+impl fmt::Display for AssetReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}({})",
+            self.script, self.line, self.function, self.argument
+        )
+    }
+}
+
+/// The result of checking that the files loaded by the scripts are part of the export.
+#[derive(Default)]
+pub struct AssetValidationReport {
+    pub checked_count: usize,
+    /// References to files that do not exist.
+    pub missing: Vec<AssetReference>,
+    /// References to files that exist but are not exported, like files in the `private` folder.
+    pub not_exported: Vec<AssetReference>,
+    /// References with a path built at runtime, like `"images/" .. name`.
+    pub unverifiable: Vec<AssetReference>,
+}
+
+impl AssetValidationReport {
+    pub fn has_errors(&self) -> bool {
+        !self.missing.is_empty() || !self.not_exported.is_empty()
+    }
+}
+
+impl fmt::Display for AssetValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Checked {} asset paths.", self.checked_count)?;
+        for reference in &self.missing {
+            writeln!(f, "❌ {reference}: file not found")?;
+        }
+        for reference in &self.not_exported {
+            writeln!(
+                f,
+                "❌ {reference}: the file is not exported, move it to an exported folder"
+            )?;
+        }
+        for reference in &self.unverifiable {
+            writeln!(
+                f,
+                "❔ {reference}: unverifiable, the path is built at runtime"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+enum LoaderArgument {
+    Literal(String),
+    Dynamic(String),
+}
+
+/// Looks for the paths passed to the loader functions in the scripts of the project and checks that the files are
+/// exported with the game. This is a textual scan, calls through aliases of the loader functions are not found.
+pub fn validate_project_assets(project_path: &Path) -> AssetValidationReport {
+    let mut report = AssetValidationReport::default();
+    let Some(project_folder) = project_path.parent() else {
+        return report;
+    };
+    let project_files = get_project_files(project_path).collect::<Vec<_>>();
+    let exported_files = project_files
+        .iter()
+        .filter_map(|(_, zip_path)| zip_path.strip_prefix("gamedata/"))
+        .collect::<HashSet<_>>();
+
+    for (file_path, zip_path) in &project_files {
+        if !file_path
+            .extension()
+            .is_some_and(|extension| extension == "luau" || extension == "lua")
+        {
+            continue;
+        }
+        let Ok(source) = fs::read_to_string(file_path) else {
+            continue;
+        };
+        let script = zip_path.trim_start_matches("gamedata/");
+        for (line, function, argument) in find_loader_calls(&source) {
+            report.checked_count += 1;
+            match argument {
+                LoaderArgument::Literal(path) => {
+                    let path = path.replace('\\', "/");
+                    let path = path.trim_start_matches("./");
+                    // The engine assets are embedded in the engine, they are never part of the export.
+                    if let Some(engine_path) = path.strip_prefix(ENGINE_ASSET_PREFIX) {
+                        if get_engine_asset(engine_path).is_none() {
+                            report.missing.push(AssetReference {
+                                script: script.to_string(),
+                                line,
+                                function,
+                                argument: format!("\"{path}\""),
+                            });
+                        }
+                        continue;
+                    }
+                    // The comparison is case-sensitive, like the file systems of Linux and of the web.
+                    if exported_files.contains(path) {
+                        continue;
+                    }
+                    let reference = AssetReference {
+                        script: script.to_string(),
+                        line,
+                        function,
+                        argument: format!("\"{path}\""),
+                    };
+                    if project_folder.join(path).is_file() {
+                        report.not_exported.push(reference);
+                    } else {
+                        report.missing.push(reference);
+                    }
+                }
+                LoaderArgument::Dynamic(expression) => {
+                    report.unverifiable.push(AssetReference {
+                        script: script.to_string(),
+                        line,
+                        function,
+                        argument: expression,
+                    });
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Returns the line, the function name and the path argument of the calls to loader functions. A call to `preload`
+/// gives an entry for every element of its list.
+fn find_loader_calls(source: &str) -> Vec<(usize, String, LoaderArgument)> {
+    let line_of = |offset: usize| source[..offset].matches('\n').count() + 1;
+    let mut calls = Vec::new();
+    for capture in LOADER_CALL_REGEX.captures_iter(source) {
+        let (Some(call), Some(function)) = (capture.get(0), capture.get(1)) else {
+            continue;
+        };
+        let line_start = source[..call.start()].rfind('\n').map_or(0, |i| i + 1);
+        let before_call = &source[line_start..call.start()];
+        // Commented out code and definitions (like the ones of luau-api) are not calls.
+        if before_call.contains("--") || before_call.trim_start().starts_with("function") {
+            continue;
+        }
+        let function = function.as_str().to_string();
+        let arguments = &source[call.end()..];
+        if function != "preload" {
+            calls.push((
+                line_of(call.start()),
+                function,
+                parse_first_argument(arguments, &[',', ')']),
+            ));
+            continue;
+        }
+        let list_offset = arguments.len() - arguments.trim_start().len();
+        let Some(mut elements) = arguments[list_offset..].strip_prefix('{') else {
+            calls.push((
+                line_of(call.start()),
+                function,
+                LoaderArgument::Dynamic(get_first_argument_text(arguments)),
+            ));
+            continue;
+        };
+        loop {
+            let element_start = elements.len() - elements.trim_start().len();
+            elements = &elements[element_start..];
+            if elements.is_empty() || elements.starts_with('}') {
+                break;
+            }
+            let offset = source.len() - elements.len();
+            calls.push((
+                line_of(offset),
+                function.clone(),
+                parse_first_argument(elements, &[',', ';', '}']),
+            ));
+            elements = &elements[get_first_argument_length(elements)..];
+            match elements.chars().next() {
+                Some(',' | ';') => elements = &elements[1..],
+                _ => break,
+            }
+        }
+    }
+    calls
+}
+
+/// Parses the string literal at the start of `arguments`. `separators` are the characters that can follow the
+/// literal for it to be the whole argument.
+fn parse_first_argument(arguments: &str, separators: &[char]) -> LoaderArgument {
+    let arguments = arguments.trim_start();
+    if let Some((Some(literal), length)) = parse_string_literal(arguments) {
+        // `"images/" .. name` starts with a literal but is not one.
+        let after = arguments[length..].trim_start();
+        if after.starts_with(separators) {
+            return LoaderArgument::Literal(literal);
+        }
+    }
+    LoaderArgument::Dynamic(get_first_argument_text(arguments))
+}
+
+/// Returns the value of the quoted string at the start of `text` and its length in `text`, or None when the text
+/// does not start with a string. The value is None when the string uses an escape sequence that is not decoded.
+fn parse_string_literal(text: &str) -> Option<(Option<String>, usize)> {
+    let mut chars = text.char_indices();
+    let (_, quote) = chars.next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let mut literal = Some(String::new());
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                let (_, escaped) = chars.next()?;
+                let decoded = match escaped {
+                    'n' => Some('\n'),
+                    't' => Some('\t'),
+                    'r' => Some('\r'),
+                    '\\' | '"' | '\'' => Some(escaped),
+                    _ => None,
+                };
+                match (literal.as_mut(), decoded) {
+                    (Some(literal), Some(decoded)) => literal.push(decoded),
+                    _ => literal = None,
+                }
+            }
+            '\n' => return None,
+            c if c == quote => return Some((literal, index + c.len_utf8())),
+            c => {
+                if let Some(literal) = literal.as_mut() {
+                    literal.push(c);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The text of the first argument, to show it in the report.
+fn get_first_argument_text(arguments: &str) -> String {
+    const MAX_LENGTH: usize = 60;
+    let argument = &arguments[..get_first_argument_length(arguments)];
+    let text = argument.lines().next().unwrap_or_default().trim();
+    if text.chars().count() > MAX_LENGTH {
+        format!("{}...", text.chars().take(MAX_LENGTH).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+/// The length of the first argument, until the separator or the bracket closing the list of arguments.
+fn get_first_argument_length(arguments: &str) -> usize {
+    let mut depth = 0;
+    let mut chars = arguments.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                // The separators inside strings are skipped.
+                let Some((_, length)) = parse_string_literal(&arguments[index..]) else {
+                    continue;
+                };
+                while chars.next_if(|(next, _)| *next < index + length).is_some() {}
+            }
+            '(' | '{' | '[' => depth += 1,
+            ')' | '}' | ']' if depth == 0 => return index,
+            ')' | '}' | ']' => depth -= 1,
+            ',' | ';' if depth == 0 => return index,
+            _ => {}
+        }
+    }
+    arguments.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{LoaderArgument, find_loader_calls, validate_project_assets};
+
+    /// Describes the calls found in the source as `line function argument`, with quotes around literal paths.
+    fn describe_calls(source: &str) -> Vec<String> {
+        find_loader_calls(source)
+            .into_iter()
+            .map(|(line, function, argument)| match argument {
+                LoaderArgument::Literal(path) => format!("{line} {function} {path:?}"),
+                LoaderArgument::Dynamic(expression) => format!("{line} {function} {expression}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn literal_paths_are_found_with_their_line() {
+        let source = r#"local hero = Loader.loadImage("textures/hero.png")
+-- Loader.loadImage("textures/old.png")
+local music = Loader.loadAudio( 'music/theme.ogg', { loop = true })
+function module.loadFont(path: string) end
+"#;
+        assert_eq!(
+            describe_calls(source),
+            vec![
+                r#"1 loadImage "textures/hero.png""#,
+                r#"3 loadAudio "music/theme.ogg""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_built_at_runtime_are_dynamic() {
+        let source = r#"Loader.loadImage("textures/" .. name .. ".png")
+Loader.loadText(getPath("level", 1))
+"#;
+        assert_eq!(
+            describe_calls(source),
+            vec![
+                r#"1 loadImage "textures/" .. name .. ".png""#,
+                r#"2 loadText getPath("level", 1)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_sequences_are_decoded() {
+        let source = r#"Loader.loadText("data\\level\"1\".txt")
+Loader.loadText("data/\u{6c}evel.txt")
+"#;
+        assert_eq!(
+            describe_calls(source),
+            vec![
+                r#"1 loadText "data\\level\"1\".txt""#,
+                // Escapes that are not decoded make the path unverifiable instead of wrong.
+                r#"2 loadText "data/\u{6c}evel.txt""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn every_path_of_preload_lists_is_found() {
+        let source = r#"Loader.preload({
+    "textures/a.png",
+    "textures/b,c.png"; prefix .. "d.png",
+}, { priority = "low" })
+Loader.preload(paths)
+"#;
+        assert_eq!(
+            describe_calls(source),
+            vec![
+                r#"2 preload "textures/a.png""#,
+                r#"3 preload "textures/b,c.png""#,
+                r#"3 preload prefix .. "d.png""#,
+                "5 preload paths",
+            ]
+        );
+    }
+
+    #[test]
+    fn engine_assets_are_checked_without_the_exported_files() {
+        let project_folder =
+            std::env::temp_dir().join(format!("vectarine-validate-engine-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_folder);
+        fs::create_dir_all(project_folder.join("scripts")).expect("the folder is created");
+        fs::write(project_folder.join("game.vecta"), "title = \"Engine\"\n")
+            .expect("the manifest is written");
+        fs::write(
+            project_folder.join("scripts/game.luau"),
+            "Loader.loadFont(\"@engine/fonts/default.ttf\")\nLoader.loadImage(\"@engine/textures/missing.png\")\n",
+        )
+        .expect("the script is written");
+
+        let report = validate_project_assets(&project_folder.join("game.vecta"));
+        assert_eq!(report.checked_count, 2);
+        assert!(report.not_exported.is_empty());
+        let missing = report
+            .missing
+            .iter()
+            .map(|reference| reference.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            missing,
+            vec!["scripts/game.luau:2: loadImage(\"@engine/textures/missing.png\")"]
+        );
+        let _ = fs::remove_dir_all(&project_folder);
+    }
+
+    #[test]
+    fn lua_scripts_are_checked_too() {
+        let project_folder =
+            std::env::temp_dir().join(format!("vectarine-validate-lua-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&project_folder);
+        fs::create_dir_all(project_folder.join("scripts")).expect("the folder is created");
+        fs::write(project_folder.join("game.vecta"), "title = \"Lua\"\n")
+            .expect("the manifest is written");
+        fs::write(
+            project_folder.join("scripts/old.lua"),
+            "Loader.loadImage(\"textures/missing.png\")\n",
+        )
+        .expect("the script is written");
+
+        let report = validate_project_assets(&project_folder.join("game.vecta"));
+        assert_eq!(report.checked_count, 1);
+        let missing = report
+            .missing
+            .iter()
+            .map(|reference| reference.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            missing,
+            vec!["scripts/old.lua:1: loadImage(\"textures/missing.png\")"]
+        );
+        let _ = fs::remove_dir_all(&project_folder);
+    }
+}
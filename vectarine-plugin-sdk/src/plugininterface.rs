@@ -1,5 +1,7 @@
 //! Plugin interface defines the Plugin Interface, a sort of SDK for plugins to interact with the runtime and the editor.
 
+use std::{any::Any, cell::RefCell, fmt, path::Path, rc::Rc};
+
 /// The plugin interface object.
 ///
 /// It is used for plugins to interact with the runtime.
@@ -9,11 +11,14 @@ pub struct PluginInterface<'a> {
     // The Lua struct is not repr(C), so good luck not using Rust!
     // We could add more fields for C friendliness?
     pub lua: &'a mlua::Lua,
+    /// Loads files with importers provided by the plugin.
+    /// Clone it to use it outside of the hook, for example inside a Lua function.
+    pub resources: &'a ResourceHandle,
 }
 
 impl<'a> PluginInterface<'a> {
-    pub fn new(lua: &'a mlua::Lua) -> Self {
-        Self { lua }
+    pub fn new(lua: &'a mlua::Lua, resources: &'a ResourceHandle) -> Self {
+        Self { lua, resources }
     }
 }
 
@@ -26,3 +31,201 @@ pub struct EditorPluginInterface<'a> {
     pub plugin_interface: PluginInterface<'a>,
     pub gui_context: &'a egui::Context,
 }
+
+/// The draw plugin interface object.
+///
+/// Provided every frame to the `draw_hook` of your plugin, after the `Update` function of the game,
+/// so that what you draw appears on top of the game.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PluginDrawInterface<'a> {
+    pub plugin_interface: PluginInterface<'a>,
+    pub canvas: &'a DrawHandle,
+}
+
+/// Returned by the handles when the engine cannot do what the plugin asked.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluginError {
+    /// The game the handle belongs to was unloaded. This happens after the `release_hook` of the plugin.
+    Released,
+    /// No resource has this id, or the resource was not loaded by an importer.
+    InvalidResource(usize),
+    /// The resource is not loaded yet, or its importer failed.
+    NotLoaded(usize),
+    /// The payload of the resource is not of the type asked for.
+    WrongPayloadType(usize),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Released => {
+                write!(f, "The game was unloaded, this handle is no longer valid")
+            }
+            PluginError::InvalidResource(id) => {
+                write!(f, "Resource {id} was not loaded by an importer")
+            }
+            PluginError::NotLoaded(id) => write!(f, "Resource {id} is not loaded"),
+            PluginError::WrongPayloadType(id) => {
+                write!(f, "The payload of resource {id} is of another type")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// A handle to a part of the engine that plugins can keep after the hook that gave it to them returns.
+///
+/// The engine invalidates its handles when the game is unloaded, right after calling the `release_hook` of the plugins.
+/// Calls made through an invalidated handle return [`PluginError::Released`] instead of using the freed game.
+pub struct PluginHandle<T: ?Sized> {
+    target: Rc<RefCell<Option<Box<T>>>>,
+}
+
+impl<T: ?Sized> Clone for PluginHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target.clone(),
+        }
+    }
+}
+
+impl<T: ?Sized> PluginHandle<T> {
+    /// Only the engine creates handles.
+    pub fn new(target: Box<T>) -> Self {
+        Self {
+            target: Rc::new(RefCell::new(Some(target))),
+        }
+    }
+
+    /// Makes every clone of the handle return [`PluginError::Released`]. Only the engine invalidates handles.
+    pub fn invalidate(&self) {
+        self.target.borrow_mut().take();
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.target.borrow().is_some()
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, PluginError> {
+        let target = self.target.borrow();
+        let target = target.as_ref().ok_or(PluginError::Released)?;
+        Ok(f(target))
+    }
+}
+
+/// What an importer makes of the content of a file.
+pub enum ImportResult {
+    /// The file was imported. The payload is kept by the engine until the file is imported again.
+    Loaded(Box<dyn Any>),
+    /// The file could not be imported. The message is shown in the resource window of the editor.
+    Error(String),
+}
+
+/// Turns the content of a file into a payload. It is called again every time the file changes.
+pub type Importer = Rc<dyn Fn(&Path, &[u8]) -> ImportResult>;
+
+/// Implemented by the engine, use it through a [`ResourceHandle`].
+pub trait ResourceHost {
+    fn load_with_importer(&self, path: &Path, importer: Importer) -> usize;
+    fn is_loaded(&self, id: usize) -> Result<bool, PluginError>;
+    fn with_payload(&self, id: usize, f: &mut dyn FnMut(&dyn Any)) -> Result<(), PluginError>;
+}
+
+/// Gives access to the resources of the game.
+pub type ResourceHandle = PluginHandle<dyn ResourceHost>;
+
+impl PluginHandle<dyn ResourceHost> {
+    /// Loads a file of the game with an importer provided by the plugin and returns the id of the resource.
+    /// The file is loaded like the other resources, so it is imported again when it changes.
+    ///
+    /// If a resource was already loaded from this path, its id is returned and the importer is not used.
+    pub fn load_with_importer(
+        &self,
+        path: &Path,
+        importer: Importer,
+    ) -> Result<usize, PluginError> {
+        self.with(|host| host.load_with_importer(path, importer))
+    }
+
+    /// Returns whether the importer of the resource succeeded.
+    pub fn is_loaded(&self, id: usize) -> Result<bool, PluginError> {
+        self.with(|host| host.is_loaded(id))?
+    }
+
+    /// Calls `f` with the payload returned by the importer of the resource.
+    pub fn with_payload<P: 'static, R>(
+        &self,
+        id: usize,
+        f: impl FnOnce(&P) -> R,
+    ) -> Result<R, PluginError> {
+        let mut f = Some(f);
+        let mut result = None;
+        self.with(|host| {
+            host.with_payload(id, &mut |payload| {
+                if let (Some(payload), Some(f)) = (payload.downcast_ref::<P>(), f.take()) {
+                    result = Some(f(payload));
+                }
+            })
+        })??;
+        result.ok_or(PluginError::WrongPayloadType(id))
+    }
+}
+
+/// Implemented by the engine, use it through a [`DrawHandle`].
+pub trait DrawHost {
+    fn draw_rect(&self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]);
+    fn draw_image(
+        &self,
+        image_id: usize,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [f32; 4],
+    ) -> Result<(), PluginError>;
+    fn draw_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: [f32; 4]);
+}
+
+/// Draws on top of the game. The positions and sizes use the coordinates of the screen, from -1 to 1,
+/// like `Graphics.drawRect` in Lua.
+pub type DrawHandle = PluginHandle<dyn DrawHost>;
+
+impl PluginHandle<dyn DrawHost> {
+    pub fn draw_rect(
+        &self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [f32; 4],
+    ) -> Result<(), PluginError> {
+        self.with(|host| host.draw_rect(x, y, width, height, color))
+    }
+
+    /// Draws an image loaded by the game, for example with `Loader.loadImage`.
+    pub fn draw_image(
+        &self,
+        image_id: usize,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: [f32; 4],
+    ) -> Result<(), PluginError> {
+        self.with(|host| host.draw_image(image_id, x, y, width, height, color))?
+    }
+
+    /// Draws text with the default font of the engine.
+    pub fn draw_text(
+        &self,
+        text: &str,
+        x: f32,
+        y: f32,
+        font_size: f32,
+        color: [f32; 4],
+    ) -> Result<(), PluginError> {
+        self.with(|host| host.draw_text(text, x, y, font_size, color))
+    }
+}
@@ -1,9 +1,18 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
 use vectarine_plugin_sdk::{
     egui,
-    mlua::ffi,
-    plugininterface::{EditorPluginInterface, PluginInterface},
+    mlua::{self, ffi},
+    plugininterface::{
+        EditorPluginInterface, ImportResult, PluginDrawInterface, PluginInterface, ResourceHandle,
+    },
 };
 
+thread_local! {
+    /// Text drawn on top of the game by the draw_hook, set from Lua with `setOverlayText`.
+    static OVERLAY_TEXT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
 unsafe extern "C-unwind" fn square_number(state: *mut ffi::lua_State) -> i32 {
     unsafe {
         let n = ffi::luaL_checknumber(state, 1);
@@ -12,6 +21,121 @@ unsafe extern "C-unwind" fn square_number(state: *mut ffi::lua_State) -> i32 {
     }
 }
 
+/// What our importer keeps from an Aseprite file. A real importer would also decode the pixels of the frames.
+struct AsepriteSprite {
+    width: u16,
+    height: u16,
+    frame_durations_ms: Vec<u16>,
+}
+
+const ASEPRITE_HEADER_SIZE: usize = 128;
+const ASEPRITE_FRAME_HEADER_SIZE: usize = 16;
+const ASEPRITE_FILE_MAGIC: u16 = 0xA5E0;
+const ASEPRITE_FRAME_MAGIC: u16 = 0xF1FA;
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+    ]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+        *data.get(offset + 2)?,
+        *data.get(offset + 3)?,
+    ]))
+}
+
+/// The importer receives the content of the file every time it changes, the engine takes care of the rest.
+/// See https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md for the format.
+fn import_aseprite(path: &Path, data: &[u8]) -> ImportResult {
+    if data.len() < ASEPRITE_HEADER_SIZE || read_u16(data, 4) != Some(ASEPRITE_FILE_MAGIC) {
+        return ImportResult::Error(format!("{} is not an Aseprite file", path.display()));
+    }
+    let frame_count = read_u16(data, 6).unwrap_or(0);
+    let mut frame_durations_ms = Vec::with_capacity(frame_count as usize);
+    let mut offset = ASEPRITE_HEADER_SIZE;
+    for frame in 0..frame_count {
+        let (Some(frame_size), Some(magic), Some(duration)) = (
+            read_u32(data, offset),
+            read_u16(data, offset + 4),
+            read_u16(data, offset + 8),
+        ) else {
+            return ImportResult::Error(format!(
+                "Frame {frame} of {} is truncated",
+                path.display()
+            ));
+        };
+        if magic != ASEPRITE_FRAME_MAGIC || (frame_size as usize) < ASEPRITE_FRAME_HEADER_SIZE {
+            return ImportResult::Error(format!(
+                "Frame {frame} of {} is corrupted",
+                path.display()
+            ));
+        }
+        frame_durations_ms.push(duration);
+        offset += frame_size as usize;
+    }
+    ImportResult::Loaded(Box::new(AsepriteSprite {
+        width: read_u16(data, 8).unwrap_or(0),
+        height: read_u16(data, 10).unwrap_or(0),
+        frame_durations_ms,
+    }))
+}
+
+/// Registers the Lua functions that use the resources of the game. The handle is cloned into the functions, so
+/// they can use it after the init_hook returns. Once the game is unloaded, the handle returns an error.
+fn add_aseprite_functions(
+    lua: &mlua::Lua,
+    module: &mlua::Table,
+    resources: &ResourceHandle,
+) -> mlua::Result<()> {
+    let load_aseprite = lua.create_function({
+        let resources = resources.clone();
+        move |_, path: String| {
+            resources
+                .load_with_importer(Path::new(&path), Rc::new(import_aseprite))
+                .map_err(mlua::Error::external)
+        }
+    })?;
+    module.set("loadAseprite", load_aseprite)?;
+
+    let get_aseprite_info = lua.create_function({
+        let resources = resources.clone();
+        move |lua, id: usize| {
+            if !resources.is_loaded(id).map_err(mlua::Error::external)? {
+                return Ok(None);
+            }
+            let info = lua.create_table()?;
+            resources
+                .with_payload(id, |sprite: &AsepriteSprite| -> mlua::Result<()> {
+                    info.set("width", sprite.width)?;
+                    info.set("height", sprite.height)?;
+                    info.set("frames", sprite.frame_durations_ms.len())?;
+                    let total_ms = sprite
+                        .frame_durations_ms
+                        .iter()
+                        .map(|ms| *ms as u32)
+                        .sum::<u32>();
+                    info.set("duration", total_ms as f32 / 1000.0)?;
+                    Ok(())
+                })
+                .map_err(mlua::Error::external)??;
+            Ok(Some(info))
+        }
+    })?;
+    module.set("getAsepriteInfo", get_aseprite_info)?;
+
+    let set_overlay_text = lua.create_function(|_, text: Option<String>| {
+        OVERLAY_TEXT.with_borrow_mut(|overlay_text| *overlay_text = text);
+        Ok(())
+    })?;
+    module.set("setOverlayText", set_overlay_text)?;
+    Ok(())
+}
+
 /// The init_hook is called when the game is loaded. You can use it to register custom lua functions, variables, etc...
 #[unsafe(no_mangle)]
 pub extern "C" fn init_hook(plugin_interface: PluginInterface) {
@@ -34,7 +158,7 @@ pub extern "C" fn init_hook(plugin_interface: PluginInterface) {
 
     // We are reimplementing the content of plugin.luau in Rust.
     // Having a version field is useful so that when developing you remember to run `uv run bundle.py --install` to update the native code.
-    let _ = value.set("VERSION", 2);
+    let _ = value.set("VERSION", 3);
     let _ = value.set("NAME", "Plugin Template");
 
     unsafe {
@@ -46,15 +170,20 @@ pub extern "C" fn init_hook(plugin_interface: PluginInterface) {
         let _ = value.set("square", square_fn);
     }
 
+    if let Err(err) = add_aseprite_functions(lua, &value, plugin_interface.resources) {
+        println!("Failed to add the Aseprite functions: {err}");
+    }
+
     // Actually register the module. The module name here should match the name you put in the manifest.
     let _ = lua.register_module("@vectarine/plugin_template", value);
 }
 
 /// The release_hook is called when the game is unloaded. You can use it to free resources if needed.
 /// You don't need to define it if you don't need it. If it is not defined, it simply won't be called.
+/// The handles of the plugin interface are invalidated right after it returns.
 #[unsafe(no_mangle)]
 pub extern "C" fn release_hook(_plugin_interface: PluginInterface) {
-    // ...
+    OVERLAY_TEXT.with_borrow_mut(|overlay_text| *overlay_text = None);
 }
 
 /// The pre_lua_hook is called every frame, before the lua script is executed.
@@ -69,6 +198,20 @@ pub extern "C" fn post_lua_hook(_plugin_interface: PluginInterface) {
     // ...
 }
 
+/// The draw_hook is called every frame, after the Update function of the game. What you draw appears on top of the game.
+/// Positions use the coordinates of the screen, from -1 to 1.
+#[unsafe(no_mangle)]
+pub extern "C" fn draw_hook(plugin_interface: PluginDrawInterface) {
+    OVERLAY_TEXT.with_borrow(|overlay_text| {
+        let Some(text) = overlay_text else {
+            return;
+        };
+        let canvas = plugin_interface.canvas;
+        let _ = canvas.draw_rect(-1.0, 0.9, 2.0, 0.1, [0.0, 0.0, 0.0, 0.6]);
+        let _ = canvas.draw_text(text, -0.98, 0.92, 0.06, [1.0, 1.0, 1.0, 1.0]);
+    });
+}
+
 /// The draw_debug_menu_hook is called only in the editor when the debug menu of your extension needs to be drawn.
 /// You can use it to add a custom editor window to your plugin.
 /// Return true if you want to keep drawing the debug menu and false to close it.